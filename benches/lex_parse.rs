@@ -0,0 +1,49 @@
+//! Lexing and parsing throughput on a generated ~10k-line source file
+//!
+//! `cargo bench --no-default-features --bench lex_parse`
+
+use aether_lang::frontend::lexer::Lexer;
+use aether_lang::frontend::parser::Parser;
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+/// `functions` copies of a 16-line function, plus a struct they share
+fn generated_source(functions: usize) -> String {
+    let mut source = String::from("struct Point { x: i64, y: i64 }\n\n");
+    for i in 0..functions {
+        source.push_str(&format!(concat!(
+            "fn compute_{i}(point: Point, count: i64) -> i64 {{\n",
+            "    let mut total: i64 = point.x * {i} + point.y\n",
+            "    let label: str = \"iteration {i} of the generated benchmark\"\n",
+            "    while total < count {{\n",
+            "        if total % 3 == 0 {{\n",
+            "            total = total + helper_{i}(total, label.len() as i64)\n",
+            "        }} else {{\n",
+            "            total = total * 2 + 1\n",
+            "        }}\n",
+            "    }}\n",
+            "    match total {{\n",
+            "        0 => 1,\n",
+            "        _ => total,\n",
+            "    }}\n",
+            "}}\n\n",
+        ), i = i));
+    }
+    source
+}
+
+fn lex_parse(c: &mut Criterion) {
+    let source = generated_source(625);
+    let mut group = c.benchmark_group("frontend");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+
+    group.bench_function("lex", |b| {
+        b.iter(|| Lexer::new(black_box(&source), 0).tokenize())
+    });
+    group.bench_function("lex+parse", |b| {
+        b.iter(|| Parser::new(Lexer::new(black_box(&source), 0)).parse_program().unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, lex_parse);
+criterion_main!(benches);
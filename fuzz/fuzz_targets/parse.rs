@@ -0,0 +1,21 @@
+//! Feeds arbitrary bytes through the lexer, parser and semantic analyzer.
+//!
+//! Run with `cargo +nightly fuzz run parse` from the repository root; the
+//! corpus in `fuzz/corpus/parse` is seeded from `tests/` and `examples/`.
+//! Errors are expected; any panic or stack overflow is a bug, and the input
+//! that caused it belongs in `tests/fuzz_corpus.rs`.
+#![no_main]
+
+use aether_lang::frontend::lexer::Lexer;
+use aether_lang::frontend::parser::Parser;
+use aether_lang::frontend::semantic::SemanticAnalyzer;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data);
+    let Ok(program) = Parser::new(Lexer::new(&source, 0)).parse_program() else {
+        return;
+    };
+    let mut analyzer = SemanticAnalyzer::new();
+    let _ = analyzer.analyze(&program);
+});
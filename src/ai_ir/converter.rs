@@ -1,8 +1,12 @@
-//! AST to AI-IR Converter
+//! AST / IR to AI-IR Converter
 //!
-//! Converts the typed AST into an AI-IR representation for AI consumption.
+//! Converts the typed AST, or a lowered Aether IR module, into an AI-IR
+//! representation for AI consumption.
 
 use crate::frontend::ast::*;
+use crate::middle::ir::{IRFunction, IRModule, IRType, InlineHint, Instruction};
+use crate::middle::ir_printer::type_to_string;
+use crate::utils::Span;
 use super::*;
 
 /// Converter from AST to AI-IR
@@ -87,6 +91,13 @@ impl AIIRConverter {
             self.module.constraints.push(constraint);
         }
         
+        if func.has_annotation("hot") {
+            self.module.hints.push(hot_hint(func_id));
+        }
+        if func.annotations.iter().any(|a| a.name.name == "inline" && a.args.is_empty()) {
+            self.module.hints.push(inlinable_hint(func_id));
+        }
+
         // Analyze function body for call edges
         self.analyze_block(&func.body, func_id);
     }
@@ -139,7 +150,7 @@ impl AIIRConverter {
                     self.analyze_expr(v, parent_func);
                 }
             }
-            Stmt::Return { value, .. } => {
+            Stmt::Return { value, .. } | Stmt::Break { break_value: value, .. } => {
                 if let Some(v) = value {
                     self.analyze_expr(v, parent_func);
                 }
@@ -179,7 +190,277 @@ impl AIIRConverter {
             }
             Expr::Block(b) => self.analyze_block(b, parent_func),
             Expr::Try { expr, .. } => self.analyze_expr(expr, parent_func),
+            Expr::Loop { body, .. } => self.analyze_block(body, parent_func),
+            Expr::While { cond, body, .. } => {
+                self.analyze_expr(cond, parent_func);
+                self.analyze_block(body, parent_func);
+            }
+            Expr::For { iter, body, .. } => {
+                self.analyze_expr(iter, parent_func);
+                self.analyze_block(body, parent_func);
+            }
+            Expr::Match { expr, arms, .. } => {
+                self.analyze_expr(expr, parent_func);
+                for arm in arms {
+                    self.analyze_expr(&arm.body, parent_func);
+                }
+            }
+            // Recorded so tooling can audit why each block is unsafe
+            Expr::Unsafe { body, reason, verifier, span } => {
+                let func_name = self.module.graph.get_node(parent_func).map_or(String::new(), |n| n.name.clone());
+                let block = self.module.graph.push_unnamed_node(SemanticNode {
+                    id: NodeId(0),
+                    kind: NodeKind::UnsafeBlock {
+                        reason: reason.clone(),
+                        verifier: verifier.as_ref().map(|v| v.name.clone()),
+                    },
+                    span: *span,
+                    name: format!("{}.unsafe", func_name),
+                    intent: None,
+                });
+                self.module.graph.add_edge(parent_func, block, EdgeKind::DependsOn);
+                self.analyze_block(body, parent_func);
+            }
             _ => {}
         }
     }
 }
+
+/// Converter from Aether IR to AI-IR
+///
+/// IR carries no source spans, so every node gets `Span::dummy()`.
+pub struct IRConverter {
+    module: AIIRModule,
+    next_constraint_id: usize,
+}
+
+impl IRConverter {
+    /// Build the semantic graph of `ir_module`: function, type and call-site
+    /// nodes linked by `Calls`, `Returns` and `FieldOf` edges, with the
+    /// function contracts as constraints
+    pub fn convert(ir_module: &IRModule) -> AIIRModule {
+        let mut converter = Self {
+            module: AIIRModule::new(ir_module.name.clone()),
+            next_constraint_id: 0,
+        };
+
+        // Declare every callee and type before any edge refers to it
+        for s in &ir_module.structs {
+            let fields = s.fields.iter()
+                .map(|(name, ty)| (name.clone(), type_to_string(ty)))
+                .collect();
+            converter.add_type(&s.name, TypeNodeKind::Struct, fields);
+        }
+        for e in &ir_module.enums {
+            let fields = e.variants.iter()
+                .map(|v| (v.name.clone(), "variant".to_string()))
+                .collect();
+            converter.add_type(&e.name, TypeNodeKind::Enum, fields);
+        }
+        for ext in &ir_module.externs {
+            converter.add_function(&ext.name, &ext.params, &ext.ret_type, EffectSet::default());
+        }
+        let func_ids: Vec<NodeId> = ir_module.functions.iter()
+            .map(|f| converter.add_function(&f.name, &f.params, f.sret_type.as_ref().unwrap_or(&f.ret_type), Self::effects(f)))
+            .collect();
+
+        for s in &ir_module.structs {
+            let struct_id = converter.type_node(&IRType::Struct(s.name.clone()));
+            for (name, ty) in &s.fields {
+                let field_ty = converter.type_node(ty);
+                converter.module.graph.add_edge(field_ty, struct_id, EdgeKind::FieldOf { field: name.clone() });
+            }
+        }
+        for ext in &ir_module.externs {
+            if let Some(func_id) = converter.module.graph.lookup(&ext.name) {
+                converter.add_return(func_id, &ext.ret_type);
+            }
+        }
+        for (func, func_id) in ir_module.functions.iter().zip(func_ids) {
+            converter.add_return(func_id, func.sret_type.as_ref().unwrap_or(&func.ret_type));
+            converter.add_call_sites(func, func_id);
+            converter.add_contracts(func, func_id);
+            if func.hot {
+                converter.module.hints.push(hot_hint(func_id));
+            }
+            if func.inline == InlineHint::Always {
+                converter.module.hints.push(inlinable_hint(func_id));
+            }
+        }
+        converter.module
+    }
+
+    fn add_type(&mut self, name: &str, type_kind: TypeNodeKind, fields: Vec<(String, String)>) -> NodeId {
+        self.module.graph.add_node(NodeKind::Type { type_kind, fields }, name.to_string(), Span::dummy())
+    }
+
+    fn add_function(&mut self, name: &str, params: &[(String, IRType)], ret: &IRType, effects: EffectSet) -> NodeId {
+        let params = params.iter()
+            .map(|(name, ty)| (name.clone(), type_to_string(ty)))
+            .collect();
+        let is_pure = effects.is_pure;
+        let return_type = (*ret != IRType::Void).then(|| type_to_string(ret));
+        self.module.graph.add_node(
+            NodeKind::Function { params, return_type, effects, is_pure },
+            name.to_string(),
+            Span::dummy(),
+        )
+    }
+
+    /// Node for `ty`, creating a primitive type node on first use
+    fn type_node(&mut self, ty: &IRType) -> NodeId {
+        let name = type_to_string(ty);
+        match self.module.graph.lookup(&name) {
+            Some(id) if matches!(self.module.graph.get_node(id).map(|n| &n.kind), Some(NodeKind::Type { .. })) => id,
+            _ => self.add_type(&name, TypeNodeKind::Primitive, Vec::new()),
+        }
+    }
+
+    fn add_return(&mut self, func_id: NodeId, ret: &IRType) {
+        if *ret != IRType::Void {
+            let ret_id = self.type_node(ret);
+            self.module.graph.add_edge(func_id, ret_id, EdgeKind::Returns);
+        }
+    }
+
+    /// One `CallSite` node per call instruction, owned by the caller and
+    /// linked to the callee when it is declared in the module
+    fn add_call_sites(&mut self, func: &IRFunction, func_id: NodeId) {
+        for inst in func.blocks.iter().flat_map(|b| &b.instructions) {
+            let Instruction::Call { func: callee, args, .. } = inst else { continue };
+            let site = self.module.graph.push_unnamed_node(SemanticNode {
+                id: NodeId(0),
+                kind: NodeKind::CallSite { callee: callee.clone(), arg_count: args.len() },
+                span: Span::dummy(),
+                name: format!("{} -> {}", func.name, callee),
+                intent: None,
+            });
+            self.module.graph.add_edge(func_id, site, EdgeKind::DependsOn);
+            let target = self.module.graph.lookup(callee)
+                .filter(|id| matches!(self.module.graph.get_node(*id).map(|n| &n.kind), Some(NodeKind::Function { .. })));
+            if let Some(callee_id) = target {
+                self.module.graph.add_edge(site, callee_id, EdgeKind::Calls);
+            }
+        }
+    }
+
+    fn add_contracts(&mut self, func: &IRFunction, func_id: NodeId) {
+        let contracts = &func.contracts;
+        let requires = contracts.requires.iter().map(|e| (e, true));
+        let ensures = contracts.ensures.iter().map(|e| (e, false));
+        for (expr, is_pre) in requires.chain(ensures) {
+            let id = ConstraintId(self.next_constraint_id);
+            self.next_constraint_id += 1;
+            let constraint = if is_pre {
+                Constraint::precondition(id, func_id, expr.clone(), Span::dummy())
+            } else {
+                Constraint::postcondition(id, func_id, expr.clone(), Span::dummy())
+            };
+            self.module.constraints.push(constraint);
+        }
+    }
+
+    /// Effects recorded in the IR contracts; unknown names are ignored
+    fn effects(func: &IRFunction) -> EffectSet {
+        let effects = func.contracts.effects.iter()
+            .filter_map(|e| match e.to_ascii_lowercase().as_str() {
+                "read" => Some(Effect::Read),
+                "write" => Some(Effect::Write),
+                "io" => Some(Effect::IO),
+                "alloc" => Some(Effect::Alloc),
+                "panic" => Some(Effect::Panic),
+                _ => None,
+            })
+            .collect();
+        EffectSet { is_pure: false, effects }
+    }
+}
+
+/// Hint for a `#[hot]` function; the call count is not measured
+fn hot_hint(target: NodeId) -> OptimizationHint {
+    OptimizationHint { target, kind: OptimizationHintKind::Hotspot { estimated_calls: 0 }, priority: u8::MAX }
+}
+
+/// Hint for an `#[inline]` function
+fn inlinable_hint(target: NodeId) -> OptimizationHint {
+    OptimizationHint { target, kind: OptimizationHintKind::Inlinable, priority: u8::MAX }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middle::ir_parser::parse_ir;
+
+    #[test]
+    fn test_unsafe_block_reasons() {
+        let source = "fn check(p: *u8) -> bool { return true }\n\
+                      fn read(p: *u8) -> u8 {\n\
+                          while true { unsafe(reason = \"p comes from a live buffer\", verifier = check) { return *p } }\n\
+                          return 0\n\
+                      }";
+        let program = crate::frontend::parser::Parser::new(crate::frontend::lexer::Lexer::new(source, 0)).parse_program().unwrap();
+        let ai = AIIRConverter::new("demo".to_string()).convert(&program);
+        let graph = &ai.graph;
+
+        let read = graph.lookup("read").unwrap();
+        let blocks: Vec<&SemanticNode> = graph.edges_from(read).iter()
+            .filter_map(|e| graph.get_node(e.to))
+            .filter(|n| matches!(n.kind, NodeKind::UnsafeBlock { .. }))
+            .collect();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].name, "read.unsafe");
+        assert!(matches!(&blocks[0].kind, NodeKind::UnsafeBlock { reason: Some(r), verifier: Some(v) }
+            if r == "p comes from a live buffer" && v == "check"));
+    }
+
+    #[test]
+    fn test_convert_ir_module() {
+        let module = parse_ir(
+            "module demo\n\
+             struct Point { x: i64, y: i64 }\n\
+             extern fn puts(s: *u8) -> i32\n\
+             #[requires(\"n > 0\")]\n\
+             #[ensures(\"result > n\")]\n\
+             #[hot]\n\
+             fn double(n: i64) -> i64 {\n\
+               bb0 entry:\n\
+                 %0 = mul arg0 2\n\
+                 %1 = call puts(\"hi\")\n\
+                 %2 = call missing()\n\
+                 ret %0\n\
+             }\n",
+        ).unwrap();
+        let ai = IRConverter::convert(&module);
+        let graph = &ai.graph;
+
+        let double = graph.lookup("double").unwrap();
+        let point = graph.lookup("Point").unwrap();
+        let i64_ty = graph.lookup("i64").unwrap();
+        assert!(matches!(graph.get_node(i64_ty).unwrap().kind, NodeKind::Type { type_kind: TypeNodeKind::Primitive, .. }));
+
+        let fields: Vec<&str> = graph.edges_to(point).iter()
+            .filter_map(|e| match &e.kind { EdgeKind::FieldOf { field } if e.from == i64_ty => Some(field.as_str()), _ => None })
+            .collect();
+        assert_eq!(fields, vec!["x", "y"]);
+        assert!(graph.edges_from(double).iter().any(|e| matches!(e.kind, EdgeKind::Returns) && e.to == i64_ty));
+
+        // Both calls get a site; only the declared callee gets a `Calls` edge
+        let sites: Vec<NodeId> = graph.edges_from(double).iter()
+            .filter(|e| matches!(e.kind, EdgeKind::DependsOn))
+            .map(|e| e.to)
+            .collect();
+        assert_eq!(sites.len(), 2);
+        let puts = graph.lookup("puts").unwrap();
+        assert!(graph.edges_from(sites[0]).iter().any(|e| matches!(e.kind, EdgeKind::Calls) && e.to == puts));
+        assert!(graph.edges_from(sites[1]).is_empty());
+
+        assert_eq!(ai.constraints.len(), 2);
+        assert!(matches!(&ai.constraints[0].kind, ConstraintKind::Precondition { expr } if expr == "n > 0"));
+        assert!(matches!(&ai.constraints[1].kind, ConstraintKind::Postcondition { expr } if expr == "result > n"));
+        assert!(ai.constraints.iter().all(|c| c.target == double));
+
+        assert_eq!(ai.hints.len(), 1);
+        assert_eq!(ai.hints[0].target, double);
+        assert!(matches!(ai.hints[0].kind, OptimizationHintKind::Hotspot { .. }));
+    }
+}
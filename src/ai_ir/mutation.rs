@@ -3,8 +3,10 @@
 //! Provides safe code transformation operations for AI self-iteration.
 //! All mutations are validated before application.
 
-use super::{NodeId, AIIRModule};
-use super::semantic_graph::NodeKind;
+use super::{NodeId, EdgeId, AIIRModule};
+use super::semantic_graph::{NodeKind, EdgeKind, SemanticNode, SemanticEdge};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
 // ==================== Mutation Types ====================
 
@@ -349,3 +351,404 @@ impl OptimizableRegions {
         self.nodes.contains(&node) && !self.frozen.contains(&node)
     }
 }
+
+// ==================== Transactional Mutator ====================
+
+/// Error from an `IRMutator` operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MutationError {
+    /// No node with this ID
+    UnknownNode(NodeId),
+    /// No live edge with this ID
+    UnknownEdge(EdgeId),
+    /// An edge endpoint does not exist
+    DanglingEdge { edge: EdgeId, node: NodeId },
+    /// An edge closes a cycle among edges of a kind that must stay acyclic
+    Cycle { edge: EdgeId },
+    /// The context is not the innermost open transaction
+    StaleContext,
+}
+
+impl fmt::Display for MutationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MutationError::UnknownNode(id) => write!(f, "no node {}", id.0),
+            MutationError::UnknownEdge(id) => write!(f, "no edge {}", id.0),
+            MutationError::DanglingEdge { edge, node } => {
+                write!(f, "edge {} refers to missing node {}", edge.0, node.0)
+            }
+            MutationError::Cycle { edge } => write!(f, "edge {} closes a forbidden cycle", edge.0),
+            MutationError::StaleContext => write!(f, "mutation context is not the innermost transaction"),
+        }
+    }
+}
+
+impl std::error::Error for MutationError {}
+
+/// State of every slot a transaction touched, saved before its first change
+#[derive(Debug, Default)]
+struct Snapshot {
+    node_count: usize,
+    edge_slots: usize,
+    nodes: HashMap<NodeId, SemanticNode>,
+    removed_edges: HashMap<EdgeId, SemanticEdge>,
+    names: HashMap<String, Option<NodeId>>,
+}
+
+impl Snapshot {
+    /// Fold in a committed nested transaction, keeping the older saved state
+    fn absorb(&mut self, inner: Snapshot) {
+        for (id, node) in inner.nodes {
+            self.nodes.entry(id).or_insert(node);
+        }
+        for (id, edge) in inner.removed_edges {
+            self.removed_edges.entry(id).or_insert(edge);
+        }
+        for (name, id) in inner.names {
+            self.names.entry(name).or_insert(id);
+        }
+    }
+}
+
+/// Handle for an open transaction, consumed by `commit` or `rollback`
+#[derive(Debug)]
+#[must_use = "a mutation context must be committed or rolled back"]
+pub struct MutationContext {
+    depth: usize,
+}
+
+/// Checked rewriting of an AI-IR graph.
+///
+/// Changes made between `begin_mutation` and `commit` are validated on
+/// commit and undone if validation fails or the transaction is rolled back.
+/// Transactions nest; any still open when the mutator drops are rolled back.
+/// Outside a transaction changes apply directly and `check_invariants`
+/// validates the whole graph.
+pub struct IRMutator<'a> {
+    module: &'a mut AIIRModule,
+    snapshots: Vec<Snapshot>,
+}
+
+impl AIIRModule {
+    /// Start rewriting this module
+    pub fn mutator(&mut self) -> IRMutator<'_> {
+        IRMutator::new(self)
+    }
+}
+
+impl<'a> IRMutator<'a> {
+    pub fn new(module: &'a mut AIIRModule) -> Self {
+        Self { module, snapshots: Vec::new() }
+    }
+
+    /// The module as currently mutated
+    pub fn module(&self) -> &AIIRModule {
+        self.module
+    }
+
+    /// Open a transaction
+    pub fn begin_mutation(&mut self) -> MutationContext {
+        let graph = &self.module.graph;
+        self.snapshots.push(Snapshot {
+            node_count: graph.node_count(),
+            edge_slots: graph.edge_slots(),
+            ..Snapshot::default()
+        });
+        MutationContext { depth: self.snapshots.len() }
+    }
+
+    /// Validate the edges added since `ctx` began and keep its changes.
+    /// On failure the transaction is rolled back. Committing the outermost
+    /// transaction bumps the module version.
+    pub fn commit(&mut self, ctx: MutationContext) -> Result<(), MutationError> {
+        if ctx.depth != self.snapshots.len() {
+            return Err(MutationError::StaleContext);
+        }
+        let Some(snapshot) = self.snapshots.pop() else {
+            return Err(MutationError::StaleContext);
+        };
+        if let Err(err) = self.check_edges(snapshot.edge_slots) {
+            self.restore(snapshot);
+            return Err(err);
+        }
+        match self.snapshots.last_mut() {
+            Some(outer) => outer.absorb(snapshot),
+            None => self.module.metadata.version += 1,
+        }
+        Ok(())
+    }
+
+    /// Undo everything since `ctx` began, including nested transactions
+    pub fn rollback(&mut self, ctx: MutationContext) {
+        while self.snapshots.len() >= ctx.depth {
+            match self.snapshots.pop() {
+                Some(snapshot) => self.restore(snapshot),
+                None => break,
+            }
+        }
+    }
+
+    /// Check every edge for dangling endpoints and forbidden cycles
+    pub fn check_invariants(&self) -> Result<(), MutationError> {
+        self.check_edges(0)
+    }
+
+    /// Replace a node in place; its ID and edges are kept
+    pub fn replace_node(&mut self, id: NodeId, new_node: SemanticNode) -> Result<(), MutationError> {
+        let old_name = match self.module.graph.get_node(id) {
+            Some(node) => node.name.clone(),
+            None => return Err(MutationError::UnknownNode(id)),
+        };
+        self.save_name(&old_name);
+        self.save_name(&new_node.name);
+        if let Some(old) = self.module.graph.replace_node(id, new_node) {
+            if let Some(snapshot) = self.snapshots.last_mut() {
+                if id.0 < snapshot.node_count {
+                    snapshot.nodes.entry(id).or_insert(old);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Add an edge. Its endpoints and acyclicity are checked on commit.
+    pub fn insert_edge(&mut self, from: NodeId, to: NodeId, kind: EdgeKind) -> EdgeId {
+        self.module.graph.add_edge(from, to, kind)
+    }
+
+    /// Remove a live edge
+    pub fn remove_edge(&mut self, id: EdgeId) -> Result<(), MutationError> {
+        let edge = self.module.graph.remove_edge(id).ok_or(MutationError::UnknownEdge(id))?;
+        if let Some(snapshot) = self.snapshots.last_mut() {
+            if id.0 < snapshot.edge_slots {
+                snapshot.removed_edges.insert(id, edge);
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy `root` and the nodes it reaches through outgoing edges, stopping
+    /// at function and type nodes, which the copy shares with the original.
+    /// Edges within the subgraph are duplicated between the copies; edges
+    /// leaving it keep their original targets. Copies are not name-indexed,
+    /// so `lookup` still finds the originals. Returns the copy of `root`.
+    ///
+    /// Panics if `root` does not exist.
+    pub fn clone_subgraph(&mut self, root: NodeId) -> NodeId {
+        let graph = &self.module.graph;
+        assert!(graph.get_node(root).is_some(), "clone_subgraph: no node {}", root.0);
+
+        let mut members = vec![root];
+        let mut seen = HashSet::from([root]);
+        let mut next = 0;
+        while next < members.len() {
+            for edge in graph.edges_from(members[next]) {
+                let shared = graph.get_node(edge.to)
+                    .is_none_or(|n| matches!(n.kind, NodeKind::Function { .. } | NodeKind::Type { .. }));
+                if !shared && seen.insert(edge.to) {
+                    members.push(edge.to);
+                }
+            }
+            next += 1;
+        }
+
+        let nodes: Vec<SemanticNode> = members.iter()
+            .filter_map(|&id| graph.get_node(id).cloned())
+            .collect();
+        let edges: Vec<(NodeId, NodeId, EdgeKind)> = members.iter()
+            .flat_map(|&id| graph.edges_from(id))
+            .map(|e| (e.from, e.to, e.kind.clone()))
+            .collect();
+
+        let graph = &mut self.module.graph;
+        let mut copies = HashMap::new();
+        for node in nodes {
+            let original = node.id;
+            copies.insert(original, graph.push_unnamed_node(node));
+        }
+        for (from, to, kind) in edges {
+            let to = copies.get(&to).copied().unwrap_or(to);
+            graph.add_edge(copies[&from], to, kind);
+        }
+        copies[&root]
+    }
+
+    /// Record the current index entry for `name` before it changes
+    fn save_name(&mut self, name: &str) {
+        let current = self.module.graph.lookup(name);
+        if let Some(snapshot) = self.snapshots.last_mut() {
+            snapshot.names.entry(name.to_string()).or_insert(current);
+        }
+    }
+
+    /// Check edges with IDs from `first` on
+    fn check_edges(&self, first: usize) -> Result<(), MutationError> {
+        let graph = &self.module.graph;
+        for edge in graph.edges().filter(|e| e.id.0 >= first) {
+            for node in [edge.from, edge.to] {
+                if graph.get_node(node).is_none() {
+                    return Err(MutationError::DanglingEdge { edge: edge.id, node });
+                }
+            }
+            if edge.kind.is_acyclic() && self.reaches(edge.to, edge.from, &edge.kind) {
+                return Err(MutationError::Cycle { edge: edge.id });
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `target` is reachable from `start` along edges of `kind`'s variant
+    fn reaches(&self, start: NodeId, target: NodeId, kind: &EdgeKind) -> bool {
+        let kind = std::mem::discriminant(kind);
+        let mut visited = HashSet::new();
+        let mut stack = vec![start];
+        while let Some(node) = stack.pop() {
+            if node == target {
+                return true;
+            }
+            if !visited.insert(node) {
+                continue;
+            }
+            stack.extend(self.module.graph.edges_from(node).into_iter()
+                .filter(|e| std::mem::discriminant(&e.kind) == kind)
+                .map(|e| e.to));
+        }
+        false
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        let graph = &mut self.module.graph;
+        for node in snapshot.nodes.into_values() {
+            graph.restore_node(node);
+        }
+        for edge in snapshot.removed_edges.into_values() {
+            graph.restore_edge(edge);
+        }
+        for (name, id) in snapshot.names {
+            graph.set_name_entry(&name, id);
+        }
+        graph.truncate(snapshot.node_count, snapshot.edge_slots);
+    }
+}
+
+impl Drop for IRMutator<'_> {
+    fn drop(&mut self) {
+        while let Some(snapshot) = self.snapshots.pop() {
+            self.restore(snapshot);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_ir::semantic_graph::ExprNodeKind;
+    use crate::frontend::ast::EffectSet;
+    use crate::utils::Span;
+
+    fn function(module: &mut AIIRModule, name: &str) -> NodeId {
+        let kind = NodeKind::Function {
+            params: Vec::new(),
+            return_type: None,
+            effects: EffectSet { is_pure: true, effects: Vec::new() },
+            is_pure: true,
+        };
+        module.graph.add_node(kind, name.to_string(), Span::dummy())
+    }
+
+    /// f owns a block that owns a call expression; the call targets g
+    fn sample_module() -> (AIIRModule, [NodeId; 4]) {
+        let mut module = AIIRModule::new("test".to_string());
+        let f = function(&mut module, "f");
+        let g = function(&mut module, "g");
+        let block = module.graph.add_node(NodeKind::Block { stmt_count: 1 }, "f.body".to_string(), Span::dummy());
+        let call = module.graph.add_node(
+            NodeKind::Expression { expr_kind: ExprNodeKind::Call, type_name: "i32".to_string() },
+            "f.call".to_string(),
+            Span::dummy(),
+        );
+        module.graph.add_edge(f, block, EdgeKind::Owns);
+        module.graph.add_edge(block, call, EdgeKind::Owns);
+        module.graph.add_edge(call, g, EdgeKind::Calls);
+        (module, [f, g, block, call])
+    }
+
+    fn targets(module: &AIIRModule, id: NodeId) -> Vec<NodeId> {
+        module.graph.edges_from(id).iter().map(|e| e.to).collect()
+    }
+
+    #[test]
+    fn test_rollback_restores_graph() {
+        let (mut module, [f, g, block, _]) = sample_module();
+        let mut mutator = module.mutator();
+        let ctx = mutator.begin_mutation();
+        let mut renamed = mutator.module().get_node(f).cloned().unwrap();
+        renamed.name = "entry".to_string();
+        mutator.replace_node(f, renamed).unwrap();
+        mutator.remove_edge(EdgeId(0)).unwrap();
+        mutator.insert_edge(f, g, EdgeKind::Calls);
+        mutator.clone_subgraph(block);
+        mutator.rollback(ctx);
+
+        assert_eq!(mutator.remove_edge(EdgeId(3)), Err(MutationError::UnknownEdge(EdgeId(3))));
+        drop(mutator);
+        assert_eq!(module.graph.node_count(), 4);
+        assert_eq!(module.graph.edge_count(), 3);
+        assert_eq!(module.graph.lookup("f"), Some(f));
+        assert_eq!(module.graph.lookup("entry"), None);
+        assert_eq!(targets(&module, f), vec![block]);
+        assert_eq!(module.metadata.version, 0);
+    }
+
+    #[test]
+    fn test_commit_checks_invariants() {
+        let (mut module, [f, g, block, call]) = sample_module();
+        let mut mutator = module.mutator();
+
+        // Calls may be recursive, ownership may not
+        let ctx = mutator.begin_mutation();
+        mutator.insert_edge(g, f, EdgeKind::Calls);
+        assert_eq!(mutator.commit(ctx), Ok(()));
+
+        let ctx = mutator.begin_mutation();
+        let back = mutator.insert_edge(call, f, EdgeKind::Owns);
+        assert_eq!(mutator.commit(ctx), Err(MutationError::Cycle { edge: back }));
+
+        let ctx = mutator.begin_mutation();
+        let dangling = mutator.insert_edge(block, NodeId(99), EdgeKind::DependsOn);
+        assert_eq!(
+            mutator.commit(ctx),
+            Err(MutationError::DanglingEdge { edge: dangling, node: NodeId(99) })
+        );
+
+        let outer = mutator.begin_mutation();
+        let inner = mutator.begin_mutation();
+        assert_eq!(mutator.commit(outer), Err(MutationError::StaleContext));
+        mutator.rollback(inner);
+        assert_eq!(mutator.check_invariants(), Ok(()));
+        drop(mutator);
+
+        assert_eq!(module.graph.edge_count(), 4);
+        assert_eq!(targets(&module, g), vec![f]);
+        assert_eq!(module.metadata.version, 1);
+    }
+
+    #[test]
+    fn test_clone_subgraph() {
+        let (mut module, [f, g, block, call]) = sample_module();
+        let mut mutator = module.mutator();
+        let ctx = mutator.begin_mutation();
+        let copy = mutator.clone_subgraph(f);
+        mutator.commit(ctx).unwrap();
+        drop(mutator);
+
+        // f, its block and its call are copied; g is shared
+        assert_eq!(module.graph.node_count(), 7);
+        let copy_block = targets(&module, copy)[0];
+        let copy_call = targets(&module, copy_block)[0];
+        assert!(![f, g, block, call].contains(&copy_block));
+        assert_eq!(module.get_node(copy_call).unwrap().name, "f.call");
+        assert_eq!(targets(&module, copy_call), vec![g]);
+        assert_eq!(module.graph.lookup("f"), Some(f));
+    }
+}
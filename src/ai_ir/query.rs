@@ -3,7 +3,11 @@
 //! This module provides query methods for AI to understand code structure,
 //! relationships, and constraints.
 
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
 use super::{NodeId, AIIRModule, EdgeKind, Constraint};
+use super::semantic_graph::{NodeKind, SemanticGraph, SemanticNode};
 
 /// Query result for callers of a function
 #[derive(Debug, Clone)]
@@ -322,3 +326,284 @@ fn parse_ownership_type(type_str: &str) -> (String, String) {
     }
     ("own".to_string(), type_str.to_string())
 }
+
+// ==================== Graph Navigation ====================
+
+/// Error returned by `topological_sort` when the graph has a cycle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleError {
+    /// A node that lies on a cycle
+    pub node: NodeId,
+}
+
+impl fmt::Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "semantic graph contains a cycle through node {}", self.node.0)
+    }
+}
+
+impl std::error::Error for CycleError {}
+
+/// Graph algorithms over the semantic graph.
+/// Edges are followed in their stored direction unless stated otherwise.
+impl SemanticGraph {
+    /// All nodes matching a predicate, in ID order
+    pub fn query_nodes(&self, predicate: impl Fn(&SemanticNode) -> bool) -> Vec<NodeId> {
+        self.nodes().iter()
+            .filter(|n| predicate(n))
+            .map(|n| n.id)
+            .collect()
+    }
+
+    /// Shortest path (fewest edges) from `from` to `to`, inclusive of both ends
+    pub fn shortest_path(&self, from: NodeId, to: NodeId) -> Option<Vec<NodeId>> {
+        if self.get_node(from).is_none() || self.get_node(to).is_none() {
+            return None;
+        }
+
+        let mut parent: Vec<Option<NodeId>> = vec![None; self.node_count()];
+        let mut visited = vec![false; self.node_count()];
+        let mut queue = VecDeque::new();
+        visited[from.0] = true;
+        queue.push_back(from);
+
+        while let Some(node) = queue.pop_front() {
+            if node == to {
+                let mut path = vec![to];
+                let mut current = to;
+                while let Some(prev) = parent[current.0] {
+                    path.push(prev);
+                    current = prev;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for edge in self.edges_from(node) {
+                if !visited[edge.to.0] {
+                    visited[edge.to.0] = true;
+                    parent[edge.to.0] = Some(node);
+                    queue.push_back(edge.to);
+                }
+            }
+        }
+        None
+    }
+
+    /// Nodes reachable from `start` when edges are treated as undirected,
+    /// in depth-first discovery order
+    pub fn connected_component(&self, start: NodeId) -> Vec<NodeId> {
+        if self.get_node(start).is_none() {
+            return Vec::new();
+        }
+
+        let mut visited = HashSet::new();
+        let mut order = Vec::new();
+        let mut stack = vec![start];
+
+        while let Some(node) = stack.pop() {
+            if !visited.insert(node) {
+                continue;
+            }
+            order.push(node);
+            let neighbors = self.edges_from(node).into_iter().map(|e| e.to)
+                .chain(self.edges_to(node).into_iter().map(|e| e.from));
+            // Push in reverse so neighbors are visited in edge order
+            let neighbors: Vec<_> = neighbors.filter(|n| !visited.contains(n)).collect();
+            stack.extend(neighbors.into_iter().rev());
+        }
+        order
+    }
+
+    /// Order nodes so every edge points from an earlier node to a later one.
+    /// Ties are broken by node ID, so the result is deterministic.
+    pub fn topological_sort(&self) -> Result<Vec<NodeId>, CycleError> {
+        let count = self.node_count();
+        let mut in_degree = vec![0usize; count];
+        for node in self.nodes() {
+            for edge in self.edges_from(node.id) {
+                in_degree[edge.to.0] += 1;
+            }
+        }
+
+        let mut ready: std::collections::BTreeSet<usize> = (0..count)
+            .filter(|&i| in_degree[i] == 0)
+            .collect();
+        let mut order = Vec::with_capacity(count);
+
+        while let Some(index) = ready.pop_first() {
+            let node = NodeId(index);
+            order.push(node);
+            for edge in self.edges_from(node) {
+                in_degree[edge.to.0] -= 1;
+                if in_degree[edge.to.0] == 0 {
+                    ready.insert(edge.to.0);
+                }
+            }
+        }
+
+        if order.len() < count {
+            // Every unsorted node has an unsorted predecessor; walking backwards
+            // must revisit a node, and the first repeat lies on a cycle.
+            let mut node = (0..count).find(|&i| in_degree[i] > 0).map(NodeId).unwrap_or(NodeId(0));
+            let mut seen = HashSet::new();
+            while seen.insert(node) {
+                match self.edges_to(node).iter().find(|e| in_degree[e.from.0] > 0) {
+                    Some(edge) => node = edge.from,
+                    None => break,
+                }
+            }
+            return Err(CycleError { node });
+        }
+        Ok(order)
+    }
+
+    /// Start a fluent query over the graph's nodes
+    pub fn query(&self) -> QueryBuilder<'_> {
+        QueryBuilder::new(self)
+    }
+}
+
+/// Node category used to filter queries without matching on `NodeKind` fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeCategory {
+    Function,
+    Type,
+    Variable,
+    Expression,
+    Block,
+    CallSite,
+    UnsafeBlock,
+}
+
+impl NodeCategory {
+    /// Category of a node kind
+    pub fn of(kind: &NodeKind) -> Self {
+        match kind {
+            NodeKind::Function { .. } => NodeCategory::Function,
+            NodeKind::Type { .. } => NodeCategory::Type,
+            NodeKind::Variable { .. } => NodeCategory::Variable,
+            NodeKind::Expression { .. } => NodeCategory::Expression,
+            NodeKind::Block { .. } => NodeCategory::Block,
+            NodeKind::CallSite { .. } => NodeCategory::CallSite,
+            NodeKind::UnsafeBlock { .. } => NodeCategory::UnsafeBlock,
+        }
+    }
+}
+
+/// Boxed node predicate held by a `QueryBuilder`
+type NodeFilter<'a> = Box<dyn Fn(&SemanticNode) -> bool + 'a>;
+
+/// Fluent node query: `graph.query().kind(NodeCategory::Function).pure(true).collect()`
+pub struct QueryBuilder<'a> {
+    graph: &'a SemanticGraph,
+    filters: Vec<NodeFilter<'a>>,
+}
+
+impl<'a> QueryBuilder<'a> {
+    pub fn new(graph: &'a SemanticGraph) -> Self {
+        Self { graph, filters: Vec::new() }
+    }
+
+    /// Keep nodes of the given category
+    pub fn kind(self, category: NodeCategory) -> Self {
+        self.filter(move |n| NodeCategory::of(&n.kind) == category)
+    }
+
+    /// Keep functions whose purity matches (non-functions are dropped)
+    pub fn pure(self, is_pure: bool) -> Self {
+        self.filter(move |n| matches!(&n.kind, NodeKind::Function { is_pure: p, .. } if *p == is_pure))
+    }
+
+    /// Keep nodes with exactly this name
+    pub fn name(self, name: &'a str) -> Self {
+        self.filter(move |n| n.name == name)
+    }
+
+    /// Keep nodes with at least one outgoing edge to `target`
+    pub fn references(self, target: NodeId) -> Self {
+        let graph = self.graph;
+        self.filter(move |n| graph.edges_from(n.id).iter().any(|e| e.to == target))
+    }
+
+    /// Keep nodes matching an arbitrary predicate
+    pub fn filter(mut self, predicate: impl Fn(&SemanticNode) -> bool + 'a) -> Self {
+        self.filters.push(Box::new(predicate));
+        self
+    }
+
+    /// Matching node IDs, in ID order
+    pub fn collect(self) -> Vec<NodeId> {
+        self.graph.query_nodes(|n| self.filters.iter().all(|f| f(n)))
+    }
+
+    /// Number of matching nodes
+    pub fn count(self) -> usize {
+        self.collect().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_ir::semantic_graph::TypeNodeKind;
+    use crate::frontend::ast::EffectSet;
+    use crate::utils::Span;
+
+    fn function(graph: &mut SemanticGraph, name: &str, is_pure: bool) -> NodeId {
+        let kind = NodeKind::Function {
+            params: Vec::new(),
+            return_type: None,
+            effects: EffectSet { is_pure, effects: Vec::new() },
+            is_pure,
+        };
+        graph.add_node(kind, name.to_string(), Span::dummy())
+    }
+
+    /// main -> parse -> lex, main -> emit, plus an unrelated type node
+    fn sample_graph() -> (SemanticGraph, [NodeId; 5]) {
+        let mut graph = SemanticGraph::new();
+        let main = function(&mut graph, "main", false);
+        let parse = function(&mut graph, "parse", true);
+        let lex = function(&mut graph, "lex", true);
+        let emit = function(&mut graph, "emit", false);
+        let token = graph.add_node(
+            NodeKind::Type { type_kind: TypeNodeKind::Struct, fields: Vec::new() },
+            "Token".to_string(),
+            Span::dummy(),
+        );
+        graph.add_edge(main, parse, EdgeKind::Calls);
+        graph.add_edge(parse, lex, EdgeKind::Calls);
+        graph.add_edge(main, emit, EdgeKind::Calls);
+        (graph, [main, parse, lex, emit, token])
+    }
+
+    #[test]
+    fn test_query_builder() {
+        let (graph, [_, parse, lex, _, token]) = sample_graph();
+        assert_eq!(graph.query().kind(NodeCategory::Function).pure(true).collect(), vec![parse, lex]);
+        assert_eq!(graph.query().kind(NodeCategory::Type).collect(), vec![token]);
+        assert_eq!(graph.query().references(lex).name("parse").count(), 1);
+    }
+
+    #[test]
+    fn test_shortest_path_and_component() {
+        let (graph, [main, parse, lex, emit, token]) = sample_graph();
+        assert_eq!(graph.shortest_path(main, lex), Some(vec![main, parse, lex]));
+        assert_eq!(graph.shortest_path(lex, main), None);
+        assert_eq!(graph.shortest_path(emit, emit), Some(vec![emit]));
+
+        assert_eq!(graph.connected_component(lex), vec![lex, parse, main, emit]);
+        assert_eq!(graph.connected_component(token), vec![token]);
+    }
+
+    #[test]
+    fn test_topological_sort() {
+        let (mut graph, [main, parse, lex, emit, token]) = sample_graph();
+        assert_eq!(graph.topological_sort(), Ok(vec![main, parse, lex, emit, token]));
+
+        // emit hangs off the cycle but is not on it
+        graph.add_edge(lex, main, EdgeKind::Calls);
+        let err = graph.topological_sort().unwrap_err();
+        assert!([main, parse, lex].contains(&err.node));
+    }
+}
@@ -17,8 +17,8 @@ pub struct SemanticGraph {
     /// All nodes in the graph
     nodes: Vec<SemanticNode>,
     
-    /// All edges in the graph
-    edges: Vec<SemanticEdge>,
+    /// All edges in the graph, indexed by ID; removed edges leave a `None` slot
+    edges: Vec<Option<SemanticEdge>>,
     
     /// Index: node name → node ID (for quick lookup)
     name_index: HashMap<String, NodeId>,
@@ -78,6 +78,18 @@ pub enum NodeKind {
     Block {
         stmt_count: usize,
     },
+
+    /// A call instruction inside a function (from IR)
+    CallSite {
+        callee: String,
+        arg_count: usize,
+    },
+
+    /// An `unsafe` block, with the justification and verifier it was given
+    UnsafeBlock {
+        reason: Option<String>,
+        verifier: Option<String>,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -85,6 +97,8 @@ pub enum TypeNodeKind {
     Struct,
     Enum,
     Alias,
+    /// Builtin scalar, pointer or array type
+    Primitive,
 }
 
 #[derive(Debug, Clone)]
@@ -141,6 +155,19 @@ pub enum EdgeKind {
     
     /// Borrow relationship
     Borrows { mutable: bool },
+
+    /// Function returns a value of the target type
+    Returns,
+
+    /// Source type is the type of the named field of the target struct
+    FieldOf { field: String },
+}
+
+impl EdgeKind {
+    /// Whether edges of this kind must never form a cycle
+    pub fn is_acyclic(&self) -> bool {
+        matches!(self, EdgeKind::Owns | EdgeKind::TypeOf | EdgeKind::Implements)
+    }
 }
 
 // ==================== SemanticGraph Implementation ====================
@@ -168,7 +195,7 @@ impl SemanticGraph {
     /// Add an edge to the graph
     pub fn add_edge(&mut self, from: NodeId, to: NodeId, kind: EdgeKind) -> EdgeId {
         let id = EdgeId(self.edges.len());
-        self.edges.push(SemanticEdge { id, from, to, kind });
+        self.edges.push(Some(SemanticEdge { id, from, to, kind }));
         
         // Update indices
         self.edges_from_index.entry(from).or_default().push(id);
@@ -187,6 +214,11 @@ impl SemanticGraph {
         self.nodes.get_mut(id.0)
     }
     
+    /// All nodes, in ID order
+    pub fn nodes(&self) -> &[SemanticNode] {
+        &self.nodes
+    }
+    
     /// Look up a node by name
     pub fn lookup(&self, name: &str) -> Option<NodeId> {
         self.name_index.get(name).copied()
@@ -196,7 +228,7 @@ impl SemanticGraph {
     pub fn edges_from(&self, id: NodeId) -> Vec<&SemanticEdge> {
         self.edges_from_index
             .get(&id)
-            .map(|ids| ids.iter().filter_map(|eid| self.edges.get(eid.0)).collect())
+            .map(|ids| ids.iter().filter_map(|eid| self.get_edge(*eid)).collect())
             .unwrap_or_default()
     }
     
@@ -204,10 +236,90 @@ impl SemanticGraph {
     pub fn edges_to(&self, id: NodeId) -> Vec<&SemanticEdge> {
         self.edges_to_index
             .get(&id)
-            .map(|ids| ids.iter().filter_map(|eid| self.edges.get(eid.0)).collect())
+            .map(|ids| ids.iter().filter_map(|eid| self.get_edge(*eid)).collect())
             .unwrap_or_default()
     }
     
+    /// Get an edge by ID
+    pub fn get_edge(&self, id: EdgeId) -> Option<&SemanticEdge> {
+        self.edges.get(id.0).and_then(Option::as_ref)
+    }
+    
+    /// All live edges, in ID order
+    pub fn edges(&self) -> impl Iterator<Item = &SemanticEdge> {
+        self.edges.iter().flatten()
+    }
+    
+    /// Replace the node `id`, keeping its ID and re-indexing its name.
+    /// Returns the previous node.
+    pub fn replace_node(&mut self, id: NodeId, mut node: SemanticNode) -> Option<SemanticNode> {
+        let slot = self.nodes.get_mut(id.0)?;
+        node.id = id;
+        let old = std::mem::replace(slot, node);
+        if self.name_index.get(&old.name) == Some(&id) {
+            self.name_index.remove(&old.name);
+        }
+        self.name_index.insert(self.nodes[id.0].name.clone(), id);
+        Some(old)
+    }
+    
+    /// Remove an edge. Its ID is never reused.
+    pub fn remove_edge(&mut self, id: EdgeId) -> Option<SemanticEdge> {
+        let edge = self.edges.get_mut(id.0)?.take()?;
+        if let Some(ids) = self.edges_from_index.get_mut(&edge.from) {
+            ids.retain(|&e| e != id);
+        }
+        if let Some(ids) = self.edges_to_index.get_mut(&edge.to) {
+            ids.retain(|&e| e != id);
+        }
+        Some(edge)
+    }
+    
+    /// Append a node without entering it in the name index
+    pub(super) fn push_unnamed_node(&mut self, mut node: SemanticNode) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        node.id = id;
+        self.nodes.push(node);
+        id
+    }
+    
+    /// Point `name` at `id`, or drop it from the index; returns the previous entry
+    pub(super) fn set_name_entry(&mut self, name: &str, id: Option<NodeId>) -> Option<NodeId> {
+        match id {
+            Some(id) => self.name_index.insert(name.to_string(), id),
+            None => self.name_index.remove(name),
+        }
+    }
+    
+    /// Put back a node saved before a mutation
+    pub(super) fn restore_node(&mut self, node: SemanticNode) {
+        let id = node.id.0;
+        self.nodes[id] = node;
+    }
+    
+    /// Put back a removed edge; the edge indices are stale until `truncate` runs
+    pub(super) fn restore_edge(&mut self, edge: SemanticEdge) {
+        let id = edge.id.0;
+        self.edges[id] = Some(edge);
+    }
+    
+    /// Drop nodes and edge slots past the given counts and rebuild the edge indices
+    pub(super) fn truncate(&mut self, node_count: usize, edge_slots: usize) {
+        self.nodes.truncate(node_count);
+        self.edges.truncate(edge_slots);
+        self.edges_from_index.clear();
+        self.edges_to_index.clear();
+        for edge in self.edges.iter().flatten() {
+            self.edges_from_index.entry(edge.from).or_default().push(edge.id);
+            self.edges_to_index.entry(edge.to).or_default().push(edge.id);
+        }
+    }
+    
+    /// Number of edge slots, including removed edges
+    pub(super) fn edge_slots(&self) -> usize {
+        self.edges.len()
+    }
+    
     /// Get all nodes of a specific kind
     pub fn nodes_of_kind<F>(&self, predicate: F) -> Vec<&SemanticNode>
     where
@@ -233,6 +345,6 @@ impl SemanticGraph {
     
     /// Count total edges
     pub fn edge_count(&self) -> usize {
-        self.edges.len()
+        self.edges().count()
     }
 }
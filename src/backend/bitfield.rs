@@ -0,0 +1,179 @@
+//! Bitfield lowering
+//!
+//! The C backend declares `#[bits(N)]` fields as C bit fields. Backends
+//! without them see such a struct as it is laid out by `BitfieldLayout`:
+//! one unsigned integer per run of bitfields, named `__bits<k>`. This pass
+//! rewrites the struct definitions that way, renumbers field pointers, and
+//! turns every load and store through a pointer to a bitfield into a load
+//! of its unit followed by shift-and-mask operations. The arithmetic is
+//! done on `u64`, so constant operands need no narrower type.
+
+use std::collections::HashMap;
+
+use crate::middle::inline::next_register;
+use crate::middle::ir::*;
+use crate::types::layout::{BitSlot, BitStorage, BitfieldLayout};
+
+/// Where a pointer to a bitfield points: its unit, and its bits in it
+#[derive(Debug, Clone)]
+struct BitPlace {
+    slot: BitSlot,
+    unit: IRType,
+    field: IRType,
+}
+
+/// `module` with its bitfields lowered to storage integers, or None when
+/// no struct has any
+pub fn lower_bitfields(module: &IRModule) -> Option<IRModule> {
+    let layouts: HashMap<String, (BitfieldLayout, Vec<IRType>)> = module.structs.iter()
+        .filter_map(|s| {
+            let types = s.fields.iter().map(|(_, ty)| ty.clone()).collect();
+            Some((s.name.clone(), (s.bitfields()?, types)))
+        })
+        .collect();
+    if layouts.is_empty() {
+        return None;
+    }
+
+    let mut lowered = module.clone();
+    for def in &mut lowered.structs {
+        if let Some((bitfields, _)) = layouts.get(&def.name) {
+            def.fields = bitfields.storage.iter().enumerate()
+                .map(|(k, member)| match *member {
+                    BitStorage::Field(i) => def.fields[i].clone(),
+                    BitStorage::Unit { bytes } => (format!("__bits{}", k), unit_type(bytes)),
+                })
+                .collect();
+            def.bits.clear();
+        }
+    }
+    for func in &mut lowered.functions {
+        lower_function(func, &layouts);
+    }
+    Some(lowered)
+}
+
+/// Unsigned integer type of a `bytes`-byte storage unit
+fn unit_type(bytes: usize) -> IRType {
+    match bytes {
+        1 => IRType::U8,
+        2 => IRType::U16,
+        4 => IRType::U32,
+        _ => IRType::U64,
+    }
+}
+
+/// The low `width` bits set
+fn mask(width: u32) -> u64 {
+    if width >= 64 { u64::MAX } else { (1 << width) - 1 }
+}
+
+fn int(n: u64) -> Value {
+    Value::Constant(Constant::Int(n as i64))
+}
+
+fn lower_function(func: &mut IRFunction, layouts: &HashMap<String, (BitfieldLayout, Vec<IRType>)>) {
+    // Field pointers may be used in later blocks, so renumber them all first
+    let mut places: HashMap<Register, BitPlace> = HashMap::new();
+    for inst in func.blocks.iter_mut().flat_map(|b| &mut b.instructions) {
+        let Instruction::GetElementPtr { dest, index: Value::Constant(Constant::Int(i)), elem_ty: IRType::Struct(name), .. } = inst else {
+            continue;
+        };
+        let Some((bitfields, types)) = layouts.get(name.as_str()) else { continue };
+        let field = *i as usize;
+        let Some(&member) = bitfields.member.get(field) else { continue };
+        if let (Some(slot), BitStorage::Unit { bytes }) = (bitfields.slots[field], bitfields.storage[member]) {
+            places.insert(*dest, BitPlace { slot, unit: unit_type(bytes), field: types[field].clone() });
+        }
+        *i = member as i64;
+    }
+    if places.is_empty() {
+        return;
+    }
+
+    let mut next = next_register(func);
+    let mut fresh = || {
+        next += 1;
+        Register(next - 1)
+    };
+    for block in &mut func.blocks {
+        let mut instructions = Vec::with_capacity(block.instructions.len());
+        for inst in block.instructions.drain(..) {
+            match inst {
+                Instruction::Load { dest, ptr: Value::Register(ptr), volatile, .. } if places.contains_key(&ptr) => {
+                    let place = &places[&ptr];
+                    let (unit, wide, shifted, masked) = (fresh(), fresh(), fresh(), fresh());
+                    instructions.extend([
+                        Instruction::Load { dest: unit, ptr: Value::Register(ptr), ty: place.unit.clone(), volatile },
+                        Instruction::Cast { dest: wide, value: Value::Register(unit), ty: IRType::U64 },
+                        Instruction::BinOp { dest: shifted, op: BinOp::Shr, left: Value::Register(wide), right: int(place.slot.shift as u64) },
+                        Instruction::BinOp { dest: masked, op: BinOp::And, left: Value::Register(shifted), right: int(mask(place.slot.width)) },
+                        Instruction::Cast { dest, value: Value::Register(masked), ty: place.field.clone() },
+                    ]);
+                }
+                Instruction::Store { ptr: Value::Register(ptr), value, volatile } if places.contains_key(&ptr) => {
+                    let place = &places[&ptr];
+                    let field_mask = mask(place.slot.width);
+                    let kept_mask = !(field_mask << place.slot.shift);
+                    let (unit, wide, kept, new, bits, moved, merged, narrowed) =
+                        (fresh(), fresh(), fresh(), fresh(), fresh(), fresh(), fresh(), fresh());
+                    instructions.extend([
+                        Instruction::Load { dest: unit, ptr: Value::Register(ptr), ty: place.unit.clone(), volatile },
+                        Instruction::Cast { dest: wide, value: Value::Register(unit), ty: IRType::U64 },
+                        Instruction::BinOp { dest: kept, op: BinOp::And, left: Value::Register(wide), right: int(kept_mask) },
+                        Instruction::Cast { dest: new, value, ty: IRType::U64 },
+                        Instruction::BinOp { dest: bits, op: BinOp::And, left: Value::Register(new), right: int(field_mask) },
+                        Instruction::BinOp { dest: moved, op: BinOp::Shl, left: Value::Register(bits), right: int(place.slot.shift as u64) },
+                        Instruction::BinOp { dest: merged, op: BinOp::Or, left: Value::Register(kept), right: Value::Register(moved) },
+                        Instruction::Cast { dest: narrowed, value: Value::Register(merged), ty: place.unit.clone() },
+                        Instruction::Store { ptr: Value::Register(ptr), value: Value::Register(narrowed), volatile },
+                    ]);
+                }
+                other => instructions.push(other),
+            }
+        }
+        block.instructions = instructions;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::lexer::Lexer;
+    use crate::frontend::parser::Parser;
+    use crate::middle::ir_gen::IRGenerator;
+
+    fn lower(source: &str) -> IRModule {
+        let program = Parser::new(Lexer::new(source, 0)).parse_program().unwrap();
+        let module = IRGenerator::new("test").generate(&program).unwrap();
+        lower_bitfields(&module).expect("module has bitfields")
+    }
+
+    #[test]
+    fn test_bitfields_become_storage_units() {
+        let module = lower(
+            "struct Header { tag: u8, #[bits(4)] kind: u16, #[bits(12)] len: u16, crc: u8 }\n\
+             fn set_len(h: *Header, n: u16) -> u8 { h.len = n\n return h.crc }\n",
+        );
+        let fields: Vec<(&str, &IRType)> = module.structs[0].fields.iter().map(|(n, t)| (n.as_str(), t)).collect();
+        assert_eq!(fields, vec![("tag", &IRType::U8), ("__bits1", &IRType::U16), ("crc", &IRType::U8)]);
+        assert!(module.structs[0].bits.is_empty());
+
+        let func = module.functions.iter().find(|f| f.name == "set_len").unwrap();
+        let insts: Vec<&Instruction> = func.blocks.iter().flat_map(|b| &b.instructions).collect();
+        // `crc` moved from field 3 to member 2
+        assert!(insts.iter().any(|i| matches!(i, Instruction::GetElementPtr { index: Value::Constant(Constant::Int(2)), .. })));
+        // `len` sits above `kind`: cleared with !(0xfff << 4) and stored shifted by 4
+        let kept = !(0xfffu64 << 4) as i64;
+        assert!(insts.iter().any(|i| matches!(i, Instruction::BinOp { op: BinOp::And, right: Value::Constant(Constant::Int(m)), .. } if *m == kept)));
+        assert!(insts.iter().any(|i| matches!(i, Instruction::BinOp { op: BinOp::Shl, right: Value::Constant(Constant::Int(4)), .. })));
+        assert!(insts.iter().any(|i| matches!(i, Instruction::Store { value: Value::Register(_), .. })));
+    }
+
+    #[test]
+    fn test_modules_without_bitfields_are_left_alone() {
+        let program = Parser::new(Lexer::new("struct P { x: i64 }\nfn main() {}\n", 0)).parse_program().unwrap();
+        let module = IRGenerator::new("test").generate(&program).unwrap();
+        assert!(lower_bitfields(&module).is_none());
+    }
+}
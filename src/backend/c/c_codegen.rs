@@ -7,32 +7,68 @@ use std::collections::{HashMap, HashSet};
 use std::process::Command;
 use std::fs;
 
-use crate::backend::codegen::CodeGen;
+use crate::backend::codegen::{CodeGen, SanitizerFlags};
+use crate::backend::cross::Toolchain;
+use crate::backend::runtime;
+use super::peephole;
+use crate::middle::collections;
+use crate::middle::coverage::{self, CoverageMap};
+use crate::middle::bench;
+use crate::middle::fileio;
 use crate::middle::ir::*;
-use crate::utils::{Error, Result};
+use crate::middle::panic::{self, PanicMode, PANIC_FN};
+use crate::middle::simd::{self, Intrinsic, SimdOp};
+use crate::types::layout::BitStorage;
+use crate::utils::{map_chunks, Error, Result};
 
 /// C code generator
+#[derive(Clone)]
 pub struct CCodeGen {
     target_triple: String,
     output: String,
     indent: usize,
-    /// Map from register to C variable name
-    var_names: HashMap<Register, String>,
-    /// Counter for generating unique variable names
-    var_counter: usize,
+    /// Registers the current function's emitted code reads; the others are never assigned
+    read_regs: HashSet<Register>,
+    /// Parameters the current function's emitted code reads
+    read_params: HashSet<usize>,
     /// Map from block ID to label name
     block_labels: HashMap<usize, String>,
     
     // Structure layouts (struct name -> fields)
     struct_layouts: HashMap<String, Vec<(String, IRType)>>,
+
+    // `#[bits(N)]` widths of the structs that have bitfields
+    struct_bits: HashMap<String, Vec<Option<u32>>>,
+
+    // Field pointers to bitfields, which have no address: loads and stores
+    // through them use `base->field` directly. Holds the field's width.
+    bitfield_places: HashMap<Register, (Value, String, u32)>,
+
+    // Aggregates emitted as C `union` rather than `struct`
+    union_names: HashSet<String>,
+
+    // Tagged unions generated for IR enums
+    enum_names: HashSet<String>,
+
+    // Payload types of each enum variant constructor
+    ctor_fields: HashMap<String, Vec<IRType>>,
     
     // Type tracking
     reg_types: HashMap<Register, IRType>,
     param_types: HashMap<usize, IRType>,
     func_ret_types: HashMap<String, IRType>,
+
+    // `extern` functions, whose C prototypes come from the system headers
+    foreign_fns: HashSet<String>,
+
+    // `#[cold]` functions; branches leading to a call to one are unlikely
+    cold_fns: HashSet<String>,
     
     // Track globals used (for enum variants)
     globals_used: HashSet<String>,
+
+    // Declared global variables (static items), name -> type
+    declared_globals: HashMap<String, IRType>,
     
     // Track calls to undefined functions (potential enum variant constructors)
     undefined_calls: HashSet<(String, usize)>,  // (func_name, arg_count)
@@ -40,45 +76,267 @@ pub struct CCodeGen {
     // Current function being generated (for main's argc/argv handling)
     current_func_name: String,
     current_func_param_count: usize,
+
+    // Hosted programs get a C `main` wrapper that calls the user's main as `aether_main`
+    wrap_main: bool,
+
+    // No C `main` at all; the user's main is still emitted as `aether_main` (`--no-main`)
+    suppress_main: bool,
+
+    // Functions that are not `pub` or exported get internal linkage (`--emit-c-header`)
+    static_internals: bool,
+
+    // No C library: no runtime, no hosted headers and no C `main` (`--freestanding`)
+    freestanding: bool,
+
+    // Function the freestanding program starts at (`--entry`)
+    entry: String,
+
+    // Runtime sanitizers to instrument for
+    sanitizers: SanitizerFlags,
+
+    // Panics are reported as one JSON line (`--json`)
+    json_panics: bool,
+
+    // C compiler selection (cross-compiler, sysroot, extra flags)
+    toolchain: Toolchain,
+
+    // Worker threads for generating function definitions (`--jobs`)
+    jobs: usize,
+}
+
+/// Bytes of poisoned guard zone placed on each side of an ASan-wrapped allocation
+const ASAN_GUARD_SIZE: usize = 32;
+
+/// Entry symbol of a `--freestanding` program unless `--entry` names another
+pub const DEFAULT_ENTRY: &str = "_start";
+
+/// Functions the prelude's `<stdio.h>`, `<stdlib.h>` and `<string.h>` declare.
+/// `extern` declarations of these use the header's prototype; any other
+/// `extern` function gets a prototype built from its AetherLang signature.
+const C_HEADER_FUNCTIONS: &[&str] = &[
+    // <stdio.h>
+    "printf", "fprintf", "sprintf", "snprintf", "scanf", "fscanf", "sscanf",
+    "puts", "putchar", "fputs", "fputc", "putc", "getchar", "getc", "fgetc", "fgets", "ungetc",
+    "fopen", "fclose", "fflush", "fread", "fwrite", "fseek", "ftell", "rewind",
+    "feof", "ferror", "clearerr", "remove", "rename", "perror", "setvbuf", "tmpfile",
+    // <stdlib.h>
+    "malloc", "calloc", "realloc", "free", "aligned_alloc", "exit", "abort", "atexit", "_Exit",
+    "getenv", "system", "atoi", "atol", "atoll", "atof", "strtol", "strtoll", "strtoul", "strtoull",
+    "strtod", "strtof", "abs", "labs", "llabs", "rand", "srand", "qsort", "bsearch",
+    // <string.h>
+    "memcpy", "memmove", "memset", "memcmp", "memchr", "strlen", "strcmp", "strncmp",
+    "strcpy", "strncpy", "strcat", "strncat", "strchr", "strrchr", "strstr", "strdup",
+    "strerror", "strtok", "strspn", "strcspn", "strpbrk",
+];
+
+/// Declaration of `name` with the C type `c_type`; the name of a function
+/// pointer goes inside its `(*)`
+fn declarator(c_type: &str, name: &str) -> String {
+    match c_type.split_once("(*)") {
+        Some((ret, params)) => format!("{}(*{}){}", ret, name, params),
+        None => format!("{} {}", c_type, name),
+    }
+}
+
+/// Runtime function behind the print builtin `name`
+fn print_runtime_fn(name: &str) -> Option<&'static str> {
+    match name {
+        "print" => Some("aether_print"),
+        "println" => Some("aether_println"),
+        "print_i64" => Some("aether_print_i64"),
+        "println_i64" => Some("aether_println_i64"),
+        _ => None,
+    }
+}
+
+/// A value `module` allocates with `malloc`: an enum variant (enums are
+/// boxed), a `new` allocation or an integer cast to an enum
+fn heap_allocated_value(module: &IRModule) -> Option<String> {
+    let constructors: HashMap<String, String> = module.enums.iter()
+        .flat_map(|e| e.variants.iter().map(move |v| (format!("{}_{}", e.name, v.name), format!("enum variant `{}::{}`", e.name, v.name))))
+        .collect();
+    module.functions.iter()
+        .flat_map(|f| &f.blocks)
+        .flat_map(|b| &b.instructions)
+        .find_map(|inst| match inst {
+            Instruction::Call { func, .. } if func == "malloc" || func == "aligned_alloc" => Some("a `new` value or an integer cast to an enum".to_string()),
+            Instruction::Call { func, .. } => constructors.get(func).cloned(),
+            _ => None,
+        })
+}
+
+/// Include guard of the header generated for module `name`
+fn header_guard(name: &str) -> String {
+    format!("AETHER_{}_H", name.to_uppercase().replace(|c: char| !c.is_ascii_alphanumeric(), "_"))
+}
+
+/// Aggregate stored inline in a value of type `ty`
+fn by_value_aggregate(ty: &IRType) -> Option<&str> {
+    match ty {
+        IRType::Struct(name) => Some(name),
+        IRType::Array(elem, _) => by_value_aggregate(elem),
+        _ => None,
+    }
+}
+
+/// Types the declarations of an interface header refer to, in first-use order
+#[derive(Default)]
+struct HeaderTypes {
+    /// `aeth_i64` and its C type
+    primitives: Vec<(String, String)>,
+    /// Structs and unions, forward-declared before any definition
+    aggregates: Vec<String>,
+    str: bool,
+    /// `AethVec` and `AethMap`, passed by pointer
+    handles: Vec<&'static str>,
+}
+
+impl HeaderTypes {
+    fn aggregate(&mut self, name: &str) {
+        if !self.aggregates.iter().any(|a| a == name) {
+            self.aggregates.push(name.to_string());
+        }
+    }
+}
+
+/// Member declarations of a struct without the `;`, declared by `decl`.
+/// `#[bits(N)]` fields become C bit fields of their storage unit's type;
+/// zero-width fields around each unit keep the C compiler from sharing
+/// it with its neighbours, so the layout is the one `BitfieldLayout` computes.
+fn struct_members(def: &IRStruct, mut decl: impl FnMut(&IRType, &str) -> String) -> Vec<String> {
+    let Some(bitfields) = def.bitfields() else {
+        return def.fields.iter().map(|(name, ty)| decl(ty, name)).collect();
+    };
+    let unit_type = |member: usize| match bitfields.storage[member] {
+        BitStorage::Unit { bytes: 1 } => Some(IRType::U8),
+        BitStorage::Unit { bytes: 2 } => Some(IRType::U16),
+        BitStorage::Unit { bytes: 4 } => Some(IRType::U32),
+        BitStorage::Unit { .. } => Some(IRType::U64),
+        BitStorage::Field(_) => None,
+    };
+    let mut members = Vec::new();
+    for (i, (name, ty)) in def.fields.iter().enumerate() {
+        let member = bitfields.member[i];
+        if i > 0 && bitfields.member[i - 1] != member {
+            let boundaries = [unit_type(bitfields.member[i - 1]), unit_type(member)];
+            for unit in boundaries.iter().flatten() {
+                members.push(format!("{}: 0", decl(unit, "")));
+            }
+        }
+        match (bitfields.slots[i], unit_type(member)) {
+            (Some(slot), Some(unit)) => members.push(format!("{} : {}", decl(&unit, name), slot.width)),
+            _ => members.push(decl(ty, name)),
+        }
+    }
+    members
+}
+
+/// Register named by an explicit `{reg}` asm constraint
+fn asm_register(constraint: &str) -> Option<&str> {
+    constraint.strip_prefix('{')?.strip_suffix('}')
+}
+
+/// GCC constraint letter that pins an operand to an x86 register, if one exists
+fn gcc_register_letter(reg: &str) -> Option<&'static str> {
+    match reg {
+        "rax" | "eax" => Some("a"),
+        "rbx" | "ebx" => Some("b"),
+        "rcx" | "ecx" => Some("c"),
+        "rdx" | "edx" => Some("d"),
+        "rsi" | "esi" => Some("S"),
+        "rdi" | "edi" => Some("D"),
+        _ => None,
+    }
 }
 
 impl CCodeGen {
-    pub fn new(target: &str) -> Self {
+    pub fn new(target: &str, sanitizers: SanitizerFlags) -> Self {
         Self {
             target_triple: target.to_string(),
             output: String::new(),
             indent: 0,
-            var_names: HashMap::new(),
-            var_counter: 0,
+            read_regs: HashSet::new(),
+            read_params: HashSet::new(),
             block_labels: HashMap::new(),
             struct_layouts: HashMap::new(),
+            struct_bits: HashMap::new(),
+            bitfield_places: HashMap::new(),
+            union_names: HashSet::new(),
+            enum_names: HashSet::new(),
+            ctor_fields: HashMap::new(),
             reg_types: HashMap::new(),
             param_types: HashMap::new(),
             func_ret_types: HashMap::new(),
+            foreign_fns: HashSet::new(),
+            cold_fns: HashSet::new(),
             globals_used: HashSet::new(),
+            declared_globals: HashMap::new(),
             undefined_calls: HashSet::new(),
             current_func_name: String::new(),
             current_func_param_count: 0,
+            wrap_main: false,
+            suppress_main: false,
+            static_internals: false,
+            freestanding: false,
+            entry: DEFAULT_ENTRY.to_string(),
+            sanitizers,
+            json_panics: false,
+            toolchain: Toolchain::new(target),
+            jobs: 1,
         }
 
 
     }
 
-    /// Generate a unique variable name
-    fn fresh_var(&mut self) -> String {
-        let name = format!("_t{}", self.var_counter);
-        self.var_counter += 1;
-        name
+    /// C variable holding a register: `%7` is `_r7`
+    fn get_var(&self, reg: Register) -> String {
+        format!("_r{}", reg.0)
     }
 
-    /// Get variable name for a register
-    fn get_var(&mut self, reg: Register) -> String {
-        if let Some(name) = self.var_names.get(&reg) {
-            name.clone()
-        } else {
-            let name = self.fresh_var();
-            self.var_names.insert(reg, name.clone());
-            name
+    /// Whether anything in the current function reads `reg`
+    fn is_read(&self, reg: Register) -> bool {
+        self.read_regs.contains(&reg)
+    }
+
+    /// Whether `inst` may be left out when nothing reads its result
+    fn is_droppable(&self, inst: &Instruction) -> bool {
+        match inst {
+            Instruction::Load { ty, volatile, .. } => !self.is_volatile_load(ty, *volatile),
+            Instruction::CheckedBinOp { .. } => false,
+            _ => !inst.has_side_effects(),
+        }
+    }
+
+    /// Collect the registers and parameters `func`'s emitted code reads.
+    /// Droppable instructions only read their operands when their own
+    /// result is read, so values that only feed dead code are dead too.
+    fn find_reads(&mut self, func: &IRFunction) {
+        let mut droppable: HashMap<Register, Vec<&Instruction>> = HashMap::new();
+        let mut pending: Vec<&Value> = Vec::new();
+        for block in &func.blocks {
+            for inst in &block.instructions {
+                match inst.dest().filter(|_| self.is_droppable(inst)) {
+                    Some(dest) => droppable.entry(dest).or_default().push(inst),
+                    None => pending.extend(inst.operands()),
+                }
+            }
+            pending.extend(block.terminator.iter().flat_map(|t| t.operands()));
+        }
+        self.read_regs.clear();
+        self.read_params.clear();
+        while let Some(value) = pending.pop() {
+            match value {
+                Value::Register(reg) if self.read_regs.insert(*reg) => {
+                    for inst in droppable.get(reg).into_iter().flatten() {
+                        pending.extend(inst.operands());
+                    }
+                }
+                Value::Parameter(i) => {
+                    self.read_params.insert(*i);
+                }
+                _ => {}
+            }
         }
     }
 
@@ -96,6 +354,11 @@ impl CCodeGen {
         self.output.push_str(line);
     }
 
+    /// `#line` so C diagnostics and debuggers point at the AetherLang source
+    fn emit_line_directive(&mut self, loc: &SourceLoc) {
+        self.write_raw(&format!("#line {} \"{}\"\n", loc.line, Self::escape_for_c(&loc.file)));
+    }
+
     /// Escape string for C (converts to valid C escape sequences)
     fn escape_for_c(s: &str) -> String {
         let mut result = String::new();
@@ -119,6 +382,11 @@ impl CCodeGen {
         result
     }
 
+    /// C keyword (`struct` or `union`) introducing a named aggregate
+    fn aggregate_keyword(&self, name: &str) -> &'static str {
+        if self.union_names.contains(name) { "union" } else { "struct" }
+    }
+
     /// Convert IR type to C type
     fn ir_type_to_c(&self, ty: &IRType) -> String {
         match ty {
@@ -134,44 +402,155 @@ impl CCodeGen {
             IRType::U64 => "uint64_t".to_string(),
             IRType::F32 => "float".to_string(),
             IRType::F64 => "double".to_string(),
-            IRType::Ptr(inner) => format!("{}*", self.ir_type_to_c(inner)),
+            // A stack array is addressed through a pointer to its first element
+            IRType::Ptr(inner) => match inner.as_ref() {
+                IRType::Array(elem, _) => format!("{}*", self.ir_type_to_c(elem)),
+                _ => format!("{}*", self.ir_type_to_c(inner)),
+            },
             IRType::Array(elem, size) => format!("{}[{}]", self.ir_type_to_c(elem), size),
-            IRType::Struct(name) => format!("struct {}", name),
+            IRType::Struct(name) => format!("{} {}", self.aggregate_keyword(name), name),
+            IRType::Str => "AethStr".to_string(),
+            IRType::Vec(_) => "AethVec*".to_string(),
+            IRType::Map(..) => "AethMap*".to_string(),
             IRType::Function { params, ret } => {
                 let params_str: Vec<_> = params.iter().map(|p| self.ir_type_to_c(p)).collect();
                 format!("{}(*)({})", self.ir_type_to_c(ret), params_str.join(", "))
             }
             // SIMD vector types - use platform-specific intrinsics
             IRType::Vector(elem, lanes) => {
+                let arm = self.target_triple.contains("arm") || self.is_aarch64();
+                let x86 = self.target_triple.contains("x86") || self.target_triple.contains("i686");
                 match (elem.as_ref(), lanes) {
                     // ARM NEON types
-                    (IRType::F32, 4) if self.target_triple.contains("aarch64") || self.target_triple.contains("arm") => 
-                        "float32x4_t".to_string(),
-                    (IRType::F64, 2) if self.target_triple.contains("aarch64") => 
-                        "float64x2_t".to_string(),
-                    (IRType::I32, 4) if self.target_triple.contains("aarch64") || self.target_triple.contains("arm") => 
-                        "int32x4_t".to_string(),
-                    (IRType::I64, 2) if self.target_triple.contains("aarch64") => 
-                        "int64x2_t".to_string(),
+                    (IRType::F32, 4) if arm => "float32x4_t".to_string(),
+                    (IRType::F64, 2) if self.is_aarch64() => "float64x2_t".to_string(),
+                    (IRType::I32, 4) if arm => "int32x4_t".to_string(),
+                    (IRType::I64, 2) if self.is_aarch64() => "int64x2_t".to_string(),
                     // x86 SSE/AVX types  
-                    (IRType::F32, 4) => "__m128".to_string(),
-                    (IRType::F32, 8) => "__m256".to_string(),
-                    (IRType::F64, 2) => "__m128d".to_string(),
-                    (IRType::F64, 4) => "__m256d".to_string(),
-                    (IRType::I32, 4) => "__m128i".to_string(),
-                    (IRType::I32, 8) => "__m256i".to_string(),
-                    (IRType::I64, 2) => "__m128i".to_string(),
-                    (IRType::I64, 4) => "__m256i".to_string(),
+                    (IRType::F32, 4) if x86 => "__m128".to_string(),
+                    (IRType::F32, 8) if x86 => "__m256".to_string(),
+                    (IRType::F64, 2) if x86 => "__m128d".to_string(),
+                    (IRType::F64, 4) if x86 => "__m256d".to_string(),
+                    (IRType::I32, 4) | (IRType::I64, 2) if x86 => "__m128i".to_string(),
+                    (IRType::I32, 8) | (IRType::I64, 4) if x86 => "__m256i".to_string(),
                     // Fallback: use GCC vector extension
-                    _ => format!("{} __attribute__((vector_size({})))", 
-                        self.ir_type_to_c(elem), 
-                        elem.size_bytes() * lanes)
+                    _ => self.gnu_vector_type(elem, *lanes),
                 }
             }
         }
     }
 
+    /// A GCC/Clang vector extension type, which supports operators and lane indexing
+    fn gnu_vector_type(&self, elem: &IRType, lanes: usize) -> String {
+        format!("{} __attribute__((vector_size({})))", self.ir_type_to_c(elem), elem.size_bytes() * lanes)
+    }
+
+    /// Whether NEON has the 64-bit-only operations (`f64`, `vdivq`, `vaddvq`)
+    fn is_aarch64(&self) -> bool {
+        self.target_triple.contains("aarch64") || self.target_triple.contains("arm64")
+    }
+
+    /// C expression for a SIMD intrinsic call: SSE/AVX intrinsics for x86
+    /// vector types, NEON for ARM ones, and the vector extension's operators
+    /// when the target has no instruction for the operation
+    fn simd_to_c(&self, intrinsic: &Intrinsic, args: &[String]) -> String {
+        let vector = self.ir_type_to_c(&intrinsic.vector());
+        let elem = self.ir_type_to_c(&intrinsic.elem);
+        let generic = self.gnu_vector_type(&intrinsic.elem, intrinsic.lanes);
+        let arg = |i: usize| args.get(i).map(String::as_str).unwrap_or("0");
+        // Every vector type converts to the extension type of the same width
+        let view = |i: usize| format!("(({})({}))", generic, arg(i));
+        let binary = |op: &str| format!("({})({} {} {})", vector, view(0), op, view(1));
+        let sum = || {
+            let lanes: Vec<_> = (0..intrinsic.lanes).map(|lane| format!("{}[{}]", view(0), lane)).collect();
+            format!("({})({})", elem, lanes.join(" + "))
+        };
+        let operator = match intrinsic.op {
+            SimdOp::Add => "+",
+            SimdOp::Sub => "-",
+            SimdOp::Mul => "*",
+            _ => "/",
+        };
+
+        if vector.starts_with("__m") {
+            let prefix = if intrinsic.width() == 32 { "_mm256" } else { "_mm" };
+            let bits = intrinsic.width() * 8;
+            let suffix = match intrinsic.elem {
+                IRType::F32 => "ps",
+                IRType::F64 => "pd",
+                IRType::I32 => "epi32",
+                _ => "epi64",
+            };
+            let float = intrinsic.is_float();
+            match intrinsic.op {
+                SimdOp::New if intrinsic.elem == IRType::I64 && bits == 128 => {
+                    // There is no `_mm_setr_epi64x`; `_mm_set_epi64x` takes the high lane first
+                    format!("_mm_set_epi64x({}, {})", arg(1), arg(0))
+                }
+                SimdOp::New => {
+                    let suffix = if intrinsic.elem == IRType::I64 { "epi64x" } else { suffix };
+                    format!("{}_setr_{}({})", prefix, suffix, args.join(", "))
+                }
+                SimdOp::Splat => {
+                    let suffix = if intrinsic.elem == IRType::I64 { "epi64x" } else { suffix };
+                    format!("{}_set1_{}({})", prefix, suffix, arg(0))
+                }
+                SimdOp::Add | SimdOp::Sub => format!("{}_{}_{}({}, {})", prefix, intrinsic.op.name(), suffix, arg(0), arg(1)),
+                SimdOp::Mul if float => format!("{}_mul_{}({}, {})", prefix, suffix, arg(0), arg(1)),
+                SimdOp::Mul if intrinsic.elem == IRType::I32 => format!("{}_mullo_epi32({}, {})", prefix, arg(0), arg(1)),
+                SimdOp::Div if float => format!("{}_div_{}({}, {})", prefix, suffix, arg(0), arg(1)),
+                SimdOp::Load if float => format!("{}_loadu_{}({})", prefix, suffix, arg(0)),
+                SimdOp::Load => format!("{}_loadu_si{}((const {}*)({}))", prefix, bits, vector, arg(0)),
+                SimdOp::Store if float => format!("{}_storeu_{}({}, {})", prefix, suffix, arg(0), arg(1)),
+                SimdOp::Store => format!("{}_storeu_si{}(({}*)({}), {})", prefix, bits, vector, arg(0), arg(1)),
+                SimdOp::Sum => sum(),
+                // 64-bit lane multiplication needs AVX-512
+                _ => binary(operator),
+            }
+        } else if vector.ends_with("_t") {
+            let suffix = match intrinsic.elem {
+                IRType::F32 => "f32",
+                IRType::F64 => "f64",
+                IRType::I32 => "s32",
+                _ => "s64",
+            };
+            match intrinsic.op {
+                SimdOp::New => format!("({}){{{}}}", vector, args.join(", ")),
+                SimdOp::Splat => format!("vdupq_n_{}({})", suffix, arg(0)),
+                SimdOp::Add | SimdOp::Sub => format!("v{}q_{}({}, {})", intrinsic.op.name(), suffix, arg(0), arg(1)),
+                SimdOp::Mul if intrinsic.elem != IRType::I64 => format!("vmulq_{}({}, {})", suffix, arg(0), arg(1)),
+                SimdOp::Div if self.is_aarch64() => format!("vdivq_{}({}, {})", suffix, arg(0), arg(1)),
+                SimdOp::Sum if self.is_aarch64() => format!("vaddvq_{}({})", suffix, arg(0)),
+                SimdOp::Sum => sum(),
+                SimdOp::Load => format!("vld1q_{}({})", suffix, arg(0)),
+                SimdOp::Store => format!("vst1q_{}({}, {})", suffix, arg(0), arg(1)),
+                _ => binary(operator),
+            }
+        } else {
+            match intrinsic.op {
+                SimdOp::New => format!("({}){{{}}}", vector, args.join(", ")),
+                SimdOp::Splat => format!("({}){{{}}}", vector, vec![arg(0); intrinsic.lanes].join(", ")),
+                SimdOp::Sum => sum(),
+                SimdOp::Load => format!("({{ {} v_; memcpy(&v_, {}, sizeof v_); v_; }})", vector, arg(0)),
+                SimdOp::Store => format!("memcpy({}, &{}, sizeof {})", arg(0), arg(1), arg(1)),
+                _ => binary(operator),
+            }
+        }
+    }
+
     /// Convert binary operator to C operator
+    /// Type of a `BinOp` result: arithmetic on a `u64` stays unsigned so it
+    /// compares cleanly with other `u64`s, everything else defaults to `i64`
+    fn binop_type(&self, op: BinOp, left: &Value, right: &Value) -> IRType {
+        let arithmetic = matches!(
+            op,
+            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod
+                | BinOp::And | BinOp::Or | BinOp::Xor | BinOp::Shl | BinOp::Shr
+        );
+        let unsigned = [left, right].iter().any(|v| self.get_value_type(v) == Some(IRType::U64));
+        if arithmetic && unsigned { IRType::U64 } else { IRType::I64 }
+    }
+
     fn binop_to_c(&self, op: BinOp) -> &'static str {
         match op {
             BinOp::Add => "+",
@@ -179,8 +558,8 @@ impl CCodeGen {
             BinOp::Mul => "*",
             BinOp::Div => "/",
             BinOp::Mod => "%",
-            BinOp::And => "&&",
-            BinOp::Or => "||",
+            BinOp::And => "&",
+            BinOp::Or => "|",
             BinOp::Xor => "^",
             BinOp::Shl => "<<",
             BinOp::Shr => ">>",
@@ -198,15 +577,18 @@ impl CCodeGen {
         match val {
             Value::Register(reg) => self.get_var(*reg),
             Value::Constant(c) => match c {
+                // -9223372036854775808LL would be the negation of an out-of-range literal
+                Constant::Int(i64::MIN) => "(-9223372036854775807LL - 1)".to_string(),
                 Constant::Int(n) => format!("{}LL", n),
                 Constant::Float(f) => format!("{}", f),
                 Constant::Bool(b) => if *b { "1" } else { "0" }.to_string(),
                 Constant::String(s) => format!("\"{}\"", Self::escape_for_c(s)),
+                Constant::Str(s) => format!("((AethStr){{ (char*)\"{}\", {} }})", Self::escape_for_c(s), s.len()),
                 Constant::Null => "NULL".to_string(),
             },
             Value::Parameter(i) => {
                 // Special handling for main's argc/argv
-                if self.current_func_name == "main" && self.current_func_param_count == 2 {
+                if self.current_func_name == "main" && self.current_func_param_count == 2 && !self.wrap_main {
                     match i {
                         0 => "argc".to_string(),
                         1 => "argv".to_string(),
@@ -217,6 +599,14 @@ impl CCodeGen {
                 }
             },
             Value::Global(name) => {
+                // Static variables are addressed through a pointer
+                if self.declared_globals.contains_key(name) {
+                    return format!("(&{})", name);
+                }
+                // A print builtin passed as a function value
+                if let Some(runtime) = print_runtime_fn(name).filter(|_| !self.func_ret_types.contains_key(name)) {
+                    return runtime.to_string();
+                }
                 // Track this global for later definition generation
                 self.globals_used.insert(name.clone());
                 name.clone()
@@ -225,6 +615,50 @@ impl CCodeGen {
         }
     }
 
+    /// C expression for `val` stored into a register variable
+    fn assigned_value_to_c(&mut self, val: &Value) -> String {
+        let c = self.value_to_c(val);
+        // A C string literal is `char*`; the register holds `*u8`
+        if let Value::Constant(Constant::String(_)) = val {
+            format!("(uint8_t*){}", c)
+        } else {
+            c
+        }
+    }
+
+    /// Type a checked operation on `left` overflows in: its integer type, or `i64`
+    fn checked_type(&self, left: &Value) -> IRType {
+        match self.get_value_type(left) {
+            Some(ty @ (IRType::I8 | IRType::I16 | IRType::I32 | IRType::I64
+                | IRType::U8 | IRType::U16 | IRType::U32 | IRType::U64)) => ty,
+            _ => IRType::I64,
+        }
+    }
+
+    /// Result type of runtime functions that have no IR declaration
+    fn builtin_ret_type(func: &str) -> Option<IRType> {
+        match func {
+            "alloc" | "malloc" | "aligned_alloc" => Some(IRType::Ptr(Box::new(IRType::U8))),
+            "arg_count" => Some(IRType::I32),
+            "args" => Some(IRType::Ptr(Box::new(IRType::Str))),
+            STR_LEN => Some(IRType::U64),
+            STR_AS_PTR => Some(IRType::Ptr(Box::new(IRType::U8))),
+            STR_SLICE | STR_FROM_PTR => Some(IRType::Str),
+            STR_EQ => Some(IRType::Bool),
+            collections::VEC_NEW | collections::VEC_WITH_CAPACITY => Some(IRType::Vec(Box::new(IRType::Void))),
+            collections::MAP_NEW => Some(IRType::Map(Box::new(IRType::Void), Box::new(IRType::Void))),
+            collections::VEC_POP | collections::VEC_GET | collections::MAP_GET => Some(IRType::Ptr(Box::new(IRType::U8))),
+            collections::VEC_LEN | collections::MAP_LEN => Some(IRType::U64),
+            collections::MAP_CONTAINS | collections::MAP_REMOVE => Some(IRType::Bool),
+            collections::VEC_PUSH | collections::VEC_CLEAR | collections::VEC_FREE
+            | collections::MAP_INSERT | collections::MAP_FREE => Some(IRType::Void),
+            _ => fileio::signature(func)
+                .or_else(|| bench::signature(func))
+                .or_else(|| simd::signature(func))
+                .map(|(_, ret)| ret),
+        }
+    }
+
     /// Analyze instruction for type inference
     fn analyze_instruction(&mut self, inst: &Instruction) {
         match inst {
@@ -233,8 +667,14 @@ impl CCodeGen {
                     self.reg_types.insert(*dest, ty);
                 }
             }
-            Instruction::BinOp { dest, .. } => {
-                self.reg_types.insert(*dest, IRType::I64); 
+            Instruction::BinOp { dest, op, left, right } => {
+                let ty = self.binop_type(*op, left, right);
+                self.reg_types.insert(*dest, ty);
+            }
+            Instruction::CheckedBinOp { dest, overflow_dest, left, .. } => {
+                let ty = self.checked_type(left);
+                self.reg_types.insert(*dest, ty);
+                self.reg_types.insert(*overflow_dest, IRType::Bool);
             }
             Instruction::UnaryOp { dest, value, .. } => {
                  if let Some(ty) = self.get_value_type(value) {
@@ -242,15 +682,24 @@ impl CCodeGen {
                 }
             }
             Instruction::Call { dest: Some(dest), func, .. } => {
-                let ret_ty = self.func_ret_types.get(func).cloned();
+                let ret_ty = self.func_ret_types.get(func).cloned().or_else(|| Self::builtin_ret_type(func));
                 if let Some(ty) = ret_ty {
                     self.reg_types.insert(*dest, ty);
                 }
             }
+            Instruction::CallIndirect { dest, callee, ty, .. } => {
+                // The callee is a function pointer even when copied from an untyped register
+                if let Value::Register(reg) = callee {
+                    self.reg_types.insert(*reg, ty.clone());
+                }
+                if let (Some(dest), IRType::Function { ret, .. }) = (dest, ty) {
+                    self.reg_types.insert(*dest, (**ret).clone());
+                }
+            }
             Instruction::Alloca { dest, ty } => {
                 self.reg_types.insert(*dest, IRType::Ptr(Box::new(ty.clone())));
             }
-            Instruction::Load { dest, ptr, ty: _ } => {
+            Instruction::Load { dest, ptr, .. } => {
                  if let Some(IRType::Ptr(inner)) = self.get_value_type(ptr) {
                     self.reg_types.insert(*dest, *inner);
                 }
@@ -266,7 +715,10 @@ impl CCodeGen {
                                  None
                              };
                              
-                             if let Some((_, field_type)) = field_info {
+                             if let Some((field_name, field_type)) = field_info {
+                                 if let Some(width) = self.bitfield_width(struct_name, *idx as usize) {
+                                     self.bitfield_places.insert(*dest, (ptr.clone(), field_name, width));
+                                 }
                                  self.reg_types.insert(*dest, IRType::Ptr(Box::new(field_type)));
                                  return; // Handled
                              }
@@ -274,15 +726,13 @@ impl CCodeGen {
                     }
                 }
                 // Fallback
-                if let Some(IRType::Ptr(inner)) = ptr_ty {
-                      self.reg_types.insert(*dest, IRType::Ptr(inner));
+                if let Some(ty) = ptr_ty.and_then(Self::element_ptr_type) {
+                      self.reg_types.insert(*dest, ty);
                 }
             }
             Instruction::Phi { dest, incoming } => {
-                if let Some((val, _)) = incoming.first() {
-                    if let Some(ty) = self.get_value_type(val) {
-                        self.reg_types.insert(*dest, ty);
-                    }
+                if let Some(ty) = incoming.iter().find_map(|(val, _)| self.get_value_type(val)) {
+                    self.reg_types.insert(*dest, ty);
                 }
             }
             Instruction::Cast { dest, ty, .. } => {
@@ -292,13 +742,36 @@ impl CCodeGen {
         }
     }
 
+    /// The width of field `index` of struct `name`, if it is a `#[bits(N)]` field
+    fn bitfield_width(&self, name: &str, index: usize) -> Option<u32> {
+        self.struct_bits.get(name)?.get(index).copied().flatten()
+    }
+
+    /// `base->field` for a pointer to a bitfield, and the field's width
+    fn bitfield_place(&mut self, ptr: &Value) -> Option<(String, u32)> {
+        let Value::Register(reg) = ptr else { return None };
+        let (base, field, width) = self.bitfield_places.get(reg).cloned()?;
+        Some((format!("{}->{}", self.value_to_c(&base), field), width))
+    }
+
+    /// Type of `&ptr[index]`: array pointers decay to pointers to their elements
+    fn element_ptr_type(ptr_ty: IRType) -> Option<IRType> {
+        match ptr_ty {
+            IRType::Ptr(inner) => match *inner {
+                IRType::Array(elem, _) => Some(IRType::Ptr(elem)),
+                inner => Some(IRType::Ptr(Box::new(inner))),
+            },
+            _ => None,
+        }
+    }
+
     /// Generate C code for a function
     fn generate_function(&mut self, func: &IRFunction) -> Result<()> {
         // Reset state for new function
-        self.var_names.clear();
-        self.var_counter = 0;
+        self.find_reads(func);
         self.block_labels.clear();
         self.reg_types.clear();
+        self.bitfield_places.clear();
         self.param_types.clear();
         // Track current function for argc/argv handling in main
         self.current_func_name = func.name.clone();
@@ -316,16 +789,20 @@ impl CCodeGen {
         }
 
 
+        if let Some(ref loc) = func.debug_loc {
+            self.emit_line_directive(loc);
+        }
+
         // Function signature
         let ret_type = self.ir_type_to_c(&func.ret_type);
         
         // Special handling for main function: use C standard signature
-        let params: Vec<String> = if func.name == "main" && func.params.len() == 2 {
+        let params: Vec<String> = if func.name == "main" && func.params.len() == 2 && !self.wrap_main {
             // Use standard C main signature: int argc, char** argv
             vec!["int argc".to_string(), "char** argv".to_string()]
         } else {
             func.params.iter().enumerate()
-                .map(|(i, (_, ty))| format!("{} _arg{}", self.ir_type_to_c(ty), i))
+                .map(|(i, (_, ty))| declarator(&self.ir_type_to_c(ty), &format!("_arg{}", i)))
                 .collect()
         };
         
@@ -359,87 +836,136 @@ impl CCodeGen {
             self.writeln("#endif");
         }
         
+        // Inlining hints and call frequency
+        match func.inline {
+            InlineHint::Auto => {}
+            InlineHint::Always => self.writeln("/* @inline */"),
+            InlineHint::Never => {
+                self.writeln("/* @inline(never) */");
+                self.writeln("#if defined(__GNUC__) || defined(__clang__)");
+                self.writeln("__attribute__((noinline))");
+                self.writeln("#endif");
+            }
+        }
+        for (flag, name, frequency) in [(func.cold, "cold", "rarely"), (func.hot, "hot", "often")] {
+            if flag {
+                self.writeln(&format!("/* @{}: called {} */", name, frequency));
+                self.writeln("#if defined(__GNUC__) || defined(__clang__)");
+                self.writeln(&format!("__attribute__(({}))", name));
+                self.writeln("#endif");
+            }
+        }
+
+        // The kernel starts a freestanding program with the stack 16-byte
+        // aligned, not just past a return address
+        if self.freestanding && func.name == self.entry && self.target_triple.contains("x86") {
+            self.writeln("/* entry point */");
+            self.writeln("__attribute__((force_align_arg_pointer))");
+        }
+
+        // Exported functions stay visible when linked into a shared library
+        if func.extern_c {
+            self.writeln("/* @extern_c: C linkage, unmangled */");
+            self.writeln("#if defined(__GNUC__) || defined(__clang__)");
+            self.writeln("__attribute__((visibility(\"default\")))");
+            self.writeln("#endif");
+        } else if func.no_mangle {
+            self.writeln("/* @no_mangle: exact symbol name */");
+            self.writeln("#if defined(__GNUC__) || defined(__clang__)");
+            self.writeln("__attribute__((visibility(\"default\")))");
+            self.writeln("#endif");
+        }
+        
         let params_str = if params.is_empty() { "void".to_string() } else { params.join(", ") };
-        self.writeln(&format!("{} {}({}) {{", ret_type, func.name, params_str));
+        let inline = if func.inline == InlineHint::Always { "inline " } else { "" };
+        self.writeln(&format!("{}{}{} {}({}) {{", self.linkage(func), inline, ret_type, self.c_function_name(&func.name), params_str));
 
         self.indent += 1;
 
         // Generate precondition assertions (requires clauses)
         if !func.contracts.requires.is_empty() {
             self.writeln("/* Precondition assertions */");
+            let (file, line) = func.debug_loc.as_ref()
+                .map_or(("<unknown>".to_string(), 0), |loc| (loc.file.clone(), loc.line));
             for (i, require) in func.contracts.requires.iter().enumerate() {
-                self.writeln(&format!("assert({});  /* requires #{} */", require, i + 1));
+                self.writeln(&format!(
+                    "if (!({})) {}(\"precondition of '{}' failed\", \"{}\", {});  /* requires #{} */",
+                    require, PANIC_FN, func.name, Self::escape_for_c(&file), line, i + 1
+                ));
             }
             self.writeln("");
         }
 
-        // Pass 1: Analyze types
-        for block in &func.blocks {
-            for inst in &block.instructions {
-                self.analyze_instruction(inst);
+        // Pass 1: Analyze types, again while a pass learns new ones (a phi
+        // can precede the definitions of its incoming values)
+        loop {
+            let known = self.reg_types.len();
+            for block in &func.blocks {
+                for inst in &block.instructions {
+                    self.analyze_instruction(inst);
+                }
+            }
+            if self.reg_types.len() == known {
+                break;
             }
         }
 
-        // Declare all variables upfront (C89 style for compatibility)
-        let mut declarations = Vec::new();
+        // Declare all variables upfront (C89 style for compatibility), in
+        // register order; results nothing reads get no variable
+        let mut declarations: Vec<(Register, String)> = Vec::new();
         for block in &func.blocks {
             for inst in &block.instructions {
-                match inst {
-                    Instruction::Assign { dest, .. } |
-                    Instruction::BinOp { dest, .. } |
-                    Instruction::UnaryOp { dest, .. } |
-                    Instruction::Alloca { dest, .. } |
-                    Instruction::Load { dest, .. } |
-                    Instruction::GetElementPtr { dest, .. } |
-                    Instruction::Phi { dest, .. } |
-                    Instruction::Cast { dest, .. } => {
-                        let var = self.get_var(*dest);
-                        let c_type = self.reg_types.get(dest)
-                            .map(|t| self.ir_type_to_c(t))
-                            .unwrap_or("int64_t".to_string());
-                        declarations.push(format!("{} {};", c_type, var));
-                    }
-                    Instruction::Call { dest: Some(dest), .. } => {
-                        let var = self.get_var(*dest);
-                        let c_type = self.reg_types.get(dest)
+                let regs = match inst {
+                    // Checked results and asm outputs are written through an
+                    // lvalue, so they are always declared
+                    Instruction::CheckedBinOp { .. } | Instruction::InlineAsm { .. } => inst.defs(),
+                    _ => inst.dest().into_iter()
+                        .filter(|r| self.is_read(*r) && !self.bitfield_places.contains_key(r))
+                        .collect(),
+                };
+                for reg in regs {
+                    let c_type = match inst {
+                        // Default to int64_t for asm outputs for now
+                        Instruction::InlineAsm { .. } => "int64_t".to_string(),
+                        _ => self.reg_types.get(&reg)
                             .map(|t| self.ir_type_to_c(t))
-                            .unwrap_or("int64_t".to_string());
-                        declarations.push(format!("{} {};", c_type, var));
-                    }
-                    Instruction::InlineAsm { operands, .. } => {
-                        for op in operands {
-                            if let Some(reg) = op.output {
-                                let var = self.get_var(reg);
-                                // Default to int64_t for asm outputs for not
-                                declarations.push(format!("int64_t {};", var));
-                            }
-                        }
-                    }
-                    _ => {}
+                            .unwrap_or("int64_t".to_string()),
+                    };
+                    declarations.push((reg, c_type));
                 }
             }
         }
-
-        // Remove duplicates and emit declarations (skip void types)
-        declarations.sort();
-        declarations.dedup();
-        for decl in declarations {
+        declarations.sort_by_key(|(reg, _)| reg.0);
+        declarations.dedup_by_key(|(reg, _)| *reg);
+        for (reg, c_type) in declarations {
             // Skip void declarations
-            if !decl.starts_with("void ") {
-                self.writeln(&decl);
+            if c_type != "void" {
+                self.writeln(&format!("{};", declarator(&c_type, &self.get_var(reg))));
             }
         }
+        let unused_params: Vec<usize> = (0..func.params.len()).filter(|i| !self.read_params.contains(i)).collect();
+        for i in unused_params {
+            let param = self.value_to_c(&Value::Parameter(i));
+            self.writeln(&format!("(void){};", param));
+        }
         if !func.blocks.is_empty() {
             self.writeln("");
         }
 
         // Generate code for each block
+        let jump_targets: HashSet<usize> = func.blocks.iter()
+            .filter_map(|b| b.terminator.as_ref())
+            .flat_map(|t| t.successors())
+            .map(|b| b.0)
+            .collect();
         for (i, block) in func.blocks.iter().enumerate() {
-            // Label (except for entry block)
+            // Label (except for entry block); a block nothing jumps to gets
+            // its name as a comment instead (-Wunused-label)
             if i > 0 {
                 let label = &self.block_labels[&i];
+                let line = if jump_targets.contains(&i) { format!("{}:", label) } else { format!("/* {}: unreachable */", label) };
                 self.indent -= 1;
-                self.writeln(&format!("{}:", label));
+                self.writeln(&line);
                 self.indent += 1;
             }
 
@@ -467,10 +993,19 @@ impl CCodeGen {
         match val {
             Value::Register(reg) => self.reg_types.get(reg).cloned(),
             Value::Parameter(idx) => self.param_types.get(idx).cloned(),
+            Value::Global(name) => self.declared_globals.get(name)
+                .map(|ty| IRType::Ptr(Box::new(ty.clone()))),
+            Value::Constant(Constant::Str(_)) => Some(IRType::Str),
+            Value::Constant(Constant::String(_)) => Some(IRType::Ptr(Box::new(IRType::U8))),
             _ => None,
         }
     }
 
+    /// Whether a load of a `ty` value is volatile
+    fn is_volatile_load(&self, ty: &IRType, volatile: bool) -> bool {
+        volatile || self.is_volatile_type(ty)
+    }
+
     /// Check if a type is volatile (for MMIO/hardware register access)
     fn is_volatile_type(&self, ty: &IRType) -> bool {
         match ty {
@@ -490,10 +1025,16 @@ impl CCodeGen {
     /// Generate C code for an instruction
 
     fn generate_instruction(&mut self, inst: &Instruction) -> Result<()> {
+        // A result nothing reads gets no variable; calls and volatile loads
+        // still run for their effects
+        let unread = inst.dest().is_some_and(|dest| !self.is_read(dest));
+        if unread && self.is_droppable(inst) {
+            return Ok(());
+        }
         match inst {
             Instruction::Assign { dest, value } => {
                 let var = self.get_var(*dest);
-                let val = self.value_to_c(value);
+                let val = self.assigned_value_to_c(value);
                 self.writeln(&format!("{} = {};", var, val));
                 
                 // Track type
@@ -508,9 +1049,28 @@ impl CCodeGen {
                 let r = self.value_to_c(right);
                 let op_str = self.binop_to_c(*op);
                 self.writeln(&format!("{} = {} {} {};", var, l, op_str, r));
-                self.reg_types.insert(*dest, IRType::I64); // Default to I64 for now
+                let ty = self.binop_type(*op, left, right);
+                self.reg_types.insert(*dest, ty);
             }
             
+            Instruction::CheckedBinOp { dest, overflow_dest, op, left, right } => {
+                let var = self.get_var(*dest);
+                let flag = self.get_var(*overflow_dest);
+                let ty = self.checked_type(left);
+                let c_type = self.ir_type_to_c(&ty);
+                let l = self.value_to_c(left);
+                let r = self.value_to_c(right);
+                let builtin = match op {
+                    BinOp::Add => "__builtin_add_overflow",
+                    BinOp::Sub => "__builtin_sub_overflow",
+                    BinOp::Mul => "__builtin_mul_overflow",
+                    _ => return Err(Error::CodeGen(format!("no checked form of '{}'", op))),
+                };
+                self.writeln(&format!("{} = {}(({}){}, ({}){}, &{});", flag, builtin, c_type, l, c_type, r, var));
+                self.reg_types.insert(*dest, ty);
+                self.reg_types.insert(*overflow_dest, IRType::Bool);
+            }
+
             Instruction::UnaryOp { dest, op, value } => {
                 let var = self.get_var(*dest);
                 let val = self.value_to_c(value);
@@ -526,7 +1086,37 @@ impl CCodeGen {
             }
             
             Instruction::Call { dest, func, args } => {
-                let args_str: Vec<_> = args.iter().map(|a| self.value_to_c(a)).collect();
+                let mut args_str: Vec<_> = args.iter().map(|a| self.value_to_c(a)).collect();
+                // C declares byte strings as `char*`; `void*` converts to either signedness
+                if self.foreign_fns.contains(func) {
+                    for (arg, text) in args.iter().zip(args_str.iter_mut()) {
+                        if matches!(self.get_value_type(arg), Some(IRType::Ptr(inner)) if matches!(*inner, IRType::U8 | IRType::I8)) {
+                            *text = format!("(void*){}", text);
+                        }
+                    }
+                }
+                // A generic payload is erased to `int64_t`; pointers go in through a cast
+                if let Some(fields) = self.ctor_fields.get(func) {
+                    for ((arg, text), ty) in args.iter().zip(args_str.iter_mut()).zip(fields) {
+                        if *ty == IRType::I64 && matches!(self.get_value_type(arg), Some(IRType::Ptr(_))) {
+                            *text = format!("({}){}", self.ir_type_to_c(ty), text);
+                        }
+                    }
+                }
+
+                // SIMD intrinsics (a function of the same name takes precedence)
+                if let Some(intrinsic) = simd::parse(func).filter(|_| !self.func_ret_types.contains_key(func)) {
+                    let call = self.simd_to_c(&intrinsic, &args_str);
+                    match dest {
+                        Some(d) if intrinsic.op != SimdOp::Store && !unread => {
+                            let var = self.get_var(*d);
+                            self.writeln(&format!("{} = {};", var, call));
+                            self.reg_types.insert(*d, intrinsic.signature().1);
+                        }
+                        _ => self.writeln(&format!("{};", call)),
+                    }
+                    return Ok(());
+                }
                 
                 // Map built-in function names to C runtime functions
                 let (c_func, is_builtin_void) = match func.as_str() {
@@ -535,43 +1125,19 @@ impl CCodeGen {
                     "print_i64" => ("aether_print_i64", true),
                     "println_i64" => ("aether_println_i64", true),
                     "assert" => ("aether_assert", true),
+                    "alloc" | "malloc" if self.sanitizers.contains(SanitizerFlags::ADDRESS) => ("aether_asan_malloc", false),
+                    "free" if self.sanitizers.contains(SanitizerFlags::ADDRESS) => ("aether_asan_free", true),
                     "alloc" => ("malloc", false),
                     "free" => ("free", true),
                     "exit" => ("exit", true),
-                    // SIMD intrinsics - map to platform-specific calls
-                    "f32x4_splat" => ("_mm_set1_ps", false),
-                    "f32x4_add" => ("_mm_add_ps", false),
-                    "f32x4_sub" => ("_mm_sub_ps", false),
-                    "f32x4_mul" => ("_mm_mul_ps", false),
-                    "f32x4_div" => ("_mm_div_ps", false),
-                    "f64x2_splat" => ("_mm_set1_pd", false),
-                    "f64x2_add" => ("_mm_add_pd", false),
-                    "f64x2_mul" => ("_mm_mul_pd", false),
-                    "i32x4_splat" => ("_mm_set1_epi32", false),
-                    "i32x4_add" => ("_mm_add_epi32", false),
-                    "i32x4_mul" => ("_mm_mullo_epi32", false),
-                    // __simd_* prefixed versions for simd.aeth
-                    "__simd_f32x4_new" => ("_mm_set_ps", false), // Note: reversed arg order
-                    "__simd_f32x4_splat" => ("_mm_set1_ps", false),
-                    "__simd_f32x4_add" => ("_mm_add_ps", false),
-                    "__simd_f32x4_sub" => ("_mm_sub_ps", false),
-                    "__simd_f32x4_mul" => ("_mm_mul_ps", false),
-                    "__simd_f32x4_div" => ("_mm_div_ps", false),
-                    "__simd_f32x4_load" => ("_mm_loadu_ps", false),
-                    "__simd_f32x4_store" => ("_mm_storeu_ps", true), // void return
-                    "__simd_f64x2_new" => ("_mm_set_pd", false),
-                    "__simd_f64x2_splat" => ("_mm_set1_pd", false),
-                    "__simd_f64x2_add" => ("_mm_add_pd", false),
-                    "__simd_f64x2_mul" => ("_mm_mul_pd", false),
-                    "__simd_i32x4_new" => ("_mm_set_epi32", false),
-                    "__simd_i32x4_splat" => ("_mm_set1_epi32", false),
-                    "__simd_i32x4_add" => ("_mm_add_epi32", false),
-                    "__simd_i32x4_mul" => ("_mm_mullo_epi32", false),
-                    _ => (func.as_str(), false),
+                    "arg_count" => ("aether_arg_count", false),
+                    "args" => ("aether_args", false),
+                    _ => (self.c_function_name(func), false),
                 };
                 
                 // Check if user-defined function returns void
-                let ret_ty = self.func_ret_types.get(func).cloned();
+                // (allocation builtins return a raw pointer even when undeclared)
+                let ret_ty = self.func_ret_types.get(func).cloned().or_else(|| Self::builtin_ret_type(func));
                 let is_undefined_call = ret_ty.is_none() && !is_builtin_void;
                 
                 // Track undefined calls - might be enum variant constructors
@@ -584,7 +1150,7 @@ impl CCodeGen {
                 let call = format!("{}({})", c_func, args_str.join(", "));
                 
                 // For void functions, don't assign to a variable
-                if is_void || dest.is_none() {
+                if is_void || dest.is_none() || unread {
                     self.writeln(&format!("{};", call));
                 } else if let Some(d) = dest {
                     let var = self.get_var(*d);
@@ -595,27 +1161,57 @@ impl CCodeGen {
                     }
                 }
             }
+
+            Instruction::CallIndirect { dest, callee, args, .. } => {
+                let callee = self.value_to_c(callee);
+                let args: Vec<String> = args.iter().map(|a| self.value_to_c(a)).collect();
+                let call = format!("{}({})", callee, args.join(", "));
+                match dest {
+                    Some(d) if !unread => {
+                        let var = self.get_var(*d);
+                        self.writeln(&format!("{} = {};", var, call));
+                    }
+                    _ => self.writeln(&format!("{};", call)),
+                }
+            }
             
             Instruction::Alloca { dest, ty } => {
                 let var = self.get_var(*dest);
-                let c_type = self.ir_type_to_c(ty);
                 // Alloca in C is just a local variable
-                self.writeln(&format!("{} _alloca_{};", c_type, var));
-                self.writeln(&format!("{} = &_alloca_{};", var, var));
+                if let IRType::Array(elem, size) = ty {
+                    let c_type = self.ir_type_to_c(elem);
+                    self.writeln(&format!("{} _alloca_{}[{}];", c_type, var, size));
+                    self.writeln(&format!("{} = _alloca_{};", var, var));
+                } else {
+                    let c_type = self.ir_type_to_c(ty);
+                    self.writeln(&format!("{};", declarator(&c_type, &format!("_alloca_{}", var))));
+                    self.writeln(&format!("{} = &_alloca_{};", var, var));
+                }
                 
                 self.reg_types.insert(*dest, IRType::Ptr(Box::new(ty.clone())));
             }
             
-            Instruction::Load { dest, ptr, ty } => {
+            Instruction::Load { dest, ptr, ty, volatile } => {
                 let var = self.get_var(*dest);
+                if let Some((place, _)) = self.bitfield_place(ptr) {
+                    if !unread {
+                        self.writeln(&format!("{} = {};", var, place));
+                    }
+                    if let Some(IRType::Ptr(inner)) = self.get_value_type(ptr) {
+                        self.reg_types.insert(*dest, *inner);
+                    }
+                    return Ok(());
+                }
                 let p = self.value_to_c(ptr);
 
                 // Check if loading from volatile pointer
-                let is_volatile = matches!(ty, IRType::Ptr(inner) if matches!(**inner, IRType::Struct(ref name) if name.contains("volatile")))
-                    || self.is_volatile_type(ty);
+                let is_volatile = self.is_volatile_load(ty, *volatile);
 
-                if is_volatile {
-                    self.writeln(&format!("{} = *(volatile typeof({})*){}; /* volatile load */", var, p, p));
+                if unread {
+                    // Only a volatile load is kept without a reader
+                    self.writeln(&format!("(void)*(volatile typeof(*{})*)({}); /* volatile load */", p, p));
+                } else if is_volatile {
+                    self.writeln(&format!("{} = *(volatile typeof(*{})*)({}); /* volatile load */", var, p, p));
                 } else {
                     self.writeln(&format!("{} = *{};", var, p));
                 }
@@ -625,7 +1221,19 @@ impl CCodeGen {
                 }
             }
 
-            Instruction::Store { ptr, value } => {
+            Instruction::Store { ptr, value, volatile } => {
+                if let Some((place, width)) = self.bitfield_place(ptr) {
+                    // Keep the low bits explicitly rather than through C's
+                    // implicit truncation, which gcc warns about for constants
+                    let mask = u64::MAX >> (64 - width.clamp(1, 64));
+                    let val = match value {
+                        Value::Constant(Constant::Int(n)) => (*n as u64 & mask).to_string(),
+                        Value::Constant(Constant::Bool(b)) => (*b as u8).to_string(),
+                        _ => format!("({}) & {:#x}ull", self.value_to_c(value), mask),
+                    };
+                    self.writeln(&format!("{} = {};", place, val));
+                    return Ok(());
+                }
                 let p = self.value_to_c(ptr);
                 let val = self.value_to_c(value);
 
@@ -633,7 +1241,8 @@ impl CCodeGen {
                 let ptr_ty = self.get_value_type(ptr);
                 let val_ty = self.get_value_type(value);
 
-                let is_volatile = ptr_ty.as_ref().map(|t| self.is_volatile_type(t)).unwrap_or(false);
+                let is_volatile = *volatile
+                    || ptr_ty.as_ref().map(|t| self.is_volatile_type(t)).unwrap_or(false);
 
                 if let (Some(IRType::Ptr(ptr_inner)), Some(IRType::Ptr(val_inner))) = (&ptr_ty, &val_ty) {
                     // Both are pointers - check if storing struct* to struct field
@@ -657,6 +1266,8 @@ impl CCodeGen {
                 }
             }
             
+            Instruction::GetElementPtr { dest, .. } if self.bitfield_places.contains_key(dest) => {}
+
             Instruction::GetElementPtr { dest, ptr, index, elem_ty: _ } => {
                 let ptr_ty = self.get_value_type(ptr);
                 let mut handled = false;
@@ -689,8 +1300,8 @@ impl CCodeGen {
                     let idx = self.value_to_c(index);
                     self.writeln(&format!("{} = &{}[{}];", var, p, idx));
                     
-                    if let Some(IRType::Ptr(inner)) = ptr_ty {
-                         self.reg_types.insert(*dest, IRType::Ptr(inner));
+                    if let Some(ty) = ptr_ty.and_then(Self::element_ptr_type) {
+                         self.reg_types.insert(*dest, ty);
                      }
                 }
             }
@@ -703,59 +1314,88 @@ impl CCodeGen {
                 self.reg_types.insert(*dest, ty.clone());
             }
 
+            Instruction::DebugLoc(loc) => self.emit_line_directive(loc),
+
             Instruction::InlineAsm { template, operands } => {
                 let mut inputs = Vec::new();
                 let mut outputs = Vec::new();
                 let mut clobbers = Vec::new();
+                // Operands pinned to registers without a constraint letter go through
+                // GCC local register variables: (declaration, result copy-back)
+                let mut pinned = Vec::new();
+                let mut copy_back = Vec::new();
                 
-                for op in operands {
+                for (i, op) in operands.iter().enumerate() {
+                    if op.kind == IRAsmOperandKind::Clobber {
+                        clobbers.push(format!("\"{}\"", op.constraint));
+                        continue;
+                    }
+                    let input = op.input.as_ref().map(|v| self.value_to_c(v));
+                    let output = op.output.map(|r| self.get_var(r));
+                    
+                    let (constraint, operand) = match asm_register(&op.constraint) {
+                        Some(reg) => match gcc_register_letter(reg) {
+                            Some(letter) => (letter.to_string(), None),
+                            None => {
+                                let pin = format!("__asm_op{}", i);
+                                let init = match (&op.kind, &input) {
+                                    (IRAsmOperandKind::Output, _) | (_, None) => String::new(),
+                                    (_, Some(val)) => format!(" = {}", val),
+                                };
+                                pinned.push(format!("register int64_t {} __asm__(\"{}\"){};", pin, reg, init));
+                                if let Some(ref var) = output {
+                                    copy_back.push(format!("{} = {};", var, pin));
+                                }
+                                ("r".to_string(), Some(pin))
+                            }
+                        },
+                        None => (op.constraint.clone(), None),
+                    };
+                    
                     match op.kind {
                         IRAsmOperandKind::Input => {
-                            if let Some(ref val) = op.input {
-                                let val_str = self.value_to_c(val);
-                                inputs.push(format!("\"{}\" ({})", op.constraint, val_str));
+                            if let Some(val) = operand.or(input) {
+                                inputs.push(format!("\"{}\" ({})", constraint, val));
                             }
                         }
                         IRAsmOperandKind::Output => {
-                            if let Some(reg) = op.output {
-                                let var = self.get_var(reg);
-                                let constraint = if !op.constraint.starts_with('=') && !op.constraint.starts_with('+') {
-                                    format!("={}", op.constraint)
-                                } else {
-                                    op.constraint.clone()
-                                };
-                                outputs.push(format!("\"{}\" ({})", constraint, var));
+                            if let (Some(reg), Some(var)) = (op.output, output) {
+                                outputs.push(format!("\"={}\" ({})", constraint, operand.unwrap_or(var)));
                                 self.reg_types.insert(reg, IRType::I64); // Default to I64
                             }
                         }
                         IRAsmOperandKind::InOut => {
-                             if let Some(reg) = op.output {
-                                 let var = self.get_var(reg);
-                                 if let Some(ref val) = op.input {
-                                     let val_str = self.value_to_c(val);
-                                     self.writeln(&format!("{} = {};", var, val_str));
-                                 }
-                                 let constraint = if !op.constraint.starts_with('+') {
-                                    format!("+{}", op.constraint)
-                                } else {
-                                    op.constraint.clone()
+                            if let (Some(reg), Some(var)) = (op.output, output) {
+                                let target = match operand {
+                                    Some(pin) => pin,
+                                    None => {
+                                        if let Some(ref val) = input {
+                                            self.writeln(&format!("{} = {};", var, val));
+                                        }
+                                        var
+                                    }
                                 };
-                                outputs.push(format!("\"{}\" ({})", constraint, var));
-                                self.reg_types.insert(reg, IRType::I64); 
-                             }
-                        }
-                        IRAsmOperandKind::Clobber => {
-                             clobbers.push(format!("\"{}\"", op.constraint));
+                                outputs.push(format!("\"+{}\" ({})", constraint, target));
+                                self.reg_types.insert(reg, IRType::I64);
+                            }
                         }
+                        IRAsmOperandKind::Clobber => {}
                     }
                 }
                 
-                let outputs_str = outputs.join(", ");
-                let inputs_str = inputs.join(", ");
-                let clobbers_str = clobbers.join(", ");
-                
-                self.writeln(&format!("__asm__ volatile (\"{}\" : {} : {} : {});", 
-                    template, outputs_str, inputs_str, clobbers_str));
+                let asm = format!("__asm__ volatile (\"{}\" : {} : {} : {});",
+                    template, outputs.join(", "), inputs.join(", "), clobbers.join(", "));
+                if pinned.is_empty() {
+                    self.writeln(&asm);
+                } else {
+                    self.writeln("{");
+                    self.indent += 1;
+                    for line in pinned.iter().chain(std::iter::once(&asm)).chain(&copy_back) {
+                        self.writeln(line);
+                    }
+                    self.indent -= 1;
+                    self.writeln("}");
+                }
             }
             
             Instruction::Phi { dest, incoming } => {
@@ -764,11 +1404,9 @@ impl CCodeGen {
                 // Here we just ensure the variable exists and track its type.
                 let var = self.get_var(*dest);
 
-                // Track type from the first incoming value
-                if let Some((val, _)) = incoming.first() {
-                    if let Some(ty) = self.get_value_type(val) {
-                        self.reg_types.insert(*dest, ty);
-                    }
+                // Track type from the first incoming value of known type
+                if let Some(ty) = incoming.iter().find_map(|(val, _)| self.get_value_type(val)) {
+                    self.reg_types.insert(*dest, ty);
                 }
 
                 // Add a comment for debugging
@@ -785,33 +1423,89 @@ impl CCodeGen {
     /// Generate phi assignments for a jump to target block.
     /// This inserts assignments like `phi_var = value;` before the jump.
     fn generate_phi_assignments(&mut self, current_block_id: usize, target_block_id: usize, func: &IRFunction) {
-        // Find all phi instructions in the target block
-        if let Some(target_block) = func.blocks.get(target_block_id) {
-            for inst in &target_block.instructions {
-                if let Instruction::Phi { dest, incoming } = inst {
-                    // Find the value for the current block
-                    for (val, from_block) in incoming {
-                        if from_block.0 == current_block_id {
-                            let var = self.get_var(*dest);
-                            let v = self.value_to_c(val);
-                            self.writeln(&format!("{} = {};  /* phi from block {} */", var, v, current_block_id));
-                            break;
-                        }
-                    }
-                }
+        // Find the value each phi of the target block takes from the current block
+        let Some(target_block) = func.blocks.get(target_block_id) else { return };
+        let copies: Vec<(Register, &Value)> = target_block.instructions.iter()
+            .filter_map(|inst| match inst {
+                Instruction::Phi { dest, incoming } if self.is_read(*dest) => incoming.iter()
+                    .find(|(_, from_block)| from_block.0 == current_block_id)
+                    .map(|(val, _)| (*dest, val)),
+                _ => None,
+            })
+            .collect();
+
+        // The phis take their values at once: when one reads another's
+        // destination (a loop swapping two variables), copy through temporaries
+        let overlapping = copies.iter().any(|(_, val)| {
+            matches!(val, Value::Register(r) if copies.iter().any(|(dest, _)| dest == r))
+        });
+        if !overlapping {
+            for (dest, val) in copies {
+                let var = self.get_var(dest);
+                let v = self.assigned_value_to_c(val);
+                self.writeln(&format!("{} = {};  /* phi from block {} */", var, v, current_block_id));
+            }
+            return;
+        }
+        self.writeln("{");
+        self.indent += 1;
+        for (i, (dest, val)) in copies.iter().enumerate() {
+            let c_type = self.reg_types.get(dest)
+                .map(|t| self.ir_type_to_c(t))
+                .unwrap_or("int64_t".to_string());
+            let v = self.assigned_value_to_c(val);
+            self.writeln(&format!("{} phi_tmp{} = {};", c_type, i, v));
+        }
+        for (i, (dest, _)) in copies.iter().enumerate() {
+            let var = self.get_var(*dest);
+            self.writeln(&format!("{} = phi_tmp{};  /* phi from block {} */", var, i, current_block_id));
+        }
+        self.indent -= 1;
+        self.writeln("}");
+    }
+
+    /// C expression a switch dispatches on: the value itself, or an enum's tag
+    fn switch_operand(&mut self, value: &Value) -> String {
+        let v = self.value_to_c(value);
+        match self.get_value_type(value) {
+            Some(IRType::Struct(name)) if self.enum_names.contains(&name) => format!("{}.tag", v),
+            Some(IRType::Ptr(inner)) if matches!(inner.as_ref(), IRType::Struct(name) if self.enum_names.contains(name)) => {
+                format!("{}->tag", v)
             }
+            _ => v,
         }
     }
 
     /// Generate C code for a terminator (with phi assignments)
+    /// C condition of a branch; unlikely towards a block calling a `#[cold]` function
+    fn branch_condition(&mut self, cond: &Value, then_target: BlockId, else_target: BlockId, func: &IRFunction) -> String {
+        let c = self.value_to_c(cond);
+        let cold_fns = &self.cold_fns;
+        let calls_cold = |target: BlockId| func.blocks.iter().find(|block| block.id == target).is_some_and(|block| {
+            block.instructions.iter().any(|inst| matches!(inst, Instruction::Call { func, .. } if cold_fns.contains(func)))
+        });
+        match (calls_cold(then_target), calls_cold(else_target)) {
+            (true, false) => format!("__builtin_expect(!!({}), 0)", c),
+            (false, true) => format!("__builtin_expect(!!({}), 1)", c),
+            _ => c,
+        }
+    }
+
     fn generate_terminator_with_phis(&mut self, term: &Terminator, current_block_id: usize, func: &IRFunction) -> Result<()> {
         match term {
             Terminator::Return { value } => {
-                if let Some(val) = value {
-                    let v = self.value_to_c(val);
-                    self.writeln(&format!("return {};", v));
-                } else {
-                    self.writeln("return;");
+                match value {
+                    // Unit only reaches the return of a value-returning function from code
+                    // that never runs (after a `loop`); return a zero of the right type
+                    Some(Value::Unit) if !matches!(func.ret_type, IRType::Void | IRType::Ptr(_)) => {
+                        let c_type = self.ir_type_to_c(&func.ret_type);
+                        self.writeln(&format!("return ({}){{0}};", c_type));
+                    }
+                    Some(val) => {
+                        let v = self.value_to_c(val);
+                        self.writeln(&format!("return {};", v));
+                    }
+                    None => self.writeln("return;"),
                 }
             }
 
@@ -823,7 +1517,7 @@ impl CCodeGen {
             }
 
             Terminator::Branch { cond, then_target, else_target } => {
-                let c = self.value_to_c(cond);
+                let c = self.branch_condition(cond, *then_target, *else_target, func);
                 let then_label = self.block_labels[&then_target.0].clone();
                 let else_label = self.block_labels[&else_target.0].clone();
 
@@ -841,6 +1535,24 @@ impl CCodeGen {
                 self.writeln("}");
             }
 
+            Terminator::Switch { value, cases, default } => {
+                let v = self.switch_operand(value);
+                self.writeln(&format!("switch ({}) {{", v));
+                for (case, target) in cases.iter().map(|(c, t)| (Some(*c), t)).chain(std::iter::once((None, default))) {
+                    match case {
+                        Some(case) => self.writeln(&format!("case {}LL: {{", case)),
+                        None => self.writeln("default: {"),
+                    }
+                    self.indent += 1;
+                    self.generate_phi_assignments(current_block_id, target.0, func);
+                    let label = self.block_labels[&target.0].clone();
+                    self.writeln(&format!("goto {};", label));
+                    self.indent -= 1;
+                    self.writeln("}");
+                }
+                self.writeln("}");
+            }
+
             Terminator::Unreachable => {
                 self.writeln("__builtin_unreachable();");
             }
@@ -871,6 +1583,18 @@ impl CCodeGen {
                 let else_label = self.block_labels[&else_target.0].clone();
                 self.writeln(&format!("if ({}) goto {}; else goto {};", c, then_label, else_label));
             }
+
+            Terminator::Switch { value, cases, default } => {
+                let v = self.switch_operand(value);
+                self.writeln(&format!("switch ({}) {{", v));
+                for (case, target) in cases {
+                    let label = &self.block_labels[&target.0];
+                    self.writeln(&format!("case {}LL: goto {};", case, label));
+                }
+                let label = &self.block_labels[&default.0];
+                self.writeln(&format!("default: goto {};", label));
+                self.writeln("}");
+            }
             
             Terminator::Unreachable => {
                 self.writeln("__builtin_unreachable();");
@@ -882,51 +1606,135 @@ impl CCodeGen {
     /// Generate the complete C source file
     pub fn generate_source(&mut self, module: &IRModule) -> Result<String> {
         self.output.clear();
+        let no_std = module.no_std || self.freestanding;
+        // What the error for a feature needing the runtime blames
+        let disabled_by = if module.no_std { "#![no_std]" } else { "--freestanding" };
+        self.wrap_main = !no_std && !module.no_main && !self.suppress_main
+            && module.functions.iter().any(|f| f.name == "main");
+        if self.freestanding && !module.functions.iter().any(|f| f.name == self.entry) {
+            return Err(Error::CodeGen(format!(
+                "a --freestanding program starts at `{}`, which it does not define (choose another with --entry)", self.entry
+            )));
+        }
+        if let Some(value) = self.freestanding.then(|| heap_allocated_value(module)).flatten() {
+            return Err(Error::CodeGen(format!("{} is heap-allocated, which --freestanding does not support", value)));
+        }
         
         // Header
         self.writeln("/* Generated by AetherLang C Backend */");
         self.writeln("#include <stdint.h>");
         self.writeln("#include <stdbool.h>");
-        self.writeln("#include <stdio.h>");
-        self.writeln("#include <stdlib.h>");
-        self.writeln("#include <string.h>");
-        // SIMD headers (platform-specific)
-        if self.target_triple.contains("aarch64") || self.target_triple.contains("arm64") || self.target_triple.contains("arm") {
-            self.writeln("#include <arm_neon.h>");
-        } else if self.target_triple.contains("x86") || self.target_triple.contains("i686") || self.target_triple.contains("x86_64") {
-            self.writeln("#include <immintrin.h>  /* SSE/AVX */");
-        }
-        // LLVM-C headers (if module uses LLVM functions)
-        let uses_llvm = module.externs.iter().any(|e| e.name.starts_with("LLVM"));
-        if uses_llvm {
-            self.writeln("#include <llvm-c/Core.h>");
-            self.writeln("#include <llvm-c/Analysis.h>");
-            self.writeln("#include <llvm-c/BitWriter.h>");
-            self.writeln("#include <llvm-c/Target.h>");
-            self.writeln("#include <llvm-c/TargetMachine.h>");
-        }
-
-        // Skip SIMD headers for other platforms
+        self.writeln("#include <stddef.h>");
+        if self.freestanding {
+            // Only the headers a freestanding C implementation provides
+            self.writeln("/* freestanding - no C library */");
+        } else {
+            self.writeln("#include <stdio.h>");
+            self.writeln("#include <stdlib.h>");
+            self.writeln("#include <string.h>");
+            if self.sanitizers.contains(SanitizerFlags::ADDRESS) {
+                self.writeln("#include <sanitizer/asan_interface.h>");
+            }
+            // SIMD headers (platform-specific)
+            if self.target_triple.contains("aarch64") || self.target_triple.contains("arm64") || self.target_triple.contains("arm") {
+                self.writeln("#include <arm_neon.h>");
+            } else if self.target_triple.contains("x86") || self.target_triple.contains("i686") || self.target_triple.contains("x86_64") {
+                self.writeln("#include <immintrin.h>  /* SSE/AVX */");
+            }
+            // LLVM-C headers (if module uses LLVM functions)
+            let uses_llvm = module.externs.iter().any(|e| e.name.starts_with("LLVM"));
+            if uses_llvm {
+                self.writeln("#include <llvm-c/Core.h>");
+                self.writeln("#include <llvm-c/Analysis.h>");
+                self.writeln("#include <llvm-c/BitWriter.h>");
+                self.writeln("#include <llvm-c/Target.h>");
+                self.writeln("#include <llvm-c/TargetMachine.h>");
+            }
+        }
+
+        // 256-bit vectors on x86 need AVX2 for every function after the headers
+        let avx = self.target_triple.contains("x86") && simd::uses_vectors_wider_than(module, 16);
+        if avx {
+            self.writeln("#if defined(__clang__)");
+            self.writeln("#pragma clang attribute push (__attribute__((target(\"avx2\"))), apply_to = function)");
+            self.writeln("#elif defined(__GNUC__)");
+            self.writeln("#pragma GCC target(\"avx2\")");
+            self.writeln("#endif");
+        }
+        self.writeln("");
+        self.writeln(runtime::STR_TYPEDEF);
+        self.write_raw(runtime::COLLECTION_TYPEDEFS);
         self.writeln("");
 
         
         // Runtime support functions (skip for no_std)
-        if !module.no_std {
+        if !no_std {
             self.writeln("/* AetherLang Runtime */");
-            self.writeln("static void aether_print(const char* s) { printf(\"%s\", s); }");
-            self.writeln("static void aether_println(const char* s) { printf(\"%s\\n\", s); }");
-            self.writeln("static void aether_print_i64(int64_t n) { printf(\"%lld\", (long long)n); }");
-            self.writeln("static void aether_println_i64(int64_t n) { printf(\"%lld\\n\", (long long)n); }");
-            self.writeln("static void aether_assert(bool c) { if(!c) { fprintf(stderr, \"Assertion failed\\n\"); exit(1); } }");
+            self.writeln("static inline void aether_print(AethStr s) { fwrite(s.data, 1, s.len, stdout); }");
+            self.writeln("static inline void aether_println(AethStr s) { fwrite(s.data, 1, s.len, stdout); putchar('\\n'); }");
+            self.writeln("static inline void aether_print_i64(int64_t n) { printf(\"%lld\", (long long)n); }");
+            self.writeln("static inline void aether_println_i64(int64_t n) { printf(\"%lld\\n\", (long long)n); }");
+            self.write_raw(&runtime::panic_runtime(panic::mode_of(module), self.json_panics));
+            self.writeln(&format!("static inline void aether_assert(bool c) {{ if(!c) {}(\"assertion failed\", \"<unknown>\", 0); }}", PANIC_FN));
+            self.writeln("static int aether_argc;");
+            self.writeln("static inline int32_t aether_arg_count(void) { return aether_argc; }");
+            self.writeln("static AethStr* aether_arg_strs;");
+            self.writeln("static inline AethStr* aether_args(void) { return aether_arg_strs; }");
+            if self.sanitizers.contains(SanitizerFlags::ADDRESS) {
+                self.emit_asan_runtime();
+            }
+            if let Some(coverage) = &module.coverage {
+                self.emit_coverage_runtime(coverage);
+            }
+            if collections::uses_runtime(module) {
+                self.write_raw(&runtime::collections_runtime());
+            }
+            if fileio::uses_runtime(module) {
+                self.write_raw(&runtime::file_runtime());
+            }
+            if bench::uses_runtime(module) {
+                self.write_raw(&runtime::bench_runtime());
+            }
             self.writeln("");
+        } else if module.coverage.is_some() {
+            return Err(Error::CodeGen(format!("--coverage needs the runtime, which {} disables", disabled_by)));
+        } else if collections::uses_runtime(module) {
+            return Err(Error::CodeGen(format!("Vec and HashMap need the runtime, which {} disables", disabled_by)));
+        } else if fileio::uses_runtime(module) {
+            return Err(Error::CodeGen(format!("file I/O needs the runtime, which {} disables", disabled_by)));
+        } else if bench::uses_runtime(module) {
+            return Err(Error::CodeGen(format!("benchmarks need the runtime, which {} disables", disabled_by)));
+        } else if panic::mode_of(module) == PanicMode::UnwindTrace {
+            return Err(Error::CodeGen(format!("--panic=unwind-trace needs the runtime, which {} disables", disabled_by)));
         } else {
-            self.writeln("/* no_std mode - runtime disabled */");
+            self.writeln(&format!("/* {} - runtime disabled */", disabled_by));
+            if panic::uses_runtime(module) {
+                // Nothing to print with, so a failed check just stops the program
+                self.writeln(&format!("static void {}(const char* m, const char* f, int64_t l) {{ (void)m; (void)f; (void)l; __builtin_trap(); }}", PANIC_FN));
+            }
             self.writeln("");
         }
+        self.emit_str_runtime();
         
         // Struct definitions
+        self.union_names = module.structs.iter()
+            .filter(|s| s.is_union)
+            .map(|s| s.name.clone())
+            .collect();
+        // Forward declarations, so definitions and prototypes may refer to any aggregate
+        if !module.structs.is_empty() || !module.enums.is_empty() {
+            self.writeln("/* Forward Declarations */");
+            for struct_def in &module.structs {
+                self.writeln(&format!("{} {};", self.aggregate_keyword(&struct_def.name), struct_def.name));
+            }
+            for enum_def in &module.enums {
+                self.writeln(&format!("struct {};", enum_def.name));
+            }
+            self.writeln("");
+        }
         self.writeln("/* Struct Definitions */");
         for struct_def in &module.structs {
+            let keyword = self.aggregate_keyword(&struct_def.name);
             // Add GCC attributes based on repr
             let attr = match struct_def.repr {
                 StructRepr::C => "",  // C layout is default for GCC structs
@@ -934,12 +1742,23 @@ impl CCodeGen {
                 StructRepr::Transparent => "",  // Transparent is a Rust concept, no direct C equivalent
                 StructRepr::Default => "",  // No special attribute
             };
-            self.writeln(&format!("struct {}{} {{", struct_def.name, attr));
-            for (field_name, field_type) in &struct_def.fields {
-                let c_type = self.ir_type_to_c(field_type);
-                self.writeln(&format!("    {} {};", c_type, field_name));
+            self.writeln(&format!("{} {}{} {{", keyword, struct_def.name, attr));
+            for member in struct_members(struct_def, |ty, name| format!("{} {}", self.ir_type_to_c(ty), name)) {
+                self.writeln(&format!("    {};", member));
             }
             self.writeln("};\n");
+            // Pin the C compiler's layout to the IR layout engine so they cannot diverge
+            if let Some(layout) = module.struct_layout(&struct_def.name) {
+                self.writeln(&format!("_Static_assert(sizeof({} {}) == {}, \"layout of {}\");",
+                    keyword, struct_def.name, layout.size, struct_def.name));
+                let fields = struct_def.fields.iter().zip(&layout.offsets).enumerate()
+                    .filter(|(i, _)| struct_def.bits.get(*i).copied().flatten().is_none())
+                    .map(|(_, field)| field);
+                for ((field_name, _), offset) in fields {
+                    self.writeln(&format!("_Static_assert(offsetof({} {}, {}) == {}, \"offset of {}.{}\");",
+                        keyword, struct_def.name, field_name, offset, struct_def.name, field_name));
+                }
+            }
             self.writeln("");
         }
         
@@ -947,11 +1766,12 @@ impl CCodeGen {
         self.writeln("/* Enum Definitions (Tagged Unions) */");
         for enum_def in &module.enums {
             let enum_name = &enum_def.name;
+            self.enum_names.insert(enum_name.clone());
             
             // Generate tag enum
             self.writeln(&format!("enum {}_Tag {{", enum_name));
-            for (idx, variant) in enum_def.variants.iter().enumerate() {
-                self.writeln(&format!("    {}_{}_TAG = {},", enum_name, variant.name, idx));
+            for variant in &enum_def.variants {
+                self.writeln(&format!("    {}_{}_TAG = {},", enum_name, variant.name, variant.discriminant));
             }
             self.writeln("};");
             self.writeln("");
@@ -984,7 +1804,13 @@ impl CCodeGen {
             
             // Generate the main enum struct
             self.writeln(&format!("struct {} {{", enum_name));
-            self.writeln(&format!("    enum {}_Tag tag;", enum_name));
+            // A `repr` fixes the tag's width; otherwise it is the C enum itself
+            let tag_type = if enum_def.tag == IRType::I32 {
+                format!("enum {}_Tag", enum_name)
+            } else {
+                self.ir_type_to_c(&enum_def.tag)
+            };
+            self.writeln(&format!("    {} tag;", tag_type));
             if !has_data_variants.is_empty() {
                 self.writeln(&format!("    union {}_Data data;", enum_name));
             }
@@ -998,10 +1824,11 @@ impl CCodeGen {
                 
                 // Register this as a known function with its return type (pointer to struct)
                 self.func_ret_types.insert(func_name.clone(), IRType::Ptr(Box::new(IRType::Struct(enum_name.clone()))));
+                self.ctor_fields.insert(func_name.clone(), variant.fields.clone());
                 
                 if variant.fields.is_empty() {
                     // Unit variant constructor
-                    self.writeln(&format!("static struct {}* {}(void) {{", enum_name, func_name));
+                    self.writeln(&format!("static inline struct {}* {}(void) {{", enum_name, func_name));
                     self.writeln(&format!("    struct {}* result = (struct {}*)malloc(sizeof(struct {}));", enum_name, enum_name, enum_name));
                     self.writeln(&format!("    result->tag = {}_{}_TAG;", enum_name, variant.name));
                     self.writeln("    return result;");
@@ -1009,7 +1836,7 @@ impl CCodeGen {
                 } else if variant.fields.len() == 1 {
                     // Single-field variant constructor
                     let c_type = self.ir_type_to_c(&variant.fields[0]);
-                    self.writeln(&format!("static struct {}* {}({} value) {{", enum_name, func_name, c_type));
+                    self.writeln(&format!("static inline struct {}* {}({} value) {{", enum_name, func_name, c_type));
                     self.writeln(&format!("    struct {}* result = (struct {}*)malloc(sizeof(struct {}));", enum_name, enum_name, enum_name));
                     self.writeln(&format!("    result->tag = {}_{}_TAG;", enum_name, variant.name));
                     self.writeln(&format!("    result->data.{} = value;", variant.name.to_lowercase()));
@@ -1020,7 +1847,7 @@ impl CCodeGen {
                     let params: Vec<String> = variant.fields.iter().enumerate()
                         .map(|(i, ty)| format!("{} _{}", self.ir_type_to_c(ty), i))
                         .collect();
-                    self.writeln(&format!("static struct {}* {}({}) {{", enum_name, func_name, params.join(", ")));
+                    self.writeln(&format!("static inline struct {}* {}({}) {{", enum_name, func_name, params.join(", ")));
                     self.writeln(&format!("    struct {}* result = (struct {}*)malloc(sizeof(struct {}));", enum_name, enum_name, enum_name));
                     self.writeln(&format!("    result->tag = {}_{}_TAG;", enum_name, variant.name));
                     for i in 0..variant.fields.len() {
@@ -1034,9 +1861,35 @@ impl CCodeGen {
 
         }
             
+        // Global variable definitions
+        if !module.globals.is_empty() {
+            self.writeln("/* Global Variables */");
+        }
+        for global in &module.globals {
+            let c_type = self.ir_type_to_c(&global.ty);
+            if global.is_extern {
+                self.writeln(&format!("extern {} {};", c_type, global.name));
+            } else if let Some(init) = &global.init {
+                let mut value = self.value_to_c(&Value::Constant(init.clone()));
+                if matches!(global.ty, IRType::Ptr(_)) {
+                    value = format!("({}){}", c_type, value);
+                }
+                self.writeln(&format!("{} {} = {};", c_type, global.name, value));
+            } else {
+                self.writeln(&format!("{} {};", c_type, global.name));
+            }
+            self.declared_globals.insert(global.name.clone(), global.ty.clone());
+        }
+        if !module.globals.is_empty() {
+            self.writeln("");
+        }
+
         // Populate layout map
         for struct_def in &module.structs {
             self.struct_layouts.insert(struct_def.name.clone(), struct_def.fields.clone());
+            if !struct_def.bits.is_empty() {
+                self.struct_bits.insert(struct_def.name.clone(), struct_def.bits.clone());
+            }
         }
 
         
@@ -1045,16 +1898,22 @@ impl CCodeGen {
             self.func_ret_types.insert(func.name.clone(), func.ret_type.clone());
         }
         
+        self.cold_fns = module.functions.iter().filter(|f| f.cold).map(|f| f.name.clone()).collect();
+
         // Also register extern function return types
         for ext in &module.externs {
             self.func_ret_types.insert(ext.name.clone(), ext.ret_type.clone());
         }
+        self.foreign_fns = module.externs.iter()
+            .filter(|ext| !module.functions.iter().any(|f| f.name == ext.name))
+            .map(|ext| ext.name.clone())
+            .collect();
         
         // Forward declarations
         for func in &module.functions {
             let ret_type = self.ir_type_to_c(&func.ret_type);
             // Special handling for main function forward declaration
-            let params: Vec<String> = if func.name == "main" && func.params.len() == 2 {
+            let params: Vec<String> = if func.name == "main" && func.params.len() == 2 && !self.wrap_main {
                 vec!["int".to_string(), "char**".to_string()]
             } else {
                 func.params.iter()
@@ -1062,20 +1921,426 @@ impl CCodeGen {
                     .collect()
             };
             let params_str = if params.is_empty() { "void".to_string() } else { params.join(", ") };
-            self.writeln(&format!("{} {}({});", ret_type, func.name, params_str));
+            self.writeln(&format!("{}{} {}({});", self.linkage(func), ret_type, self.c_function_name(&func.name), params_str));
+        }
+        // Foreign functions no included header declares (LLVM-C has its own headers)
+        for ext in &module.externs {
+            if !self.foreign_fns.contains(&ext.name) || ext.name.starts_with("LLVM")
+                || (!self.freestanding && C_HEADER_FUNCTIONS.contains(&ext.name.as_str())) {
+                continue;
+            }
+            let params: Vec<String> = ext.params.iter().map(|(_, ty)| self.ir_type_to_c(ty)).collect();
+            let params_str = if params.is_empty() { "void".to_string() } else { params.join(", ") };
+            self.writeln(&format!("extern {} {}({});", self.ir_type_to_c(&ext.ret_type), ext.name, params_str));
         }
 
         self.writeln("");
         
-        // Function definitions
-        for func in &module.functions {
-            self.generate_function(func)?;
+        // Function definitions, each worker writing its own buffer
+        let mut output = std::mem::take(&mut self.output);
+        let definitions = map_chunks(&module.functions, self.jobs, |functions| {
+            let mut worker = self.clone();
+            functions.iter().try_for_each(|func| worker.generate_function(func))?;
+            Ok(worker.output)
+        });
+        for text in definitions {
+            output.push_str(&text?);
         }
-        
+        self.output = output;
+
+        if let Some(main) = module.functions.iter().find(|f| f.name == "main").filter(|_| self.wrap_main) {
+            self.emit_main_wrapper(main);
+        }
+        if avx {
+            self.writeln("#if defined(__clang__)");
+            self.writeln("#pragma clang attribute pop");
+            self.writeln("#endif");
+        }
+
+        self.output = Self::peephole_optimize(&self.output);
         Ok(self.output.clone())
 
     }
 
+    /// Generate a C header declaring every `#[extern_c]` function.
+    /// Struct types are declared opaquely, so C callers pass them by pointer.
+    pub fn generate_header(&mut self, module: &IRModule) -> String {
+        self.union_names = module.structs.iter()
+            .filter(|s| s.is_union)
+            .map(|s| s.name.clone())
+            .collect();
+        let guard = header_guard(&module.name);
+        let exports: Vec<&IRFunction> = module.functions.iter().filter(|f| f.extern_c).collect();
+
+        let mut header = String::new();
+        header.push_str("/* Generated by AetherLang C Backend */\n");
+        header.push_str(&format!("#ifndef {}\n#define {}\n\n", guard, guard));
+        header.push_str("#include <stdint.h>\n#include <stdbool.h>\n\n");
+        header.push_str("#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n");
+
+        // Opaque declarations for aggregates named in the signatures
+        let mut aggregates = Vec::new();
+        for func in &exports {
+            for ty in func.params.iter().map(|(_, ty)| ty).chain(std::iter::once(&func.ret_type)) {
+                let mut inner = ty;
+                while let IRType::Ptr(pointee) = inner {
+                    inner = pointee;
+                }
+                if let IRType::Struct(name) = inner {
+                    if !aggregates.contains(name) {
+                        aggregates.push(name.clone());
+                    }
+                }
+            }
+        }
+        for name in &aggregates {
+            header.push_str(&format!("{} {};\n", self.aggregate_keyword(name), name));
+        }
+        if !aggregates.is_empty() {
+            header.push('\n');
+        }
+
+        for func in &exports {
+            let params: Vec<String> = func.params.iter()
+                .map(|(name, ty)| format!("{} {}", self.ir_type_to_c(ty), name))
+                .collect();
+            let params_str = if params.is_empty() { "void".to_string() } else { params.join(", ") };
+            header.push_str(&format!("{} {}({});\n", self.ir_type_to_c(&func.ret_type), func.name, params_str));
+        }
+
+        header.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n");
+        header.push_str(&format!("#endif /* {} */\n", guard));
+        header
+    }
+
+    /// Generate the header of a .c/.h pair (`--emit-c-header`): the `pub`
+    /// structs with their layout, the functions C code may call, and the
+    /// foreign functions the module expects the rest of the program to define
+    pub fn generate_interface_header(&mut self, module: &IRModule) -> String {
+        self.union_names = module.structs.iter()
+            .filter(|s| s.is_union)
+            .map(|s| s.name.clone())
+            .collect();
+        let guard = header_guard(&module.name);
+        let exports: Vec<&IRFunction> = module.functions.iter()
+            .filter(|f| f.is_pub || f.extern_c || f.no_mangle || (f.name == "main" && self.suppress_main))
+            .collect();
+
+        // Aggregates used by value need complete definitions, as do their fields
+        let mut defined: Vec<&str> = module.structs.iter().filter(|s| s.is_pub).map(|s| s.name.as_str()).collect();
+        for func in &exports {
+            for ty in func.params.iter().map(|(_, ty)| ty).chain(std::iter::once(&func.ret_type)) {
+                if let Some(name) = by_value_aggregate(ty) {
+                    if !defined.contains(&name) {
+                        defined.push(name);
+                    }
+                }
+            }
+        }
+        let mut i = 0;
+        while i < defined.len() {
+            if let Some(def) = module.structs.iter().find(|s| s.name == defined[i]) {
+                for (_, ty) in &def.fields {
+                    if let Some(name) = by_value_aggregate(ty) {
+                        if !defined.contains(&name) {
+                            defined.push(name);
+                        }
+                    }
+                }
+            }
+            i += 1;
+        }
+
+        let mut used = HeaderTypes::default();
+        let mut body = String::new();
+        let structs: Vec<&IRStruct> = module.structs.iter().filter(|s| defined.contains(&s.name.as_str())).collect();
+        if !structs.is_empty() {
+            body.push_str("/* Structs, laid out exactly as in the .c file */\n");
+        }
+        for def in structs {
+            let keyword = self.aggregate_keyword(&def.name);
+            let attr = if def.repr == StructRepr::Packed { " __attribute__((packed))" } else { "" };
+            used.aggregate(&def.name);
+            body.push_str(&format!("{} {}{} {{\n", keyword, def.name, attr));
+            for member in struct_members(def, |ty, name| self.header_decl(ty, name, &mut used)) {
+                body.push_str(&format!("    {};\n", member));
+            }
+            body.push_str("};\n");
+            if let Some(layout) = module.struct_layout(&def.name) {
+                body.push_str("#ifndef __cplusplus\n");
+                body.push_str(&format!("_Static_assert(sizeof({} {}) == {}, \"layout of {}\");\n", keyword, def.name, layout.size, def.name));
+                body.push_str("#endif\n");
+            }
+            body.push('\n');
+        }
+
+        if !exports.is_empty() {
+            body.push_str("/* Functions */\n");
+        }
+        for func in &exports {
+            let name = self.c_function_name(&func.name);
+            let source = if func.extern_c || func.no_mangle || func.name == "main" {
+                func.name.clone()
+            } else {
+                func.name.replace(crate::middle::mangle::SEPARATOR, "::")
+            };
+            let params: Vec<String> = if func.name == "main" && func.params.len() == 2 {
+                vec!["int argc".to_string(), "char** argv".to_string()]
+            } else {
+                func.params.iter().map(|(param, ty)| self.header_decl(ty, param, &mut used)).collect()
+            };
+            let params_str = if params.is_empty() { "void".to_string() } else { params.join(", ") };
+            let ret = self.header_type(&func.ret_type, &mut used);
+            body.push_str(&format!("/* {} */\n{} {}({});\n", source, ret, name, params_str));
+        }
+
+        let mut foreign: Vec<&IRExtern> = Vec::new();
+        for ext in &module.externs {
+            let declared = module.functions.iter().any(|f| f.name == ext.name)
+                || foreign.iter().any(|f| f.name == ext.name)
+                || ext.name.starts_with("LLVM")
+                || C_HEADER_FUNCTIONS.contains(&ext.name.as_str());
+            if !declared {
+                foreign.push(ext);
+            }
+        }
+        if !foreign.is_empty() {
+            body.push_str("\n/* Defined elsewhere in the program (extern blocks and imported modules) */\n");
+        }
+        for ext in foreign {
+            let params: Vec<String> = ext.params.iter().map(|(param, ty)| self.header_decl(ty, param, &mut used)).collect();
+            let params_str = if params.is_empty() { "void".to_string() } else { params.join(", ") };
+            let ret = self.header_type(&ext.ret_type, &mut used);
+            body.push_str(&format!("{} {}({});\n", ret, ext.name, params_str));
+        }
+
+        let mut header = String::new();
+        header.push_str("/* Generated by AetherLang C Backend */\n");
+        header.push_str(&format!("/* C interface of module `{}`.\n", module.name));
+        header.push_str(" *\n * Symbol names are stable across builds:\n");
+        header.push_str(&format!(" *   pub fn name            ->  {}__name\n", module.name));
+        header.push_str(" *   Type::method (impl)    ->  Type__method\n");
+        header.push_str(" *   m::name (in `mod m`)   ->  m__name\n");
+        header.push_str(" *   #[extern_c] fn name    ->  name\n");
+        header.push_str(" *   fn main (--no-main)    ->  aether_main\n */\n");
+        header.push_str(&format!("#ifndef {}\n#define {}\n\n", guard, guard));
+        header.push_str("#include <stdint.h>\n#include <stdbool.h>\n#include <stddef.h>\n\n");
+        header.push_str("#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n");
+        if !used.primitives.is_empty() {
+            header.push_str("/* AetherLang primitive types */\n");
+            for (alias, c_type) in &used.primitives {
+                header.push_str(&format!("typedef {} {};\n", c_type, alias));
+            }
+            header.push('\n');
+        }
+        if used.str {
+            header.push_str(&format!("#ifndef AETHER_STR_DEFINED\n#define AETHER_STR_DEFINED\n{}\n#endif\n\n", runtime::STR_TYPEDEF));
+        }
+        for handle in &used.handles {
+            header.push_str(&format!("typedef struct {} {};\n", handle, handle));
+        }
+        if !used.aggregates.is_empty() {
+            for name in &used.aggregates {
+                header.push_str(&format!("{} {};\n", self.aggregate_keyword(name), name));
+            }
+            header.push('\n');
+        }
+        header.push_str(&body);
+        header.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n");
+        header.push_str(&format!("#endif /* {} */\n", guard));
+        header
+    }
+
+    /// `ty` as spelled in an interface header, noting the types it needs
+    fn header_type(&self, ty: &IRType, used: &mut HeaderTypes) -> String {
+        match ty {
+            IRType::Bool | IRType::I8 | IRType::I16 | IRType::I32 | IRType::I64
+            | IRType::U8 | IRType::U16 | IRType::U32 | IRType::U64 | IRType::F32 | IRType::F64 => {
+                let alias = format!("aeth_{}", crate::middle::mangle::type_tag(ty));
+                if !used.primitives.iter().any(|(a, _)| *a == alias) {
+                    used.primitives.push((alias.clone(), self.ir_type_to_c(ty)));
+                }
+                alias
+            }
+            IRType::Ptr(inner) => match inner.as_ref() {
+                IRType::Array(elem, _) => format!("{}*", self.header_type(elem, used)),
+                _ => format!("{}*", self.header_type(inner, used)),
+            },
+            IRType::Struct(name) => {
+                used.aggregate(name);
+                format!("{} {}", self.aggregate_keyword(name), name)
+            }
+            IRType::Str => {
+                used.str = true;
+                "AethStr".to_string()
+            }
+            IRType::Vec(_) | IRType::Map(..) => {
+                let handle = if matches!(ty, IRType::Vec(_)) { "AethVec" } else { "AethMap" };
+                if !used.handles.contains(&handle) {
+                    used.handles.push(handle);
+                }
+                format!("{}*", handle)
+            }
+            _ => self.ir_type_to_c(ty),
+        }
+    }
+
+    /// Declaration of `name` with type `ty` in an interface header
+    fn header_decl(&self, ty: &IRType, name: &str, used: &mut HeaderTypes) -> String {
+        match ty {
+            IRType::Array(elem, size) => format!("{} {}[{}]", self.header_type(elem, used), name, size),
+            IRType::Function { params, ret } => {
+                let params: Vec<String> = params.iter().map(|p| self.header_type(p, used)).collect();
+                format!("{} (*{})({})", self.header_type(ret, used), name, params.join(", "))
+            }
+            _ => format!("{} {}", self.header_type(ty, used), name),
+        }
+    }
+
+    /// C name of an AetherLang function
+    fn c_function_name<'a>(&self, name: &'a str) -> &'a str {
+        if (self.wrap_main || self.suppress_main) && name == "main" { "aether_main" } else { name }
+    }
+
+    /// `static ` for a function other units cannot call when building a
+    /// .c/.h pair; `pub`, exported and entry point functions stay external
+    fn linkage(&self, func: &IRFunction) -> &'static str {
+        let external = func.is_pub || func.extern_c || func.no_mangle || func.name == "main";
+        if self.static_internals && !external { "static " } else { "" }
+    }
+
+    /// Emit the `str` operations; callers bounds-check `slice`
+    fn emit_str_runtime(&mut self) {
+        self.writeln("/* str */");
+        self.writeln(&format!("static inline uint64_t {}(AethStr s) {{ return s.len; }}", STR_LEN));
+        self.writeln(&format!("static inline uint8_t* {}(AethStr s) {{ return (uint8_t*)s.data; }}", STR_AS_PTR));
+        self.writeln(&format!(
+            "static inline AethStr {}(const void* p) {{ AethStr s = {{ (char*)p, 0 }}; while (p && s.data[s.len]) s.len++; return s; }}",
+            STR_FROM_PTR
+        ));
+        self.writeln(&format!(
+            "static inline AethStr {}(AethStr s, uint64_t start, uint64_t end) {{ AethStr r = {{ s.data + start, end - start }}; return r; }}",
+            STR_SLICE
+        ));
+        self.writeln(&format!(
+            "static inline bool {}(AethStr a, AethStr b) {{ if (a.len != b.len) return false; for (size_t i = 0; i < a.len; i++) if (a.data[i] != b.data[i]) return false; return true; }}",
+            STR_EQ
+        ));
+        self.writeln("");
+    }
+
+    /// Emit the process entry point: record argc/argv for `args()` and
+    /// `arg_count()`, call the user's main and return its result as the exit status
+    fn emit_main_wrapper(&mut self, main: &IRFunction) {
+        let argv = match main.params.last() {
+            Some((_, IRType::Ptr(inner))) if **inner == IRType::Str => "aether_arg_strs",
+            _ => "(uint8_t**)argv",
+        };
+        let args = match main.params.len() {
+            2 => format!("(int32_t)argc, {}", argv),
+            1 => argv.to_string(),
+            _ => String::new(),
+        };
+        self.writeln("int main(int argc, char** argv) {");
+        self.writeln("    aether_argc = argc;");
+        self.writeln("    aether_arg_strs = malloc(sizeof(AethStr) * (size_t)(argc + 1));");
+        self.writeln(&format!("    for (int i = 0; i < argc; i++) aether_arg_strs[i] = {}(argv[i]);", STR_FROM_PTR));
+        if main.ret_type == IRType::Void {
+            self.writeln(&format!("    aether_main({});", args));
+            self.writeln("    return 0;");
+        } else {
+            self.writeln(&format!("    return (int)aether_main({});", args));
+        }
+        self.writeln("}");
+    }
+
+    /// Emit the block counters, their source locations, and a destructor that
+    /// dumps them when the program exits
+    fn emit_coverage_runtime(&mut self, coverage: &CoverageMap) {
+        let len = coverage.points.len().max(1);
+        self.writeln(&format!("static uint64_t {}[{}];", coverage::COUNTERS, len));
+        self.writeln(&format!(
+            "static const struct {{ const char* file; uint32_t line; uint32_t col; }} {}[{}] = {{",
+            coverage::MAP, len
+        ));
+        let file = Self::escape_for_c(&coverage.file);
+        for (line, col) in &coverage.points {
+            self.writeln(&format!("    {{\"{}\", {}, {}}},", file, line, col));
+        }
+        self.writeln("};");
+        self.writeln(&format!("static void {}(int64_t id) {{ {}[id]++; }}", coverage::HIT_FN, coverage::COUNTERS));
+        self.writeln(&format!("static void {}(const uint8_t* path) {{", coverage::DUMP_FN));
+        self.writeln("    FILE* f = fopen((const char*)path, \"w\");");
+        self.writeln("    if (!f) return;");
+        self.writeln(&format!("    fprintf(f, \"{}\\n\");", coverage::DATA_HEADER));
+        self.writeln(&format!("    for (size_t i = 0; i < {}; i++) {{", coverage.points.len()));
+        self.writeln(&format!(
+            "        fprintf(f, \"%u %u %llu\\n\", {map}[i].line, {map}[i].col, (unsigned long long){}[i]);",
+            coverage::COUNTERS, map = coverage::MAP
+        ));
+        self.writeln("    }");
+        self.writeln("    fclose(f);");
+        self.writeln("}");
+        self.writeln(&format!(
+            "__attribute__((destructor)) static void __aeth_cov_exit(void) {{ {}((const uint8_t*)\"{}\"); }}",
+            coverage::DUMP_FN, Self::escape_for_c(&coverage.data_path)
+        ));
+    }
+
+    /// Emit allocation wrappers that surround each block with poisoned guard zones
+    fn emit_asan_runtime(&mut self) {
+        self.writeln(&format!("#define AETHER_ASAN_GUARD {}", ASAN_GUARD_SIZE));
+        self.writeln("static inline void* aether_asan_malloc(size_t n) {");
+        self.writeln("    uint8_t* p = (uint8_t*)malloc(n + 2 * AETHER_ASAN_GUARD);");
+        self.writeln("    if (!p) return NULL;");
+        self.writeln("    __asan_poison_memory_region(p, AETHER_ASAN_GUARD);");
+        self.writeln("    __asan_poison_memory_region(p + AETHER_ASAN_GUARD + n, AETHER_ASAN_GUARD);");
+        self.writeln("    return p + AETHER_ASAN_GUARD;");
+        self.writeln("}");
+        self.writeln("static inline void aether_asan_free(void* p) {");
+        self.writeln("    if (p) free((uint8_t*)p - AETHER_ASAN_GUARD);");
+        self.writeln("}");
+    }
+
+    /// Leave out the C `main` wrapper, so the output can be linked into a
+    /// program with its own `main`
+    pub fn with_no_main(mut self, no_main: bool) -> Self {
+        self.suppress_main = no_main;
+        self
+    }
+
+    /// Generate code for `-ffreestanding`: no C library headers, runtime or
+    /// C `main` wrapper; the program starts at its `entry` function
+    pub fn with_freestanding(mut self, freestanding: bool, entry: &str) -> Self {
+        self.freestanding = freestanding;
+        self.entry = entry.to_string();
+        self
+    }
+
+    /// Give functions that are not `pub` internal linkage
+    pub fn with_static_internals(mut self, enabled: bool) -> Self {
+        self.static_internals = enabled;
+        self
+    }
+
+    /// Report panics as JSON lines on stderr
+    pub fn with_json_panics(mut self, json: bool) -> Self {
+        self.json_panics = json;
+        self
+    }
+
+    /// Generate function definitions on `jobs` worker threads
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Use a specific toolchain when compiling the generated C
+    pub fn with_toolchain(mut self, toolchain: Toolchain) -> Self {
+        self.toolchain = toolchain;
+        self
+    }
+
     /// Compile C source to object file using clang/gcc
     fn compile_c_to_object(&self, c_source: &str) -> Result<Vec<u8>> {
         // Write C source to temp file
@@ -1085,12 +2350,13 @@ impl CCodeGen {
         
         fs::write(&c_file, c_source).map_err(|e| Error::Io(e.to_string()))?;
         
-        // Try clang first, then gcc
-        let compilers = ["clang", "gcc", "cc"];
+        // Try clang first, then gcc (or the cross-compiler for foreign targets)
         let mut last_error = String::new();
         
-        for compiler in &compilers {
+        for compiler in &self.toolchain.compilers() {
             let result = Command::new(compiler)
+                .args(self.toolchain.compiler_args(compiler))
+                .args(self.sanitizers.compiler_args())
                 .args(&["-c", "-o"])
                 .arg(&obj_file)
                 .arg(&c_file)
@@ -1123,6 +2389,13 @@ impl CCodeGen {
         Err(Error::CodeGen(format!("Failed to compile C code: {}", last_error)))
     }
 
+    /// Simplify the patterns the generator leaves in `c_source`: identities
+    /// like `x + 0`, stores that are overwritten or only returned, and
+    /// branches on a constant
+    pub fn peephole_optimize(c_source: &str) -> String {
+        peephole::optimize(c_source)
+    }
+
     /// Get the generated C source (for debugging)
     pub fn get_c_source(&self) -> &str {
         &self.output
@@ -1161,7 +2434,7 @@ mod tests {
 
     fn generate_c(source: &str) -> String {
         let ir_module = compile_to_ir(source);
-        let mut codegen = CCodeGen::new("x86_64-pc-windows-msvc");
+        let mut codegen = CCodeGen::new("x86_64-pc-windows-msvc", SanitizerFlags::NONE);
         codegen.generate_source(&ir_module).unwrap()
     }
 
@@ -1169,10 +2442,129 @@ mod tests {
     fn test_empty_function() {
         let c = generate_c("fn main() {}");
         println!("{}", c);
-        assert!(c.contains("void main(void)"));
+        assert!(c.contains("void aether_main(void)"));
         assert!(c.contains("return;"));
     }
 
+    #[test]
+    fn test_extern_c_export() {
+        let source = "struct Point { x: i64, y: i64 }\n#[extern_c]\npub fn point_x(p: *Point) -> i64 { return p.x }\npub fn helper() -> i64 { return 1 }";
+        let ir_module = compile_to_ir(source);
+        let mut codegen = CCodeGen::new("x86_64-unknown-linux-gnu", SanitizerFlags::NONE);
+
+        // Exports keep their source name; other pub functions get the module prefix
+        let c = codegen.generate_source(&ir_module).unwrap();
+        assert!(c.contains("__attribute__((visibility(\"default\")))\n#endif\nint64_t point_x(struct Point* _arg0) {"));
+        assert!(c.contains("int64_t test__helper(void)"));
+
+        let header = codegen.generate_header(&ir_module);
+        assert!(header.contains("#ifndef AETHER_TEST_H"));
+        assert!(header.contains("struct Point;\n"));
+        assert!(header.contains("int64_t point_x(struct Point* p);"));
+        assert!(!header.contains("helper"));
+    }
+
+    #[test]
+    fn test_interface_header() {
+        let source = "extern \"C\" { fn host_log(msg: *u8, len: u64) -> i32; fn puts(s: *u8) -> i32; }\n\
+            pub struct Span { pub start: u64, pub name: str }\n\
+            struct Hidden { v: i64 }\n\
+            fn helper(x: i64) -> i64 { return x }\n\
+            pub fn width(s: *Span) -> u64 { return s.start }\n\
+            fn main() {}";
+        let ir_module = compile_to_ir(source);
+        let mut codegen = CCodeGen::new("x86_64-unknown-linux-gnu", SanitizerFlags::NONE)
+            .with_static_internals(true)
+            .with_no_main(true);
+
+        let c = codegen.generate_source(&ir_module).unwrap();
+        assert!(c.contains("static int64_t helper(int64_t _arg0) {"), "{}", c);
+        assert!(c.contains("\nuint64_t test__width(struct Span* _arg0) {"), "{}", c);
+        assert!(c.contains("\nvoid aether_main(void) {") && !c.contains("int main("), "{}", c);
+
+        let header = codegen.generate_interface_header(&ir_module);
+        assert!(header.contains("typedef uint64_t aeth_u64;\n"), "{}", header);
+        assert!(header.contains("#define AETHER_STR_DEFINED\ntypedef struct AethStr"), "{}", header);
+        assert!(header.contains("struct Span {\n    aeth_u64 start;\n    AethStr name;\n};"), "{}", header);
+        assert!(header.contains("/* test::width */\naeth_u64 test__width(struct Span* s);"), "{}", header);
+        assert!(header.contains("void aether_main(void);"), "{}", header);
+        // Foreign functions without a standard header are declared; private items are not
+        assert!(header.contains("aeth_i32 host_log(aeth_u8* msg, aeth_u64 len);"), "{}", header);
+        assert!(!header.contains("puts") && !header.contains("Hidden") && !header.contains("helper"), "{}", header);
+    }
+
+    #[test]
+    fn test_optimization_hints() {
+        let c = generate_c("#[inline]\nfn square(x: i64) -> i64 { return x * x }\n\
+            #[inline(never)]\nfn cube(x: i64) -> i64 { return x * x * x }\n\
+            #[cold]\nfn fail(code: i64) -> i64 { return code }\n\
+            #[hot]\nfn step(x: i64) -> i64 { if x > 100 { fail(x); } return square(x) + cube(x) }");
+        assert!(c.contains("\ninline int64_t square(int64_t _arg0) {"), "{}", c);
+        assert!(c.contains("__attribute__((noinline))\n#endif\nint64_t cube("), "{}", c);
+        assert!(c.contains("__attribute__((cold))\n#endif\nint64_t fail("), "{}", c);
+        assert!(c.contains("__attribute__((hot))\n#endif\nint64_t step("), "{}", c);
+        // The branch towards the call to the cold function is unlikely
+        assert!(c.contains("if (__builtin_expect(!!("), "{}", c);
+        assert!(c.contains("), 0)) {"), "{}", c);
+    }
+
+    #[test]
+    fn test_registers_and_unread_results() {
+        let c = generate_c("extern \"C\" { fn ext(p: *u8) -> i64; }\nfn f(a: i64, unused: i64) -> i64 { let dead: i64 = a * 3\n ext(\"x\" as *u8)\n return a + 1 }");
+        // Temporaries are named after their IR registers and declared in register order
+        assert!(c.contains("int64_t _r"), "{}", c);
+        assert!(!c.contains("_t0"), "{}", c);
+        // Nothing reads `dead`, so neither it nor the multiplication is emitted
+        assert!(!c.contains("* 3LL"), "{}", c);
+        assert!(c.contains("(void)_arg1;"), "{}", c);
+        // An extern the system headers do not declare gets a prototype
+        assert!(c.contains("extern int64_t ext(uint8_t*);"), "{}", c);
+        assert!(c.contains("ext((void*)"), "{}", c);
+    }
+
+    #[test]
+    fn test_main_wrapper() {
+        // Unit main exits with 0
+        let c = generate_c("fn main() {}");
+        assert!(c.contains("int main(int argc, char** argv) {"));
+        assert!(c.contains("    aether_main();\n    return 0;"));
+
+        // Integer results become the exit status, arguments are forwarded
+        let c = generate_c("fn main(args: [str]) -> i32 { return arg_count() }");
+        assert!(c.contains("int32_t aether_main(AethStr* _arg0)"));
+        assert!(c.contains("aether_arg_count()"));
+        assert!(c.contains("return (int)aether_main(aether_arg_strs);"));
+
+        let c = generate_c("fn main(argc: i32, argv: **u8) -> i32 { return argc }");
+        assert!(c.contains("return (int)aether_main((int32_t)argc, (uint8_t**)argv);"));
+
+        // no_main programs provide their own entry point
+        let c = generate_c("#![no_main]\nfn main() {}");
+        assert!(!c.contains("aether_main"));
+    }
+
+    #[test]
+    fn test_freestanding() {
+        let freestanding = |source: &str, entry: &str| {
+            CCodeGen::new("x86_64-unknown-linux-gnu", SanitizerFlags::NONE)
+                .with_freestanding(true, entry)
+                .generate_source(&compile_to_ir(source))
+        };
+        let c = freestanding("extern \"C\" { fn exit(code: i32); }\nfn _start() { exit(1) }", DEFAULT_ENTRY).unwrap();
+        assert!(!c.contains("#include <stdio.h>") && !c.contains("aether_print"), "{}", c);
+        assert!(!c.contains("int main("), "{}", c);
+        assert!(c.contains("__attribute__((force_align_arg_pointer))\nvoid _start(void) {"), "{}", c);
+        // No header declares the C library functions an extern names
+        assert!(c.contains("extern void exit(int32_t);"), "{}", c);
+
+        let err = freestanding("fn main() {}", DEFAULT_ENTRY).unwrap_err();
+        assert!(err.to_string().contains("starts at `_start`"), "{}", err);
+        assert!(freestanding("fn main() {}", "main").is_ok());
+
+        let err = freestanding("enum E { A(i64), B }\nfn _start() { let e: E = E::A(1) }", DEFAULT_ENTRY).unwrap_err();
+        assert!(err.to_string().contains("enum variant `E::A` is heap-allocated"), "{}", err);
+    }
+
     #[test]
     fn test_return_constant() {
         let c = generate_c("fn answer() -> i64 { return 42 }");
@@ -1194,4 +2586,153 @@ mod tests {
         assert!(c.contains("if"));
         assert!(c.contains("goto"));
     }
+
+    #[test]
+    fn test_struct_layout_assertions() {
+        let c = generate_c("struct Pair { a: u8, b: u64 }\nfn main() {}");
+        println!("{}", c);
+        assert!(c.contains("_Static_assert(sizeof(struct Pair) == 16"));
+        assert!(c.contains("_Static_assert(offsetof(struct Pair, b) == 8"));
+    }
+
+    #[test]
+    fn test_static_globals() {
+        let c = generate_c("static mut COUNTER: i64 = 7\nfn bump() { COUNTER = COUNTER + 1 }");
+        println!("{}", c);
+        assert!(c.contains("int64_t COUNTER = 7LL;"));
+        assert!(c.contains("*(&COUNTER) ="));
+    }
+
+    #[test]
+    fn test_address_sanitizer_wraps_alloc() {
+        let ir_module = compile_to_ir("fn main() { let p: *u8 = alloc(16)\n free(p) }");
+        let mut codegen = CCodeGen::new("x86_64-unknown-linux-gnu", SanitizerFlags::ADDRESS);
+        let c = codegen.generate_source(&ir_module).unwrap();
+        println!("{}", c);
+        assert!(c.contains("#include <sanitizer/asan_interface.h>"));
+        assert!(c.contains("__asan_poison_memory_region"));
+        assert!(c.contains("aether_asan_malloc(16LL)"));
+        assert!(c.contains("aether_asan_free("));
+    }
+
+    #[test]
+    fn test_union_definition() {
+        let c = generate_c("union Bits { as_float: f32, as_int: u32 }\nfn bits(b: *Bits) -> u32 { unsafe { return b.as_int } }");
+        println!("{}", c);
+        assert!(c.contains("union Bits {"));
+        assert!(c.contains("_Static_assert(sizeof(union Bits) == 4"));
+        assert!(c.contains("->as_int;"));
+    }
+
+    #[test]
+    fn test_volatile_pointer_access() {
+        let c = generate_c("fn poke(reg: *volatile u32, v: u32) -> u32 { unsafe { *reg = v\n return *reg } }");
+        println!("{}", c);
+        assert!(c.contains("*(volatile typeof(*_r0)*)(_r0) = _r1;"));
+        assert!(c.contains("return *(volatile typeof(*_r0)*)(_r0);"));
+    }
+
+    #[test]
+    fn test_inline_asm_constraints() {
+        let c = generate_c("fn f(n: i64) { let mut r: i64 = 0\n unsafe { asm!(\"syscall\", inout(rax) r, in(r10) n, out(reg) r, clobber(\"rcx\")) } }");
+        println!("{}", c);
+        assert!(c.contains("register int64_t __asm_op1 __asm__(\"r10\") = "));
+        assert!(c.contains(": \"+a\" ("));
+        assert!(c.contains(", \"=r\" ("));
+        assert!(c.contains(": \"r\" (__asm_op1) : \"rcx\");"));
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    fn test_inline_asm_syscall_exit_code() {
+        let source = "fn main() -> i32 { let code: i64 = 42\n unsafe { asm!(\"syscall\", in(rax) 60, in(rdi) code, clobber(\"rcx\"), clobber(\"r11\"), clobber(\"memory\")) }\n return 0 }";
+        let ir_module = compile_to_ir(source);
+        let mut codegen = CCodeGen::new("x86_64-unknown-linux-gnu", SanitizerFlags::NONE);
+        let c = codegen.generate_source(&ir_module).unwrap();
+
+        let dir = std::env::temp_dir();
+        let c_path = dir.join(format!("aether_asm_test_{}.c", std::process::id()));
+        let exe_path = dir.join(format!("aether_asm_test_{}", std::process::id()));
+        fs::write(&c_path, c).unwrap();
+        let compiled = ["cc", "gcc", "clang"].iter().any(|cc| {
+            Command::new(cc).arg("-o").arg(&exe_path).arg(&c_path)
+                .status().map(|s| s.success()).unwrap_or(false)
+        });
+        let _ = fs::remove_file(&c_path);
+        if !compiled {
+            eprintln!("skipping: no C compiler available");
+            return;
+        }
+        let status = Command::new(&exe_path).status().unwrap();
+        let _ = fs::remove_file(&exe_path);
+        assert_eq!(status.code(), Some(42));
+    }
+
+    #[test]
+    fn test_enum_match_switch() {
+        let variants: Vec<String> = (0..50).map(|i| format!("V{}", i)).collect();
+        let arms: Vec<String> = variants.iter().enumerate()
+            .map(|(i, v)| format!("        Wide::{} => {},", v, i))
+            .collect();
+        let source = format!(
+            "enum Wide {{ {} }}\nfn pick(w: Wide) -> i64 {{\n    match w {{\n{}\n    }}\n}}\nfn main() {{}}",
+            variants.join(", "),
+            arms.join("\n"),
+        );
+        let c = generate_c(&source);
+        // One dispatch on the loaded tag rather than a chain of 50 comparisons
+        assert_eq!(c.matches("switch (").count(), 1);
+        assert!(c.contains("(int32_t*)"));
+        assert!(c.contains("case 49LL:"));
+    }
+
+    #[test]
+    fn test_switch_without_wildcard_panics() {
+        // Analysis rejects this match, but an enum's tag cast from an integer
+        // may be any value, and nothing past the last case may be undefined
+        let c = generate_c("fn pick(x: i64) -> i64 {\n    match x {\n        1 => 10,\n        2 => 20,\n    }\n}");
+        assert!(c.contains("default: {\n        goto L_match_unmatched_"), "{}", c);
+        assert!(c.contains("__aeth_panic(\"no match arm matched\""), "{}", c);
+    }
+
+    #[test]
+    fn test_line_directives() {
+        let source = "fn half(n: i64) -> i64 {\n    let h: i64 = n / 2;\n    return h;\n}";
+        let program = Parser::new(Lexer::new(source, 0)).parse_program().unwrap();
+        let ir_module = IRGenerator::new("test").with_debug_info("src/half.aeth", source).generate(&program).unwrap();
+        let c = CCodeGen::new("x86_64-unknown-linux-gnu", SanitizerFlags::NONE).generate_source(&ir_module).unwrap();
+        assert!(c.contains("#line 1 \"src/half.aeth\"\nint64_t half(int64_t _arg0) {"), "{}", c);
+        let body = &c[c.find("#line 2 \"src/half.aeth\"").unwrap()..];
+        assert!(body.find("/ 2LL").unwrap() < body.find("#line 3 \"src/half.aeth\"").unwrap());
+    }
+    #[test]
+    fn test_str_fat_pointer() {
+        let c = generate_c("fn first(s: str) -> str { return s.slice(0, 1) }\nfn main() -> i32 effect[io] { println(\"hi\\n\"); return first(\"ab\").len() as i32 }");
+        assert!(c.contains("typedef struct AethStr { char* data; size_t len; } AethStr;"));
+        assert!(c.contains("AethStr first(AethStr _arg0)"));
+        // The length is known at compile time, so embedded escapes do not need strlen
+        assert!(c.contains("aether_println(((AethStr){ (char*)\"hi\\n\", 3 }));"), "{}", c);
+        assert!(c.contains("return __aeth_str_slice(_r0, "));
+        assert!(c.contains("= __aeth_str_len("));
+    }
+
+    #[test]
+    fn test_simd_intrinsics_per_target() {
+        let source = "fn f(a: f32x8, b: f64x4, c: i64x2) -> f32 { let d: f64x4 = __simd_f64x4_mul(b, b); let e: i64x2 = __simd_i64x2_add(c, c); return __simd_f32x8_sum(__simd_f32x8_add(a, a)) }";
+        let ir_module = compile_to_ir(source);
+        let x86 = CCodeGen::new("x86_64-unknown-linux-gnu", SanitizerFlags::NONE).generate_source(&ir_module).unwrap();
+        assert!(x86.contains("#include <immintrin.h>"));
+        assert!(x86.contains("#pragma GCC target(\"avx2\")"));
+        assert!(x86.contains("= _mm256_add_ps("), "{}", x86);
+        // `d` and `e` are never read, so their calls are kept as bare statements
+        assert!(x86.contains("    _mm256_mul_pd("));
+        assert!(x86.contains("    _mm_add_epi64("));
+
+        let arm = CCodeGen::new("aarch64-unknown-linux-gnu", SanitizerFlags::NONE).generate_source(&ir_module).unwrap();
+        assert!(arm.contains("#include <arm_neon.h>"));
+        assert!(arm.contains("    vaddq_s64("), "{}", arm);
+        // NEON has no 256-bit registers, so wide vectors use the vector extension
+        assert!(arm.contains("float __attribute__((vector_size(32))) _arg0"));
+        assert!(!arm.contains("_mm"));
+    }
 }
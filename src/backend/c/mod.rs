@@ -4,5 +4,6 @@
 //! It's a fallback for when LLVM is not available.
 
 mod c_codegen;
+mod peephole;
 
-pub use c_codegen::CCodeGen;
+pub use c_codegen::{CCodeGen, DEFAULT_ENTRY};
@@ -0,0 +1,538 @@
+//! Peephole simplification of the generated C
+//!
+//! Works on the tokens of single lines of each function definition, so the
+//! rewrites only touch code the generator wrote in its own shapes:
+//! arithmetic identities on integer temporaries (`x + 0`, `x * 1`, `x * 0`,
+//! `x - x`, `x / 1`), an assignment overwritten by the next line, a temporary
+//! stored only to be returned, and `if (1)` / `if (0)` branches. Temporaries
+//! and labels left unused afterwards are removed, as `-Wall` would warn
+//! about them.
+
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Ident,
+    Number,
+    Literal,
+    Punct,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    kind: Kind,
+    text: &'a str,
+    start: usize,
+    end: usize,
+}
+
+const PUNCTS: &[&str] = &[
+    "<<=", ">>=", "...", "->", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||",
+    "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=",
+];
+
+/// Tokens of one line of C; comments end the line
+fn tokenize(line: &str) -> Vec<Token<'_>> {
+    let bytes = line.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+        let kind = if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        } else if c == b'/' && bytes.get(i + 1) == Some(&b'/') {
+            break;
+        } else if c == b'/' && bytes.get(i + 1) == Some(&b'*') {
+            match line[i + 2..].find("*/") {
+                Some(end) => i += end + 4,
+                None => break,
+            }
+            continue;
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            Kind::Ident
+        } else if c.is_ascii_digit() {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_' || bytes[i] == b'.') {
+                i += 1;
+            }
+            Kind::Number
+        } else if c == b'"' || c == b'\'' {
+            i += 1;
+            while i < bytes.len() && bytes[i] != c {
+                i += if bytes[i] == b'\\' { 2 } else { 1 };
+            }
+            i = (i + 1).min(bytes.len());
+            Kind::Literal
+        } else if c.is_ascii() {
+            let rest = &bytes[i..];
+            i += PUNCTS.iter().find(|p| rest.starts_with(p.as_bytes())).map_or(1, |p| p.len());
+            Kind::Punct
+        } else {
+            i += line[i..].chars().next().map_or(1, char::len_utf8);
+            Kind::Punct
+        };
+        tokens.push(Token { kind, text: &line[start..i], start, end: i });
+    }
+    tokens
+}
+
+/// Tokens of each line of `body`, for the passes that look at every line
+fn tokenize_all(body: &[String]) -> Vec<Vec<Token<'_>>> {
+    body.iter().map(|line| tokenize(line)).collect()
+}
+
+/// Registers of the generated code: `_r0`, `_r1`, ..
+fn is_temp(name: &str) -> bool {
+    name.strip_prefix("_r").is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Integer types at least as wide as `int`, on which the identities keep the
+/// type of the expression
+fn is_wide_integer(ty: &str) -> bool {
+    matches!(ty, "int32_t" | "uint32_t" | "int64_t" | "uint64_t" | "size_t" | "intptr_t" | "uintptr_t" | "ptrdiff_t")
+}
+
+/// A zero of type `ty`
+fn zero(ty: &str) -> &'static str {
+    match ty {
+        "int64_t" | "intptr_t" | "ptrdiff_t" => "0LL",
+        "uint64_t" | "uintptr_t" | "size_t" => "0ULL",
+        "uint32_t" => "0U",
+        _ => "0",
+    }
+}
+
+/// The value of an integer literal such as `1` or `0LL`
+fn literal_value(text: &str) -> Option<u64> {
+    text.trim_end_matches(['u', 'U', 'l', 'L']).parse().ok()
+}
+
+/// `name = value;`: the name and the byte range of the value
+fn assignment<'a>(tokens: &[Token<'a>]) -> Option<(&'a str, usize, usize)> {
+    match tokens {
+        [name, eq, value @ .., semi] if name.kind == Kind::Ident && eq.text == "=" && semi.text == ";" && !value.is_empty() => {
+            Some((name.text, value[0].start, value[value.len() - 1].end))
+        }
+        _ => None,
+    }
+}
+
+/// `return name;`: the name
+fn returned<'a>(tokens: &[Token<'a>]) -> Option<&'a str> {
+    match tokens {
+        [ret, name, semi] if ret.text == "return" && name.kind == Kind::Ident && semi.text == ";" => Some(name.text),
+        _ => None,
+    }
+}
+
+/// `type name;`: the name and the type
+fn declaration<'a>(line: &'a str, tokens: &[Token<'a>]) -> Option<(&'a str, &'a str)> {
+    let [ty @ .., name, semi] = tokens else { return None };
+    let simple = |t: &Token| t.kind == Kind::Ident || t.text == "*";
+    let statement = |t: &Token| matches!(t.text, "return" | "goto" | "case" | "else" | "do" | "sizeof");
+    (semi.text == ";" && name.kind == Kind::Ident && !ty.is_empty() && ty.iter().all(simple)
+        && ty[0].kind == Kind::Ident && !statement(&ty[0]))
+        .then(|| (name.text, line[ty[0].start..ty[ty.len() - 1].end].trim()))
+}
+
+/// Whether evaluating `tokens` has no effect and cannot trap: no calls,
+/// memory reads, divisions or volatile accesses
+fn is_pure(tokens: &[Token]) -> bool {
+    tokens.iter().enumerate().all(|(i, t)| {
+        let after_operand = i > 0 && (matches!(tokens[i - 1].kind, Kind::Ident | Kind::Number) || tokens[i - 1].text == ")");
+        match t.kind {
+            Kind::Ident => t.text != "volatile" && tokens.get(i + 1).is_none_or(|next| next.text != "("),
+            Kind::Number | Kind::Literal => true,
+            Kind::Punct => match t.text {
+                "*" => after_operand,
+                "+" | "-" | "&" | "|" | "^" | "~" | "!" | "==" | "!=" | "<" | ">" | "<=" | ">=" | "&&" | "||"
+                | "?" | ":" | "(" | ")" | "." => true,
+                _ => false,
+            },
+        }
+    })
+}
+
+/// Whether a statement declares a variable, and so must stay in its block
+fn is_declaration(tokens: &[Token]) -> bool {
+    tokens.first().is_some_and(|t| {
+        t.text.ends_with("_t") || matches!(t.text,
+            "struct" | "union" | "enum" | "const" | "static" | "volatile" | "unsigned" | "signed"
+            | "char" | "short" | "int" | "long" | "float" | "double" | "void" | "bool" | "_Bool")
+    })
+}
+
+fn indent_of(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// Whether `line` opens a function definition
+fn is_signature(line: &str) -> bool {
+    !line.starts_with(char::is_whitespace) && line.ends_with(") {") && !line.contains('=')
+}
+
+/// A function definition: its return type and the types of its parameters
+/// and declared variables
+struct Function {
+    ret: String,
+    vars: HashMap<String, String>,
+}
+
+impl Function {
+    /// The definition opened by `signature`, e.g. `int64_t f(int64_t _arg0) {`
+    fn parse(signature: &str, body: &[String]) -> Option<Self> {
+        if !is_signature(signature) {
+            return None;
+        }
+        let open = signature.find('(')?;
+        let head = tokenize(&signature[..open]);
+        let [ret @ .., _name] = head.as_slice() else { return None };
+        let ret = ret.iter().filter(|t| !matches!(t.text, "static" | "inline")).map(|t| t.text).collect::<Vec<_>>();
+        let mut vars = HashMap::new();
+        let params = &signature[open + 1..signature.len() - 3];
+        for param in params.split(',') {
+            let tokens = tokenize(param);
+            let declared = tokens.iter().copied().chain(std::iter::once(Token { kind: Kind::Punct, text: ";", start: param.len(), end: param.len() }));
+            let tokens: Vec<_> = declared.collect();
+            if let Some((name, ty)) = declaration(param, &tokens) {
+                vars.insert(name.to_string(), ty.to_string());
+            }
+        }
+        for line in body {
+            if let Some((name, ty)) = declaration(line, &tokenize(line)) {
+                vars.insert(name.to_string(), ty.to_string());
+            }
+        }
+        Some(Self { ret: ret.join(" ").replace(" *", "*"), vars })
+    }
+
+    fn integer_type(&self, name: &str) -> Option<&str> {
+        self.vars.get(name).map(String::as_str).filter(|ty| is_wide_integer(ty))
+    }
+
+    /// `line` with one arithmetic identity simplified, if it has one
+    fn simplify_identity(&self, line: &str) -> Option<String> {
+        let tokens = tokenize(line);
+        for i in 1..tokens.len().saturating_sub(3) {
+            let (before, a, op, b, after) = (tokens[i - 1], tokens[i], tokens[i + 1], tokens[i + 2], tokens[i + 3]);
+            let Some(ty) = (a.kind == Kind::Ident).then(|| self.integer_type(a.text)).flatten() else { continue };
+            let literal = (b.kind == Kind::Number).then(|| literal_value(b.text)).flatten();
+            let result = match (op.text, literal) {
+                ("+", Some(0)) | ("*", Some(1)) | ("/", Some(1)) => a.text,
+                ("*", Some(0)) => b.text,
+                ("-", _) if b.text == a.text => zero(ty),
+                _ => continue,
+            };
+            let grouped = before.text == "(" && after.text == ")";
+            let statement = (before.text == "=" || before.text == "return") && after.text == ";";
+            if !grouped && !statement {
+                continue;
+            }
+            // `f(x + 0)` keeps the call's parentheses
+            let call = i >= 2 && (matches!(tokens[i - 2].kind, Kind::Ident | Kind::Number) || matches!(tokens[i - 2].text, ")" | "]"));
+            let (start, end) = if grouped && !call { (before.start, after.end) } else { (a.start, b.end) };
+            return Some(format!("{}{}{}", &line[..start], result, &line[end..]));
+        }
+        None
+    }
+
+    fn optimize(&self, mut body: Vec<String>) -> Vec<String> {
+        for line in &mut body {
+            while let Some(simplified) = self.simplify_identity(line) {
+                *line = simplified;
+            }
+        }
+        self.remove_overwritten(&mut body);
+        self.fold_returns(&mut body);
+        fold_constant_branches(&mut body);
+        self.remove_unused_temps(&mut body);
+        remove_unused_labels(&mut body);
+        body
+    }
+
+    /// `a = b; a = c;` becomes `a = c;` when `b` has no effect and `c` does
+    /// not read `a`
+    fn remove_overwritten(&self, body: &mut Vec<String>) {
+        let tokens = tokenize_all(body);
+        let overwritten: Vec<bool> = (0..body.len()).map(|i| {
+            let (first, Some(second)) = (&tokens[i], tokens.get(i + 1)) else { return false };
+            match (assignment(first), assignment(second)) {
+                (Some((a, ..)), Some((b, ..))) if a == b => {
+                    self.vars.get(a).is_some_and(|ty| !ty.contains("volatile"))
+                        && is_pure(&first[2..first.len() - 1])
+                        && !second[1..].iter().any(|t| t.text == a)
+                }
+                _ => false,
+            }
+        }).collect();
+        drop(tokens);
+        let mut i = 0;
+        body.retain(|_| {
+            i += 1;
+            !overwritten[i - 1]
+        });
+    }
+
+    /// `tmp = expr; return tmp;` becomes `return expr;` when the temporary
+    /// has the function's return type, so the conversion is unchanged
+    fn fold_returns(&self, body: &mut Vec<String>) {
+        let tokens = tokenize_all(body);
+        let mut folded = Vec::with_capacity(body.len());
+        let mut i = 0;
+        while i < body.len() {
+            let fold = match (assignment(&tokens[i]), tokens.get(i + 1).and_then(|next| returned(next))) {
+                (Some((tmp, start, end)), Some(ret)) if tmp == ret && is_temp(tmp)
+                    && self.vars.get(tmp).is_some_and(|ty| ty.replace(" *", "*") == self.ret) => {
+                    Some(format!("{}return {};", indent_of(&body[i]), &body[i][start..end]))
+                }
+                _ => None,
+            };
+            match fold {
+                Some(fold) => {
+                    folded.push(fold);
+                    i += 2;
+                }
+                None => {
+                    folded.push(body[i].clone());
+                    i += 1;
+                }
+            }
+        }
+        drop(tokens);
+        *body = folded;
+    }
+
+    /// Drop the declarations of temporaries that are never read, and the
+    /// stores to them
+    fn remove_unused_temps(&self, body: &mut Vec<String>) {
+        let tokens = tokenize_all(body);
+        let mut reads: HashMap<&str, usize> = HashMap::new();
+        // Pure stores to each temporary, with the temporaries they read
+        let mut stores: HashMap<&str, Vec<Vec<&str>>> = HashMap::new();
+        for (line, tokens) in body.iter().zip(&tokens) {
+            // A declaration reads nothing, and a store not its target
+            let skip = match (declaration(line, tokens), assignment(tokens)) {
+                (Some(_), _) => tokens.len(),
+                (None, Some(_)) => 1,
+                (None, None) => 0,
+            };
+            let read = || tokens[skip..].iter().filter(|token| token.kind == Kind::Ident && is_temp(token.text)).map(|token| token.text);
+            for name in read() {
+                *reads.entry(name).or_default() += 1;
+            }
+            if let Some((name, ..)) = assignment(tokens).filter(|_| is_pure(&tokens[2..tokens.len() - 1])) {
+                stores.entry(name).or_default().push(read().collect());
+            }
+        }
+
+        // Dropping the pure stores to an unused temporary drops their reads,
+        // which can leave other temporaries unused
+        let mut unused: HashSet<&str> = HashSet::new();
+        let mut pending: Vec<&str> = self.vars.keys()
+            .map(String::as_str)
+            .filter(|name| is_temp(name) && !reads.contains_key(name))
+            .collect();
+        while let Some(name) = pending.pop() {
+            if !unused.insert(name) {
+                continue;
+            }
+            for read in stores.get(name).into_iter().flatten() {
+                for read in read {
+                    let count = reads.get_mut(read).expect("counted above");
+                    *count -= 1;
+                    if *count == 0 {
+                        pending.push(read);
+                    }
+                }
+            }
+        }
+        if unused.is_empty() {
+            return;
+        }
+
+        let mut kept = Vec::with_capacity(body.len());
+        for (line, tokens) in body.iter().zip(&tokens) {
+            if declaration(line, tokens).is_some_and(|(name, _)| unused.contains(name)) {
+                continue;
+            }
+            match assignment(tokens) {
+                Some((name, start, end)) if unused.contains(name) => {
+                    if !is_pure(&tokens[2..tokens.len() - 1]) {
+                        kept.push(format!("{}(void)({});", indent_of(line), &line[start..end]));
+                    }
+                }
+                _ => kept.push(line.clone()),
+            }
+        }
+        drop(tokens);
+        *body = kept;
+    }
+}
+
+/// `if (1) { A } else { B }` becomes `A`, and `if (0)` the else branch
+fn fold_constant_branches(body: &mut Vec<String>) {
+    let mut i = 0;
+    while i < body.len() {
+        let taken = match body[i].trim() {
+            "if (1) {" => true,
+            "if (0) {" => false,
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        let Some(then_end) = block_end(body, i + 1) else { return };
+        let else_end = match body[then_end].trim() {
+            "}" => None,
+            "} else {" => match block_end(body, then_end + 1) {
+                Some(end) if body[end].trim() == "}" => Some(end),
+                _ => return,
+            },
+            _ => return,
+        };
+        let kept: Vec<String> = match (taken, else_end) {
+            (true, _) => body[i + 1..then_end].to_vec(),
+            (false, Some(end)) => body[then_end + 1..end].to_vec(),
+            (false, None) => Vec::new(),
+        };
+        let indent = indent_of(&body[i]).to_string();
+        let scoped = kept.iter().any(|line| is_declaration(&tokenize(line)));
+        let mut replacement: Vec<String> = kept.into_iter()
+            .map(|line| if scoped { line } else { line.strip_prefix("    ").map_or(line.clone(), str::to_string) })
+            .collect();
+        if scoped {
+            replacement.insert(0, format!("{}{{", indent));
+            replacement.push(format!("{}}}", indent));
+        }
+        body.splice(i..=else_end.unwrap_or(then_end), replacement);
+    }
+}
+
+/// Line of the `}` closing the block whose body starts at `start`
+fn block_end(body: &[String], start: usize) -> Option<usize> {
+    let mut depth = 1usize;
+    for (offset, line) in body[start..].iter().enumerate() {
+        for token in tokenize(line) {
+            match token.text {
+                "{" => depth += 1,
+                "}" => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(start + offset);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    None
+}
+
+/// Drop labels no `goto` names
+fn remove_unused_labels(body: &mut Vec<String>) {
+    let tokens = tokenize_all(body);
+    let mut targets: HashSet<&str> = HashSet::new();
+    for pair in tokens.iter().flat_map(|line| line.windows(2)) {
+        if pair[0].text == "goto" || pair[0].text == "&&" {
+            targets.insert(pair[1].text);
+        }
+    }
+    let unused: Vec<bool> = body.iter().zip(&tokens).map(|(line, tokens)| match tokens.as_slice() {
+        [label, colon] if colon.text == ":" && label.kind == Kind::Ident && label.text != "default"
+            && !line.starts_with(char::is_whitespace) => !targets.contains(label.text),
+        _ => false,
+    }).collect();
+    drop(tokens);
+    let mut i = 0;
+    body.retain(|_| {
+        i += 1;
+        !unused[i - 1]
+    });
+}
+
+/// Simplify every function definition in `c_source`
+pub fn optimize(c_source: &str) -> String {
+    let lines: Vec<&str> = c_source.lines().collect();
+    let mut out = String::with_capacity(c_source.len());
+    let mut i = 0;
+    while i < lines.len() {
+        out.push_str(lines[i]);
+        out.push('\n');
+        let end = is_signature(lines[i]).then(|| lines[i + 1..].iter().position(|line| *line == "}")).flatten();
+        if let Some(end) = end.map(|n| i + 1 + n) {
+            let body: Vec<String> = lines[i + 1..end].iter().map(|line| line.to_string()).collect();
+            if let Some(function) = Function::parse(lines[i], &body) {
+                for line in function.optimize(body) {
+                    out.push_str(&line);
+                    out.push('\n');
+                }
+                i = end;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    if !c_source.ends_with('\n') {
+        out.pop();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(body: &str) -> String {
+        let source = format!("int64_t f(int64_t _arg0, double _arg1) {{\n{}}}\n", body);
+        let out = optimize(&source);
+        out.strip_prefix("int64_t f(int64_t _arg0, double _arg1) {\n").unwrap().strip_suffix("}\n").unwrap().to_string()
+    }
+
+    #[test]
+    fn test_identities() {
+        let body = "    int64_t _r1;\n    int64_t _r2;\n    _r1 = _arg0 + 0LL;\n    _r2 = (_r1 * 1LL) + (_r1 - _r1);\n    _r2 = g(_r2 * 0LL);\n    return _r2;\n";
+        assert_eq!(function(body), "    return g(0LL);\n");
+
+        // Floating point keeps its identities: `x * 0` is not 0 for NaN
+        let body = "    _arg1 = _arg1 * 0;\n    _arg0 = _arg0 / 1;\n    return _arg0;\n";
+        assert_eq!(function(body), "    _arg1 = _arg1 * 0;\n    _arg0 = _arg0;\n    return _arg0;\n");
+    }
+
+    #[test]
+    fn test_stores() {
+        // The first store is dead; the one that calls a function is not
+        let body = "    int64_t _r1;\n    _r1 = _arg0;\n    _r1 = 2LL;\n    _arg0 = g();\n    _arg0 = 1LL;\n    _arg0 = _arg0 + _r1;\n    return _arg0;\n";
+        assert_eq!(function(body), "    int64_t _r1;\n    _r1 = 2LL;\n    _arg0 = g();\n    _arg0 = 1LL;\n    _arg0 = _arg0 + _r1;\n    return _arg0;\n");
+
+        // A temporary returned right away goes, with its declaration
+        let body = "    int64_t _r1;\n    _r1 = _arg0 * 3LL;\n    return _r1;\n";
+        assert_eq!(function(body), "    return _arg0 * 3LL;\n");
+
+        // .. unless returning it converts it
+        let body = "    int32_t _r1;\n    _r1 = _arg0;\n    return _r1;\n";
+        assert_eq!(function(body), body);
+
+        // A temporary read only by an unused one is unused too; its call stays
+        let body = "    int64_t _r1;\n    int64_t _r2;\n    _r1 = g();\n    _r2 = _r1 + 1LL;\n    return 0LL;\n";
+        assert_eq!(function(body), "    (void)(g());\n    return 0LL;\n");
+    }
+
+    #[test]
+    fn test_constant_branches() {
+        let body = "    if (1) {\n        goto L_then_1;\n    } else {\n        goto L_else_2;\n    }\nL_then_1:\n    return 1LL;\nL_else_2:\n    return 2LL;\n";
+        assert_eq!(function(body), "    goto L_then_1;\nL_then_1:\n    return 1LL;\n    return 2LL;\n");
+
+        let body = "    if (0) {\n        return 1LL;\n    }\n    if (0) {\n        return 2LL;\n    } else {\n        int64_t x = 3;\n        return x;\n    }\n";
+        assert_eq!(function(body), "    {\n        int64_t x = 3;\n        return x;\n    }\n");
+    }
+
+    #[test]
+    fn test_other_code_is_kept() {
+        let source = "/* x + 0 */\nstatic const char* s = \"if (1) {\";\nstruct P {\n    int64_t x;\n};\nvoid g(int64_t* p) {\n    *p = 1;\n    *p = 2;\n}\n";
+        assert_eq!(optimize(source), source);
+    }
+}
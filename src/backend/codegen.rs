@@ -17,3 +17,89 @@ pub trait CodeGen {
     /// Get the backend name
     fn name(&self) -> &str;
 }
+
+/// Set of runtime sanitizers to instrument generated code with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SanitizerFlags(u8);
+
+impl SanitizerFlags {
+    pub const NONE: Self = Self(0);
+    pub const ADDRESS: Self = Self(1 << 0);
+    pub const MEMORY: Self = Self(1 << 1);
+    pub const UNDEFINED: Self = Self(1 << 2);
+
+    /// All sanitizers with their command-line names
+    const NAMES: [(&'static str, SanitizerFlags); 3] = [
+        ("address", Self::ADDRESS),
+        ("memory", Self::MEMORY),
+        ("undefined", Self::UNDEFINED),
+    ];
+
+    /// Parse a comma-separated list such as `address,undefined`
+    pub fn parse(list: &str) -> std::result::Result<Self, String> {
+        let mut flags = Self::NONE;
+        for name in list.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            match Self::NAMES.iter().find(|(n, _)| *n == name) {
+                Some((_, flag)) => flags.insert(*flag),
+                None => return Err(format!(
+                    "unknown sanitizer '{}' (expected address, memory or undefined)", name
+                )),
+            }
+        }
+        // ASan and MSan use conflicting shadow memory layouts
+        if flags.contains(Self::ADDRESS) && flags.contains(Self::MEMORY) {
+            return Err("address and memory sanitizers cannot be combined".to_string());
+        }
+        Ok(flags)
+    }
+
+    pub fn contains(self, other: Self) -> bool {
+        other.0 != 0 && self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Names of the enabled sanitizers, in a stable order
+    pub fn names(self) -> Vec<&'static str> {
+        Self::NAMES.iter()
+            .filter(|(_, flag)| self.contains(*flag))
+            .map(|(name, _)| *name)
+            .collect()
+    }
+
+    /// C compiler flags enabling these sanitizers (empty if none)
+    pub fn compiler_args(self) -> Vec<String> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+        vec![
+            format!("-fsanitize={}", self.names().join(",")),
+            "-fno-omit-frame-pointer".to_string(),
+            "-g".to_string(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sanitizers() {
+        let flags = SanitizerFlags::parse("address,undefined").unwrap();
+        assert!(flags.contains(SanitizerFlags::ADDRESS));
+        assert!(flags.contains(SanitizerFlags::UNDEFINED));
+        assert!(!flags.contains(SanitizerFlags::MEMORY));
+        assert_eq!(flags.compiler_args()[0], "-fsanitize=address,undefined");
+
+        assert!(SanitizerFlags::parse("thread").is_err());
+        assert!(SanitizerFlags::parse("address,memory").is_err());
+        assert!(SanitizerFlags::parse("").unwrap().compiler_args().is_empty());
+    }
+}
@@ -0,0 +1,261 @@
+//! Cross-compilation toolchain selection
+//!
+//! Decides which C compiler to invoke for a target triple and which
+//! `--sysroot` / `-target` flags to pass when the target is not the host.
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::utils::{Error, Result};
+
+/// Triple of the machine the compiler is running on
+pub fn host_triple() -> String {
+    #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+    return "arm64-apple-darwin".to_string();
+
+    #[cfg(all(target_os = "macos", target_arch = "x86_64"))]
+    return "x86_64-apple-darwin".to_string();
+
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    return "x86_64-unknown-linux-gnu".to_string();
+
+    #[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+    return "aarch64-unknown-linux-gnu".to_string();
+
+    #[cfg(all(target_os = "windows", target_arch = "x86_64"))]
+    return "x86_64-pc-windows-msvc".to_string();
+
+    // Fallback
+    #[cfg(not(any(
+        all(target_os = "macos", target_arch = "aarch64"),
+        all(target_os = "macos", target_arch = "x86_64"),
+        all(target_os = "linux", target_arch = "x86_64"),
+        all(target_os = "linux", target_arch = "aarch64"),
+        all(target_os = "windows", target_arch = "x86_64"),
+    )))]
+    return "x86_64-unknown-linux-gnu".to_string();
+}
+
+/// Architecture and OS family of a triple, ignoring vendor and ABI
+/// (so `aarch64-linux-gnu` and `aarch64-unknown-linux-gnu` compare equal)
+fn triple_key(triple: &str) -> (&str, &str) {
+    let arch = match triple.split('-').next().unwrap_or("") {
+        "arm64" => "aarch64",
+        "amd64" => "x86_64",
+        arch => arch,
+    };
+    let os = if triple.contains("linux") {
+        "linux"
+    } else if triple.contains("darwin") || triple.contains("apple") {
+        "darwin"
+    } else if triple.contains("windows") {
+        "windows"
+    } else {
+        "none"
+    };
+    (arch, os)
+}
+
+/// Cross-compilation target configuration, loaded from a file such as:
+///
+/// ```toml
+/// [target]
+/// triple = "aarch64-linux-gnu"
+/// sysroot = "/opt/sysroots/aarch64"
+/// compiler = "aarch64-linux-gnu-gcc"
+/// cflags = ["-march=armv8-a", "-mtune=cortex-a72"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CrossConfig {
+    pub triple: Option<String>,
+    pub sysroot: Option<PathBuf>,
+    pub compiler: Option<String>,
+    pub cflags: Vec<String>,
+}
+
+impl CrossConfig {
+    /// Load a configuration file
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| Error::Io(format!("{}: {}", path.display(), e)))?;
+        Self::parse(&text)
+    }
+
+    /// Parse the TOML subset used by cross configs: `key = "string"` and
+    /// `key = ["string", ...]` pairs, optionally under a `[target]` table
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut config = Self::default();
+        for (n, raw) in text.lines().enumerate() {
+            let line = strip_comment(raw).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = |msg: &str| Error::CrossConfig(format!("line {}: {}", n + 1, msg));
+            if line.starts_with('[') {
+                if line != "[target]" {
+                    return Err(invalid(&format!("unknown table {}", line)));
+                }
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or_else(|| invalid("expected `key = value`"))?;
+            let value = value.trim();
+            match key.trim() {
+                "triple" => config.triple = Some(parse_string(value).ok_or_else(|| invalid("expected a string"))?),
+                "sysroot" => config.sysroot = Some(PathBuf::from(parse_string(value).ok_or_else(|| invalid("expected a string"))?)),
+                "compiler" => config.compiler = Some(parse_string(value).ok_or_else(|| invalid("expected a string"))?),
+                "cflags" => config.cflags = parse_string_array(value).ok_or_else(|| invalid("expected an array of strings"))?,
+                other => return Err(invalid(&format!("unknown key '{}'", other))),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Drop a trailing `# comment` that is not inside a string
+pub(crate) fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+pub(crate) fn parse_string(value: &str) -> Option<String> {
+    let inner = value.strip_prefix('"')?.strip_suffix('"')?;
+    if inner.contains('"') {
+        return None;
+    }
+    Some(inner.to_string())
+}
+
+pub(crate) fn parse_string_array(value: &str) -> Option<Vec<String>> {
+    let inner = value.strip_prefix('[')?.strip_suffix(']')?.trim();
+    if inner.is_empty() {
+        return Some(Vec::new());
+    }
+    inner.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(parse_string)
+        .collect()
+}
+
+/// The C compiler setup for one target
+#[derive(Debug, Clone, PartialEq)]
+pub struct Toolchain {
+    /// Resolved target triple
+    pub triple: String,
+    /// Target libraries and headers passed as `--sysroot`
+    pub sysroot: Option<PathBuf>,
+    /// Compiler to use instead of clang/gcc when cross-compiling
+    pub cross_compiler: Option<String>,
+    /// Additional flags appended to every compile
+    pub cflags: Vec<String>,
+    /// Whether the triple came from `--target native` (a config may override it)
+    native_request: bool,
+}
+
+impl Toolchain {
+    /// Toolchain for a `--target` value, where `native` means the host
+    pub fn new(target: &str) -> Self {
+        let native_request = target == "native";
+        Self {
+            triple: if native_request { host_triple() } else { target.to_string() },
+            sysroot: None,
+            cross_compiler: None,
+            cflags: Vec::new(),
+            native_request,
+        }
+    }
+
+    /// Apply a cross config. Its triple is used unless `--target` was given explicitly.
+    pub fn with_config(mut self, config: CrossConfig) -> Self {
+        if let Some(triple) = config.triple {
+            if self.native_request {
+                self.triple = triple;
+            }
+        }
+        if config.sysroot.is_some() {
+            self.sysroot = config.sysroot;
+        }
+        if config.compiler.is_some() {
+            self.cross_compiler = config.compiler;
+        }
+        self.cflags.extend(config.cflags);
+        self
+    }
+
+    /// Whether the target differs from the host
+    pub fn is_cross(&self) -> bool {
+        triple_key(&self.triple) != triple_key(&host_triple())
+    }
+
+    /// Compilers to try, in order
+    pub fn compilers(&self) -> Vec<String> {
+        if !self.is_cross() {
+            return ["clang", "gcc", "cc"].iter().map(|c| c.to_string()).collect();
+        }
+        let cross = self.cross_compiler.clone()
+            .unwrap_or_else(|| format!("{}-gcc", self.triple));
+        // clang can target any triple given -target and a sysroot
+        vec![cross, "clang".to_string()]
+    }
+
+    /// Target flags for an invocation of `compiler`
+    pub fn compiler_args(&self, compiler: &str) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.is_cross() {
+            // GCC cross-compilers are built for one target and reject -target
+            if compiler.contains("clang") {
+                args.push("-target".to_string());
+                args.push(self.triple.clone());
+            }
+            if let Some(ref sysroot) = self.sysroot {
+                args.push(format!("--sysroot={}", sysroot.display()));
+            }
+        }
+        args.extend(self.cflags.iter().cloned());
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cross_config() {
+        let config = CrossConfig::parse(
+            "# Raspberry Pi 4\n[target]\ntriple = \"aarch64-linux-gnu\"\nsysroot = \"/opt/rpi\" # mounted image\ncflags = [\"-mcpu=cortex-a72\", \"-O2\"]\n",
+        ).unwrap();
+        assert_eq!(config.triple.as_deref(), Some("aarch64-linux-gnu"));
+        assert_eq!(config.sysroot, Some(PathBuf::from("/opt/rpi")));
+        assert_eq!(config.compiler, None);
+        assert_eq!(config.cflags, vec!["-mcpu=cortex-a72", "-O2"]);
+
+        assert!(CrossConfig::parse("linker = \"ld\"").is_err());
+        assert!(CrossConfig::parse("triple = aarch64").is_err());
+    }
+
+    #[test]
+    fn test_cross_invocation() {
+        let native = Toolchain::new("native");
+        assert!(!native.is_cross());
+        assert_eq!(native.compilers()[0], "clang");
+        assert!(native.compiler_args("clang").is_empty());
+
+        // Pick a target that is never the host
+        let target = if host_triple().starts_with("riscv64") { "mips-linux-gnu" } else { "riscv64-linux-gnu" };
+        let config = CrossConfig { sysroot: Some(PathBuf::from("/sysroot")), ..Default::default() };
+        let cross = Toolchain::new(target).with_config(config);
+        assert!(cross.is_cross());
+        assert_eq!(cross.compilers(), vec![format!("{}-gcc", target), "clang".to_string()]);
+        assert_eq!(cross.compiler_args(&cross.compilers()[0]), vec!["--sysroot=/sysroot"]);
+        assert_eq!(cross.compiler_args("clang"), vec!["-target", target, "--sysroot=/sysroot"]);
+    }
+}
@@ -5,7 +5,8 @@
 
 use std::fs::File;
 use std::io::{self, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
 // ==================== ELF Constants ====================
 
@@ -108,17 +109,26 @@ pub struct Elf64_Shdr {
 
 // ==================== Linker ====================
 
+/// Where executables are loaded unless `set_base_address` says otherwise
+pub const DEFAULT_BASE_ADDRESS: u64 = 0x400000;
+
 pub struct Linker {
-    entry_point: u64,
+    /// Defaults to the base address
+    entry_point: Option<u64>,
+    base_address: u64,
     segments: Vec<Segment>,
     sections: Vec<Section>,
     shstrtab: Vec<u8>, // Section Header String Table
+    library_paths: Vec<PathBuf>,
+    libraries: Vec<String>,
+    symbols: Vec<Symbol>,
 }
 
 struct Segment {
     flags: u32,
     data: Vec<u8>,
     vaddr: u64,
+    align: u64,
 }
 
 struct Section {
@@ -133,31 +143,118 @@ struct Section {
     entsize: u64,
 }
 
+/// A named address listed in the link map
+struct Symbol {
+    name: String,
+    vaddr: u64,
+    size: u64,
+}
+
+/// Round `offset` up to a multiple of `align` (0 and 1 mean unaligned)
+fn align_up(offset: u64, align: u64) -> u64 {
+    if align <= 1 {
+        offset
+    } else {
+        offset.div_ceil(align) * align
+    }
+}
+
+/// View a header struct as raw bytes
+fn as_bytes<T>(value: &T) -> &[u8] {
+    unsafe {
+        std::slice::from_raw_parts(value as *const T as *const u8, std::mem::size_of::<T>())
+    }
+}
+
+impl Default for Linker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Linker {
     pub fn new() -> Self {
         // Initialize with null byte for string table
         Self {
-            entry_point: 0x400000,
+            entry_point: None,
+            base_address: DEFAULT_BASE_ADDRESS,
             segments: Vec::new(),
             sections: Vec::new(),
             shstrtab: vec![0], // Starts with null byte
+            library_paths: Vec::new(),
+            libraries: Vec::new(),
+            symbols: Vec::new(),
+        }
+    }
+
+    /// Search `dir` for libraries (`-L<dir>`)
+    pub fn add_library_path<P: AsRef<Path>>(&mut self, dir: P) {
+        self.library_paths.push(dir.as_ref().to_path_buf());
+    }
+
+    /// Link against `lib` (`-l<lib>`), e.g. `m` for libm or `foo` for libfoo.a/libfoo.so
+    pub fn add_library(&mut self, lib: &str) {
+        if !self.libraries.iter().any(|l| l == lib) {
+            self.libraries.push(lib.to_string());
         }
     }
 
+    /// Flags passed to the system linker: search paths first, then libraries
+    pub fn link_args(&self) -> Vec<String> {
+        self.library_paths.iter()
+            .map(|dir| format!("-L{}", dir.display()))
+            .chain(self.libraries.iter().map(|lib| format!("-l{}", lib)))
+            .collect()
+    }
+
+    /// Command linking `objects` into the executable `output` with the system linker.
+    /// Libraries come after the objects so static archives resolve their symbols.
+    pub fn system_link_command<P: AsRef<Path>>(&self, linker: &str, objects: &[P], output: &Path) -> Command {
+        let mut cmd = Command::new(linker);
+        cmd.arg("-o").arg(output);
+        for object in objects {
+            cmd.arg(object.as_ref());
+        }
+        cmd.args(self.link_args());
+        cmd
+    }
+
     pub fn set_entry_point(&mut self, addr: u64) {
-        self.entry_point = addr;
+        self.entry_point = Some(addr);
+    }
+
+    /// Load the image at `addr` instead of `DEFAULT_BASE_ADDRESS`
+    pub fn set_base_address(&mut self, addr: u64) {
+        self.base_address = addr;
+    }
+
+    pub fn base_address(&self) -> u64 {
+        self.base_address
+    }
+
+    /// Record a symbol of `size` bytes at `vaddr` for the link map
+    pub fn add_symbol(&mut self, name: &str, vaddr: u64, size: u64) {
+        self.symbols.push(Symbol { name: name.to_string(), vaddr, size });
+    }
+
+    /// Address of a symbol added with `add_symbol`
+    pub fn symbol_address(&self, name: &str) -> Option<u64> {
+        self.symbols.iter().find(|s| s.name == name).map(|s| s.vaddr)
     }
 
-    pub fn add_segment(&mut self, data: Vec<u8>, flags: u32, vaddr: u64) {
+    /// Add a loadable segment. Its file offset is rounded up to `align`.
+    pub fn add_segment(&mut self, data: Vec<u8>, flags: u32, vaddr: u64, align: u64) {
         self.segments.push(Segment {
             flags,
             data,
             vaddr,
+            align,
         });
     }
 
-    /// Add a section and return its index
-    pub fn add_section(&mut self, name: &str, data: Vec<u8>, sh_type: u32, flags: u64, vaddr: u64) -> usize {
+    /// Add a section and return its index.
+    /// Its file offset is rounded up to `align`, which is also stored in `sh_addralign`.
+    pub fn add_section(&mut self, name: &str, data: Vec<u8>, sh_type: u32, flags: u64, vaddr: u64, align: u64) -> usize {
         self.sections.push(Section {
             name: name.to_string(),
             sh_type,
@@ -166,12 +263,84 @@ impl Linker {
             vaddr,
             link: 0,
             info: 0,
-            align: 16, // Default alignment
+            align,
             entsize: 0,
         });
         self.sections.len()
     }
 
+    /// Compute file offsets for segment data, section data, the section
+    /// name table and the section header table.
+    ///
+    /// Section `i` shares the data of segment `i` when one exists (so the
+    /// segment is placed at the stricter of the two alignments); remaining
+    /// sections get their own data appended after the segments.
+    fn layout(&self) -> FileLayout {
+        let mut offset = std::mem::size_of::<Elf64_Ehdr>() as u64
+            + self.segments.len() as u64 * std::mem::size_of::<Elf64_Phdr>() as u64;
+
+        let mut segment_offsets = Vec::with_capacity(self.segments.len());
+        for (i, segment) in self.segments.iter().enumerate() {
+            let section_align = self.sections.get(i).map(|s| s.align).unwrap_or(1);
+            offset = align_up(offset, segment.align.max(section_align));
+            segment_offsets.push(offset);
+            offset += segment.data.len() as u64;
+        }
+
+        let mut section_offsets = Vec::with_capacity(self.sections.len());
+        for (i, section) in self.sections.iter().enumerate() {
+            if let Some(&seg_offset) = segment_offsets.get(i) {
+                section_offsets.push(seg_offset);
+            } else {
+                offset = align_up(offset, section.align);
+                section_offsets.push(offset);
+                offset += section.data.len() as u64;
+            }
+        }
+
+        let shstrtab_offset = offset;
+        offset += self.shstrtab.len() as u64;
+
+        FileLayout {
+            segment_offsets,
+            section_offsets,
+            shstrtab_offset,
+            sh_offset: align_up(offset, 8),
+        }
+    }
+
+    /// Human-readable memory layout: every section's address, file offset
+    /// and size, then the symbols in address order
+    pub fn link_map(&self) -> String {
+        let layout = self.layout();
+        let mut map = String::new();
+        map.push_str(&format!("Entry point:  {:#018x}\n", self.entry_point.unwrap_or(self.base_address)));
+        map.push_str(&format!("Base address: {:#018x}\n", self.base_address));
+
+        map.push_str("\nSections:\n");
+        map.push_str(&format!("  {:<16} {:<18} {:<10} Size\n", "Name", "Address", "Offset"));
+        for (section, offset) in self.sections.iter().zip(&layout.section_offsets) {
+            map.push_str(&format!(
+                "  {:<16} {:#018x} {:#010x} {:#010x}\n",
+                section.name, section.vaddr, offset, section.data.len()
+            ));
+        }
+
+        map.push_str("\nSymbols:\n");
+        map.push_str(&format!("  {:<18} {:<10} Name\n", "Address", "Size"));
+        let mut symbols: Vec<&Symbol> = self.symbols.iter().collect();
+        symbols.sort_by_key(|s| s.vaddr);
+        for symbol in symbols {
+            map.push_str(&format!("  {:#018x} {:#010x} {}\n", symbol.vaddr, symbol.size, symbol.name));
+        }
+        map
+    }
+
+    /// Write `link_map` to `path`
+    pub fn emit_link_map<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        std::fs::write(path, self.link_map())
+    }
+
     /// Emit the linked ELF file
     pub fn emit<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
         let mut file = File::create(path)?;
@@ -191,11 +360,12 @@ impl Linker {
             self.shstrtab.extend_from_slice(section.name.as_bytes());
             self.shstrtab.push(0); // Null terminator
         }
-        
-        // Add .shstrtab section itself to the list (temporarily or logic wise)
-        // Usually .shstrtab is the last section.
-        let _shstrtab_offset_in_shdr = self.shstrtab.len(); 
-        // We will write shstrtab data at the end of file content, before Section Headers
+
+        // .shstrtab names itself
+        let shstrtab_name_offset = self.shstrtab.len() as u32;
+        self.shstrtab.extend_from_slice(b".shstrtab\0");
+
+        let layout = self.layout();
 
         // 1. Prepare Headers
         let mut ehdr = Elf64_Ehdr::default();
@@ -213,7 +383,7 @@ impl Linker {
         ehdr.e_type = ET_EXEC;
         ehdr.e_machine = EM_X86_64;
         ehdr.e_version = 1;
-        ehdr.e_entry = self.entry_point;
+        ehdr.e_entry = self.entry_point.unwrap_or(self.base_address);
         ehdr.e_ehsize = std::mem::size_of::<Elf64_Ehdr>() as u16;
         ehdr.e_phentsize = std::mem::size_of::<Elf64_Phdr>() as u16;
         ehdr.e_phnum = self.segments.len() as u16;
@@ -221,155 +391,168 @@ impl Linker {
         // +1 for Null Section, +1 for .shstrtab
         ehdr.e_shnum = (self.sections.len() + 2) as u16; 
         ehdr.e_shstrndx = (self.sections.len() + 1) as u16; // Index of .shstrtab
+        ehdr.e_phoff = std::mem::size_of::<Elf64_Ehdr>() as u64;
+        ehdr.e_shoff = layout.sh_offset;
         
-        // Calculate offsets
-        let ph_offset = std::mem::size_of::<Elf64_Ehdr>() as u64;
-        ehdr.e_phoff = ph_offset;
-        
-        // Write ELF Header
-        let ehdr_bytes = unsafe {
-            std::slice::from_raw_parts(
-                &ehdr as *const _ as *const u8,
-                std::mem::size_of::<Elf64_Ehdr>()
-            )
-        };
-        file.write_all(ehdr_bytes)?;
-        
-        // Write Program Headers
-        let mut current_offset = ph_offset + (self.segments.len() as u64 * std::mem::size_of::<Elf64_Phdr>() as u64);
+        let mut out: Vec<u8> = Vec::new();
+        out.extend_from_slice(as_bytes(&ehdr));
         
-        for segment in &self.segments {
+        // Program Headers
+        for (segment, &offset) in self.segments.iter().zip(&layout.segment_offsets) {
             let phdr = Elf64_Phdr {
                 p_type: PT_LOAD,
                 p_flags: segment.flags,
-                p_offset: current_offset,
+                p_offset: offset,
                 p_vaddr: segment.vaddr,
                 p_paddr: segment.vaddr,
                 p_filesz: segment.data.len() as u64,
                 p_memsz: segment.data.len() as u64,
-                p_align: 0x1000, 
-            };
-            
-            let phdr_bytes = unsafe {
-                std::slice::from_raw_parts(
-                    &phdr as *const _ as *const u8,
-                    std::mem::size_of::<Elf64_Phdr>()
-                )
+                p_align: segment.align.max(1),
             };
-            file.write_all(phdr_bytes)?;
-            
-            // Advance offset for next segment's data
-            // Note: Use the segment's data length for file offset calculation
-            current_offset += segment.data.len() as u64;
+            out.extend_from_slice(as_bytes(&phdr));
         }
         
-        // Write Segment Data
-        for segment in &self.segments {
-            file.write_all(&segment.data)?;
+        // Segment Data (zero padding up to each aligned offset)
+        for (segment, &offset) in self.segments.iter().zip(&layout.segment_offsets) {
+            out.resize(offset as usize, 0);
+            out.extend_from_slice(&segment.data);
+        }
+
+        // Data for sections without a backing segment
+        for (section, &offset) in self.sections.iter().zip(&layout.section_offsets).skip(self.segments.len()) {
+            out.resize(offset as usize, 0);
+            out.extend_from_slice(&section.data);
         }
         
-        // Write .shstrtab Data (it's not in segments usually, but resides in file)
-        let shstrtab_file_offset = current_offset;
-        file.write_all(&self.shstrtab)?;
-        current_offset += self.shstrtab.len() as u64;
-        
-        // Write Section Headers
-        // Update ELF Header with Section Header Offset (need to seek back or write it later? We passed it, so seek back)
-        let sh_offset = current_offset;
+        // .shstrtab Data (not part of any segment, but resides in file)
+        out.resize(layout.shstrtab_offset as usize, 0);
+        out.extend_from_slice(&self.shstrtab);
         
+        // Section Headers
+        out.resize(layout.sh_offset as usize, 0);
+
         // 1. Null Section
-        let null_shdr = Elf64_Shdr::default();
-        let null_bytes = unsafe {
-            std::slice::from_raw_parts(
-                &null_shdr as *const _ as *const u8,
-                std::mem::size_of::<Elf64_Shdr>()
-            )
-        };
-        file.write_all(null_bytes)?;
+        out.extend_from_slice(as_bytes(&Elf64_Shdr::default()));
         
         // 2. User Sections
-        // We need to calculate file offsets for sections. 
-        // For simplicity in this self-hosted linker, we assume sections map to segments.
-        // Or we just point them to where we wrote segment data.
-        // Simplification: We blindly assume the first segment is .text and second is data if exists.
-        // This is fragile. Stronger logic: user adds section, we verify it matches a segment or we append it.
-        // For this iteration: just write headers that point to 0 or where we think they are.
-        
-        let mut section_data_offset = ph_offset + (self.segments.len() as u64 * std::mem::size_of::<Elf64_Phdr>() as u64);
-
         for (i, section) in self.sections.iter().enumerate() {
             let shdr = Elf64_Shdr {
                 sh_name: name_offsets[i+1], // +1 because 0 is null section name
                 sh_type: section.sh_type,
                 sh_flags: section.flags,
                 sh_addr: section.vaddr,
-                sh_offset: section_data_offset, // This assumes section data corresponds 1:1 to segments in order.
+                sh_offset: layout.section_offsets[i],
                 sh_size: section.data.len() as u64,
                 sh_link: section.link,
                 sh_info: section.info,
                 sh_addralign: section.align,
                 sh_entsize: section.entsize,
             };
-            
-            let shdr_bytes = unsafe {
-                std::slice::from_raw_parts(
-                    &shdr as *const _ as *const u8,
-                    std::mem::size_of::<Elf64_Shdr>()
-                )
-            };
-            file.write_all(shdr_bytes)?;
-            
-            section_data_offset += section.data.len() as u64;
+            out.extend_from_slice(as_bytes(&shdr));
         }
         
         // 3. .shstrtab Section Header
-        
-        // Add .shstrtab name to the string table itself?
-        // Standard convention: The string table usually contains its own name.
-        // Let's ensure we added ".shstrtab" to the string table.
-        // But for now, let's just create the header.
-        
-        let _shstrtab_name_offset = self.shstrtab.len() as u32;
-        // We actually modify shstrtab AFTER the loop in a real implementation to include its own name
-        // but since we already wrote it to file, we can't easily append.
-        // Quick fix: Add ".shstrtab" initially or just use 0 (no name) for now.
-        // Or better: Append it to the vector before writing to file.
-        
-        // RE-DOING string table logic properly:
-        // We need to rebuild string table including ".shstrtab" at the end if we want it named.
-        
         let shstrtab_shdr = Elf64_Shdr {
-            sh_name: 0, // No name for now to avoid logic complexity
+            sh_name: shstrtab_name_offset,
             sh_type: SHT_STRTAB,
             sh_flags: 0,
             sh_addr: 0,
-            sh_offset: shstrtab_file_offset,
+            sh_offset: layout.shstrtab_offset,
             sh_size: self.shstrtab.len() as u64,
             sh_link: 0,
             sh_info: 0,
             sh_addralign: 1,
             sh_entsize: 0,
         };
+        out.extend_from_slice(as_bytes(&shstrtab_shdr));
 
-         // Fix: sh_name for .shstrtab. 
-         // We should have added ".shstrtab" to the string table.
-         
-        let shstrtab_bytes = unsafe {
-            std::slice::from_raw_parts(
-                &shstrtab_shdr as *const _ as *const u8,
-                std::mem::size_of::<Elf64_Shdr>()
-            )
-        };
-        file.write_all(shstrtab_bytes)?;
-
-        // Go back and update e_shoff
-        // Elf64_Ehdr is at 0
-        // e_shoff is at offset 40 (0x28)
-        let shoff_bytes = sh_offset.to_le_bytes();
-        use std::io::Seek;
-        file.seek(std::io::SeekFrom::Start(40))?;
-        file.write_all(&shoff_bytes)?;
-        
+        file.write_all(&out)?;
         Ok(())
     }
 }
+
+/// File offsets computed before writing the ELF image
+struct FileLayout {
+    segment_offsets: Vec<u64>,
+    section_offsets: Vec<u64>,
+    shstrtab_offset: u64,
+    sh_offset: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+        u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+    }
+
+    /// Fetch (sh_offset, sh_addralign) of section header `index`
+    fn section_header(bytes: &[u8], index: usize) -> (u64, u64) {
+        let shoff = read_u64(bytes, 40) as usize;
+        let base = shoff + index * std::mem::size_of::<Elf64_Shdr>();
+        (read_u64(bytes, base + 24), read_u64(bytes, base + 48))
+    }
+
+    #[test]
+    fn test_link_args() {
+        let mut linker = Linker::new();
+        linker.add_library("m");
+        linker.add_library_path("/opt/lib");
+        linker.add_library("m");
+        linker.add_library("foo");
+        assert_eq!(linker.link_args(), vec!["-L/opt/lib", "-lm", "-lfoo"]);
+
+        let cmd = linker.system_link_command("cc", &["main.o"], Path::new("main"));
+        let args: Vec<_> = cmd.get_args().map(|a| a.to_string_lossy().into_owned()).collect();
+        assert_eq!(args, vec!["-o", "main", "main.o", "-L/opt/lib", "-lm", "-lfoo"]);
+    }
+
+    #[test]
+    fn test_section_alignment() {
+        let path = std::env::temp_dir().join(format!("aether_linker_align_{}.elf", std::process::id()));
+
+        let mut linker = Linker::new();
+        let code = vec![0x90; 7];
+        linker.add_segment(code.clone(), PF_R | PF_X, 0x401000, 4);
+        linker.add_section(".text", code, SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, 0x401000, 4);
+        let rodata = vec![0xAB; 32];
+        linker.add_section(".rodata", rodata.clone(), SHT_PROGBITS, SHF_ALLOC, 0, 16);
+        linker.emit(&path).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        // Header (64) + one program header (56) = 120, rounded up to 4
+        let (text_offset, text_align) = section_header(&bytes, 1);
+        assert_eq!((text_offset, text_align), (120, 4));
+
+        // .text ends at 127; .rodata must start at the next 16-byte boundary
+        let (rodata_offset, rodata_align) = section_header(&bytes, 2);
+        assert_eq!((rodata_offset, rodata_align), (128, 16));
+        assert_eq!(&bytes[128..160], rodata.as_slice());
+    }
+
+    #[test]
+    fn test_link_map() {
+        let mut linker = Linker::new();
+        assert_eq!(linker.base_address(), DEFAULT_BASE_ADDRESS);
+        linker.set_base_address(0x8000);
+        let code = vec![0x90; 0x20];
+        linker.add_segment(code.clone(), PF_R | PF_X, 0x9000, 0x1000);
+        linker.add_section(".text", code, SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, 0x9000, 16);
+        linker.add_symbol("main", 0x9010, 0x10);
+        linker.add_symbol("_start", 0x9000, 0x10);
+        assert_eq!(linker.symbol_address("main"), Some(0x9010));
+
+        let map = linker.link_map();
+        // The entry point defaults to the base address
+        assert!(map.contains("Entry point:  0x0000000000008000"), "{}", map);
+        assert!(map.contains(".text            0x0000000000009000 0x00001000 0x00000020"), "{}", map);
+        let start = map.find("_start").unwrap();
+        assert!(start < map.find("main").unwrap(), "symbols are sorted by address:\n{}", map);
+
+        linker.set_entry_point(0x9000);
+        assert!(linker.link_map().contains("Entry point:  0x0000000000009000"));
+    }
+}
@@ -0,0 +1,273 @@
+//! Linker Scripts for the Self-Hosted Linker
+//!
+//! A small subset of the GNU ld script language, enough to place code for
+//! embedded and bare-metal targets:
+//!
+//! ```text
+//! ENTRY(_start)
+//! BASE_ADDRESS(0x8000)
+//! SECTIONS {
+//!     .text 0x1000 : { }
+//! }
+//! ```
+//!
+//! Section addresses are offsets from the base address.
+
+use std::fs;
+use std::path::Path;
+
+use crate::utils::{Error, Result};
+
+/// Parsed linker script
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LinkerScript {
+    /// Symbol execution starts at
+    pub entry: Option<String>,
+    /// Virtual address of the image
+    pub base_address: Option<u64>,
+    /// Output sections in script order, with their offset from the base address
+    pub sections: Vec<SectionPlacement>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionPlacement {
+    pub name: String,
+    pub offset: Option<u64>,
+}
+
+impl LinkerScript {
+    /// Load a script file
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| Error::Io(format!("{}: {}", path.display(), e)))?;
+        Self::parse(&text)
+    }
+
+    /// Parse `ENTRY(sym)`, `BASE_ADDRESS(addr)` and `SECTIONS { ... }` commands
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut parser = ScriptParser { tokens: tokenize(text)?, pos: 0 };
+        let mut script = Self::default();
+        while let Some((token, line)) = parser.next() {
+            match token {
+                Token::Word(word) if word == "ENTRY" => {
+                    parser.expect(Token::LParen)?;
+                    script.entry = Some(parser.word()?);
+                    parser.expect(Token::RParen)?;
+                }
+                Token::Word(word) if word == "BASE_ADDRESS" => {
+                    parser.expect(Token::LParen)?;
+                    script.base_address = Some(parser.number()?);
+                    parser.expect(Token::RParen)?;
+                }
+                Token::Word(word) if word == "SECTIONS" => {
+                    parser.expect(Token::LBrace)?;
+                    while !parser.eat(&Token::RBrace) {
+                        script.sections.push(parser.section()?);
+                    }
+                }
+                Token::Semi => {}
+                other => return Err(invalid(line, &format!("unknown command {}", other))),
+            }
+        }
+        Ok(script)
+    }
+
+    /// Offset of section `name` from the base address, if the script places it
+    pub fn section_offset(&self, name: &str) -> Option<u64> {
+        self.sections.iter().find(|s| s.name == name).and_then(|s| s.offset)
+    }
+}
+
+fn invalid(line: usize, msg: &str) -> Error {
+    Error::LinkerScript(format!("line {}: {}", line, msg))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Number(u64),
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Colon,
+    Semi,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "`{}`", word),
+            Token::Number(n) => write!(f, "`{:#x}`", n),
+            Token::LParen => f.write_str("`(`"),
+            Token::RParen => f.write_str("`)`"),
+            Token::LBrace => f.write_str("`{`"),
+            Token::RBrace => f.write_str("`}`"),
+            Token::Colon => f.write_str("`:`"),
+            Token::Semi => f.write_str("`;`"),
+        }
+    }
+}
+
+/// Split `text` into tokens tagged with their line, dropping `/* */` comments
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>> {
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let token = match c {
+            '\n' => {
+                line += 1;
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            '/' if text[i..].starts_with("/*") => {
+                let end = text[i + 2..].find("*/").ok_or_else(|| invalid(line, "unterminated comment"))?;
+                let comment = &text[i..i + 2 + end + 2];
+                line += comment.matches('\n').count();
+                while chars.peek().is_some_and(|&(j, _)| j < i + comment.len()) {
+                    chars.next();
+                }
+                continue;
+            }
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '{' => Token::LBrace,
+            '}' => Token::RBrace,
+            ':' => Token::Colon,
+            ';' => Token::Semi,
+            c if c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$') => {
+                let mut end = i + c.len_utf8();
+                while let Some(&(j, next)) = chars.peek() {
+                    if !(next.is_ascii_alphanumeric() || matches!(next, '_' | '.' | '$')) {
+                        break;
+                    }
+                    end = j + next.len_utf8();
+                    chars.next();
+                }
+                let word = &text[i..end];
+                if c.is_ascii_digit() {
+                    Token::Number(parse_number(word).ok_or_else(|| invalid(line, &format!("invalid number `{}`", word)))?)
+                } else {
+                    Token::Word(word.to_string())
+                }
+            }
+            other => return Err(invalid(line, &format!("unexpected character `{}`", other))),
+        };
+        tokens.push((token, line));
+    }
+    Ok(tokens)
+}
+
+/// Decimal or `0x` hexadecimal, with an optional `K` or `M` multiplier
+fn parse_number(word: &str) -> Option<u64> {
+    let (digits, scale) = match word.as_bytes().last()? {
+        b'K' | b'k' => (&word[..word.len() - 1], 1024),
+        b'M' | b'm' => (&word[..word.len() - 1], 1024 * 1024),
+        _ => (word, 1),
+    };
+    let value = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    };
+    value.checked_mul(scale)
+}
+
+struct ScriptParser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl ScriptParser {
+    fn next(&mut self) -> Option<(Token, usize)> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Line of the last token, for errors at the end of the script
+    fn end_line(&self) -> usize {
+        self.tokens.last().map(|(_, line)| *line).unwrap_or(1)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.tokens.get(self.pos).is_some_and(|(t, _)| t == token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.next() {
+            Some((token, _)) if token == expected => Ok(()),
+            Some((token, line)) => Err(invalid(line, &format!("expected {}, found {}", expected, token))),
+            None => Err(invalid(self.end_line(), &format!("expected {}, found the end of the script", expected))),
+        }
+    }
+
+    fn word(&mut self) -> Result<String> {
+        match self.next() {
+            Some((Token::Word(word), _)) => Ok(word),
+            Some((token, line)) => Err(invalid(line, &format!("expected a name, found {}", token))),
+            None => Err(invalid(self.end_line(), "expected a name, found the end of the script")),
+        }
+    }
+
+    fn number(&mut self) -> Result<u64> {
+        match self.next() {
+            Some((Token::Number(n), _)) => Ok(n),
+            Some((token, line)) => Err(invalid(line, &format!("expected an address, found {}", token))),
+            None => Err(invalid(self.end_line(), "expected an address, found the end of the script")),
+        }
+    }
+
+    /// `.name [offset] : { }`
+    fn section(&mut self) -> Result<SectionPlacement> {
+        let name = self.word()?;
+        let offset = match self.tokens.get(self.pos) {
+            Some((Token::Number(n), _)) => {
+                let n = *n;
+                self.pos += 1;
+                Some(n)
+            }
+            _ => None,
+        };
+        self.expect(Token::Colon)?;
+        self.expect(Token::LBrace)?;
+        self.expect(Token::RBrace)?;
+        self.eat(&Token::Semi);
+        Ok(SectionPlacement { name, offset })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_linker_script() {
+        let script = LinkerScript::parse(
+            "/* bare-metal layout */\nENTRY(_start)\nBASE_ADDRESS(0x8000)\nSECTIONS {\n    .text 0x1000 : { }\n    .data : { }\n}\n",
+        ).unwrap();
+        assert_eq!(script.entry.as_deref(), Some("_start"));
+        assert_eq!(script.base_address, Some(0x8000));
+        assert_eq!(script.section_offset(".text"), Some(0x1000));
+        assert_eq!(script.section_offset(".data"), None);
+        assert_eq!(script.sections.len(), 2);
+
+        assert_eq!(LinkerScript::parse("BASE_ADDRESS(64K)").unwrap().base_address, Some(0x10000));
+        assert_eq!(LinkerScript::parse("").unwrap(), LinkerScript::default());
+    }
+
+    #[test]
+    fn test_linker_script_errors() {
+        let message = |text: &str| LinkerScript::parse(text).unwrap_err().to_string();
+        assert!(message("MEMORY { }").contains("line 1: unknown command `MEMORY`"));
+        assert!(message("ENTRY(_start)\nBASE_ADDRESS(start)").contains("line 2: expected an address"));
+        assert!(message("SECTIONS {\n  .text 0x1000 { }\n}").contains("line 2: expected `:`"));
+        assert!(message("ENTRY(_start").contains("found the end of the script"));
+        assert!(message("/* open").contains("unterminated comment"));
+    }
+}
@@ -7,16 +7,30 @@ use llvm_sys::prelude::*;
 use llvm_sys::target::*;
 use llvm_sys::target_machine::*;
 use llvm_sys::analysis::*;
+use llvm_sys::debuginfo::*;
+use llvm_sys::transforms::pass_builder::*;
+use llvm_sys::bit_reader::LLVMParseBitcodeInContext2;
+use llvm_sys::bit_writer::LLVMWriteBitcodeToFile;
+use llvm_sys::linker::LLVMLinkModules2;
 use llvm_sys::LLVMIntPredicate;
 
 use std::collections::HashMap;
 use std::ffi::{CStr, CString};
+use std::path::{Path, PathBuf};
 use std::ptr;
 
-use crate::backend::codegen::CodeGen;
+use crate::backend::codegen::{CodeGen, SanitizerFlags};
+use crate::middle::collections;
+use crate::middle::bench;
+use crate::middle::fileio;
+use crate::middle::mangle;
 use crate::middle::ir::*;
+use crate::middle::panic::{PANIC_FN, TRACE_ENTER_FN, TRACE_EXIT_FN};
 use crate::utils::{Error, Result};
 
+/// A phi awaiting its incoming values: (phi, block index, incoming values)
+type PendingPhi = (LLVMValueRef, usize, Vec<(Value, BlockId)>);
+
 /// LLVM-based code generator
 pub struct LLVMCodeGen {
     target_triple: String,
@@ -32,10 +46,40 @@ pub struct LLVMCodeGen {
     current_function: Option<LLVMValueRef>,
     // Allocas for multiply-assigned registers
     alloca_map: HashMap<Register, LLVMValueRef>,
+    // Phis of the current function, filled in once every block exists
+    pending_phis: Vec<PendingPhi>,
+    // Runtime sanitizers to instrument for
+    sanitizers: SanitizerFlags,
+    // Field types of union aggregates (fields overlap, so GEPs become bitcasts)
+    union_fields: HashMap<String, Vec<IRType>>,
+    // Element of each struct field in structs with explicit padding elements
+    field_elements: HashMap<String, Vec<u32>>,
+    // DWARF, emitted when the IR carries source locations (`-g`)
+    di_builder: Option<LLVMDIBuilderRef>,
+    di_files: HashMap<String, LLVMMetadataRef>,
+    // Subprogram of the function being generated
+    di_scope: Option<LLVMMetadataRef>,
+    // Writing or linking bitcode for `--lto`
+    lto: bool,
+    // Drop debug info and local symbol names before emitting (`--strip`)
+    strip: bool,
+}
+
+/// Named metadata listing the libraries a bitcode module links against
+const LIBRARIES_METADATA: &str = "aether.libraries";
+
+/// Result of `LLVMCodeGen::link_bitcode`
+pub struct LinkedBitcode {
+    /// Object code for the merged, optimized module
+    pub object: Vec<u8>,
+    /// Libraries recorded by the inputs, first occurrence first
+    pub libraries: Vec<String>,
+    /// Functions called but defined by none of the inputs
+    pub undefined: Vec<String>,
 }
 
 impl LLVMCodeGen {
-    pub fn new(target: &str) -> Self {
+    pub fn new(target: &str, sanitizers: SanitizerFlags) -> Self {
         unsafe {
             let context = LLVMContextCreate();
             let module_name = CString::new("aether_module").unwrap();
@@ -51,6 +95,15 @@ impl LLVMCodeGen {
                 block_map: HashMap::new(),
                 current_function: None,
                 alloca_map: HashMap::new(),
+                pending_phis: Vec::new(),
+                sanitizers,
+                union_fields: HashMap::new(),
+                field_elements: HashMap::new(),
+                di_builder: None,
+                di_files: HashMap::new(),
+                di_scope: None,
+                lto: false,
+                strip: false,
             };
             
             codegen.declare_builtins();
@@ -58,6 +111,36 @@ impl LLVMCodeGen {
         }
     }
     
+    /// Remove debug info and the names of non-exported symbols before emitting
+    pub fn with_strip(mut self, strip: bool) -> Self {
+        self.strip = strip;
+        self
+    }
+
+    /// Drop DWARF and rename internal functions and globals to anonymous ones
+    fn strip_module(&mut self) {
+        use llvm_sys::LLVMLinkage::{LLVMInternalLinkage, LLVMPrivateLinkage};
+        let empty = CString::new("").unwrap();
+        unsafe {
+            LLVMStripModuleDebugInfo(self.module);
+            let local = |value| matches!(LLVMGetLinkage(value), LLVMInternalLinkage | LLVMPrivateLinkage);
+            let mut func = LLVMGetFirstFunction(self.module);
+            while !func.is_null() {
+                if local(func) {
+                    LLVMSetValueName2(func, empty.as_ptr(), 0);
+                }
+                func = LLVMGetNextFunction(func);
+            }
+            let mut global = LLVMGetFirstGlobal(self.module);
+            while !global.is_null() {
+                if local(global) {
+                    LLVMSetValueName2(global, empty.as_ptr(), 0);
+                }
+                global = LLVMGetNextGlobal(global);
+            }
+        }
+    }
+
     /// Declare C standard library builtin functions
     fn declare_builtins(&mut self) {
         unsafe {
@@ -85,6 +168,12 @@ impl LLVMCodeGen {
             let malloc_name = CString::new("malloc").unwrap();
             LLVMAddFunction(self.module, malloc_name.as_ptr(), malloc_ty);
             
+            // aligned_alloc(i64, i64) -> i8*
+            let mut aligned_params = [i64_ty, i64_ty];
+            let aligned_ty = LLVMFunctionType(i8_ptr_ty, aligned_params.as_mut_ptr(), 2, 0);
+            let aligned_name = CString::new("aligned_alloc").unwrap();
+            LLVMAddFunction(self.module, aligned_name.as_ptr(), aligned_ty);
+
             // free(i8*) -> void
             let free_ty = LLVMFunctionType(void_ty, [i8_ptr_ty].as_mut_ptr(), 1, 0);
             let free_name = CString::new("free").unwrap();
@@ -112,6 +201,140 @@ impl LLVMCodeGen {
             let strlen_ty = LLVMFunctionType(i64_ty, [i8_ptr_ty].as_mut_ptr(), 1, 0);
             let strlen_name = CString::new("strlen").unwrap();
             LLVMAddFunction(self.module, strlen_name.as_ptr(), strlen_ty);
+
+            // memcmp(i8*, i8*, i64) -> i32
+            let mut memcmp_params = [i8_ptr_ty, i8_ptr_ty, i64_ty];
+            let memcmp_ty = LLVMFunctionType(i32_ty, memcmp_params.as_mut_ptr(), 3, 0);
+            let memcmp_name = CString::new("memcmp").unwrap();
+            LLVMAddFunction(self.module, memcmp_name.as_ptr(), memcmp_ty);
+
+            // Panic runtime, linked from backend::runtime
+            let mut panic_params = [i8_ptr_ty, i8_ptr_ty, i64_ty];
+            let panic_ty = LLVMFunctionType(void_ty, panic_params.as_mut_ptr(), 3, 0);
+            let panic_name = CString::new(PANIC_FN).unwrap();
+            LLVMAddFunction(self.module, panic_name.as_ptr(), panic_ty);
+
+            let enter_ty = LLVMFunctionType(void_ty, [i8_ptr_ty].as_mut_ptr(), 1, 0);
+            let enter_name = CString::new(TRACE_ENTER_FN).unwrap();
+            LLVMAddFunction(self.module, enter_name.as_ptr(), enter_ty);
+
+            let exit_trace_ty = LLVMFunctionType(void_ty, ptr::null_mut(), 0, 0);
+            let exit_trace_name = CString::new(TRACE_EXIT_FN).unwrap();
+            LLVMAddFunction(self.module, exit_trace_name.as_ptr(), exit_trace_ty);
+
+            // Collection runtime, linked from backend::runtime
+            let i1_ty = LLVMInt1TypeInContext(self.context);
+            let collection_fns: [(&str, LLVMTypeRef, &mut [LLVMTypeRef]); 15] = [
+                (collections::VEC_NEW, i8_ptr_ty, &mut []),
+                (collections::VEC_WITH_CAPACITY, i8_ptr_ty, &mut [i64_ty]),
+                (collections::VEC_PUSH, void_ty, &mut [i8_ptr_ty, i8_ptr_ty, i64_ty]),
+                (collections::VEC_POP, i8_ptr_ty, &mut [i8_ptr_ty, i64_ty]),
+                (collections::VEC_GET, i8_ptr_ty, &mut [i8_ptr_ty, i64_ty, i64_ty]),
+                (collections::VEC_LEN, i64_ty, &mut [i8_ptr_ty]),
+                (collections::VEC_CLEAR, void_ty, &mut [i8_ptr_ty]),
+                (collections::VEC_FREE, void_ty, &mut [i8_ptr_ty]),
+                (collections::MAP_NEW, i8_ptr_ty, &mut []),
+                (collections::MAP_INSERT, void_ty, &mut [i8_ptr_ty, i8_ptr_ty, i64_ty, i8_ptr_ty, i64_ty]),
+                (collections::MAP_GET, i8_ptr_ty, &mut [i8_ptr_ty, i8_ptr_ty, i64_ty]),
+                (collections::MAP_CONTAINS, i1_ty, &mut [i8_ptr_ty, i8_ptr_ty, i64_ty]),
+                (collections::MAP_REMOVE, i1_ty, &mut [i8_ptr_ty, i8_ptr_ty, i64_ty]),
+                (collections::MAP_LEN, i64_ty, &mut [i8_ptr_ty]),
+                (collections::MAP_FREE, void_ty, &mut [i8_ptr_ty]),
+            ];
+            for (name, ret, params) in collection_fns {
+                let fn_ty = LLVMFunctionType(ret, params.as_mut_ptr(), params.len() as u32, 0);
+                let name_c = CString::new(name).unwrap();
+                LLVMAddFunction(self.module, name_c.as_ptr(), fn_ty);
+            }
+
+            // File I/O and benchmark runtimes, linked from backend::runtime
+            let runtime_fns = fileio::BUILTINS.iter().map(|(_, name)| *name)
+                .chain([bench::NOW_FN, bench::SAMPLE_FN, bench::BLACK_BOX_FN, bench::REPORT_FN]);
+            for name in runtime_fns {
+                let Some((params, ret)) = fileio::signature(name).or_else(|| bench::signature(name)) else { continue };
+                let mut params: Vec<LLVMTypeRef> = params.iter().map(|ty| self.ir_type_to_llvm(ty)).collect();
+                let fn_ty = LLVMFunctionType(self.ir_type_to_llvm(&ret), params.as_mut_ptr(), params.len() as u32, 0);
+                let name_c = CString::new(name).unwrap();
+                LLVMAddFunction(self.module, name_c.as_ptr(), fn_ty);
+            }
+        }
+        self.define_str_runtime();
+    }
+
+    /// `{ ptr, i64 }`, the layout of `str`
+    fn str_type(&self) -> LLVMTypeRef {
+        unsafe {
+            let mut fields = [LLVMPointerTypeInContext(self.context, 0), LLVMInt64TypeInContext(self.context)];
+            LLVMStructTypeInContext(self.context, fields.as_mut_ptr(), 2, 0)
+        }
+    }
+
+    /// Define the `str` operations as internal functions; callers bounds-check `slice`
+    fn define_str_runtime(&mut self) {
+        unsafe {
+            let str_ty = self.str_type();
+            let ptr_ty = LLVMPointerTypeInContext(self.context, 0);
+            let i64_ty = LLVMInt64TypeInContext(self.context);
+            let builder = LLVMCreateBuilderInContext(self.context);
+            let empty = CString::new("").unwrap();
+            let define = |name: &str, ret: LLVMTypeRef, params: &mut [LLVMTypeRef]| {
+                let name_c = CString::new(name).unwrap();
+                let fn_ty = LLVMFunctionType(ret, params.as_mut_ptr(), params.len() as u32, 0);
+                let func = LLVMAddFunction(self.module, name_c.as_ptr(), fn_ty);
+                LLVMSetLinkage(func, llvm_sys::LLVMLinkage::LLVMInternalLinkage);
+                let entry = LLVMAppendBasicBlockInContext(self.context, func, c"entry".as_ptr());
+                LLVMPositionBuilderAtEnd(builder, entry);
+                func
+            };
+
+            let len_fn = define(STR_LEN, i64_ty, &mut [str_ty]);
+            LLVMBuildRet(builder, LLVMBuildExtractValue(builder, LLVMGetParam(len_fn, 0), 1, empty.as_ptr()));
+
+            let as_ptr_fn = define(STR_AS_PTR, ptr_ty, &mut [str_ty]);
+            LLVMBuildRet(builder, LLVMBuildExtractValue(builder, LLVMGetParam(as_ptr_fn, 0), 0, empty.as_ptr()));
+
+            let from_ptr_fn = define(STR_FROM_PTR, str_ty, &mut [ptr_ty]);
+            let data = LLVMGetParam(from_ptr_fn, 0);
+            let strlen_name = CString::new("strlen").unwrap();
+            let strlen = LLVMGetNamedFunction(self.module, strlen_name.as_ptr());
+            let len = LLVMBuildCall2(builder, LLVMGlobalGetValueType(strlen), strlen, [data].as_mut_ptr(), 1, empty.as_ptr());
+            let with_data = LLVMBuildInsertValue(builder, LLVMGetUndef(str_ty), data, 0, empty.as_ptr());
+            LLVMBuildRet(builder, LLVMBuildInsertValue(builder, with_data, len, 1, empty.as_ptr()));
+
+            let slice_fn = define(STR_SLICE, str_ty, &mut [str_ty, i64_ty, i64_ty]);
+            let (s, start, end) = (LLVMGetParam(slice_fn, 0), LLVMGetParam(slice_fn, 1), LLVMGetParam(slice_fn, 2));
+            let data = LLVMBuildExtractValue(builder, s, 0, empty.as_ptr());
+            let i8_ty = LLVMInt8TypeInContext(self.context);
+            let sub_data = LLVMBuildGEP2(builder, i8_ty, data, [start].as_mut_ptr(), 1, empty.as_ptr());
+            let sub_len = LLVMBuildSub(builder, end, start, empty.as_ptr());
+            let with_data = LLVMBuildInsertValue(builder, LLVMGetUndef(str_ty), sub_data, 0, empty.as_ptr());
+            LLVMBuildRet(builder, LLVMBuildInsertValue(builder, with_data, sub_len, 1, empty.as_ptr()));
+
+            // Lengths first, so that memcmp never reads past the shorter one
+            let i1_ty = LLVMInt1TypeInContext(self.context);
+            let eq_fn = define(STR_EQ, i1_ty, &mut [str_ty, str_ty]);
+            let (a, b) = (LLVMGetParam(eq_fn, 0), LLVMGetParam(eq_fn, 1));
+            let len = LLVMBuildExtractValue(builder, a, 1, empty.as_ptr());
+            let other_len = LLVMBuildExtractValue(builder, b, 1, empty.as_ptr());
+            let same_len = LLVMBuildICmp(builder, LLVMIntPredicate::LLVMIntEQ, len, other_len, empty.as_ptr());
+            let bytes_block = LLVMAppendBasicBlockInContext(self.context, eq_fn, c"bytes".as_ptr());
+            let differ_block = LLVMAppendBasicBlockInContext(self.context, eq_fn, c"differ".as_ptr());
+            LLVMBuildCondBr(builder, same_len, bytes_block, differ_block);
+            LLVMPositionBuilderAtEnd(builder, differ_block);
+            LLVMBuildRet(builder, LLVMConstInt(i1_ty, 0, 0));
+            LLVMPositionBuilderAtEnd(builder, bytes_block);
+            let memcmp_name = CString::new("memcmp").unwrap();
+            let memcmp = LLVMGetNamedFunction(self.module, memcmp_name.as_ptr());
+            let mut args = [
+                LLVMBuildExtractValue(builder, a, 0, empty.as_ptr()),
+                LLVMBuildExtractValue(builder, b, 0, empty.as_ptr()),
+                len,
+            ];
+            let order = LLVMBuildCall2(builder, LLVMGlobalGetValueType(memcmp), memcmp, args.as_mut_ptr(), 3, empty.as_ptr());
+            let zero = LLVMConstInt(LLVMInt32TypeInContext(self.context), 0, 0);
+            LLVMBuildRet(builder, LLVMBuildICmp(builder, LLVMIntPredicate::LLVMIntEQ, order, zero, empty.as_ptr()));
+
+            LLVMDisposeBuilder(builder);
         }
     }
 
@@ -170,6 +393,8 @@ impl LLVMCodeGen {
                     let elem_ty = self.ir_type_to_llvm(elem);
                     LLVMVectorType(elem_ty, *lanes as u32)
                 }
+                IRType::Str => self.str_type(),
+                IRType::Vec(_) | IRType::Map(..) => LLVMPointerTypeInContext(self.context, 0),
             }
         }
     }
@@ -195,6 +420,27 @@ impl LLVMCodeGen {
             let llvm_func = LLVMAddFunction(self.module, name.as_ptr(), func_type);
             self.current_function = Some(llvm_func);
             
+            // #[extern_c] and #[no_mangle] functions keep their name and stay visible outside the object
+            if func.extern_c || func.no_mangle {
+                LLVMSetLinkage(llvm_func, llvm_sys::LLVMLinkage::LLVMExternalLinkage);
+                LLVMSetVisibility(llvm_func, llvm_sys::LLVMVisibility::LLVMDefaultVisibility);
+            }
+            
+            if let (Some(di), Some(loc)) = (self.di_builder, &func.debug_loc) {
+                let file = self.di_file(&loc.file);
+                let ty = LLVMDIBuilderCreateSubroutineType(di, file, ptr::null_mut(), 0, LLVMDIFlagZero);
+                let subprogram = LLVMDIBuilderCreateFunction(
+                    di, file,
+                    func.name.as_ptr().cast(), func.name.len(),
+                    func.name.as_ptr().cast(), func.name.len(),
+                    file, loc.line as u32, ty,
+                    0, 1, loc.line as u32, LLVMDIFlagZero, 0,
+                );
+                LLVMSetSubprogram(llvm_func, subprogram);
+                self.di_scope = Some(subprogram);
+                self.set_debug_location(loc);
+            }
+            
             // Clear mappings for new function
             self.value_map.clear();
             self.block_map.clear();
@@ -274,6 +520,28 @@ impl LLVMCodeGen {
                 }
             }
 
+            // Inlining hints and call frequency; calls to cold functions make
+            // the branches leading to them unlikely
+            let mut hints: Vec<&str> = Vec::new();
+            match func.inline {
+                InlineHint::Auto => {}
+                InlineHint::Always => hints.push("alwaysinline"),
+                InlineHint::Never => hints.push("noinline"),
+            }
+            if func.cold {
+                hints.push("cold");
+            }
+            if func.hot {
+                hints.push("hot");
+            }
+            for hint in hints {
+                let kind = llvm_sys::core::LLVMGetEnumAttributeKindForName(hint.as_ptr() as *const _, hint.len());
+                if kind != 0 {
+                    let attr = llvm_sys::core::LLVMCreateEnumAttribute(self.context, kind, 0);
+                    llvm_sys::core::LLVMAddAttributeAtIndex(llvm_func, u32::MAX, attr);
+                }
+            }
+
             // Add SIMD optimization hints if function is marked simd
             if func.simd {
                 // Add target-features for SIMD (AVX2 on x86_64, NEON on ARM)
@@ -337,8 +605,10 @@ impl LLVMCodeGen {
             // Store alloca_map for use in generate_instruction
             self.alloca_map = alloca_map;
             
-            // Generate code for each block
-            for (i, block) in func.blocks.iter().enumerate() {
+            // Generate code for each block, definitions before the uses they
+            // dominate
+            for i in block_order(func) {
+                let block = &func.blocks[i];
                 let llvm_block = self.block_map[&i];
                 LLVMPositionBuilderAtEnd(self.builder, llvm_block);
                 
@@ -355,12 +625,74 @@ impl LLVMCodeGen {
                     LLVMBuildUnreachable(self.builder);
                 }
             }
+            self.fill_phis(func)?;
             
             self.current_function = None;
+            if self.di_scope.take().is_some() {
+                // Later functions without debug info must not inherit this scope
+                LLVMSetCurrentDebugLocation2(self.builder, ptr::null_mut());
+            }
             Ok(())
         }
     }
 
+    /// Create the DWARF compile unit for `file` and mark the module as carrying debug info
+    unsafe fn init_debug_info(&mut self, file: &str) {
+        let di = LLVMCreateDIBuilder(self.module);
+        self.di_builder = Some(di);
+        let di_file = self.di_file(file);
+        let producer = "aethc 0.1.0";
+        LLVMDIBuilderCreateCompileUnit(
+            di,
+            LLVMDWARFSourceLanguage::LLVMDWARFSourceLanguageC,
+            di_file,
+            producer.as_ptr().cast(), producer.len(),
+            0,
+            ptr::null(), 0,
+            0,
+            ptr::null(), 0,
+            LLVMDWARFEmissionKind::LLVMDWARFEmissionKindFull,
+            0, 0, 0,
+            ptr::null(), 0,
+            ptr::null(), 0,
+        );
+        let key = "Debug Info Version";
+        let version = LLVMConstInt(LLVMInt32TypeInContext(self.context), LLVMDebugMetadataVersion() as u64, 0);
+        LLVMAddModuleFlag(
+            self.module,
+            llvm_sys::LLVMModuleFlagBehavior::LLVMModuleFlagBehaviorWarning,
+            key.as_ptr().cast(), key.len(),
+            LLVMValueAsMetadata(version),
+        );
+    }
+
+    /// DIFile for a source path, created on first use
+    unsafe fn di_file(&mut self, path: &str) -> LLVMMetadataRef {
+        if let Some(&file) = self.di_files.get(path) {
+            return file;
+        }
+        let path_ref = std::path::Path::new(path);
+        let name = path_ref.file_name().map_or(path.to_string(), |n| n.to_string_lossy().into_owned());
+        let dir = match path_ref.parent().filter(|p| !p.as_os_str().is_empty()) {
+            Some(dir) => dir.to_string_lossy().into_owned(),
+            None => ".".to_string(),
+        };
+        let di = self.di_builder.expect("debug info is enabled");
+        let file = LLVMDIBuilderCreateFile(di, name.as_ptr().cast(), name.len(), dir.as_ptr().cast(), dir.len());
+        self.di_files.insert(path.to_string(), file);
+        file
+    }
+
+    /// Attach `loc` to the instructions built from now on
+    unsafe fn set_debug_location(&mut self, loc: &SourceLoc) {
+        if let Some(scope) = self.di_scope {
+            let location = LLVMDIBuilderCreateDebugLocation(
+                self.context, loc.line as u32, loc.col as u32, scope, ptr::null_mut(),
+            );
+            LLVMSetCurrentDebugLocation2(self.builder, location);
+        }
+    }
+
     /// Generate LLVM IR for an instruction
     fn generate_instruction(&mut self, inst: &Instruction) -> Result<()> {
         unsafe {
@@ -474,6 +806,46 @@ impl LLVMCodeGen {
                     self.value_map.insert(*dest, result);
                 }
                 
+                Instruction::CheckedBinOp { dest, overflow_dest, op, left, right } => {
+                    let mut lhs = self.get_value(left)?;
+                    let mut rhs = self.get_value(right)?;
+                    let name = CString::new("").unwrap();
+
+                    // Widen the narrower operand; the check is signed, so sign-extend
+                    let lhs_bits = LLVMGetIntTypeWidth(LLVMTypeOf(lhs));
+                    let rhs_bits = LLVMGetIntTypeWidth(LLVMTypeOf(rhs));
+                    if lhs_bits > rhs_bits {
+                        rhs = LLVMBuildSExt(self.builder, rhs, LLVMTypeOf(lhs), name.as_ptr());
+                    } else if rhs_bits > lhs_bits {
+                        lhs = LLVMBuildSExt(self.builder, lhs, LLVMTypeOf(rhs), name.as_ptr());
+                    }
+                    let int_ty = LLVMTypeOf(lhs);
+
+                    let intrinsic = match op {
+                        BinOp::Add => "sadd",
+                        BinOp::Sub => "ssub",
+                        BinOp::Mul => "smul",
+                        _ => return Err(Error::CodeGen(format!("no checked form of '{}'", op))),
+                    };
+                    // {iN, i1} @llvm.sadd.with.overflow.iN(iN, iN)
+                    let intrinsic_name = CString::new(format!(
+                        "llvm.{}.with.overflow.i{}", intrinsic, LLVMGetIntTypeWidth(int_ty)
+                    )).unwrap();
+                    let mut fields = [int_ty, LLVMInt1TypeInContext(self.context)];
+                    let ret_ty = LLVMStructTypeInContext(self.context, fields.as_mut_ptr(), 2, 0);
+                    let fn_ty = LLVMFunctionType(ret_ty, [int_ty, int_ty].as_mut_ptr(), 2, 0);
+                    let mut func = LLVMGetNamedFunction(self.module, intrinsic_name.as_ptr());
+                    if func.is_null() {
+                        func = LLVMAddFunction(self.module, intrinsic_name.as_ptr(), fn_ty);
+                    }
+
+                    let pair = LLVMBuildCall2(self.builder, fn_ty, func, [lhs, rhs].as_mut_ptr(), 2, name.as_ptr());
+                    let result = LLVMBuildExtractValue(self.builder, pair, 0, name.as_ptr());
+                    let overflowed = LLVMBuildExtractValue(self.builder, pair, 1, name.as_ptr());
+                    self.value_map.insert(*dest, result);
+                    self.value_map.insert(*overflow_dest, overflowed);
+                }
+
                 Instruction::UnaryOp { dest, op, value } => {
                     let val = self.get_value(value)?;
                     let name = CString::new("").unwrap();
@@ -487,40 +859,7 @@ impl LLVMCodeGen {
                 
                 Instruction::Call { dest, func, args } => {
                     let func_name = CString::new(func.as_str()).unwrap();
-                    let mut callee = LLVMGetNamedFunction(self.module, func_name.as_ptr());
-                    
-                    if callee.is_null() {
-                        // Check if this looks like an enum variant constructor (Type_Variant pattern)
-                        if func.contains('_') {
-                            // Auto-declare as enum variant constructor
-                            // Returns i64 (tag + optional data pointer packed)
-                            let i64_ty = LLVMInt64TypeInContext(self.context);
-                            let i8_ptr_ty = LLVMPointerTypeInContext(self.context, 0);
-                            
-                            // Create function type based on number of args
-                            let func_ty = if args.is_empty() {
-                                LLVMFunctionType(i64_ty, std::ptr::null_mut(), 0, 0)
-                            } else {
-                                // For variants with data, accept pointer args
-                                let mut param_types: Vec<LLVMTypeRef> = args.iter()
-                                    .map(|_| i8_ptr_ty)
-                                    .collect();
-                                LLVMFunctionType(i64_ty, param_types.as_mut_ptr(), args.len() as u32, 0)
-                            };
-                            
-                            callee = LLVMAddFunction(self.module, func_name.as_ptr(), func_ty);
-                            
-                            // Create a simple implementation that returns a hash-based tag
-                            let entry = LLVMAppendBasicBlockInContext(self.context, callee, b"entry\0".as_ptr() as *const _);
-                            let builder = LLVMCreateBuilderInContext(self.context);
-                            LLVMPositionBuilderAtEnd(builder, entry);
-                            
-                            let hash_value = func.bytes().fold(0u64, |acc, b| acc.wrapping_mul(31).wrapping_add(b as u64));
-                            let tag = LLVMConstInt(i64_ty, hash_value % 10000, 0);
-                            LLVMBuildRet(builder, tag);
-                            LLVMDisposeBuilder(builder);
-                        }
-                    }
+                    let callee = LLVMGetNamedFunction(self.module, func_name.as_ptr());
                     
                     if callee.is_null() {
                         return Err(Error::CodeGen(format!("Unknown function: {}", func)));
@@ -605,6 +944,35 @@ impl LLVMCodeGen {
                     }
                 }
                 
+                Instruction::CallIndirect { dest, callee, ty, args } => {
+                    let IRType::Function { params, ret } = ty else {
+                        return Err(Error::CodeGen(format!("indirect call through a value of type {:?}", ty)));
+                    };
+                    let callee = self.get_value(callee)?;
+                    let mut llvm_args: Vec<_> = args.iter()
+                        .map(|a| self.get_value(a))
+                        .collect::<Result<Vec<_>>>()?;
+                    let mut param_types: Vec<_> = params.iter().map(|p| self.ir_type_to_llvm(p)).collect();
+                    let func_ty = LLVMFunctionType(
+                        self.ir_type_to_llvm(ret),
+                        param_types.as_mut_ptr(),
+                        param_types.len() as u32,
+                        0,
+                    );
+                    let name = if **ret == IRType::Void { CString::new("").unwrap() } else { CString::new("call").unwrap() };
+                    let result = LLVMBuildCall2(
+                        self.builder,
+                        func_ty,
+                        callee,
+                        llvm_args.as_mut_ptr(),
+                        llvm_args.len() as u32,
+                        name.as_ptr()
+                    );
+                    if let Some(d) = dest.filter(|_| **ret != IRType::Void) {
+                        self.value_map.insert(d, result);
+                    }
+                }
+
                 Instruction::Alloca { dest, ty } => {
                     let llvm_ty = self.ir_type_to_llvm(ty);
                     let name = CString::new("").unwrap();
@@ -612,16 +980,19 @@ impl LLVMCodeGen {
                     self.value_map.insert(*dest, ptr);
                 }
                 
-                Instruction::Load { dest, ptr, ty } => {
+                Instruction::Load { dest, ptr, ty, volatile } => {
                     let ptr_val = self.get_value(ptr)?;
                     let name = CString::new("").unwrap();
                     // Use the actual element type from IR
                     let elem_ty = self.ir_type_to_llvm(ty);
                     let result = LLVMBuildLoad2(self.builder, elem_ty, ptr_val, name.as_ptr());
+                    if *volatile {
+                        LLVMSetVolatile(result, 1);
+                    }
                     self.value_map.insert(*dest, result);
                 }
 
-                Instruction::Store { ptr, value } => {
+                Instruction::Store { ptr, value, volatile } => {
                     let mut ptr_val = self.get_value(ptr)?;
                     let store_val = self.get_value(value)?;
                     // Ensure ptr_val is a pointer type
@@ -633,7 +1004,9 @@ impl LLVMCodeGen {
                         ptr_val = LLVMBuildIntToPtr(self.builder, ptr_val, ptr_type, name.as_ptr());
                     }
                     let store_inst = LLVMBuildStore(self.builder, store_val, ptr_val);
-                    let _ = store_inst; // Suppress unused warning
+                    if *volatile {
+                        LLVMSetVolatile(store_inst, 1);
+                    }
                 }
                 
                 Instruction::GetElementPtr { dest, ptr, index, elem_ty } => {
@@ -642,13 +1015,30 @@ impl LLVMCodeGen {
                     
                     // Check if this is struct field access or pointer arithmetic
                     match elem_ty {
-                        IRType::Struct(_struct_name) => {
+                        IRType::Struct(struct_name) if self.union_fields.contains_key(struct_name) => {
+                            // Union fields all live at offset 0: reinterpret the base pointer
+                            let field_idx = if let Value::Constant(Constant::Int(i)) = index {
+                                *i as usize
+                            } else {
+                                0
+                            };
+                            let field_ty = self.union_fields[struct_name].get(field_idx).cloned()
+                                .unwrap_or(IRType::I8);
+                            let field_ptr_ty = LLVMPointerType(self.ir_type_to_llvm(&field_ty), 0);
+                            let result = LLVMBuildBitCast(self.builder, ptr_val, field_ptr_ty, name.as_ptr());
+                            self.value_map.insert(*dest, result);
+                        }
+                        IRType::Struct(struct_name) => {
                             // Struct field access: use LLVMBuildStructGEP2
                             let field_idx = if let Value::Constant(Constant::Int(i)) = index {
                                 *i as u32
                             } else {
                                 0
                             };
+                            // Past any explicit padding elements before it
+                            let field_idx = self.field_elements.get(struct_name)
+                                .and_then(|elements| elements.get(field_idx as usize).copied())
+                                .unwrap_or(field_idx);
                             let struct_ty = self.ir_type_to_llvm(elem_ty);
                             let result = LLVMBuildStructGEP2(
                                 self.builder,
@@ -678,25 +1068,18 @@ impl LLVMCodeGen {
                 }
                 
                 Instruction::Phi { dest, incoming } => {
-                    // Create empty Phi node first, incoming values will be added by a second pass
-                    // This is needed because incoming blocks may not have been generated yet
-                    if !incoming.is_empty() {
-                        // Use i32 as default type for now (most enum/int values)
-                        let i32_ty = LLVMInt32TypeInContext(self.context);
-                        let name = CString::new("").unwrap();
-                        let phi = LLVMBuildPhi(self.builder, i32_ty, name.as_ptr());
-                        self.value_map.insert(*dest, phi);
-                        
-                        // TODO: Implement proper two-phase Phi filling after all blocks generated
-                        // For now, add dummy incoming to make Phi valid (will have incorrect values)
-                        for (_, block_id) in incoming {
-                            let llvm_block = self.block_map[&block_id.0];
-                            let dummy_val = LLVMConstInt(i32_ty, 0, 0);
-                            let mut values = [dummy_val];
-                            let mut blocks = [llvm_block];
-                            LLVMAddIncoming(phi, values.as_mut_ptr(), blocks.as_mut_ptr(), 1);
-                        }
-                    }
+                    // Incoming values may come from blocks not generated yet,
+                    // so they are added by `fill_phis`
+                    let ty = self.phi_type(incoming);
+                    let name = CString::new("").unwrap();
+                    let phi = LLVMBuildPhi(self.builder, ty, name.as_ptr());
+                    self.value_map.insert(*dest, phi);
+                    let block = LLVMGetInsertBlock(self.builder);
+                    let index = self.block_map.iter()
+                        .find(|(_, &b)| b == block)
+                        .map(|(&i, _)| i)
+                        .unwrap_or(0);
+                    self.pending_phis.push((phi, index, incoming.clone()));
                 }
                 
                 Instruction::Cast { dest, value, ty } => {
@@ -744,6 +1127,8 @@ impl LLVMCodeGen {
                     self.value_map.insert(*dest, result);
                 }
                 
+                Instruction::DebugLoc(loc) => self.set_debug_location(loc),
+
                 Instruction::InlineAsm { template, operands } => {
                     // Build LLVM inline assembly
                     // Format: constraints and template for LLVM
@@ -775,16 +1160,15 @@ impl LLVMCodeGen {
                             }
                             IRAsmOperandKind::InOut => {
                                 if let Some(reg) = op.output {
-                                    let constraint = if !op.constraint.starts_with('+') {
-                                        format!("+{}", op.constraint)
-                                    } else {
-                                        op.constraint.clone()
-                                    };
-                                    output_constraints.push(constraint);
+                                    // LLVM has no `+` modifier: emit an output plus an input
+                                    // tied to it by index
+                                    let tied = output_constraints.len();
+                                    output_constraints.push(format!("={}", op.constraint.trim_start_matches('+')));
                                     output_regs.push(reg);
                                     if let Some(ref val) = op.input {
                                         let llvm_val = self.get_value(val)?;
                                         inputs.push(llvm_val);
+                                        input_constraints.push(tied.to_string());
                                     }
                                 }
                             }
@@ -938,6 +1322,29 @@ impl LLVMCodeGen {
                     LLVMBuildCondBr(self.builder, cond_val, then_block, else_block);
                 }
                 
+                Terminator::Switch { value, cases, default } => {
+                    let mut switch_val = self.get_value(value)?;
+                    let name = CString::new("tag").unwrap();
+                    // Enums dispatch on their leading i32 tag
+                    match LLVMGetTypeKind(LLVMTypeOf(switch_val)) {
+                        llvm_sys::LLVMTypeKind::LLVMPointerTypeKind => {
+                            let i32_ty = LLVMInt32TypeInContext(self.context);
+                            switch_val = LLVMBuildLoad2(self.builder, i32_ty, switch_val, name.as_ptr());
+                        }
+                        llvm_sys::LLVMTypeKind::LLVMStructTypeKind => {
+                            switch_val = LLVMBuildExtractValue(self.builder, switch_val, 0, name.as_ptr());
+                        }
+                        _ => {}
+                    }
+                    let switch_ty = LLVMTypeOf(switch_val);
+                    let default_block = self.block_map[&default.0];
+                    let switch = LLVMBuildSwitch(self.builder, switch_val, default_block, cases.len() as u32);
+                    for (case, target) in cases {
+                        let case_val = LLVMConstInt(switch_ty, *case as u64, 1);
+                        LLVMAddCase(switch, case_val, self.block_map[&target.0]);
+                    }
+                }
+                
                 Terminator::Unreachable => {
                     LLVMBuildUnreachable(self.builder);
                 }
@@ -947,6 +1354,71 @@ impl LLVMCodeGen {
     }
 
     /// Get LLVM value from IR value
+    /// Type of a phi: that of an input generated already (blocks are visited
+    /// in reverse postorder, so one from outside a loop is), else of a constant
+    fn phi_type(&self, incoming: &[(Value, BlockId)]) -> LLVMTypeRef {
+        unsafe {
+            let generated = incoming.iter().find_map(|(value, _)| match value {
+                Value::Register(reg) => self.value_map.get(reg).map(|&v| LLVMTypeOf(v)),
+                Value::Parameter(i) => self.value_map.get(&Register(1000 + *i)).map(|&v| LLVMTypeOf(v)),
+                _ => None,
+            });
+            let constant = || incoming.iter().find_map(|(value, _)| match value {
+                Value::Constant(Constant::Bool(_)) => Some(LLVMInt1TypeInContext(self.context)),
+                Value::Constant(Constant::Float(_)) => Some(LLVMDoubleTypeInContext(self.context)),
+                Value::Constant(Constant::Null | Constant::String(_)) => {
+                    Some(LLVMPointerType(LLVMInt8TypeInContext(self.context), 0))
+                }
+                _ => None,
+            });
+            generated.or_else(constant).unwrap_or_else(|| LLVMInt64TypeInContext(self.context))
+        }
+    }
+
+    /// Add the incoming values of the current function's phis: one per edge
+    /// into the phi's block, undefined where the phi names no value
+    fn fill_phis(&mut self, func: &IRFunction) -> Result<()> {
+        unsafe {
+            for (phi, block, incoming) in std::mem::take(&mut self.pending_phis) {
+                let ty = LLVMTypeOf(phi);
+                for (p, pred) in func.blocks.iter().enumerate() {
+                    let edges = pred.terminator.iter()
+                        .flat_map(|t| t.successors())
+                        .filter(|t| t.0 == block)
+                        .count();
+                    if edges == 0 {
+                        continue;
+                    }
+                    let llvm_pred = self.block_map[&p];
+                    let term = LLVMGetBasicBlockTerminator(llvm_pred);
+                    if term.is_null() {
+                        LLVMPositionBuilderAtEnd(self.builder, llvm_pred);
+                    } else {
+                        LLVMPositionBuilderBefore(self.builder, term);
+                    }
+                    let mut value = match incoming.iter().find(|(_, from)| from.0 == p) {
+                        Some((v, _)) => self.get_value(v).unwrap_or_else(|_| LLVMGetUndef(ty)),
+                        None => LLVMGetUndef(ty),
+                    };
+                    let value_ty = LLVMTypeOf(value);
+                    if value_ty != ty
+                        && LLVMGetTypeKind(value_ty) == llvm_sys::LLVMTypeKind::LLVMIntegerTypeKind
+                        && LLVMGetTypeKind(ty) == llvm_sys::LLVMTypeKind::LLVMIntegerTypeKind
+                    {
+                        let name = CString::new("").unwrap();
+                        value = LLVMBuildIntCast2(self.builder, value, ty, 1, name.as_ptr());
+                    }
+                    for _ in 0..edges {
+                        let mut values = [value];
+                        let mut blocks = [llvm_pred];
+                        LLVMAddIncoming(phi, values.as_mut_ptr(), blocks.as_mut_ptr(), 1);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn get_value(&self, val: &Value) -> Result<LLVMValueRef> {
         unsafe {
             match val {
@@ -986,6 +1458,14 @@ impl LLVMCodeGen {
                             let name_c = CString::new("str").unwrap();
                             Ok(LLVMBuildGlobalStringPtr(self.builder, s_c.as_ptr(), name_c.as_ptr()))
                         }
+                        Constant::Str(s) => {
+                            let s_clean: String = s.chars().filter(|&c| c != '\0').collect();
+                            let i64_ty = LLVMInt64TypeInContext(self.context);
+                            let len = LLVMConstInt(i64_ty, s_clean.len() as u64, 0);
+                            let s_c = CString::new(s_clean).unwrap();
+                            let data = LLVMBuildGlobalString(self.builder, s_c.as_ptr(), c"str".as_ptr());
+                            Ok(LLVMConstStructInContext(self.context, [data, len].as_mut_ptr(), 2, 0))
+                        }
                         Constant::Null => {
                             let ptr_ty = LLVMPointerType(LLVMInt8TypeInContext(self.context), 0);
                             Ok(LLVMConstNull(ptr_ty))
@@ -999,33 +1479,18 @@ impl LLVMCodeGen {
                         .ok_or_else(|| Error::CodeGen(format!("Unknown parameter: {}", i)))
                 }
                 Value::Global(name) => {
+                    // Declared globals evaluate to their address; functions
+                    // (e.g. closures) evaluate to the function pointer
                     let name_c = CString::new(name.as_str()).unwrap();
-                    let mut global = LLVMGetNamedGlobal(self.module, name_c.as_ptr());
-                    if global.is_null() {
-                        // Check if this looks like an enum variant (Type_Variant pattern)
-                        if name.contains('_') {
-                            // Auto-declare as i32 constant (enum discriminant)
-                            // Use a simple hash of the name as the value
-                            let hash_value = name.bytes().fold(0u64, |acc, b| acc.wrapping_mul(31).wrapping_add(b as u64));
-                            let i32_ty = LLVMInt32TypeInContext(self.context);
-                            global = LLVMAddGlobal(self.module, i32_ty, name_c.as_ptr());
-                            LLVMSetInitializer(global, LLVMConstInt(i32_ty, hash_value % 10000, 0));
-                            LLVMSetGlobalConstant(global, 1);
-                            LLVMSetLinkage(global, llvm_sys::LLVMLinkage::LLVMPrivateLinkage);
-                        }
+                    let global = LLVMGetNamedGlobal(self.module, name_c.as_ptr());
+                    if !global.is_null() {
+                        return Ok(global);
                     }
-                    if global.is_null() {
-                        Err(Error::CodeGen(format!("Unknown global: {}", name)))
-                    } else {
-                        // For enum variants (auto-declared), load the value
-                        if name.contains('_') {
-                            let i32_ty = LLVMInt32TypeInContext(self.context);
-                            let load_name = CString::new("load").unwrap();
-                            Ok(LLVMBuildLoad2(self.builder, i32_ty, global, load_name.as_ptr()))
-                        } else {
-                            Ok(global)
-                        }
+                    let func = LLVMGetNamedFunction(self.module, name_c.as_ptr());
+                    if !func.is_null() {
+                        return Ok(func);
                     }
+                    Err(Error::CodeGen(format!("Unknown global: {}", name)))
                 }
                 Value::Unit => {
                     // Unit type represented as i32 0 for compatibility with enum types
@@ -1036,6 +1501,139 @@ impl LLVMCodeGen {
         }
     }
 
+    /// Declare a global variable with its constant initializer
+    fn declare_global(&mut self, global: &IRGlobal) {
+        unsafe {
+            let name_c = CString::new(global.name.as_str()).unwrap();
+            let ty = self.ir_type_to_llvm(&global.ty);
+            let llvm_global = LLVMAddGlobal(self.module, ty, name_c.as_ptr());
+
+            if global.is_extern {
+                // Defined elsewhere: external linkage, no initializer
+                LLVMSetLinkage(llvm_global, llvm_sys::LLVMLinkage::LLVMExternalLinkage);
+                return;
+            }
+
+            let init = match &global.init {
+                Some(Constant::Int(n)) => LLVMConstInt(ty, *n as u64, 1),
+                Some(Constant::Float(f)) => LLVMConstReal(ty, *f),
+                Some(Constant::Bool(b)) => LLVMConstInt(ty, *b as u64, 0),
+                Some(Constant::String(s)) => self.private_string(&global.name, s).0,
+                Some(Constant::Str(s)) => {
+                    // The bytes' address and length
+                    let (data, len) = self.private_string(&global.name, s);
+                    let i64_ty = LLVMInt64TypeInContext(self.context);
+                    let mut fields = [data, LLVMConstInt(i64_ty, len as u64, 0)];
+                    LLVMConstStructInContext(self.context, fields.as_mut_ptr(), 2, 0)
+                }
+                Some(Constant::Null) | None => LLVMConstNull(ty),
+            };
+            LLVMSetInitializer(llvm_global, init);
+            if !global.is_mut {
+                LLVMSetGlobalConstant(llvm_global, 1);
+            }
+        }
+    }
+
+    /// A private constant `[N x i8]` global named after `owner` holding the
+    /// bytes of `s`, NUL-terminated, and the number of bytes before the NUL
+    unsafe fn private_string(&mut self, owner: &str, s: &str) -> (LLVMValueRef, usize) {
+        let s_clean: String = s.chars().filter(|&c| c != '\0').collect();
+        let bytes = LLVMConstStringInContext2(
+            self.context,
+            s_clean.as_ptr() as *const _,
+            s_clean.len(),
+            0, // null terminate
+        );
+        let str_name = CString::new(format!("{}.str", owner)).unwrap();
+        let str_global = LLVMAddGlobal(self.module, LLVMTypeOf(bytes), str_name.as_ptr());
+        LLVMSetInitializer(str_global, bytes);
+        LLVMSetGlobalConstant(str_global, 1);
+        LLVMSetLinkage(str_global, llvm_sys::LLVMLinkage::LLVMPrivateLinkage);
+        (str_global, s_clean.len())
+    }
+
+    /// Define `arg_count()` and `args()` over the globals filled in by the main wrapper
+    unsafe fn declare_arg_builtins(&mut self) {
+        let i32_ty = LLVMInt32TypeInContext(self.context);
+        let ptr_ty = LLVMPointerTypeInContext(self.context, 0);
+        // Bitcode modules share one copy, which the linked `main` fills in
+        let linkage = if self.lto {
+            llvm_sys::LLVMLinkage::LLVMLinkOnceODRLinkage
+        } else {
+            llvm_sys::LLVMLinkage::LLVMInternalLinkage
+        };
+        for (global_name, func_name, ty) in [("aether_argc", "arg_count", i32_ty), ("aether_argv", "args", ptr_ty)] {
+            let global_c = CString::new(global_name).unwrap();
+            let global = LLVMAddGlobal(self.module, ty, global_c.as_ptr());
+            LLVMSetInitializer(global, LLVMConstNull(ty));
+            LLVMSetLinkage(global, linkage);
+
+            let func_c = CString::new(func_name).unwrap();
+            let func_ty = LLVMFunctionType(ty, ptr::null_mut(), 0, 0);
+            let func = LLVMAddFunction(self.module, func_c.as_ptr(), func_ty);
+            LLVMSetLinkage(func, linkage);
+
+            let entry = LLVMAppendBasicBlockInContext(self.context, func, c"entry".as_ptr());
+            let builder = LLVMCreateBuilderInContext(self.context);
+            LLVMPositionBuilderAtEnd(builder, entry);
+            let value = LLVMBuildLoad2(builder, ty, global, c"".as_ptr());
+            LLVMBuildRet(builder, value);
+            LLVMDisposeBuilder(builder);
+        }
+    }
+
+    /// Rename the user's main to `aether_main` and define the real
+    /// `i32 main(i32 argc, ptr argv)`, whose exit status is main's result (0 for unit)
+    unsafe fn emit_main_wrapper(&mut self, user_main: &IRFunction) {
+        let main_c = CString::new("main").unwrap();
+        let user_fn = LLVMGetNamedFunction(self.module, main_c.as_ptr());
+        if user_fn.is_null() {
+            return;
+        }
+        let renamed = "aether_main";
+        LLVMSetValueName2(user_fn, renamed.as_ptr() as *const _, renamed.len());
+
+        let i32_ty = LLVMInt32TypeInContext(self.context);
+        let ptr_ty = LLVMPointerTypeInContext(self.context, 0);
+        let mut param_types = [i32_ty, ptr_ty];
+        let main_ty = LLVMFunctionType(i32_ty, param_types.as_mut_ptr(), 2, 0);
+        let main_fn = LLVMAddFunction(self.module, main_c.as_ptr(), main_ty);
+
+        let entry = LLVMAppendBasicBlockInContext(self.context, main_fn, c"entry".as_ptr());
+        let builder = LLVMCreateBuilderInContext(self.context);
+        LLVMPositionBuilderAtEnd(builder, entry);
+        let empty = c"".as_ptr();
+
+        let argc = LLVMGetParam(main_fn, 0);
+        let argv = LLVMGetParam(main_fn, 1);
+        let argc_c = CString::new("aether_argc").unwrap();
+        let argv_c = CString::new("aether_argv").unwrap();
+        LLVMBuildStore(builder, argc, LLVMGetNamedGlobal(self.module, argc_c.as_ptr()));
+        LLVMBuildStore(builder, argv, LLVMGetNamedGlobal(self.module, argv_c.as_ptr()));
+
+        let mut args = match user_main.params.len() {
+            2 => vec![argc, argv],
+            1 => vec![argv],
+            _ => Vec::new(),
+        };
+        let result = LLVMBuildCall2(
+            builder,
+            LLVMGlobalGetValueType(user_fn),
+            user_fn,
+            args.as_mut_ptr(),
+            args.len() as u32,
+            empty,
+        );
+        let status = if user_main.ret_type == IRType::Void {
+            LLVMConstInt(i32_ty, 0, 0)
+        } else {
+            LLVMBuildIntCast2(builder, result, i32_ty, 1, empty)
+        };
+        LLVMBuildRet(builder, status);
+        LLVMDisposeBuilder(builder);
+    }
+
     /// Verify the generated module
     fn verify_module(&self) -> Result<()> {
         unsafe {
@@ -1102,6 +1700,20 @@ impl LLVMCodeGen {
             // Get data layout
             let data_layout = LLVMCreateTargetDataLayout(target_machine);
             LLVMSetModuleDataLayout(self.module, data_layout);
+
+            if self.lto {
+                if let Err(e) = self.run_lto_passes(target_machine) {
+                    LLVMDisposeTargetMachine(target_machine);
+                    return Err(e);
+                }
+            }
+
+            if !self.sanitizers.is_empty() {
+                if let Err(e) = self.run_sanitizer_passes(target_machine) {
+                    LLVMDisposeTargetMachine(target_machine);
+                    return Err(e);
+                }
+            }
             
             // Emit to memory buffer
             let mut mem_buf: LLVMMemoryBufferRef = ptr::null_mut();
@@ -1139,6 +1751,179 @@ impl LLVMCodeGen {
         }
     }
 
+    /// Declare a union as a struct holding its most aligned field, padded to the union size
+    unsafe fn declare_union(&mut self, module: &IRModule, ir_union: &IRStruct) {
+        let name_c = CString::new(ir_union.name.as_str()).unwrap();
+        let union_ty = LLVMStructCreateNamed(self.context, name_c.as_ptr());
+        self.union_fields.insert(
+            ir_union.name.clone(),
+            ir_union.fields.iter().map(|(_, ty)| ty.clone()).collect(),
+        );
+
+        let layout = module.struct_layout(&ir_union.name);
+        let widest = ir_union.fields.iter()
+            .filter_map(|(_, ty)| module.layout_of(ty).map(|l| (ty, l)))
+            .max_by_key(|(_, l)| (l.align, l.size));
+
+        let mut body = Vec::new();
+        let mut used = 0;
+        if let Some((ty, field_layout)) = widest {
+            body.push(self.ir_type_to_llvm(ty));
+            used = field_layout.size;
+        }
+        let total = layout.map(|l| l.size).unwrap_or(used);
+        if total > used {
+            body.push(LLVMArrayType2(LLVMInt8TypeInContext(self.context), (total - used) as u64));
+        }
+        let packed = if ir_union.repr == StructRepr::Packed { 1 } else { 0 };
+        LLVMStructSetBody(union_ty, body.as_mut_ptr(), body.len() as u32, packed);
+    }
+
+    /// Declare a struct with its fields at the offsets `IRModule::struct_layout`
+    /// gives them: padding the layout engine leaves becomes explicit `[N x i8]`
+    /// elements, so LLVM never pads on its own
+    unsafe fn declare_struct(&mut self, module: &IRModule, ir_struct: &IRStruct) {
+        let name_c = CString::new(ir_struct.name.as_str()).unwrap();
+        let struct_ty = LLVMStructCreateNamed(self.context, name_c.as_ptr());
+        let i8_ty = LLVMInt8TypeInContext(self.context);
+        let mut body: Vec<LLVMTypeRef> = Vec::new();
+        let layouts = ir_struct.fields.iter().map(|(_, ty)| module.layout_of(ty)).collect::<Option<Vec<_>>>();
+        match (module.struct_layout(&ir_struct.name), layouts) {
+            (Some(layout), Some(fields)) => {
+                let mut elements = Vec::new();
+                let mut end = 0;
+                for (((_, ty), offset), field) in ir_struct.fields.iter().zip(&layout.offsets).zip(&fields) {
+                    if *offset > end {
+                        body.push(LLVMArrayType2(i8_ty, (*offset - end) as u64));
+                    }
+                    elements.push(body.len() as u32);
+                    body.push(self.ir_type_to_llvm(ty));
+                    end = offset + field.size;
+                }
+                if layout.size > end {
+                    body.push(LLVMArrayType2(i8_ty, (layout.size - end) as u64));
+                }
+                if body.len() != ir_struct.fields.len() {
+                    self.field_elements.insert(ir_struct.name.clone(), elements);
+                }
+            }
+            // No fixed layout (e.g. erased generics): LLVM lays the fields out
+            _ => body.extend(ir_struct.fields.iter().map(|(_, ty)| self.ir_type_to_llvm(ty))),
+        }
+        // Every gap is explicit, so packing only drops the trailing alignment
+        // LLVM would add, which the layout engine already accounts for
+        let packed = if ir_struct.repr == StructRepr::Packed { 1 } else { 0 };
+        LLVMStructSetBody(struct_ty, body.as_mut_ptr(), body.len() as u32, packed);
+    }
+
+    /// Define the `Enum_Variant` constructors: each heap-allocates the tagged
+    /// union laid out by `IRModule::layout_of`, stores the variant's
+    /// discriminant in the leading tag and its payload behind it
+    unsafe fn define_enum_constructors(&mut self, module: &IRModule) {
+        let ptr_ty = LLVMPointerTypeInContext(self.context, 0);
+        let i8_ty = LLVMInt8TypeInContext(self.context);
+        let i64_ty = LLVMInt64TypeInContext(self.context);
+        let malloc_c = CString::new("malloc").unwrap();
+        let mut malloc_params = [i64_ty];
+        let malloc_ty = LLVMFunctionType(ptr_ty, malloc_params.as_mut_ptr(), 1, 0);
+        let mut malloc = LLVMGetNamedFunction(self.module, malloc_c.as_ptr());
+        if malloc.is_null() {
+            malloc = LLVMAddFunction(self.module, malloc_c.as_ptr(), malloc_ty);
+        }
+        let empty = c"".as_ptr();
+
+        for e in &module.enums {
+            let size = module.layout_of(&IRType::Struct(e.name.clone())).map_or(8, |l| l.size.max(1));
+            let tag_ty = self.ir_type_to_llvm(&e.tag);
+            for variant in &e.variants {
+                let name_c = CString::new(format!("{}_{}", e.name, variant.name)).unwrap();
+                if !LLVMGetNamedFunction(self.module, name_c.as_ptr()).is_null() {
+                    continue;
+                }
+                let mut param_types: Vec<LLVMTypeRef> = variant.fields.iter().map(|t| self.ir_type_to_llvm(t)).collect();
+                let func_ty = LLVMFunctionType(ptr_ty, param_types.as_mut_ptr(), param_types.len() as u32, 0);
+                let func = LLVMAddFunction(self.module, name_c.as_ptr(), func_ty);
+                LLVMSetLinkage(func, llvm_sys::LLVMLinkage::LLVMInternalLinkage);
+
+                let entry = LLVMAppendBasicBlockInContext(self.context, func, c"entry".as_ptr());
+                let builder = LLVMCreateBuilderInContext(self.context);
+                LLVMPositionBuilderAtEnd(builder, entry);
+                let mut args = [LLVMConstInt(i64_ty, size as u64, 0)];
+                let result = LLVMBuildCall2(builder, malloc_ty, malloc, args.as_mut_ptr(), 1, empty);
+                LLVMBuildStore(builder, LLVMConstInt(tag_ty, variant.discriminant as u64, 1), result);
+                let fields = module.variant_fields(&e.name, &variant.name).unwrap_or_default();
+                for (i, (offset, _)) in fields.iter().enumerate() {
+                    let mut index = [LLVMConstInt(i64_ty, *offset as u64, 0)];
+                    let addr = LLVMBuildGEP2(builder, i8_ty, result, index.as_mut_ptr(), 1, empty);
+                    LLVMBuildStore(builder, LLVMGetParam(func, i as u32), addr);
+                }
+                LLVMBuildRet(builder, result);
+                LLVMDisposeBuilder(builder);
+            }
+        }
+    }
+
+    /// Optimize the linked module as a whole
+    unsafe fn run_lto_passes(&self, target_machine: LLVMTargetMachineRef) -> Result<()> {
+        let pipeline = CString::new("lto<O2>").unwrap();
+        let options = LLVMCreatePassBuilderOptions();
+        let error = LLVMRunPasses(self.module, pipeline.as_ptr(), target_machine, options);
+        LLVMDisposePassBuilderOptions(options);
+
+        if !error.is_null() {
+            let msg_ptr = llvm_sys::error::LLVMGetErrorMessage(error);
+            let msg = CStr::from_ptr(msg_ptr).to_string_lossy().to_string();
+            llvm_sys::error::LLVMDisposeErrorMessage(msg_ptr);
+            return Err(Error::CodeGen(format!("LTO passes failed: {}", msg)));
+        }
+        Ok(())
+    }
+
+    /// Mark defined functions for instrumentation and run the sanitizer passes.
+    /// UBSan checks are inserted by C frontends, so `undefined` has no IR pass.
+    unsafe fn run_sanitizer_passes(&self, target_machine: LLVMTargetMachineRef) -> Result<()> {
+        let mut passes = Vec::new();
+        let mut attrs = Vec::new();
+        if self.sanitizers.contains(SanitizerFlags::ADDRESS) {
+            passes.push("asan");
+            attrs.push("sanitize_address");
+        }
+        if self.sanitizers.contains(SanitizerFlags::MEMORY) {
+            passes.push("msan");
+            attrs.push("sanitize_memory");
+        }
+        if passes.is_empty() {
+            return Ok(());
+        }
+
+        // The instrumentation passes skip functions without the sanitize attribute
+        let mut func = LLVMGetFirstFunction(self.module);
+        while !func.is_null() {
+            if LLVMCountBasicBlocks(func) > 0 {
+                for attr in &attrs {
+                    let kind = LLVMGetEnumAttributeKindForName(attr.as_ptr() as *const _, attr.len());
+                    let attr_ref = LLVMCreateEnumAttribute(self.context, kind, 0);
+                    // Function index is -1 (LLVMAttributeFunctionIndex)
+                    LLVMAddAttributeAtIndex(func, u32::MAX, attr_ref);
+                }
+            }
+            func = LLVMGetNextFunction(func);
+        }
+
+        let pipeline = CString::new(passes.join(",")).unwrap();
+        let options = LLVMCreatePassBuilderOptions();
+        let error = LLVMRunPasses(self.module, pipeline.as_ptr(), target_machine, options);
+        LLVMDisposePassBuilderOptions(options);
+
+        if !error.is_null() {
+            let msg_ptr = llvm_sys::error::LLVMGetErrorMessage(error);
+            let msg = CStr::from_ptr(msg_ptr).to_string_lossy().to_string();
+            llvm_sys::error::LLVMDisposeErrorMessage(msg_ptr);
+            return Err(Error::CodeGen(format!("Sanitizer instrumentation failed: {}", msg)));
+        }
+        Ok(())
+    }
+
     /// Print LLVM IR to string (for debugging)
     pub fn print_ir(&self) -> String {
         unsafe {
@@ -1153,13 +1938,41 @@ impl LLVMCodeGen {
 impl CodeGen for LLVMCodeGen {
     fn generate(&mut self, module: &IRModule) -> Result<Vec<u8>> {
         Self::init_targets();
+        self.lower_module(module)?;
+        if self.strip {
+            self.strip_module();
+        }
         
+        // Emit object code
+        self.emit_object()
+    }
+    
+    fn target_triple(&self) -> &str {
+        &self.target_triple
+    }
+    
+    fn name(&self) -> &str {
+        "LLVM"
+    }
+}
+
+impl LLVMCodeGen {
+    /// Translate `module` into the LLVM module and verify it
+    fn lower_module(&mut self, module: &IRModule) -> Result<()> {
+        // LLVM structs get no bit fields: each run is one integer, accessed by mask
+        let lowered = crate::backend::bitfield::lower_bitfields(module);
+        let module = lowered.as_ref().unwrap_or(module);
+
         // Set module name
         unsafe {
             let name = CString::new(module.name.as_str()).unwrap();
             LLVMSetModuleIdentifier(self.module, name.as_ptr(), module.name.len());
         }
         
+        if let Some(loc) = module.functions.iter().find_map(|f| f.debug_loc.as_ref()) {
+            unsafe { self.init_debug_info(&loc.file); }
+        }
+        
         // Declare extern functions first
         for ext in &module.externs {
             unsafe {
@@ -1192,52 +2005,172 @@ impl CodeGen for LLVMCodeGen {
             unsafe {
                 let name_c = CString::new(ir_struct.name.as_str()).unwrap();
                 let existing = LLVMGetTypeByName2(self.context, name_c.as_ptr());
-                if existing.is_null() {
-                    // Create struct type
-                    let struct_ty = LLVMStructCreateNamed(self.context, name_c.as_ptr());
-                    // Convert field types
-                    let mut field_types: Vec<LLVMTypeRef> = ir_struct.fields.iter()
-                        .map(|(_, ty)| self.ir_type_to_llvm(ty))
-                        .collect();
-                    // Set struct body with appropriate packing based on repr
-                    let packed = match ir_struct.repr {
-                        StructRepr::Packed => 1,  // packed (no padding)
-                        _ => 0,  // C, Default, Transparent all use natural alignment
-                    };
-                    LLVMStructSetBody(
-                        struct_ty,
-                        field_types.as_mut_ptr(),
-                        field_types.len() as u32,
-                        packed
-                    );
+                if existing.is_null() && ir_struct.is_union {
+                    self.declare_union(module, ir_struct);
+                } else if existing.is_null() {
+                    self.declare_struct(module, ir_struct);
                 }
             }
         }
         
+        unsafe { self.define_enum_constructors(module); }
+
+        // Declare global variables
+        for global in &module.globals {
+            self.declare_global(global);
+        }
+        
+        // Hosted programs record their arguments for args()/arg_count()
+        let hosted = !module.no_std && !module.no_main;
+        if hosted {
+            unsafe { self.declare_arg_builtins(); }
+        }
+        
         // Generate code for each function
         for func in &module.functions {
             self.generate_function(func)?;
         }
         
-        // Verify
-        self.verify_module()?;
+        if let Some(main) = module.functions.iter().find(|f| f.name == "main").filter(|_| hosted) {
+            unsafe { self.emit_main_wrapper(main); }
+        }
         
-        // Emit object code
-        self.emit_object()
+        if let Some(di) = self.di_builder {
+            unsafe { LLVMDIBuilderFinalize(di); }
+        }
+        
+        // Verify
+        self.verify_module()
     }
-    
-    fn target_triple(&self) -> &str {
-        &self.target_triple
+
+    /// Lower `module` and write it to `path` as bitcode for `aethc link`,
+    /// recording `libraries` as named metadata
+    pub fn write_bitcode(&mut self, module: &IRModule, libraries: &[String], path: &Path) -> Result<()> {
+        self.lto = true;
+        self.lower_module(module)?;
+        unsafe {
+            let name = CString::new(LIBRARIES_METADATA).unwrap();
+            for lib in libraries {
+                let md = LLVMMDStringInContext2(self.context, lib.as_ptr() as *const _, lib.len());
+                let node = LLVMMDNodeInContext2(self.context, [md].as_mut_ptr(), 1);
+                LLVMAddNamedMetadataOperand(self.module, name.as_ptr(), LLVMMetadataAsValue(self.context, node));
+            }
+            let path_c = CString::new(path.to_string_lossy().as_bytes())
+                .map_err(|_| Error::Io(format!("invalid path {}", path.display())))?;
+            if LLVMWriteBitcodeToFile(self.module, path_c.as_ptr()) != 0 {
+                return Err(Error::Io(format!("could not write {}", path.display())));
+            }
+        }
+        Ok(())
     }
-    
-    fn name(&self) -> &str {
-        "LLVM"
+
+    /// Link the bitcode files written by `write_bitcode` into this module,
+    /// run the LTO pipeline over the result and emit an object file.
+    /// Functions several inputs define are renamed apart with the input's
+    /// file stem; two definitions of `main` are an error.
+    pub fn link_bitcode(&mut self, paths: &[PathBuf]) -> Result<LinkedBitcode> {
+        Self::init_targets();
+        self.lto = true;
+        unsafe {
+            for path in paths {
+                let path_c = CString::new(path.to_string_lossy().as_bytes())
+                    .map_err(|_| Error::Io(format!("invalid path {}", path.display())))?;
+                let mut buffer: LLVMMemoryBufferRef = ptr::null_mut();
+                let mut error_msg: *mut i8 = ptr::null_mut();
+                if LLVMCreateMemoryBufferWithContentsOfFile(path_c.as_ptr(), &mut buffer, &mut error_msg) != 0 {
+                    let msg = CStr::from_ptr(error_msg).to_string_lossy().to_string();
+                    LLVMDisposeMessage(error_msg);
+                    return Err(Error::Io(format!("{}: {}", path.display(), msg)));
+                }
+                let mut src: LLVMModuleRef = ptr::null_mut();
+                let failed = LLVMParseBitcodeInContext2(self.context, buffer, &mut src) != 0;
+                LLVMDisposeMemoryBuffer(buffer);
+                if failed {
+                    return Err(Error::Lto(format!("{} is not LLVM bitcode", path.display())));
+                }
+
+                let prefix = path.file_stem().and_then(|s| s.to_str()).unwrap_or("module");
+                let mut func = LLVMGetFirstFunction(src);
+                while !func.is_null() {
+                    let mut len = 0;
+                    let name = CStr::from_ptr(LLVMGetValueName2(func, &mut len)).to_owned();
+                    let existing = LLVMGetNamedFunction(self.module, name.as_ptr());
+                    let clashes = LLVMIsDeclaration(func) == 0
+                        && !existing.is_null()
+                        && LLVMIsDeclaration(existing) == 0
+                        && LLVMGetLinkage(func) == llvm_sys::LLVMLinkage::LLVMExternalLinkage;
+                    if clashes {
+                        let name = name.to_string_lossy();
+                        if name == "main" || name == "aether_main" {
+                            LLVMDisposeModule(src);
+                            return Err(Error::Lto(format!("`main` is defined again in {}", path.display())));
+                        }
+                        let renamed = mangle::function_symbol(&[prefix], &name);
+                        LLVMSetValueName2(func, renamed.as_ptr() as *const _, renamed.len());
+                    }
+                    func = LLVMGetNextFunction(func);
+                }
+
+                // Takes ownership of `src`
+                if LLVMLinkModules2(self.module, src) != 0 {
+                    return Err(Error::Lto(format!("could not link {}", path.display())));
+                }
+            }
+
+            let libraries = self.recorded_libraries();
+            let mut undefined = Vec::new();
+            let mut func = LLVMGetFirstFunction(self.module);
+            while !func.is_null() {
+                if LLVMIsDeclaration(func) != 0 && !LLVMGetFirstUse(func).is_null() {
+                    let mut len = 0;
+                    let name = LLVMGetValueName2(func, &mut len);
+                    undefined.push(CStr::from_ptr(name).to_string_lossy().into_owned());
+                }
+                func = LLVMGetNextFunction(func);
+            }
+
+            self.verify_module()?;
+            let object = self.emit_object()?;
+            Ok(LinkedBitcode { object, libraries, undefined })
+        }
+    }
+
+    /// Libraries listed in the module's `aether.libraries` metadata, without duplicates
+    unsafe fn recorded_libraries(&self) -> Vec<String> {
+        let name = CString::new(LIBRARIES_METADATA).unwrap();
+        let count = LLVMGetNamedMetadataNumOperands(self.module, name.as_ptr());
+        let mut nodes = vec![ptr::null_mut(); count as usize];
+        LLVMGetNamedMetadataOperands(self.module, name.as_ptr(), nodes.as_mut_ptr());
+        let mut libraries = Vec::new();
+        for node in nodes {
+            let mut field = ptr::null_mut();
+            if LLVMGetMDNodeNumOperands(node) == 1 {
+                LLVMGetMDNodeOperands(node, &mut field);
+            }
+            if field.is_null() {
+                continue;
+            }
+            let mut len = 0;
+            let text = LLVMGetMDString(field, &mut len);
+            if text.is_null() {
+                continue;
+            }
+            let bytes = std::slice::from_raw_parts(text as *const u8, len as usize);
+            let lib = String::from_utf8_lossy(bytes).into_owned();
+            if !libraries.contains(&lib) {
+                libraries.push(lib);
+            }
+        }
+        libraries
     }
 }
 
 impl Drop for LLVMCodeGen {
     fn drop(&mut self) {
         unsafe {
+            if let Some(di) = self.di_builder {
+                LLVMDisposeDIBuilder(di);
+            }
             LLVMDisposeBuilder(self.builder);
             LLVMDisposeModule(self.module);
             LLVMContextDispose(self.context);
@@ -1245,6 +2178,34 @@ impl Drop for LLVMCodeGen {
     }
 }
 
+/// Reachable blocks in reverse postorder, then the unreachable ones
+fn block_order(func: &IRFunction) -> Vec<usize> {
+    let n = func.blocks.len();
+    let mut visited = vec![false; n];
+    let mut postorder = Vec::with_capacity(n);
+    let mut stack = Vec::new();
+    if func.entry_block.0 < n {
+        visited[func.entry_block.0] = true;
+        stack.push((func.entry_block.0, 0));
+    }
+    while let Some(&mut (b, ref mut next)) = stack.last_mut() {
+        let succs = func.blocks[b].terminator.as_ref().map(|t| t.successors()).unwrap_or_default();
+        if let Some(&BlockId(s)) = succs.get(*next) {
+            *next += 1;
+            if s < n && !visited[s] {
+                visited[s] = true;
+                stack.push((s, 0));
+            }
+        } else {
+            postorder.push(b);
+            stack.pop();
+        }
+    }
+    postorder.reverse();
+    postorder.extend((0..n).filter(|&b| !visited[b]));
+    postorder
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1263,7 +2224,7 @@ mod tests {
     #[test]
     fn test_empty_function() {
         let ir_module = compile_to_ir("fn main() {}");
-        let mut codegen = LLVMCodeGen::new("x86_64-unknown-linux-gnu");
+        let mut codegen = LLVMCodeGen::new("x86_64-unknown-linux-gnu", SanitizerFlags::NONE);
         
         // Should not panic
         let result = codegen.generate(&ir_module);
@@ -1274,7 +2235,7 @@ mod tests {
     #[test]
     fn test_return_constant() {
         let ir_module = compile_to_ir("fn answer() -> i64 { return 42 }");
-        let mut codegen = LLVMCodeGen::new("x86_64-unknown-linux-gnu");
+        let mut codegen = LLVMCodeGen::new("x86_64-unknown-linux-gnu", SanitizerFlags::NONE);
         
         let result = codegen.generate(&ir_module);
         println!("LLVM IR:\n{}", codegen.print_ir());
@@ -1284,7 +2245,7 @@ mod tests {
     #[test]
     fn test_binary_expression() {
         let ir_module = compile_to_ir("fn add() -> i64 { return 1 + 2 }");
-        let mut codegen = LLVMCodeGen::new("x86_64-unknown-linux-gnu");
+        let mut codegen = LLVMCodeGen::new("x86_64-unknown-linux-gnu", SanitizerFlags::NONE);
         
         let result = codegen.generate(&ir_module);
         println!("LLVM IR:\n{}", codegen.print_ir());
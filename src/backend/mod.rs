@@ -1,8 +1,14 @@
 //! Backend module - Code generation
 #![allow(unused_imports)]
 
+pub mod bitfield;
 pub mod codegen;
+pub mod cross;
 pub mod linker;
+pub mod linker_script;
+pub mod native;
+pub mod runtime;
+pub mod static_link;
 
 // C Backend (always available)
 pub mod c;
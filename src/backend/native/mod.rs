@@ -0,0 +1,73 @@
+//! Native Backend - machine code without LLVM or a C compiler
+//!
+//! Lowers IR straight to machine code and links it into a static ELF64
+//! executable with the self-hosted linker.
+
+pub mod x86_64;
+
+use std::path::Path;
+
+use crate::backend::linker::{Linker, PF_R, PF_X, SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS};
+use crate::backend::linker_script::LinkerScript;
+use crate::middle::ir::IRModule;
+use crate::utils::{Error, Result};
+
+/// Offset of the code segment from the base address
+const TEXT_OFFSET: u64 = 0x1000;
+
+/// Compile `module` for x86-64 Linux and write the executable to `path`
+pub fn emit_executable(module: &IRModule, path: &Path) -> Result<()> {
+    link_executable(module, path, None, None)
+}
+
+/// Like `emit_executable`, laying the image out as `script` says and
+/// writing the resulting link map to `map` when given
+pub fn link_executable(module: &IRModule, path: &Path, script: Option<&LinkerScript>, map: Option<&Path>) -> Result<()> {
+    let machine = x86_64::compile_module(module)?;
+    let mut linker = Linker::new();
+    let mut text_offset = TEXT_OFFSET;
+    if let Some(script) = script {
+        if let Some(base) = script.base_address {
+            linker.set_base_address(base);
+        }
+        for section in &script.sections {
+            if section.name != ".text" {
+                return Err(Error::LinkerScript(format!(
+                    "the native backend only emits `.text`, not `{}`", section.name
+                )));
+            }
+        }
+        text_offset = script.section_offset(".text").unwrap_or(TEXT_OFFSET);
+    }
+    let text_vaddr = linker.base_address().checked_add(text_offset)
+        .ok_or_else(|| Error::LinkerScript(format!("`.text` at {:#x} does not fit in the address space", text_offset)))?;
+    if text_vaddr % 0x1000 != 0 {
+        return Err(Error::LinkerScript(format!("`.text` must start on a page boundary, not {:#x}", text_vaddr)));
+    }
+    for (name, range) in &machine.symbols {
+        linker.add_symbol(name, text_vaddr + range.start as u64, range.len() as u64);
+    }
+    let entry = script.and_then(|s| s.entry.as_deref()).unwrap_or("_start");
+    let entry_vaddr = linker.symbol_address(entry)
+        .ok_or_else(|| Error::LinkerScript(format!("entry symbol `{}` is not defined", entry)))?;
+
+    // Page-align the segment so its file offset matches the vaddr page offset
+    let code = machine.code;
+    linker.add_segment(code.clone(), PF_R | PF_X, text_vaddr, 0x1000);
+    linker.add_section(".text", code, SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, text_vaddr, 16);
+    linker.set_entry_point(entry_vaddr);
+    linker.emit(path).map_err(|e| io_error(path, e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).map_err(|e| io_error(path, e))?;
+    }
+    if let Some(map) = map {
+        linker.emit_link_map(map).map_err(|e| io_error(map, e))?;
+    }
+    Ok(())
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> Error {
+    Error::Io(format!("{}: {}", path.display(), e))
+}
@@ -0,0 +1,903 @@
+//! x86-64 Machine Code Generation
+//!
+//! An instruction encoder, a linear scan register allocator and a lowering
+//! of integer IR functions to System V x86-64 machine code.
+
+use std::collections::HashMap;
+
+use crate::middle::ir::*;
+use crate::utils::{Error, Result};
+
+// ==================== Registers ====================
+
+/// 64-bit general purpose register, in hardware encoding order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Reg {
+    Rax, Rcx, Rdx, Rbx, Rsp, Rbp, Rsi, Rdi,
+    R8, R9, R10, R11, R12, R13, R14, R15,
+}
+
+impl Reg {
+    /// The 4-bit register number (bit 3 goes in the REX prefix)
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    fn low(self) -> u8 {
+        self.code() & 7
+    }
+
+    fn ext(self) -> u8 {
+        self.code() >> 3
+    }
+
+    /// Whether the System V ABI requires a callee to preserve this register
+    pub fn is_callee_saved(self) -> bool {
+        matches!(self, Reg::Rbx | Reg::Rsp | Reg::Rbp | Reg::R12 | Reg::R13 | Reg::R14 | Reg::R15)
+    }
+}
+
+/// Registers handed out by the allocator, caller-saved first.
+/// `rax` and `r11` are kept free to stage spilled operands and results.
+pub const ALLOCATABLE: [Reg; 12] = [
+    Reg::Rcx, Reg::Rdx, Reg::Rsi, Reg::Rdi, Reg::R8, Reg::R9, Reg::R10,
+    Reg::Rbx, Reg::R12, Reg::R13, Reg::R14, Reg::R15,
+];
+
+/// Integer argument registers of the System V calling convention
+pub const ARG_REGS: [Reg; 6] = [Reg::Rdi, Reg::Rsi, Reg::Rdx, Reg::Rcx, Reg::R8, Reg::R9];
+
+/// Condition code of a conditional jump or `setcc`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cond {
+    E = 0x4,
+    Ne = 0x5,
+    L = 0xC,
+    Ge = 0xD,
+    Le = 0xE,
+    G = 0xF,
+}
+
+// ==================== Encoder ====================
+
+/// Jump or call target, bound to an offset with `Assembler::bind`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Label(usize);
+
+/// x86-64 instruction encoder.
+/// Jumps and calls take labels, which `finish` resolves to rel32 offsets.
+#[derive(Debug, Default)]
+pub struct Assembler {
+    code: Vec<u8>,
+    labels: Vec<Option<usize>>,
+    /// (offset of a rel32 field, label it refers to)
+    fixups: Vec<(usize, Label)>,
+}
+
+impl Assembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bytes emitted so far
+    pub fn len(&self) -> usize {
+        self.code.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.code.is_empty()
+    }
+
+    pub fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    /// Point `label` at the next instruction
+    pub fn bind(&mut self, label: Label) {
+        self.labels[label.0] = Some(self.code.len());
+    }
+
+    /// Patch every jump and call, failing on a label that was never bound
+    pub fn finish(mut self) -> Result<Vec<u8>> {
+        for &(at, label) in &self.fixups {
+            let target = self.labels[label.0]
+                .ok_or_else(|| Error::CodeGen(format!("jump to unbound label {}", label.0)))?;
+            let rel = target as i64 - (at as i64 + 4);
+            self.code[at..at + 4].copy_from_slice(&(rel as i32).to_le_bytes());
+        }
+        Ok(self.code)
+    }
+
+    fn rex_w(&mut self, reg: u8, rm: u8) {
+        self.code.push(0x48 | (reg << 2) | rm);
+    }
+
+    fn modrm_reg(&mut self, reg: u8, rm: u8) {
+        self.code.push(0xC0 | (reg << 3) | rm);
+    }
+
+    /// `op r/m64, r64` with both operands registers
+    fn op_rr(&mut self, opcode: &[u8], rm: Reg, reg: Reg) {
+        self.rex_w(reg.ext(), rm.ext());
+        self.code.extend_from_slice(opcode);
+        self.modrm_reg(reg.low(), rm.low());
+    }
+
+    /// `op r/m64, imm32` from the 0x81 group, `ext` selecting the operation
+    fn op_ri(&mut self, ext: u8, dst: Reg, imm: i32) {
+        self.rex_w(0, dst.ext());
+        self.code.push(0x81);
+        self.modrm_reg(ext, dst.low());
+        self.code.extend_from_slice(&imm.to_le_bytes());
+    }
+
+    /// `op r64, [base + disp32]` or the store form, by opcode
+    fn op_mem(&mut self, opcode: u8, reg: Reg, base: Reg, disp: i32) {
+        self.rex_w(reg.ext(), base.ext());
+        self.code.push(opcode);
+        self.code.push(0x80 | (reg.low() << 3) | base.low());
+        if base.low() == 4 {
+            // rsp and r12 as a base need a SIB byte
+            self.code.push(0x24);
+        }
+        self.code.extend_from_slice(&disp.to_le_bytes());
+    }
+
+    fn rel32(&mut self, label: Label) {
+        self.fixups.push((self.code.len(), label));
+        self.code.extend_from_slice(&[0; 4]);
+    }
+
+    /// mov dst, src
+    pub fn mov(&mut self, dst: Reg, src: Reg) {
+        self.op_rr(&[0x89], dst, src);
+    }
+
+    /// mov dst, imm (sign-extended imm32 when it fits, movabs otherwise)
+    pub fn mov_imm(&mut self, dst: Reg, imm: i64) {
+        if let Ok(imm) = i32::try_from(imm) {
+            self.rex_w(0, dst.ext());
+            self.code.push(0xC7);
+            self.modrm_reg(0, dst.low());
+            self.code.extend_from_slice(&imm.to_le_bytes());
+        } else {
+            self.rex_w(0, dst.ext());
+            self.code.push(0xB8 + dst.low());
+            self.code.extend_from_slice(&imm.to_le_bytes());
+        }
+    }
+
+    /// mov dst, [base + disp]
+    pub fn load(&mut self, dst: Reg, base: Reg, disp: i32) {
+        self.op_mem(0x8B, dst, base, disp);
+    }
+
+    /// mov [base + disp], src
+    pub fn store(&mut self, base: Reg, disp: i32, src: Reg) {
+        self.op_mem(0x89, src, base, disp);
+    }
+
+    /// add dst, src
+    pub fn add(&mut self, dst: Reg, src: Reg) {
+        self.op_rr(&[0x01], dst, src);
+    }
+
+    /// add dst, imm32
+    pub fn add_imm(&mut self, dst: Reg, imm: i32) {
+        self.op_ri(0, dst, imm);
+    }
+
+    /// sub dst, src
+    pub fn sub(&mut self, dst: Reg, src: Reg) {
+        self.op_rr(&[0x29], dst, src);
+    }
+
+    /// sub dst, imm32
+    pub fn sub_imm(&mut self, dst: Reg, imm: i32) {
+        self.op_ri(5, dst, imm);
+    }
+
+    /// and dst, src
+    pub fn and(&mut self, dst: Reg, src: Reg) {
+        self.op_rr(&[0x21], dst, src);
+    }
+
+    /// or dst, src
+    pub fn or(&mut self, dst: Reg, src: Reg) {
+        self.op_rr(&[0x09], dst, src);
+    }
+
+    /// xor dst, src
+    pub fn xor(&mut self, dst: Reg, src: Reg) {
+        self.op_rr(&[0x31], dst, src);
+    }
+
+    /// imul dst, src (signed, truncated to 64 bits)
+    pub fn imul(&mut self, dst: Reg, src: Reg) {
+        self.rex_w(dst.ext(), src.ext());
+        self.code.extend_from_slice(&[0x0F, 0xAF]);
+        self.modrm_reg(dst.low(), src.low());
+    }
+
+    /// mul src (unsigned rdx:rax = rax * src)
+    pub fn mul(&mut self, src: Reg) {
+        self.rex_w(0, src.ext());
+        self.code.push(0xF7);
+        self.modrm_reg(4, src.low());
+    }
+
+    /// cmp left, right
+    pub fn cmp(&mut self, left: Reg, right: Reg) {
+        self.op_rr(&[0x39], left, right);
+    }
+
+    /// cmp left, imm32
+    pub fn cmp_imm(&mut self, left: Reg, imm: i32) {
+        self.op_ri(7, left, imm);
+    }
+
+    /// dst = 1 if `cond` holds, else 0 (a `setcc` of the low byte, then `movzx`)
+    pub fn set(&mut self, cond: Cond, dst: Reg) {
+        // A REX prefix selects sil/dil rather than dh/bh
+        self.code.push(0x40 | dst.ext());
+        self.code.extend_from_slice(&[0x0F, 0x90 + cond as u8]);
+        self.modrm_reg(0, dst.low());
+        self.rex_w(dst.ext(), dst.ext());
+        self.code.extend_from_slice(&[0x0F, 0xB6]);
+        self.modrm_reg(dst.low(), dst.low());
+    }
+
+    /// jmp label
+    pub fn jmp(&mut self, label: Label) {
+        self.code.push(0xE9);
+        self.rel32(label);
+    }
+
+    /// Conditional jump to label
+    pub fn jcc(&mut self, cond: Cond, label: Label) {
+        self.code.extend_from_slice(&[0x0F, 0x80 + cond as u8]);
+        self.rel32(label);
+    }
+
+    /// je label
+    pub fn je(&mut self, label: Label) {
+        self.jcc(Cond::E, label);
+    }
+
+    /// jne label
+    pub fn jne(&mut self, label: Label) {
+        self.jcc(Cond::Ne, label);
+    }
+
+    /// call label
+    pub fn call(&mut self, label: Label) {
+        self.code.push(0xE8);
+        self.rel32(label);
+    }
+
+    pub fn ret(&mut self) {
+        self.code.push(0xC3);
+    }
+
+    pub fn push(&mut self, reg: Reg) {
+        if reg.ext() != 0 {
+            self.code.push(0x41);
+        }
+        self.code.push(0x50 + reg.low());
+    }
+
+    pub fn pop(&mut self, reg: Reg) {
+        if reg.ext() != 0 {
+            self.code.push(0x41);
+        }
+        self.code.push(0x58 + reg.low());
+    }
+
+    pub fn syscall(&mut self) {
+        self.code.extend_from_slice(&[0x0F, 0x05]);
+    }
+
+    pub fn ud2(&mut self) {
+        self.code.extend_from_slice(&[0x0F, 0x0B]);
+    }
+}
+
+// ==================== Liveness ====================
+
+/// Positions (in block order) from the first definition or use of an IR
+/// register to its last, widened over any loop it is live in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LiveInterval {
+    pub reg: Register,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Liveness intervals of every register in `func`, sorted by start, and the
+/// positions of its calls. Each instruction and terminator takes one position.
+pub fn live_intervals(func: &IRFunction) -> (Vec<LiveInterval>, Vec<usize>) {
+    let mut ranges: HashMap<Register, (usize, usize)> = HashMap::new();
+    let mut touch = |reg: Register, pos: usize| {
+        let range = ranges.entry(reg).or_insert((pos, pos));
+        range.0 = range.0.min(pos);
+        range.1 = range.1.max(pos);
+    };
+    let mut calls = Vec::new();
+    // (first, last) position of each block
+    let mut spans = HashMap::new();
+    let mut pos = 0;
+    for block in &func.blocks {
+        let first = pos;
+        for inst in &block.instructions {
+            for value in inst.operands() {
+                if let Value::Register(r) = value {
+                    touch(*r, pos);
+                }
+            }
+            for r in inst.defs() {
+                touch(r, pos);
+            }
+            if matches!(inst, Instruction::Call { .. }) {
+                calls.push(pos);
+            }
+            pos += 1;
+        }
+        if let Some(term) = &block.terminator {
+            for value in term.operands() {
+                if let Value::Register(r) = value {
+                    touch(*r, pos);
+                }
+            }
+        }
+        spans.insert(block.id, (first, pos));
+        pos += 1;
+    }
+
+    // A register used anywhere in a loop must survive the whole loop: the
+    // back edge carries it from the loop's end to its header
+    let loops: Vec<(usize, usize)> = func.blocks.iter()
+        .flat_map(|block| {
+            let (_, last) = spans[&block.id];
+            let targets = block.terminator.as_ref().map(Terminator::successors).unwrap_or_default();
+            targets.into_iter().filter_map(|t| spans.get(&t).copied())
+                .filter(move |&(header, _)| header <= last)
+                .map(move |(header, _)| (header, last))
+        })
+        .collect();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for range in ranges.values_mut() {
+            for &(header, last) in &loops {
+                if range.0 <= last && range.1 >= header && (range.0 > header || range.1 < last) {
+                    range.0 = range.0.min(header);
+                    range.1 = range.1.max(last);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    let mut intervals: Vec<LiveInterval> = ranges.into_iter()
+        .map(|(reg, (start, end))| LiveInterval { reg, start, end })
+        .collect();
+    intervals.sort_by_key(|iv| (iv.start, iv.reg.0));
+    (intervals, calls)
+}
+
+// ==================== Register Allocation ====================
+
+/// Where an IR register lives for its whole interval
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    Reg(Reg),
+    /// Index of an 8-byte spill slot in the stack frame
+    Stack(usize),
+}
+
+/// Result of register allocation for one function
+#[derive(Debug, Clone, Default)]
+pub struct Allocation {
+    pub locations: HashMap<Register, Location>,
+    pub spill_slots: usize,
+}
+
+impl Allocation {
+    pub fn location(&self, reg: Register) -> Option<Location> {
+        self.locations.get(&reg).copied()
+    }
+
+    /// Callee-saved registers the function writes, in encoding order
+    pub fn callee_saved(&self) -> Vec<Reg> {
+        let mut regs: Vec<Reg> = self.locations.values()
+            .filter_map(|loc| match loc {
+                Location::Reg(r) if r.is_callee_saved() => Some(*r),
+                _ => None,
+            })
+            .collect();
+        regs.sort();
+        regs.dedup();
+        regs
+    }
+}
+
+/// Linear scan register allocator (Poletto and Sarkar).
+/// Intervals live across a call only get callee-saved registers, so calls
+/// need no caller-side saves; when no register is free the interval that
+/// ends last is spilled to the stack frame.
+#[derive(Debug, Clone)]
+pub struct RegAllocator {
+    registers: Vec<Reg>,
+}
+
+impl Default for RegAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RegAllocator {
+    pub fn new() -> Self {
+        Self::with_registers(&ALLOCATABLE)
+    }
+
+    /// Allocate from `registers` only, in preference order
+    pub fn with_registers(registers: &[Reg]) -> Self {
+        Self { registers: registers.to_vec() }
+    }
+
+    pub fn allocate(&self, func: &IRFunction) -> Allocation {
+        let (intervals, calls) = live_intervals(func);
+        let mut alloc = Allocation::default();
+        // (interval, register) currently holding a value
+        let mut active: Vec<(LiveInterval, Reg)> = Vec::new();
+        for iv in intervals {
+            active.retain(|(other, _)| other.end >= iv.start);
+            let crosses_call = calls.iter().any(|&c| iv.start < c && c < iv.end);
+            let usable = |r: &Reg| !crosses_call || r.is_callee_saved();
+            let free = self.registers.iter().copied()
+                .find(|r| usable(r) && active.iter().all(|(_, used)| used != r));
+            if let Some(reg) = free {
+                alloc.locations.insert(iv.reg, Location::Reg(reg));
+                active.push((iv, reg));
+                continue;
+            }
+            // Take the register of the usable interval that ends last, if
+            // that ends after this one
+            let victim = active.iter().enumerate()
+                .filter(|(_, (_, r))| usable(r))
+                .max_by_key(|(_, (other, _))| other.end)
+                .filter(|(_, (other, _))| other.end > iv.end)
+                .map(|(i, _)| i);
+            let spilled = match victim {
+                Some(i) => {
+                    let (other, reg) = active.remove(i);
+                    alloc.locations.insert(iv.reg, Location::Reg(reg));
+                    active.push((iv, reg));
+                    other.reg
+                }
+                None => iv.reg,
+            };
+            alloc.locations.insert(spilled, Location::Stack(alloc.spill_slots));
+            alloc.spill_slots += 1;
+        }
+        alloc
+    }
+}
+
+// ==================== Lowering ====================
+
+/// Lower every function of `module` to one code blob. A `_start` entry at
+/// offset 0 calls `main` and exits with its result.
+pub fn compile_module(module: &IRModule) -> Result<MachineCode> {
+    if !module.functions.iter().any(|f| f.name == "main") {
+        return Err(Error::CodeGen("the native backend needs a `main` function".to_string()));
+    }
+    let mut asm = Assembler::new();
+    let labels: HashMap<String, Label> = module.functions.iter()
+        .map(|f| (f.name.clone(), asm.new_label()))
+        .collect();
+    asm.call(labels["main"]);
+    asm.mov(Reg::Rdi, Reg::Rax);
+    asm.mov_imm(Reg::Rax, 60);
+    asm.syscall();
+    let mut symbols = vec![("_start".to_string(), 0..asm.len())];
+    for func in &module.functions {
+        let start = asm.len();
+        FunctionLowering::new(func, &mut asm, &labels)?.lower()?;
+        symbols.push((func.name.clone(), start..asm.len()));
+    }
+    Ok(MachineCode { code: asm.finish()?, symbols })
+}
+
+/// Position-independent code for a whole module
+pub struct MachineCode {
+    /// Starts with the `_start` stub that calls `main` and exits with its result
+    pub code: Vec<u8>,
+    /// Byte range of `_start` and of each function within `code`
+    pub symbols: Vec<(String, std::ops::Range<usize>)>,
+}
+
+/// Lowers one function into a shared assembler
+struct FunctionLowering<'a> {
+    func: &'a IRFunction,
+    asm: &'a mut Assembler,
+    functions: &'a HashMap<String, Label>,
+    alloc: Allocation,
+    callee_saved: Vec<Reg>,
+    blocks: HashMap<BlockId, Label>,
+    /// Bytes reserved below the callee-saved registers
+    frame_size: i32,
+}
+
+impl<'a> FunctionLowering<'a> {
+    fn new(func: &'a IRFunction, asm: &'a mut Assembler, functions: &'a HashMap<String, Label>) -> Result<Self> {
+        if func.params.len() > ARG_REGS.len() {
+            return Err(Error::CodeGen(format!(
+                "`{}` takes {} parameters; the native backend passes at most {} in registers",
+                func.name, func.params.len(), ARG_REGS.len()
+            )));
+        }
+        if func.sret_type.is_some() {
+            return Err(Error::CodeGen(format!("`{}` returns a struct, which the native backend cannot lower", func.name)));
+        }
+        let alloc = RegAllocator::new().allocate(func);
+        let callee_saved = alloc.callee_saved();
+        // Incoming arguments are saved to slots after the spill slots
+        let slots = alloc.spill_slots + func.params.len();
+        // Keep rsp 16-byte aligned: rbp and the saved registers are pushed too
+        let mut frame_size = 8 * slots as i32;
+        if (callee_saved.len() + slots) % 2 == 1 {
+            frame_size += 8;
+        }
+        let blocks = func.blocks.iter().map(|b| (b.id, asm.new_label())).collect();
+        Ok(Self { func, asm, functions, alloc, callee_saved, blocks, frame_size })
+    }
+
+    /// rbp-relative offset of frame slot `slot`
+    fn slot_disp(&self, slot: usize) -> i32 {
+        -8 * (self.callee_saved.len() + slot + 1) as i32
+    }
+
+    fn unsupported(&self, what: impl std::fmt::Display) -> Error {
+        Error::CodeGen(format!("the native backend cannot lower {} (in `{}`)", what, self.func.name))
+    }
+
+    fn lower(mut self) -> Result<()> {
+        self.asm.bind(self.functions[&self.func.name]);
+        self.asm.push(Reg::Rbp);
+        self.asm.mov(Reg::Rbp, Reg::Rsp);
+        for reg in self.callee_saved.clone() {
+            self.asm.push(reg);
+        }
+        if self.frame_size > 0 {
+            self.asm.sub_imm(Reg::Rsp, self.frame_size);
+        }
+        for (i, reg) in ARG_REGS.iter().take(self.func.params.len()).enumerate() {
+            let disp = self.slot_disp(self.alloc.spill_slots + i);
+            self.asm.store(Reg::Rbp, disp, *reg);
+        }
+
+        for (i, block) in self.func.blocks.iter().enumerate() {
+            self.asm.bind(self.blocks[&block.id]);
+            for inst in &block.instructions {
+                self.lower_instruction(inst)?;
+            }
+            let next = self.func.blocks.get(i + 1).map(|b| b.id);
+            match &block.terminator {
+                Some(term) => self.lower_terminator(term, next)?,
+                None => self.asm.ud2(),
+            }
+        }
+        Ok(())
+    }
+
+    /// Move `value` into `dst`
+    fn load(&mut self, value: &Value, dst: Reg) -> Result<()> {
+        match value {
+            Value::Constant(Constant::Int(n)) => self.asm.mov_imm(dst, *n),
+            Value::Constant(Constant::Bool(b)) => self.asm.mov_imm(dst, *b as i64),
+            Value::Unit => self.asm.mov_imm(dst, 0),
+            Value::Parameter(i) => {
+                let disp = self.slot_disp(self.alloc.spill_slots + i);
+                self.asm.load(dst, Reg::Rbp, disp);
+            }
+            Value::Register(r) => match self.alloc.location(*r) {
+                Some(Location::Reg(src)) if src != dst => self.asm.mov(dst, src),
+                Some(Location::Reg(_)) => {}
+                Some(Location::Stack(slot)) => {
+                    let disp = self.slot_disp(slot);
+                    self.asm.load(dst, Reg::Rbp, disp);
+                }
+                None => return Err(self.unsupported(format!("unallocated register {}", r))),
+            },
+            other => return Err(self.unsupported(format!("the value `{}`", other))),
+        }
+        Ok(())
+    }
+
+    /// The register holding `value`, loading it into `scratch` if needed
+    fn operand(&mut self, value: &Value, scratch: Reg) -> Result<Reg> {
+        if let Value::Register(r) = value {
+            if let Some(Location::Reg(reg)) = self.alloc.location(*r) {
+                return Ok(reg);
+            }
+        }
+        self.load(value, scratch)?;
+        Ok(scratch)
+    }
+
+    /// Write `src` to wherever `dest` lives
+    fn define(&mut self, dest: Register, src: Reg) {
+        match self.alloc.location(dest) {
+            Some(Location::Reg(reg)) if reg != src => self.asm.mov(reg, src),
+            Some(Location::Stack(slot)) => {
+                let disp = self.slot_disp(slot);
+                self.asm.store(Reg::Rbp, disp, src);
+            }
+            _ => {}
+        }
+    }
+
+    fn lower_instruction(&mut self, inst: &Instruction) -> Result<()> {
+        match inst {
+            Instruction::Assign { dest, value } => {
+                self.load(value, Reg::Rax)?;
+                self.define(*dest, Reg::Rax);
+            }
+            Instruction::BinOp { dest, op, left, right } => {
+                self.load(left, Reg::Rax)?;
+                let right = self.operand(right, Reg::R11)?;
+                let cond = match op {
+                    BinOp::Add => { self.asm.add(Reg::Rax, right); None }
+                    BinOp::Sub => { self.asm.sub(Reg::Rax, right); None }
+                    BinOp::Mul => { self.asm.imul(Reg::Rax, right); None }
+                    BinOp::And => { self.asm.and(Reg::Rax, right); None }
+                    BinOp::Or => { self.asm.or(Reg::Rax, right); None }
+                    BinOp::Xor => { self.asm.xor(Reg::Rax, right); None }
+                    BinOp::Eq => Some(Cond::E),
+                    BinOp::Ne => Some(Cond::Ne),
+                    BinOp::Lt => Some(Cond::L),
+                    BinOp::Le => Some(Cond::Le),
+                    BinOp::Gt => Some(Cond::G),
+                    BinOp::Ge => Some(Cond::Ge),
+                    BinOp::Div | BinOp::Mod | BinOp::Shl | BinOp::Shr => {
+                        return Err(self.unsupported(format!("`{}`", op)));
+                    }
+                };
+                if let Some(cond) = cond {
+                    self.asm.cmp(Reg::Rax, right);
+                    self.asm.set(cond, Reg::Rax);
+                }
+                self.define(*dest, Reg::Rax);
+            }
+            Instruction::Call { dest, func, args } => {
+                let Some(&label) = self.functions.get(func) else {
+                    return Err(self.unsupported(format!("a call to the external function `{}`", func)));
+                };
+                if args.len() > ARG_REGS.len() {
+                    return Err(self.unsupported(format!("a call to `{}` with {} arguments", func, args.len())));
+                }
+                // Arguments go through the stack so none overwrites another
+                // before it is read
+                for arg in args {
+                    self.load(arg, Reg::Rax)?;
+                    self.asm.push(Reg::Rax);
+                }
+                for reg in ARG_REGS[..args.len()].iter().rev() {
+                    self.asm.pop(*reg);
+                }
+                self.asm.call(label);
+                if let Some(dest) = dest {
+                    self.define(*dest, Reg::Rax);
+                }
+            }
+            Instruction::DebugLoc(_) => {}
+            other => return Err(self.unsupported(format!("`{:?}`", other))),
+        }
+        Ok(())
+    }
+
+    fn lower_terminator(&mut self, term: &Terminator, next: Option<BlockId>) -> Result<()> {
+        match term {
+            Terminator::Return { value } => {
+                if let Some(value) = value {
+                    self.load(value, Reg::Rax)?;
+                }
+                if self.frame_size > 0 {
+                    self.asm.add_imm(Reg::Rsp, self.frame_size);
+                }
+                for reg in self.callee_saved.iter().rev() {
+                    self.asm.pop(*reg);
+                }
+                self.asm.pop(Reg::Rbp);
+                self.asm.ret();
+            }
+            Terminator::Jump { target } => {
+                if next != Some(*target) {
+                    self.asm.jmp(self.blocks[target]);
+                }
+            }
+            Terminator::Branch { cond, then_target, else_target } => {
+                let cond = self.operand(cond, Reg::Rax)?;
+                self.asm.cmp_imm(cond, 0);
+                self.asm.jne(self.blocks[then_target]);
+                if next != Some(*else_target) {
+                    self.asm.jmp(self.blocks[else_target]);
+                }
+            }
+            Terminator::Unreachable => self.asm.ud2(),
+            Terminator::Switch { .. } => return Err(self.unsupported("`switch`")),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middle::ir_parser::parse_ir;
+
+    fn encode(f: impl FnOnce(&mut Assembler)) -> Vec<u8> {
+        let mut asm = Assembler::new();
+        f(&mut asm);
+        asm.finish().unwrap()
+    }
+
+    const FIB: &str = "\
+module fib
+
+fn fib(n: i64) -> i64 {
+  bb0 entry:
+    %0 = arg0
+    %1 = lt %0 2
+    br %1, bb1, bb2
+  bb1 then:
+    ret %0
+  bb2 else:
+    %2 = sub %0 1
+    %3 = call fib(%2)
+    %4 = sub %0 2
+    %5 = call fib(%4)
+    %6 = add %3 %5
+    ret %6
+}
+
+fn main() -> i64 {
+  bb0 entry:
+    %0 = 0
+    %1 = 0
+    br bb1
+  bb1 while_cond:
+    %2 = lt %1 10
+    br %2, bb2, bb3
+  bb2 while_body:
+    %3 = add %0 %1
+    %0 = %3
+    %4 = add %1 1
+    %1 = %4
+    br bb1
+  bb3 while_exit:
+    %5 = call fib(10)
+    %6 = add %5 %0
+    %7 = sub %6 58
+    ret %7
+}
+";
+
+    #[test]
+    fn test_encodings() {
+        assert_eq!(encode(|a| a.mov_imm(Reg::Rdi, 42)), [0x48, 0xC7, 0xC7, 0x2A, 0, 0, 0]);
+        assert_eq!(encode(|a| a.mov_imm(Reg::R9, 1 << 40)), [0x49, 0xB9, 0, 0, 0, 0, 0, 1, 0, 0]);
+        assert_eq!(encode(|a| a.mov(Reg::Rbp, Reg::Rsp)), [0x48, 0x89, 0xE5]);
+        assert_eq!(encode(|a| a.add(Reg::Rax, Reg::R11)), [0x4C, 0x01, 0xD8]);
+        assert_eq!(encode(|a| a.sub_imm(Reg::Rsp, 16)), [0x48, 0x81, 0xEC, 0x10, 0, 0, 0]);
+        assert_eq!(encode(|a| a.imul(Reg::Rax, Reg::R11)), [0x49, 0x0F, 0xAF, 0xC3]);
+        assert_eq!(encode(|a| a.mul(Reg::Rcx)), [0x48, 0xF7, 0xE1]);
+        assert_eq!(encode(|a| a.cmp(Reg::Rcx, Reg::Rdx)), [0x48, 0x39, 0xD1]);
+        assert_eq!(encode(|a| a.load(Reg::Rax, Reg::Rbp, -8)), [0x48, 0x8B, 0x85, 0xF8, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(encode(|a| a.store(Reg::R12, 0, Reg::Rdi)), [0x49, 0x89, 0xBC, 0x24, 0, 0, 0, 0]);
+        assert_eq!(encode(|a| { a.push(Reg::R12); a.pop(Reg::Rbx); a.ret() }), [0x41, 0x54, 0x5B, 0xC3]);
+        assert_eq!(encode(|a| a.set(Cond::L, Reg::Rsi)), [0x40, 0x0F, 0x9C, 0xC6, 0x48, 0x0F, 0xB6, 0xF6]);
+    }
+
+    #[test]
+    fn test_labels() {
+        let code = encode(|a| {
+            let top = a.new_label();
+            let end = a.new_label();
+            a.bind(top);
+            a.je(end);
+            a.jmp(top);
+            a.bind(end);
+            a.call(top);
+        });
+        // je +5 over the jmp, jmp -11 back to the je, call -16 back to the start
+        assert_eq!(&code[..6], [0x0F, 0x84, 5, 0, 0, 0]);
+        assert_eq!(&code[6..11], [0xE9, 0xF5, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(&code[11..], [0xE8, 0xF0, 0xFF, 0xFF, 0xFF]);
+
+        let mut asm = Assembler::new();
+        let label = asm.new_label();
+        asm.jmp(label);
+        assert!(asm.finish().is_err());
+    }
+
+    #[test]
+    fn test_live_intervals() {
+        let module = parse_ir(FIB).unwrap();
+        let main = &module.functions[1];
+        let (intervals, calls) = live_intervals(main);
+        assert_eq!(calls, vec![10]);
+        let interval = |n| intervals.iter().find(|iv| iv.reg == Register(n)).unwrap();
+        // The loop counters are live across the whole loop (positions 3-9)
+        assert_eq!((interval(1).start, interval(1).end), (1, 9));
+        assert_eq!((interval(0).start, interval(0).end), (0, 11));
+        // Temporaries inside the loop are widened to it as well
+        assert_eq!((interval(4).start, interval(4).end), (3, 9));
+        assert_eq!((interval(7).start, interval(7).end), (12, 13));
+    }
+
+    #[test]
+    fn test_allocation() {
+        let module = parse_ir(FIB).unwrap();
+        let fib = &module.functions[0];
+        let alloc = RegAllocator::new().allocate(fib);
+        assert_eq!(alloc.spill_slots, 0);
+        // %0 and %3 are live across a call
+        for r in [0, 3] {
+            let Some(Location::Reg(reg)) = alloc.location(Register(r)) else { panic!() };
+            assert!(reg.is_callee_saved(), "{:?}", reg);
+        }
+        assert!(!alloc.callee_saved().is_empty());
+
+        // With two registers, %0 (live to the end) is spilled for the loop temporaries
+        let main = &module.functions[1];
+        let alloc = RegAllocator::with_registers(&[Reg::Rbx, Reg::R12]).allocate(main);
+        assert!(alloc.spill_slots > 0);
+        assert!(matches!(alloc.location(Register(0)), Some(Location::Stack(_))));
+        let mut used: Vec<_> = alloc.locations.values().filter_map(|l| match l {
+            Location::Reg(r) => Some(*r),
+            Location::Stack(_) => None,
+        }).collect();
+        used.sort();
+        used.dedup();
+        assert_eq!(used, vec![Reg::Rbx, Reg::R12]);
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let module = parse_ir("module m\n\nfn main() -> i64 {\n  bb0 entry:\n    %0 = div 7 2\n    ret %0\n}\n").unwrap();
+        assert!(compile_module(&module).is_err());
+    }
+
+    #[test]
+    fn test_symbol_ranges() {
+        let module = parse_ir(FIB).unwrap();
+        let machine = compile_module(&module).unwrap();
+        let names: Vec<&str> = machine.symbols.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names[0], "_start");
+        assert_eq!(names.len(), module.functions.len() + 1);
+        // The ranges tile the code in order
+        let mut end = 0;
+        for (name, range) in &machine.symbols {
+            assert_eq!(range.start, end, "{}", name);
+            assert!(range.end > range.start, "{}", name);
+            end = range.end;
+        }
+        assert_eq!(end, machine.code.len());
+    }
+
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    #[test]
+    fn test_run_native() {
+        let module = parse_ir(FIB).unwrap();
+        let path = std::env::temp_dir().join(format!("aether_native_{}", std::process::id()));
+        super::super::emit_executable(&module, &path).unwrap();
+        let status = std::process::Command::new(&path).status().unwrap();
+        let _ = std::fs::remove_file(&path);
+        // fib(10) + (0 + 1 + ... + 9) - 58
+        assert_eq!(status.code(), Some(42));
+    }
+}
@@ -0,0 +1,410 @@
+//! C source of the panic, collection, file I/O and benchmark runtimes
+//!
+//! The C backend emits these into every hosted program; the LLVM backend
+//! compiles them separately and links them next to the generated object.
+
+use crate::middle::bench::{self, BLACK_BOX_FN, NOW_FN, REPORT_FN, SAMPLE_FN};
+use crate::middle::collections::{
+    self, KeyKind, MAP_CONTAINS, MAP_FREE, MAP_GET, MAP_INSERT, MAP_LEN, MAP_NEW, MAP_REMOVE,
+    VEC_CLEAR, VEC_FREE, VEC_GET, VEC_LEN, VEC_NEW, VEC_POP, VEC_PUSH, VEC_WITH_CAPACITY,
+};
+use crate::middle::fileio::{self, FILE_CLOSE, FILE_OPEN, FILE_READ, FILE_WRITE, LAST_ERROR, READ_FILE, WRITE_FILE};
+use crate::middle::ir::IRModule;
+use crate::middle::panic::{self, PanicMode, PANIC_FN, TRACE_ENTER_FN, TRACE_EXIT_FN};
+
+/// C layout of `str`
+pub const STR_TYPEDEF: &str = "typedef struct AethStr { char* data; size_t len; } AethStr;";
+
+/// C layout of the collection handles. Map slots hold a 16-byte key (an
+/// `i64` or a `str`) followed by the value; `states` marks each slot empty (0),
+/// full (1) or removed (2).
+pub const COLLECTION_TYPEDEFS: &str = concat!(
+    "typedef struct AethVec { uint8_t* data; uint64_t len; uint64_t cap; } AethVec;\n",
+    "typedef struct AethMap { uint8_t* slots; uint8_t* states; uint64_t cap; uint64_t len; uint64_t used; uint64_t value_size; int64_t key_kind; } AethMap;\n",
+);
+
+/// Frames kept by the shadow stack; deeper calls are counted but not named
+pub const TRACE_DEPTH: usize = 256;
+
+/// Definitions of `__aeth_panic` (and the shadow stack for `unwind-trace`).
+/// `json` reports each panic as a single JSON line instead of text.
+pub fn panic_runtime(mode: PanicMode, json: bool) -> String {
+    let trace = mode == PanicMode::UnwindTrace;
+    let mut out = String::new();
+    out.push_str("#include <stdio.h>\n#include <stdlib.h>\n#include <stdint.h>\n");
+    if trace {
+        out.push_str(&format!("static __thread const char* __aeth_trace_stack[{}];\n", TRACE_DEPTH));
+        out.push_str("static __thread int64_t __aeth_trace_depth;\n");
+        out.push_str(&format!(
+            "void {}(const char* name) {{ if (__aeth_trace_depth < {}) __aeth_trace_stack[__aeth_trace_depth] = name; __aeth_trace_depth++; }}\n",
+            TRACE_ENTER_FN, TRACE_DEPTH
+        ));
+        out.push_str(&format!(
+            "void {}(void) {{ if (__aeth_trace_depth > 0) __aeth_trace_depth--; }}\n",
+            TRACE_EXIT_FN
+        ));
+    }
+    if json {
+        // Messages and paths are escaped so the line is always valid JSON
+        out.push_str(concat!(
+            "static void __aeth_json_string(const char* s) {\n",
+            "    fputc('\"', stderr);\n",
+            "    for (; s && *s; s++) {\n",
+            "        unsigned char c = (unsigned char)*s;\n",
+            "        if (c == '\"' || c == '\\\\') fprintf(stderr, \"\\\\%c\", c);\n",
+            "        else if (c < 0x20) fprintf(stderr, \"\\\\u%04x\", c);\n",
+            "        else fputc(c, stderr);\n",
+            "    }\n",
+            "    fputc('\"', stderr);\n",
+            "}\n",
+        ));
+    }
+    out.push_str(&format!("void {}(const char* msg, const char* file, int64_t line) {{\n", PANIC_FN));
+    // Output printed before the failure must not be lost to abort()
+    out.push_str("    fflush(stdout);\n");
+    if json {
+        out.push_str("    fputs(\"{\\\"type\\\":\\\"panic\\\",\\\"message\\\":\", stderr);\n");
+        out.push_str("    __aeth_json_string(msg);\n");
+        out.push_str("    fputs(\",\\\"file\\\":\", stderr);\n");
+        out.push_str("    __aeth_json_string(file);\n");
+        out.push_str("    fprintf(stderr, \",\\\"line\\\":%lld\", (long long)line);\n");
+        if trace {
+            out.push_str(&format!("    int64_t n = __aeth_trace_depth < {0} ? __aeth_trace_depth : {0};\n", TRACE_DEPTH));
+            out.push_str("    fputs(\",\\\"backtrace\\\":[\", stderr);\n");
+            out.push_str("    for (int64_t i = 0; i < n; i++) { if (i) fputc(',', stderr); __aeth_json_string(__aeth_trace_stack[i]); }\n");
+            out.push_str("    fputc(']', stderr);\n");
+        }
+        out.push_str("    fputs(\"}\\n\", stderr);\n");
+    } else {
+        out.push_str("    fprintf(stderr, \"panic at %s:%lld: %s\\n\", file, (long long)line, msg);\n");
+        if trace {
+            // Outermost call first, matching the order the functions were entered
+            out.push_str(&format!("    int64_t n = __aeth_trace_depth < {0} ? __aeth_trace_depth : {0};\n", TRACE_DEPTH));
+            out.push_str("    fputs(\"backtrace:\\n\", stderr);\n");
+            out.push_str("    for (int64_t i = 0; i < n; i++) fprintf(stderr, \"  %lld: %s\\n\", (long long)i, __aeth_trace_stack[i]);\n");
+            out.push_str("    if (__aeth_trace_depth > n) fprintf(stderr, \"  ... %lld deeper frames\\n\", (long long)(__aeth_trace_depth - n));\n");
+        }
+    }
+    out.push_str("    fflush(stderr);\n    abort();\n}\n");
+    out
+}
+
+/// Runtime source for a program whose code comes from another backend, or
+/// `None` if `module` needs none
+#[cfg_attr(not(feature = "llvm"), allow(dead_code))]
+pub fn standalone_runtime(module: &IRModule, json: bool) -> Option<String> {
+    let uses_collections = collections::uses_runtime(module);
+    let uses_files = fileio::uses_runtime(module);
+    let uses_bench = bench::uses_runtime(module);
+    if !panic::uses_runtime(module) && !uses_collections && !uses_files && !uses_bench {
+        return None;
+    }
+    let mut out = panic_runtime(panic::mode_of(module), json);
+    if uses_collections || uses_files {
+        out.push_str("#include <stddef.h>\n");
+        out.push_str(STR_TYPEDEF);
+        out.push('\n');
+    }
+    if uses_collections {
+        out.push_str(COLLECTION_TYPEDEFS);
+        out.push_str(&collections_runtime());
+    }
+    if uses_files {
+        out.push_str(&file_runtime());
+    }
+    if uses_bench {
+        out.push_str(&bench_runtime());
+    }
+    Some(out)
+}
+
+/// Definitions of the `Vec` and `HashMap` runtime functions. Callers check
+/// bounds and key presence themselves so the panic can carry a source location.
+pub fn collections_runtime() -> String {
+    let mut out = String::new();
+    out.push_str("#include <string.h>\n#include <stdbool.h>\n");
+    out.push_str(&format!(
+        "static void* __aeth_alloc_ok(void* p) {{ if (!p) {}(\"out of memory\", \"<runtime>\", 0); return p; }}\n",
+        PANIC_FN
+    ));
+
+    // Vec: capacity doubles from 4, or from the one reserved by `with_capacity`,
+    // whose buffer waits for the first push to learn the element size
+    out.push_str(&format!("AethVec* {}(void) {{ return __aeth_alloc_ok(calloc(1, sizeof(AethVec))); }}\n", VEC_NEW));
+    out.push_str(&format!(
+        "AethVec* {}(uint64_t cap) {{ AethVec* v = __aeth_alloc_ok(calloc(1, sizeof(AethVec))); v->cap = cap; return v; }}\n",
+        VEC_WITH_CAPACITY
+    ));
+    out.push_str(&format!(concat!(
+        "void {}(AethVec* v, const void* elem, uint64_t size) {{\n",
+        "    if (v->len == v->cap || !v->data) {{\n",
+        "        if (v->data || !v->cap) v->cap = v->cap ? v->cap * 2 : 4;\n",
+        "        v->data = __aeth_alloc_ok(realloc(v->data, v->cap * size));\n",
+        "    }}\n",
+        "    memcpy(v->data + v->len * size, elem, size);\n",
+        "    v->len++;\n",
+        "}}\n"), VEC_PUSH));
+    out.push_str(&format!("void* {}(AethVec* v, uint64_t size) {{ v->len--; return v->data + v->len * size; }}\n", VEC_POP));
+    out.push_str(&format!("void* {}(AethVec* v, uint64_t index, uint64_t size) {{ return v->data + index * size; }}\n", VEC_GET));
+    out.push_str(&format!("uint64_t {}(AethVec* v) {{ return v->len; }}\n", VEC_LEN));
+    out.push_str(&format!("void {}(AethVec* v) {{ v->len = 0; }}\n", VEC_CLEAR));
+    out.push_str(&format!("void {}(AethVec* v) {{ if (v) {{ free(v->data); free(v); }} }}\n", VEC_FREE));
+
+    // HashMap: open addressing with linear probing; FNV-1a for strings, a
+    // 64-bit finalizer for integers. Tables stay at most 3/4 used.
+    let str_kind = KeyKind::Str as i64;
+    out.push_str(&format!(concat!(
+        "static uint64_t __aeth_map_hash(const void* key, int64_t kind) {{\n",
+        "    if (kind == {0}) {{\n",
+        "        const AethStr* s = key;\n",
+        "        uint64_t h = 1469598103934665603ULL;\n",
+        "        for (size_t i = 0; i < s->len; i++) {{ h ^= (uint8_t)s->data[i]; h *= 1099511628211ULL; }}\n",
+        "        return h;\n",
+        "    }}\n",
+        "    uint64_t x = *(const uint64_t*)key;\n",
+        "    x ^= x >> 33; x *= 0xff51afd7ed558ccdULL; x ^= x >> 33; x *= 0xc4ceb9fe1a85ec53ULL; x ^= x >> 33;\n",
+        "    return x;\n",
+        "}}\n",
+        "static bool __aeth_map_key_eq(const void* a, const void* b, int64_t kind) {{\n",
+        "    if (kind == {0}) {{ const AethStr* x = a; const AethStr* y = b; return x->len == y->len && memcmp(x->data, y->data, x->len) == 0; }}\n",
+        "    return *(const int64_t*)a == *(const int64_t*)b;\n",
+        "}}\n"), str_kind));
+    out.push_str(concat!(
+        "static uint8_t* __aeth_map_slot(AethMap* m, uint64_t i) { return m->slots + i * (16 + m->value_size); }\n",
+        "/* Slot holding key, or the slot to insert it into */\n",
+        "static uint64_t __aeth_map_find(AethMap* m, const void* key, bool* found) {\n",
+        "    uint64_t mask = m->cap - 1, i = __aeth_map_hash(key, m->key_kind) & mask;\n",
+        "    int64_t removed = -1;\n",
+        "    for (;;) {\n",
+        "        if (m->states[i] == 0) { *found = false; return removed >= 0 ? (uint64_t)removed : i; }\n",
+        "        if (m->states[i] == 2) { if (removed < 0) removed = (int64_t)i; }\n",
+        "        else if (__aeth_map_key_eq(__aeth_map_slot(m, i), key, m->key_kind)) { *found = true; return i; }\n",
+        "        i = (i + 1) & mask;\n",
+        "    }\n",
+        "}\n",
+        "static void __aeth_map_rehash(AethMap* m, uint64_t cap) {\n",
+        "    AethMap old = *m;\n",
+        "    uint64_t slot = 16 + m->value_size;\n",
+        "    m->cap = cap; m->len = 0; m->used = 0;\n",
+        "    m->slots = __aeth_alloc_ok(calloc(cap, slot));\n",
+        "    m->states = __aeth_alloc_ok(calloc(cap, 1));\n",
+        "    for (uint64_t i = 0; i < old.cap; i++) {\n",
+        "        if (old.states[i] != 1) continue;\n",
+        "        bool found;\n",
+        "        uint64_t j = __aeth_map_find(m, old.slots + i * slot, &found);\n",
+        "        memcpy(__aeth_map_slot(m, j), old.slots + i * slot, slot);\n",
+        "        m->states[j] = 1; m->len++; m->used++;\n",
+        "    }\n",
+        "    free(old.slots); free(old.states);\n",
+        "}\n",
+    ));
+    out.push_str(&format!("AethMap* {}(void) {{ return __aeth_alloc_ok(calloc(1, sizeof(AethMap))); }}\n", MAP_NEW));
+    out.push_str(&format!(concat!(
+        "void {}(AethMap* m, const void* key, int64_t kind, const void* value, uint64_t size) {{\n",
+        "    if (m->cap == 0) {{ m->key_kind = kind; m->value_size = size; __aeth_map_rehash(m, 8); }}\n",
+        "    else if ((m->used + 1) * 4 > m->cap * 3) __aeth_map_rehash(m, (m->len + 1) * 2 > m->cap ? m->cap * 2 : m->cap);\n",
+        "    bool found;\n",
+        "    uint64_t i = __aeth_map_find(m, key, &found);\n",
+        "    uint8_t* slot = __aeth_map_slot(m, i);\n",
+        "    if (!found) {{\n",
+        "        memset(slot, 0, 16);\n",
+        "        memcpy(slot, key, kind == {} ? sizeof(AethStr) : sizeof(int64_t));\n",
+        "        if (m->states[i] == 0) m->used++;\n",
+        "        m->states[i] = 1; m->len++;\n",
+        "    }}\n",
+        "    memcpy(slot + 16, value, size);\n",
+        "}}\n"), MAP_INSERT, str_kind));
+    out.push_str(&format!(concat!(
+        "void* {}(AethMap* m, const void* key, int64_t kind) {{\n",
+        "    (void)kind;\n",
+        "    if (m->cap == 0) return NULL;\n",
+        "    bool found;\n",
+        "    uint64_t i = __aeth_map_find(m, key, &found);\n",
+        "    return found ? __aeth_map_slot(m, i) + 16 : NULL;\n",
+        "}}\n"), MAP_GET));
+    out.push_str(&format!("bool {}(AethMap* m, const void* key, int64_t kind) {{ return {}(m, key, kind) != NULL; }}\n", MAP_CONTAINS, MAP_GET));
+    out.push_str(&format!(concat!(
+        "bool {}(AethMap* m, const void* key, int64_t kind) {{\n",
+        "    (void)kind;\n",
+        "    if (m->cap == 0) return false;\n",
+        "    bool found;\n",
+        "    uint64_t i = __aeth_map_find(m, key, &found);\n",
+        "    if (found) {{ m->states[i] = 2; m->len--; }}\n",
+        "    return found;\n",
+        "}}\n"), MAP_REMOVE));
+    out.push_str(&format!("uint64_t {}(AethMap* m) {{ return m->len; }}\n", MAP_LEN));
+    out.push_str(&format!("void {}(AethMap* m) {{ if (m) {{ free(m->slots); free(m->states); free(m); }} }}\n", MAP_FREE));
+    out
+}
+
+/// Definitions of the file I/O runtime functions; see `middle::fileio`
+pub fn file_runtime() -> String {
+    let mut out = String::new();
+    out.push_str("#include <errno.h>\n#include <string.h>\n");
+    out.push_str("static int32_t __aeth_errno;\n");
+    out.push_str("static int32_t __aeth_io_result(int32_t failed) { __aeth_errno = failed ? (errno ? errno : EIO) : 0; return failed ? -1 : 0; }\n");
+    // Paths are not NUL-terminated, so each call copies one
+    out.push_str(concat!(
+        "static char* __aeth_c_path(AethStr s) {\n",
+        "    char* p = malloc(s.len + 1);\n",
+        "    if (p) { memcpy(p, s.data, s.len); p[s.len] = 0; }\n",
+        "    return p;\n",
+        "}\n",
+        "static FILE* __aeth_fopen(AethStr path, AethStr mode) {\n",
+        "    char* p = __aeth_c_path(path);\n",
+        "    char* m = __aeth_c_path(mode);\n",
+        "    errno = ENOMEM;\n",
+        "    FILE* f = p && m ? fopen(p, m) : NULL;\n",
+        "    free(p); free(m);\n",
+        "    return f;\n",
+        "}\n",
+    ));
+    out.push_str(&format!(concat!(
+        "AethStr {}(AethStr path) {{\n",
+        "    AethStr out = {{ NULL, 0 }};\n",
+        "    FILE* f = __aeth_fopen(path, (AethStr){{ (char*)\"rb\", 2 }});\n",
+        "    if (!f) {{ __aeth_io_result(1); return out; }}\n",
+        "    long size = fseek(f, 0, SEEK_END) == 0 ? ftell(f) : -1;\n",
+        "    char* data = size >= 0 && fseek(f, 0, SEEK_SET) == 0 ? malloc((size_t)size + 1) : NULL;\n",
+        "    size_t got = data ? fread(data, 1, (size_t)size, f) : 0;\n",
+        "    if (!data || ferror(f)) {{ __aeth_io_result(1); free(data); fclose(f); return out; }}\n",
+        "    fclose(f);\n",
+        "    data[got] = 0;\n",
+        "    out.data = data; out.len = got;\n",
+        "    __aeth_io_result(0);\n",
+        "    return out;\n",
+        "}}\n"), READ_FILE));
+    out.push_str(&format!(concat!(
+        "int32_t {}(AethStr path, const uint8_t* data, uint64_t len) {{\n",
+        "    FILE* f = __aeth_fopen(path, (AethStr){{ (char*)\"wb\", 2 }});\n",
+        "    if (!f) return __aeth_io_result(1);\n",
+        "    int failed = fwrite(data, 1, len, f) != len;\n",
+        "    failed |= fclose(f) != 0;\n",
+        "    return __aeth_io_result(failed);\n",
+        "}}\n"), WRITE_FILE));
+    out.push_str(&format!(
+        "uint8_t* {}(AethStr path, AethStr mode) {{ FILE* f = __aeth_fopen(path, mode); __aeth_io_result(!f); return (uint8_t*)f; }}\n",
+        FILE_OPEN
+    ));
+    for (func, call) in [(FILE_READ, "fread(buf, 1, len, (FILE*)f)"), (FILE_WRITE, "fwrite(buf, 1, len, (FILE*)f)")] {
+        out.push_str(&format!(concat!(
+            "int64_t {}(uint8_t* f, void* buf, uint64_t len) {{\n",
+            "    if (!f) {{ errno = EBADF; return __aeth_io_result(1); }}\n",
+            "    size_t n = {};\n",
+            "    if (n < len && ferror((FILE*)f)) {{ clearerr((FILE*)f); return __aeth_io_result(1); }}\n",
+            "    __aeth_io_result(0);\n",
+            "    return (int64_t)n;\n",
+            "}}\n"), func, call));
+    }
+    out.push_str(&format!(
+        "int32_t {}(uint8_t* f) {{ if (!f) {{ errno = EBADF; return __aeth_io_result(1); }} return __aeth_io_result(fclose((FILE*)f) != 0); }}\n",
+        FILE_CLOSE
+    ));
+    out.push_str(&format!("int32_t {}(void) {{ return __aeth_errno; }}\n", LAST_ERROR));
+    out
+}
+
+/// Definitions of the benchmark runtime functions; see `middle::bench`.
+/// Samples are folded into a running mean and variance (Welford's method).
+pub fn bench_runtime() -> String {
+    let mut out = String::new();
+    out.push_str("#include <string.h>\n#include <time.h>\n");
+    out.push_str("static uint64_t __aeth_bench_count;\n");
+    out.push_str("static double __aeth_bench_mean, __aeth_bench_m2;\n");
+    out.push_str(&format!(concat!(
+        "int64_t {}(void) {{\n",
+        "    struct timespec ts;\n",
+        "    clock_gettime(CLOCK_MONOTONIC, &ts);\n",
+        "    return (int64_t)ts.tv_sec * 1000000000LL + ts.tv_nsec;\n",
+        "}}\n"), NOW_FN));
+    out.push_str(&format!(concat!(
+        "void {}(int64_t ns) {{\n",
+        "    double delta = (double)ns - __aeth_bench_mean;\n",
+        "    __aeth_bench_count++;\n",
+        "    __aeth_bench_mean += delta / (double)__aeth_bench_count;\n",
+        "    __aeth_bench_m2 += delta * ((double)ns - __aeth_bench_mean);\n",
+        "}}\n"), SAMPLE_FN));
+    out.push_str(&format!("void {}(int64_t v) {{ __asm__ volatile(\"\" : : \"r\"(v) : \"memory\"); }}\n", BLACK_BOX_FN));
+    // sqrt without libm: Newton's method from an overestimate
+    out.push_str(concat!(
+        "static double __aeth_bench_sqrt(double x) {\n",
+        "    if (x <= 0) return 0;\n",
+        "    double r = x > 1 ? x : 1;\n",
+        "    for (int i = 0; i < 64; i++) r = 0.5 * (r + x / r);\n",
+        "    return r;\n",
+        "}\n",
+    ));
+    // Four significant digits in the largest unit that keeps the value >= 1, as criterion prints
+    out.push_str(concat!(
+        "static void __aeth_bench_time(char* buf, size_t size, double ns) {\n",
+        "    static const char* units[] = { \"ps\", \"ns\", \"µs\", \"ms\", \"s\" };\n",
+        "    double v = ns * 1000.0;\n",
+        "    int u = 0;\n",
+        "    while (u < 4 && v >= 1000.0) { v /= 1000.0; u++; }\n",
+        "    int decimals = v < 10 ? 4 : v < 100 ? 3 : v < 1000 ? 2 : 1;\n",
+        "    snprintf(buf, size, \"%.*f %s\", decimals, v, units[u]);\n",
+        "}\n",
+    ));
+    out.push_str(&format!(concat!(
+        "void {}(const char* name) {{\n",
+        "    uint64_t n = __aeth_bench_count;\n",
+        "    double sd = n > 1 ? __aeth_bench_sqrt(__aeth_bench_m2 / (double)(n - 1)) : 0;\n",
+        "    double half = n > 0 ? 1.96 * sd / __aeth_bench_sqrt((double)n) : 0;\n",
+        "    char lo[32], mean[32], hi[32], dev[32];\n",
+        "    __aeth_bench_time(lo, sizeof lo, __aeth_bench_mean - half > 0 ? __aeth_bench_mean - half : 0);\n",
+        "    __aeth_bench_time(mean, sizeof mean, __aeth_bench_mean);\n",
+        "    __aeth_bench_time(hi, sizeof hi, __aeth_bench_mean + half);\n",
+        "    __aeth_bench_time(dev, sizeof dev, sd);\n",
+        "    if (strlen(name) > 23) printf(\"%s\\n%-24s\", name, \"\");\n",
+        "    else printf(\"%-24s\", name);\n",
+        "    printf(\"time:   [%s %s %s]\\n\", lo, mean, hi);\n",
+        "    printf(\"%-24sstd. dev.: %s (%llu iterations)\\n\", \"\", dev, (unsigned long long)n);\n",
+        "    fflush(stdout);\n",
+        "    __aeth_bench_count = 0;\n",
+        "    __aeth_bench_mean = __aeth_bench_m2 = 0;\n",
+        "}}\n"), REPORT_FN));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_panic_runtime() {
+        let plain = panic_runtime(PanicMode::Abort, false);
+        assert!(plain.contains("void __aeth_panic(const char* msg, const char* file, int64_t line)"));
+        assert!(plain.contains("abort();"));
+        assert!(!plain.contains(TRACE_ENTER_FN));
+
+        let traced = panic_runtime(PanicMode::UnwindTrace, true);
+        assert!(traced.contains("void __aeth_trace_enter(const char* name)"));
+        assert!(traced.contains("\\\"backtrace\\\""));
+    }
+
+    #[test]
+    fn test_collections_runtime() {
+        let rt = collections_runtime();
+        for func in crate::middle::collections::RUNTIME_FNS {
+            assert!(rt.contains(&format!(" {}(", func)), "{} is not defined", func);
+        }
+        assert!(rt.contains("v->cap ? v->cap * 2 : 4"));
+    }
+
+    #[test]
+    fn test_file_runtime() {
+        let rt = file_runtime();
+        for (_, func) in fileio::BUILTINS {
+            assert!(rt.contains(&format!(" {}(", func)), "{} is not defined", func);
+        }
+    }
+
+    #[test]
+    fn test_bench_runtime() {
+        let rt = bench_runtime();
+        for func in [NOW_FN, SAMPLE_FN, BLACK_BOX_FN, REPORT_FN] {
+            assert!(rt.contains(&format!(" {}(", func)), "{} is not defined", func);
+        }
+        assert!(rt.contains("CLOCK_MONOTONIC"));
+    }
+}
@@ -0,0 +1,361 @@
+//! Static linking of relocatable ELF objects
+//!
+//! Reads the x86-64 `.o` files a C compiler produces for `--freestanding`
+//! builds, lays their allocated sections out in three segments (code,
+//! read-only data, writable data), applies the relocations and writes the
+//! executable with the self-hosted `Linker`. Only what non-PIC static code
+//! needs is supported: no GOT, PLT, TLS or shared libraries.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::backend::linker::{
+    Linker, DEFAULT_BASE_ADDRESS, EM_X86_64, ET_REL, PF_R, PF_W, PF_X, SHF_ALLOC, SHF_EXECINSTR,
+    SHF_WRITE, SHT_NOBITS, SHT_PROGBITS, SHT_REL, SHT_RELA, SHT_SYMTAB,
+};
+use crate::utils::{Error, Result};
+
+/// C compiler flags for an object `link_objects` can link: no C library
+/// and no code needing a GOT, stack protector or unwind tables
+pub const FREESTANDING_CFLAGS: &[&str] = &[
+    "-ffreestanding", "-nostdlib", "-fno-builtin", "-fno-pic", "-fno-stack-protector",
+    "-fno-asynchronous-unwind-tables", "-fno-common",
+];
+
+const PAGE_SIZE: u64 = 0x1000;
+
+const SHN_UNDEF: u16 = 0;
+const SHN_ABS: u16 = 0xFFF1;
+const SHN_COMMON: u16 = 0xFFF2;
+
+const STB_LOCAL: u8 = 0;
+const STB_WEAK: u8 = 2;
+
+const R_X86_64_NONE: u32 = 0;
+const R_X86_64_64: u32 = 1;
+const R_X86_64_PC32: u32 = 2;
+const R_X86_64_PLT32: u32 = 4;
+const R_X86_64_32: u32 = 10;
+const R_X86_64_32S: u32 = 11;
+const R_X86_64_PC64: u32 = 24;
+
+/// Output segments, in address order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Group {
+    Text,
+    ReadOnly,
+    Data,
+}
+
+const GROUPS: [(Group, &str, u32); 3] = [
+    (Group::Text, ".text", PF_R | PF_X),
+    (Group::ReadOnly, ".rodata", PF_R),
+    (Group::Data, ".data", PF_R | PF_W),
+];
+
+impl Group {
+    fn of(flags: u64) -> Self {
+        if flags & SHF_EXECINSTR != 0 {
+            Group::Text
+        } else if flags & SHF_WRITE != 0 {
+            Group::Data
+        } else {
+            Group::ReadOnly
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+struct SectionHeader {
+    name: String,
+    sh_type: u32,
+    flags: u64,
+    offset: usize,
+    size: usize,
+    link: usize,
+    info: usize,
+    align: u64,
+}
+
+struct ElfSymbol {
+    name: String,
+    bind: u8,
+    shndx: u16,
+    value: u64,
+    size: u64,
+}
+
+/// A relocatable object being linked
+struct Object<'a> {
+    name: String,
+    bytes: &'a [u8],
+    sections: Vec<SectionHeader>,
+    symbols: Vec<ElfSymbol>,
+    /// Output segment and offset in it of each placed section
+    placement: HashMap<usize, (Group, u64)>,
+}
+
+fn link_error(object: &str, message: impl std::fmt::Display) -> Error {
+    Error::Link(format!("{}: {}", object, message))
+}
+
+fn read<const N: usize>(bytes: &[u8], offset: usize) -> Option<[u8; N]> {
+    bytes.get(offset..offset.checked_add(N)?)?.try_into().ok()
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Option<u16> {
+    read(bytes, offset).map(u16::from_le_bytes)
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    read(bytes, offset).map(u32::from_le_bytes)
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> Option<u64> {
+    read(bytes, offset).map(u64::from_le_bytes)
+}
+
+/// NUL-terminated string at `offset` of a string table
+fn c_str(table: &[u8], offset: usize) -> String {
+    let tail = table.get(offset..).unwrap_or_default();
+    let end = tail.iter().position(|&b| b == 0).unwrap_or(tail.len());
+    String::from_utf8_lossy(&tail[..end]).into_owned()
+}
+
+fn align_to(offset: u64, align: u64) -> u64 {
+    if align <= 1 { offset } else { offset.div_ceil(align) * align }
+}
+
+impl<'a> Object<'a> {
+    fn parse(name: &str, bytes: &'a [u8]) -> Result<Self> {
+        let malformed = || link_error(name, "not a valid ELF object");
+        if bytes.get(..4) != Some(b"\x7fELF") || bytes.get(4) != Some(&2) || bytes.get(5) != Some(&1) {
+            return Err(link_error(name, "not a 64-bit little-endian ELF file"));
+        }
+        if u16_at(bytes, 16) != Some(ET_REL) {
+            return Err(link_error(name, "not a relocatable object"));
+        }
+        if u16_at(bytes, 18) != Some(EM_X86_64) {
+            return Err(link_error(name, "only x86-64 objects can be linked"));
+        }
+        let shoff = u64_at(bytes, 40).ok_or_else(malformed)? as usize;
+        let shentsize = u16_at(bytes, 58).ok_or_else(malformed)? as usize;
+        let shnum = u16_at(bytes, 60).ok_or_else(malformed)? as usize;
+        let shstrndx = u16_at(bytes, 62).ok_or_else(malformed)? as usize;
+
+        let mut headers = Vec::with_capacity(shnum);
+        for i in 0..shnum {
+            let at = shoff + i * shentsize;
+            let field32 = |off| u32_at(bytes, at + off).ok_or_else(malformed);
+            let field64 = |off| u64_at(bytes, at + off).ok_or_else(malformed);
+            headers.push((field32(0)?, SectionHeader {
+                name: String::new(),
+                sh_type: field32(4)?,
+                flags: field64(8)?,
+                offset: field64(24)? as usize,
+                size: field64(32)? as usize,
+                link: field32(40)? as usize,
+                info: field32(44)? as usize,
+                align: field64(48)?,
+            }));
+        }
+        let names = headers.get(shstrndx).map(|(_, h)| (h.offset, h.size));
+        let mut sections = Vec::with_capacity(shnum);
+        for (name_offset, mut header) in headers {
+            let table = names.and_then(|(offset, size)| bytes.get(offset..offset + size)).unwrap_or_default();
+            header.name = c_str(table, name_offset as usize);
+            if header.sh_type != SHT_NOBITS && bytes.get(header.offset..header.offset + header.size).is_none() {
+                return Err(malformed());
+            }
+            sections.push(header);
+        }
+
+        let mut object = Self { name: name.to_string(), bytes, sections, symbols: Vec::new(), placement: HashMap::new() };
+        object.symbols = object.read_symbols();
+        Ok(object)
+    }
+
+    fn data(&self, index: usize) -> &'a [u8] {
+        let section = &self.sections[index];
+        &self.bytes[section.offset..section.offset + section.size]
+    }
+
+    fn read_symbols(&self) -> Vec<ElfSymbol> {
+        let Some(index) = self.sections.iter().position(|s| s.sh_type == SHT_SYMTAB) else {
+            return Vec::new();
+        };
+        let table = self.data(index);
+        let strings = self.sections.get(self.sections[index].link).map(|_| self.data(self.sections[index].link)).unwrap_or_default();
+        table.chunks_exact(24).map(|entry| ElfSymbol {
+            name: c_str(strings, u32::from_le_bytes(entry[0..4].try_into().unwrap()) as usize),
+            bind: entry[4] >> 4,
+            shndx: u16::from_le_bytes(entry[6..8].try_into().unwrap()),
+            value: u64::from_le_bytes(entry[8..16].try_into().unwrap()),
+            size: u64::from_le_bytes(entry[16..24].try_into().unwrap()),
+        }).collect()
+    }
+}
+
+/// Address of symbol `index` of `object`, resolving undefined ones through `globals`
+fn symbol_address(object: &Object, index: usize, bases: &[u64; 3], globals: &HashMap<String, u64>) -> Result<u64> {
+    let symbol = object.symbols.get(index)
+        .ok_or_else(|| link_error(&object.name, format!("relocation names missing symbol {}", index)))?;
+    match symbol.shndx {
+        SHN_UNDEF => match globals.get(&symbol.name) {
+            Some(&address) => Ok(address),
+            None if symbol.bind == STB_WEAK => Ok(0),
+            None => Err(link_error(&object.name, format!("undefined symbol `{}`", symbol.name))),
+        },
+        SHN_ABS => Ok(symbol.value),
+        SHN_COMMON => Err(link_error(&object.name, format!("common symbol `{}` (compile with -fno-common)", symbol.name))),
+        shndx => match object.placement.get(&(shndx as usize)) {
+            Some(&(group, offset)) => Ok(bases[group.index()] + offset + symbol.value),
+            None => Err(link_error(&object.name, format!("`{}` is in a section that is not loaded", symbol.name))),
+        },
+    }
+}
+
+/// Link the relocatable x86-64 objects `objects` (name and contents) into
+/// a static executable at `path` that starts at symbol `entry`.
+/// `map` receives the link map when given.
+pub fn link_objects(objects: &[(String, Vec<u8>)], entry: &str, path: &Path, map: Option<&Path>) -> Result<()> {
+    let mut objects = objects.iter()
+        .map(|(name, bytes)| Object::parse(name, bytes))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Place every allocated section in its segment; `.bss` is zero-filled
+    let mut contents: [Vec<u8>; 3] = Default::default();
+    let mut aligns = [1u64; 3];
+    for object in &mut objects {
+        for (index, section) in object.sections.iter().enumerate() {
+            if section.flags & SHF_ALLOC == 0 || !matches!(section.sh_type, SHT_PROGBITS | SHT_NOBITS) && section.name != ".eh_frame" {
+                continue;
+            }
+            let group = Group::of(section.flags);
+            let buffer = &mut contents[group.index()];
+            let offset = align_to(buffer.len() as u64, section.align);
+            buffer.resize(offset as usize, 0);
+            if section.sh_type == SHT_NOBITS {
+                buffer.resize(buffer.len() + section.size, 0);
+            } else {
+                buffer.extend_from_slice(&object.bytes[section.offset..section.offset + section.size]);
+            }
+            aligns[group.index()] = aligns[group.index()].max(section.align);
+            object.placement.insert(index, (group, offset));
+        }
+    }
+
+    // Each segment starts on its own page after the previous one
+    let mut bases = [0u64; 3];
+    let mut next = DEFAULT_BASE_ADDRESS + PAGE_SIZE;
+    for (group, _, _) in GROUPS {
+        bases[group.index()] = next;
+        next = align_to(next + contents[group.index()].len() as u64, PAGE_SIZE);
+    }
+
+    let mut globals = HashMap::new();
+    let mut linker = Linker::new();
+    for object in &objects {
+        for (index, symbol) in object.symbols.iter().enumerate() {
+            if symbol.bind == STB_LOCAL || symbol.shndx == SHN_UNDEF || symbol.name.is_empty() {
+                continue;
+            }
+            let address = symbol_address(object, index, &bases, &globals)?;
+            if globals.insert(symbol.name.clone(), address).is_some() && symbol.bind != STB_WEAK {
+                return Err(link_error(&object.name, format!("`{}` is defined more than once", symbol.name)));
+            }
+            linker.add_symbol(&symbol.name, address, symbol.size);
+        }
+    }
+
+    for object in &objects {
+        for section in &object.sections {
+            if section.sh_type == SHT_REL {
+                return Err(link_error(&object.name, "REL relocations are not supported on x86-64"));
+            }
+            if section.sh_type != SHT_RELA {
+                continue;
+            }
+            let Some(&(group, target)) = object.placement.get(&section.info) else {
+                continue;
+            };
+            let relocations = &object.bytes[section.offset..section.offset + section.size];
+            for entry in relocations.chunks_exact(24) {
+                let offset = u64::from_le_bytes(entry[0..8].try_into().unwrap());
+                let info = u64::from_le_bytes(entry[8..16].try_into().unwrap());
+                let addend = i64::from_le_bytes(entry[16..24].try_into().unwrap());
+                let kind = info as u32;
+                if kind == R_X86_64_NONE {
+                    continue;
+                }
+                let symbol = symbol_address(object, (info >> 32) as usize, &bases, &globals)?;
+                let at = (target + offset) as usize;
+                let place = bases[group.index()] + target + offset;
+                let value = symbol.wrapping_add(addend as u64);
+                let buffer = &mut contents[group.index()];
+                let overflow = || link_error(&object.name, format!("relocation at {:#x} is out of range", place));
+                let bytes: Vec<u8> = match kind {
+                    R_X86_64_64 => value.to_le_bytes().to_vec(),
+                    R_X86_64_PC64 => value.wrapping_sub(place).to_le_bytes().to_vec(),
+                    R_X86_64_PC32 | R_X86_64_PLT32 => {
+                        let relative = value.wrapping_sub(place) as i64;
+                        i32::try_from(relative).map_err(|_| overflow())?.to_le_bytes().to_vec()
+                    }
+                    R_X86_64_32 => u32::try_from(value).map_err(|_| overflow())?.to_le_bytes().to_vec(),
+                    R_X86_64_32S => i32::try_from(value as i64).map_err(|_| overflow())?.to_le_bytes().to_vec(),
+                    other => return Err(link_error(&object.name, format!(
+                        "unsupported relocation type {} (compile with -fno-pic)", other
+                    ))),
+                };
+                buffer.get_mut(at..at + bytes.len())
+                    .ok_or_else(|| link_error(&object.name, format!("relocation at {:#x} is outside its section", place)))?
+                    .copy_from_slice(&bytes);
+            }
+        }
+    }
+
+    let entry_address = *globals.get(entry)
+        .ok_or_else(|| Error::Link(format!("entry symbol `{}` is not defined", entry)))?;
+    for (group, name, flags) in GROUPS {
+        let data = std::mem::take(&mut contents[group.index()]);
+        if data.is_empty() {
+            continue;
+        }
+        let vaddr = bases[group.index()];
+        let section_flags = match group {
+            Group::Text => SHF_ALLOC | SHF_EXECINSTR,
+            Group::ReadOnly => SHF_ALLOC,
+            Group::Data => SHF_ALLOC | SHF_WRITE,
+        };
+        linker.add_segment(data.clone(), flags, vaddr, PAGE_SIZE);
+        linker.add_section(name, data, SHT_PROGBITS, section_flags, vaddr, aligns[group.index()]);
+    }
+    linker.set_entry_point(entry_address);
+
+    let io_error = |path: &Path, e: std::io::Error| Error::Io(format!("{}: {}", path.display(), e));
+    linker.emit(path).map_err(|e| io_error(path, e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).map_err(|e| io_error(path, e))?;
+    }
+    if let Some(map) = map {
+        linker.emit_link_map(map).map_err(|e| io_error(map, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejects_non_objects() {
+        let path = std::env::temp_dir().join(format!("aether_static_link_{}", std::process::id()));
+        let err = link_objects(&[("a.o".to_string(), b"not elf".to_vec())], "_start", &path, None).unwrap_err();
+        assert!(err.to_string().contains("a.o: not a 64-bit little-endian ELF file"), "{}", err);
+    }
+}
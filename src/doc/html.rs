@@ -0,0 +1,190 @@
+//! HTML pages for `aethc doc`
+//!
+//! Each module page has a sidebar listing its items by kind and a section
+//! per item with its signature, doc comment and members.
+
+use super::{DocDatabase, DocFormat, ItemDoc, ItemKind, ModuleDoc};
+
+const STYLE: &str = "\
+body { margin: 0; font-family: sans-serif; display: flex; }
+nav { width: 16em; min-height: 100vh; padding: 1em; background: #f5f5f5; box-sizing: border-box; }
+nav h2 { font-size: 1em; margin-bottom: 0.3em; }
+nav ul { list-style: none; padding-left: 0.5em; margin: 0; }
+main { flex: 1; padding: 1em 2em; max-width: 60em; }
+pre { background: #f5f5f5; padding: 0.6em; overflow-x: auto; }
+pre.signature { font-weight: bold; }
+.item { border-top: 1px solid #ddd; margin-top: 1.5em; }
+.member { margin-left: 1.5em; }
+a { color: #3060a0; text-decoration: none; }
+";
+
+/// Page of `module`, linking to the other modules in `database`
+pub fn render_module(database: &DocDatabase, module: &ModuleDoc) -> String {
+    let mut body = format!("<h1>Module <code>{}</code></h1>\n", escape(&module.name));
+    for kind in ItemKind::SECTIONS {
+        for item in module.items_of(kind) {
+            render_item(item, &mut body);
+        }
+    }
+    page(&module.name, &sidebar(database, Some(module)), &body)
+}
+
+/// Index page listing every module and its items
+pub fn render_index(database: &DocDatabase) -> String {
+    let mut body = String::from("<h1>Modules</h1>\n<ul>\n");
+    for module in &database.modules {
+        body.push_str(&format!(
+            "<li><a href=\"{}\">{}</a> <small>{}</small></li>\n",
+            escape(&module.page(DocFormat::Html)), escape(&module.name), escape(&module.path)
+        ));
+    }
+    body.push_str("</ul>\n");
+    page("Documentation", &sidebar(database, None), &body)
+}
+
+fn page(title: &str, sidebar: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n{}<main>\n{}</main>\n</body>\n</html>\n",
+        escape(title), STYLE, sidebar, body
+    )
+}
+
+fn sidebar(database: &DocDatabase, current: Option<&ModuleDoc>) -> String {
+    let mut nav = String::from("<nav>\n<h2><a href=\"index.html\">Modules</a></h2>\n<ul>\n");
+    for module in &database.modules {
+        nav.push_str(&format!("<li><a href=\"{}\">{}</a></li>\n", escape(&module.page(DocFormat::Html)), escape(&module.name)));
+    }
+    nav.push_str("</ul>\n");
+    if let Some(module) = current {
+        for kind in ItemKind::SECTIONS {
+            let items: Vec<&ItemDoc> = module.items_of(kind).collect();
+            if items.is_empty() {
+                continue;
+            }
+            nav.push_str(&format!("<h2>{}</h2>\n<ul>\n", kind.title()));
+            for item in items {
+                nav.push_str(&format!("<li><a href=\"#{}\">{}</a></li>\n", escape(&item.anchor()), escape(&item.name)));
+            }
+            nav.push_str("</ul>\n");
+        }
+    }
+    nav.push_str("</nav>\n");
+    nav
+}
+
+fn render_item(item: &ItemDoc, out: &mut String) {
+    out.push_str(&format!("<section class=\"item\" id=\"{}\">\n", escape(&item.anchor())));
+    out.push_str(&format!("<h2>{} <code>{}</code></h2>\n", kind_label(item.kind), escape(&item.name)));
+    out.push_str(&format!("<pre class=\"signature\">{}</pre>\n", escape(&item.signature)));
+    out.push_str(&markdown_to_html(&item.doc));
+    for kind in [ItemKind::Field, ItemKind::Variant, ItemKind::Method] {
+        let members: Vec<&ItemDoc> = item.members.iter().filter(|m| m.kind == kind).collect();
+        if members.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("<h3>{}</h3>\n", kind.title()));
+        for member in members {
+            out.push_str(&format!(
+                "<div class=\"member\" id=\"{}.{}.{}\">\n<pre class=\"signature\">{}</pre>\n{}</div>\n",
+                member.kind.anchor(), escape(&item.name), escape(&member.name), escape(&member.signature), markdown_to_html(&member.doc)
+            ));
+        }
+    }
+    out.push_str("</section>\n");
+}
+
+fn kind_label(kind: ItemKind) -> &'static str {
+    match kind {
+        ItemKind::Struct => "Struct",
+        ItemKind::Enum => "Enum",
+        ItemKind::Interface => "Interface",
+        ItemKind::Trait => "Trait",
+        ItemKind::Type => "Type",
+        ItemKind::Const => "Constant",
+        ItemKind::Function => "Function",
+        ItemKind::Method => "Method",
+        ItemKind::Field => "Field",
+        ItemKind::Variant => "Variant",
+    }
+}
+
+/// Paragraphs, `-` lists and fenced code blocks of a doc comment
+fn markdown_to_html(lines: &[String]) -> String {
+    let mut html = String::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut in_list = false;
+    let mut code: Option<Vec<&str>> = None;
+    for line in lines {
+        let trimmed = line.trim();
+        if let Some(block) = code.as_mut() {
+            if trimmed.starts_with("```") {
+                html.push_str(&format!("<pre><code>{}</code></pre>\n", escape(&block.join("\n"))));
+                code = None;
+            } else {
+                block.push(line);
+            }
+            continue;
+        }
+        let item = trimmed.strip_prefix("- ").or_else(|| trimmed.strip_prefix("* "));
+        if trimmed.is_empty() || trimmed.starts_with("```") || item.is_some() {
+            flush_paragraph(&mut paragraph, &mut html);
+        }
+        if item.is_none() && in_list {
+            html.push_str("</ul>\n");
+            in_list = false;
+        }
+        if trimmed.starts_with("```") {
+            code = Some(Vec::new());
+        } else if let Some(item) = item {
+            if !in_list {
+                html.push_str("<ul>\n");
+                in_list = true;
+            }
+            html.push_str(&format!("<li>{}</li>\n", inline(item)));
+        } else if !trimmed.is_empty() {
+            paragraph.push(trimmed);
+        }
+    }
+    flush_paragraph(&mut paragraph, &mut html);
+    if in_list {
+        html.push_str("</ul>\n");
+    }
+    if let Some(block) = code {
+        html.push_str(&format!("<pre><code>{}</code></pre>\n", escape(&block.join("\n"))));
+    }
+    html
+}
+
+fn flush_paragraph(paragraph: &mut Vec<&str>, html: &mut String) {
+    if !paragraph.is_empty() {
+        html.push_str(&format!("<p>{}</p>\n", inline(&paragraph.join(" "))));
+        paragraph.clear();
+    }
+}
+
+/// Escape `text`, turning `code` spans into `<code>`
+fn inline(text: &str) -> String {
+    let mut html = String::new();
+    for (i, part) in text.split('`').enumerate() {
+        if i % 2 == 1 {
+            html.push_str(&format!("<code>{}</code>", escape(part)));
+        } else {
+            html.push_str(&escape(part));
+        }
+    }
+    html
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,58 @@
+//! Markdown pages for `aethc doc`
+
+use super::{DocDatabase, DocFormat, ItemDoc, ItemKind, ModuleDoc};
+
+/// Page of `module`, one section per kind of item
+pub fn render_module(module: &ModuleDoc) -> String {
+    let mut page = format!("# Module `{}`\n", module.name);
+    for kind in ItemKind::SECTIONS {
+        let items: Vec<&ItemDoc> = module.items_of(kind).collect();
+        if items.is_empty() {
+            continue;
+        }
+        page.push_str(&format!("\n## {}\n", kind.title()));
+        for item in items {
+            render_item(item, &mut page);
+        }
+    }
+    page
+}
+
+/// Index page listing every module with a summary of its items
+pub fn render_index(database: &DocDatabase) -> String {
+    let mut page = String::from("# Modules\n\n");
+    for module in &database.modules {
+        page.push_str(&format!("- [{}]({}) ({})\n", module.name, module.page(DocFormat::Markdown), module.path));
+        for item in &module.items {
+            let summary = item.summary();
+            if summary.is_empty() {
+                page.push_str(&format!("  - `{}`\n", item.name));
+            } else {
+                page.push_str(&format!("  - `{}`: {}\n", item.name, summary));
+            }
+        }
+    }
+    page
+}
+
+fn render_item(item: &ItemDoc, page: &mut String) {
+    page.push_str(&format!("\n### `{}`\n\n```aether\n{}\n```\n", item.name, item.signature));
+    if !item.doc.is_empty() {
+        page.push_str(&format!("\n{}\n", item.doc.join("\n")));
+    }
+    for kind in [ItemKind::Field, ItemKind::Variant, ItemKind::Method] {
+        let members: Vec<&ItemDoc> = item.members.iter().filter(|m| m.kind == kind).collect();
+        if members.is_empty() {
+            continue;
+        }
+        page.push_str(&format!("\n#### {}\n\n", kind.title()));
+        for member in members {
+            let summary = member.summary();
+            if summary.is_empty() {
+                page.push_str(&format!("- `{}`\n", member.signature));
+            } else {
+                page.push_str(&format!("- `{}`: {}\n", member.signature, summary));
+            }
+        }
+    }
+}
@@ -0,0 +1,509 @@
+//! Documentation Generator
+//!
+//! Collects the public items of a program and the modules it imports,
+//! together with their `///` comments, into a `DocDatabase`. The database
+//! is rendered as one HTML or Markdown page per module and saved as JSON
+//! for the language server's hover provider.
+
+pub mod html;
+pub mod markdown;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::frontend::ast::*;
+use crate::frontend::formatter::{expr_to_string, function_signature, params_to_string, type_to_string};
+use crate::frontend::module::ModuleLoader;
+use crate::utils::{Error, Result};
+
+/// File name of the JSON database inside the output directory
+pub const DATABASE_FILE: &str = "docs.json";
+
+/// Page format of `aethc doc`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocFormat {
+    Html,
+    Markdown,
+}
+
+impl DocFormat {
+    pub fn parse(name: &str) -> std::result::Result<Self, String> {
+        match name {
+            "html" => Ok(Self::Html),
+            "markdown" | "md" => Ok(Self::Markdown),
+            _ => Err(format!("unknown doc format '{}' (expected html or markdown)", name)),
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Markdown => "md",
+        }
+    }
+}
+
+/// What kind of definition an `ItemDoc` describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemKind {
+    Struct,
+    Enum,
+    Interface,
+    Trait,
+    Type,
+    Const,
+    Function,
+    Method,
+    Field,
+    Variant,
+}
+
+impl ItemKind {
+    /// Top-level kinds in the order pages list them
+    pub const SECTIONS: [ItemKind; 7] = [
+        ItemKind::Struct, ItemKind::Enum, ItemKind::Interface, ItemKind::Trait,
+        ItemKind::Type, ItemKind::Const, ItemKind::Function,
+    ];
+
+    /// Heading of a group of items of this kind
+    pub fn title(self) -> &'static str {
+        match self {
+            Self::Struct => "Structs",
+            Self::Enum => "Enums",
+            Self::Interface => "Interfaces",
+            Self::Trait => "Traits",
+            Self::Type => "Type Aliases",
+            Self::Const => "Constants",
+            Self::Function => "Functions",
+            Self::Method => "Methods",
+            Self::Field => "Fields",
+            Self::Variant => "Variants",
+        }
+    }
+
+    /// Prefix of the item's anchor, as in `struct.Point`
+    pub fn anchor(self) -> &'static str {
+        match self {
+            Self::Struct => "struct",
+            Self::Enum => "enum",
+            Self::Interface => "interface",
+            Self::Trait => "trait",
+            Self::Type => "type",
+            Self::Const => "const",
+            Self::Function => "fn",
+            Self::Method => "method",
+            Self::Field => "field",
+            Self::Variant => "variant",
+        }
+    }
+}
+
+/// One documented definition
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemDoc {
+    pub name: String,
+    pub kind: ItemKind,
+    /// Declaration as written, without a body
+    pub signature: String,
+    /// Lines of the `///` comment
+    pub doc: Vec<String>,
+    /// Fields, variants and methods of a type; method signatures of an interface
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<ItemDoc>,
+}
+
+impl ItemDoc {
+    fn new(name: &str, kind: ItemKind, signature: String, doc: &[String]) -> Self {
+        Self { name: name.to_string(), kind, signature, doc: doc.to_vec(), members: Vec::new() }
+    }
+
+    /// First paragraph of the doc comment, on one line
+    pub fn summary(&self) -> String {
+        self.doc.iter()
+            .map(|line| line.trim())
+            .take_while(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// `#name` target of the item on its module's page
+    pub fn anchor(&self) -> String {
+        format!("{}.{}", self.kind.anchor(), self.name)
+    }
+
+    /// Markdown shown when hovering over the item
+    pub fn hover_markdown(&self) -> String {
+        let mut text = format!("```aether\n{}\n```", self.signature);
+        if !self.doc.is_empty() {
+            text.push_str("\n\n");
+            text.push_str(&self.doc.join("\n"));
+        }
+        text
+    }
+}
+
+/// The documented items of one module
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModuleDoc {
+    /// `geo`, or `geo::shapes` for a `mod` block inside it
+    pub name: String,
+    /// Source file the module is defined in
+    pub path: String,
+    pub items: Vec<ItemDoc>,
+}
+
+impl ModuleDoc {
+    /// File name of the module's page
+    pub fn page(&self, format: DocFormat) -> String {
+        format!("{}.{}", self.name.replace("::", "."), format.extension())
+    }
+
+    /// Items of `kind`, in source order
+    pub fn items_of(&self, kind: ItemKind) -> impl Iterator<Item = &ItemDoc> {
+        self.items.iter().filter(move |item| item.kind == kind)
+    }
+}
+
+/// Everything `aethc doc` found, the documented file's module first
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DocDatabase {
+    pub modules: Vec<ModuleDoc>,
+}
+
+impl DocDatabase {
+    /// Load a database written by `aethc doc`
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| Error::Io(format!("{}: {}", path.display(), e)))?;
+        serde_json::from_str(&text)
+            .map_err(|e| Error::Io(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    /// Top-level item `name`, in `module` or else in any module
+    pub fn find(&self, module: Option<&str>, name: &str) -> Option<&ItemDoc> {
+        self.modules.iter()
+            .filter(|m| module.is_none_or(|module| m.name == module))
+            .flat_map(|m| &m.items)
+            .find(|item| item.name == name && item.kind != ItemKind::Method)
+    }
+
+    /// Method `name` of type `owner`, or of the only type that has one when
+    /// the owner is unknown
+    pub fn find_method(&self, owner: Option<&str>, name: &str) -> Option<&ItemDoc> {
+        let mut methods = self.modules.iter()
+            .flat_map(|m| &m.items)
+            .filter(|item| owner.is_none_or(|owner| item.name == owner))
+            .flat_map(|item| &item.members)
+            .filter(|member| member.kind == ItemKind::Method && member.name == name);
+        let first = methods.next()?;
+        (owner.is_some() || methods.next().is_none()).then_some(first)
+    }
+}
+
+/// Walks programs and collects their documented items
+#[derive(Debug, Clone, Default)]
+pub struct DocGenerator {
+    private_items: bool,
+}
+
+impl DocGenerator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also document items without `pub`
+    pub fn with_private_items(mut self, enabled: bool) -> Self {
+        self.private_items = enabled;
+        self
+    }
+
+    /// Document the program parsed from `path` and the project modules it
+    /// imports, which are looked up next to it
+    pub fn document_program(&self, path: &Path, program: &Program) -> Result<DocDatabase> {
+        let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("main");
+        let mut database = DocDatabase::default();
+        self.document_items(name, &path.display().to_string(), &program.items, &mut database.modules);
+
+        let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let mut loader = ModuleLoader::new();
+        loader.add_search_path(dir.to_path_buf());
+        for file in loader.dependency_files(&program.items) {
+            let Some(module) = file.file_stem().and_then(|s| s.to_str()) else { continue };
+            let parsed = loader.load_module(module)?;
+            self.document_items(module, &file.display().to_string(), &parsed.items, &mut database.modules);
+        }
+        Ok(database)
+    }
+
+    /// Add module `name` made of `items`, then its `mod` blocks, to `modules`
+    pub fn document_items(&self, name: &str, path: &str, items: &[Item], modules: &mut Vec<ModuleDoc>) {
+        let mut module = ModuleDoc { name: name.to_string(), path: path.to_string(), items: Vec::new() };
+        let mut nested = Vec::new();
+        for item in items {
+            match item {
+                Item::Function(f) if self.shown(f.is_pub) => {
+                    module.items.push(ItemDoc::new(&f.name.name, ItemKind::Function, function_signature(f), &f.doc));
+                }
+                Item::Struct(def) if self.shown(def.is_pub) => {
+                    let mut doc = ItemDoc::new(&def.name.name, ItemKind::Struct, struct_signature(def), &def.doc);
+                    for field in def.fields.iter().filter(|f| self.shown(f.is_pub)) {
+                        let signature = format!("{}: {}", field.name.name, type_to_string(&field.ty));
+                        doc.members.push(ItemDoc::new(&field.name.name, ItemKind::Field, signature, &[]));
+                    }
+                    doc.members.extend(self.methods(&def.name.name, items));
+                    module.items.push(doc);
+                }
+                // Enums are always public
+                Item::Enum(def) => {
+                    let mut doc = ItemDoc::new(&def.name.name, ItemKind::Enum, enum_signature(def), &def.doc);
+                    for variant in &def.variants {
+                        doc.members.push(ItemDoc::new(&variant.name.name, ItemKind::Variant, variant_signature(variant), &[]));
+                    }
+                    doc.members.extend(self.methods(&def.name.name, items));
+                    module.items.push(doc);
+                }
+                Item::Interface(def) | Item::Trait(def) if self.shown(def.is_pub) => {
+                    let kind = if matches!(item, Item::Trait(_)) { ItemKind::Trait } else { ItemKind::Interface };
+                    let keyword = if kind == ItemKind::Trait { "trait" } else { "interface" };
+                    let mut doc = ItemDoc::new(&def.name.name, kind, format!("{}{} {}{}", pub_prefix(def.is_pub), keyword, def.name.name, type_params(&def.type_params)), &[]);
+                    for sig in &def.methods {
+                        let mut signature = format!("fn {}({})", sig.name.name, params_to_string(&sig.params));
+                        if let Some(ret) = &sig.ret_type {
+                            signature.push_str(&format!(" -> {}", type_to_string(ret)));
+                        }
+                        doc.members.push(ItemDoc::new(&sig.name.name, ItemKind::Method, signature, &[]));
+                    }
+                    for method in &def.default_methods {
+                        doc.members.push(ItemDoc::new(&method.name.name, ItemKind::Method, function_signature(method), &method.doc));
+                    }
+                    module.items.push(doc);
+                }
+                Item::TypeAlias(alias) if self.shown(alias.is_pub) => {
+                    let signature = format!("{}type {}{} = {}", pub_prefix(alias.is_pub), alias.name.name, type_params(&alias.type_params), type_to_string(&alias.ty));
+                    module.items.push(ItemDoc::new(&alias.name.name, ItemKind::Type, signature, &[]));
+                }
+                // Constants are always public
+                Item::Const(def) => {
+                    let ty = def.ty.as_ref().map(|ty| format!(": {}", type_to_string(ty))).unwrap_or_default();
+                    let signature = format!("const {}{} = {}", def.name.name, ty, expr_to_string(&def.value));
+                    module.items.push(ItemDoc::new(&def.name.name, ItemKind::Const, signature, &[]));
+                }
+                Item::Module(def) if self.shown(def.is_pub) => {
+                    if let Some(items) = &def.items {
+                        nested.push((format!("{}::{}", name, def.name.name), items));
+                    }
+                }
+                _ => {}
+            }
+        }
+        modules.push(module);
+        for (name, items) in nested {
+            self.document_items(&name, path, items, modules);
+        }
+    }
+
+    /// Documented methods of every `impl` block for `owner` in `items`
+    fn methods(&self, owner: &str, items: &[Item]) -> Vec<ItemDoc> {
+        items.iter()
+            .filter_map(|item| match item {
+                Item::Impl(block) if block.target.name == owner => Some(block),
+                _ => None,
+            })
+            // Interface methods are public wherever the type is
+            .flat_map(|block| block.methods.iter().filter(|m| block.interface.is_some() || self.shown(m.is_pub)))
+            .map(|m| ItemDoc::new(&m.name.name, ItemKind::Method, function_signature(m), &m.doc))
+            .collect()
+    }
+
+    fn shown(&self, is_pub: bool) -> bool {
+        is_pub || self.private_items
+    }
+
+    /// Write a page per module, an index page and the JSON database into
+    /// `dir`; returns the files written
+    pub fn write(&self, database: &DocDatabase, dir: &Path, format: DocFormat) -> Result<Vec<PathBuf>> {
+        fs::create_dir_all(dir).map_err(|e| Error::Io(format!("{}: {}", dir.display(), e)))?;
+        let mut pages = vec![(format!("index.{}", format.extension()), render_index(database, format))];
+        for module in &database.modules {
+            let page = match format {
+                DocFormat::Html => html::render_module(database, module),
+                DocFormat::Markdown => markdown::render_module(module),
+            };
+            pages.push((module.page(format), page));
+        }
+        pages.push((DATABASE_FILE.to_string(), database.to_json()));
+
+        let mut written = Vec::new();
+        for (name, text) in pages {
+            let path = dir.join(name);
+            fs::write(&path, text).map_err(|e| Error::Io(format!("{}: {}", path.display(), e)))?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+}
+
+fn render_index(database: &DocDatabase, format: DocFormat) -> String {
+    match format {
+        DocFormat::Html => html::render_index(database),
+        DocFormat::Markdown => markdown::render_index(database),
+    }
+}
+
+fn pub_prefix(is_pub: bool) -> &'static str {
+    if is_pub { "pub " } else { "" }
+}
+
+fn type_params(params: &[Ident]) -> String {
+    if params.is_empty() {
+        return String::new();
+    }
+    let names: Vec<&str> = params.iter().map(|p| p.name.as_str()).collect();
+    format!("<{}>", names.join(", "))
+}
+
+fn generic_params(params: &[GenericParam]) -> String {
+    if params.is_empty() {
+        return String::new();
+    }
+    let params: Vec<String> = params.iter()
+        .map(|p| match p {
+            GenericParam::Type { name, bounds } if bounds.is_empty() => name.name.clone(),
+            GenericParam::Type { name, bounds } => format!("{}: {}", name.name, bounds.iter().map(type_to_string).collect::<Vec<_>>().join(" + ")),
+            GenericParam::Const { name, ty } => format!("const {}: {}", name.name, type_to_string(ty)),
+        })
+        .collect();
+    format!("<{}>", params.join(", "))
+}
+
+fn struct_signature(def: &StructDef) -> String {
+    format!("{}struct {}{}", pub_prefix(def.is_pub), def.name.name, generic_params(&def.generic_params))
+}
+
+fn enum_signature(def: &EnumDef) -> String {
+    let repr = def.repr.as_ref().map(|r| format!("#[repr({})]\n", r.name)).unwrap_or_default();
+    format!("{}enum {}{}", repr, def.name.name, generic_params(&def.generic_params))
+}
+
+fn variant_signature(variant: &Variant) -> String {
+    let mut signature = variant.name.name.clone();
+    if !variant.fields.is_empty() {
+        let fields: Vec<String> = variant.fields.iter().map(type_to_string).collect();
+        signature.push_str(&format!("({})", fields.join(", ")));
+    }
+    if let Some(value) = &variant.discriminant {
+        signature.push_str(&format!(" = {}", expr_to_string(value)));
+    }
+    signature
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::lexer::Lexer;
+    use crate::frontend::parser::Parser;
+
+    const SHAPES: &str = "\
+/// A point on the plane.
+///
+/// Coordinates are in pixels.
+pub struct Point {
+    pub x: i64,
+    y: i64,
+}
+
+impl Point {
+    /// Distance from the origin, squared
+    pub fn norm(self: ref Point) -> i64 {
+        return self.x * self.x
+    }
+
+    fn hidden(self: ref Point) -> i64 {
+        return 0
+    }
+}
+
+/// Compass directions
+enum Direction {
+    North,
+    South = 2,
+}
+
+#[inline]
+/// Twice `n`
+pub fn double(n: i64) -> i64 {
+    return n * 2
+}
+
+fn private() -> i64 {
+    return 1
+}
+";
+
+    fn database(private: bool) -> DocDatabase {
+        let program = Parser::new(Lexer::new(SHAPES, 0)).parse_program().unwrap();
+        let mut modules = Vec::new();
+        DocGenerator::new().with_private_items(private).document_items("shapes", "shapes.aeth", &program.items, &mut modules);
+        DocDatabase { modules }
+    }
+
+    #[test]
+    fn test_collect_public_items() {
+        let database = database(false);
+        let names: Vec<&str> = database.modules[0].items.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, ["Point", "Direction", "double"]);
+
+        let point = database.find(None, "Point").unwrap();
+        assert_eq!(point.signature, "pub struct Point");
+        assert_eq!(point.doc, ["A point on the plane.", "", "Coordinates are in pixels."]);
+        assert_eq!(point.summary(), "A point on the plane.");
+        let members: Vec<(&str, ItemKind)> = point.members.iter().map(|m| (m.name.as_str(), m.kind)).collect();
+        assert_eq!(members, [("x", ItemKind::Field), ("norm", ItemKind::Method)]);
+
+        let double = database.find(Some("shapes"), "double").unwrap();
+        assert_eq!(double.signature, "pub fn double(n: i64) -> i64");
+        // Doc comments after the attributes attach too
+        assert_eq!(double.doc, ["Twice `n`"]);
+
+        let direction = database.find(None, "Direction").unwrap();
+        assert_eq!(direction.members[1].signature, "South = 2");
+
+        let norm = database.find_method(None, "norm").unwrap();
+        assert_eq!(norm.doc, ["Distance from the origin, squared"]);
+        assert!(database.find(None, "private").is_none());
+    }
+
+    #[test]
+    fn test_private_items_and_json() {
+        let database = database(true);
+        assert!(database.find(None, "private").is_some());
+        assert!(database.find_method(Some("Point"), "hidden").is_some());
+
+        let json = database.to_json();
+        let loaded: DocDatabase = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, database);
+    }
+
+    #[test]
+    fn test_render_pages() {
+        let database = database(false);
+        let module = &database.modules[0];
+        let page = html::render_module(&database, module);
+        assert!(page.contains(r##"<a href="#struct.Point">Point</a>"##), "{}", page);
+        assert!(page.contains(r#"<section class="item" id="fn.double">"#), "{}", page);
+        assert!(page.contains("<p>Twice <code>n</code></p>"), "{}", page);
+        assert!(page.contains("pub fn double(n: i64) -&gt; i64"), "{}", page);
+
+        let page = markdown::render_module(module);
+        assert!(page.contains("## Structs"), "{}", page);
+        assert!(page.contains("```aether\npub fn double(n: i64) -> i64\n```"), "{}", page);
+        assert!(page.contains("A point on the plane.\n\nCoordinates are in pixels."), "{}", page);
+    }
+}
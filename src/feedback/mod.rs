@@ -8,8 +8,144 @@
 
 pub mod iteration;
 
-use serde::{Serialize, Deserialize};
-use crate::utils::Error;
+use std::collections::{BTreeMap, HashMap};
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use crate::middle::ir::IRModule;
+use crate::utils::{Error, Span};
+
+// ==================== Message Templates ====================
+
+/// Locales with message templates; the first is the default
+pub const LOCALES: &[&str] = &["en", "zh"];
+
+/// Locale used when none is selected
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Message template of each error code, per locale. Placeholders are
+/// `{name}`; errors without an entry use `FALLBACK_TEMPLATE`.
+static MESSAGE_TEMPLATES: &[(&str, &str, &str)] = &[
+    ("en", "E0001", "Type mismatch: expected {expected}, got {got}"),
+    ("zh", "E0001", "类型不匹配：期望 {expected}，实际为 {got}"),
+    ("en", "E0002", "Undefined variable: {name}"),
+    ("zh", "E0002", "未定义的变量：{name}"),
+    ("en", "E0003", "Argument count mismatch for '{func_name}': expected {expected}, got {got}"),
+    ("zh", "E0003", "'{func_name}' 的参数个数不匹配：期望 {expected} 个，实际为 {got} 个"),
+    ("en", "E0008", "Undefined type: {name}"),
+    ("zh", "E0008", "未定义的类型：{name}"),
+    ("en", "E0009", "No method '{method}' found for type {ty}"),
+    ("zh", "E0009", "类型 {ty} 没有方法 '{method}'"),
+    ("en", "E0010", "Effect violation: {message}"),
+    ("zh", "E0010", "效果违规：{message}"),
+    ("en", "E0011", "Unknown field: {field}"),
+    ("zh", "E0011", "未知字段：{field}"),
+];
+
+/// Template of errors whose message is not broken into arguments
+const FALLBACK_TEMPLATE: &str = "{message}";
+
+/// Check a `--locale` value against `LOCALES`
+pub fn parse_locale(name: &str) -> std::result::Result<&'static str, String> {
+    LOCALES.iter()
+        .find(|l| **l == name)
+        .copied()
+        .ok_or_else(|| format!("unknown locale '{}' (expected one of: {})", name, LOCALES.join(", ")))
+}
+
+/// Template for `code` in `locale`, falling back to the default locale
+fn template_for(code: &str, locale: &str) -> Option<&'static str> {
+    let find = |locale: &str| MESSAGE_TEMPLATES.iter()
+        .find(|(l, c, _)| *l == locale && *c == code)
+        .map(|(_, _, template)| *template);
+    find(locale).or_else(|| find(DEFAULT_LOCALE))
+}
+
+/// A message kept as a template and its arguments, formatted on demand
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageTemplate {
+    pub template: &'static str,
+    pub args: HashMap<String, String>,
+}
+
+impl MessageTemplate {
+    pub fn new(template: &'static str) -> Self {
+        Self { template, args: HashMap::new() }
+    }
+
+    /// A message with no structure beyond its text
+    pub fn plain(text: impl Into<String>) -> Self {
+        Self::new(FALLBACK_TEMPLATE).with_arg("message", text)
+    }
+
+    pub fn with_arg(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.args.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Substitute the arguments; unknown placeholders are kept as written
+    pub fn format(&self) -> String {
+        let mut out = String::with_capacity(self.template.len());
+        let mut rest = self.template;
+        while let Some(open) = rest.find('{') {
+            out.push_str(&rest[..open]);
+            let after = &rest[open + 1..];
+            match after.find('}').and_then(|close| self.args.get(&after[..close]).map(|v| (close, v))) {
+                Some((close, value)) => {
+                    out.push_str(value);
+                    rest = &after[close + 1..];
+                }
+                None => {
+                    out.push('{');
+                    rest = after;
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+impl std::fmt::Display for MessageTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.format())
+    }
+}
+
+/// JSON form: the template, its arguments (in key order) and the formatted text
+#[derive(Serialize, Deserialize)]
+struct MessageTemplateJson {
+    template: String,
+    args: BTreeMap<String, String>,
+    #[serde(default)]
+    text: String,
+}
+
+impl Serialize for MessageTemplate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        MessageTemplateJson {
+            template: self.template.to_string(),
+            args: self.args.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            text: self.format(),
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MessageTemplate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let json = MessageTemplateJson::deserialize(deserializer)?;
+        let args = json.args.into_iter().collect();
+        // Templates from another compiler version are kept as formatted text
+        match MESSAGE_TEMPLATES.iter().map(|(_, _, t)| *t).chain([FALLBACK_TEMPLATE]).find(|t| *t == json.template) {
+            Some(template) => Ok(Self { template, args }),
+            None => Ok(Self::plain(json.text)),
+        }
+    }
+}
+
+/// `locale` when deserializing feedback, if it is a known one
+fn deserialize_locale<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<&'static str, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    Ok(parse_locale(&name).unwrap_or(DEFAULT_LOCALE))
+}
 
 // ==================== Structured Error Report ====================
 
@@ -23,7 +159,7 @@ pub struct ErrorReport {
     pub severity: Severity,
     
     /// Human-readable message
-    pub message: String,
+    pub message: MessageTemplate,
     
     /// Location information
     pub location: Option<Location>,
@@ -35,7 +171,7 @@ pub struct ErrorReport {
     pub related: Vec<RelatedInfo>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Severity {
     Error,
     Warning,
@@ -92,23 +228,39 @@ pub struct CompilationFeedback {
     
     /// AI-IR summary (if generated)
     pub ai_ir_summary: Option<AIIRSummary>,
+
+    /// Template set the diagnostics' messages come from
+    #[serde(deserialize_with = "deserialize_locale")]
+    pub locale: &'static str,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompilationStats {
     /// Parse time in milliseconds
-    pub parse_time_ms: u64,
+    pub parse_time_ms: f64,
     
     /// Semantic analysis time
-    pub semantic_time_ms: u64,
+    pub semantic_time_ms: f64,
     
     /// IR generation time
-    pub ir_gen_time_ms: u64,
+    pub ir_gen_time_ms: f64,
+
+    /// Optimizer time, all passes included
+    #[serde(default)]
+    pub optimize_time_ms: f64,
+
+    /// Backend time, excluding the external C compiler
+    #[serde(default)]
+    pub codegen_time_ms: f64,
+
+    /// Wall time of the spawned C compiler
+    #[serde(default)]
+    pub c_compiler_time_ms: f64,
     
     /// Total time
-    pub total_time_ms: u64,
+    pub total_time_ms: f64,
     
-    /// Number of functions
+    /// Number of functions, methods included
     pub function_count: usize,
     
     /// Number of types
@@ -116,6 +268,124 @@ pub struct CompilationStats {
     
     /// Lines of code
     pub loc: usize,
+
+    /// Errors dropped after reaching `--max-errors`
+    #[serde(default)]
+    pub errors_suppressed: usize,
+
+    /// Optimizer passes in the order they first ran (`--timings`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub passes: Vec<PassStats>,
+
+    /// IR size of each function, largest first (`--timings=json`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub functions: Vec<FunctionStats>,
+}
+
+/// Time spent in one optimizer pass over every iteration it ran
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PassStats {
+    pub name: String,
+    pub runs: usize,
+    pub time_ms: f64,
+    /// Module instructions before the pass first ran
+    pub instructions_before: usize,
+    /// Module instructions after the pass last ran
+    pub instructions_after: usize,
+}
+
+/// IR size of one function before and after optimization
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionStats {
+    pub name: String,
+    pub blocks: usize,
+    pub instructions: usize,
+    pub optimized_instructions: usize,
+}
+
+/// How `--timings` reports the statistics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimingsFormat {
+    /// A table on stderr
+    Human,
+    /// The full statistics, per-function IR sizes included, as JSON on stderr
+    Json,
+}
+
+impl TimingsFormat {
+    pub fn parse(name: &str) -> std::result::Result<Self, String> {
+        match name {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown timings format '{}' (expected human or json)", name)),
+        }
+    }
+}
+
+impl CompilationStats {
+    /// Add one run of pass `name` that took `time_ms` and changed the
+    /// module from `before` to `after` instructions
+    pub fn record_pass(&mut self, name: &str, time_ms: f64, before: usize, after: usize) {
+        match self.passes.iter_mut().find(|p| p.name == name) {
+            Some(pass) => {
+                pass.runs += 1;
+                pass.time_ms += time_ms;
+                pass.instructions_after = after;
+            }
+            None => self.passes.push(PassStats {
+                name: name.to_string(),
+                runs: 1,
+                time_ms,
+                instructions_before: before,
+                instructions_after: after,
+            }),
+        }
+    }
+
+    /// Record the size of every function of a freshly lowered module
+    pub fn record_functions(&mut self, module: &IRModule) {
+        self.functions = module.functions.iter().map(|f| FunctionStats {
+            name: f.name.clone(),
+            blocks: f.blocks.len(),
+            instructions: f.instruction_count(),
+            optimized_instructions: f.instruction_count(),
+        }).collect();
+        self.functions.sort_by_key(|f| std::cmp::Reverse(f.instructions));
+    }
+
+    /// Update the recorded functions with their size in the optimized `module`
+    pub fn record_optimized_functions(&mut self, module: &IRModule) {
+        let sizes: HashMap<&str, usize> = module.functions.iter()
+            .map(|f| (f.name.as_str(), f.instruction_count()))
+            .collect();
+        for function in &mut self.functions {
+            // Functions the optimizer removed have no instructions left
+            function.optimized_instructions = sizes.get(function.name.as_str()).copied().unwrap_or(0);
+        }
+    }
+
+    /// Per-phase table printed by `--timings`
+    pub fn render_table(&self) -> String {
+        let mut out = format!("  {:<24} {:>10}\n", "Phase", "Time (ms)");
+        let mut row = |label: &str, ms: f64, note: &str| {
+            let line = format!("  {:<24} {:>10.3}  {}", label, ms, note);
+            out.push_str(line.trim_end());
+            out.push('\n');
+        };
+        let counts = format!("{} lines, {} functions, {} types", self.loc, self.function_count, self.type_count);
+        row("Parse", self.parse_time_ms, &counts);
+        row("Semantic analysis", self.semantic_time_ms, "");
+        row("IR generation", self.ir_gen_time_ms, "");
+        row("Optimization", self.optimize_time_ms, "");
+        for pass in &self.passes {
+            let note = format!("{} -> {} instructions, {} run(s)", pass.instructions_before, pass.instructions_after, pass.runs);
+            row(&format!("  {}", pass.name), pass.time_ms, &note);
+        }
+        row("Code generation", self.codegen_time_ms, "");
+        row("C compiler", self.c_compiler_time_ms, "");
+        row("Total", self.total_time_ms, "");
+        out
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -133,15 +403,35 @@ impl ErrorReport {
     /// Create an error report from a compiler error
     /// Enhanced with multiple suggestions for AI error recovery
     pub fn from_error(error: &Error, file_name: &str) -> Self {
-        let (code, message, suggestions) = generate_error_info(error);
+        Self::from_error_localized(error, file_name, DEFAULT_LOCALE)
+    }
+
+    /// Create an error report whose message uses the templates of `locale`
+    pub fn from_error_localized(error: &Error, file_name: &str, locale: &str) -> Self {
+        let (code, mut suggestions) = generate_error_info(error);
+        let message = error_message(error, locale);
+        // Suggestions are located without the file name, which only the report knows
+        for location in suggestions.iter_mut().filter_map(|s| s.location.as_mut()) {
+            location.file = file_name.to_string();
+        }
         
-        let location = error.span().map(|s| Location {
+        let locate = |s: Span| Location {
             file: file_name.to_string(),
             line: s.start as u32,
             column: 0,
             end_line: Some(s.end as u32),
             end_column: None,
-        });
+        };
+        let location = error.span().map(locate);
+
+        // Both definitions of a duplicate
+        let related = match error {
+            Error::DuplicateDefinition { first, .. } | Error::DuplicateSymbol { first, .. } => vec![RelatedInfo {
+                message: "first defined here".to_string(),
+                location: Some(locate(*first)),
+            }],
+            _ => vec![],
+        };
         
         Self {
             code,
@@ -149,7 +439,7 @@ impl ErrorReport {
             message,
             location,
             suggestions,
-            related: vec![],
+            related,
         }
     }
     
@@ -158,7 +448,7 @@ impl ErrorReport {
         Self {
             code: code.to_string(),
             severity: Severity::Warning,
-            message: message.to_string(),
+            message: MessageTemplate::plain(message),
             location,
             suggestions: vec![],
             related: vec![],
@@ -175,6 +465,15 @@ impl ErrorReport {
         });
     }
     
+    /// For an operation outside `unsafe`, suggest the block to wrap it in,
+    /// spelled out with the operation's code from `source`
+    pub fn suggest_unsafe_block(&mut self, error: &Error, source: &str) {
+        let (Error::UnsafeRequired { span, .. } | Error::UnsafeOperationOutsideUnsafeBlock { span, .. }) = error else { return };
+        let Some(code) = source.get(span.start..span.end) else { return };
+        let wrapped = format!("unsafe {{ {} }}", code);
+        self.add_suggestion(&format!("wrap it in an unsafe block: `{}`", wrapped), Some(wrapped.clone()), 0.8);
+    }
+
     /// Sort suggestions by confidence (highest first)
     pub fn sort_suggestions(&mut self) {
         self.suggestions.sort_by(|a, b| {
@@ -183,9 +482,264 @@ impl ErrorReport {
     }
 }
 
+// ==================== rustc-Compatible Output ====================
+
+/// Diagnostic output format (`--error-format`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+    #[default]
+    Human,
+    /// One rustc-style JSON object per line, rendered in full
+    Json,
+    /// As `Json`, rendered as a single `file:line:col: level: message` line
+    JsonDiagnosticShort,
+    /// As `Json`, rendered in full with ANSI colors
+    JsonDiagnosticRenderedAnsi,
+}
+
+impl ErrorFormat {
+    pub fn parse(name: &str) -> std::result::Result<Self, String> {
+        match name {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            "json-diagnostic-short" => Ok(Self::JsonDiagnosticShort),
+            "json-diagnostic-rendered-ansi" => Ok(Self::JsonDiagnosticRenderedAnsi),
+            _ => Err(format!(
+                "unknown error format '{}' (expected human, json, json-diagnostic-short or json-diagnostic-rendered-ansi)",
+                name
+            )),
+        }
+    }
+
+    pub fn is_json(self) -> bool {
+        self != Self::Human
+    }
+}
+
+/// When to color diagnostics (`--color`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// 16-color ANSI codes when stderr is a terminal
+    #[default]
+    Auto,
+    /// 16-color ANSI codes
+    Always,
+    Never,
+    /// As `Always`
+    Ansi16,
+    /// 24-bit ANSI codes
+    Truecolor,
+}
+
+impl ColorChoice {
+    pub fn parse(name: &str) -> std::result::Result<Self, String> {
+        match name {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            "ansi16" => Ok(Self::Ansi16),
+            "truecolor" => Ok(Self::Truecolor),
+            _ => Err(format!("unknown color mode '{}' (expected auto, always, never, ansi16 or truecolor)", name)),
+        }
+    }
+
+    /// The colors to use when the output is a terminal (`is_terminal`) or not
+    pub fn resolve(self, is_terminal: bool) -> ColorConfig {
+        match self {
+            Self::Auto if is_terminal => ColorConfig::Ansi16,
+            Self::Auto | Self::Never => ColorConfig::Never,
+            Self::Always | Self::Ansi16 => ColorConfig::Ansi16,
+            Self::Truecolor => ColorConfig::Truecolor,
+        }
+    }
+}
+
+/// The ANSI escape codes diagnostics are rendered with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorConfig {
+    #[default]
+    Never,
+    Ansi16,
+    Truecolor,
+}
+
+/// What a piece of a rendered diagnostic is, which decides its color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// The level and its underline
+    Severity(Severity),
+    /// Error codes and messages
+    Bold,
+    /// Line numbers, bars and arrows in the margin
+    Gutter,
+}
+
+impl ColorConfig {
+    /// The SGR parameters of `style`, or `None` without colors
+    fn sgr(self, style: Style) -> Option<&'static str> {
+        let truecolor = match self {
+            Self::Never => return None,
+            Self::Ansi16 => false,
+            Self::Truecolor => true,
+        };
+        Some(match (style, truecolor) {
+            (Style::Severity(Severity::Error), false) => "1;31",
+            (Style::Severity(Severity::Error), true) => "1;38;2;230;70;70",
+            (Style::Severity(Severity::Warning), false) => "33",
+            (Style::Severity(Severity::Warning), true) => "38;2;230;180;50",
+            (Style::Severity(Severity::Info), false) => "34",
+            (Style::Severity(Severity::Info), true) => "38;2;90;150;230",
+            (Style::Severity(Severity::Hint), _) => "2",
+            (Style::Bold, _) => "1",
+            (Style::Gutter, false) => "1;34",
+            (Style::Gutter, true) => "1;38;2;90;150;230",
+        })
+    }
+
+    /// `text` wrapped in the escape codes of `style`
+    pub fn paint(self, style: Style, text: &str) -> String {
+        match self.sgr(style) {
+            Some(sgr) => format!("\x1b[{}m{}\x1b[0m", sgr, text),
+            None => text.to_string(),
+        }
+    }
+}
+
+/// Where a report points, resolved against the source it was found in
+struct ResolvedSpan<'a> {
+    file: String,
+    byte_start: usize,
+    byte_end: usize,
+    line_start: usize,
+    line_end: usize,
+    column_start: usize,
+    column_end: usize,
+    /// Source line holding the start of the span
+    text: &'a str,
+}
+
+impl ErrorReport {
+    /// rustc's name for the severity
+    fn level(&self) -> &'static str {
+        match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "note",
+            Severity::Hint => "help",
+        }
+    }
+
+    /// The report's span in `source`. Locations hold the byte offsets of the
+    /// span in `line` and `end_line`.
+    fn resolve_span<'a>(&self, source: &'a str) -> Option<ResolvedSpan<'a>> {
+        let location = self.location.as_ref()?;
+        let byte_start = (location.line as usize).min(source.len());
+        let byte_end = (location.end_line.unwrap_or(location.line) as usize).clamp(byte_start, source.len());
+        let lines = crate::utils::SourceLines::new(&location.file, source);
+        let (file, line_start, column_start) = lines.locate(Span::new(byte_start, byte_end, 0));
+        let (_, line_end, column_end) = lines.locate(Span::new(byte_end, byte_end, 0));
+        let line_begin = source[..byte_start].rfind('\n').map_or(0, |i| i + 1);
+        let line_stop = source[byte_start..].find('\n').map_or(source.len(), |i| byte_start + i);
+        Some(ResolvedSpan {
+            file: file.to_string(),
+            byte_start,
+            byte_end,
+            line_start,
+            line_end,
+            column_start,
+            column_end,
+            text: &source[line_begin..line_stop],
+        })
+    }
+
+    /// The report as rustc prints it in `format`: one line for
+    /// `JsonDiagnosticShort`, otherwise the header, location and source
+    /// snippet, colored for `JsonDiagnosticRenderedAnsi`
+    pub fn render(&self, source: Option<&str>, format: ErrorFormat) -> String {
+        let color = if format == ErrorFormat::JsonDiagnosticRenderedAnsi { ColorConfig::Ansi16 } else { ColorConfig::Never };
+        self.render_colored(source, format, color)
+    }
+
+    /// As `render`, with the level and underline in the severity's color,
+    /// the code and message in bold and the margin in blue
+    pub fn render_colored(&self, source: Option<&str>, format: ErrorFormat, color: ColorConfig) -> String {
+        let span = source.and_then(|s| self.resolve_span(s));
+        let level = self.level();
+        if format == ErrorFormat::JsonDiagnosticShort {
+            return match &span {
+                Some(span) => format!("{}:{}:{}: {}[{}]: {}\n", span.file, span.line_start, span.column_start, level, self.code, self.message),
+                None => format!("{}[{}]: {}\n", level, self.code, self.message),
+            };
+        }
+        let severity = Style::Severity(self.severity);
+        let mut out = format!("{}{}\n", color.paint(severity, &format!("{}[{}]", level, self.code)), color.paint(Style::Bold, &format!(": {}", self.message)));
+        let mut gutter = String::new();
+        if let Some(span) = span {
+            gutter = " ".repeat(span.line_start.to_string().len());
+            let bar = color.paint(Style::Gutter, &format!("{} |", gutter));
+            out.push_str(&format!("{}{} {}:{}:{}\n", gutter, color.paint(Style::Gutter, "-->"), span.file, span.line_start, span.column_start));
+            out.push_str(&format!("{}\n", bar));
+            out.push_str(&format!("{} {}\n", color.paint(Style::Gutter, &format!("{} |", span.line_start)), span.text));
+            // Underline up to the end of the span or of its first line
+            let width = if span.line_end == span.line_start { span.column_end.saturating_sub(span.column_start) } else { (span.text.len() + 1).saturating_sub(span.column_start) };
+            let marker = format!("{}{}", " ".repeat(span.column_start - 1), "^".repeat(width.max(1)));
+            out.push_str(&format!("{} {}\n", bar, color.paint(severity, &marker)));
+        }
+        for suggestion in &self.suggestions {
+            out.push_str(&format!("{}{} help: {}\n", gutter, color.paint(Style::Gutter, " ="), suggestion.message));
+        }
+        out
+    }
+
+    /// The report in rustc's JSON diagnostic schema, as cargo expects on stderr.
+    /// `source` is the text the report's offsets refer to; without it the
+    /// report has no spans.
+    pub fn to_rustc_json(&self, source: Option<&str>, format: ErrorFormat) -> serde_json::Value {
+        let span = source.and_then(|s| self.resolve_span(s));
+        let span_json = |replacement: Option<&String>| span.as_ref().map(|span| serde_json::json!({
+            "file_name": span.file,
+            "byte_start": span.byte_start,
+            "byte_end": span.byte_end,
+            "line_start": span.line_start,
+            "line_end": span.line_end,
+            "column_start": span.column_start,
+            "column_end": span.column_end,
+            "is_primary": true,
+            "text": [{
+                "text": span.text,
+                "highlight_start": span.column_start,
+                "highlight_end": if span.line_end == span.line_start { span.column_end } else { span.text.len() + 1 },
+            }],
+            "label": null,
+            "suggested_replacement": replacement,
+            "suggestion_applicability": replacement.map(|_| "MaybeIncorrect"),
+            "expansion": null,
+        }));
+        let children: Vec<serde_json::Value> = self.suggestions.iter()
+            .map(|suggestion| serde_json::json!({
+                "message": suggestion.message,
+                "code": null,
+                "level": "help",
+                "spans": suggestion.replacement.as_ref().and_then(|r| span_json(Some(r))).into_iter().collect::<Vec<_>>(),
+                "children": [],
+                "rendered": null,
+            }))
+            .collect();
+        serde_json::json!({
+            "$message_type": "diagnostic",
+            "message": self.message.to_string(),
+            "code": { "code": self.code, "explanation": explain(&self.code) },
+            "level": self.level(),
+            "spans": span_json(None).into_iter().collect::<Vec<_>>(),
+            "children": children,
+            "rendered": self.render(source, format),
+        })
+    }
+}
+
 /// Generate error info with multiple suggestions
 /// This is the core smart error recovery logic
-fn generate_error_info(error: &Error) -> (String, String, Vec<Suggestion>) {
+fn generate_error_info(error: &Error) -> (String, Vec<Suggestion>) {
     match error {
         // ========== Type Errors ==========
         Error::TypeMismatch { expected, got, span } => {
@@ -215,8 +769,7 @@ fn generate_error_info(error: &Error) -> (String, String, Vec<Suggestion>) {
             }
             
             (
-                "E0001".to_string(),
-                format!("Type mismatch: expected {}, got {}", expected, got),
+                error.code().to_string(),
                 suggestions,
             )
         }
@@ -235,10 +788,17 @@ fn generate_error_info(error: &Error) -> (String, String, Vec<Suggestion>) {
             // AI error pattern: typos in common variable names
             let common_typos = get_common_typos(name);
             for (typo, correct) in common_typos {
+                // Replaces the misspelled name itself
                 suggestions.push(Suggestion {
                     message: format!("Did you mean '{}'?", correct),
                     replacement: Some(correct.clone()),
-                    location: None,
+                    location: Some(Location {
+                        file: String::new(),
+                        line: span.start as u32,
+                        column: 0,
+                        end_line: Some(span.end as u32),
+                        end_column: None,
+                    }),
                     confidence: 0.75,
                 });
             }
@@ -252,8 +812,7 @@ fn generate_error_info(error: &Error) -> (String, String, Vec<Suggestion>) {
             });
             
             (
-                "E0002".to_string(),
-                format!("Undefined variable: {}", name),
+                error.code().to_string(),
                 suggestions,
             )
         }
@@ -261,8 +820,7 @@ fn generate_error_info(error: &Error) -> (String, String, Vec<Suggestion>) {
         // ========== Effect Violation ==========
         Error::EffectViolation { message, span } => {
             (
-                "E0010".to_string(),
-                format!("Effect violation: {}", message),
+                error.code().to_string(),
                 vec![
                     Suggestion {
                         message: "Remove 'pure' annotation from the function".to_string(),
@@ -317,16 +875,160 @@ fn generate_error_info(error: &Error) -> (String, String, Vec<Suggestion>) {
             }
             
             (
-                "E0003".to_string(),
-                format!("Argument count mismatch: expected {}, got {}", expected, got),
+                error.code().to_string(),
                 suggestions,
             )
         }
         
+        // ========== Lints ==========
+        Error::UnusedVariable { name, .. } => (
+            error.code().to_string(),
+            vec![
+                Suggestion {
+                    message: format!("Prefix the name with '_' if '{}' is intentionally unused", name),
+                    replacement: Some(format!("_{}", name)),
+                    location: None,
+                    confidence: 0.8,
+                },
+                Suggestion {
+                    message: "Remove the binding".to_string(),
+                    replacement: None,
+                    location: None,
+                    confidence: 0.5,
+                },
+            ],
+        ),
+        Error::UnusedItem { name, .. } | Error::UnusedField { name, .. } => (
+            error.code().to_string(),
+            vec![
+                Suggestion {
+                    message: format!("Remove '{}'", name),
+                    replacement: None,
+                    location: None,
+                    confidence: 0.7,
+                },
+                Suggestion {
+                    message: "Keep it with #[allow(dead_code)] on the item".to_string(),
+                    replacement: Some("#[allow(dead_code)]".to_string()),
+                    location: None,
+                    confidence: 0.5,
+                },
+            ],
+        ),
+        Error::UnreachableCode { .. } => (
+            error.code().to_string(),
+            vec![Suggestion {
+                message: "Remove the statements after the return, break or continue".to_string(),
+                replacement: None,
+                location: None,
+                confidence: 0.8,
+            }],
+        ),
+        Error::MissingTailValue { .. } => (
+            error.code().to_string(),
+            vec![Suggestion {
+                message: "Remove the trailing `;` so the final expression is the return value".to_string(),
+                replacement: None,
+                location: None,
+                confidence: 0.8,
+            }],
+        ),
+        Error::TooManyErrors { .. } => (
+            error.code().to_string(),
+            vec![Suggestion {
+                message: "Fix the first errors, or raise the limit with --max-errors (0 for no limit)".to_string(),
+                replacement: None,
+                location: None,
+                confidence: 0.6,
+            }],
+        ),
+        Error::NotIterable { .. } => (
+            error.code().to_string(),
+            vec![Suggestion {
+                message: "Iterate over a fixed-size array, or use a `while` loop over an index".to_string(),
+                replacement: None,
+                location: None,
+                confidence: 0.7,
+            }],
+        ),
+        Error::NotFreestanding { name, .. } => (
+            error.code().to_string(),
+            vec![Suggestion {
+                message: format!("Define '{}' in the program, or build without --freestanding to use the C library", name),
+                replacement: None,
+                location: None,
+                confidence: 0.6,
+            }],
+        ),
+        Error::AssignToImmutable { name, .. } => (
+            error.code().to_string(),
+            vec![Suggestion {
+                message: format!("Declare '{}' with `let mut` to assign to it", name),
+                replacement: None,
+                location: None,
+                confidence: 0.9,
+            }],
+        ),
+        Error::NotEquatable { ty, .. } => (
+            error.code().to_string(),
+            vec![
+                Suggestion {
+                    message: format!("Add #[derive(Eq)] to the definition of '{}' to compare its values field by field", ty),
+                    replacement: None,
+                    location: None,
+                    confidence: 0.8,
+                },
+                operator_impl_suggestion("Eq", ty, ty, 0.5),
+            ],
+        ),
+        Error::NotOrdered { ty, .. } if !ty.contains(['*', '&']) && ty != "str" => (
+            error.code().to_string(),
+            vec![operator_impl_suggestion("Ord", ty, ty, 0.6)],
+        ),
+        Error::MissingOperatorImpl { op, ty, rhs, .. } => (
+            error.code().to_string(),
+            vec![operator_impl_suggestion(op.operator_interface().unwrap_or_default().0, ty, rhs, 0.8)],
+        ),
+        Error::UnsatisfiedBound { ty, interface, .. } => (
+            error.code().to_string(),
+            vec![Suggestion {
+                message: format!("Add `impl {} for {} {{ .. }}` implementing the methods of '{}'", interface, ty, interface),
+                replacement: None,
+                location: None,
+                confidence: 0.8,
+            }],
+        ),
+        Error::MissingBound { param, interface, .. } => (
+            error.code().to_string(),
+            vec![Suggestion {
+                message: format!("Bound the type parameter: `<{}: {}>`", param, interface),
+                replacement: Some(format!("{}: {}", param, interface)),
+                location: None,
+                confidence: 0.9,
+            }],
+        ),
+        Error::UnsupportedPayload { ty, container, .. } => (
+            error.code().to_string(),
+            vec![Suggestion {
+                message: format!("Store a pointer instead: `{}<*{}>`", container, ty),
+                replacement: Some(format!("*{}", ty)),
+                location: None,
+                confidence: 0.7,
+            }],
+        ),
+        Error::NonExhaustiveMatch { missing, .. } => (
+            error.code().to_string(),
+            vec![Suggestion {
+                message: format!("Add an arm for `{}`, or a `_` arm for every value left", missing),
+                replacement: Some(format!("{} => ", missing)),
+                location: None,
+                confidence: 0.8,
+            }],
+        ),
+
         // ========== Default Case ==========
         _ => (
-            "E9999".to_string(),
-            format!("{}", error),
+            error.code().to_string(),
             vec![Suggestion {
                 message: "Check the error message for details".to_string(),
                 replacement: None,
@@ -337,63 +1039,1352 @@ fn generate_error_info(error: &Error) -> (String, String, Vec<Suggestion>) {
     }
 }
 
-/// Get common typos for variable names (AI error pattern recognition)
-fn get_common_typos(name: &str) -> Vec<(String, String)> {
-    let mut typos = Vec::new();
-    
-    // Common patterns AI often confuses
-    let patterns = [
-        ("resut", "result"),
-        ("reuslt", "result"),
-        ("reslut", "result"),
-        ("lenght", "length"),
-        ("lenth", "length"),
-        ("indx", "index"),
-        ("idx", "index"),
-        ("cnt", "count"),
-        ("coutn", "count"),
-        ("val", "value"),
-        ("valu", "value"),
-        ("tmp", "temp"),
-        ("i", "index"),  // Often AI uses 'i' but declares 'index'
-    ];
-    
-    for (wrong, correct) in patterns {
-        if name == wrong {
-            typos.push((wrong.to_string(), correct.to_string()));
-        }
-        if name == correct {
-            // Also suggest the abbreviation might be intended
-            typos.push((correct.to_string(), wrong.to_string()));
-        }
+/// Suggest implementing operator interface `interface` for `ty`, with a
+/// skeleton impl whose method takes a `rhs`
+fn operator_impl_suggestion(interface: &str, ty: &str, rhs: &str, confidence: f64) -> Suggestion {
+    use crate::frontend::ast::BinOp;
+    let method = [BinOp::Add, BinOp::Sub, BinOp::Mul, BinOp::Div, BinOp::Mod, BinOp::Eq, BinOp::Lt].into_iter()
+        .filter_map(BinOp::operator_interface)
+        .find_map(|(name, method)| (name == interface).then_some(method))
+        .unwrap_or("op");
+    let ret = match interface {
+        "Eq" => "bool",
+        "Ord" => "i32",
+        _ => ty,
+    };
+    let skeleton = format!(
+        "impl {} for {} {{\n    fn {}(self: {}, other: {}) -> {} {{\n        // ...\n    }}\n}}",
+        interface, ty, method, ty, rhs, ret
+    );
+    Suggestion {
+        message: format!("Implement '{}' for '{}':\n{}", interface, ty, skeleton),
+        replacement: Some(skeleton),
+        location: None,
+        confidence,
     }
-    
-    typos
 }
 
-impl CompilationFeedback {
-    /// Create a successful feedback
-    pub fn success(source_file: String, stats: CompilationStats) -> Self {
-        Self {
-            success: true,
-            source_file,
-            diagnostics: vec![],
-            stats,
-            ai_ir_summary: None,
-        }
-    }
-    
-    /// Create a failed feedback
-    pub fn failure(source_file: String, errors: Vec<ErrorReport>, stats: CompilationStats) -> Self {
-        Self {
-            success: false,
-            source_file,
-            diagnostics: errors,
-            stats,
-            ai_ir_summary: None,
-        }
+/// The message of `error` as a template of `locale` and its arguments
+fn error_message(error: &Error, locale: &str) -> MessageTemplate {
+    let template = match template_for(error.code(), locale) {
+        Some(template) => MessageTemplate::new(template),
+        None => return MessageTemplate::plain(error.to_string()),
+    };
+    match error {
+        Error::TypeMismatch { expected, got, .. } => template
+            .with_arg("expected", expected.as_str())
+            .with_arg("got", got.as_str()),
+        Error::UndefinedVariable { name, .. } | Error::UndefinedType { name, .. } => template
+            .with_arg("name", name.as_str()),
+        Error::ArgCountMismatch { func_name, expected, got, .. } => template
+            .with_arg("func_name", func_name.as_str())
+            .with_arg("expected", expected.to_string())
+            .with_arg("got", got.to_string()),
+        Error::MethodNotFound { method, ty, .. } => template
+            .with_arg("method", method.as_str())
+            .with_arg("ty", ty.as_str()),
+        Error::EffectViolation { message, .. } => template
+            .with_arg("message", message.as_str()),
+        Error::UnknownField { field, .. } => template
+            .with_arg("field", field.as_str()),
+        _ => MessageTemplate::plain(error.to_string()),
     }
-    
+}
+
+// ==================== Error Explanations ====================
+
+/// Long-form explanations for `aethc explain <code>`, keyed by `Error::code()`
+static ERROR_EXPLANATIONS: &[(&str, &str)] = &[
+    ("E0001", r#"A value's type does not match the type its context requires.
+
+Erroneous code example:
+
+    fn main() {
+        let flag: i32 = true
+    }
+
+AetherLang never converts between types implicitly, not even between
+integer widths. Change the annotation, or convert the value with `as`:
+
+    fn main() {
+        let flag: bool = true
+        let wide: i64 = 42 as i64
+    }
+"#),
+    ("E0002", r#"A name was used that is not defined in the current scope.
+
+Erroneous code example:
+
+    fn main() -> i32 {
+        return count
+    }
+
+Declare the variable before using it, and check the spelling:
+
+    fn main() -> i32 {
+        let count: i32 = 0
+        return count
+    }
+"#),
+    ("E0003", r#"A function was called with the wrong number of arguments.
+
+Erroneous code example:
+
+    fn add(a: i32, b: i32) -> i32 { return a + b }
+
+    fn main() -> i32 {
+        return add(1)
+    }
+
+Pass exactly one argument per declared parameter. For methods the
+receiver is passed with `value.method(...)` and is not counted:
+
+    fn main() -> i32 {
+        return add(1, 2)
+    }
+"#),
+    ("E0004", r#"The same name was defined twice in one scope.
+
+Erroneous code example:
+
+    fn helper() {}
+    fn helper() {}
+
+The message gives the offset of the first definition; the error points at
+the second. Rename one of the definitions, or move it into its own `mod`
+block:
+
+    fn helper() {}
+    mod extra { fn helper() {} }
+"#),
+    ("E0005", r#"Two definitions were emitted under the same symbol name.
+
+Erroneous code example:
+
+    struct Point { x: i64 }
+    impl Point { fn new() -> i64 { return 1 } }
+    fn Point__new() -> i64 { return 2 }
+
+Methods are emitted as `Type__method` and functions inside modules as
+`module__name`, so a hand-written function with such a name can collide
+with a generated one. Rename the function:
+
+    fn make_point() -> i64 { return 2 }
+"#),
+    ("E0006", r#"Something that is not a function was called.
+
+Erroneous code example:
+
+    fn main() {
+        let x: i32 = 5
+        x()
+    }
+
+Only functions, closures and function pointers can be called. Check that
+the name refers to the function you meant and is not shadowed by a local.
+"#),
+    ("E0007", r#"A struct literal or field access was used on a type that is not a struct.
+
+Erroneous code example:
+
+    fn main() {
+        let n: i32 = 1
+        let m: i32 = n.value
+    }
+
+Field access and `Name { ... }` literals require a struct type. Check the
+type of the expression, or declare the struct you meant to use.
+"#),
+    ("E0008", r#"A type name was used that is not defined.
+
+Erroneous code example:
+
+    fn area(shape: Shape) -> f64 { return 0.0 }
+
+Define the type, import its module with `use`, or fix the spelling:
+
+    struct Shape { width: f64, height: f64 }
+
+    fn area(shape: Shape) -> f64 { return shape.width * shape.height }
+"#),
+    ("E0009", r#"A method was called that the receiver's type does not provide.
+
+Erroneous code example:
+
+    struct Counter { count: i64 }
+
+    fn main() {
+        let c: Counter = Counter { count: 0 }
+        c.bump()
+    }
+
+Add the method to an `impl` block for the type, or implement an
+interface that declares it:
+
+    impl Counter {
+        fn bump(self: *Counter) { self.count = self.count + 1 }
+    }
+"#),
+    ("E0010", r#"A function performs an effect that its signature does not declare.
+
+Erroneous code example:
+
+    fn greet() pure {
+        println("hello")
+    }
+
+`pure` functions may not perform any effect, and `effect[...]` functions
+may only perform the listed ones. Declare the effect on the function:
+
+    fn greet() effect[io] {
+        println("hello")
+    }
+"#),
+    ("E0011", r#"A struct field was accessed or initialized that the struct does not have.
+
+Erroneous code example:
+
+    struct Point { x: i64, y: i64 }
+
+    fn main() {
+        let p: Point = Point { x: 1, z: 2 }
+    }
+
+Use one of the fields declared on the struct, or add the field to its
+definition.
+"#),
+    ("E0012", r#"The `*` operator was applied to a value that is not a pointer or reference.
+
+Erroneous code example:
+
+    fn main() {
+        let n: i32 = 1
+        let m: i32 = *n
+    }
+
+Only pointers (`*T`) and references (`&T`) can be dereferenced. Remove the
+`*`, or take the address of the value first.
+"#),
+    ("E0013", r#"An index expression was used on a value that cannot be indexed.
+
+Erroneous code example:
+
+    fn main() {
+        let n: i32 = 1
+        let m: i32 = n[0]
+    }
+
+Indexing works on arrays, slices and pointers. Check the type of the
+value being indexed.
+"#),
+    ("E0014", r#"`size_of`, `align_of` or `offset_of` was used on a type without a known layout.
+
+Erroneous code example:
+
+    fn main() -> i64 {
+        return size_of::<Missing>()
+    }
+
+The type must be fully defined before its layout can be computed.
+Define the struct, or query the size of a pointer to it instead.
+"#),
+    ("E0015", r#"A `static` or `const` was initialized with an expression that is not a constant.
+
+Erroneous code example:
+
+    fn compute() -> i64 { return 4 }
+    static LIMIT: i64 = compute()
+
+Static initializers are evaluated at compile time and may only use
+literals, other constants and arithmetic on them:
+
+    static LIMIT: i64 = 4 * 10
+"#),
+    ("E0016", r#"An operation that can break memory safety was used outside an `unsafe` block.
+
+Erroneous code example:
+
+    fn main() {
+        asm!("nop")
+    }
+
+Inline assembly, raw pointer dereferences and mutable statics must be
+wrapped in `unsafe`:
+
+    fn main() {
+        unsafe {
+            asm!("nop")
+        }
+    }
+"#),
+    ("E0017", r#"An inline assembly operand is invalid.
+
+Erroneous code example:
+
+    fn main() {
+        unsafe {
+            asm!("mov {0}, 1", out(banana) x)
+        }
+    }
+
+Operands must name a known register or register class (such as `reg`
+or `rax`) and refer to an assignable place for outputs.
+"#),
+    ("E0018", r#"A `#[repr(...)]` attribute was used on an item it does not apply to.
+
+Erroneous code example:
+
+    #[repr(u8)]
+    struct Flags { bits: u8 }
+
+Integer reprs apply to enums; `repr(C)` and `repr(packed)` apply to
+structs:
+
+    #[repr(C)]
+    struct Flags { bits: u8 }
+"#),
+    ("E0019", r#"An interface implementation does not define one of the interface's associated types.
+
+Erroneous code example:
+
+    interface Iterator {
+        type Item
+        fn next(self: *Self) -> Self::Item;
+    }
+
+    impl Iterator for Counter {
+        fn next(self: *Counter) -> u32 { return 1 }
+    }
+
+Define every associated type declared by the interface:
+
+    impl Iterator for Counter {
+        type Item = u32
+        fn next(self: *Counter) -> Self::Item { return 1 }
+    }
+"#),
+    ("E0020", r#"`main` was declared with a signature the runtime cannot call.
+
+Erroneous code example:
+
+    fn main(name: str) -> bool {
+        return true
+    }
+
+`main` takes no parameters, `args: [str]`, or `(argc: i32, argv: **u8)`,
+and returns nothing or an integer exit status:
+
+    fn main(args: [str]) -> i32 {
+        return 0
+    }
+"#),
+    ("E0021", r#"A generic function was instantiated with the wrong number of arguments.
+
+Erroneous code example:
+
+    fn zeros<const N: usize>() -> i64 {
+        return N
+    }
+
+    fn main() {
+        let n: i64 = zeros::<4, 8>()
+    }
+
+Pass exactly one argument per generic parameter:
+
+    let n: i64 = zeros::<4>()
+"#),
+    ("E0022", r#"An `unsafe` block contains no unsafe operations.
+
+This is a warning. Example:
+
+    fn add(a: i64, b: i64) -> i64 {
+        unsafe { return a + b }
+    }
+
+Unsafe operations are raw pointer dereferences and indexing, inline
+assembly, mutable static and union field accesses, and calls to extern or
+raw memory functions. Remove the `unsafe` block when it contains none:
+
+    fn add(a: i64, b: i64) -> i64 {
+        return a + b
+    }
+"#),
+    ("E0023", r#"A raw pointer was dereferenced or indexed outside an `unsafe` block.
+
+Erroneous code example (an error in strict mode, a warning otherwise):
+
+    fn first(p: *i64) -> i64 {
+        return p[0]
+    }
+
+Wrap the access in `unsafe` to state that the pointer is valid:
+
+    fn first(p: *i64) -> i64 {
+        unsafe { return p[0] }
+    }
+"#),
+    ("E0024", r#"A struct invariant is not a boolean condition on the struct's own fields.
+
+Erroneous code example:
+
+    static LIMIT: i64 = 10
+
+    struct Range [invariant lo <= LIMIT] {
+        lo: i64,
+        hi: i64,
+    }
+
+Invariants are checked each time the struct is constructed, so they may
+only read its fields:
+
+    struct Range [invariant lo <= hi] {
+        lo: i64,
+        hi: i64,
+    }
+"#),
+    ("E0025", r#"An item or struct field of another module was used without being `pub`.
+
+Erroneous code example, with `shapes.aeth` containing:
+
+    pub struct Point {
+        pub x: i64,
+        y: i64,
+    }
+
+    fn origin() -> Point { return Point { x: 0, y: 0 } }
+
+and the program:
+
+    use shapes::origin
+
+    fn y_of(p: Point) -> i64 {
+        return p.y
+    }
+
+Only `pub` items and fields are visible outside the module that defines
+them. Mark them `pub` in `shapes.aeth`:
+
+    pub struct Point {
+        pub x: i64,
+        pub y: i64,
+    }
+
+    pub fn origin() -> Point { return Point { x: 0, y: 0 } }
+"#),
+    ("E0026", r#"A field was read from a value that is not a struct, even after
+dereferencing.
+
+Erroneous code example:
+
+    fn main() {
+        let n: i64 = 1
+        let p: *i64 = &n as *i64
+        let m: i64 = p.value
+    }
+
+Field access sees through up to 8 layers of pointers and references, so
+`a.next.next.value` works when `next` is a `*Node`. The value underneath
+must be a struct that declares the field.
+"#),
+    ("E0027", r#"An or-pattern binds a variable in some alternatives but not others.
+
+Erroneous code example:
+
+    enum Shape {
+        Circle(i64),
+        Square(i64),
+        Empty,
+    }
+
+    fn size(s: Shape) -> i64 {
+        return match s {
+            Shape::Circle(n) | Shape::Empty => n,
+            Shape::Square(n) => n,
+        }
+    }
+
+Whichever alternative matched, the arm's guard and body use the same
+names, so each alternative must bind every one of them, with the same type.
+"#),
+    ("E0028", r#"A `let` pattern does not match every value of its type.
+
+Erroneous code example:
+
+    enum Shape {
+        Circle(i64),
+        Empty,
+    }
+
+    fn radius(s: Shape) -> i64 {
+        let Shape::Circle(r): Shape = s
+        return r
+    }
+
+A `let` has nowhere to go when the value does not match. Use `match`, or
+`if let Shape::Circle(r) = s { .. } else { .. }`, to handle the other cases.
+"#),
+    ("E0029", r#"A struct pattern leaves out some of the struct's fields.
+
+Erroneous code example:
+
+    struct Point { x: i64, y: i64 }
+
+    fn first(p: Point) -> i64 {
+        let Point { x }: Point = p
+        return x
+    }
+
+List every field, or end the pattern with `..` to ignore the rest:
+`Point { x, .. }`.
+"#),
+    ("E0030", r#"A value was used after it was moved.
+
+Erroneous code example:
+
+    fn consume(s: String) {}
+
+    fn main() {
+        let s: String = "hi"
+        consume(s)
+        consume(s)
+    }
+
+After a move the original binding is no longer valid. Pass a reference
+instead, or use a fresh value for the second call.
+"#),
+    ("E0031", r#"A value was moved while a borrow of it was still alive.
+
+Moving would leave the borrow pointing at invalid data. End the borrow
+before moving the value, or move a copy instead.
+"#),
+    ("E0032", r#"A value was borrowed mutably while it was already borrowed immutably.
+
+Erroneous code example:
+
+    fn main() {
+        let mut n: i32 = 1
+        let r: &i32 = &n
+        let m: &mut i32 = &mut n
+    }
+
+A mutable borrow requires exclusive access. Finish using the shared
+borrow before taking the mutable one.
+"#),
+    ("E0033", r#"A value was borrowed while it was already borrowed mutably.
+
+While a mutable borrow is alive, no other borrow of the same value is
+allowed. Finish using the mutable borrow first.
+"#),
+    ("E0034", r#"A value was borrowed mutably more than once at the same time.
+
+Erroneous code example:
+
+    fn main() {
+        let mut n: i32 = 1
+        let a: &mut i32 = &mut n
+        let b: &mut i32 = &mut n
+    }
+
+Only one mutable borrow may be alive at a time. Reuse the first borrow
+or let it go out of scope before taking another.
+"#),
+    ("E0035", r#"A value was moved out from behind a borrow.
+
+Borrowed data is owned elsewhere and cannot be taken. Copy the value,
+or take ownership of the original instead of borrowing it.
+"#),
+    ("E0036", r#"A mutable borrow was taken of a binding that is not mutable.
+
+Erroneous code example:
+
+    fn main() {
+        let n: i32 = 1
+        let r: &mut i32 = &mut n
+    }
+
+Declare the binding with `let mut`:
+
+    fn main() {
+        let mut n: i32 = 1
+        let r: &mut i32 = &mut n
+    }
+"#),
+    ("E0037", r#"Two variants of an enum have the same discriminant.
+
+Erroneous code example:
+
+    enum Errno { EPERM = 1, ENOENT = 1 }
+
+A variant without `= value` takes the previous variant's value plus one,
+so `enum E { A = 1, B, C = 2 }` also collides. Give each variant a
+distinct value.
+"#),
+    ("E0038", r#"An enum discriminant does not fit in the enum's integer type.
+
+Erroneous code example:
+
+    #[repr(u8)]
+    enum Big { A = 255, B }
+
+The type comes from `#[repr(...)]` and is `i32` without one. Pick a
+wider repr or smaller values.
+"#),
+    ("E0039", r#"A `let` reuses the name of a function parameter.
+
+This is a warning. Example:
+
+    fn scale(n: i64) -> i64 {
+        let n: i64 = n * 2;
+        return n
+    }
+
+The parameter cannot be used after the `let`. Shadowing another `let` is
+fine and does not warn; for parameters, pick a new name if the original
+value is still needed.
+"#),
+    ("E0040", r#"A local variable or parameter is never read.
+
+This is a warning. Example:
+
+    fn area(w: i64, h: i64) -> i64 {
+        let unused: i64 = w + h;
+        return w * h
+    }
+
+Remove the binding, or start its name with `_` if it is intentionally
+unused. Assigning to a variable does not count as reading it.
+`#[allow(unused_variables)]` on the function silences the warning.
+"#),
+    ("E0041", r#"A private function or struct is never used.
+
+This is a warning. Example:
+
+    fn old_helper() -> i64 { return 1 }
+
+    fn main() effect[io] { println_i64(2) }
+
+Items are used when something reachable from `main`, a `pub` item or an
+exported (`#[extern_c]`) function refers to them; a function that only
+calls itself is still unused. Remove the item, or keep it with
+`#[allow(dead_code)]`. Programs without `main` treat every function as
+used.
+"#),
+    ("E0042", r#"A private struct field is never read.
+
+This is a warning. Example:
+
+    struct Config { name: i64, verbose: bool }
+
+Setting the field in a struct literal or assigning to it does not count
+as a read. Remove the field, mark it `pub`, or add `#[allow(dead_code)]`
+to the struct. Structs with attributes such as `#[repr(C)]` are not
+checked, since C code may read their fields.
+"#),
+    ("E0043", r#"A statement follows a `return`, `break` or `continue` and never runs.
+
+This is a warning. Example:
+
+    fn f() -> i64 {
+        return 1;
+        println_i64(2);
+    }
+
+Remove the dead statements, or silence the warning with
+`#[allow(unreachable_code)]` on the function.
+"#),
+    ("E0044", r#"A function with a return type ends in a statement instead of a value.
+
+Erroneous code example:
+
+    fn add(a: i64, b: i64) -> i64 {
+        a + b;
+    }
+
+A block's value is its final expression, written without a trailing
+semicolon; `a + b;` is a statement and leaves the body without a value.
+Remove the semicolon, or end the body with `return a + b`.
+"#),
+    ("E0045", r#"The analyzer stopped reporting errors after reaching the limit.
+
+A fundamental mistake, such as a misspelled type used everywhere, can
+cause a cascade of follow-up errors. After `--max-errors` errors (20 by
+default) the rest are counted but not shown. Fix the first errors and
+compile again, or pass `--max-errors 0` to see all of them.
+"#),
+    ("E0046", r#"A `for` loop iterates over a value that is not an array.
+
+Erroneous code example:
+
+    fn sum(p: *i64) -> i64 {
+        let mut s: i64 = 0;
+        for x in p {
+            s = s + x;
+        }
+        s
+    }
+
+`for x in a` visits each element of a fixed-size array `[T; N]`. Slices
+and raw pointers carry no length, so loop over an index with `while`
+and stop at a length passed alongside them.
+"#),
+    ("E0047", r#"An attribute the compiler does not know, reported as a warning.
+
+Example:
+
+    #[inlin]
+    fn square(x: i64) -> i64 {
+        x * x
+    }
+
+Unknown attributes are ignored, so a misspelled `#[inline]`, `#[cold]` or
+`#[repr(C)]` silently has no effect. Check the spelling against the
+attributes listed in the language reference.
+"#),
+    ("E0048", r#"An attribute is written on an item it cannot apply to.
+
+Erroneous code example:
+
+    #[inline]
+    struct Point {
+        x: i64,
+        y: i64,
+    }
+
+Optimization hints such as `#[inline]`, `#[inline(never)]`, `#[cold]` and
+`#[hot]`, and code generation attributes such as `#[naked]` or
+`#[extern_c]`, only apply to functions. `#[repr(..)]` only applies to
+types. Move the attribute to the function it was meant for.
+"#),
+    ("E0049", r#"A struct's fields are declared in an order that wastes space on
+padding, reported as a warning.
+
+Example:
+
+    struct Packet {
+        flag: bool,
+        count: u64,
+        tag: u16,
+    }
+
+Each field starts at a multiple of its alignment, so `count` is preceded
+by 7 bytes of padding and the struct ends with 6 more: 24 bytes for 11
+bytes of data. Declare the most aligned fields first, or add
+`#[field_order(optimal)]` to let the compiler order them (16 bytes here).
+Write `#[field_order(declaration)]` to keep the order as written, for
+instance to match a layout defined elsewhere, and silence the warning.
+`aethc build --show-struct-layout` prints the offsets of every field.
+"#),
+    ("E0050", r#"A value was converted implicitly to a numeric type that cannot
+represent all of its values. An error in strict mode, a warning otherwise.
+
+Example:
+
+    fn store(byte: u8) {}
+
+    fn main() {
+        let n: i64 = 300
+        store(n)
+    }
+
+`n` is truncated to 44. Conversions to a narrower integer, between signed
+and unsigned integers, and from `f64` to `f32` are lossy; literals are
+exempt. Write the conversion with `as` to show it is intended
+(`store(n as u8)`), or widen the destination type.
+"#),
+    ("E0051", r#"A program built with `--freestanding` used a builtin that the C
+library or the AetherLang runtime provides.
+
+Erroneous code example:
+
+    fn _start() {
+        print("hello")
+    }
+
+Freestanding programs are linked without libc, so `print`, `alloc`,
+`exit`, `String` and the other hosted builtins do not exist. Declare the
+functions the program provides or links against in an `extern` block, or
+use `asm!` to make system calls directly:
+
+    fn _start() {
+        unsafe {
+            asm!("syscall", in(rax) 60, in(rdi) 42, clobber("rcx"), clobber("r11"))
+        }
+    }
+"#),
+    ("E0052", r#"The left-hand side of an assignment is not a place that can
+hold a value.
+
+Erroneous code example:
+
+    fn main() {
+        let mut a: i64 = 1
+        a + 1 = 2
+    }
+
+Only variables, struct fields (`p.x`), elements (`items[i]`) and
+dereferenced pointers (`*ptr`) can be assigned, with `=` or a compound
+operator like `+=`. Assign to the variable instead: `a = 2 - 1`.
+"#),
+    ("E0053", r#"A variable that is not declared `mut` was assigned, or one of its
+fields or elements was.
+
+Erroneous code example:
+
+    fn main() {
+        let count: i64 = 0
+        count += 1
+    }
+
+Declare the variable with `let mut count: i64 = 0`. Writing through a
+pointer, as in `*ptr = 1` or `node.next.value = 1` where `next` is a
+pointer, needs no `mut` on the variable holding it.
+"#),
+    ("E0054", r#"`==` or `!=` was used on a struct, or an enum with payloads, that
+does not declare how its values compare.
+
+Erroneous code example:
+
+    struct Point { x: i32, y: i32 }
+
+    fn same(a: Point, b: Point) -> bool {
+        return a == b
+    }
+
+Annotate the type with `#[derive(Eq)]`: `==` then compares the fields
+(or the variant and its payload) one by one, and so needs every field's
+type to be comparable too. Enums without payloads compare by variant, and
+`str` and `String` by their bytes, without the attribute. To decide
+equality yourself, implement `Eq` instead:
+
+    impl Eq for Point {
+        fn eq(self: Point, other: Point) -> bool {
+            return self.x == other.x
+        }
+    }
+"#),
+    ("E0055", r#"`<`, `<=`, `>` or `>=` was used on a value that has no ordering.
+
+Erroneous code example:
+
+    enum Level { Low, High }
+
+    fn above(a: Level, b: Level) -> bool {
+        return a > b
+    }
+
+Only numbers, chars and pointers are ordered, and structs and enums that
+implement `Ord`. Its `cmp` returns a negative integer, zero or a positive
+integer as `self` is less than, equal to or greater than `other`:
+
+    impl Ord for Level {
+        fn cmp(self: Level, other: Level) -> i32 {
+            return (self as i32) - (other as i32)
+        }
+    }
+
+Or compare a number derived from the value, such as `a as i32 > b as i32`.
+"#),
+    ("E0056", r#"An empty array literal needs an expected type to take its element type from.
+
+Erroneous code example:
+
+    println_i64([])
+
+`[]` has no element to infer a type from, so it takes the one of the
+array type expected where it is written: the annotation of a `let`, a
+parameter, a struct field or a variant payload. Write it where an array
+type is expected, such as `let empty: [i64; 0] = []`.
+"#),
+    ("E0057", r#"The type of an expression, parameter or variable could not be inferred.
+
+Erroneous code example:
+
+    let t: (i64, i64) = (1, 2)
+    t.frob(3)
+
+After checking each function the compiler reports what it has left
+without a type, such as calls to methods it cannot see. Only the innermost
+unknown expression is reported, the ones around it being unknown through
+it. This is an error in strict mode and a warning otherwise; annotate the
+value or call a function whose signature is known.
+"#),
+    ("E0058", r#"An arithmetic operator was used on a struct or enum that does not
+implement it.
+
+Erroneous code example:
+
+    struct Vec3 { x: i64, y: i64, z: i64 }
+
+    fn sum(a: Vec3, b: Vec3) -> Vec3 {
+        return a + b
+    }
+
+Operators on user types call the method of an operator interface the
+left operand's type implements: `+` calls `Add::add`, `-` `Sub::sub`,
+`*` `Mul::mul`, `/` `Div::div` and `%` `Rem::rem`. The method takes the
+right operand, whose type must be exactly that of its `other` parameter,
+and the expression has its return type. `a += b` assigns `a + b`.
+
+    impl Add for Vec3 {
+        fn add(self: Vec3, other: Vec3) -> Vec3 {
+            return Vec3 { x: self.x + other.x, y: self.y + other.y, z: self.z + other.z }
+        }
+    }
+
+`==` and `!=` call `Eq::eq`, and `<`, `<=`, `>` and `>=` compare what
+`Ord::cmp` returns against 0 (see E0054 and E0055).
+"#),
+    ("E0059", r#"A `#[bits(N)]` field cannot be packed as written.
+
+Erroneous code example:
+
+    struct Flags {
+        #[bits(9)]
+        mode: u8,
+    }
+
+Consecutive bitfields share one unsigned integer, so a bitfield must be
+a `bool` of width 1 or an unsigned integer no wider than its type, with
+a width of at least 1. Bitfields cannot be combined with `#[repr(packed)]`,
+`#[repr(transparent)]` or `#[field_order(optimal)]`, and since they do not
+start on a byte boundary, `&s.mode` cannot be taken; copy the value into
+a local first.
+
+Storing a computed value keeps its low bits, but an integer constant
+must fit the width: `s.mode = 9` is rejected for a `#[bits(3)]` field.
+"#),
+    ("E0060", r#"A generic function was called with a type that does not implement an
+interface its type parameter is bound by.
+
+Erroneous code example:
+
+    interface Shape {
+        fn area(self: *Self) -> f64;
+    }
+
+    struct Line { len: f64 }
+
+    fn area_of<T: Shape>(s: *T) -> f64 {
+        return s.area()
+    }
+
+    fn main() {
+        let l = Line { len: 2.0 }
+        let a: f64 = area_of(&l)
+    }
+
+The bound `T: Shape` lets the body call the methods of `Shape` on a `T`,
+so every type the function is called with needs an `impl Shape` block.
+Implement the interface for the type, or call a function that does not
+require it:
+
+    impl Shape for Line {
+        fn area(self: *Line) -> f64 {
+            return 0.0
+        }
+    }
+"#),
+    ("E0061", r#"A method of an interface was called on a value of a type parameter
+that is not bound by the interface.
+
+Erroneous code example:
+
+    interface Shape {
+        fn area(self: *Self) -> f64;
+    }
+
+    fn area_of<T>(s: *T) -> f64 {
+        return s.area()
+    }
+
+Nothing is known about an unbounded type parameter, so no method can be
+called on it. Bound it by the interface that declares the method; calls
+must then pass types that implement it (see E0060):
+
+    fn area_of<T: Shape>(s: *T) -> f64 {
+        return s.area()
+    }
+"#),
+    ("E0062", r#"A generic enum or the builtin `Vec` was given a type it cannot hold.
+
+Erroneous code example:
+
+    struct Point {
+        x: i64,
+        y: i64,
+    }
+
+    fn origin() -> Option<Point> {
+        return Some(Point { x: 0, y: 0 })
+    }
+
+The payloads of generic enums, including the builtin `Option` and
+`Result`, are stored as 64-bit integers. Integers, `bool`, `char`,
+pointers and other generic enums fit; structs, plain enums, tuples,
+arrays, `str` and floats do not. Store a pointer to the value instead:
+
+    fn first(points: *Point) -> Option<*Point> {
+        return Some(points)
+    }
+
+The builtin `Vec` copies its elements by size, so it also holds floats,
+`str`s, structs, tuples and enums, but not fixed-size arrays; store a
+pointer to the array instead. `pop` moves the element into an `Option`,
+so it is rejected on a `Vec` of floats, `str`s, structs or tuples; index
+the last element instead.
+"#),
+    ("E0063", r#"A `match` has no arm for some value of the matched type.
+
+Erroneous code example:
+
+    enum Tok { Plus, Minus, Num(i64) }
+
+    fn weight(t: Tok) -> i64 {
+        return match t {
+            Tok::Plus => 1,
+            Tok::Num(n) if n > 10 => n,
+        }
+    }
+
+Every value must match an arm, as nothing runs for one that does not.
+An arm with a guard covers nothing, since the guard may fail; an
+or-pattern covers each of its alternatives. Integers and strings have
+too many values to list, so matching them needs a `_` arm:
+
+    fn weight(t: Tok) -> i64 {
+        return match t {
+            Tok::Plus | Tok::Minus => 1,
+            Tok::Num(n) if n > 10 => n,
+            Tok::Num(_) => 0,
+        }
+    }
+"#),
+    ("E0100", r#"The parser found a token it did not expect.
+
+Erroneous code example:
+
+    fn main() {
+        let x: i32 = 1 +
+    }
+
+The message names the token that was expected. Common causes are a
+missing operand, an unbalanced bracket, or a missing `:` type annotation
+on `let`.
+"#),
+    ("E0101", r#"The parser expected a specific construct that was missing.
+
+The message names what was expected (for example `'{'` after a function
+signature). Check the code just before the reported location.
+"#),
+    ("E0102", r#"A name was expected but something else was found.
+
+Erroneous code example:
+
+    fn 1st() {}
+
+Identifiers start with a letter or `_`:
+
+    fn first() {}
+"#),
+    ("E0103", r#"A type was expected but something else was found.
+
+Erroneous code example:
+
+    fn main() {
+        let x: = 1
+    }
+
+Write the type after the `:`:
+
+    fn main() {
+        let x: i32 = 1
+    }
+"#),
+    ("E0104", r#"An expression was expected but something else was found.
+
+Erroneous code example:
+
+    fn main() -> i32 {
+        return )
+    }
+
+Check for a missing value after an operator, `=`, or `return`.
+"#),
+    ("E0105", r#"A pattern was expected in a `match` arm or `let` binding.
+
+Patterns are literals, names, `_`, enum variants such as `Shape::Circle(r)`,
+or struct patterns. Check the left-hand side of the `=>`.
+"#),
+    ("E0106", r#"An array type is missing its size.
+
+Erroneous code example:
+
+    fn main() {
+        let a: [i32; ] = [1, 2]
+    }
+
+Write the length as an integer or a const generic parameter:
+
+    fn main() {
+        let a: [i32; 2] = [1, 2]
+    }
+"#),
+    ("E0107", r#"An operator was used where it is not valid.
+
+Check that binary operators have an operand on each side and that the
+operator exists in AetherLang.
+"#),
+    ("E0108", r#"A string literal, character literal or block comment was not closed.
+
+Erroneous code example:
+
+    fn main() effect[io] {
+        println("hello)
+    }
+
+String literals must end with `"` on the same line, character literals
+with `'`, and every `/*` needs a matching `*/`.
+"#),
+    ("E0109", r#"Expressions, types or patterns are nested too deeply.
+
+The parser, the type checker and IR generation limit how deeply
+constructs may be nested (2048 levels, or `--max-nesting N`) so that
+pathological input cannot exhaust the stack. Long chains of binary
+operators such as `a + b + c + ...` do not count against the limit.
+Split the expression into several `let` bindings or helper functions.
+"#),
+    ("E0200", r#"A module could not be loaded.
+
+Erroneous code example:
+
+    use missing_module
+
+The module is searched for as `<name>.aeth` next to the importing file
+and in the standard library directories. Check the name and that the
+file exists and parses.
+"#),
+    ("E0201", r#"A module file was found but could not be imported.
+
+Erroneous code example:
+
+    // util.aeth
+    pub fn helper() -> i64 { return 1 +
+
+    // main.aeth
+    use util
+
+The error is reported at the `use` that led to the broken module, which
+may be imported indirectly; the message gives the module file, line and
+column. Fix the imported file.
+"#),
+    ("E0202", r#"Modules import each other in a cycle.
+
+Erroneous code example:
+
+    // a.aeth
+    use b
+
+    // b.aeth
+    use a
+
+The message gives the whole cycle, e.g. `a -> b -> c -> a`, starting from
+the file being compiled when the cycle returns to it, and every cycle the
+imports contain is reported. Move what the modules share into a module
+that imports neither of them. Two modules importing the same third one
+is not a cycle.
+"#),
+    ("E0300", r#"The file passed to `--cross-config` is invalid.
+
+A cross config contains `key = "string"` or `key = ["string", ...]` pairs,
+optionally under a `[target]` table:
+
+    [target]
+    triple = "aarch64-linux-gnu"
+    sysroot = "/opt/sysroots/aarch64"
+    compiler = "aarch64-linux-gnu-gcc"
+    cflags = ["-march=armv8-a"]
+
+The recognized keys are `triple`, `sysroot`, `compiler` and `cflags`.
+"#),
+    ("E0301", r#"The file passed to `--transform-rules` is invalid.
+
+A rules file may turn off built-in rules with a top-level `disable` array
+and adds rules in `[[rule]]` tables:
+
+    disable = ["none-check"]
+
+    [[rule]]
+    kind = "rename-call"
+    from = "puts"
+    to = "println"
+
+The built-in kinds are `bool-comparison`, `none-check` and
+`c-string-literal`. The kinds `rename-call` and `rename-type` need both
+`from` and `to`.
+"#),
+    ("E0302", r#"An Aether Script (`.ath`) source could not be parsed.
+
+Script is indentation based: a block opens with `:` at the end of a line
+and continues while the following lines are indented further.
+
+    def main():
+        print("hello")
+
+The message says which token the parser expected instead.
+"#),
+    ("E0303", r#"The linker script passed to `--linker-script` is invalid.
+
+The native backend understands three commands; `/* */` comments are allowed:
+
+    ENTRY(_start)
+    BASE_ADDRESS(0x8000)
+    SECTIONS {
+        .text 0x1000 : { }
+    }
+
+Addresses are decimal or `0x` hexadecimal, optionally followed by `K` or
+`M`. Section addresses are offsets from the base address, and the entry
+must name a symbol the program defines, such as `_start` or `main`.
+"#),
+    ("E0304", r#"The self-hosted linker could not link a `--freestanding` build.
+
+It links the C compiler's x86-64 object into a static executable without a
+system linker, so every symbol must be defined by the program itself:
+
+    undefined symbol `memcpy`
+
+means the generated code calls a C library function. Define it in the
+program (a `#[no_mangle]` function) or avoid the construct that needs it.
+The entry symbol named by `--entry` (default `_start`) must exist, and
+only non-PIC relocations are supported.
+"#),
+    ("E0400", r#"A textual IR file passed to `aethc opt` could not be parsed.
+
+IR files use the format printed by `--emit-ir`:
+
+    module demo
+    fn double(n: i64) -> i64 {
+      bb0 entry:
+        %0 = mul arg0 2
+        ret %0
+    }
+
+Blocks are introduced by `bbN label:`, and loads and GEPs name their
+element type (`%1 = load i64, %0`). The message gives the line of the
+first token that did not fit the grammar.
+"#),
+    ("E0401", r#"`aethc link` could not combine its `.alito` inputs.
+
+Each input must be written by `aethc --lto build`, with the same backend
+that links it. Functions and statics that several modules define
+differently are renamed apart, which fails for:
+
+    - `main` or an `#[extern_c]` export defined by two modules
+    - an `extern` declaration naming a symbol two modules define
+    - a struct or enum with different definitions in two modules
+"#),
+    ("E9001", r#"The compiler could not read or write a file.
+
+Check that the input exists and that the output directory is writable.
+"#),
+    ("E9002", r#"The LLVM backend reported an error.
+
+This usually indicates a compiler bug. Try the C backend with `--emit-c`
+and report the problem with the source that triggered it.
+"#),
+    ("E9003", r#"Code generation failed.
+
+This covers failures of the C compiler or linker as well as constructs the
+backend cannot lower yet. The message includes the underlying tool's error.
+"#),
+    ("E9004", r#"IR generation or an optimizer pass produced malformed IR.
+
+This is a compiler bug. The message lists each violation with the function,
+block and instruction involved. Rebuilding with `--verify-ir` checks the IR
+after every optimizer pass, which pinpoints the pass that broke it. Please
+report the problem with the source that triggered it.
+"#),
+    ("E9999", r#"An error without a specific code.
+
+Read the accompanying message for details.
+"#),
+];
+
+/// Look up the long-form explanation of an error code (`E0001`, `e0001` or `0001`)
+pub fn explain(code: &str) -> Option<&'static str> {
+    let code = code.trim().to_ascii_uppercase();
+    let code = if code.starts_with('E') { code } else { format!("E{}", code) };
+    ERROR_EXPLANATIONS.iter()
+        .find(|(c, _)| *c == code)
+        .map(|(_, text)| *text)
+}
+
+/// Get common typos for variable names (AI error pattern recognition)
+fn get_common_typos(name: &str) -> Vec<(String, String)> {
+    let mut typos = Vec::new();
+    
+    // Common patterns AI often confuses
+    let patterns = [
+        ("resut", "result"),
+        ("reuslt", "result"),
+        ("reslut", "result"),
+        ("lenght", "length"),
+        ("lenth", "length"),
+        ("indx", "index"),
+        ("idx", "index"),
+        ("cnt", "count"),
+        ("coutn", "count"),
+        ("val", "value"),
+        ("valu", "value"),
+        ("tmp", "temp"),
+        ("i", "index"),  // Often AI uses 'i' but declares 'index'
+    ];
+    
+    for (wrong, correct) in patterns {
+        if name == wrong {
+            typos.push((wrong.to_string(), correct.to_string()));
+        }
+        if name == correct {
+            // Also suggest the abbreviation might be intended
+            typos.push((correct.to_string(), wrong.to_string()));
+        }
+    }
+    
+    typos
+}
+
+impl CompilationFeedback {
+    /// Create a successful feedback
+    pub fn success(source_file: String, stats: CompilationStats) -> Self {
+        Self {
+            success: true,
+            source_file,
+            diagnostics: vec![],
+            stats,
+            ai_ir_summary: None,
+            locale: DEFAULT_LOCALE,
+        }
+    }
+    
+    /// Create a failed feedback
+    pub fn failure(source_file: String, errors: Vec<ErrorReport>, stats: CompilationStats) -> Self {
+        Self {
+            success: false,
+            source_file,
+            diagnostics: errors,
+            stats,
+            ai_ir_summary: None,
+            locale: DEFAULT_LOCALE,
+        }
+    }
+    
+    /// Select the template set for the diagnostics' messages
+    pub fn with_locale(mut self, locale: &'static str) -> Self {
+        self.locale = locale;
+        self
+    }
+
     /// Output as JSON
     pub fn to_json(&self) -> String {
         serde_json::to_string_pretty(self).unwrap_or_else(|_| "{}".to_string())
@@ -408,13 +2399,118 @@ impl CompilationFeedback {
 impl Default for CompilationStats {
     fn default() -> Self {
         Self {
-            parse_time_ms: 0,
-            semantic_time_ms: 0,
-            ir_gen_time_ms: 0,
-            total_time_ms: 0,
+            parse_time_ms: 0.0,
+            semantic_time_ms: 0.0,
+            ir_gen_time_ms: 0.0,
+            optimize_time_ms: 0.0,
+            codegen_time_ms: 0.0,
+            c_compiler_time_ms: 0.0,
+            total_time_ms: 0.0,
             function_count: 0,
             type_count: 0,
             loc: 0,
+            errors_suppressed: 0,
+            passes: Vec::new(),
+            functions: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Span;
+
+    #[test]
+    fn test_explain() {
+        let errors = [
+            Error::TypeMismatch { expected: "i32".into(), got: "bool".into(), span: Span::dummy() },
+            Error::UseAfterMove { var: "s".into(), span: Span::dummy() },
+            Error::ExpectedType { span: Span::dummy() },
+            Error::CrossConfig("bad".into()),
+            Error::CodeGen("boom".into()),
+        ];
+        for error in &errors {
+            assert!(explain(error.code()).is_some(), "no explanation for {}", error.code());
+            assert_eq!(ErrorReport::from_error(error, "t.aeth").code, error.code());
         }
+        assert_eq!(explain("e0001"), explain("E0001"));
+        assert_eq!(explain("0001"), explain("E0001"));
+        assert!(explain("E4242").is_none());
+    }
+
+    #[test]
+    fn test_message_templates() {
+        let error = Error::ArgCountMismatch { func_name: "f".into(), expected: 2, got: 1, span: Span::dummy() };
+        let en = ErrorReport::from_error(&error, "t.aeth");
+        assert_eq!(en.message.format(), error.to_string());
+        let zh = ErrorReport::from_error_localized(&error, "t.aeth", "zh");
+        assert_eq!(zh.message.format(), "'f' 的参数个数不匹配：期望 2 个，实际为 1 个");
+
+        // Arguments stay structured in JSON and survive a round trip
+        let json = serde_json::to_value(&zh).unwrap();
+        assert_eq!(json["message"]["args"]["expected"], "2");
+        assert_eq!(json["message"]["text"], zh.message.format());
+        let back: ErrorReport = serde_json::from_value(json).unwrap();
+        assert_eq!(back.message, zh.message);
+
+        // Errors without a template keep their whole text
+        let other = Error::UseAfterMove { var: "s".into(), span: Span::dummy() };
+        let report = ErrorReport::from_error_localized(&other, "t.aeth", "zh");
+        assert_eq!(report.message.format(), other.to_string());
+        assert_eq!(MessageTemplate::new("{a} {b}").with_arg("a", "x").format(), "x {b}");
+        assert!(parse_locale("fr").is_err());
+    }
+
+    #[test]
+    fn test_rustc_json() {
+        let source = "fn main() {\n    let x: i64 = y;\n}\n";
+        let at = source.find('y').unwrap();
+        let error = Error::UndefinedVariable { name: "y".into(), span: Span::new(at, at + 1, 0) };
+        let report = ErrorReport::from_error(&error, "t.aeth");
+
+        let json = report.to_rustc_json(Some(source), ErrorFormat::Json);
+        assert_eq!(json["$message_type"], "diagnostic");
+        assert_eq!(json["message"], "Undefined variable: y");
+        assert_eq!(json["code"]["code"], "E0002");
+        assert!(json["code"]["explanation"].is_string());
+        assert_eq!(json["level"], "error");
+        let span = &json["spans"][0];
+        assert_eq!((span["byte_start"].as_u64(), span["byte_end"].as_u64()), (Some(at as u64), Some(at as u64 + 1)));
+        assert_eq!((span["line_start"].as_u64(), span["column_start"].as_u64(), span["column_end"].as_u64()), (Some(2), Some(18), Some(19)));
+        assert_eq!(span["text"][0]["text"], "    let x: i64 = y;");
+        assert!(json["children"].as_array().unwrap().iter().all(|c| c["level"] == "help"));
+        let rendered = json["rendered"].as_str().unwrap();
+        assert!(rendered.starts_with("error[E0002]: Undefined variable: y\n --> t.aeth:2:18\n  |\n2 |     let x: i64 = y;\n  |                  ^\n"), "{}", rendered);
+
+        let short = report.to_rustc_json(Some(source), ErrorFormat::JsonDiagnosticShort);
+        assert_eq!(short["rendered"], "t.aeth:2:18: error[E0002]: Undefined variable: y\n");
+        let ansi = report.render(Some(source), ErrorFormat::JsonDiagnosticRenderedAnsi);
+        assert!(ansi.starts_with("\x1b[1;31merror[E0002]\x1b[0m"), "{:?}", ansi);
+
+        // Without the source there is nothing to point into
+        assert_eq!(report.to_rustc_json(None, ErrorFormat::Json)["spans"], serde_json::json!([]));
+        assert_eq!(ErrorFormat::parse("json-diagnostic-short"), Ok(ErrorFormat::JsonDiagnosticShort));
+        assert!(ErrorFormat::parse("xml").is_err());
+    }
+
+    #[test]
+    fn test_color_config() {
+        assert_eq!(ColorChoice::Auto.resolve(true), ColorConfig::Ansi16);
+        assert_eq!(ColorChoice::Auto.resolve(false), ColorConfig::Never);
+        assert_eq!(ColorChoice::Always.resolve(false), ColorConfig::Ansi16);
+        assert_eq!(ColorChoice::parse("truecolor").map(|c| c.resolve(false)), Ok(ColorConfig::Truecolor));
+        assert!(ColorChoice::parse("sometimes").is_err());
+
+        assert_eq!(ColorConfig::Never.paint(Style::Bold, "E0002"), "E0002");
+        assert_eq!(ColorConfig::Ansi16.paint(Style::Severity(Severity::Warning), "^"), "\x1b[33m^\x1b[0m");
+        assert_eq!(ColorConfig::Ansi16.paint(Style::Severity(Severity::Info), "note"), "\x1b[34mnote\x1b[0m");
+        assert_eq!(ColorConfig::Ansi16.paint(Style::Severity(Severity::Hint), "help"), "\x1b[2mhelp\x1b[0m");
+        assert!(ColorConfig::Truecolor.paint(Style::Severity(Severity::Error), "error").starts_with("\x1b[1;38;2;"));
+
+        // The underline takes the severity's color
+        let report = ErrorReport::warning("W0001", "unused variable", Some(Location { file: "t.aeth".to_string(), line: 4, column: 0, end_line: Some(5), end_column: None }));
+        let rendered = report.render_colored(Some("let x = 1"), ErrorFormat::Human, ColorConfig::Ansi16);
+        assert!(rendered.contains("\x1b[33m    ^\x1b[0m"), "{:?}", rendered);
     }
 }
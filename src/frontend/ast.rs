@@ -9,9 +9,13 @@ use crate::utils::Span;
 
 /// A generic parameter: either a type parameter or a const parameter
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub enum GenericParam {
-    /// Type parameter: `T`, `U`
-    Type(Ident),
+    /// Type parameter: `T`, `U`, or with interface bounds `T: Shape + Eq`
+    Type {
+        name: Ident,
+        bounds: Vec<Type>,
+    },
     /// Const parameter: `const N: usize`
     Const {
         name: Ident,
@@ -22,7 +26,7 @@ pub enum GenericParam {
 impl GenericParam {
     pub fn name(&self) -> &Ident {
         match self {
-            GenericParam::Type(name) => name,
+            GenericParam::Type { name, .. } => name,
             GenericParam::Const { name, .. } => name,
         }
     }
@@ -30,6 +34,7 @@ impl GenericParam {
 
 /// A generic argument: either a type or a const value
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub enum GenericArg {
     /// Type argument: `i32`, `String`
     Type(Type),
@@ -39,14 +44,34 @@ pub enum GenericArg {
 
 /// A complete program (compilation unit)
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct Program {
     pub items: Vec<Item>,
     /// Inner attributes: #![no_std], #![no_main], etc.
     pub inner_attrs: Vec<Annotation>,
 }
 
+impl Program {
+    /// Libraries named by `extern ... link = "lib"` blocks, including those in inline modules
+    pub fn link_libraries(&self) -> Vec<String> {
+        fn collect(items: &[Item], libs: &mut Vec<String>) {
+            for item in items {
+                match item {
+                    Item::Extern(ExternBlock { link: Some(lib), .. }) if !libs.contains(lib) => libs.push(lib.clone()),
+                    Item::Module(ModuleDef { items: Some(items), .. }) => collect(items, libs),
+                    _ => {}
+                }
+            }
+        }
+        let mut libs = Vec::new();
+        collect(&self.items, &mut libs);
+        libs
+    }
+}
+
 /// Top-level items
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub enum Item {
     Function(Function),
     Struct(StructDef),
@@ -74,6 +99,7 @@ pub enum Item {
 
 /// Function definition
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct Function {
     pub name: Ident,
     pub params: Vec<Param>,
@@ -86,19 +112,59 @@ pub struct Function {
     pub effects: EffectSet,
     pub is_pub: bool,
     pub type_params: Vec<Ident>,
+    /// Generic parameters including const generics: `<T, const N: usize>`
+    pub generic_params: Vec<GenericParam>,
+    /// Lines of the `///` comment before the function
+    pub doc: Vec<String>,
+}
+
+impl Function {
+    /// Whether the function carries `#[name]` or `@name`
+    pub fn has_annotation(&self, name: &str) -> bool {
+        self.annotations.iter().any(|a| a.name.name == name)
+    }
+
+    /// `#[extern_c]`: exported with C linkage under its source name
+    pub fn is_extern_c(&self) -> bool {
+        self.has_annotation("extern_c")
+    }
+
+    /// `#[no_mangle]`: emitted under its exact source name
+    pub fn is_no_mangle(&self) -> bool {
+        self.has_annotation("no_mangle")
+    }
+
+    /// Whether the symbol is the source name rather than a mangled path
+    pub fn is_unmangled(&self) -> bool {
+        self.is_extern_c() || self.is_no_mangle()
+    }
+
+    /// Names of the `const` generic parameters, in declaration order
+    pub fn const_params(&self) -> Vec<&str> {
+        self.generic_params.iter()
+            .filter_map(|p| match p {
+                GenericParam::Const { name, .. } => Some(name.name.as_str()),
+                GenericParam::Type { .. } => None,
+            })
+            .collect()
+    }
 }
 
 /// Function parameter
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct Param {
     pub name: Ident,
     pub ownership: Ownership,
     pub ty: Type,
+    /// Destructuring pattern; `name` is then a hidden binding of the whole argument
+    pub pattern: Option<Pattern>,
     pub span: Span,
 }
 
 /// Ownership modifier
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub enum Ownership {
     /// Owned value (default, move semantics)
     Own,
@@ -120,6 +186,7 @@ impl Default for Ownership {
 
 /// Annotation (e.g., @inline, @test, @static)
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct Annotation {
     pub name: Ident,
     pub args: Vec<Expr>,
@@ -128,6 +195,7 @@ pub struct Annotation {
 
 /// Contract clause (requires/ensures/invariant)
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct Contract {
     pub kind: ContractKind,
     pub condition: Expr,
@@ -135,6 +203,7 @@ pub struct Contract {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub enum ContractKind {
     /// Precondition: caller must satisfy
     Requires,
@@ -145,13 +214,45 @@ pub enum ContractKind {
 }
 
 /// Effect set for a function
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct EffectSet {
     pub is_pure: bool,
     pub effects: Vec<Effect>,
 }
 
+impl EffectSet {
+    /// Whether code allowed these effects may run code with `other`'s
+    pub fn allows(&self, other: &EffectSet) -> bool {
+        other.effects.iter().all(|e| self.effects.contains(e))
+    }
+}
+
+impl std::fmt::Display for EffectSet {
+    /// `pure` or `effect[io, alloc]` as written in source; nothing when
+    /// there is no annotation
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_pure {
+            return f.write_str("pure");
+        }
+        if self.effects.is_empty() {
+            return Ok(());
+        }
+        let names: Vec<&str> = self.effects.iter()
+            .map(|e| match e {
+                Effect::Read => "read",
+                Effect::Write => "write",
+                Effect::IO => "io",
+                Effect::Alloc => "alloc",
+                Effect::Panic => "panic",
+            })
+            .collect();
+        write!(f, "effect[{}]", names.join(", "))
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub enum Effect {
     Read,
     Write,
@@ -163,6 +264,7 @@ pub enum Effect {
 
 /// Struct definition
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct StructDef {
     pub name: Ident,
     pub fields: Vec<Field>,
@@ -175,47 +277,111 @@ pub struct StructDef {
     pub generic_params: Vec<GenericParam>,
     /// Legacy type_params for backward compatibility
     pub type_params: Vec<Ident>,
+    /// Lines of the `///` comment before the struct
+    pub doc: Vec<String>,
+}
+
+/// Field layout requested with `#[field_order(..)]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
+pub enum FieldOrder {
+    /// `#[field_order(declaration)]`: as written, without a padding warning
+    Declaration,
+    /// `#[field_order(optimal)]`: most aligned and largest fields first
+    Optimal,
+}
+
+impl StructDef {
+    /// Order given by `#[field_order(..)]`, if the struct has one that the
+    /// compiler knows
+    pub fn field_order(&self) -> Option<FieldOrder> {
+        let ann = self.annotations.iter().find(|a| a.name.name == "field_order")?;
+        match ann.args.as_slice() {
+            [Expr::Ident(id)] if id.name == "declaration" => Some(FieldOrder::Declaration),
+            [Expr::Ident(id)] if id.name == "optimal" => Some(FieldOrder::Optimal),
+            _ => None,
+        }
+    }
+
+    /// `#[derive(Eq)]`: `==` compares the fields
+    pub fn derives_eq(&self) -> bool {
+        derives(&self.annotations, "Eq")
+    }
+}
+
+/// Whether `annotations` hold a `#[derive(..)]` naming `name`
+fn derives(annotations: &[Annotation], name: &str) -> bool {
+    annotations.iter()
+        .filter(|a| a.name.name == "derive")
+        .flat_map(|a| &a.args)
+        .any(|arg| matches!(arg, Expr::Ident(id) if id.name == name))
 }
 
 /// Struct field
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct Field {
     pub name: Ident,
     pub ty: Type,
     pub span: Span,
+    /// Readable from outside the defining module
+    pub is_pub: bool,
+    /// Width from `#[bits(N)]`: the field is packed into a shared integer
+    pub bits: Option<usize>,
 }
 
 /// Enum definition
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct EnumDef {
     pub name: Ident,
     pub variants: Vec<Variant>,
     pub span: Span,
+    /// Integer type of the discriminant from `#[repr(i32)]`
+    pub repr: Option<Ident>,
+    /// Attributes written before the enum
+    pub annotations: Vec<Annotation>,
     /// Generic parameters including const generics
     pub generic_params: Vec<GenericParam>,
     /// Legacy type_params for backward compatibility
     pub type_params: Vec<Ident>,
+    /// Lines of the `///` comment before the enum
+    pub doc: Vec<String>,
+}
+
+impl EnumDef {
+    /// `#[derive(Eq)]`: `==` compares the variants and their payloads
+    pub fn derives_eq(&self) -> bool {
+        derives(&self.annotations, "Eq")
+    }
 }
 
 /// Enum variant
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct Variant {
     pub name: Ident,
     pub fields: Vec<Type>,
+    /// Explicit discriminant: `EPERM = 1`
+    pub discriminant: Option<Expr>,
     pub span: Span,
 }
 
 /// Impl block
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct ImplBlock {
     pub target: Ident,
     pub interface: Option<Ident>,
     pub methods: Vec<Function>,
+    /// Associated type bindings: `type Item = i64`
+    pub associated_types: Vec<AssociatedTypeBinding>,
     pub span: Span,
 }
 
 /// Trait definition (interface with optional default implementations)
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct InterfaceDef {
     pub name: Ident,
     /// Type parameters (generics)
@@ -234,6 +400,7 @@ pub struct InterfaceDef {
 
 /// Associated type in a trait
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct AssociatedType {
     pub name: Ident,
     /// Optional default type
@@ -243,8 +410,18 @@ pub struct AssociatedType {
     pub span: Span,
 }
 
+/// Concrete type chosen for an associated type in an impl block
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
+pub struct AssociatedTypeBinding {
+    pub name: Ident,
+    pub ty: Type,
+    pub span: Span,
+}
+
 /// Function signature (for traits/interfaces)
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunctionSig {
     pub name: Ident,
     pub params: Vec<Param>,
@@ -258,6 +435,7 @@ pub struct FunctionSig {
 
 /// Constant definition
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConstDef {
     pub name: Ident,
     pub ty: Option<Type>,
@@ -267,6 +445,7 @@ pub struct ConstDef {
 
 /// Type alias definition (type Foo = Bar)
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct TypeAliasDef {
     pub name: Ident,
     /// Type parameters for generic aliases
@@ -281,6 +460,7 @@ pub struct TypeAliasDef {
 
 /// Macro definition
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct MacroDef {
     pub name: Ident,
     pub kind: MacroKind,
@@ -290,6 +470,7 @@ pub struct MacroDef {
 
 /// Kind of macro
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub enum MacroKind {
     /// Declarative macro (pattern matching)
     Declarative {
@@ -304,6 +485,7 @@ pub enum MacroKind {
 
 /// A single macro rule (pattern => template)
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct MacroRule {
     /// Pattern to match
     pub pattern: MacroPattern,
@@ -314,18 +496,21 @@ pub struct MacroRule {
 
 /// Macro pattern (simplified)
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct MacroPattern {
     pub tokens: Vec<MacroToken>,
 }
 
 /// Macro template (simplified)
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct MacroTemplate {
     pub tokens: Vec<MacroToken>,
 }
 
 /// Token in a macro pattern/template
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub enum MacroToken {
     /// Literal token
     Literal(String),
@@ -339,6 +524,7 @@ pub enum MacroToken {
 
 /// Module definition
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct ModuleDef {
     pub name: Ident,
     /// Inline module items (if Some) or external file (if None)
@@ -349,6 +535,7 @@ pub struct ModuleDef {
 
 /// Use/import declaration
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct UseDecl {
     /// Path to import (e.g., std::io::File)
     pub path: Vec<Ident>,
@@ -360,6 +547,7 @@ pub struct UseDecl {
 
 /// Kind of use declaration
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub enum UseKind {
     /// Import single item (use foo::bar)
     Simple,
@@ -375,9 +563,12 @@ pub enum UseKind {
 
 /// Extern block for FFI declarations
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct ExternBlock {
     /// ABI specification (e.g., "C", "stdcall")
     pub abi: Option<String>,
+    /// Library providing the declarations (`link = "m"` links with `-lm`)
+    pub link: Option<String>,
     /// Foreign function declarations
     pub items: Vec<ForeignItem>,
     pub span: Span,
@@ -385,6 +576,7 @@ pub struct ExternBlock {
 
 /// Foreign item (function or static) declaration
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub enum ForeignItem {
     /// Foreign function with optional contracts
     Fn {
@@ -408,17 +600,21 @@ pub enum ForeignItem {
 
 /// Static variable definition (global)
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct StaticDef {
     pub name: Ident,
     pub ty: Type,
     pub value: Option<Expr>,
     pub is_mut: bool,
     pub is_pub: bool,
+    /// `#[no_mangle]`: linked under its exact source name
+    pub no_mangle: bool,
     pub span: Span,
 }
 
 /// Union definition (overlapping memory layout)
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct UnionDef {
     pub name: Ident,
     pub fields: Vec<Field>,
@@ -430,6 +626,7 @@ pub struct UnionDef {
 
 /// Memory representation attribute
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub enum Repr {
     /// C-compatible layout
     C,
@@ -441,6 +638,7 @@ pub enum Repr {
 
 /// Code block
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct Block {
     pub stmts: Vec<Stmt>,
     pub span: Span,
@@ -448,13 +646,16 @@ pub struct Block {
 
 /// Statement
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub enum Stmt {
-    /// let [mut] name [: type] = expr
+    /// let [mut] name [: type] = expr, or let pattern: type = expr
     Let {
         name: Ident,
         mutable: bool,
         ty: Option<Type>,
         value: Option<Expr>,
+        /// Destructuring pattern; `name` is then a hidden binding of the whole value
+        pattern: Option<Box<Pattern>>,
         span: Span,
     },
     /// Expression statement
@@ -464,8 +665,8 @@ pub enum Stmt {
         value: Option<Expr>,
         span: Span,
     },
-    /// break
-    Break { span: Span },
+    /// break, or `break value` leaving a `loop` with a value
+    Break { break_value: Option<Expr>, span: Span },
     /// continue
     Continue { span: Span },
     /// Empty statement (;)
@@ -474,14 +675,16 @@ pub enum Stmt {
 
 /// Expression
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expr {
     /// Literal value
     Literal(Literal),
     /// Identifier
     Ident(Ident),
-    /// Path (e.g. Option::Some)
+    /// Path (e.g. Option::Some), optionally with turbofish arguments (`fill::<4>`)
     Path {
         segments: Vec<Ident>,
+        generic_args: Vec<GenericArg>,
         span: Span,
     },
     /// Binary operation
@@ -524,6 +727,15 @@ pub enum Expr {
     },
     /// Block expression
     Block(Block),
+    /// `let x = e` in expression position: binds `x` in the enclosing scope
+    /// and evaluates to the bound value
+    Let {
+        name: Ident,
+        mutable: bool,
+        ty: Option<Type>,
+        value: Box<Expr>,
+        span: Span,
+    },
     /// If expression
     If {
         cond: Box<Expr>,
@@ -566,6 +778,19 @@ pub enum Expr {
         elements: Vec<Expr>,
         span: Span,
     },
+    /// `[do { .. }; N]`: an array whose elements `init` computes in a loop
+    ArrayFrom {
+        init: Box<Expr>,
+        len: usize,
+        span: Span,
+    },
+    /// `do { stmts; yield value }`, one element of `[do { .. }; N]`; `_` is
+    /// the element's index, and a final expression stands for `yield`
+    DoBlock {
+        stmts: Vec<Stmt>,
+        accumulator: Box<Expr>,
+        span: Span,
+    },
     /// Tuple literal
     Tuple {
         elements: Vec<Expr>,
@@ -621,10 +846,75 @@ pub enum Expr {
         body: Box<Expr>,
         span: Span,
     },
+    /// Compile-time type size (size_of::<T>())
+    SizeOf {
+        ty: Type,
+        span: Span,
+    },
+    /// Compile-time type alignment (align_of::<T>())
+    AlignOf {
+        ty: Type,
+        span: Span,
+    },
+    /// Compile-time field offset (offset_of(Struct, field))
+    OffsetOf {
+        ty: Type,
+        field: Ident,
+        span: Span,
+    },
+    /// Typed heap allocation: `new::<T>()`, `new_array::<T>(n)` or `new S { .. }`
+    New {
+        ty: Type,
+        /// Element count of `new_array`
+        count: Option<Box<Expr>>,
+        /// Struct literal initializing the allocation
+        init: Option<Box<Expr>>,
+        span: Span,
+    },
+}
+
+/// Location an assignment writes to
+#[derive(Debug, Clone, Copy)]
+pub enum Place<'a> {
+    /// A variable or static
+    Variable(&'a Ident),
+    /// `base.field`
+    Field { base: &'a Expr, field: &'a Ident },
+    /// `base[index]`
+    Index { base: &'a Expr, index: &'a Expr },
+    /// `*ptr`
+    Deref(&'a Expr),
+}
+
+impl Expr {
+    /// The location this expression names, if it can be assigned to
+    pub fn place(&self) -> Option<Place<'_>> {
+        match self {
+            Expr::Ident(ident) => Some(Place::Variable(ident)),
+            Expr::Field { expr, field, .. } => Some(Place::Field { base: expr, field }),
+            Expr::Index { expr, index, .. } => Some(Place::Index { base: expr, index }),
+            Expr::Deref { expr, .. } => Some(Place::Deref(expr)),
+            _ => None,
+        }
+    }
+}
+
+impl Drop for Expr {
+    fn drop(&mut self) {
+        // Long chains like `a + b + c + ..` nest to the left; unlink them in
+        // a loop, as dropping them recursively could exhaust the stack
+        let unlink = |left: &mut Box<Expr>| std::mem::replace(&mut **left, Expr::Literal(Literal::Bool(false, Span::dummy())));
+        let Expr::Binary { left, .. } = self else { return };
+        let mut next = unlink(left);
+        while let Expr::Binary { left, .. } = &mut next {
+            next = unlink(left);
+        }
+    }
 }
 
 /// Match arm
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct MatchArm {
     pub pattern: Pattern,
     pub guard: Option<Box<Expr>>,
@@ -634,6 +924,7 @@ pub struct MatchArm {
 
 /// Pattern for matching
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub enum Pattern {
     /// Wildcard (_)
     Wildcard { span: Span },
@@ -641,10 +932,12 @@ pub enum Pattern {
     Binding { name: Ident, mutable: bool, span: Span },
     /// Literal
     Literal(Literal),
-    /// Struct pattern
+    /// Struct pattern (`Point { x, y: 0, .. }`)
     Struct {
         name: Ident,
         fields: Vec<(Ident, Pattern)>,
+        /// Ends in `..`, so unlisted fields are ignored
+        rest: bool,
         span: Span,
     },
     /// Tuple pattern
@@ -656,10 +949,27 @@ pub enum Pattern {
         fields: Vec<Pattern>,
         span: Span,
     },
+    /// Alternatives (`p1 | p2 | p3`); matches if any of them does
+    Or { alternatives: Vec<Pattern>, span: Span },
+}
+
+impl Pattern {
+    pub fn span(&self) -> Span {
+        match self {
+            Pattern::Wildcard { span }
+            | Pattern::Binding { span, .. }
+            | Pattern::Struct { span, .. }
+            | Pattern::Tuple { span, .. }
+            | Pattern::Variant { span, .. }
+            | Pattern::Or { span, .. } => *span,
+            Pattern::Literal(lit) => lit.span(),
+        }
+    }
 }
 
 /// Inline assembly operand
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct AsmOperand {
     pub kind: AsmOperandKind,
     pub options: String, // "reg", "memory", etc.
@@ -668,12 +978,14 @@ pub struct AsmOperand {
 
 /// Closure parameter (optionally typed)
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct ClosureParam {
     pub name: Ident,
     pub ty: Option<Type>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub enum AsmOperandKind {
     Input,  // in(reg) val
     Output, // out(reg) val
@@ -681,8 +993,52 @@ pub enum AsmOperandKind {
     Clobber, // clobber("memory")
 }
 
+/// Registers that may be named in asm operands and clobbers
+const ASM_REGISTERS: &[&str] = &[
+    // x86_64
+    "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp",
+    "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15",
+    "eax", "ebx", "ecx", "edx", "esi", "edi",
+    "xmm0", "xmm1", "xmm2", "xmm3", "xmm4", "xmm5", "xmm6", "xmm7",
+    "xmm8", "xmm9", "xmm10", "xmm11", "xmm12", "xmm13", "xmm14", "xmm15",
+    // aarch64
+    "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10",
+    "x11", "x12", "x13", "x14", "x15", "x16", "x17", "x18", "x19", "x20",
+    "x21", "x22", "x23", "x24", "x25", "x26", "x27", "x28", "x29", "x30",
+    "lr", "sp",
+];
+
+/// Whether a clobber string is one the backends understand
+pub fn is_known_asm_clobber(name: &str) -> bool {
+    matches!(name, "memory" | "cc") || ASM_REGISTERS.contains(&name)
+}
+
+impl AsmOperand {
+    /// Constraint string for this operand's register class, without the
+    /// `=`/`+` output prefix. Register classes map to GCC constraint letters
+    /// (`reg` -> `r`, `mem` -> `m`, `imm` -> `i`, `xmm_reg` -> `x`), explicit
+    /// registers become `{rax}`, and raw GCC constraints such as `"r"` or
+    /// `"a"` pass through. Returns None for an unknown class.
+    pub fn constraint(&self) -> Option<String> {
+        let spec = self.options.trim_start_matches(['=', '+']);
+        let constraint = match spec {
+            "reg" => "r".to_string(),
+            "reg_byte" => "q".to_string(),
+            "xmm_reg" => "x".to_string(),
+            "mem" | "memory" => "m".to_string(),
+            "imm" => "i".to_string(),
+            reg if ASM_REGISTERS.contains(&reg) => format!("{{{}}}", reg),
+            raw if !raw.is_empty() && (raw.chars().all(|c| "rmigabcdSDqQxX&".contains(c))
+                || raw.chars().all(|c| c.is_ascii_digit())) => raw.to_string(),
+            _ => return None,
+        };
+        Some(constraint)
+    }
+}
+
 /// Literal value
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub enum Literal {
     Int(i64, Span),
     Float(f64, Span),
@@ -705,6 +1061,7 @@ impl Literal {
 
 /// Identifier
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ident {
     pub name: String,
     pub span: Span,
@@ -712,6 +1069,7 @@ pub struct Ident {
 
 /// Binary operator
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinOp {
     // Arithmetic
     Add,
@@ -743,8 +1101,43 @@ pub enum BinOp {
     DivAssign,
 }
 
+impl BinOp {
+    /// Whether the operator writes to its left operand
+    pub fn is_assignment(self) -> bool {
+        matches!(self, BinOp::Assign | BinOp::AddAssign | BinOp::SubAssign | BinOp::MulAssign | BinOp::DivAssign)
+    }
+
+    /// The operator a compound assignment applies (`Add` for `+=`)
+    pub fn compound_op(self) -> Option<BinOp> {
+        match self {
+            BinOp::AddAssign => Some(BinOp::Add),
+            BinOp::SubAssign => Some(BinOp::Sub),
+            BinOp::MulAssign => Some(BinOp::Mul),
+            BinOp::DivAssign => Some(BinOp::Div),
+            _ => None,
+        }
+    }
+
+    /// The interface a user type implements to overload the operator, and
+    /// the method it calls: `+` is `Add::add`. Ordering operators compare
+    /// the integer `Ord::cmp` returns against 0.
+    pub fn operator_interface(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            BinOp::Add => Some(("Add", "add")),
+            BinOp::Sub => Some(("Sub", "sub")),
+            BinOp::Mul => Some(("Mul", "mul")),
+            BinOp::Div => Some(("Div", "div")),
+            BinOp::Mod => Some(("Rem", "rem")),
+            BinOp::Eq | BinOp::Ne => Some(("Eq", "eq")),
+            BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => Some(("Ord", "cmp")),
+            _ => None,
+        }
+    }
+}
+
 /// Unary operator
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnOp {
     /// Negation (-)
     Neg,
@@ -756,6 +1149,7 @@ pub enum UnOp {
 
 /// Type representation
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub enum Type {
     /// Named type (i32, String)
     Named(String, Span),
@@ -787,10 +1181,11 @@ pub enum Type {
     Slice(Box<Type>, Span),
     /// Tuple type ((T1, T2, ...))
     Tuple(Vec<Type>, Span),
-    /// Function type (fn(A, B) -> R)
+    /// Function type (fn(A, B) -> R effect[io])
     Function {
         params: Vec<Type>,
         ret: Box<Type>,
+        effects: Box<EffectSet>,
         span: Span,
     },
     /// Never type (!)
@@ -0,0 +1,1080 @@
+//! Pretty-printer turning an AST back into Aether source
+//!
+//! The output uses four-space indentation, one statement per line and only
+//! the parentheses that precedence requires, so it parses back to the same
+//! tree. Comments are not part of the AST; with `with_comments`, the line
+//! comments between items and between statements of the original source are
+//! carried over.
+
+use crate::frontend::ast::*;
+
+/// Prints a `Program` as Aether source
+pub struct Formatter<'a> {
+    out: String,
+    indent: usize,
+    /// Original text, for its comments
+    source: Option<&'a str>,
+    /// Offset in `source` up to which comments have been emitted
+    cursor: usize,
+}
+
+impl Default for Formatter<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Formatter<'a> {
+    pub fn new() -> Self {
+        Self { out: String::new(), indent: 0, source: None, cursor: 0 }
+    }
+
+    /// Keep the comments of `source`, the text `program` was parsed from
+    pub fn with_comments(mut self, source: &'a str) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    pub fn format_program(mut self, program: &Program) -> String {
+        for attr in &program.inner_attrs {
+            self.out.push_str(&format!("#![{}]\n", attr.name.name));
+        }
+        if !program.inner_attrs.is_empty() && !program.items.is_empty() {
+            self.out.push('\n');
+        }
+        self.items(&program.items);
+        self.comments_before(usize::MAX);
+        self.out
+    }
+
+    fn items(&mut self, items: &[Item]) {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                self.out.push('\n');
+            }
+            let span = item_span(item);
+            // Attributes come before the item's span
+            let start = item_attributes(item).iter().map(|a| a.span.start).chain([span.start]).min().unwrap_or(0);
+            self.comments_before(start);
+            self.item(item);
+            self.cursor = self.cursor.max(span.end);
+        }
+    }
+
+    fn item(&mut self, item: &Item) {
+        match item {
+            Item::Function(f) => self.function(f),
+            Item::Struct(s) => {
+                self.attributes(&s.annotations);
+                self.line_start();
+                self.out.push_str(&format!("{}struct {}", pub_prefix(s.is_pub), s.name.name));
+                self.generic_params(&s.generic_params);
+                if !s.invariants.is_empty() {
+                    self.out.push(' ');
+                    self.contracts(&s.invariants);
+                }
+                self.fields(&s.fields, s.span.end);
+            }
+            Item::Enum(e) => {
+                self.attributes(&e.annotations);
+                self.line_start();
+                self.out.push_str(&format!("enum {}", e.name.name));
+                self.generic_params(&e.generic_params);
+                self.out.push_str(" {\n");
+                self.indent += 1;
+                for variant in &e.variants {
+                    self.comments_before(variant.span.start);
+                    self.line_start();
+                    self.out.push_str(&variant.name.name);
+                    if !variant.fields.is_empty() {
+                        let fields: Vec<String> = variant.fields.iter().map(type_to_string).collect();
+                        self.out.push_str(&format!("({})", fields.join(", ")));
+                    }
+                    if let Some(value) = &variant.discriminant {
+                        self.out.push_str(" = ");
+                        self.expr(value);
+                    }
+                    self.out.push_str(",\n");
+                    self.cursor = self.cursor.max(variant.span.end);
+                }
+                self.close_brace(e.span.end);
+            }
+            Item::Impl(imp) => {
+                self.line_start();
+                match &imp.interface {
+                    Some(interface) => self.out.push_str(&format!("impl {} for {} {{\n", interface.name, imp.target.name)),
+                    None => self.out.push_str(&format!("impl {} {{\n", imp.target.name)),
+                }
+                self.indent += 1;
+                for binding in &imp.associated_types {
+                    self.comments_before(binding.span.start);
+                    self.line(&format!("type {} = {}", binding.name.name, type_to_string(&binding.ty)));
+                    self.cursor = self.cursor.max(binding.span.end);
+                }
+                for (i, method) in imp.methods.iter().enumerate() {
+                    if i > 0 || !imp.associated_types.is_empty() {
+                        self.out.push('\n');
+                    }
+                    self.comments_before(method.span.start);
+                    self.function(method);
+                    self.cursor = self.cursor.max(method.span.end);
+                }
+                self.close_brace(imp.span.end);
+            }
+            Item::Interface(def) => self.interface("interface", def),
+            Item::Trait(def) => self.interface("trait", def),
+            Item::Const(c) => {
+                self.line_start();
+                self.out.push_str(&format!("const {}", c.name.name));
+                if let Some(ty) = &c.ty {
+                    self.out.push_str(&format!(": {}", type_to_string(ty)));
+                }
+                self.out.push_str(" = ");
+                self.expr(&c.value);
+                self.out.push('\n');
+            }
+            Item::Macro(m) => {
+                // Macros are never produced by the parser
+                self.line(&format!("// macro {} omitted", m.name.name));
+            }
+            Item::Module(m) => {
+                self.line_start();
+                self.out.push_str(&format!("{}mod {}", pub_prefix(m.is_pub), m.name.name));
+                match &m.items {
+                    Some(items) => {
+                        self.out.push_str(" {\n");
+                        self.indent += 1;
+                        self.items(items);
+                        self.close_brace(m.span.end);
+                    }
+                    None => self.out.push('\n'),
+                }
+            }
+            Item::Use(u) => {
+                self.line_start();
+                self.out.push_str(pub_prefix(u.is_pub));
+                self.out.push_str("use ");
+                self.out.push_str(&use_to_string(u));
+                self.out.push('\n');
+            }
+            Item::Extern(block) => {
+                self.line_start();
+                self.out.push_str("extern ");
+                if let Some(abi) = &block.abi {
+                    self.out.push_str(&format!("{} ", quote(abi)));
+                }
+                if let Some(link) = &block.link {
+                    self.out.push_str(&format!("link = {} ", quote(link)));
+                }
+                self.out.push_str("{\n");
+                self.indent += 1;
+                for foreign in &block.items {
+                    self.foreign_item(foreign);
+                }
+                self.close_brace(block.span.end);
+            }
+            Item::Static(s) => {
+                self.line_start();
+                self.out.push_str(&format!(
+                    "{}static {}{}: {}",
+                    pub_prefix(s.is_pub),
+                    if s.is_mut { "mut " } else { "" },
+                    s.name.name,
+                    type_to_string(&s.ty)
+                ));
+                if let Some(value) = &s.value {
+                    self.out.push_str(" = ");
+                    self.expr(value);
+                }
+                self.out.push('\n');
+            }
+            Item::Union(u) => {
+                if let Some(repr) = u.repr {
+                    let repr = match repr {
+                        Repr::C => "C",
+                        Repr::Packed => "packed",
+                        Repr::Transparent => "transparent",
+                    };
+                    self.line(&format!("#[repr({})]", repr));
+                }
+                self.line_start();
+                self.out.push_str(&format!("{}union {}", pub_prefix(u.is_pub), u.name.name));
+                self.fields(&u.fields, u.span.end);
+            }
+            Item::TypeAlias(alias) => {
+                self.line_start();
+                self.out.push_str(&format!("{}type {}", pub_prefix(alias.is_pub), alias.name.name));
+                if !alias.type_params.is_empty() {
+                    let params: Vec<&str> = alias.type_params.iter().map(|p| p.name.as_str()).collect();
+                    self.out.push_str(&format!("<{}>", params.join(", ")));
+                }
+                self.out.push_str(&format!(" = {}\n", type_to_string(&alias.ty)));
+            }
+        }
+    }
+
+    fn function(&mut self, f: &Function) {
+        self.attributes(&f.annotations);
+        self.line_start();
+        self.signature(f);
+        self.out.push(' ');
+        self.block(&f.body);
+        self.out.push('\n');
+    }
+
+    fn signature(&mut self, f: &Function) {
+        self.out.push_str(&format!("{}fn {}", pub_prefix(f.is_pub), f.name.name));
+        self.generic_params(&f.generic_params);
+        self.out.push_str(&format!("({})", params_to_string(&f.params)));
+        if let Some(ret) = &f.ret_type {
+            self.out.push_str(&format!(" -> {}", type_to_string(ret)));
+        }
+        if !f.contracts.is_empty() {
+            self.out.push(' ');
+            self.contracts(&f.contracts);
+        }
+        self.effects(&f.effects);
+    }
+
+    fn interface(&mut self, keyword: &str, def: &InterfaceDef) {
+        self.line_start();
+        self.out.push_str(&format!("{}{} {}", pub_prefix(def.is_pub), keyword, def.name.name));
+        if !def.type_params.is_empty() {
+            let params: Vec<&str> = def.type_params.iter().map(|p| p.name.as_str()).collect();
+            self.out.push_str(&format!("<{}>", params.join(", ")));
+        }
+        self.out.push_str(" {\n");
+        self.indent += 1;
+        for assoc in &def.associated_types {
+            self.comments_before(assoc.span.start);
+            let mut text = format!("type {}", assoc.name.name);
+            if !assoc.bounds.is_empty() {
+                let bounds: Vec<String> = assoc.bounds.iter().map(type_to_string).collect();
+                text.push_str(&format!(": {}", bounds.join(" + ")));
+            }
+            if let Some(default) = &assoc.default_ty {
+                text.push_str(&format!(" = {}", type_to_string(default)));
+            }
+            self.line(&text);
+            self.cursor = self.cursor.max(assoc.span.end);
+        }
+        for sig in &def.methods {
+            self.comments_before(sig.span.start);
+            let mut text = format!("fn {}({})", sig.name.name, params_to_string(&sig.params));
+            if let Some(ret) = &sig.ret_type {
+                text.push_str(&format!(" -> {}", type_to_string(ret)));
+            }
+            self.line(&text);
+            self.cursor = self.cursor.max(sig.span.end);
+        }
+        for method in &def.default_methods {
+            self.function(method);
+        }
+        self.close_brace(def.span.end);
+    }
+
+    fn foreign_item(&mut self, item: &ForeignItem) {
+        match item {
+            ForeignItem::Fn { name, params, ret_type, annotations, variadic, span } => {
+                self.comments_before(annotations.first().map_or(span.start, |a| a.span.start));
+                for annotation in annotations {
+                    self.line(&format!("@{}", annotation_to_string(annotation)));
+                }
+                let mut params = params_to_string(params);
+                if *variadic {
+                    params.push_str(if params.is_empty() { "..." } else { ", ..." });
+                }
+                let mut text = format!("fn {}({})", name.name, params);
+                if let Some(ret) = ret_type {
+                    text.push_str(&format!(" -> {}", type_to_string(ret)));
+                }
+                text.push(';');
+                self.line(&text);
+                self.cursor = self.cursor.max(span.end);
+            }
+            ForeignItem::Static { name, ty, is_mut, span } => {
+                self.comments_before(span.start);
+                self.line(&format!("static {}{}: {};", if *is_mut { "mut " } else { "" }, name.name, type_to_string(ty)));
+                self.cursor = self.cursor.max(span.end);
+            }
+        }
+    }
+
+    fn fields(&mut self, fields: &[Field], end: usize) {
+        self.out.push_str(" {\n");
+        self.indent += 1;
+        for field in fields {
+            self.comments_before(field.span.start);
+            if let Some(bits) = field.bits {
+                self.line(&format!("#[bits({})]", bits));
+            }
+            self.line(&format!("{}{}: {},", pub_prefix(field.is_pub), field.name.name, type_to_string(&field.ty)));
+            self.cursor = self.cursor.max(field.span.end);
+        }
+        self.close_brace(end);
+    }
+
+    fn attributes(&mut self, annotations: &[Annotation]) {
+        for annotation in annotations {
+            self.line(&format!("#[{}]", annotation_to_string(annotation)));
+        }
+    }
+
+    fn generic_params(&mut self, params: &[GenericParam]) {
+        if params.is_empty() {
+            return;
+        }
+        let params: Vec<String> = params.iter()
+            .map(|p| match p {
+                GenericParam::Type { name, bounds } if bounds.is_empty() => name.name.clone(),
+                GenericParam::Type { name, bounds } => format!("{}: {}", name.name, bounds.iter().map(type_to_string).collect::<Vec<_>>().join(" + ")),
+                GenericParam::Const { name, ty } => format!("const {}: {}", name.name, type_to_string(ty)),
+            })
+            .collect();
+        self.out.push_str(&format!("<{}>", params.join(", ")));
+    }
+
+    fn contracts(&mut self, contracts: &[Contract]) {
+        self.out.push('[');
+        for (i, contract) in contracts.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.out.push_str(match contract.kind {
+                ContractKind::Requires => "requires ",
+                ContractKind::Ensures => "ensures ",
+                ContractKind::Invariant => "invariant ",
+            });
+            self.expr(&contract.condition);
+        }
+        self.out.push(']');
+    }
+
+    fn effects(&mut self, effects: &EffectSet) {
+        if effects.is_pure || !effects.effects.is_empty() {
+            self.out.push_str(&format!(" {}", effects));
+        }
+    }
+
+    // ==================== Statements ====================
+
+    /// `{ ... }`, starting at the current position and ending without a newline
+    fn block(&mut self, block: &Block) {
+        self.out.push_str("{\n");
+        self.indent += 1;
+        for stmt in &block.stmts {
+            self.comments_before(stmt.span().start);
+            self.stmt(stmt);
+            self.cursor = self.cursor.max(stmt.span().end);
+        }
+        // `expr;` at the end leaves the block without a value, so the `;` stays
+        if let [.., Stmt::Expr(_), Stmt::Empty { .. }] = block.stmts.as_slice() {
+            self.out.pop();
+            self.out.push_str(";\n");
+        }
+        self.comments_before(block.span.end.saturating_sub(1));
+        self.indent -= 1;
+        self.line_start();
+        self.out.push('}');
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Let { name, mutable, ty, value, pattern, .. } => {
+                self.line_start();
+                match pattern {
+                    Some(pattern) => self.out.push_str(&format!("let {}", pattern_to_string(pattern))),
+                    None => self.out.push_str(&format!("let {}{}", if *mutable { "mut " } else { "" }, name.name)),
+                }
+                if let Some(ty) = ty {
+                    self.out.push_str(&format!(": {}", type_to_string(ty)));
+                }
+                if let Some(value) = value {
+                    self.out.push_str(" = ");
+                    self.expr(value);
+                }
+                self.out.push('\n');
+            }
+            Stmt::Expr(expr) => {
+                self.line_start();
+                self.expr(expr);
+                self.out.push('\n');
+            }
+            Stmt::Return { value, .. } => {
+                self.line_start();
+                self.out.push_str("return");
+                if let Some(value) = value {
+                    self.out.push(' ');
+                    self.expr(value);
+                }
+                self.out.push('\n');
+            }
+            Stmt::Break { break_value: None, .. } => self.line("break"),
+            Stmt::Break { break_value: Some(value), .. } => {
+                self.line_start();
+                self.out.push_str("break ");
+                self.expr(value);
+                self.out.push('\n');
+            }
+            Stmt::Continue { .. } => self.line("continue"),
+            Stmt::Empty { .. } => {}
+        }
+    }
+
+    // ==================== Expressions ====================
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal(lit) => self.out.push_str(&literal_to_string(lit)),
+            Expr::Ident(ident) => self.out.push_str(&ident.name),
+            Expr::Path { segments, generic_args, .. } => {
+                let names: Vec<&str> = segments.iter().map(|s| s.name.as_str()).collect();
+                self.out.push_str(&names.join("::"));
+                if !generic_args.is_empty() {
+                    self.out.push_str(&format!("::<{}>", generic_args_to_string(generic_args)));
+                }
+            }
+            Expr::Binary { left, op, right, .. } => {
+                let bp = binop_precedence(*op);
+                let assign = bp == ASSIGN_PRECEDENCE;
+                // Left-associative except for assignment
+                self.operand(left, if assign { bp + 1 } else { bp });
+                self.out.push_str(&format!(" {} ", binop_str(*op)));
+                self.operand(right, if assign { bp } else { bp + 1 });
+            }
+            Expr::Unary { op, expr, .. } => {
+                self.out.push_str(match op {
+                    UnOp::Neg => "-",
+                    UnOp::Not => "!",
+                    UnOp::BitNot => "~",
+                });
+                self.operand(expr, PREFIX_PRECEDENCE);
+            }
+            Expr::Call { func, args, .. } => {
+                self.operand(func, POSTFIX_PRECEDENCE);
+                self.args(args);
+            }
+            Expr::Field { expr, field, .. } => {
+                self.operand(expr, POSTFIX_PRECEDENCE);
+                self.out.push('.');
+                self.out.push_str(&field.name);
+            }
+            Expr::MethodCall { expr, method, args, .. } => {
+                self.operand(expr, POSTFIX_PRECEDENCE);
+                self.out.push('.');
+                self.out.push_str(&method.name);
+                self.args(args);
+            }
+            Expr::Index { expr, index, .. } => {
+                self.operand(expr, POSTFIX_PRECEDENCE);
+                self.out.push('[');
+                self.expr(index);
+                self.out.push(']');
+            }
+            Expr::Block(block) => self.block(block),
+            Expr::If { cond, then_block, else_block, .. } => {
+                self.out.push_str("if ");
+                self.expr(cond);
+                self.out.push(' ');
+                self.block(then_block);
+                if let Some(else_block) = else_block {
+                    self.out.push_str(" else ");
+                    match else_block.stmts.as_slice() {
+                        [Stmt::Expr(nested @ Expr::If { .. })] => self.expr(nested),
+                        _ => self.block(else_block),
+                    }
+                }
+            }
+            Expr::Match { expr, arms, span } => {
+                self.out.push_str("match ");
+                self.expr(expr);
+                self.out.push_str(" {\n");
+                self.indent += 1;
+                for arm in arms {
+                    self.comments_before(arm.span.start);
+                    self.line_start();
+                    self.out.push_str(&pattern_to_string(&arm.pattern));
+                    if let Some(guard) = &arm.guard {
+                        self.out.push_str(" if ");
+                        self.expr(guard);
+                    }
+                    self.out.push_str(" => ");
+                    self.expr(&arm.body);
+                    self.out.push_str(",\n");
+                    self.cursor = self.cursor.max(arm.span.end);
+                }
+                self.comments_before(span.end.saturating_sub(1));
+                self.indent -= 1;
+                self.line_start();
+                self.out.push('}');
+            }
+            Expr::Loop { body, .. } => {
+                self.out.push_str("loop ");
+                self.block(body);
+            }
+            Expr::While { cond, body, .. } => {
+                self.out.push_str("while ");
+                self.expr(cond);
+                self.out.push(' ');
+                self.block(body);
+            }
+            Expr::For { var, iter, body, .. } => {
+                self.out.push_str(&format!("for {} in ", var.name));
+                self.expr(iter);
+                self.out.push(' ');
+                self.block(body);
+            }
+            Expr::StructLit { name, fields, .. } => {
+                self.out.push_str(&name.name);
+                self.struct_fields(fields);
+            }
+            Expr::Array { elements, .. } => {
+                self.out.push('[');
+                self.comma_separated(elements);
+                self.out.push(']');
+            }
+            Expr::ArrayFrom { init, len, .. } => {
+                self.out.push('[');
+                self.expr(init);
+                self.out.push_str(&format!("; {}]", len));
+            }
+            Expr::DoBlock { stmts, accumulator, .. } => {
+                self.out.push_str("do {\n");
+                self.indent += 1;
+                for stmt in stmts {
+                    self.comments_before(stmt.span().start);
+                    self.stmt(stmt);
+                    self.cursor = self.cursor.max(stmt.span().end);
+                }
+                self.comments_before(accumulator.span().start);
+                self.line_start();
+                self.out.push_str("yield ");
+                self.expr(accumulator);
+                self.out.push('\n');
+                self.indent -= 1;
+                self.line_start();
+                self.out.push('}');
+            }
+            Expr::Tuple { elements, .. } => {
+                self.out.push('(');
+                self.comma_separated(elements);
+                if elements.len() == 1 {
+                    self.out.push(',');
+                }
+                self.out.push(')');
+            }
+            Expr::Ref { mutable, expr, .. } => {
+                self.out.push_str(if *mutable { "&mut " } else { "&" });
+                self.operand(expr, PREFIX_PRECEDENCE);
+            }
+            Expr::Deref { expr, .. } => {
+                self.out.push('*');
+                self.operand(expr, PREFIX_PRECEDENCE);
+            }
+            Expr::Cast { expr, ty, .. } => {
+                self.operand(expr, CAST_PRECEDENCE);
+                self.out.push_str(&format!(" as {}", type_to_string(ty)));
+            }
+            Expr::Range { start, end, .. } => {
+                if let Some(start) = start {
+                    self.operand(start, CAST_PRECEDENCE);
+                }
+                self.out.push_str("..");
+                if let Some(end) = end {
+                    self.operand(end, CAST_PRECEDENCE);
+                }
+            }
+            Expr::Unsafe { body, reason, verifier, .. } => {
+                self.out.push_str("unsafe");
+                let mut metadata = Vec::new();
+                if let Some(reason) = reason {
+                    metadata.push(format!("reason = {}", quote(reason)));
+                }
+                if let Some(verifier) = verifier {
+                    metadata.push(format!("verifier = {}", verifier.name));
+                }
+                if !metadata.is_empty() {
+                    self.out.push_str(&format!("({})", metadata.join(", ")));
+                }
+                self.out.push(' ');
+                self.block(body);
+            }
+            Expr::Asm { template, operands, .. } => {
+                self.out.push_str("asm!(");
+                self.out.push_str(&quote(template));
+                for operand in operands {
+                    let kind = match operand.kind {
+                        AsmOperandKind::Input => "in",
+                        AsmOperandKind::Output => "out",
+                        AsmOperandKind::InOut => "inout",
+                        AsmOperandKind::Clobber => "clobber",
+                    };
+                    self.out.push_str(&format!(", {}({})", kind, quote(&operand.options)));
+                    if let Some(expr) = &operand.expr {
+                        self.out.push(' ');
+                        self.expr(expr);
+                    }
+                }
+                self.out.push(')');
+            }
+            Expr::Try { expr, .. } => {
+                self.operand(expr, POSTFIX_PRECEDENCE);
+                self.out.push('?');
+            }
+            Expr::Let { name, mutable, ty, value, .. } => {
+                self.out.push_str(&format!("let {}{}", if *mutable { "mut " } else { "" }, name.name));
+                if let Some(ty) = ty {
+                    self.out.push_str(&format!(": {}", type_to_string(ty)));
+                }
+                self.out.push_str(" = ");
+                self.expr(value);
+            }
+            Expr::Closure { params, ret_type, body, .. } => {
+                let params: Vec<String> = params.iter()
+                    .map(|p| match &p.ty {
+                        Some(ty) => format!("{}: {}", p.name.name, type_to_string(ty)),
+                        None => p.name.name.clone(),
+                    })
+                    .collect();
+                self.out.push_str(&format!("|{}| ", params.join(", ")));
+                if let Some(ret) = ret_type {
+                    self.out.push_str(&format!("-> {} ", type_to_string(ret)));
+                }
+                self.expr(body);
+            }
+            Expr::SizeOf { ty, .. } => self.out.push_str(&format!("size_of::<{}>()", type_to_string(ty))),
+            Expr::AlignOf { ty, .. } => self.out.push_str(&format!("align_of::<{}>()", type_to_string(ty))),
+            Expr::OffsetOf { ty, field, .. } => {
+                self.out.push_str(&format!("offset_of({}, {})", type_to_string(ty), field.name));
+            }
+            Expr::New { ty, count, init, .. } => match (count, init) {
+                (Some(count), _) => {
+                    self.out.push_str(&format!("new_array::<{}>(", type_to_string(ty)));
+                    self.expr(count);
+                    self.out.push(')');
+                }
+                (None, Some(init)) => {
+                    self.out.push_str("new ");
+                    self.expr(init);
+                }
+                (None, None) => self.out.push_str(&format!("new::<{}>()", type_to_string(ty))),
+            },
+        }
+    }
+
+    /// `expr`, parenthesized if it binds more loosely than `min_precedence`
+    fn operand(&mut self, expr: &Expr, min_precedence: u8) {
+        if expr_precedence(expr) < min_precedence {
+            self.out.push('(');
+            self.expr(expr);
+            self.out.push(')');
+        } else {
+            self.expr(expr);
+        }
+    }
+
+    fn args(&mut self, args: &[Expr]) {
+        self.out.push('(');
+        self.comma_separated(args);
+        self.out.push(')');
+    }
+
+    fn comma_separated(&mut self, exprs: &[Expr]) {
+        for (i, expr) in exprs.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(expr);
+        }
+    }
+
+    fn struct_fields(&mut self, fields: &[(Ident, Expr)]) {
+        self.out.push_str(" { ");
+        for (i, (name, value)) in fields.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.out.push_str(&format!("{}: ", name.name));
+            self.expr(value);
+        }
+        self.out.push_str(" }");
+    }
+
+    // ==================== Output helpers ====================
+
+    /// Emit the comments of the original source between the cursor and `offset`
+    fn comments_before(&mut self, offset: usize) {
+        let Some(source) = self.source else { return };
+        let offset = offset.min(source.len());
+        if offset <= self.cursor {
+            return;
+        }
+        let gap = &source[self.cursor..offset];
+        self.cursor = offset;
+        // A blank line between a comment and what follows is kept
+        let mut blank = false;
+        let mut emitted = false;
+        // The last segment is the indentation leading up to `offset`
+        let lines = gap.rsplit_once('\n').map_or("", |(lines, _)| lines);
+        for text in lines.split('\n').map(str::trim) {
+            if text.starts_with("//") {
+                if blank && emitted {
+                    self.out.push('\n');
+                }
+                self.line(text);
+                emitted = true;
+                blank = false;
+            } else if text.is_empty() {
+                blank = true;
+            }
+        }
+        if blank && emitted {
+            self.out.push('\n');
+        }
+    }
+
+    fn close_brace(&mut self, end: usize) {
+        self.comments_before(end.saturating_sub(1));
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    fn line(&mut self, text: &str) {
+        self.line_start();
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn line_start(&mut self) {
+        for _ in 0..self.indent {
+            self.out.push_str("    ");
+        }
+    }
+}
+
+const ASSIGN_PRECEDENCE: u8 = 1;
+const CAST_PRECEDENCE: u8 = 12;
+const PREFIX_PRECEDENCE: u8 = 13;
+const POSTFIX_PRECEDENCE: u8 = 14;
+
+/// Binding power of `op`, as in `TokenKind::binary_precedence`
+fn binop_precedence(op: BinOp) -> u8 {
+    match op {
+        BinOp::Assign | BinOp::AddAssign | BinOp::SubAssign | BinOp::MulAssign | BinOp::DivAssign => ASSIGN_PRECEDENCE,
+        BinOp::Or => 2,
+        BinOp::And => 3,
+        BinOp::BitOr => 4,
+        BinOp::BitXor => 5,
+        BinOp::BitAnd => 6,
+        BinOp::Eq | BinOp::Ne => 7,
+        BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => 8,
+        BinOp::Shl | BinOp::Shr => 9,
+        BinOp::Add | BinOp::Sub => 10,
+        BinOp::Mul | BinOp::Div | BinOp::Mod => 11,
+    }
+}
+
+fn expr_precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Binary { op, .. } => binop_precedence(*op),
+        Expr::Cast { .. } => CAST_PRECEDENCE,
+        Expr::Unary { .. } | Expr::Ref { .. } | Expr::Deref { .. } => PREFIX_PRECEDENCE,
+        Expr::Range { .. } | Expr::Closure { .. } | Expr::Let { .. } => 0,
+        _ => POSTFIX_PRECEDENCE,
+    }
+}
+
+pub fn binop_str(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "+",
+        BinOp::Sub => "-",
+        BinOp::Mul => "*",
+        BinOp::Div => "/",
+        BinOp::Mod => "%",
+        BinOp::Eq => "==",
+        BinOp::Ne => "!=",
+        BinOp::Lt => "<",
+        BinOp::Le => "<=",
+        BinOp::Gt => ">",
+        BinOp::Ge => ">=",
+        BinOp::And => "&&",
+        BinOp::Or => "||",
+        BinOp::BitAnd => "&",
+        BinOp::BitOr => "|",
+        BinOp::BitXor => "^",
+        BinOp::Shl => "<<",
+        BinOp::Shr => ">>",
+        BinOp::Assign => "=",
+        BinOp::AddAssign => "+=",
+        BinOp::SubAssign => "-=",
+        BinOp::MulAssign => "*=",
+        BinOp::DivAssign => "/=",
+    }
+}
+
+/// A function's header as written: visibility, generics, parameters,
+/// return type, contracts and effects, without the body
+pub fn function_signature(f: &Function) -> String {
+    let mut formatter = Formatter::new();
+    formatter.signature(f);
+    formatter.out
+}
+
+pub fn type_to_string(ty: &Type) -> String {
+    match ty {
+        Type::Named(name, _) => name.clone(),
+        Type::Generic(name, args, _) => {
+            let args: Vec<String> = args.iter().map(type_to_string).collect();
+            format!("{}<{}>", name, args.join(", "))
+        }
+        Type::GenericWithArgs { name, args, .. } => format!("{}<{}>", name, generic_args_to_string(args)),
+        Type::Pointer(inner, _) => format!("*{}", type_to_string(inner)),
+        Type::Ref { mutable, lifetime, inner, .. } => {
+            let lifetime = lifetime.as_ref().map(|l| format!("'{} ", l.trim_start_matches('\''))).unwrap_or_default();
+            format!("&{}{}{}", lifetime, if *mutable { "mut " } else { "" }, type_to_string(inner))
+        }
+        Type::Array { elem, size, .. } => format!("[{}; {}]", type_to_string(elem), size),
+        Type::Slice(elem, _) => format!("[{}]", type_to_string(elem)),
+        Type::Tuple(elems, _) => {
+            let elems: Vec<String> = elems.iter().map(type_to_string).collect();
+            if elems.len() == 1 {
+                format!("({},)", elems[0])
+            } else {
+                format!("({})", elems.join(", "))
+            }
+        }
+        Type::Function { params, ret, effects, .. } => {
+            let params: Vec<String> = params.iter().map(type_to_string).collect();
+            let ty = format!("fn({}) -> {}", params.join(", "), type_to_string(ret));
+            if effects.is_pure || !effects.effects.is_empty() {
+                format!("{} {}", ty, effects)
+            } else {
+                ty
+            }
+        }
+        Type::Never(_) => "!".to_string(),
+        Type::Unit(_) => "()".to_string(),
+        Type::Infer(_) => "_".to_string(),
+        Type::Owned { inner, ownership, .. } => match ownership {
+            Ownership::Shared => format!("shared {}", type_to_string(inner)),
+            _ => format!("own {}", type_to_string(inner)),
+        },
+        Type::Volatile(inner, _) => format!("*volatile {}", type_to_string(inner)),
+    }
+}
+
+pub fn expr_to_string(expr: &Expr) -> String {
+    let mut formatter = Formatter::new();
+    formatter.expr(expr);
+    formatter.out
+}
+
+fn generic_args_to_string(args: &[GenericArg]) -> String {
+    let args: Vec<String> = args.iter()
+        .map(|arg| match arg {
+            GenericArg::Type(ty) => type_to_string(ty),
+            GenericArg::Const(Expr::Literal(lit)) => literal_to_string(lit),
+            GenericArg::Const(expr) => {
+                let mut formatter = Formatter::new();
+                formatter.expr(expr);
+                format!("{{{}}}", formatter.out)
+            }
+        })
+        .collect();
+    args.join(", ")
+}
+
+pub fn params_to_string(params: &[Param]) -> String {
+    let params: Vec<String> = params.iter()
+        .map(|p| {
+            let ownership = match p.ownership {
+                Ownership::Ref => "ref ",
+                Ownership::Mut => "mut ",
+                Ownership::Own | Ownership::Shared => "",
+            };
+            let name = p.pattern.as_ref().map_or_else(|| p.name.name.clone(), pattern_to_string);
+            format!("{}: {}{}", name, ownership, type_to_string(&p.ty))
+        })
+        .collect();
+    params.join(", ")
+}
+
+fn annotation_to_string(annotation: &Annotation) -> String {
+    if annotation.args.is_empty() {
+        return annotation.name.name.clone();
+    }
+    let mut formatter = Formatter::new();
+    formatter.comma_separated(&annotation.args);
+    format!("{}({})", annotation.name.name, formatter.out)
+}
+
+fn use_to_string(decl: &UseDecl) -> String {
+    let path: Vec<&str> = decl.path.iter().map(|p| p.name.as_str()).collect();
+    let path = path.join("::");
+    match &decl.kind {
+        UseKind::Simple => path,
+        UseKind::Alias(alias) => format!("{} as {}", path, alias.name),
+        UseKind::Glob => format!("{}::*", path),
+        UseKind::Group(decls) => {
+            let names: Vec<String> = decls.iter().map(use_to_string).collect();
+            format!("{}::{{{}}}", path, names.join(", "))
+        }
+    }
+}
+
+pub fn pattern_to_string(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Wildcard { .. } => "_".to_string(),
+        Pattern::Binding { name, mutable, .. } => format!("{}{}", if *mutable { "mut " } else { "" }, name.name),
+        Pattern::Literal(lit) => literal_to_string(lit),
+        Pattern::Struct { name, fields, rest, .. } => {
+            let mut fields: Vec<String> = fields.iter()
+                .map(|(field, p)| match p {
+                    // Shorthand
+                    Pattern::Binding { name, mutable: false, .. } if name.name == field.name => field.name.clone(),
+                    _ => format!("{}: {}", field.name, pattern_to_string(p)),
+                })
+                .collect();
+            if *rest {
+                fields.push("..".to_string());
+            }
+            format!("{} {{ {} }}", name.name, fields.join(", "))
+        }
+        Pattern::Tuple { elements, .. } => {
+            let elements: Vec<String> = elements.iter().map(pattern_to_string).collect();
+            if elements.len() == 1 {
+                return format!("({},)", elements[0]);
+            }
+            format!("({})", elements.join(", "))
+        }
+        Pattern::Variant { enum_name, variant, fields, .. } => {
+            let mut text = match enum_name {
+                Some(enum_name) => format!("{}::{}", enum_name.name, variant.name),
+                None => variant.name.clone(),
+            };
+            if !fields.is_empty() {
+                let fields: Vec<String> = fields.iter().map(pattern_to_string).collect();
+                text.push_str(&format!("({})", fields.join(", ")));
+            }
+            text
+        }
+        Pattern::Or { alternatives, .. } => {
+            let alternatives: Vec<String> = alternatives.iter().map(pattern_to_string).collect();
+            alternatives.join(" | ")
+        }
+    }
+}
+
+fn literal_to_string(lit: &Literal) -> String {
+    match lit {
+        Literal::Int(n, _) => n.to_string(),
+        Literal::Float(f, _) => format!("{:?}", f),
+        Literal::String(s, _) => quote(s),
+        Literal::Char(c, _) => match c {
+            '\'' => "'\\''".to_string(),
+            _ => format!("'{}'", escape(&c.to_string())),
+        },
+        Literal::Bool(b, _) => b.to_string(),
+    }
+}
+
+/// String literal for `text`, with the escapes the lexer understands
+fn quote(text: &str) -> String {
+    format!("\"{}\"", escape(text))
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\0' => escaped.push_str("\\0"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn pub_prefix(is_pub: bool) -> &'static str {
+    if is_pub { "pub " } else { "" }
+}
+
+fn item_span(item: &Item) -> crate::utils::Span {
+    match item {
+        Item::Function(f) => f.span,
+        Item::Struct(s) => s.span,
+        Item::Enum(e) => e.span,
+        Item::Impl(i) => i.span,
+        Item::Interface(i) | Item::Trait(i) => i.span,
+        Item::Const(c) => c.span,
+        Item::Macro(m) => m.span,
+        Item::Module(m) => m.span,
+        Item::Use(u) => u.span,
+        Item::Extern(e) => e.span,
+        Item::Static(s) => s.span,
+        Item::Union(u) => u.span,
+        Item::TypeAlias(t) => t.span,
+    }
+}
+
+fn item_attributes(item: &Item) -> &[Annotation] {
+    match item {
+        Item::Function(f) => &f.annotations,
+        Item::Struct(s) => &s.annotations,
+        Item::Enum(e) => &e.annotations,
+        _ => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::lexer::Lexer;
+    use crate::frontend::parser::Parser;
+
+    fn format(source: &str) -> String {
+        let program = Parser::new(Lexer::new(source, 0)).parse_program().unwrap();
+        Formatter::new().with_comments(source).format_program(&program)
+    }
+
+    #[test]
+    fn test_format_is_stable() {
+        let source = "// header\nstruct P { x: i64, pub y: *u8 }\n\n/// Doc\nfn f(p: *P, n: i64) -> i64 effect[io] {\n    let s: i64 = (n + 1) * 2 - (3 - n)\n    // tally\n    if s > 0 && !(p.x == 0) { return -(s as i64) } else if n == 1 { puts(\"a\\n\") }\n    return match n { 0 | 1 => 1, k if k > 2 => { k }, _ => (*p).x }\n}\n";
+        let formatted = format(source);
+        assert_eq!(formatted, "\
+// header
+struct P {
+    x: i64,
+    pub y: *u8,
+}
+
+/// Doc
+fn f(p: *P, n: i64) -> i64 effect[io] {
+    let s: i64 = (n + 1) * 2 - (3 - n)
+    // tally
+    if s > 0 && !(p.x == 0) {
+        return -(s as i64)
+    } else if n == 1 {
+        puts(\"a\\n\")
+    }
+    return match n {
+        0 | 1 => 1,
+        k if k > 2 => {
+            k
+        },
+        _ => (*p).x,
+    }
+}
+");
+        assert_eq!(format(&formatted), formatted);
+    }
+
+    #[test]
+    fn test_keeps_tail_semicolon() {
+        assert_eq!(format("fn f() { g(); h(); }"), "fn f() {\n    g()\n    h();\n}\n");
+    }
+}
@@ -0,0 +1,142 @@
+//! Text dumps of what semantic analysis inferred (`--print-types`,
+//! `--print-symbols`), for development without an editor that shows them
+
+use std::fmt::Write;
+
+use crate::frontend::semantic::{Symbol, SymbolKind};
+use crate::types::type_system::ResolvedType;
+use crate::utils::{SourceLines, Span};
+
+/// Longest expression text shown after an annotation
+const SNIPPET_LEN: usize = 40;
+
+/// `source` annotated with the type of each expression in `types`: every
+/// line holding the start of an expression, followed by one
+/// `  at file:line:col: type` line per expression starting on it
+pub fn annotate_types(file: &str, source: &str, types: &[(Span, ResolvedType)]) -> String {
+    let lines = SourceLines::new(file, source);
+    let mut types: Vec<(Span, String)> = types.iter()
+        .filter(|(span, _)| span.file_id == 0 && !span.is_empty() && span.end <= source.len())
+        .map(|(span, ty)| (*span, ty.to_string()))
+        .collect();
+    // Outer expressions before the ones they start with
+    types.sort_by_key(|(span, _)| (span.start, std::cmp::Reverse(span.end)));
+    types.dedup();
+
+    let mut out = String::new();
+    let mut shown_line = None;
+    for (span, ty) in &types {
+        let line_start = source[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        if shown_line != Some(line_start) {
+            let number = source[..line_start].matches('\n').count() + 1;
+            let text = source[line_start..].lines().next().unwrap_or("");
+            let _ = writeln!(out, "{:>5} | {}", number, text);
+            shown_line = Some(line_start);
+        }
+        let (file, line, col) = lines.locate(*span);
+        let text = source[span.start..span.end].lines().next().unwrap_or("");
+        let snippet = match text.char_indices().nth(SNIPPET_LEN) {
+            Some((end, _)) => format!("{}..", &text[..end]),
+            None => text.to_string(),
+        };
+        let _ = writeln!(out, "  at {}:{}:{}: {}  // {}", file, line, col, ty, snippet);
+    }
+    out
+}
+
+fn kind_name(symbol: &Symbol) -> &'static str {
+    match symbol.kind {
+        SymbolKind::Variable if symbol.mutable => "mut variable",
+        SymbolKind::Variable => "variable",
+        SymbolKind::Function { .. } => "function",
+        SymbolKind::Struct { .. } => "struct",
+        SymbolKind::Enum { .. } => "enum",
+        SymbolKind::Param { .. } => "parameter",
+        SymbolKind::TypeParam => "type parameter",
+        SymbolKind::ConstParam { .. } => "const parameter",
+        SymbolKind::TypeAlias { .. } => "type alias",
+        SymbolKind::Static { is_mut: true } => "static mut",
+        SymbolKind::Static { is_mut: false } => "static",
+        SymbolKind::Union { .. } => "union",
+    }
+}
+
+/// Table of `symbols` defined in the program, in definition order, with
+/// their kinds, types and locations. Builtins, which have no location, are
+/// left out.
+pub fn symbol_table<'a>(file: &str, source: &str, symbols: impl IntoIterator<Item = &'a Symbol>) -> String {
+    let lines = SourceLines::new(file, source);
+    let mut symbols: Vec<&Symbol> = symbols.into_iter().filter(|symbol| symbol.span != Span::dummy()).collect();
+    symbols.sort_by_key(|symbol| (symbol.span.file_id, symbol.span.start, symbol.name.clone()));
+    let rows: Vec<[String; 4]> = symbols.iter().map(|symbol| {
+        let location = match symbol.span.file_id {
+            0 if symbol.span.start <= source.len() => {
+                let (file, line, col) = lines.locate(symbol.span);
+                format!("{}:{}:{}", file, line, col)
+            }
+            _ => "(imported)".to_string(),
+        };
+        let ty = match (&symbol.kind, &symbol.ty) {
+            (SymbolKind::TypeAlias { target }, _) => target.to_string(),
+            (_, ResolvedType::Unknown) => "-".to_string(),
+            (_, ty) => ty.to_string(),
+        };
+        [symbol.name.clone(), kind_name(symbol).to_string(), ty, location]
+    }).collect();
+
+    let header = ["NAME", "KIND", "TYPE", "DEFINED"].map(String::from);
+    let mut widths = header.clone().map(|title| title.chars().count());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut out = String::new();
+    for row in std::iter::once(&header).chain(&rows) {
+        let cells: Vec<String> = row.iter().zip(widths).map(|(cell, width)| format!("{:<width$}", cell)).collect();
+        let _ = writeln!(out, "{}", cells.join("  ").trim_end());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::lexer::Lexer;
+    use crate::frontend::parser::Parser;
+    use crate::frontend::semantic::SemanticAnalyzer;
+
+    const SOURCE: &str = "struct P { x: i64 }\nfn f(p: P) -> i64 {\n    return p.x + 1\n}\nfn main() {}\n";
+
+    fn analyzed() -> SemanticAnalyzer {
+        let program = Parser::new(Lexer::new(SOURCE, 0)).parse_program().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.set_record_types(true);
+        analyzer.analyze(&program).unwrap();
+        analyzer
+    }
+
+    #[test]
+    fn test_annotate_types() {
+        let out = annotate_types("demo.aeth", SOURCE, analyzed().expr_types());
+        assert_eq!(out, "    3 |     return p.x + 1\n\
+            \x20 at demo.aeth:3:12: i64  // p.x + 1\n\
+            \x20 at demo.aeth:3:12: i64  // p.x\n\
+            \x20 at demo.aeth:3:12: P  // p\n\
+            \x20 at demo.aeth:3:18: i64  // 1\n");
+    }
+
+    #[test]
+    fn test_symbol_table() {
+        let analyzer = analyzed();
+        let out = symbol_table("demo.aeth", SOURCE, analyzer.symbols.globals());
+        assert_eq!(out.lines().take(4).collect::<Vec<_>>(), [
+            "NAME    KIND      TYPE          DEFINED",
+            "P       struct    P             demo.aeth:1:1",
+            "f       function  fn(P) -> i64  demo.aeth:2:1",
+            "main    function  fn() -> ()    demo.aeth:5:1",
+        ]);
+        // The prelude's types come from outside the file
+        assert!(out.lines().skip(4).all(|row| row.ends_with("(imported)")), "{}", out);
+    }
+}
@@ -3,8 +3,9 @@
 //! Converts source code into a stream of tokens.
 #![allow(dead_code)]
 
+use crate::frontend::source_db::SourceDb;
 use crate::frontend::token::{Token, TokenKind};
-use crate::utils::Span;
+use crate::utils::{Error, Span};
 
 /// The lexer state
 pub struct Lexer {
@@ -16,6 +17,12 @@ pub struct Lexer {
     start: usize,
     /// File ID for span tracking
     file_id: usize,
+    /// Interned identifier and literal text
+    db: SourceDb,
+    /// Reused buffer for the text of the current token
+    scratch: String,
+    /// Malformed tokens seen so far
+    errors: Vec<Error>,
 }
 
 impl Lexer {
@@ -26,8 +33,26 @@ impl Lexer {
             pos: 0,
             start: 0,
             file_id,
+            db: SourceDb::new(),
+            scratch: String::new(),
+            errors: Vec::new(),
         }
     }
+
+    /// Take the errors for unterminated literals and comments
+    pub fn take_errors(&mut self) -> Vec<Error> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// Strings interned so far
+    pub fn db(&self) -> &SourceDb {
+        &self.db
+    }
+
+    /// Take the interned strings, leaving an empty arena behind
+    pub fn take_db(&mut self) -> SourceDb {
+        std::mem::take(&mut self.db)
+    }
     
     /// Get the current character without advancing
     fn peek(&self) -> Option<char> {
@@ -69,8 +94,8 @@ impl Lexer {
                 ' ' | '\t' | '\r' | '\n' => {
                     self.advance();
                 }
-                // Line comment
-                '/' if self.peek_next() == Some('/') => {
+                // Line comment; doc comments are tokens
+                '/' if self.peek_next() == Some('/') && !self.at_doc_comment() => {
                     // Skip until end of line
                     while let Some(c) = self.peek() {
                         if c == '\n' {
@@ -81,6 +106,7 @@ impl Lexer {
                 }
                 // Block comment
                 '/' if self.peek_next() == Some('*') => {
+                    let start = self.pos;
                     self.advance(); // skip /
                     self.advance(); // skip *
                     let mut depth = 1;
@@ -101,12 +127,40 @@ impl Lexer {
                             }
                         }
                     }
+                    if depth > 0 {
+                        self.errors.push(Error::Unterminated {
+                            what: "block comment",
+                            span: Span::new(start, self.pos, self.file_id),
+                        });
+                    }
                 }
                 _ => break,
             }
         }
     }
     
+    /// Whether a `///` doc comment starts here (`////` is a plain comment)
+    fn at_doc_comment(&self) -> bool {
+        let at = |i: usize| self.source.get(self.pos + i).copied();
+        at(0) == Some('/') && at(1) == Some('/') && at(2) == Some('/') && at(3) != Some('/')
+    }
+
+    /// Read a `///` line, dropping the marker and one following space
+    fn read_doc_comment(&mut self) -> Token {
+        self.pos += 3;
+        self.scratch.clear();
+        while let Some(c) = self.peek() {
+            if c == '\n' {
+                break;
+            }
+            self.scratch.push(c);
+            self.advance();
+        }
+        let text = self.scratch.strip_prefix(' ').unwrap_or(&self.scratch).trim_end();
+        let symbol = self.db.intern(text);
+        self.make_token(TokenKind::DocComment(symbol))
+    }
+
     /// Read an identifier or keyword
     fn read_identifier(&mut self) -> Token {
         while let Some(c) = self.peek() {
@@ -117,11 +171,12 @@ impl Lexer {
             }
         }
         
-        let text: String = self.source[self.start..self.pos].iter().collect();
+        self.scratch.clear();
+        self.scratch.extend(&self.source[self.start..self.pos]);
         
         // Check if it's a keyword
-        let kind = TokenKind::keyword_from_str(&text)
-            .unwrap_or_else(|| TokenKind::Ident(text));
+        let kind = TokenKind::keyword_from_str(&self.scratch)
+            .unwrap_or_else(|| TokenKind::Ident(self.db.intern(&self.scratch)));
         
         self.make_token(kind)
     }
@@ -141,12 +196,10 @@ impl Lexer {
                 }
             }
             
-            let text: String = self.source[self.start..self.pos]
-                .iter()
-                .filter(|&&c| c != '_')
-                .collect();
+            self.scratch.clear();
+            self.scratch.extend(self.source[self.start + 2..self.pos].iter().filter(|&&c| c != '_'));
             
-            let value = i64::from_str_radix(&text[2..], 16).unwrap_or(0);
+            let value = i64::from_str_radix(&self.scratch, 16).unwrap_or(0);
             return self.make_token(TokenKind::IntLit(value));
         }
         
@@ -193,16 +246,14 @@ impl Lexer {
             }
         }
         
-        let text: String = self.source[self.start..self.pos]
-            .iter()
-            .filter(|&&c| c != '_')
-            .collect();
+        self.scratch.clear();
+        self.scratch.extend(self.source[self.start..self.pos].iter().filter(|&&c| c != '_'));
         
         if is_float {
-            let value = text.parse().unwrap_or(0.0);
+            let value = self.scratch.parse().unwrap_or(0.0);
             self.make_token(TokenKind::FloatLit(value))
         } else {
-            let value = text.parse().unwrap_or(0);
+            let value = self.scratch.parse().unwrap_or(0);
             self.make_token(TokenKind::IntLit(value))
         }
     }
@@ -212,10 +263,12 @@ impl Lexer {
         self.advance(); // consume opening quote
         
         let mut value = String::new();
+        let mut terminated = false;
         
         while let Some(c) = self.peek() {
             if c == '"' {
                 self.advance(); // consume closing quote
+                terminated = true;
                 break;
             } else if c == '\\' {
                 self.advance();
@@ -237,8 +290,13 @@ impl Lexer {
                 self.advance();
             }
         }
+
+        if !terminated {
+            self.errors.push(Error::Unterminated { what: "string literal", span: self.make_span() });
+        }
         
-        self.make_token(TokenKind::StringLit(value))
+        let symbol = self.db.intern(&value);
+        self.make_token(TokenKind::StringLit(symbol))
     }
     
     /// Read a character literal or lifetime parameter
@@ -264,13 +322,15 @@ impl Lexer {
                 }
                 
                 // Check if there's a closing quote (char literal) or not (lifetime)
-                if self.peek() == Some('\'') && name.len() == 1 {
+                let mut chars = name.chars();
+                if let (Some(ch), None, Some('\'')) = (chars.next(), chars.next(), self.peek()) {
                     // It's a character literal like 'a'
                     self.advance(); // consume closing quote
-                    return self.make_token(TokenKind::CharLit(name.chars().next().unwrap()));
+                    return self.make_token(TokenKind::CharLit(ch));
                 } else {
                     // It's a lifetime like 'a or 'static
-                    return self.make_token(TokenKind::Lifetime(name));
+                    let symbol = self.db.intern(&name);
+                    return self.make_token(TokenKind::Lifetime(symbol));
                 }
             }
         }
@@ -295,6 +355,8 @@ impl Lexer {
         // Consume closing quote
         if self.peek() == Some('\'') {
             self.advance();
+        } else {
+            self.errors.push(Error::Unterminated { what: "character literal", span: self.make_span() });
         }
         
         self.make_token(TokenKind::CharLit(c))
@@ -308,8 +370,13 @@ impl Lexer {
         if self.is_at_end() {
             return Token::eof(self.make_span());
         }
+        if self.at_doc_comment() {
+            return self.read_doc_comment();
+        }
         
-        let c = self.advance().unwrap();
+        let Some(c) = self.advance() else {
+            return Token::eof(self.make_span());
+        };
         
         // Identifiers and keywords
         if c.is_alphabetic() || c == '_' {
@@ -495,7 +562,7 @@ mod tests {
         let tokens = lexer.tokenize();
         
         assert!(matches!(tokens[0].kind, TokenKind::Fn));
-        assert!(matches!(tokens[1].kind, TokenKind::Ident(ref s) if s == "main"));
+        assert!(matches!(tokens[1].kind, TokenKind::Ident(s) if lexer.db().resolve(s) == "main"));
         assert!(matches!(tokens[2].kind, TokenKind::LParen));
         assert!(matches!(tokens[3].kind, TokenKind::RParen));
         assert!(matches!(tokens[4].kind, TokenKind::LBrace));
@@ -503,6 +570,18 @@ mod tests {
         assert!(matches!(tokens[6].kind, TokenKind::Eof));
     }
     
+    #[test]
+    fn test_unterminated() {
+        for (source, what) in [("\"abc\n\"", "string literal"), ("'1", "character literal"), ("'\\n", "character literal"), ("/* a /* b */", "block comment")] {
+            let mut lexer = Lexer::new(source, 0);
+            let tokens = lexer.tokenize();
+            assert!(matches!(tokens.last().map(|t| t.kind), Some(TokenKind::Eof)));
+            let errors = lexer.take_errors();
+            assert!(matches!(errors.first(), Some(Error::Unterminated { what: w, .. }) if *w == what), "{:?}", source);
+        }
+        assert!(Lexer::new("'a' 'static \"ok\" /* */", 0).take_errors().is_empty());
+    }
+
     #[test]
     fn test_numbers() {
         let mut lexer = Lexer::new("42 3.14 0xFF_FF", 0);
@@ -518,7 +597,7 @@ mod tests {
         let mut lexer = Lexer::new(r#""hello\nworld""#, 0);
         let tokens = lexer.tokenize();
         
-        assert!(matches!(tokens[0].kind, TokenKind::StringLit(ref s) if s == "hello\nworld"));
+        assert!(matches!(tokens[0].kind, TokenKind::StringLit(s) if lexer.db().resolve(s) == "hello\nworld"));
     }
     
     #[test]
@@ -555,8 +634,17 @@ mod tests {
         let tokens = lexer.tokenize();
         
         assert!(matches!(tokens[0].kind, TokenKind::At));
-        assert!(matches!(tokens[1].kind, TokenKind::Ident(ref s) if s == "test"));
+        assert!(matches!(tokens[1].kind, TokenKind::Ident(s) if lexer.db().resolve(s) == "test"));
         assert!(matches!(tokens[2].kind, TokenKind::Question));
         assert!(matches!(tokens[3].kind, TokenKind::Tilde));
     }
+
+    #[test]
+    fn test_doc_comments() {
+        let mut lexer = Lexer::new("/// Adds `a`\n//// not a doc\n// plain\nfn", 0);
+        let tokens = lexer.tokenize();
+
+        assert!(matches!(tokens[0].kind, TokenKind::DocComment(s) if lexer.db().resolve(s) == "Adds `a`"));
+        assert!(matches!(tokens[1].kind, TokenKind::Fn));
+    }
 }
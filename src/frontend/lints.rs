@@ -0,0 +1,717 @@
+//! Warnings reported after semantic analysis
+//!
+//! - unused variables: locals and parameters that are never read
+//! - dead code: private functions and structs not reachable from `main`,
+//!   `pub` items or exported functions, and fields that are never read.
+//!   In a program without `main` every function is an entry point
+//! - unreachable code: statements after `return`, `break` or `continue`
+//!
+//! Names starting with `_` never warn. An item silences its own warnings
+//! with `#[allow(unused)]`, or per lint with `#[allow(unused_variables)]`,
+//! `#[allow(dead_code)]` and `#[allow(unreachable_code)]`; as `#![allow(..)]`
+//! these apply to the whole program.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::frontend::ast::*;
+use crate::utils::{Error, Span};
+
+/// Lint names accepted by `#[allow(...)]`, besides `unused`
+const UNUSED_VARIABLES: &str = "unused_variables";
+const DEAD_CODE: &str = "dead_code";
+const UNREACHABLE_CODE: &str = "unreachable_code";
+
+/// Function annotations that make a function an entry point
+const ENTRY_ANNOTATIONS: [&str; 4] = ["extern_c", "no_mangle", "test", "bench"];
+
+/// Lint `program`, returning its warnings in source order
+pub fn check_program(program: &Program) -> Vec<Error> {
+    let no_main = program.inner_attrs.iter().any(|a| matches!(a.name.name.as_str(), "no_main" | "no_std"))
+        || !program.items.iter().any(|item| matches!(item, Item::Function(f) if f.name.name == "main"));
+    let mut lints = Lints { no_main, ..Lints::default() };
+    lints.collect_items(&program.items);
+
+    let reachable = lints.reachable();
+    let mut warnings = std::mem::take(&mut lints.warnings);
+    for (id, node) in lints.nodes.iter().enumerate() {
+        if reachable.contains(&id) || node.name.starts_with('_') {
+            continue;
+        }
+        if let Some(kind) = node.kind {
+            warnings.push(Error::UnusedItem { kind: kind.to_string(), name: node.name.clone(), span: node.span });
+        }
+    }
+    for (id, node) in lints.nodes.iter().enumerate() {
+        if !reachable.contains(&id) {
+            continue;
+        }
+        for field in &node.fields {
+            if !lints.fields_read.contains(&field.name.name) && !field.name.name.starts_with('_') {
+                warnings.push(Error::UnusedField {
+                    name: format!("{}.{}", node.name, field.name.name),
+                    span: field.span,
+                });
+            }
+        }
+    }
+    // `#![allow(..)]` silences a lint for the whole program
+    warnings.retain(|w| !allows(&program.inner_attrs, lint_name(w)));
+    warnings.sort_by_key(|w| w.span().map(|s| s.start));
+    warnings
+}
+
+/// The `#[allow(...)]` name silencing `warning`
+fn lint_name(warning: &Error) -> &'static str {
+    match warning {
+        Error::UnusedVariable { .. } => UNUSED_VARIABLES,
+        Error::UnreachableCode { .. } => UNREACHABLE_CODE,
+        _ => DEAD_CODE,
+    }
+}
+
+/// Whether `annotations` contain `#[allow(lint)]` or `#[allow(unused)]`
+fn allows(annotations: &[Annotation], lint: &str) -> bool {
+    annotations.iter()
+        .filter(|a| a.name.name == "allow")
+        .flat_map(|a| &a.args)
+        .any(|arg| matches!(arg, Expr::Ident(id) if id.name == lint || id.name == "unused"))
+}
+
+/// An item in the reachability graph
+struct Node {
+    name: String,
+    /// "Function" or "Struct" when the item warns if unreachable
+    kind: Option<&'static str>,
+    span: Span,
+    root: bool,
+    /// Names the item refers to
+    refs: HashSet<String>,
+    /// Private fields that warn if never read
+    fields: Vec<Field>,
+}
+
+#[derive(Default)]
+struct Lints {
+    nodes: Vec<Node>,
+    /// Field names read anywhere in the program
+    fields_read: HashSet<String>,
+    warnings: Vec<Error>,
+    /// Without `main` (libraries, kernels) every function may be an entry point
+    no_main: bool,
+}
+
+impl Lints {
+    fn collect_items(&mut self, items: &[Item]) {
+        let structs: HashSet<&str> = items.iter()
+            .filter_map(|item| match item {
+                Item::Struct(s) => Some(s.name.name.as_str()),
+                _ => None,
+            })
+            .collect();
+        for item in items {
+            match item {
+                Item::Function(func) => {
+                    let root = func.is_pub
+                        || func.name.name == "main"
+                        || self.no_main
+                        || allows(&func.annotations, DEAD_CODE)
+                        || ENTRY_ANNOTATIONS.iter().any(|a| func.has_annotation(a));
+                    let mut body = self.body(&func.annotations);
+                    body.function(func);
+                    let refs = self.finish(body);
+                    self.nodes.push(Node { name: func.name.name.clone(), kind: Some("Function"), span: func.name.span, root, refs, fields: Vec::new() });
+                }
+                Item::Struct(s) => {
+                    let mut body = self.body(&s.annotations);
+                    for field in &s.fields {
+                        body.ty(&field.ty);
+                    }
+                    for invariant in &s.invariants {
+                        // Invariants name fields directly
+                        body.expr(&invariant.condition);
+                        body.fields.extend(body.names.iter().cloned());
+                    }
+                    let refs = self.finish(body);
+                    let dead_code = !allows(&s.annotations, DEAD_CODE);
+                    // Layout attributes mean the fields may be read from C
+                    let fields = if dead_code && s.annotations.is_empty() {
+                        s.fields.iter().filter(|f| !f.is_pub).cloned().collect()
+                    } else {
+                        Vec::new()
+                    };
+                    self.nodes.push(Node {
+                        name: s.name.name.clone(),
+                        kind: Some("Struct"),
+                        span: s.name.span,
+                        root: s.is_pub || !dead_code,
+                        refs,
+                        fields,
+                    });
+                }
+                Item::Impl(imp) => {
+                    let mut refs = HashSet::new();
+                    if let Some(interface) = &imp.interface {
+                        refs.insert(interface.name.clone());
+                    }
+                    for method in &imp.methods {
+                        let mut body = self.body(&method.annotations);
+                        body.function(method);
+                        refs.extend(self.finish(body));
+                    }
+                    for binding in &imp.associated_types {
+                        let mut body = self.body(&[]);
+                        body.ty(&binding.ty);
+                        refs.extend(self.finish(body));
+                    }
+                    // Interface methods are called through the interface, and
+                    // impls of other types are live whenever the program is
+                    let root = imp.interface.is_some() || !structs.contains(imp.target.name.as_str());
+                    self.push_root(&imp.target.name, imp.span, root, refs);
+                }
+                Item::Interface(def) | Item::Trait(def) => {
+                    let mut refs = HashSet::new();
+                    for sig in &def.methods {
+                        let mut body = self.body(&[]);
+                        for param in &sig.params {
+                            body.ty(&param.ty);
+                        }
+                        if let Some(ty) = &sig.ret_type {
+                            body.ty(ty);
+                        }
+                        refs.extend(self.finish(body));
+                    }
+                    for method in &def.default_methods {
+                        let mut body = self.body(&method.annotations);
+                        body.function(method);
+                        refs.extend(self.finish(body));
+                    }
+                    self.push_root(&def.name.name, def.span, true, refs);
+                }
+                Item::Enum(e) => {
+                    let mut body = self.body(&[]);
+                    for variant in &e.variants {
+                        variant.fields.iter().for_each(|ty| body.ty(ty));
+                        if let Some(value) = &variant.discriminant {
+                            body.expr(value);
+                        }
+                    }
+                    let refs = self.finish(body);
+                    self.push_root(&e.name.name, e.span, true, refs);
+                }
+                Item::Union(u) => {
+                    let mut body = self.body(&[]);
+                    u.fields.iter().for_each(|f| body.ty(&f.ty));
+                    let refs = self.finish(body);
+                    self.push_root(&u.name.name, u.span, true, refs);
+                }
+                Item::Const(c) => {
+                    let mut body = self.body(&[]);
+                    if let Some(ty) = &c.ty {
+                        body.ty(ty);
+                    }
+                    body.expr(&c.value);
+                    let refs = self.finish(body);
+                    self.push_root(&c.name.name, c.span, true, refs);
+                }
+                Item::Static(s) => {
+                    let mut body = self.body(&[]);
+                    body.ty(&s.ty);
+                    if let Some(value) = &s.value {
+                        body.expr(value);
+                    }
+                    let refs = self.finish(body);
+                    self.push_root(&s.name.name, s.span, true, refs);
+                }
+                Item::TypeAlias(alias) => {
+                    let mut body = self.body(&[]);
+                    body.ty(&alias.ty);
+                    let refs = self.finish(body);
+                    self.push_root(&alias.name.name, alias.span, true, refs);
+                }
+                Item::Extern(block) => {
+                    let mut body = self.body(&[]);
+                    for foreign in &block.items {
+                        match foreign {
+                            ForeignItem::Fn { params, ret_type, .. } => {
+                                params.iter().for_each(|p| body.ty(&p.ty));
+                                if let Some(ty) = ret_type {
+                                    body.ty(ty);
+                                }
+                            }
+                            ForeignItem::Static { ty, .. } => body.ty(ty),
+                        }
+                    }
+                    let refs = self.finish(body);
+                    self.push_root("", block.span, true, refs);
+                }
+                Item::Macro(mac) => {
+                    let mut refs = HashSet::new();
+                    match &mac.kind {
+                        MacroKind::Declarative { rules } => {
+                            for rule in rules {
+                                macro_names(&rule.template.tokens, &mut refs);
+                            }
+                        }
+                        MacroKind::Procedural { handler } => {
+                            refs.insert(handler.name.clone());
+                        }
+                    }
+                    self.push_root(&mac.name.name, mac.span, true, refs);
+                }
+                Item::Use(decl) => {
+                    let mut refs = HashSet::new();
+                    use_names(decl, &mut refs);
+                    self.push_root("", decl.span, true, refs);
+                }
+                Item::Module(module) => {
+                    if let Some(items) = &module.items {
+                        self.collect_items(items);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Walker for the body of an item carrying `annotations`
+    fn body(&self, annotations: &[Annotation]) -> Body {
+        Body {
+            unused_variables: !allows(annotations, UNUSED_VARIABLES),
+            unreachable_code: !allows(annotations, UNREACHABLE_CODE),
+            ..Body::default()
+        }
+    }
+
+    /// Keep a walker's warnings and field reads, returning the names it saw
+    fn finish(&mut self, body: Body) -> HashSet<String> {
+        self.warnings.extend(body.warnings);
+        self.fields_read.extend(body.fields);
+        body.names
+    }
+
+    /// Add an item that never warns itself
+    fn push_root(&mut self, name: &str, span: Span, root: bool, refs: HashSet<String>) {
+        self.nodes.push(Node { name: name.to_string(), kind: None, span, root, refs, fields: Vec::new() });
+    }
+
+    /// Nodes reachable from the roots through the names they refer to
+    fn reachable(&self) -> HashSet<usize> {
+        let mut by_name: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (id, node) in self.nodes.iter().enumerate() {
+            by_name.entry(node.name.as_str()).or_default().push(id);
+        }
+        let mut stack: Vec<usize> = (0..self.nodes.len()).filter(|&id| self.nodes[id].root).collect();
+        let mut reached: HashSet<usize> = stack.iter().copied().collect();
+        while let Some(id) = stack.pop() {
+            for name in &self.nodes[id].refs {
+                for &next in by_name.get(name.as_str()).into_iter().flatten() {
+                    if reached.insert(next) {
+                        stack.push(next);
+                    }
+                }
+            }
+        }
+        reached
+    }
+}
+
+/// Identifiers in a macro template, which may name items
+fn macro_names(tokens: &[MacroToken], names: &mut HashSet<String>) {
+    for token in tokens {
+        match token {
+            MacroToken::Literal(text) => {
+                names.insert(text.clone());
+            }
+            MacroToken::Repetition { pattern, .. } => macro_names(pattern, names),
+            MacroToken::Variable { .. } => {}
+        }
+    }
+}
+
+fn use_names(decl: &UseDecl, names: &mut HashSet<String>) {
+    names.extend(decl.path.iter().map(|id| id.name.clone()));
+    if let UseKind::Group(decls) = &decl.kind {
+        decls.iter().for_each(|d| use_names(d, names));
+    }
+}
+
+/// A local binding and whether it was read
+struct Binding {
+    name: String,
+    span: Span,
+    used: bool,
+}
+
+/// Walks one item: tracks locals and collects the names and fields it uses
+#[derive(Default)]
+struct Body {
+    scopes: Vec<Vec<Binding>>,
+    /// Names that are not locals: items, variants, builtins
+    names: HashSet<String>,
+    /// Field names read
+    fields: HashSet<String>,
+    warnings: Vec<Error>,
+    unused_variables: bool,
+    unreachable_code: bool,
+}
+
+impl Body {
+    fn function(&mut self, func: &Function) {
+        self.scopes.push(Vec::new());
+        for param in &func.params {
+            self.ty(&param.ty);
+            match &param.pattern {
+                Some(pattern) => self.pattern(pattern),
+                None => self.bind(&param.name),
+            }
+        }
+        if let Some(ty) = &func.ret_type {
+            self.ty(ty);
+        }
+        for contract in &func.contracts {
+            self.expr(&contract.condition);
+        }
+        self.block(&func.body);
+        self.exit_scope();
+    }
+
+    fn bind(&mut self, name: &Ident) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(Binding { name: name.name.clone(), span: name.span, used: false });
+        }
+    }
+
+    /// A read of `name`: the innermost local of that name, or an item
+    fn read(&mut self, name: &str) {
+        let local = self.scopes.iter_mut().rev()
+            .find_map(|scope| scope.iter_mut().rev().find(|b| b.name == name));
+        match local {
+            Some(binding) => binding.used = true,
+            None => {
+                self.names.insert(name.to_string());
+            }
+        }
+    }
+
+    fn exit_scope(&mut self) {
+        let Some(scope) = self.scopes.pop() else { return };
+        if !self.unused_variables {
+            return;
+        }
+        for binding in scope {
+            // Capitalized bindings are usually unit variants or constants
+            let exempt = binding.name.starts_with(|c: char| c == '_' || c.is_uppercase()) || binding.name == "self";
+            if !binding.used && !exempt {
+                self.warnings.push(Error::UnusedVariable { name: binding.name, span: binding.span });
+            }
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.scopes.push(Vec::new());
+        let mut diverged = false;
+        for stmt in &block.stmts {
+            if diverged && !matches!(stmt, Stmt::Empty { .. }) {
+                if self.unreachable_code {
+                    self.warnings.push(Error::UnreachableCode { span: stmt.span() });
+                }
+                // Report each dead run once, but keep walking it for names
+                diverged = false;
+            }
+            self.stmt(stmt);
+            diverged |= matches!(stmt, Stmt::Return { .. } | Stmt::Break { .. } | Stmt::Continue { .. });
+        }
+        self.exit_scope();
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Let { name, ty, value, pattern, .. } => {
+                if let Some(ty) = ty {
+                    self.ty(ty);
+                }
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+                match pattern {
+                    Some(pattern) => self.pattern(pattern),
+                    None => self.bind(name),
+                }
+            }
+            Stmt::Expr(expr) => self.expr(expr),
+            Stmt::Return { value: Some(value), .. } | Stmt::Break { break_value: Some(value), .. } => self.expr(value),
+            Stmt::Return { value: None, .. } | Stmt::Break { break_value: None, .. } | Stmt::Continue { .. } | Stmt::Empty { .. } => {}
+        }
+    }
+
+    /// Bind the names a pattern introduces; struct fields it names are read
+    fn pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Wildcard { .. } | Pattern::Literal(_) => {}
+            Pattern::Binding { name, .. } => self.bind(name),
+            Pattern::Struct { name, fields, .. } => {
+                self.names.insert(name.name.clone());
+                for (field, pattern) in fields {
+                    self.fields.insert(field.name.clone());
+                    self.pattern(pattern);
+                }
+            }
+            Pattern::Tuple { elements, .. } => elements.iter().for_each(|p| self.pattern(p)),
+            Pattern::Variant { enum_name, variant, fields, .. } => {
+                if let Some(enum_name) = enum_name {
+                    self.names.insert(enum_name.name.clone());
+                }
+                self.names.insert(variant.name.clone());
+                fields.iter().for_each(|p| self.pattern(p));
+            }
+            // Every alternative binds the same names
+            Pattern::Or { alternatives, .. } => {
+                if let Some(first) = alternatives.first() {
+                    self.pattern(first);
+                }
+                for alternative in alternatives.iter().skip(1) {
+                    let mut names = Body::default();
+                    names.pattern(alternative);
+                    self.names.extend(names.names);
+                    self.fields.extend(names.fields);
+                }
+            }
+        }
+    }
+
+    fn ty(&mut self, ty: &Type) {
+        match ty {
+            Type::Named(name, _) => {
+                self.names.extend(name.split("::").map(str::to_string));
+            }
+            Type::Generic(name, args, _) => {
+                self.names.extend(name.split("::").map(str::to_string));
+                args.iter().for_each(|t| self.ty(t));
+            }
+            Type::GenericWithArgs { name, args, .. } => {
+                self.names.extend(name.split("::").map(str::to_string));
+                self.generic_args(args);
+            }
+            Type::Pointer(inner, _) | Type::Slice(inner, _) | Type::Volatile(inner, _) => self.ty(inner),
+            Type::Ref { inner, .. } | Type::Owned { inner, .. } => self.ty(inner),
+            Type::Array { elem, .. } => self.ty(elem),
+            Type::Tuple(types, _) => types.iter().for_each(|t| self.ty(t)),
+            Type::Function { params, ret, .. } => {
+                params.iter().for_each(|t| self.ty(t));
+                self.ty(ret);
+            }
+            Type::Never(_) | Type::Unit(_) | Type::Infer(_) => {}
+        }
+    }
+
+    fn generic_args(&mut self, args: &[GenericArg]) {
+        for arg in args {
+            match arg {
+                GenericArg::Type(ty) => self.ty(ty),
+                GenericArg::Const(expr) => self.expr(expr),
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal(_) => {}
+            Expr::Ident(id) => self.read(&id.name),
+            Expr::Path { segments, generic_args, .. } => {
+                self.names.extend(segments.iter().map(|s| s.name.clone()));
+                self.generic_args(generic_args);
+            }
+            Expr::Binary { left, op: BinOp::Assign, right, .. } => {
+                self.expr(right);
+                self.place(left);
+            }
+            Expr::Binary { .. } => {
+                // Long chains like `a + b + c + ..` nest to the left, so
+                // their left operands are walked in a loop
+                let mut rights = Vec::new();
+                let mut leftmost = expr;
+                while let Expr::Binary { left, op, right, .. } = leftmost {
+                    if *op == BinOp::Assign {
+                        break;
+                    }
+                    rights.push(right);
+                    leftmost = left;
+                }
+                self.expr(leftmost);
+                rights.into_iter().rev().for_each(|right| self.expr(right));
+            }
+            Expr::Unary { expr, .. } | Expr::Deref { expr, .. } | Expr::Try { expr, .. } | Expr::Ref { expr, .. } => self.expr(expr),
+            Expr::Call { func, args, .. } => {
+                self.expr(func);
+                args.iter().for_each(|a| self.expr(a));
+            }
+            Expr::Field { expr, field, .. } => {
+                self.expr(expr);
+                self.fields.insert(field.name.clone());
+            }
+            Expr::MethodCall { expr, method, args, .. } => {
+                self.expr(expr);
+                self.names.insert(method.name.clone());
+                args.iter().for_each(|a| self.expr(a));
+            }
+            Expr::Index { expr, index, .. } => {
+                self.expr(expr);
+                self.expr(index);
+            }
+            Expr::Block(block) => self.block(block),
+            Expr::Let { name, ty, value, .. } => {
+                if let Some(ty) = ty {
+                    self.ty(ty);
+                }
+                self.expr(value);
+                self.bind(name);
+            }
+            Expr::If { cond, then_block, else_block, .. } => {
+                self.expr(cond);
+                self.block(then_block);
+                if let Some(else_block) = else_block {
+                    self.block(else_block);
+                }
+            }
+            Expr::Match { expr, arms, .. } => {
+                self.expr(expr);
+                for arm in arms {
+                    self.scopes.push(Vec::new());
+                    self.pattern(&arm.pattern);
+                    if let Some(guard) = &arm.guard {
+                        self.expr(guard);
+                    }
+                    self.expr(&arm.body);
+                    self.exit_scope();
+                }
+            }
+            Expr::Loop { body, .. } => self.block(body),
+            Expr::While { cond, body, .. } => {
+                self.expr(cond);
+                self.block(body);
+            }
+            Expr::For { var, iter, body, .. } => {
+                self.expr(iter);
+                self.scopes.push(Vec::new());
+                self.bind(var);
+                self.block(body);
+                self.exit_scope();
+            }
+            Expr::StructLit { name, fields, .. } => {
+                self.names.insert(name.name.clone());
+                fields.iter().for_each(|(_, value)| self.expr(value));
+            }
+            Expr::Array { elements, .. } | Expr::Tuple { elements, .. } => elements.iter().for_each(|e| self.expr(e)),
+            Expr::ArrayFrom { init, .. } => self.expr(init),
+            Expr::DoBlock { stmts, accumulator, .. } => {
+                self.scopes.push(Vec::new());
+                stmts.iter().for_each(|stmt| self.stmt(stmt));
+                self.expr(accumulator);
+                self.exit_scope();
+            }
+            Expr::Cast { expr, ty, .. } => {
+                self.expr(expr);
+                self.ty(ty);
+            }
+            Expr::Range { start, end, .. } => {
+                start.iter().chain(end).for_each(|e| self.expr(e));
+            }
+            Expr::Unsafe { body, verifier, .. } => {
+                if let Some(verifier) = verifier {
+                    self.read(&verifier.name);
+                }
+                self.block(body);
+            }
+            Expr::Asm { operands, .. } => {
+                operands.iter().filter_map(|op| op.expr.as_ref()).for_each(|e| self.expr(e));
+            }
+            Expr::Closure { params, ret_type, body, .. } => {
+                self.scopes.push(Vec::new());
+                for param in params {
+                    if let Some(ty) = &param.ty {
+                        self.ty(ty);
+                    }
+                    self.bind(&param.name);
+                }
+                if let Some(ty) = ret_type {
+                    self.ty(ty);
+                }
+                self.expr(body);
+                self.exit_scope();
+            }
+            Expr::SizeOf { ty, .. } | Expr::AlignOf { ty, .. } => self.ty(ty),
+            Expr::OffsetOf { ty, field, .. } => {
+                self.ty(ty);
+                self.fields.insert(field.name.clone());
+            }
+            Expr::New { ty, count, init, .. } => {
+                self.ty(ty);
+                count.iter().chain(init).for_each(|e| self.expr(e));
+            }
+        }
+    }
+
+    /// The target of `=`: writing a local does not read it
+    fn place(&mut self, target: &Expr) {
+        match target {
+            Expr::Ident(id) => {
+                let local = self.scopes.iter().rev().any(|scope| scope.iter().any(|b| b.name == id.name));
+                if !local {
+                    self.names.insert(id.name.clone());
+                }
+            }
+            // Writing a field reads the struct but not the field
+            Expr::Field { expr, .. } => self.expr(expr),
+            _ => self.expr(target),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::lexer::Lexer;
+    use crate::frontend::parser::Parser;
+
+    fn lint(source: &str) -> Vec<String> {
+        let program = Parser::new(Lexer::new(source, 0)).parse_program().unwrap();
+        check_program(&program).iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_unused_variables() {
+        assert_eq!(
+            lint("fn main() { let x: i64 = 1; let y: i64 = 2; let mut z: i64 = 0; z = y; let _w: i64 = 3; }"),
+            vec!["Unused variable 'x'", "Unused variable 'z'"]
+        );
+        assert_eq!(lint("fn main() { let n: i64 = 2; f(n); }\nfn f(a: i64, _b: i64) {}"), vec!["Unused variable 'a'"]);
+        assert!(lint("#[allow(unused_variables)]\nfn main() { let x: i64 = 1; }").is_empty());
+    }
+
+    #[test]
+    fn test_dead_items_and_fields() {
+        let source = "struct P { x: i64, y: i64 }\n\
+                      struct Unused { a: i64 }\n\
+                      fn helper(p: P) -> i64 { return p.x }\n\
+                      fn orphan() -> i64 { return dead() }\n\
+                      fn dead() -> i64 { return 1 }\n\
+                      #[allow(dead_code)]\nfn kept() {}\n\
+                      pub fn api() {}\n\
+                      fn main() { helper(P { x: 1, y: 2 }); }";
+        assert_eq!(lint(source), vec![
+            "Field 'P.y' is never read",
+            "Struct 'Unused' is never used",
+            "Function 'orphan' is never used",
+            "Function 'dead' is never used",
+        ]);
+    }
+
+    #[test]
+    fn test_unreachable_code() {
+        assert_eq!(
+            lint("fn main() -> i64 { return 1; let x: i64 = 2; return x; }\npub fn f() { loop { break; f(); } }"),
+            vec!["Unreachable statement", "Unreachable statement"]
+        );
+        assert!(lint("#[allow(unused)]\nfn main() { return; main(); }").is_empty());
+        assert!(lint("#![allow(unused)]\nfn f() {}\nfn main() { let x: i64 = 1; }").is_empty());
+    }
+}
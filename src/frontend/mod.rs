@@ -1,8 +1,12 @@
 //! Frontend module - Lexer, Parser, Semantic Analysis
 
+pub mod source_db;
 pub mod token;
 pub mod lexer;
 pub mod ast;
 pub mod parser;
+pub mod formatter;
 pub mod semantic;
+pub mod lints;
+pub mod inspect;
 pub mod module;
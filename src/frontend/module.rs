@@ -5,11 +5,12 @@
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::fs;
+use std::sync::{Arc, Mutex};
 
 use crate::frontend::ast::Item;
 use crate::frontend::lexer::Lexer;
 use crate::frontend::parser::Parser;
-use crate::utils::{Result, Error};
+use crate::utils::{Result, Error, SourceLines};
 
 /// Represents a parsed module with its exported symbols
 #[derive(Debug, Clone)]
@@ -64,14 +65,15 @@ impl ParsedModule {
     }
 }
 
-/// Module loader for dynamic module resolution
+/// Module loader for dynamic module resolution. Clones share the cache of
+/// parsed modules, so a compilation that hands clones to each pass parses
+/// every module file once.
+#[derive(Clone)]
 pub struct ModuleLoader {
     /// Search paths for module files
     search_paths: Vec<PathBuf>,
     /// Cache of parsed modules by module name
-    parsed_modules: HashMap<String, ParsedModule>,
-    /// Modules currently being parsed (for circular dependency detection)
-    parsing_stack: Vec<String>,
+    parsed_modules: Arc<Mutex<HashMap<String, Arc<ParsedModule>>>>,
 }
 
 impl ModuleLoader {
@@ -83,8 +85,7 @@ impl ModuleLoader {
                 PathBuf::from("src_aether"),
                 PathBuf::from("stdlib"),
             ],
-            parsed_modules: HashMap::new(),
-            parsing_stack: Vec::new(),
+            parsed_modules: Arc::default(),
         }
     }
     
@@ -106,20 +107,12 @@ impl ModuleLoader {
         None
     }
     
-    /// Load and parse a module by name
-    pub fn load_module(&mut self, module_name: &str) -> Result<&ParsedModule> {
+    /// Load and parse a module by name. Errors in the module name its file
+    /// and the line and column they occur at.
+    pub fn load_module(&mut self, module_name: &str) -> Result<Arc<ParsedModule>> {
         // Check cache first
-        if self.parsed_modules.contains_key(module_name) {
-            return Ok(self.parsed_modules.get(module_name).unwrap());
-        }
-        
-        // Check for circular dependency
-        if self.parsing_stack.contains(&module_name.to_string()) {
-            return Err(Error::ModuleError(format!(
-                "Circular module dependency detected: {} -> {}",
-                self.parsing_stack.join(" -> "),
-                module_name
-            )));
+        if let Some(parsed) = self.cache().get(module_name) {
+            return Ok(Arc::clone(parsed));
         }
         
         // Find module file
@@ -131,14 +124,16 @@ impl ModuleLoader {
             )))?;
         
         // Parse the module
-        self.parsing_stack.push(module_name.to_string());
-        let parsed = self.parse_module_file(&module_path, module_name)?;
-        self.parsing_stack.pop();
+        let parsed = Arc::new(self.parse_module_file(&module_path, module_name)?);
         
         // Cache the result
-        self.parsed_modules.insert(module_name.to_string(), parsed);
+        self.cache().insert(module_name.to_string(), Arc::clone(&parsed));
         
-        Ok(self.parsed_modules.get(module_name).unwrap())
+        Ok(parsed)
+    }
+
+    fn cache(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<ParsedModule>>> {
+        self.parsed_modules.lock().expect("module cache lock poisoned")
     }
     
     /// Parse a module file
@@ -151,7 +146,11 @@ impl ModuleLoader {
         // Lex and Parse
         let lexer = Lexer::new(&source, 0);
         let mut parser = Parser::new(lexer);
-        let program = parser.parse_program()?;
+        let program = parser.parse_program().map_err(|e| {
+            let lines = SourceLines::new(&path.display().to_string(), &source);
+            let (file, line, column) = lines.locate(e.span().unwrap_or_default());
+            Error::ModuleError(format!("{}:{}:{}: {}", file, line, column, e))
+        })?;
         
         Ok(ParsedModule {
             path: path.clone(),
@@ -161,13 +160,53 @@ impl ModuleLoader {
     }
     
     /// Get a cached module if available
-    pub fn get_cached_module(&self, module_name: &str) -> Option<&ParsedModule> {
-        self.parsed_modules.get(module_name)
+    pub fn get_cached_module(&self, module_name: &str) -> Option<Arc<ParsedModule>> {
+        self.cache().get(module_name).cloned()
     }
     
     /// Check if a module is cached
     pub fn is_cached(&self, module_name: &str) -> bool {
-        self.parsed_modules.contains_key(module_name)
+        self.cache().contains_key(module_name)
+    }
+
+    /// Files of the modules `items` import, directly or through other
+    /// modules, in discovery order. A module that fails to parse is still
+    /// listed; its own imports are not.
+    pub fn dependency_files(&mut self, items: &[Item]) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        let mut pending = Vec::new();
+        collect_imports(items, &mut pending);
+        let mut seen = Vec::new();
+        while let Some(name) = pending.pop() {
+            if seen.contains(&name) {
+                continue;
+            }
+            seen.push(name.clone());
+            let Some(path) = self.find_module_file(&name) else { continue };
+            files.push(path);
+            if let Ok(module) = self.load_module(&name) {
+                let mut imports = Vec::new();
+                collect_imports(&module.items, &mut imports);
+                pending.extend(imports);
+            }
+        }
+        files
+    }
+}
+
+/// Module names named by the `use` declarations in `items`, including
+/// those inside `mod` blocks
+pub(crate) fn collect_imports(items: &[Item], names: &mut Vec<String>) {
+    for item in items.iter().rev() {
+        match item {
+            Item::Use(use_decl) => {
+                if let Some(first) = use_decl.path.first() {
+                    names.push(first.name.clone());
+                }
+            }
+            Item::Module(m) => collect_imports(m.items.as_deref().unwrap_or_default(), names),
+            _ => {}
+        }
     }
 }
 
@@ -6,29 +6,111 @@
 use crate::frontend::token::{Token, TokenKind};
 use crate::frontend::ast::*;
 use crate::frontend::lexer::Lexer;
+use crate::frontend::source_db::{sym, SourceDb, Symbol};
 use crate::utils::{Span, Error, Result};
+use std::collections::{HashMap, VecDeque};
+
+/// Deepest nesting of expressions, types, patterns and modules accepted by default
+pub const DEFAULT_MAX_NESTING: usize = 2048;
+
+/// Tokens looked at behind and ahead of the current one
+const LOOKBEHIND: usize = 2;
+const LOOKAHEAD: usize = 2;
+
+/// Where the parser pulls its tokens from
+enum TokenSource {
+    /// Lexed one token at a time as parsing reaches them
+    Lexer(Lexer),
+    /// Tokenized up front, with the arena their symbols live in
+    Tokens(std::vec::IntoIter<Token>, SourceDb),
+}
+
+impl TokenSource {
+    fn next(&mut self) -> Token {
+        match self {
+            TokenSource::Lexer(lexer) => lexer.next_token(),
+            TokenSource::Tokens(tokens, _) => tokens.next().unwrap_or(Token::eof(Span::dummy())),
+        }
+    }
+
+    fn db(&self) -> &SourceDb {
+        match self {
+            TokenSource::Lexer(lexer) => lexer.db(),
+            TokenSource::Tokens(_, db) => db,
+        }
+    }
+}
 
 /// The parser
 pub struct Parser {
-    tokens: Vec<Token>,
+    source: TokenSource,
+    /// Tokens from `LOOKBEHIND` before the current one to `LOOKAHEAD` after
+    /// it, cut short by Eof
+    window: VecDeque<Token>,
+    /// Stream index of `window[0]`
+    first: usize,
+    /// Stream index of the current token
     pos: usize,
+    /// Current nesting depth
+    depth: usize,
+    max_nesting: usize,
+    /// `///` lines, keyed by the stream index of the token they precede
+    docs: HashMap<usize, Vec<String>>,
 }
 
 impl Parser {
-    /// Create a new parser from a lexer
-    pub fn new(mut lexer: Lexer) -> Self {
-        Self {
-            tokens: lexer.tokenize(),
+    /// Create a new parser pulling tokens from a lexer
+    pub fn new(lexer: Lexer) -> Self {
+        Self::from_source(TokenSource::Lexer(lexer))
+    }
+
+    /// Create a parser from pre-tokenized input whose symbols live in `db`
+    pub fn from_tokens(mut tokens: Vec<Token>, db: SourceDb) -> Self {
+        // The helpers below rely on the stream ending in Eof
+        if tokens.last().is_none_or(|t| t.kind != TokenKind::Eof) {
+            let end = tokens.last().map_or(Span::dummy(), |t| t.span);
+            tokens.push(Token::eof(end));
+        }
+        Self::from_source(TokenSource::Tokens(tokens.into_iter(), db))
+    }
+
+    fn from_source(source: TokenSource) -> Self {
+        let mut parser = Self {
+            source,
+            window: VecDeque::with_capacity(LOOKBEHIND + LOOKAHEAD + 1),
+            first: 0,
             pos: 0,
+            depth: 0,
+            max_nesting: DEFAULT_MAX_NESTING,
+            docs: HashMap::new(),
+        };
+        parser.fill();
+        parser
+    }
+
+    /// Pull tokens until the window reaches `LOOKAHEAD` past the current one or Eof
+    fn fill(&mut self) {
+        while self.first + self.window.len() <= self.pos + LOOKAHEAD
+            && self.window.back().is_none_or(|t| t.kind != TokenKind::Eof)
+        {
+            let token = self.source.next();
+            // Doc comments are kept aside for the item they precede
+            if let TokenKind::DocComment(text) = token.kind {
+                let text = self.source.db().resolve(text).to_string();
+                self.docs.entry(self.first + self.window.len()).or_default().push(text);
+                continue;
+            }
+            self.window.push_back(token);
         }
     }
 
-    /// Create a parser from pre-tokenized input
-    pub fn from_tokens(tokens: Vec<Token>) -> Self {
-        Self { tokens, pos: 0 }
+    /// Allow `limit` levels of nesting; deep input needs a correspondingly large stack
+    pub fn with_max_nesting(mut self, limit: usize) -> Self {
+        self.max_nesting = limit;
+        self
     }
 
-    /// Parse generic parameters: <T, U> or <T, const N: usize>
+    /// Parse generic parameters: <T, U>, <T: Shape> or <T, const N: usize>
     fn parse_generic_params(&mut self) -> Result<Vec<GenericParam>> {
         self.expect(TokenKind::Lt)?;
         let mut params = Vec::new();
@@ -44,9 +126,18 @@ impl Parser {
                     ty: Box::new(ty),
                 });
             } else {
-                // Regular type parameter
+                // Regular type parameter, with optional bounds: `T: Shape + Eq`
                 let name = self.parse_ident()?;
-                params.push(GenericParam::Type(name));
+                let mut bounds = Vec::new();
+                if self.consume(&TokenKind::Colon) {
+                    loop {
+                        bounds.push(self.parse_type()?);
+                        if !self.consume(&TokenKind::Plus) {
+                            break;
+                        }
+                    }
+                }
+                params.push(GenericParam::Type { name, bounds });
             }
 
             if self.check(&TokenKind::Gt) {
@@ -85,7 +176,7 @@ impl Parser {
         }
 
         // Check for integer literal: 3, 42
-        if let TokenKind::IntLit(n) = self.current_kind().clone() {
+        if let TokenKind::IntLit(n) = *self.current_kind() {
             let span = self.current().span;
             self.advance();
             return Ok(GenericArg::Const(Expr::Literal(Literal::Int(n, span))));
@@ -94,7 +185,8 @@ impl Parser {
         // Check for identifier - could be type or const param
         // Heuristic: if followed by < or ::, it's a type; if it's uppercase single letter, assume type param
         // Otherwise, try to parse as type first
-        if let TokenKind::Ident(name) = self.current_kind().clone() {
+        if let TokenKind::Ident(symbol) = *self.current_kind() {
+            let name = self.source.db().resolve(symbol);
             // Look ahead to determine if this is a type or const
             if let Some(next) = self.peek() {
                 match &next.kind {
@@ -111,7 +203,7 @@ impl Parser {
                             // Likely a const param name like SIZE, N, M
                             let span = self.current().span;
                             self.advance();
-                            return Ok(GenericArg::Const(Expr::Ident(Ident { name: name.clone(), span })));
+                            return Ok(GenericArg::Const(Expr::Ident(Ident { name: self.text(symbol), span })));
                         }
                         // Treat as type
                         let ty = self.parse_type()?;
@@ -130,9 +222,28 @@ impl Parser {
     // ==================== Helper Methods ====================
 
     fn current(&self) -> &Token {
-        self.tokens.get(self.pos).unwrap_or_else(|| {
-            self.tokens.last().expect("tokens should not be empty")
-        })
+        self.at(self.pos)
+    }
+
+    /// Token at stream index `index`, which is within the window or past Eof
+    fn at(&self, index: usize) -> &Token {
+        &self.window[(index - self.first).min(self.window.len() - 1)]
+    }
+
+    /// Span of the last consumed token
+    fn prev_span(&self) -> Span {
+        self.at(self.pos.saturating_sub(1)).span
+    }
+
+    /// Run `f` one nesting level deeper, failing once the limit is exceeded
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth >= self.max_nesting {
+            return Err(Error::NestingTooDeep { limit: self.max_nesting, span: self.current().span });
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
     }
 
     fn current_kind(&self) -> &TokenKind {
@@ -140,13 +251,23 @@ impl Parser {
     }
 
     fn peek(&self) -> Option<&Token> {
-        self.tokens.get(self.pos + 1)
+        self.peek_nth(1)
+    }
+
+    /// The token `n` (at most `LOOKAHEAD`) after the current one
+    fn peek_nth(&self, n: usize) -> Option<&Token> {
+        self.window.get(self.pos + n - self.first)
     }
 
     fn advance(&mut self) -> Token {
-        let token = self.current().clone();
-        if self.pos < self.tokens.len() - 1 {
+        let token = *self.current();
+        if self.peek().is_some() {
             self.pos += 1;
+            if self.pos - self.first > LOOKBEHIND {
+                self.window.pop_front();
+                self.first += 1;
+            }
+            self.fill();
         }
         token
     }
@@ -165,12 +286,27 @@ impl Parser {
         } else {
             Err(Error::UnexpectedToken {
                 expected: format!("{:?}", expected),
-                got: format!("{:?}", self.current_kind()),
+                got: self.describe(self.current_kind()),
                 span: self.current().span,
             })
         }
     }
 
+    /// Owned text of an interned identifier or literal
+    fn text(&self, symbol: Symbol) -> String {
+        self.source.db().resolve(symbol).to_string()
+    }
+
+    /// `{:?}` of a token kind with its symbol resolved, for diagnostics
+    fn describe(&self, kind: &TokenKind) -> String {
+        match *kind {
+            TokenKind::Ident(s) => format!("Ident({:?})", self.source.db().resolve(s)),
+            TokenKind::StringLit(s) => format!("StringLit({:?})", self.source.db().resolve(s)),
+            TokenKind::Lifetime(s) => format!("Lifetime({:?})", self.source.db().resolve(s)),
+            _ => format!("{:?}", kind),
+        }
+    }
+
     fn consume(&mut self, kind: &TokenKind) -> bool {
         if self.check(kind) {
             self.advance();
@@ -184,6 +320,20 @@ impl Parser {
 
     /// Parse a complete program
     pub fn parse_program(&mut self) -> Result<Program> {
+        let program = self.parse_items();
+        // A malformed token is reported ahead of anything the parser made of it
+        if let TokenSource::Lexer(lexer) = &mut self.source {
+            if program.is_err() {
+                while lexer.next_token().kind != TokenKind::Eof {}
+            }
+            if let Some(error) = lexer.take_errors().into_iter().next() {
+                return Err(error);
+            }
+        }
+        program
+    }
+
+    fn parse_items(&mut self) -> Result<Program> {
         // Parse inner attributes: #![no_std], #![no_main], etc.
         let mut inner_attrs = Vec::new();
         while self.check(&TokenKind::Hash) {
@@ -214,17 +364,21 @@ impl Parser {
         let name = self.parse_ident()?;
         
         // Inner attributes like #![no_std] don't need args
+        let args = self.parse_attribute_args()?;
         self.expect(TokenKind::RBracket)?;
         
         Ok(Annotation {
             name,
-            args: Vec::new(),
-            span: start_span.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+            args,
+            span: start_span.merge(&self.prev_span()),
         })
     }
 
     /// Parse a top-level item
     fn parse_item(&mut self) -> Result<Item> {
+        // Doc comments may come before or after the attributes
+        let mut doc = self.take_docs();
+
         // Collect attributes (#[...])
         let mut attributes = Vec::new();
         while self.check(&TokenKind::Hash) {
@@ -235,18 +389,41 @@ impl Parser {
         while self.check(&TokenKind::At) {
             attributes.push(self.parse_annotation()?);
         }
-        
+        doc.extend(self.take_docs());
+
+        let mut item = self.parse_item_kind(attributes)?;
+        match &mut item {
+            Item::Function(func) => func.doc = doc,
+            Item::Struct(def) => def.doc = doc,
+            Item::Enum(def) => def.doc = doc,
+            _ => {}
+        }
+        Ok(item)
+    }
+
+    /// Doc comment lines directly before the current token
+    fn take_docs(&mut self) -> Vec<String> {
+        self.docs.remove(&self.pos).unwrap_or_default()
+    }
+
+    /// Parse the item after its attributes
+    fn parse_item_kind(&mut self, attributes: Vec<Annotation>) -> Result<Item> {
         // Handle pub modifier - peek ahead to see what comes next
         match self.current_kind() {
             TokenKind::Pub => {
                 // pub can precede fn, struct, enum, impl, interface, etc.
                 if let Some(next) = self.peek() {
                     match &next.kind {
-                        TokenKind::Fn => Ok(Item::Function(self.parse_function()?)),
+                        TokenKind::Fn => {
+                            let mut func = self.parse_function()?;
+                            func.annotations = attributes;
+                            Ok(Item::Function(func))
+                        }
                         TokenKind::Struct => Ok(Item::Struct(self.parse_struct_with_attrs(attributes)?)),
+                        TokenKind::Union => Ok(Item::Union(self.parse_union_def(&attributes)?)),
                         TokenKind::Enum => {
                             self.advance(); // consume 'pub'
-                            Ok(Item::Enum(self.parse_enum()?))
+                            Ok(Item::Enum(self.parse_enum(&attributes)?))
                         },
                         TokenKind::Impl => {
                             self.advance(); // consume 'pub'
@@ -257,8 +434,8 @@ impl Parser {
                             Ok(Item::Interface(self.parse_interface()?))
                         },
                         _ => Err(Error::UnexpectedToken {
-                            expected: "fn, struct, enum, impl or interface after pub".to_string(),
-                            got: format!("{:?}", next.kind),
+                            expected: "fn, struct, union, enum, impl or interface after pub".to_string(),
+                            got: self.describe(&next.kind),
                             span: next.span,
                         }),
                     }
@@ -276,14 +453,14 @@ impl Parser {
                 Ok(Item::Function(func))
             }
             TokenKind::Struct => Ok(Item::Struct(self.parse_struct_with_attrs(attributes)?)),
-            TokenKind::Enum => Ok(Item::Enum(self.parse_enum()?)),
+            TokenKind::Enum => Ok(Item::Enum(self.parse_enum(&attributes)?)),
             TokenKind::Impl => Ok(Item::Impl(self.parse_impl()?)),
             TokenKind::Interface => Ok(Item::Interface(self.parse_interface()?)),
             TokenKind::Const => Ok(Item::Const(self.parse_const()?)),
             // Phase 8: System features
             TokenKind::Extern => Ok(Item::Extern(self.parse_extern_block()?)),
-            TokenKind::Static => Ok(Item::Static(self.parse_static_item()?)),
-            TokenKind::Union => Ok(Item::Union(self.parse_union_def()?)),
+            TokenKind::Static => Ok(Item::Static(self.parse_static_item(&attributes)?)),
+            TokenKind::Union => Ok(Item::Union(self.parse_union_def(&attributes)?)),
             // P1 Phase B: Trait and Type alias
             TokenKind::Trait => Ok(Item::Trait(self.parse_trait_def()?)),
             TokenKind::Type => Ok(Item::TypeAlias(self.parse_type_alias()?)),
@@ -293,7 +470,7 @@ impl Parser {
             TokenKind::Mod => Ok(Item::Module(self.parse_mod()?)),
             _ => Err(Error::UnexpectedToken {
                 expected: "item (fn, struct, enum, impl, interface, const, extern, static, union, trait, type, use)".to_string(),
-                got: format!("{:?}", self.current_kind()),
+                got: self.describe(self.current_kind()),
                 span: self.current().span,
             }),
         }
@@ -306,7 +483,16 @@ impl Parser {
         self.expect(TokenKind::LBracket)?;
         
         let name = self.parse_ident()?;
+        let args = self.parse_attribute_args()?;
+        
+        let end_token = self.expect(TokenKind::RBracket)?;
+        let span = start_span.merge(&end_token.span);
         
+        Ok(Annotation { name, args, span })
+    }
+
+    /// Optional `(arg, ...)` after an attribute name
+    fn parse_attribute_args(&mut self) -> Result<Vec<Expr>> {
         let mut args = Vec::new();
         if self.check(&TokenKind::LParen) {
             self.advance(); // consume '('
@@ -318,17 +504,28 @@ impl Parser {
             }
             self.expect(TokenKind::RParen)?;
         }
-        
-        let end_token = self.expect(TokenKind::RBracket)?;
-        let span = start_span.merge(&end_token.span);
-        
-        Ok(Annotation { name, args, span })
+        Ok(args)
+    }
+
+    /// Optional `#[bits(N)]` before a struct field
+    fn parse_field_bits(&mut self) -> Result<Option<usize>> {
+        let mut bits = None;
+        while self.check(&TokenKind::Hash) {
+            let attr = self.parse_attribute()?;
+            match (attr.name.name.as_str(), attr.args.as_slice()) {
+                ("bits", [Expr::Literal(Literal::Int(n, _))]) if *n >= 0 => bits = Some(*n as usize),
+                ("bits", _) => return Err(Error::Expected("bit width in `#[bits(N)]`".into(), attr.span)),
+                _ => return Err(Error::Expected("`#[bits(N)]` on a struct field".into(), attr.span)),
+            }
+        }
+        Ok(bits)
     }
 
     /// Parse a function definition
     /// Syntax: fn name(params) -> type [requires ..., ensures ...] effect[...] { body }
     fn parse_function(&mut self) -> Result<Function> {
         let start = self.current().span;
+        let doc = self.take_docs();
         
         // Check for pub
         let is_pub = self.consume(&TokenKind::Pub);
@@ -337,18 +534,17 @@ impl Parser {
 
         let name = self.parse_ident()?;
 
-        let type_params = if self.check(&TokenKind::Lt) {
-            // parse_generic_params returns Vec<GenericParam>, extract only Type params as Ident
+        let generic_params = if self.check(&TokenKind::Lt) {
             self.parse_generic_params()?
-                .into_iter()
-                .filter_map(|p| match p {
-                    GenericParam::Type(ident) => Some(ident),
-                    GenericParam::Const { .. } => None,
-                })
-                .collect()
         } else {
             Vec::new()
         };
+        let type_params = generic_params.iter()
+            .filter_map(|p| match p {
+                GenericParam::Type { name, .. } => Some(name.clone()),
+                GenericParam::Const { .. } => None,
+            })
+            .collect();
 
         self.expect(TokenKind::LParen)?;
         let params = self.parse_params()?;
@@ -378,11 +574,13 @@ impl Parser {
             params,
             ret_type,
             body,
-            span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+            span: start.merge(&self.prev_span()),
             annotations: Vec::new(),
             contracts,
             effects,
             is_pub,
+            generic_params,
+            doc,
         })
     }
     
@@ -410,14 +608,14 @@ impl Parser {
                 _ => {
                     return Err(Error::UnexpectedToken {
                         expected: "requires, ensures, or invariant".to_string(),
-                        got: format!("{:?}", self.current_kind()),
+                        got: self.describe(self.current_kind()),
                         span: self.current().span,
                     });
                 }
             };
             
             let condition = self.parse_expr()?;
-            let end_span = self.tokens[self.pos.saturating_sub(1)].span;
+            let end_span = self.prev_span();
             
             contracts.push(Contract {
                 kind,
@@ -444,8 +642,8 @@ impl Parser {
             let mut effects = Vec::new();
             
             while !self.check(&TokenKind::RBracket) && !self.is_at_end() {
-                if let TokenKind::Ident(ref name) = self.current_kind().clone() {
-                    let effect = match name.as_str() {
+                if let TokenKind::Ident(name) = *self.current_kind() {
+                    let effect = match self.source.db().resolve(name) {
                         "read" => Effect::Read,
                         "write" => Effect::Write,
                         "io" => Effect::IO,
@@ -454,7 +652,7 @@ impl Parser {
                         _ => {
                             return Err(Error::UnexpectedToken {
                                 expected: "read, write, io, alloc, or panic".to_string(),
-                                got: name.clone(),
+                                got: self.text(name),
                                 span: self.current().span,
                             });
                         }
@@ -494,7 +692,12 @@ impl Parser {
 
     fn parse_param(&mut self) -> Result<Param> {
         let start = self.current().span;
-        let name = self.parse_ident()?;
+        let (name, pattern) = if self.at_destructuring_pattern() {
+            let pattern = self.parse_pattern()?;
+            (Self::hidden_binding(&pattern), Some(pattern))
+        } else {
+            (self.parse_ident()?, None)
+        };
         self.expect(TokenKind::Colon)?;
 
         let ownership = if self.consume(&TokenKind::Own) {
@@ -513,17 +716,18 @@ impl Parser {
             name,
             ownership,
             ty,
-            span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+            pattern,
+            span: start.merge(&self.prev_span()),
         })
     }
 
     fn parse_ident(&mut self) -> Result<Ident> {
-        let token = self.current().clone();
-        match &token.kind {
+        let token = *self.current();
+        match token.kind {
             TokenKind::Ident(name) => {
                 self.advance();
                 Ok(Ident {
-                    name: name.clone(),
+                    name: self.text(name),
                     span: token.span,
                 })
             }
@@ -556,11 +760,15 @@ impl Parser {
         Ok(Annotation {
             name,
             args,
-            span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+            span: start.merge(&self.prev_span()),
         })
     }
 
     fn parse_type(&mut self) -> Result<Type> {
+        self.nested(Self::parse_type_inner)
+    }
+
+    fn parse_type_inner(&mut self) -> Result<Type> {
         let start = self.current().span;
 
         // Ownership modifiers: own T, ref T, mut T, shared T
@@ -570,7 +778,7 @@ impl Parser {
             return Ok(Type::Owned {
                 inner: Box::new(inner),
                 ownership: Ownership::Own,
-                span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+                span: start.merge(&self.prev_span()),
             });
         }
         
@@ -579,24 +787,32 @@ impl Parser {
             return Ok(Type::Owned {
                 inner: Box::new(inner),
                 ownership: Ownership::Shared,
-                span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+                span: start.merge(&self.prev_span()),
             });
         }
 
         // Pointer type
         if self.consume(&TokenKind::Star) {
+            // Volatile pointer: *volatile T
+            if self.consume(&TokenKind::Volatile) {
+                let inner = self.parse_type()?;
+                return Ok(Type::Volatile(
+                    Box::new(inner),
+                    start.merge(&self.prev_span()),
+                ));
+            }
             let inner = self.parse_type()?;
             return Ok(Type::Pointer(
                 Box::new(inner),
-                start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+                start.merge(&self.prev_span()),
             ));
         }
 
         // Reference type with optional lifetime: &T or &'a T or &'a mut T
         if self.consume(&TokenKind::And) {
             // Check for optional lifetime: &'a
-            let lifetime = if let TokenKind::Lifetime(name) = self.current_kind() {
-                let lt = name.clone();
+            let lifetime = if let TokenKind::Lifetime(name) = *self.current_kind() {
+                let lt = self.text(name);
                 self.advance();
                 Some(lt)
             } else {
@@ -608,7 +824,7 @@ impl Parser {
                 mutable,
                 lifetime,
                 inner: Box::new(inner),
-                span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+                span: start.merge(&self.prev_span()),
             });
         }
 
@@ -628,21 +844,46 @@ impl Parser {
                 return Ok(Type::Array {
                     elem: Box::new(elem),
                     size,
-                    span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+                    span: start.merge(&self.prev_span()),
                 });
             } else {
                 self.expect(TokenKind::RBracket)?;
                 return Ok(Type::Slice(
                     Box::new(elem),
-                    start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+                    start.merge(&self.prev_span()),
                 ));
             }
         }
 
+        // Function type: fn(A, B) -> R, optionally `pure` or `effect[...]`
+        if self.consume(&TokenKind::Fn) {
+            self.expect(TokenKind::LParen)?;
+            let mut params = Vec::new();
+            while !self.check(&TokenKind::RParen) && !self.is_at_end() {
+                params.push(self.parse_type()?);
+                if !self.consume(&TokenKind::Comma) {
+                    break;
+                }
+            }
+            self.expect(TokenKind::RParen)?;
+            let ret = if self.consume(&TokenKind::Arrow) {
+                self.parse_type()?
+            } else {
+                Type::Unit(self.prev_span())
+            };
+            let effects = self.parse_effect_annotation()?;
+            return Ok(Type::Function {
+                params,
+                ret: Box::new(ret),
+                effects: Box::new(effects),
+                span: start.merge(&self.prev_span()),
+            });
+        }
+
         // Unit or tuple
         if self.consume(&TokenKind::LParen) {
             if self.consume(&TokenKind::RParen) {
-                return Ok(Type::Unit(start.merge(&self.tokens[self.pos.saturating_sub(1)].span)));
+                return Ok(Type::Unit(start.merge(&self.prev_span())));
             }
 
             let first = self.parse_type()?;
@@ -660,7 +901,7 @@ impl Parser {
                 self.expect(TokenKind::RParen)?;
                 return Ok(Type::Tuple(
                     types,
-                    start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+                    start.merge(&self.prev_span()),
                 ));
             }
             self.expect(TokenKind::RParen)?;
@@ -668,9 +909,9 @@ impl Parser {
         }
 
         // Named type or Generic type
-        if let TokenKind::Ident(name) = self.current_kind().clone() {
+        if let TokenKind::Ident(name) = *self.current_kind() {
             self.advance();
-            let ty_name = name;
+            let ty_name = self.text(name);
 
             // Parse generic arguments if present: Foo<i32, String> or Foo<i32, 3, 3>
             if self.consume(&TokenKind::Lt) {
@@ -695,10 +936,22 @@ impl Parser {
                      })
                      .collect();
 
-                 return Ok(Type::Generic(ty_name, inner_types, start.merge(&self.tokens[self.pos.saturating_sub(1)].span)));
+                 return Ok(Type::Generic(ty_name, inner_types, start.merge(&self.prev_span())));
+            }
+
+            // Associated type path: Self::Item
+            if self.check(&TokenKind::ColonColon) {
+                if let Some(TokenKind::Ident(member)) = self.peek().map(|t| t.kind) {
+                    self.advance(); // consume '::'
+                    self.advance(); // consume member name
+                    return Ok(Type::Named(
+                        format!("{}::{}", ty_name, self.source.db().resolve(member)),
+                        start.merge(&self.prev_span()),
+                    ));
+                }
             }
 
-            return Ok(Type::Named(ty_name, start.merge(&self.tokens[self.pos.saturating_sub(1)].span)));
+            return Ok(Type::Named(ty_name, start.merge(&self.prev_span())));
         }
 
         Err(Error::ExpectedType { span: self.current().span })
@@ -717,7 +970,35 @@ impl Parser {
 
         Ok(Block {
             stmts,
-            span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+            span: start.merge(&self.prev_span()),
+        })
+    }
+
+    /// `do { stmts; yield value }`, where the yield may be a final expression
+    fn parse_do_block(&mut self) -> Result<Expr> {
+        let start = self.current().span;
+        self.expect(TokenKind::Do)?;
+        self.expect(TokenKind::LBrace)?;
+        let mut stmts = Vec::new();
+        let accumulator = loop {
+            if self.consume(&TokenKind::Yield) {
+                let value = self.parse_expr()?;
+                self.consume(&TokenKind::Semicolon);
+                break value;
+            }
+            if self.check(&TokenKind::RBrace) || self.is_at_end() {
+                match stmts.pop() {
+                    Some(Stmt::Expr(value)) => break value,
+                    _ => return Err(Error::Expected("`yield` ending the do block".into(), self.current().span)),
+                }
+            }
+            stmts.push(self.parse_stmt()?);
+        };
+        self.expect(TokenKind::RBrace)?;
+        Ok(Expr::DoBlock {
+            stmts,
+            accumulator: Box::new(accumulator),
+            span: start.merge(&self.prev_span()),
         })
     }
 
@@ -725,11 +1006,7 @@ impl Parser {
         match self.current_kind() {
             TokenKind::Let => self.parse_let_stmt(),
             TokenKind::Return => self.parse_return_stmt(),
-            TokenKind::Break => {
-                let span = self.current().span;
-                self.advance();
-                Ok(Stmt::Break { span })
-            }
+            TokenKind::Break => self.parse_break_stmt(),
             TokenKind::Continue => {
                 let span = self.current().span;
                 self.advance();
@@ -740,11 +1017,9 @@ impl Parser {
                 self.advance();
                 Ok(Stmt::Empty { span })
             }
-            _ => {
-                let expr = self.parse_expr()?;
-                self.consume(&TokenKind::Semicolon);
-                Ok(Stmt::Expr(expr))
-            }
+            // A `;` after the expression is left to parse as `Stmt::Empty`,
+            // so a block ending in `expr;` has no tail value
+            _ => Ok(Stmt::Expr(self.parse_expr()?)),
         }
     }
 
@@ -753,7 +1028,12 @@ impl Parser {
         self.expect(TokenKind::Let)?;
 
         let mutable = self.consume(&TokenKind::Mut);
-        let name = self.parse_ident()?;
+        let (name, pattern) = if !mutable && self.at_destructuring_pattern() {
+            let pattern = self.parse_pattern()?;
+            (Self::hidden_binding(&pattern), Some(Box::new(pattern)))
+        } else {
+            (self.parse_ident()?, None)
+        };
 
         // P5.1: Type annotation is REQUIRED for let statements
         // AetherLang enforces explicit types to reduce AI hallucinations
@@ -777,7 +1057,8 @@ impl Parser {
             mutable,
             ty,
             value,
-            span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+            pattern,
+            span: start.merge(&self.prev_span()),
         })
     }
 
@@ -796,7 +1077,26 @@ impl Parser {
 
         Ok(Stmt::Return {
             value,
-            span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+            span: start.merge(&self.prev_span()),
+        })
+    }
+
+    fn parse_break_stmt(&mut self) -> Result<Stmt> {
+        let start = self.current().span;
+        self.expect(TokenKind::Break)?;
+
+        let break_value = if !self.check(&TokenKind::Semicolon)
+            && !self.check(&TokenKind::RBrace)
+            && !self.is_at_end()
+        {
+            Some(self.parse_expr()?)
+        } else {
+            None
+        };
+
+        Ok(Stmt::Break {
+            break_value,
+            span: start.merge(&self.prev_span()),
         })
     }
 
@@ -808,10 +1108,14 @@ impl Parser {
 
     /// Parse expression with binding power (Pratt parsing)
     fn parse_expr_bp(&mut self, min_bp: u8) -> Result<Expr> {
+        self.nested(|p| p.parse_expr_bp_inner(min_bp))
+    }
+
+    fn parse_expr_bp_inner(&mut self, min_bp: u8) -> Result<Expr> {
         let mut left = self.parse_primary()?;
 
         loop {
-            let op_token = self.current().clone();
+            let op_token = *self.current();
             
             // Handle Call: expr(args)
             if op_token.kind == TokenKind::LParen {
@@ -890,7 +1194,7 @@ impl Parser {
         
         // Calculate span
         let start_span = func.span();
-        let end_span = self.tokens[self.pos.saturating_sub(1)].span;
+        let end_span = self.prev_span();
 
         Ok(Expr::Call {
             func: Box::new(func),
@@ -899,26 +1203,103 @@ impl Parser {
         })
     }
 
+    /// Parse a layout builtin after its name has been consumed.
+    /// Returns None if `ident` is not used as a layout builtin.
+    fn parse_layout_builtin(&mut self, ident: &Ident) -> Result<Option<Expr>> {
+        match ident.name.as_str() {
+            "size_of" | "align_of" => {
+                // Require turbofish: name::<T>()
+                if !self.check(&TokenKind::ColonColon)
+                    || !matches!(self.peek().map(|t| &t.kind), Some(TokenKind::Lt))
+                {
+                    return Ok(None);
+                }
+                self.advance(); // ::
+                self.advance(); // <
+                let ty = self.parse_type()?;
+                self.expect(TokenKind::Gt)?;
+                self.expect(TokenKind::LParen)?;
+                self.expect(TokenKind::RParen)?;
+                let span = ident.span.merge(&self.prev_span());
+                if ident.name == "size_of" {
+                    Ok(Some(Expr::SizeOf { ty, span }))
+                } else {
+                    Ok(Some(Expr::AlignOf { ty, span }))
+                }
+            }
+            "offset_of" if self.check(&TokenKind::LParen) => {
+                self.advance(); // (
+                let ty = self.parse_type()?;
+                self.expect(TokenKind::Comma)?;
+                let field = self.parse_ident()?;
+                self.expect(TokenKind::RParen)?;
+                let span = ident.span.merge(&self.prev_span());
+                Ok(Some(Expr::OffsetOf { ty, field, span }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Parse a typed allocation after `new`/`new_array` has been consumed.
+    /// Returns None if `ident` is an ordinary name.
+    fn parse_new(&mut self, ident: &Ident) -> Result<Option<Expr>> {
+        let turbofish = self.check(&TokenKind::ColonColon)
+            && matches!(self.peek().map(|t| &t.kind), Some(TokenKind::Lt));
+        match ident.name.as_str() {
+            "new" | "new_array" if turbofish => {
+                self.advance(); // ::
+                self.advance(); // <
+                let ty = self.parse_type()?;
+                self.expect(TokenKind::Gt)?;
+                self.expect(TokenKind::LParen)?;
+                let count = if ident.name == "new_array" {
+                    Some(Box::new(self.parse_expr()?))
+                } else {
+                    None
+                };
+                self.expect(TokenKind::RParen)?;
+                let span = ident.span.merge(&self.prev_span());
+                Ok(Some(Expr::New { ty, count, init: None, span }))
+            }
+            // `new Point { x: 1 }`: only a struct literal may follow
+            "new" if matches!(self.current().kind, TokenKind::Ident(_))
+                && matches!(self.peek().map(|t| &t.kind), Some(TokenKind::LBrace)) => {
+                let init = self.parse_primary()?;
+                let Expr::StructLit { name, span, .. } = &init else {
+                    return Err(Error::UnexpectedToken {
+                        expected: "struct literal after 'new'".to_string(),
+                        got: format!("{:?}", init),
+                        span: init.span(),
+                    });
+                };
+                let ty = Type::Named(name.name.clone(), name.span);
+                let span = ident.span.merge(span);
+                Ok(Some(Expr::New { ty, count: None, init: Some(Box::new(init)), span }))
+            }
+            _ => Ok(None),
+        }
+    }
+
     fn parse_primary(&mut self) -> Result<Expr> {
-        let token = self.current().clone();
+        let token = *self.current();
 
-        let expr = match &token.kind {
+        let expr = match token.kind {
             // Literals
             TokenKind::IntLit(n) => {
                 self.advance();
-                Expr::Literal(Literal::Int(*n, token.span))
+                Expr::Literal(Literal::Int(n, token.span))
             }
             TokenKind::FloatLit(n) => {
                 self.advance();
-                Expr::Literal(Literal::Float(*n, token.span))
+                Expr::Literal(Literal::Float(n, token.span))
             }
             TokenKind::StringLit(s) => {
                 self.advance();
-                Expr::Literal(Literal::String(s.clone(), token.span))
+                Expr::Literal(Literal::String(self.text(s), token.span))
             }
             TokenKind::CharLit(c) => {
                 self.advance();
-                Expr::Literal(Literal::Char(*c, token.span))
+                Expr::Literal(Literal::Char(c, token.span))
             }
             TokenKind::True => {
                 self.advance();
@@ -932,16 +1313,23 @@ impl Parser {
             // Identifier or struct literal
             TokenKind::Ident(_) => {
                 let ident = self.parse_ident()?;
+
+                // Layout builtins: size_of::<T>(), align_of::<T>(), offset_of(T, field)
+                if let Some(expr) = self.parse_layout_builtin(&ident)? {
+                    return Ok(expr);
+                }
+                // Typed allocation: new::<T>(), new_array::<T>(n), new S { .. }
+                if let Some(expr) = self.parse_new(&ident)? {
+                    return Ok(expr);
+                }
                 
                 // Check if this is a struct literal: TypeName { field: value, ... }
                 // Use lookahead: only parse as struct lit if { is followed by ident:
                 // This avoids ambiguity with `if cond { ... }`
                 let is_struct_lit = if self.check(&TokenKind::LBrace) {
                     // Look ahead: check if next token after { is Ident followed by Colon
-                    self.pos + 1 < self.tokens.len() && 
-                    matches!(&self.tokens[self.pos + 1].kind, TokenKind::Ident(_)) &&
-                    self.pos + 2 < self.tokens.len() &&
-                    matches!(&self.tokens[self.pos + 2].kind, TokenKind::Colon)
+                    matches!(self.peek_nth(1), Some(Token { kind: TokenKind::Ident(_), .. })) &&
+                    matches!(self.peek_nth(2), Some(Token { kind: TokenKind::Colon, .. }))
                 } else {
                     false
                 };
@@ -971,15 +1359,30 @@ impl Parser {
                     });
                 }
 
-                // Check for Path: Ident::Ident
+                // Check for Path: Ident::Ident, possibly ending in a turbofish (Ident::<4>)
                 if self.check(&TokenKind::ColonColon) {
                     let mut segments = vec![ident];
+                    let mut generic_args = Vec::new();
+                    let mut end = segments[0].span;
                     while self.consume(&TokenKind::ColonColon) {
-                        segments.push(self.parse_ident()?);
+                        if self.consume(&TokenKind::Lt) {
+                            loop {
+                                generic_args.push(self.parse_generic_arg()?);
+                                if !self.consume(&TokenKind::Comma) {
+                                    break;
+                                }
+                            }
+                            end = self.expect(TokenKind::Gt)?.span;
+                            break;
+                        }
+                        let segment = self.parse_ident()?;
+                        end = segment.span;
+                        segments.push(segment);
                     }
-                    let span = segments[0].span.merge(&segments.last().unwrap().span);
+                    let span = segments[0].span.merge(&end);
                     return Ok(Expr::Path {
                         segments,
+                        generic_args,
                         span,
                     });
                 }
@@ -995,7 +1398,7 @@ impl Parser {
                     self.advance();
                     return Ok(Expr::Tuple {
                         elements: Vec::new(),
-                        span: token.span.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+                        span: token.span.merge(&self.prev_span()),
                     });
                 }
 
@@ -1011,7 +1414,7 @@ impl Parser {
                     self.expect(TokenKind::RParen)?;
                     return Ok(Expr::Tuple {
                         elements,
-                        span: token.span.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+                        span: token.span.merge(&self.prev_span()),
                     });
                 }
 
@@ -1039,6 +1442,27 @@ impl Parser {
             }
 
             // While
+            // `let x = e` as an expression, e.g. `while (let c = next()) != 0 { .. }`
+            TokenKind::Let => {
+                self.advance();
+                let mutable = self.consume(&TokenKind::Mut);
+                let name = self.parse_ident()?;
+                let ty = if self.consume(&TokenKind::Colon) {
+                    Some(self.parse_type()?)
+                } else {
+                    None
+                };
+                self.expect(TokenKind::Eq)?;
+                let value = self.parse_expr()?;
+                Expr::Let {
+                    span: token.span.merge(&value.span()),
+                    name,
+                    mutable,
+                    ty,
+                    value: Box::new(value),
+                }
+            }
+
             TokenKind::While => {
                 self.advance();
                 let cond = self.parse_expr()?;
@@ -1068,7 +1492,7 @@ impl Parser {
             // Unary operators
             TokenKind::Minus => {
                 self.advance();
-                let expr = self.parse_primary()?;
+                let expr = self.nested(Self::parse_primary)?;
                 Expr::Unary {
                     op: UnOp::Neg,
                     span: token.span.merge(&expr.span()),
@@ -1077,7 +1501,7 @@ impl Parser {
             }
             TokenKind::Not => {
                 self.advance();
-                let expr = self.parse_primary()?;
+                let expr = self.nested(Self::parse_primary)?;
                 Expr::Unary {
                     op: UnOp::Not,
                     span: token.span.merge(&expr.span()),
@@ -1086,7 +1510,7 @@ impl Parser {
             }
             TokenKind::Star => {
                 self.advance();
-                let expr = self.parse_primary()?;
+                let expr = self.nested(Self::parse_primary)?;
                 Expr::Deref {
                     span: token.span.merge(&expr.span()),
                     expr: Box::new(expr),
@@ -1095,7 +1519,7 @@ impl Parser {
             TokenKind::And => {
                 self.advance();
                 let mutable = self.consume(&TokenKind::Mut);
-                let expr = self.parse_primary()?;
+                let expr = self.nested(Self::parse_primary)?;
                 Expr::Ref {
                     mutable,
                     span: token.span.merge(&expr.span()),
@@ -1118,8 +1542,8 @@ impl Parser {
                         
                         match key.name.as_str() {
                             "reason" => {
-                                if let TokenKind::StringLit(s) = self.current_kind() {
-                                    reason = Some(s.clone());
+                                if let TokenKind::StringLit(s) = *self.current_kind() {
+                                    reason = Some(self.text(s));
                                     self.advance();
                                 }
                             }
@@ -1153,8 +1577,8 @@ impl Parser {
                 self.expect(TokenKind::LParen)?;
                 
                 // Parse template string
-                let template = if let TokenKind::StringLit(s) = self.current_kind() {
-                    let t = s.clone();
+                let template = if let TokenKind::StringLit(s) = *self.current_kind() {
+                    let t = self.text(s);
                     self.advance();
                     t
                 } else {
@@ -1166,8 +1590,8 @@ impl Parser {
                     let ident_str = if let TokenKind::In = self.current_kind() {
                         self.advance();
                         "in".to_string()
-                    } else if let TokenKind::Ident(name) = self.current_kind() {
-                        let s = name.clone();
+                    } else if let TokenKind::Ident(name) = *self.current_kind() {
+                        let s = self.text(name);
                         self.advance();
                         s
                     } else {
@@ -1179,18 +1603,18 @@ impl Parser {
                         "out" => AsmOperandKind::Output,
                         "inout" => AsmOperandKind::InOut,
                         "clobber" => AsmOperandKind::Clobber,
-                        _ => return Err(Error::Expected("asm operand type (in/out/inout/clobber)".into(), self.tokens[self.pos.saturating_sub(1)].span)),
+                        _ => return Err(Error::Expected("asm operand type (in/out/inout/clobber)".into(), self.prev_span())),
                     };
                     
                     self.expect(TokenKind::LParen)?;
                     
                     // Parse options (register class or clobber string)
-                    let options = if let TokenKind::StringLit(s) = self.current_kind() {
-                        let o = s.clone();
+                    let options = if let TokenKind::StringLit(s) = *self.current_kind() {
+                        let o = self.text(s);
                         self.advance();
                         o
-                    } else if let TokenKind::Ident(i) = &self.current().kind {
-                        let o = i.clone();
+                    } else if let TokenKind::Ident(i) = self.current().kind {
+                        let o = self.text(i);
                         self.advance();
                         o
                     } else {
@@ -1213,7 +1637,7 @@ impl Parser {
                 Expr::Asm {
                     template,
                     operands,
-                    span: token.span.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+                    span: token.span.merge(&self.prev_span()),
                 }
             }
 
@@ -1227,7 +1651,30 @@ impl Parser {
                     self.advance();
                     return Ok(Expr::Array {
                         elements,
-                        span: token.span.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+                        span: token.span.merge(&self.prev_span()),
+                    });
+                }
+
+                // `[do { .. }; N]` computes each element in a loop
+                if self.check(&TokenKind::Do) {
+                    let init = self.parse_do_block()?;
+                    self.expect(TokenKind::Semicolon)?;
+                    let len = match self.current_kind() {
+                        TokenKind::IntLit(n) => {
+                            let n = *n as usize;
+                            self.advance();
+                            n
+                        }
+                        _ => return Err(Error::Expected(
+                            "integer literal for array repeat count".into(),
+                            self.current().span
+                        )),
+                    };
+                    self.expect(TokenKind::RBracket)?;
+                    return Ok(Expr::ArrayFrom {
+                        init: Box::new(init),
+                        len,
+                        span: token.span.merge(&self.prev_span()),
                     });
                 }
 
@@ -1266,7 +1713,7 @@ impl Parser {
 
                 Expr::Array {
                     elements,
-                    span: token.span.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+                    span: token.span.merge(&self.prev_span()),
                 }
             }
 
@@ -1336,7 +1783,7 @@ impl Parser {
                 }
                 self.expect(TokenKind::RParen)?;
                 expr = Expr::Call {
-                    span: expr.span().merge(&self.tokens[self.pos.saturating_sub(1)].span),
+                    span: expr.span().merge(&self.prev_span()),
                     func: Box::new(expr),
                     args,
                 };
@@ -1353,7 +1800,7 @@ impl Parser {
                     }
                     self.expect(TokenKind::RParen)?;
                     expr = Expr::MethodCall {
-                        span: expr.span().merge(&self.tokens[self.pos.saturating_sub(1)].span),
+                        span: expr.span().merge(&self.prev_span()),
                         expr: Box::new(expr),
                         method: field,
                         args,
@@ -1371,14 +1818,14 @@ impl Parser {
                 let index = self.parse_expr()?;
                 self.expect(TokenKind::RBracket)?;
                 expr = Expr::Index {
-                    span: expr.span().merge(&self.tokens[self.pos.saturating_sub(1)].span),
+                    span: expr.span().merge(&self.prev_span()),
                     expr: Box::new(expr),
                     index: Box::new(index),
                 };
             } else if self.consume(&TokenKind::Question) {
                 // Error propagation (try operator)
                 expr = Expr::Try {
-                    span: expr.span().merge(&self.tokens[self.pos.saturating_sub(1)].span),
+                    span: expr.span().merge(&self.prev_span()),
                     expr: Box::new(expr),
                 };
             } else {
@@ -1392,6 +1839,15 @@ impl Parser {
         let start = self.current().span;
         self.expect(TokenKind::If)?;
 
+        // `if let P = e { .. } else { .. }` is sugar for a two-armed match
+        let if_let = if self.consume(&TokenKind::Let) {
+            let pattern = self.parse_or_pattern()?;
+            self.expect(TokenKind::Eq)?;
+            Some(pattern)
+        } else {
+            None
+        };
+
         let cond = self.parse_expr()?;
         let then_block = self.parse_block()?;
 
@@ -1413,6 +1869,24 @@ impl Parser {
 
         let end = else_block.as_ref().map(|b| b.span).unwrap_or(then_block.span);
 
+        if let Some(pattern) = if_let {
+            let span = start.merge(&end);
+            let otherwise = else_block.unwrap_or(Block { stmts: Vec::new(), span: end });
+            return Ok(Expr::Match {
+                expr: Box::new(cond),
+                arms: vec![
+                    MatchArm { span: pattern.span().merge(&then_block.span), pattern, guard: None, body: Expr::Block(then_block) },
+                    MatchArm {
+                        pattern: Pattern::Wildcard { span: otherwise.span },
+                        guard: None,
+                        span: otherwise.span,
+                        body: Expr::Block(otherwise),
+                    },
+                ],
+                span,
+            });
+        }
+
         Ok(Expr::If {
             cond: Box::new(cond),
             then_block,
@@ -1438,13 +1912,18 @@ impl Parser {
         Ok(Expr::Match {
             expr: Box::new(expr),
             arms,
-            span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+            span: start.merge(&self.prev_span()),
         })
     }
 
     fn parse_match_arm(&mut self) -> Result<MatchArm> {
         let start = self.current().span;
-        let pattern = self.parse_pattern()?;
+        let pattern = self.parse_or_pattern()?;
+        let guard = if self.consume(&TokenKind::If) {
+            Some(Box::new(self.parse_expr()?))
+        } else {
+            None
+        };
 
         self.expect(TokenKind::FatArrow)?;
         let body = self.parse_expr()?;
@@ -1453,23 +1932,41 @@ impl Parser {
 
         Ok(MatchArm {
             pattern,
-            guard: None,
+            guard,
             body,
-            span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+            span: start.merge(&self.prev_span()),
         })
     }
 
+    /// `p1 | p2 | ...`; a single pattern is returned as is
+    fn parse_or_pattern(&mut self) -> Result<Pattern> {
+        let first = self.parse_pattern()?;
+        if !self.check(&TokenKind::Or) {
+            return Ok(first);
+        }
+        let mut alternatives = vec![first];
+        while self.consume(&TokenKind::Or) {
+            alternatives.push(self.parse_pattern()?);
+        }
+        let span = alternatives[0].span().merge(&alternatives[alternatives.len() - 1].span());
+        Ok(Pattern::Or { alternatives, span })
+    }
+
     fn parse_pattern(&mut self) -> Result<Pattern> {
-        let token = self.current().clone();
+        self.nested(Self::parse_pattern_inner)
+    }
+
+    fn parse_pattern_inner(&mut self) -> Result<Pattern> {
+        let token = *self.current();
 
-        match &token.kind {
-            TokenKind::Ident(name) if name == "_" => {
+        match token.kind {
+            TokenKind::Ident(sym::UNDERSCORE) => {
                 self.advance();
                 Ok(Pattern::Wildcard { span: token.span })
             }
             TokenKind::Ident(name) => {
                 self.advance();
-                let first_ident = Ident { name: name.clone(), span: token.span };
+                let first_ident = Ident { name: self.text(name), span: token.span };
                 
                 // Check for path pattern (e.g., Color::Red)
                 if self.consume(&TokenKind::ColonColon) {
@@ -1478,16 +1975,7 @@ impl Parser {
                     
                     // Check for optional tuple payload (e.g., Some(x))
                     let fields = if self.consume(&TokenKind::LParen) {
-                        let mut fields = vec![];
-                        if !self.check(&TokenKind::RParen) {
-                            fields.push(self.parse_pattern()?);
-                            while self.consume(&TokenKind::Comma) {
-                                if self.check(&TokenKind::RParen) { break; }
-                                fields.push(self.parse_pattern()?);
-                            }
-                        }
-                        self.expect(TokenKind::RParen)?;
-                        fields
+                        self.parse_pattern_list(TokenKind::RParen)?
                     } else {
                         vec![]
                     };
@@ -1498,25 +1986,48 @@ impl Parser {
                         fields,
                         span: token.span.merge(&end_span),
                     })
+                } else if self.check(&TokenKind::LBrace) {
+                    self.parse_struct_pattern(first_ident)
+                } else if self.consume(&TokenKind::LParen) {
+                    // Variant of the matched enum (e.g., Some(x))
+                    let fields = self.parse_pattern_list(TokenKind::RParen)?;
+                    Ok(Pattern::Variant {
+                        enum_name: None,
+                        variant: first_ident,
+                        fields,
+                        span: token.span.merge(&self.prev_span()),
+                    })
                 } else {
-                    if name == "_" {
-                        Ok(Pattern::Wildcard { span: token.span })
-                    } else {
-                        Ok(Pattern::Binding {
-                            name: first_ident,
-                            mutable: false,
-                            span: token.span,
-                        })
-                    }
+                    Ok(Pattern::Binding {
+                        name: first_ident,
+                        mutable: false,
+                        span: token.span,
+                    })
+                }
+            }
+            TokenKind::Mut => {
+                self.advance();
+                let name = self.parse_ident()?;
+                Ok(Pattern::Binding { span: token.span.merge(&name.span), name, mutable: true })
+            }
+            TokenKind::LParen => {
+                self.advance();
+                let mut elements = self.parse_pattern_list(TokenKind::RParen)?;
+                let span = token.span.merge(&self.prev_span());
+                // `(p)` is just a parenthesized pattern; `(p,)` is a 1-tuple
+                let trailing_comma = matches!(self.at(self.pos.saturating_sub(2)).kind, TokenKind::Comma);
+                if elements.len() == 1 && !trailing_comma {
+                    return Ok(elements.remove(0));
                 }
+                Ok(Pattern::Tuple { elements, span })
             }
             TokenKind::IntLit(n) => {
                 self.advance();
-                Ok(Pattern::Literal(Literal::Int(*n, token.span)))
+                Ok(Pattern::Literal(Literal::Int(n, token.span)))
             }
             TokenKind::StringLit(s) => {
                 self.advance();
-                Ok(Pattern::Literal(Literal::String(s.clone(), token.span)))
+                Ok(Pattern::Literal(Literal::String(self.text(s), token.span)))
             }
             TokenKind::True => {
                 self.advance();
@@ -1530,6 +2041,63 @@ impl Parser {
         }
     }
 
+    /// Comma-separated patterns up to and including `close`
+    fn parse_pattern_list(&mut self, close: TokenKind) -> Result<Vec<Pattern>> {
+        let mut patterns = Vec::new();
+        while !self.check(&close) && !self.is_at_end() {
+            patterns.push(self.parse_or_pattern()?);
+            if !self.consume(&TokenKind::Comma) {
+                break;
+            }
+        }
+        self.expect(close)?;
+        Ok(patterns)
+    }
+
+    /// `Name { field, field: pattern, .. }`, from the `{`
+    fn parse_struct_pattern(&mut self, name: Ident) -> Result<Pattern> {
+        self.expect(TokenKind::LBrace)?;
+        let mut fields = Vec::new();
+        let mut rest = false;
+        while !self.check(&TokenKind::RBrace) && !self.is_at_end() {
+            if self.consume(&TokenKind::DotDot) {
+                rest = true;
+                break;
+            }
+            let field = self.parse_ident()?;
+            // Shorthand `x` binds the field to a variable of the same name
+            let pattern = if self.consume(&TokenKind::Colon) {
+                self.parse_or_pattern()?
+            } else {
+                Pattern::Binding { name: field.clone(), mutable: false, span: field.span }
+            };
+            fields.push((field, pattern));
+            if !self.consume(&TokenKind::Comma) {
+                break;
+            }
+        }
+        let end = self.expect(TokenKind::RBrace)?.span;
+        Ok(Pattern::Struct { span: name.span.merge(&end), name, fields, rest })
+    }
+
+    /// Whether a `let` or parameter starts with a pattern rather than a name
+    fn at_destructuring_pattern(&self) -> bool {
+        match self.current().kind {
+            TokenKind::LParen => true,
+            TokenKind::Ident(_) => matches!(
+                self.peek().map(|t| &t.kind),
+                Some(TokenKind::LBrace | TokenKind::LParen | TokenKind::ColonColon)
+            ),
+            _ => false,
+        }
+    }
+
+    /// Name standing for the whole value a pattern destructures
+    fn hidden_binding(pattern: &Pattern) -> Ident {
+        let span = pattern.span();
+        Ident { name: format!("__pattern{}", span.start), span }
+    }
+
     // ==================== Struct, Enum, Impl, Interface, Const ====================
 
     fn parse_struct(&mut self) -> Result<StructDef> {
@@ -1557,7 +2125,7 @@ impl Parser {
         // Extract type-only params for backward compatibility
         let type_params: Vec<Ident> = generic_params.iter()
             .filter_map(|p| match p {
-                GenericParam::Type(name) => Some(name.clone()),
+                GenericParam::Type { name, .. } => Some(name.clone()),
                 GenericParam::Const { .. } => None,
             })
             .collect();
@@ -1573,9 +2141,9 @@ impl Parser {
 
         let mut fields = Vec::new();
         while !self.check(&TokenKind::RBrace) && !self.is_at_end() {
-            // Allow optional 'pub' visibility on fields (currently ignored)
-            let _is_pub_field = self.consume(&TokenKind::Pub);
-            
+            let bits = self.parse_field_bits()?;
+            let is_pub_field = self.consume(&TokenKind::Pub);
+
             let field_name = self.parse_ident()?;
             self.expect(TokenKind::Colon)?;
             let ty = self.parse_type()?;
@@ -1584,6 +2152,8 @@ impl Parser {
                 span: field_name.span.merge(&ty.span()),
                 name: field_name,
                 ty,
+                is_pub: is_pub_field,
+                bits,
             });
 
             self.consume(&TokenKind::Comma);
@@ -1596,14 +2166,21 @@ impl Parser {
             generic_params,
             type_params,
             fields,
-            span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+            span: start.merge(&self.prev_span()),
             annotations,
             invariants,
             is_pub,
+            doc: Vec::new(),
         })
     }
 
-    fn parse_enum(&mut self) -> Result<EnumDef> {
+    fn parse_enum(&mut self, attributes: &[Annotation]) -> Result<EnumDef> {
+        let repr = attributes.iter()
+            .find(|a| a.name.name == "repr")
+            .and_then(|a| match a.args.first() {
+                Some(Expr::Ident(id)) => Some(id.clone()),
+                _ => None,
+            });
         let start = self.current().span;
         self.expect(TokenKind::Enum)?;
         let name = self.parse_ident()?;
@@ -1617,7 +2194,7 @@ impl Parser {
         // Extract type-only params for backward compatibility
         let type_params: Vec<Ident> = generic_params.iter()
             .filter_map(|p| match p {
-                GenericParam::Type(name) => Some(name.clone()),
+                GenericParam::Type { name, .. } => Some(name.clone()),
                 GenericParam::Const { .. } => None,
             })
             .collect();
@@ -1638,10 +2215,17 @@ impl Parser {
                 self.expect(TokenKind::RParen)?;
             }
 
+            let discriminant = if self.consume(&TokenKind::Eq) {
+                Some(self.parse_expr()?)
+            } else {
+                None
+            };
+
             variants.push(Variant {
                 span: var_name.span,
                 name: var_name,
                 fields,
+                discriminant,
             });
 
             self.consume(&TokenKind::Comma);
@@ -1652,9 +2236,12 @@ impl Parser {
         Ok(EnumDef {
             name,
             variants,
-            span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+            span: start.merge(&self.prev_span()),
+            repr,
+            annotations: attributes.to_vec(),
             generic_params,
             type_params,
+            doc: Vec::new(),
         })
     }
 
@@ -1673,8 +2260,23 @@ impl Parser {
         self.expect(TokenKind::LBrace)?;
 
         let mut methods = Vec::new();
+        let mut associated_types = Vec::new();
         while !self.check(&TokenKind::RBrace) && !self.is_at_end() {
-            methods.push(self.parse_function()?);
+            if self.check(&TokenKind::Type) {
+                associated_types.push(self.parse_associated_type_binding()?);
+            } else {
+                // Doc comments may come before or after the attributes
+                let mut doc = self.take_docs();
+                let mut attributes = Vec::new();
+                while self.check(&TokenKind::Hash) {
+                    attributes.push(self.parse_attribute()?);
+                }
+                let mut method = self.parse_function()?;
+                doc.append(&mut method.doc);
+                method.doc = doc;
+                method.annotations = attributes;
+                methods.push(method);
+            }
         }
 
         self.expect(TokenKind::RBrace)?;
@@ -1683,7 +2285,8 @@ impl Parser {
             target,
             interface,
             methods,
-            span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+            associated_types,
+            span: start.merge(&self.prev_span()),
         })
     }
 
@@ -1695,8 +2298,14 @@ impl Parser {
         self.expect(TokenKind::LBrace)?;
 
         let mut methods = Vec::new();
+        let mut associated_types = Vec::new();
         while !self.check(&TokenKind::RBrace) && !self.is_at_end() {
-            methods.push(self.parse_fn_sig()?);
+            if self.check(&TokenKind::Type) {
+                associated_types.push(self.parse_associated_type()?);
+            } else {
+                methods.push(self.parse_fn_sig()?);
+            }
+            self.consume(&TokenKind::Semicolon);
         }
 
         self.expect(TokenKind::RBrace)?;
@@ -1706,13 +2315,59 @@ impl Parser {
             type_params: Vec::new(),
             methods,
             default_methods: Vec::new(),
-            associated_types: Vec::new(),
+            associated_types,
             supertraits: Vec::new(),
-            span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+            span: start.merge(&self.prev_span()),
             is_pub: false,
         })
     }
 
+    /// Parse an associated type declaration: type Item [: Bound + Bound] [= Default]
+    fn parse_associated_type(&mut self) -> Result<AssociatedType> {
+        let start = self.current().span;
+        self.expect(TokenKind::Type)?;
+        let name = self.parse_ident()?;
+
+        let mut bounds = Vec::new();
+        if self.consume(&TokenKind::Colon) {
+            loop {
+                bounds.push(self.parse_type()?);
+                if !self.consume(&TokenKind::Plus) {
+                    break;
+                }
+            }
+        }
+
+        let default_ty = if self.consume(&TokenKind::Eq) {
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+
+        Ok(AssociatedType {
+            name,
+            default_ty,
+            bounds,
+            span: start.merge(&self.prev_span()),
+        })
+    }
+
+    /// Parse an associated type binding in an impl block: type Item = Type
+    fn parse_associated_type_binding(&mut self) -> Result<AssociatedTypeBinding> {
+        let start = self.current().span;
+        self.expect(TokenKind::Type)?;
+        let name = self.parse_ident()?;
+        self.expect(TokenKind::Eq)?;
+        let ty = self.parse_type()?;
+        self.consume(&TokenKind::Semicolon);
+
+        Ok(AssociatedTypeBinding {
+            name,
+            ty,
+            span: start.merge(&self.prev_span()),
+        })
+    }
+
     fn parse_fn_sig(&mut self) -> Result<FunctionSig> {
         let start = self.current().span;
         self.expect(TokenKind::Fn)?;
@@ -1734,7 +2389,7 @@ impl Parser {
             ret_type,
             effects: EffectSet::default(),
             contracts: Vec::new(),
-            span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+            span: start.merge(&self.prev_span()),
         })
     }
 
@@ -1757,7 +2412,7 @@ impl Parser {
             name,
             ty,
             value,
-            span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+            span: start.merge(&self.prev_span()),
         })
     }
 
@@ -1788,8 +2443,13 @@ impl Parser {
         self.expect(TokenKind::LBrace)?;
 
         let mut methods = Vec::new();
+        let mut associated_types = Vec::new();
         while !self.check(&TokenKind::RBrace) && !self.is_at_end() {
-            methods.push(self.parse_fn_sig()?);
+            if self.check(&TokenKind::Type) {
+                associated_types.push(self.parse_associated_type()?);
+            } else {
+                methods.push(self.parse_fn_sig()?);
+            }
             self.consume(&TokenKind::Semicolon);
         }
 
@@ -1800,9 +2460,9 @@ impl Parser {
             type_params,
             methods,
             default_methods: Vec::new(),
-            associated_types: Vec::new(),
+            associated_types,
             supertraits: Vec::new(),
-            span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+            span: start.merge(&self.prev_span()),
             is_pub: false,
         })
     }
@@ -1838,7 +2498,7 @@ impl Parser {
             type_params,
             ty,
             is_pub: false,
-            span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+            span: start.merge(&self.prev_span()),
         })
     }
     
@@ -1862,13 +2522,19 @@ impl Parser {
             }
             
             // Check for multiple imports: use foo::{a, b}
-            // Simplified: just skip the braces content for now
+            // Only the names are kept; anything else in the braces is skipped
             if self.consume(&TokenKind::LBrace) {
+                let mut names = Vec::new();
                 while !self.check(&TokenKind::RBrace) && !self.is_at_end() {
-                    self.advance();
+                    if matches!(self.current_kind(), TokenKind::Ident(_)) {
+                        let name = self.parse_ident()?;
+                        names.push(UseDecl { span: name.span, path: vec![name], kind: UseKind::Simple, is_pub: false });
+                    } else {
+                        self.advance();
+                    }
                 }
                 self.expect(TokenKind::RBrace)?;
-                kind = UseKind::Simple; // Simplified - treat as simple import
+                kind = UseKind::Group(names);
                 break;
             }
             
@@ -1882,13 +2548,17 @@ impl Parser {
         Ok(UseDecl {
             path,
             kind,
-            span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+            span: start.merge(&self.prev_span()),
             is_pub: false,
         })
     }
     
     /// Parse module declaration: mod name { items } or mod name;
     fn parse_mod(&mut self) -> Result<ModuleDef> {
+        self.nested(Self::parse_mod_inner)
+    }
+
+    fn parse_mod_inner(&mut self) -> Result<ModuleDef> {
         let start = self.current().span;
         self.expect(TokenKind::Mod)?;
         
@@ -1911,7 +2581,7 @@ impl Parser {
         Ok(ModuleDef {
             name,
             items,
-            span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+            span: start.merge(&self.prev_span()),
             is_pub: false,
         })
     }
@@ -1924,14 +2594,33 @@ impl Parser {
         self.expect(TokenKind::Extern)?;
 
         // Parse optional ABI string (e.g., "C", "stdcall")
-        let abi = if let TokenKind::StringLit(s) = self.current_kind() {
-            let abi_str = s.clone();
+        let abi = if let TokenKind::StringLit(s) = *self.current_kind() {
+            let abi_str = self.text(s);
             self.advance();
             Some(abi_str)
         } else {
             None
         };
 
+        // Parse optional library: link = "m"
+        let link = if *self.current_kind() == TokenKind::Ident(sym::LINK) {
+            self.advance();
+            self.expect(TokenKind::Eq)?;
+            match *self.current_kind() {
+                TokenKind::StringLit(lib) => {
+                    self.advance();
+                    Some(self.text(lib))
+                }
+                _ => return Err(Error::UnexpectedToken {
+                    expected: "library name string".to_string(),
+                    got: self.describe(self.current_kind()),
+                    span: self.current().span,
+                }),
+            }
+        } else {
+            None
+        };
+
         self.expect(TokenKind::LBrace)?;
 
         let mut items = Vec::new();
@@ -1943,8 +2632,9 @@ impl Parser {
 
         Ok(ExternBlock {
             abi,
+            link,
             items,
-            span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+            span: start.merge(&self.prev_span()),
         })
     }
 
@@ -2002,7 +2692,7 @@ impl Parser {
                     ret_type,
                     annotations,
                     variadic,
-                    span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+                    span: start.merge(&self.prev_span()),
                 })
             }
             TokenKind::Static => {
@@ -2017,19 +2707,19 @@ impl Parser {
                     name,
                     ty,
                     is_mut,
-                    span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+                    span: start.merge(&self.prev_span()),
                 })
             }
             _ => Err(Error::UnexpectedToken {
                 expected: "fn or static in extern block".to_string(),
-                got: format!("{:?}", self.current_kind()),
+                got: self.describe(self.current_kind()),
                 span: self.current().span,
             }),
         }
     }
 
     /// Parse static variable: static [mut] name: type = value;
-    fn parse_static_item(&mut self) -> Result<StaticDef> {
+    fn parse_static_item(&mut self, attributes: &[Annotation]) -> Result<StaticDef> {
         let start = self.current().span;
         let is_pub = self.consume(&TokenKind::Pub);
         self.expect(TokenKind::Static)?;
@@ -2054,12 +2744,14 @@ impl Parser {
             value,
             is_mut,
             is_pub,
-            span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+            no_mangle: attributes.iter().any(|a| a.name.name == "no_mangle"),
+            span: start.merge(&self.prev_span()),
         })
     }
 
     /// Parse union definition: union Name { fields... }
-    fn parse_union_def(&mut self) -> Result<UnionDef> {
+    fn parse_union_def(&mut self, attributes: &[Annotation]) -> Result<UnionDef> {
+        let repr = Self::parse_repr(attributes)?;
         let start = self.current().span;
         let is_pub = self.consume(&TokenKind::Pub);
         self.expect(TokenKind::Union)?;
@@ -2070,6 +2762,7 @@ impl Parser {
 
         let mut fields = Vec::new();
         while !self.check(&TokenKind::RBrace) && !self.is_at_end() {
+            let is_pub_field = self.consume(&TokenKind::Pub);
             let field_name = self.parse_ident()?;
             self.expect(TokenKind::Colon)?;
             let field_ty = self.parse_type()?;
@@ -2079,6 +2772,8 @@ impl Parser {
                 name: field_name.clone(),
                 ty: field_ty,
                 span: field_name.span,
+                is_pub: is_pub_field,
+                bits: None,
             });
         }
 
@@ -2087,12 +2782,32 @@ impl Parser {
         Ok(UnionDef {
             name,
             fields,
-            span: start.merge(&self.tokens[self.pos.saturating_sub(1)].span),
+            span: start.merge(&self.prev_span()),
             is_pub,
-            repr: None, // TODO: Parse #[repr(...)] attribute
+            repr,
         })
     }
 
+    /// Extract `#[repr(...)]` from an item's attributes
+    fn parse_repr(attributes: &[Annotation]) -> Result<Option<Repr>> {
+        let Some(ann) = attributes.iter().find(|a| a.name.name == "repr") else {
+            return Ok(None);
+        };
+        match ann.args.first() {
+            Some(Expr::Ident(id)) if id.name == "C" => Ok(Some(Repr::C)),
+            Some(Expr::Ident(id)) if id.name == "packed" => Ok(Some(Repr::Packed)),
+            Some(Expr::Ident(id)) if id.name == "transparent" => Ok(Some(Repr::Transparent)),
+            other => Err(Error::UnexpectedToken {
+                expected: "repr(C), repr(packed) or repr(transparent)".to_string(),
+                got: match other {
+                    Some(Expr::Ident(id)) => format!("repr({})", id.name),
+                    _ => "repr".to_string(),
+                },
+                span: ann.span,
+            }),
+        }
+    }
+
     fn token_to_binop(kind: &TokenKind) -> Result<BinOp> {
         match kind {
             TokenKind::Plus => Ok(BinOp::Add),
@@ -2139,11 +2854,14 @@ impl Expr {
             Expr::Block(block) => block.span,
             Expr::If { span, .. } => *span,
             Expr::Match { span, .. } => *span,
+            Expr::Let { span, .. } => *span,
             Expr::Loop { span, .. } => *span,
             Expr::While { span, .. } => *span,
             Expr::For { span, .. } => *span,
             Expr::StructLit { span, .. } => *span,
             Expr::Array { span, .. } => *span,
+            Expr::ArrayFrom { span, .. } => *span,
+            Expr::DoBlock { span, .. } => *span,
             Expr::Tuple { span, .. } => *span,
             Expr::Ref { span, .. } => *span,
             Expr::Deref { span, .. } => *span,
@@ -2153,6 +2871,24 @@ impl Expr {
             Expr::Asm { span, .. } => *span,
             Expr::Try { span, .. } => *span,
             Expr::Closure { span, .. } => *span,
+            Expr::SizeOf { span, .. } => *span,
+            Expr::New { span, .. } => *span,
+            Expr::AlignOf { span, .. } => *span,
+            Expr::OffsetOf { span, .. } => *span,
+        }
+    }
+}
+
+// Helper for Stmt span
+impl Stmt {
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Let { span, .. } => *span,
+            Stmt::Expr(expr) => expr.span(),
+            Stmt::Return { span, .. } => *span,
+            Stmt::Break { span, .. } => *span,
+            Stmt::Continue { span } => *span,
+            Stmt::Empty { span } => *span,
         }
     }
 }
@@ -2192,6 +2928,39 @@ mod tests {
         assert_eq!(program.items.len(), 1);
     }
 
+    #[test]
+    fn test_bitfield_attribute() {
+        let program = parse("struct Flags { #[bits(1)] is_ready: bool, #[bits(7)] pub reserved: u8, count: u16 }").unwrap();
+        let Item::Struct(def) = &program.items[0] else { panic!("expected a struct") };
+        let bits: Vec<Option<usize>> = def.fields.iter().map(|f| f.bits).collect();
+        assert_eq!(bits, vec![Some(1), Some(7), None]);
+        assert!(def.fields[1].is_pub);
+
+        assert!(parse("struct S { #[bits] a: u8 }").is_err());
+        assert!(parse("struct S { #[inline] a: u8 }").is_err());
+    }
+
+    #[test]
+    fn test_type_param_bounds() {
+        let program = parse("fn area<T: Shape, U: Shape + Eq, V>(a: T, b: U, c: V) -> f64 { return 0.0 }").unwrap();
+        let Item::Function(func) = &program.items[0] else { panic!("expected a function") };
+        let bounds: Vec<(&str, Vec<&str>)> = func.generic_params.iter()
+            .map(|p| match p {
+                GenericParam::Type { name, bounds } => (name.name.as_str(), bounds.iter()
+                    .map(|b| match b {
+                        Type::Named(interface, _) => interface.as_str(),
+                        other => panic!("expected an interface, got {:?}", other),
+                    })
+                    .collect()),
+                GenericParam::Const { .. } => panic!("expected type parameters"),
+            })
+            .collect();
+        assert_eq!(bounds, vec![("T", vec!["Shape"]), ("U", vec!["Shape", "Eq"]), ("V", vec![])]);
+        assert_eq!(func.type_params.len(), 3);
+
+        assert!(parse("fn f<T:>(a: T) {}").is_err());
+    }
+
     #[test]
     fn test_if_expr() {
         let program = parse("fn main() { if x > 0 { return 1 } else { return 0 } }").unwrap();
@@ -2223,6 +2992,22 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_function_type_effects() {
+        let program = parse("fn sort(less: fn(i64, i64) -> bool pure, log: fn(str) effect[io, alloc], f: fn()) { }").unwrap();
+        let Item::Function(f) = &program.items[0] else { panic!("Expected function") };
+        let effects: Vec<&EffectSet> = f.params.iter()
+            .map(|p| match &p.ty {
+                Type::Function { effects, .. } => &**effects,
+                other => panic!("Expected a function type, got {:?}", other),
+            })
+            .collect();
+        assert!(effects[0].is_pure);
+        assert_eq!(effects[1].effects, vec![Effect::IO, Effect::Alloc]);
+        assert_eq!(*effects[2], EffectSet::default());
+        assert!(matches!(&f.params[2].ty, Type::Function { ret, .. } if matches!(**ret, Type::Unit(_))));
+    }
+
     #[test]
     fn test_pure_function() {
         let program = parse("fn add(a: i32, b: i32) -> i32 pure { a + b }").unwrap();
@@ -2244,4 +3029,100 @@ mod tests {
             panic!("Expected function");
         }
     }
+
+    #[test]
+    fn test_extern_link() {
+        let program = parse("extern \"C\" link = \"m\" { fn cos(x: f64) -> f64; }\nextern \"C\" { fn puts(s: *u8) -> i32; }").unwrap();
+        match &program.items[0] {
+            Item::Extern(ext) => assert_eq!(ext.link.as_deref(), Some("m")),
+            _ => panic!("Expected extern block"),
+        }
+        assert_eq!(program.link_libraries(), vec!["m".to_string()]);
+    }
+
+    #[test]
+    fn test_nested_module() {
+        let program = parse("mod outer { fn f() {} mod inner { fn g() {} } }").unwrap();
+        match &program.items[0] {
+            Item::Module(outer) => {
+                assert_eq!(outer.name.name, "outer");
+                let items = outer.items.as_ref().unwrap();
+                assert_eq!(items.len(), 2);
+                assert!(matches!(&items[1], Item::Module(inner) if inner.name.name == "inner"));
+            }
+            _ => panic!("Expected module"),
+        }
+    }
+
+    #[test]
+    fn test_tail_semicolon() {
+        let program = parse("fn f() -> i64 { 1 }\nfn g() { 1; }").unwrap();
+        let bodies: Vec<_> = program.items.iter().map(|item| match item {
+            Item::Function(func) => &func.body.stmts,
+            _ => panic!("Expected function"),
+        }).collect();
+        assert!(matches!(bodies[0].as_slice(), [Stmt::Expr(_)]));
+        assert!(matches!(bodies[1].as_slice(), [Stmt::Expr(_), Stmt::Empty { .. }]));
+    }
+
+    #[test]
+    fn test_do_block_array() {
+        let program = parse("fn f() { let a: [i64; 4] = [do { let i: i64 = _; i * i }; 4]; }").unwrap();
+        let Item::Function(func) = &program.items[0] else { panic!("Expected function") };
+        let Stmt::Let { value: Some(Expr::ArrayFrom { init, len, .. }), .. } = &func.body.stmts[0] else {
+            panic!("Expected array from a do block");
+        };
+        assert_eq!(*len, 4);
+        assert!(matches!(init.as_ref(), Expr::DoBlock { stmts, .. } if matches!(stmts[0], Stmt::Let { .. })));
+        assert!(parse("fn f() { let a: [i64; 2] = [do { let i: i64 = _; }; 2]; }").is_err());
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        // Unoptimized builds need far more stack per level than the test threads have
+        let deep = std::thread::Builder::new().stack_size(crate::utils::STACK_SIZE).spawn(|| {
+            let parens = format!("fn f() -> i64 {{ {}1{} }}", "(".repeat(10_000), ")".repeat(10_000));
+            let unary = format!("fn f() -> i64 {{ {}1 }}", "-".repeat(10_000));
+            let pointers = format!("fn f(x: {}i64) {{}}", "*".repeat(10_000));
+            let modules = "mod m { ".repeat(10_000);
+            [parens, unary, pointers, modules].map(|source| parse(&source))
+        });
+        for result in deep.unwrap().join().unwrap() {
+            assert!(matches!(result, Err(Error::NestingTooDeep { limit: DEFAULT_MAX_NESTING, .. })), "{:?}", result);
+        }
+
+        let ok = format!("fn f() -> i64 {{ {}1{} }}", "(".repeat(20), ")".repeat(20));
+        assert!(parse(&ok).is_ok());
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let program = parse(concat!(
+            "/// A point\n/// on the plane\nstruct P { x: i64 }\n",
+            "impl P {\n    /// Read x\n    fn get(self: ref P) -> i64 { return self.x }\n}\n",
+            "/// Stray\n\nfn f() -> i64 { /// inside\n return 1 }\n",
+        )).unwrap();
+        let Item::Struct(point) = &program.items[0] else { panic!() };
+        assert_eq!(point.doc, ["A point", "on the plane"]);
+        let Item::Impl(block) = &program.items[1] else { panic!() };
+        assert_eq!(block.methods[0].doc, ["Read x"]);
+        // A doc comment attaches to the next item even across blank lines,
+        // and doc comments inside bodies are ignored
+        let Item::Function(f) = &program.items[2] else { panic!() };
+        assert_eq!(f.doc, ["Stray"]);
+    }
+
+    #[test]
+    fn test_malformed_input_is_an_error() {
+        let inputs = [
+            "fn", "fn f(", "fn f<", "struct S {", "fn f() { let x: [i64; ] }",
+            "fn f() { \"abc }", "fn f() { 'a }", "/* never closed", "mod a { mod b {",
+            "}}}", "fn f() { x.0.1.2(((( }", "impl<T", "#[", "#![", "\u{0}\u{ffff}",
+        ];
+        for input in inputs {
+            assert!(parse(input).is_err(), "{:?} parsed", input);
+        }
+        assert!(parse("").is_ok());
+        assert!(Parser::from_tokens(Vec::new(), SourceDb::new()).parse_program().is_ok());
+    }
 }
@@ -3,20 +3,86 @@
 //! Performs:
 //! - Symbol table management (scopes, definitions)
 //! - Type checking
-//! - Type checking
 //! - Ownership analysis (own/ref/mut)
+//!
+//! in three phases: `CollectionPass` registers every top-level name,
+//! `ResolutionPass` resolves the types items declare, and `CheckingPass`
+//! type-checks their bodies.
 #![allow(dead_code)]
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 use crate::frontend::ast::*;
-use crate::frontend::module::ModuleLoader;
+use crate::frontend::module::{collect_imports, ModuleLoader};
+use crate::frontend::parser::DEFAULT_MAX_NESTING;
+use crate::middle::mangle;
+use crate::middle::simd::{self, SimdOp};
+use crate::stdlib;
 use crate::types::*;
 use crate::types::type_system::ConstBinOp;
-use crate::utils::{Span, Error, Result};
+use crate::types::layout::{optimal_field_order, Layout, LayoutEngine};
+use crate::utils::{map_chunks, Span, Error, Result};
+
+/// Type and span of each `break` in a `loop`, and whether it had a value
+type LoopBreaks = Vec<(ResolvedType, Span, bool)>;
+
+/// A constructor a pattern can match, with its named fields
+type ConstructorFields = (PatternConstructor, Vec<(String, ResolvedType)>);
+
+/// Attributes the compiler reads on functions and methods
+const FUNCTION_ATTRIBUTES: [&str; 12] = [
+    "inline", "cold", "hot", "simd", "naked", "interrupt", "volatile", "gpu", "extern_c", "no_mangle", "test", "bench",
+];
+
+/// Attributes the compiler reads on structs
+const STRUCT_ATTRIBUTES: [&str; 3] = ["repr", "field_order", "derive"];
+
+/// Attributes accepted on any item
+const ITEM_ATTRIBUTES: [&str; 3] = ["allow", "production", "prototype"];
+
+/// Checks that report errors in strict mode and warnings in lenient mode.
+///
+/// A unit is strict with `--strict`, or without `--lenient` when it is
+/// marked `#![production]` or any of its items `#[production]`; a
+/// `#[prototype]` function is checked leniently either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Strictness {
+    /// `requires`/`ensures` conditions that are not `bool`
+    pub contract_types: bool,
+    /// Function values passed or assigned where their effects are not allowed
+    pub effects: bool,
+    /// Implicit integer narrowing, sign changes and `f64` to `f32`
+    pub lossy_conversions: bool,
+    /// Raw pointer, `static mut`, integer-pointer cast and extern call
+    /// operations outside `unsafe`
+    pub unsafe_operations: bool,
+    /// Bindings, parameters and expressions whose type could not be inferred
+    pub uninferred_types: bool,
+}
+
+impl Strictness {
+    pub const STRICT: Self = Self {
+        contract_types: true, effects: true, lossy_conversions: true, unsafe_operations: true, uninferred_types: true,
+    };
+    pub const LENIENT: Self = Self {
+        contract_types: false, effects: false, lossy_conversions: false, unsafe_operations: false, uninferred_types: false,
+    };
+}
+
+/// One way to build a value, as exhaustiveness checking splits a type
+#[derive(Debug, Clone, PartialEq)]
+enum PatternConstructor {
+    /// `Enum::Variant`, by that path
+    Variant(String),
+    Bool(bool),
+    Tuple,
+    Struct(String),
+}
 
 // ==================== Symbol Table ====================
 
-/// Unique identifier for a scope
+/// Depth of a scope on the symbol table's stack; the global scope is 0.
+/// Ids are only meaningful while the scope is live.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ScopeId(usize);
 
@@ -30,99 +96,287 @@ pub struct Symbol {
     pub mutable: bool,
 }
 
+/// Integer type and discriminant of each variant of `e`: explicit values
+/// must be distinct, and a variant without one follows the previous by one
+fn enum_discriminants(e: &EnumDef) -> Result<(PrimitiveType, Vec<i64>)> {
+    let repr = match e.repr.as_ref().map(|id| (id, id.name.as_str())) {
+        None | Some((_, "C")) => PrimitiveType::I32,
+        Some((_, "i8")) => PrimitiveType::I8,
+        Some((_, "i16")) => PrimitiveType::I16,
+        Some((_, "i32")) => PrimitiveType::I32,
+        Some((_, "i64")) => PrimitiveType::I64,
+        Some((_, "isize")) => PrimitiveType::Isize,
+        Some((_, "u8")) => PrimitiveType::U8,
+        Some((_, "u16")) => PrimitiveType::U16,
+        Some((_, "u32")) => PrimitiveType::U32,
+        Some((_, "u64")) => PrimitiveType::U64,
+        Some((_, "usize")) => PrimitiveType::Usize,
+        Some((id, _)) => return Err(Error::InvalidRepr {
+            repr: id.name.clone(),
+            item: format!("enum '{}'", e.name.name),
+            span: id.span,
+        }),
+    };
+    let (min, max) = repr.int_range().unwrap_or((0, 0));
+
+    let mut discriminants: Vec<i64> = Vec::new();
+    let mut next: i128 = 0;
+    for variant in &e.variants {
+        let value = match &variant.discriminant {
+            Some(expr) => const_int(expr).ok_or_else(|| Error::TypeMismatch {
+                expected: "integer constant".to_string(),
+                got: format!("{:?}", expr),
+                span: expr.span(),
+            })?,
+            None => next,
+        };
+        let name = format!("{}::{}", e.name.name, variant.name.name);
+        if value < min.max(i64::MIN as i128) || value > max.min(i64::MAX as i128) {
+            return Err(Error::DiscriminantOutOfRange {
+                variant: name,
+                ty: format!("{:?}", repr).to_lowercase(),
+                span: variant.span,
+            });
+        }
+        if let Some(first) = discriminants.iter().position(|&d| d as i128 == value) {
+            return Err(Error::DuplicateDiscriminant {
+                variant: name,
+                first: e.variants[first].name.name.clone(),
+                span: variant.span,
+            });
+        }
+        discriminants.push(value as i64);
+        next = value + 1;
+    }
+    Ok((repr, discriminants))
+}
+
+/// Value of an integer constant expression made of literals and arithmetic
+fn const_int(expr: &Expr) -> Option<i128> {
+    match expr {
+        Expr::Literal(Literal::Int(n, _)) => Some(*n as i128),
+        Expr::Unary { op: UnOp::Neg, expr, .. } => const_int(expr)?.checked_neg(),
+        Expr::Unary { op: UnOp::BitNot, expr, .. } => Some(!const_int(expr)?),
+        Expr::Binary { op, left, right, .. } => {
+            let (l, r) = (const_int(left)?, const_int(right)?);
+            match op {
+                BinOp::Add => l.checked_add(r),
+                BinOp::Sub => l.checked_sub(r),
+                BinOp::Mul => l.checked_mul(r),
+                BinOp::Div => l.checked_div(r),
+                BinOp::Mod => l.checked_rem(r),
+                BinOp::Shl => u32::try_from(r).ok().and_then(|r| l.checked_shl(r)),
+                BinOp::Shr => u32::try_from(r).ok().and_then(|r| l.checked_shr(r)),
+                BinOp::BitOr => Some(l | r),
+                BinOp::BitAnd => Some(l & r),
+                BinOp::BitXor => Some(l ^ r),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
 /// Kind of symbol
 #[derive(Debug, Clone)]
 pub enum SymbolKind {
     Variable,
-    Function { params: Vec<ResolvedType>, ret: ResolvedType, type_params: Vec<String>, const_params: Vec<(String, ResolvedType)>, effects: EffectSet },
+    /// `link_name` is the mangled symbol call sites refer to (see `middle::mangle`)
+    Function { params: Vec<ResolvedType>, ret: ResolvedType, type_params: Vec<String>, const_params: Vec<(String, ResolvedType)>, effects: EffectSet, link_name: String },
     Struct { fields: Vec<(String, ResolvedType)>, type_params: Vec<String>, const_params: Vec<(String, ResolvedType)> },
-    Enum { variants: Vec<String>, type_params: Vec<String>, const_params: Vec<(String, ResolvedType)> },
+    /// Each variant with its discriminant, stored as `repr`
+    Enum { variants: Vec<(String, i64)>, repr: PrimitiveType, type_params: Vec<String>, const_params: Vec<(String, ResolvedType)> },
     Param { ownership: Ownership },
     TypeParam,
     /// Const generic parameter (e.g., N in `const N: usize`)
     ConstParam { ty: ResolvedType },
     TypeAlias { target: ResolvedType },
+    /// Global variable (static item or extern static)
+    Static { is_mut: bool },
+    /// Untagged union; all fields share offset 0
+    Union { fields: Vec<(String, ResolvedType)> },
 }
 
-/// A scope containing symbols
-#[derive(Debug)]
+/// A scope containing symbols. Cloning a symbol table shares the maps, so
+/// workers checking functions in parallel all read the one global scope.
+#[derive(Debug, Clone, Default)]
 struct Scope {
-    parent: Option<ScopeId>,
-    symbols: HashMap<String, Symbol>,
+    symbols: Arc<HashMap<String, Symbol>>,
 }
 
+/// Scope depth saved by `SymbolTable::snapshot`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScopeSnapshot(usize);
+
 /// Symbol table with nested scopes
+///
+/// Only live scopes are kept: the stack runs from the global scope to the
+/// innermost one, and `exit_scope` pops. Popped scopes are cleared and kept
+/// for reuse, so memory is bounded by the deepest nesting, not the number of
+/// blocks analyzed.
+#[derive(Clone)]
 pub struct SymbolTable {
     scopes: Vec<Scope>,
-    current: ScopeId,
+    /// Cleared scopes whose maps are reused by `enter_scope`
+    spare: Vec<Scope>,
+    /// Functions whose calls count as unsafe operations (externs, raw memory builtins)
+    unsafe_functions: HashSet<String>,
+    /// Functions declared in `extern` blocks
+    extern_functions: HashSet<String>,
+    /// Global symbols defined by `define_shallow`, still waiting for their
+    /// full definition
+    shallow: HashSet<String>,
 }
 
 impl SymbolTable {
     pub fn new() -> Self {
-        // Create global scope
-        let global = Scope {
-            parent: None,
-            symbols: HashMap::new(),
-        };
         Self {
-            scopes: vec![global],
-            current: ScopeId(0),
+            scopes: vec![Scope::default()],
+            spare: Vec::new(),
+            unsafe_functions: HashSet::new(),
+            extern_functions: HashSet::new(),
+            shallow: HashSet::new(),
         }
     }
 
     /// Enter a new scope
     pub fn enter_scope(&mut self) -> ScopeId {
-        let id = ScopeId(self.scopes.len());
-        self.scopes.push(Scope {
-            parent: Some(self.current),
-            symbols: HashMap::new(),
-        });
-        self.current = id;
-        id
+        let scope = self.spare.pop().unwrap_or_default();
+        self.scopes.push(scope);
+        self.current()
     }
 
-    /// Exit the current scope
+    /// Exit the current scope, discarding its symbols. The global scope is never exited.
     pub fn exit_scope(&mut self) {
-        if let Some(parent) = self.scopes[self.current.0].parent {
-            self.current = parent;
+        if self.scopes.len() > 1 {
+            let mut scope = self.scopes.pop().unwrap();
+            if let Some(symbols) = Arc::get_mut(&mut scope.symbols) {
+                symbols.clear();
+                self.spare.push(scope);
+            }
+        }
+    }
+
+    /// The innermost live scope
+    pub fn current(&self) -> ScopeId {
+        ScopeId(self.scopes.len() - 1)
+    }
+
+    /// Remember the current depth, to return to with `restore`
+    pub fn snapshot(&self) -> ScopeSnapshot {
+        ScopeSnapshot(self.scopes.len())
+    }
+
+    /// Exit every scope entered since `snapshot`, including ones left open
+    /// by an early return
+    pub fn restore(&mut self, snapshot: ScopeSnapshot) {
+        while self.scopes.len() > snapshot.0.max(1) {
+            self.exit_scope();
         }
     }
 
     /// Define a symbol in the current scope
     pub fn define(&mut self, symbol: Symbol) -> Result<()> {
-        let scope = &mut self.scopes[self.current.0];
-        if let Some(existing) = scope.symbols.get(&symbol.name) {
-            // Allow extern functions to override builtin function definitions
-            if matches!(existing.kind, SymbolKind::Function { .. }) 
-               && matches!(symbol.kind, SymbolKind::Function { .. }) {
-                // Silently replace - extern declaration overrides builtin
-                scope.symbols.insert(symbol.name.clone(), symbol);
-                return Ok(());
-            }
-            return Err(Error::DuplicateDefinition {
-                name: symbol.name.clone(),
-                span: symbol.span,
-            });
+        let scope = self.scopes.last_mut().expect("the global scope is never exited");
+        let symbols = Arc::make_mut(&mut scope.symbols);
+        if let Some(existing) = symbols.get(&symbol.name) {
+            // A declared function (such as an extern) or enum may replace a
+            // builtin, which is registered without a source span
+            let replaces_builtin = existing.span == Span::dummy()
+                && matches!((&existing.kind, &symbol.kind),
+                    (SymbolKind::Function { .. }, SymbolKind::Function { .. }) | (SymbolKind::Enum { .. }, SymbolKind::Enum { .. }));
+            // The full definition of an item replaces its shallow one
+            let completes_shallow = existing.span == symbol.span && self.shallow.remove(&symbol.name);
+            if !replaces_builtin && !completes_shallow {
+                return Err(Error::DuplicateDefinition {
+                    name: symbol.name.clone(),
+                    first: existing.span,
+                    span: symbol.span,
+                });
+            }
+        }
+        symbols.insert(symbol.name.clone(), symbol);
+        Ok(())
+    }
+
+    /// Define a global symbol whose type is not resolved yet, so that items
+    /// before its definition can name it. `define` with the same name and
+    /// span replaces it.
+    pub fn define_shallow(&mut self, symbol: Symbol) -> Result<()> {
+        let name = symbol.name.clone();
+        self.define(symbol)?;
+        self.shallow.insert(name);
+        Ok(())
+    }
+
+    /// Define a local variable, shadowing a variable or parameter of the same
+    /// name in the current scope
+    pub fn shadow(&mut self, symbol: Symbol) -> Result<()> {
+        let scope = self.scopes.last_mut().expect("the global scope is never exited");
+        let symbols = Arc::make_mut(&mut scope.symbols);
+        if let Some(existing) = symbols.get(&symbol.name) {
+            if !matches!(existing.kind, SymbolKind::Variable | SymbolKind::Param { .. }) {
+                return Err(Error::DuplicateDefinition {
+                    name: symbol.name.clone(),
+                    first: existing.span,
+                    span: symbol.span,
+                });
+            }
         }
-        scope.symbols.insert(symbol.name.clone(), symbol);
+        symbols.insert(symbol.name.clone(), symbol);
         Ok(())
     }
 
+    /// Symbols of the global scope, in no particular order
+    pub fn globals(&self) -> impl Iterator<Item = &Symbol> {
+        self.scopes[0].symbols.values()
+    }
+
     /// Look up a symbol, searching from current scope upward
     pub fn lookup(&self, name: &str) -> Option<&Symbol> {
-        let mut scope_id = Some(self.current);
-        while let Some(id) = scope_id {
-            if let Some(symbol) = self.scopes[id.0].symbols.get(name) {
-                return Some(symbol);
-            }
-            scope_id = self.scopes[id.0].parent;
+        self.scopes.iter().rev().find_map(|scope| scope.symbols.get(name))
+    }
+
+    /// Look up `path` from inside `module_path`, searching the innermost
+    /// enclosing `mod` first. Locals and statics shadow module functions.
+    pub fn lookup_in_module(&self, module_path: &[String], path: &str) -> Option<&Symbol> {
+        let plain = self.lookup(path);
+        if plain.is_some_and(|symbol| !matches!(symbol.kind, SymbolKind::Function { .. })) {
+            return plain;
         }
-        None
+        (1..=module_path.len()).rev()
+            .find_map(|depth| self.lookup(&format!("{}::{}", module_path[..depth].join("::"), path)))
+            .or(plain)
+    }
+
+    /// Look up a symbol in `scope` and the scopes nested inside it
+    pub fn lookup_from(&self, scope: ScopeId, name: &str) -> Option<&Symbol> {
+        self.scopes[scope.0..].iter().rev().find_map(|scope| scope.symbols.get(name))
     }
 
     /// Look up a symbol only in the current scope
     pub fn lookup_local(&self, name: &str) -> Option<&Symbol> {
-        self.scopes[self.current.0].symbols.get(name)
+        self.scopes.last().and_then(|scope| scope.symbols.get(name))
+    }
+
+    /// Treat calls to `name` as unsafe operations
+    pub fn mark_function_unsafe(&mut self, name: &str) {
+        self.unsafe_functions.insert(name.to_string());
+    }
+
+    /// Whether `name` was marked with `mark_function_unsafe`
+    pub fn is_unsafe_function(&self, name: &str) -> bool {
+        self.unsafe_functions.contains(name)
+    }
+
+    /// Treat `name` as declared in an `extern` block, callable only in `unsafe`
+    pub fn mark_function_extern(&mut self, name: &str) {
+        self.extern_functions.insert(name.to_string());
+    }
+
+    /// Whether `name` was marked with `mark_function_extern`
+    pub fn is_extern_function(&self, name: &str) -> bool {
+        self.extern_functions.contains(name)
     }
 }
 
@@ -162,6 +416,14 @@ impl OwnershipState {
         self.owned.insert(name, span);
     }
 
+    /// Track a new binding that shadows `name`: it is owned, unmoved and unborrowed
+    pub fn rebind(&mut self, name: String, span: Span) {
+        self.moved.remove(&name);
+        self.borrowed.remove(&name);
+        self.mut_borrowed.remove(&name);
+        self.owned.insert(name, span);
+    }
+
     /// Check if a variable is owned and not moved
     pub fn is_available(&self, name: &str) -> bool {
         self.owned.contains_key(name) && !self.moved.contains_key(name)
@@ -249,6 +511,21 @@ impl Default for OwnershipState {
     }
 }
 
+/// Most pointer and reference layers that field access and method calls see
+/// through
+const MAX_AUTO_DEREF: usize = 8;
+
+/// Default for `--max-errors`
+pub const DEFAULT_MAX_ERRORS: usize = 20;
+
+/// Builtins implemented with the C library or the runtime, which
+/// `--freestanding` does not register
+const HOSTED_BUILTINS: &[&str] = &[
+    "print", "println", "puts", "print_i64", "println_i64",
+    "read_file", "write_file", "file_open", "file_read", "file_write", "file_close", "last_error",
+    "alloc", "malloc", "free", "delete", "atof", "strcmp", "exit", "arg_count", "args", "assert",
+];
+
 // ==================== Module System ====================
 
 use std::path::PathBuf;
@@ -261,10 +538,38 @@ pub struct ModuleResolver {
     cached_modules: HashMap<String, Vec<Symbol>>,
     /// Module loader for parsing modules
     loader: ModuleLoader,
+    /// What each loaded module keeps to itself
+    private_names: HashMap<String, PrivateNames>,
+    /// Modules whose imports are being loaded, outermost first
+    loading: Vec<String>,
+    /// The module being compiled, which every import path starts from
+    root: String,
+    /// Import cycles found by the current load, in the order found
+    cycles: Vec<Vec<String>>,
+}
+
+/// Names a module declares without `pub`
+#[derive(Debug, Clone, Default)]
+pub struct PrivateNames {
+    /// Functions and structs
+    pub items: Vec<String>,
+    /// Fields of each struct or union, for structs that have any
+    pub fields: HashMap<String, Vec<String>>,
+}
+
+impl Default for ModuleResolver {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ModuleResolver {
     pub fn new() -> Self {
+        Self::with_loader(ModuleLoader::new())
+    }
+
+    /// Resolve modules through `loader`, sharing its parsed files
+    pub fn with_loader(loader: ModuleLoader) -> Self {
         Self {
             search_paths: vec![
                 PathBuf::from("."),
@@ -272,33 +577,83 @@ impl ModuleResolver {
                 PathBuf::from("stdlib"),
             ],
             cached_modules: HashMap::new(),
-            loader: ModuleLoader::new(),
+            loader,
+            private_names: HashMap::new(),
+            loading: Vec::new(),
+            root: String::new(),
+            cycles: Vec::new(),
         }
     }
+
+    /// Name the module being compiled, so an import of it closes a cycle
+    pub fn set_root(&mut self, name: &str) {
+        self.root = name.to_string();
+    }
     
     /// Find module file by name
     pub fn find_module(&self, name: &str) -> Option<PathBuf> {
         self.loader.find_module_file(name)
     }
     
-    /// Load a module and return its public items as symbols
+    /// Load a module and return its public items as symbols, then load the
+    /// modules it imports so that errors in them surface at `span`.
+    ///
+    /// The imports are walked depth first. An import of a module whose
+    /// imports are still loading closes a cycle (`a -> b -> a`); the walk
+    /// goes on to find the others and the load fails with the first one.
+    /// [`take_cycles`](Self::take_cycles) returns the rest.
     pub fn load_module_symbols(&mut self, module_name: &str, span: Span) -> Result<Vec<(String, Symbol)>> {
+        if self.loading.is_empty() {
+            self.cycles.clear();
+            let symbols = self.load_module_tree(module_name, span)?;
+            return match self.cycles.is_empty() {
+                true => Ok(symbols),
+                false => Err(Error::CyclicImport { cycle: self.cycles.remove(0), span }),
+            };
+        }
+        self.load_module_tree(module_name, span)
+    }
+
+    /// Cycles the last failed load found besides the one it failed with
+    pub fn take_cycles(&mut self) -> Vec<Vec<String>> {
+        std::mem::take(&mut self.cycles)
+    }
+
+    fn load_module_tree(&mut self, module_name: &str, span: Span) -> Result<Vec<(String, Symbol)>> {
+        let path: Vec<&String> = std::iter::once(&self.root).chain(&self.loading).collect();
+        if let Some(start) = path.iter().position(|name| *name == module_name) {
+            let cycle = path[start..].iter().map(|name| name.to_string()).chain([module_name.to_string()]).collect();
+            self.cycles.push(cycle);
+            return Ok(Vec::new());
+        }
+
         // Check cache first
         if let Some(cached) = self.cached_modules.get(module_name) {
             return Ok(cached.iter().map(|s| (s.name.clone(), s.clone())).collect());
         }
         
         // Load and parse the module
-        let parsed = self.loader.load_module(module_name)?;
+        let parsed = self.loader.load_module(module_name).map_err(|e| {
+            let reason = match e {
+                Error::ModuleError(reason) => reason,
+                e => e.to_string(),
+            };
+            let reason = match self.loading.is_empty() {
+                true => reason,
+                false => format!("{} (imported through {} -> {})", reason, self.loading.join(" -> "), module_name),
+            };
+            let module = self.loading.first().map_or(module_name, String::as_str).to_string();
+            Error::ImportFailed { module, reason, span }
+        })?;
         
-        // Clone the items to avoid borrow conflict
-        let items: Vec<Item> = parsed.items.clone();
+        let items = &parsed.items;
+        self.private_names.insert(module_name.to_string(), Self::collect_private_names(items));
         
         // Convert public items to symbols
         let mut symbols = Vec::new();
-        for item in &items {
-            if Self::is_item_public(&item) {
-                if let Some(symbol) = self.item_to_symbol(&item, span) {
+        for item in items {
+            if Self::is_item_public(item) {
+                if let Some(symbol) = self.item_to_symbol(module_name, item, span) {
                     symbols.push(symbol);
                 }
             }
@@ -325,9 +680,10 @@ impl ModuleResolver {
                                 ret: ret.clone(),
                                 type_params: vec![],
                                 const_params: vec![],
-                                effects: EffectSet::default()
+                                effects: EffectSet::default(),
+                                link_name: mangle::method_symbol(&type_name, &method.name.name),
                             },
-                            ty: ResolvedType::Function { params, ret: Box::new(ret) },
+                            ty: ResolvedType::Function { params, ret: Box::new(ret), effects: Box::default() },
                             span,
                             mutable: false,
                         });
@@ -338,10 +694,52 @@ impl ModuleResolver {
         
         // Cache the symbols
         self.cached_modules.insert(module_name.to_string(), symbols.clone());
+
+        let mut imports = Vec::new();
+        collect_imports(items, &mut imports);
+        self.loading.push(module_name.to_string());
+        imports.retain(|name| self.loader.find_module_file(name).is_some());
+        // `collect_imports` lists them last first
+        let loaded = imports.iter().rev().try_for_each(|name| self.load_module_tree(name, span).map(drop));
+        self.loading.pop();
+        loaded?;
         
         Ok(symbols.iter().map(|s| (s.name.clone(), s.clone())).collect())
     }
     
+    /// Private items and fields of a loaded module
+    pub fn private_names(&self, module_name: &str) -> PrivateNames {
+        self.private_names.get(module_name).cloned().unwrap_or_default()
+    }
+
+    fn collect_private_names(items: &[Item]) -> PrivateNames {
+        let mut names = PrivateNames::default();
+        for item in items {
+            let (name, fields) = match item {
+                Item::Function(f) if !f.is_pub => {
+                    names.items.push(f.name.name.clone());
+                    continue;
+                }
+                Item::Struct(s) => {
+                    if !s.is_pub {
+                        names.items.push(s.name.name.clone());
+                    }
+                    (&s.name.name, &s.fields)
+                }
+                Item::Union(u) => (&u.name.name, &u.fields),
+                _ => continue,
+            };
+            let private: Vec<String> = fields.iter()
+                .filter(|f| !f.is_pub)
+                .map(|f| f.name.name.clone())
+                .collect();
+            if !private.is_empty() {
+                names.fields.insert(name.clone(), private);
+            }
+        }
+        names
+    }
+
     /// Check if an item is public
     fn is_item_public(item: &Item) -> bool {
         match item {
@@ -355,7 +753,7 @@ impl ModuleResolver {
 
     
     /// Convert an AST item to a symbol
-    fn item_to_symbol(&self, item: &Item, span: Span) -> Option<Symbol> {
+    fn item_to_symbol(&self, module_name: &str, item: &Item, span: Span) -> Option<Symbol> {
         match item {
             Item::Function(f) => {
                 let params: Vec<ResolvedType> = f.params.iter()
@@ -366,8 +764,15 @@ impl ModuleResolver {
                     .unwrap_or(ResolvedType::unit());
                 Some(Symbol {
                     name: f.name.name.clone(),
-                    kind: SymbolKind::Function { params: params.clone(), ret: ret.clone(), type_params: vec![], const_params: vec![], effects: EffectSet::default() },
-                    ty: ResolvedType::Function { params, ret: Box::new(ret) },
+                    kind: SymbolKind::Function {
+                        params: params.clone(),
+                        ret: ret.clone(),
+                        type_params: vec![],
+                        const_params: vec![],
+                        effects: EffectSet::default(),
+                        link_name: mangle::link_name::<&str>(module_name, &[], &f.name.name, f.is_pub, f.is_unmangled()),
+                    },
+                    ty: ResolvedType::Function { params, ret: Box::new(ret), effects: Box::default() },
                     span,
                     mutable: false,
                 })
@@ -385,12 +790,13 @@ impl ModuleResolver {
                 })
             }
             Item::Enum(e) => {
-                let variants: Vec<String> = e.variants.iter()
-                    .map(|v| v.name.name.clone())
-                    .collect();
+                // Invalid discriminants are reported when the defining module is checked
+                let (repr, discriminants) = enum_discriminants(e)
+                    .unwrap_or_else(|_| (PrimitiveType::I32, (0..e.variants.len() as i64).collect()));
+                let variants = e.variants.iter().map(|v| v.name.name.clone()).zip(discriminants).collect();
                 Some(Symbol {
                     name: e.name.name.clone(),
-                    kind: SymbolKind::Enum { variants, type_params: vec![], const_params: vec![] },
+                    kind: SymbolKind::Enum { variants, repr, type_params: vec![], const_params: vec![] },
                     ty: ResolvedType::Enum { name: e.name.name.clone() },
                     span,
                     mutable: false,
@@ -441,7 +847,7 @@ impl ModuleResolver {
                     _ => ResolvedType::Struct { name: name.clone(), fields: vec![] },
                 }
             }
-            Type::Pointer(inner, _) => {
+            Type::Pointer(inner, _) | Type::Volatile(inner, _) => {
                 ResolvedType::Pointer(Box::new(self.ast_type_to_resolved(inner)))
             }
             Type::Ref { mutable, inner, .. } => {
@@ -466,6 +872,7 @@ impl ModuleResolver {
 // ==================== Semantic Analyzer ====================
 
 /// Semantic analyzer
+#[derive(Clone)]
 pub struct SemanticAnalyzer {
     pub symbols: SymbolTable,
     pub errors: Vec<Error>,
@@ -473,12 +880,89 @@ pub struct SemanticAnalyzer {
     // AI-Native extensions
     /// Current function's declared effects (for effect propagation checking)
     current_effects: Option<EffectSet>,
-    /// Whether we're in strict mode (@production) or lenient mode (@prototype)
-    strict_mode: bool,
-    /// Module resolver for use statements
-    module_resolver: ModuleResolver,
+    /// Effects called for in the closure being checked, which become part
+    /// of its type
+    closure_effects: Option<Vec<Effect>>,
+    /// Checks of the item being analyzed that are errors rather than warnings
+    strictness: Strictness,
+    /// Strictness of the unit, for items not marked `#[prototype]`
+    unit_strictness: Strictness,
+    /// `--strict` (Some(true)) or `--lenient` (Some(false)), over any annotation
+    strict_override: Option<bool>,
+    /// Code and span of the errors that lenient mode would report as warnings
+    strict_only: Vec<(&'static str, Span)>,
+    /// Name of the compilation unit, used for the link names of its `pub` functions
+    module_name: String,
+    /// `mod` blocks enclosing the current item, outermost first
+    module_path: Vec<String>,
+    /// Module resolver for use statements, shared with the workers of the checking phase
+    module_resolver: Arc<Mutex<ModuleResolver>>,
     /// Imported modules: module_name -> Vec<(symbol_name, Symbol)>
     pub imported_modules: HashMap<String, Vec<(String, Symbol)>>,
+    /// Items and fields of imported modules that are not `pub`
+    private_imports: HashMap<String, PrivateNames>,
+    /// Struct/enum layouts for size_of/align_of/offset_of
+    pub layouts: LayoutEngine,
+    /// Nesting depth of `unsafe` blocks around the current expression
+    unsafe_depth: usize,
+    /// Unsafe operations seen inside `unsafe` blocks so far
+    unsafe_operations: usize,
+    /// Parameters of the function being checked that no `let` has shadowed yet
+    unshadowed_params: HashSet<String>,
+    /// Breaks of each enclosing loop, innermost last; `None` for `while` and
+    /// `for`, which cannot break with a value
+    loop_breaks: Vec<Option<LoopBreaks>>,
+    /// Diagnostics that do not fail the analysis
+    warnings: Vec<Error>,
+    /// Associated types declared by each interface: interface -> declarations
+    interface_assoc_types: HashMap<String, Vec<AssociatedType>>,
+    /// Associated type bindings from impl blocks: (impl target, assoc name) -> type
+    assoc_types: HashMap<(String, String), ResolvedType>,
+    /// Target type of the impl block being checked (what `Self` refers to)
+    current_impl: Option<String>,
+    /// Methods from impl blocks, keyed by target type name. Every type declared
+    /// in this program has an entry, so calling a missing method on it is an error.
+    impl_methods: Arc<HashMap<String, Vec<Function>>>,
+    /// Extension methods from `impl i32 { .. }` and the like, keyed by primitive
+    primitive_methods: Arc<HashMap<PrimitiveType, Vec<Function>>>,
+    /// Interfaces implemented by each type (for default method lookup)
+    impl_interfaces: HashMap<String, Vec<String>>,
+    /// Default method bodies declared by each interface
+    interface_defaults: Arc<HashMap<String, Vec<Function>>>,
+    /// Method signatures each interface requires of its impls
+    interface_methods: Arc<HashMap<String, Vec<FunctionSig>>>,
+    /// Interface bounds of each generic function's type parameters, by link name
+    type_param_bounds: HashMap<String, Vec<(String, Vec<String>)>>,
+    /// Interface bounds of the type parameters of the function being checked
+    current_bounds: HashMap<String, Vec<String>>,
+    /// Struct whose invariants are being checked, and the scope holding its
+    /// fields; data defined outside that scope is out of reach
+    invariant_scope: Option<(String, ScopeId)>,
+    /// Worker threads for checking items (`--jobs`)
+    jobs: usize,
+    /// Errors kept before the rest are dropped (`--max-errors`, 0 = no limit)
+    max_errors: usize,
+    /// Errors dropped after reaching `max_errors`
+    suppressed_errors: usize,
+    /// No C library: hosted builtins are left out (`--freestanding`)
+    freestanding: bool,
+    /// Expressions being checked around the current one
+    nesting: usize,
+    /// Deepest nesting of expressions and types accepted (`--max-nesting`)
+    max_nesting: usize,
+    /// Type of every expression checked, when recorded (`--print-types`)
+    expr_types: Option<Vec<(Span, ResolvedType)>>,
+    /// What the function being checked has left without a type, inner
+    /// expressions before those containing them
+    uninferred: Vec<(String, Span)>,
+    /// Structs and enums declared `#[derive(Eq)]`, whose values `==` compares
+    derived_eq: HashSet<String>,
+    /// Widths of `#[bits(N)]` fields, by (struct, field)
+    bitfields: HashMap<(String, String), u32>,
+    /// The field access being checked is the operand of `&`
+    borrowing_field: bool,
+    /// The integer constant an assignment stores into the field access being checked
+    storing_constant: Option<(i64, Span)>,
 }
 
 impl SemanticAnalyzer {
@@ -488,17 +972,128 @@ impl SemanticAnalyzer {
             errors: Vec::new(),
             ownership: OwnershipState::new(),
             current_effects: None,
-            strict_mode: false, // Default: lenient mode
-            module_resolver: ModuleResolver::new(),
+            closure_effects: None,
+            strictness: Strictness::LENIENT,
+            unit_strictness: Strictness::LENIENT,
+            strict_override: None,
+            strict_only: Vec::new(),
+            module_name: "module".to_string(),
+            module_path: Vec::new(),
+            module_resolver: Arc::new(Mutex::new(ModuleResolver::new())),
             imported_modules: HashMap::new(),
+            private_imports: HashMap::new(),
+            layouts: LayoutEngine::new(),
+            unsafe_depth: 0,
+            unsafe_operations: 0,
+            unshadowed_params: HashSet::new(),
+            loop_breaks: Vec::new(),
+            warnings: Vec::new(),
+            interface_assoc_types: HashMap::new(),
+            assoc_types: HashMap::new(),
+            current_impl: None,
+            impl_methods: Arc::default(),
+            primitive_methods: Arc::default(),
+            impl_interfaces: HashMap::new(),
+            interface_defaults: Arc::default(),
+            interface_methods: Arc::default(),
+            type_param_bounds: HashMap::new(),
+            current_bounds: HashMap::new(),
+            invariant_scope: None,
+            jobs: 1,
+            max_errors: DEFAULT_MAX_ERRORS,
+            suppressed_errors: 0,
+            freestanding: false,
+            nesting: 0,
+            max_nesting: DEFAULT_MAX_NESTING,
+            expr_types: None,
+            uninferred: Vec::new(),
+            derived_eq: HashSet::new(),
+            bitfields: HashMap::new(),
+            borrowing_field: false,
+            storing_constant: None,
         };
         analyzer.register_builtins();
         analyzer
     }
     
-    /// Set strict mode for production-level checking
+    /// Force strict (`--strict`) or lenient (`--lenient`) mode, whatever the
+    /// `#[production]` annotations say
     pub fn set_strict_mode(&mut self, strict: bool) {
-        self.strict_mode = strict;
+        self.strict_override = Some(strict);
+        self.unit_strictness = if strict { Strictness::STRICT } else { Strictness::LENIENT };
+        self.strictness = self.unit_strictness;
+    }
+
+    /// Whether `error` is reported only because the code was checked
+    /// strictly, and would be a warning in lenient mode
+    pub fn is_strict_only(&self, error: &Error) -> bool {
+        error.span().is_some_and(|span| self.strict_only.contains(&(error.code(), span)))
+    }
+
+    /// Check items on `jobs` worker threads
+    pub fn set_jobs(&mut self, jobs: usize) {
+        self.jobs = jobs;
+    }
+
+    /// Keep at most `max` errors (0 for no limit); the rest are counted
+    /// and replaced by a single "too many errors" entry
+    pub fn set_max_errors(&mut self, max: usize) {
+        self.max_errors = max;
+    }
+
+    /// Accept expressions and types nested at most `limit` levels deep, the
+    /// same limit the parser applies
+    pub fn set_max_nesting(&mut self, limit: usize) {
+        self.max_nesting = limit;
+    }
+
+    /// Record the type of every expression checked, for `expr_types`
+    pub fn set_record_types(&mut self, enabled: bool) {
+        self.expr_types = enabled.then(Vec::new);
+    }
+
+    /// Span and type of each expression checked, in the order they were
+    /// checked; empty unless `set_record_types` enabled recording
+    pub fn expr_types(&self) -> &[(Span, ResolvedType)] {
+        self.expr_types.as_deref().unwrap_or_default()
+    }
+
+    /// Number of errors dropped after reaching the `max_errors` limit
+    pub fn suppressed_errors(&self) -> usize {
+        self.suppressed_errors
+    }
+
+    /// Analyze a program linked without the C library: the builtins it
+    /// provides are not defined, and using one is a `NotFreestanding` error
+    pub fn set_freestanding(&mut self, freestanding: bool) {
+        self.freestanding = freestanding;
+        self.symbols = SymbolTable::new();
+        self.register_builtins();
+    }
+
+    /// The error for `name`, undefined at `span`: in freestanding mode a
+    /// hosted builtin or `String` gets one saying why
+    fn undefined_name(&self, name: &str, span: Span) -> Option<Error> {
+        (self.freestanding && (name == "String" || HOSTED_BUILTINS.contains(&name)))
+            .then(|| Error::NotFreestanding { name: name.to_string(), span })
+    }
+
+    /// Load imported modules through `loader`, sharing its parsed files
+    pub fn set_module_loader(&mut self, loader: ModuleLoader) {
+        let mut resolver = ModuleResolver::with_loader(loader);
+        resolver.set_root(&self.module_name);
+        self.module_resolver = Arc::new(Mutex::new(resolver));
+    }
+
+    /// Set the compilation unit name (the IR module name, normally the file stem)
+    pub fn set_module_name(&mut self, name: &str) {
+        self.module_name = name.to_string();
+        self.module_resolver.lock().expect("module resolver lock poisoned").set_root(name);
+    }
+
+    /// Warnings collected during analysis
+    pub fn warnings(&self) -> &[Error] {
+        &self.warnings
     }
     
     /// Register built-in functions
@@ -509,12 +1104,23 @@ impl SemanticAnalyzer {
         let panic_effects = EffectSet { is_pure: false, effects: vec![Effect::Panic] };
 
         // I/O functions - require effect[io]
-        self.define_builtin_with_effects("print", vec![ResolvedType::String], ResolvedType::unit(), io_effects.clone());
-        self.define_builtin_with_effects("println", vec![ResolvedType::String], ResolvedType::unit(), io_effects.clone());
+        self.define_builtin_with_effects("print", vec![ResolvedType::Str], ResolvedType::unit(), io_effects.clone());
+        self.define_builtin_with_effects("println", vec![ResolvedType::Str], ResolvedType::unit(), io_effects.clone());
         self.define_builtin_with_effects("puts", vec![ResolvedType::Pointer(Box::new(ResolvedType::U8))], ResolvedType::I32, io_effects.clone());
         self.define_builtin_with_effects("print_i64", vec![ResolvedType::I64], ResolvedType::unit(), io_effects.clone());
         self.define_builtin_with_effects("println_i64", vec![ResolvedType::I64], ResolvedType::unit(), io_effects.clone());
 
+        // File I/O - require effect[io]; failures are reported through last_error()
+        let byte_ptr = || ResolvedType::Pointer(Box::new(ResolvedType::U8));
+        let usize_ty = ResolvedType::Primitive(PrimitiveType::Usize);
+        self.define_builtin_with_effects("read_file", vec![ResolvedType::Str], ResolvedType::Str, io_effects.clone());
+        self.define_builtin_with_effects("write_file", vec![ResolvedType::Str, byte_ptr(), usize_ty.clone()], ResolvedType::I32, io_effects.clone());
+        self.define_builtin_with_effects("file_open", vec![ResolvedType::Str, ResolvedType::Str], byte_ptr(), io_effects.clone());
+        self.define_builtin_with_effects("file_read", vec![byte_ptr(), byte_ptr(), usize_ty.clone()], ResolvedType::I64, io_effects.clone());
+        self.define_builtin_with_effects("file_write", vec![byte_ptr(), byte_ptr(), usize_ty], ResolvedType::I64, io_effects.clone());
+        self.define_builtin_with_effects("file_close", vec![byte_ptr()], ResolvedType::I32, io_effects.clone());
+        self.define_builtin("last_error", vec![], ResolvedType::I32);
+
         // Memory functions - require effect[alloc]
         self.define_builtin_with_effects("alloc", vec![ResolvedType::U64],
             ResolvedType::Pointer(Box::new(ResolvedType::U8)), alloc_effects.clone());
@@ -523,6 +1129,10 @@ impl SemanticAnalyzer {
         self.define_builtin_with_effects("free",
             vec![ResolvedType::Pointer(Box::new(ResolvedType::U8))],
             ResolvedType::unit(), alloc_effects.clone());
+        // Frees a `new`/`new_array` allocation of any type
+        self.define_builtin_with_effects("delete",
+            vec![ResolvedType::Pointer(Box::new(ResolvedType::Unknown))],
+            ResolvedType::unit(), alloc_effects.clone());
 
         // C library functions for self-hosting (pure - no side effects)
         self.define_builtin("atof", vec![ResolvedType::Pointer(Box::new(ResolvedType::U8))],
@@ -535,60 +1145,73 @@ impl SemanticAnalyzer {
         // Process control - require effect[panic] (never returns)
         self.define_builtin_with_effects("exit", vec![ResolvedType::I32], ResolvedType::never(), panic_effects.clone());
 
+        // Command-line arguments, recorded by the generated C entry point
+        self.define_builtin("arg_count", vec![], ResolvedType::I32);
+        self.define_builtin("args", vec![], ResolvedType::Slice(Box::new(ResolvedType::Str)));
+
         // Debug - require effect[panic]
         self.define_builtin_with_effects("assert", vec![ResolvedType::BOOL], ResolvedType::UNIT, panic_effects.clone());
         
-        // SIMD intrinsics for f32x4
-        let f32x4 = ResolvedType::Vector(Box::new(ResolvedType::Primitive(PrimitiveType::F32)), 4);
-        self.define_builtin("f32x4_splat", vec![ResolvedType::Primitive(PrimitiveType::F32)], f32x4.clone());
-        self.define_builtin("f32x4_add", vec![f32x4.clone(), f32x4.clone()], f32x4.clone());
-        self.define_builtin("f32x4_sub", vec![f32x4.clone(), f32x4.clone()], f32x4.clone());
-        self.define_builtin("f32x4_mul", vec![f32x4.clone(), f32x4.clone()], f32x4.clone());
-        self.define_builtin("f32x4_div", vec![f32x4.clone(), f32x4.clone()], f32x4.clone());
-        self.define_builtin("f32x4_sum", vec![f32x4.clone()], ResolvedType::Primitive(PrimitiveType::F32));
-        
-        // __simd_* prefixed versions for simd.aeth
-        let f32_ty = ResolvedType::Primitive(PrimitiveType::F32);
-        let f64_ty = ResolvedType::Primitive(PrimitiveType::F64);
-        let i32_ty = ResolvedType::Primitive(PrimitiveType::I32);
-        let f32_ptr = ResolvedType::Pointer(Box::new(f32_ty.clone()));
-        
-        // f32x4 intrinsics
-        self.define_builtin("__simd_f32x4_new", vec![f32_ty.clone(), f32_ty.clone(), f32_ty.clone(), f32_ty.clone()], f32x4.clone());
-        self.define_builtin("__simd_f32x4_splat", vec![f32_ty.clone()], f32x4.clone());
-        self.define_builtin("__simd_f32x4_add", vec![f32x4.clone(), f32x4.clone()], f32x4.clone());
-        self.define_builtin("__simd_f32x4_sub", vec![f32x4.clone(), f32x4.clone()], f32x4.clone());
-        self.define_builtin("__simd_f32x4_mul", vec![f32x4.clone(), f32x4.clone()], f32x4.clone());
-        self.define_builtin("__simd_f32x4_div", vec![f32x4.clone(), f32x4.clone()], f32x4.clone());
-        self.define_builtin("__simd_f32x4_sum", vec![f32x4.clone()], f32_ty.clone());
-        self.define_builtin("__simd_f32x4_load", vec![f32_ptr.clone()], f32x4.clone());
-        self.define_builtin("__simd_f32x4_store", vec![f32_ptr.clone(), f32x4.clone()], ResolvedType::unit());
-        
-        // SIMD intrinsics for f64x2
-        let f64x2 = ResolvedType::Vector(Box::new(ResolvedType::Primitive(PrimitiveType::F64)), 2);
-        self.define_builtin("f64x2_splat", vec![ResolvedType::Primitive(PrimitiveType::F64)], f64x2.clone());
-        self.define_builtin("f64x2_add", vec![f64x2.clone(), f64x2.clone()], f64x2.clone());
-        self.define_builtin("f64x2_mul", vec![f64x2.clone(), f64x2.clone()], f64x2.clone());
-        
-        // __simd_* prefixed versions for f64x2
-        self.define_builtin("__simd_f64x2_new", vec![f64_ty.clone(), f64_ty.clone()], f64x2.clone());
-        self.define_builtin("__simd_f64x2_splat", vec![f64_ty.clone()], f64x2.clone());
-        self.define_builtin("__simd_f64x2_add", vec![f64x2.clone(), f64x2.clone()], f64x2.clone());
-        self.define_builtin("__simd_f64x2_mul", vec![f64x2.clone(), f64x2.clone()], f64x2.clone());
-        
-        // SIMD intrinsics for i32x4
-        let i32x4 = ResolvedType::Vector(Box::new(ResolvedType::Primitive(PrimitiveType::I32)), 4);
-        self.define_builtin("i32x4_splat", vec![ResolvedType::Primitive(PrimitiveType::I32)], i32x4.clone());
-        self.define_builtin("i32x4_add", vec![i32x4.clone(), i32x4.clone()], i32x4.clone());
-        self.define_builtin("i32x4_mul", vec![i32x4.clone(), i32x4.clone()], i32x4.clone());
-        
-        // __simd_* prefixed versions for i32x4
-        self.define_builtin("__simd_i32x4_new", vec![i32_ty.clone(), i32_ty.clone(), i32_ty.clone(), i32_ty.clone()], i32x4.clone());
-        self.define_builtin("__simd_i32x4_splat", vec![i32_ty.clone()], i32x4.clone());
-        self.define_builtin("__simd_i32x4_add", vec![i32x4.clone(), i32x4.clone()], i32x4.clone());
-        self.define_builtin("__simd_i32x4_mul", vec![i32x4.clone(), i32x4.clone()], i32x4.clone());
+        // SIMD intrinsics (see middle::simd)
+        for (name, elem, lanes, op) in simd::intrinsics() {
+            let elem = ResolvedType::Primitive(match elem {
+                "f32" => PrimitiveType::F32,
+                "f64" => PrimitiveType::F64,
+                "i32" => PrimitiveType::I32,
+                _ => PrimitiveType::I64,
+            });
+            let vector = ResolvedType::Vector(Box::new(elem.clone()), lanes);
+            let ptr = ResolvedType::Pointer(Box::new(elem.clone()));
+            let (params, ret) = match op {
+                SimdOp::New => (vec![elem; lanes], vector),
+                SimdOp::Splat => (vec![elem], vector),
+                SimdOp::Add | SimdOp::Sub | SimdOp::Mul | SimdOp::Div => (vec![vector.clone(), vector.clone()], vector),
+                SimdOp::Sum => (vec![vector], elem),
+                SimdOp::Load => (vec![ptr], vector),
+                SimdOp::Store => (vec![ptr, vector], ResolvedType::unit()),
+            };
+            self.define_builtin(&name, params, ret);
+        }
+
+        // `Option<T>` and `Result<T, E>`, as declared in stdlib/core.aeth
+        let param = |name: &str| ResolvedType::GenericParam(name.to_string());
+        self.define_builtin_enum("Option", &["T"], vec![("Some", vec![param("T")]), ("None", vec![])]);
+        self.define_builtin_enum("Result", &["T", "E"], vec![("Ok", vec![param("T")]), ("Err", vec![param("E")])]);
     }
-    
+
+    /// Define `String` and its methods from stdlib/string.aeth; a program may
+    /// declare its own instead
+    fn register_builtin_string(&mut self, program: &Program) -> Result<()> {
+        // Its buffer lives on the heap, which freestanding programs lack
+        if self.freestanding || self.symbols.lookup("String").is_some() {
+            return Ok(());
+        }
+        for item in stdlib::prelude_items(program) {
+            self.collect_definition(item)?;
+        }
+        Ok(())
+    }
+
+    /// Define a built-in generic enum; a program may declare its own instead
+    fn define_builtin_enum(&mut self, name: &str, type_params: &[&str], variants: Vec<(&str, Vec<ResolvedType>)>) {
+        let repr = PrimitiveType::I32;
+        self.layouts.register_enum(name, Layout::new(repr.size_of(), repr.align_of()),
+            variants.iter().map(|(_, payload)| payload.clone()).collect());
+        let symbol = Symbol {
+            name: name.to_string(),
+            kind: SymbolKind::Enum {
+                variants: variants.iter().zip(0..).map(|((v, _), i)| (v.to_string(), i)).collect(),
+                repr,
+                type_params: type_params.iter().map(|p| p.to_string()).collect(),
+                const_params: vec![],
+            },
+            ty: ResolvedType::Enum { name: name.to_string() },
+            span: Span::dummy(),
+            mutable: false,
+        };
+        let _ = self.symbols.define(symbol);
+    }
+
     /// Define a built-in function
     fn define_builtin(&mut self, name: &str, params: Vec<ResolvedType>, ret: ResolvedType) {
         self.define_builtin_with_effects(name, params, ret, EffectSet::default());
@@ -596,9 +1219,16 @@ impl SemanticAnalyzer {
 
     /// Define a built-in function with specific effects
     fn define_builtin_with_effects(&mut self, name: &str, params: Vec<ResolvedType>, ret: ResolvedType, effects: EffectSet) {
+        if self.freestanding && HOSTED_BUILTINS.contains(&name) {
+            return;
+        }
+        // Builtins that take or hand out raw pointers work on unchecked memory
+        if params.iter().chain(std::iter::once(&ret)).any(|ty| matches!(ty, ResolvedType::Pointer(_))) {
+            self.symbols.mark_function_unsafe(name);
+        }
         let symbol = Symbol {
             name: name.to_string(),
-            kind: SymbolKind::Function { params, ret, type_params: vec![], const_params: vec![], effects },
+            kind: SymbolKind::Function { params, ret, type_params: vec![], const_params: vec![], effects, link_name: name.to_string() },
             ty: ResolvedType::Unknown,
             span: Span::dummy(),
             mutable: false,
@@ -608,90 +1238,542 @@ impl SemanticAnalyzer {
 
     /// Analyze a program
     pub fn analyze(&mut self, program: &Program) -> Result<()> {
-        // Pass 1: Collect all top-level definitions
-        for item in &program.items {
-            self.collect_definition(item)?;
+        self.analyze_phases(program).map_err(PhaseError::into_error)
+    }
+
+    /// Analyze a program in three phases, each of which only starts once
+    /// the one before it succeeded: collection, resolution and checking
+    pub fn analyze_phases(&mut self, program: &Program) -> std::result::Result<(), PhaseError> {
+        CollectionPass::run(self, program).map_err(PhaseError::Collection)?;
+        ResolutionPass::run(self, program).map_err(PhaseError::Resolution)?;
+        CheckingPass::run(self, program).map_err(PhaseError::Checking)
+    }
+
+    /// Warn about attributes the compiler does not know, and reject known
+    /// ones on items they do not apply to
+    fn check_attributes(&mut self, items: &[Item]) {
+        for item in items {
+            match item {
+                Item::Function(func) => {
+                    self.check_item_attributes(&func.annotations, &FUNCTION_ATTRIBUTES, &format!("function '{}'", func.name.name));
+                }
+                Item::Impl(block) => {
+                    for method in &block.methods {
+                        let item = format!("method '{}::{}'", block.target.name, method.name.name);
+                        self.check_item_attributes(&method.annotations, &FUNCTION_ATTRIBUTES, &item);
+                    }
+                }
+                Item::Struct(def) => {
+                    self.check_item_attributes(&def.annotations, &STRUCT_ATTRIBUTES, &format!("struct '{}'", def.name.name));
+                    self.check_field_order(def);
+                    self.check_bitfields(def);
+                    let fields = self.layouts.struct_fields(&def.name.name).map(<[_]>::to_vec).unwrap_or_default();
+                    let fields = fields.into_iter().zip(&def.fields).map(|((_, ty), field)| (ty, field.span)).collect();
+                    self.check_derives(&def.annotations, def.derives_eq(), fields);
+                }
+                Item::Enum(def) => {
+                    let payloads = self.layouts.enum_payloads(&def.name.name).map(<[_]>::to_vec).unwrap_or_default();
+                    let fields = payloads.into_iter().zip(&def.variants)
+                        .flat_map(|(payload, variant)| payload.into_iter().map(|ty| (ty, variant.span)))
+                        .collect();
+                    self.check_derives(&def.annotations, def.derives_eq(), fields);
+                }
+                Item::Module(ModuleDef { items: Some(items), .. }) => self.check_attributes(items),
+                _ => {}
+            }
         }
+    }
 
-        // Pass 2: Type check all items
-        for item in &program.items {
-            self.check_item(item)?;
+    fn check_item_attributes(&mut self, annotations: &[Annotation], accepted: &[&str], item: &str) {
+        for annotation in annotations {
+            let name = annotation.name.name.as_str();
+            let known_hint = match annotation.args.as_slice() {
+                [] => true,
+                [Expr::Ident(id)] => id.name == "never" || id.name == "always",
+                _ => false,
+            };
+            if accepted.contains(&name) || ITEM_ATTRIBUTES.contains(&name) {
+                // `#[inline]` takes no argument besides `never` and `always`
+                if name == "inline" && !known_hint {
+                    let args: Vec<String> = annotation.args.iter().map(crate::frontend::formatter::expr_to_string).collect();
+                    self.warnings.push(Error::UnknownAttribute { name: format!("inline({})", args.join(", ")), span: annotation.span });
+                }
+            } else if FUNCTION_ATTRIBUTES.contains(&name) || STRUCT_ATTRIBUTES.contains(&name) {
+                self.push_error(Error::MisplacedAttribute { name: name.to_string(), item: item.to_string(), span: annotation.span });
+            } else {
+                self.warnings.push(Error::UnknownAttribute { name: name.to_string(), span: annotation.span });
+            }
         }
+    }
 
-        if self.errors.is_empty() {
-            Ok(())
-        } else {
-            Err(self.errors[0].clone())
+    /// Warn about `#[derive(..)]` names the compiler cannot derive, and check
+    /// that a type deriving `Eq` holds only values `==` can compare
+    fn check_derives(&mut self, annotations: &[Annotation], derives_eq: bool, fields: Vec<(ResolvedType, Span)>) {
+        for annotation in annotations.iter().filter(|a| a.name.name == "derive") {
+            for arg in &annotation.args {
+                if !matches!(arg, Expr::Ident(id) if id.name == "Eq") {
+                    let name = format!("derive({})", crate::frontend::formatter::expr_to_string(arg));
+                    self.warnings.push(Error::UnknownAttribute { name, span: annotation.span });
+                }
+            }
+        }
+        if !derives_eq {
+            return;
+        }
+        for (ty, span) in fields {
+            if !self.is_equatable(&ty) {
+                self.push_error(Error::NotEquatable { ty: ty.to_string(), span });
+            }
         }
     }
 
-    /// Collect a top-level definition
-    fn collect_definition(&mut self, item: &Item) -> Result<()> {
-        match item {
-            Item::Function(func) => {
-                let params: Vec<ResolvedType> = func.params.iter()
-                    .map(|p| self.resolve_type(&p.ty))
-                    .collect::<Result<Vec<_>>>()?;
-                let ret = func.ret_type.as_ref()
-                    .map(|t| self.resolve_type(t))
-                    .transpose()?
-                    .unwrap_or(ResolvedType::unit());
+    /// Check `#[field_order(..)]`, and warn when a struct laid out as
+    /// declared takes more padding than it would with its fields ordered
+    /// largest first
+    fn check_field_order(&mut self, def: &StructDef) {
+        let repr = def.annotations.iter()
+            .find(|a| a.name.name == "repr")
+            .map(|a| a.args.iter().map(crate::frontend::formatter::expr_to_string).collect::<Vec<_>>().join(", "));
+        match (def.field_order(), repr) {
+            // `repr` fixes the order, so only a plain struct can be reordered
+            (Some(FieldOrder::Optimal), Some(repr)) => {
+                let item = format!("struct '{}' with #[field_order(optimal)]", def.name.name);
+                self.push_error(Error::InvalidRepr { repr, item, span: def.span });
+                return;
+            }
+            (Some(_), _) | (None, Some(_)) => return,
+            // Bitfields are packed by their own rule
+            (None, None) if def.fields.iter().any(|f| f.bits.is_some()) => return,
+            (None, None) => {}
+        }
+        if let Some(ann) = def.annotations.iter().find(|a| a.name.name == "field_order") {
+            let args: Vec<String> = ann.args.iter().map(crate::frontend::formatter::expr_to_string).collect();
+            self.warnings.push(Error::UnknownAttribute { name: format!("field_order({})", args.join(", ")), span: ann.span });
+            return;
+        }
+        let Some(fields) = self.layouts.struct_fields(&def.name.name) else { return };
+        let Some(layouts) = fields.iter().map(|(_, ty)| self.layouts.layout_of(ty)).collect::<Option<Vec<_>>>() else {
+            return;
+        };
+        let reordered: Vec<(String, ResolvedType)> = optimal_field_order(&layouts).into_iter()
+            .map(|i| fields[i].clone())
+            .collect();
+        let padding = self.layouts.compute_padding_bytes(fields);
+        let optimal = self.layouts.compute_padding_bytes(&reordered);
+        if optimal < padding {
+            self.warnings.push(Error::SuboptimalFieldOrder { name: def.name.name.clone(), padding, optimal, span: def.name.span });
+        }
+    }
 
-                self.symbols.define(Symbol {
-                    name: func.name.name.clone(),
-                    kind: SymbolKind::Function { params: params.clone(), ret: ret.clone(), type_params: func.type_params.iter().map(|p| p.name.clone()).collect(), const_params: vec![], effects: func.effects.clone() },
-                    ty: ResolvedType::Function {
-                        params,
-                        ret: Box::new(ret),
-                    },
-                    span: func.span,
-                    mutable: false,
-                })?;
+    /// Check the `#[bits(N)]` fields of a struct: each is a `bool` of width
+    /// 1 or an unsigned integer no wider than its type, in a struct whose
+    /// layout the packing rule can decide
+    fn check_bitfields(&mut self, def: &StructDef) {
+        if def.fields.iter().all(|f| f.bits.is_none()) {
+            return;
+        }
+        let conflict = def.annotations.iter().find_map(|a| {
+            let arg = a.args.first().map(crate::frontend::formatter::expr_to_string).unwrap_or_default();
+            match (a.name.name.as_str(), arg.as_str()) {
+                ("repr", "packed" | "transparent") | ("field_order", _) => Some(format!("#[{}({})]", a.name.name, arg)),
+                _ => None,
             }
-            Item::Struct(s) => {
-                self.symbols.enter_scope();
+        });
+        let types = self.layouts.struct_fields(&def.name.name).map(<[_]>::to_vec).unwrap_or_default();
+        for (field, (_, ty)) in def.fields.iter().zip(types) {
+            let Some(width) = field.bits else { continue };
+            let max = match &ty {
+                ResolvedType::Primitive(PrimitiveType::Bool) => Some(1),
+                ResolvedType::Primitive(p @ (PrimitiveType::U8 | PrimitiveType::U16 | PrimitiveType::U32
+                    | PrimitiveType::U64 | PrimitiveType::Usize)) => Some(p.size_of() * 8),
+                _ => None,
+            };
+            let reason = match (max, &conflict) {
+                (_, Some(attr)) => format!("bitfields cannot be used in a struct with {}", attr),
+                (None, None) => format!("type '{}' is not bool or an unsigned integer", ty),
+                (Some(_), None) if width == 0 => "width must be at least 1".to_string(),
+                (Some(max), None) if width > max => format!("width {} does not fit in '{}' ({} bits)", width, ty, max),
+                _ => continue,
+            };
+            self.push_error(Error::InvalidBitfield { field: field.name.name.clone(), reason, span: field.span });
+        }
+    }
 
-                // Collect type params and const params separately
-                let mut type_params = Vec::new();
-                let mut const_params = Vec::new();
+    /// Reject borrowing bitfield `field` of `struct_name`, or storing an
+    /// integer constant into it that needs more bits than it has
+    fn check_bitfield_use(&self, struct_name: &str, field: &str, borrowed: bool, stored: Option<(i64, Span)>, span: Span) -> Result<()> {
+        let Some(&width) = self.bitfields.get(&(struct_name.to_string(), field.to_string())) else {
+            return Ok(());
+        };
+        let (reason, span) = match stored {
+            _ if borrowed => ("a bitfield has no address to borrow".to_string(), span),
+            Some((value, span)) if value < 0 || (width < 64 && value >> width != 0) => {
+                (format!("{} does not fit in {} bits", value, width), span)
+            }
+            _ => return Ok(()),
+        };
+        Err(Error::InvalidBitfield { field: field.to_string(), reason, span })
+    }
 
-                for param in &s.generic_params {
-                    match param {
-                        crate::frontend::ast::GenericParam::Type(ident) => {
-                            type_params.push(ident.name.clone());
-                            self.symbols.define(Symbol {
-                                name: ident.name.clone(),
-                                kind: SymbolKind::TypeParam,
-                                ty: ResolvedType::GenericParam(ident.name.clone()),
-                                span: ident.span,
-                                mutable: false,
-                            })?;
-                        }
-                        crate::frontend::ast::GenericParam::Const { name, ty } => {
-                            let resolved_ty = self.resolve_type(ty)?;
-                            const_params.push((name.name.clone(), resolved_ty.clone()));
-                            self.symbols.define(Symbol {
-                                name: name.name.clone(),
-                                kind: SymbolKind::ConstParam { ty: resolved_ty.clone() },
-                                ty: resolved_ty,
-                                span: name.span,
-                                mutable: false,
-                            })?;
-                        }
-                    }
-                }
+    /// Record `error`, or count it once `max_errors` have been kept
+    fn push_error(&mut self, error: Error) {
+        if self.max_errors == 0 || self.errors.len() < self.max_errors {
+            self.errors.push(error);
+            return;
+        }
+        if self.suppressed_errors == 0 {
+            self.errors.push(Error::TooManyErrors { limit: self.max_errors });
+        }
+        self.suppressed_errors += 1;
+    }
 
-                // Also handle legacy type_params field for backward compatibility
-                for param in &s.type_params {
-                    if !type_params.contains(&param.name) {
-                        type_params.push(param.name.clone());
-                        self.symbols.define(Symbol {
-                            name: param.name.clone(),
-                            kind: SymbolKind::TypeParam,
-                            ty: ResolvedType::GenericParam(param.name.clone()),
-                            span: param.span,
-                            mutable: false,
-                        })?;
+    /// Check `stmts` as the body of a `main` that may use every effect and
+    /// return the type of the last one; for `aethc repl`, whose items have
+    /// already been analyzed
+    pub fn check_snippet(&mut self, stmts: &[Stmt]) -> Result<ResolvedType> {
+        self.errors.clear();
+        let outer = self.symbols.snapshot();
+        self.symbols.enter_scope();
+        self.ownership = OwnershipState::new();
+        self.current_effects = Some(EffectSet {
+            is_pure: false,
+            effects: vec![Effect::Read, Effect::Write, Effect::IO, Effect::Alloc, Effect::Panic],
+        });
+        let result = stmts.iter().try_fold(ResolvedType::unit(), |_, stmt| self.check_stmt(stmt));
+        self.current_effects = None;
+        self.symbols.restore(outer);
+        let ty = result?;
+        match self.errors.first() {
+            Some(error) => Err(error.clone()),
+            None => Ok(ty),
+        }
+    }
+
+    /// Copy of the analyzer after resolution, with no diagnostics of its own
+    fn fork(&self) -> Self {
+        let mut worker = self.clone();
+        worker.errors.clear();
+        worker.warnings.clear();
+        worker.strict_only.clear();
+        worker.suppressed_errors = 0;
+        if let Some(types) = &mut worker.expr_types {
+            types.clear();
+        }
+        worker
+    }
+
+    /// Type check a top-level item of the program
+    fn check_top_level_item(&mut self, item: &Item, hosted: bool) -> Result<()> {
+        if let Item::Function(func) = item {
+            if hosted && func.name.name == "main" {
+                self.check_main_signature(func)?;
+            }
+        }
+        self.check_item(item)
+    }
+
+    /// Bind the associated types that impl blocks leave to their interface's default
+    fn bind_default_assoc_types(&mut self, items: &[Item]) -> Result<()> {
+        for item in items {
+            match item {
+                Item::Impl(impl_block) => {
+                    let Some(interface) = &impl_block.interface else { continue };
+                    let declared = self.interface_assoc_types.get(&interface.name).cloned().unwrap_or_default();
+                    for assoc in &declared {
+                        let key = (impl_block.target.name.clone(), assoc.name.name.clone());
+                        if self.assoc_types.contains_key(&key) {
+                            continue;
+                        }
+                        match &assoc.default_ty {
+                            Some(default_ty) => {
+                                let ty = self.resolve_type(default_ty)?;
+                                self.assoc_types.insert(key, ty);
+                            }
+                            None => return Err(Error::MissingAssociatedType {
+                                name: assoc.name.name.clone(),
+                                interface: interface.name.clone(),
+                                span: impl_block.span,
+                            }),
+                        }
+                    }
+                }
+                Item::Module(m) => {
+                    if let Some(items) = &m.items {
+                        self.module_path.push(m.name.name.clone());
+                        let result = self.bind_default_assoc_types(items);
+                        self.module_path.pop();
+                        result?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// `::`-joined path of an item named `name` in the current module
+    fn qualified_name(&self, name: &str) -> String {
+        self.module_path.iter().map(String::as_str)
+            .chain(std::iter::once(name))
+            .collect::<Vec<_>>()
+            .join("::")
+    }
+
+    /// Type of a builtin `str` method call: `len()`, `as_ptr()` or `slice(start, end)`
+    fn check_str_method(&mut self, method: &Ident, args: &[Expr], span: Span) -> Result<ResolvedType> {
+        let (arity, ret) = match method.name.as_str() {
+            "len" => (0, ResolvedType::Primitive(PrimitiveType::Usize)),
+            "as_ptr" => (0, ResolvedType::ptr(Box::new(ResolvedType::U8))),
+            "slice" => (2, ResolvedType::Str),
+            _ => return Err(Error::MethodNotFound { method: method.name.clone(), ty: "str".to_string(), span }),
+        };
+        if args.len() != arity {
+            return Err(Error::ArgCountMismatch {
+                func_name: format!("str::{}", method.name),
+                expected: arity,
+                got: args.len(),
+                span,
+            });
+        }
+        for arg in args {
+            match self.check_expr(arg)? {
+                ResolvedType::Primitive(p) if p.is_integer() => {}
+                ResolvedType::Unknown => {}
+                other => return Err(Error::TypeMismatch {
+                    expected: "integer".to_string(),
+                    got: format!("{:?}", other),
+                    span: arg.span(),
+                }),
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Type of a builtin `Vec<T>` or `HashMap<K, V>` method call
+    fn check_collection_method(&mut self, name: &str, type_args: &[ResolvedType], method: &Ident, args: &[Expr], span: Span) -> Result<ResolvedType> {
+        let arg = |i: usize| type_args.get(i).cloned().unwrap_or(ResolvedType::Unknown);
+        let usize_ty = ResolvedType::Primitive(PrimitiveType::Usize);
+        let (params, ret) = match (name, method.name.as_str()) {
+            ("Vec", "push") => (vec![arg(0)], ResolvedType::unit()),
+            ("Vec", "pop") => (vec![], ResolvedType::Generic("Option".to_string(), vec![arg(0)])),
+            ("Vec", "get") => (vec![usize_ty.clone()], ResolvedType::Generic("Option".to_string(), vec![
+                ResolvedType::Reference { mutable: false, inner: Box::new(arg(0)) },
+            ])),
+            ("Vec", "clear") => (vec![], ResolvedType::unit()),
+            ("HashMap", "insert") => (vec![arg(0), arg(1)], ResolvedType::unit()),
+            ("HashMap", "get") => (vec![arg(0)], arg(1)),
+            ("HashMap", "contains" | "remove") => (vec![arg(0)], ResolvedType::BOOL),
+            (_, "len") => (vec![], usize_ty),
+            (_, "is_empty") => (vec![], ResolvedType::BOOL),
+            (_, "free") => (vec![], ResolvedType::unit()),
+            _ => return Err(Error::MethodNotFound { method: method.name.clone(), ty: name.to_string(), span }),
+        };
+        if args.len() != params.len() {
+            return Err(Error::ArgCountMismatch {
+                func_name: format!("{}::{}", name, method.name),
+                expected: params.len(),
+                got: args.len(),
+                span,
+            });
+        }
+        if matches!(method.name.as_str(), "push" | "insert") {
+            self.require_alloc(&format!("{}::{}", name, method.name), span)?;
+        }
+        // `pop` moves the element into an `Option`, whose payload is erased
+        if (name, method.name.as_str()) == ("Vec", "pop") {
+            self.check_payload_type("Option", &arg(0), method.span)?;
+        }
+        for (arg, param) in args.iter().zip(&params) {
+            let got = self.check_expr(arg)?;
+            let compatible = match (param, &got) {
+                // Indices and integer keys accept any integer
+                (ResolvedType::Primitive(a), ResolvedType::Primitive(b)) if a.is_integer() => b.is_integer(),
+                _ => self.types_compatible(param, &got),
+            };
+            if !compatible {
+                return Err(Error::TypeMismatch {
+                    expected: format!("{:?}", param),
+                    got: format!("{:?}", got),
+                    span: arg.span(),
+                });
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Name and type arguments of a builtin collection, seen through references
+    fn builtin_collection<'t>(&self, ty: &'t ResolvedType) -> Option<(&'t str, &'t [ResolvedType])> {
+        match ty {
+            ResolvedType::Reference { inner, .. } => self.builtin_collection(inner),
+            ResolvedType::Generic(name, args) if matches!(name.as_str(), "Vec" | "HashMap") && !self.impl_methods.contains_key(name) => {
+                Some((name, args))
+            }
+            _ => None,
+        }
+    }
+
+    /// `unwrap`, `unwrap_or` and `map` on `Option`, and `unwrap` and `map_err`
+    /// on `Result`, unless the program's own impl defines them
+    fn check_prelude_method(&mut self, receiver_ty: &ResolvedType, method: &Ident, args: &[Expr], span: Span) -> Result<Option<ResolvedType>> {
+        let Some((ResolvedType::Generic(name, type_args), _)) = Self::auto_deref(receiver_ty) else { return Ok(None) };
+        if self.find_method(name, &method.name).is_some() {
+            return Ok(None);
+        }
+        let arg = |i: usize| type_args.get(i).cloned().unwrap_or(ResolvedType::Unknown);
+        let (name, value_ty, error_ty) = (name.clone(), arg(0), arg(1));
+        let arg_count = match (name.as_str(), method.name.as_str()) {
+            ("Option" | "Result", "unwrap") => 0,
+            ("Option", "unwrap_or" | "map") | ("Result", "map_err") => 1,
+            // Nothing would define any other method of the prelude enums
+            ("Option" | "Result", _) if self.symbols.lookup(&format!("{}::{}", name, method.name)).is_none() => {
+                return Err(Error::MethodNotFound { method: method.name.clone(), ty: name, span });
+            }
+            _ => return Ok(None),
+        };
+        if args.len() != arg_count {
+            return Err(Error::ArgCountMismatch {
+                func_name: format!("{}::{}", name, method.name),
+                expected: arg_count,
+                got: args.len(),
+                span,
+            });
+        }
+        match method.name.as_str() {
+            "unwrap" => Ok(Some(value_ty)),
+            "unwrap_or" => {
+                let got = self.check_expr(&args[0])?;
+                if !self.types_compatible(&value_ty, &got) {
+                    return Err(Error::TypeMismatch {
+                        expected: format!("{:?}", value_ty),
+                        got: format!("{:?}", got),
+                        span: args[0].span(),
+                    });
+                }
+                Ok(Some(if value_ty == ResolvedType::Unknown { got } else { value_ty }))
+            }
+            _ => {
+                // `map` transforms the value and `map_err` the error
+                let input = if method.name == "map" { value_ty.clone() } else { error_ty };
+                let mapped = match self.check_expr(&args[0])? {
+                    ResolvedType::Function { params, ret, .. } if params.len() == 1 && self.types_compatible(&params[0], &input) => *ret,
+                    ResolvedType::Unknown => ResolvedType::Unknown,
+                    got => return Err(Error::TypeMismatch {
+                        expected: format!("a function taking {:?}", input),
+                        got: format!("{:?}", got),
+                        span: args[0].span(),
+                    }),
+                };
+                let type_args = if method.name == "map" { vec![mapped] } else { vec![value_ty, mapped] };
+                Ok(Some(ResolvedType::Generic(name, type_args)))
+            }
+        }
+    }
+
+    /// Allocating builtins need `effect[alloc]` in functions that declare their effects
+    fn require_alloc(&self, name: &str, span: Span) -> Result<()> {
+        match &self.current_effects {
+            Some(effects) if effects.is_pure || !effects.effects.contains(&Effect::Alloc) => Err(Error::EffectViolation {
+                message: format!("calling '{}' requires effect[Alloc]. Add 'effect[alloc]' to function signature.", name),
+                span,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Collect a top-level definition
+    fn collect_definition(&mut self, item: &Item) -> Result<()> {
+        match item {
+            Item::Module(m) => {
+                if let Some(items) = &m.items {
+                    self.module_path.push(m.name.name.clone());
+                    let result = items.iter().try_for_each(|item| self.collect_definition(item));
+                    self.module_path.pop();
+                    result?;
+                }
+            }
+            Item::Function(func) => {
+                // Const parameters may appear in the signature (`[i64; N]`)
+                self.symbols.enter_scope();
+                let const_params = self.define_const_params(func)?;
+                self.define_type_params(func)?;
+                let params: Vec<ResolvedType> = func.params.iter()
+                    .map(|p| self.resolve_type(&p.ty))
+                    .collect::<Result<Vec<_>>>()?;
+                let ret = func.ret_type.as_ref()
+                    .map(|t| self.resolve_type(t))
+                    .transpose()?
+                    .unwrap_or(ResolvedType::unit());
+                self.symbols.exit_scope();
+
+                let link_name = mangle::link_name(&self.module_name, &self.module_path, &func.name.name, func.is_pub, func.is_unmangled());
+                let bounds = Self::type_param_bounds(func);
+                if !bounds.is_empty() {
+                    self.type_param_bounds.insert(link_name.clone(), bounds);
+                }
+                self.symbols.define(Symbol {
+                    name: self.qualified_name(&func.name.name),
+                    kind: SymbolKind::Function {
+                        params: params.clone(),
+                        ret: ret.clone(),
+                        type_params: func.type_params.iter().map(|p| p.name.clone()).collect(),
+                        const_params,
+                        effects: func.effects.clone(),
+                        link_name,
+                    },
+                    ty: ResolvedType::Function {
+                        params,
+                        ret: Box::new(ret),
+                        effects: Box::new(func.effects.clone()),
+                    },
+                    span: func.span,
+                    mutable: false,
+                })?;
+            }
+            Item::Struct(s) => {
+                Arc::make_mut(&mut self.impl_methods).entry(s.name.name.clone()).or_default();
+                self.symbols.enter_scope();
+
+                // Collect type params and const params separately
+                let mut type_params = Vec::new();
+                let mut const_params = Vec::new();
+
+                for param in &s.generic_params {
+                    match param {
+                        crate::frontend::ast::GenericParam::Type { name: ident, .. } => {
+                            type_params.push(ident.name.clone());
+                            self.symbols.define(Symbol {
+                                name: ident.name.clone(),
+                                kind: SymbolKind::TypeParam,
+                                ty: ResolvedType::GenericParam(ident.name.clone()),
+                                span: ident.span,
+                                mutable: false,
+                            })?;
+                        }
+                        crate::frontend::ast::GenericParam::Const { name, ty } => {
+                            let resolved_ty = self.resolve_type(ty)?;
+                            const_params.push((name.name.clone(), resolved_ty.clone()));
+                            self.symbols.define(Symbol {
+                                name: name.name.clone(),
+                                kind: SymbolKind::ConstParam { ty: resolved_ty.clone() },
+                                ty: resolved_ty,
+                                span: name.span,
+                                mutable: false,
+                            })?;
+                        }
+                    }
+                }
+
+                // Also handle legacy type_params field for backward compatibility
+                for param in &s.type_params {
+                    if !type_params.contains(&param.name) {
+                        type_params.push(param.name.clone());
+                        self.symbols.define(Symbol {
+                            name: param.name.clone(),
+                            kind: SymbolKind::TypeParam,
+                            ty: ResolvedType::GenericParam(param.name.clone()),
+                            span: param.span,
+                            mutable: false,
+                        })?;
                     }
                 }
 
@@ -701,6 +1783,27 @@ impl SemanticAnalyzer {
 
                 self.symbols.exit_scope();
 
+                let packed = s.annotations.iter().any(|ann| {
+                    ann.name.name == "repr"
+                        && matches!(ann.args.first(), Some(Expr::Ident(id)) if id.name == "packed")
+                });
+                if s.derives_eq() {
+                    self.derived_eq.insert(s.name.name.clone());
+                }
+                let bits: Vec<Option<u32>> = s.fields.iter().map(|f| f.bits.map(|b| b as u32)).collect();
+                if s.field_order() == Some(FieldOrder::Optimal) {
+                    self.layouts.register_optimal_struct(&s.name.name, fields.clone());
+                } else if bits.iter().any(Option::is_some) && !packed {
+                    for field in &s.fields {
+                        if let Some(width) = field.bits {
+                            self.bitfields.insert((s.name.name.clone(), field.name.name.clone()), width as u32);
+                        }
+                    }
+                    self.layouts.register_bitfield_struct(&s.name.name, fields.clone(), &bits);
+                } else {
+                    self.layouts.register_struct(&s.name.name, fields.clone(), packed);
+                }
+
                 self.symbols.define(Symbol {
                     name: s.name.name.clone(),
                     kind: SymbolKind::Struct {
@@ -717,6 +1820,7 @@ impl SemanticAnalyzer {
                 })?;
             }
             Item::Enum(e) => {
+                Arc::make_mut(&mut self.impl_methods).entry(e.name.name.clone()).or_default();
                 self.symbols.enter_scope();
 
                 // Collect type params and const params separately
@@ -725,7 +1829,7 @@ impl SemanticAnalyzer {
 
                 for param in &e.generic_params {
                     match param {
-                        crate::frontend::ast::GenericParam::Type(ident) => {
+                        crate::frontend::ast::GenericParam::Type { name: ident, .. } => {
                             type_params.push(ident.name.clone());
                             self.symbols.define(Symbol {
                                 name: ident.name.clone(),
@@ -763,15 +1867,25 @@ impl SemanticAnalyzer {
                     }
                 }
 
-                let variants: Vec<String> = e.variants.iter()
+                let (repr, discriminants) = enum_discriminants(e)?;
+                let variants: Vec<(String, i64)> = e.variants.iter()
                     .map(|v| v.name.name.clone())
+                    .zip(discriminants)
                     .collect();
 
+                let payloads: Vec<Vec<ResolvedType>> = e.variants.iter()
+                    .map(|v| v.fields.iter().map(|t| self.resolve_type(t)).collect::<Result<Vec<_>>>())
+                    .collect::<Result<Vec<_>>>()?;
+                self.layouts.register_enum(&e.name.name, Layout::new(repr.size_of(), repr.align_of()), payloads);
+                if e.derives_eq() {
+                    self.derived_eq.insert(e.name.name.clone());
+                }
+
                 self.symbols.exit_scope();
 
                 self.symbols.define(Symbol {
                     name: e.name.name.clone(),
-                    kind: SymbolKind::Enum { variants, type_params, const_params },
+                    kind: SymbolKind::Enum { variants, repr, type_params, const_params },
                     ty: ResolvedType::Enum { name: e.name.name.clone() },
                     span: e.span,
                     mutable: false,
@@ -804,50 +1918,107 @@ impl SemanticAnalyzer {
                                 .transpose()?
                                 .unwrap_or(ResolvedType::unit());
 
+                            self.symbols.mark_function_unsafe(&name.name);
+                            self.symbols.mark_function_extern(&name.name);
                             self.symbols.define(Symbol {
                                 name: name.name.clone(),
-                                kind: SymbolKind::Function { params: param_types.clone(), ret: ret.clone(), type_params: vec![], const_params: vec![], effects: EffectSet::default() },
+                                kind: SymbolKind::Function { params: param_types.clone(), ret: ret.clone(), type_params: vec![], const_params: vec![], effects: EffectSet::default(), link_name: name.name.clone() },
                                 ty: ResolvedType::Function {
                                     params: param_types,
                                     ret: Box::new(ret),
+                                    effects: Box::default(),
                                 },
                                 span: name.span,
                                 mutable: false,
                             })?;
                         }
-                        crate::frontend::ast::ForeignItem::Static { name, ty, .. } => {
+                        crate::frontend::ast::ForeignItem::Static { name, ty, is_mut, .. } => {
                             let resolved_ty = self.resolve_type(ty)?;
                             self.symbols.define(Symbol {
                                 name: name.name.clone(),
-                                kind: SymbolKind::Variable,
+                                kind: SymbolKind::Static { is_mut: *is_mut },
                                 ty: resolved_ty,
                                 span: name.span,
-                                mutable: false,
+                                mutable: *is_mut,
                             })?;
                         }
                     }
                 }
             }
-            Item::TypeAlias(alias) => {
-                // Resolve the target type
-                let target = self.resolve_type(&alias.ty)?;
+            Item::Static(st) => {
+                let ty = self.resolve_type(&st.ty)?;
+                self.symbols.define(Symbol {
+                    name: st.name.name.clone(),
+                    kind: SymbolKind::Static { is_mut: st.is_mut },
+                    ty,
+                    span: st.span,
+                    mutable: st.is_mut,
+                })?;
+            }
+            Item::Union(u) => {
+                Arc::make_mut(&mut self.impl_methods).entry(u.name.name.clone()).or_default();
+                let fields: Vec<(String, ResolvedType)> = u.fields.iter()
+                    .map(|f| Ok((f.name.name.clone(), self.resolve_type(&f.ty)?)))
+                    .collect::<Result<Vec<_>>>()?;
+
+                self.layouts.register_union(&u.name.name, fields.clone(), u.repr == Some(Repr::Packed));
+
                 self.symbols.define(Symbol {
-                    name: alias.name.name.clone(),
-                    kind: SymbolKind::TypeAlias { target: target.clone() },
-                    ty: target,
-                    span: alias.span,
+                    name: u.name.name.clone(),
+                    kind: SymbolKind::Union { fields: fields.clone() },
+                    ty: ResolvedType::Struct {
+                        name: u.name.name.clone(),
+                        fields,
+                    },
+                    span: u.span,
                     mutable: false,
                 })?;
             }
-            Item::Use(use_decl) => {
-                // Resolve use declaration by loading module symbols
-                self.resolve_use_decl(use_decl)?;
+            // Resolved before any other item, by `ResolutionPass`
+            Item::TypeAlias(_) => {}
+            // Loaded by `CollectionPass`
+            Item::Use(_) => {}
+            Item::Interface(iface) | Item::Trait(iface) => {
+                self.interface_assoc_types.insert(iface.name.name.clone(), iface.associated_types.clone());
+                Arc::make_mut(&mut self.interface_defaults).insert(iface.name.name.clone(), iface.default_methods.clone());
+                Arc::make_mut(&mut self.interface_methods).insert(iface.name.name.clone(), iface.methods.clone());
             }
-            _ => {} // Impl and Interface handled separately
+            Item::Impl(impl_block) => {
+                let target = impl_block.target.name.clone();
+                for binding in &impl_block.associated_types {
+                    let ty = self.resolve_type(&binding.ty)?;
+                    self.assoc_types.insert((target.clone(), binding.name.name.clone()), ty);
+                }
+                // Methods of a primitive are called on values, never through its name
+                let target_ty = self.resolve_type(&Type::Named(target.clone(), impl_block.target.span));
+                if let Ok(ResolvedType::Primitive(primitive)) = target_ty {
+                    Arc::make_mut(&mut self.primitive_methods).entry(primitive).or_default()
+                        .extend(impl_block.methods.iter().cloned());
+                } else {
+                    Arc::make_mut(&mut self.impl_methods).entry(target.clone()).or_default()
+                        .extend(impl_block.methods.iter().cloned());
+                }
+                if let Some(interface) = &impl_block.interface {
+                    self.impl_interfaces.entry(target).or_default().push(interface.name.clone());
+                }
+            }
+            _ => {} // Impl methods are checked by `CheckingPass`
         }
         Ok(())
     }
     
+    /// Define `alias` with its target resolved
+    fn resolve_type_alias(&mut self, alias: &TypeAliasDef) -> Result<()> {
+        let target = self.resolve_type(&alias.ty)?;
+        self.symbols.define(Symbol {
+            name: alias.name.name.clone(),
+            kind: SymbolKind::TypeAlias { target: target.clone() },
+            ty: target,
+            span: alias.span,
+            mutable: false,
+        })
+    }
+
     /// Resolve a use declaration by importing symbols from the target module
     fn resolve_use_decl(&mut self, use_decl: &UseDecl) -> Result<()> {
         // Get module name from path (first segment)
@@ -863,10 +2034,32 @@ impl SemanticAnalyzer {
         }
         
         // Try to load the actual module file
-        if self.module_resolver.find_module(&module_name).is_some() {
+        let loaded = {
+            let mut resolver = self.module_resolver.lock().expect("module resolver lock poisoned");
+            resolver.find_module(&module_name)
+                .map(|_| resolver.load_module_symbols(&module_name, use_decl.span)
+                    .map(|symbols| (symbols, resolver.private_names(&module_name))))
+        };
+        if let Some(loaded) = loaded {
             // Load module and get symbols
-            match self.module_resolver.load_module_symbols(&module_name, use_decl.span) {
-                Ok(symbols) => {
+            match loaded {
+                Ok((symbols, private)) => {
+                    // Naming a private item is an error; a glob only brings
+                    // in what is public
+                    let named: Vec<&Ident> = match &use_decl.kind {
+                        UseKind::Group(decls) => decls.iter().filter_map(|d| d.path.first()).collect(),
+                        UseKind::Glob => Vec::new(),
+                        UseKind::Simple | UseKind::Alias(_) => use_decl.path.get(1).into_iter().collect(),
+                    };
+                    if let Some(name) = named.into_iter().find(|name| private.items.contains(&name.name)) {
+                        return Err(Error::PrivateSymbol {
+                            name: name.name.clone(),
+                            module: module_name,
+                            span: name.span,
+                        });
+                    }
+                    self.private_imports.insert(module_name.clone(), private);
+
                     // Store in imported_modules for qualified name lookup
                     self.imported_modules.insert(module_name.clone(), symbols.clone());
 
@@ -883,13 +2076,20 @@ impl SemanticAnalyzer {
                     }
                     return Ok(());
                 }
-                Err(_) => {
-                    // Fall back to placeholders
+                // Every cycle is reported, the first one as the failure
+                Err(e @ Error::CyclicImport { .. }) => {
+                    let others = self.module_resolver.lock().expect("module resolver lock poisoned").take_cycles();
+                    for cycle in others {
+                        self.push_error(Error::CyclicImport { cycle, span: use_decl.span });
+                    }
+                    return Err(e);
                 }
+                // The file exists, so a broken import is a real error
+                Err(e) => return Err(e),
             }
         }
         
-        // Module not found or failed to load - register placeholders for self-hosting
+        // Module not found - register placeholders for self-hosting
         self.register_module_placeholder(&module_name, use_decl)?;
         
         Ok(())
@@ -1002,7 +2202,7 @@ impl SemanticAnalyzer {
                 })?;
                 self.symbols.define(Symbol {
                     name: "TokenKind".to_string(),
-                    kind: SymbolKind::Enum { variants: vec![], type_params: vec![], const_params: vec![] },
+                    kind: SymbolKind::Enum { variants: vec![], repr: PrimitiveType::I32, type_params: vec![], const_params: vec![] },
                     ty: ResolvedType::Enum { name: "TokenKind".to_string() },
                     span: use_decl.span,
                     mutable: false,
@@ -1019,6 +2219,7 @@ impl SemanticAnalyzer {
                         type_params: vec![],
                         const_params: vec![],
                         effects: EffectSet::default(),
+                        link_name: "keyword_from_str".to_string(),
                     },
                     ty: ResolvedType::Function {
                         params: vec![ResolvedType::Reference {
@@ -1026,6 +2227,7 @@ impl SemanticAnalyzer {
                             inner: Box::new(ResolvedType::Struct { name: "String".to_string(), fields: vec![] }),
                         }],
                         ret: Box::new(ResolvedType::Enum { name: "TokenKind".to_string() }),
+                        effects: Box::default(),
                     },
                     span: use_decl.span,
                     mutable: false,
@@ -1045,7 +2247,7 @@ impl SemanticAnalyzer {
     fn check_item(&mut self, item: &Item) -> Result<()> {
         match item {
             Item::Function(func) => self.check_function(func),
-            Item::Struct(_) => Ok(()), // Already collected
+            Item::Struct(s) => self.check_invariants(s),
             Item::Enum(_) => Ok(()),   // Already collected
             Item::Impl(impl_block) => self.check_impl(impl_block),
             Item::Interface(_) => Ok(()), // Already collected
@@ -1058,163 +2260,1556 @@ impl SemanticAnalyzer {
             Item::Module(m) => {
                 // Recursively check module items
                 if let Some(items) = &m.items {
-                    for item in items {
-                        self.check_item(item)?;
-                    }
+                    self.module_path.push(m.name.name.clone());
+                    let result = items.iter().try_for_each(|item| self.check_item(item));
+                    self.module_path.pop();
+                    result?;
                 }
                 Ok(())
             }
             Item::Use(_) => Ok(()), // Import resolution handled elsewhere
             // Phase 8: FFI and System features
             Item::Extern(_) => Ok(()), // FFI - external symbols registered elsewhere
-            Item::Static(_) => Ok(()), // TODO: Check static variable type
-            Item::Union(_) => Ok(()), // TODO: Check union field types
+            Item::Static(st) => self.check_static(st),
+            Item::Union(u) => self.check_union(u),
             Item::Trait(_) => Ok(()), // Trait definitions checked in collect phase
             Item::TypeAlias(_) => Ok(()), // Type aliases resolved in collect phase
         }
     }
 
-    /// Type check a function
-    fn check_function(&mut self, func: &Function) -> Result<()> {
-        self.symbols.enter_scope();
-        self.ownership = OwnershipState::new();
-        
-        // Set effect context for this function (for effect propagation checking)
-        self.current_effects = Some(func.effects.clone());
-
-        // Add parameters to scope
-        for param in &func.params {
-            let ty = self.resolve_type(&param.ty)?;
-            self.symbols.define(Symbol {
-                name: param.name.name.clone(),
-                kind: SymbolKind::Param { ownership: param.ownership },
-                ty: ty.clone(),
-                span: param.span,
-                mutable: param.ownership == Ownership::Mut,
-            })?;
-            self.ownership.add_owned(param.name.name.clone(), param.span);
-        }
-        
-        // Resolve return type for 'result' variable in ensures contracts
-        let return_type = if let Some(ref ret_ty) = func.ret_type {
-            self.resolve_type(ret_ty)?
-        } else {
-            ResolvedType::UNIT
-        };
+    /// Check an `as` cast between numbers, pointers and field-less enums
+    fn check_cast(&mut self, expr: &Expr, ty: &Type, span: Span) -> Result<ResolvedType> {
+        let source_ty = self.check_expr(expr)?;
+        let target_ty = self.resolve_type(ty)?;
         
-        // Check contract expressions (requires, ensures)
-        for contract in &func.contracts {
-            // For 'ensures' contracts, add 'result' variable to scope
-            // This allows postconditions to reference the return value
-            let is_ensures = matches!(contract.kind, crate::frontend::ast::ContractKind::Ensures);
-            
-            if is_ensures && return_type != ResolvedType::UNIT {
-                // Temporarily add 'result' variable for ensures checking
-                self.symbols.enter_scope();
-                self.symbols.define(Symbol {
-                    name: "result".to_string(),
-                    kind: SymbolKind::Variable,
-                    ty: return_type.clone(),
-                    span: func.span,
-                    mutable: false,
-                })?;
+        // Allow explicit casts in the following cases:
+        // 1. Integer to Integer (any size)
+        // 2. Integer to Pointer (for raw memory access)
+        // 3. Pointer to Integer (address extraction)
+        // 4. Pointer to Pointer (reinterpret)
+        // 5. Same type (no-op)
+        // 6. Field-less enum to its discriminant, and back inside `unsafe`
+
+        match (&source_ty, &target_ty) {
+            (ResolvedType::Enum { name }, ResolvedType::Primitive(p)) if p.is_integer() => {
+                self.expect_fieldless_enum(name, span)?;
+                return Ok(target_ty);
             }
-            
-            let contract_ty = self.check_expr(&contract.condition)?;
-            
-            // Contract expressions must be boolean
-            if contract_ty != ResolvedType::BOOL && contract_ty != ResolvedType::Unknown {
-                if self.strict_mode {
-                    if is_ensures && return_type != ResolvedType::UNIT {
-                        self.symbols.exit_scope();
-                    }
-                    return Err(Error::TypeMismatch {
-                        expected: "bool".to_string(),
-                        got: format!("{:?}", contract_ty),
-                        span: contract.span,
-                    });
-                } else {
-                    // In lenient mode, just warn (add to errors but don't fail)
-                    self.errors.push(Error::TypeMismatch {
-                        expected: "bool".to_string(),
-                        got: format!("{:?}", contract_ty),
-                        span: contract.span,
+            (ResolvedType::Primitive(p), ResolvedType::Enum { name }) if p.is_integer() => {
+                self.expect_fieldless_enum(name, span)?;
+                if self.unsafe_depth == 0 {
+                    return Err(Error::UnsafeRequired {
+                        what: format!("Casting an integer to enum '{}' is unchecked (the value must be one of its discriminants) and", name),
+                        span,
                     });
                 }
+                self.unsafe_operations += 1;
+                return Ok(target_ty);
             }
-            
-            if is_ensures && return_type != ResolvedType::UNIT {
-                self.symbols.exit_scope();
-            }
+            _ => {}
         }
-
-        // Check function body
-        self.check_block(&func.body)?;
         
-        // Clear effect context
-        self.current_effects = None;
+        let is_valid_cast = match (&source_ty, &target_ty) {
+            // Same type
+            (s, t) if s == t => true,
+            
+            // Int to Int
+            (ResolvedType::Primitive(p1), ResolvedType::Primitive(p2)) 
+                if p1.is_integer() && p2.is_integer() => true,
 
-        self.symbols.exit_scope();
-        Ok(())
-    }
+            // Char to its code point
+            (ResolvedType::Primitive(PrimitiveType::Char), ResolvedType::Primitive(p)) if p.is_integer() => true,
+            
+            // Int to Pointer
+            (ResolvedType::Primitive(p), ResolvedType::Pointer(_)) => {
+                p.is_integer()
+            },
+            
+            // Pointer to Int
+            (ResolvedType::Pointer(_), ResolvedType::Primitive(p))
+                if p.is_integer() => true,
+            
+            // Pointer to Pointer
+            (ResolvedType::Pointer(_), ResolvedType::Pointer(_)) => true,
 
-    /// Type check an impl block
-    fn check_impl(&mut self, impl_block: &ImplBlock) -> Result<()> {
-        for method in &impl_block.methods {
-            self.check_function(method)?;
-        }
-        Ok(())
+            // `str` to its data pointer
+            (ResolvedType::Str, ResolvedType::Pointer(_)) => true,
+            
+            // Reference to Pointer (same inner type or compatible)
+            (ResolvedType::Reference { inner, .. }, ResolvedType::Pointer(ptr_inner)) => {
+                inner.as_ref() == ptr_inner.as_ref()
+            },
+            
+            // Unknown source (permissive for now)
+            (ResolvedType::Unknown, _) => true,
+            
+            _ => false,
+        };
+        
+        if !is_valid_cast {
+            return Err(Error::TypeMismatch {
+                expected: format!("{:?}", target_ty),
+                got: format!("{:?}", source_ty),
+                span,
+            });
+        }
+        // An integer made into a pointer points wherever it says
+        let is_integer = |ty: &ResolvedType| matches!(ty, ResolvedType::Primitive(p) if p.is_integer());
+        let is_pointer = |ty: &ResolvedType| matches!(ty, ResolvedType::Pointer(_));
+        if (is_integer(&source_ty) && is_pointer(&target_ty)) || (is_pointer(&source_ty) && is_integer(&target_ty)) {
+            self.require_unsafe(format!("cast from {} to {}", source_ty, target_ty), span)?;
+        }
+        
+        Ok(target_ty)
+    }
+
+    /// Only enums without payloads convert to and from their discriminant
+    fn expect_fieldless_enum(&self, name: &str, span: Span) -> Result<()> {
+        let fieldless = self.layouts.enum_payloads(name)
+            .is_some_and(|variants| variants.iter().all(Vec::is_empty));
+        if fieldless {
+            return Ok(());
+        }
+        Err(Error::TypeMismatch {
+            expected: "an enum without payloads in an integer cast".to_string(),
+            got: name.to_string(),
+            span,
+        })
+    }
+
+    /// Check a union definition: fields must be distinct and sized, repr must fit a union
+    fn check_union(&mut self, u: &UnionDef) -> Result<()> {
+        if u.repr == Some(Repr::Transparent) {
+            return Err(Error::InvalidRepr {
+                repr: "transparent".to_string(),
+                item: format!("union '{}'", u.name.name),
+                span: u.span,
+            });
+        }
+        for (i, field) in u.fields.iter().enumerate() {
+            if let Some(first) = u.fields[..i].iter().find(|f| f.name.name == field.name.name) {
+                return Err(Error::DuplicateDefinition {
+                    name: format!("{}.{}", u.name.name, field.name.name),
+                    first: first.span,
+                    span: field.span,
+                });
+            }
+            let ty = self.resolve_type(&field.ty)?;
+            if self.layouts.layout_of(&ty).is_none() {
+                return Err(Error::UnsizedType { ty: format!("{:?}", ty), span: field.span });
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `name` refers to a union type
+    fn is_union(&self, name: &str) -> bool {
+        matches!(self.symbols.lookup(name).map(|s| &s.kind), Some(SymbolKind::Union { .. }))
+    }
+
+    /// Check that the current function may have the effects of calling
+    /// `func`: those declared by the function it names, or those in its type
+    /// when it is a function value such as a parameter
+    fn check_call_effects(&mut self, func: &Expr, func_ty: &ResolvedType, span: Span) -> Result<()> {
+        let Expr::Ident(ident) = func else { return Ok(()) };
+        let callee_effects = match self.symbols.lookup(&ident.name).map(|s| &s.kind) {
+            Some(SymbolKind::Function { effects, .. }) => effects.clone(),
+            Some(_) => match func_ty {
+                ResolvedType::Function { effects, .. } => (**effects).clone(),
+                _ => return Ok(()),
+            },
+            None => return self.check_builtin_call_effects(&ident.name, span),
+        };
+        if let Some(used) = self.closure_effects.as_mut() {
+            for effect in &callee_effects.effects {
+                if !used.contains(effect) {
+                    used.push(*effect);
+                }
+            }
+        }
+        // P5.2: Effect propagation - HARD ERROR if caller doesn't declare required effects
+        let Some(caller_effects) = &self.current_effects else { return Ok(()) };
+        for effect in &callee_effects.effects {
+            // If caller is pure, it cannot call any effectful function
+            if caller_effects.is_pure {
+                return Err(Error::EffectViolation {
+                    message: format!(
+                        "pure function cannot call '{}' which has effect {:?}",
+                        ident.name, effect
+                    ),
+                    span,
+                });
+            }
+            // If caller doesn't have this effect, error
+            if !caller_effects.effects.contains(effect) {
+                return Err(Error::EffectViolation {
+                    message: format!(
+                        "function with effect[{:?}] called from function without that effect. Add 'effect[{:?}]' to caller's signature.",
+                        effect, effect
+                    ),
+                    span,
+                });
+            }
+        }
+        // If callee is pure but caller is not - that's fine (pure is subset of any effect)
+        Ok(())
+    }
+
+    /// Effects of the C functions called without a declaration
+    fn check_builtin_call_effects(&mut self, name: &str, span: Span) -> Result<()> {
+        let io_builtins = ["print", "println", "print_i64", "println_i64", "puts", "printf", "exit"];
+        let alloc_builtins = ["malloc", "free", "realloc", "alloc"];
+        let Some(caller_effects) = &self.current_effects else { return Ok(()) };
+
+        if caller_effects.is_pure {
+            if io_builtins.contains(&name) || alloc_builtins.contains(&name) {
+                return Err(Error::EffectViolation {
+                    message: format!("pure function cannot call impure builtin '{}'", name),
+                    span,
+                });
+            }
+        } else {
+            // Check specific effects for builtins
+            if io_builtins.contains(&name) && !caller_effects.effects.contains(&Effect::IO) {
+                return Err(Error::EffectViolation {
+                    message: format!("calling '{}' requires effect[IO]. Add 'effect[io]' to function signature.", name),
+                    span,
+                });
+            }
+            if alloc_builtins.contains(&name) && !caller_effects.effects.contains(&Effect::Alloc) {
+                return Err(Error::EffectViolation {
+                    message: format!("calling '{}' requires effect[Alloc]. Add 'effect[alloc]' to function signature.", name),
+                    span,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// A function value passed or assigned where a function type is expected
+    /// may only have effects the type allows: `pure` takes only pure values.
+    /// An error in strict mode and a warning otherwise.
+    fn check_effect_flow(&mut self, expected: &ResolvedType, got: &ResolvedType, span: Span) -> Result<()> {
+        let (ResolvedType::Function { effects: allowed, .. }, ResolvedType::Function { effects, .. }) = (expected, got) else {
+            return Ok(());
+        };
+        if allowed.allows(effects) {
+            return Ok(());
+        }
+        let describe = |effects: &EffectSet| if effects.effects.is_empty() && !effects.is_pure {
+            "no effects".to_string()
+        } else {
+            format!("`{}`", effects)
+        };
+        let error = Error::EffectViolation {
+            message: format!("expected a function with {}, found one with {}", describe(allowed), describe(effects)),
+            span,
+        };
+        self.strict_check(self.strictness.effects, error)
+    }
+
+    /// A value converted implicitly to a numeric type that cannot hold all
+    /// of its values (a narrower or differently signed integer, `f32` from
+    /// `f64`). Literals are exempt, their value being known to the reader.
+    /// An error in strict mode and a warning otherwise.
+    fn check_lossy_conversion(&mut self, expected: &ResolvedType, got: &ResolvedType, value: &Expr) -> Result<()> {
+        let (ResolvedType::Primitive(to), ResolvedType::Primitive(from)) = (expected, got) else {
+            return Ok(());
+        };
+        let literal = match value {
+            Expr::Literal(_) => true,
+            Expr::Unary { op: UnOp::Neg, expr, .. } => matches!(expr.as_ref(), Expr::Literal(_)),
+            _ => false,
+        };
+        let lossy = if to.is_integer() && from.is_integer() {
+            to.size_of() < from.size_of() || to.is_signed() != from.is_signed()
+        } else {
+            *to == PrimitiveType::F32 && *from == PrimitiveType::F64
+        };
+        if literal || to == from || !lossy || !self.types_compatible(expected, got) {
+            return Ok(());
+        }
+        let error = Error::LossyConversion { from: got.to_string(), to: expected.to_string(), span: value.span() };
+        self.strict_check(self.strictness.lossy_conversions, error)
+    }
+
+    /// Report `error` as an error when `strict`, the check's setting in the
+    /// current `Strictness`, and as a warning otherwise
+    fn strict_check(&mut self, strict: bool, error: Error) -> Result<()> {
+        if !strict {
+            self.warnings.push(error);
+            return Ok(());
+        }
+        if let Some(span) = error.span() {
+            self.strict_only.push((error.code(), span));
+        }
+        Err(error)
+    }
+
+    /// Check a closure. Its type has the effects its body calls for.
+    fn check_closure(&mut self, params: &[ClosureParam], ret_type: Option<&Type>, body: &Expr) -> Result<ResolvedType> {
+        // Enter a new scope for closure parameters
+        self.symbols.enter_scope();
+        
+        // Add parameters to scope
+        let mut param_types = Vec::new();
+        for param in params {
+            let ty = if let Some(t) = &param.ty {
+                self.resolve_type(t)?
+            } else {
+                // Infer type from usage (for now, default to i64)
+                ResolvedType::Primitive(PrimitiveType::I64)
+            };
+            param_types.push(ty.clone());
+            self.symbols.define(Symbol {
+                name: param.name.name.clone(),
+                kind: SymbolKind::Variable,
+                ty,
+                span: param.name.span,
+                mutable: false,
+            })?;
+        }
+        
+        // Check body and determine return type, collecting its effects
+        let outer = self.closure_effects.replace(Vec::new());
+        let body_ty = self.check_expr(body);
+        let used = std::mem::replace(&mut self.closure_effects, outer).unwrap_or_default();
+        let body_ty = body_ty?;
+        
+        let ret_ty = if let Some(t) = ret_type {
+            self.resolve_type(t)?
+        } else {
+            body_ty
+        };
+        
+        self.symbols.exit_scope();
+        
+        Ok(ResolvedType::Function {
+            params: param_types,
+            ret: Box::new(ret_ty),
+            effects: Box::new(EffectSet { is_pure: false, effects: used }),
+        })
+    }
+
+    /// Record a raw pointer access. Outside `unsafe` this is an error in strict
+    /// mode and a warning otherwise.
+    fn unsafe_operation(&mut self, operation: &str, span: Span) -> Result<()> {
+        if self.unsafe_depth > 0 {
+            self.unsafe_operations += 1;
+            return Ok(());
+        }
+        let error = Error::UnsafeOperationOutsideUnsafeBlock { operation: operation.to_string(), span };
+        self.strict_check(self.strictness.unsafe_operations, error)
+    }
+
+    /// Record an operation only `unsafe` allows. Outside `unsafe` this is an
+    /// error in strict mode and a warning otherwise.
+    fn require_unsafe(&mut self, what: String, span: Span) -> Result<()> {
+        self.unsafe_operations += 1;
+        if self.unsafe_depth > 0 {
+            return Ok(());
+        }
+        self.strict_check(self.strictness.unsafe_operations, Error::UnsafeRequired { what, span })
+    }
+
+    /// Check an inline asm expression: it must be inside `unsafe`, operands must
+    /// name a known register class, inputs must be scalars and outputs mutable places
+    fn check_asm(&mut self, operands: &[AsmOperand], span: Span) -> Result<ResolvedType> {
+        if self.unsafe_depth == 0 {
+            return Err(Error::UnsafeRequired { what: "inline assembly".to_string(), span });
+        }
+        self.unsafe_operations += 1;
+        let invalid = |operand: &AsmOperand, reason: &str| Error::InvalidAsmOperand {
+            operand: operand.options.clone(),
+            reason: reason.to_string(),
+            span,
+        };
+        for op in operands {
+            if op.kind == AsmOperandKind::Clobber {
+                if !is_known_asm_clobber(&op.options) {
+                    return Err(invalid(op, "unknown clobber (expected memory, cc or a register name)"));
+                }
+                continue;
+            }
+            if op.constraint().is_none() {
+                return Err(invalid(op, "unknown register class"));
+            }
+            let Some(expr) = &op.expr else { continue };
+            let ty = self.check_expr(expr)?;
+            if !matches!(ty, ResolvedType::Primitive(_) | ResolvedType::Pointer(_)
+                | ResolvedType::Reference { .. } | ResolvedType::Unknown | ResolvedType::GenericParam(_))
+            {
+                return Err(invalid(op, &format!("operand of type {:?} does not fit in a register", ty)));
+            }
+            if matches!(op.kind, AsmOperandKind::Output | AsmOperandKind::InOut) && !self.is_mutable_place(expr) {
+                return Err(invalid(op, "output must be a mutable variable or a dereferenced pointer"));
+            }
+        }
+        Ok(ResolvedType::unit())
+    }
+
+    /// Whether an expression names a variable or pointer target that may be written through
+    fn is_mutable_place(&self, expr: &Expr) -> bool {
+        match expr.place() {
+            Some(Place::Variable(ident)) => self.is_mutable_variable(ident),
+            Some(Place::Deref(_)) => true,
+            _ => false,
+        }
+    }
+
+    fn is_mutable_variable(&self, ident: &Ident) -> bool {
+        match self.symbols.lookup(&ident.name) {
+            Some(symbol) => match symbol.kind {
+                SymbolKind::Variable => symbol.mutable,
+                SymbolKind::Static { is_mut } => is_mut,
+                _ => false,
+            },
+            None => false,
+        }
+    }
+
+    /// The left operand of an assignment must be a place. Assigning to a
+    /// variable, or to a field or element of a struct or array held in one,
+    /// writes the variable, which must then be `mut`.
+    fn check_assign_target(&self, target: &Expr) -> Result<()> {
+        if target.place().is_none() {
+            return Err(Error::InvalidAssignTarget { span: target.span() });
+        }
+        // Undefined names are reported when the operand is checked
+        match self.stored_in(target) {
+            Some((variable, _)) if !self.is_mutable_variable(variable) => {
+                Err(Error::AssignToImmutable { name: variable.name.clone(), span: target.span() })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The variable whose storage holds place `expr`, and the place's type;
+    /// `None` for places reached through a pointer
+    fn stored_in<'e>(&self, expr: &'e Expr) -> Option<(&'e Ident, ResolvedType)> {
+        match expr.place()? {
+            Place::Variable(ident) => Some((ident, self.symbols.lookup(&ident.name)?.ty.clone())),
+            Place::Field { base, field } => match self.stored_in(base)? {
+                (variable, ResolvedType::Struct { name, fields }) => {
+                    let (_, ty) = self.struct_fields(&name, &fields).into_iter().find(|(n, _)| *n == field.name)?;
+                    Some((variable, ty))
+                }
+                _ => None,
+            },
+            Place::Index { base, .. } => match self.stored_in(base)? {
+                (variable, ResolvedType::Array { elem, .. }) => Some((variable, *elem)),
+                _ => None,
+            },
+            Place::Deref(_) => None,
+        }
+    }
+
+    /// Check a static item: the initializer must be a constant of the declared type
+    fn check_static(&mut self, st: &StaticDef) -> Result<()> {
+        let ty = self.resolve_type(&st.ty)?;
+        if let Some(value) = &st.value {
+            if !self.is_const_initializer(value) {
+                return Err(Error::NonConstInitializer {
+                    name: st.name.name.clone(),
+                    span: value.span(),
+                });
+            }
+            let value_ty = self.check_expr(value)?;
+            if !self.types_compatible(&ty, &value_ty) {
+                return Err(Error::TypeMismatch {
+                    expected: format!("{:?}", ty),
+                    got: format!("{:?}", value_ty),
+                    span: value.span(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether an expression can be evaluated at compile time
+    fn is_const_initializer(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Literal(_) => true,
+            Expr::Unary { expr, .. } => self.is_const_initializer(expr),
+            Expr::Binary { op, left, right, .. } => {
+                !op.is_assignment()
+                    && self.is_const_initializer(left)
+                    && self.is_const_initializer(right)
+            }
+            Expr::Cast { expr, .. } => self.is_const_initializer(expr),
+            Expr::SizeOf { .. } | Expr::AlignOf { .. } | Expr::OffsetOf { .. } => true,
+            _ => false,
+        }
+    }
+
+
+    /// Validate the entry point of a hosted program. The C `main(argc, argv)`
+    /// wrapper can only call these forms and turn their result into an exit status.
+    fn check_main_signature(&self, func: &Function) -> Result<()> {
+        let invalid = |reason: String| Error::InvalidMainSignature { reason, span: func.span };
+        if !func.type_params.is_empty() {
+            return Err(invalid("main cannot have type parameters".to_string()));
+        }
+
+        let params = func.params.iter()
+            .map(|p| self.resolve_type(&p.ty))
+            .collect::<Result<Vec<_>>>()?;
+        let argv = ResolvedType::Pointer(Box::new(ResolvedType::Pointer(Box::new(ResolvedType::U8))));
+        let params_ok = match params.as_slice() {
+            [] => true,
+            [ResolvedType::Slice(elem)] => **elem == ResolvedType::Str,
+            [argc, ptr] => *argc == ResolvedType::I32 && *ptr == argv,
+            _ => false,
+        };
+        if !params_ok {
+            return Err(invalid("expected no parameters, `args: [str]` or `argc: i32, argv: **u8`".to_string()));
+        }
+
+        let ret = func.ret_type.as_ref()
+            .map(|t| self.resolve_type(t))
+            .transpose()?
+            .unwrap_or(ResolvedType::UNIT);
+        match ret {
+            ResolvedType::Primitive(p) if p == PrimitiveType::Unit || p.is_integer() => Ok(()),
+            other => Err(invalid(format!("return type must be unit or an integer, found {:?}", other))),
+        }
+    }
+
+    /// Type check a function
+    /// Bring the const generic parameters of `func` into the current scope
+    fn define_const_params(&mut self, func: &Function) -> Result<Vec<(String, ResolvedType)>> {
+        let mut const_params = Vec::new();
+        for param in &func.generic_params {
+            if let crate::frontend::ast::GenericParam::Const { name, ty } = param {
+                let resolved_ty = self.resolve_type(ty)?;
+                const_params.push((name.name.clone(), resolved_ty.clone()));
+                self.symbols.define(Symbol {
+                    name: name.name.clone(),
+                    kind: SymbolKind::ConstParam { ty: resolved_ty.clone() },
+                    ty: resolved_ty,
+                    span: name.span,
+                    mutable: false,
+                })?;
+            }
+        }
+        Ok(const_params)
+    }
+
+    /// Define the type parameters of `func`, so that any name can be one,
+    /// not only a single uppercase letter
+    fn define_type_params(&mut self, func: &Function) -> Result<()> {
+        for param in &func.generic_params {
+            if let GenericParam::Type { name, .. } = param {
+                self.symbols.define(Symbol {
+                    name: name.name.clone(),
+                    kind: SymbolKind::TypeParam,
+                    ty: ResolvedType::GenericParam(name.name.clone()),
+                    span: name.span,
+                    mutable: false,
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The interfaces each type parameter of `func` is bound by, for those
+    /// with any bounds: `<T: Shape + Eq>` gives `T -> [Shape, Eq]`
+    fn type_param_bounds(func: &Function) -> Vec<(String, Vec<String>)> {
+        func.generic_params.iter()
+            .filter_map(|p| match p {
+                GenericParam::Type { name, bounds } if !bounds.is_empty() => {
+                    let interfaces = bounds.iter()
+                        .map(|bound| match bound {
+                            Type::Named(interface, _) => interface.clone(),
+                            other => format!("{:?}", other),
+                        })
+                        .collect();
+                    Some((name.name.clone(), interfaces))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Make the bounds of `func`'s type parameters those in effect, checking
+    /// that each names an interface
+    fn enter_bounds(&mut self, func: &Function) -> Result<()> {
+        self.current_bounds.clear();
+        for param in &func.generic_params {
+            let GenericParam::Type { name, bounds } = param else { continue };
+            for bound in bounds {
+                let interface = match bound {
+                    Type::Named(interface, _) if self.interface_methods.contains_key(interface) => interface,
+                    Type::Named(other, span) if self.symbols.lookup(other).is_none() => {
+                        return Err(Error::UndefinedType { name: other.clone(), span: *span });
+                    }
+                    other => return Err(Error::TypeMismatch {
+                        expected: format!("an interface as bound of '{}'", name.name),
+                        got: format!("{:?}", other),
+                        span: other.span(),
+                    }),
+                };
+                self.current_bounds.entry(name.name.clone()).or_default().push(interface.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn check_function(&mut self, func: &Function) -> Result<()> {
+        // Restoring also drops scopes an error left open, so they cannot leak into the next function
+        let outer = self.symbols.snapshot();
+        self.symbols.enter_scope();
+        self.strictness = if func.has_annotation("prototype") { Strictness::LENIENT } else { self.unit_strictness };
+        self.uninferred.clear();
+        let result = self.check_function_in_scope(func).and_then(|()| self.audit_types());
+        self.strictness = self.unit_strictness;
+        self.symbols.restore(outer);
+        result
+    }
+
+    /// Report what the function just checked left without a type, so that
+    /// no unknown reaches IR generation unnoticed. Expressions are unknown
+    /// through the innermost one, which alone is reported.
+    fn audit_types(&mut self) -> Result<()> {
+        let mut reported: Vec<Span> = Vec::new();
+        for (what, span) in std::mem::take(&mut self.uninferred) {
+            if reported.iter().any(|inner| span.start <= inner.start && inner.end <= span.end) {
+                continue;
+            }
+            reported.push(span);
+            self.strict_check(self.strictness.uninferred_types, Error::UninferredType { what, span })?;
+        }
+        Ok(())
+    }
+
+    /// Parameters, contracts and body of `func`, in its freshly entered scope
+    fn check_function_in_scope(&mut self, func: &Function) -> Result<()> {
+        self.ownership = OwnershipState::new();
+        
+        // Set effect context for this function (for effect propagation checking)
+        self.current_effects = Some(func.effects.clone());
+
+        self.define_const_params(func)?;
+        self.define_type_params(func)?;
+        self.enter_bounds(func)?;
+        self.unshadowed_params = func.params.iter().map(|p| p.name.name.clone()).collect();
+
+        // Add parameters to scope
+        for param in &func.params {
+            let ty = self.resolve_type(&param.ty)?;
+            if ty == ResolvedType::Unknown {
+                self.uninferred.push((format!("parameter '{}'", param.name.name), param.name.span));
+            }
+            self.symbols.define(Symbol {
+                name: param.name.name.clone(),
+                kind: SymbolKind::Param { ownership: param.ownership },
+                ty: ty.clone(),
+                span: param.span,
+                mutable: param.ownership == Ownership::Mut,
+            })?;
+            self.ownership.add_owned(param.name.name.clone(), param.span);
+            if let Some(pattern) = &param.pattern {
+                self.bind_irrefutable(pattern, &ty)?;
+            }
+        }
+        
+        // Resolve return type for 'result' variable in ensures contracts
+        let return_type = if let Some(ref ret_ty) = func.ret_type {
+            self.resolve_type(ret_ty)?
+        } else {
+            ResolvedType::UNIT
+        };
+        
+        // Check contract expressions (requires, ensures)
+        for contract in &func.contracts {
+            // For 'ensures' contracts, add 'result' variable to scope
+            // This allows postconditions to reference the return value
+            let is_ensures = matches!(contract.kind, crate::frontend::ast::ContractKind::Ensures);
+            let contract_scope = self.symbols.snapshot();
+            
+            if is_ensures && return_type != ResolvedType::UNIT {
+                // Temporarily add 'result' variable for ensures checking
+                self.symbols.enter_scope();
+                self.symbols.define(Symbol {
+                    name: "result".to_string(),
+                    kind: SymbolKind::Variable,
+                    ty: return_type.clone(),
+                    span: func.span,
+                    mutable: false,
+                })?;
+            }
+            
+            let contract_ty = self.check_expr(&contract.condition)?;
+            
+            // Contract expressions must be boolean
+            if contract_ty != ResolvedType::BOOL && contract_ty != ResolvedType::Unknown {
+                let error = Error::TypeMismatch {
+                    expected: "bool".to_string(),
+                    got: format!("{:?}", contract_ty),
+                    span: contract.span,
+                };
+                self.strict_check(self.strictness.contract_types, error)?;
+            }
+            
+            self.symbols.restore(contract_scope);
+        }
+
+        // Check function body
+        let body_ty = self.check_block(&func.body)?;
+        self.check_tail(func, &return_type, &body_ty)?;
+        
+        // Clear effect context
+        self.current_effects = None;
+        Ok(())
+    }
+
+    /// Struct invariants must be boolean conditions on the struct's fields
+    fn check_invariants(&mut self, s: &StructDef) -> Result<()> {
+        if s.invariants.is_empty() {
+            return Ok(());
+        }
+        let outer = self.symbols.snapshot();
+        let scope = self.symbols.enter_scope();
+        let result = self.check_invariants_in_scope(s, scope);
+        self.invariant_scope = None;
+        self.symbols.restore(outer);
+        result
+    }
+
+    fn check_invariants_in_scope(&mut self, s: &StructDef, scope: ScopeId) -> Result<()> {
+        for param in &s.generic_params {
+            let (name, kind, ty) = match param {
+                GenericParam::Type { name: ident, .. } => {
+                    (ident, SymbolKind::TypeParam, ResolvedType::GenericParam(ident.name.clone()))
+                }
+                GenericParam::Const { name, ty } => {
+                    let ty = self.resolve_type(ty)?;
+                    (name, SymbolKind::ConstParam { ty: ty.clone() }, ty)
+                }
+            };
+            self.symbols.define(Symbol { name: name.name.clone(), kind, ty, span: name.span, mutable: false })?;
+        }
+        for field in &s.fields {
+            let ty = self.resolve_type(&field.ty)?;
+            self.symbols.define(Symbol {
+                name: field.name.name.clone(),
+                kind: SymbolKind::Variable,
+                ty,
+                span: field.span,
+                mutable: false,
+            })?;
+        }
+        self.invariant_scope = Some((s.name.name.clone(), scope));
+        for contract in &s.invariants {
+            if contract.kind != ContractKind::Invariant {
+                return Err(Error::InvalidInvariant {
+                    name: s.name.name.clone(),
+                    reason: "only `invariant` clauses apply to structs".to_string(),
+                    span: contract.span,
+                });
+            }
+            let ty = self.check_expr(&contract.condition)?;
+            if ty != ResolvedType::BOOL && ty != ResolvedType::Unknown {
+                return Err(Error::InvalidInvariant {
+                    name: s.name.name.clone(),
+                    reason: format!("expected a bool condition, found {:?}", ty),
+                    span: contract.span,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Type check an impl block
+    fn check_impl(&mut self, impl_block: &ImplBlock) -> Result<()> {
+        let target = impl_block.target.name.clone();
+
+        // Bindings must name associated types the interface declares; missing
+        // ones were defaulted by `bind_default_assoc_types`
+        if let Some(interface) = &impl_block.interface {
+            let declared = self.interface_assoc_types.get(&interface.name).cloned().unwrap_or_default();
+            for binding in &impl_block.associated_types {
+                if !declared.iter().any(|a| a.name.name == binding.name.name) {
+                    return Err(Error::UndefinedType {
+                        name: format!("{}::{}", interface.name, binding.name.name),
+                        span: binding.span,
+                    });
+                }
+            }
+        }
+
+        let outer_impl = self.current_impl.replace(target);
+        let result = impl_block.methods.iter().try_for_each(|method| self.check_function(method));
+        self.current_impl = outer_impl;
+        result
+    }
+
+    /// Name of the user-defined type a method receiver refers to, looking
+    /// through pointers and references
+    fn receiver_type_name(ty: &ResolvedType) -> Option<&str> {
+        match Self::auto_deref(ty)?.0 {
+            ResolvedType::Struct { name, .. }
+            | ResolvedType::Enum { name }
+            | ResolvedType::Generic(name, _)
+            | ResolvedType::GenericWithConsts { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+
+    /// `ty` with its pointer and reference layers peeled off, and how many
+    /// there were; `None` past `MAX_AUTO_DEREF` layers
+    fn auto_deref(ty: &ResolvedType) -> Option<(&ResolvedType, usize)> {
+        let mut ty = ty;
+        let mut derefs = 0;
+        while let ResolvedType::Pointer(inner) | ResolvedType::Reference { inner, .. } = ty {
+            if derefs == MAX_AUTO_DEREF {
+                return None;
+            }
+            ty = inner;
+            derefs += 1;
+        }
+        Some((ty, derefs))
+    }
+
+    /// Fields of struct `name`. A struct type written inside its own
+    /// definition (`next: *Node`) is resolved before its fields are known, so
+    /// an empty list is looked up again.
+    fn struct_fields(&self, name: &str, fields: &[(String, ResolvedType)]) -> Vec<(String, ResolvedType)> {
+        if fields.is_empty() {
+            if let Some(SymbolKind::Struct { fields, .. }) = self.symbols.lookup(name).map(|s| &s.kind) {
+                return fields.clone();
+            }
+        }
+        fields.to_vec()
+    }
+
+    /// Find a method in the type's impl blocks or its interfaces' default methods
+    fn find_method(&self, type_name: &str, method: &str) -> Option<Function> {
+        let inherent = self.impl_methods.get(type_name)
+            .and_then(|methods| methods.iter().find(|m| m.name.name == method));
+        if let Some(found) = inherent {
+            return Some(found.clone());
+        }
+        self.impl_interfaces.get(type_name)?.iter()
+            .filter_map(|iface| self.interface_defaults.get(iface))
+            .flat_map(|defaults| defaults.iter())
+            .find(|m| m.name.name == method)
+            .cloned()
+    }
+
+    /// Check that the types a call binds to the callee's type parameters
+    /// implement the interfaces those are bound by
+    fn check_call_bounds(&self, callee: &Expr, bindings: &HashMap<String, ResolvedType>, span: Span) -> Result<()> {
+        let func = match callee {
+            Expr::Ident(ident) => ident.name.clone(),
+            Expr::Path { segments, .. } => segments.iter().map(|s| s.name.as_str()).collect::<Vec<_>>().join("::"),
+            _ => return Ok(()),
+        };
+        let Some(SymbolKind::Function { link_name, .. }) = self.symbols.lookup(&func).map(|s| &s.kind) else {
+            return Ok(());
+        };
+        for (param, interfaces) in self.type_param_bounds.get(link_name).into_iter().flatten() {
+            let Some(ty) = bindings.get(param) else { continue };
+            if let Some(interface) = interfaces.iter().find(|i| !self.implements(ty, i)) {
+                return Err(Error::UnsatisfiedBound {
+                    ty: ty.to_string(),
+                    interface: interface.clone(),
+                    span,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `ty` implements `interface`: through an impl block, or for a
+    /// type parameter of the function being checked, through its bounds
+    fn implements(&self, ty: &ResolvedType, interface: &str) -> bool {
+        let name = match Self::strip_refs(ty) {
+            ResolvedType::Unknown => return true,
+            ResolvedType::GenericParam(param) => {
+                return self.current_bounds.get(param).is_some_and(|bounds| bounds.iter().any(|b| b == interface));
+            }
+            other => Self::user_type_name(other).map_or_else(|| other.to_string(), str::to_string),
+        };
+        self.impl_interfaces.get(&name).is_some_and(|interfaces| interfaces.iter().any(|i| i == interface))
+    }
+
+    /// Parameters and return type of `method` as `interface` declares it,
+    /// required or with a default body
+    fn interface_method(&self, interface: &str, method: &str) -> Option<(Vec<Param>, Option<Type>)> {
+        let required = self.interface_methods.get(interface)?.iter()
+            .find(|m| m.name.name == method)
+            .map(|m| (m.params.clone(), m.ret_type.clone()));
+        required.or_else(|| self.interface_defaults.get(interface)?.iter()
+            .find(|m| m.name.name == method)
+            .map(|m| (m.params.clone(), m.ret_type.clone())))
+            .filter(|(params, _)| params.first().is_some_and(|p| p.name.name == "self"))
+    }
+
+    /// Type check `receiver.method(args)` on a value of type parameter
+    /// `param`, whose methods are those of the interfaces it is bound by
+    fn check_bounded_method(&mut self, param: &str, method: &Ident, args: &[Expr], span: Span) -> Result<ResolvedType> {
+        let bounds = self.current_bounds.get(param).cloned().unwrap_or_default();
+        let found = bounds.iter().find_map(|interface| self.interface_method(interface, &method.name));
+        let Some((params, ret_type)) = found else {
+            let mut declaring: Vec<&String> = self.interface_methods.keys()
+                .filter(|interface| self.interface_method(interface, &method.name).is_some())
+                .collect();
+            declaring.sort();
+            if let Some(interface) = declaring.first() {
+                return Err(Error::MissingBound {
+                    param: param.to_string(),
+                    interface: interface.to_string(),
+                    span: method.span,
+                });
+            }
+            if !bounds.is_empty() {
+                return Err(Error::MethodNotFound { method: method.name.clone(), ty: param.to_string(), span });
+            }
+            // Unbounded: nothing to resolve against
+            for arg in args {
+                self.check_expr(arg)?;
+            }
+            return Ok(ResolvedType::Unknown);
+        };
+        self.check_method_args(param, &params[1..], ret_type.as_ref(), method, args, span)
+    }
+
+    /// Type check `receiver.method(args)` against the receiver type's impl methods
+    fn check_method_call(&mut self, receiver_ty: &ResolvedType, method: &Ident, args: &[Expr], span: Span) -> Result<ResolvedType> {
+        let Some(type_name) = Self::receiver_type_name(receiver_ty).map(str::to_string) else {
+            if let Some((ResolvedType::GenericParam(param), _)) = Self::auto_deref(receiver_ty) {
+                return self.check_bounded_method(&param.clone(), method, args, span);
+            }
+            if let ResolvedType::Primitive(p) = receiver_ty {
+                if self.primitive_methods.contains_key(p) {
+                    return self.check_primitive_method(p, method, args, span);
+                }
+            }
+            // Builtin or not-yet-inferred receiver: nothing to resolve against
+            for arg in args {
+                self.check_expr(arg)?;
+            }
+            return Ok(ResolvedType::Unknown);
+        };
+
+        let found = self.find_method(&type_name, &method.name);
+        let Some(func) = found.filter(|f| f.params.first().is_some_and(|p| p.name.name == "self")) else {
+            // Methods of imported types are only known by their qualified symbol
+            let qualified = format!("{}::{}", type_name, method.name);
+            let imported_ret = match self.symbols.lookup(&qualified).map(|s| &s.kind) {
+                Some(SymbolKind::Function { ret, .. }) => Some(ret.clone()),
+                // Imported or builtin type whose methods we cannot see
+                _ if !self.impl_methods.contains_key(&type_name) => Some(ResolvedType::Unknown),
+                _ => None,
+            };
+            let Some(ret) = imported_ret else {
+                return Err(Error::MethodNotFound { method: method.name.clone(), ty: type_name, span });
+            };
+            for arg in args {
+                self.check_expr(arg)?;
+            }
+            return Ok(ret);
+        };
+
+        self.check_method_args(&type_name, &func.params[1..], func.ret_type.as_ref(), method, args, span)
+    }
+
+    /// Type check `receiver.method(args)` against the extension methods of
+    /// a primitive receiver
+    fn check_primitive_method(&mut self, primitive: &PrimitiveType, method: &Ident, args: &[Expr], span: Span) -> Result<ResolvedType> {
+        let type_name = ResolvedType::Primitive(primitive.clone()).to_string();
+        let found = self.primitive_methods.get(primitive).into_iter().flatten()
+            .find(|m| m.name.name == method.name && m.params.first().is_some_and(|p| p.name.name == "self"))
+            .cloned();
+        let Some(func) = found else {
+            return Err(Error::MethodNotFound { method: method.name.clone(), ty: type_name, span });
+        };
+        self.check_method_args(&type_name, &func.params[1..], func.ret_type.as_ref(), method, args, span)
+    }
+
+    /// Parameter and return types of a method of `type_name`, whose
+    /// signature may mention `Self` for it
+    fn method_signature(&mut self, type_name: &str, params: &[Param], ret_type: Option<&Type>) -> Result<(Vec<ResolvedType>, ResolvedType)> {
+        let outer_impl = self.current_impl.replace(type_name.to_string());
+        let signature = params.iter()
+            .map(|p| self.resolve_type(&p.ty))
+            .collect::<Result<Vec<_>>>()
+            .and_then(|params| {
+                let ret = ret_type
+                    .map(|t| self.resolve_type(t))
+                    .transpose()?
+                    .unwrap_or(ResolvedType::unit());
+                Ok((params, ret))
+            });
+        self.current_impl = outer_impl;
+        signature
+    }
+
+    /// Check the arguments of a call to a method of `type_name` taking
+    /// `self` and then `params`, and give its return type
+    fn check_method_args(&mut self, type_name: &str, params: &[Param], ret_type: Option<&Type>, method: &Ident, args: &[Expr], span: Span) -> Result<ResolvedType> {
+        let (params, ret) = self.method_signature(type_name, params, ret_type)?;
+
+        if args.len() != params.len() {
+            return Err(Error::ArgCountMismatch {
+                func_name: format!("{}::{}", type_name, method.name),
+                expected: params.len(),
+                got: args.len(),
+                span,
+            });
+        }
+        for (arg, param_ty) in args.iter().zip(&params) {
+            let arg_ty = self.check_expr_expecting(arg, param_ty)?;
+            if !matches!(param_ty, ResolvedType::GenericParam(_)) && !self.types_compatible(param_ty, &arg_ty) {
+                return Err(Error::TypeMismatch {
+                    expected: format!("{:?}", param_ty),
+                    got: format!("{:?}", arg_ty),
+                    span: arg.span(),
+                });
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Resolve `Self` or `Self::Assoc` against the impl block being checked.
+    /// Outside an impl (e.g. in interface signatures) the type stays abstract.
+    fn resolve_self_type(&self, name: &str, span: Span) -> Result<ResolvedType> {
+        let Some(target) = &self.current_impl else {
+            return Ok(ResolvedType::GenericParam(name.to_string()));
+        };
+        match name.strip_prefix("Self::") {
+            Some(assoc) => self.assoc_types.get(&(target.clone(), assoc.to_string()))
+                .cloned()
+                .ok_or(Error::UndefinedType { name: format!("{}::{}", target, assoc), span }),
+            None => self.resolve_type(&Type::Named(target.clone(), span)),
+        }
     }
 
     /// Type check a block
+    /// A block's value is its final expression. A block ending in a
+    /// statement (including `expr;`) is unit, or `!` when it always leaves
+    /// through `return`, `break`, `continue` or a diverging call.
     fn check_block(&mut self, block: &Block) -> Result<ResolvedType> {
         let mut last_ty = ResolvedType::unit();
+        let mut diverges = false;
 
         for stmt in &block.stmts {
             last_ty = self.check_stmt(stmt)?;
+            diverges |= last_ty == ResolvedType::NEVER
+                || matches!(stmt, Stmt::Return { .. } | Stmt::Break { .. } | Stmt::Continue { .. });
+        }
+
+        Ok(match block.stmts.last() {
+            Some(Stmt::Expr(_)) => last_ty,
+            _ if diverges => ResolvedType::never(),
+            _ => ResolvedType::unit(),
+        })
+    }
+
+    /// A function with a return type yields its body's value
+    fn check_tail(&self, func: &Function, return_type: &ResolvedType, body_ty: &ResolvedType) -> Result<()> {
+        if matches!(return_type, ResolvedType::Unknown) || *return_type == ResolvedType::UNIT
+            || *return_type == ResolvedType::NEVER
+        {
+            return Ok(());
+        }
+        match func.body.stmts.last() {
+            Some(Stmt::Expr(tail)) => {
+                if self.types_compatible(return_type, body_ty) {
+                    return Ok(());
+                }
+                Err(Error::TypeMismatch {
+                    expected: format!("{:?}", return_type),
+                    got: format!("{:?}", body_ty),
+                    span: tail.span(),
+                })
+            }
+            _ if *body_ty == ResolvedType::NEVER => Ok(()),
+            last => Err(Error::MissingTailValue {
+                name: func.name.name.clone(),
+                expected: return_type.to_string(),
+                span: last.map_or(func.body.span, |stmt| stmt.span()),
+            }),
+        }
+    }
+
+    /// Define the names `pattern` binds, each with the type of the part of
+    /// the matched value it stands for
+    fn bind_pattern(&mut self, pattern: &Pattern, ty: &ResolvedType) -> Result<()> {
+        for (name, mutable, ty) in self.pattern_bindings(pattern, ty)? {
+            self.bind_variable(name, ty, mutable, name.span)?;
+        }
+        Ok(())
+    }
+
+    /// Bind the names of a `let` or parameter pattern, which must match
+    /// every value of `ty`
+    fn bind_irrefutable(&mut self, pattern: &Pattern, ty: &ResolvedType) -> Result<()> {
+        if self.is_refutable(pattern, ty) {
+            return Err(Error::RefutablePattern { span: pattern.span() });
+        }
+        self.bind_pattern(pattern, ty)
+    }
+
+    /// Names a pattern binds, with their mutability and types. Variant
+    /// payloads take the declared field types; the alternatives of an
+    /// or-pattern must agree on their names and types.
+    fn pattern_bindings<'p>(&self, pattern: &'p Pattern, ty: &ResolvedType) -> Result<Vec<(&'p Ident, bool, ResolvedType)>> {
+        match pattern {
+            Pattern::Binding { name, mutable, .. } => Ok(vec![(name, *mutable, ty.clone())]),
+            Pattern::Wildcard { .. } | Pattern::Literal(_) => Ok(vec![]),
+            Pattern::Struct { name, fields, rest, span } => {
+                let declared = self.struct_pattern_fields(name, ty)?;
+                let mut typed = Vec::new();
+                for (field, pattern) in fields {
+                    let (_, field_ty) = declared.iter().find(|(n, _)| *n == field.name)
+                        .ok_or_else(|| Error::UnknownField { field: field.name.clone(), span: field.span })?;
+                    typed.push((pattern, field_ty.clone()));
+                }
+                let missing: Vec<String> = declared.iter()
+                    .filter(|(n, _)| !fields.iter().any(|(f, _)| f.name == *n))
+                    .map(|(n, _)| format!("`{}`", n))
+                    .collect();
+                if !rest && !missing.is_empty() {
+                    return Err(Error::MissingPatternFields { name: name.name.clone(), fields: missing.join(", "), span: *span });
+                }
+                self.bindings_of_all(typed.into_iter())
+            }
+            Pattern::Tuple { elements, span } => {
+                let types = self.tuple_pattern_types(elements.len(), ty, *span)?;
+                self.bindings_of_all(elements.iter().zip(types))
+            }
+            Pattern::Variant { enum_name, variant, fields, .. } => {
+                let payload = Self::pattern_enum(enum_name, ty)
+                    .and_then(|(name, type_args)| self.variant_payload(name, &variant.name, type_args));
+                self.bindings_of_all(fields.iter().enumerate().map(|(i, field)| {
+                    let field_ty = payload.as_ref().and_then(|p| p.get(i)).cloned().unwrap_or(ResolvedType::Unknown);
+                    (field, field_ty)
+                }))
+            }
+            Pattern::Or { alternatives, .. } => {
+                let first = self.pattern_bindings(&alternatives[0], ty)?;
+                for alternative in &alternatives[1..] {
+                    let other = self.pattern_bindings(alternative, ty)?;
+                    for (name, _, name_ty) in &first {
+                        match other.iter().find(|(n, _, _)| n.name == name.name) {
+                            None => return Err(Error::InconsistentOrPattern { name: name.name.clone(), span: alternative.span() }),
+                            Some((n, _, other_ty)) if *other_ty != *name_ty
+                                && *other_ty != ResolvedType::Unknown && *name_ty != ResolvedType::Unknown => {
+                                return Err(Error::TypeMismatch {
+                                    expected: format!("{:?}", name_ty),
+                                    got: format!("{:?}", other_ty),
+                                    span: n.span,
+                                });
+                            }
+                            Some(_) => {}
+                        }
+                    }
+                    if let Some((extra, _, _)) = other.iter().find(|(n, _, _)| !first.iter().any(|(f, _, _)| f.name == n.name)) {
+                        return Err(Error::InconsistentOrPattern { name: extra.name.clone(), span: alternatives[0].span() });
+                    }
+                }
+                Ok(first)
+            }
+        }
+    }
+
+    /// Fields of the struct a struct pattern names, instantiated as in the
+    /// matched type when that is the same struct. Like field access, the
+    /// pattern sees through pointers to the struct.
+    fn struct_pattern_fields(&self, name: &Ident, ty: &ResolvedType) -> Result<Vec<(String, ResolvedType)>> {
+        match Self::auto_deref(ty).map_or(ty, |(ty, _)| ty) {
+            ResolvedType::Struct { name: matched, fields } if *matched == name.name => {
+                return Ok(self.struct_fields(matched, fields));
+            }
+            ResolvedType::Unknown | ResolvedType::GenericParam(_) => {}
+            _ => return Err(Error::TypeMismatch {
+                expected: format!("{:?}", ty),
+                got: name.name.clone(),
+                span: name.span,
+            }),
+        }
+        match self.symbols.lookup(&name.name).map(|s| &s.kind) {
+            Some(SymbolKind::Struct { fields, .. }) => Ok(fields.clone()),
+            _ => Err(Error::NotAStruct { span: name.span }),
+        }
+    }
+
+    /// Element types a tuple pattern of `len` elements takes from `ty`
+    fn tuple_pattern_types(&self, len: usize, ty: &ResolvedType, span: Span) -> Result<Vec<ResolvedType>> {
+        match ty {
+            ResolvedType::Tuple(types) if types.len() == len => Ok(types.clone()),
+            ResolvedType::Unknown | ResolvedType::GenericParam(_) => Ok(vec![ResolvedType::Unknown; len]),
+            _ => Err(Error::TypeMismatch {
+                expected: format!("{:?}", ty),
+                got: format!("a tuple of {} elements", len),
+                span,
+            }),
+        }
+    }
+
+    /// Whether some value of type `ty` fails to match `pattern`
+    fn is_refutable(&self, pattern: &Pattern, ty: &ResolvedType) -> bool {
+        match pattern {
+            Pattern::Wildcard { .. } | Pattern::Binding { .. } => false,
+            Pattern::Literal(_) => true,
+            Pattern::Struct { name, fields, .. } => {
+                let declared = self.struct_pattern_fields(name, ty).unwrap_or_default();
+                fields.iter().any(|(field, pattern)| {
+                    let field_ty = declared.iter().find(|(n, _)| *n == field.name).map_or(ResolvedType::Unknown, |(_, t)| t.clone());
+                    self.is_refutable(pattern, &field_ty)
+                })
+            }
+            Pattern::Tuple { elements, span } => {
+                let types = self.tuple_pattern_types(elements.len(), ty, *span)
+                    .unwrap_or_else(|_| vec![ResolvedType::Unknown; elements.len()]);
+                elements.iter().zip(&types).any(|(e, t)| self.is_refutable(e, t))
+            }
+            Pattern::Variant { enum_name, variant, fields, .. } => {
+                let enum_name = Self::pattern_enum(enum_name, ty);
+                // Only the variant of a single-variant enum always matches
+                let only_variant = enum_name
+                    .and_then(|(name, _)| match &self.symbols.lookup(name)?.kind {
+                        SymbolKind::Enum { variants, .. } => Some(variants.len() == 1),
+                        _ => None,
+                    })
+                    .unwrap_or(false);
+                let payload = enum_name
+                    .and_then(|(name, type_args)| self.variant_payload(name, &variant.name, type_args))
+                    .unwrap_or_default();
+                !only_variant || fields.iter().enumerate().any(|(i, field)| {
+                    self.is_refutable(field, payload.get(i).unwrap_or(&ResolvedType::Unknown))
+                })
+            }
+            Pattern::Or { alternatives, .. } => alternatives.iter().all(|a| self.is_refutable(a, ty)),
+        }
+    }
+
+    /// A value no unguarded arm matches, written as a pattern, or `None` if
+    /// the arms cover every value of `ty`. A guarded arm covers nothing.
+    fn uncovered_value(&self, arms: &[MatchArm], ty: &ResolvedType) -> Option<String> {
+        let rows = arms.iter()
+            .filter(|arm| arm.guard.is_none())
+            .map(|arm| vec![Some(&arm.pattern)])
+            .collect();
+        self.uncovered_row(rows, std::slice::from_ref(ty)).map(|mut row| row.remove(0))
+    }
+
+    /// Patterns, one per column of `types`, of values no row matches; a
+    /// `None` entry matches anything. Each constructor of the first column's
+    /// type is checked against the rows that can match it.
+    fn uncovered_row(&self, rows: Vec<Vec<Option<&Pattern>>>, types: &[ResolvedType]) -> Option<Vec<String>> {
+        let Some((ty, rest)) = types.split_first() else {
+            return rows.is_empty().then(Vec::new);
+        };
+        // An or-pattern covers what each of its alternatives does
+        let mut expanded = Vec::new();
+        let mut pending = rows;
+        while let Some(mut row) = pending.pop() {
+            match row[0] {
+                Some(Pattern::Or { alternatives, .. }) => pending.extend(alternatives.iter().map(|alternative| {
+                    row[0] = Some(alternative);
+                    row.clone()
+                })),
+                Some(Pattern::Binding { .. } | Pattern::Wildcard { .. }) => {
+                    row[0] = None;
+                    expanded.push(row);
+                }
+                _ => expanded.push(row),
+            }
+        }
+        let rows = expanded;
+
+        let Some(constructors) = self.pattern_constructors(ty, &rows) else {
+            // Too many values to list: only the rows matching anything count
+            let defaults = rows.iter().filter(|row| row[0].is_none()).map(|row| row[1..].to_vec()).collect();
+            let mut witness = self.uncovered_row(defaults, rest)?;
+            witness.insert(0, "_".to_string());
+            return Some(witness);
+        };
+        for (constructor, fields) in constructors {
+            let specialized = rows.iter()
+                .filter_map(|row| {
+                    let mut inner = Self::specialize(row[0], &constructor, &fields)?;
+                    inner.extend_from_slice(&row[1..]);
+                    Some(inner)
+                })
+                .collect();
+            let types: Vec<ResolvedType> = fields.iter().map(|(_, ty)| ty.clone()).chain(rest.iter().cloned()).collect();
+            if let Some(mut witness) = self.uncovered_row(specialized, &types) {
+                let inner: Vec<String> = witness.drain(..fields.len()).collect();
+                let value = match &constructor {
+                    PatternConstructor::Variant(name) if inner.is_empty() => name.clone(),
+                    PatternConstructor::Variant(name) => format!("{}({})", name, inner.join(", ")),
+                    PatternConstructor::Bool(b) => b.to_string(),
+                    PatternConstructor::Tuple => format!("({})", inner.join(", ")),
+                    PatternConstructor::Struct(name) => {
+                        let fields: Vec<String> = fields.iter().zip(&inner).map(|((field, _), w)| format!("{}: {}", field, w)).collect();
+                        format!("{} {{ {} }}", name, fields.join(", "))
+                    }
+                };
+                witness.insert(0, value);
+                return Some(witness);
+            }
+        }
+        None
+    }
+
+    /// Every constructor of `ty` with its fields, or `None` for types with
+    /// too many values to list. Like the patterns, this sees through
+    /// pointers; an enum is also recognized by the variant patterns matched against it.
+    fn pattern_constructors(&self, ty: &ResolvedType, rows: &[Vec<Option<&Pattern>>]) -> Option<Vec<ConstructorFields>> {
+        let ty = Self::auto_deref(ty).map_or(ty, |(ty, _)| ty);
+        match ty {
+            ResolvedType::Primitive(PrimitiveType::Bool) => {
+                return Some(vec![(PatternConstructor::Bool(true), vec![]), (PatternConstructor::Bool(false), vec![])]);
+            }
+            ResolvedType::Tuple(types) => {
+                let fields = types.iter().enumerate().map(|(i, ty)| (i.to_string(), ty.clone())).collect();
+                return Some(vec![(PatternConstructor::Tuple, fields)]);
+            }
+            ResolvedType::Struct { name, fields } => {
+                return Some(vec![(PatternConstructor::Struct(name.clone()), self.struct_fields(name, fields))]);
+            }
+            _ => {}
+        }
+        let (enum_name, type_args) = match ty {
+            ResolvedType::Enum { name } => (name.as_str(), &[][..]),
+            ResolvedType::Generic(name, args) => (name.as_str(), args.as_slice()),
+            _ => rows.iter().find_map(|row| match row[0] {
+                Some(Pattern::Variant { enum_name, .. }) => Self::pattern_enum(enum_name, ty),
+                _ => None,
+            })?,
+        };
+        let SymbolKind::Enum { variants, .. } = &self.symbols.lookup(enum_name)?.kind else { return None };
+        Some(variants.iter()
+            .map(|(variant, _)| {
+                let payload = self.variant_payload(enum_name, variant, type_args).unwrap_or_default();
+                let fields = payload.into_iter().enumerate().map(|(i, ty)| (i.to_string(), ty)).collect();
+                (PatternConstructor::Variant(format!("{}::{}", enum_name, variant)), fields)
+            })
+            .collect())
+    }
+
+    /// The patterns `pattern` requires of the `fields` of `constructor`, or
+    /// `None` if it matches another constructor
+    fn specialize<'p>(pattern: Option<&'p Pattern>, constructor: &PatternConstructor, fields: &[(String, ResolvedType)]) -> Option<Vec<Option<&'p Pattern>>> {
+        let Some(pattern) = pattern else { return Some(vec![None; fields.len()]) };
+        let required: Vec<Option<&Pattern>> = match (pattern, constructor) {
+            (Pattern::Variant { variant, fields, .. }, PatternConstructor::Variant(name))
+                if name.rsplit("::").next() == Some(variant.name.as_str()) => fields.iter().map(Some).collect(),
+            (Pattern::Literal(Literal::Bool(b, _)), PatternConstructor::Bool(expected)) if b == expected => vec![],
+            (Pattern::Tuple { elements, .. }, PatternConstructor::Tuple) => elements.iter().map(Some).collect(),
+            // Struct fields are matched by name; the ones left out match anything
+            (Pattern::Struct { fields: named, .. }, PatternConstructor::Struct(_)) => fields.iter()
+                .map(|(field, _)| named.iter().find(|(f, _)| f.name == *field).map(|(_, p)| p))
+                .collect(),
+            _ => return None,
+        };
+        Some(required.into_iter().chain(std::iter::repeat(None)).take(fields.len()).collect())
+    }
+
+    fn bindings_of_all<'p>(&self, patterns: impl Iterator<Item = (&'p Pattern, ResolvedType)>) -> Result<Vec<(&'p Ident, bool, ResolvedType)>> {
+        let mut bindings = Vec::new();
+        for (pattern, ty) in patterns {
+            bindings.extend(self.pattern_bindings(pattern, &ty)?);
+        }
+        Ok(bindings)
+    }
+
+    /// The enum a variant pattern names, or else the enum type it is matched
+    /// against, with the type arguments of that type
+    fn pattern_enum<'a>(enum_name: &'a Option<Ident>, ty: &'a ResolvedType) -> Option<(&'a str, &'a [ResolvedType])> {
+        let type_args = match ty {
+            ResolvedType::Generic(name, args) if enum_name.as_ref().is_none_or(|e| e.name == *name) => args.as_slice(),
+            _ => &[],
+        };
+        match (enum_name, ty) {
+            (Some(name), _) => Some((name.name.as_str(), type_args)),
+            (None, ResolvedType::Enum { name } | ResolvedType::Generic(name, _)) => Some((name.as_str(), type_args)),
+            _ => None,
+        }
+    }
+
+    /// Declared payload types of `enum_name::variant`, with the enum's type
+    /// parameters replaced by `type_args`; the rest are left to inference
+    fn variant_payload(&self, enum_name: &str, variant: &str, type_args: &[ResolvedType]) -> Option<Vec<ResolvedType>> {
+        let SymbolKind::Enum { variants, type_params, .. } = &self.symbols.lookup(enum_name)?.kind else { return None };
+        let index = variants.iter().position(|(v, _)| v == variant)?;
+        let payload = self.layouts.enum_payloads(enum_name)?.get(index)?;
+        let substitutions: HashMap<String, ResolvedType> = type_params.iter().enumerate()
+            .map(|(i, p)| (p.clone(), type_args.get(i).cloned().unwrap_or(ResolvedType::Unknown)))
+            .collect();
+        Some(payload.iter().map(|ty| self.substitute_type(ty, &substitutions)).collect())
+    }
+
+    /// `Some`, `None`, `Ok` and `Err` build the variants of the builtin
+    /// `Option` and `Result` wherever nothing else has those names
+    fn check_prelude_variant(&mut self, variant: &Ident, args: &[Expr]) -> Result<Option<ResolvedType>> {
+        let enum_name = match variant.name.as_str() {
+            "Some" | "None" => "Option",
+            "Ok" | "Err" => "Result",
+            _ => return Ok(None),
+        };
+        self.check_variant_constructor(enum_name, variant, args)
+    }
+
+    /// Type of the generic enum value `enum_name::variant(args)`, with the
+    /// type arguments the payload determines; `None` for anything else
+    fn check_variant_constructor(&mut self, enum_name: &str, variant: &Ident, args: &[Expr]) -> Result<Option<ResolvedType>> {
+        let Some(SymbolKind::Enum { variants, type_params, .. }) = self.symbols.lookup(enum_name).map(|s| &s.kind) else {
+            return Ok(None);
+        };
+        let Some(index) = variants.iter().position(|(v, _)| *v == variant.name) else {
+            return Ok(None);
+        };
+        let type_params = type_params.clone();
+        let payload = self.layouts.enum_payloads(enum_name).and_then(|p| p.get(index)).cloned().unwrap_or_default();
+        if type_params.is_empty() {
+            return self.check_payload_args(enum_name, variant, &payload, args)
+                .map(|()| Some(ResolvedType::Enum { name: enum_name.to_string() }));
+        }
+        if args.len() != payload.len() {
+            return Err(Error::ArgCountMismatch {
+                func_name: format!("{}::{}", enum_name, variant.name),
+                expected: payload.len(),
+                got: args.len(),
+                span: variant.span,
+            });
+        }
+        let mut inferred = HashMap::new();
+        for (arg, declared) in args.iter().zip(&payload) {
+            let got = self.check_expr(arg)?;
+            if matches!(declared, ResolvedType::GenericParam(_)) {
+                self.check_payload_type(enum_name, &got, arg.span())?;
+            }
+            Self::infer_type_args(declared, &got, &mut inferred);
+            let expected = self.substitute_type(declared, &inferred);
+            if !matches!(expected, ResolvedType::GenericParam(_)) && !self.types_compatible(&expected, &got) {
+                return Err(Error::TypeMismatch {
+                    expected: format!("{:?}", expected),
+                    got: format!("{:?}", got),
+                    span: arg.span(),
+                });
+            }
         }
+        let type_args = type_params.iter()
+            .map(|p| inferred.get(p).cloned().unwrap_or(ResolvedType::Unknown))
+            .collect();
+        Ok(Some(ResolvedType::Generic(enum_name.to_string(), type_args)))
+    }
+
+    /// The value `Enum::Variant` names: the enum for a variant without a
+    /// payload, and otherwise the function constructing it
+    fn check_variant_path(&mut self, enum_name: &str, variant: &Ident) -> Result<Option<ResolvedType>> {
+        let Some(SymbolKind::Enum { variants, type_params, .. }) = self.symbols.lookup(enum_name).map(|s| &s.kind) else {
+            return Ok(None);
+        };
+        let Some(index) = variants.iter().position(|(v, _)| *v == variant.name) else {
+            return Ok(None);
+        };
+        if !type_params.is_empty() {
+            return self.check_variant_constructor(enum_name, variant, &[]);
+        }
+        let ty = ResolvedType::Enum { name: enum_name.to_string() };
+        let payload = self.layouts.enum_payloads(enum_name).and_then(|p| p.get(index)).cloned().unwrap_or_default();
+        Ok(Some(match payload.is_empty() {
+            true => ty,
+            false => ResolvedType::Function { params: payload, ret: Box::new(ty), effects: Box::default() },
+        }))
+    }
+
+    /// The type of `Type::name`, a function in the impl blocks of `type_name`
+    fn associated_function_type(&mut self, type_name: &str, name: &str) -> Result<Option<ResolvedType>> {
+        let Some(func) = self.find_method(type_name, name) else {
+            return Ok(None);
+        };
+        let (params, ret) = self.method_signature(type_name, &func.params, func.ret_type.as_ref())?;
+        Ok(Some(ResolvedType::Function { params, ret: Box::new(ret), effects: Box::new(func.effects.clone()) }))
+    }
+
+    /// Check `args` against the payload of a variant of a non-generic enum
+    fn check_payload_args(&mut self, enum_name: &str, variant: &Ident, payload: &[ResolvedType], args: &[Expr]) -> Result<()> {
+        if args.len() != payload.len() {
+            return Err(Error::ArgCountMismatch {
+                func_name: format!("{}::{}", enum_name, variant.name),
+                expected: payload.len(),
+                got: args.len(),
+                span: variant.span,
+            });
+        }
+        for (arg, expected) in args.iter().zip(payload) {
+            let got = self.check_expr_expecting(arg, expected)?;
+            // As in calls, a reference passes for a pointer to the same type
+            let got = match (expected, got) {
+                (ResolvedType::Pointer(_), ResolvedType::Reference { inner, .. }) => ResolvedType::Pointer(inner),
+                (_, got) => got,
+            };
+            if !self.types_compatible(expected, &got) {
+                return Err(Error::TypeMismatch {
+                    expected: format!("{:?}", expected),
+                    got: format!("{:?}", got),
+                    span: arg.span(),
+                });
+            }
+        }
+        Ok(())
+    }
 
-        Ok(last_ty)
+    /// Bind the type parameters in `declared` to the parts of `actual` they stand for
+    fn infer_type_args(declared: &ResolvedType, actual: &ResolvedType, inferred: &mut HashMap<String, ResolvedType>) {
+        match (declared, actual) {
+            (_, ResolvedType::Unknown) => {}
+            (ResolvedType::GenericParam(name), _) => {
+                inferred.entry(name.clone()).or_insert_with(|| actual.clone());
+            }
+            (ResolvedType::Pointer(d), ResolvedType::Pointer(a) | ResolvedType::Reference { inner: a, .. })
+            | (ResolvedType::Slice(d), ResolvedType::Slice(a)) => {
+                Self::infer_type_args(d, a, inferred);
+            }
+            (ResolvedType::Generic(dn, d), ResolvedType::Generic(an, a)) if dn == an => {
+                for (d, a) in d.iter().zip(a) {
+                    Self::infer_type_args(d, a, inferred);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// A match guard must be a boolean condition
+    fn check_guard(&mut self, guard: &Expr) -> Result<()> {
+        let ty = self.check_expr(guard)?;
+        if ty != ResolvedType::bool() && ty != ResolvedType::Unknown {
+            return Err(Error::TypeMismatch {
+                expected: "bool".to_string(),
+                got: format!("{:?}", ty),
+                span: guard.span(),
+            });
+        }
+        Ok(())
     }
 
     /// Type check a statement
     fn check_stmt(&mut self, stmt: &Stmt) -> Result<ResolvedType> {
         match stmt {
-            Stmt::Let { name, mutable, ty, value, span } => {
+            Stmt::Let { name, mutable, ty, value, pattern, span } => {
                 let declared_ty = ty.as_ref()
                     .map(|t| self.resolve_type(t))
                     .transpose()?;
 
                 let value_ty = value.as_ref()
-                    .map(|e| self.check_expr(e))
+                    .map(|e| match &declared_ty {
+                        Some(declared) => self.check_expr_expecting(e, declared),
+                        None => self.check_expr(e),
+                    })
                     .transpose()?;
 
-                let final_ty = match (declared_ty, value_ty) {
-                    (Some(d), Some(v)) => {
-                        // Strict Type System: No implicit conversions allowed
-                        if !self.types_compatible(&d, &v) {
-                            return Err(Error::TypeMismatch {
-                                expected: format!("{:?}", d),
-                                got: format!("{:?}", v),
-                                span: *span,
-                            });
-                        }
-                        d
-                    }
-                    (Some(d), None) => d,
-                    (None, Some(v)) => v,
-                    (None, None) => ResolvedType::Unknown,
-                };
-
-                self.symbols.define(Symbol {
-                    name: name.name.clone(),
-                    kind: SymbolKind::Variable,
-                    ty: final_ty,
-                    span: *span,
-                    mutable: *mutable,
-                })?;
+                let final_ty = self.define_let(name, *mutable, declared_ty, value_ty, value.as_ref(), *span)?;
 
-                self.ownership.add_owned(name.name.clone(), *span);
+                if let Some(pattern) = pattern {
+                    self.bind_irrefutable(pattern, &final_ty)?;
+                }
 
                 Ok(ResolvedType::unit())
             }
@@ -1226,28 +3821,189 @@ impl SemanticAnalyzer {
                     Ok(ResolvedType::unit())
                 }
             }
-            Stmt::Break { .. } | Stmt::Continue { .. } | Stmt::Empty { .. } => {
+            Stmt::Break { break_value, span } => self.check_break(break_value.as_ref(), *span),
+            Stmt::Continue { .. } | Stmt::Empty { .. } => {
                 Ok(ResolvedType::unit())
             }
         }
     }
 
-    /// Type check an expression
+    /// Check a `loop`: its type is that of the values it breaks with, which
+    /// must agree, or `Never` when no `break` has a value
+    fn check_loop(&mut self, body: &Block) -> Result<ResolvedType> {
+        self.symbols.enter_scope();
+        let breaks = self.check_loop_body(body, Some(Vec::new()));
+        self.symbols.exit_scope();
+        let breaks = breaks?.unwrap_or_default();
+        if !breaks.iter().any(|(_, _, valued)| *valued) {
+            return Ok(ResolvedType::never());
+        }
+        let ty = breaks[0].0.clone();
+        for (other, span, _) in &breaks[1..] {
+            if !self.types_compatible(&ty, other) {
+                self.push_error(Error::TypeMismatch { expected: format!("{:?}", ty), got: format!("{:?}", other), span: *span });
+            }
+        }
+        Ok(ty)
+    }
+
+    /// Check the body of a loop, returning the breaks `check_break` recorded in `breaks`
+    fn check_loop_body(&mut self, body: &Block, breaks: Option<LoopBreaks>) -> Result<Option<LoopBreaks>> {
+        self.loop_breaks.push(breaks);
+        let checked = self.check_block(body);
+        let breaks = self.loop_breaks.pop().flatten();
+        checked.map(|_| breaks)
+    }
+
+    /// Check `break` and record its type for the enclosing `loop`
+    fn check_break(&mut self, value: Option<&Expr>, span: Span) -> Result<ResolvedType> {
+        let ty = match value {
+            Some(expr) => self.check_expr(expr)?,
+            None => ResolvedType::unit(),
+        };
+        let span = value.map_or(span, |expr| expr.span());
+        match self.loop_breaks.last_mut() {
+            Some(Some(breaks)) => breaks.push((ty, span, value.is_some())),
+            // `while` and `for` loops have type `()`
+            Some(None) if value.is_some() => {
+                self.push_error(Error::TypeMismatch { expected: "()".to_string(), got: format!("{:?}", ty), span });
+            }
+            _ => {}
+        }
+        Ok(ResolvedType::unit())
+    }
+
+    /// Define the variable a `let` binds, typed by its annotation or else its value
+    fn define_let(
+        &mut self,
+        name: &Ident,
+        mutable: bool,
+        declared_ty: Option<ResolvedType>,
+        value_ty: Option<ResolvedType>,
+        value: Option<&Expr>,
+        span: Span,
+    ) -> Result<ResolvedType> {
+        let final_ty = match (declared_ty, value_ty) {
+            (Some(d), Some(v)) => {
+                // Strict Type System: No implicit conversions allowed
+                if !self.types_compatible(&d, &v) {
+                    return Err(Error::TypeMismatch {
+                        expected: format!("{:?}", d),
+                        got: format!("{:?}", v),
+                        span,
+                    });
+                }
+                self.check_effect_flow(&d, &v, span)?;
+                if let Some(value) = value {
+                    self.check_lossy_conversion(&d, &v, value)?;
+                }
+                d
+            }
+            (Some(d), None) => d,
+            (None, Some(v)) => v,
+            (None, None) => {
+                self.uninferred.push((format!("variable '{}'", name.name), name.span));
+                ResolvedType::Unknown
+            }
+        };
+
+        self.bind_variable(name, final_ty.clone(), mutable, span)?;
+        Ok(final_ty)
+    }
+
+    /// Define a local variable, shadowing any earlier one of the same name
+    fn bind_variable(&mut self, name: &Ident, ty: ResolvedType, mutable: bool, span: Span) -> Result<()> {
+        self.symbols.shadow(Symbol {
+            name: name.name.clone(),
+            kind: SymbolKind::Variable,
+            ty,
+            span,
+            mutable,
+        })?;
+        if self.unshadowed_params.remove(&name.name) {
+            self.warnings.push(Error::ShadowedParameter { name: name.name.clone(), span: name.span });
+        }
+
+        // The new binding starts out owned, whatever happened to the old one
+        self.ownership.rebind(name.name.clone(), span);
+        Ok(())
+    }
+
+    /// Type check an expression, failing once it is nested deeper than the limit
     fn check_expr(&mut self, expr: &Expr) -> Result<ResolvedType> {
+        if self.nesting >= self.max_nesting {
+            return Err(Error::NestingTooDeep { limit: self.max_nesting, span: expr.span() });
+        }
+        self.nesting += 1;
+        let ty = self.check_expr_inner(expr);
+        self.nesting -= 1;
+        if let (Some(types), Ok(ty)) = (&mut self.expr_types, &ty) {
+            types.push((expr.span(), ty.clone()));
+        }
+        if let Ok(ResolvedType::Unknown) = ty {
+            self.uninferred.push(("this expression".to_string(), expr.span()));
+        }
+        ty
+    }
+
+    /// Type check `expr` where a value of type `expected` is wanted. An
+    /// empty array literal has no element to infer from, and takes its
+    /// element type from `expected`.
+    fn check_expr_expecting(&mut self, expr: &Expr, expected: &ResolvedType) -> Result<ResolvedType> {
+        match (expr, expected) {
+            (Expr::Array { elements, span }, ResolvedType::Array { size: 0, elem }) if elements.is_empty() && **elem != ResolvedType::Unknown => {
+                if let Some(types) = &mut self.expr_types {
+                    types.push((*span, expected.clone()));
+                }
+                Ok(expected.clone())
+            }
+            _ => self.check_expr(expr),
+        }
+    }
+
+    fn check_expr_inner(&mut self, expr: &Expr) -> Result<ResolvedType> {
         match expr {
             Expr::Literal(lit) => Ok(self.literal_type(lit)),
             
             Expr::Ident(ident) => {
-                if let Some(symbol) = self.symbols.lookup(&ident.name) {
+                // Accessing a `static mut` is a data race hazard
+                let symbol = self.symbols.lookup_in_module(&self.module_path, &ident.name);
+                if matches!(symbol.map(|symbol| &symbol.kind), Some(SymbolKind::Static { is_mut: true })) {
+                    self.require_unsafe(format!("access to mutable static '{}'", ident.name), ident.span)?;
+                }
+                if let Some(symbol) = self.symbols.lookup_in_module(&self.module_path, &ident.name) {
+                    if let Some((name, scope)) = &self.invariant_scope {
+                        let is_data = matches!(symbol.kind, SymbolKind::Variable | SymbolKind::Static { .. });
+                        if is_data && self.symbols.lookup_from(*scope, &ident.name).is_none() {
+                            return Err(Error::InvalidInvariant {
+                                name: name.clone(),
+                                reason: format!("'{}' is not one of its fields", ident.name),
+                                span: ident.span,
+                            });
+                        }
+                    }
                     // For functions, return the function type from SymbolKind
-                    if let SymbolKind::Function { params, ret, .. } = &symbol.kind {
+                    if let SymbolKind::Function { params, ret, effects, .. } = &symbol.kind {
                         Ok(ResolvedType::Function {
                             params: params.clone(),
                             ret: Box::new(ret.clone()),
+                            effects: Box::new(effects.clone()),
                         })
                     } else {
                         Ok(symbol.ty.clone())
                     }
+                } else if let Some(module) = self.private_imports.iter()
+                    .find_map(|(module, p)| p.items.contains(&ident.name).then_some(module))
+                {
+                    Err(Error::PrivateSymbol {
+                        name: ident.name.clone(),
+                        module: module.clone(),
+                        span: ident.span,
+                    })
+                } else if let Some(ty) = self.check_prelude_variant(ident, &[])? {
+                    Ok(ty)
+                } else if let Some(error) = self.undefined_name(&ident.name, ident.span) {
+                    Err(error)
                 } else {
                     Err(Error::UndefinedVariable {
                         name: ident.name.clone(),
@@ -1257,11 +4013,31 @@ impl SemanticAnalyzer {
             }
 
 
-            Expr::Path { segments, span } => {
+            Expr::Path { segments, generic_args, span } if segments.len() == 1 && !generic_args.is_empty() => {
+                self.check_instantiation(&segments[0], generic_args, *span)
+            }
+
+            Expr::Path { segments, span, .. } => {
                 // Phase 11: Basic path resolution for Enum constructors and Struct static methods
                 if segments.len() >= 2 {
                     let type_name = &segments[0].name;
                     let symbol_name = &segments[1].name;
+
+                    // Builtin collections, unless the program defines its own
+                    let arity = match type_name.as_str() { "Vec" => 1, "HashMap" => 2, _ => 0 };
+                    let params = match (type_name.as_str(), symbol_name.as_str()) {
+                        (_, "new") => Some(vec![]),
+                        ("Vec", "with_capacity") => Some(vec![ResolvedType::Primitive(PrimitiveType::Usize)]),
+                        _ => None,
+                    };
+                    if let Some(params) = params.filter(|_| arity > 0 && segments.len() == 2 && !self.impl_methods.contains_key(type_name)) {
+                        self.require_alloc(&format!("{}::{}", type_name, symbol_name), *span)?;
+                        return Ok(ResolvedType::Function {
+                            params,
+                            ret: Box::new(ResolvedType::Generic(type_name.clone(), vec![ResolvedType::Unknown; arity])),
+                            effects: Box::new(EffectSet { is_pure: false, effects: vec![Effect::Alloc] }),
+                        });
+                    }
                     
                     // Check if this is an imported module symbol (e.g., helper::greet)
                     if let Some(module_symbols) = self.imported_modules.get(type_name) {
@@ -1270,10 +4046,11 @@ impl SemanticAnalyzer {
                                 // Found the symbol in the imported module
                                 // For functions, ensure we return a Function type
                                 match &symbol.kind {
-                                    SymbolKind::Function { params, ret, .. } => {
+                                    SymbolKind::Function { params, ret, effects, .. } => {
                                         return Ok(ResolvedType::Function {
                                             params: params.clone(),
                                             ret: Box::new(ret.clone()),
+                                            effects: Box::new(effects.clone()),
                                         });
                                     }
                                     _ => return Ok(symbol.ty.clone()),
@@ -1281,25 +4058,44 @@ impl SemanticAnalyzer {
                             }
                         }
                     }
+                    if self.private_imports.get(type_name).is_some_and(|p| p.items.contains(symbol_name)) {
+                        return Err(Error::PrivateSymbol {
+                            name: symbol_name.clone(),
+                            module: type_name.clone(),
+                            span: segments[1].span,
+                        });
+                    }
                     
                     // Also check qualified name lookup (module::symbol registered in symbol table)
-                    let qualified_name = format!("{}::{}", type_name, symbol_name);
-                    if let Some(symbol) = self.symbols.lookup(&qualified_name) {
+                    let qualified_name = segments.iter().map(|s| s.name.as_str()).collect::<Vec<_>>().join("::");
+                    if let Some(symbol) = self.symbols.lookup_in_module(&self.module_path, &qualified_name) {
                         return Ok(symbol.ty.clone());
                     }
                     
                     if let Some(symbol) = self.symbols.lookup(type_name) {
+                         let is_enum = matches!(symbol.kind, SymbolKind::Enum { .. });
+                         let is_struct = matches!(symbol.kind, SymbolKind::Struct { .. });
                          // Enum variant (e.g., TokenKind::Eof)
-                         if matches!(symbol.kind, SymbolKind::Enum { .. }) {
-                             return Ok(ResolvedType::Unknown);
+                         if is_enum {
+                             if let Some(ty) = self.check_variant_path(type_name, &segments[1])? {
+                                 return Ok(ty);
+                             }
                          }
-                         // Struct static method (e.g., String::new)
-                         if matches!(symbol.kind, SymbolKind::Struct { .. }) {
-                             return Ok(ResolvedType::Unknown);
+                         // Associated function (e.g., Point::new)
+                         if is_enum || is_struct {
+                             if let Some(ty) = self.associated_function_type(type_name, &segments[1].name)? {
+                                 return Ok(ty);
+                             }
+                             // Builtin types, whose functions the checker does not see
+                             if !is_enum && !self.impl_methods.contains_key(type_name) {
+                                 return Ok(ResolvedType::Unknown);
+                             }
                          }
                     }
                 }
-                
+                if let Some(error) = segments.first().and_then(|first| self.undefined_name(&first.name, first.span)) {
+                    return Err(error);
+                }
                 Err(Error::UndefinedVariable {
                     name: segments.iter().map(|i| i.name.clone()).collect::<Vec<_>>().join("::"),
                     span: *span,
@@ -1307,18 +4103,21 @@ impl SemanticAnalyzer {
             }
 
 
-            Expr::Binary { left, op, right, span } => {
-                let left_ty = self.check_expr(left)?;
-                let right_ty = self.check_expr(right)?;
-                self.check_binary_op(&left_ty, *op, &right_ty, *span)
-            }
+            Expr::Binary { .. } => self.check_binary_chain(expr),
             
-            Expr::Try { expr, .. } => {
-                // Determine the error type (basic check: expr must be Result)
-                let _ty = self.check_expr(expr)?;
-                // TODO: Verify type is Result<T, E> and return T
-                // For now, return the inner type blindly or assume ok
-                Ok(_ty) 
+            Expr::Try { expr, span } => {
+                // `?` unwraps the value of an `Ok` or `Some`, returning anything else
+                match self.check_expr(expr)? {
+                    ResolvedType::Generic(name, type_args) if matches!(name.as_str(), "Result" | "Option") => {
+                        Ok(type_args.into_iter().next().unwrap_or(ResolvedType::Unknown))
+                    }
+                    ResolvedType::Unknown => Ok(ResolvedType::Unknown),
+                    got => Err(Error::TypeMismatch {
+                        expected: "Result<T, E> or Option<T>".to_string(),
+                        got: format!("{:?}", got),
+                        span: *span,
+                    }),
+                }
             }
 
             Expr::Unary { op, expr, .. } => {
@@ -1327,72 +4126,30 @@ impl SemanticAnalyzer {
             }
 
             Expr::Call { func, args, span } => {
-                let func_ty = self.check_expr(func)?;
-
-                // P5.2: Effect propagation - HARD ERROR if caller doesn't declare required effects
-                if let Some(ref caller_effects) = self.current_effects {
-                    // Get callee's effects from symbol table
-                    if let Expr::Ident(ident) = func.as_ref() {
-                        if let Some(symbol) = self.symbols.lookup(&ident.name) {
-                            if let SymbolKind::Function { effects: callee_effects, .. } = &symbol.kind {
-                                // Check each effect the callee requires
-                                for effect in &callee_effects.effects {
-                                    // If caller is pure, it cannot call any effectful function
-                                    if caller_effects.is_pure {
-                                        return Err(Error::EffectViolation {
-                                            message: format!(
-                                                "pure function cannot call '{}' which has effect {:?}",
-                                                ident.name, effect
-                                            ),
-                                            span: *span,
-                                        });
-                                    }
-                                    // If caller doesn't have this effect, error
-                                    if !caller_effects.effects.contains(effect) {
-                                        return Err(Error::EffectViolation {
-                                            message: format!(
-                                                "function with effect[{:?}] called from function without that effect. Add 'effect[{:?}]' to caller's signature.",
-                                                effect, effect
-                                            ),
-                                            span: *span,
-                                        });
-                                    }
-                                }
-                                // If callee is pure but caller is not - that's fine (pure is subset of any effect)
-                            }
-                        } else {
-                            // Check built-in impure functions
-                            let io_builtins = ["print", "println", "print_i64", "println_i64", "puts", "printf", "exit"];
-                            let alloc_builtins = ["malloc", "free", "realloc", "alloc"];
-
-                            if caller_effects.is_pure {
-                                if io_builtins.contains(&ident.name.as_str()) || alloc_builtins.contains(&ident.name.as_str()) {
-                                    return Err(Error::EffectViolation {
-                                        message: format!("pure function cannot call impure builtin '{}'", ident.name),
-                                        span: *span,
-                                    });
-                                }
-                            } else {
-                                // Check specific effects for builtins
-                                if io_builtins.contains(&ident.name.as_str()) && !caller_effects.effects.contains(&Effect::IO) {
-                                    return Err(Error::EffectViolation {
-                                        message: format!("calling '{}' requires effect[IO]. Add 'effect[io]' to function signature.", ident.name),
-                                        span: *span,
-                                    });
-                                }
-                                if alloc_builtins.contains(&ident.name.as_str()) && !caller_effects.effects.contains(&Effect::Alloc) {
-                                    return Err(Error::EffectViolation {
-                                        message: format!("calling '{}' requires effect[Alloc]. Add 'effect[alloc]' to function signature.", ident.name),
-                                        span: *span,
-                                    });
-                                }
-                            }
-                        }
+                let constructed = match func.as_ref() {
+                    Expr::Ident(ident) if self.symbols.lookup(&ident.name).is_none() => self.check_prelude_variant(ident, args)?,
+                    Expr::Path { segments, .. } if segments.len() == 2 => {
+                        self.check_variant_constructor(&segments[0].name, &segments[1], args)?
+                    }
+                    _ => None,
+                };
+                if let Some(ty) = constructed {
+                    return Ok(ty);
+                }
+                let func_ty = self.check_expr(func)?;
+                if let Expr::Ident(ident) = func.as_ref() {
+                    let is_local = self.symbols.lookup(&ident.name).is_some_and(|symbol| matches!(symbol.kind, SymbolKind::Variable));
+                    if self.symbols.is_extern_function(&ident.name) && !is_local {
+                        self.require_unsafe(format!("call to extern function '{}'", ident.name), *span)?;
+                    } else if self.unsafe_depth > 0 && self.symbols.is_unsafe_function(&ident.name) {
+                        self.unsafe_operations += 1;
                     }
                 }
 
+                self.check_call_effects(func, &func_ty, *span)?;
+
                 match func_ty {
-                    ResolvedType::Function { params, ret } => {
+                    ResolvedType::Function { params, ret, .. } => {
                         // For method calls (func is field access), skip the self parameter
                         let expected_args = if matches!(func.as_ref(), Expr::Field { .. }) && !params.is_empty() {
                             params.len() - 1  // Exclude self parameter
@@ -1421,12 +4178,17 @@ impl SemanticAnalyzer {
                         // Infer generic type parameters from arguments
                         let mut type_substitutions: HashMap<String, ResolvedType> = HashMap::new();
                         for (arg, param_ty) in args.iter().zip(params.iter()) {
-                            let arg_ty = self.check_expr(arg)?;
+                            let arg_ty = self.check_expr_expecting(arg, param_ty)?;
+                            self.check_effect_flow(param_ty, &arg_ty, arg.span())?;
+                            self.check_lossy_conversion(param_ty, &arg_ty, arg)?;
                             // If param is a generic type, bind it to the actual arg type
                             if let ResolvedType::GenericParam(name) = param_ty {
                                 type_substitutions.insert(name.clone(), arg_ty);
+                            } else {
+                                Self::infer_type_args(param_ty, &arg_ty, &mut type_substitutions);
                             }
                         }
+                        self.check_call_bounds(func, &type_substitutions, *span)?;
                         
                         // Substitute generic params in return type
                         let actual_ret = self.substitute_type(&ret, &type_substitutions);
@@ -1444,20 +4206,36 @@ impl SemanticAnalyzer {
             }
 
             Expr::Field { expr, field, span } => {
+                let borrowed = std::mem::take(&mut self.borrowing_field);
+                let stored = self.storing_constant.take();
                 let expr_ty = self.check_expr(expr)?;
-                
-                let struct_ty = if let ResolvedType::Pointer(inner) = &expr_ty {
-                    inner.as_ref()
-                } else if let ResolvedType::Reference { inner, .. } = &expr_ty {
-                    inner.as_ref()
-                } else {
-                    &expr_ty
-                };
+                let struct_ty = Self::auto_deref(&expr_ty).map(|(ty, _)| ty);
 
                 match struct_ty {
-                    ResolvedType::Struct { name: _, fields } => {
-                        for (fname, fty) in fields {
+                    Some(ResolvedType::Struct { name, fields }) => {
+                        let fields = self.struct_fields(name, fields);
+                        // Reading a union field reinterprets whatever was last written
+                        if self.is_union(name) {
+                            if self.unsafe_depth == 0 {
+                                return Err(Error::UnsafeRequired {
+                                    what: format!("access to union field '{}.{}'", name, field.name),
+                                    span: *span,
+                                });
+                            }
+                            self.unsafe_operations += 1;
+                        }
+                        for (fname, fty) in &fields {
                             if fname == &field.name {
+                                let module = self.private_imports.iter()
+                                    .find(|(_, p)| p.fields.get(name).is_some_and(|f| f.contains(fname)));
+                                if let Some((module, _)) = module {
+                                    return Err(Error::PrivateSymbol {
+                                        name: format!("{}.{}", name, fname),
+                                        module: module.clone(),
+                                        span: field.span,
+                                    });
+                                }
+                                self.check_bitfield_use(name, fname, borrowed, stored, *span)?;
                                 return Ok(fty.clone());
                             }
                         }
@@ -1466,14 +4244,18 @@ impl SemanticAnalyzer {
                             span: *span,
                         })
                     }
-                    _ => Err(Error::NotAStruct { span: *span }),
+                    _ => Err(Error::FieldOfNonStruct {
+                        field: field.name.clone(),
+                        ty: expr_ty.to_string(),
+                        span: *span,
+                    }),
                 }
             }
 
             Expr::If { cond, then_block, else_block, .. } => {
                 let cond_ty = self.check_expr(cond)?;
                 if cond_ty != ResolvedType::bool() && cond_ty != ResolvedType::Unknown {
-                    self.errors.push(Error::TypeMismatch {
+                    self.push_error(Error::TypeMismatch {
                         expected: "bool".to_string(),
                         got: format!("{:?}", cond_ty),
                         span: cond.span(),
@@ -1486,10 +4268,11 @@ impl SemanticAnalyzer {
 
                 if let Some(else_block) = else_block {
                     self.symbols.enter_scope();
-                    let _else_ty = self.check_block(else_block)?;
+                    let else_ty = self.check_block(else_block)?;
                     self.symbols.exit_scope();
                     // TODO: Check then_ty == else_ty
-                    Ok(then_ty)
+                    // A branch that never finishes takes the other's type
+                    Ok(if then_ty == ResolvedType::NEVER { else_ty } else { then_ty })
                 } else {
                     Ok(ResolvedType::unit())
                 }
@@ -1502,51 +4285,56 @@ impl SemanticAnalyzer {
                 Ok(ty)
             }
 
-            Expr::Loop { body, .. } => {
-                self.symbols.enter_scope();
-                self.check_block(body)?;
-                self.symbols.exit_scope();
-                Ok(ResolvedType::never())
-            }
+            Expr::Loop { body, .. } => self.check_loop(body),
 
             Expr::While { cond, body, .. } => {
                 let cond_ty = self.check_expr(cond)?;
                 if cond_ty != ResolvedType::bool() && cond_ty != ResolvedType::Unknown {
-                    self.errors.push(Error::TypeMismatch {
+                    self.push_error(Error::TypeMismatch {
                         expected: "bool".to_string(),
                         got: format!("{:?}", cond_ty),
                         span: cond.span(),
                     });
                 }
                 self.symbols.enter_scope();
-                self.check_block(body)?;
+                self.check_loop_body(body, None)?;
                 self.symbols.exit_scope();
                 Ok(ResolvedType::unit())
             }
 
             Expr::For { var, iter, body, span } => {
-                let _iter_ty = self.check_expr(iter)?;
-                // TODO: Get element type from iterator
+                let iter_ty = self.check_expr(iter)?;
+                // Arrays and `Vec`s, also behind a reference; slices and pointers have no length
+                let array = match &iter_ty {
+                    ResolvedType::Reference { inner, .. } | ResolvedType::Pointer(inner) => inner.as_ref(),
+                    other => other,
+                };
+                let elem_ty = match array {
+                    ResolvedType::Array { elem, .. } => (**elem).clone(),
+                    _ if matches!(self.builtin_collection(array), Some(("Vec", _))) => {
+                        self.builtin_collection(array).unwrap().1.first().cloned().unwrap_or(ResolvedType::Unknown)
+                    }
+                    ResolvedType::Unknown => ResolvedType::Unknown,
+                    _ => return Err(Error::NotIterable { ty: iter_ty.to_string(), span: iter.span() }),
+                };
 
                 self.symbols.enter_scope();
                 self.symbols.define(Symbol {
                     name: var.name.clone(),
                     kind: SymbolKind::Variable,
-                    ty: ResolvedType::Unknown, // Would be element type
+                    ty: elem_ty,
                     span: *span,
                     mutable: false,
                 })?;
-                self.check_block(body)?;
+                self.check_loop_body(body, None)?;
                 self.symbols.exit_scope();
                 Ok(ResolvedType::unit())
             }
 
-            Expr::Array { elements, .. } => {
+            Expr::Array { elements, span } => {
+                // `check_expr_expecting` types the ones whose type is known
                 if elements.is_empty() {
-                    return Ok(ResolvedType::Array {
-                        elem: Box::new(ResolvedType::Unknown),
-                        size: 0,
-                    });
+                    return Err(Error::EmptyArrayType { span: *span });
                 }
                 let first_ty = self.check_expr(&elements[0])?;
                 for elem in elements.iter().skip(1) {
@@ -1559,6 +4347,24 @@ impl SemanticAnalyzer {
                 })
             }
 
+            Expr::ArrayFrom { init, len, .. } => {
+                let elem = self.check_expr(init)?;
+                Ok(ResolvedType::Array { elem: Box::new(elem), size: *len })
+            }
+
+            // The type of one element, computed with `_` bound to its index
+            Expr::DoBlock { stmts, accumulator, span } => {
+                self.symbols.enter_scope();
+                let index = Ident { name: "_".to_string(), span: *span };
+                self.bind_variable(&index, ResolvedType::Primitive(PrimitiveType::I64), false, *span)?;
+                for stmt in stmts {
+                    self.check_stmt(stmt)?;
+                }
+                let ty = self.check_expr(accumulator)?;
+                self.symbols.exit_scope();
+                Ok(ty)
+            }
+
             Expr::Tuple { elements, .. } => {
                 let types: Vec<ResolvedType> = elements.iter()
                     .map(|e| self.check_expr(e))
@@ -1567,6 +4373,7 @@ impl SemanticAnalyzer {
             }
 
             Expr::Ref { mutable, expr, span } => {
+                self.borrowing_field = matches!(expr.as_ref(), Expr::Field { .. });
                 let inner_ty = self.check_expr(expr)?;
                 
                 // Check ownership for borrowing
@@ -1587,7 +4394,10 @@ impl SemanticAnalyzer {
             Expr::Deref { expr, span } => {
                 let ty = self.check_expr(expr)?;
                 match ty {
-                    ResolvedType::Pointer(inner) => Ok(*inner),
+                    ResolvedType::Pointer(inner) => {
+                        self.unsafe_operation("dereference of raw pointer", *span)?;
+                        Ok(*inner)
+                    }
                     ResolvedType::Reference { inner, .. } => Ok(*inner),
                     _ => Err(Error::CannotDeref { span: *span }),
                 }
@@ -1601,7 +4411,7 @@ impl SemanticAnalyzer {
                 if !matches!(index_ty, ResolvedType::Primitive(PrimitiveType::Usize) 
                     | ResolvedType::Primitive(PrimitiveType::I32)
                     | ResolvedType::Primitive(PrimitiveType::I64)) {
-                    self.errors.push(Error::TypeMismatch {
+                    self.push_error(Error::TypeMismatch {
                         expected: "integer".to_string(),
                         got: format!("{:?}", index_ty),
                         span: index.span(),
@@ -1611,32 +4421,60 @@ impl SemanticAnalyzer {
                 match expr_ty {
                     ResolvedType::Array { elem, .. } => Ok(*elem),
                     ResolvedType::Slice(elem) => Ok(*elem),
+                    _ if matches!(self.builtin_collection(&expr_ty), Some(("Vec", _))) => {
+                        Ok(self.builtin_collection(&expr_ty).unwrap().1.first().cloned().unwrap_or(ResolvedType::Unknown))
+                    }
                     // Support pointer indexing: ptr[i] dereferences and offsets
-                    ResolvedType::Pointer(elem) => Ok(*elem),
+                    ResolvedType::Pointer(elem) => {
+                        self.unsafe_operation("indexing of raw pointer", *span)?;
+                        Ok(*elem)
+                    }
                     _ => Err(Error::NotIndexable { span: *span }),
                 }
             }
 
-            Expr::Match { expr, arms, .. } => {
-                let _expr_ty = self.check_expr(expr)?;
+            Expr::Match { expr, arms, span } => {
+                let expr_ty = self.check_expr(expr)?;
                 
                 let mut result_ty = None;
                 for arm in arms {
                     // TODO: Check pattern against expr_ty
-                    let arm_ty = self.check_expr(&arm.body)?;
-                    if result_ty.is_none() {
+                    // Names bound by the pattern are visible in the guard and body
+                    let outer = self.symbols.snapshot();
+                    self.symbols.enter_scope();
+                    let arm_ty = self.bind_pattern(&arm.pattern, &expr_ty)
+                        .and_then(|()| match &arm.guard {
+                            Some(guard) => self.check_guard(guard),
+                            None => Ok(()),
+                        })
+                        .and_then(|()| self.check_expr(&arm.body));
+                    self.symbols.restore(outer);
+                    let arm_ty = arm_ty?;
+                    if result_ty.is_none() || result_ty == Some(ResolvedType::NEVER) {
                         result_ty = Some(arm_ty);
                     }
                     // TODO: Check all arms have same type
                 }
+                if let Some(missing) = self.uncovered_value(arms, &expr_ty) {
+                    return Err(Error::NonExhaustiveMatch { missing, span: *span });
+                }
                 Ok(result_ty.unwrap_or(ResolvedType::unit()))
             }
 
-            Expr::Unsafe { body, .. } => {
+            Expr::Unsafe { body, span, .. } => {
+                let operations_before = self.unsafe_operations;
                 self.symbols.enter_scope();
-                let ty = self.check_block(body)?;
+                self.unsafe_depth += 1;
+                let ty = self.check_block(body);
+                self.unsafe_depth -= 1;
                 self.symbols.exit_scope();
-                Ok(ty)
+                if ty.is_ok() && self.unsafe_operations == operations_before {
+                    self.warnings.push(Error::UnnecessaryUnsafe { span: *span });
+                }
+                // Operations count for the innermost block only, so an outer
+                // block around nothing but this one is still unnecessary
+                self.unsafe_operations = operations_before;
+                ty
             }
 
             Expr::MethodCall { expr, method, args, span } => {
@@ -1661,10 +4499,18 @@ impl SemanticAnalyzer {
                             // Returns same pointer type
                             Ok(ResolvedType::Pointer(inner.clone()))
                         } else {
-                            Ok(ResolvedType::Unknown)
+                            self.check_method_call(&receiver_ty, method, args, *span)
                         }
                     },
-                    _ => Ok(ResolvedType::Unknown)
+                    ResolvedType::Str => self.check_str_method(method, args, *span),
+                    _ if self.builtin_collection(&receiver_ty).is_some() => {
+                        let (name, type_args) = self.builtin_collection(&receiver_ty).unwrap();
+                        self.check_collection_method(name, type_args, method, args, *span)
+                    }
+                    _ => match self.check_prelude_method(&receiver_ty, method, args, *span)? {
+                        Some(ty) => Ok(ty),
+                        None => self.check_method_call(&receiver_ty, method, args, *span),
+                    },
                 }
             }
             
@@ -1674,15 +4520,42 @@ impl SemanticAnalyzer {
                     .ok_or(Error::UndefinedType { name: name.name.clone(), span: *span })
                     .cloned()?;
 
+                // A union literal initializes exactly one of its fields
+                if let SymbolKind::Union { fields: def_fields } = &symbol.kind {
+                    if fields.len() != 1 {
+                        return Err(Error::TypeMismatch {
+                            expected: format!("exactly one field initializer for union '{}'", name.name),
+                            got: format!("{} initializers", fields.len()),
+                            span: *span,
+                        });
+                    }
+                    let (fname, fvalue) = &fields[0];
+                    let def_ty = def_fields.iter()
+                        .find(|(n, _)| n == &fname.name)
+                        .map(|(_, ty)| ty.clone())
+                        .ok_or(Error::UnknownField { field: fname.name.clone(), span: *span })?;
+                    let value_ty = self.check_expr(fvalue)?;
+                    if !self.types_compatible(&def_ty, &value_ty) {
+                        return Err(Error::TypeMismatch {
+                            expected: format!("{:?}", def_ty),
+                            got: format!("{:?}", value_ty),
+                            span: fvalue.span(),
+                        });
+                    }
+                    return Ok(symbol.ty.clone());
+                }
+
                 if let SymbolKind::Struct { fields: def_fields, type_params, .. } = &symbol.kind {
                     let mut inferred_params = std::collections::HashMap::new();
                     
                     // Check each field
                     for (fname, fvalue) in fields {
-                        let fvalue_ty = self.check_expr(fvalue)?;
-                        
                         // Find definition
                         if let Some((_, def_ty)) = def_fields.iter().find(|(n, _)| n == &fname.name) {
+                             if let Expr::Literal(Literal::Int(value, value_span)) = fvalue {
+                                 self.check_bitfield_use(&name.name, &fname.name, false, Some((*value, *value_span)), *span)?;
+                             }
+                             let fvalue_ty = self.check_expr_expecting(fvalue, def_ty)?;
                              // Unify def_ty and fvalue_ty
                              if let ResolvedType::GenericParam(p_name) = def_ty {
                                  inferred_params.insert(p_name.clone(), fvalue_ty.clone());
@@ -1726,99 +4599,64 @@ impl SemanticAnalyzer {
             }
 
 
-            Expr::Cast { expr, ty, span } => {
-                let source_ty = self.check_expr(expr)?;
-                let target_ty = self.resolve_type(ty)?;
-                
-                // Allow explicit casts in the following cases:
-                // 1. Integer to Integer (any size)
-                // 2. Integer to Pointer (for raw memory access)
-                // 3. Pointer to Integer (address extraction)
-                // 4. Pointer to Pointer (reinterpret)
-                // 5. Same type (no-op)
-                
-                let is_valid_cast = match (&source_ty, &target_ty) {
-                    // Same type
-                    (s, t) if s == t => true,
-                    
-                    // Int to Int
-                    (ResolvedType::Primitive(p1), ResolvedType::Primitive(p2)) 
-                        if p1.is_integer() && p2.is_integer() => true,
-                    
-                    // Int to Pointer
-                    (ResolvedType::Primitive(p), ResolvedType::Pointer(_)) => {
-                        p.is_integer()
-                    },
-                    
-                    // Pointer to Int
-                    (ResolvedType::Pointer(_), ResolvedType::Primitive(p))
-                        if p.is_integer() => true,
-                    
-                    // Pointer to Pointer
-                    (ResolvedType::Pointer(_), ResolvedType::Pointer(_)) => true,
-                    
-                    // Reference to Pointer (same inner type or compatible)
-                    (ResolvedType::Reference { inner, .. }, ResolvedType::Pointer(ptr_inner)) => {
-                        inner.as_ref() == ptr_inner.as_ref()
-                    },
-                    
-                    // Unknown source (permissive for now)
-                    (ResolvedType::Unknown, _) => true,
-                    
-                    _ => false,
+            Expr::Cast { expr, ty, span } => self.check_cast(expr, ty, *span),
+            Expr::Range { .. } => Ok(ResolvedType::Unknown),
+            Expr::Asm { operands, span, .. } => self.check_asm(operands, *span),
+
+            Expr::SizeOf { ty, span } | Expr::AlignOf { ty, span } => {
+                let resolved = self.resolve_type(ty)?;
+                if self.layouts.layout_of(&resolved).is_none() {
+                    return Err(Error::UnsizedType { ty: format!("{:?}", resolved), span: *span });
+                }
+                Ok(ResolvedType::U64)
+            }
+            Expr::OffsetOf { ty, field, span } => {
+                let name = match self.resolve_type(ty)? {
+                    ResolvedType::Struct { name, .. } => name,
+                    _ => return Err(Error::NotAStruct { span: *span }),
                 };
-                
-                if !is_valid_cast {
-                    return Err(Error::TypeMismatch {
-                        expected: format!("{:?}", target_ty),
-                        got: format!("{:?}", source_ty),
-                        span: *span,
-                    });
+                if !self.layouts.has_field(&name, &field.name) {
+                    return Err(Error::UnknownField { field: field.name.clone(), span: field.span });
                 }
-                
-                Ok(target_ty)
+                if self.layouts.offset_of(&name, &field.name).is_none() {
+                    return Err(Error::UnsizedType { ty: name, span: *span });
+                }
+                Ok(ResolvedType::U64)
             }
-            Expr::Range { .. } => Ok(ResolvedType::Unknown),
-            Expr::Asm { .. } => Ok(ResolvedType::unit()),
-            
-            Expr::Closure { params, ret_type, body, .. } => {
-                // Enter a new scope for closure parameters
-                self.symbols.enter_scope();
-                
-                // Add parameters to scope
-                let mut param_types = Vec::new();
-                for param in params {
-                    let ty = if let Some(t) = &param.ty {
-                        self.resolve_type(t)?
-                    } else {
-                        // Infer type from usage (for now, default to i64)
-                        ResolvedType::Primitive(PrimitiveType::I64)
-                    };
-                    param_types.push(ty.clone());
-                    self.symbols.define(Symbol {
-                        name: param.name.name.clone(),
-                        kind: SymbolKind::Variable,
-                        ty,
-                        span: param.name.span,
-                        mutable: false,
-                    })?;
+            Expr::New { ty, count, init, span } => {
+                let resolved = self.resolve_type(ty)?;
+                if self.layouts.layout_of(&resolved).is_none() {
+                    return Err(Error::UnsizedType { ty: format!("{:?}", resolved), span: *span });
                 }
-                
-                // Check body and determine return type
-                let body_ty = self.check_expr(body)?;
-                
-                let ret_ty = if let Some(t) = ret_type {
-                    self.resolve_type(t)?
-                } else {
-                    body_ty
-                };
-                
-                self.symbols.exit_scope();
-                
-                Ok(ResolvedType::Function {
-                    params: param_types,
-                    ret: Box::new(ret_ty),
-                })
+                let name = if count.is_some() { "new_array" } else { "new" };
+                if self.freestanding {
+                    return Err(Error::NotFreestanding { name: name.to_string(), span: *span });
+                }
+                self.require_alloc(name, *span)?;
+                if let Some(count) = count {
+                    match self.check_expr(count)? {
+                        ResolvedType::Primitive(p) if p.is_integer() => {}
+                        ResolvedType::Unknown => {}
+                        other => return Err(Error::TypeMismatch {
+                            expected: "integer".to_string(),
+                            got: format!("{:?}", other),
+                            span: count.span(),
+                        }),
+                    }
+                }
+                if let Some(init) = init {
+                    self.check_expr(init)?;
+                }
+                Ok(ResolvedType::Pointer(Box::new(resolved)))
+            }
+            
+            Expr::Closure { params, ret_type, body, .. } => self.check_closure(params, ret_type.as_ref(), body),
+
+            // The binding outlives the expression, so it lands in the enclosing scope
+            Expr::Let { name, mutable, ty, value, span } => {
+                let declared_ty = ty.as_ref().map(|t| self.resolve_type(t)).transpose()?;
+                let value_ty = self.check_expr(value)?;
+                self.define_let(name, *mutable, declared_ty, Some(value_ty), Some(value), *span)
             }
         }
     }
@@ -1828,7 +4666,7 @@ impl SemanticAnalyzer {
         match lit {
             Literal::Int(_, _) => ResolvedType::Primitive(PrimitiveType::I64), // Default to i64
             Literal::Float(_, _) => ResolvedType::Primitive(PrimitiveType::F32),
-            Literal::String(_, _) => ResolvedType::Pointer(Box::new(ResolvedType::Primitive(PrimitiveType::U8))), // C-style string pointer
+            Literal::String(_, _) => ResolvedType::Str,
             Literal::Char(_, _) => ResolvedType::Primitive(PrimitiveType::Char),
             Literal::Bool(_, _) => ResolvedType::Primitive(PrimitiveType::Bool),
         }
@@ -1836,10 +4674,35 @@ impl SemanticAnalyzer {
 
 
     /// Check binary operation and return result type
-    fn check_binary_op(&self, left: &ResolvedType, op: BinOp, right: &ResolvedType, _span: Span) -> Result<ResolvedType> {
+    fn check_binary_op(&mut self, left: &ResolvedType, op: BinOp, right: &ResolvedType, span: Span) -> Result<ResolvedType> {
         match op {
+            // Concatenation builds a new `String`
+            BinOp::Add if Self::string_type(left).is_some() => {
+                if Self::string_type(right).is_none() && !matches!(right, ResolvedType::Str | ResolvedType::Unknown) {
+                    return Err(Error::TypeMismatch {
+                        expected: "String or str".to_string(),
+                        got: right.to_string(),
+                        span,
+                    });
+                }
+                Ok(Self::string_type(left).cloned().unwrap_or(ResolvedType::Unknown))
+            }
+            _ if self.operator_impl(left, op).is_some() => self.check_operator_impl(left, op, right, span),
             // Comparison operators return bool
-            BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => {
+            BinOp::Eq | BinOp::Ne => {
+                for ty in [left, right] {
+                    if !self.is_equatable(ty) {
+                        return Err(Error::NotEquatable { ty: ty.to_string(), span });
+                    }
+                }
+                Ok(ResolvedType::bool())
+            }
+            BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => {
+                for ty in [left, right] {
+                    if matches!(Self::strip_refs(ty), ResolvedType::Struct { .. } | ResolvedType::Enum { .. } | ResolvedType::Str) {
+                        return Err(Error::NotOrdered { ty: ty.to_string(), span });
+                    }
+                }
                 Ok(ResolvedType::bool())
             }
             // Logical operators return bool
@@ -1847,9 +4710,30 @@ impl SemanticAnalyzer {
                 Ok(ResolvedType::bool())
             }
             // Assignment returns unit
-            BinOp::Assign | BinOp::AddAssign | BinOp::SubAssign | BinOp::MulAssign | BinOp::DivAssign => {
+            BinOp::Assign => {
+                self.check_effect_flow(left, right, span)?;
+                Ok(ResolvedType::unit())
+            }
+            // `a += b` stores `a + b`, which must have the type of `a`
+            BinOp::AddAssign | BinOp::SubAssign | BinOp::MulAssign | BinOp::DivAssign => {
+                let result = match op.compound_op() {
+                    Some(applied) if Self::user_type_name(left).is_some() => self.check_binary_op(left, applied, right, span)?,
+                    _ => return Ok(ResolvedType::unit()),
+                };
+                if !self.types_compatible(Self::strip_refs(left), &result) {
+                    return Err(Error::TypeMismatch { expected: left.to_string(), got: result.to_string(), span });
+                }
                 Ok(ResolvedType::unit())
             }
+            // Arithmetic on structs and enums is only what their operator impls define
+            _ if op.operator_interface().is_some() && Self::user_type_name(left).is_some() => {
+                Err(Error::MissingOperatorImpl {
+                    op,
+                    ty: Self::strip_refs(left).to_string(),
+                    rhs: Self::strip_refs(right).to_string(),
+                    span,
+                })
+            }
             // Arithmetic and bitwise: handle F32/F64 mixed operations
             _ => {
                 use crate::types::type_system::PrimitiveType;
@@ -1865,6 +4749,97 @@ impl SemanticAnalyzer {
         }
     }
 
+    /// The struct or enum a value of `ty`, or a reference to one, is; `None`
+    /// for `String`, which has its own operators
+    fn user_type_name(ty: &ResolvedType) -> Option<&str> {
+        match Self::strip_refs(ty) {
+            ResolvedType::Struct { name, .. } if name != "String" => Some(name),
+            ResolvedType::Enum { name } => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The method of the operator interface implemented by `left`'s type
+    /// that `op` calls, such as `add` of `impl Add for Vec3`
+    fn operator_impl(&self, left: &ResolvedType, op: BinOp) -> Option<Function> {
+        let (interface, method) = op.operator_interface()?;
+        let type_name = Self::user_type_name(left)?;
+        if !self.impl_interfaces.get(type_name)?.iter().any(|i| i == interface) {
+            return None;
+        }
+        self.find_method(type_name, method)
+    }
+
+    /// Type `left op right` through the operator impl of `left`'s type. Its
+    /// method must take exactly `right`'s type; ordering and equality
+    /// operators give a bool, others the method's return type. Kept out of
+    /// `check_expr`, whose frame every nested block adds to the stack.
+    #[inline(never)]
+    fn check_operator_impl(&mut self, left: &ResolvedType, op: BinOp, right: &ResolvedType, span: Span) -> Result<ResolvedType> {
+        let (interface, method) = op.operator_interface().unwrap_or_default();
+        let type_name = Self::user_type_name(left).unwrap_or_default().to_string();
+        let func = self.operator_impl(left, op).filter(|f| f.params.len() == 2);
+        let signature = match func {
+            Some(func) => Some(self.method_signature(&type_name, &func.params, func.ret_type.as_ref())?),
+            None => None,
+        };
+        let Some((params, ret)) = signature.filter(|(params, _)| self.types_compatible(Self::strip_refs(&params[1]), Self::strip_refs(right))) else {
+            return Err(Error::MissingOperatorImpl {
+                op,
+                ty: type_name,
+                rhs: Self::strip_refs(right).to_string(),
+                span,
+            });
+        };
+        let (returns, valid) = match interface {
+            "Eq" => ("bool", self.types_compatible(&ResolvedType::bool(), &ret)),
+            "Ord" => ("an integer", matches!(&ret, ResolvedType::Primitive(p) if p.is_integer()) || ret == ResolvedType::Unknown),
+            _ => return Ok(ret),
+        };
+        if !valid {
+            return Err(Error::TypeMismatch {
+                expected: format!("{}::{} returning {}", interface, method, returns),
+                got: format!("{}::{} taking {} and returning {}", type_name, method, params[1], ret),
+                span,
+            });
+        }
+        Ok(ResolvedType::bool())
+    }
+
+    /// Whether `==` can compare values of `ty`: strings compare by content,
+    /// enums without payloads by variant, and other structs and enums only
+    /// when they derive or implement `Eq`
+    fn is_equatable(&self, ty: &ResolvedType) -> bool {
+        let implements_eq = Self::user_type_name(ty).and_then(|name| self.impl_interfaces.get(name))
+            .is_some_and(|interfaces| interfaces.iter().any(|i| i == "Eq"));
+        if implements_eq {
+            return true;
+        }
+        match Self::strip_refs(ty) {
+            ResolvedType::Struct { name, .. } => name == "String" || self.derived_eq.contains(name),
+            ResolvedType::Enum { name } => self.derived_eq.contains(name)
+                || self.layouts.enum_payloads(name).is_none_or(|payloads| payloads.iter().all(Vec::is_empty)),
+            _ => true,
+        }
+    }
+
+    /// `ty` with any references around it removed
+    fn strip_refs(mut ty: &ResolvedType) -> &ResolvedType {
+        while let ResolvedType::Reference { inner, .. } = ty {
+            ty = inner;
+        }
+        ty
+    }
+
+    /// The `String` struct type of a `String` value or reference
+    fn string_type(ty: &ResolvedType) -> Option<&ResolvedType> {
+        match ty {
+            ResolvedType::Struct { name, .. } if name == "String" => Some(ty),
+            ResolvedType::Reference { inner, .. } => Self::string_type(inner),
+            _ => None,
+        }
+    }
+
     /// Check unary operation
     fn check_unary_op(&self, op: UnOp, ty: &ResolvedType) -> Result<ResolvedType> {
         match op {
@@ -1874,7 +4849,72 @@ impl SemanticAnalyzer {
         }
     }
     
-    /// Substitute generic type parameters with actual types
+    /// Type check a binary operation and those nested in its left operand.
+    /// Long chains like `a + b + c + ..` nest to the left, so they are walked
+    /// in a loop rather than recursively.
+    fn check_binary_chain(&mut self, expr: &Expr) -> Result<ResolvedType> {
+        let mut chain = Vec::new();
+        let mut leftmost = expr;
+        while let Expr::Binary { left, op, right, span } = leftmost {
+            chain.push((*op, right.as_ref(), *span));
+            leftmost = left;
+        }
+        // A constant stored into a bitfield must fit its width
+        if let Some((BinOp::Assign, right, _)) = chain.last() {
+            if matches!(leftmost, Expr::Field { .. }) {
+                self.storing_constant = match right {
+                    Expr::Literal(Literal::Int(value, span)) => Some((*value, *span)),
+                    _ => None,
+                };
+            }
+        }
+        let left_ty = self.check_expr(leftmost);
+        self.storing_constant = None;
+        let mut left_ty = left_ty?;
+        let links = chain.len();
+        for (i, (op, right, span)) in chain.into_iter().rev().enumerate() {
+            if op.is_assignment() {
+                // Only the innermost operand can be a place
+                if i > 0 {
+                    return Err(Error::InvalidAssignTarget { span });
+                }
+                self.check_assign_target(leftmost)?;
+            }
+            let right_ty = self.check_expr(right)?;
+            if op == BinOp::Assign {
+                self.check_lossy_conversion(&left_ty, &right_ty, right)?;
+            }
+            left_ty = self.check_binary_op(&left_ty, op, &right_ty, span)?;
+            // `check_expr` records the outermost operation
+            if let Some(types) = self.expr_types.as_mut().filter(|_| i + 1 < links) {
+                types.push((span, left_ty.clone()));
+            }
+        }
+        Ok(left_ty)
+    }
+
+    /// Reject a type argument of a generic enum, or an element type of the
+    /// builtin `Vec`, that IR generation cannot lay out. Enum payloads are
+    /// erased to one `i64`, so aggregates and floats would be truncated or
+    /// fail to compile in C; `Vec` copies elements by size, but an array
+    /// is lowered to the address of its first element, which would dangle.
+    fn check_payload_type(&self, container: &str, ty: &ResolvedType, span: Span) -> Result<()> {
+        let erased = container != "Vec";
+        let fits = match ty {
+            ResolvedType::Primitive(p) => !(erased && p.is_float()),
+            ResolvedType::Str | ResolvedType::Struct { .. } | ResolvedType::Tuple(_) | ResolvedType::Enum { .. } => !erased,
+            ResolvedType::Pointer(_) | ResolvedType::Reference { .. } | ResolvedType::Function { .. }
+            | ResolvedType::Generic(..) | ResolvedType::GenericParam(_) | ResolvedType::Unknown => true,
+            _ => false,
+        };
+        match fits {
+            true => Ok(()),
+            false => Err(Error::UnsupportedPayload { ty: ty.to_string(), container: container.to_string(), span }),
+        }
+    }
+
+    /// Substitute generic type parameters with actual types. Only declared
+    /// types are substituted into, so `resolve_type` has bounded their depth.
     fn substitute_type(&self, ty: &ResolvedType, substitutions: &HashMap<String, ResolvedType>) -> ResolvedType {
         match ty {
             ResolvedType::GenericParam(name) => {
@@ -1901,10 +4941,11 @@ impl SemanticAnalyzer {
             ResolvedType::Tuple(types) => {
                 ResolvedType::Tuple(types.iter().map(|t| self.substitute_type(t, substitutions)).collect())
             }
-            ResolvedType::Function { params, ret } => {
+            ResolvedType::Function { params, ret, effects } => {
                 ResolvedType::Function {
                     params: params.iter().map(|p| self.substitute_type(p, substitutions)).collect(),
                     ret: Box::new(self.substitute_type(ret, substitutions)),
+                    effects: effects.clone(),
                 }
             }
             // Other types pass through unchanged
@@ -1914,8 +4955,16 @@ impl SemanticAnalyzer {
 
     /// Resolve an AST type to a ResolvedType
     fn resolve_type(&self, ty: &Type) -> Result<ResolvedType> {
+        self.resolve_type_at(ty, 0)
+    }
+
+    /// Resolve a type nested `depth` levels deep in the one being resolved
+    fn resolve_type_at(&self, ty: &Type, depth: usize) -> Result<ResolvedType> {
+        if depth >= self.max_nesting {
+            return Err(Error::NestingTooDeep { limit: self.max_nesting, span: ty.span() });
+        }
         match ty {
-            Type::Named(name, _) => {
+            Type::Named(name, span) => {
                 match name.as_str() {
                     "i8" => Ok(ResolvedType::Primitive(PrimitiveType::I8)),
                     "i16" => Ok(ResolvedType::Primitive(PrimitiveType::I16)),
@@ -1931,6 +4980,8 @@ impl SemanticAnalyzer {
                     "f64" => Ok(ResolvedType::Primitive(PrimitiveType::F64)),
                     "bool" => Ok(ResolvedType::Primitive(PrimitiveType::Bool)),
                     "char" => Ok(ResolvedType::Primitive(PrimitiveType::Char)),
+                    // Borrowed C string, as passed in `fn main(args: [str])`
+                    "str" => Ok(ResolvedType::Str),
                     // SIMD vector types
                     "f32x4" => Ok(ResolvedType::Vector(Box::new(ResolvedType::Primitive(PrimitiveType::F32)), 4)),
                     "f32x8" => Ok(ResolvedType::Vector(Box::new(ResolvedType::Primitive(PrimitiveType::F32)), 8)),
@@ -1940,6 +4991,8 @@ impl SemanticAnalyzer {
                     "i32x8" => Ok(ResolvedType::Vector(Box::new(ResolvedType::Primitive(PrimitiveType::I32)), 8)),
                     "i64x2" => Ok(ResolvedType::Vector(Box::new(ResolvedType::Primitive(PrimitiveType::I64)), 2)),
                     "i64x4" => Ok(ResolvedType::Vector(Box::new(ResolvedType::Primitive(PrimitiveType::I64)), 4)),
+                    "Self" => self.resolve_self_type(name, *span),
+                    _ if name.starts_with("Self::") => self.resolve_self_type(name, *span),
                     _ => {
                         // Look up in symbol table
                         if let Some(sym) = self.symbols.lookup(name) {
@@ -1952,6 +5005,8 @@ impl SemanticAnalyzer {
                             } else {
                                 Ok(sym.ty.clone())
                             }
+                        } else if let Some(error) = self.undefined_name(name, *span) {
+                            Err(error)
                         } else {
                             // Check if it's a single uppercase letter (common type param convention)
                             if name.len() == 1 && name.chars().next().map(|c| c.is_uppercase()).unwrap_or(false) {
@@ -1967,33 +5022,55 @@ impl SemanticAnalyzer {
                 }
             }
             Type::Pointer(inner, _) => {
-                Ok(ResolvedType::Pointer(Box::new(self.resolve_type(inner)?)))
+                Ok(ResolvedType::Pointer(Box::new(self.resolve_type_at(inner, depth + 1)?)))
             }
             Type::Ref { mutable, inner, .. } => {
                 Ok(ResolvedType::Reference {
                     mutable: *mutable,
-                    inner: Box::new(self.resolve_type(inner)?),
+                    inner: Box::new(self.resolve_type_at(inner, depth + 1)?),
                 })
             }
             Type::Array { elem, size, .. } => {
                 Ok(ResolvedType::Array {
-                    elem: Box::new(self.resolve_type(elem)?),
+                    elem: Box::new(self.resolve_type_at(elem, depth + 1)?),
                     size: *size,
                 })
             }
             Type::Slice(elem, _) => {
-                Ok(ResolvedType::Slice(Box::new(self.resolve_type(elem)?)))
+                Ok(ResolvedType::Slice(Box::new(self.resolve_type_at(elem, depth + 1)?)))
             }
             Type::Tuple(types, _) => {
                 let resolved: Vec<ResolvedType> = types.iter()
-                    .map(|t| self.resolve_type(t))
+                    .map(|t| self.resolve_type_at(t, depth + 1))
                     .collect::<Result<Vec<_>>>()?;
                 Ok(ResolvedType::Tuple(resolved))
             }
             Type::Generic(name, args, _) => {
                 let resolved_args: Vec<ResolvedType> = args.iter()
-                    .map(|arg| self.resolve_type(arg))
+                    .map(|arg| self.resolve_type_at(arg, depth + 1))
                     .collect::<Result<Vec<_>>>()?;
+                // The builtin map hashes integers and `str` only
+                if name == "HashMap" && !self.impl_methods.contains_key(name) {
+                    if let Some(key) = resolved_args.first() {
+                        if !matches!(key, ResolvedType::Str | ResolvedType::Unknown | ResolvedType::GenericParam(_))
+                            && !matches!(key, ResolvedType::Primitive(p) if p.is_integer())
+                        {
+                            return Err(Error::TypeMismatch {
+                                expected: "integer or str map key".to_string(),
+                                got: format!("{:?}", key),
+                                span: ty.span(),
+                            });
+                        }
+                    }
+                }
+                // Generic enums erase their payloads, and the builtin `Vec` copies its elements untyped
+                let generic_enum = matches!(self.symbols.lookup(name).map(|s| &s.kind),
+                    Some(SymbolKind::Enum { type_params, .. }) if !type_params.is_empty());
+                if generic_enum || (name == "Vec" && !self.impl_methods.contains_key(name)) {
+                    for (arg, resolved) in args.iter().zip(&resolved_args) {
+                        self.check_payload_type(name, resolved, arg.span())?;
+                    }
+                }
                 Ok(ResolvedType::Generic(name.clone(), resolved_args))
             }
             Type::GenericWithArgs { name, args, .. } => {
@@ -2004,7 +5081,7 @@ impl SemanticAnalyzer {
                 for arg in args {
                     match arg {
                         crate::frontend::ast::GenericArg::Type(ty) => {
-                            type_args.push(self.resolve_type(ty)?);
+                            type_args.push(self.resolve_type_at(ty, depth + 1)?);
                         }
                         crate::frontend::ast::GenericArg::Const(expr) => {
                             const_args.push(self.eval_const_expr(expr)?);
@@ -2023,25 +5100,58 @@ impl SemanticAnalyzer {
                     })
                 }
             }
-            Type::Function { params, ret, .. } => {
+            Type::Function { params, ret, effects, .. } => {
                 let param_types: Vec<ResolvedType> = params.iter()
-                    .map(|t| self.resolve_type(t))
+                    .map(|t| self.resolve_type_at(t, depth + 1))
                     .collect::<Result<Vec<_>>>()?;
                 Ok(ResolvedType::Function {
                     params: param_types,
-                    ret: Box::new(self.resolve_type(ret)?),
+                    ret: Box::new(self.resolve_type_at(ret, depth + 1)?),
+                    effects: effects.clone(),
                 })
             }
             Type::Unit(_) => Ok(ResolvedType::unit()),
             Type::Never(_) => Ok(ResolvedType::never()),
             Type::Infer(_) => Ok(ResolvedType::Unknown),
             // For now, just resolve the inner type (ownership is handled separately)
-            Type::Owned { inner, .. } => self.resolve_type(inner),
-            // Volatile type (Phase 8) - resolve inner type, volatile semantics handled at IR level
+            Type::Owned { inner, .. } => self.resolve_type_at(inner, depth + 1),
+            // Volatile pointer (*volatile T) - a plain pointer here; ir_gen marks accesses through it volatile
             Type::Volatile(inner, _) => {
-                Ok(ResolvedType::Pointer(Box::new(self.resolve_type(inner)?)))
+                Ok(ResolvedType::Pointer(Box::new(self.resolve_type_at(inner, depth + 1)?)))
+            }
+        }
+    }
+
+    /// Check a turbofish instantiation `name::<args>` of a generic function
+    fn check_instantiation(&self, name: &Ident, args: &[GenericArg], span: Span) -> Result<ResolvedType> {
+        let symbol = self.symbols.lookup(&name.name).ok_or_else(|| Error::UndefinedVariable {
+            name: name.name.clone(),
+            span: name.span,
+        })?;
+        let SymbolKind::Function { type_params, const_params, .. } = &symbol.kind else {
+            return Err(Error::NotCallable { span: name.span });
+        };
+        let expected = type_params.len() + const_params.len();
+        if args.len() != expected {
+            return Err(Error::GenericArgCountMismatch {
+                name: name.name.clone(),
+                expected,
+                got: args.len(),
+                span,
+            });
+        }
+        for arg in args {
+            match arg {
+                GenericArg::Const(expr) => { self.eval_const_expr(expr)?; }
+                // Single-letter const parameters (`N`) parse as type arguments
+                GenericArg::Type(Type::Named(n, _)) if matches!(
+                    self.symbols.lookup(n).map(|s| &s.kind),
+                    Some(SymbolKind::ConstParam { .. })
+                ) => {}
+                GenericArg::Type(ty) => { self.resolve_type(ty)?; }
             }
         }
+        Ok(symbol.ty.clone())
     }
 
     /// Evaluate a const expression to a ConstValue
@@ -2110,6 +5220,10 @@ impl SemanticAnalyzer {
         if matches!(expected, ResolvedType::Unknown) || matches!(got, ResolvedType::Unknown) {
             return true;
         }
+        // An expression that never finishes can stand in for any value
+        if *got == ResolvedType::NEVER {
+            return true;
+        }
         
         // Strict equality - no implicit conversions between numeric types
         match (expected, got) {
@@ -2126,34 +5240,280 @@ impl SemanticAnalyzer {
                         (I64, U64) | (U64, I64) |
                         // Integer literal (I64) can be assigned to any integer type
                         (I8, I64) | (U8, I64) | (I16, I64) | (U16, I64) |
-                        (I32, I64) | (U32, I64) | (U64, I64) |
+                        (I32, I64) | (U32, I64) | (Usize, I64) | (Isize, I64) |
                         // Allow implicit conversion between F32 and F64 for stdlib compatibility
                         (F32, F64) | (F64, F32)
                     )
                 }
             }
-            (ResolvedType::Pointer(a), ResolvedType::Pointer(b)) => self.types_compatible(a, b),
-            (ResolvedType::Reference { mutable: ma, inner: ia, .. }, 
-             ResolvedType::Reference { mutable: mb, inner: ib, .. }) => {
-                // Mutable reference can be used where immutable is expected
-                (*ma || !*mb) && self.types_compatible(ia, ib)
+            (ResolvedType::Pointer(a), ResolvedType::Pointer(b)) => self.types_compatible(a, b),
+            // A `str` decays to its data pointer, and a C string converts to a `str`
+            (ResolvedType::Pointer(byte), ResolvedType::Str) | (ResolvedType::Str, ResolvedType::Pointer(byte)) => {
+                matches!(byte.as_ref(), ResolvedType::Primitive(PrimitiveType::U8 | PrimitiveType::I8))
+            }
+            (ResolvedType::Reference { mutable: ma, inner: ia, .. }, 
+             ResolvedType::Reference { mutable: mb, inner: ib, .. }) => {
+                // Mutable reference can be used where immutable is expected
+                (*ma || !*mb) && self.types_compatible(ia, ib)
+            }
+            (ResolvedType::Array { elem: ea, size: sa, .. },
+             ResolvedType::Array { elem: eb, size: sb, .. }) => {
+                sa == sb && self.types_compatible(ea, eb)
+            }
+            (ResolvedType::Tuple(a), ResolvedType::Tuple(b)) => {
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| self.types_compatible(x, y))
+            }
+            (ResolvedType::Struct { name: na, .. }, ResolvedType::Struct { name: nb, .. }) => na == nb,
+            (ResolvedType::Generic(na, a), ResolvedType::Generic(nb, b)) => {
+                na == nb && a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| self.types_compatible(x, y))
+            }
+            // Effects are checked separately, see `check_effect_flow`
+            (ResolvedType::Function { params: pa, ret: ra, .. }, ResolvedType::Function { params: pb, ret: rb, .. }) => {
+                pa.len() == pb.len() && pa.iter().zip(pb.iter()).all(|(x, y)| self.types_compatible(x, y))
+                    && self.types_compatible(ra, rb)
+            }
+            (a, b) => a == b,
+        }
+    }
+}
+
+impl Default for SemanticAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ==================== Analysis Phases ====================
+
+/// The first error of a failed phase of `SemanticAnalyzer::analyze_phases`
+#[derive(Debug, Clone)]
+pub enum PhaseError {
+    /// Registering top-level names and imports
+    Collection(Error),
+    /// Resolving the types of signatures, fields, aliases and impls
+    Resolution(Error),
+    /// Type-checking items and bodies
+    Checking(Error),
+}
+
+impl PhaseError {
+    /// Name of the phase that failed
+    pub fn phase(&self) -> &'static str {
+        match self {
+            PhaseError::Collection(_) => "collection",
+            PhaseError::Resolution(_) => "resolution",
+            PhaseError::Checking(_) => "checking",
+        }
+    }
+
+    pub fn error(&self) -> &Error {
+        match self {
+            PhaseError::Collection(error) | PhaseError::Resolution(error) | PhaseError::Checking(error) => error,
+        }
+    }
+
+    pub fn into_error(self) -> Error {
+        match self {
+            PhaseError::Collection(error) | PhaseError::Resolution(error) | PhaseError::Checking(error) => error,
+        }
+    }
+}
+
+impl std::fmt::Display for PhaseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} failed: {}", self.phase(), self.error())
+    }
+}
+
+/// Phase 1: registers the symbols of imported modules, the `String` prelude
+/// and every type the program declares, with a shallow type. Nothing is
+/// resolved, so any item may name a type declared further down.
+struct CollectionPass;
+
+impl CollectionPass {
+    fn run(analyzer: &mut SemanticAnalyzer, program: &Program) -> Result<()> {
+        analyzer.register_builtin_string(program)?;
+        Self::items(analyzer, &program.items)
+    }
+
+    fn items(analyzer: &mut SemanticAnalyzer, items: &[Item]) -> Result<()> {
+        for item in items {
+            match item {
+                Item::Module(ModuleDef { items: Some(items), .. }) => Self::items(analyzer, items)?,
+                Item::Use(use_decl) => analyzer.resolve_use_decl(use_decl)?,
+                _ => {
+                    if let Some(symbol) = Self::shallow_symbol(item) {
+                        analyzer.symbols.define_shallow(symbol)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The symbol of a type declaration, without its fields, variants or target
+    fn shallow_symbol(item: &Item) -> Option<Symbol> {
+        let type_params = |params: &[GenericParam], legacy: &[Ident]| -> Vec<String> {
+            params.iter()
+                .filter_map(|p| match p {
+                    GenericParam::Type { name: ident, .. } => Some(ident.name.clone()),
+                    GenericParam::Const { .. } => None,
+                })
+                .chain(legacy.iter().map(|p| p.name.clone()))
+                .collect()
+        };
+        let (name, kind, ty, span) = match item {
+            Item::Struct(s) => (
+                &s.name,
+                SymbolKind::Struct { fields: vec![], type_params: type_params(&s.generic_params, &s.type_params), const_params: vec![] },
+                ResolvedType::Struct { name: s.name.name.clone(), fields: vec![] },
+                s.span,
+            ),
+            Item::Enum(e) => (
+                &e.name,
+                SymbolKind::Enum { variants: vec![], repr: PrimitiveType::I32, type_params: type_params(&e.generic_params, &e.type_params), const_params: vec![] },
+                ResolvedType::Enum { name: e.name.name.clone() },
+                e.span,
+            ),
+            Item::Union(u) => (
+                &u.name,
+                SymbolKind::Union { fields: vec![] },
+                ResolvedType::Struct { name: u.name.name.clone(), fields: vec![] },
+                u.span,
+            ),
+            Item::TypeAlias(alias) => (
+                &alias.name,
+                SymbolKind::TypeAlias { target: ResolvedType::Unknown },
+                ResolvedType::Unknown,
+                alias.span,
+            ),
+            _ => return None,
+        };
+        Some(Symbol { name: name.name.clone(), kind, ty, span, mutable: false })
+    }
+}
+
+/// Phase 2: resolves the types of every item against the collected names.
+/// Type aliases go first, each after the aliases its target names, so that
+/// no type is resolved through an alias that is still shallow.
+struct ResolutionPass;
+
+impl ResolutionPass {
+    fn run(analyzer: &mut SemanticAnalyzer, program: &Program) -> Result<()> {
+        let mut pending = Vec::new();
+        Self::aliases(&program.items, &mut pending);
+        while !pending.is_empty() {
+            // An alias cycle has no order; its aliases resolve as written
+            let ready = pending.iter()
+                .position(|alias: &&TypeAliasDef| !pending.iter().any(|other| {
+                    other.name.name != alias.name.name && type_mentions(&alias.ty, &other.name.name)
+                }))
+                .unwrap_or(0);
+            analyzer.resolve_type_alias(pending.remove(ready))?;
+        }
+        for item in &program.items {
+            analyzer.collect_definition(item)?;
+        }
+        // Any item may name an associated type left to its interface's default
+        analyzer.bind_default_assoc_types(&program.items)
+    }
+
+    fn aliases<'a>(items: &'a [Item], aliases: &mut Vec<&'a TypeAliasDef>) {
+        for item in items {
+            match item {
+                Item::TypeAlias(alias) => aliases.push(alias),
+                Item::Module(ModuleDef { items: Some(items), .. }) => Self::aliases(items, aliases),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Whether `ty` names `name` anywhere
+fn type_mentions(ty: &Type, name: &str) -> bool {
+    match ty {
+        Type::Named(n, _) => n == name,
+        Type::Generic(n, args, _) => n == name || args.iter().any(|t| type_mentions(t, name)),
+        Type::GenericWithArgs { name: n, args, .. } => n == name || args.iter().any(|arg| {
+            matches!(arg, GenericArg::Type(t) if type_mentions(t, name))
+        }),
+        Type::Pointer(inner, _) | Type::Slice(inner, _) | Type::Volatile(inner, _)
+        | Type::Ref { inner, .. } | Type::Array { elem: inner, .. } | Type::Owned { inner, .. } => type_mentions(inner, name),
+        Type::Tuple(types, _) => types.iter().any(|t| type_mentions(t, name)),
+        Type::Function { params, ret, .. } => params.iter().any(|t| type_mentions(t, name)) || type_mentions(ret, name),
+        Type::Never(_) | Type::Unit(_) | Type::Infer(_) => false,
+    }
+}
+
+/// Phase 3: checks attributes and type-checks every item. Items only read
+/// what the earlier phases registered, so they are checked on worker
+/// threads; an item that fails does not stop the others, and the
+/// diagnostics are merged in source order.
+struct CheckingPass;
+
+impl CheckingPass {
+    fn run(analyzer: &mut SemanticAnalyzer, program: &Program) -> Result<()> {
+        analyzer.check_attributes(&program.items);
+
+        let production = program.inner_attrs.iter().any(|attr| attr.name.name == "production")
+            || program.items.iter().any(|item| {
+                let annotations = match item {
+                    Item::Function(func) => &func.annotations,
+                    Item::Struct(def) => &def.annotations,
+                    _ => return false,
+                };
+                annotations.iter().any(|annotation| annotation.name.name == "production")
+            });
+        analyzer.unit_strictness = match analyzer.strict_override.unwrap_or(production) {
+            true => Strictness::STRICT,
+            false => Strictness::LENIENT,
+        };
+        analyzer.strictness = analyzer.unit_strictness;
+
+        let hosted = !program.inner_attrs.iter()
+            .any(|attr| matches!(attr.name.name.as_str(), "no_std" | "no_main"));
+        let base = &*analyzer;
+        let outcomes = map_chunks(&program.items, base.jobs, |items| {
+            let mut worker = base.fork();
+            let mut outcomes = Vec::new();
+            for item in items {
+                let result = worker.check_top_level_item(item, hosted);
+                let failed = result.is_err();
+                outcomes.push((
+                    result,
+                    std::mem::take(&mut worker.errors),
+                    std::mem::take(&mut worker.warnings),
+                    std::mem::take(&mut worker.strict_only),
+                    worker.expr_types.as_mut().map(std::mem::take),
+                ));
+                if failed {
+                    // The failed item may have left scopes entered
+                    worker = base.fork();
+                }
+            }
+            outcomes
+        });
+        let mut first_failure = None;
+        for (result, errors, warnings, strict_only, types) in outcomes.into_iter().flatten() {
+            for error in errors {
+                analyzer.push_error(error);
             }
-            (ResolvedType::Array { elem: ea, size: sa, .. },
-             ResolvedType::Array { elem: eb, size: sb, .. }) => {
-                sa == sb && self.types_compatible(ea, eb)
+            analyzer.warnings.extend(warnings);
+            analyzer.strict_only.extend(strict_only);
+            if let (Some(all), Some(types)) = (&mut analyzer.expr_types, types) {
+                all.extend(types);
             }
-            (ResolvedType::Tuple(a), ResolvedType::Tuple(b)) => {
-                a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| self.types_compatible(x, y))
+            if let Err(error) = result {
+                first_failure.get_or_insert_with(|| error.clone());
+                analyzer.push_error(error);
             }
-            (ResolvedType::Struct { name: na, .. }, ResolvedType::Struct { name: nb, .. }) => na == nb,
-            (a, b) => a == b,
         }
-    }
-}
 
-impl Default for SemanticAnalyzer {
-    fn default() -> Self {
-        Self::new()
+        match (first_failure, analyzer.errors.first()) {
+            (Some(error), _) => Err(error),
+            (None, Some(error)) => Err(error.clone()),
+            (None, None) => Ok(()),
+        }
     }
 }
 
@@ -2189,4 +5549,1118 @@ mod tests {
         let result = analyze("fn main() { return y }");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_layout_builtins() {
+        let src = "struct P { a: u8, b: u64 }\n\
+                   fn main() { let s: u64 = size_of::<P>() let o: u64 = offset_of(P, b) }";
+        assert!(analyze(src).is_ok());
+        assert!(analyze("struct P { a: u8 }\nfn main() { let o: u64 = offset_of(P, missing) }").is_err());
+        assert!(analyze("fn main() { let s: u64 = size_of::<T>() }").is_err());
+    }
+
+    #[test]
+    fn test_static_items() {
+        assert!(analyze("static mut N: i64 = 1 + 2\nfn main() { N = N + 1 }").is_ok());
+        assert!(analyze("fn f() -> i64 { return 1 }\nstatic N: i64 = f()").is_err());
+
+        // Strict mode requires unsafe for static mut access
+        let src = "static mut N: i64 = 0\nfn main() { N = 1 }";
+        let program = Parser::new(Lexer::new(src, 0)).parse_program().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.set_strict_mode(true);
+        assert!(analyzer.analyze(&program).is_err());
+
+        let src = "static mut N: i64 = 0\nfn main() { unsafe { N = 1 } }";
+        let program = Parser::new(Lexer::new(src, 0)).parse_program().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.set_strict_mode(true);
+        assert!(analyzer.analyze(&program).is_ok());
+    }
+
+    #[test]
+    fn test_union_fields() {
+        let union_def = "#[repr(C)]\nunion Bits { as_float: f32, as_int: u32 }\n";
+
+        // Field reads are typed by the accessed field and need unsafe
+        let ok = format!("{}fn f() -> u32 {{ let b: Bits = Bits {{ as_float: 1.0 }}\n unsafe {{ return b.as_int }} }}", union_def);
+        assert!(analyze(&ok).is_ok());
+        let unsafe_missing = format!("{}fn f() -> u32 {{ let b: Bits = Bits {{ as_float: 1.0 }}\n return b.as_int }}", union_def);
+        assert!(matches!(analyze(&unsafe_missing), Err(Error::UnsafeRequired { .. })));
+        let wrong_ty = format!("{}fn f() {{ let b: Bits = Bits {{ as_int: 1 }}\n unsafe {{ let x: bool = b.as_float }} }}", union_def);
+        assert!(analyze(&wrong_ty).is_err());
+
+        // Literals initialize exactly one field
+        let two_fields = format!("{}fn f() {{ let b: Bits = Bits {{ as_float: 1.0, as_int: 2 }} }}", union_def);
+        assert!(analyze(&two_fields).is_err());
+
+        assert!(matches!(analyze("#[repr(transparent)]\nunion U { a: u8 }"), Err(Error::InvalidRepr { .. })));
+    }
+
+    #[test]
+    fn test_enum_discriminants() {
+        let errno = "#[repr(u8)]\nenum Errno { EPERM = 1, ENOENT, EIO = 5 }\n";
+        assert!(analyze(&format!("{}fn f(e: Errno) -> i32 {{ return e as i32 }}", errno)).is_ok());
+
+        // Implicit values follow the previous variant, so `C` collides with `B`
+        assert!(matches!(analyze("enum E { A = 1, B, C = 2 }"), Err(Error::DuplicateDiscriminant { ref first, .. }) if first == "B"));
+        assert!(matches!(analyze("#[repr(u8)]\nenum E { A = 255, B }"), Err(Error::DiscriminantOutOfRange { ref variant, .. }) if variant == "E::B"));
+        assert!(matches!(analyze("#[repr(i8)]\nenum E { A = -129 }"), Err(Error::DiscriminantOutOfRange { .. })));
+        assert!(matches!(analyze("#[repr(f32)]\nenum E { A }"), Err(Error::InvalidRepr { .. })));
+
+        // Integers become enums only through an unchecked cast in `unsafe`
+        let checked = format!("{}fn f(n: i32) -> Errno {{ return n as Errno }}", errno);
+        assert!(matches!(analyze(&checked), Err(Error::UnsafeRequired { .. })));
+        assert!(analyze(&format!("{}fn f(n: i32) -> Errno {{ unsafe {{ return n as Errno }} }}", errno)).is_ok());
+
+        let payload = "enum V { A(i64), B }\nfn f(v: V) -> i64 { return v as i64 }";
+        assert!(matches!(analyze(payload), Err(Error::TypeMismatch { .. })));
+    }
+
+    #[test]
+    fn test_inline_asm_operands() {
+        let ok = "fn f() -> i64 { let mut r: i64 = 39\n unsafe { asm!(\"syscall\", inout(rax) r, in(rdi) 0, clobber(\"rcx\"), clobber(\"memory\")) }\n return r }";
+        assert!(analyze(ok).is_ok());
+
+        let no_unsafe = "fn f() { asm!(\"nop\") }";
+        assert!(matches!(analyze(no_unsafe), Err(Error::UnsafeRequired { .. })));
+        let bad_clobber = "fn f() { unsafe { asm!(\"nop\", clobber(\"everything\")) } }";
+        assert!(matches!(analyze(bad_clobber), Err(Error::InvalidAsmOperand { .. })));
+        let bad_class = "fn f() { unsafe { asm!(\"nop\", in(vreg) 1) } }";
+        assert!(matches!(analyze(bad_class), Err(Error::InvalidAsmOperand { .. })));
+        let immutable_out = "fn f() { let r: i64 = 0\n unsafe { asm!(\"nop\", out(reg) r) } }";
+        assert!(matches!(analyze(immutable_out), Err(Error::InvalidAsmOperand { .. })));
+        let undefined_in = "fn f() { unsafe { asm!(\"nop\", in(reg) missing) } }";
+        assert!(matches!(analyze(undefined_in), Err(Error::UndefinedVariable { .. })));
+    }
+
+    #[test]
+    fn test_main_signature() {
+        assert!(analyze("fn main() {}").is_ok());
+        assert!(analyze("fn main() -> i32 { return 0 }").is_ok());
+        assert!(analyze("fn main(args: [str]) -> i32 { return arg_count() }").is_ok());
+        assert!(analyze("fn main(argc: i32, argv: **u8) -> i32 { return argc }").is_ok());
+
+        for bad in [
+            "fn main(n: i64) {}",
+            "fn main(args: [i32]) {}",
+            "fn main(argc: i64, argv: **u8) {}",
+            "fn main() -> bool { return true }",
+        ] {
+            assert!(matches!(analyze(bad), Err(Error::InvalidMainSignature { .. })), "{}", bad);
+        }
+
+        // Without a hosted entry point, main is an ordinary function
+        assert!(analyze("#![no_main]\nfn main(n: i64) {}").is_ok());
+    }
+
+    #[test]
+    fn test_method_resolution() {
+        let counter = "struct Counter { count: i64 }\nimpl Counter {\n fn bump(self: *Counter, n: i64) -> i64 { return self.count + n }\n fn new() -> Counter { return Counter { count: 0 } }\n}\n";
+
+        // The method's return type flows into the caller
+        let ok = format!("{}fn f(c: *Counter) -> i64 {{ let v: i64 = c.bump(1)\n return v }}", counter);
+        assert!(analyze(&ok).is_ok());
+        let wrong_ret = format!("{}fn f(c: *Counter) {{ let v: bool = c.bump(1) }}", counter);
+        assert!(matches!(analyze(&wrong_ret), Err(Error::TypeMismatch { .. })));
+
+        // Arguments are checked against the parameters after `self`
+        let wrong_arg = format!("{}fn f(c: *Counter) {{ c.bump(true) }}", counter);
+        assert!(matches!(analyze(&wrong_arg), Err(Error::TypeMismatch { .. })));
+        let wrong_count = format!("{}fn f(c: *Counter) {{ c.bump(1, 2) }}", counter);
+        assert!(matches!(analyze(&wrong_count), Err(Error::ArgCountMismatch { .. })));
+
+        // Unknown methods and associated functions called as methods are rejected
+        let missing = format!("{}fn f(c: *Counter) {{ c.reset() }}", counter);
+        assert!(matches!(analyze(&missing), Err(Error::MethodNotFound { .. })));
+        let no_self = format!("{}fn f(c: *Counter) {{ c.new() }}", counter);
+        assert!(matches!(analyze(&no_self), Err(Error::MethodNotFound { .. })));
+    }
+
+    #[test]
+    fn test_primitive_methods() {
+        let ext = "impl i32 {\n fn clamp_to(self: i32, hi: i32) -> i32 { if self > hi { return hi } return self }\n fn zero() -> i32 { return 0 }\n}\n";
+
+        let ok = format!("{}fn f(x: i32) -> i32 {{ let v: i32 = x.clamp_to(10)\n return v }}", ext);
+        assert!(analyze(&ok).is_ok());
+        let wrong_ret = format!("{}fn f(x: i32) {{ let v: bool = x.clamp_to(10) }}", ext);
+        assert!(matches!(analyze(&wrong_ret), Err(Error::TypeMismatch { .. })));
+        let wrong_arg = format!("{}fn f(x: i32) {{ x.clamp_to(true) }}", ext);
+        assert!(matches!(analyze(&wrong_arg), Err(Error::TypeMismatch { .. })));
+
+        // Methods belong to the one primitive they extend
+        let missing = format!("{}fn f(x: i32) {{ x.abs() }}", ext);
+        assert!(matches!(analyze(&missing), Err(Error::MethodNotFound { ref ty, .. }) if ty == "i32"));
+        let no_self = format!("{}fn f(x: i32) {{ x.zero() }}", ext);
+        assert!(matches!(analyze(&no_self), Err(Error::MethodNotFound { .. })));
+    }
+
+    #[test]
+    fn test_associated_types() {
+        let iface = "interface Iterator {\n    type Item\n    fn next(self: *Self) -> Self::Item;\n}\nstruct Counter { count: i64 }\n";
+
+        // Self::Item resolves to the impl's binding
+        let ok = format!("{}impl Iterator for Counter {{ type Item = i64\n fn next(self: *Counter) -> Self::Item {{ let v: i64 = (*self).count\n return v }} }}", iface);
+        assert!(analyze(&ok).is_ok());
+        let wrong = format!("{}impl Iterator for Counter {{ type Item = bool\n fn next(self: *Counter) -> Self::Item {{ let v: Self::Item = (*self).count\n return v }} }}", iface);
+        assert!(analyze(&wrong).is_err());
+
+        let missing = format!("{}impl Iterator for Counter {{ fn next(self: *Counter) -> i64 {{ return 0 }} }}", iface);
+        assert!(matches!(analyze(&missing), Err(Error::MissingAssociatedType { .. })));
+    }
+
+    #[test]
+    fn test_const_generic_instantiation() {
+        let generic = "fn scaled<const N: usize>(x: i64) -> i64 { return x * N }\n";
+        assert!(analyze(&format!("{}fn main() -> i64 {{ return scaled::<4>(2) }}", generic)).is_ok());
+        assert!(matches!(
+            analyze(&format!("{}fn main() -> i64 {{ return scaled::<4, 8>(2) }}", generic)),
+            Err(Error::GenericArgCountMismatch { expected: 1, got: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_unsafe_tracking() {
+        let run = |src: &str, strict: bool| {
+            let program = Parser::new(Lexer::new(src, 0)).parse_program().unwrap();
+            let mut analyzer = SemanticAnalyzer::new();
+            analyzer.set_strict_mode(strict);
+            let result = analyzer.analyze(&program);
+            (result, analyzer.warnings().to_vec())
+        };
+
+        let (result, warnings) = run("fn f(p: *i64) -> i64 { unsafe { return *p } }", true);
+        assert!(result.is_ok() && warnings.is_empty(), "{:?} {:?}", result, warnings);
+
+        let (result, warnings) = run("extern \"C\" { fn abs(x: i32) -> i32; }\nfn f() -> i32 { unsafe { return abs(1) } }", true);
+        assert!(result.is_ok() && warnings.is_empty(), "{:?} {:?}", result, warnings);
+
+        let (result, warnings) = run("fn f(a: i64) -> i64 { unsafe { return a + 1 } }", true);
+        assert!(result.is_ok());
+        assert!(matches!(warnings.as_slice(), [Error::UnnecessaryUnsafe { .. }]));
+
+        // The inner block covers the dereference, leaving the outer one unnecessary
+        let (result, warnings) = run("fn f(p: *i64) -> i64 { unsafe { unsafe { return *p } } }", true);
+        assert!(result.is_ok());
+        assert!(matches!(warnings.as_slice(), [Error::UnnecessaryUnsafe { span }] if span.start == 23), "{:?}", warnings);
+
+        // Raw pointer access outside unsafe: an error in strict mode, a warning otherwise
+        let outside = "fn f(p: *i64) -> i64 { return p[0] }";
+        assert!(matches!(run(outside, true).0, Err(Error::UnsafeOperationOutsideUnsafeBlock { .. })));
+        let (result, warnings) = run(outside, false);
+        assert!(result.is_ok());
+        assert!(matches!(warnings.as_slice(), [Error::UnsafeOperationOutsideUnsafeBlock { .. }]));
+    }
+
+    #[test]
+    fn test_unsafe_gated_operations() {
+        let run = |src: &str, strict: bool| {
+            let program = Parser::new(Lexer::new(src, 0)).parse_program().unwrap();
+            let mut analyzer = SemanticAnalyzer::new();
+            analyzer.set_strict_mode(strict);
+            let result = analyzer.analyze(&program);
+            (result, analyzer.warnings().to_vec())
+        };
+        let externs = "extern \"C\" { fn abs(x: i32) -> i32; }\n";
+        let statics = "static mut N: i64 = 0\n";
+
+        // Each operation is accepted inside `unsafe` without warnings
+        let inside = [
+            "fn f(p: *i64) -> i64 { unsafe { return *p } }".to_string(),
+            "fn f(a: usize) -> *u8 { unsafe { return a as *u8 } }".to_string(),
+            "fn f(p: *u8) -> usize { unsafe { return p as usize } }".to_string(),
+            format!("{}fn f() -> i32 {{ unsafe {{ return abs(1) }} }}", externs),
+            format!("{}fn f() -> i64 {{ unsafe {{ return N }} }}", statics),
+            format!("{}fn f() {{ unsafe {{ N = 1 }} }}", statics),
+        ];
+        for src in &inside {
+            let (result, warnings) = run(src, true);
+            assert!(result.is_ok() && warnings.is_empty(), "{}: {:?} {:?}", src, result, warnings);
+        }
+
+        // Outside it they are errors in strict mode and warnings otherwise
+        let outside = [
+            "fn f(a: usize) -> *u8 { return a as *u8 }".to_string(),
+            "fn f(p: *u8) -> usize { return p as usize }".to_string(),
+            format!("{}fn f() -> i32 {{ return abs(1) }}", externs),
+            format!("{}fn f() -> i64 {{ return N }}", statics),
+            format!("{}fn f() {{ N = 1 }}", statics),
+        ];
+        for src in &outside {
+            assert!(matches!(run(src, true).0, Err(Error::UnsafeRequired { .. })), "{}", src);
+            let (result, warnings) = run(src, false);
+            assert!(result.is_ok(), "{}: {:?}", src, result);
+            assert!(matches!(warnings.as_slice(), [Error::UnsafeRequired { .. }]), "{}: {:?}", src, warnings);
+        }
+        assert!(matches!(run("fn f(p: *i64) -> i64 { return *p }", true).0, Err(Error::UnsafeOperationOutsideUnsafeBlock { .. })));
+
+        // References, pointer-to-pointer casts and integer casts stay safe
+        let (result, warnings) = run("fn f(r: &i64, p: *u8) -> i64 { let q: *i64 = p as *i64\n return *r as i64 }", true);
+        assert!(result.is_ok() && warnings.is_empty(), "{:?} {:?}", result, warnings);
+    }
+
+    #[test]
+    fn test_effects_in_function_types() {
+        let run = |src: &str, strict: bool| {
+            let program = Parser::new(Lexer::new(src, 0)).parse_program().unwrap();
+            let mut analyzer = SemanticAnalyzer::new();
+            analyzer.set_strict_mode(strict);
+            let result = analyzer.analyze(&program);
+            (result, analyzer.warnings().to_vec())
+        };
+        let sort = "
+            fn sort2(a: i64, b: i64, less: fn(i64, i64) -> bool pure) -> i64 pure {
+                if less(b, a) { return b; }
+                return a
+            }
+            fn by_value(a: i64, b: i64) -> bool pure { return a < b }
+            fn noisy(a: i64, b: i64) -> bool effect[io] { println_i64(a); return a < b }
+        ";
+
+        // A pure comparator, a named one or a closure, flows into a pure parameter
+        let pure = format!("{}\nfn main() effect[io] {{ println_i64(sort2(2, 1, by_value)); println_i64(sort2(2, 1, |a: i64, b: i64| a < b)) }}", sort);
+        let (result, warnings) = run(&pure, true);
+        assert!(result.is_ok() && warnings.is_empty(), "{:?} {:?}", result, warnings);
+
+        // A printing one does not: an error in strict mode, a warning otherwise
+        let printing = format!("{}\nfn main() effect[io] {{ println_i64(sort2(2, 1, noisy)) }}", sort);
+        match run(&printing, true).0 {
+            Err(Error::EffectViolation { message, .. }) => {
+                assert!(message.contains("`pure`") && message.contains("`effect[io]`"), "{}", message);
+            }
+            other => panic!("expected an effect violation, got {:?}", other),
+        }
+        let (result, warnings) = run(&printing, false);
+        assert!(result.is_ok());
+        assert!(matches!(warnings.as_slice(), [Error::EffectViolation { .. }]));
+
+        // Closures and builtins carry the effects they use
+        let closure = format!("{}\nfn main() effect[io] {{ println_i64(sort2(2, 1, |a: i64, b: i64| {{ println_i64(a); a < b }})) }}", sort);
+        assert!(matches!(run(&closure, true).0, Err(Error::EffectViolation { .. })));
+        let builtin = "fn each(f: fn(i64) pure) {}\nfn main() { each(println_i64) }";
+        assert!(matches!(run(builtin, true).0, Err(Error::EffectViolation { .. })));
+
+        // Calling through a parameter has the effects of its type
+        let call = "fn apply(f: fn(i64) effect[io]) { f(1) }";
+        assert!(matches!(run(call, false).0, Err(Error::EffectViolation { .. })));
+        let (result, _) = run("fn apply(f: fn(i64) effect[io]) effect[io] { f(1) }", true);
+        assert!(result.is_ok(), "{:?}", result);
+
+        // The same applies to assignments
+        let assign = format!("{}\nfn main() {{ let f: fn(i64, i64) -> bool pure = noisy; }}", sort);
+        assert!(matches!(run(&assign, true).0, Err(Error::EffectViolation { .. })));
+    }
+
+    #[test]
+    fn test_nested_modules() {
+        let source = "
+            mod outer {
+                fn base() -> i32 { return 40 }
+                mod inner {
+                    fn add(x: i32) -> i32 { return base() + x }
+                }
+                fn twice() -> i32 { return inner::add(1) }
+            }
+            fn main() -> i32 { return outer::inner::add(2) + outer::twice() }
+        ";
+        assert!(analyze(source).is_ok());
+        // Module items are not visible unqualified from outside the module
+        assert!(matches!(
+            analyze("mod m { fn f() {} } fn main() { f() }"),
+            Err(Error::UndefinedVariable { .. })
+        ));
+        assert!(analyze("mod m { fn f() {} } fn main() { m::inner::f() }").is_err());
+    }
+
+    #[test]
+    fn test_str_methods() {
+        assert!(analyze("fn f(s: str) -> usize { let t: str = s.slice(1, 3); return t.len() }").is_ok());
+        assert!(analyze("fn f(s: str) -> *u8 { return s.as_ptr() }").is_ok());
+        // C strings convert to `str` and back
+        assert!(analyze("fn f(p: *u8) -> *u8 { let s: str = p; return s }").is_ok());
+        assert!(matches!(analyze("fn f(s: str) { s.slice(1) }"), Err(Error::ArgCountMismatch { .. })));
+        assert!(matches!(analyze("fn f(s: str) { s.chars() }"), Err(Error::MethodNotFound { .. })));
+    }
+
+    #[test]
+    fn test_collections() {
+        assert!(analyze("fn f() -> i64 effect[alloc, panic] { let mut v: Vec<i64> = Vec::new(); v.push(1); return v[0] + v.pop().unwrap_or(0) }").is_ok());
+        // `get` borrows the element, `pop` moves it out, both as an `Option`
+        assert!(analyze("fn f(v: &Vec<i64>) -> bool { let first: &i64 = v.get(0).unwrap_or(v.get(1).unwrap()); return v.is_empty() }").is_ok());
+        assert!(matches!(analyze("fn f(v: &Vec<i64>) -> i64 { let first: i64 = v.get(0); return first }"), Err(Error::TypeMismatch { .. })));
+        assert!(analyze("fn f() -> i64 effect[alloc] { let v: Vec<i64> = Vec::with_capacity(8); let mut n: i64 = 0; for x in v { n = n + x } return n }").is_ok());
+        assert!(matches!(analyze("fn f(v: Vec<i64>) { for x in v { let b: bool = x } }"), Err(Error::TypeMismatch { .. })));
+        assert!(analyze("fn f(m: &HashMap<str, i32>) -> bool { return m.contains(\"a\") && m.len() > 0 }").is_ok());
+        assert!(matches!(analyze("fn f(v: Vec<i64>) effect[alloc] { v.push(\"a\") }"), Err(Error::TypeMismatch { .. })));
+        assert!(matches!(analyze("fn f(v: Vec<i64>) { v.insert(1, 2) }"), Err(Error::MethodNotFound { .. })));
+        assert!(matches!(analyze("fn f(m: HashMap<f64, i64>) { }"), Err(Error::TypeMismatch { .. })));
+        // Elements are copied by size, except arrays; `pop` also fits them into an `Option`
+        let point = "struct P { x: i64, y: i64 }\n";
+        assert!(analyze(&format!("{}fn f(v: Vec<P>) -> i64 {{ return v[0].x }}", point)).is_ok());
+        assert!(analyze("fn f(v: Vec<(i64, bool)>) { }").is_ok());
+        assert!(matches!(analyze("fn f(v: Vec<[i64; 2]>) { }"), Err(Error::UnsupportedPayload { .. })));
+        assert!(matches!(analyze(&format!("{}fn f(v: Vec<P>) -> i64 {{ return v.pop().unwrap().x }}", point)), Err(Error::UnsupportedPayload { .. })));
+        assert!(analyze(&format!("{}fn f(v: Vec<*P>) -> *P {{ return v.pop().unwrap() }}", point)).is_ok());
+        assert!(analyze("fn f(v: Vec<str>) -> str { return v[0] }").is_ok());
+        assert!(matches!(analyze("fn f(v: Vec<str>) -> str { return v.pop().unwrap() }"), Err(Error::UnsupportedPayload { .. })));
+        // Growing a collection allocates
+        assert!(matches!(analyze("fn f(v: Vec<i64>) effect[io] { v.push(1) }"), Err(Error::EffectViolation { .. })));
+    }
+
+    #[test]
+    fn test_typed_allocation() {
+        let point = "struct Point { x: i64, y: i64 }\n";
+        let ok = format!("{}fn f() -> i64 effect[alloc] {{ let p: *Point = new::<Point>(); let n: i64 = p.x; delete(p); return n }}", point);
+        assert!(analyze(&ok).is_ok());
+        let lit = format!("{}fn f() -> *Point effect[alloc] {{ return new Point {{ x: 1, y: 2 }} }}", point);
+        assert!(analyze(&lit).is_ok());
+        assert!(analyze("fn f(n: usize) -> *i64 effect[alloc] { return new_array::<i64>(n) }").is_ok());
+        assert!(matches!(analyze("fn f() -> *i64 { return new::<i64>() }"), Err(Error::EffectViolation { .. })));
+        assert!(matches!(analyze("fn f() -> *i64 effect[alloc] { return new_array::<i64>(1.5) }"), Err(Error::TypeMismatch { .. })));
+    }
+
+    #[test]
+    fn test_file_builtins_need_io() {
+        assert!(analyze("fn f(p: str) -> i32 effect[io] { let s: str = read_file(p); return write_file(p, s, s.len()) }").is_ok());
+        assert!(analyze("fn f() -> i32 { return last_error() }").is_ok());
+        assert!(matches!(analyze("fn f(p: str) -> *u8 { return file_open(p, \"rb\") }"), Err(Error::EffectViolation { .. })));
+    }
+
+    fn warnings_of(source: &str) -> Vec<Error> {
+        let program = Parser::new(Lexer::new(source, 0)).parse_program().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.analyze(&program).unwrap();
+        analyzer.warnings().to_vec()
+    }
+
+    #[test]
+    fn test_duplicate_items() {
+        let duplicate = |source: &str| match analyze(source) {
+            Err(Error::DuplicateDefinition { name, first, span }) => {
+                assert!(first.start < span.start, "{:?} should precede {:?}", first, span);
+                name
+            }
+            other => panic!("expected a duplicate definition, got {:?}", other),
+        };
+        assert_eq!(duplicate("fn helper() {}\nfn helper() {}"), "helper");
+        assert_eq!(duplicate("fn f(x: i64) -> i64 { return x }\nfn f(y: bool) {}"), "f");
+        assert_eq!(duplicate("struct P { x: i64 }\nstruct P { y: i64 }"), "P");
+        assert_eq!(duplicate("enum E { A }\nfn E() {}"), "E");
+        // A declaration may still replace a builtin
+        assert!(analyze("extern \"C\" {\n    fn exit(status: i32);\n}\nfn main() { exit(0) }").is_ok());
+    }
+
+    #[test]
+    fn test_let_shadowing() {
+        // A later `let` replaces the binding, even with another type
+        assert!(analyze("fn f() -> bool { let x: i64 = 1; let x: bool = x > 0; return x }").is_ok());
+        assert!(matches!(
+            analyze("fn f() { let x: i64 = 1; let x: bool = true; let y: i64 = x; }"),
+            Err(Error::TypeMismatch { .. })
+        ));
+        assert!(analyze("fn f() -> i64 { let (a, b): (i64, i64) = (1, 2); let (a, c): (i64, i64) = (b, 3); return a + c }").is_ok());
+        // The new binding has no borrows, unlike the one it hides
+        assert!(matches!(
+            analyze("fn f() { let mut x: i64 = 1; let a: &mut i64 = &mut x; let b: &mut i64 = &mut x; }"),
+            Err(Error::CannotMutBorrowTwice { .. })
+        ));
+        assert!(analyze("fn f() { let mut x: i64 = 1; let a: &mut i64 = &mut x; let mut x: i64 = 2; let b: &mut i64 = &mut x; }").is_ok());
+
+        // Hiding a parameter warns; hiding a variable, here or in a block, does not
+        let warnings = warnings_of("fn f(n: i64) -> i64 { let n: i64 = n * 2; let n: i64 = n + 1; return n }");
+        assert!(matches!(warnings.as_slice(), [Error::ShadowedParameter { name, .. }] if name == "n"), "{:?}", warnings);
+        assert!(warnings_of("fn f(n: i64) -> i64 { let m: i64 = n; { let m: i64 = 2; } return m }").is_empty());
+    }
+
+    #[test]
+    fn test_loop_break_value() {
+        assert!(analyze("fn f(n: i64) -> i64 { let mut i: i64 = 0; let r: i64 = loop { i = i + 1; if i > n { break i * 2; } }; return r }").is_ok());
+        assert!(analyze("fn f() -> bool { return loop { break true; } }").is_ok());
+        // A loop without `break value` still never yields
+        assert!(analyze("fn f() -> i64 { loop { } }").is_ok());
+        assert!(matches!(analyze("fn f() { let x: i64 = loop { break true; }; }"), Err(Error::TypeMismatch { .. })));
+        assert!(matches!(
+            analyze("fn f(c: bool) -> i64 { return loop { if c { break 1; } break false; } }"),
+            Err(Error::TypeMismatch { .. })
+        ));
+        assert!(matches!(analyze("fn f() { while true { break 1; } }"), Err(Error::TypeMismatch { .. })));
+    }
+
+    #[test]
+    fn test_attributes() {
+        let source = "#[inline]\nfn a() {}\n#[inline(never)]\n#[cold]\nfn b() {}\n#[hot]\nfn c() {}\n\
+                      #[repr(C)]\nstruct P { x: i64 }\nimpl P {\n    #[inline]\n    fn get(self: ref P) -> i64 { return self.x }\n}";
+        assert!(warnings_of(source).is_empty());
+
+        let warnings = warnings_of("#[inlin]\nfn a() {}\n#[inline(sometimes)]\nfn b() {}");
+        let names: Vec<&str> = warnings.iter()
+            .filter_map(|w| match w { Error::UnknownAttribute { name, .. } => Some(name.as_str()), _ => None })
+            .collect();
+        assert_eq!(names, vec!["inlin", "inline(sometimes)"]);
+        assert_eq!(warnings[0].span().map(|s| s.start), Some(0));
+
+        match analyze("#[inline]\nstruct P { x: i64 }") {
+            Err(Error::MisplacedAttribute { name, item, .. }) => assert_eq!((name.as_str(), item.as_str()), ("inline", "struct 'P'")),
+            other => panic!("expected a misplaced attribute, got {:?}", other),
+        }
+        assert!(matches!(analyze("#[repr(C)]\nfn f() {}"), Err(Error::MisplacedAttribute { .. })));
+    }
+
+    #[test]
+    fn test_field_order() {
+        let fields = "{ flag: bool, count: u64, tag: u16 }";
+        match warnings_of(&format!("struct Packet {}", fields)).as_slice() {
+            [Error::SuboptimalFieldOrder { name, padding, optimal, .. }] => {
+                assert_eq!((name.as_str(), *padding, *optimal), ("Packet", 13, 5));
+            }
+            other => panic!("expected a field order warning, got {:?}", other),
+        }
+        // Already ordered, opted out, fixed by `repr` or reordered by the compiler
+        assert!(warnings_of("struct Packet { count: u64, tag: u16, flag: bool }").is_empty());
+        assert!(warnings_of(&format!("#[field_order(declaration)]\nstruct Packet {}", fields)).is_empty());
+        assert!(warnings_of(&format!("#[repr(C)]\nstruct Packet {}", fields)).is_empty());
+
+        let optimal = format!("#[field_order(optimal)]\nstruct Packet {}\nfn main() {{ let o: u64 = offset_of(Packet, tag) }}", fields);
+        let program = Parser::new(Lexer::new(&optimal, 0)).parse_program().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.analyze(&program).unwrap();
+        assert!(analyzer.warnings().is_empty());
+        assert_eq!(analyzer.layouts.offset_of("Packet", "tag"), Some(8));
+
+        let unknown = warnings_of(&format!("#[field_order(random)]\nstruct Packet {}", fields));
+        assert!(matches!(unknown.as_slice(), [Error::UnknownAttribute { name, .. }] if name == "field_order(random)"));
+        assert!(matches!(analyze(&format!("#[repr(C)]\n#[field_order(optimal)]\nstruct Packet {}", fields)), Err(Error::InvalidRepr { .. })));
+    }
+
+    fn variable(name: &str, ty: ResolvedType) -> Symbol {
+        Symbol { name: name.to_string(), kind: SymbolKind::Variable, ty, span: Span::dummy(), mutable: false }
+    }
+
+    #[test]
+    fn test_scope_shadowing_and_sibling_isolation() {
+        let mut table = SymbolTable::new();
+        table.define(variable("x", ResolvedType::I64)).unwrap();
+
+        table.enter_scope();
+        table.define(variable("x", ResolvedType::BOOL)).unwrap();
+        table.define(variable("y", ResolvedType::I64)).unwrap();
+        assert_eq!(table.lookup("x").unwrap().ty, ResolvedType::BOOL);
+        table.exit_scope();
+        assert_eq!(table.lookup("x").unwrap().ty, ResolvedType::I64);
+
+        // A sibling reuses the popped scope's storage but not its symbols
+        table.enter_scope();
+        assert!(table.lookup("y").is_none());
+        assert!(table.lookup_local("x").is_none());
+
+        let mark = table.snapshot();
+        table.enter_scope();
+        table.enter_scope();
+        table.define(variable("z", ResolvedType::I64)).unwrap();
+        table.restore(mark);
+        assert_eq!(table.current(), ScopeId(1));
+        assert!(table.lookup("z").is_none());
+
+        table.exit_scope();
+        table.exit_scope();
+        assert_eq!(table.current(), ScopeId(0));
+        assert!(table.lookup("x").is_some());
+    }
+
+    /// `depth` nested blocks, then `sequential` sibling blocks, each binding a local
+    fn many_blocks(depth: usize, sequential: usize) -> String {
+        let mut body = String::new();
+        for i in 0..depth {
+            body.push_str(&format!("{{ let n{}: i64 = {} ", i, i));
+        }
+        body.push_str(&"} ".repeat(depth));
+        for i in 0..sequential {
+            body.push_str(&format!("{{ let s{}: i64 = {} }} ", i, i));
+        }
+        format!("fn main() {{ {} }}\nfn after() {{ let n0: bool = true }}", body)
+    }
+
+    /// Analyze `source` on a large stack: live scopes, spare scopes and time taken
+    fn analyze_blocks(source: String) -> (usize, usize, std::time::Duration) {
+        std::thread::Builder::new().stack_size(512 << 20).spawn(move || {
+            let program = Parser::new(Lexer::new(&source, 0))
+                .with_max_nesting(usize::MAX)
+                .parse_program()
+                .unwrap();
+            let mut analyzer = SemanticAnalyzer::new();
+            analyzer.set_max_nesting(usize::MAX);
+            let start = std::time::Instant::now();
+            analyzer.analyze(&program).unwrap();
+            let elapsed = start.elapsed();
+            (analyzer.symbols.scopes.len(), analyzer.symbols.spare.len(), elapsed)
+        }).unwrap().join().unwrap()
+    }
+
+    #[test]
+    fn test_scopes_freed_after_many_blocks() {
+        // All scopes are popped again, and storage is bounded by the nesting depth
+        let (live, spare, _) = analyze_blocks(many_blocks(10_000, 10_000));
+        assert_eq!(live, 1);
+        assert!(spare <= 10_000 + 2, "{} spare scopes", spare);
+
+        let (_, spare, _) = analyze_blocks(many_blocks(0, 10_000));
+        assert!(spare <= 3, "{} spare scopes for sequential blocks", spare);
+
+        // Four times the blocks should take roughly four times as long, not sixteen
+        let best = |n: usize| (0..3).map(|_| analyze_blocks(many_blocks(n, n)).2).min().unwrap();
+        let (small, large) = (best(2_500), best(10_000));
+        assert!(large < small * 10, "{:?} for 2,500 blocks but {:?} for 10,000", small, large);
+    }
+
+    #[test]
+    fn test_error_size() {
+        // Every nested block holds `Result`s in its frames, so a larger
+        // `Error` lowers the depth `test_scopes_freed_after_many_blocks` reaches
+        assert!(std::mem::size_of::<Error>() <= 80, "{} bytes", std::mem::size_of::<Error>());
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let analyze_nested = |source: &str| {
+            let program = Parser::new(Lexer::new(source, 0)).parse_program().unwrap();
+            let mut analyzer = SemanticAnalyzer::new();
+            analyzer.set_max_nesting(20);
+            analyzer.analyze(&program)
+        };
+        let unary = format!("fn f() -> i64 {{ return {}1; }}", "-".repeat(30));
+        let pointers = format!("fn f(x: {}i64) {{}}", "*".repeat(30));
+        for source in [unary, pointers] {
+            assert!(matches!(analyze_nested(&source), Err(Error::NestingTooDeep { limit: 20, .. })), "{}", source);
+        }
+
+        // Chains of binary operators are checked in a loop, whatever their length
+        let chain = format!("fn f(x: i64) -> i64 {{ return {}; }}", vec!["x"; 1000].join(" + "));
+        assert!(analyze_nested(&chain).is_ok());
+    }
+
+    #[test]
+    fn test_assignment_targets() {
+        let structs = "struct P { x: i64 }\nstruct L { p: P }\n";
+        let ok = [
+            "fn f() { let mut a: i64 = 1; a += 2; }",
+            "fn f() { let mut l: L = L { p: P { x: 1 } }; l.p.x = 2; }",
+            "fn f() { let mut a: [i64; 2] = [1, 2]; a[0] -= 1; }",
+            "fn f(p: *P) { p.x = 1; (*p).x *= 2; }",
+            "fn f(p: *i64) { *p = 1; *p += 2; }",
+        ];
+        for source in ok {
+            assert!(analyze(&format!("{}{}", structs, source)).is_ok(), "{}", source);
+        }
+
+        let invalid = ["fn f() { let mut a: i64 = 1; a + 1 = 2; }", "fn f() { 1 += 2; }"];
+        for source in invalid {
+            let err = analyze(&format!("{}{}", structs, source)).unwrap_err();
+            assert!(matches!(err, Error::InvalidAssignTarget { .. }), "{}: {:?}", source, err);
+        }
+
+        let immutable = [
+            "fn f() { let a: i64 = 1; a = 2; }",
+            "fn f() { let a: i64 = 1; a += 2; }",
+            "fn f() { let l: L = L { p: P { x: 1 } }; l.p.x = 2; }",
+            "fn f() { let a: [i64; 2] = [1, 2]; a[1] = 3; }",
+        ];
+        for source in immutable {
+            let err = analyze(&format!("{}{}", structs, source)).unwrap_err();
+            assert!(matches!(&err, Error::AssignToImmutable { name, .. } if name.len() == 1), "{}: {:?}", source, err);
+        }
+    }
+
+    #[test]
+    fn test_bitfields() {
+        let flags = "struct Flags {\n    #[bits(1)]\n    is_ready: bool,\n    #[bits(7)]\n    reserved: u8,\n    count: u16,\n}\n";
+        let ok = [
+            "fn f(s: Flags) -> u8 { return s.reserved }",
+            "fn f() { let mut s: Flags = Flags { is_ready: true, reserved: 3, count: 0 }; s.reserved = 9; }",
+            "fn f(s: Flags) -> *u16 { return &s.count }",
+        ];
+        for source in ok {
+            assert!(analyze(&format!("{}{}", flags, source)).is_ok(), "{}", source);
+        }
+        let err = analyze(&format!("{}fn f(s: Flags) -> *u8 {{ return &s.reserved }}", flags)).unwrap_err();
+        assert!(matches!(err, Error::InvalidBitfield { ref field, .. } if field == "reserved"), "{:?}", err);
+        // Constants stored into a bitfield must fit its width; other values are masked
+        let too_wide = [
+            "fn f(s: *Flags) { s.reserved = 128 }",
+            "fn f() -> Flags { return Flags { is_ready: true, reserved: 200, count: 0 } }",
+        ];
+        for source in too_wide {
+            let err = analyze(&format!("{}{}", flags, source)).unwrap_err();
+            assert!(matches!(err, Error::InvalidBitfield { ref reason, .. } if reason.contains("does not fit in 7 bits")), "{}: {:?}", source, err);
+        }
+        assert!(analyze(&format!("{}fn f(s: *Flags, n: u8) {{ s.reserved = 127\n s.reserved = n\n s.count = 70000 }}", flags)).is_ok());
+
+        let invalid = [
+            "struct S { #[bits(9)] a: u8 }",
+            "struct S { #[bits(0)] a: u32 }",
+            "struct S { #[bits(2)] a: bool }",
+            "struct S { #[bits(3)] a: i32 }",
+            "#[repr(packed)] struct S { #[bits(3)] a: u32 }",
+            "#[field_order(optimal)] struct S { #[bits(3)] a: u32, b: u64 }",
+        ];
+        for source in invalid {
+            let err = analyze(source).unwrap_err();
+            assert!(matches!(err, Error::InvalidBitfield { .. }), "{}: {:?}", source, err);
+        }
+
+        let mut parser = Parser::new(Lexer::new(flags, 0));
+        let program = parser.parse_program().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.analyze(&program).unwrap();
+        assert_eq!(analyzer.layouts.struct_layout("Flags").unwrap().offsets, vec![0, 0, 2]);
+    }
+
+    #[test]
+    fn test_interface_bounds() {
+        let shapes = "interface Shape {\n    fn area(self: *Self) -> i64;\n}\n\
+            struct Square { side: i64 }\nstruct Line { len: i64 }\n\
+            impl Shape for Square {\n    fn area(self: *Square) -> i64 { return self.side * self.side }\n}\n\
+            fn area_of<T: Shape>(s: *T) -> i64 { return s.area() }\n";
+        let ok = [
+            "fn f(q: Square) -> i64 { return area_of(&q) }",
+            "fn twice<S: Shape>(s: *S) -> i64 { return area_of(s) * 2 }",
+        ];
+        for source in ok {
+            assert!(analyze(&format!("{}{}", shapes, source)).is_ok(), "{}", source);
+        }
+
+        let err = analyze(&format!("{}fn f(l: Line) -> i64 {{ return area_of(&l) }}", shapes)).unwrap_err();
+        assert!(matches!(&err, Error::UnsatisfiedBound { ty, interface, .. } if ty == "Line" && interface == "Shape"), "{:?}", err);
+        let err = analyze(&format!("{}fn f<S>(s: *S) -> i64 {{ return area_of(s) }}", shapes)).unwrap_err();
+        assert!(matches!(&err, Error::UnsatisfiedBound { ty, .. } if ty == "S"), "{:?}", err);
+        let err = analyze(&format!("{}fn f<S>(s: *S) -> i64 {{ return s.area() }}", shapes)).unwrap_err();
+        assert!(matches!(&err, Error::MissingBound { param, interface, .. } if param == "S" && interface == "Shape"), "{:?}", err);
+        let err = analyze(&format!("{}fn f<S: Shape>(s: *S) -> i64 {{ return s.perimeter() }}", shapes)).unwrap_err();
+        assert!(matches!(err, Error::MethodNotFound { .. }), "{:?}", err);
+        let err = analyze(&format!("{}fn f<S: Square>(s: *S) -> i64 {{ return 0 }}", shapes)).unwrap_err();
+        assert!(matches!(err, Error::TypeMismatch { .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn test_equality_needs_derive() {
+        let types = "struct P { x: i64 }\n#[derive(Eq)] struct Q { x: i64 }\nenum C { A, B }\n\
+            enum S { Dot(i64) }\n#[derive(Eq)] enum T { Dot(i64), Pair(Q, C) }\n";
+        let ok = [
+            "fn f(a: Q, b: Q) -> bool { return a == b }",
+            "fn f(a: C, b: C) -> bool { return a != b }",
+            "fn f(a: T, b: T) -> bool { return a == b }",
+            "fn f(a: str, b: str) -> bool { return a == b }",
+            "fn f(a: *P, b: *P) -> bool { return a == b }",
+        ];
+        for source in ok {
+            assert!(analyze(&format!("{}{}", types, source)).is_ok(), "{}", source);
+        }
+
+        let not_equatable = [
+            "fn f(a: P, b: P) -> bool { return a == b }",
+            "fn f(a: S, b: S) -> bool { return a != b }",
+        ];
+        for source in not_equatable {
+            let err = analyze(&format!("{}{}", types, source)).unwrap_err();
+            assert!(matches!(err, Error::NotEquatable { .. }), "{}: {:?}", source, err);
+        }
+        let err = analyze("struct P { x: i64 }\n#[derive(Eq)] struct R { p: P }\n").unwrap_err();
+        assert!(matches!(&err, Error::NotEquatable { ty, .. } if ty == "P"), "{:?}", err);
+
+        let err = analyze(&format!("{}fn f(a: C, b: C) -> bool {{ return a < b }}", types)).unwrap_err();
+        assert!(matches!(err, Error::NotOrdered { .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn test_operator_impls() {
+        let types = "struct V { x: i64 }\n\
+                     impl Add for V { fn add(self: V, other: V) -> V { return V { x: self.x + other.x } } }\n\
+                     impl Mul for V { fn mul(self: V, k: i64) -> V { return V { x: self.x * k } } }\n\
+                     impl Eq for V { fn eq(self: V, other: V) -> bool { return self.x == other.x } }\n\
+                     impl Ord for V { fn cmp(self: V, other: V) -> i32 { return 0 } }\n";
+        let ok = [
+            "fn f(a: V, b: V) -> V { return a + b + a }",
+            "fn f(a: V) -> V { return a * 3 }",
+            "fn f(a: V, b: V) -> bool { return a == b || a != b }",
+            "fn f(a: V, b: V) -> bool { return a < b && a >= b }",
+            "fn f(b: V) -> i64 { let mut a: V = V { x: 1 }\n a += b\n a *= 2\n return a.x }",
+            "fn f(p: *V) -> *V { unsafe { return p + 1 } }",
+        ];
+        for source in ok {
+            assert!(analyze(&format!("{}{}", types, source)).is_ok(), "{}", source);
+        }
+
+        // The right operand must have exactly the type the method takes
+        let missing = [
+            ("fn f(a: V, b: V) -> V { return a - b }", "Sub"),
+            ("fn f(a: V) -> V { return a + 1 }", "Add"),
+            ("fn f(a: V, b: V) -> V { return a * b }", "Mul"),
+            ("fn f(b: V) { let mut a: V = V { x: 1 }\n a -= b }", "Sub"),
+        ];
+        for (source, expected) in missing {
+            let err = analyze(&format!("{}{}", types, source)).unwrap_err();
+            assert!(matches!(&err, Error::MissingOperatorImpl { op, .. } if op.operator_interface().is_some_and(|(i, _)| i == expected)), "{}: {:?}", source, err);
+        }
+        let err = analyze("struct W { x: i64 }\nimpl Ord for W { fn cmp(self: W, other: W) -> bool { return true } }\n\
+                           fn f(a: W, b: W) -> bool { return a < b }").unwrap_err();
+        assert!(matches!(err, Error::TypeMismatch { .. }), "{:?}", err);
+
+        // An inherent method is not an operator impl
+        let err = analyze("struct W { x: i64 }\nimpl W { fn add(self: W, other: W) -> W { return other } }\n\
+                           fn f(a: W) -> W { return a + a }").unwrap_err();
+        assert!(matches!(err, Error::MissingOperatorImpl { .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn test_paths_have_types() {
+        let types = "enum Shape { Empty, Circle(i64) }\nstruct P { x: i64 }\nimpl P {\n    fn new(x: i64) -> P { return P { x: x } }\n}\n";
+        let ok = [
+            "fn f() -> Shape { return Shape::Circle(2) }",
+            "fn f() -> i64 { let p: P = P::new(1)\n return p.x }",
+            "fn f() -> i64 { let e: [i64; 0] = []\n return 0 }",
+            "fn g(e: [i64; 0]) -> i64 { return 0 }\nfn f() -> i64 { return g([]) }",
+        ];
+        for source in ok {
+            assert!(analyze(&format!("{}{}", types, source)).is_ok(), "{}", source);
+        }
+
+        // Variants and associated functions no longer pass for anything
+        let mismatched = [
+            "fn f() -> i64 { let n: i64 = Shape::Empty\n return n }",
+            "fn f() -> Shape { return Shape::Circle(true) }",
+            "fn f() -> i64 { let p: i64 = P::new(1)\n return p }",
+        ];
+        for source in mismatched {
+            let err = analyze(&format!("{}{}", types, source)).unwrap_err();
+            assert!(matches!(err, Error::TypeMismatch { .. }), "{}: {:?}", source, err);
+        }
+        let err = analyze(&format!("{}fn f() -> Shape {{ return Shape::Circle() }}", types)).unwrap_err();
+        assert!(matches!(err, Error::ArgCountMismatch { .. }), "{:?}", err);
+
+        // An empty array needs an array type to take its element type from
+        let err = analyze("fn f() effect[io] { println_i64([]) }").unwrap_err();
+        assert!(matches!(err, Error::EmptyArrayType { .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn test_uninferred_types() {
+        let run = |src: &str, strict: bool| {
+            let program = Parser::new(Lexer::new(src, 0)).parse_program().unwrap();
+            let mut analyzer = SemanticAnalyzer::new();
+            analyzer.set_strict_mode(strict);
+            let result = analyzer.analyze(&program);
+            (result, analyzer.warnings().to_vec())
+        };
+        // A method of a tuple is unknown; only the call is reported, not
+        // the `let` it initializes
+        let unknown = "fn f() -> i64 {\n    let t: (i64, i64) = (1, 2)\n    t.frob(3)\n    return 0\n}";
+        let (result, warnings) = run(unknown, false);
+        assert!(result.is_ok());
+        let uninferred: Vec<_> = warnings.iter().filter(|w| matches!(w, Error::UninferredType { .. })).collect();
+        assert!(matches!(uninferred.as_slice(), [Error::UninferredType { what, span }] if what == "this expression" && span.start == unknown.find("t.frob").unwrap()), "{:?}", uninferred);
+        assert!(matches!(run(unknown, true).0, Err(Error::UninferredType { .. })));
+
+        let known = "enum C { A, B }\nfn f() -> C { let c: C = C::A\n return c }";
+        let (result, warnings) = run(known, true);
+        assert!(result.is_ok() && warnings.is_empty(), "{:?} {:?}", result, warnings);
+    }
+
+    #[test]
+    fn test_struct_invariants() {
+        let fields = "{ lo: i64, hi: i64 }\nfn main() {}";
+        assert!(analyze(&format!("struct Range [invariant lo <= hi] {}", fields)).is_ok());
+
+        let err = analyze(&format!("struct Range [invariant lo + hi] {}", fields)).unwrap_err();
+        assert_eq!(err.code(), "E0024");
+
+        let err = analyze(&format!("static LIMIT: i64 = 10\nstruct Range [invariant hi <= LIMIT] {}", fields)).unwrap_err();
+        assert!(matches!(err, Error::InvalidInvariant { ref reason, .. } if reason.contains("'LIMIT'")), "{}", err);
+    }
+
+    #[test]
+    fn test_match_guards() {
+        let guarded = |guard: &str| analyze(&format!(
+            "fn sign(x: i64) -> i64 {{\n    return match x {{ n if {} => n, _ => 0 }}\n}}", guard
+        ));
+        assert!(guarded("n > 0").is_ok());
+        let err = guarded("n + 1").unwrap_err();
+        assert!(matches!(err, Error::TypeMismatch { ref expected, .. } if expected == "bool"), "{}", err);
+
+        // Pattern bindings end with their arm
+        let err = analyze("fn f(x: i64) -> i64 {\n    let y: i64 = match x { n => n, _ => n }\n    return y\n}").unwrap_err();
+        assert!(matches!(err, Error::UndefinedVariable { ref name, .. } if name == "n"), "{}", err);
+    }
+
+    #[test]
+    fn test_or_patterns() {
+        let shape = "enum Shape { Circle(i64), Square(i64), Named(bool) }\n";
+        let arm = |pattern: &str| analyze(&format!(
+            "{}fn f(s: Shape) -> i64 {{\n    return match s {{ {} => 1, _ => 0 }}\n}}", shape, pattern
+        ));
+        // Payload bindings are typed, so a guard can compare them
+        assert!(arm("Shape::Circle(r) | Shape::Square(r) if r > 10").is_ok());
+        let err = arm("Shape::Circle(r) | Shape::Square(_) if r > 10").unwrap_err();
+        assert!(matches!(err, Error::InconsistentOrPattern { ref name, .. } if name == "r"), "{}", err);
+        assert_eq!(err.code(), "E0027");
+        let err = arm("Shape::Circle(r) | Shape::Named(r)").unwrap_err();
+        assert!(matches!(err, Error::TypeMismatch { .. }), "{}", err);
+    }
+
+    #[test]
+    fn test_match_exhaustiveness() {
+        let tok = "enum Tok { Plus, Minus, Num(i64) }\n";
+        let arms = |arms: &str| analyze(&format!("{}fn f(t: Tok) -> i64 {{\n    return match t {{ {} }}\n}}", tok, arms));
+        let missing = |result: Result<()>| match result {
+            Err(Error::NonExhaustiveMatch { missing, .. }) => missing,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(missing(arms("Tok::Plus => 1, Tok::Num(n) => n")), "Tok::Minus");
+        // A guarded arm covers nothing; an or-pattern covers all its alternatives
+        assert_eq!(missing(arms("Tok::Plus | Tok::Minus => 1, Tok::Num(n) if n > 10 => n")), "Tok::Num(_)");
+        assert!(arms("Tok::Plus | Tok::Minus => 1, Tok::Num(n) if n > 10 => n, Tok::Num(n) => 0 - n").is_ok());
+        assert!(arms("Tok::Num(0) => 0, _ => 1").is_ok());
+
+        // Payloads, tuples and booleans are split further; integers need `_`
+        let nested = analyze("fn f(o: Option<Option<i64> >) -> i64 {\n    return match o { Some(Some(v)) => v, Option::None => 0 }\n}");
+        assert_eq!(missing(nested), "Option::Some(Option::None)");
+        let pair = analyze(&format!("{}fn f(p: (bool, Tok)) -> i64 {{\n    return match p {{ (true, _) => 1, (false, Tok::Num(n)) => n, (_, Tok::Plus) => 2 }}\n}}", tok));
+        assert_eq!(missing(pair), "(false, Tok::Minus)");
+        assert_eq!(missing(analyze("fn f(n: i64) -> i64 {\n    return match n { 1 => 10, 2 => 20 }\n}")), "_");
+        let err = analyze("fn f(b: bool) -> i64 {\n    return match b { true => 1, false => 0 }\n}");
+        assert!(err.is_ok(), "{:?}", err);
+    }
+
+    #[test]
+    fn test_destructuring_let() {
+        let decls = "struct Point { x: i64, y: i64 }\nenum Shape { Dot(Point), Empty }\n";
+        let body = |stmts: &str| analyze(&format!("{}fn f(s: Shape, p: Point) -> i64 {{\n{}\n}}", decls, stmts));
+        // Bound names take the types of the parts they stand for
+        assert!(body("let (a, (b, c)): (i64, (bool, i64)) = (1, (true, 2))\nif b { return a + c }\nreturn 0").is_ok());
+        assert!(body("let Point { x, .. }: Point = p\nreturn x").is_ok());
+        let err = body("let (a, b): (i64, bool) = (1, true)\nlet c: bool = a\nreturn 0").unwrap_err();
+        assert!(matches!(err, Error::TypeMismatch { .. }), "{}", err);
+
+        let err = body("let Point { x }: Point = p\nreturn x").unwrap_err();
+        assert!(matches!(err, Error::MissingPatternFields { ref fields, .. } if fields == "`y`"), "{}", err);
+        let err = body("let Point { z, .. }: Point = p\nreturn 0").unwrap_err();
+        assert!(matches!(err, Error::UnknownField { ref field, .. } if field == "z"), "{}", err);
+        let err = body("let Shape::Dot(Point { x, .. }): Shape = s\nreturn x").unwrap_err();
+        assert_eq!(err.code(), "E0028");
+        // `if let` handles the values the pattern does not match
+        assert!(body("if let Shape::Dot(Point { x, .. }) = s { return x }\nreturn 0").is_ok());
+    }
+
+    #[test]
+    fn test_let_expression() {
+        let body = |stmts: &str| analyze(&format!("fn f(n: i64) -> i64 {{\n{}\n}}", stmts));
+        // The binding has the value's type and stays in scope after the condition
+        assert!(body("if (let m = n * 2) > 4 { return m }\nreturn m + 1").is_ok());
+        assert!(body("while (let m: i64 = n) > 0 { return m }\nreturn 0").is_ok());
+        let err = body("if (let b = n > 1) { return 0 }\nlet c: i64 = b\nreturn c").unwrap_err();
+        assert!(matches!(err, Error::TypeMismatch { .. }), "{}", err);
+        let err = body("let k: i64 = (let m: bool = n)\nreturn k").unwrap_err();
+        assert!(matches!(err, Error::TypeMismatch { .. }), "{}", err);
+    }
+
+    #[test]
+    fn test_auto_deref_field_chains() {
+        let list = "struct Node { value: i64, next: *Node }\n";
+        let ok = analyze(&format!(
+            "{}fn third(head: **Node) -> i64 {{\n    return head.next.next.value\n}}", list
+        ));
+        assert!(ok.is_ok(), "{:?}", ok.err());
+
+        let too_deep = format!("{}fn f(p: {}Node) -> i64 {{\n    return p.value\n}}", list, "*".repeat(MAX_AUTO_DEREF + 1));
+        let err = analyze(&too_deep).unwrap_err();
+        assert!(matches!(err, Error::FieldOfNonStruct { ref field, .. } if field == "value"), "{}", err);
+        let err = analyze("fn f(x: i64) -> i64 {\n    return x.value\n}").unwrap_err();
+        assert!(matches!(err, Error::FieldOfNonStruct { ref ty, .. } if ty == "i64"), "{}", err);
+    }
+
+    #[test]
+    fn test_tail_expressions() {
+        let body = |stmts: &str| analyze(&format!("fn f(n: i64) -> i64 {{\n{}\n}}", stmts));
+        assert!(body("n + 1").is_ok());
+        assert!(body("if n > 0 { n } else { 0 }").is_ok());
+        assert!(body("if n > 0 { return 1 } else { n }").is_ok());
+        assert!(body("match n { 0 => 1, _ => { let m: i64 = n; m } }").is_ok());
+        assert!(body("loop { return n }").is_ok());
+        assert!(body("return n;").is_ok());
+        let err = body("n + 1;").unwrap_err();
+        assert!(matches!(err, Error::MissingTailValue { ref name, .. } if name == "f"), "{}", err);
+        let err = body("let m: i64 = n").unwrap_err();
+        assert!(matches!(err, Error::MissingTailValue { .. }), "{}", err);
+        let err = body("if n > 0 { return 1 }").unwrap_err();
+        assert!(matches!(err, Error::TypeMismatch { .. }), "{}", err);
+    }
+
+    #[test]
+    fn test_for_element_types() {
+        let body = |params: &str, stmts: &str| analyze(&format!("fn f({}) -> i64 {{\n{}\n}}", params, stmts));
+        assert!(body("a: [i64; 4]", "let mut s: i64 = 0; for x in a { s = s + x; } s").is_ok());
+        assert!(body("a: &[i64; 4]", "let mut s: i64 = 0; for x in a { s = s + x; } s").is_ok());
+        let err = body("a: [i64; 2]", "for x in a { let b: bool = x; } 0").unwrap_err();
+        assert!(matches!(err, Error::TypeMismatch { .. }), "{}", err);
+        for params in ["v: *i64", "v: [i64]", "v: i64"] {
+            let err = body(params, "for x in v { } 0").unwrap_err();
+            assert!(matches!(err, Error::NotIterable { .. }), "{}: {}", params, err);
+        }
+    }
+
+    #[test]
+    fn test_max_errors() {
+        let source: String = (0..5).map(|i| format!("fn f{i}() -> i64 {{ return x{i} }}\n")).collect();
+        let program = Parser::new(Lexer::new(&source, 0)).parse_program().unwrap();
+        for jobs in [1, 3] {
+            let mut analyzer = SemanticAnalyzer::new();
+            analyzer.set_jobs(jobs);
+            analyzer.set_max_errors(2);
+            let err = analyzer.analyze(&program).unwrap_err();
+            assert!(matches!(err, Error::UndefinedVariable { ref name, .. } if name == "x0"), "{}", err);
+            let names: Vec<String> = analyzer.errors.iter().map(|e| e.to_string()).collect();
+            assert_eq!(names, ["Undefined variable: x0", "Undefined variable: x1", "Too many errors (limit 2); stopping here"]);
+            assert_eq!(analyzer.suppressed_errors(), 3);
+        }
+    }
+
+    #[test]
+    fn test_option_and_result() {
+        // `Some(42)` is an `Option` of the literal's integer type
+        assert!(analyze("fn f() { let x: Option<i32> = Some(42)\n let y: i32 = x.unwrap() }").is_ok());
+        assert!(matches!(analyze("fn f() { let x: Option<bool> = Some(42) }"), Err(Error::TypeMismatch { .. })));
+        assert!(analyze("fn f() -> Option<i64> { return None }").is_ok());
+        assert!(matches!(analyze("fn f() { let x: Option<i64> = Some(1, 2) }"), Err(Error::ArgCountMismatch { .. })));
+
+        // `?` on a `Result<T, E>` is the `T`
+        let parse = "fn parse(n: i64) -> Result<i64, bool> { if n < 0 { return Err(true) }\n return Ok(n) }\n";
+        assert!(analyze(&format!("{}fn f() -> Result<i64, bool> {{ let v: i64 = parse(1)?\n return Ok(v) }}", parse)).is_ok());
+        let wrong = format!("{}fn f() -> Result<i64, bool> {{ let v: bool = parse(1)?\n return Ok(1) }}", parse);
+        assert!(matches!(analyze(&wrong), Err(Error::TypeMismatch { .. })));
+        assert!(matches!(analyze("fn f() -> i64 { let n: i64 = 1\n return n? }"), Err(Error::TypeMismatch { .. })));
+
+        let twice = "fn twice(n: i64) -> i64 { return n * 2 }\n";
+        assert!(analyze(&format!("{}fn f() {{ let x: Option<i64> = Some(2).map(twice)\n let y: i64 = x.unwrap_or(0) }}", twice)).is_ok());
+        assert!(analyze(&format!("{}fn f() {{ let r: Result<bool, i64> = Err(1)\n let s: Result<bool, i64> = r.map_err(twice) }}", twice)).is_ok());
+        assert!(matches!(analyze("fn f() { let x: Option<i64> = Some(2)\n let y: i64 = x.unwrap_or(true) }"), Err(Error::TypeMismatch { .. })));
+        // Methods outside the prelude's have nothing to call
+        assert!(matches!(analyze("fn f() { let r: Result<i64, bool> = Ok(1)\n let v: i64 = r.unwrap_or(1) }"),
+            Err(Error::MethodNotFound { ref method, ref ty, .. }) if method == "unwrap_or" && ty == "Result"));
+        assert!(matches!(analyze("fn f() { let o: Option<i64> = None\n o.frobnicate() }"), Err(Error::MethodNotFound { .. })));
+        assert!(analyze("enum Option<T> { Some(T), None }\nimpl Option { fn is_some(self: Option) -> bool { return true } }\n\
+            fn f() -> bool { let o: Option<i64> = Option::None\n return o.is_some() }").is_ok());
+
+        // A program may declare the enums itself
+        assert!(analyze("enum Option<T> { Some(T), None }\nfn f() -> Option<i64> { return Option::Some(1) }").is_ok());
+
+        // Payloads are erased to an integer slot, which a struct or a float does not fit
+        let point = "struct P { x: i64, y: i64 }\n";
+        assert!(matches!(analyze(&format!("{}fn f(b: bool) -> Option<P> {{ return None }}", point)),
+            Err(Error::UnsupportedPayload { .. })));
+        assert!(matches!(analyze(&format!("{}fn f() -> i64 {{ match Some(P {{ x: 1, y: 2 }}) {{ Some(p) => p.x, None => 0 }} }}", point)),
+            Err(Error::UnsupportedPayload { .. })));
+        assert!(matches!(analyze("fn f() -> Result<i64, f64> { return Ok(1) }"), Err(Error::UnsupportedPayload { .. })));
+        assert!(analyze(&format!("{}fn f(p: *P) -> Option<*P> {{ return Some(p) }}", point)).is_ok());
+    }
+
+    #[test]
+    fn test_strictness() {
+        let run = |src: &str, strict: Option<bool>| {
+            let program = Parser::new(Lexer::new(src, 0)).parse_program().unwrap();
+            let mut analyzer = SemanticAnalyzer::new();
+            if let Some(strict) = strict {
+                analyzer.set_strict_mode(strict);
+            }
+            let result = analyzer.analyze(&program);
+            let strict_only = result.as_ref().err().is_some_and(|e| analyzer.is_strict_only(e));
+            (result, analyzer.warnings().to_vec(), strict_only)
+        };
+        let narrow = "fn narrow(n: i64) { let b: u8 = n }";
+
+        // Lenient by default: a warning; literals are never lossy
+        let (result, warnings, _) = run(narrow, None);
+        assert!(result.is_ok());
+        assert!(matches!(warnings.as_slice(), [Error::LossyConversion { ref from, ref to, .. }] if from == "i64" && to == "u8"));
+        assert!(run("fn f() { let b: u8 = 7\n let c: i32 = -1 }", None).1.is_empty());
+
+        // `#[production]` on any item makes the whole unit strict
+        let (result, _, strict_only) = run(&format!("#[production]\nfn main() {{}}\n{}", narrow), None);
+        assert!(matches!(result, Err(Error::LossyConversion { .. })) && strict_only);
+        assert!(matches!(run(&format!("#![production]\n{}", narrow), None).0, Err(Error::LossyConversion { .. })));
+
+        // `#[prototype]` relaxes one function of a strict unit, even under --strict
+        let prototype = format!("#![production]\n#[prototype]\n{}\nfn g(p: *i64) -> i64 {{ return p[0] }}", narrow);
+        assert!(matches!(run(&prototype, None).0, Err(Error::UnsafeOperationOutsideUnsafeBlock { .. })));
+        let relaxed = format!("#[prototype]\n{}", narrow);
+        let (result, warnings, _) = run(&relaxed, Some(true));
+        assert!(result.is_ok() && warnings.len() == 1, "{:?}", warnings);
+
+        // --lenient wins over the annotations
+        let (result, warnings, _) = run(&format!("#![production]\n{}", narrow), Some(false));
+        assert!(result.is_ok() && warnings.len() == 1);
+
+        // Errors that are not strictness checks do not go away
+        let (result, _, strict_only) = run("#![production]\nfn f() -> i64 { return x }", None);
+        assert!(result.is_err() && !strict_only);
+    }
+
+    #[test]
+    fn test_builtin_string() {
+        // The prelude's own code passes the analyzer
+        let prelude = Program { items: stdlib::string_items().to_vec(), inner_attrs: vec![] };
+        assert!(SemanticAnalyzer::new().analyze(&prelude).is_ok());
+
+        let ok = "fn f(name: &String) -> usize effect[alloc] {
+            let mut s: String = String::from_str(\"hi \")
+            s.push_char('x')
+            let t: String = s + name + \"!\"
+            let view: str = t.as_str()
+            return view.len()
+        }";
+        assert!(analyze(ok).is_ok(), "{:?}", analyze(ok));
+        assert!(matches!(analyze("fn f(s: String) -> String { return s + 1 }"), Err(Error::TypeMismatch { .. })));
+        assert!(matches!(analyze("fn f(s: String) { s.push_str(1) }"), Err(Error::TypeMismatch { .. })));
+
+        // A program may declare a `String` of its own
+        assert!(analyze("struct String { bytes: *u8 }\nfn f(s: String) -> *u8 { return s.bytes }").is_ok());
+    }
+
+    #[test]
+    fn test_analysis_phases() {
+        // Types, including single-letter ones and aliases of aliases, may be
+        // named before they are declared
+        let forward = "struct P { q: Q }\nstruct Q { v: i64 }\ntype Meters = Len\ntype Len = i64\n\
+                       fn get(p: P) -> i64 { return p.q.v }\n\
+                       fn add(m: Meters) -> i64 { return m + 1 }\n\
+                       fn main() -> i64 { let p: P = P { q: Q { v: 3 } }\n return get(p) + add(2) }";
+        assert!(analyze(forward).is_ok(), "{:?}", analyze(forward));
+
+        let phase = |source: &str| {
+            let program = Parser::new(Lexer::new(source, 0)).parse_program().unwrap();
+            SemanticAnalyzer::new().analyze_phases(&program).map_err(|e| e.phase())
+        };
+        assert_eq!(phase("struct A { x: i64 }\nstruct A { y: i64 }"), Err("collection"));
+        assert_eq!(phase("#[repr(f32)]\nenum E { A }"), Err("resolution"));
+        assert_eq!(phase("fn f() -> i64 { return missing }"), Err("checking"));
+    }
+
+    #[test]
+    fn test_freestanding() {
+        let freestanding = |source: &str| {
+            let program = Parser::new(Lexer::new(source, 0)).parse_program().unwrap();
+            let mut analyzer = SemanticAnalyzer::new();
+            analyzer.set_freestanding(true);
+            analyzer.analyze(&program)
+        };
+        let not_freestanding = |source: &str| match freestanding(source) {
+            Err(Error::NotFreestanding { name, .. }) => name,
+            other => panic!("expected NotFreestanding, got {:?}", other),
+        };
+        assert_eq!(not_freestanding("fn _start() effect[io] { print(\"hi\") }"), "print");
+        assert_eq!(not_freestanding("fn _start() effect[alloc] { let p: *u8 = alloc(8) }"), "alloc");
+        assert_eq!(not_freestanding("fn _start() effect[alloc] { let p: *i64 = new::<i64>() }"), "new");
+        assert_eq!(not_freestanding("fn _start() { let s: String = String::new() }"), "String");
+
+        // What the program declares itself is available, even under a builtin's name
+        assert!(freestanding("extern \"C\" { fn exit(code: i32); }\nfn _start() { unsafe { exit(1) } }").is_ok());
+        assert!(analyze("fn main() effect[io] { print(\"hi\") }").is_ok());
+    }
 }
@@ -0,0 +1,120 @@
+//! Interned identifiers and string literals
+//!
+//! Tokens carry a `Symbol` instead of an owned `String`, so copying or
+//! comparing a token never touches the heap. The text lives once in the
+//! `SourceDb` the lexer fills and the parser reads from.
+
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+
+/// Index of an interned string in a `SourceDb`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+/// Contextual keywords the parser compares against, interned up front so
+/// the comparison is an integer check
+pub mod sym {
+    use super::Symbol;
+
+    pub const UNDERSCORE: Symbol = Symbol(0);
+    pub const LINK: Symbol = Symbol(1);
+
+    pub(super) const PREDEFINED: [&str; 2] = ["_", "link"];
+}
+
+/// String arena for one compilation unit
+#[derive(Debug, Clone)]
+pub struct SourceDb {
+    strings: Vec<Box<str>>,
+    index: HashMap<Box<str>, Symbol, BuildHasherDefault<FxHasher>>,
+}
+
+impl Default for SourceDb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SourceDb {
+    pub fn new() -> Self {
+        let mut db = Self { strings: Vec::new(), index: HashMap::default() };
+        for text in sym::PREDEFINED {
+            db.intern(text);
+        }
+        db
+    }
+
+    /// Symbol for `text`, adding it on first use
+    pub fn intern(&mut self, text: &str) -> Symbol {
+        if let Some(&symbol) = self.index.get(text) {
+            return symbol;
+        }
+        let symbol = Symbol(self.strings.len() as u32);
+        self.strings.push(text.into());
+        self.index.insert(text.into(), symbol);
+        symbol
+    }
+
+    /// Text of an interned symbol
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.strings[symbol.0 as usize]
+    }
+
+    /// Symbol for `text` if it has been interned
+    pub fn get(&self, text: &str) -> Option<Symbol> {
+        self.index.get(text).copied()
+    }
+}
+
+/// Multiply-rotate hash (rustc's FxHash); identifiers are short and not
+/// attacker-controlled, so SipHash's DoS resistance buys nothing here
+#[derive(Default)]
+struct FxHasher {
+    hash: u64,
+}
+
+impl FxHasher {
+    const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+    fn add(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(Self::SEED);
+    }
+}
+
+impl Hasher for FxHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            self.add(u64::from_le_bytes(chunk.try_into().unwrap()));
+        }
+        for &byte in chunks.remainder() {
+            self.add(byte as u64);
+        }
+    }
+
+    fn write_u8(&mut self, byte: u8) {
+        self.add(byte as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern_round_trip() {
+        let mut db = SourceDb::new();
+        let a = db.intern("point");
+        let b = db.intern("point");
+        assert_eq!(a, b);
+        assert_ne!(a, db.intern("Point"));
+        assert_eq!(db.resolve(a), "point");
+        assert_eq!(db.intern("_"), sym::UNDERSCORE);
+        assert_eq!(db.get("link"), Some(sym::LINK));
+        assert_eq!(db.get("missing"), None);
+    }
+}
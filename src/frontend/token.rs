@@ -1,9 +1,10 @@
 //! Token definitions for AetherLang
 #![allow(dead_code)]
 
+use crate::frontend::source_db::Symbol;
 use crate::utils::Span;
 /// A token produced by the lexer
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Token {
     pub kind: TokenKind,
     pub span: Span,
@@ -19,8 +20,8 @@ impl Token {
     }
 }
 
-/// Token kinds
-#[derive(Debug, Clone, PartialEq)]
+/// Token kinds. Text-carrying kinds hold a `Symbol` interned in the lexer's `SourceDb`.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TokenKind {
     // ============ Keywords ============
     /// fn
@@ -73,6 +74,10 @@ pub enum TokenKind {
     Asm,
     /// as (cast)
     As,
+    /// do (array element block)
+    Do,
+    /// yield (value of a do block)
+    Yield,
     
     // ============ AI-Native Keywords (New) ============
     /// type (type definition)
@@ -112,13 +117,13 @@ pub enum TokenKind {
     
     // ============ Identifiers and Literals ============
     /// Identifier (variable name, function name, etc.)
-    Ident(String),
+    Ident(Symbol),
     /// Integer literal
     IntLit(i64),
     /// Floating-point literal
     FloatLit(f64),
     /// String literal
-    StringLit(String),
+    StringLit(Symbol),
     /// Character literal
     CharLit(char),
     
@@ -216,7 +221,11 @@ pub enum TokenKind {
     
     // ============ Lifetime ============
     /// Lifetime parameter ('a, 'static)
-    Lifetime(String),
+    Lifetime(Symbol),
+
+    // ============ Comments ============
+    /// `/// text`, with the marker and one leading space removed
+    DocComment(Symbol),
     
     // ============ Special ============
     /// End of file
@@ -255,6 +264,8 @@ impl TokenKind {
                 | TokenKind::False
                 | TokenKind::Asm
                 | TokenKind::As
+                | TokenKind::Do
+                | TokenKind::Yield
                 // AI-Native keywords
                 | TokenKind::Type
                 | TokenKind::Trait
@@ -303,6 +314,8 @@ impl TokenKind {
             "false" => Some(TokenKind::False),
             "asm" => Some(TokenKind::Asm),
             "as" => Some(TokenKind::As),
+            "do" => Some(TokenKind::Do),
+            "yield" => Some(TokenKind::Yield),
             // AI-Native keywords
             "type" => Some(TokenKind::Type),
             "trait" => Some(TokenKind::Trait),
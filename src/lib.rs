@@ -0,0 +1,19 @@
+//! AetherLang Compiler Library
+//!
+//! The compiler as a library: the `aethc` binary, the LSP server and other
+//! tools drive it through [`CompileSession`].
+
+pub mod frontend;
+pub mod middle;
+pub mod backend;
+pub mod types;
+pub mod utils;
+pub mod stdlib;
+pub mod ai_ir;
+pub mod feedback;
+pub mod lsp;
+pub mod script;
+pub mod session;
+pub mod doc;
+
+pub use session::{Artifact, CompileResult, CompileSession, SourceInput, Stage};
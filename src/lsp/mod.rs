@@ -1,15 +1,36 @@
 //! Language Server Protocol (LSP) Support
 //!
 //! Provides IDE integration for AetherLang through the LSP protocol.
-//! This is a foundation module - actual LSP communication would use tower-lsp or similar.
+//! JSON-RPC messages are dispatched by `handle_message`; `serve` speaks the
+//! protocol's Content-Length framing over a pair of streams.
 #![allow(dead_code)]
 
+pub mod references;
+pub mod rename;
+
 use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::doc::{DocDatabase, DocGenerator, ItemDoc, DATABASE_FILE};
+use crate::frontend::ast::{Function, Item, Ownership, Param, Program, Stmt, Type};
+use crate::frontend::formatter::type_to_string;
+use crate::frontend::lexer::Lexer;
+use crate::frontend::module::ModuleLoader;
+use crate::frontend::parser::Parser;
+use crate::frontend::semantic::{self, SemanticAnalyzer};
+use crate::feedback::{self, ErrorReport, Severity};
+use crate::session::CompileSession;
+
+use references::{ReferenceMap, Symbol};
+use rename::RenameError;
 
 // ==================== LSP Message Types ====================
 
-/// Represents a position in a text document
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Represents a position in a text document, ordered by line, then character
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Position {
     pub line: u32,
     pub character: u32,
@@ -95,14 +116,118 @@ pub struct DiagnosticRelated {
     pub message: String,
 }
 
+/// Quick fix offered for a diagnostic
+#[derive(Debug, Clone)]
+pub struct CodeAction {
+    pub title: String,
+    pub diagnostic: Diagnostic,
+    /// Edit to the document the diagnostic is in
+    pub edit: Option<TextEdit>,
+    /// Run instead of an edit when the fix has no location
+    pub command: Option<Command>,
+    pub is_preferred: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit {
+    pub range: Range,
+    pub new_text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Command {
+    pub title: String,
+    pub command: String,
+    pub arguments: Vec<String>,
+}
+
+/// Edits to several documents, applied together
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspaceEdit {
+    /// Edits by document URI, the one the request came from first
+    pub changes: Vec<(String, Vec<TextEdit>)>,
+}
+
+/// Command of code actions that only display a suggestion
+pub const SHOW_SUGGESTION_COMMAND: &str = "aether.showSuggestion";
+
+/// Suggestions at least this confident are marked preferred
+const PREFERRED_CONFIDENCE: f64 = 0.8;
+
+/// Signature of the call the cursor is in
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignatureHelp {
+    pub signature: SignatureInformation,
+    /// Parameter the cursor is in, counting commas at the call's depth
+    pub active_parameter: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignatureInformation {
+    /// `fn name(a: T, b: ref U) -> R`
+    pub label: String,
+    pub parameters: Vec<ParameterInformation>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParameterInformation {
+    /// None for builtins, which are registered by type only
+    pub name: Option<String>,
+    pub ty: String,
+    /// None for builtins
+    pub ownership: Option<Ownership>,
+}
+
+impl ParameterInformation {
+    fn from_param(param: &Param) -> Self {
+        Self { name: Some(param.name.name.clone()), ty: type_to_string(&param.ty), ownership: Some(param.ownership) }
+    }
+
+    /// The parameter as written in a declaration
+    pub fn label(&self) -> String {
+        let ownership = match self.ownership {
+            Some(Ownership::Ref) => "ref ",
+            Some(Ownership::Mut) => "mut ",
+            _ => "",
+        };
+        match &self.name {
+            Some(name) => format!("{}: {}{}", name, ownership, self.ty),
+            None => self.ty.clone(),
+        }
+    }
+}
+
+impl SignatureInformation {
+    fn new(name: &str, parameters: Vec<ParameterInformation>, ret: Option<String>) -> Self {
+        let params: Vec<String> = parameters.iter().map(ParameterInformation::label).collect();
+        let ret = ret.map(|r| format!(" -> {}", r)).unwrap_or_default();
+        Self { label: format!("fn {}({}){}", name, params.join(", "), ret), parameters }
+    }
+
+    fn from_function(func: &Function, hide_self: bool) -> Self {
+        let params = func.params.iter()
+            .filter(|p| !(hide_self && p.name.name == "self"))
+            .map(ParameterInformation::from_param)
+            .collect();
+        Self::new(&func.name.name, params, func.ret_type.as_ref().map(type_to_string))
+    }
+}
+
 // ==================== Language Server ====================
 
 /// AetherLang Language Server
 pub struct LanguageServer {
     /// Open documents
     documents: HashMap<String, TextDocument>,
+    /// Reports from the last check of each open document, which keep the
+    /// suggestions their `Diagnostic`s drop
+    reports: HashMap<String, Vec<ErrorReport>>,
+    /// Notifications (published diagnostics) waiting to be sent
+    notifications: Vec<Value>,
     /// Keywords for completion
     keywords: Vec<&'static str>,
+    /// `doc/docs.json` of the workspace, written by `aethc doc`
+    doc_database: Option<DocDatabase>,
 }
 
 impl LanguageServer {
@@ -110,6 +235,8 @@ impl LanguageServer {
     pub fn new() -> Self {
         Self {
             documents: HashMap::new(),
+            reports: HashMap::new(),
+            notifications: Vec::new(),
             keywords: vec![
                 "fn", "let", "mut", "if", "else", "while", "for", "return",
                 "struct", "enum", "impl", "interface", "trait", "const",
@@ -117,6 +244,7 @@ impl LanguageServer {
                 "requires", "ensures", "invariant", "pure", "effect",
                 "own", "ref", "shared", "as", "in",
             ],
+            doc_database: None,
         }
     }
 
@@ -128,7 +256,8 @@ impl LanguageServer {
             version,
             content,
         };
-        self.documents.insert(uri, doc);
+        self.documents.insert(uri.clone(), doc);
+        self.check_document(&uri);
     }
 
     /// Update a document
@@ -137,17 +266,81 @@ impl LanguageServer {
             doc.content = content;
             doc.version = version;
         }
+        self.check_document(uri);
     }
 
     /// Close a document
     pub fn close_document(&mut self, uri: &str) {
         self.documents.remove(uri);
+        self.reports.remove(uri);
     }
 
-    /// Get diagnostics for a document (stub - integration with actual parser pending)
-    pub fn get_diagnostics(&self, _uri: &str) -> Vec<Diagnostic> {
-        // TODO: Integrate with actual lexer/parser/semantic analyzer
-        Vec::new()
+    /// Check a document and queue its diagnostics for publishing
+    fn check_document(&mut self, uri: &str) {
+        let Some(doc) = self.documents.get(uri) else { return };
+        let path = uri.strip_prefix("file://").unwrap_or(uri);
+        let mut session = CompileSession::from_source(path, &doc.content);
+        if let Some(dir) = Path::new(path).parent().filter(|d| !d.as_os_str().is_empty()) {
+            session = session.with_root(dir);
+        }
+        self.reports.insert(uri.to_string(), session.check().diagnostics);
+        let diagnostics: Vec<Value> = self.get_diagnostics(uri).iter().map(diagnostic_json).collect();
+        self.notifications.push(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        }));
+    }
+
+    /// Notifications queued since the last call
+    pub fn take_notifications(&mut self) -> Vec<Value> {
+        std::mem::take(&mut self.notifications)
+    }
+
+    /// Diagnostics from the last check of a document
+    pub fn get_diagnostics(&self, uri: &str) -> Vec<Diagnostic> {
+        let (Some(doc), Some(reports)) = (self.documents.get(uri), self.reports.get(uri)) else {
+            return Vec::new();
+        };
+        reports.iter().map(|report| to_diagnostic(uri, &doc.content, report)).collect()
+    }
+
+    /// Quick fixes for the diagnostics overlapping `range`, one per suggestion.
+    /// A suggestion with a replacement and a location becomes an edit; any
+    /// other suggestion becomes a command that displays its advice.
+    pub fn get_code_actions(&self, uri: &str, range: Range) -> Vec<CodeAction> {
+        let (Some(doc), Some(reports)) = (self.documents.get(uri), self.reports.get(uri)) else {
+            return Vec::new();
+        };
+        let mut actions = Vec::new();
+        for report in reports {
+            let diagnostic = to_diagnostic(uri, &doc.content, report);
+            if diagnostic.range.end < range.start || range.end < diagnostic.range.start {
+                continue;
+            }
+            for suggestion in &report.suggestions {
+                let edit = match (&suggestion.replacement, &suggestion.location) {
+                    (Some(replacement), Some(location)) => Some(TextEdit {
+                        range: location_range(&doc.content, location),
+                        new_text: replacement.clone(),
+                    }),
+                    _ => None,
+                };
+                let command = edit.is_none().then(|| Command {
+                    title: suggestion.message.clone(),
+                    command: SHOW_SUGGESTION_COMMAND.to_string(),
+                    arguments: vec![suggestion.message.clone()],
+                });
+                actions.push(CodeAction {
+                    title: suggestion.message.clone(),
+                    diagnostic: diagnostic.clone(),
+                    edit,
+                    command,
+                    is_preferred: suggestion.confidence >= PREFERRED_CONFIDENCE,
+                });
+            }
+        }
+        actions
     }
 
     /// Get completions at position
@@ -179,22 +372,88 @@ impl LanguageServer {
         completions
     }
 
-    /// Get hover info at position
-    pub fn get_hover(&self, _uri: &str, _position: Position) -> Option<HoverInfo> {
-        // TODO: Implement proper position-based lookup
-        None
+    /// Use a database written by `aethc doc` for items the open documents
+    /// and their modules do not define
+    pub fn set_doc_database(&mut self, database: DocDatabase) {
+        self.doc_database = Some(database);
     }
 
-    /// Go to definition
-    pub fn goto_definition(&self, _uri: &str, _position: Position) -> Option<Location> {
-        // TODO: Implement proper go-to-definition
-        None
+    /// Signature and doc comment of the item or method at `position`
+    pub fn get_hover(&self, uri: &str, position: Position) -> Option<HoverInfo> {
+        let doc = self.documents.get(uri)?;
+        let program = Parser::new(Lexer::new(&doc.content, 0)).parse_program().ok()?;
+        let map = ReferenceMap::build(&program, &doc.content);
+        let (symbol, occurrence) = map.symbol_at(offset_at(&doc.content, position))?;
+
+        let path = Path::new(uri.strip_prefix("file://").unwrap_or(uri));
+        let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("main");
+        let mut local = DocDatabase::default();
+        DocGenerator::new().with_private_items(true)
+            .document_items(name, &path.display().to_string(), &program.items, &mut local.modules);
+        let found = |database: &DocDatabase| -> Option<ItemDoc> {
+            match symbol {
+                Symbol::Item(name) => database.find(None, name),
+                Symbol::Qualified(module, name) => database.find(Some(module), name),
+                Symbol::Method(owner, name) => database.find_method(owner.as_deref(), name),
+            }.cloned()
+        };
+        let item = found(&local)
+            .or_else(|| {
+                let Symbol::Qualified(module, _) = symbol else { return None };
+                let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+                let mut loader = ModuleLoader::new();
+                loader.add_search_path(dir.to_path_buf());
+                let parsed = loader.load_module(module).ok()?;
+                let mut imported = DocDatabase::default();
+                DocGenerator::new().document_items(module, &parsed.path.display().to_string(), &parsed.items, &mut imported.modules);
+                found(&imported)
+            })
+            .or_else(|| self.doc_database.as_ref().and_then(found))?;
+        Some(HoverInfo { contents: item.hover_markdown(), range: Some(span_range(&doc.content, occurrence.span)) })
     }
-    
-    /// Find references
-    pub fn find_references(&self, _uri: &str, _position: Position) -> Vec<Location> {
-        // TODO: Implement find references
-        Vec::new()
+
+    /// Definition of the item or method at `position`, if in the same document
+    pub fn goto_definition(&self, uri: &str, position: Position) -> Option<Location> {
+        let (content, map, symbol) = self.symbol_at(uri, position)?;
+        let span = map.definition(&symbol)?;
+        Some(Location { uri: uri.to_string(), range: span_range(&content, span) })
+    }
+
+    /// Every spelling in the document of the item or method at `position`
+    pub fn find_references(&self, uri: &str, position: Position) -> Vec<Location> {
+        let Some((content, map, symbol)) = self.symbol_at(uri, position) else { return Vec::new() };
+        map.occurrences(&symbol).into_iter()
+            .map(|o| Location { uri: uri.to_string(), range: span_range(&content, o.span) })
+            .collect()
+    }
+
+    /// Reference map of a document and the symbol at `position`
+    fn symbol_at(&self, uri: &str, position: Position) -> Option<(String, ReferenceMap, Symbol)> {
+        let doc = self.documents.get(uri)?;
+        let program = Parser::new(Lexer::new(&doc.content, 0)).parse_program().ok()?;
+        let map = ReferenceMap::build(&program, &doc.content);
+        let (symbol, _) = map.symbol_at(offset_at(&doc.content, position))?;
+        let symbol = symbol.clone();
+        Some((doc.content.clone(), map, symbol))
+    }
+
+    /// Rename the item or method at `position` throughout the document and,
+    /// for an imported item, the module file defining it
+    pub fn rename(&self, uri: &str, position: Position, new_name: &str) -> Result<WorkspaceEdit, RenameError> {
+        let doc = self.documents.get(uri)
+            .ok_or_else(|| RenameError { message: format!("{} is not open", uri), location: None })?;
+        let path = Path::new(uri.strip_prefix("file://").unwrap_or(uri));
+        let renaming = rename::rename_at(path, &doc.content, offset_at(&doc.content, position), new_name)?;
+        let changes = renaming.files.iter().enumerate()
+            .map(|(i, file)| {
+                let file_uri = if i == 0 { uri.to_string() } else { format!("file://{}", file.path.display()) };
+                let edits = file.spans.iter()
+                    .map(|span| TextEdit { range: span_range(&file.source, *span), new_text: new_name.to_string() })
+                    .collect();
+                (file_uri, edits)
+            })
+            .collect();
+        Ok(WorkspaceEdit { changes })
     }
     
     /// Get document symbols
@@ -202,6 +461,526 @@ impl LanguageServer {
         // TODO: Implement document symbols from AST
         Vec::new()
     }
+
+    /// Signature of the innermost unclosed call before `position`
+    pub fn get_signature_help(&self, uri: &str, position: Position) -> Option<SignatureHelp> {
+        let doc = self.documents.get(uri)?;
+        let offset = offset_at(&doc.content, position);
+        let call = enclosing_call(&doc.content[..offset])?;
+        let analysis = Analysis::new(uri, &doc.content, offset)?;
+        let signature = analysis.resolve(&call.callee, offset)?;
+        Some(SignatureHelp { signature, active_parameter: call.commas })
+    }
+
+    /// Handle one JSON-RPC message, returning the response to a request.
+    /// Notifications update the open documents and get no response.
+    pub fn handle_message(&mut self, message: &Value) -> Option<Value> {
+        let method = message["method"].as_str()?;
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default().to_string();
+        let position = || Position {
+            line: params["position"]["line"].as_u64().unwrap_or(0) as u32,
+            character: params["position"]["character"].as_u64().unwrap_or(0) as u32,
+        };
+        let result = match method {
+            "initialize" => {
+                let root = params["rootUri"].as_str().map(|uri| uri.strip_prefix("file://").unwrap_or(uri));
+                if let Some(root) = root {
+                    if let Ok(database) = DocDatabase::load(&Path::new(root).join("doc").join(DATABASE_FILE)) {
+                        self.doc_database = Some(database);
+                    }
+                }
+                json!({
+                    "capabilities": {
+                        // Full document sync
+                        "textDocumentSync": 1,
+                        "signatureHelpProvider": { "triggerCharacters": ["(", ","] },
+                        "codeActionProvider": { "codeActionKinds": ["quickfix"] },
+                        "definitionProvider": true,
+                        "referencesProvider": true,
+                        "renameProvider": true,
+                        "hoverProvider": true,
+                    },
+                    "serverInfo": { "name": "aethc" },
+                })
+            }
+            "textDocument/didOpen" => {
+                let doc = &params["textDocument"];
+                let text = doc["text"].as_str().unwrap_or_default().to_string();
+                self.open_document(uri, text, doc["version"].as_i64().unwrap_or(0) as i32);
+                return None;
+            }
+            "textDocument/didChange" => {
+                let text = params["contentChanges"].as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+                if let Some(text) = text {
+                    let version = params["textDocument"]["version"].as_i64().unwrap_or(0) as i32;
+                    self.update_document(&uri, text.to_string(), version);
+                }
+                return None;
+            }
+            "textDocument/didClose" => {
+                self.close_document(&uri);
+                return None;
+            }
+            "textDocument/signatureHelp" => match self.get_signature_help(&uri, position()) {
+                Some(help) => signature_help_json(&help),
+                None => Value::Null,
+            },
+            "textDocument/codeAction" => {
+                let range = range_from_json(&params["range"]);
+                // Only the diagnostics the client asks about, matched by code and range
+                let wanted: Vec<(Option<String>, Range)> = params["context"]["diagnostics"].as_array()
+                    .map(|list| list.iter().map(|d| (d["code"].as_str().map(str::to_string), range_from_json(&d["range"]))).collect())
+                    .unwrap_or_default();
+                let actions: Vec<Value> = self.get_code_actions(&uri, range).iter()
+                    .filter(|action| wanted.is_empty() || wanted.iter().any(|(code, range)| {
+                        *code == action.diagnostic.code && *range == action.diagnostic.range
+                    }))
+                    .map(|action| code_action_json(&uri, action))
+                    .collect();
+                Value::Array(actions)
+            }
+            "textDocument/definition" => match self.goto_definition(&uri, position()) {
+                Some(location) => location_json(&location),
+                None => Value::Null,
+            },
+            "textDocument/hover" => match self.get_hover(&uri, position()) {
+                Some(hover) => hover_json(&hover),
+                None => Value::Null,
+            },
+            "textDocument/references" => {
+                Value::Array(self.find_references(&uri, position()).iter().map(location_json).collect())
+            }
+            "textDocument/rename" => {
+                let new_name = params["newName"].as_str().unwrap_or_default();
+                match self.rename(&uri, position(), new_name) {
+                    Ok(edit) => workspace_edit_json(&edit),
+                    Err(e) => {
+                        let id = message.get("id")?;
+                        return Some(json!({
+                            "jsonrpc": "2.0",
+                            "id": id,
+                            // RequestFailed
+                            "error": { "code": -32803, "message": e.to_string() },
+                        }));
+                    }
+                }
+            }
+            "shutdown" => Value::Null,
+            _ => {
+                let id = message.get("id")?;
+                return Some(json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": -32601, "message": format!("method not found: {}", method) },
+                }));
+            }
+        };
+        let id = message.get("id")?;
+        Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+    }
+
+    /// Serve requests from `input` until `exit` or end of input
+    pub fn serve(&mut self, input: &mut impl BufRead, output: &mut impl Write) -> io::Result<()> {
+        loop {
+            let mut length = None;
+            loop {
+                let mut header = String::new();
+                if input.read_line(&mut header)? == 0 {
+                    return Ok(());
+                }
+                let header = header.trim();
+                if header.is_empty() {
+                    break;
+                }
+                if let Some(value) = header.strip_prefix("Content-Length:") {
+                    length = value.trim().parse::<usize>().ok();
+                }
+            }
+            let Some(length) = length else { continue };
+            let mut body = vec![0; length];
+            input.read_exact(&mut body)?;
+            let Ok(message) = serde_json::from_slice::<Value>(&body) else { continue };
+            if message["method"] == "exit" {
+                return Ok(());
+            }
+            let response = self.handle_message(&message);
+            for message in self.take_notifications().into_iter().chain(response) {
+                let text = message.to_string();
+                write!(output, "Content-Length: {}\r\n\r\n{}", text.len(), text)?;
+            }
+            output.flush()?;
+        }
+    }
+}
+
+/// `report` against the text of the document it was found in
+fn to_diagnostic(uri: &str, content: &str, report: &ErrorReport) -> Diagnostic {
+    let range = report.location.as_ref()
+        .map(|location| location_range(content, location))
+        .unwrap_or(Range { start: Position { line: 0, character: 0 }, end: Position { line: 0, character: 0 } });
+    let severity = match report.severity {
+        Severity::Error => DiagnosticSeverity::Error,
+        Severity::Warning => DiagnosticSeverity::Warning,
+        Severity::Info => DiagnosticSeverity::Information,
+        Severity::Hint => DiagnosticSeverity::Hint,
+    };
+    let related = report.related.iter()
+        .filter_map(|info| Some(DiagnosticRelated {
+            location: Location { uri: uri.to_string(), range: location_range(content, info.location.as_ref()?) },
+            message: info.message.clone(),
+        }))
+        .collect();
+    Diagnostic { range, severity, code: Some(report.code.clone()), message: report.message.to_string(), related }
+}
+
+/// Range of a report location, which holds byte offsets in `line` and `end_line`
+fn location_range(content: &str, location: &feedback::Location) -> Range {
+    let start = location.line as usize;
+    let end = location.end_line.map_or(start, |end| end as usize);
+    Range { start: position_at(content, start), end: position_at(content, end.max(start)) }
+}
+
+fn range_from_json(value: &Value) -> Range {
+    let position = |p: &Value| Position {
+        line: p["line"].as_u64().unwrap_or(0) as u32,
+        character: p["character"].as_u64().unwrap_or(0) as u32,
+    };
+    Range { start: position(&value["start"]), end: position(&value["end"]) }
+}
+
+fn range_json(range: &Range) -> Value {
+    json!({
+        "start": { "line": range.start.line, "character": range.start.character },
+        "end": { "line": range.end.line, "character": range.end.character },
+    })
+}
+
+fn diagnostic_json(diagnostic: &Diagnostic) -> Value {
+    json!({
+        "range": range_json(&diagnostic.range),
+        "severity": diagnostic.severity as u8,
+        "code": diagnostic.code,
+        "source": "aethc",
+        "message": diagnostic.message,
+    })
+}
+
+fn code_action_json(uri: &str, action: &CodeAction) -> Value {
+    let mut value = json!({
+        "title": action.title,
+        "kind": "quickfix",
+        "diagnostics": [diagnostic_json(&action.diagnostic)],
+        "isPreferred": action.is_preferred,
+    });
+    if let Some(edit) = &action.edit {
+        value["edit"] = json!({ "changes": { uri: [{ "range": range_json(&edit.range), "newText": edit.new_text }] } });
+    }
+    if let Some(command) = &action.command {
+        value["command"] = json!({ "title": command.title, "command": command.command, "arguments": command.arguments });
+    }
+    value
+}
+
+/// `signatureHelp` result; parameter labels are offsets into the signature label
+fn signature_help_json(help: &SignatureHelp) -> Value {
+    let label = &help.signature.label;
+    let mut search_from = label.find('(').unwrap_or(0);
+    let parameters: Vec<Value> = help.signature.parameters.iter()
+        .map(|param| {
+            let text = param.label();
+            let start = label[search_from..].find(&text).map_or(search_from, |i| search_from + i);
+            search_from = start + text.len();
+            let utf16 = |end: usize| label[..end].encode_utf16().count();
+            json!({ "label": [utf16(start), utf16(search_from)] })
+        })
+        .collect();
+    json!({
+        "signatures": [{ "label": label, "parameters": parameters }],
+        "activeSignature": 0,
+        "activeParameter": help.active_parameter,
+    })
+}
+
+fn hover_json(hover: &HoverInfo) -> Value {
+    let mut value = json!({ "contents": { "kind": "markdown", "value": hover.contents } });
+    if let Some(range) = &hover.range {
+        value["range"] = range_json(range);
+    }
+    value
+}
+
+fn location_json(location: &Location) -> Value {
+    json!({ "uri": location.uri, "range": range_json(&location.range) })
+}
+
+fn workspace_edit_json(edit: &WorkspaceEdit) -> Value {
+    let changes: serde_json::Map<String, Value> = edit.changes.iter()
+        .map(|(uri, edits)| {
+            let edits = edits.iter().map(|e| json!({ "range": range_json(&e.range), "newText": e.new_text })).collect();
+            (uri.clone(), Value::Array(edits))
+        })
+        .collect();
+    json!({ "changes": changes })
+}
+
+/// LSP range of a byte span
+fn span_range(content: &str, span: crate::utils::Span) -> Range {
+    Range { start: position_at(content, span.start), end: position_at(content, span.end) }
+}
+
+/// Byte offset of an LSP position (whose character counts UTF-16 units)
+fn offset_at(content: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match content[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return content.len(),
+        }
+    }
+    let mut units = 0;
+    for (i, c) in content[line_start..].char_indices() {
+        if c == '\n' || units >= position.character as usize {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    content.len()
+}
+
+/// LSP position of a byte offset
+fn position_at(content: &str, offset: usize) -> Position {
+    let offset = offset.min(content.len());
+    let before = &content[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
+
+/// A call whose argument list is open at the cursor
+#[derive(Debug, PartialEq)]
+struct OpenCall {
+    /// Callee as written: `f`, `Type::f`, `module::f` or `receiver.f`
+    callee: String,
+    /// Commas between the `(` and the cursor at the call's own depth
+    commas: usize,
+}
+
+/// Innermost call left open by `text`, skipping strings, chars and comments
+fn enclosing_call(text: &str) -> Option<OpenCall> {
+    // Open delimiters: (char, byte offset, commas directly inside)
+    let mut open: Vec<(char, usize, usize)> = Vec::new();
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+            }
+            b'\'' => {
+                // A char literal, or the quote of a lifetime
+                if bytes.get(i + 1) == Some(&b'\\') {
+                    i += 2;
+                    while i < bytes.len() && bytes[i] != b'\'' {
+                        i += 1;
+                    }
+                } else if let Some(c) = text[i + 1..].chars().next() {
+                    if text[i + 1 + c.len_utf8()..].starts_with('\'') {
+                        i += c.len_utf8() + 1;
+                    }
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i < bytes.len() && !(bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/')) {
+                    i += 1;
+                }
+                i += 1;
+            }
+            c @ (b'(' | b'[' | b'{') => open.push((c as char, i, 0)),
+            b')' | b']' | b'}' => {
+                open.pop();
+            }
+            b',' => {
+                if let Some(top) = open.last_mut() {
+                    top.2 += 1;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    // Arguments may be arrays or parenthesized; a block ends the search
+    for &(delim, at, commas) in open.iter().rev() {
+        match delim {
+            '{' => return None,
+            '(' => {
+                if let Some(callee) = callee_before(&text[..at]) {
+                    return Some(OpenCall { callee, commas });
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The path or method name right before a `(`, unless it is a keyword or
+/// the name of a function being declared
+fn callee_before(text: &str) -> Option<String> {
+    let text = text.trim_end();
+    let start = text.rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':' || c == '.'))
+        .map_or(0, |i| i + text[i..].chars().next().map_or(1, char::len_utf8));
+    let callee = &text[start..];
+    let name = callee.rsplit(['.', ':']).next().unwrap_or_default();
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    const KEYWORDS: &[&str] = &["if", "while", "for", "match", "return", "in", "fn", "let", "as", "else"];
+    if KEYWORDS.contains(&callee) {
+        return None;
+    }
+    if text[..start].trim_end().ends_with("fn") {
+        return None;
+    }
+    Some(callee.to_string())
+}
+
+/// A document parsed and analyzed for name resolution
+struct Analysis {
+    program: Program,
+    analyzer: SemanticAnalyzer,
+    modules: ModuleLoader,
+}
+
+impl Analysis {
+    /// Analyze `content`; if it does not parse, as while a call is being
+    /// typed, analyze it again with the line holding `offset` blanked out
+    fn new(uri: &str, content: &str, offset: usize) -> Option<Self> {
+        let line_start = content[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = content[offset..].find('\n').map_or(content.len(), |i| offset + i);
+        let blanked = format!("{}{}{}", &content[..line_start], " ".repeat(line_end - line_start), &content[line_end..]);
+        let program = [content, blanked.as_str()].into_iter()
+            .find_map(|text| Parser::new(Lexer::new(text, 0)).parse_program().ok())?;
+
+        let mut modules = ModuleLoader::new();
+        let path = uri.strip_prefix("file://").unwrap_or(uri);
+        if let Some(dir) = Path::new(path).parent().filter(|d| !d.as_os_str().is_empty()) {
+            modules.add_search_path(dir.to_path_buf());
+        }
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.set_module_loader(modules.clone());
+        // Errors elsewhere in the document do not stop resolution
+        let _ = analyzer.analyze(&program);
+        Some(Self { program, analyzer, modules })
+    }
+
+    /// Items of the document followed by those of every module it imported
+    fn items(&self) -> Vec<Item> {
+        let mut items = self.program.items.clone();
+        for name in self.analyzer.imported_modules.keys() {
+            if let Some(module) = self.modules.get_cached_module(name) {
+                items.extend(module.items.iter().cloned());
+            }
+        }
+        items
+    }
+
+    fn resolve(&self, callee: &str, offset: usize) -> Option<SignatureInformation> {
+        if let Some((receiver, method)) = callee.rsplit_once('.') {
+            let target = self.receiver_type(receiver, offset);
+            let func = self.method(target.as_deref(), method)?;
+            return Some(SignatureInformation::from_function(&func, true));
+        }
+        if let Some((prefix, name)) = callee.rsplit_once("::") {
+            if let Some(func) = self.method(Some(prefix), name) {
+                return Some(SignatureInformation::from_function(&func, false));
+            }
+            let module = self.modules.get_cached_module(prefix)?;
+            return match module.find_public_item(name)? {
+                Item::Function(func) => Some(SignatureInformation::from_function(func, false)),
+                _ => None,
+            };
+        }
+        let local = self.items().into_iter().find_map(|item| match item {
+            Item::Function(func) if func.name.name == callee => Some(func),
+            _ => None,
+        });
+        if let Some(func) = local {
+            return Some(SignatureInformation::from_function(&func, false));
+        }
+        // Builtins are registered by type only
+        match &self.analyzer.symbols.lookup(callee)?.kind {
+            semantic::SymbolKind::Function { params, ret, .. } => {
+                let params = params.iter()
+                    .map(|ty| ParameterInformation { name: None, ty: ty.to_string(), ownership: None })
+                    .collect();
+                Some(SignatureInformation::new(callee, params, Some(ret.to_string())))
+            }
+            _ => None,
+        }
+    }
+
+    /// Method `name` of the impl blocks for `target`, or of any impl block
+    /// when the receiver's type is not known
+    fn method(&self, target: Option<&str>, name: &str) -> Option<Function> {
+        self.items().into_iter().find_map(|item| match item {
+            Item::Impl(imp) if target.is_none_or(|t| imp.target.name == t) => {
+                imp.methods.into_iter().find(|m| m.name.name == name)
+            }
+            _ => None,
+        })
+    }
+
+    /// Declared type of a receiver: `self`, or a parameter or `let` of the
+    /// function containing `offset`
+    fn receiver_type(&self, receiver: &str, offset: usize) -> Option<String> {
+        let contains = |func: &Function| func.span.start <= offset && offset <= func.span.end;
+        for item in &self.program.items {
+            let (func, target) = match item {
+                Item::Function(func) if contains(func) => (func, None),
+                Item::Impl(imp) => match imp.methods.iter().find(|m| contains(m)) {
+                    Some(func) => (func, Some(imp.target.name.clone())),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            if receiver == "self" {
+                return target;
+            }
+            let declared = func.params.iter()
+                .filter(|p| p.name.name == receiver)
+                .map(|p| &p.ty)
+                .chain(func.body.stmts.iter().filter_map(|stmt| match stmt {
+                    Stmt::Let { name, ty: Some(ty), .. } if name.name == receiver => Some(ty),
+                    _ => None,
+                }))
+                .last()?;
+            return type_name(declared);
+        }
+        None
+    }
+}
+
+/// Name of the type a value of `ty` has methods from
+fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Named(name, _) | Type::Generic(name, _, _) | Type::GenericWithArgs { name, .. } => Some(name.clone()),
+        Type::Pointer(inner, _) | Type::Ref { inner, .. } => type_name(inner),
+        _ => None,
+    }
 }
 
 impl Default for LanguageServer {
@@ -231,3 +1010,271 @@ pub enum SymbolKind {
     Field,
     Module,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "\
+struct Point { x: i64, y: i64 }
+
+impl Point {
+    fn new(x: i64, y: i64) -> Point { return Point { x: x, y: y }; }
+    fn dist(self: ref Point, other: ref Point, scale: i64) -> i64 { return (self.x - other.x) * scale; }
+}
+
+fn inner(a: i64, b: mut Point) -> i64 { return a; }
+fn outer(v: i64) -> i64 { return v; }
+
+fn main() {
+    let p: Point = Point::new(1, 2);
+";
+
+    /// Signature help with the cursor at the end of `SOURCE` followed by `typed`
+    fn signature_at(typed: &str) -> Option<SignatureHelp> {
+        let mut server = LanguageServer::new();
+        let content = format!("{}{}\n}}\n", SOURCE, typed);
+        let line = SOURCE.lines().count() as u32;
+        server.open_document("file:///tmp/sig.aeth".to_string(), content, 1);
+        server.get_signature_help("file:///tmp/sig.aeth", Position { line, character: typed.len() as u32 })
+    }
+
+    #[test]
+    fn test_signature_help_user_function() {
+        let help = signature_at("    outer(inner(p.x, ").unwrap();
+        assert_eq!(help.signature.label, "fn inner(a: i64, b: mut Point) -> i64");
+        assert_eq!(help.active_parameter, 1);
+        let b = &help.signature.parameters[1];
+        assert_eq!((b.name.as_deref(), b.ty.as_str(), b.ownership), (Some("b"), "Point", Some(Ownership::Mut)));
+
+        // Closing the inner call goes back to the outer one
+        let help = signature_at("    outer(inner(1, p) ").unwrap();
+        assert_eq!((help.signature.label.as_str(), help.active_parameter), ("fn outer(v: i64) -> i64", 0));
+        // Commas in nested calls, arrays and strings do not count
+        let help = signature_at("    inner(outer(1), \"a, (b\", ").unwrap();
+        assert_eq!(help.active_parameter, 2);
+    }
+
+    #[test]
+    fn test_signature_help_methods_and_builtins() {
+        let help = signature_at("    let d: i64 = p.dist(p, ").unwrap();
+        assert_eq!(help.signature.label, "fn dist(other: ref Point, scale: i64) -> i64");
+        assert_eq!(help.active_parameter, 1);
+
+        let help = signature_at("    let q: Point = Point::new(").unwrap();
+        assert_eq!(help.signature.label, "fn new(x: i64, y: i64) -> Point");
+
+        let help = signature_at("    let v: f32x4 = f32x4_add(a, ").unwrap();
+        assert_eq!(help.signature.label, "fn f32x4_add(f32x4, f32x4) -> f32x4");
+        assert_eq!(help.signature.parameters[0].name, None);
+        assert_eq!(help.active_parameter, 1);
+    }
+
+    #[test]
+    fn test_signature_help_outside_calls() {
+        assert_eq!(signature_at("    let x: i64 = 1"), None);
+        assert_eq!(signature_at("    if (p.x > 0"), None);
+        assert_eq!(signature_at("    outer(1); let s: str = \"f("), None);
+        assert_eq!(signature_at("    undefined_function("), None);
+    }
+
+    #[test]
+    fn test_signature_help_request() {
+        let mut server = LanguageServer::new();
+        let text = format!("{}    inner(1, \n}}\n", SOURCE);
+        let line = SOURCE.lines().count();
+        let open = json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": "file:///tmp/sig.aeth", "languageId": "aether", "version": 1, "text": text } },
+        });
+        assert_eq!(server.handle_message(&open), None);
+
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "method": "textDocument/signatureHelp",
+            "params": { "textDocument": { "uri": "file:///tmp/sig.aeth" }, "position": { "line": line, "character": 13 } },
+        });
+        let response = server.handle_message(&request).unwrap();
+        assert_eq!(response["id"], 7);
+        let result = &response["result"];
+        assert_eq!(result["signatures"][0]["label"], "fn inner(a: i64, b: mut Point) -> i64");
+        assert_eq!(result["signatures"][0]["parameters"][1]["label"], json!([17, 29]));
+        assert_eq!(result["activeParameter"], 1);
+
+        let unknown = json!({ "jsonrpc": "2.0", "id": 8, "method": "textDocument/unknown", "params": {} });
+        assert_eq!(server.handle_message(&unknown).unwrap()["error"]["code"], -32601);
+    }
+
+    const TYPO: &str = "fn main() -> i64 {\n    let result: i64 = 1;\n    return resut;\n}\n";
+
+    #[test]
+    fn test_code_actions() {
+        let uri = "file:///tmp/typo.aeth";
+        let mut server = LanguageServer::new();
+        server.open_document(uri.to_string(), TYPO.to_string(), 1);
+        let diagnostics = server.get_diagnostics(uri);
+        let typo = diagnostics.iter().find(|d| d.code.as_deref() == Some("E0002")).unwrap();
+        let at = |line, character| Position { line, character };
+        assert_eq!(typo.range, Range { start: at(2, 11), end: at(2, 16) });
+
+        let cursor = Range { start: at(2, 12), end: at(2, 12) };
+        let actions = server.get_code_actions(uri, cursor);
+        let rename = actions.iter().find(|a| a.title == "Did you mean 'result'?").unwrap();
+        let edit = rename.edit.as_ref().unwrap();
+        assert_eq!((edit.range, edit.new_text.as_str()), (typo.range, "result"));
+        assert!(!rename.is_preferred);
+        // Advice without a location only gets displayed
+        let define = actions.iter().find(|a| a.title.starts_with("Define 'resut'")).unwrap();
+        assert!(define.edit.is_none() && define.is_preferred);
+        assert_eq!(define.command.as_ref().unwrap().command, SHOW_SUGGESTION_COMMAND);
+        assert!(server.get_code_actions(uri, Range { start: at(0, 0), end: at(0, 2) }).is_empty());
+
+        // Applying the edit fixes the error
+        let fixed = TYPO.replace("resut", &edit.new_text);
+        server.update_document(uri, fixed, 2);
+        assert!(server.get_diagnostics(uri).iter().all(|d| d.severity != DiagnosticSeverity::Error));
+    }
+
+    #[test]
+    fn test_code_action_request() {
+        let uri = "file:///tmp/typo.aeth";
+        let mut server = LanguageServer::new();
+        let open = json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": uri, "languageId": "aether", "version": 1, "text": TYPO } },
+        });
+        assert_eq!(server.handle_message(&open), None);
+        let published = server.take_notifications();
+        assert_eq!(published[0]["method"], "textDocument/publishDiagnostics");
+        let diagnostic = published[0]["params"]["diagnostics"].as_array().unwrap().iter()
+            .find(|d| d["code"] == "E0002").unwrap().clone();
+
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "textDocument/codeAction",
+            "params": {
+                "textDocument": { "uri": uri },
+                "range": diagnostic["range"],
+                "context": { "diagnostics": [diagnostic] },
+            },
+        });
+        let response = server.handle_message(&request).unwrap();
+        let actions = response["result"].as_array().unwrap();
+        let rename = actions.iter().find(|a| a["title"] == "Did you mean 'result'?").unwrap();
+        assert_eq!(rename["kind"], "quickfix");
+        let edits = &rename["edit"]["changes"][uri];
+        assert_eq!(edits[0]["newText"], "result");
+        assert_eq!(edits[0]["range"], diagnostic["range"]);
+        assert!(actions.iter().any(|a| a["command"]["command"] == SHOW_SUGGESTION_COMMAND && a.get("edit").is_none()));
+    }
+
+    const SHAPES: &str = "struct Point { x: i64 }\nimpl Point {\n    fn new(x: i64) -> Point { return Point { x: x }; }\n    fn get(self: ref Point) -> i64 { return self.x; }\n}\nfn twice(p: Point) -> Point { let q: Point = Point::new(p.get() * 2); return q; }\nfn main() { let Point: i64 = 1; }\n";
+
+    /// Position of the `nth` occurrence of `needle` in `text`
+    fn find(text: &str, needle: &str, nth: usize) -> Position {
+        let offset = text.match_indices(needle).nth(nth).unwrap().0;
+        position_at(text, offset)
+    }
+
+    #[test]
+    fn test_references() {
+        let uri = "file:///tmp/shapes.aeth";
+        let mut server = LanguageServer::new();
+        server.open_document(uri.to_string(), SHAPES.to_string(), 1);
+
+        // Type positions, the impl target, literals and paths; not the local in main
+        let refs = server.find_references(uri, find(SHAPES, "Point", 4));
+        assert_eq!(refs.len(), 9);
+        let definition = server.goto_definition(uri, find(SHAPES, "Point", 7)).unwrap();
+        assert_eq!(definition.range.start, Position { line: 0, character: 7 });
+        assert!(server.goto_definition(uri, find(SHAPES, "Point", 9)).is_none());
+
+        // A method call on a declared parameter resolves to the impl
+        let get = server.goto_definition(uri, find(SHAPES, "get", 1)).unwrap();
+        assert_eq!(get.range.start, find(SHAPES, "get", 0));
+    }
+
+    #[test]
+    fn test_rename_request() {
+        let uri = "file:///tmp/shapes.aeth";
+        let mut server = LanguageServer::new();
+        server.open_document(uri.to_string(), SHAPES.to_string(), 1);
+        let rename = |server: &mut LanguageServer, position: Position, new_name: &str| {
+            server.handle_message(&json!({
+                "jsonrpc": "2.0",
+                "id": 7,
+                "method": "textDocument/rename",
+                "params": {
+                    "textDocument": { "uri": uri },
+                    "position": { "line": position.line, "character": position.character },
+                    "newName": new_name,
+                },
+            })).unwrap()
+        };
+
+        let response = rename(&mut server, find(SHAPES, "Point", 0), "Vec2");
+        let edits = response["result"]["changes"][uri].as_array().unwrap();
+        assert_eq!(edits.len(), 9, "{}", response);
+        assert!(edits.iter().all(|e| e["newText"] == "Vec2"));
+
+        // A local of a function using the struct would capture the new name
+        let response = rename(&mut server, find(SHAPES, "Point", 0), "q");
+        assert_eq!(response["error"]["code"], -32803);
+        assert!(response["error"]["message"].as_str().unwrap().contains("local named `q`"));
+        let response = rename(&mut server, find(SHAPES, "get", 0), "new");
+        assert!(response["error"]["message"].as_str().unwrap().contains("already has a method `new`"));
+        let response = rename(&mut server, find(SHAPES, "get", 0), "while");
+        assert!(response["error"]["message"].as_str().unwrap().contains("keyword"));
+    }
+
+    #[test]
+    fn test_hover() {
+        let uri = "file:///tmp/hover.aeth";
+        let source = "/// A point\nstruct Point { x: i64 }\nimpl Point {\n    /// Read `x`\n    fn get(self: ref Point) -> i64 { return self.x; }\n}\nfn main() { let p: Point = Point { x: 1 }; let v: i64 = p.get(); }\n";
+        let mut server = LanguageServer::new();
+        server.open_document(uri.to_string(), source.to_string(), 1);
+
+        let hover = server.get_hover(uri, find(source, "Point", 3)).unwrap();
+        assert_eq!(hover.contents, "```aether\nstruct Point\n```\n\nA point");
+        let get = find(source, "get", 1);
+        let response = server.handle_message(&json!({
+            "jsonrpc": "2.0",
+            "id": 4,
+            "method": "textDocument/hover",
+            "params": {
+                "textDocument": { "uri": uri },
+                "position": { "line": get.line, "character": get.character },
+            },
+        })).unwrap();
+        let contents = response["result"]["contents"]["value"].as_str().unwrap();
+        assert!(contents.contains("fn get(self: ref Point) -> i64") && contents.ends_with("Read `x`"), "{}", response);
+        assert_eq!(response["result"]["range"]["start"]["character"], get.character);
+
+        // Items only the database knows about
+        let mut database = DocDatabase::default();
+        let program = Parser::new(Lexer::new("/// Library\npub fn helper() {}", 0)).parse_program().unwrap();
+        DocGenerator::new().document_items("lib", "lib.aeth", &program.items, &mut database.modules);
+        server.set_doc_database(database);
+        server.open_document(uri.to_string(), "fn main() { helper(); }".to_string(), 2);
+        let hover = server.get_hover(uri, Position { line: 0, character: 13 }).unwrap();
+        assert!(hover.contents.ends_with("Library"));
+    }
+
+    #[test]
+    fn test_serve() {
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#;
+        let exit = r#"{"jsonrpc":"2.0","method":"exit"}"#;
+        let input = format!("Content-Length: {}\r\n\r\n{}Content-Length: {}\r\n\r\n{}", body.len(), body, exit.len(), exit);
+        let mut output = Vec::new();
+        LanguageServer::new().serve(&mut input.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let (header, body) = output.split_once("\r\n\r\n").unwrap();
+        assert_eq!(header, format!("Content-Length: {}", body.len()));
+        let response: Value = serde_json::from_str(body).unwrap();
+        assert!(response["result"]["capabilities"]["signatureHelpProvider"].is_object());
+    }
+}
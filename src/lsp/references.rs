@@ -0,0 +1,550 @@
+//! Reference map: where the items and methods of one file are defined and used
+//!
+//! Names are resolved syntactically. Locals shadow items, `module::name`
+//! paths go through the modules the file imports, and a method call is tied
+//! to a type when its receiver is `self` or a local declared with one.
+
+use std::collections::HashSet;
+
+use crate::frontend::ast::*;
+use crate::utils::Span;
+
+use super::type_name;
+
+/// What a name refers to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Symbol {
+    /// A top-level item of the file, or one it imports by name
+    Item(String),
+    /// `name` of an imported module, written `module::name`
+    Qualified(String, String),
+    /// Method `name` of a type; `None` when the receiver's type is unknown
+    Method(Option<String>, String),
+}
+
+/// One spelling of a symbol's name in the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Occurrence {
+    pub span: Span,
+    pub is_definition: bool,
+}
+
+/// A local variable, parameter or pattern binding
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub name: String,
+    pub span: Span,
+    /// Function the binding is declared in
+    pub function: Span,
+}
+
+/// An item brought in by `use module::name` or `use module::*`
+#[derive(Debug, Clone)]
+pub struct Import {
+    pub module: String,
+    /// `None` for a glob
+    pub name: Option<String>,
+    /// Name it is known by here (`use m::a as b` binds `b`), and where
+    pub binds: Option<(String, Span)>,
+}
+
+/// Every symbol occurrence and local binding of a program
+#[derive(Debug, Default)]
+pub struct ReferenceMap {
+    occurrences: Vec<(Symbol, Occurrence)>,
+    pub bindings: Vec<Binding>,
+    pub imports: Vec<Import>,
+    /// Modules the file imports or declares
+    pub modules: HashSet<String>,
+}
+
+impl ReferenceMap {
+    /// Map the references of `program`, whose text is `source`
+    pub fn build(program: &Program, source: &str) -> Self {
+        let mut walker = Walker {
+            map: ReferenceMap::default(),
+            source,
+            scopes: Vec::new(),
+            current_impl: None,
+            current_fn: Span::default(),
+        };
+        // Module names must be known before the paths that use them
+        walker.collect_modules(&program.items);
+        walker.items(&program.items);
+        walker.map
+    }
+
+    /// Occurrences of `symbol`, in source order
+    pub fn occurrences(&self, symbol: &Symbol) -> Vec<Occurrence> {
+        let mut found: Vec<Occurrence> = self.occurrences.iter()
+            .filter(|(s, _)| s == symbol)
+            .map(|(_, o)| *o)
+            .collect();
+        found.sort_by_key(|o| o.span.start);
+        found
+    }
+
+    /// Where `symbol` is defined, if in this file
+    pub fn definition(&self, symbol: &Symbol) -> Option<Span> {
+        self.occurrences(symbol).into_iter().find(|o| o.is_definition).map(|o| o.span)
+    }
+
+    /// The symbol whose name covers byte `offset`
+    pub fn symbol_at(&self, offset: usize) -> Option<(&Symbol, Occurrence)> {
+        self.occurrences.iter()
+            .find(|(_, o)| o.span.start <= offset && offset <= o.span.end)
+            .map(|(s, o)| (s, *o))
+    }
+
+    /// Types with a method `name` defined in this file
+    pub fn method_owners(&self, name: &str) -> Vec<String> {
+        let mut owners: Vec<String> = self.occurrences.iter()
+            .filter_map(|(s, o)| match s {
+                Symbol::Method(Some(owner), m) if m == name && o.is_definition => Some(owner.clone()),
+                _ => None,
+            })
+            .collect();
+        owners.dedup();
+        owners
+    }
+}
+
+struct Walker<'a> {
+    map: ReferenceMap,
+    source: &'a str,
+    /// Locals in scope, innermost last, with the type they were declared as
+    scopes: Vec<Vec<(String, Option<String>)>>,
+    current_impl: Option<String>,
+    current_fn: Span,
+}
+
+impl Walker<'_> {
+    fn collect_modules(&mut self, items: &[Item]) {
+        for item in items {
+            match item {
+                Item::Use(use_decl) => {
+                    if let Some(module) = use_decl.path.first() {
+                        self.map.modules.insert(module.name.clone());
+                    }
+                }
+                Item::Module(m) => {
+                    self.map.modules.insert(m.name.name.clone());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Record `symbol` at `span` if the source there really spells `name`
+    fn record(&mut self, symbol: Symbol, name: &str, start: usize, is_definition: bool) {
+        let end = start + name.len();
+        if self.source.get(start..end) == Some(name) {
+            let span = Span::new(start, end, 0);
+            self.map.occurrences.push((symbol, Occurrence { span, is_definition }));
+        }
+    }
+
+    fn define(&mut self, ident: &Ident) {
+        self.record(Symbol::Item(ident.name.clone()), &ident.name, ident.span.start, true);
+    }
+
+    fn use_item(&mut self, ident: &Ident) {
+        self.record(Symbol::Item(ident.name.clone()), &ident.name, ident.span.start, false);
+    }
+
+    fn items(&mut self, items: &[Item]) {
+        for item in items {
+            self.item(item);
+        }
+    }
+
+    fn item(&mut self, item: &Item) {
+        match item {
+            Item::Function(func) => {
+                self.define(&func.name);
+                self.function(func);
+            }
+            Item::Struct(s) => {
+                self.define(&s.name);
+                for field in &s.fields {
+                    self.ty(&field.ty);
+                }
+            }
+            Item::Union(u) => {
+                self.define(&u.name);
+                for field in &u.fields {
+                    self.ty(&field.ty);
+                }
+            }
+            Item::Enum(e) => {
+                self.define(&e.name);
+                for variant in &e.variants {
+                    variant.fields.iter().for_each(|ty| self.ty(ty));
+                }
+            }
+            Item::Impl(imp) => {
+                self.use_item(&imp.target);
+                if let Some(interface) = &imp.interface {
+                    self.use_item(interface);
+                }
+                for binding in &imp.associated_types {
+                    self.ty(&binding.ty);
+                }
+                let outer = self.current_impl.replace(imp.target.name.clone());
+                for method in &imp.methods {
+                    self.method_definition(&imp.target.name, method);
+                }
+                self.current_impl = outer;
+            }
+            Item::Interface(iface) | Item::Trait(iface) => {
+                self.define(&iface.name);
+                let outer = self.current_impl.replace(iface.name.name.clone());
+                for sig in &iface.methods {
+                    self.record(Symbol::Method(Some(iface.name.name.clone()), sig.name.name.clone()), &sig.name.name, sig.name.span.start, true);
+                    self.signature(&sig.params, sig.ret_type.as_ref());
+                }
+                for method in &iface.default_methods {
+                    self.method_definition(&iface.name.name, method);
+                }
+                self.current_impl = outer;
+            }
+            Item::Const(c) => {
+                self.define(&c.name);
+                c.ty.iter().for_each(|ty| self.ty(ty));
+                self.expr(&c.value);
+            }
+            Item::Static(s) => {
+                self.define(&s.name);
+                self.ty(&s.ty);
+                s.value.iter().for_each(|value| self.expr(value));
+            }
+            Item::TypeAlias(alias) => {
+                self.define(&alias.name);
+                self.ty(&alias.ty);
+            }
+            Item::Extern(block) => {
+                for foreign in &block.items {
+                    match foreign {
+                        ForeignItem::Fn { name, params, ret_type, .. } => {
+                            self.define(name);
+                            self.signature(params, ret_type.as_ref());
+                        }
+                        ForeignItem::Static { name, ty, .. } => {
+                            self.define(name);
+                            self.ty(ty);
+                        }
+                    }
+                }
+            }
+            Item::Use(use_decl) => self.use_decl(use_decl),
+            Item::Module(_) | Item::Macro(_) => {}
+        }
+    }
+
+    fn use_decl(&mut self, use_decl: &UseDecl) {
+        let Some(module) = use_decl.path.first() else { return };
+        let module = module.name.clone();
+        match &use_decl.kind {
+            UseKind::Simple | UseKind::Alias(_) => {
+                let Some(name) = use_decl.path.get(1) else { return };
+                self.record(Symbol::Qualified(module.clone(), name.name.clone()), &name.name, name.span.start, false);
+                let binds = match &use_decl.kind {
+                    UseKind::Alias(alias) => (alias.name.clone(), alias.span),
+                    _ => (name.name.clone(), name.span),
+                };
+                self.map.imports.push(Import { module, name: Some(name.name.clone()), binds: Some(binds) });
+            }
+            UseKind::Group(decls) => {
+                for decl in decls {
+                    let Some(name) = decl.path.first() else { continue };
+                    self.record(Symbol::Qualified(module.clone(), name.name.clone()), &name.name, name.span.start, false);
+                    let binds = match &decl.kind {
+                        UseKind::Alias(alias) => (alias.name.clone(), alias.span),
+                        _ => (name.name.clone(), name.span),
+                    };
+                    self.map.imports.push(Import { module: module.clone(), name: Some(name.name.clone()), binds: Some(binds) });
+                }
+            }
+            UseKind::Glob => self.map.imports.push(Import { module, name: None, binds: None }),
+        }
+    }
+
+    fn method_definition(&mut self, owner: &str, method: &Function) {
+        self.record(Symbol::Method(Some(owner.to_string()), method.name.name.clone()), &method.name.name, method.name.span.start, true);
+        self.function(method);
+    }
+
+    fn signature(&mut self, params: &[Param], ret: Option<&Type>) {
+        for param in params {
+            self.ty(&param.ty);
+        }
+        ret.iter().for_each(|ty| self.ty(ty));
+    }
+
+    fn function(&mut self, func: &Function) {
+        let outer = std::mem::replace(&mut self.current_fn, func.span);
+        self.signature(&func.params, func.ret_type.as_ref());
+        self.scopes.push(Vec::new());
+        for param in &func.params {
+            self.bind(&param.name, Some(&param.ty));
+            if let Some(pattern) = &param.pattern {
+                self.pattern(pattern);
+            }
+        }
+        for contract in &func.contracts {
+            self.expr(&contract.condition);
+        }
+        self.block(&func.body);
+        self.scopes.pop();
+        self.current_fn = outer;
+    }
+
+    fn bind(&mut self, name: &Ident, ty: Option<&Type>) {
+        let declared = ty.and_then(type_name);
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push((name.name.clone(), declared));
+        }
+        self.map.bindings.push(Binding { name: name.name.clone(), span: name.span, function: self.current_fn });
+    }
+
+    fn local(&self, name: &str) -> Option<&Option<String>> {
+        self.scopes.iter().rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|(local, _)| local == name)
+            .map(|(_, ty)| ty)
+    }
+
+    fn ty(&mut self, ty: &Type) {
+        match ty {
+            Type::Named(name, span) | Type::Generic(name, _, span) | Type::GenericWithArgs { name, span, .. } => {
+                match name.split_once("::") {
+                    Some((module, member)) if self.map.modules.contains(module) => {
+                        let start = span.start + module.len() + 2;
+                        self.record(Symbol::Qualified(module.to_string(), member.to_string()), member, start, false);
+                    }
+                    // `Self::Item`
+                    Some(_) => {}
+                    None => self.record(Symbol::Item(name.clone()), name, span.start, false),
+                }
+                match ty {
+                    Type::Generic(_, args, _) => args.iter().for_each(|arg| self.ty(arg)),
+                    Type::GenericWithArgs { args, .. } => {
+                        for arg in args {
+                            match arg {
+                                GenericArg::Type(ty) => self.ty(ty),
+                                GenericArg::Const(expr) => self.expr(expr),
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Type::Pointer(inner, _) | Type::Ref { inner, .. } | Type::Array { elem: inner, .. }
+            | Type::Slice(inner, _) | Type::Owned { inner, .. } | Type::Volatile(inner, _) => self.ty(inner),
+            Type::Tuple(elements, _) => elements.iter().for_each(|ty| self.ty(ty)),
+            Type::Function { params, ret, .. } => {
+                params.iter().for_each(|ty| self.ty(ty));
+                self.ty(ret);
+            }
+            Type::Never(_) | Type::Unit(_) | Type::Infer(_) => {}
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.scopes.push(Vec::new());
+        self.stmts(&block.stmts);
+        self.scopes.pop();
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            match stmt {
+                Stmt::Let { name, ty, value, pattern, .. } => {
+                    ty.iter().for_each(|ty| self.ty(ty));
+                    value.iter().for_each(|value| self.expr(value));
+                    self.bind(name, ty.as_ref());
+                    if let Some(pattern) = pattern {
+                        self.pattern(pattern);
+                    }
+                }
+                Stmt::Expr(expr) => self.expr(expr),
+                Stmt::Return { value, .. } | Stmt::Break { break_value: value, .. } => value.iter().for_each(|value| self.expr(value)),
+                Stmt::Continue { .. } | Stmt::Empty { .. } => {}
+            }
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Binding { name, .. } => self.bind(name, None),
+            Pattern::Struct { name, fields, .. } => {
+                self.use_item(name);
+                fields.iter().for_each(|(_, p)| self.pattern(p));
+            }
+            Pattern::Variant { enum_name, variant, fields, .. } => {
+                if let Some(enum_name) = enum_name {
+                    self.use_item(enum_name);
+                    self.record(Symbol::Method(Some(enum_name.name.clone()), variant.name.clone()), &variant.name, variant.span.start, false);
+                }
+                fields.iter().for_each(|p| self.pattern(p));
+            }
+            Pattern::Tuple { elements, .. } => elements.iter().for_each(|p| self.pattern(p)),
+            Pattern::Or { alternatives, .. } => alternatives.iter().for_each(|p| self.pattern(p)),
+            Pattern::Wildcard { .. } | Pattern::Literal(_) => {}
+        }
+    }
+
+    fn path(&mut self, segments: &[Ident]) {
+        let [first, rest @ ..] = segments else { return };
+        let owner = if first.name == "Self" {
+            self.current_impl.clone()
+        } else if self.map.modules.contains(&first.name) {
+            let Some((member, rest)) = rest.split_first() else { return };
+            self.record(Symbol::Qualified(first.name.clone(), member.name.clone()), &member.name, member.span.start, false);
+            if let Some(method) = rest.first() {
+                self.record(Symbol::Method(Some(member.name.clone()), method.name.clone()), &method.name, method.span.start, false);
+            }
+            return;
+        } else {
+            self.use_item(first);
+            Some(first.name.clone())
+        };
+        if let (Some(owner), Some(member)) = (owner, rest.first()) {
+            self.record(Symbol::Method(Some(owner), member.name.clone()), &member.name, member.span.start, false);
+        }
+    }
+
+    /// Type a method is called on, when the receiver's declaration says
+    fn receiver_type(&self, receiver: &Expr) -> Option<String> {
+        match receiver {
+            Expr::Ident(ident) if ident.name == "self" => self.current_impl.clone(),
+            Expr::Ident(ident) => self.local(&ident.name).cloned().flatten(),
+            Expr::Deref { expr, .. } => self.receiver_type(expr),
+            Expr::StructLit { name, .. } => Some(name.name.clone()),
+            _ => None,
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Ident(ident) => {
+                if self.local(&ident.name).is_none() {
+                    self.use_item(ident);
+                }
+            }
+            Expr::Path { segments, generic_args, .. } => {
+                self.path(segments);
+                for arg in generic_args {
+                    match arg {
+                        GenericArg::Type(ty) => self.ty(ty),
+                        GenericArg::Const(expr) => self.expr(expr),
+                    }
+                }
+            }
+            Expr::Asm { operands, .. } => {
+                for operand in operands {
+                    operand.expr.iter().for_each(|e| self.expr(e));
+                }
+            }
+            Expr::Binary { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            Expr::Unary { expr, .. } | Expr::Field { expr, .. } | Expr::Ref { expr, .. }
+            | Expr::Deref { expr, .. } | Expr::Try { expr, .. } => self.expr(expr),
+            Expr::Call { func, args, .. } => {
+                self.expr(func);
+                args.iter().for_each(|arg| self.expr(arg));
+            }
+            Expr::MethodCall { expr, method, args, .. } => {
+                self.expr(expr);
+                let owner = self.receiver_type(expr);
+                self.record(Symbol::Method(owner, method.name.clone()), &method.name, method.span.start, false);
+                args.iter().for_each(|arg| self.expr(arg));
+            }
+            Expr::Index { expr, index, .. } => {
+                self.expr(expr);
+                self.expr(index);
+            }
+            Expr::Block(block) => self.block(block),
+            Expr::Let { name, ty, value, .. } => {
+                ty.iter().for_each(|ty| self.ty(ty));
+                self.expr(value);
+                self.bind(name, ty.as_ref());
+            }
+            Expr::If { cond, then_block, else_block, .. } => {
+                // `if let` bindings are visible in the then-branch
+                self.scopes.push(Vec::new());
+                self.expr(cond);
+                self.block(then_block);
+                self.scopes.pop();
+                else_block.iter().for_each(|block| self.block(block));
+            }
+            Expr::Match { expr, arms, .. } => {
+                self.expr(expr);
+                for arm in arms {
+                    self.scopes.push(Vec::new());
+                    self.pattern(&arm.pattern);
+                    arm.guard.iter().for_each(|guard| self.expr(guard));
+                    self.expr(&arm.body);
+                    self.scopes.pop();
+                }
+            }
+            Expr::Loop { body, .. } => self.block(body),
+            Expr::Unsafe { body, verifier, .. } => {
+                verifier.iter().for_each(|verifier| self.use_item(verifier));
+                self.block(body);
+            }
+            Expr::While { cond, body, .. } => {
+                self.scopes.push(Vec::new());
+                self.expr(cond);
+                self.block(body);
+                self.scopes.pop();
+            }
+            Expr::For { var, iter, body, .. } => {
+                self.expr(iter);
+                self.scopes.push(Vec::new());
+                self.bind(var, None);
+                self.block(body);
+                self.scopes.pop();
+            }
+            Expr::StructLit { name, fields, .. } => {
+                self.use_item(name);
+                fields.iter().for_each(|(_, value)| self.expr(value));
+            }
+            Expr::Array { elements, .. } | Expr::Tuple { elements, .. } => elements.iter().for_each(|e| self.expr(e)),
+            Expr::ArrayFrom { init, .. } => self.expr(init),
+            Expr::DoBlock { stmts, accumulator, .. } => {
+                self.scopes.push(Vec::new());
+                self.stmts(stmts);
+                self.expr(accumulator);
+                self.scopes.pop();
+            }
+            Expr::Cast { expr, ty, .. } => {
+                self.expr(expr);
+                self.ty(ty);
+            }
+            Expr::Range { start, end, .. } => {
+                start.iter().for_each(|e| self.expr(e));
+                end.iter().for_each(|e| self.expr(e));
+            }
+            Expr::Closure { params, ret_type, body, .. } => {
+                self.scopes.push(Vec::new());
+                for param in params {
+                    param.ty.iter().for_each(|ty| self.ty(ty));
+                    self.bind(&param.name, param.ty.as_ref());
+                }
+                ret_type.iter().for_each(|ty| self.ty(ty));
+                self.expr(body);
+                self.scopes.pop();
+            }
+            Expr::SizeOf { ty, .. } | Expr::AlignOf { ty, .. } | Expr::OffsetOf { ty, .. } => self.ty(ty),
+            Expr::New { ty, count, init, .. } => {
+                self.ty(ty);
+                count.iter().for_each(|e| self.expr(e));
+                init.iter().for_each(|e| self.expr(e));
+            }
+            Expr::Literal(_) => {}
+        }
+    }
+}
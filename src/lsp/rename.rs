@@ -0,0 +1,266 @@
+//! Renaming an item or method everywhere the reference map finds it,
+//! including the module file it is imported from
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::frontend::lexer::Lexer;
+use crate::frontend::module::ModuleLoader;
+use crate::frontend::parser::Parser;
+use crate::frontend::token::TokenKind;
+use crate::utils::{SourceLines, Span};
+
+use super::references::{ReferenceMap, Symbol};
+
+/// Why a rename was refused; `location` is the clashing name, if any
+#[derive(Debug, Clone)]
+pub struct RenameError {
+    pub message: String,
+    pub location: Option<(PathBuf, Span)>,
+}
+
+impl RenameError {
+    fn new(message: impl Into<String>) -> Self {
+        Self { message: message.into(), location: None }
+    }
+}
+
+impl fmt::Display for RenameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)?;
+        if let Some((path, span)) = &self.location {
+            if let Ok(source) = fs::read_to_string(path) {
+                let lines = SourceLines::new(&path.display().to_string(), &source);
+                let (file, line, column) = lines.locate(*span);
+                write!(f, "\n  --> {}:{}:{}", file, line, column)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Occurrences of the old name to rewrite in one file
+#[derive(Debug, Clone)]
+pub struct FileEdit {
+    pub path: PathBuf,
+    pub source: String,
+    pub spans: Vec<Span>,
+}
+
+impl FileEdit {
+    /// `source` with every span replaced by `new_name`
+    pub fn apply(&self, new_name: &str) -> String {
+        let mut text = self.source.clone();
+        for span in self.spans.iter().rev() {
+            text.replace_range(span.start..span.end, new_name);
+        }
+        text
+    }
+}
+
+/// The edits making up a rename, the file it was started from first
+#[derive(Debug, Clone)]
+pub struct Renaming {
+    pub new_name: String,
+    pub files: Vec<FileEdit>,
+}
+
+impl Renaming {
+    pub fn occurrences(&self) -> usize {
+        self.files.iter().map(|f| f.spans.len()).sum()
+    }
+}
+
+/// What is being renamed
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+    /// An item defined in the file
+    Item(String),
+    /// An item of an imported module
+    Module { module: String, name: String },
+    /// A method of a type defined in the file
+    Method { owner: String, name: String },
+}
+
+/// A parsed file and its references
+struct Unit {
+    path: PathBuf,
+    source: String,
+    map: ReferenceMap,
+}
+
+impl Unit {
+    fn parse(path: &Path, source: &str) -> Result<Self, RenameError> {
+        let program = Parser::new(Lexer::new(source, 0)).parse_program()
+            .map_err(|e| RenameError::new(format!("{} does not parse: {}", path.display(), e)))?;
+        let map = ReferenceMap::build(&program, source);
+        Ok(Self { path: path.to_path_buf(), source: source.to_string(), map })
+    }
+
+    fn edit(&self, symbol: &Symbol) -> FileEdit {
+        let spans = self.map.occurrences(symbol).into_iter().map(|o| o.span).collect();
+        FileEdit { path: self.path.clone(), source: self.source.clone(), spans }
+    }
+
+    fn conflict(&self, message: String, span: Span) -> RenameError {
+        RenameError { message, location: Some((self.path.clone(), span)) }
+    }
+
+    /// Refuse if `new_name` already names an item here, or a local in a
+    /// function where one of `spans` would then refer to it
+    fn check_conflicts(&self, new_name: &str, spans: &[Span]) -> Result<(), RenameError> {
+        if let Some(span) = self.map.definition(&Symbol::Item(new_name.to_string())) {
+            return Err(self.conflict(format!("`{}` is already defined", new_name), span));
+        }
+        let imported = self.map.imports.iter()
+            .filter_map(|import| import.binds.as_ref())
+            .find(|(name, _)| name == new_name);
+        if let Some((_, span)) = imported {
+            return Err(self.conflict(format!("`{}` is already imported", new_name), *span));
+        }
+        let shadowing = self.map.bindings.iter()
+            .filter(|b| b.name == new_name)
+            .find(|b| spans.iter().any(|s| b.function.start <= s.start && s.end <= b.function.end));
+        if let Some(binding) = shadowing {
+            return Err(self.conflict(format!("a local named `{}` would shadow the renamed item", new_name), binding.span));
+        }
+        Ok(())
+    }
+}
+
+/// Rename `old` (`name`, `Type::method` or `module::name`) in the file at
+/// `path` and in the module it comes from
+pub fn rename_symbol(path: &Path, source: &str, old: &str, new_name: &str) -> Result<Renaming, RenameError> {
+    check_identifier(new_name)?;
+    let unit = Unit::parse(path, source)?;
+    let target = resolve_name(&unit, old)?;
+    rename_target(&unit, target, new_name)
+}
+
+/// Rename the item or method whose name covers byte `offset`
+pub fn rename_at(path: &Path, source: &str, offset: usize, new_name: &str) -> Result<Renaming, RenameError> {
+    check_identifier(new_name)?;
+    let unit = Unit::parse(path, source)?;
+    let (symbol, _) = unit.map.symbol_at(offset)
+        .ok_or_else(|| RenameError::new("no item or method to rename here"))?;
+    let target = match symbol.clone() {
+        Symbol::Item(name) => resolve_name(&unit, &name)?,
+        Symbol::Qualified(module, name) => Target::Module { module, name },
+        Symbol::Method(Some(owner), name) => Target::Method { owner, name },
+        Symbol::Method(None, name) => match unit.map.method_owners(&name).as_slice() {
+            [owner] => Target::Method { owner: owner.clone(), name },
+            _ => return Err(RenameError::new(format!("cannot tell which type's method `{}` this is", name))),
+        },
+    };
+    rename_target(&unit, target, new_name)
+}
+
+fn check_identifier(name: &str) -> Result<(), RenameError> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && name != "_";
+    if !valid {
+        return Err(RenameError::new(format!("`{}` is not a valid identifier", name)));
+    }
+    if TokenKind::keyword_from_str(name).is_some() || name == "self" || name == "Self" {
+        return Err(RenameError::new(format!("`{}` is a keyword", name)));
+    }
+    Ok(())
+}
+
+fn resolve_name(unit: &Unit, old: &str) -> Result<Target, RenameError> {
+    if let Some((prefix, name)) = old.split_once("::") {
+        let (prefix, name) = (prefix.to_string(), name.to_string());
+        return Ok(if unit.map.modules.contains(&prefix) {
+            Target::Module { module: prefix, name }
+        } else {
+            Target::Method { owner: prefix, name }
+        });
+    }
+    if unit.map.definition(&Symbol::Item(old.to_string())).is_some() {
+        return Ok(Target::Item(old.to_string()));
+    }
+    let imported = unit.map.imports.iter()
+        .find(|import| import.binds.as_ref().is_some_and(|(binds, _)| binds == old));
+    match imported {
+        Some(import) => Ok(Target::Module {
+            module: import.module.clone(),
+            name: import.name.clone().unwrap_or_default(),
+        }),
+        None => Err(RenameError::new(format!("no item named `{}` in {}", old, unit.path.display()))),
+    }
+}
+
+fn rename_target(unit: &Unit, target: Target, new_name: &str) -> Result<Renaming, RenameError> {
+    let files = match target {
+        Target::Item(name) => {
+            let edit = unit.edit(&Symbol::Item(name));
+            unit.check_conflicts(new_name, &edit.spans)?;
+            vec![edit]
+        }
+        Target::Method { owner, name } => {
+            let symbol = Symbol::Method(Some(owner.clone()), name.clone());
+            if unit.map.definition(&symbol).is_none() {
+                return Err(RenameError::new(format!("no method `{}` on type `{}` in {}", name, owner, unit.path.display())));
+            }
+            if let Some(span) = unit.map.definition(&Symbol::Method(Some(owner.clone()), new_name.to_string())) {
+                return Err(unit.conflict(format!("`{}` already has a method `{}`", owner, new_name), span));
+            }
+            let mut edit = unit.edit(&symbol);
+            // Calls on receivers of unknown type can only mean the one method of that name
+            if unit.map.method_owners(&name) == [owner] {
+                edit.spans.extend(unit.edit(&Symbol::Method(None, name)).spans);
+                edit.spans.sort_by_key(|s| s.start);
+            }
+            vec![edit]
+        }
+        Target::Module { module, name } => rename_in_module(unit, &module, &name, new_name)?,
+    };
+    Ok(Renaming { new_name: new_name.to_string(), files })
+}
+
+/// Rename public item `name` of `module` in the module's file and in `unit`
+fn rename_in_module(unit: &Unit, module: &str, name: &str, new_name: &str) -> Result<Vec<FileEdit>, RenameError> {
+    let project = unit.path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let mut loader = ModuleLoader::new();
+    loader.add_search_path(project.to_path_buf());
+    let module_path = loader.find_module_file(module)
+        .ok_or_else(|| RenameError::new(format!("cannot rename `{}::{}`: module `{}` is not in the search paths", module, name, module)))?;
+    let inside = match (module_path.canonicalize(), project.canonicalize()) {
+        (Ok(file), Ok(root)) => file.starts_with(root),
+        _ => false,
+    };
+    if !inside {
+        return Err(RenameError::new(format!(
+            "cannot rename `{}::{}`: it is defined in {}, outside the project",
+            module, name, module_path.display()
+        )));
+    }
+    let module_source = fs::read_to_string(&module_path)
+        .map_err(|e| RenameError::new(format!("{}: {}", module_path.display(), e)))?;
+    let module_unit = Unit::parse(&module_path, &module_source)?;
+    let item = Symbol::Item(name.to_string());
+    if module_unit.map.definition(&item).is_none() {
+        return Err(RenameError::new(format!("module `{}` has no item `{}`", module, name)));
+    }
+    let module_edit = module_unit.edit(&item);
+    module_unit.check_conflicts(new_name, &module_edit.spans)?;
+
+    // Qualified uses, plus bare ones when the name itself was imported
+    let mut edit = unit.edit(&Symbol::Qualified(module.to_string(), name.to_string()));
+    let bare = unit.map.definition(&item).is_none() && unit.map.imports.iter().any(|import| {
+        import.module == module && match &import.binds {
+            Some((binds, _)) => binds == name && import.name.as_deref() == Some(name),
+            None => true,
+        }
+    });
+    if bare {
+        let uses = unit.edit(&item).spans;
+        unit.check_conflicts(new_name, &uses)?;
+        edit.spans.extend(uses);
+        edit.spans.sort_by_key(|s| s.start);
+    }
+    Ok(vec![edit, module_edit])
+}
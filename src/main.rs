@@ -2,29 +2,38 @@
 //! 
 //! A self-hosting systems programming language.
 
-mod frontend;
-mod middle;
-mod backend;
-mod types;
-mod utils;
-mod stdlib;
-mod ai_ir;
-mod feedback;
-mod lsp;
-mod script;
+mod watch;
+mod repl;
+
+use aether_lang::{backend, feedback, frontend, lsp, middle, types, utils};
+use aether_lang::{CompileResult, CompileSession};
+use aether_lang::doc::{DocFormat, DocGenerator};
 
 use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
 use std::process;
+use std::time::Instant;
+use std::io::IsTerminal;
 
-use frontend::lexer::Lexer;
-use frontend::parser::Parser as AethParser;
-use frontend::semantic::SemanticAnalyzer;
-use middle::ir_gen::IRGenerator;
+use frontend::semantic;
 use middle::optimize::Optimizer;
-use middle::ir_printer::print_ir;
-use backend::{CCodeGen, codegen::CodeGen};
+use middle::ir_printer::{print_ir, print_struct_layouts};
+use middle::dot::function_to_dot;
+use middle::ir::IRModule;
+use middle::ir_parser::parse_ir;
+use middle::lto;
+use middle::validate::validate_module;
+use middle::bench::BenchConfig;
+use middle::coverage::CoverageData;
+use middle::overflow::OverflowMode;
+use middle::panic::PanicMode;
+use middle::strip::strip_module;
+use feedback::{ColorChoice, ColorConfig, CompilationFeedback, CompilationStats, ErrorFormat, ErrorReport, Severity, Style, TimingsFormat};
+use utils::Error;
+use backend::{CCodeGen, codegen::SanitizerFlags};
+use backend::cross::{CrossConfig, Toolchain};
+use backend::linker::Linker;
 
 /// AetherLang Compiler
 #[derive(Parser, Debug)]
@@ -48,15 +57,102 @@ struct Cli {
     #[arg(long)]
     emit_c: bool,
 
-    /// Emit Aether IR (for debugging)
+    /// Emit a .c/.h pair for embedding in a C project: the header declares
+    /// the pub functions and structs, other functions become static
+    /// (implies --emit-c)
+    #[arg(long, conflicts_with_all = ["emit_object", "emit_static_lib", "emit_shared_lib", "lto"])]
+    emit_c_header: bool,
+
+    /// Leave out the C `main`; the program's main is kept as `aether_main`
+    #[arg(long)]
+    no_main: bool,
+
+    /// Build without the C library: no libc builtins, runtime or C `main`.
+    /// The C compiler only builds an object; the self-hosted linker links
+    /// the static x86-64 executable
+    #[arg(long)]
+    freestanding: bool,
+
+    /// Symbol a --freestanding program starts at [default: _start]
+    #[arg(long, value_name = "SYMBOL", requires = "freestanding")]
+    entry: Option<String>,
+
+    /// Compile to an object file without linking
+    #[arg(long, conflicts_with_all = ["emit_c", "emit_static_lib", "emit_shared_lib"])]
+    emit_object: bool,
+
+    /// Compile to a static library (lib<name>.a) for use from C or Rust
+    #[arg(long, conflicts_with_all = ["emit_c", "emit_shared_lib"])]
+    emit_static_lib: bool,
+
+    /// Compile to a shared library (lib<name>.so)
+    #[arg(long, conflicts_with = "emit_c")]
+    emit_shared_lib: bool,
+
+    /// Write the unoptimized module to <input>.alito (LLVM bitcode with
+    /// --backend llvm) and leave optimization to `aethc link`
+    #[arg(long, conflicts_with_all = ["emit_c", "emit_object", "emit_static_lib", "emit_shared_lib", "coverage"])]
+    lto: bool,
+
+    /// Validate the IR after every optimizer pass
+    #[arg(long, global = true)]
+    verify_ir: bool,
+
+    /// Also write a C header declaring the #[extern_c] functions
     #[arg(long)]
+    emit_header: bool,
+
+    /// Emit Aether IR (for debugging)
+    #[arg(long, global = true)]
     emit_ir: bool,
 
+    /// Write the parsed program to <input>.ast.json, for editor tooling and linters
+    #[cfg(feature = "json-ast")]
+    #[arg(long, global = true)]
+    emit_json_ast: bool,
+
+    /// Indent the JSON written by --emit-json-ast
+    #[cfg(feature = "json-ast")]
+    #[arg(long, global = true, requires = "emit_json_ast")]
+    json_ast_pretty: bool,
+
+    /// Write each function's control flow graph to <input>_<function>.dot
+    #[arg(long, global = true)]
+    emit_dot: bool,
+
+    /// Render the .dot files to SVG with Graphviz and open them (implies --emit-dot)
+    #[arg(long, global = true)]
+    dot_open: bool,
+
+    /// Print the size, alignment, padding and field offsets of every struct
+    #[arg(long, global = true)]
+    show_struct_layout: bool,
+
+    /// Print the inferred type of every expression, under the source line it starts on
+    #[arg(long, global = true)]
+    print_types: bool,
+
+    /// Print the program's symbols with their kinds, types and locations
+    #[arg(long, global = true)]
+    print_symbols: bool,
+
     /// Optimization level (0-3)
-    #[arg(short = 'O', default_value = "0")]
+    #[arg(short = 'O', default_value = "0", global = true)]
     opt_level: u8,
 
-    /// Backend to use (c, llvm)
+    /// Map generated code back to the .aeth source (#line directives / DWARF)
+    #[arg(short = 'g', long = "debug-info", global = true)]
+    debug_info: bool,
+
+    /// Remove debug info, symbol names and coverage instrumentation from the output
+    #[arg(long, global = true)]
+    strip: bool,
+
+    /// Remove unreachable code and unused definitions even at -O0
+    #[arg(long, global = true)]
+    strip_dead_code: bool,
+
+    /// Backend to use (c, llvm, native)
     #[arg(long, default_value = "c")]
     backend: String,
     
@@ -64,9 +160,200 @@ struct Cli {
     #[arg(long, default_value = "native")]
     target: String,
     
-    /// Custom linker script for kernel/bare-metal development
+    /// Custom linker script for kernel/bare-metal development. The C backend
+    /// hands it to the system linker; the native backend reads ENTRY,
+    /// BASE_ADDRESS and SECTIONS itself
     #[arg(long, value_name = "FILE")]
     linker_script: Option<PathBuf>,
+
+    /// Write the sections and symbols of an executable the self-hosted linker
+    /// builds (native backend, --freestanding) to FILE
+    #[arg(long, value_name = "FILE")]
+    link_map: Option<PathBuf>,
+
+    /// Link with a system or pre-compiled library (libLIB.a / libLIB.so)
+    #[arg(short = 'l', long = "link-library", value_name = "LIB", global = true)]
+    link_libraries: Vec<String>,
+
+    /// Add a directory to the library search path
+    #[arg(short = 'L', long = "library-path", value_name = "DIR", global = true)]
+    library_paths: Vec<PathBuf>,
+
+    /// Treat warnings as errors
+    #[arg(long, global = true)]
+    deny_warnings: bool,
+
+    /// Report production checks (contract types, effects, lossy conversions,
+    /// unsafe operations) as errors, as `#[production]` does
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Report production checks as warnings, even in `#[production]` code
+    #[arg(long, conflicts_with = "strict", global = true)]
+    lenient: bool,
+
+    /// Stop reporting semantic errors after N of them (0 for no limit)
+    #[arg(long, value_name = "N", default_value_t = semantic::DEFAULT_MAX_ERRORS, global = true)]
+    max_errors: usize,
+
+    /// Reject code nested more than N levels deep
+    #[arg(long, value_name = "N", default_value_t = frontend::parser::DEFAULT_MAX_NESTING, global = true)]
+    max_nesting: usize,
+
+    /// Report diagnostics and runtime panics as one JSON line each
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Diagnostic format: human, or json, json-diagnostic-short or
+    /// json-diagnostic-rendered-ansi for rustc-style JSON lines (implies --json)
+    #[arg(long, value_name = "FORMAT", value_parser = ErrorFormat::parse, default_value = "human", global = true)]
+    error_format: ErrorFormat,
+
+    /// Color diagnostics: auto (when stderr is a terminal), always, never,
+    /// or always with ansi16 or truecolor codes. JSON output is never colored.
+    #[arg(long, value_name = "WHEN", value_parser = ColorChoice::parse, default_value = "auto", global = true)]
+    color: ColorChoice,
+
+    /// Never color diagnostics (same as --color=never)
+    #[arg(long, conflicts_with = "color", global = true)]
+    no_color: bool,
+
+    /// Print how long each phase took to stderr; --timings=json also lists
+    /// the IR size of every function
+    #[arg(long, value_name = "FORMAT", num_args = 0..=1, require_equals = true, default_missing_value = "human", value_parser = TimingsFormat::parse, global = true)]
+    timings: Option<TimingsFormat>,
+
+    /// Language of diagnostic messages (en, zh)
+    #[arg(long, value_name = "LOCALE", value_parser = feedback::parse_locale, default_value = feedback::DEFAULT_LOCALE, global = true)]
+    locale: &'static str,
+
+    /// Runtime failure reporting: abort, or unwind-trace to also print a backtrace
+    #[arg(long, value_name = "MODE", value_parser = PanicMode::parse, default_value = "abort")]
+    panic: PanicMode,
+
+    /// Integer overflow behavior: wrapping, saturating or panicking
+    #[arg(long, value_name = "MODE", value_parser = OverflowMode::parse, conflicts_with = "overflow_check")]
+    overflow_mode: Option<OverflowMode>,
+
+    /// Panic on integer overflow (same as --overflow-mode=panicking)
+    #[arg(long)]
+    overflow_check: bool,
+
+    /// Worker threads for semantic analysis and code generation (default: one per CPU)
+    #[arg(short = 'j', long, value_name = "N", global = true, value_parser = clap::value_parser!(u32).range(1..))]
+    jobs: Option<u32>,
+
+    /// Count basic block executions and write them to <output>.aethcov on exit
+    #[arg(long)]
+    coverage: bool,
+
+    /// Instrument with runtime sanitizers (comma-separated: address, memory, undefined)
+    #[arg(long, value_name = "KINDS", value_parser = SanitizerFlags::parse, default_value = "")]
+    sanitize: SanitizerFlags,
+
+    /// Target libraries and headers for cross-compilation
+    #[arg(long, value_name = "PATH")]
+    sysroot: Option<PathBuf>,
+
+    /// C compiler to use for a foreign target (default: <triple>-gcc)
+    #[arg(long, value_name = "NAME")]
+    cross_compiler: Option<String>,
+
+    /// Cross-compilation config file (triple, sysroot, compiler, cflags)
+    #[arg(long, value_name = "FILE")]
+    cross_config: Option<PathBuf>,
+
+    /// Rewrite rules for Core transpiled from Aether Script (.ath)
+    #[arg(long, value_name = "FILE")]
+    transform_rules: Option<PathBuf>,
+}
+
+impl Cli {
+    /// Worker threads per compilation phase
+    fn jobs(&self) -> usize {
+        self.jobs.map_or_else(utils::default_jobs, |n| n as usize)
+    }
+
+    /// What integer arithmetic does on overflow
+    fn overflow_mode(&self) -> OverflowMode {
+        match self.overflow_mode {
+            Some(mode) => mode,
+            None if self.overflow_check => OverflowMode::Panicking,
+            None => OverflowMode::Wrapping,
+        }
+    }
+
+    /// Entry symbol of a --freestanding program
+    fn entry(&self) -> &str {
+        self.entry.as_deref().unwrap_or(backend::c::DEFAULT_ENTRY)
+    }
+
+    /// Strict (Some(true)) or lenient mode forced on the command line
+    fn strictness(&self) -> Option<bool> {
+        match (self.strict, self.lenient) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        }
+    }
+
+    /// The colors of human-readable diagnostics on stderr
+    fn color(&self) -> ColorConfig {
+        if self.no_color || self.json {
+            return ColorConfig::Never;
+        }
+        self.color.resolve(std::io::stderr().is_terminal())
+    }
+
+    /// What the C backend builds from the generated source
+    fn compile_mode(&self) -> CompileMode {
+        if self.emit_object {
+            CompileMode::ObjectFile
+        } else if self.emit_static_lib {
+            CompileMode::StaticLib
+        } else if self.emit_shared_lib {
+            CompileMode::SharedLib
+        } else {
+            CompileMode::Binary
+        }
+    }
+}
+
+/// Final artifact of a C backend build
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompileMode {
+    /// A linked executable
+    Binary,
+    /// An object file (`cc -c`), not linked
+    ObjectFile,
+    /// An object file archived with `ar rcs`
+    StaticLib,
+    /// A position-independent shared library
+    SharedLib,
+}
+
+impl CompileMode {
+    /// Output path when `-o` is not given
+    fn default_output(self, input: &Path) -> PathBuf {
+        let stem = input.file_stem().and_then(|s| s.to_str()).unwrap_or("out");
+        match self {
+            CompileMode::Binary => input.with_extension(std::env::consts::EXE_EXTENSION),
+            CompileMode::ObjectFile => input.with_extension("o"),
+            CompileMode::StaticLib => input.with_file_name(format!("lib{}.a", stem)),
+            CompileMode::SharedLib => input.with_file_name(format!(
+                "{}{}.{}", std::env::consts::DLL_PREFIX, stem, std::env::consts::DLL_EXTENSION
+            )),
+        }
+    }
+
+    /// Compiler flags selecting this kind of output
+    fn compiler_args(self) -> &'static [&'static str] {
+        match self {
+            CompileMode::Binary => &[],
+            CompileMode::ObjectFile | CompileMode::StaticLib => &["-c"],
+            CompileMode::SharedLib => &["-shared", "-fPIC"],
+        }
+    }
 }
 
 #[derive(Subcommand, Debug)]
@@ -80,11 +367,118 @@ enum Commands {
         #[arg(short, long)]
         output: Option<PathBuf>,
     },
+    /// Compile a source file and run the resulting program
+    Run {
+        /// Input source file
+        input: PathBuf,
+
+        /// Arguments passed to the program
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Time a function: run it many times and print criterion-style statistics
+    Bench {
+        /// Input source file
+        input: PathBuf,
+
+        /// Function to benchmark (default: every `#[bench]` function)
+        function: Option<String>,
+
+        /// Calls per function
+        #[arg(short = 'n', long, default_value_t = 1000, value_parser = clap::value_parser!(u64).range(1..))]
+        iterations: u64,
+    },
+    /// Optimize a textual IR file (as written by --emit-ir) and print the result
+    Opt {
+        /// Input IR file
+        input: PathBuf,
+
+        /// Write the optimized IR here instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Merge modules built with --lto, optimize them together and build an executable
+    Link {
+        /// Objects written by `aethc --lto build`
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Output executable
+        #[arg(short, long)]
+        output: PathBuf,
+    },
     /// Check a source file for errors
     Check {
         /// Input source file
         input: PathBuf,
     },
+    /// Evaluate statements and expressions interactively
+    Repl,
+    /// Recheck (or rebuild) a program whenever it or a module it imports changes
+    Watch {
+        /// Source file, or a directory containing main.aeth
+        input: PathBuf,
+
+        /// Build an executable on each change instead of only checking
+        #[arg(long)]
+        build: bool,
+
+        /// Output file for --build
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Shell command to run after each successful build; the previous
+        /// instance is killed first (implies --build)
+        #[arg(long, value_name = "CMD")]
+        exec: Option<String>,
+    },
+    /// Annotate a source file with the hit counts recorded by a --coverage build
+    CovReport {
+        /// Coverage data written by the instrumented program
+        data: PathBuf,
+
+        /// Source file the program was built from
+        source: PathBuf,
+
+        /// Also write an LCOV tracefile here
+        #[arg(long, value_name = "FILE")]
+        lcov: Option<PathBuf>,
+    },
+    /// Explain an error code in detail (e.g. `aethc explain E0001`)
+    Explain {
+        /// Error code as printed in diagnostics
+        code: String,
+    },
+    /// Run the language server over stdin and stdout
+    Lsp,
+    /// Rename an item or method (`name`, `Type::method` or `module::name`)
+    /// in a source file and the module it is imported from
+    Rename {
+        /// Source file
+        input: PathBuf,
+        /// Current name
+        old: String,
+        /// New name
+        new: String,
+    },
+    /// Generate documentation from `///` comments for a source file and
+    /// the modules it imports
+    Doc {
+        /// Input source file
+        input: PathBuf,
+
+        /// Directory to write the pages and docs.json into
+        #[arg(short, long, value_name = "DIR", default_value = "doc")]
+        output_dir: PathBuf,
+
+        /// Page format: html or markdown
+        #[arg(long, value_parser = DocFormat::parse, default_value = "html")]
+        format: DocFormat,
+
+        /// Also document items without `pub`
+        #[arg(long)]
+        document_private_items: bool,
+    },
     /// Print version information
     Version,
     
@@ -99,47 +493,126 @@ enum Commands {
 /// Get the target triple for code generation
 fn get_target_triple(target: &str) -> String {
     if target == "native" {
-        // Auto-detect native target
-        #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
-        return "arm64-apple-darwin".to_string();
-        
-        #[cfg(all(target_os = "macos", target_arch = "x86_64"))]
-        return "x86_64-apple-darwin".to_string();
-        
-        #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
-        return "x86_64-unknown-linux-gnu".to_string();
-        
-        #[cfg(all(target_os = "linux", target_arch = "aarch64"))]
-        return "aarch64-unknown-linux-gnu".to_string();
-        
-        #[cfg(all(target_os = "windows", target_arch = "x86_64"))]
-        return "x86_64-pc-windows-msvc".to_string();
-        
-        // Fallback
-        #[cfg(not(any(
-            all(target_os = "macos", target_arch = "aarch64"),
-            all(target_os = "macos", target_arch = "x86_64"),
-            all(target_os = "linux", target_arch = "x86_64"),
-            all(target_os = "linux", target_arch = "aarch64"),
-            all(target_os = "windows", target_arch = "x86_64"),
-        )))]
-        return "x86_64-unknown-linux-gnu".to_string();
+        return backend::cross::host_triple();
     }
     target.to_string()
 }
 
+/// Resolve the C toolchain from --target, --cross-config, --sysroot and --cross-compiler
+fn build_toolchain(cli: &Cli) -> Toolchain {
+    let mut toolchain = Toolchain::new(&cli.target);
+    if let Some(ref path) = cli.cross_config {
+        match CrossConfig::load(path) {
+            Ok(config) => toolchain = toolchain.with_config(config),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(ref sysroot) = cli.sysroot {
+        toolchain.sysroot = Some(sysroot.clone());
+    }
+    if let Some(ref compiler) = cli.cross_compiler {
+        toolchain.cross_compiler = Some(compiler.clone());
+    }
+    toolchain
+}
+
 fn main() {
+    // Deeply nested code recurses through every phase, further than the
+    // default main thread stack allows
+    let compiler = std::thread::Builder::new()
+        .stack_size(utils::STACK_SIZE)
+        .spawn(run)
+        .expect("failed to start the compiler thread");
+    if let Err(panic) = compiler.join() {
+        std::panic::resume_unwind(panic);
+    }
+}
+
+fn run() {
     env_logger::init();
     
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+    // A JSON error format is `--json` with rustc's diagnostic schema
+    cli.json |= cli.error_format.is_json();
+    cli.emit_c |= cli.emit_c_header;
     
     // Handle subcommands
     match &cli.command {
         Some(Commands::Build { input, output }) => {
-            compile_file(input, output.clone(), &cli);
+            compile_file(input, output.clone(), cli.compile_mode(), None, &cli);
+        }
+        Some(Commands::Run { input, args }) => {
+            run_file(input, args, None, &cli);
+        }
+        Some(Commands::Bench { input, function, iterations }) => {
+            let config = BenchConfig { function: function.clone(), iterations: *iterations };
+            run_file(input, &[], Some(&config), &cli);
+        }
+        Some(Commands::Opt { input, output }) => {
+            opt_file(input, output.as_ref(), &cli);
+        }
+        Some(Commands::Link { inputs, output }) => {
+            link_files(inputs, output, &cli);
         }
         Some(Commands::Check { input }) => {
-            check_file(input);
+            check_file(input, &cli);
+        }
+        Some(Commands::Repl) => {
+            // Compiler flags before the subcommand apply to every evaluation
+            let compiler_args = std::env::args_os().skip(1)
+                .take_while(|arg| arg != "repl")
+                .collect();
+            repl::repl(compiler_args);
+        }
+        Some(Commands::Watch { input, build, output, exec }) => {
+            // Compiler flags come before the subcommand and are passed on to
+            // every rebuild
+            let mut compiler_args: Vec<_> = std::env::args_os().skip(1)
+                .take_while(|arg| arg != "watch")
+                .collect();
+            if cli.json && !compiler_args.iter().any(|arg| arg == "--json") {
+                compiler_args.push("--json".into());
+            }
+            if !compiler_args.iter().any(|arg| arg == "--locale") {
+                compiler_args.push("--locale".into());
+                compiler_args.push(cli.locale.into());
+            }
+            watch::watch(watch::WatchOptions {
+                input: input.clone(),
+                build: *build,
+                output: output.clone(),
+                exec: exec.clone(),
+                json: cli.json,
+                compiler_args,
+            });
+        }
+        Some(Commands::CovReport { data, source, lcov }) => {
+            cov_report(data, source, lcov.as_ref());
+        }
+        Some(Commands::Explain { code }) => {
+            match feedback::explain(code) {
+                Some(text) => print!("{}", text),
+                None => {
+                    eprintln!("Error: no extended information for error code '{}'", code);
+                    process::exit(1);
+                }
+            }
+        }
+        Some(Commands::Lsp) => {
+            let stdin = std::io::stdin();
+            if let Err(e) = lsp::LanguageServer::new().serve(&mut stdin.lock(), &mut std::io::stdout()) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        Some(Commands::Rename { input, old, new }) => {
+            rename_file(input, old, new);
+        }
+        Some(Commands::Doc { input, output_dir, format, document_private_items }) => {
+            doc_file(input, output_dir, *format, *document_private_items);
         }
         Some(Commands::Version) => {
             println!("aethc 0.1.0");
@@ -148,16 +621,17 @@ fn main() {
         }
         Some(Commands::LinkTest { output }) => {
             use backend::linker::{Linker, PF_R, PF_X, SHT_PROGBITS, SHF_ALLOC, SHF_EXECINSTR};
+            use backend::native::x86_64::{Assembler, Reg};
             
             println!("Testing Self-Hosted Linker...");
             let mut linker = Linker::new();
             
-            // Minimal shellcode: exit(42)
-            let code = vec![
-                0x48, 0xc7, 0xc7, 0x2a, 0x00, 0x00, 0x00, // mov rdi, 42
-                0x48, 0xc7, 0xc0, 0x3c, 0x00, 0x00, 0x00, // mov rax, 60
-                0x0f, 0x05                                // syscall
-            ];
+            // Minimal program: exit(42)
+            let mut asm = Assembler::new();
+            asm.mov_imm(Reg::Rdi, 42);
+            asm.mov_imm(Reg::Rax, 60);
+            asm.syscall();
+            let code = asm.finish().expect("no labels to resolve");
             
             // Add as .text section
             // In our current simple implementation, segments and sections must be added in sync
@@ -166,9 +640,10 @@ fn main() {
             // Our emit logic assumes Sections == Segments 1:1 for the loop.
             // So we add text segment AND text section.
             
-            let vaddr = 0x400000 + 0x40;
-            linker.add_segment(code.clone(), PF_R | PF_X, vaddr);
-            linker.add_section(".text", code, SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, vaddr);
+            // Page-align the segment so its file offset matches the vaddr page offset
+            let vaddr = 0x400000 + 0x1000;
+            linker.add_segment(code.clone(), PF_R | PF_X, vaddr, 0x1000);
+            linker.add_section(".text", code, SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, vaddr, 16);
             
             linker.set_entry_point(vaddr);
             
@@ -190,7 +665,7 @@ fn main() {
         None => {
             // Default: compile the input file
             if let Some(ref input) = cli.input {
-                compile_file(input, cli.output.clone(), &cli);
+                compile_file(input, cli.output.clone(), cli.compile_mode(), None, &cli);
             } else {
                 eprintln!("Error: No input file specified");
                 eprintln!("Usage: aethc <FILE> or aethc build <FILE>");
@@ -200,90 +675,86 @@ fn main() {
     }
 }
 
-/// Compile a source file (.aeth or .ath)
-fn compile_file(input: &PathBuf, output: Option<PathBuf>, cli: &Cli) {
+/// Compile a source file (.aeth or .ath) into the artifact `mode` selects.
+/// Returns the path of the linked executable, if one was produced.
+fn compile_file(
+    input: &Path,
+    output: Option<PathBuf>,
+    mode: CompileMode,
+    bench: Option<&BenchConfig>,
+    cli: &Cli,
+) -> Option<PathBuf> {
     println!("AetherLang Compiler v0.1.0");
     println!("Compiling: {}", input.display());
+    let start = Instant::now();
     
-    // 1. Read source file
-    let source = match fs::read_to_string(input) {
-        Ok(s) => s,
-        Err(e) => {
-            eprintln!("Error reading file: {}", e);
+    // 1-5. Read, parse, analyze and lower to Aether IR
+    let mut session = session_from_cli(input, cli);
+    if let Some(config) = bench {
+        session = session.with_benchmark(config.clone());
+    }
+    if cli.coverage {
+        if cli.backend != "c" {
+            eprintln!("Error: --coverage is only supported by the C backend");
             process::exit(1);
         }
-    };
-    
-    // 1.5. Check if Aether Script (.ath) - Transpile to Core first
-    let core_source = if input.extension().map(|e| e == "ath").unwrap_or(false) {
-        println!("  [Script] Detected Aether Script (.ath)");
-        
-        // Parse Script
-        let mut script_parser = script::parser::Parser::new(&source);
-        let script_module = match script_parser.parse() {
-            Ok(m) => m,
-            Err(e) => {
-                eprintln!("Script Parse error: {}", e);
-                process::exit(1);
-            }
+        let exe_path = output.clone().unwrap_or_else(|| input.with_extension(""));
+        session = session.with_coverage(exe_path.with_extension("aethcov"));
+    }
+    #[cfg(feature = "json-ast")]
+    if cli.emit_json_ast {
+        let result = session.emit_json_ast(cli.json_ast_pretty);
+        report_diagnostics(&result, cli);
+        let Some(json) = result.text() else {
+            process::exit(1);
         };
-        println!("  [✓] Script parsed ({} statements)", script_module.stmts.len());
-        
-        // Transpile to Core with source mapping enabled
-        let source_path = input.to_string_lossy().to_string();
-        let mut transpiler = script::transpiler::Transpiler::new()
-            .with_source_file(&source_path);
-        let generated = transpiler.transpile(&script_module);
-        println!("  [✓] Transpiled to Aether Core ({} bytes)", generated.len());
-        
-        // Optionally write generated .aeth to disk for debugging
+        let json_path = input.with_extension("ast.json");
+        if let Err(e) = fs::write(&json_path, json) {
+            eprintln!("Error writing AST: {}", e);
+            process::exit(1);
+        }
+        println!("  [✓] Wrote AST to {}", json_path.display());
+        return None;
+    }
+    let (result, ir_module) = session.lower();
+    // Keep the transpiled Core for debugging, even if it does not compile
+    if let Some(ref generated) = result.transpiled {
         let gen_path = input.with_extension("gen.aeth");
-        if let Err(e) = fs::write(&gen_path, &generated) {
+        if let Err(e) = fs::write(&gen_path, generated) {
             eprintln!("  [!] Could not write generated Core: {}", e);
         } else {
             println!("  [→] Generated Core written to: {}", gen_path.display());
         }
-        
-        generated
-    } else {
-        source
-    };
-    
-    // 2. Lexer -> Tokens (using Core source)
-    let lexer = Lexer::new(&core_source, 0);
-    
-    // 3. Parser -> AST
-    let mut parser = AethParser::new(lexer);
-    let program = match parser.parse_program() {
-        Ok(p) => p,
-        Err(e) => {
-            eprintln!("Parse error: {}", e);
-            process::exit(1);
-        }
-    };
-    println!("  [✓] Parsed {} items", program.items.len());
-    
-    // 4. Semantic Analysis -> Typed AST
-    let mut analyzer = SemanticAnalyzer::new();
-    if let Err(e) = analyzer.analyze(&program) {
-        eprintln!("Semantic error: {}", e);
-        process::exit(1);
     }
+    print_inspections(&result);
+    report_diagnostics(&result, cli);
+    let Some(mut ir_module) = ir_module else {
+        process::exit(1);
+    };
+    let link_libraries = result.link_libraries;
+    let mut stats = result.stats;
     println!("  [✓] Semantic analysis passed");
-    
-    // 5. IR Generation -> Aether IR
-    let module_name = input.file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("module");
-    let mut ir_gen = IRGenerator::new(module_name);
-    let mut ir_module = match ir_gen.generate(&program) {
-        Ok(m) => m,
-        Err(e) => {
-            eprintln!("IR generation error: {}", e);
+    println!("  [✓] Generated IR ({} functions)", ir_module.functions.len());
+    if cli.show_struct_layout {
+        print!("\n{}", print_struct_layouts(&ir_module));
+    }
+    if cli.strip {
+        strip_module(&mut ir_module);
+    }
+
+    // Emit a C header for #[extern_c] functions if requested
+    if cli.emit_header {
+        let header = CCodeGen::new(&get_target_triple(&cli.target), cli.sanitize)
+            .generate_header(&ir_module);
+        let h_path = output.as_ref()
+            .map(|o| o.with_extension("h"))
+            .unwrap_or_else(|| input.with_extension("h"));
+        if let Err(e) = fs::write(&h_path, &header) {
+            eprintln!("Error writing header: {}", e);
             process::exit(1);
         }
-    };
-    println!("  [✓] Generated IR ({} functions)", ir_module.functions.len());
+        println!("  [✓] Wrote header to {}", h_path.display());
+    }
     
     // Emit IR if requested
     if cli.emit_ir {
@@ -295,23 +766,124 @@ fn compile_file(input: &PathBuf, output: Option<PathBuf>, cli: &Cli) {
             println!("  [✓] Wrote IR to {}", ir_path.display());
         }
         println!("\n{}", ir_text);
-        return;
+        report_timings(&mut stats, start, input, cli);
+        return None;
     }
     
+    // Catch IR generation bugs here rather than in the backend
+    if let Err(e) = validate_module(&ir_module) {
+        eprintln!("IR validation error: {}", e);
+        process::exit(1);
+    }
+    
+    // Optimization waits until `aethc link` has every module
+    if cli.lto {
+        let path = output.unwrap_or_else(|| input.with_extension(lto::EXTENSION));
+        write_lto_object(ir_module, link_libraries, &path, cli);
+        report_timings(&mut stats, start, input, cli);
+        return None;
+    }
+
     // 6. Optimization -> Optimized IR
-    if cli.opt_level > 0 {
-        let mut optimizer = Optimizer::new();
-        optimizer.optimize(&mut ir_module);
-        println!("  [✓] Optimized (level {})", cli.opt_level);
+    let optimizer = if cli.opt_level > 0 {
+        Some((Optimizer::new(), format!("Optimized (level {})", cli.opt_level)))
+    } else if cli.strip_dead_code {
+        Some((Optimizer::dead_code_only(), "Removed dead code".to_string()))
+    } else {
+        None
+    };
+    if let Some((optimizer, done)) = optimizer {
+        let optimize_start = Instant::now();
+        let mut optimizer = optimizer.with_verification(cli.verify_ir);
+        let optimized = if cli.timings.is_some() {
+            optimizer.optimize_with_stats(&mut ir_module, &mut stats)
+        } else {
+            optimizer.optimize(&mut ir_module)
+        };
+        if let Err(e) = optimized {
+            eprintln!("IR validation error: {}", e);
+            process::exit(1);
+        }
+        stats.optimize_time_ms = elapsed_ms(optimize_start);
+        if cli.timings.is_some() {
+            stats.record_optimized_functions(&ir_module);
+        }
+        println!("  [✓] {}", done);
+    }
+
+    if cli.emit_dot || cli.dot_open {
+        emit_dot_files(&ir_module, input, cli.dot_open);
     }
     
+    // Libraries from the command line, then those named by `extern ... link = "lib"`
+    let mut linker = Linker::new();
+    for dir in &cli.library_paths {
+        linker.add_library_path(dir);
+    }
+    for lib in cli.link_libraries.iter().chain(&link_libraries) {
+        linker.add_library(lib);
+    }
+
     // 7. Code Generation
+    let built = emit_module(&ir_module, &linker, input, output, mode, &mut stats, cli);
+    report_timings(&mut stats, start, input, cli);
+    built
+}
+
+fn elapsed_ms(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+/// Print `stats` to stderr as `--timings` asks, once every phase has run
+fn report_timings(stats: &mut CompilationStats, start: Instant, input: &Path, cli: &Cli) {
+    let Some(format) = cli.timings else {
+        return;
+    };
+    stats.total_time_ms = elapsed_ms(start);
+    match format {
+        TimingsFormat::Json => eprintln!("{}", serde_json::to_string_pretty(stats).unwrap_or_default()),
+        TimingsFormat::Human if cli.json => {
+            let feedback = CompilationFeedback::success(input.display().to_string(), stats.clone());
+            eprintln!("{}", feedback.to_json_compact());
+        }
+        TimingsFormat::Human => {
+            eprintln!("Timings for {}:", input.display());
+            eprint!("{}", stats.render_table());
+        }
+    }
+}
+
+/// Run the selected backend over an optimized module. Intermediate files are
+/// written next to `input`; returns the path of the linked executable, if any.
+fn emit_module(
+    ir_module: &IRModule,
+    linker: &Linker,
+    input: &Path,
+    output: Option<PathBuf>,
+    mode: CompileMode,
+    stats: &mut CompilationStats,
+    cli: &Cli,
+) -> Option<PathBuf> {
+    if cli.freestanding && (cli.backend != "c" || mode == CompileMode::SharedLib) {
+        eprintln!("Error: --freestanding builds executables, objects and static libraries with the c backend");
+        process::exit(1);
+    }
     match cli.backend.as_str() {
         "c" => {
-            let mut codegen = CCodeGen::new(&cli.target);
+            let toolchain = build_toolchain(cli);
+            let mut codegen = CCodeGen::new(&toolchain.triple, cli.sanitize)
+                .with_toolchain(toolchain.clone())
+                .with_json_panics(cli.json)
+                .with_jobs(cli.jobs())
+                .with_no_main(cli.no_main)
+                .with_freestanding(cli.freestanding, cli.entry())
+                .with_static_internals(cli.emit_c_header);
             
             // Generate C source
-            let c_source = match codegen.generate_source(&ir_module) {
+            let codegen_start = Instant::now();
+            let generated = codegen.generate_source(ir_module);
+            stats.codegen_time_ms = elapsed_ms(codegen_start);
+            let c_source = match generated {
                 Ok(s) => s,
                 Err(e) => {
                     eprintln!("Code generation error: {}", e);
@@ -327,15 +899,26 @@ fn compile_file(input: &PathBuf, output: Option<PathBuf>, cli: &Cli) {
                     process::exit(1);
                 }
                 println!("  [✓] Generated C code: {}", c_path.display());
+                if cli.emit_c_header {
+                    let h_path = c_path.with_extension("h");
+                    if let Err(e) = fs::write(&h_path, codegen.generate_interface_header(ir_module)) {
+                        eprintln!("Error writing header: {}", e);
+                        process::exit(1);
+                    }
+                    println!("  [✓] Generated C header: {}", h_path.display());
+                }
+                None
             } else {
-                // Compile C code to executable
-                let _obj_path = input.with_extension("o");
-                let exe_path = output.unwrap_or_else(|| {
-                    #[cfg(windows)]
-                    { input.with_extension("exe") }
-                    #[cfg(not(windows))]
-                    { input.with_extension("") }
-                });
+                // Compile C code to an executable, object or library
+                let out_path = output.unwrap_or_else(|| mode.default_output(input));
+                // A static library archives the object compiled next to it, and
+                // the self-hosted linker links a freestanding executable from one
+                let freestanding_link = cli.freestanding && mode == CompileMode::Binary;
+                let compiled_path = if mode == CompileMode::StaticLib || freestanding_link {
+                    out_path.with_extension("o")
+                } else {
+                    out_path.clone()
+                };
                 
                 // Write C source
                 let c_path = input.with_extension("c");
@@ -344,33 +927,63 @@ fn compile_file(input: &PathBuf, output: Option<PathBuf>, cli: &Cli) {
                     process::exit(1);
                 }
                 
-                // Compile with clang/gcc
-                let compilers = ["clang", "gcc", "cc"];
+                // Compile with clang/gcc, or the cross-compiler for a foreign target
                 let mut compiled = false;
+                let mut last_stderr = None;
                 
-                for compiler in &compilers {
+                for compiler in &toolchain.compilers() {
                     let mut cmd = std::process::Command::new(compiler);
+                    cmd.args(toolchain.compiler_args(compiler));
+                    cmd.args(cli.sanitize.compiler_args());
+                    if cli.debug_info && !cli.strip {
+                        cmd.arg("-g");
+                    }
+                    cmd.args(mode.compiler_args());
+                    if cli.freestanding {
+                        cmd.args(backend::static_link::FREESTANDING_CFLAGS);
+                    }
+                    if freestanding_link {
+                        cmd.arg("-c");
+                    }
+                    if cli.strip && matches!(mode, CompileMode::Binary | CompileMode::SharedLib) {
+                        cmd.arg("-s");
+                    }
                     cmd.args(&["-o"])
-                        .arg(&exe_path)
+                        .arg(&compiled_path)
                         .arg(&c_path);
+                    if matches!(mode, CompileMode::Binary | CompileMode::SharedLib) && !freestanding_link {
+                        cmd.args(linker.link_args());
+                    }
                     
                     // Add linker script if provided
-                    if let Some(ref ld_script) = cli.linker_script {
+                    if let (Some(ref ld_script), CompileMode::Binary) = (&cli.linker_script, mode) {
                         cmd.arg("-T").arg(ld_script);
                         cmd.arg("-nostdlib"); // Usually needed with custom linker scripts
                     }
                     
+                    let compile_start = Instant::now();
                     let result = cmd.output();
+                    stats.c_compiler_time_ms += elapsed_ms(compile_start);
                     
                     if let Ok(output) = result {
                         if output.status.success() {
                             compiled = true;
                             println!("  [✓] Compiled with {}", compiler);
-                            if cli.linker_script.is_some() {
+                            if toolchain.is_cross() {
+                                println!("  [✓] Cross-compiled for {}", toolchain.triple);
+                            }
+                            if cli.linker_script.is_some() && mode == CompileMode::Binary {
                                 println!("  [✓] Using custom linker script");
                             }
+                            if !cli.sanitize.is_empty() {
+                                println!("  [✓] Sanitizers: {}", cli.sanitize.names().join(", "));
+                            }
+                            if !linker.link_args().is_empty() && matches!(mode, CompileMode::Binary | CompileMode::SharedLib) {
+                                println!("  [✓] Linked {}", linker.link_args().join(" "));
+                            }
                             break;
                         }
+                        last_stderr = Some(String::from_utf8_lossy(&output.stderr).into_owned());
                     }
                 }
                 
@@ -378,28 +991,75 @@ fn compile_file(input: &PathBuf, output: Option<PathBuf>, cli: &Cli) {
                 let _ = fs::remove_file(&c_path);
                 
                 if !compiled {
-                    eprintln!("Error: Could not find C compiler (clang/gcc)");
+                    if toolchain.is_cross() {
+                        eprintln!("Error: Could not cross-compile for {} (tried {})",
+                            toolchain.triple, toolchain.compilers().join(", "));
+                        process::exit(1);
+                    }
+                    // A compiler ran and rejected the code; show why instead of blaming the toolchain
+                    match last_stderr {
+                        Some(stderr) if !linker.link_args().is_empty() => eprintln!("Error: Linking failed:\n{}", stderr),
+                        Some(stderr) => eprintln!("Error: C compilation failed:\n{}", stderr),
+                        None => eprintln!("Error: Could not find C compiler (clang/gcc)"),
+                    }
                     process::exit(1);
                 }
+
+                if freestanding_link {
+                    let linked = fs::read(&compiled_path)
+                        .map_err(|e| Error::Io(format!("{}: {}", compiled_path.display(), e)))
+                        .and_then(|object| backend::static_link::link_objects(
+                            &[(compiled_path.display().to_string(), object)], cli.entry(), &out_path, cli.link_map.as_deref(),
+                        ));
+                    let _ = fs::remove_file(&compiled_path);
+                    if let Err(e) = linked {
+                        eprintln!("Error: {}", e);
+                        process::exit(1);
+                    }
+                    println!("  [✓] Linked with the self-hosted linker");
+                }
+
+                if mode == CompileMode::StaticLib {
+                    let _ = fs::remove_file(&out_path);
+                    let archived = std::process::Command::new("ar")
+                        .arg("rcs").arg(&out_path).arg(&compiled_path)
+                        .output();
+                    let _ = fs::remove_file(&compiled_path);
+                    match archived {
+                        Ok(out) if out.status.success() => println!("  [✓] Archived with ar"),
+                        Ok(out) => {
+                            eprintln!("Error: ar failed:\n{}", String::from_utf8_lossy(&out.stderr));
+                            process::exit(1);
+                        }
+                        Err(e) => {
+                            eprintln!("Error: could not run ar: {}", e);
+                            process::exit(1);
+                        }
+                    }
+                }
                 
-                println!("\n✅ Output: {}", exe_path.display());
+                println!("\n✅ Output: {}", out_path.display());
+                (mode == CompileMode::Binary).then_some(out_path)
             }
         }
         #[cfg(feature = "llvm")]
         "llvm" => {
-            use backend::llvm::LLVMCodeGen;
+            use backend::{codegen::CodeGen, llvm::LLVMCodeGen};
+            if matches!(mode, CompileMode::StaticLib | CompileMode::SharedLib) {
+                eprintln!("Error: the llvm backend does not build libraries yet; use --backend c");
+                process::exit(1);
+            }
             // Get target triple from CLI or auto-detect native
             let target_triple = get_target_triple(&cli.target);
-            let mut codegen = LLVMCodeGen::new(&target_triple);
+            let mut codegen = LLVMCodeGen::new(&target_triple, cli.sanitize).with_strip(cli.strip);
             
-            match codegen.generate(&ir_module) {
+            let codegen_start = Instant::now();
+            let generated = codegen.generate(ir_module);
+            stats.codegen_time_ms = elapsed_ms(codegen_start);
+            match generated {
                 Ok(bytes) => {
-                    let obj_path = output.unwrap_or_else(|| input.with_extension("o"));
-                    if let Err(e) = fs::write(&obj_path, &bytes) {
-                        eprintln!("Error writing object file: {}", e);
-                        process::exit(1);
-                    }
-                    println!("  [✓] Generated object file: {}", obj_path.display());
+                    let runtime = backend::runtime::standalone_runtime(ir_module, cli.json);
+                    link_llvm_object(&bytes, runtime, linker, input, output, mode, cli.strip)
                 }
                 Err(e) => {
                     eprintln!("LLVM code generation error: {}", e);
@@ -407,41 +1067,523 @@ fn compile_file(input: &PathBuf, output: Option<PathBuf>, cli: &Cli) {
                 }
             }
         }
+        "native" => {
+            if mode != CompileMode::Binary {
+                eprintln!("Error: the native backend only builds executables; use --backend c");
+                process::exit(1);
+            }
+            let script = cli.linker_script.as_deref().map(|path| {
+                backend::linker_script::LinkerScript::load(path).unwrap_or_else(|e| {
+                    eprintln!("Error: {}: {}", path.display(), e);
+                    process::exit(1);
+                })
+            });
+            let out_path = output.unwrap_or_else(|| mode.default_output(input));
+            let codegen_start = Instant::now();
+            let linked = backend::native::link_executable(ir_module, &out_path, script.as_ref(), cli.link_map.as_deref());
+            stats.codegen_time_ms = elapsed_ms(codegen_start);
+            if let Err(e) = linked {
+                eprintln!("Native code generation error: {}", e);
+                process::exit(1);
+            }
+            println!("  [✓] Linked with the self-hosted linker");
+            if let Some(map) = &cli.link_map {
+                println!("  [✓] Link map: {}", map.display());
+            }
+            println!("\n✅ Output: {}", out_path.display());
+            Some(out_path)
+        }
         _ => {
-            eprintln!("Unknown backend: {}. Use 'c' or 'llvm'", cli.backend);
+            eprintln!("Unknown backend: {}. Use 'c', 'llvm' or 'native'", cli.backend);
             process::exit(1);
         }
     }
 }
 
-/// Check a source file for errors without generating code
-fn check_file(input: &PathBuf) {
-    println!("Checking: {}", input.display());
-    
+/// Write an object file produced by the LLVM backend, compile the C runtime
+/// it needs next to it and link both into an executable when `mode` asks for one
+#[cfg(feature = "llvm")]
+fn link_llvm_object(
+    bytes: &[u8],
+    runtime: Option<String>,
+    linker: &Linker,
+    input: &Path,
+    output: Option<PathBuf>,
+    mode: CompileMode,
+    strip: bool,
+) -> Option<PathBuf> {
+    // Libraries to link: emit the object next to the input and link an executable
+    let links = mode == CompileMode::Binary && !linker.link_args().is_empty();
+    let obj_path = if links {
+        input.with_extension("o")
+    } else {
+        output.clone().unwrap_or_else(|| input.with_extension("o"))
+    };
+    if let Err(e) = fs::write(&obj_path, bytes) {
+        eprintln!("Error writing object file: {}", e);
+        process::exit(1);
+    }
+    println!("  [✓] Generated object file: {}", obj_path.display());
+
+    // The runtime is C; compile it to an object linked next to ours
+    let mut objects = vec![obj_path.clone()];
+    if let Some(runtime) = runtime {
+        let rt_source = obj_path.with_extension("rt.c");
+        let rt_object = obj_path.with_extension("rt.o");
+        let built = fs::write(&rt_source, runtime).is_ok()
+            && std::process::Command::new("cc")
+                .arg("-c").arg("-o").arg(&rt_object).arg(&rt_source)
+                .status()
+                .is_ok_and(|status| status.success());
+        let _ = fs::remove_file(&rt_source);
+        if !built {
+            eprintln!("Error: could not compile the runtime");
+            process::exit(1);
+        }
+        println!("  [✓] Generated runtime: {}", rt_object.display());
+        objects.push(rt_object);
+    }
+    if !links {
+        return None;
+    }
+
+    let exe_path = output.unwrap_or_else(|| input.with_extension(""));
+    let mut cmd = linker.system_link_command("cc", &objects, &exe_path);
+    if strip {
+        cmd.arg("-s");
+    }
+    match cmd.output() {
+        Ok(out) if out.status.success() => {
+            println!("  [✓] Linked {}", linker.link_args().join(" "));
+            println!("\n✅ Output: {}", exe_path.display());
+            Some(exe_path)
+        }
+        Ok(out) => {
+            eprintln!("Error: linking failed:\n{}", String::from_utf8_lossy(&out.stderr));
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Error: could not run the system linker: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Print a compile error in `locale`; with `--json`, as one JSON line
+fn report_error(cli: &Cli, stage: &str, error: &Error, file: &Path) {
+    let report = ErrorReport::from_error_localized(error, &file.to_string_lossy(), cli.locale);
+    print_error_report(cli, stage, &report, None);
+}
+
+/// Print a session diagnostic that stopped `stage`; with `--json`, as one
+/// JSON line. rustc-style lines point into `source`, the text the report's
+/// offsets refer to.
+fn print_error_report(cli: &Cli, stage: &str, report: &ErrorReport, source: Option<&str>) {
+    if cli.error_format.is_json() {
+        eprintln!("{}", report.to_rustc_json(source, cli.error_format));
+    } else if cli.json {
+        eprintln!("{}", serde_json::to_string(report).unwrap_or_default());
+    } else {
+        let color = cli.color();
+        let severity = Style::Severity(report.severity);
+        eprintln!("{} {}: {}", stage, color.paint(severity, "error"), color.paint(Style::Bold, &report.message.to_string()));
+    }
+}
+
+/// Print where `report` points in `source` and its help, as rustc renders
+/// them below the message
+fn print_source_snippet(report: &ErrorReport, source: Option<&str>, color: ColorConfig) {
+    let rendered = report.render_colored(source, ErrorFormat::Human, color);
+    eprint!("{}", rendered.split_once('\n').map_or("", |(_, rest)| rest));
+}
+
+/// Print what `--print-types` and `--print-symbols` asked for
+fn print_inspections(result: &CompileResult) {
+    if let Some(ref types) = result.types {
+        print!("\nTypes:\n{}", types);
+    }
+    if let Some(ref symbols) = result.symbols {
+        print!("\nSymbols:\n{}", symbols);
+    }
+}
+
+/// Print the diagnostics of a session; exit if it failed, or if it has
+/// warnings and `--deny-warnings` is set
+fn report_diagnostics(result: &CompileResult, cli: &Cli) {
+    // Reports of a script are mapped back into it from the transpiled source
+    let source = result.diagnostics.first()
+        .and_then(|d| std::fs::read_to_string(&d.location.as_ref()?.file).ok())
+        .or_else(|| result.transpiled.clone());
+    let source = source.as_deref();
+    // The author of a script never saw the Core, so show where in the script
+    let show_location = result.source_map.is_some() && !cli.json && !cli.error_format.is_json();
+    let warnings: Vec<&ErrorReport> = result.warnings().collect();
+    for warning in &warnings {
+        if cli.deny_warnings {
+            let mut report = (*warning).clone();
+            report.severity = Severity::Error;
+            print_error_report(cli, "Lint", &report, source);
+        } else if cli.error_format.is_json() {
+            eprintln!("{}", warning.to_rustc_json(source, cli.error_format));
+        } else if cli.json {
+            eprintln!("{}", serde_json::to_string(warning).unwrap_or_default());
+        } else {
+            let color = cli.color();
+            let label = color.paint(Style::Severity(Severity::Warning), "Warning");
+            eprintln!("  [!] {}[{}]: {}", label, color.paint(Style::Bold, &warning.code), warning.message);
+        }
+        if show_location {
+            print_source_snippet(warning, source, cli.color());
+        }
+    }
+    if let Some(stage) = result.failed_stage {
+        for error in result.errors() {
+            print_error_report(cli, &stage.to_string(), error, source);
+            if show_location {
+                print_source_snippet(error, source, cli.color());
+            }
+        }
+        process::exit(1);
+    }
+    if cli.deny_warnings && !warnings.is_empty() {
+        eprintln!("Error: {} warning(s) treated as errors (--deny-warnings)", warnings.len());
+        process::exit(1);
+    }
+}
+
+/// Session for compiling `input` with the options given on the command line
+fn session_from_cli(input: &Path, cli: &Cli) -> CompileSession {
+    let mut session = CompileSession::from_path(input)
+        .with_opt_level(cli.opt_level)
+        .with_backend(&cli.backend)
+        .with_toolchain(build_toolchain(cli))
+        .with_panic_mode(cli.panic)
+        .with_overflow_mode(cli.overflow_mode())
+        .with_max_errors(cli.max_errors)
+        .with_max_nesting(cli.max_nesting)
+        .with_jobs(cli.jobs())
+        .with_locale(cli.locale)
+        .with_debug_info(cli.debug_info)
+        .with_verify_ir(cli.verify_ir)
+        .with_strip_dead_code(cli.strip_dead_code)
+        .with_sanitizers(cli.sanitize)
+        .with_json_panics(cli.json)
+        .with_timings(cli.timings.is_some())
+        .with_print_types(cli.print_types)
+        .with_print_symbols(cli.print_symbols);
+    if let Some(strict) = cli.strictness() {
+        session = session.with_strict(strict);
+    }
+    if let Some(ref rules) = cli.transform_rules {
+        session = session.with_transform_rules(rules);
+    }
+    if cli.freestanding {
+        session = session.with_freestanding(cli.entry());
+    }
+    session
+}
+
+/// Build a source file into a temporary executable, run it with `args`
+/// and exit with the program's status. With `bench` the executable is the
+/// benchmark harness instead of the program's `main`.
+fn run_file(input: &Path, args: &[String], bench: Option<&BenchConfig>, cli: &Cli) {
+    let stem = input.file_stem().and_then(|s| s.to_str()).unwrap_or("program");
+    let exe_name = format!("aether_run_{}_{}", stem, process::id());
+    #[cfg(windows)]
+    let exe_name = format!("{}.exe", exe_name);
+    let exe_path = std::env::temp_dir().join(exe_name);
+
+    let Some(exe_path) = compile_file(input, Some(exe_path), CompileMode::Binary, bench, cli) else {
+        let command = if bench.is_some() { "bench" } else { "run" };
+        eprintln!("Error: `{}` needs an executable (use the C backend without --emit-c/--emit-ir/--lto)", command);
+        process::exit(1);
+    };
+
+    let status = std::process::Command::new(&exe_path).args(args).status();
+    let _ = fs::remove_file(&exe_path);
+    match status {
+        // A program killed by a signal has no exit code
+        Ok(status) => process::exit(status.code().unwrap_or(1)),
+        Err(e) => {
+            eprintln!("Error running {}: {}", exe_path.display(), e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Print `source` annotated with the hit counts in `data`, optionally writing LCOV
+fn cov_report(data: &PathBuf, source: &PathBuf, lcov: Option<&PathBuf>) {
+    let read = |path: &PathBuf| match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("Error reading {}: {}", path.display(), e);
+            process::exit(1);
+        }
+    };
+    let coverage = match CoverageData::parse(&read(data)) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{}: {}", data.display(), e);
+            process::exit(1);
+        }
+    };
+    print!("{}", coverage.annotate(&read(source)));
+    if let Some(path) = lcov {
+        if let Err(e) = fs::write(path, coverage.to_lcov(&source.to_string_lossy())) {
+            eprintln!("Error writing LCOV: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Rewrite every occurrence of `old` as `new` in `input` and its imported modules
+fn rename_file(input: &PathBuf, old: &str, new: &str) {
     let source = match fs::read_to_string(input) {
-        Ok(s) => s,
+        Ok(text) => text,
         Err(e) => {
-            eprintln!("Error reading file: {}", e);
+            eprintln!("Error reading {}: {}", input.display(), e);
             process::exit(1);
         }
     };
-    
-    let lexer = Lexer::new(&source, 0);
-    let mut parser = AethParser::new(lexer);
-    
-    let program = match parser.parse_program() {
-        Ok(p) => p,
+    let renaming = match lsp::rename::rename_symbol(input, &source, old, new) {
+        Ok(renaming) => renaming,
         Err(e) => {
-            eprintln!("Parse error: {}", e);
+            eprintln!("error: {}", e);
             process::exit(1);
         }
     };
-    
-    let mut analyzer = SemanticAnalyzer::new();
-    if let Err(e) = analyzer.analyze(&program) {
-        eprintln!("Semantic error: {}", e);
+    for file in &renaming.files {
+        if file.spans.is_empty() {
+            continue;
+        }
+        if let Err(e) = fs::write(&file.path, file.apply(new)) {
+            eprintln!("Error writing {}: {}", file.path.display(), e);
+            process::exit(1);
+        }
+        println!("{}: {} occurrence(s)", file.path.display(), file.spans.len());
+    }
+    println!("Renamed `{}` to `{}` ({} occurrence(s))", old, new, renaming.occurrences());
+}
+
+/// Parse `input` and write its documentation into `output_dir`
+fn doc_file(input: &Path, output_dir: &Path, format: DocFormat, private_items: bool) {
+    let source = match fs::read_to_string(input) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("Error reading {}: {}", input.display(), e);
+            process::exit(1);
+        }
+    };
+    let program = match frontend::parser::Parser::new(frontend::lexer::Lexer::new(&source, 0)).parse_program() {
+        Ok(program) => program,
+        Err(e) => {
+            let lines = utils::SourceLines::new(&input.display().to_string(), &source);
+            let (file, line, column) = lines.locate(e.span().unwrap_or_default());
+            eprintln!("{}:{}:{}: error: {}", file, line, column, e);
+            process::exit(1);
+        }
+    };
+    let generator = DocGenerator::new().with_private_items(private_items);
+    let written = generator.document_program(input, &program)
+        .and_then(|database| generator.write(&database, output_dir, format));
+    match written {
+        Ok(files) => {
+            for file in &files {
+                println!("Wrote {}", file.display());
+            }
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Read IR text, run the optimizer at -O<level>, and print the resulting IR
+fn opt_file(input: &PathBuf, output: Option<&PathBuf>, cli: &Cli) {
+    let text = match fs::read_to_string(input) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("Error reading {}: {}", input.display(), e);
+            process::exit(1);
+        }
+    };
+    let mut module = match parse_ir(&text) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("{}: {}", input.display(), e);
+            process::exit(1);
+        }
+    };
+    if let Err(e) = validate_module(&module) {
+        eprintln!("IR validation error: {}", e);
         process::exit(1);
     }
-    
+    if cli.opt_level > 0 {
+        let mut optimizer = Optimizer::new().with_verification(cli.verify_ir);
+        if let Err(e) = optimizer.optimize(&mut module) {
+            eprintln!("IR validation error: {}", e);
+            process::exit(1);
+        }
+    }
+
+    let ir_text = print_ir(&module);
+    match output {
+        Some(path) => {
+            if let Err(e) = fs::write(path, &ir_text) {
+                eprintln!("Error writing IR: {}", e);
+                process::exit(1);
+            }
+        }
+        None => print!("{}", ir_text),
+    }
+}
+
+/// Write `module` for `aethc link`: an `.alito` object, or bitcode with the LLVM backend
+fn write_lto_object(module: IRModule, libraries: Vec<String>, path: &Path, cli: &Cli) {
+    let written = match cli.backend.as_str() {
+        "c" => fs::write(path, lto::LtoObject::new(module, libraries).to_bytes())
+            .map_err(|e| Error::Io(e.to_string())),
+        #[cfg(feature = "llvm")]
+        "llvm" => backend::llvm::LLVMCodeGen::new(&get_target_triple(&cli.target), cli.sanitize)
+            .write_bitcode(&module, &libraries, path),
+        _ => {
+            eprintln!("Unknown backend: {}. Use 'c' or 'llvm'", cli.backend);
+            process::exit(1);
+        }
+    };
+    if let Err(e) = written {
+        eprintln!("Error writing {}: {}", path.display(), e);
+        process::exit(1);
+    }
+    println!("  [✓] Wrote LTO object: {}", path.display());
+}
+
+/// Merge objects written by `--lto`, optimize the whole program and build `output`
+fn link_files(inputs: &[PathBuf], output: &Path, cli: &Cli) {
+    println!("AetherLang Compiler v0.1.0");
+    println!("Linking: {} objects", inputs.len());
+
+    let mut linker = Linker::new();
+    for dir in &cli.library_paths {
+        linker.add_library_path(dir);
+    }
+    for lib in &cli.link_libraries {
+        linker.add_library(lib);
+    }
+
+    #[cfg(feature = "llvm")]
+    if cli.backend == "llvm" {
+        use middle::ir::{IRFunction, IRType, Instruction};
+        let mut codegen = backend::llvm::LLVMCodeGen::new(&get_target_triple(&cli.target), cli.sanitize);
+        let linked = match codegen.link_bitcode(inputs) {
+            Ok(linked) => linked,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        };
+        println!("  [✓] Linked and optimized bitcode");
+        for lib in &linked.libraries {
+            linker.add_library(lib);
+        }
+        // The runtime pieces needed are those the program still calls
+        let mut calls = IRFunction::new("calls", Vec::new(), IRType::Void);
+        let entry = calls.add_block("entry");
+        for func in linked.undefined {
+            calls.get_block_mut(entry).unwrap().push(Instruction::Call { dest: None, func, args: Vec::new() });
+        }
+        let mut stub = IRModule::new("linked");
+        stub.functions.push(calls);
+        let runtime = backend::runtime::standalone_runtime(&stub, cli.json);
+        link_llvm_object(&linked.object, runtime, &linker, output, Some(output.to_path_buf()), CompileMode::Binary, cli.strip);
+        return;
+    }
+
+    let mut objects = Vec::new();
+    for input in inputs {
+        let object = fs::read(input)
+            .map_err(|e| Error::Io(e.to_string()))
+            .and_then(|bytes| lto::LtoObject::from_bytes(&bytes));
+        match object {
+            Ok(object) => objects.push(object),
+            Err(e) => {
+                eprintln!("Error reading {}: {}", input.display(), e);
+                process::exit(1);
+            }
+        }
+    }
+    let name = output.file_stem().and_then(|s| s.to_str()).unwrap_or("module");
+    let linked = match lto::link(objects, name) {
+        Ok(linked) => linked,
+        Err(e) => {
+            report_error(cli, "Link", &e, output);
+            process::exit(1);
+        }
+    };
+    let mut module = linked.module;
+    println!("  [✓] Merged {} modules ({} functions)", inputs.len(), module.functions.len());
+    if cli.strip {
+        strip_module(&mut module);
+    }
+    for lib in &linked.libraries {
+        linker.add_library(lib);
+    }
+
+    if let Err(e) = validate_module(&module) {
+        eprintln!("IR validation error: {}", e);
+        process::exit(1);
+    }
+    let mut optimizer = Optimizer::new().with_verification(cli.verify_ir);
+    if let Err(e) = optimizer.optimize(&mut module) {
+        eprintln!("IR validation error: {}", e);
+        process::exit(1);
+    }
+    println!("  [✓] Optimized across modules");
+
+    if cli.emit_ir {
+        println!("\n{}", print_ir(&module));
+        return;
+    }
+
+    emit_module(&module, &linker, output, Some(output.to_path_buf()), CompileMode::Binary, &mut CompilationStats::default(), cli);
+}
+
+/// Write the CFG of every function next to `input`, optionally rendering and
+/// opening each one
+fn emit_dot_files(module: &IRModule, input: &Path, open: bool) {
+    let stem = input.file_stem().and_then(|s| s.to_str()).unwrap_or("module");
+    for func in &module.functions {
+        let dot_path = input.with_file_name(format!("{}_{}.dot", stem, func.name));
+        if let Err(e) = fs::write(&dot_path, function_to_dot(func)) {
+            eprintln!("Error writing {}: {}", dot_path.display(), e);
+            process::exit(1);
+        }
+        if open {
+            let svg_path = dot_path.with_extension("svg");
+            let rendered = process::Command::new("dot")
+                .arg("-Tsvg").arg(&dot_path).arg("-o").arg(&svg_path)
+                .status();
+            if !matches!(rendered, Ok(status) if status.success()) {
+                eprintln!("  [!] Could not render {} (is Graphviz installed?)", dot_path.display());
+                continue;
+            }
+            let viewer = if cfg!(target_os = "macos") { "open" } else { "xdg-open" };
+            if let Err(e) = process::Command::new(viewer).arg(&svg_path).spawn() {
+                eprintln!("  [!] Could not open {}: {}", svg_path.display(), e);
+            }
+        }
+    }
+    println!("  [✓] Wrote {} control flow graphs", module.functions.len());
+}
+
+/// Check a source file for errors without generating code
+fn check_file(input: &Path, cli: &Cli) {
+    println!("Checking: {}", input.display());
+    let start = Instant::now();
+    let mut result = session_from_cli(input, cli).check();
+    print_inspections(&result);
+    report_diagnostics(&result, cli);
     println!("✅ No errors found");
+    report_timings(&mut result.stats, start, input, cli);
 }
@@ -0,0 +1,163 @@
+//! Microbenchmark harness (`aethc bench`)
+//!
+//! In benchmark mode the program's `main` is replaced by a harness that calls
+//! each benchmarked function `iterations` times, reads
+//! `clock_gettime(CLOCK_MONOTONIC)` around every call, and prints the per-call
+//! time in criterion's layout:
+//!
+//! ```text
+//! fib                     time:   [1.2040 µs 1.2113 µs 1.2186 µs]
+//!                         std. dev.: 36.881 ns (1000 iterations)
+//! ```
+//!
+//! The bracket holds the lower bound, mean and upper bound of the 95%
+//! confidence interval of the mean. Functions are benchmarked if named on the
+//! command line or, by default, if annotated `#[bench]`; they must take no
+//! parameters. Integer results go through a black box so the optimizer keeps
+//! the call.
+
+use crate::middle::ir::{
+    BinOp, Constant, IRFunction, IRModule, IRType, Instruction, Register, Terminator, Value,
+};
+
+/// `__aeth_bench_now() -> i64`, monotonic time in nanoseconds
+pub const NOW_FN: &str = "__aeth_bench_now";
+/// `__aeth_bench_sample(ns: i64)`, record one call's duration
+pub const SAMPLE_FN: &str = "__aeth_bench_sample";
+/// `__aeth_bench_black_box(value: i64)`, keep a result alive
+pub const BLACK_BOX_FN: &str = "__aeth_bench_black_box";
+/// `__aeth_bench_report(name: *u8)`, print and reset the recorded samples
+pub const REPORT_FN: &str = "__aeth_bench_report";
+
+/// What `aethc bench` measures
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchConfig {
+    /// Function to benchmark; `None` selects every `#[bench]` function
+    pub function: Option<String>,
+    /// Calls per function
+    pub iterations: u64,
+}
+
+/// Parameter and return types of a benchmark runtime function
+pub fn signature(func: &str) -> Option<(Vec<IRType>, IRType)> {
+    Some(match func {
+        NOW_FN => (vec![], IRType::I64),
+        SAMPLE_FN | BLACK_BOX_FN => (vec![IRType::I64], IRType::Void),
+        REPORT_FN => (vec![IRType::Ptr(Box::new(IRType::U8))], IRType::Void),
+        _ => return None,
+    })
+}
+
+/// Whether `module` calls into the benchmark runtime
+pub fn uses_runtime(module: &IRModule) -> bool {
+    module.functions.iter()
+        .flat_map(|f| &f.blocks)
+        .flat_map(|b| &b.instructions)
+        .any(|inst| matches!(inst, Instruction::Call { func, .. } if signature(func).is_some()))
+}
+
+/// Build `main`, timing each `(name, function)` in turn
+pub fn harness(targets: &[(String, &IRFunction)], iterations: u64) -> IRFunction {
+    let mut main = IRFunction::new("main", Vec::new(), IRType::I32);
+    let mut next_reg = 0;
+    let mut reg = || {
+        next_reg += 1;
+        Register(next_reg - 1)
+    };
+    let mut current = main.add_block("entry");
+
+    for (name, func) in targets {
+        let counter = reg();
+        let cond_block = main.add_block("bench_cond");
+        let body_block = main.add_block("bench_body");
+        let done_block = main.add_block("bench_done");
+
+        let block = &mut main.blocks[current.0];
+        block.push(Instruction::Assign { dest: counter, value: Value::Constant(Constant::Int(0)) });
+        block.set_terminator(Terminator::Jump { target: cond_block });
+
+        let cond = reg();
+        let block = &mut main.blocks[cond_block.0];
+        block.push(Instruction::BinOp {
+            dest: cond,
+            op: BinOp::Lt,
+            left: Value::Register(counter),
+            right: Value::Constant(Constant::Int(iterations as i64)),
+        });
+        block.set_terminator(Terminator::Branch {
+            cond: Value::Register(cond),
+            then_target: body_block,
+            else_target: done_block,
+        });
+
+        let (start, end, elapsed, next) = (reg(), reg(), reg(), reg());
+        let keeps_result = matches!(
+            func.ret_type,
+            IRType::Bool | IRType::I8 | IRType::I16 | IRType::I32 | IRType::I64
+                | IRType::U8 | IRType::U16 | IRType::U32 | IRType::U64
+        );
+        let result = keeps_result.then(|| (reg(), reg()));
+        let block = &mut main.blocks[body_block.0];
+        block.push(Instruction::Call { dest: Some(start), func: NOW_FN.to_string(), args: vec![] });
+        block.push(Instruction::Call { dest: result.map(|(r, _)| r), func: func.name.clone(), args: vec![] });
+        block.push(Instruction::Call { dest: Some(end), func: NOW_FN.to_string(), args: vec![] });
+        if let Some((result, widened)) = result {
+            block.push(Instruction::Cast { dest: widened, value: Value::Register(result), ty: IRType::I64 });
+            block.push(Instruction::Call {
+                dest: None,
+                func: BLACK_BOX_FN.to_string(),
+                args: vec![Value::Register(widened)],
+            });
+        }
+        block.push(Instruction::BinOp {
+            dest: elapsed,
+            op: BinOp::Sub,
+            left: Value::Register(end),
+            right: Value::Register(start),
+        });
+        block.push(Instruction::Call { dest: None, func: SAMPLE_FN.to_string(), args: vec![Value::Register(elapsed)] });
+        block.push(Instruction::BinOp {
+            dest: next,
+            op: BinOp::Add,
+            left: Value::Register(counter),
+            right: Value::Constant(Constant::Int(1)),
+        });
+        block.push(Instruction::Assign { dest: counter, value: Value::Register(next) });
+        block.set_terminator(Terminator::Jump { target: cond_block });
+
+        main.blocks[done_block.0].push(Instruction::Call {
+            dest: None,
+            func: REPORT_FN.to_string(),
+            args: vec![Value::Constant(Constant::String(name.clone()))],
+        });
+        current = done_block;
+    }
+
+    main.blocks[current.0].set_terminator(Terminator::Return { value: Some(Value::Constant(Constant::Int(0))) });
+    main
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_harness_times_each_target() {
+        let fib = IRFunction::new("fib", Vec::new(), IRType::I64);
+        let noop = IRFunction::new("noop", Vec::new(), IRType::Void);
+        let main = harness(&[("fib".to_string(), &fib), ("noop".to_string(), &noop)], 10);
+
+        let calls: Vec<&str> = main.blocks.iter()
+            .flat_map(|b| &b.instructions)
+            .filter_map(|inst| match inst {
+                Instruction::Call { func, .. } => Some(func.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(calls, vec![
+            NOW_FN, "fib", NOW_FN, BLACK_BOX_FN, SAMPLE_FN, REPORT_FN,
+            NOW_FN, "noop", NOW_FN, SAMPLE_FN, REPORT_FN,
+        ]);
+        assert!(matches!(main.blocks.last().unwrap().terminator, Some(Terminator::Return { .. })));
+    }
+}
@@ -0,0 +1,59 @@
+//! Builtin collections: `Vec<T>` and `HashMap<K, V>`
+//!
+//! Both are heap handles (`vec<T>` and `map<K, V>` in the IR) whose storage
+//! is managed by the runtime in `backend::runtime`. Elements are copied in and
+//! out by address, so every operation that touches an element passes its
+//! size; the IR generator knows it from the instantiated element type.
+//!
+//! Growth doubles the capacity through `realloc`, starting from the one
+//! given to `Vec::with_capacity`. `get` and `pop` return an `Option`, while
+//! out-of-range indexing and missing map keys panic. Memory is released
+//! explicitly with `free()`.
+
+use crate::middle::ir::{IRModule, Instruction};
+
+pub const VEC_NEW: &str = "__aeth_vec_new";
+/// `with_capacity(cap)`; the buffer is allocated by the first push
+pub const VEC_WITH_CAPACITY: &str = "__aeth_vec_with_capacity";
+/// `push(v, *elem, elem_size)`
+pub const VEC_PUSH: &str = "__aeth_vec_push";
+/// `pop(v, elem_size) -> *elem`; callers check that `v` is not empty
+pub const VEC_POP: &str = "__aeth_vec_pop";
+/// `get(v, index, elem_size) -> *elem`; callers check the index
+pub const VEC_GET: &str = "__aeth_vec_get";
+pub const VEC_LEN: &str = "__aeth_vec_len";
+pub const VEC_CLEAR: &str = "__aeth_vec_clear";
+pub const VEC_FREE: &str = "__aeth_vec_free";
+
+pub const MAP_NEW: &str = "__aeth_map_new";
+/// `insert(m, *key, key_kind, *value, value_size)`
+pub const MAP_INSERT: &str = "__aeth_map_insert";
+/// `get(m, *key, key_kind) -> *value`; panics when the key is missing
+pub const MAP_GET: &str = "__aeth_map_get";
+pub const MAP_CONTAINS: &str = "__aeth_map_contains";
+pub const MAP_REMOVE: &str = "__aeth_map_remove";
+pub const MAP_LEN: &str = "__aeth_map_len";
+pub const MAP_FREE: &str = "__aeth_map_free";
+
+/// How the runtime hashes and compares a map key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyKind {
+    /// Any integer, widened to `i64`
+    Int = 0,
+    /// `str`, compared by content
+    Str = 1,
+}
+
+/// All collection runtime functions
+pub const RUNTIME_FNS: [&str; 15] = [
+    VEC_NEW, VEC_WITH_CAPACITY, VEC_PUSH, VEC_POP, VEC_GET, VEC_LEN, VEC_CLEAR, VEC_FREE,
+    MAP_NEW, MAP_INSERT, MAP_GET, MAP_CONTAINS, MAP_REMOVE, MAP_LEN, MAP_FREE,
+];
+
+/// Whether `module` calls into the collection runtime
+pub fn uses_runtime(module: &IRModule) -> bool {
+    module.functions.iter()
+        .flat_map(|f| &f.blocks)
+        .flat_map(|b| &b.instructions)
+        .any(|inst| matches!(inst, Instruction::Call { func, .. } if RUNTIME_FNS.contains(&func.as_str())))
+}
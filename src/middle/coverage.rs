@@ -0,0 +1,191 @@
+//! Block coverage instrumentation (`--coverage`)
+//!
+//! With coverage enabled every basic block starts with
+//! `call __aeth_cov_hit(<block id>)`, where the id indexes the module-wide
+//! counter array `__aeth_cov_counters`. `__aeth_cov_map` records the source
+//! line and column each block was lowered from. When the program exits the
+//! runtime calls `__aeth_cov_dump(path)`, which writes one line per block:
+//!
+//! ```text
+//! aethcov 1
+//! <line> <col> <hits>
+//! ```
+//!
+//! `aethc cov-report` reads that file back, annotates the source with hit
+//! counts and can convert it to LCOV for existing coverage tools.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::middle::ir::{Constant, IRFunction, Instruction, Value};
+use crate::utils::{Error, Result, SourceLines, Span};
+
+/// Runtime function incrementing a block's counter
+pub const HIT_FN: &str = "__aeth_cov_hit";
+/// Runtime function writing the counters to a file
+pub const DUMP_FN: &str = "__aeth_cov_dump";
+/// Counter array, one `u64` per instrumented block
+pub const COUNTERS: &str = "__aeth_cov_counters";
+/// Source location of each block
+pub const MAP: &str = "__aeth_cov_map";
+
+/// First line of a coverage data file
+pub const DATA_HEADER: &str = "aethcov 1";
+
+/// Instrumented blocks of a module, attached to the IR for the backends
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoverageMap {
+    /// Source file the locations refer to
+    pub file: String,
+    /// Where the program writes its counters on exit
+    pub data_path: String,
+    /// (line, column) of each block, indexed by counter id
+    pub points: Vec<(usize, usize)>,
+}
+
+/// Assigns counters to blocks as functions are lowered
+#[derive(Clone)]
+pub struct CoverageInstrumenter {
+    lines: SourceLines,
+    map: CoverageMap,
+}
+
+impl CoverageInstrumenter {
+    pub fn new(file: &str, source: &str, data_path: &str) -> Self {
+        Self {
+            lines: SourceLines::new(file, source),
+            map: CoverageMap { file: file.to_string(), data_path: data_path.to_string(), points: Vec::new() },
+        }
+    }
+
+    /// Prepend a counter increment to every block of `func`.
+    /// `block_span` gives the source a block was created for.
+    pub fn instrument(&mut self, func: &mut IRFunction, block_span: impl Fn(usize) -> Span) {
+        for (index, block) in func.blocks.iter_mut().enumerate() {
+            let id = self.map.points.len();
+            let (_, line, col) = self.lines.locate(block_span(index));
+            self.map.points.push((line, col));
+            let at = block.instructions.iter()
+                .position(|inst| !matches!(inst, Instruction::Phi { .. }))
+                .unwrap_or(block.instructions.len());
+            block.instructions.insert(at, Instruction::Call {
+                dest: None,
+                func: HIT_FN.to_string(),
+                args: vec![Value::Constant(Constant::Int(id as i64))],
+            });
+        }
+    }
+
+    pub fn finish(self) -> CoverageMap {
+        self.map
+    }
+}
+
+/// Counters read back from a coverage data file
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoverageData {
+    /// (line, column, hits) of each block
+    pub blocks: Vec<(usize, usize, u64)>,
+}
+
+impl CoverageData {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut lines = text.lines();
+        if lines.next().map(str::trim) != Some(DATA_HEADER) {
+            return Err(Error::Io(format!("not a coverage data file (expected '{}')", DATA_HEADER)));
+        }
+        let mut blocks = Vec::new();
+        for (n, line) in lines.enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let parsed = match fields.as_slice() {
+                [line, col, hits] => line.parse().ok().zip(col.parse().ok()).zip(hits.parse().ok()),
+                _ => None,
+            };
+            let ((line, col), hits) = parsed.ok_or_else(|| {
+                Error::Io(format!("malformed coverage record on line {}: '{}'", n + 2, line))
+            })?;
+            blocks.push((line, col, hits));
+        }
+        Ok(Self { blocks })
+    }
+
+    /// Hits per source line: the most-executed block starting on that line
+    pub fn line_hits(&self) -> BTreeMap<usize, u64> {
+        let mut hits = BTreeMap::new();
+        for &(line, _, count) in &self.blocks {
+            let entry = hits.entry(line).or_insert(0);
+            *entry = (*entry).max(count);
+        }
+        hits
+    }
+
+    /// `source` with each line prefixed by its hit count, in the style of gcov:
+    /// `-` for lines without code and `#####` for code that never ran
+    pub fn annotate(&self, source: &str) -> String {
+        let hits = self.line_hits();
+        let mut out = String::new();
+        for (i, text) in source.lines().enumerate() {
+            let count = match hits.get(&(i + 1)) {
+                None => "-".to_string(),
+                Some(0) => "#####".to_string(),
+                Some(n) => n.to_string(),
+            };
+            let _ = writeln!(out, "{:>9}:{:>5}:{}", count, i + 1, text);
+        }
+        out
+    }
+
+    /// LCOV tracefile for `source_path`
+    pub fn to_lcov(&self, source_path: &str) -> String {
+        let hits = self.line_hits();
+        let mut out = format!("TN:\nSF:{}\n", source_path);
+        for (line, count) in &hits {
+            let _ = writeln!(out, "DA:{},{}", line, count);
+        }
+        let _ = writeln!(out, "LF:{}", hits.len());
+        let _ = writeln!(out, "LH:{}", hits.values().filter(|&&n| n > 0).count());
+        out.push_str("end_of_record\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middle::ir::IRType;
+
+    #[test]
+    fn test_instrument_blocks() {
+        let source = "fn f() {\n    g()\n}\n";
+        let mut func = IRFunction::new("f", vec![], IRType::Void);
+        func.add_block("entry");
+        func.add_block("next");
+        let mut cov = CoverageInstrumenter::new("f.aeth", source, "f.aethcov");
+        cov.instrument(&mut func, |block| if block == 0 { Span::new(0, 1, 0) } else { Span::new(13, 16, 0) });
+
+        for (id, block) in func.blocks.iter().enumerate() {
+            assert_eq!(block.instructions[0], Instruction::Call {
+                dest: None,
+                func: HIT_FN.to_string(),
+                args: vec![Value::Constant(Constant::Int(id as i64))],
+            });
+        }
+        assert_eq!(cov.finish().points, vec![(1, 1), (2, 5)]);
+    }
+
+    #[test]
+    fn test_report() {
+        let data = CoverageData::parse("aethcov 1\n1 1 1\n2 5 3\n2 9 0\n3 5 0\n").unwrap();
+        let source = "fn main() {\n    loop_body()\n    never()\n}\n";
+        assert_eq!(
+            data.annotate(source),
+            "        1:    1:fn main() {\n        3:    2:    loop_body()\n    #####:    3:    never()\n        -:    4:}\n"
+        );
+        assert_eq!(
+            data.to_lcov("main.aeth"),
+            "TN:\nSF:main.aeth\nDA:1,1\nDA:2,3\nDA:3,0\nLF:3\nLH:2\nend_of_record\n"
+        );
+        assert!(CoverageData::parse("1 1 1\n").is_err());
+        assert!(CoverageData::parse("aethcov 1\n1 x 1\n").is_err());
+    }
+}
@@ -0,0 +1,99 @@
+//! Control flow graphs as Graphviz `.dot` files (`--emit-dot`)
+//!
+//! Each basic block becomes a box listing its label, instructions and
+//! terminator. Branch edges are labeled `true`/`false`, switch edges with
+//! their case value, and jumps are unlabeled.
+
+use std::fmt::Write;
+
+use crate::middle::ir::{IRFunction, Terminator};
+use crate::middle::ir_printer::{instruction_to_string, terminator_to_string};
+
+/// `digraph` describing the CFG of `func`
+pub fn function_to_dot(func: &IRFunction) -> String {
+    let mut dot = String::new();
+    writeln!(dot, "digraph \"{}\" {{", escape(&func.name)).unwrap();
+    writeln!(dot, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+
+    for block in &func.blocks {
+        let mut label = format!("{}:\\l", escape(&block.label));
+        for inst in &block.instructions {
+            write!(label, "  {}\\l", escape(&instruction_to_string(inst))).unwrap();
+        }
+        if let Some(term) = &block.terminator {
+            write!(label, "  {}\\l", escape(&terminator_to_string(term))).unwrap();
+        }
+        writeln!(dot, "    b{} [label=\"{}\"];", block.id.0, label).unwrap();
+    }
+
+    for block in &func.blocks {
+        let from = block.id.0;
+        match &block.terminator {
+            Some(Terminator::Jump { target }) => {
+                writeln!(dot, "    b{} -> b{};", from, target.0).unwrap();
+            }
+            Some(Terminator::Branch { then_target, else_target, .. }) => {
+                writeln!(dot, "    b{} -> b{} [label=\"true\"];", from, then_target.0).unwrap();
+                writeln!(dot, "    b{} -> b{} [label=\"false\"];", from, else_target.0).unwrap();
+            }
+            Some(Terminator::Switch { cases, default, .. }) => {
+                for (value, target) in cases {
+                    writeln!(dot, "    b{} -> b{} [label=\"{}\"];", from, target.0, value).unwrap();
+                }
+                writeln!(dot, "    b{} -> b{} [label=\"default\"];", from, default.0).unwrap();
+            }
+            Some(Terminator::Return { .. } | Terminator::Unreachable) | None => {}
+        }
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+/// Text safe inside a double-quoted dot string
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\l"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middle::ir::{Constant, IRType, Value};
+
+    #[test]
+    fn test_branch_edges() {
+        let mut func = IRFunction::new("pick", Vec::new(), IRType::I64);
+        let entry = func.add_block("entry");
+        let yes = func.add_block("yes");
+        let no = func.add_block("no");
+        func.blocks[entry.0].set_terminator(Terminator::Branch {
+            cond: Value::Constant(Constant::Bool(true)),
+            then_target: yes,
+            else_target: no,
+        });
+        func.blocks[yes.0].set_terminator(Terminator::Jump { target: no });
+        func.blocks[no.0].set_terminator(Terminator::Return {
+            value: Some(Value::Constant(Constant::String("a\"b".to_string()))),
+        });
+
+        let dot = function_to_dot(&func);
+        assert!(dot.starts_with("digraph \"pick\" {\n"));
+        assert!(dot.contains("    b0 -> b1 [label=\"true\"];\n    b0 -> b2 [label=\"false\"];\n"));
+        assert!(dot.contains("    b1 -> b2;\n"));
+        assert!(dot.contains("    b1 [label=\"yes:\\l  br bb2\\l\"];\n"));
+        // The printed string literal keeps its escapes, escaped once more
+        assert!(dot.contains("b2 [label=\"no:\\l  ret \\\"a\\\\\\\"b\\\"\\l\"];"));
+        assert!(dot.ends_with("}\n"));
+    }
+}
@@ -0,0 +1,64 @@
+//! File I/O builtins: `read_file`, `write_file` and the `file_*` handle API
+//!
+//! Calls to the builtins lower to the `__aeth_*` runtime functions in
+//! `backend::runtime`. Failures never abort: they return a null `str` data
+//! pointer, a null handle or a negative count, and record the C `errno`
+//! for `last_error()`. Every successful call resets `last_error()` to 0.
+//!
+//! Handles are opaque `*u8` (a C `FILE*`). `read_file` returns a heap buffer
+//! the caller releases with `free(s.as_ptr())`.
+
+use crate::middle::ir::{IRModule, IRType, Instruction};
+
+/// `read_file(path: str) -> str`
+pub const READ_FILE: &str = "__aeth_read_file";
+/// `write_file(path: str, data: *u8, len: usize) -> i32`, 0 or -1
+pub const WRITE_FILE: &str = "__aeth_write_file";
+/// `file_open(path: str, mode: str) -> *u8`, null on failure
+pub const FILE_OPEN: &str = "__aeth_file_open";
+/// `file_read(f: *u8, buf: *u8, len: usize) -> i64`, bytes read or -1
+pub const FILE_READ: &str = "__aeth_file_read";
+/// `file_write(f: *u8, data: *u8, len: usize) -> i64`, bytes written or -1
+pub const FILE_WRITE: &str = "__aeth_file_write";
+/// `file_close(f: *u8) -> i32`, 0 or -1
+pub const FILE_CLOSE: &str = "__aeth_file_close";
+/// `last_error() -> i32`, the `errno` of the last failed file builtin
+pub const LAST_ERROR: &str = "__aeth_last_error";
+
+/// Source-level name of each runtime function
+pub const BUILTINS: [(&str, &str); 7] = [
+    ("read_file", READ_FILE),
+    ("write_file", WRITE_FILE),
+    ("file_open", FILE_OPEN),
+    ("file_read", FILE_READ),
+    ("file_write", FILE_WRITE),
+    ("file_close", FILE_CLOSE),
+    ("last_error", LAST_ERROR),
+];
+
+/// Runtime function implementing the builtin `name`
+pub fn runtime_name(name: &str) -> Option<&'static str> {
+    BUILTINS.iter().find(|(builtin, _)| *builtin == name).map(|(_, func)| *func)
+}
+
+/// Parameter and return types of a file runtime function
+pub fn signature(func: &str) -> Option<(Vec<IRType>, IRType)> {
+    let ptr = || IRType::Ptr(Box::new(IRType::U8));
+    Some(match func {
+        READ_FILE => (vec![IRType::Str], IRType::Str),
+        WRITE_FILE => (vec![IRType::Str, ptr(), IRType::U64], IRType::I32),
+        FILE_OPEN => (vec![IRType::Str, IRType::Str], ptr()),
+        FILE_READ | FILE_WRITE => (vec![ptr(), ptr(), IRType::U64], IRType::I64),
+        FILE_CLOSE => (vec![ptr()], IRType::I32),
+        LAST_ERROR => (vec![], IRType::I32),
+        _ => return None,
+    })
+}
+
+/// Whether `module` calls into the file runtime
+pub fn uses_runtime(module: &IRModule) -> bool {
+    module.functions.iter()
+        .flat_map(|f| &f.blocks)
+        .flat_map(|b| &b.instructions)
+        .any(|inst| matches!(inst, Instruction::Call { func, .. } if signature(func).is_some()))
+}
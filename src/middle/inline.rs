@@ -0,0 +1,277 @@
+//! Function inlining
+//!
+//! Replaces calls to small functions with a copy of the callee's body.
+//! `#[inline]` functions are inlined wherever their body can be copied,
+//! `#[inline(never)]` and `#[cold]` functions never are, and other functions
+//! only while they have at most `AUTO_INLINE_LIMIT` instructions.
+//!
+//! A body can be copied when it is a single block ending in a return, without
+//! phis, allocas, inline assembly or calls to the function itself, and the
+//! function has no contracts or attributes that change how it is generated.
+//! The callee stays in the module for other callers and for the linker.
+
+use std::collections::HashMap;
+
+use crate::middle::ir::*;
+use crate::middle::optimize::OptimizationPass;
+
+/// Instructions a function without `#[inline]` may have and still be inlined
+pub const AUTO_INLINE_LIMIT: usize = 8;
+
+/// Instructions an `#[inline]` function may have and still be inlined
+pub const ALWAYS_INLINE_LIMIT: usize = 64;
+
+/// Inlines calls to small single-block functions
+pub struct Inliner;
+
+impl Inliner {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for Inliner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OptimizationPass for Inliner {
+    fn name(&self) -> &'static str {
+        "inline"
+    }
+
+    fn run_on_module(&mut self, module: &mut IRModule) -> bool {
+        let bodies: HashMap<String, Callee> = module.functions.iter()
+            .filter_map(|func| {
+                let body = inlinable_body(func)?;
+                Some((func.name.clone(), Callee { body: body.clone(), params: func.params.len() }))
+            })
+            .collect();
+        if bodies.is_empty() {
+            return false;
+        }
+        let mut changed = false;
+        for func in &mut module.functions {
+            if !func.naked {
+                changed |= inline_calls(func, &bodies);
+            }
+        }
+        changed
+    }
+
+    fn run_on_function(&mut self, _func: &mut IRFunction) -> bool {
+        // Inlining needs the callees' bodies, see `run_on_module`
+        false
+    }
+}
+
+/// Body of a function the inliner may copy into its callers
+struct Callee {
+    body: BasicBlock,
+    params: usize,
+}
+
+/// The block to copy in place of calls to `func`, if it may be inlined
+fn inlinable_body(func: &IRFunction) -> Option<&BasicBlock> {
+    let limit = match func.inline {
+        InlineHint::Never => return None,
+        // Rarely run code is not worth growing its callers for
+        InlineHint::Auto if func.cold => return None,
+        InlineHint::Always => ALWAYS_INLINE_LIMIT,
+        InlineHint::Auto => AUTO_INLINE_LIMIT,
+    };
+    if func.naked || func.interrupt || func.volatile || func.simd || func.sret_type.is_some() || func.name == "main"
+        || !func.contracts.requires.is_empty() || !func.contracts.ensures.is_empty() {
+        return None;
+    }
+    let [block] = func.blocks.as_slice() else {
+        return None;
+    };
+    if !matches!(block.terminator, Some(Terminator::Return { .. })) {
+        return None;
+    }
+    let size = block.instructions.iter().filter(|inst| !matches!(inst, Instruction::DebugLoc(_))).count();
+    let copyable = block.instructions.iter().all(|inst| match inst {
+        Instruction::Phi { .. } | Instruction::Alloca { .. } | Instruction::InlineAsm { .. } => false,
+        Instruction::Call { func: callee, .. } => *callee != func.name,
+        _ => true,
+    });
+    (size <= limit && copyable).then_some(block)
+}
+
+/// Replace the calls in `func` to functions in `bodies` with their bodies
+fn inline_calls(func: &mut IRFunction, bodies: &HashMap<String, Callee>) -> bool {
+    let name = func.name.clone();
+    let mut next = next_register(func);
+    let mut changed = false;
+    for block in &mut func.blocks {
+        let mut instructions = Vec::with_capacity(block.instructions.len());
+        for inst in std::mem::take(&mut block.instructions) {
+            let callee = match &inst {
+                Instruction::Call { dest, func: callee, args } if *callee != name => bodies.get(callee)
+                    .filter(|callee| callee.params == args.len())
+                    .filter(|callee| dest.is_none() || matches!(callee.body.terminator, Some(Terminator::Return { value: Some(_) }))),
+                _ => None,
+            };
+            match (callee, &inst) {
+                (Some(callee), Instruction::Call { dest, args, .. }) => {
+                    instructions.extend(copy_body(&callee.body, args, *dest, &mut next));
+                    changed = true;
+                }
+                _ => instructions.push(inst),
+            }
+        }
+        block.instructions = instructions;
+    }
+    changed
+}
+
+/// One past the highest register `func` defines
+pub(crate) fn next_register(func: &IRFunction) -> usize {
+    func.blocks.iter()
+        .flat_map(|block| &block.instructions)
+        .flat_map(|inst| inst.defs())
+        .map(|reg| reg.0 + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// `body` with fresh registers and its parameters replaced by `args`,
+/// ending with the assignment of the returned value to `dest`
+fn copy_body(body: &BasicBlock, args: &[Value], dest: Option<Register>, next: &mut usize) -> Vec<Instruction> {
+    let mut renamed: HashMap<Register, Register> = HashMap::new();
+    let substitute = |value: &mut Value, renamed: &HashMap<Register, Register>| match value {
+        Value::Register(reg) => {
+            if let Some(fresh) = renamed.get(reg) {
+                *reg = *fresh;
+            }
+        }
+        Value::Parameter(i) => *value = args[*i].clone(),
+        _ => {}
+    };
+
+    let mut copied = Vec::with_capacity(body.instructions.len() + 1);
+    for inst in &body.instructions {
+        if matches!(inst, Instruction::DebugLoc(_)) {
+            continue;
+        }
+        let mut inst = inst.clone();
+        for value in inst.operands_mut() {
+            substitute(value, &renamed);
+        }
+        for reg in inst.defs_mut() {
+            let fresh = Register(*next);
+            *next += 1;
+            renamed.insert(*reg, fresh);
+            *reg = fresh;
+        }
+        copied.push(inst);
+    }
+    if let (Some(dest), Some(Terminator::Return { value: Some(value) })) = (dest, &body.terminator) {
+        let mut value = value.clone();
+        substitute(&mut value, &renamed);
+        copied.push(Instruction::Assign { dest, value });
+    }
+    copied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `fn <name>(x: i64) -> i64 { return x * x; }`
+    fn square(name: &str) -> IRFunction {
+        let mut func = IRFunction::new(name, vec![("x".to_string(), IRType::I64)], IRType::I64);
+        let entry = func.add_block("entry");
+        let block = func.get_block_mut(entry).unwrap();
+        block.push(Instruction::Assign { dest: Register(0), value: Value::Parameter(0) });
+        block.push(Instruction::BinOp {
+            dest: Register(1),
+            op: BinOp::Mul,
+            left: Value::Register(Register(0)),
+            right: Value::Register(Register(0)),
+        });
+        block.set_terminator(Terminator::Return { value: Some(Value::Register(Register(1))) });
+        func
+    }
+
+    /// `fn main() -> i64 { return <callee>(7); }`
+    fn caller(callee: &str) -> IRFunction {
+        let mut func = IRFunction::new("main", vec![], IRType::I64);
+        let entry = func.add_block("entry");
+        let block = func.get_block_mut(entry).unwrap();
+        block.push(Instruction::Call {
+            dest: Some(Register(0)),
+            func: callee.to_string(),
+            args: vec![Value::Constant(Constant::Int(7))],
+        });
+        block.set_terminator(Terminator::Return { value: Some(Value::Register(Register(0))) });
+        func
+    }
+
+    fn calls(func: &IRFunction, callee: &str) -> usize {
+        func.blocks.iter()
+            .flat_map(|block| &block.instructions)
+            .filter(|inst| matches!(inst, Instruction::Call { func, .. } if func == callee))
+            .count()
+    }
+
+    #[test]
+    fn test_inlines_small_function() {
+        let mut module = IRModule::new("test");
+        module.functions.push(square("square"));
+        module.functions.push(caller("square"));
+
+        assert!(Inliner::new().run_on_module(&mut module));
+        let main = &module.functions[1];
+        assert_eq!(calls(main, "square"), 0);
+        assert_eq!(main.blocks[0].instructions, vec![
+            Instruction::Assign { dest: Register(1), value: Value::Constant(Constant::Int(7)) },
+            Instruction::BinOp {
+                dest: Register(2),
+                op: BinOp::Mul,
+                left: Value::Register(Register(1)),
+                right: Value::Register(Register(1)),
+            },
+            Instruction::Assign { dest: Register(0), value: Value::Register(Register(2)) },
+        ]);
+    }
+
+    #[test]
+    fn test_respects_inline_never() {
+        let mut module = IRModule::new("test");
+        let mut callee = square("square");
+        callee.inline = InlineHint::Never;
+        module.functions.push(callee);
+        module.functions.push(caller("square"));
+
+        assert!(!Inliner::new().run_on_module(&mut module));
+        assert_eq!(calls(&module.functions[1], "square"), 1);
+    }
+
+    #[test]
+    fn test_inline_hint_raises_size_limit() {
+        let mut big = square("big");
+        let block = &mut big.blocks[0];
+        for i in 0..AUTO_INLINE_LIMIT {
+            block.push(Instruction::BinOp {
+                dest: Register(2 + i),
+                op: BinOp::Add,
+                left: Value::Register(Register(1 + i)),
+                right: Value::Constant(Constant::Int(1)),
+            });
+        }
+        block.terminator = Some(Terminator::Return { value: Some(Value::Register(Register(1 + AUTO_INLINE_LIMIT))) });
+
+        let mut module = IRModule::new("test");
+        module.functions.push(big.clone());
+        module.functions.push(caller("big"));
+        assert!(!Inliner::new().run_on_module(&mut module));
+
+        big.inline = InlineHint::Always;
+        module.functions[0] = big;
+        assert!(Inliner::new().run_on_module(&mut module));
+        assert_eq!(calls(&module.functions[1], "big"), 0);
+    }
+}
@@ -5,6 +5,9 @@
 
 use std::fmt;
 
+use crate::middle::coverage::CoverageMap;
+use crate::types::layout::{align_to, layout_enum, layout_struct, layout_union, padding_bytes, BitfieldLayout, Layout, StructLayout};
+
 /// Struct representation/layout specification
 #[derive(Debug, Clone, PartialEq)]
 pub enum StructRepr {
@@ -25,50 +28,87 @@ impl Default for StructRepr {
 }
 
 /// IR Struct definition
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct IRStruct {
     pub name: String,
     pub fields: Vec<(String, IRType)>,
     pub repr: StructRepr,
+    /// Untagged union: all fields start at offset 0
+    pub is_union: bool,
+    /// Declared `pub`: part of the unit's C interface
+    pub is_pub: bool,
+    /// `#[bits(N)]` width of each field; empty when the struct has none
+    pub bits: Vec<Option<u32>>,
+}
+
+impl IRStruct {
+    /// Packing of the struct's `#[bits(N)]` fields, if it has any
+    pub fn bitfields(&self) -> Option<BitfieldLayout> {
+        if self.bits.iter().any(Option::is_some) {
+            Some(BitfieldLayout::new(&self.bits))
+        } else {
+            None
+        }
+    }
 }
 
 /// IR Enum variant
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct IRVariant {
     pub name: String,
     /// Fields of this variant (empty for unit variants)
     pub fields: Vec<IRType>,
+    /// Value stored in the tag for this variant
+    pub discriminant: i64,
 }
 
 /// IR Enum definition (sum type / tagged union)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct IREnum {
     pub name: String,
     pub variants: Vec<IRVariant>,
+    /// Integer type of the tag (`I32` unless the enum has a `repr`)
+    pub tag: IRType,
 }
 
 /// IR Module - contains all functions
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct IRModule {
     pub name: String,
     pub functions: Vec<IRFunction>,
     pub structs: Vec<IRStruct>,
     pub enums: Vec<IREnum>,
     pub externs: Vec<IRExtern>,
+    /// Global variables (static items and extern statics)
+    pub globals: Vec<IRGlobal>,
     /// #![no_std] - disable standard library
     pub no_std: bool,
     /// #![no_main] - no default main entry point
     pub no_main: bool,
+    /// Block counters inserted by `--coverage`
+    pub coverage: Option<CoverageMap>,
 }
 
 /// External function declaration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct IRExtern {
     pub name: String,
     pub params: Vec<(String, IRType)>,
     pub ret_type: IRType,
 }
 
+/// Global variable (static item or extern static)
+#[derive(Debug, Clone, PartialEq)]
+pub struct IRGlobal {
+    pub name: String,
+    pub ty: IRType,
+    /// Constant initializer (None means zero-initialized)
+    pub init: Option<Constant>,
+    pub is_mut: bool,
+    /// Defined in another object file (extern static)
+    pub is_extern: bool,
+}
+
 impl IRModule {
     pub fn new(name: &str) -> Self {
         Self {
@@ -77,23 +117,148 @@ impl IRModule {
             structs: Vec::new(),
             enums: Vec::new(),
             externs: Vec::new(),
+            globals: Vec::new(),
             no_std: false,
             no_main: false,
+            coverage: None,
         }
     }
+
+    /// Instructions plus terminators over all functions
+    pub fn instruction_count(&self) -> usize {
+        self.functions.iter().map(IRFunction::instruction_count).sum()
+    }
     
     pub fn add_struct(&mut self, name: &str, fields: Vec<(String, IRType)>, repr: StructRepr) {
         self.structs.push(IRStruct {
             name: name.to_string(),
             fields,
             repr,
+            is_union: false,
+            is_pub: false,
+            bits: Vec::new(),
         });
     }
+
+    pub fn add_union(&mut self, name: &str, fields: Vec<(String, IRType)>, repr: StructRepr) {
+        self.structs.push(IRStruct {
+            name: name.to_string(),
+            fields,
+            repr,
+            is_union: true,
+            is_pub: false,
+            bits: Vec::new(),
+        });
+    }
+
+    /// Whether a named aggregate is a union
+    pub fn is_union(&self, name: &str) -> bool {
+        self.structs.iter().any(|s| s.name == name && s.is_union)
+    }
+
+    /// Look up a declared global variable by name
+    pub fn global(&self, name: &str) -> Option<&IRGlobal> {
+        self.globals.iter().find(|g| g.name == name)
+    }
+
+    /// Layout of an IR type, using the module's struct and enum definitions.
+    /// Enums are laid out as the tagged unions emitted by the backends.
+    pub fn layout_of(&self, ty: &IRType) -> Option<Layout> {
+        self.layout_inner(ty, &mut Vec::new())
+    }
+
+    /// Byte offset from the start of the enum and type of each payload field
+    /// of `enum_name::variant`, as laid out by `layout_of`
+    pub fn variant_fields(&self, enum_name: &str, variant: &str) -> Option<Vec<(usize, IRType)>> {
+        let e = self.enums.iter().find(|e| e.name == enum_name)?;
+        let index = e.variants.iter().position(|v| v.name == variant)?;
+        let payloads = e.variants.iter()
+            .map(|v| {
+                let fields = v.fields.iter().map(|f| self.layout_of(f)).collect::<Option<Vec<_>>>()?;
+                Some(layout_struct(&fields, false))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let align = payloads.iter().filter(|p| p.size > 0).map(|p| p.align).max().unwrap_or(1);
+        let data = align_to(e.tag.size_bytes(), align);
+        Some(payloads[index].offsets.iter()
+            .zip(&e.variants[index].fields)
+            .map(|(offset, ty)| (data + offset, ty.clone()))
+            .collect())
+    }
+
+    /// Size, alignment and field offsets of a named struct
+    pub fn struct_layout(&self, name: &str) -> Option<StructLayout> {
+        self.struct_layout_inner(name, &mut Vec::new())
+    }
+
+    /// Bytes of padding between and after `fields` laid out in order;
+    /// fields without a fixed layout count as taking none
+    pub fn compute_padding_bytes(&self, fields: &[(String, IRType)]) -> usize {
+        let layouts: Vec<Layout> = fields.iter()
+            .filter_map(|(_, ty)| self.layout_of(ty))
+            .collect();
+        padding_bytes(&layouts, false)
+    }
+
+    fn layout_inner(&self, ty: &IRType, visiting: &mut Vec<String>) -> Option<Layout> {
+        match ty {
+            IRType::Void => Some(Layout::new(0, 1)),
+            IRType::Ptr(_) | IRType::Function { .. } | IRType::Vec(_) | IRType::Map(..) => Some(Layout::pointer()),
+            IRType::Str => Some(Layout::new(16, Layout::pointer().align)),
+            IRType::Array(elem, count) => Some(Layout::array(self.layout_inner(elem, visiting)?, *count)),
+            IRType::Vector(elem, lanes) => Some(Layout::vector(self.layout_inner(elem, visiting)?, *lanes)),
+            IRType::Struct(name) => {
+                if let Some(e) = self.enums.iter().find(|e| &e.name == name) {
+                    if visiting.contains(name) {
+                        return None;
+                    }
+                    visiting.push(name.clone());
+                    let payloads = e.variants.iter()
+                        .map(|v| {
+                            let fields = v.fields.iter()
+                                .map(|f| self.layout_inner(f, visiting))
+                                .collect::<Option<Vec<_>>>()?;
+                            Some(layout_struct(&fields, false).layout())
+                        })
+                        .collect::<Option<Vec<_>>>();
+                    visiting.pop();
+                    let tag = e.tag.size_bytes();
+                    Some(layout_enum(Layout::new(tag, tag), &payloads?))
+                } else {
+                    self.struct_layout_inner(name, visiting).map(|l| l.layout())
+                }
+            }
+            scalar => {
+                let size = scalar.size_bytes();
+                Some(Layout::new(size, size))
+            }
+        }
+    }
+
+    fn struct_layout_inner(&self, name: &str, visiting: &mut Vec<String>) -> Option<StructLayout> {
+        let def = self.structs.iter().find(|s| s.name == name)?;
+        if visiting.iter().any(|v| v == name) {
+            return None;
+        }
+        visiting.push(name.to_string());
+        let fields = def.fields.iter()
+            .map(|(_, ty)| self.layout_inner(ty, visiting))
+            .collect::<Option<Vec<_>>>();
+        visiting.pop();
+        let packed = def.repr == StructRepr::Packed;
+        if def.is_union {
+            Some(layout_union(&fields?, packed))
+        } else if let Some(bitfields) = def.bitfields() {
+            Some(bitfields.layout(&fields?))
+        } else {
+            Some(layout_struct(&fields?, packed))
+        }
+    }
 }
 
 
 /// IR Function
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct IRFunction {
     pub name: String,
     pub params: Vec<(String, IRType)>,
@@ -112,10 +277,46 @@ pub struct IRFunction {
     pub interrupt: bool,
     /// Volatile function - all memory accesses are volatile (for MMIO)
     pub volatile: bool,
+    /// `#[extern_c]` - exported with C linkage under its unmangled name
+    pub extern_c: bool,
+    /// `#[no_mangle]` - emitted under its exact name and visible to the linker
+    pub no_mangle: bool,
+    /// Declared `pub`: part of the unit's C interface
+    pub is_pub: bool,
+    /// `#[inline]` / `#[inline(never)]`, consulted by the inliner and the backends
+    pub inline: InlineHint,
+    /// `#[cold]` - rarely called; branches leading to a call are unlikely
+    pub cold: bool,
+    /// `#[hot]` - called often, optimized for speed over size
+    pub hot: bool,
+    /// Where the function is declared (set with `-g`)
+    pub debug_loc: Option<SourceLoc>,
+}
+
+/// Inlining request of a function
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InlineHint {
+    /// Inlined when the inliner finds it small enough
+    #[default]
+    Auto,
+    /// `#[inline]`: inlined wherever possible
+    Always,
+    /// `#[inline(never)]`: never inlined
+    Never,
+}
+
+/// Source position a piece of IR was lowered from, for debug info
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLoc {
+    pub file: String,
+    /// 1-based
+    pub line: usize,
+    /// 1-based
+    pub col: usize,
 }
 
 /// Contract expressions for runtime assertion generation
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct IRContracts {
     /// Preconditions (checked at function entry)
     pub requires: Vec<String>,
@@ -139,6 +340,13 @@ impl IRFunction {
             naked: false,
             interrupt: false,
             volatile: false,
+            extern_c: false,
+            no_mangle: false,
+            is_pub: false,
+            inline: InlineHint::Auto,
+            cold: false,
+            hot: false,
+            debug_loc: None,
         }
     }
 
@@ -151,10 +359,17 @@ impl IRFunction {
     pub fn get_block_mut(&mut self, id: BlockId) -> Option<&mut BasicBlock> {
         self.blocks.get_mut(id.0)
     }
+
+    /// Instructions plus terminators over all blocks
+    pub fn instruction_count(&self) -> usize {
+        self.blocks.iter()
+            .map(|b| b.instructions.len() + b.terminator.is_some() as usize)
+            .sum()
+    }
 }
 
 /// Basic Block - a sequence of instructions with single entry/exit
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct BasicBlock {
     pub id: BlockId,
     pub label: String,
@@ -196,28 +411,38 @@ impl fmt::Display for Register {
 }
 
 /// IR Instruction (non-terminating)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Instruction {
     /// dest = value
     Assign { dest: Register, value: Value },
     
     /// dest = left op right
     BinOp { dest: Register, op: BinOp, left: Value, right: Value },
+
+    /// dest = left op right (wrapping), overflow_dest = whether the exact
+    /// result does not fit the operands' type (`add`, `sub` and `mul` only)
+    CheckedBinOp { dest: Register, overflow_dest: Register, op: BinOp, left: Value, right: Value },
     
     /// dest = op value
     UnaryOp { dest: Register, op: UnaryOp, value: Value },
     
     /// dest = func(args...)
     Call { dest: Option<Register>, func: String, args: Vec<Value> },
+
+    /// dest = call through the function pointer `callee` (with its function
+    /// type for LLVM opaque pointers)
+    CallIndirect { dest: Option<Register>, callee: Value, ty: IRType, args: Vec<Value> },
     
     /// dest = alloca type
     Alloca { dest: Register, ty: IRType },
     
     /// dest = load ptr (with explicit element type for LLVM opaque pointers)
-    Load { dest: Register, ptr: Value, ty: IRType },
+    /// Volatile loads are never removed, merged or reordered by the optimizer
+    Load { dest: Register, ptr: Value, ty: IRType, volatile: bool },
     
     /// store value, ptr
-    Store { ptr: Value, value: Value },
+    /// Volatile stores are never removed, merged or reordered by the optimizer
+    Store { ptr: Value, value: Value, volatile: bool },
     
     /// dest = gep ptr, index (with explicit element type for LLVM opaque pointers)
     GetElementPtr { dest: Register, ptr: Value, index: Value, elem_ty: IRType },
@@ -233,9 +458,155 @@ pub enum Instruction {
         template: String,
         operands: Vec<IRAsmOperand>,
     },
+
+    /// loc "file" line col - the instructions that follow were lowered from here (`-g`)
+    DebugLoc(SourceLoc),
+}
+
+impl Instruction {
+    /// Register defined by this instruction, if any (the result of a checked operation)
+    pub fn dest(&self) -> Option<Register> {
+        match self {
+            Instruction::Assign { dest, .. }
+            | Instruction::BinOp { dest, .. }
+            | Instruction::CheckedBinOp { dest, .. }
+            | Instruction::UnaryOp { dest, .. }
+            | Instruction::Alloca { dest, .. }
+            | Instruction::Load { dest, .. }
+            | Instruction::GetElementPtr { dest, .. }
+            | Instruction::Phi { dest, .. }
+            | Instruction::Cast { dest, .. } => Some(*dest),
+            Instruction::Call { dest, .. } | Instruction::CallIndirect { dest, .. } => *dest,
+            Instruction::Store { .. } | Instruction::InlineAsm { .. } | Instruction::DebugLoc(_) => None,
+        }
+    }
+
+    /// Values read by this instruction
+    pub fn operands(&self) -> Vec<&Value> {
+        match self {
+            Instruction::Assign { value, .. }
+            | Instruction::UnaryOp { value, .. }
+            | Instruction::Cast { value, .. } => vec![value],
+            Instruction::BinOp { left, right, .. }
+            | Instruction::CheckedBinOp { left, right, .. } => vec![left, right],
+            Instruction::Call { args, .. } => args.iter().collect(),
+            Instruction::CallIndirect { callee, args, .. } => std::iter::once(callee).chain(args).collect(),
+            Instruction::Alloca { .. } | Instruction::DebugLoc(_) => vec![],
+            Instruction::Load { ptr, .. } => vec![ptr],
+            Instruction::Store { ptr, value, .. } => vec![ptr, value],
+            Instruction::GetElementPtr { ptr, index, .. } => vec![ptr, index],
+            Instruction::Phi { incoming, .. } => incoming.iter().map(|(v, _)| v).collect(),
+            Instruction::InlineAsm { operands, .. } => {
+                operands.iter().filter_map(|op| op.input.as_ref()).collect()
+            }
+        }
+    }
+
+    /// Values read by this instruction, mutably, in the order `operands` lists them
+    pub fn operands_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Instruction::Assign { value, .. }
+            | Instruction::UnaryOp { value, .. }
+            | Instruction::Cast { value, .. } => vec![value],
+            Instruction::BinOp { left, right, .. }
+            | Instruction::CheckedBinOp { left, right, .. } => vec![left, right],
+            Instruction::Call { args, .. } => args.iter_mut().collect(),
+            Instruction::CallIndirect { callee, args, .. } => std::iter::once(callee).chain(args).collect(),
+            Instruction::Alloca { .. } | Instruction::DebugLoc(_) => vec![],
+            Instruction::Load { ptr, .. } => vec![ptr],
+            Instruction::Store { ptr, value, .. } => vec![ptr, value],
+            Instruction::GetElementPtr { ptr, index, .. } => vec![ptr, index],
+            Instruction::Phi { incoming, .. } => incoming.iter_mut().map(|(v, _)| v).collect(),
+            Instruction::InlineAsm { operands, .. } => {
+                operands.iter_mut().filter_map(|op| op.input.as_mut()).collect()
+            }
+        }
+    }
+
+    /// Every register defined by this instruction, including the overflow
+    /// flag of a checked operation and inline assembly outputs
+    pub fn defs(&self) -> Vec<Register> {
+        match self {
+            Instruction::CheckedBinOp { dest, overflow_dest, .. } => vec![*dest, *overflow_dest],
+            Instruction::InlineAsm { operands, .. } => operands.iter().filter_map(|op| op.output).collect(),
+            _ => self.dest().into_iter().collect(),
+        }
+    }
+
+    /// Every register defined by this instruction, mutably, in the order `defs` lists them
+    pub fn defs_mut(&mut self) -> Vec<&mut Register> {
+        match self {
+            Instruction::CheckedBinOp { dest, overflow_dest, .. } => vec![dest, overflow_dest],
+            Instruction::InlineAsm { operands, .. } => operands.iter_mut().filter_map(|op| op.output.as_mut()).collect(),
+            Instruction::Assign { dest, .. }
+            | Instruction::BinOp { dest, .. }
+            | Instruction::UnaryOp { dest, .. }
+            | Instruction::Alloca { dest, .. }
+            | Instruction::Load { dest, .. }
+            | Instruction::GetElementPtr { dest, .. }
+            | Instruction::Phi { dest, .. }
+            | Instruction::Cast { dest, .. } => vec![dest],
+            Instruction::Call { dest, .. } | Instruction::CallIndirect { dest, .. } => dest.iter_mut().collect(),
+            Instruction::Store { .. } | Instruction::DebugLoc(_) => vec![],
+        }
+    }
+
+    /// Whether this instruction is a volatile memory access
+    pub fn is_volatile(&self) -> bool {
+        matches!(
+            self,
+            Instruction::Load { volatile: true, .. } | Instruction::Store { volatile: true, .. }
+        )
+    }
+
+    /// Whether this instruction has effects beyond defining its destination
+    /// register. Such instructions must never be removed, merged or hoisted.
+    pub fn has_side_effects(&self) -> bool {
+        match self {
+            // Locations are kept so the code after them stays attributed to its source
+            Instruction::Call { .. } | Instruction::CallIndirect { .. } | Instruction::Store { .. }
+            | Instruction::InlineAsm { .. } | Instruction::DebugLoc(_) => true,
+            Instruction::Load { volatile, .. } => *volatile,
+            _ => false,
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+impl Terminator {
+    /// Values read by this terminator
+    pub fn operands(&self) -> Vec<&Value> {
+        match self {
+            Terminator::Return { value: Some(value) } => vec![value],
+            Terminator::Branch { cond, .. } => vec![cond],
+            Terminator::Switch { value, .. } => vec![value],
+            Terminator::Return { value: None } | Terminator::Jump { .. } | Terminator::Unreachable => vec![],
+        }
+    }
+
+    /// Values read by this terminator, mutably
+    pub fn operands_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Terminator::Return { value: Some(value) } => vec![value],
+            Terminator::Branch { cond, .. } => vec![cond],
+            Terminator::Switch { value, .. } => vec![value],
+            Terminator::Return { value: None } | Terminator::Jump { .. } | Terminator::Unreachable => vec![],
+        }
+    }
+
+    /// Blocks control can transfer to, in order (duplicates kept)
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Jump { target } => vec![*target],
+            Terminator::Branch { then_target, else_target, .. } => vec![*then_target, *else_target],
+            Terminator::Switch { cases, default, .. } => {
+                cases.iter().map(|(_, target)| *target).chain(std::iter::once(*default)).collect()
+            }
+            Terminator::Return { .. } | Terminator::Unreachable => vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct IRAsmOperand {
     pub kind: IRAsmOperandKind,
     pub constraint: String,
@@ -252,7 +623,7 @@ pub enum IRAsmOperandKind {
 }
 
 /// Block terminator
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Terminator {
     /// return value
     Return { value: Option<Value> },
@@ -262,13 +633,17 @@ pub enum Terminator {
     
     /// br cond, then_target, else_target
     Branch { cond: Value, then_target: BlockId, else_target: BlockId },
+
+    /// switch value, default [case: target, ...]
+    /// An enum value is compared by its tag (the variant index)
+    Switch { value: Value, cases: Vec<(i64, BlockId)>, default: BlockId },
     
     /// unreachable
     Unreachable,
 }
 
 /// IR Value
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Register(Register),
     Constant(Constant),
@@ -290,12 +665,14 @@ impl fmt::Display for Value {
 }
 
 /// Constant value
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Constant {
     Int(i64),
     Float(f64),
     Bool(bool),
     String(String),
+    /// `str` literal: the bytes of `String` plus their length
+    Str(String),
     Null,
 }
 
@@ -306,6 +683,7 @@ impl fmt::Display for Constant {
             Constant::Float(n) => write!(f, "{}", n),
             Constant::Bool(b) => write!(f, "{}", b),
             Constant::String(s) => write!(f, "\"{}\"", s),
+            Constant::Str(s) => write!(f, "str \"{}\"", s),
             Constant::Null => write!(f, "null"),
         }
     }
@@ -366,11 +744,27 @@ pub enum IRType {
     Array(Box<IRType>, usize),
     Struct(String),
     Function { params: Vec<IRType>, ret: Box<IRType> },
+    /// String slice `{ data: *u8, len: u64 }`, passed by value
+    Str,
+    /// Handle to a runtime-managed `Vec<T>` (see `middle::collections`)
+    Vec(Box<IRType>),
+    /// Handle to a runtime-managed `HashMap<K, V>`
+    Map(Box<IRType>, Box<IRType>),
     /// SIMD vector type: Vector(element_type, lane_count)
     /// e.g., Vector(F32, 4) = f32x4, Vector(F64, 2) = f64x2
     Vector(Box<IRType>, usize),
 }
 
+/// `str` builtins, lowered as calls the backends implement inline
+pub const STR_LEN: &str = "__aeth_str_len";
+pub const STR_AS_PTR: &str = "__aeth_str_as_ptr";
+/// `slice(s, start, end)`; panics unless `start <= end <= len`
+pub const STR_SLICE: &str = "__aeth_str_slice";
+/// `str` of a NUL-terminated C string
+pub const STR_FROM_PTR: &str = "__aeth_str_from_ptr";
+/// `a == b` on `str`: same length and bytes
+pub const STR_EQ: &str = "__aeth_str_eq";
+
 impl IRType {
     pub fn size_bytes(&self) -> usize {
         match self {
@@ -381,7 +775,8 @@ impl IRType {
             IRType::I64 | IRType::U64 | IRType::F64 | IRType::Ptr(_) => 8,
             IRType::Array(elem, count) => elem.size_bytes() * count,
             IRType::Struct(_) => 8, // Placeholder
-            IRType::Function { .. } => 8,
+            IRType::Function { .. } | IRType::Vec(_) | IRType::Map(..) => 8,
+            IRType::Str => 16,
             IRType::Vector(elem, lanes) => elem.size_bytes() * lanes,
         }
     }
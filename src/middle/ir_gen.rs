@@ -3,18 +3,75 @@
 //! Converts the typed AST into three-address code IR.
 #![allow(dead_code)]
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use crate::frontend::ast::{
-    self, Program, Item, Stmt, Expr, Type as AstType,
+    self, Program, Item, Stmt, Expr, Place, Type as AstType,
 };
+use crate::frontend::module::ModuleLoader;
+use crate::frontend::parser::DEFAULT_MAX_NESTING;
 use crate::middle::ir::{
     IRModule, IRFunction, IRType, BlockId, Register,
     Instruction, Terminator, Value, Constant, UnaryOp,
-    BinOp as IRBinOp, IRAsmOperand, IRAsmOperandKind, IRExtern,
+    BinOp as IRBinOp, IRAsmOperand, IRAsmOperandKind, IRExtern, IRGlobal, InlineHint, SourceLoc,
+    STR_AS_PTR, STR_EQ, STR_FROM_PTR, STR_LEN, STR_SLICE,
 };
-use crate::utils::Result;
+use crate::middle::bench::{self, BenchConfig};
+use crate::middle::collections::{
+    KeyKind, MAP_CONTAINS, MAP_FREE, MAP_GET, MAP_INSERT, MAP_LEN, MAP_NEW, MAP_REMOVE,
+    VEC_CLEAR, VEC_FREE, VEC_GET, VEC_LEN, VEC_NEW, VEC_POP, VEC_PUSH, VEC_WITH_CAPACITY,
+};
+use crate::middle::coverage::CoverageInstrumenter;
+use crate::middle::fileio;
+use crate::middle::mangle;
+use crate::middle::mono_const::{ConstMonomorphizer, ConstSubstitution};
+use crate::middle::overflow::{self, OverflowMode};
+use crate::middle::panic::{self, PanicMode, PANIC_FN};
+use crate::middle::simd;
+use crate::stdlib::{self, PRELUDE_FILE_ID};
+use crate::types::type_system::ConstValue;
+use crate::types::layout::{layout_struct, optimal_field_order, Layout};
+use crate::utils::{map_chunks, Error, Result, SourceLines, Span};
+
+/// Value debug builds leave in a variable after `delete`; non-canonical, so any use faults
+const DELETED_POINTER: i64 = 0xDEAD_BEEF_DEAD_BEEF_u64 as i64;
+
+/// `Option<T>` and `Result<T, E>` (see stdlib/core.aeth): their variants,
+/// and whether each carries a payload
+const PRELUDE_ENUMS: [(&str, [(&str, bool); 2]); 2] = [
+    ("Option", [("Some", true), ("None", false)]),
+    ("Result", [("Ok", true), ("Err", true)]),
+];
+
+/// Whether `ty` is or contains the struct or enum `name`
+fn mentions(ty: &IRType, name: &str) -> bool {
+    match ty {
+        IRType::Struct(s) => s == name,
+        IRType::Ptr(inner) | IRType::Array(inner, _) | IRType::Vec(inner) | IRType::Vector(inner, _) => mentions(inner, name),
+        IRType::Map(k, v) => mentions(k, name) || mentions(v, name),
+        IRType::Function { params, ret } => params.iter().any(|p| mentions(p, name)) || mentions(ret, name),
+        _ => false,
+    }
+}
+
+/// Where `break` and `continue` jump from the body of a loop
+#[derive(Clone)]
+struct LoopTargets {
+    continue_block: BlockId,
+    break_block: BlockId,
+    /// Slot receiving `break value` in a `loop`, and the value's type
+    result: Option<(Register, IRType)>,
+}
+
+/// Where an assignment stores its value
+enum Lvalue {
+    /// A local held in a register
+    Register(Register, IRType),
+    /// Memory at `ptr`, holding a value of type `ty`
+    Memory { ptr: Value, ty: IRType, volatile: bool },
+}
 
 /// IR Generator
+#[derive(Clone)]
 pub struct IRGenerator {
     /// Current module being built
     module: IRModule,
@@ -30,10 +87,115 @@ pub struct IRGenerator {
     reg_types: HashMap<Register, IRType>,
     /// Struct definitions (name -> fields)
     struct_defs: HashMap<String, Vec<(String, IRType)>>,
+    /// Generic enums, laid out once with their type parameters erased
+    generic_enums: HashSet<String>,
+    /// Type parameter each payload field of a generic enum variant holds,
+    /// by (enum, variant)
+    erased_payloads: HashMap<(String, String), Vec<Option<usize>>>,
+    /// Type arguments of the generic enum each function returns
+    returned_type_args: HashMap<String, Vec<IRType>>,
+    /// Type arguments of the generic enum each local or parameter is declared as
+    local_type_args: HashMap<Register, Vec<IRType>>,
+    /// Extern functions returning a field-less enum, which C returns as its
+    /// discriminant (function name -> enum type)
+    extern_enum_returns: HashMap<String, IRType>,
     /// Current function's sret pointer (for struct returns)
     sret_ptr: Option<Value>,
+    /// Struct or tuple the current function returns by value through its sret pointer
+    sret_struct: Option<IRType>,
     /// Pre-scanned function signatures for forward reference (name -> (ret_type, sret_type))
     function_signatures: HashMap<String, (IRType, Option<IRType>)>,
+    /// Pre-scanned parameter types, as declared (before any sret parameter)
+    function_params: HashMap<String, Vec<IRType>>,
+    /// Associated type bindings: (impl target, assoc name) -> concrete type
+    assoc_types: HashMap<(String, String), AstType>,
+    /// Target type of the impl block being lowered (substituted for `Self`)
+    current_impl: Option<String>,
+    /// Locals holding `*volatile T` pointers (accesses through them are volatile)
+    volatile_locals: HashSet<String>,
+    /// Locals declared as `*T` or `&T`; unlike struct values, which are
+    /// already addresses, `&` of one needs a stack slot
+    pointer_locals: HashSet<String>,
+    /// Element type and length of locals declared as `[T; N]` (or a pointer
+    /// to one), which lower to a pointer to the first element
+    array_locals: HashMap<String, (IRType, usize)>,
+    /// Enclosing `mod` blocks of the item being lowered
+    module_path: Vec<String>,
+    /// Source path of each function (`helper`, `geo::area`, `Point::new`) -> emitted symbol
+    function_symbols: HashMap<String, String>,
+    /// Extension methods of primitives: (type tag of the receiver, method) -> emitted symbol
+    primitive_methods: HashMap<(String, String), String>,
+    /// Symbol and span of every function emitted, for duplicate detection
+    function_spans: Vec<(String, Span)>,
+    /// Generic parameters of const generic functions (symbol -> const param name per position)
+    const_generic_params: HashMap<String, Vec<Option<String>>>,
+    /// Lowered bodies of const generic functions, specialized per call site
+    const_templates: HashMap<String, IRFunction>,
+    /// Requested specializations: (template symbol, instance symbol, substitution)
+    const_instances: Vec<(String, String, ConstSubstitution)>,
+    /// Functions with interface-bounded type parameters (`<T: Shape>`) and
+    /// their enclosing `mod` path, by symbol; only specializations are emitted
+    bounded_templates: HashMap<String, (ast::Function, Vec<String>)>,
+    /// Requested specializations: (template symbol, instance symbol, type arguments)
+    bounded_instances: Vec<(String, String, HashMap<String, AstType>)>,
+    /// Concrete types of the type parameters of the specialization being lowered
+    type_args: HashMap<String, AstType>,
+    /// Counter assignment for `--coverage`
+    coverage: Option<CoverageInstrumenter>,
+    /// Span of the first code lowered into each block of the current function
+    block_spans: HashMap<usize, Span>,
+    /// Source of the unit, for panic locations and `-g`
+    source_lines: Option<SourceLines>,
+    /// Whether to record source locations in the IR (`-g`)
+    debug_info: bool,
+    /// Shadow stack bookkeeping for `--panic`
+    panic_mode: PanicMode,
+    /// Debug-build checks, such as poisoning pointers passed to `delete`
+    debug_assertions: bool,
+    /// What `+`, `-` and `*` do on integer overflow
+    overflow_mode: OverflowMode,
+    /// Last location marked in the current function: (block, file, line)
+    last_debug_loc: Option<(BlockId, String, usize)>,
+    /// Replace `main` with a timing harness (`aethc bench`)
+    benchmark: Option<BenchConfig>,
+    /// Source paths of `#[bench]` functions
+    bench_functions: Vec<String>,
+    /// Structs whose literals are followed by an invariant check
+    invariant_structs: HashSet<String>,
+    /// Structs with invariants whose checker has not been generated yet
+    pending_invariants: Vec<ast::StructDef>,
+    /// Structs and enums declared `#[derive(Eq)]`, which `==` compares
+    /// with their generated equality function
+    derived_eq: HashSet<String>,
+    /// Derived types whose equality function has not been generated yet
+    pending_equalities: Vec<String>,
+    /// Interfaces each type implements, as (type, interface); an operator
+    /// interface's method is what its operator calls
+    interface_impls: HashSet<(String, String)>,
+    /// Function whose body is being lowered, which names its closures
+    closure_owner: String,
+    /// Closures lowered so far in `closure_owner`
+    closure_count: usize,
+    /// Enclosing loops, innermost last
+    loops: Vec<LoopTargets>,
+    /// Worker threads for lowering function bodies (`--jobs`)
+    jobs: usize,
+    /// Parser of imported modules, whose cache may be shared with analysis
+    module_loader: ModuleLoader,
+    /// Expressions being lowered around the current one
+    nesting: usize,
+    /// Deepest nesting of expressions accepted (`--max-nesting`)
+    max_nesting: usize,
+}
+
+/// Name of the function checking the invariants of struct `name`
+fn invariant_checker(name: &str) -> String {
+    format!("__check_invariants_{}", name)
+}
+
+/// Name of the function comparing two values of the `#[derive(Eq)]` type `name`
+fn equality_function(name: &str) -> String {
+    format!("__eq_{}", name)
 }
 
 impl IRGenerator {
@@ -46,11 +208,118 @@ impl IRGenerator {
             locals: HashMap::new(),
             reg_types: HashMap::new(),
             struct_defs: HashMap::new(),
+            generic_enums: PRELUDE_ENUMS.iter().map(|(name, _)| name.to_string()).collect(),
+            erased_payloads: [("Option", "Some", 0), ("Result", "Ok", 0), ("Result", "Err", 1)].into_iter()
+                .map(|(name, variant, param)| ((name.to_string(), variant.to_string()), vec![Some(param)]))
+                .collect(),
+            returned_type_args: HashMap::new(),
+            local_type_args: HashMap::new(),
+            extern_enum_returns: HashMap::new(),
             sret_ptr: None,
+            sret_struct: None,
             function_signatures: HashMap::new(),
+            function_params: HashMap::new(),
+            assoc_types: HashMap::new(),
+            current_impl: None,
+            volatile_locals: HashSet::new(),
+            pointer_locals: HashSet::new(),
+            array_locals: HashMap::new(),
+            module_path: Vec::new(),
+            function_symbols: HashMap::new(),
+            primitive_methods: HashMap::new(),
+            function_spans: Vec::new(),
+            const_generic_params: HashMap::new(),
+            const_templates: HashMap::new(),
+            const_instances: Vec::new(),
+            bounded_templates: HashMap::new(),
+            bounded_instances: Vec::new(),
+            type_args: HashMap::new(),
+            coverage: None,
+            block_spans: HashMap::new(),
+            source_lines: None,
+            debug_info: false,
+            panic_mode: PanicMode::default(),
+            debug_assertions: false,
+            overflow_mode: OverflowMode::default(),
+            last_debug_loc: None,
+            benchmark: None,
+            bench_functions: Vec::new(),
+            invariant_structs: HashSet::new(),
+            pending_invariants: Vec::new(),
+            derived_eq: HashSet::new(),
+            pending_equalities: Vec::new(),
+            interface_impls: HashSet::new(),
+            closure_owner: String::new(),
+            closure_count: 0,
+            loops: Vec::new(),
+            jobs: 1,
+            module_loader: ModuleLoader::new(),
+            nesting: 0,
+            max_nesting: DEFAULT_MAX_NESTING,
         }
     }
 
+    /// Report runtime failures at their location in `source`
+    pub fn with_source(mut self, file: &str, source: &str) -> Self {
+        self.source_lines = Some(SourceLines::new(file, source));
+        self
+    }
+
+    /// Record the source location of functions and statements in `source`
+    pub fn with_debug_info(mut self, file: &str, source: &str) -> Self {
+        self.debug_info = true;
+        self.with_source(file, source)
+    }
+
+    /// Maintain a shadow stack for panic backtraces
+    pub fn with_panic_mode(mut self, mode: PanicMode) -> Self {
+        self.panic_mode = mode;
+        self
+    }
+
+    /// Enable debug-build checks (`-O0`)
+    pub fn with_debug_assertions(mut self, enabled: bool) -> Self {
+        self.debug_assertions = enabled;
+        self
+    }
+
+    /// Check integer arithmetic for overflow instead of wrapping
+    pub fn with_overflow_mode(mut self, mode: OverflowMode) -> Self {
+        self.overflow_mode = mode;
+        self
+    }
+
+    /// Count executions of every basic block, attributed to lines of `source`
+    pub fn with_coverage(mut self, file: &str, source: &str, data_path: &str) -> Self {
+        self.coverage = Some(CoverageInstrumenter::new(file, source, data_path));
+        self
+    }
+
+    /// Lower function bodies on `jobs` worker threads
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Read imported modules through `loader`, which may already hold them
+    pub fn with_module_loader(mut self, loader: ModuleLoader) -> Self {
+        self.module_loader = loader;
+        self
+    }
+
+    /// Accept expressions nested at most `limit` levels deep, the same limit
+    /// the parser and analyzer apply
+    pub fn with_max_nesting(mut self, limit: usize) -> Self {
+        self.max_nesting = limit;
+        self
+    }
+
+    /// Replace `main` with a harness timing the configured functions
+    pub fn with_benchmark(mut self, config: BenchConfig) -> Self {
+        self.benchmark = Some(config);
+        self
+    }
+
     /// Generate IR for a program
     pub fn generate(&mut self, program: &Program) -> Result<IRModule> {
         // Process inner attributes: #![no_std], #![no_main]
@@ -69,17 +338,421 @@ impl IRGenerator {
         }
         
         // Phase 1: Collect all function signatures (for forward reference)
-        for item in &program.items {
+        let prelude_items = stdlib::prelude_items(program);
+        for item in prelude_items.iter().chain(&program.items) {
             self.collect_signatures(item);
         }
         
-        // Phase 2: Generate IR for all items
-        for item in &program.items {
-            self.generate_item(item)?;
+        // Phase 2: Generate type and global declarations first, including
+        // those in `mod` blocks, so function bodies can see them (layout
+        // queries, static variable accesses)
+        self.generate_declarations(prelude_items)?;
+        self.generate_declarations(&program.items)?;
+        self.reorder_fields(&program.items);
+        self.lower_extern_enums();
+        let prelude = self.declare_prelude_enums();
+
+        // Phase 3: Generate IR for all remaining items
+        self.generate_prelude_bodies(prelude_items)?;
+        self.generate_bodies(&program.items)?;
+
+        // Specializations of interface-bounded functions, which may call for more
+        let mut lowered = 0;
+        while let Some((template, name, type_args)) = self.bounded_instances.get(lowered).cloned() {
+            lowered += 1;
+            let (func, path) = self.bounded_templates[&template].clone();
+            self.type_args = type_args;
+            self.module_path = path;
+            self.generate_function_with_name(&func, &name)?;
+        }
+        self.type_args.clear();
+        self.module_path.clear();
+
+        // Invariant checkers, once every struct layout is known
+        for def in std::mem::take(&mut self.pending_invariants) {
+            self.generate_invariant_checker(&def)?;
+        }
+        for name in std::mem::take(&mut self.pending_equalities) {
+            self.generate_equality_function(&name);
+        }
+
+        // Phase 4: Specialize const generic functions for the arguments seen at call sites
+        for (template, name, substitution) in std::mem::take(&mut self.const_instances) {
+            let template = self.const_templates.get(&template)
+                .ok_or_else(|| Error::CodeGen(format!("no body for const generic function '{}'", template)))?;
+            let func = ConstMonomorphizer::new(substitution).specialize(template, &name)?;
+            self.module.functions.push(func);
+        }
+
+        self.prune_prelude_enums(&prelude);
+        self.prune_prelude_items(prelude_items);
+        self.order_structs();
+        self.check_duplicate_symbols()?;
+        if let Some(config) = self.benchmark.take() {
+            self.install_bench_harness(&config)?;
+        }
+        if self.panic_mode == PanicMode::UnwindTrace {
+            // Backtraces show source paths (`geo::area`) rather than symbols
+            let source_paths: HashMap<&str, &str> = self.function_symbols.iter()
+                .map(|(path, symbol)| (symbol.as_str(), path.as_str()))
+                .collect();
+            for func in &mut self.module.functions {
+                let name = source_paths.get(func.name.as_str()).map_or(func.name.clone(), |p| p.to_string());
+                panic::instrument_trace(func, &name);
+            }
         }
+        self.module.coverage = self.coverage.take().map(CoverageInstrumenter::finish);
         Ok(self.module.clone())
     }
     
+    /// Declare the prelude enums the program does not define itself,
+    /// returning their names
+    fn declare_prelude_enums(&mut self) -> Vec<&'static str> {
+        use crate::middle::ir::{IREnum, IRVariant};
+        let mut declared = Vec::new();
+        for (name, variants) in PRELUDE_ENUMS {
+            if self.module.enums.iter().any(|e| e.name == name) {
+                continue;
+            }
+            self.module.enums.push(IREnum {
+                name: name.to_string(),
+                variants: variants.iter().zip(0..).map(|((variant, payload), discriminant)| IRVariant {
+                    name: variant.to_string(),
+                    // The type parameter is erased, as in user-defined generic enums
+                    fields: if *payload { vec![IRType::I64] } else { vec![] },
+                    discriminant,
+                }).collect(),
+                tag: IRType::I32,
+            });
+            declared.push(name);
+        }
+        declared
+    }
+
+    /// Drop the declared prelude enums that no function, struct or global
+    /// refers to, so programs without them lower as before
+    fn prune_prelude_enums(&mut self, declared: &[&str]) {
+        let module = &self.module;
+        let used = |name: &str| {
+            let constructor = format!("{}_", name);
+            module.functions.iter().any(|f| {
+                f.params.iter().any(|(_, ty)| mentions(ty, name))
+                    || mentions(&f.ret_type, name)
+                    || f.blocks.iter().flat_map(|b| &b.instructions).any(|inst| {
+                        matches!(inst, Instruction::Call { func, .. } if func.starts_with(&constructor))
+                    })
+            })
+                || module.structs.iter().flat_map(|s| &s.fields).any(|(_, ty)| mentions(ty, name))
+                || module.globals.iter().any(|g| mentions(&g.ty, name))
+                || module.externs.iter().any(|e| e.params.iter().any(|(_, ty)| mentions(ty, name)) || mentions(&e.ret_type, name))
+        };
+        let unused: Vec<&str> = declared.iter().copied().filter(|name| !used(name)).collect();
+        self.module.enums.retain(|e| !unused.contains(&e.name.as_str()));
+    }
+
+    /// Lower the prelude's functions without debug info or coverage, their
+    /// spans pointing into stdlib/string.aeth rather than the program
+    fn generate_prelude_bodies(&mut self, items: &[Item]) -> Result<()> {
+        let coverage = self.coverage.take();
+        let source_lines = self.source_lines.take();
+        let debug_info = std::mem::replace(&mut self.debug_info, false);
+        let result = self.generate_bodies(items);
+        self.coverage = coverage;
+        self.source_lines = source_lines;
+        self.debug_info = debug_info;
+        result
+    }
+
+    /// Drop the prelude's functions the program does not reach, and its
+    /// structs once nothing refers to them, so programs that do not use
+    /// `String` lower as before
+    fn prune_prelude_items(&mut self, items: &[Item]) {
+        let defined: HashSet<String> = self.function_spans.iter()
+            .filter(|(_, span)| span.file_id == PRELUDE_FILE_ID)
+            .map(|(symbol, _)| symbol.clone())
+            .collect();
+        let callees = |func: &IRFunction| -> Vec<String> {
+            func.blocks.iter().flat_map(|b| &b.instructions).flat_map(|inst| {
+                let call = match inst {
+                    Instruction::Call { func, .. } => Some(func.clone()),
+                    _ => None,
+                };
+                let globals = inst.operands().into_iter().filter_map(|value| match value {
+                    Value::Global(name) => Some(name.clone()),
+                    _ => None,
+                });
+                call.into_iter().chain(globals).collect::<Vec<_>>()
+            }).collect()
+        };
+        let mut pending: Vec<String> = self.module.functions.iter()
+            .filter(|f| !defined.contains(&f.name))
+            .flat_map(callees)
+            .collect();
+        let mut reached = HashSet::new();
+        while let Some(name) = pending.pop() {
+            if defined.contains(&name) && reached.insert(name.clone()) {
+                if let Some(func) = self.module.functions.iter().find(|f| f.name == name) {
+                    pending.extend(callees(func));
+                }
+            }
+        }
+        let unreached = |symbol: &String| defined.contains(symbol) && !reached.contains(symbol);
+        self.module.functions.retain(|f| !unreached(&f.name));
+        self.function_spans.retain(|(symbol, _)| !unreached(symbol));
+
+        for item in items {
+            let Item::Struct(def) = item else { continue };
+            let name = def.name.name.as_str();
+            let module = &self.module;
+            let used = module.functions.iter().any(|f| {
+                f.params.iter().any(|(_, ty)| mentions(ty, name))
+                    || mentions(&f.ret_type, name)
+                    || f.blocks.iter().flat_map(|b| &b.instructions)
+                        .any(|inst| matches!(inst, Instruction::Alloca { ty, .. } if mentions(ty, name)))
+            })
+                || module.structs.iter().filter(|s| s.name != name).flat_map(|s| &s.fields).any(|(_, ty)| mentions(ty, name))
+                || module.globals.iter().any(|g| mentions(&g.ty, name));
+            if !used {
+                self.module.structs.retain(|s| s.name != name);
+                self.struct_defs.remove(name);
+            }
+        }
+    }
+
+    /// `a + b` with a `String` on the left: a new `String` holding the bytes
+    /// of `a` and then of `b`, a `String` or a `str`, built with
+    /// `String::new` and `String::push_str`
+    fn generate_string_concat(&mut self, left: Value, right: Value) -> Option<Value> {
+        let left_ty = self.get_value_type(&left);
+        if !Self::is_string_struct(left_ty.as_ref()) {
+            return None;
+        }
+        let (left, _) = self.auto_deref_struct(left, left_ty);
+        let result = self.call_method("String", "new", vec![]);
+        let left = self.call_method("String", "as_str", vec![left]);
+        self.call_method("String", "push_str", vec![result.clone(), left]);
+
+        let right_ty = self.get_value_type(&right);
+        let right = if Self::is_string_struct(right_ty.as_ref()) {
+            let (right, _) = self.auto_deref_struct(right, right_ty);
+            self.call_method("String", "as_str", vec![right])
+        } else {
+            self.coerce_str(right, &IRType::Str)
+        };
+        self.call_method("String", "push_str", vec![result.clone(), right]);
+        Some(result)
+    }
+
+    /// Whether `ty` is the prelude's `String`, or a pointer to one
+    fn is_string_struct(mut ty: Option<&IRType>) -> bool {
+        while let Some(IRType::Ptr(inner)) = ty {
+            ty = Some(inner.as_ref());
+        }
+        matches!(ty, Some(IRType::Struct(name)) if name == "String")
+    }
+
+    /// Call a method of struct or enum `type_name`, such as the prelude's
+    /// `String`, returning a struct result through a fresh sret slot
+    fn call_method(&mut self, type_name: &str, method: &str, args: Vec<Value>) -> Value {
+        let func = self.resolve_function(&[type_name, method])
+            .unwrap_or_else(|| mangle::method_symbol(type_name, method));
+        let (ret_type, sret_type) = self.function_signatures.get(&func).cloned().unwrap_or((IRType::Void, None));
+        if let Some(IRType::Ptr(result)) = sret_type {
+            let slot = self.alloc_register();
+            self.emit_current_with_type(Instruction::Alloca { dest: slot, ty: (*result).clone() }, IRType::Ptr(result));
+            let args = std::iter::once(Value::Register(slot)).chain(args).collect();
+            self.emit_current_with_type(Instruction::Call { dest: None, func, args }, IRType::Void);
+            return Value::Register(slot);
+        }
+        if ret_type == IRType::Void {
+            self.emit_current_with_type(Instruction::Call { dest: None, func, args }, IRType::Void);
+            return Value::Unit;
+        }
+        let dest = self.alloc_register();
+        self.emit_current_with_type(Instruction::Call { dest: Some(dest), func, args }, ret_type);
+        Value::Register(dest)
+    }
+
+    /// What a function returning `ret_type` copies to its sret pointer:
+    /// structs and tuples, and enums, which are heap pointers
+    fn sret_struct_of(&self, ret_type: &IRType) -> Option<IRType> {
+        match ret_type {
+            IRType::Struct(_) => Some(ret_type.clone()),
+            _ => self.enum_of(ret_type).map(|e| IRType::Struct(e.name.clone())),
+        }
+    }
+
+    /// Copy a returned value built anywhere but the sret pointer there
+    fn store_sret(&mut self, returned: Value) {
+        let sret = self.locals.get("__sret").map(|(v, _)| v.clone());
+        if let (Some(ty), Some(sret)) = (self.sret_struct.clone(), sret) {
+            if returned != sret && self.get_value_type(&returned).is_some_and(|t| matches!(t, IRType::Ptr(_))) {
+                self.emit_store_field(sret, returned, &ty);
+            }
+        }
+    }
+
+    /// Swap the program's `main` for the `aethc bench` harness
+    fn install_bench_harness(&mut self, config: &BenchConfig) -> Result<()> {
+        let paths = match &config.function {
+            Some(name) => vec![name.clone()],
+            None => self.bench_functions.clone(),
+        };
+        if paths.is_empty() {
+            return Err(Error::CodeGen("nothing to benchmark: name a function or mark one #[bench]".to_string()));
+        }
+        self.module.functions.retain(|f| f.name != "main");
+        let mut targets = Vec::new();
+        for path in paths {
+            let func = self.function_symbols.get(&path)
+                .and_then(|symbol| self.module.functions.iter().find(|f| &f.name == symbol))
+                .ok_or_else(|| Error::CodeGen(format!("no function '{}' to benchmark", path)))?;
+            if !func.params.is_empty() {
+                return Err(Error::CodeGen(format!("benchmarked function '{}' must take no parameters", path)));
+            }
+            targets.push((path, func));
+        }
+        let harness = bench::harness(&targets, config.iterations);
+        self.module.functions.push(harness);
+        Ok(())
+    }
+
+    /// Items generated before function bodies
+    fn is_declaration_item(item: &Item) -> bool {
+        matches!(item, Item::Struct(_) | Item::Union(_) | Item::Enum(_) | Item::Static(_) | Item::Extern(_) | Item::Use(_))
+    }
+
+    /// Generate the declaration items of `items` and of the modules among them
+    fn generate_declarations(&mut self, items: &[Item]) -> Result<()> {
+        for item in items {
+            match item {
+                Item::Module(m) => {
+                    if let Some(items) = &m.items {
+                        self.module_path.push(m.name.name.clone());
+                        let result = self.generate_declarations(items);
+                        self.module_path.pop();
+                        result?;
+                    }
+                }
+                _ if Self::is_declaration_item(item) => self.generate_item(item)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Lay out the fields of `#[field_order(optimal)]` structs largest
+    /// first, before any body refers to a field by its index
+    fn reorder_fields(&mut self, items: &[Item]) {
+        for item in items {
+            match item {
+                Item::Module(ast::ModuleDef { items: Some(items), .. }) => self.reorder_fields(items),
+                Item::Struct(def) if def.field_order() == Some(ast::FieldOrder::Optimal) => {
+                    let Some(index) = self.module.structs.iter().position(|s| s.name == def.name.name) else { continue };
+                    let fields = &self.module.structs[index].fields;
+                    // Fields without a layout (generic parameters) keep the declared order
+                    let Some(layouts) = fields.iter().map(|(_, ty)| self.module.layout_of(ty)).collect::<Option<Vec<_>>>() else {
+                        continue;
+                    };
+                    let fields: Vec<(String, IRType)> = optimal_field_order(&layouts).into_iter()
+                        .map(|i| fields[i].clone())
+                        .collect();
+                    self.struct_defs.insert(def.name.name.clone(), fields.clone());
+                    self.module.structs[index].fields = fields;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Lower the remaining items. Each reads only the declarations, so with
+    /// several jobs they are lowered by forked generators and the results are
+    /// appended in source order, the same as with one.
+    fn generate_bodies(&mut self, items: &[Item]) -> Result<()> {
+        let items: Vec<&Item> = items.iter().filter(|item| !Self::is_declaration_item(item)).collect();
+        // Counters are numbered in lowering order
+        if self.coverage.is_some() {
+            return items.into_iter().try_for_each(|item| self.generate_item(item));
+        }
+        let forked_at = (self.module.functions.len(), self.function_spans.len());
+        let workers = map_chunks(&items, self.jobs, |items| {
+            let mut worker = self.clone();
+            let result = items.iter().try_for_each(|item| worker.generate_item(item));
+            result.map(|()| worker)
+        });
+        for worker in workers {
+            self.absorb(worker?, forked_at);
+        }
+        Ok(())
+    }
+
+    /// Put every struct after the structs it holds by value, so backends can
+    /// define them in order; tuple structs are declared as they are first seen
+    fn order_structs(&mut self) {
+        let mut pending = std::mem::take(&mut self.module.structs);
+        while !pending.is_empty() {
+            let ready = pending.iter()
+                .position(|def| def.fields.iter().all(|(_, ty)| match ty {
+                    IRType::Struct(name) => *name == def.name || !pending.iter().any(|p| p.name == *name),
+                    _ => true,
+                }))
+                .unwrap_or(0);
+            self.module.structs.push(pending.remove(ready));
+        }
+    }
+
+    /// Take over what a generator forked with `forked_at` functions and
+    /// function spans has lowered since
+    fn absorb(&mut self, worker: IRGenerator, forked_at: (usize, usize)) {
+        self.module.functions.extend(worker.module.functions.into_iter().skip(forked_at.0));
+        // Tuple structs declared while lowering bodies
+        for def in worker.module.structs {
+            if let Some(fields) = worker.struct_defs.get(&def.name).filter(|_| !self.struct_defs.contains_key(&def.name)) {
+                self.struct_defs.insert(def.name.clone(), fields.clone());
+                self.module.structs.push(def);
+            }
+        }
+        self.function_spans.extend(worker.function_spans.into_iter().skip(forked_at.1));
+        self.const_templates.extend(worker.const_templates);
+        for (template, instance, substitution) in worker.const_instances {
+            if !self.const_instances.iter().any(|(_, seen, _)| *seen == instance) {
+                if let Some(signature) = worker.function_signatures.get(&instance) {
+                    self.function_signatures.insert(instance.clone(), signature.clone());
+                }
+                self.const_instances.push((template, instance, substitution));
+            }
+        }
+        for (template, instance, type_args) in worker.bounded_instances {
+            if !self.bounded_instances.iter().any(|(_, seen, _)| *seen == instance) {
+                if let Some(signature) = worker.function_signatures.get(&instance) {
+                    self.function_signatures.insert(instance.clone(), signature.clone());
+                }
+                if let Some(params) = worker.function_params.get(&instance) {
+                    self.function_params.insert(instance.clone(), params.clone());
+                }
+                self.bounded_instances.push((template, instance, type_args));
+            }
+        }
+    }
+
+    /// Pass field-less enums to and from extern functions as their
+    /// discriminant, the way C declares them
+    fn lower_extern_enums(&mut self) {
+        let mut externs = std::mem::take(&mut self.module.externs);
+        for ext in &mut externs {
+            for (_, ty) in &mut ext.params {
+                if let Some(tag) = self.c_enum_tag(ty) {
+                    *ty = tag;
+                }
+            }
+            if let Some(tag) = self.c_enum_tag(&ext.ret_type) {
+                let enum_ty = std::mem::replace(&mut ext.ret_type, tag);
+                self.extern_enum_returns.insert(ext.name.clone(), enum_ty);
+            }
+        }
+        self.module.externs = externs;
+    }
+
     /// Register C library extern functions for self-hosting
     fn register_c_library_externs(&mut self) {
         // atof - string to float conversion
@@ -114,32 +787,75 @@ impl IRGenerator {
         });
     }
     
+    /// Record the parameter and return types `symbol`, lowered from `func`, is called with
+    fn record_signature(&mut self, symbol: String, func: &ast::Function) {
+        let ret_type = if let Some(ref ty) = func.ret_type {
+            self.lower_type(ty)
+        } else {
+            IRType::Void
+        };
+
+        // Check if this is an sret function (direct struct or pointer-to-struct return)
+        let sret_type = match &ret_type {
+            IRType::Struct(_) => Some(IRType::Ptr(Box::new(ret_type.clone()))),  // Convert to pointer
+            IRType::Ptr(inner) if matches!(inner.as_ref(), IRType::Struct(_)) => Some(ret_type.clone()),
+            _ => None,
+        };
+
+        if let Some(args) = func.ret_type.as_ref().and_then(|ty| self.enum_type_args(ty)) {
+            self.returned_type_args.insert(symbol.clone(), args);
+        }
+        let params = self.lower_params(&func.params).into_iter().map(|(_, ty)| ty).collect();
+        self.function_params.insert(symbol.clone(), params);
+        self.function_signatures.insert(symbol, (ret_type, sret_type));
+    }
+
     /// Collect function signatures for forward reference
     fn collect_signatures(&mut self, item: &Item) {
         match item {
             Item::Function(func) => {
-                let func_name = func.name.name.clone();
-                let ret_type = if let Some(ref ty) = func.ret_type {
-                    self.ast_type_to_ir(ty)
-                } else {
-                    IRType::Void
-                };
-                
-                // Check if this is an sret function (direct struct or pointer-to-struct return)
-                let sret_type = match &ret_type {
-                    IRType::Struct(_) => Some(IRType::Ptr(Box::new(ret_type.clone()))),  // Convert to pointer
-                    IRType::Ptr(inner) if matches!(inner.as_ref(), IRType::Struct(_)) => Some(ret_type.clone()),
-                    _ => None,
-                };
-                
-                self.function_signatures.insert(func_name, (ret_type, sret_type));
+                let func_name = self.function_symbol(func);
+                self.function_symbols.insert(self.source_path(&[&func.name.name]), func_name.clone());
+                if func.has_annotation("bench") {
+                    self.bench_functions.push(self.source_path(&[&func.name.name]));
+                }
+                let bounded = func.generic_params.iter()
+                    .any(|p| matches!(p, ast::GenericParam::Type { bounds, .. } if !bounds.is_empty()));
+                if bounded {
+                    self.bounded_templates.insert(func_name.clone(), (func.clone(), self.module_path.clone()));
+                }
+                if !func.const_params().is_empty() {
+                    let positions = func.generic_params.iter()
+                        .map(|p| match p {
+                            ast::GenericParam::Const { name, .. } => Some(name.name.clone()),
+                            ast::GenericParam::Type { .. } => None,
+                        })
+                        .collect();
+                    self.const_generic_params.insert(func_name.clone(), positions);
+                }
+                self.record_signature(func_name, func);
             }
             Item::Impl(impl_block) => {
                 let type_name = &impl_block.target.name;
+                for binding in &impl_block.associated_types {
+                    self.assoc_types.insert((type_name.clone(), binding.name.name.clone()), binding.ty.clone());
+                }
+                if let Some(interface) = &impl_block.interface {
+                    self.interface_impls.insert((type_name.clone(), interface.name.clone()));
+                }
+                self.current_impl = Some(type_name.clone());
+                let target_ty = self.lower_type(&AstType::Named(type_name.clone(), impl_block.target.span));
+                let is_primitive = matches!(target_ty,
+                    IRType::Bool | IRType::I8 | IRType::I16 | IRType::I32 | IRType::I64
+                    | IRType::U8 | IRType::U16 | IRType::U32 | IRType::U64 | IRType::F32 | IRType::F64);
                 for method in &impl_block.methods {
-                    let func_name = format!("{}_{}", type_name, method.name.name);
+                    let func_name = self.method_symbol(type_name, &method.name.name);
+                    self.function_symbols.insert(self.source_path(&[type_name, &method.name.name]), func_name.clone());
+                    if is_primitive {
+                        self.primitive_methods.insert((mangle::type_tag(&target_ty), method.name.name.clone()), func_name.clone());
+                    }
                     let ret_type = if let Some(ref ty) = method.ret_type {
-                        self.ast_type_to_ir(ty)
+                        self.lower_type(ty)
                     } else {
                         IRType::Void
                     };
@@ -151,14 +867,35 @@ impl IRGenerator {
                         _ => None,
                     };
                     
+                    let params = self.lower_params(&method.params).into_iter().map(|(_, ty)| ty).collect();
+                    self.function_params.insert(func_name.clone(), params);
                     self.function_signatures.insert(func_name, (ret_type, sret_type));
                 }
+                self.current_impl = None;
+            }
+            Item::Enum(e) if !e.generic_params.is_empty() || !e.type_params.is_empty() => {
+                self.generic_enums.insert(e.name.name.clone());
+                let params: Vec<&str> = match e.generic_params.is_empty() {
+                    true => e.type_params.iter().map(|p| p.name.as_str()).collect(),
+                    false => e.generic_params.iter().map(|p| p.name().name.as_str()).collect(),
+                };
+                for variant in &e.variants {
+                    let fields = variant.fields.iter()
+                        .map(|field| match field {
+                            AstType::Named(name, _) => params.iter().position(|p| p == name),
+                            _ => None,
+                        })
+                        .collect();
+                    self.erased_payloads.insert((e.name.name.clone(), variant.name.name.clone()), fields);
+                }
             }
             Item::Module(m) => {
                 if let Some(items) = &m.items {
+                    self.module_path.push(m.name.name.clone());
                     for sub_item in items {
                         self.collect_signatures(sub_item);
                     }
+                    self.module_path.pop();
                 }
             }
             _ => {}
@@ -168,10 +905,19 @@ impl IRGenerator {
     /// Generate IR for a top-level item
     fn generate_item(&mut self, item: &Item) -> Result<()> {
         match item {
+            Item::Function(func) if !func.const_params().is_empty() => {
+                // Kept out of the module; only its specializations are emitted
+                self.generate_function(func)?;
+                let template = self.module.functions.pop().expect("function was just generated");
+                self.const_templates.insert(template.name.clone(), template);
+                Ok(())
+            }
+            // Kept out of the module; only its specializations are emitted
+            Item::Function(func) if self.bounded_templates.contains_key(&self.function_symbol(func)) => Ok(()),
             Item::Function(func) => self.generate_function(func),
             Item::Struct(struct_def) => {
                 let fields: Vec<_> = struct_def.fields.iter()
-                    .map(|f| (f.name.name.clone(), self.ast_type_to_ir(&f.ty)))
+                    .map(|f| (f.name.name.clone(), self.lower_type(&f.ty)))
                     .collect();
                 
                 // Extract repr from annotations
@@ -194,45 +940,80 @@ impl IRGenerator {
                 
                 self.struct_defs.insert(struct_def.name.name.clone(), fields.clone());
                 self.module.add_struct(&struct_def.name.name, fields, repr);
+                if let Some(added) = self.module.structs.last_mut() {
+                    added.is_pub = struct_def.is_pub;
+                    if struct_def.fields.iter().any(|f| f.bits.is_some()) {
+                        added.bits = struct_def.fields.iter().map(|f| f.bits.map(|b| b as u32)).collect();
+                    }
+                }
+                if !struct_def.invariants.is_empty() && struct_def.generic_params.is_empty() {
+                    self.invariant_structs.insert(struct_def.name.name.clone());
+                    self.pending_invariants.push(struct_def.clone());
+                }
+                if struct_def.derives_eq() && struct_def.generic_params.is_empty() {
+                    self.derived_eq.insert(struct_def.name.name.clone());
+                    self.pending_equalities.push(struct_def.name.name.clone());
+                }
                 Ok(())
             }
             Item::Enum(enum_def) => {
                 use crate::middle::ir::{IREnum, IRVariant};
+                let tag = match &enum_def.repr {
+                    Some(repr) if repr.name != "C" => self.ast_type_to_ir(&AstType::Named(repr.name.clone(), repr.span)),
+                    _ => IRType::I32,
+                };
+                // Semantic analysis checked the explicit values; the rest count up
+                let mut next = 0;
                 let mut variants = Vec::new();
                 for variant in &enum_def.variants {
                     let fields: Vec<IRType> = variant.fields
                         .iter()
-                        .map(|ty| self.ast_type_to_ir(ty))
+                        .map(|ty| self.lower_type(ty))
                         .collect();
+                    let discriminant = match variant.discriminant.as_ref().and_then(|d| self.eval_const_value(d)) {
+                        Some(Constant::Int(n)) => n,
+                        _ => next,
+                    };
+                    next = discriminant.wrapping_add(1);
                     variants.push(IRVariant {
                         name: variant.name.name.clone(),
                         fields,
+                        discriminant,
                     });
                 }
                 self.module.enums.push(IREnum {
                     name: enum_def.name.name.clone(),
                     variants,
+                    tag,
                 });
+                if enum_def.derives_eq() && enum_def.generic_params.is_empty() {
+                    self.derived_eq.insert(enum_def.name.name.clone());
+                    self.pending_equalities.push(enum_def.name.name.clone());
+                }
                 Ok(())
             }
 
             Item::Impl(impl_block) => {
                 let type_name = &impl_block.target.name;
-                for method in &impl_block.methods {
-                    self.generate_method(type_name, method)?;
-                }
-                Ok(())
+                self.current_impl = Some(type_name.clone());
+                let result = impl_block.methods.iter()
+                    .try_for_each(|method| self.generate_method(type_name, method));
+                self.current_impl = None;
+                result
             }
             Item::Interface(_) => Ok(()),
             Item::Const(_) => Ok(()),
             // Macro/Module/Use are handled at earlier compilation stages
             Item::Macro(_) => Ok(()),
             Item::Module(m) => {
-                // Recursively generate module items
+                // Recursively generate module items; declarations were generated up front
                 if let Some(items) = &m.items {
-                    for item in items {
-                        self.generate_item(item)?;
-                    }
+                    self.module_path.push(m.name.name.clone());
+                    let result = items.iter()
+                        .filter(|item| !Self::is_declaration_item(item))
+                        .try_for_each(|item| self.generate_item(item));
+                    self.module_path.pop();
+                    result?;
                 }
                 Ok(())
             }
@@ -265,15 +1046,56 @@ impl IRGenerator {
                                 ret_type: ir_ret,
                             });
                         }
-                        ast::ForeignItem::Static { .. } => {
-                            // TODO: Handle static extern variables
+                        ast::ForeignItem::Static { name, ty, is_mut, .. } => {
+                            self.module.globals.push(IRGlobal {
+                                name: name.name.clone(),
+                                ty: self.ast_type_to_ir(ty),
+                                init: None,
+                                is_mut: *is_mut,
+                                is_extern: true,
+                            });
                         }
                     }
                 }
                 Ok(())
             }
-            Item::Static(_) => Ok(()), // TODO: Generate global variable IR
-            Item::Union(_) => Ok(()), // TODO: Generate union type IR
+            Item::Static(static_def) => {
+                let ty = self.ast_type_to_ir(&static_def.ty);
+                let init = match &static_def.value {
+                    Some(expr) => Some(self.eval_static_initializer(expr, &ty).ok_or_else(|| {
+                        crate::utils::Error::CodeGen(format!(
+                            "initializer of static '{}' is not a constant expression",
+                            static_def.name.name
+                        ))
+                    })?),
+                    None => None,
+                };
+                // Statics are never path-prefixed, so `#[no_mangle]` needs nothing here
+                self.module.globals.push(IRGlobal {
+                    name: static_def.name.name.clone(),
+                    ty,
+                    init,
+                    is_mut: static_def.is_mut,
+                    is_extern: false,
+                });
+                Ok(())
+            }
+            Item::Union(union_def) => {
+                use crate::frontend::ast::Repr;
+                let fields: Vec<_> = union_def.fields.iter()
+                    .map(|f| (f.name.name.clone(), self.ast_type_to_ir(&f.ty)))
+                    .collect();
+                let repr = match union_def.repr {
+                    Some(Repr::C) => crate::middle::ir::StructRepr::C,
+                    Some(Repr::Packed) => crate::middle::ir::StructRepr::Packed,
+                    Some(Repr::Transparent) => crate::middle::ir::StructRepr::Transparent,
+                    None => crate::middle::ir::StructRepr::Default,
+                };
+                // Field access lowers exactly like a struct; backends overlap the storage
+                self.struct_defs.insert(union_def.name.name.clone(), fields.clone());
+                self.module.add_union(&union_def.name.name, fields, repr);
+                Ok(())
+            }
             Item::Trait(_) => Ok(()), // Trait definitions don't generate IR directly
             Item::TypeAlias(_) => Ok(()), // Type aliases are resolved at semantic level
         }
@@ -345,20 +1167,21 @@ impl IRGenerator {
     
     /// Register function signatures from an imported module
     fn register_imported_functions(&mut self, module_name: &str) -> Result<()> {
-        use crate::frontend::module::ModuleLoader;
         use crate::frontend::ast::Item;
         
-        let mut loader = ModuleLoader::new();
-        
         // Try to load the module
-        if let Ok(parsed) = loader.load_module(module_name) {
-            let items = parsed.items.clone();
-            
-            for item in &items {
+        if let Ok(parsed) = self.module_loader.load_module(module_name) {
+            for item in &parsed.items {
                 if let Item::Function(func) = item {
                     if func.is_pub {
-                        // Register function signature with module prefix
-                        let prefixed_name = format!("{}_{}", module_name, func.name.name);
+                        // Register function signature with module prefix (#[extern_c] and #[no_mangle] functions keep their name)
+                        let prefixed_name = if mangle::keeps_source_name(&func.name.name, func.is_unmangled()) {
+                            func.name.name.clone()
+                        } else {
+                            mangle::function_symbol(&[module_name], &func.name.name)
+                        };
+                        self.function_symbols.insert(format!("{}::{}", module_name, func.name.name), prefixed_name.clone());
+                        self.function_symbols.entry(func.name.name.clone()).or_insert_with(|| prefixed_name.clone());
                         
                         let ret_type = if let Some(ref ty) = func.ret_type {
                             self.ast_type_to_ir(ty)
@@ -387,33 +1210,183 @@ impl IRGenerator {
         Ok(())
     }
 
-    /// Generate IR for a method with Type_method naming convention
+    /// Generate IR for a method, emitted as `Type__method`
     fn generate_method(&mut self, type_name: &str, func: &ast::Function) -> Result<()> {
-        // Create a modified function name with type prefix
-        let prefixed_name = format!("{}_{}", type_name, func.name.name);
+        let prefixed_name = self.method_symbol(type_name, &func.name.name);
         self.generate_function_with_name(func, &prefixed_name)
     }
 
+    /// Symbol for a free function declared in the current module path
+    fn function_symbol(&self, func: &ast::Function) -> String {
+        mangle::link_name(&self.module.name, &self.module_path, &func.name.name, func.is_pub, func.is_unmangled())
+    }
+
+    /// Symbol for a method of an impl in the current module path
+    fn method_symbol(&self, type_name: &str, method: &str) -> String {
+        mangle::function_symbol(&self.module_path, &mangle::method_symbol(type_name, method))
+    }
+
+    /// `::`-joined source path of an item declared in the current module path
+    fn source_path(&self, segments: &[&str]) -> String {
+        self.module_path.iter().map(String::as_str)
+            .chain(segments.iter().copied())
+            .collect::<Vec<_>>()
+            .join("::")
+    }
+
+    /// Resolve a function referenced as `segments` from the current module,
+    /// searching the innermost enclosing `mod` first
+    fn resolve_function(&self, segments: &[&str]) -> Option<String> {
+        let mut segments = segments.to_vec();
+        if segments.first() == Some(&"Self") {
+            segments[0] = self.current_impl.as_deref()?;
+        }
+        let path = segments.join("::");
+        (0..=self.module_path.len()).rev().find_map(|depth| {
+            let key = self.module_path[..depth].iter().map(String::as_str)
+                .chain(std::iter::once(path.as_str()))
+                .collect::<Vec<_>>()
+                .join("::");
+            self.function_symbols.get(&key).cloned()
+        })
+    }
+
+    /// Symbol of the specialization of `symbol` for `args`, queuing it for emission.
+    /// Functions without const parameters are returned unchanged.
+    fn instantiate_const_generic(&mut self, symbol: String, args: &[ast::GenericArg]) -> String {
+        let Some(positions) = self.const_generic_params.get(&symbol) else {
+            return symbol;
+        };
+        let substitution: ConstSubstitution = positions.iter().zip(args)
+            .filter_map(|(param, arg)| {
+                let value = match arg {
+                    ast::GenericArg::Const(expr) => match (self.try_eval_const_expr(expr), expr) {
+                        (Some(n), _) => ConstValue::Int(n),
+                        (None, Expr::Ident(id)) => ConstValue::Param(id.name.clone()),
+                        (None, other) => ConstValue::Param(format!("{:?}", other)),
+                    },
+                    // Single-letter const parameters (`N`) parse as type arguments
+                    ast::GenericArg::Type(ty) => ConstValue::Param(match ty {
+                        AstType::Named(n, _) => n.clone(),
+                        other => format!("{:?}", other),
+                    }),
+                };
+                param.clone().map(|p| (p, value))
+            })
+            .collect();
+        let suffixes: Vec<String> = substitution.iter()
+            .map(|(_, value)| match value {
+                ConstValue::Int(n) if *n < 0 => format!("m{}", -n),
+                ConstValue::Int(n) => n.to_string(),
+                ConstValue::Param(p) => p.clone(),
+                other => format!("{:?}", other),
+            })
+            .collect();
+        let name = mangle::generic_type_name(&symbol, &suffixes);
+
+        if !self.const_instances.iter().any(|(_, instance, _)| *instance == name) {
+            if let Some(signature) = self.function_signatures.get(&symbol).cloned() {
+                self.function_signatures.insert(name.clone(), signature);
+            }
+            self.const_instances.push((symbol, name.clone(), substitution));
+        }
+        name
+    }
+
+    /// Symbol of the specialization of the interface-bounded function `symbol`
+    /// for the types of `args`, queuing it for emission. A type parameter is
+    /// bound to the type of the first argument declared as it, or a pointer
+    /// to it; a struct or enum by its name. Other functions are returned unchanged.
+    fn instantiate_bounded(&mut self, symbol: String, args: &[Value]) -> String {
+        let Some((template, path)) = self.bounded_templates.get(&symbol).cloned() else {
+            return symbol;
+        };
+        let type_params: Vec<&ast::Ident> = template.generic_params.iter()
+            .filter_map(|p| match p {
+                ast::GenericParam::Type { name, .. } => Some(name),
+                ast::GenericParam::Const { .. } => None,
+            })
+            .collect();
+        let mut type_args: HashMap<String, AstType> = HashMap::new();
+        for (param, arg) in template.params.iter().zip(args) {
+            let mut declared = &param.ty;
+            while let AstType::Pointer(inner, _) | AstType::Ref { inner, .. } = declared {
+                declared = inner;
+            }
+            let AstType::Named(name, span) = declared else { continue };
+            if type_args.contains_key(name) || !type_params.iter().any(|p| p.name == *name) {
+                continue;
+            }
+            let Some(mut actual) = self.get_value_type(arg) else { continue };
+            while let IRType::Ptr(inner) = actual {
+                actual = *inner;
+            }
+            let concrete = match actual {
+                IRType::Struct(name) => name,
+                other => mangle::type_tag(&other),
+            };
+            type_args.insert(name.clone(), AstType::Named(concrete, *span));
+        }
+
+        let outer = (std::mem::replace(&mut self.type_args, type_args), std::mem::replace(&mut self.module_path, path));
+        // Parameters no argument decides stay erased
+        let tags: Vec<String> = type_params.iter()
+            .map(|p| mangle::type_tag(&self.ast_type_to_ir(&AstType::Named(p.name.clone(), p.span))))
+            .collect();
+        let name = mangle::generic_type_name(&symbol, &tags);
+        if !self.bounded_instances.iter().any(|(_, instance, _)| *instance == name) {
+            self.record_signature(name.clone(), &template);
+            self.bounded_instances.push((symbol, name.clone(), self.type_args.clone()));
+        }
+        (self.type_args, self.module_path) = outer;
+        name
+    }
+
+    /// Report two functions lowered to the same symbol, which the C compiler
+    /// or linker would otherwise reject with a far less helpful message
+    fn check_duplicate_symbols(&self) -> Result<()> {
+        let mut seen: HashMap<&str, Span> = HashMap::new();
+        for (symbol, span) in &self.function_spans {
+            if let Some(first) = seen.insert(symbol, *span) {
+                return Err(Error::DuplicateSymbol { symbol: symbol.clone(), first, second: *span });
+            }
+        }
+        Ok(())
+    }
+
     /// Generate IR for a function with a specific name
     fn generate_function_with_name(&mut self, func: &ast::Function, name: &str) -> Result<()> {
         self.next_register = 0;
         self.locals.clear();
         self.reg_types.clear();
+        self.local_type_args.clear();
+        self.block_spans.clear();
+        self.last_debug_loc = None;
         self.sret_ptr = None;
+        self.volatile_locals = func.params.iter()
+            .filter(|p| matches!(p.ty, AstType::Volatile(..)))
+            .map(|p| p.name.name.clone())
+            .collect();
+        self.pointer_locals = func.params.iter()
+            .filter(|p| Self::is_pointer_type(&p.ty))
+            .map(|p| p.name.name.clone())
+            .collect();
+        self.array_locals = func.params.iter()
+            .filter_map(|p| Some((p.name.name.clone(), self.array_shape_of(&p.ty)?)))
+            .collect();
 
         // Convert parameters
-        let mut params: Vec<(String, IRType)> = func.params.iter()
-            .map(|p| (p.name.name.clone(), self.ast_type_to_ir(&p.ty)))
-            .collect();
+        let mut params = self.lower_params(&func.params);
             
         let ret_type = if let Some(ref ty) = func.ret_type {
-            self.ast_type_to_ir(ty)
+            self.lower_type(ty)
         } else {
             IRType::Void
         };
         
         // Check if this function returns a struct (sret calling convention)
         // Both direct struct returns and pointer-to-struct returns use sret
+        self.sret_struct = self.sret_struct_of(&ret_type);
         let uses_sret = match &ret_type {
             IRType::Struct(_) => true,  // Direct struct return
             IRType::Ptr(inner) => matches!(inner.as_ref(), IRType::Struct(_)),  // Ptr(Struct)
@@ -441,6 +1414,8 @@ impl IRGenerator {
             ret_type.clone()
         };
 
+        self.closure_owner = name.to_string();
+        self.closure_count = 0;
         let mut ir_func = IRFunction::new(name, params.clone(), actual_ret_type);
         // Mark as sret function if it returns a struct
         if uses_sret {
@@ -471,9 +1446,18 @@ impl IRGenerator {
         }
 
         self.current_fn = Some(ir_func);
+        self.record_param_type_args(func);
+        self.destructure_params(func)?;
+        self.generate_preconditions(func)?;
 
         // Generate function body
         let last_value = self.generate_block(&func.body)?;
+        let ret_type = self.current_fn.as_ref().map_or(IRType::Void, |f| f.ret_type.clone());
+        let last_value = last_value.map(|val| self.coerce_str(val, &ret_type));
+        // A struct or enum in tail position is copied out as by `return`
+        if let Some(val) = last_value.clone().filter(|_| self.get_current_terminator().is_none()) {
+            self.store_sret(val);
+        }
 
         // Add implicit return if needed (same as generate_function)
         if let Some(ref mut ir_func) = self.current_fn {
@@ -495,7 +1479,10 @@ impl IRGenerator {
         }
 
         // Finalize function
-        let ir_func = self.current_fn.take().unwrap();
+        let mut ir_func = self.current_fn.take().unwrap();
+        ir_func.debug_loc = self.source_loc(func.span);
+        self.instrument_coverage(&mut ir_func, func.span);
+        self.function_spans.push((ir_func.name.clone(), func.span));
         self.module.functions.push(ir_func);
         Ok(())
     }
@@ -505,20 +1492,33 @@ impl IRGenerator {
         self.next_register = 0;
         self.locals.clear();
         self.reg_types.clear();
+        self.local_type_args.clear();
+        self.block_spans.clear();
+        self.last_debug_loc = None;
         self.sret_ptr = None;
+        self.volatile_locals = func.params.iter()
+            .filter(|p| matches!(p.ty, AstType::Volatile(..)))
+            .map(|p| p.name.name.clone())
+            .collect();
+        self.pointer_locals = func.params.iter()
+            .filter(|p| Self::is_pointer_type(&p.ty))
+            .map(|p| p.name.name.clone())
+            .collect();
+        self.array_locals = func.params.iter()
+            .filter_map(|p| Some((p.name.name.clone(), self.array_shape_of(&p.ty)?)))
+            .collect();
 
         // Convert parameters
-        let mut params: Vec<(String, IRType)> = func.params.iter()
-            .map(|p| (p.name.name.clone(), self.ast_type_to_ir(&p.ty)))
-            .collect();
+        let mut params = self.lower_params(&func.params);
             
         let ret_type = if let Some(ref ty) = func.ret_type {
-            self.ast_type_to_ir(ty)
+            self.lower_type(ty)
         } else {
             IRType::Void
         };
 
         // Check if this function returns a struct (sret calling convention)
+        self.sret_struct = self.sret_struct_of(&ret_type);
         let uses_sret = match &ret_type {
             IRType::Struct(_) => true,
             IRType::Ptr(inner) => matches!(inner.as_ref(), IRType::Struct(_)),
@@ -540,14 +1540,14 @@ impl IRGenerator {
             ret_type.clone()
         };
 
-        // Generate function name with module prefix for pub functions (but not main)
-        let func_name = if func.is_pub && func.name.name != "main" {
-            format!("{}_{}", self.module.name, func.name.name)
-        } else {
-            func.name.name.clone()
-        };
+        let func_name = self.function_symbol(func);
+        self.closure_owner = func_name.clone();
+        self.closure_count = 0;
 
         let mut ir_func = IRFunction::new(&func_name, params.clone(), actual_ret_type);
+        ir_func.extern_c = func.is_extern_c();
+        ir_func.no_mangle = func.is_no_mangle();
+        ir_func.is_pub = func.is_pub;
 
         
         // Mark as sret function
@@ -555,13 +1555,19 @@ impl IRGenerator {
             ir_func.sret_type = Some(sret_ret_type.clone());
         }
         
-        // Check for function annotations (@simd, @naked, @interrupt, @volatile, @gpu)
+        // Check for function annotations (@simd, @naked, @interrupt, @volatile, @inline, @cold, @hot, @gpu)
         for annotation in &func.annotations {
             match annotation.name.name.as_str() {
                 "simd" => ir_func.simd = true,
                 "naked" => ir_func.naked = true,
                 "interrupt" => ir_func.interrupt = true,
                 "volatile" => ir_func.volatile = true,
+                "inline" => {
+                    let never = matches!(annotation.args.first(), Some(Expr::Ident(id)) if id.name == "never");
+                    ir_func.inline = if never { InlineHint::Never } else { InlineHint::Always };
+                }
+                "cold" => ir_func.cold = true,
+                "hot" => ir_func.hot = true,
                 "gpu" => {
                     // Mark function as GPU kernel (for future CUDA/Metal codegen)
                     // Currently just adds a comment in C output
@@ -595,9 +1601,18 @@ impl IRGenerator {
         }
 
         self.current_fn = Some(ir_func);
+        self.record_param_type_args(func);
+        self.destructure_params(func)?;
+        self.generate_preconditions(func)?;
 
         // Generate body
         let last_value = self.generate_block(&func.body)?;
+        let ret_type = self.current_fn.as_ref().map_or(IRType::Void, |f| f.ret_type.clone());
+        let last_value = last_value.map(|val| self.coerce_str(val, &ret_type));
+        // A struct or enum in tail position is copied out as by `return`
+        if let Some(val) = last_value.clone().filter(|_| self.get_current_terminator().is_none()) {
+            self.store_sret(val);
+        }
 
         // Add implicit return if needed
         if let Some(ref mut ir_func) = self.current_fn {
@@ -619,7 +1634,10 @@ impl IRGenerator {
         }
 
         // Finalize function
-        if let Some(ir_func) = self.current_fn.take() {
+        if let Some(mut ir_func) = self.current_fn.take() {
+            ir_func.debug_loc = self.source_loc(func.span);
+            self.instrument_coverage(&mut ir_func, func.span);
+            self.function_spans.push((ir_func.name.clone(), func.span));
             self.module.functions.push(ir_func);
         }
 
@@ -628,37 +1646,222 @@ impl IRGenerator {
 
     /// Generate IR for a block
     fn generate_block(&mut self, block: &ast::Block) -> Result<Option<Value>> {
+        // A `let` in the block shadows outer variables only until its end
+        let saved = (self.locals.clone(), self.volatile_locals.clone(), self.pointer_locals.clone(), self.array_locals.clone());
         let mut last_value = None;
         for stmt in &block.stmts {
             last_value = self.generate_stmt(stmt)?;
         }
+        (self.locals, self.volatile_locals, self.pointer_locals, self.array_locals) = saved;
         Ok(last_value)
     }
 
-    /// Generate IR for a statement
-    fn generate_stmt(&mut self, stmt: &ast::Stmt) -> Result<Option<Value>> {
-        match stmt {
-            Stmt::Let { name, value, ty: type_annotation, .. } => {
-                let reg = self.alloc_register();
-                let mut var_type = IRType::I64;
+    /// `loop { .. }`: its value is whatever a `break value` stored in the
+    /// result slot, which is allocated before the loop once a break has a value
+    fn generate_loop(&mut self, body: &ast::Block) -> Result<Value> {
+        let entry = self.current_block;
+        let body_block = self.add_block("loop_body");
+        let exit_block = self.add_block("loop_exit");
+        self.set_terminator_current(Terminator::Jump { target: body_block });
 
-                if let Some(expr) = value {
-                    let val = self.generate_expr(expr)?;
-                    if let Some(t) = self.get_value_type(&val) {
-                        var_type = t;
-                    }
-                    self.emit_current(Instruction::Assign { dest: reg, value: val });
-                    self.reg_types.insert(reg, var_type.clone());
-                } else if let Some(ref ast_ty) = type_annotation {
-                    var_type = self.ast_type_to_ir(ast_ty);
-                }
-                
-                self.locals.insert(name.name.clone(), (Value::Register(reg), var_type));
-                Ok(None)
-            }
+        self.current_block = body_block;
+        let result = self.generate_loop_body(body, body_block, exit_block)?;
+        // Jump back to body (infinite loop)
+        if self.get_current_terminator().is_none() {
+            self.set_terminator_current(Terminator::Jump { target: body_block });
+        }
 
-            Stmt::Expr(expr) => {
-                let val = self.generate_expr(expr)?;
+        // Exit block (unreachable unless break)
+        self.current_block = exit_block;
+        let Some((slot, ty)) = result else {
+            return Ok(Value::Unit);
+        };
+        let slot_ty = IRType::Ptr(Box::new(ty.clone()));
+        self.reg_types.insert(slot, slot_ty);
+        if let Some(block) = self.current_fn.as_mut().and_then(|func| func.get_block_mut(entry)) {
+            block.push(Instruction::Alloca { dest: slot, ty: ty.clone() });
+        }
+        Ok(self.emit_load(Value::Register(slot), &ty))
+    }
+
+    /// Generate the body of a loop whose `continue` and `break` jump to the
+    /// given blocks, returning the result slot a `break value` allocated
+    fn generate_loop_body(&mut self, body: &ast::Block, continue_block: BlockId, break_block: BlockId) -> Result<Option<(Register, IRType)>> {
+        self.loops.push(LoopTargets { continue_block, break_block, result: None });
+        let generated = self.generate_block(body);
+        let targets = self.loops.pop();
+        generated?;
+        Ok(targets.and_then(|t| t.result))
+    }
+
+    /// Result slot of the innermost loop, reserving it for values of type `ty`
+    /// on the first `break value`
+    fn loop_result_slot(&mut self, ty: IRType) -> Option<Register> {
+        if let Some((slot, _)) = self.loops.last()?.result {
+            return Some(slot);
+        }
+        let slot = self.alloc_register();
+        self.loops.last_mut()?.result = Some((slot, ty));
+        Some(slot)
+    }
+
+    /// `[do { .. }; len]`: a stack array filled by a loop running the do
+    /// block once per element, with `_` bound to the index
+    fn generate_array_from(&mut self, init: &Expr, len: usize) -> Result<Value> {
+        let Expr::DoBlock { stmts, accumulator, .. } = init else {
+            return Err(Error::CodeGen("array initializer is not a do block".to_string()));
+        };
+        let entry = self.current_block;
+        let index = self.alloc_register();
+        self.emit_current_with_type(
+            Instruction::Assign { dest: index, value: Value::Constant(Constant::Int(0)) },
+            IRType::I64,
+        );
+        let cond_block = self.add_block("array_from_cond");
+        let body_block = self.add_block("array_from_body");
+        let exit_block = self.add_block("array_from_exit");
+        self.set_terminator_current(Terminator::Jump { target: cond_block });
+
+        self.current_block = cond_block;
+        let more = self.alloc_register();
+        self.emit_current_with_type(Instruction::BinOp {
+            dest: more,
+            op: IRBinOp::Lt,
+            left: Value::Register(index),
+            right: Value::Constant(Constant::Int(len as i64)),
+        }, IRType::Bool);
+        self.set_terminator_current(Terminator::Branch {
+            cond: Value::Register(more),
+            then_target: body_block,
+            else_target: exit_block,
+        });
+
+        self.current_block = body_block;
+        let saved = (self.locals.clone(), self.volatile_locals.clone(), self.pointer_locals.clone(), self.array_locals.clone());
+        self.locals.insert("_".to_string(), (Value::Register(index), IRType::I64));
+        for stmt in stmts {
+            self.generate_stmt(stmt)?;
+        }
+        let value = self.generate_expr(accumulator)?;
+        (self.locals, self.volatile_locals, self.pointer_locals, self.array_locals) = saved;
+
+        // The element type is known now; the array itself lives before the loop
+        let elem_ty = self.get_value_type(&value).unwrap_or(IRType::I64);
+        let array_ty = IRType::Array(Box::new(elem_ty.clone()), len);
+        let array = self.alloc_register();
+        let body_end = std::mem::replace(&mut self.current_block, entry);
+        self.emit_current_with_type(
+            Instruction::Alloca { dest: array, ty: array_ty.clone() },
+            IRType::Ptr(Box::new(array_ty)),
+        );
+        self.current_block = body_end;
+
+        let slot = self.alloc_register();
+        self.emit_current_with_type(
+            Instruction::GetElementPtr {
+                dest: slot,
+                ptr: Value::Register(array),
+                index: Value::Register(index),
+                elem_ty: elem_ty.clone(),
+            },
+            IRType::Ptr(Box::new(elem_ty)),
+        );
+        self.emit_current(Instruction::Store { ptr: Value::Register(slot), value, volatile: false });
+        let next = self.alloc_register();
+        self.emit_current_with_type(Instruction::BinOp {
+            dest: next,
+            op: IRBinOp::Add,
+            left: Value::Register(index),
+            right: Value::Constant(Constant::Int(1)),
+        }, IRType::I64);
+        self.emit_current(Instruction::Assign { dest: index, value: Value::Register(next) });
+        self.set_terminator_current(Terminator::Jump { target: cond_block });
+
+        self.current_block = exit_block;
+        Ok(Value::Register(array))
+    }
+
+    /// Bind `name` (and the parts `pattern` names) to `value`, returning its register
+    fn generate_let(
+        &mut self,
+        name: &ast::Ident,
+        type_annotation: Option<&AstType>,
+        value: Option<&Expr>,
+        pattern: Option<&ast::Pattern>,
+    ) -> Result<Register> {
+        let reg = self.alloc_register();
+        let mut var_type = IRType::I64;
+
+        let is_volatile = matches!(type_annotation, Some(AstType::Volatile(..)))
+            || value.is_some_and(|v| self.is_volatile_ptr(v));
+        if is_volatile {
+            self.volatile_locals.insert(name.name.clone());
+        } else {
+            self.volatile_locals.remove(&name.name);
+        }
+        if type_annotation.is_some_and(Self::is_pointer_type) {
+            self.pointer_locals.insert(name.name.clone());
+        } else {
+            self.pointer_locals.remove(&name.name);
+        }
+        match type_annotation.and_then(|ty| self.array_shape_of(ty)) {
+            Some(shape) => self.array_locals.insert(name.name.clone(), shape),
+            None => self.array_locals.remove(&name.name),
+        };
+
+        if let Some(args) = type_annotation.and_then(|ty| self.enum_type_args(ty)) {
+            self.local_type_args.insert(reg, args);
+        }
+
+        if let Some(expr) = value {
+            let declared = type_annotation.map(|ast_ty| self.lower_type(ast_ty));
+            let mut val = match &declared {
+                Some(declared) => self.generate_expr_as(expr, declared)?,
+                None => self.generate_expr(expr)?,
+            };
+            if let Some(declared) = &declared {
+                val = self.coerce_str(val, declared);
+            }
+            if let Some(t) = self.get_value_type(&val) {
+                var_type = t;
+            }
+            // `Vec::new()` leaves the element type to the annotation, an
+            // integer expression takes the width of the declared type, and
+            // a pointer unwrapped from the erased `Option` payload gets its type back
+            match declared {
+                Some(declared @ (IRType::Vec(_) | IRType::Map(..))) => var_type = declared,
+                Some(declared) if Self::is_integer_type(&declared) && Self::is_integer_type(&var_type) => var_type = declared,
+                Some(declared @ IRType::Ptr(_)) if var_type == IRType::I64 => {
+                    val = self.emit_cast(val, declared.clone());
+                    var_type = declared;
+                }
+                _ => {}
+            }
+            self.emit_current(Instruction::Assign { dest: reg, value: val });
+            self.reg_types.insert(reg, var_type.clone());
+        } else if let Some(ast_ty) = type_annotation {
+            var_type = self.ast_type_to_ir(ast_ty);
+        }
+
+        self.locals.insert(name.name.clone(), (Value::Register(reg), var_type.clone()));
+        if let Some(pattern) = pattern {
+            self.destructure(pattern, Value::Register(reg), &var_type)?;
+        }
+        Ok(reg)
+    }
+
+    /// Generate IR for a statement
+    fn generate_stmt(&mut self, stmt: &ast::Stmt) -> Result<Option<Value>> {
+        self.note_span(stmt.span());
+        self.mark_debug_loc(stmt.span());
+        match stmt {
+            Stmt::Let { name, value, ty: type_annotation, pattern, .. } => {
+                self.generate_let(name, type_annotation.as_ref(), value.as_ref(), pattern.as_deref())?;
+                Ok(None)
+            }
+
+            Stmt::Expr(expr) => {
+                let val = self.generate_expr(expr)?;
                 Ok(Some(val))
             }
 
@@ -675,7 +1878,11 @@ impl IRGenerator {
                 if is_sret {
                     // Still generate the expression to populate sret pointer
                     if let Some(expr) = value {
-                        let _ = self.generate_expr(expr)?;
+                        let returned = match self.sret_struct.clone() {
+                            Some(ty) => self.generate_expr_as(expr, &ty)?,
+                            None => self.generate_expr(expr)?,
+                        };
+                        self.store_sret(returned);
                     }
                     self.set_terminator_current(Terminator::Return { value: None });
                     return Ok(None);
@@ -683,6 +1890,9 @@ impl IRGenerator {
                 
                 let ret_val = if let Some(expr) = value {
                     let mut val = self.generate_expr(expr)?;
+                    if let Some(ret_type) = self.current_fn.as_ref().map(|f| f.ret_type.clone()) {
+                        val = self.coerce_str(val, &ret_type);
+                    }
                     
                     // Convert to function return type if needed
                     if let Some(func) = &self.current_fn {
@@ -708,13 +1918,24 @@ impl IRGenerator {
                 Ok(None)
             }
 
-            Stmt::Break { .. } => {
-                // TODO: Track loop context for break
+            Stmt::Break { break_value, .. } => {
+                if let Some(expr) = break_value {
+                    let value = self.generate_expr(expr)?;
+                    let ty = self.get_value_type(&value).unwrap_or(IRType::I64);
+                    if let Some(slot) = self.loop_result_slot(ty) {
+                        self.emit_current(Instruction::Store { ptr: Value::Register(slot), value, volatile: false });
+                    }
+                }
+                if let Some(target) = self.loops.last().map(|l| l.break_block) {
+                    self.set_terminator_current(Terminator::Jump { target });
+                }
                 Ok(None)
             }
 
             Stmt::Continue { .. } => {
-                // TODO: Track loop context for continue
+                if let Some(target) = self.loops.last().map(|l| l.continue_block) {
+                    self.set_terminator_current(Terminator::Jump { target });
+                }
                 Ok(None)
             }
 
@@ -723,14 +1944,407 @@ impl IRGenerator {
 
     }
 
-    /// Generate IR for an expression
+    /// Element type of an indexed pointer or array
+    fn element_type(base_ty: Option<IRType>) -> IRType {
+        match base_ty {
+            Some(IRType::Ptr(inner)) | Some(IRType::Array(inner, _)) => *inner,
+            // Fallback to pointer type (for **u8 argv case)
+            _ => IRType::Ptr(Box::new(IRType::U8)),
+        }
+    }
+
+    /// Generate IR for `target = value` or a compound assignment like
+    /// `target += value`. The target's address is computed once, before the
+    /// value is evaluated.
+    fn generate_assign(&mut self, target: &Expr, op: ast::BinOp, value: &Expr, span: Span) -> Result<Value> {
+        let lvalue = self.generate_lvalue(target, span)?;
+        let mut value = self.generate_expr(value)?;
+        if let Some(op) = op.compound_op() {
+            let current = match &lvalue {
+                Lvalue::Register(reg, _) => Value::Register(*reg),
+                Lvalue::Memory { ptr, ty, volatile } => {
+                    let dest = self.alloc_register();
+                    self.emit_current_with_type(Instruction::Load {
+                        dest,
+                        ptr: ptr.clone(),
+                        ty: ty.clone(),
+                        volatile: *volatile,
+                    }, ty.clone());
+                    Value::Register(dest)
+                }
+            };
+            let overloaded = if self.is_address(target) { None } else { self.generate_operator_call(current.clone(), op, value.clone()) };
+            value = match overloaded {
+                Some(result) => result,
+                None => self.generate_binary(current, op, value, span)?,
+            };
+        }
+        match lvalue {
+            Lvalue::Register(reg, _) => {
+                self.emit_current(Instruction::Assign { dest: reg, value: value.clone() });
+            }
+            Lvalue::Memory { ptr, ty, volatile } => {
+                let stored = self.coerce_str(value.clone(), &ty);
+                // A struct value is a pointer to it; store what it points to
+                let stored = match (&ty, self.get_value_type(&stored)) {
+                    (IRType::Struct(_), Some(IRType::Ptr(_))) => self.emit_load(stored, &ty),
+                    _ => stored,
+                };
+                self.emit_current(Instruction::Store { ptr, value: stored, volatile });
+            }
+        }
+        Ok(value)
+    }
+
+    /// Address and type of `base.field`, or `None` if `base` is not a struct.
+    /// The struct is not loaded: `(*ptr).field` offsets `ptr` and
+    /// `items[i].field` the element's address.
+    fn generate_field_ptr(&mut self, base: &Expr, field: &ast::Ident, span: Span) -> Result<Option<(Value, IRType)>> {
+        let (base_val, base_ty) = match base.place() {
+            Some(Place::Deref(ptr)) => {
+                let ptr = self.generate_expr(ptr)?;
+                let ty = self.get_value_type(&ptr);
+                (ptr, ty)
+            }
+            Some(Place::Index { .. }) => match self.generate_lvalue(base, span)? {
+                Lvalue::Memory { ptr, ty, .. } => (ptr, Some(IRType::Ptr(Box::new(ty)))),
+                Lvalue::Register(reg, ty) => (Value::Register(reg), Some(ty)),
+            },
+            _ => {
+                let value = self.generate_expr(base)?;
+                let ty = self.get_value_type(&value);
+                (value, ty)
+            }
+        };
+        // `&mut self` and `**Node` bases: load down to the struct pointer
+        let (base_val, base_ty) = self.auto_deref_struct(base_val, base_ty);
+        let Some(IRType::Ptr(inner)) = base_ty else {
+            return Ok(None);
+        };
+        let IRType::Struct(struct_name) = *inner else {
+            return Ok(None);
+        };
+        let fields = self.struct_defs.get(&struct_name).cloned()
+            .ok_or_else(|| Error::UndefinedType { span, name: struct_name.clone() })?;
+        let (index, (_, field_ty)) = fields.iter().enumerate()
+            .find(|(_, (name, _))| name == &field.name)
+            .ok_or_else(|| Error::UnknownField { span: field.span, field: field.name.clone() })?;
+        let field_ty = field_ty.clone();
+        let dest = self.alloc_register();
+        self.emit_current_with_type(Instruction::GetElementPtr {
+            dest,
+            ptr: base_val,
+            index: Value::Constant(Constant::Int(index as i64)),
+            elem_ty: IRType::Struct(struct_name),
+        }, IRType::Ptr(Box::new(field_ty.clone())));
+        Ok(Some((Value::Register(dest), field_ty)))
+    }
+
+    /// Where an assignment to `target` stores: the register of a local, or the
+    /// address of a static, field, element or pointer target
+    fn generate_lvalue(&mut self, target: &Expr, span: Span) -> Result<Lvalue> {
+        match target.place() {
+            Some(Place::Variable(ident)) => {
+                if let Some((Value::Register(reg), ty)) = self.locals.get(&ident.name) {
+                    return Ok(Lvalue::Register(*reg, ty.clone()));
+                }
+                if let Some(global) = self.module.global(&ident.name) {
+                    return Ok(Lvalue::Memory { ptr: Value::Global(ident.name.clone()), ty: global.ty.clone(), volatile: false });
+                }
+                Err(Error::CodeGen(format!("Cannot assign to '{}': variable not found in locals", ident.name)))
+            }
+            Some(Place::Field { base, field }) => {
+                let (ptr, ty) = self.generate_field_ptr(base, field, span)?.ok_or_else(|| {
+                    Error::CodeGen(format!("Cannot assign to field '{}' of a non-struct value", field.name))
+                })?;
+                Ok(Lvalue::Memory { ptr, ty, volatile: self.is_volatile_base(base) })
+            }
+            Some(Place::Index { base, index }) => {
+                let base_val = self.generate_expr(base)?;
+                if let Some((handle, IRType::Vec(elem))) = self.collection_handle(base_val.clone()) {
+                    let slot = self.generate_vec_slot(handle, &elem, index, span)?;
+                    return Ok(Lvalue::Memory { ptr: slot, ty: *elem, volatile: false });
+                }
+                let index = self.generate_expr(index)?;
+                let elem_ty = Self::element_type(self.get_value_type(&base_val));
+                let dest = self.alloc_register();
+                self.emit_current_with_type(Instruction::GetElementPtr {
+                    dest,
+                    ptr: base_val,
+                    index,
+                    elem_ty: elem_ty.clone(),
+                }, IRType::Ptr(Box::new(elem_ty.clone())));
+                Ok(Lvalue::Memory { ptr: Value::Register(dest), ty: elem_ty, volatile: self.is_volatile_ptr(base) })
+            }
+            Some(Place::Deref(ptr_expr)) => {
+                let ptr = self.generate_expr(ptr_expr)?;
+                let ty = match self.get_value_type(&ptr) {
+                    Some(IRType::Ptr(inner)) => *inner,
+                    // Fallback to U8 for unknown pointer types
+                    _ => IRType::U8,
+                };
+                Ok(Lvalue::Memory { ptr, ty, volatile: self.is_volatile_ptr(ptr_expr) })
+            }
+            None => Err(Error::CodeGen("Invalid assignment target".to_string())),
+        }
+    }
+
+    /// Generate IR for a binary operation and those nested in its left
+    /// operand. Long chains like `a + b + c + ..` nest to the left, so they are
+    /// lowered in a loop rather than recursively.
+    fn generate_binary_chain(&mut self, expr: &ast::Expr) -> Result<Value> {
+        let mut chain = Vec::new();
+        let mut leftmost = expr;
+        while let Expr::Binary { left, op, right, span } = leftmost {
+            if op.is_assignment() {
+                break;
+            }
+            chain.push((*op, right.as_ref(), *span));
+            leftmost = left;
+        }
+        let mut value = self.generate_expr(leftmost)?;
+        let mut left = Some(leftmost);
+        for (op, right, span) in chain.into_iter().rev() {
+            self.note_span(span);
+            let right_val = self.generate_expr(right)?;
+            let left_is_address = left.is_some_and(|l| self.is_address(l));
+            let by_address = left_is_address || self.is_address(right);
+            let overloaded = match op {
+                ast::BinOp::Eq | ast::BinOp::Ne if !by_address => self.generate_equality(value.clone(), right_val.clone()),
+                _ if !left_is_address => self.generate_operator_call(value.clone(), op, right_val.clone()),
+                _ => None,
+            };
+            value = match overloaded {
+                Some(equal) if op == ast::BinOp::Ne => self.emit_binop(IRBinOp::Eq, equal, Value::Constant(Constant::Bool(false)), IRType::Bool),
+                Some(result) => result,
+                None => self.generate_binary(value, op, right_val, span)?,
+            };
+            left = None;
+        }
+        Ok(value)
+    }
+
+    /// Whether `expr` is a pointer or reference rather than a struct or
+    /// enum value, so that `==` on it compares addresses
+    fn is_address(&self, expr: &ast::Expr) -> bool {
+        match expr {
+            Expr::Ident(ident) => self.pointer_locals.contains(&ident.name),
+            Expr::Ref { .. } => true,
+            Expr::Cast { ty, .. } => Self::is_pointer_type(ty),
+            _ => false,
+        }
+    }
+
+    /// `left == right` for values compared by content rather than by the
+    /// address holding them: strings, enums and `#[derive(Eq)]` structs.
+    /// `None` leaves the comparison to a plain `eq`.
+    fn generate_equality(&mut self, left: Value, right: Value) -> Option<Value> {
+        let left_ty = self.get_value_type(&left)?;
+        let right_ty = self.get_value_type(&right)?;
+        let is_text = |ty: &IRType| *ty == IRType::Str || Self::is_string_struct(Some(ty));
+        let ty = match left_ty {
+            _ if is_text(&left_ty) || is_text(&right_ty) => IRType::Str,
+            _ if left_ty != right_ty => return None,
+            _ if self.enum_of(&left_ty).is_some() => left_ty,
+            // Struct values are held by address
+            IRType::Ptr(inner) if matches!(*inner, IRType::Struct(_)) => *inner,
+            _ => return None,
+        };
+        self.emit_equality(left, right, &ty)
+    }
+
+    /// `left == right` for two values of type `ty`, a struct being given by
+    /// its address; `None` when `==` on `ty` is a plain `eq`
+    fn emit_equality(&mut self, left: Value, right: Value, ty: &IRType) -> Option<Value> {
+        if *ty == IRType::Str || Self::is_string_struct(Some(ty)) {
+            let left = self.emit_as_str(left);
+            let right = self.emit_as_str(right);
+            return Some(self.emit_call(STR_EQ, vec![left, right], IRType::Bool));
+        }
+        // `impl Eq` takes precedence over comparing by variant or field
+        let implemented = match ty {
+            IRType::Struct(name) => Some(name.clone()),
+            _ => self.enum_of(ty).map(|e| e.name.clone()),
+        }.filter(|name| self.interface_impls.contains(&(name.clone(), "Eq".to_string())));
+        if let Some(name) = implemented {
+            return Some(self.call_method(&name, "eq", vec![left, right]));
+        }
+        let name = match self.enum_of(ty) {
+            Some(e) if e.variants.iter().all(|v| v.fields.is_empty()) => {
+                let tag = e.tag.clone();
+                let left = self.emit_load(left, &tag);
+                let right = self.emit_load(right, &tag);
+                return Some(self.emit_binop(IRBinOp::Eq, left, right, IRType::Bool));
+            }
+            Some(e) => e.name.clone(),
+            None => match ty {
+                IRType::Struct(name) => name.clone(),
+                _ => return None,
+            },
+        };
+        if !self.derived_eq.contains(&name) {
+            return None;
+        }
+        Some(self.emit_call(&equality_function(&name), vec![left, right], IRType::Bool))
+    }
+
+    /// `left op right` as a call to the operator impl of the struct or enum
+    /// `left` is (`Vec3::add` for `+`); `None` when its type has none.
+    /// Ordering operators compare what `cmp` returns against 0, and `==`
+    /// is left to `generate_equality`.
+    fn generate_operator_call(&mut self, left: Value, op: ast::BinOp, right: Value) -> Option<Value> {
+        let (interface, method) = op.operator_interface().filter(|(interface, _)| *interface != "Eq")?;
+        let left_ty = self.get_value_type(&left);
+        let type_name = match left_ty.as_ref()? {
+            IRType::Struct(name) => name.clone(),
+            IRType::Ptr(inner) => match inner.as_ref() {
+                IRType::Struct(name) => name.clone(),
+                IRType::Ptr(pointee) => match pointee.as_ref() {
+                    IRType::Struct(name) => name.clone(),
+                    _ => return None,
+                },
+                _ => return None,
+            },
+            _ => return None,
+        };
+        if !self.interface_impls.contains(&(type_name.clone(), interface.to_string())) {
+            return None;
+        }
+        let (left, _) = self.auto_deref_struct(left, left_ty);
+        let result = self.call_method(&type_name, method, vec![left, right]);
+        if interface != "Ord" {
+            return Some(result);
+        }
+        let ir_op = self.ast_binop_to_ir(op);
+        Some(self.emit_binop(ir_op, result, Value::Constant(Constant::Int(0)), IRType::Bool))
+    }
+
+    /// The `str` of a `String`, `str` or C string
+    fn emit_as_str(&mut self, value: Value) -> Value {
+        let ty = self.get_value_type(&value);
+        if Self::is_string_struct(ty.as_ref()) {
+            let (value, _) = self.auto_deref_struct(value, ty);
+            return self.call_method("String", "as_str", vec![value]);
+        }
+        self.coerce_str(value, &IRType::Str)
+    }
+
+    /// Generate IR for `left_val op right_val`
+    fn generate_binary(&mut self, left_val: Value, op: ast::BinOp, right_val: Value, span: Span) -> Result<Value> {
+        if op == ast::BinOp::Add {
+            if let Some(concatenated) = self.generate_string_concat(left_val.clone(), right_val.clone()) {
+                return Ok(concatenated);
+            }
+        }
+
+        // Check for pointer arithmetic: ptr + offset or ptr - offset
+        let left_ty = self.get_value_type(&left_val);
+        if let Some(IRType::Ptr(inner)) = &left_ty {
+            if op == ast::BinOp::Add || op == ast::BinOp::Sub {
+                // This is pointer arithmetic, use GEP instead of add
+                let dest = self.alloc_register();
+                let offset_val = if op == ast::BinOp::Sub {
+                    // For subtraction, negate the offset
+                    let neg_dest = self.alloc_register();
+                    let zero = Value::Constant(Constant::Int(0));
+                    self.emit_current_with_type(Instruction::BinOp {
+                        dest: neg_dest,
+                        op: IRBinOp::Sub,
+                        left: zero,
+                        right: right_val.clone(),
+                    }, IRType::I64);
+                    Value::Register(neg_dest)
+                } else {
+                    right_val
+                };
+                
+                self.emit_current_with_type(Instruction::GetElementPtr {
+                    dest,
+                    ptr: left_val,
+                    index: offset_val,
+                    elem_ty: (**inner).clone(),
+                }, IRType::Ptr(inner.clone()));
+                
+                return Ok(Value::Register(dest));
+            }
+        }
+        
+        let ir_op = self.ast_binop_to_ir(op);
+        let dest = self.alloc_register();
+        
+        // Unify types for binary operations: convert right to left's type if different integers
+        let right_ty = self.get_value_type(&right_val);
+        let unified_right = if let (Some(lt), Some(rt)) = (&left_ty, &right_ty) {
+            if Self::is_integer_type(lt) && Self::is_integer_type(rt) && lt != rt {
+                // Cast right to left's type
+                let cast_dest = self.alloc_register();
+                self.emit_current_with_type(Instruction::Cast {
+                    dest: cast_dest,
+                    value: right_val.clone(),
+                    ty: lt.clone(),
+                }, lt.clone());
+                Value::Register(cast_dest)
+            } else {
+                right_val
+            }
+        } else {
+            right_val
+        };
+        
+        // Result of binary op is usually primitive or bool (I64/Bool)
+        let res_ty = match ir_op {
+            IRBinOp::Eq | IRBinOp::Ne | IRBinOp::Lt | IRBinOp::Le | IRBinOp::Gt | IRBinOp::Ge => IRType::Bool,
+            _ => left_ty.unwrap_or(IRType::I64), // Use left type for arithmetic
+        };
+        if self.overflow_mode != OverflowMode::Wrapping && overflow::is_checked(ir_op, &res_ty) {
+            return Ok(self.emit_checked_binop(ir_op, left_val, unified_right, res_ty, span));
+        }
+
+        self.emit_current_with_type(Instruction::BinOp {
+            dest,
+            op: ir_op,
+            left: left_val,
+            right: unified_right,
+        }, res_ty);
+        
+        Ok(Value::Register(dest))
+    }
+
+    /// Generate IR for an expression, failing once it is nested deeper than the limit
     fn generate_expr(&mut self, expr: &ast::Expr) -> Result<Value> {
+        if self.nesting >= self.max_nesting {
+            return Err(Error::NestingTooDeep { limit: self.max_nesting, span: expr.span() });
+        }
+        self.nesting += 1;
+        let value = self.generate_expr_inner(expr);
+        self.nesting -= 1;
+        value
+    }
+
+    fn generate_expr_inner(&mut self, expr: &ast::Expr) -> Result<Value> {
+        self.note_span(expr.span());
         match expr {
             Expr::Literal(lit) => Ok(self.generate_literal(lit)),
 
             Expr::Ident(ident) => {
                 if let Some((val, _ty)) = self.locals.get(&ident.name) {
                     Ok(val.clone())
+                } else if let Some(global) = self.module.global(&ident.name) {
+                    // Static variable: load its current value
+                    let ty = global.ty.clone();
+                    let dest = self.alloc_register();
+                    self.emit_current_with_type(Instruction::Load {
+                        dest,
+                        ptr: Value::Global(ident.name.clone()),
+                        ty: ty.clone(),
+                        volatile: false,
+                    }, ty);
+                    Ok(Value::Register(dest))
+                } else if let Some((constructor, ty)) = self.prelude_constructor(&ident.name)
+                    .and_then(|c| self.constructor_type(&c).map(|ty| (c, ty)))
+                {
+                    // A bare `None`
+                    Ok(self.emit_call(&constructor, vec![], ty))
                 } else {
                     Ok(Value::Global(ident.name.clone()))
                 }
@@ -740,272 +2354,28 @@ impl IRGenerator {
             Expr::Path { segments, .. } => {
                 // Path to enum variant or module item (e.g. "TokenKind::IntLit")
                 // Generate as a function call to the constructor
-                let path_str = segments.iter().map(|s| s.name.clone()).collect::<Vec<_>>().join("_");
+                let names: Vec<&str> = segments.iter().map(|s| s.name.as_str()).collect();
+                let path_str = self.resolve_function(&names).unwrap_or_else(|| names.join("_"));
                 
                 // For enum variants, generate a function call (constructor returns pointer)
                 // This handles unit variants like TokenKind::IntLit
                 let dest = self.alloc_register();
-                self.emit_current(Instruction::Call {
+                let ty = self.constructor_type(&path_str);
+                let call = Instruction::Call {
                     dest: Some(dest),
                     func: path_str,
                     args: vec![],
-                });
-                Ok(Value::Register(dest))
-            }
-            Expr::Binary { left, op, right, .. } => {
-                let left_val = self.generate_expr(left)?;
-                let right_val = self.generate_expr(right)?;
-                
-                // Handle assignment specially
-                if matches!(op, ast::BinOp::Assign) {
-                    // 1. Assign to Variable
-                    if let Expr::Ident(ident) = left.as_ref() {
-                        if let Some((dest_val, _)) = self.locals.get(&ident.name) {
-                            if let Value::Register(reg) = dest_val {
-                                self.emit_current(Instruction::Assign {
-                                    dest: *reg,
-                                    value: right_val.clone(),
-                                });
-                                return Ok(right_val);
-                            }
-                        }
-                    } 
-                    // 2. Assign to Field (including (*ptr).field = val pattern)
-                    else if let Expr::Field { expr: base, field, .. } = left.as_ref() {
-                         // Handle (*ptr).field = val pattern: base is Deref expression
-                         // In this case, we need to get the pointer from the deref, not the value
-                         let (base_val, base_ty) = if let Expr::Deref { expr: inner_ptr, .. } = base.as_ref() {
-                             // base is (*ptr), so inner_ptr is the pointer - use it directly
-                             let ptr_val = self.generate_expr(inner_ptr)?;
-                             let ptr_ty = self.get_value_type(&ptr_val);
-                             (ptr_val, ptr_ty)
-                         } else {
-                             // Normal case: base is already a pointer expression
-                             let val = self.generate_expr(base)?;
-                             let ty = self.get_value_type(&val);
-                             (val, ty)
-                         };
-                         
-                         let mut base_val = base_val;
-                         
-                         // Handle Ptr(Ptr(Struct)) case - &mut self where self is a reference
-                         // Load the inner pointer first
-                         let effective_ty = if let Some(IRType::Ptr(inner)) = &base_ty {
-                             if let IRType::Ptr(inner2) = inner.as_ref() {
-                                 if let IRType::Struct(_) = inner2.as_ref() {
-                                     // Load the inner pointer to get Ptr(Struct)
-                                     let deref_dest = self.alloc_register();
-                                     self.emit_current_with_type(Instruction::Load {
-                                         dest: deref_dest,
-                                         ptr: base_val.clone(),
-                                         ty: (**inner).clone(),
-                                     }, (**inner).clone());
-                                     base_val = Value::Register(deref_dest);
-                                     Some((**inner).clone())
-                                 } else {
-                                     base_ty.clone()
-                                 }
-                             } else {
-                                 base_ty.clone()
-                             }
-                         } else {
-                             base_ty.clone()
-                         };
-                         
-                         if let Some(IRType::Ptr(inner)) = effective_ty {
-                            if let IRType::Struct(struct_name) = *inner {
-                                 let fields = self.struct_defs.get(&struct_name).cloned()
-                                     .ok_or_else(|| crate::utils::Error::UndefinedType { 
-                                         span: crate::utils::Span::dummy(),
-                                         name: struct_name.clone() 
-                                     })?;
-                                 
-                                 let (idx, (_, field_ty)) = fields.iter().enumerate()
-                                     .find(|(_, (n, _))| n == &field.name)
-                                     .ok_or_else(|| crate::utils::Error::UnknownField { 
-                                         span: crate::utils::Span::dummy(),
-                                         field: field.name.clone(),
-                                     })?;
-                                     
-                                 let field_ty = field_ty.clone();
-                                     
-                                 let dest = self.alloc_register();
-                                 let idx_val = Value::Constant(Constant::Int(idx as i64));
-                                 
-                                 self.emit_current_with_type(Instruction::GetElementPtr {
-                                     dest,
-                                     ptr: base_val,
-                                     index: idx_val,
-                                     elem_ty: IRType::Struct(struct_name.clone()),
-                                 }, IRType::Ptr(Box::new(field_ty.clone())));
-                                 
-                                 // Store directly to field pointer
-                                 self.emit_current(Instruction::Store {
-                                     ptr: Value::Register(dest),
-                                     value: right_val.clone(),
-                                 });
-                                 
-                                 return Ok(right_val);
-                            }
-                         }
-                    }
-                    // 3. Assign to Deref (*ptr = val)
-                    else if let Expr::Deref { expr: ptr_expr, .. } = left.as_ref() {
-                        let ptr_val = self.generate_expr(ptr_expr)?;
-                        self.emit_current(Instruction::Store {
-                            ptr: ptr_val,
-                            value: right_val.clone(),
-                        });
-                        return Ok(right_val);
-                    }
-                    // 4. Assign to Index (ptr[i] = val)
-                    else if let Expr::Index { expr: base_expr, index, .. } = left.as_ref() {
-                        let base_val = self.generate_expr(base_expr)?;
-                        let idx_val = self.generate_expr(index)?;
-                        
-                        // Use GetElementPtr to calculate address
-                        let gep_reg = self.alloc_register();
-                        let elem_ty = IRType::I8; // Use I8 for generic element access
-                        self.emit_current_with_type(Instruction::GetElementPtr {
-                            dest: gep_reg,
-                            ptr: base_val,
-                            index: idx_val,
-                            elem_ty: elem_ty.clone(),
-                        }, IRType::Ptr(Box::new(elem_ty)));
-                        
-                        // Store to the calculated address
-                        self.emit_current(Instruction::Store {
-                            ptr: Value::Register(gep_reg),
-                            value: right_val.clone(),
-                        });
-                        return Ok(right_val);
-                    }
-                    // 5. Assign to Field of Index: g.nodes[id].field = val
-                    else if let Expr::Field { expr: field_base, field, .. } = left.as_ref() {
-                        // Check if field_base is an Index expression
-                        if let Expr::Index { expr: index_base, index, .. } = field_base.as_ref() {
-                            // Generate base pointer (e.g., g.nodes)
-                            let base_val = self.generate_expr(index_base)?;
-                            let idx_val = self.generate_expr(index)?;
-                            
-                            // Get pointer to array element
-                            let elem_ptr = self.alloc_register();
-                            let elem_ty = IRType::I8; // Generic byte-level access
-                            self.emit_current_with_type(Instruction::GetElementPtr {
-                                dest: elem_ptr,
-                                ptr: base_val,
-                                index: idx_val,
-                                elem_ty: elem_ty.clone(),
-                            }, IRType::Ptr(Box::new(elem_ty)));
-                            
-                            // Get pointer to field within element
-                            let field_ptr = self.alloc_register();
-                            let field_idx = match field.name.as_str() {
-                                "value" => Value::Constant(Constant::Int(2)), // Assume field offset
-                                "evaluated" => Value::Constant(Constant::Int(5)),
-                                _ => Value::Constant(Constant::Int(0)),
-                            };
-                            self.emit_current_with_type(Instruction::GetElementPtr {
-                                dest: field_ptr,
-                                ptr: Value::Register(elem_ptr),
-                                index: field_idx,
-                                elem_ty: IRType::I8,
-                            }, IRType::Ptr(Box::new(IRType::I8)));
-                            
-                            // Store to field
-                            self.emit_current(Instruction::Store {
-                                ptr: Value::Register(field_ptr),
-                                value: right_val.clone(),
-                            });
-                            return Ok(right_val);
-                        }
-                    }
-                    
-                    // 4. Fallback: If we get here with Assign, the target is not in locals
-                    // This can happen with re-assignment to variables. Handle by storing to the register.
-                    if let Expr::Ident(ident) = left.as_ref() {
-                        // Variable exists but not in locals - likely needs alloca
-                        return Err(crate::utils::Error::CodeGen(
-                            format!("Cannot assign to '{}': variable not found in locals. Consider using 'let mut' for mutable variables.", ident.name)
-                        ));
-                    }
-                    
-                    // For any other Assign target, return an error
-                    return Err(crate::utils::Error::CodeGen(
-                        "Invalid assignment target".to_string()
-                    ));
-                }
-                
-                // Check for pointer arithmetic: ptr + offset or ptr - offset
-                let left_ty = self.get_value_type(&left_val);
-                if let Some(IRType::Ptr(inner)) = &left_ty {
-                    if *op == ast::BinOp::Add || *op == ast::BinOp::Sub {
-                        // This is pointer arithmetic, use GEP instead of add
-                        let dest = self.alloc_register();
-                        let offset_val = if *op == ast::BinOp::Sub {
-                            // For subtraction, negate the offset
-                            let neg_dest = self.alloc_register();
-                            let zero = Value::Constant(Constant::Int(0));
-                            self.emit_current_with_type(Instruction::BinOp {
-                                dest: neg_dest,
-                                op: IRBinOp::Sub,
-                                left: zero,
-                                right: right_val.clone(),
-                            }, IRType::I64);
-                            Value::Register(neg_dest)
-                        } else {
-                            right_val
-                        };
-                        
-                        self.emit_current_with_type(Instruction::GetElementPtr {
-                            dest,
-                            ptr: left_val,
-                            index: offset_val,
-                            elem_ty: (**inner).clone(),
-                        }, IRType::Ptr(inner.clone()));
-                        
-                        return Ok(Value::Register(dest));
-                    }
-                }
-                
-                let ir_op = self.ast_binop_to_ir(*op);
-                let dest = self.alloc_register();
-                
-                // Unify types for binary operations: convert right to left's type if different integers
-                let right_ty = self.get_value_type(&right_val);
-                let unified_right = if let (Some(lt), Some(rt)) = (&left_ty, &right_ty) {
-                    if Self::is_integer_type(lt) && Self::is_integer_type(rt) && lt != rt {
-                        // Cast right to left's type
-                        let cast_dest = self.alloc_register();
-                        self.emit_current_with_type(Instruction::Cast {
-                            dest: cast_dest,
-                            value: right_val.clone(),
-                            ty: lt.clone(),
-                        }, lt.clone());
-                        Value::Register(cast_dest)
-                    } else {
-                        right_val
-                    }
-                } else {
-                    right_val
                 };
-                
-                // Result of binary op is usually primitive or bool (I64/Bool)
-                let res_ty = match ir_op {
-                    IRBinOp::Eq | IRBinOp::Ne | IRBinOp::Lt | IRBinOp::Le | IRBinOp::Gt | IRBinOp::Ge => IRType::Bool,
-                    _ => left_ty.unwrap_or(IRType::I64), // Use left type for arithmetic
-                };
-
-                self.emit_current_with_type(Instruction::BinOp {
-                    dest,
-                    op: ir_op,
-                    left: left_val,
-                    right: unified_right,
-                }, res_ty);
-                
+                match ty {
+                    Some(ty) => self.emit_current_with_type(call, ty),
+                    None => self.emit_current(call),
+                }
                 Ok(Value::Register(dest))
             }
+            Expr::Binary { left, op, right, span } if op.is_assignment() => self.generate_assign(left, *op, right, *span),
+            Expr::Binary { .. } => self.generate_binary_chain(expr),
 
+    
             Expr::Unary { op, expr: inner, .. } => {
                 let val = self.generate_expr(inner)?;
                 let ir_op = match op {
@@ -1020,23 +2390,83 @@ impl IRGenerator {
                 Ok(Value::Register(dest))
             }
 
-            Expr::Call { func, args, .. } => {
+            Expr::Call { func, args, span } => {
+                // `assert` reports through the panic runtime with its location
+                if let (Expr::Ident(ident), [cond]) = (func.as_ref(), args.as_slice()) {
+                    if ident.name == "assert" && !self.locals.contains_key("assert") && self.resolve_function(&["assert"]).is_none() {
+                        self.generate_check(cond, "assertion failed", *span)?;
+                        return Ok(Value::Unit);
+                    }
+                    if ident.name == "delete" && !self.locals.contains_key("delete") && self.resolve_function(&["delete"]).is_none() {
+                        return self.generate_delete(cond);
+                    }
+                }
+                if let Some((_, IRType::Function { params, ret })) = self.local_of(func) {
+                    return self.generate_indirect_call(func, &params, &ret, args);
+                }
                 let func_name = if let Expr::Ident(ident) = func.as_ref() {
-                    ident.name.clone()
-                } else if let Expr::Path { segments, .. } = func.as_ref() {
-                    segments.iter().map(|s| s.name.clone()).collect::<Vec<_>>().join("_")
+                    // Locals (function pointers) shadow functions of the same name
+                    let symbol = if self.locals.contains_key(&ident.name) {
+                        None
+                    } else {
+                        self.resolve_function(&[&ident.name])
+                            .or_else(|| fileio::runtime_name(&ident.name).map(str::to_string))
+                            .or_else(|| self.prelude_constructor(&ident.name))
+                    };
+                    symbol.unwrap_or_else(|| ident.name.clone())
+                } else if let Expr::Path { segments, generic_args, .. } = func.as_ref() {
+                    // Functions and methods, else an enum variant constructor (`Enum_Variant`)
+                    let names: Vec<&str> = segments.iter().map(|s| s.name.as_str()).collect();
+                    let builtin_new = match (names.as_slice(), args.as_slice()) {
+                        (["Vec", "new"], []) => Some((VEC_NEW, None, IRType::Vec(Box::new(IRType::Void)))),
+                        (["Vec", "with_capacity"], [cap]) => Some((VEC_WITH_CAPACITY, Some(cap), IRType::Vec(Box::new(IRType::Void)))),
+                        (["HashMap", "new"], []) => Some((MAP_NEW, None, IRType::Map(Box::new(IRType::Void), Box::new(IRType::Void)))),
+                        _ => None,
+                    };
+                    if let Some((func, cap, ty)) = builtin_new.filter(|_| self.resolve_function(&names).is_none()) {
+                        let args = match cap {
+                            Some(cap) => {
+                                let cap = self.generate_expr(cap)?;
+                                vec![self.emit_cast(cap, IRType::U64)]
+                            }
+                            None => vec![],
+                        };
+                        return Ok(self.emit_call(func, args, ty));
+                    }
+                    let symbol = self.resolve_function(&names).unwrap_or_else(|| names.join("_"));
+                    if generic_args.is_empty() {
+                        symbol
+                    } else {
+                        self.instantiate_const_generic(symbol, generic_args)
+                    }
                 } else {
                     let _val = self.generate_expr(func)?;
                     "indirect".to_string()
                 };
 
+                // The arguments of an interface-bounded function pick its specialization
+                let mut generated = Vec::new();
+                if self.bounded_templates.contains_key(&func_name) {
+                    for arg in args {
+                        generated.push(self.generate_expr(arg)?);
+                    }
+                }
+                let func_name = self.instantiate_bounded(func_name, &generated);
+                let mut generated = generated.into_iter();
+
                 // Generate argument values
                 let mut arg_vals: Vec<Value> = Vec::new();
                 
                 // Look up function signature for type conversion
-                let param_types: Vec<IRType> = self.module.functions.iter()
-                    .find(|f| f.name == func_name)
-                    .map(|f| f.params.iter().map(|(_, ty)| ty.clone()).collect())
+                let param_types: Vec<IRType> = match func_name.as_str() {
+                    "print" | "println" => Some(vec![IRType::Str]),
+                    _ => self.function_params.get(&func_name).cloned(),
+                }
+                    .or_else(|| fileio::signature(&func_name).map(|(params, _)| params))
+                    .or_else(|| simd::signature(&func_name).map(|(params, _)| params))
+                    .or_else(|| self.module.functions.iter()
+                        .find(|f| f.name == func_name)
+                        .map(|f| f.params.iter().map(|(_, ty)| ty.clone()).collect()))
                     .or_else(|| {
                         self.module.externs.iter()
                             .find(|e| e.name == func_name)
@@ -1045,10 +2475,20 @@ impl IRGenerator {
                     .unwrap_or_default();
                 
                 for (i, arg) in args.iter().enumerate() {
-                    let mut val = self.generate_expr(arg)?;
+                    let mut val = match generated.next() {
+                        Some(val) => val,
+                        None => self.generate_expr(arg)?,
+                    };
+                    if let Some(expected_ty) = param_types.get(i) {
+                        val = self.coerce_str(val, expected_ty);
+                    }
                     
                     // If we have type info, check and convert if needed
                     if let (Some(expected_ty), Some(actual_ty)) = (param_types.get(i), self.get_value_type(&val)) {
+                        // A field-less enum passed to C as its discriminant
+                        if Self::is_integer_type(expected_ty) && self.c_enum_tag(&actual_ty).as_ref() == Some(expected_ty) {
+                            val = self.emit_load(val, expected_ty);
+                        }
                         // Allow implicit integer conversions (e.g., i64 -> i32)
                         if Self::is_integer_type(expected_ty) && Self::is_integer_type(&actual_ty) && expected_ty != &actual_ty {
                             let dest = self.alloc_register();
@@ -1071,6 +2511,9 @@ impl IRGenerator {
                             .find(|f| f.name == func_name)
                             .map(|f| (f.ret_type.clone(), f.sret_type.clone()))
                     })
+                    .or_else(|| fileio::signature(&func_name).map(|(_, ret)| (ret, None)))
+                    .or_else(|| simd::signature(&func_name).map(|(_, ret)| (ret, None)))
+                    .or_else(|| self.constructor_type(&func_name).map(|ty| (ty, None)))
                     .unwrap_or_else(|| {
                         self.module.externs.iter()
                             .find(|e| e.name == func_name)
@@ -1116,12 +2559,16 @@ impl IRGenerator {
                 } else {
                     // Regular non-void function
                     let dest = self.alloc_register();
+                    let enum_ty = self.extern_enum_returns.get(&func_name).cloned();
                     self.emit_current_with_type(Instruction::Call {
                         dest: Some(dest),
                         func: func_name,
                         args: arg_vals,
-                    }, ret_type);
-                    Ok(Value::Register(dest))
+                    }, ret_type.clone());
+                    match enum_ty {
+                        Some(enum_ty) => Ok(self.emit_enum_from_tag(Value::Register(dest), &ret_type, enum_ty)),
+                        None => Ok(Value::Register(dest)),
+                    }
                 }
             }
 
@@ -1177,7 +2624,12 @@ impl IRGenerator {
                         return Ok(Value::Register(dest));
                     }
                 }
-                Ok(Value::Unit)
+                // Only one branch reaches the merge, so its value is the if's
+                match (then_jumps_to_merge, else_jumps_to_merge) {
+                    (true, false) => Ok(then_result.unwrap_or(Value::Unit)),
+                    (false, true) => Ok(else_result.unwrap_or(Value::Unit)),
+                    _ => Ok(Value::Unit),
+                }
             }
 
             Expr::Block(block) => {
@@ -1188,89 +2640,32 @@ impl IRGenerator {
                 }
             }
 
-            Expr::Field { expr: base, field, .. } => {
-                // Special case: (*ptr).field - use ptr directly for GEP
-                let (base_val, effective_ty) = if let Expr::Deref { expr: inner_ptr, .. } = base.as_ref() {
-                    // For (*ptr).field, use ptr directly (don't load the struct)
-                    let ptr_val = self.generate_expr(inner_ptr)?;
-                    let ptr_ty = self.get_value_type(&ptr_val);
-                    (ptr_val, ptr_ty)
-                } else {
-                    let mut base_val = self.generate_expr(base)?;
-                    let base_ty = self.get_value_type(&base_val);
-
-                    // Handle Ptr(Ptr(Struct)) case - &mut self where self is a reference
-                    let effective_ty = if let Some(IRType::Ptr(inner)) = &base_ty {
-                        if let IRType::Ptr(inner2) = inner.as_ref() {
-                            if let IRType::Struct(_) = inner2.as_ref() {
-                                // Load the inner pointer to get Ptr(Struct)
-                                let deref_dest = self.alloc_register();
-                                self.emit_current_with_type(Instruction::Load {
-                                    dest: deref_dest,
-                                    ptr: base_val.clone(),
-                                    ty: (**inner).clone(),
-                                }, (**inner).clone());
-                                base_val = Value::Register(deref_dest);
-                                Some((**inner).clone())
-                            } else {
-                                base_ty.clone()
-                            }
-                        } else {
-                            base_ty.clone()
-                        }
-                    } else {
-                        base_ty.clone()
-                    };
-                    (base_val, effective_ty)
+            // Bound like a `let` statement; the variable's register is the result
+            Expr::Let { name, ty, value, .. } => {
+                let reg = self.generate_let(name, ty.as_ref(), Some(value), None)?;
+                Ok(Value::Register(reg))
+            }
+
+            Expr::Field { expr: base, field, span } => {
+                let Some((dest, field_ty)) = self.generate_field_ptr(base, field, *span)? else {
+                    return Ok(Value::Unit); // Error handling fallback
                 };
-                
-                if let Some(IRType::Ptr(inner)) = effective_ty {
-                    if let IRType::Struct(struct_name) = *inner {
-                         let fields = self.struct_defs.get(&struct_name).cloned()
-                             .ok_or_else(|| crate::utils::Error::UndefinedType { 
-                                 span: crate::utils::Span::dummy(),
-                                 name: struct_name.clone() 
-                             })?;
-                         
-                         let (idx, (_, field_ty)) = fields.iter().enumerate()
-                             .find(|(_, (n, _))| n == &field.name)
-                             .ok_or_else(|| crate::utils::Error::UnknownField { 
-                                 span: crate::utils::Span::dummy(),
-                                 field: field.name.clone(),
-                             })?;
-                             
-                         let field_ty = field_ty.clone();
-                             
-                         let dest = self.alloc_register();
-                         self.emit_current_with_type(Instruction::GetElementPtr {
-                             dest,
-                             ptr: base_val,
-                             index: Value::Constant(Constant::Int(idx as i64)),
-                             elem_ty: IRType::Struct(struct_name.clone()),
-                         }, IRType::Ptr(Box::new(field_ty.clone())));
-                         
-                         // If field is a struct, return the pointer (for chained field access)
-                         // Otherwise load the value
-                         if let IRType::Struct(_) = &field_ty {
-                             // Return pointer to nested struct
-                             return Ok(Value::Register(dest));
-                         } else {
-                             // Load primitive/pointer field value
-                             let load_dest = self.alloc_register();
-                             self.emit_current_with_type(Instruction::Load {
-                                 dest: load_dest,
-                                 ptr: Value::Register(dest),
-                                 ty: field_ty.clone(),
-                             }, field_ty.clone());
-                             
-                             return Ok(Value::Register(load_dest));
-                         }
-                    }
+                // If field is a struct, return the pointer (for chained field access)
+                // Otherwise load the value
+                if let IRType::Struct(_) = &field_ty {
+                    return Ok(dest);
                 }
-                Ok(Value::Unit) // Error handling fallback
+                let load_dest = self.alloc_register();
+                self.emit_current_with_type(Instruction::Load {
+                    dest: load_dest,
+                    ptr: dest,
+                    ty: field_ty.clone(),
+                    volatile: self.is_volatile_base(base),
+                }, field_ty);
+                Ok(Value::Register(load_dest))
             }
 
-            Expr::StructLit { name, fields, .. } => {
+            Expr::StructLit { name, fields, span } => {
                 let struct_type = IRType::Struct(name.name.clone());
                 
                 // Use sret pointer if available (caller-allocated), otherwise alloca
@@ -1298,7 +2693,9 @@ impl IRGenerator {
                               field: field_name.name.clone(),
                           })?;
                           
-                     let field_val = self.generate_expr(field_expr)?;
+                     let field_ty = field_ty.clone();
+                     let field_val = self.generate_expr_as(field_expr, &field_ty)?;
+                     let field_val = self.coerce_str(field_val, &field_ty);
                      let field_ptr = self.alloc_register();
                      
                      self.emit_current_with_type(Instruction::GetElementPtr {
@@ -1308,37 +2705,21 @@ impl IRGenerator {
                          elem_ty: struct_type.clone(),
                      }, IRType::Ptr(Box::new(field_ty.clone())));
                      
-                     self.emit_current(Instruction::Store {
-                         ptr: Value::Register(field_ptr),
-                         value: field_val,
-                     });
+                     self.emit_store_field(Value::Register(field_ptr), field_val, &field_ty);
                 }
                 
                 // Clear sret_ptr after use (only one struct literal should use it)
                 self.sret_ptr = None;
-                
+
+                if self.invariant_structs.contains(&name.name) {
+                    let holds = self.emit_call(&invariant_checker(&name.name), vec![ptr_val.clone()], IRType::Bool);
+                    self.check_value(holds, &format!("invariant of '{}' violated", name.name), *span);
+                }
+                
                 Ok(ptr_val)
             }
             
-            // Minimal implementations for others
-            Expr::Loop { body, .. } => {
-                // Create blocks for infinite loop
-                let body_block = self.add_block("loop_body");
-                let exit_block = self.add_block("loop_exit");
-                
-                // Jump to body block
-                self.set_terminator_current(Terminator::Jump { target: body_block });
-                
-                // Body block
-                self.current_block = body_block;
-                self.generate_block(body)?;
-                // Jump back to body (infinite loop)
-                self.set_terminator_current(Terminator::Jump { target: body_block });
-                
-                // Exit block (unreachable unless break)
-                self.current_block = exit_block;
-                Ok(Value::Unit)
-            }
+            Expr::Loop { body, .. } => self.generate_loop(body),
             Expr::While { cond, body, .. } => {
                 // Create blocks for the loop
                 let cond_block = self.add_block("while_cond");
@@ -1359,41 +2740,131 @@ impl IRGenerator {
                 
                 // Body block
                 self.current_block = body_block;
-                self.generate_block(body)?;
+                self.generate_loop_body(body, cond_block, exit_block)?;
                 // Jump back to condition
-                self.set_terminator_current(Terminator::Jump { target: cond_block });
+                if self.get_current_terminator().is_none() {
+                    self.set_terminator_current(Terminator::Jump { target: cond_block });
+                }
                 
                 // Continue in exit block
                 self.current_block = exit_block;
                 Ok(Value::Unit)
             }
             Expr::For { var, iter, body, .. } => {
-                // Simplified: evaluate iter as a range and loop
-                // For now treat as: evaluate iter, then execute body
-                let _iter_val = self.generate_expr(iter)?;
-                
-                // Create loop blocks
+                let iter_val = self.generate_expr(iter)?;
+                // A `Vec` is measured again before each iteration, an array once here
+                let (elem_ty, vec, len) = match self.collection_handle(iter_val.clone()) {
+                    Some((handle, IRType::Vec(elem))) => (*elem, Some(handle), 0),
+                    _ => {
+                        let Some((elem_ty, len)) = self.array_shape(iter, &iter_val) else {
+                            return Err(Error::CodeGen(format!(
+                                "cannot lower `for {} in ..`: the length of the iterated value is not known",
+                                var.name
+                            )));
+                        };
+                        if len == 0 {
+                            return Ok(Value::Unit);
+                        }
+                        (elem_ty, None, len)
+                    }
+                };
+
+                let index = self.alloc_register();
+                self.emit_current_with_type(
+                    Instruction::Assign { dest: index, value: Value::Constant(Constant::Int(0)) },
+                    IRType::I64,
+                );
+                let cond_block = self.add_block("for_cond");
                 let body_block = self.add_block("for_body");
+                let step_block = self.add_block("for_step");
                 let exit_block = self.add_block("for_exit");
-                
-                // Register loop variable (placeholder)
-                let var_reg = self.alloc_register();
-                self.locals.insert(var.name.clone(), (Value::Register(var_reg), IRType::I64));
-                
-                // Jump to body
-                self.set_terminator_current(Terminator::Jump { target: body_block });
+                self.set_terminator_current(Terminator::Jump { target: cond_block });
+
+                self.current_block = cond_block;
+                let bound = match &vec {
+                    Some(handle) => {
+                        let len = self.emit_call(VEC_LEN, vec![handle.clone()], IRType::U64);
+                        self.emit_cast(len, IRType::I64)
+                    }
+                    None => Value::Constant(Constant::Int(len as i64)),
+                };
+                let more = self.alloc_register();
+                self.emit_current_with_type(Instruction::BinOp {
+                    dest: more,
+                    op: IRBinOp::Lt,
+                    left: Value::Register(index),
+                    right: bound,
+                }, IRType::Bool);
+                self.set_terminator_current(Terminator::Branch {
+                    cond: Value::Register(more),
+                    then_target: body_block,
+                    else_target: exit_block,
+                });
+
+                // Bind the element for the body only
                 self.current_block = body_block;
-                self.generate_block(body)?;
-                self.set_terminator_current(Terminator::Jump { target: exit_block });
-                
+                let slot = match vec {
+                    Some(handle) => {
+                        let size = Value::Constant(Constant::Int(self.size_of(&elem_ty)));
+                        let slot = self.emit_call(VEC_GET, vec![handle, Value::Register(index), size], IRType::Ptr(Box::new(IRType::U8)));
+                        self.emit_cast(slot, IRType::Ptr(Box::new(elem_ty.clone())))
+                    }
+                    None => {
+                        let slot = self.alloc_register();
+                        self.emit_current_with_type(Instruction::GetElementPtr {
+                            dest: slot,
+                            ptr: iter_val,
+                            index: Value::Register(index),
+                            elem_ty: elem_ty.clone(),
+                        }, IRType::Ptr(Box::new(elem_ty.clone())));
+                        Value::Register(slot)
+                    }
+                };
+                // A struct element is bound by its address, as struct values are
+                let (elem, elem_ty) = match elem_ty {
+                    IRType::Struct(_) => (slot, IRType::Ptr(Box::new(elem_ty))),
+                    _ => (self.emit_load(slot, &elem_ty), elem_ty),
+                };
+                let saved = (self.locals.clone(), self.volatile_locals.clone(), self.pointer_locals.clone(), self.array_locals.clone());
+                self.locals.insert(var.name.clone(), (elem, elem_ty));
+                self.volatile_locals.remove(&var.name);
+                self.pointer_locals.remove(&var.name);
+                self.array_locals.remove(&var.name);
+                self.generate_loop_body(body, step_block, exit_block)?;
+                (self.locals, self.volatile_locals, self.pointer_locals, self.array_locals) = saved;
+                if self.get_current_terminator().is_none() {
+                    self.set_terminator_current(Terminator::Jump { target: step_block });
+                }
+
+                self.current_block = step_block;
+                let next = self.alloc_register();
+                self.emit_current_with_type(Instruction::BinOp {
+                    dest: next,
+                    op: IRBinOp::Add,
+                    left: Value::Register(index),
+                    right: Value::Constant(Constant::Int(1)),
+                }, IRType::I64);
+                self.emit_current(Instruction::Assign { dest: index, value: Value::Register(next) });
+                self.set_terminator_current(Terminator::Jump { target: cond_block });
+
                 self.current_block = exit_block;
                 Ok(Value::Unit)
             }
-            Expr::Match { expr, arms, .. } => {
+            Expr::Match { expr, arms, span } => {
                 // Generate the value to match against
+                let type_args = self.type_args_of(expr);
                 let match_val = self.generate_expr(expr)?;
                 let match_ty = self.get_value_type(&match_val).unwrap_or(IRType::I64);
                 
+                if let Some(cases) = self.switch_cases(arms) {
+                    // Enums dispatch on the tag they lead with
+                    let switch_val = match self.enum_of(&match_ty).map(|e| e.tag.clone()) {
+                        Some(tag) => self.emit_load(match_val, &tag),
+                        None => match_val,
+                    };
+                    return self.generate_switch_match(switch_val, match_ty, arms, &cases, *span);
+                }
+                
                 // Create blocks for each arm and the exit block
                 let exit_block = self.add_block("match_exit");
                 let mut arm_blocks: Vec<BlockId> = Vec::new();
@@ -1420,51 +2891,61 @@ impl IRGenerator {
                     // Arm condition block
                     self.current_block = arm_blocks[i];
                     
+                    // Analysis rejects matches that leave values uncovered,
+                    // but an integer cast to an enum may still carry any tag
                     let next_block = if i + 1 < arm_blocks.len() {
                         arm_blocks[i + 1]
                     } else {
-                        exit_block // Default fallthrough
+                        self.unmatched_block(*span)
                     };
                     
-                    // Generate pattern matching condition
-                    match &arm.pattern {
-                        ast::Pattern::Wildcard { .. } => {
-                            // Wildcard always matches - jump to body
-                            self.set_terminator_current(Terminator::Jump { target: body_blocks[i] });
-                        }
-                        ast::Pattern::Literal(lit) => {
-                            // Compare with literal
-                            let lit_val = self.generate_literal(lit);
-                            let cmp_reg = self.alloc_register();
-                            self.emit_current_with_type(
-                                Instruction::BinOp {
-                                    dest: cmp_reg,
-                                    op: IRBinOp::Eq,
-                                    left: match_val.clone(),
-                                    right: lit_val,
-                                },
-                                IRType::Bool
-                            );
-                            self.set_terminator_current(Terminator::Branch {
-                                cond: Value::Register(cmp_reg),
-                                then_target: body_blocks[i],
-                                else_target: next_block,
-                            });
-                        }
-                        ast::Pattern::Binding { name, .. } => {
-                            // Binding always matches and binds the value
-                            self.locals.insert(name.name.clone(), (match_val.clone(), match_ty.clone()));
-                            self.set_terminator_current(Terminator::Jump { target: body_blocks[i] });
+                    // A guarded or-pattern runs the guard for each alternative
+                    // that matches until one passes
+                    let alternatives: Vec<&ast::Pattern> = match (&arm.pattern, &arm.guard) {
+                        (ast::Pattern::Or { alternatives, .. }, Some(_)) => alternatives.iter().collect(),
+                        _ => vec![&arm.pattern],
+                    };
+                    let mut shared = Vec::new();
+                    for (k, alternative) in alternatives.iter().enumerate() {
+                        let fail = if k + 1 < alternatives.len() { self.add_block("match_or") } else { next_block };
+                        let Some(guard) = &arm.guard else {
+                            let mut bindings = Vec::new();
+                            self.generate_pattern_test(alternative, match_val.clone(), &match_ty, &type_args, body_blocks[i], fail, &mut bindings)?;
+                            for (name, value, ty) in bindings {
+                                self.locals.insert(name, (value, ty));
+                            }
+                            break;
+                        };
+                        let guard_block = self.add_block(&format!("match_guard_{}", i));
+                        let mut bindings = Vec::new();
+                        self.generate_pattern_test(alternative, match_val.clone(), &match_ty, &type_args, guard_block, fail, &mut bindings)?;
+                        self.current_block = guard_block;
+                        if alternatives.len() > 1 {
+                            self.assign_shared(&mut shared, bindings);
+                        } else {
+                            shared.extend(bindings);
                         }
-                        _ => {
-                            // Struct, Tuple, Variant patterns - simplified: just go to body
-                            self.set_terminator_current(Terminator::Jump { target: body_blocks[i] });
+                        for (name, value, ty) in &shared {
+                            self.locals.insert(name.clone(), (value.clone(), ty.clone()));
                         }
+                        let cond = self.generate_expr(guard)?;
+                        self.set_terminator_current(Terminator::Branch {
+                            cond,
+                            then_target: body_blocks[i],
+                            else_target: fail,
+                        });
+                        self.current_block = fail;
                     }
-                    
+
                     // Arm body block
                     self.current_block = body_blocks[i];
                     let body_val = self.generate_expr(&arm.body)?;
+                    // The match has the type of its arms, not of the matched value
+                    if i == 0 {
+                        if let Some(ty) = self.get_value_type(&body_val) {
+                            self.reg_types.insert(result_reg, ty);
+                        }
+                    }
                     
                     // Store result and jump to exit
                     self.emit_current(Instruction::Assign {
@@ -1517,14 +2998,20 @@ impl IRGenerator {
                     self.emit_current(Instruction::Store {
                         ptr: Value::Register(idx_reg),
                         value: val.clone(),
+                        volatile: false,
                     });
                 }
                 
                 Ok(Value::Register(dest))
             }
-            Expr::Tuple { .. } => Ok(Value::Unit),
+            Expr::ArrayFrom { init, len, .. } => self.generate_array_from(init, *len),
+            Expr::DoBlock { span, .. } => Err(Error::CodeGen(format!(
+                "do block outside of `[do {{ .. }}; N]` at offset {}", span.start
+            ))),
+            Expr::Tuple { elements, .. } if elements.is_empty() => Ok(Value::Unit),
+            Expr::Tuple { elements, .. } => self.generate_tuple(elements, None),
 
-            Expr::MethodCall { expr: receiver, method, args, .. } => {
+            Expr::MethodCall { expr: receiver, method, args, span } => {
                  if method.name == "add" && args.len() == 1 {
                      let ptr_val = self.generate_expr(receiver)?;
                      let offset_val = self.generate_expr(&args[0])?;
@@ -1552,106 +3039,125 @@ impl IRGenerator {
                      // -> Struct_method(&receiver, args...)
                      let receiver_val = self.generate_expr(receiver)?;
                      let receiver_ty = self.get_value_type(&receiver_val);
+                     let is_str = receiver_ty == Some(IRType::Str)
+                         || matches!(receiver_val, Value::Constant(Constant::String(_)));
+                     if is_str && matches!(method.name.as_str(), "len" | "as_ptr" | "slice") {
+                         return self.generate_str_method(receiver_val, &method.name, args, *span);
+                     }
+                     if let Some((handle, ty)) = self.collection_handle(receiver_val.clone()) {
+                         return self.generate_collection_method(handle, &ty, &method.name, args, *span);
+                     }
+                     if let Some(prelude) = self.prelude_enum_of(receiver_ty.as_ref()) {
+                         if let Some(value) = self.generate_prelude_method(receiver_val.clone(), prelude, &method.name, args, *span)? {
+                             return Ok(value);
+                         }
+                     }
                      
-                     // Determine struct name from receiver type
-                     let struct_name = match &receiver_ty {
-                         Some(IRType::Ptr(inner)) => match inner.as_ref() {
-                             IRType::Struct(name) => Some(name.clone()),
-                             IRType::Ptr(inner2) => match inner2.as_ref() {
+                     // Extension methods of primitives take `self` by value
+                     let primitive_method = receiver_ty.as_ref()
+                         .and_then(|ty| self.primitive_methods.get(&(mangle::type_tag(ty), method.name.clone())))
+                         .cloned();
+                     let (receiver_val, func_name) = if let Some(func_name) = primitive_method {
+                         (receiver_val, func_name)
+                     } else {
+                         // Methods take `self` as a struct pointer
+                         let (receiver_val, receiver_ty) = self.auto_deref_struct(receiver_val, receiver_ty);
+                         let struct_name = match &receiver_ty {
+                             Some(IRType::Ptr(inner)) => match inner.as_ref() {
                                  IRType::Struct(name) => Some(name.clone()),
                                  _ => None,
                              },
+                             Some(IRType::Struct(name)) => Some(name.clone()),
                              _ => None,
-                         },
-                         Some(IRType::Struct(name)) => Some(name.clone()),
-                         _ => None,
+                         };
+                         // Unknown struct type for method call
+                         let Some(struct_name) = struct_name else { return Ok(Value::Unit) };
+                         let func_name = self.resolve_function(&[&struct_name, &method.name])
+                             .unwrap_or_else(|| mangle::method_symbol(&struct_name, &method.name));
+                         (receiver_val, func_name)
                      };
                      
-                     if let Some(struct_name) = struct_name {
-                         // Generate mangled function name: Struct_method
-                         let func_name = format!("{}_{}", struct_name, method.name);
-                         
-                         // Generate arg values
-                         let mut arg_vals = vec![receiver_val]; // self as first arg
-                         for arg in args {
-                             arg_vals.push(self.generate_expr(arg)?);
+                     // Generate arg values
+                     let param_types = self.function_params.get(&func_name).cloned().unwrap_or_default();
+                     let mut arg_vals = vec![receiver_val]; // self as first arg
+                     for (i, arg) in args.iter().enumerate() {
+                         let val = self.generate_expr(arg)?;
+                         let val = match param_types.get(i + 1) {
+                             Some(expected_ty) => self.coerce_str(val, expected_ty),
+                             None => val,
+                         };
+                         arg_vals.push(val);
+                     }
+                     
+                     // Look up function return type and sret info from pre-scanned signatures
+                     let (ret_type, sret_type) = self.function_signatures.get(&func_name)
+                         .cloned()
+                         .or_else(|| {
+                             // Fallback to module.functions if not in signatures
+                             self.module.functions.iter()
+                                 .find(|f| f.name == func_name)
+                                 .map(|f| (f.ret_type.clone(), f.sret_type.clone()))
+                         })
+                         .unwrap_or((IRType::Void, None));
+                     
+                     // Check if this is an sret function
+                     if let Some(sret_ty) = sret_type {
+                         let sret_ptr = self.alloc_register();
+                         if let IRType::Ptr(inner) = &sret_ty {
+                             if let IRType::Struct(s_name) = inner.as_ref() {
+                                 let struct_ty = IRType::Struct(s_name.clone());
+                                 self.emit_current_with_type(
+                                     Instruction::Alloca { dest: sret_ptr, ty: struct_ty },
+                                     sret_ty.clone()
+                                 );
+                             }
                          }
                          
-                         // Look up function return type and sret info from pre-scanned signatures
-                         let (ret_type, sret_type) = self.function_signatures.get(&func_name)
-                             .cloned()
-                             .or_else(|| {
-                                 // Fallback to module.functions if not in signatures
-                                 self.module.functions.iter()
-                                     .find(|f| f.name == func_name)
-                                     .map(|f| (f.ret_type.clone(), f.sret_type.clone()))
-                             })
-                             .unwrap_or((IRType::Void, None));
+                         let mut sret_args = vec![Value::Register(sret_ptr)];
+                         sret_args.extend(arg_vals);
                          
-                         // Check if this is an sret function
-                         if let Some(sret_ty) = sret_type {
-                             let sret_ptr = self.alloc_register();
-                             if let IRType::Ptr(inner) = &sret_ty {
-                                 if let IRType::Struct(s_name) = inner.as_ref() {
-                                     let struct_ty = IRType::Struct(s_name.clone());
-                                     self.emit_current_with_type(
-                                         Instruction::Alloca { dest: sret_ptr, ty: struct_ty },
-                                         sret_ty.clone()
-                                     );
-                                 }
-                             }
-                             
-                             let mut sret_args = vec![Value::Register(sret_ptr)];
-                             sret_args.extend(arg_vals);
-                             
-                             self.emit_current_with_type(Instruction::Call {
-                                 dest: None,
-                                 func: func_name,
-                                 args: sret_args,
-                             }, IRType::Void);
-                             
-                             Ok(Value::Register(sret_ptr))
-                         } else if ret_type == IRType::Void {
-                             // Void return
-                             self.emit_current_with_type(Instruction::Call {
-                                 dest: None,
-                                 func: func_name,
-                                 args: arg_vals,
-                             }, IRType::Void);
-                             Ok(Value::Unit)
-                         } else {
-                             // Returns a value
-                             let dest = self.alloc_register();
-                             self.emit_current_with_type(Instruction::Call {
-                                 dest: Some(dest),
-                                 func: func_name,
-                                 args: arg_vals,
-                             }, ret_type);
-                             Ok(Value::Register(dest))
-                         }
-                     } else {
-                         // Unknown struct type for method call
+                         self.emit_current_with_type(Instruction::Call {
+                             dest: None,
+                             func: func_name,
+                             args: sret_args,
+                         }, IRType::Void);
+                         
+                         Ok(Value::Register(sret_ptr))
+                     } else if ret_type == IRType::Void {
+                         // Void return
+                         self.emit_current_with_type(Instruction::Call {
+                             dest: None,
+                             func: func_name,
+                             args: arg_vals,
+                         }, IRType::Void);
                          Ok(Value::Unit)
+                     } else {
+                         // Returns a value
+                         let dest = self.alloc_register();
+                         self.emit_current_with_type(Instruction::Call {
+                             dest: Some(dest),
+                             func: func_name,
+                             args: arg_vals,
+                         }, ret_type);
+                         Ok(Value::Register(dest))
                      }
                  }
             },
-            Expr::Index { expr, index, .. } => {
+            Expr::Index { expr, index, span } => {
                 // Generate base pointer/array
                 let base_val = self.generate_expr(expr)?;
+                if let Some((handle, IRType::Vec(elem))) = self.collection_handle(base_val.clone()) {
+                    let slot = self.generate_vec_slot(handle, &elem, index, *span)?;
+                    // Like a struct field, a struct element is used in place
+                    if matches!(*elem, IRType::Struct(_)) {
+                        return Ok(slot);
+                    }
+                    return Ok(self.emit_load(slot, &elem));
+                }
                 // Generate index
                 let idx_val = self.generate_expr(index)?;
                 
-                // Infer element type from base type
-                let base_ty = self.get_value_type(&base_val);
-                let elem_type = if let Some(IRType::Ptr(inner)) = base_ty {
-                    // For Ptr(T), element type is T
-                    (*inner).clone()
-                } else if let Some(IRType::Array(inner, _)) = base_ty {
-                    (*inner).clone()
-                } else {
-                    // Fallback to pointer type (for **u8 argv case)
-                    IRType::Ptr(Box::new(IRType::U8))
-                };
+                let elem_type = Self::element_type(self.get_value_type(&base_val));
                 
                 // Use GetElementPtr to calculate pointer offset
                 let gep_reg = self.alloc_register();
@@ -1668,6 +3174,7 @@ impl IRGenerator {
                     dest,
                     ptr: Value::Register(gep_reg),
                     ty: elem_type.clone(),
+                    volatile: self.is_volatile_ptr(expr),
                 }, elem_type);
                 
                 Ok(Value::Register(dest))
@@ -1676,9 +3183,18 @@ impl IRGenerator {
                 // Get the address of the inner expression
                 // If inner is an Ident, look up its alloca pointer
                 if let Expr::Ident(ident) = inner.as_ref() {
-                    if let Some((ptr_val, _)) = self.locals.get(&ident.name) {
+                    if let Some((ptr_val, ty)) = self.locals.get(&ident.name).cloned() {
+                        // Collection handles are shared, so a copy behind a pointer will do
+                        if matches!(ty, IRType::Vec(_) | IRType::Map(..)) {
+                            return Ok(self.emit_spill(ptr_val, &ty));
+                        }
+                        // A pointer local lives in a register; give it a slot so
+                        // `&p` is a pointer to a pointer
+                        if self.pointer_locals.contains(&ident.name) {
+                            return Ok(self.emit_spill(ptr_val, &ty));
+                        }
                         // Return the pointer to the local variable
-                        return Ok(ptr_val.clone());
+                        return Ok(ptr_val);
                     }
                 }
                 // For other cases, generate the expression and hope it's a pointer
@@ -1704,15 +3220,27 @@ impl IRGenerator {
                     dest,
                     ptr: ptr_val,
                     ty: elem_type.clone(),
+                    volatile: self.is_volatile_ptr(ptr_expr),
                 }, elem_type);
                 
                 Ok(Value::Register(dest))
             },
             Expr::Unsafe { body, .. } => Ok(self.generate_block(body)?.unwrap_or(Value::Unit)),
             Expr::Cast { expr, ty, .. } => {
+                let target_ty = self.ast_type_to_ir(ty);
                 let val = self.generate_expr(expr)?;
+                let val = self.coerce_str(val, &target_ty);
+                // Field-less enums convert through their tag
+                let source_tag = self.get_value_type(&val)
+                    .and_then(|t| self.enum_of(&t).map(|e| e.tag.clone()));
+                if let Some(tag) = source_tag.filter(|_| Self::is_integer_type(&target_ty)) {
+                    let tag_val = self.emit_load(val, &tag);
+                    return Ok(self.emit_cast(tag_val, target_ty));
+                }
+                if let Some(tag) = self.enum_of(&target_ty).map(|e| e.tag.clone()) {
+                    return Ok(self.emit_enum_from_tag(val, &tag, target_ty));
+                }
                 let dest = self.alloc_register();
-                let target_ty = self.ast_type_to_ir(ty);
                 self.emit_current_with_type(Instruction::Cast {
                     dest,
                     value: val,
@@ -1747,9 +3275,16 @@ impl IRGenerator {
                          ast::AsmOperandKind::Clobber => IRAsmOperandKind::Clobber,
                      };
                      
+                     // Clobbers keep their name; operands get a normalized constraint
+                     let constraint = if kind == IRAsmOperandKind::Clobber {
+                         op.options.clone()
+                     } else {
+                         op.constraint().unwrap_or_else(|| op.options.clone())
+                     };
+                     
                      ir_operands.push(IRAsmOperand {
                          kind,
-                         constraint: op.options.clone(),
+                         constraint,
                          input,
                          output,
                      });
@@ -1763,41 +3298,52 @@ impl IRGenerator {
                 // Post-ASM assignments (update variables from output registers)
                 for (i, op) in operands.iter().enumerate() {
                      let ir_op = &ir_operands[i];
-                     if let Some(reg) = ir_op.output {
-                          if let Some(expr) = &op.expr {
-                              if let Expr::Ident(ident) = expr {
-                                  if let Some((dest_val, _)) = self.locals.get(&ident.name) {
-                                      if let Value::Register(dest_reg) = dest_val {
-                                           self.emit_current(Instruction::Assign {
-                                               dest: *dest_reg,
-                                               value: Value::Register(reg)
-                                           });
-                                      }
+                     if let (Some(reg), Some(expr)) = (ir_op.output, &op.expr) {
+                          match expr {
+                              Expr::Ident(ident) => {
+                                  if let Some((Value::Register(dest_reg), _)) = self.locals.get(&ident.name) {
+                                      self.emit_current(Instruction::Assign {
+                                          dest: *dest_reg,
+                                          value: Value::Register(reg)
+                                      });
+                                  } else if self.module.global(&ident.name).is_some() {
+                                      self.emit_current(Instruction::Store {
+                                          ptr: Value::Global(ident.name.clone()),
+                                          value: Value::Register(reg),
+                                          volatile: false,
+                                      });
                                   }
                               }
+                              Expr::Deref { expr: ptr_expr, .. } => {
+                                  let ptr_val = self.generate_expr(ptr_expr)?;
+                                  self.emit_current(Instruction::Store {
+                                      ptr: ptr_val,
+                                      value: Value::Register(reg),
+                                      volatile: self.is_volatile_ptr(ptr_expr),
+                                  });
+                              }
+                              _ => {}
                           }
                      }
                 }
                 
                 Ok(Value::Unit)
             }
-            Expr::Try { expr, .. } => {
-                // Basic error propagation (if err return err, else value)
-                // For now just evaluate expression
-                self.generate_expr(expr)
-            }
+            Expr::Try { expr, .. } => self.generate_try(expr),
             
-            Expr::Closure { params, ret_type, body, .. } => {
-                // Generate a unique closure function name
-                static CLOSURE_COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
-                let closure_id = CLOSURE_COUNTER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-                let closure_name = format!("__closure_{}", closure_id);
+            Expr::Closure { params, ret_type, body, span } => {
+                // Named after the enclosing function, so the name does not
+                // depend on what else has been lowered
+                let closure_name = format!("__closure_{}_{}", self.closure_owner, self.closure_count);
+                self.closure_count += 1;
                 
                 // Save current state
                 let saved_fn = self.current_fn.take();
                 let saved_block = self.current_block;
                 let saved_locals = self.locals.clone();
                 let saved_reg = self.next_register;
+                let saved_spans = std::mem::take(&mut self.block_spans);
+                let saved_loc = self.last_debug_loc.take();
                 
                 // Create new function for closure
                 self.next_register = 0;
@@ -1833,12 +3379,23 @@ impl IRGenerator {
                 
                 // Generate closure body
                 let body_val = self.generate_expr(body)?;
-                
+
+                // Without an annotation the closure returns what its body yields
+                if ret_type.is_none() {
+                    if let Some(ty) = self.get_value_type(&body_val).filter(|ty| *ty != IRType::Void) {
+                        if let Some(func) = self.current_fn.as_mut() {
+                            func.ret_type = ty;
+                        }
+                    }
+                }
+
                 // Add return
                 self.set_terminator_current(Terminator::Return { value: Some(body_val) });
                 
                 // Finalize and add function to module
-                let closure_fn = self.current_fn.take().unwrap();
+                let mut closure_fn = self.current_fn.take().unwrap();
+                closure_fn.debug_loc = self.source_loc(*span);
+                self.instrument_coverage(&mut closure_fn, *span);
                 self.module.functions.push(closure_fn);
                 
                 // Restore state
@@ -1846,158 +3403,1545 @@ impl IRGenerator {
                 self.current_block = saved_block;
                 self.locals = saved_locals;
                 self.next_register = saved_reg;
+                self.block_spans = saved_spans;
+                self.last_debug_loc = saved_loc;
                 
                 // Return function pointer as global reference
                 Ok(Value::Global(closure_name))
             }
 
-        }
-    }
+            // Layout builtins fold to integer constants
+            Expr::SizeOf { .. } | Expr::AlignOf { .. } | Expr::OffsetOf { .. } => {
+                Ok(Value::Constant(Constant::Int(self.fold_layout_builtin(expr)?)))
+            }
+            Expr::New { ty, count, init, span } => self.generate_new(ty, count.as_deref(), init.as_deref(), *span),
 
-    /// Generate a constant value from a literal
-    fn generate_literal(&self, lit: &ast::Literal) -> Value {
-        match lit {
-            ast::Literal::Int(n, _) => Value::Constant(Constant::Int(*n)),
-            ast::Literal::Float(n, _) => Value::Constant(Constant::Float(*n)),
-            ast::Literal::Bool(b, _) => Value::Constant(Constant::Bool(*b)),
-            ast::Literal::String(s, _) => Value::Constant(Constant::String(s.clone())),
-            ast::Literal::Char(c, _) => Value::Constant(Constant::Int(*c as i64)),
         }
     }
 
-    // ==================== Helper Methods ====================
-
-    fn alloc_register(&mut self) -> Register {
-        let reg = Register(self.next_register);
-        self.next_register += 1;
-        reg
-    }
-
-    fn add_block(&mut self, label: &str) -> BlockId {
-        if let Some(ref mut func) = self.current_fn {
-            func.add_block(label)
+    /// Heap-allocate a `ty` (or `count` of them), typed by the layout engine
+    fn generate_new(&mut self, ty: &AstType, count: Option<&Expr>, init: Option<&Expr>, span: Span) -> Result<Value> {
+        let layout = self.layout_of_ast_type(ty).ok_or_else(|| {
+            crate::utils::Error::CodeGen(format!("cannot compute layout of {:?}", ty))
+        })?;
+        let elem = self.ast_type_to_ir(ty);
+        // malloc(0) may return null, so empty types still take a byte
+        let size = layout.size.max(1) as i64;
+        let bytes = match count {
+            None => Value::Constant(Constant::Int(size)),
+            Some(count) => {
+                let count = self.generate_expr(count)?;
+                let count = self.emit_cast(count, IRType::U64);
+                let fits = self.emit_binop(IRBinOp::Le, count.clone(), Value::Constant(Constant::Int(i64::MAX / size)), IRType::Bool);
+                self.check_value(fits, "new_array size overflows", span);
+                self.emit_binop(IRBinOp::Mul, count, Value::Constant(Constant::Int(size)), IRType::U64)
+            }
+        };
+        // malloc only guarantees alignment for the fundamental types
+        let ptr_u8 = IRType::Ptr(Box::new(IRType::U8));
+        let raw = if layout.align > 16 {
+            let align = Value::Constant(Constant::Int(layout.align as i64));
+            self.emit_call("aligned_alloc", vec![align, bytes], ptr_u8)
         } else {
-            BlockId(0)
+            self.emit_call("malloc", vec![bytes], ptr_u8)
+        };
+        let ptr = self.emit_cast(raw, IRType::Ptr(Box::new(elem)));
+        if let Some(init) = init {
+            // The struct literal writes its fields straight into the allocation
+            let outer = self.sret_ptr.replace(ptr.clone());
+            self.generate_expr(init)?;
+            self.sret_ptr = outer;
         }
+        Ok(ptr)
     }
 
-    fn emit(&mut self, func: &mut IRFunction, inst: Instruction) {
-        if let Some(block) = func.get_block_mut(self.current_block) {
-            block.push(inst);
+    /// Free `ptr`; debug builds then poison a variable holding it
+    fn generate_delete(&mut self, ptr: &Expr) -> Result<Value> {
+        let value = self.generate_expr(ptr)?;
+        self.emit_call("free", vec![value], IRType::Void);
+        if let (true, Expr::Ident(ident)) = (self.debug_assertions, ptr) {
+            if let Some((Value::Register(reg), ty)) = self.locals.get(&ident.name).cloned() {
+                let poison = self.emit_cast(Value::Constant(Constant::Int(DELETED_POINTER)), ty);
+                self.emit_current(Instruction::Assign { dest: reg, value: poison });
+            }
         }
+        Ok(Value::Unit)
     }
 
-    fn emit_current(&mut self, inst: Instruction) {
-        if let Some(ref mut func) = self.current_fn {
-            if let Some(block) = func.get_block_mut(self.current_block) {
-                block.push(inst);
+    /// Evaluate a size_of/align_of/offset_of expression
+    fn fold_layout_builtin(&self, expr: &ast::Expr) -> Result<i64> {
+        match expr {
+            Expr::SizeOf { ty, .. } | Expr::AlignOf { ty, .. } => {
+                let layout = self.layout_of_ast_type(ty).ok_or_else(|| {
+                    crate::utils::Error::CodeGen(format!("cannot compute layout of {:?}", ty))
+                })?;
+                let value = if matches!(expr, Expr::SizeOf { .. }) { layout.size } else { layout.align };
+                Ok(value as i64)
             }
+            Expr::OffsetOf { ty, field, .. } => {
+                let struct_name = match ty {
+                    AstType::Named(name, _) if self.is_declared_type(name) => Some(name.clone()),
+                    _ => match self.ast_type_to_ir(ty) {
+                        IRType::Struct(name) => Some(name),
+                        _ => None,
+                    },
+                };
+                let offset = match struct_name {
+                    Some(name) => {
+                        let index = self.module.structs.iter()
+                            .find(|s| s.name == name)
+                            .and_then(|s| s.fields.iter().position(|(f, _)| f == &field.name));
+                        index.and_then(|i| self.module.struct_layout(&name).map(|l| l.offsets[i]))
+                    }
+                    _ => None,
+                };
+                let offset = offset.ok_or_else(|| {
+                    crate::utils::Error::CodeGen(format!("cannot compute offset of field '{}'", field.name))
+                })?;
+                Ok(offset as i64)
+            }
+            _ => Err(crate::utils::Error::CodeGen("not a layout builtin".to_string())),
         }
     }
-    
-    fn emit_current_with_type(&mut self, inst: Instruction, ty: IRType) {
-        if let Instruction::Assign { dest, .. } | 
-               Instruction::BinOp { dest, .. } | 
-               Instruction::UnaryOp { dest, .. } | 
-               Instruction::Call { dest: Some(dest), .. } | 
-               Instruction::Alloca { dest, .. } | 
-               Instruction::Load { dest, .. } | 
-               Instruction::GetElementPtr { dest, .. } | 
-               Instruction::Cast { dest, .. } |
-               Instruction::Phi { dest, .. } = &inst {
-            self.reg_types.insert(*dest, ty);
-        }
-        self.emit_current(inst);
-    }
-    
-    /// Check if an IR type is an integer type
-    fn is_integer_type(ty: &IRType) -> bool {
-        matches!(ty, IRType::I8 | IRType::I16 | IRType::I32 | IRType::I64 |
-                     IRType::U8 | IRType::U16 | IRType::U32 | IRType::U64)
+
+    /// Fold a static initializer to a constant of the static's type
+    fn eval_static_initializer(&self, expr: &ast::Expr, ty: &IRType) -> Option<Constant> {
+        let value = self.eval_const_value(expr)?;
+        // Integer literals may initialize float statics
+        Some(match (value, ty) {
+            (Constant::Int(n), IRType::F32 | IRType::F64) => Constant::Float(n as f64),
+            (value, _) => value,
+        })
     }
-    
-    fn get_value_type(&self, val: &Value) -> Option<IRType> {
-        match val {
-            Value::Register(reg) => self.reg_types.get(reg).cloned(),
-            Value::Parameter(idx) => {
-                if let Some(func) = &self.current_fn {
-                     if *idx < func.params.len() {
-                         return Some(func.params[*idx].1.clone());
-                     }
-                }
-                None
+
+    /// Evaluate a constant expression of any literal type
+    fn eval_const_value(&self, expr: &ast::Expr) -> Option<Constant> {
+        match expr {
+            Expr::Literal(lit) => match self.generate_literal(lit) {
+                Value::Constant(c) => Some(c),
+                _ => None,
             },
-            Value::Constant(c) => Some(match c {
-                Constant::Int(_) => IRType::I64,
-                Constant::Float(_) => IRType::F64,
-                Constant::Bool(_) => IRType::Bool,
-                Constant::String(_) => IRType::Ptr(Box::new(IRType::U8)),
-                Constant::Null => IRType::Ptr(Box::new(IRType::Void)),
-            }),
-            Value::Global(_) => Some(IRType::Ptr(Box::new(IRType::Void))), // Unknown global
-            Value::Unit => Some(IRType::Void),
+            Expr::Unary { op, expr, .. } => match (op, self.eval_const_value(expr)?) {
+                (ast::UnOp::Neg, Constant::Int(n)) => Some(Constant::Int(n.wrapping_neg())),
+                (ast::UnOp::Neg, Constant::Float(f)) => Some(Constant::Float(-f)),
+                (ast::UnOp::Not, Constant::Bool(b)) => Some(Constant::Bool(!b)),
+                (ast::UnOp::BitNot, Constant::Int(n)) => Some(Constant::Int(!n)),
+                _ => None,
+            },
+            Expr::Binary { op, left, right, .. } => {
+                match (self.eval_const_value(left)?, self.eval_const_value(right)?) {
+                    (Constant::Int(l), Constant::Int(r)) => Some(Constant::Int(match op {
+                        ast::BinOp::Add => l.checked_add(r)?,
+                        ast::BinOp::Sub => l.checked_sub(r)?,
+                        ast::BinOp::Mul => l.checked_mul(r)?,
+                        ast::BinOp::Div => l.checked_div(r)?,
+                        ast::BinOp::Mod => l.checked_rem(r)?,
+                        ast::BinOp::BitAnd => l & r,
+                        ast::BinOp::BitOr => l | r,
+                        ast::BinOp::BitXor => l ^ r,
+                        ast::BinOp::Shl => l.checked_shl(r as u32)?,
+                        ast::BinOp::Shr => l.checked_shr(r as u32)?,
+                        _ => return None,
+                    })),
+                    (Constant::Float(l), Constant::Float(r)) => Some(Constant::Float(match op {
+                        ast::BinOp::Add => l + r,
+                        ast::BinOp::Sub => l - r,
+                        ast::BinOp::Mul => l * r,
+                        ast::BinOp::Div => l / r,
+                        _ => return None,
+                    })),
+                    _ => None,
+                }
+            }
+            Expr::Cast { expr, ty, .. } => {
+                let value = self.eval_const_value(expr)?;
+                Some(match (value, self.ast_type_to_ir(ty)) {
+                    (Constant::Int(n), IRType::F32 | IRType::F64) => Constant::Float(n as f64),
+                    (Constant::Float(f), IRType::F32 | IRType::F64) => Constant::Float(f),
+                    (Constant::Float(f), _) => Constant::Int(f as i64),
+                    (Constant::Bool(b), IRType::Bool) => Constant::Bool(b),
+                    (Constant::Bool(b), _) => Constant::Int(b as i64),
+                    (value, _) => value,
+                })
+            }
+            Expr::SizeOf { .. } | Expr::AlignOf { .. } | Expr::OffsetOf { .. } => {
+                self.fold_layout_builtin(expr).ok().map(Constant::Int)
+            }
+            _ => None,
         }
     }
 
-    fn set_terminator_current(&mut self, term: Terminator) {
-        if let Some(ref mut func) = self.current_fn {
-            if let Some(block) = func.get_block_mut(self.current_block) {
-                block.set_terminator(term);
+    /// Generate a constant value from a literal
+    /// Case values of each arm when the whole match can lower to a `Switch`:
+    /// integer literals and field-less variants, alone or in or-patterns,
+    /// with `_` (None) as the default
+    fn switch_cases(&self, arms: &[ast::MatchArm]) -> Option<Vec<Option<Vec<i64>>>> {
+        arms.iter()
+            .map(|arm| if arm.guard.is_some() { None } else { self.constant_case(&arm.pattern) })
+            .collect()
+    }
+
+    /// Values a pattern matches if it only tests constants: `Some(None)` for
+    /// a pattern that matches anything
+    fn constant_case(&self, pattern: &ast::Pattern) -> Option<Option<Vec<i64>>> {
+        match pattern {
+            ast::Pattern::Wildcard { .. } => Some(None),
+            ast::Pattern::Literal(ast::Literal::Int(n, _)) => Some(Some(vec![*n])),
+            ast::Pattern::Literal(ast::Literal::Bool(b, _)) => Some(Some(vec![*b as i64])),
+            ast::Pattern::Variant { enum_name, variant, fields, .. }
+                if fields.iter().all(|f| matches!(f, ast::Pattern::Wildcard { .. })) =>
+            {
+                Some(Some(vec![self.variant_tag(enum_name.as_ref().map(|n| n.name.as_str()), &variant.name)?]))
+            }
+            ast::Pattern::Or { alternatives, .. } => {
+                let mut values = Vec::new();
+                for alternative in alternatives {
+                    match self.constant_case(alternative)? {
+                        Some(v) => values.extend(v),
+                        None => return Some(None),
+                    }
+                }
+                Some(Some(values))
             }
+            _ => None,
         }
     }
 
-    fn get_current_terminator(&self) -> Option<&Terminator> {
-        if let Some(ref func) = self.current_fn {
-            if let Some(block) = func.blocks.get(self.current_block.0) {
-                return block.terminator.as_ref();
+    /// Payload field at byte `offset` of the enum `value` points to; a
+    /// struct payload is used in place
+    fn emit_payload(&mut self, value: Value, offset: usize, field_ty: IRType) -> (Value, IRType) {
+        let bytes = self.emit_cast(value, IRType::Ptr(Box::new(IRType::U8)));
+        let addr = self.alloc_register();
+        self.emit_current_with_type(Instruction::GetElementPtr {
+            dest: addr,
+            ptr: bytes,
+            index: Value::Constant(Constant::Int(offset as i64)),
+            elem_ty: IRType::U8,
+        }, IRType::Ptr(Box::new(IRType::U8)));
+        match field_ty {
+            IRType::Struct(_) => {
+                let field_ptr = IRType::Ptr(Box::new(field_ty));
+                (self.emit_cast(Value::Register(addr), field_ptr.clone()), field_ptr)
             }
+            _ => (self.emit_load(Value::Register(addr), &field_ty), field_ty),
         }
-        None
     }
-    
-    fn ast_binop_to_ir(&self, op: ast::BinOp) -> IRBinOp {
-        match op {
-            ast::BinOp::Add => IRBinOp::Add,
-            ast::BinOp::Sub => IRBinOp::Sub,
-            ast::BinOp::Mul => IRBinOp::Mul,
-            ast::BinOp::Div => IRBinOp::Div,
-            ast::BinOp::Mod => IRBinOp::Mod,
-            ast::BinOp::Eq => IRBinOp::Eq,
-            ast::BinOp::Ne => IRBinOp::Ne,
-            ast::BinOp::Lt => IRBinOp::Lt,
-            ast::BinOp::Le => IRBinOp::Le,
-            ast::BinOp::Gt => IRBinOp::Gt,
-            ast::BinOp::Ge => IRBinOp::Ge,
-            ast::BinOp::And => IRBinOp::And,
-            ast::BinOp::Or => IRBinOp::Or,
-            ast::BinOp::BitXor => IRBinOp::Xor,
-            ast::BinOp::Shl => IRBinOp::Shl,
-            ast::BinOp::Shr => IRBinOp::Shr,
-            ast::BinOp::BitAnd => IRBinOp::And,
-            ast::BinOp::BitOr => IRBinOp::Or,
-            ast::BinOp::Assign 
-            | ast::BinOp::AddAssign 
-            | ast::BinOp::SubAssign
-            | ast::BinOp::MulAssign
-            | ast::BinOp::DivAssign => panic!("Assignment should be handled separately"),
+
+    /// Copy the bindings of one or-pattern alternative into the registers in
+    /// `shared`, allocating one for each name not seen before
+    fn assign_shared(&mut self, shared: &mut Vec<(String, Value, IRType)>, bindings: Vec<(String, Value, IRType)>) {
+        for (name, value, ty) in bindings {
+            let reg = match shared.iter().find(|(n, _, _)| *n == name) {
+                Some((_, Value::Register(reg), _)) => *reg,
+                _ => {
+                    let reg = self.alloc_register();
+                    self.reg_types.insert(reg, ty.clone());
+                    shared.push((name, Value::Register(reg), ty));
+                    reg
+                }
+            };
+            self.emit_current(Instruction::Assign { dest: reg, value });
         }
     }
 
-    /// Try to evaluate a const expression to an integer value at compile time
-    fn try_eval_const_expr(&self, expr: &ast::Expr) -> Option<i64> {
-        match expr {
-            ast::Expr::Literal(lit) => {
-                match lit {
-                    ast::Literal::Int(n, _) => Some(*n),
-                    ast::Literal::Bool(b, _) => Some(if *b { 1 } else { 0 }),
-                    _ => None,
-                }
+    /// Branch to `on_match` if `value` matches `pattern`, else to `on_fail`,
+    /// ending the current block. The names the pattern binds are appended
+    /// to `bindings`, with values available in `on_match`. `type_args` are
+    /// those of the generic enum `value` points to, where known.
+    #[allow(clippy::too_many_arguments)]
+    fn generate_pattern_test(
+        &mut self,
+        pattern: &ast::Pattern,
+        value: Value,
+        ty: &IRType,
+        type_args: &[IRType],
+        on_match: BlockId,
+        on_fail: BlockId,
+        bindings: &mut Vec<(String, Value, IRType)>,
+    ) -> Result<()> {
+        match pattern {
+            ast::Pattern::Wildcard { .. } => {
+                self.set_terminator_current(Terminator::Jump { target: on_match });
             }
-            ast::Expr::Binary { op, left, right, .. } => {
-                let l = self.try_eval_const_expr(left)?;
-                let r = self.try_eval_const_expr(right)?;
-                Some(match op {
+            ast::Pattern::Binding { name, .. } => {
+                bindings.push((name.name.clone(), value, ty.clone()));
+                self.set_terminator_current(Terminator::Jump { target: on_match });
+            }
+            ast::Pattern::Literal(lit) => {
+                let lit_val = self.generate_literal(lit);
+                let cmp = self.emit_binop(IRBinOp::Eq, value, lit_val, IRType::Bool);
+                self.set_terminator_current(Terminator::Branch { cond: cmp, then_target: on_match, else_target: on_fail });
+            }
+            ast::Pattern::Variant { enum_name, variant, fields, .. } => {
+                let enum_name = match (enum_name, ty) {
+                    (Some(name), _) => Some(name.name.clone()),
+                    (None, IRType::Ptr(inner)) => match inner.as_ref() {
+                        IRType::Struct(name) => Some(name.clone()),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                let tag = self.variant_tag(enum_name.as_deref(), &variant.name);
+                let payload = enum_name.as_deref().and_then(|e| self.module.variant_fields(e, &variant.name));
+                let erased = enum_name.clone()
+                    .and_then(|e| self.erased_payloads.get(&(e, variant.name.clone())).cloned())
+                    .unwrap_or_default();
+                // Enums are heap pointers; anything else cannot be inspected
+                let (Some(tag), Some(payload), IRType::Ptr(_)) = (tag, payload, ty) else {
+                    self.set_terminator_current(Terminator::Jump { target: on_match });
+                    return Ok(());
+                };
+
+                // The tag leads the enum
+                let tag_ty = self.enum_of(ty).map_or(IRType::I32, |e| e.tag.clone());
+                let actual = self.emit_load(value.clone(), &tag_ty);
+                let cmp = self.emit_binop(IRBinOp::Eq, actual, Value::Constant(Constant::Int(tag)), IRType::Bool);
+                let mut next = self.add_block("match_payload");
+                self.set_terminator_current(Terminator::Branch { cond: cmp, then_target: next, else_target: on_fail });
+
+                for (i, (field, (offset, field_ty))) in fields.iter().zip(payload).enumerate() {
+                    self.current_block = next;
+                    if matches!(field, ast::Pattern::Wildcard { .. }) {
+                        continue;
+                    }
+                    let (mut field_val, mut field_ty) = self.emit_payload(value.clone(), offset, field_ty);
+                    // A pointer gets its type back from the erased `i64`
+                    if let Some(ptr @ IRType::Ptr(_)) = erased.get(i).copied().flatten().and_then(|param| type_args.get(param)) {
+                        field_val = self.emit_cast(field_val, ptr.clone());
+                        field_ty = ptr.clone();
+                    }
+                    // Bindings cannot fail, so only nested tests need a block
+                    if let ast::Pattern::Binding { name, .. } = field {
+                        bindings.push((name.name.clone(), field_val, field_ty));
+                        continue;
+                    }
+                    next = self.add_block("match_payload");
+                    self.generate_pattern_test(field, field_val, &field_ty, &[], next, on_fail, bindings)?;
+                }
+                self.current_block = next;
+                self.set_terminator_current(Terminator::Jump { target: on_match });
+            }
+            ast::Pattern::Or { alternatives, .. } => {
+                // Each alternative copies its bindings into registers shared by all of them
+                let mut shared = Vec::new();
+                for (i, alternative) in alternatives.iter().enumerate() {
+                    let fail = if i + 1 < alternatives.len() { self.add_block("match_or") } else { on_fail };
+                    let bind = self.add_block("match_or_bind");
+                    let mut alt_bindings = Vec::new();
+                    self.generate_pattern_test(alternative, value.clone(), ty, type_args, bind, fail, &mut alt_bindings)?;
+                    self.current_block = bind;
+                    self.assign_shared(&mut shared, alt_bindings);
+                    self.set_terminator_current(Terminator::Jump { target: on_match });
+                    self.current_block = fail;
+                }
+                bindings.extend(shared);
+            }
+            ast::Pattern::Struct { .. } | ast::Pattern::Tuple { .. } => {
+                // Structs and tuples are pointers to their fields
+                let (value, ty) = self.auto_deref_struct(value, Some(ty.clone()));
+                let struct_name = match &ty {
+                    Some(IRType::Ptr(inner)) => match inner.as_ref() {
+                        IRType::Struct(name) => Some(name.clone()),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                let Some((struct_name, fields)) = struct_name
+                    .and_then(|name| self.struct_defs.get(&name).cloned().map(|fields| (name, fields))) else {
+                    self.set_terminator_current(Terminator::Jump { target: on_match });
+                    return Ok(());
+                };
+                let subpatterns: Vec<(usize, &ast::Pattern)> = match pattern {
+                    ast::Pattern::Struct { fields: named, .. } => named.iter()
+                        .filter_map(|(field, p)| Some((fields.iter().position(|(n, _)| *n == field.name)?, p)))
+                        .collect(),
+                    ast::Pattern::Tuple { elements, .. } => elements.iter().enumerate().collect(),
+                    _ => Vec::new(),
+                };
+                for (index, subpattern) in subpatterns {
+                    if matches!(subpattern, ast::Pattern::Wildcard { .. }) {
+                        continue;
+                    }
+                    let Some((_, field_ty)) = fields.get(index) else { continue };
+                    let (field_val, field_ty) = self.emit_field(value.clone(), &struct_name, index, field_ty);
+                    if let ast::Pattern::Binding { name, .. } = subpattern {
+                        bindings.push((name.name.clone(), field_val, field_ty));
+                        continue;
+                    }
+                    let next = self.add_block("match_field");
+                    self.generate_pattern_test(subpattern, field_val, &field_ty, &[], next, on_fail, bindings)?;
+                    self.current_block = next;
+                }
+                self.set_terminator_current(Terminator::Jump { target: on_match });
+            }
+        }
+        Ok(())
+    }
+
+    /// Bind the names of a `let` or parameter pattern, which the semantic
+    /// pass has checked to match every value
+    fn destructure(&mut self, pattern: &ast::Pattern, value: Value, ty: &IRType) -> Result<()> {
+        let bind = self.add_block("let_bind");
+        let refuted = self.add_block("let_refuted");
+        let mut bindings = Vec::new();
+        self.generate_pattern_test(pattern, value, ty, &[], bind, refuted, &mut bindings)?;
+        self.current_block = refuted;
+        self.set_terminator_current(Terminator::Unreachable);
+        self.current_block = bind;
+        for (name, value, ty) in bindings {
+            let reg = self.alloc_register();
+            self.emit_current(Instruction::Assign { dest: reg, value });
+            self.reg_types.insert(reg, ty.clone());
+            self.locals.insert(name, (Value::Register(reg), ty));
+        }
+        Ok(())
+    }
+
+    /// Remember the type arguments of the parameters declared as a generic enum
+    fn record_param_type_args(&mut self, func: &ast::Function) {
+        for param in &func.params {
+            let args = self.enum_type_args(&param.ty);
+            if let (Some(args), Some((Value::Register(reg), _))) = (args, self.locals.get(&param.name.name)) {
+                self.local_type_args.insert(*reg, args);
+            }
+        }
+    }
+
+    /// Type arguments of the generic enum `expr` points to, where known: a
+    /// local or parameter declared with them, or a call to a function returning them
+    fn type_args_of(&self, expr: &Expr) -> Vec<IRType> {
+        let known = match expr {
+            Expr::Ident(ident) => match self.locals.get(&ident.name) {
+                Some((Value::Register(reg), _)) => self.local_type_args.get(reg),
+                _ => None,
+            },
+            Expr::Call { func, .. } => {
+                let names: Vec<&str> = match func.as_ref() {
+                    Expr::Ident(ident) => vec![ident.name.as_str()],
+                    Expr::Path { segments, .. } => segments.iter().map(|s| s.name.as_str()).collect(),
+                    _ => Vec::new(),
+                };
+                self.resolve_function(&names).and_then(|symbol| self.returned_type_args.get(&symbol))
+            }
+            _ => None,
+        };
+        known.cloned().unwrap_or_default()
+    }
+
+    /// Type arguments of `ty` if it instantiates a generic enum
+    fn enum_type_args(&self, ty: &AstType) -> Option<Vec<IRType>> {
+        match ty {
+            AstType::Generic(name, args, _) if self.generic_enums.contains(name) => {
+                Some(args.iter().map(|arg| self.ast_type_to_ir(arg)).collect())
+            }
+            _ => None,
+        }
+    }
+
+    fn destructure_params(&mut self, func: &ast::Function) -> Result<()> {
+        for param in &func.params {
+            let Some(pattern) = &param.pattern else { continue };
+            if let Some((value, ty)) = self.locals.get(&param.name.name).cloned() {
+                self.destructure(pattern, value, &ty)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Type of the enum an `Enum_Variant` constructor builds
+    fn constructor_type(&self, symbol: &str) -> Option<IRType> {
+        self.module.enums.iter()
+            .find(|e| e.variants.iter().any(|v| symbol.strip_prefix(e.name.as_str()).and_then(|rest| rest.strip_prefix('_')) == Some(v.name.as_str())))
+            .map(|e| IRType::Ptr(Box::new(IRType::Struct(e.name.clone()))))
+    }
+
+    /// `Option_Some` for `Some` and so on: the constructor of the prelude
+    /// enum variant a bare name stands for
+    fn prelude_constructor(&self, variant: &str) -> Option<String> {
+        PRELUDE_ENUMS.iter()
+            .find(|(_, variants)| variants.iter().any(|(v, _)| *v == variant))
+            .map(|(name, _)| format!("{}_{}", name, variant))
+            .filter(|symbol| self.constructor_type(symbol).is_some())
+    }
+
+    /// Discriminant of `variant`, looked up in `enum_name` or else in every enum
+    fn variant_tag(&self, enum_name: Option<&str>, variant: &str) -> Option<i64> {
+        self.module.enums.iter()
+            .filter(|e| enum_name.is_none_or(|name| e.name == name))
+            .find_map(|e| e.variants.iter().find(|v| v.name == variant))
+            .map(|v| v.discriminant)
+    }
+
+    /// Enum definition behind a value of type `ty` (enums are heap pointers)
+    fn enum_of(&self, ty: &IRType) -> Option<&crate::middle::ir::IREnum> {
+        let IRType::Ptr(inner) = ty else { return None };
+        let IRType::Struct(name) = inner.as_ref() else { return None };
+        self.module.enums.iter().find(|e| &e.name == name)
+    }
+
+    /// Discriminant type of `ty` if it is an enum without payloads
+    fn c_enum_tag(&self, ty: &IRType) -> Option<IRType> {
+        self.enum_of(ty)
+            .filter(|e| e.variants.iter().all(|v| v.fields.is_empty()))
+            .map(|e| e.tag.clone())
+    }
+
+    /// The prelude enum behind a value of type `ty`, with the variant that
+    /// holds a value (`Some` or `Ok`)
+    fn prelude_enum_of(&self, ty: Option<&IRType>) -> Option<(&'static str, &'static str)> {
+        let name = self.enum_of(ty?)?.name.as_str();
+        PRELUDE_ENUMS.iter().find(|(n, _)| *n == name).map(|(n, variants)| (*n, variants[0].0))
+    }
+
+    /// Whether the enum `value` points to holds `variant`
+    fn emit_is_variant(&mut self, value: Value, enum_name: &str, variant: &str) -> Value {
+        let tag = self.variant_tag(Some(enum_name), variant).unwrap_or_default();
+        let tag_ty = self.module.enums.iter().find(|e| e.name == enum_name).map_or(IRType::I32, |e| e.tag.clone());
+        let actual = self.emit_load(value, &tag_ty);
+        self.emit_binop(IRBinOp::Eq, actual, Value::Constant(Constant::Int(tag)), IRType::Bool)
+    }
+
+    /// Payload of `variant` of the prelude enum `value` points to
+    fn emit_prelude_payload(&mut self, value: Value, enum_name: &str, variant: &str) -> Value {
+        match self.module.variant_fields(enum_name, variant).and_then(|fields| fields.into_iter().next()) {
+            Some((offset, ty)) => self.emit_payload(value, offset, ty).0,
+            None => Value::Unit,
+        }
+    }
+
+    /// `expr?`: the payload of an `Ok` or `Some`, else return early with the
+    /// `Err` or `None` itself, valid in any instantiation of the erased enum
+    fn generate_try(&mut self, expr: &Expr) -> Result<Value> {
+        let value = self.generate_expr(expr)?;
+        let Some((enum_name, holds_value)) = self.prelude_enum_of(self.get_value_type(&value).as_ref()) else {
+            return Ok(value);
+        };
+        let is_value = self.emit_is_variant(value.clone(), enum_name, holds_value);
+        let unwrap = self.add_block("try_ok");
+        let early_return = self.add_block("try_return");
+        self.set_terminator_current(Terminator::Branch { cond: is_value, then_target: unwrap, else_target: early_return });
+
+        self.current_block = early_return;
+        let is_sret = self.locals.contains_key("__sret");
+        if is_sret {
+            self.store_sret(value.clone());
+        }
+        let returns_void = self.current_fn.as_ref().is_some_and(|f| f.ret_type == IRType::Void);
+        self.set_terminator_current(Terminator::Return { value: (!returns_void).then(|| value.clone()) });
+
+        self.current_block = unwrap;
+        Ok(self.emit_prelude_payload(value, enum_name, holds_value))
+    }
+
+    /// `unwrap`, `unwrap_or` and `map` on `Option`, and `unwrap` and
+    /// `map_err` on `Result`, unless the program's own impl defines them
+    fn generate_prelude_method(
+        &mut self,
+        value: Value,
+        (enum_name, holds_value): (&'static str, &'static str),
+        method: &str,
+        args: &[Expr],
+        span: Span,
+    ) -> Result<Option<Value>> {
+        if self.resolve_function(&[enum_name, method]).is_some() {
+            return Ok(None);
+        }
+        match (enum_name, method, args) {
+            (_, "unwrap", []) => {
+                let is_value = self.emit_is_variant(value.clone(), enum_name, holds_value);
+                let message = if enum_name == "Option" { "called `unwrap` on a `None` value" } else { "called `unwrap` on an `Err` value" };
+                self.check_value(is_value, message, span);
+                Ok(Some(self.emit_prelude_payload(value, enum_name, holds_value)))
+            }
+            ("Option", "unwrap_or", [default]) => {
+                let is_value = self.emit_is_variant(value.clone(), enum_name, holds_value);
+                let (some, none, merge) = (self.add_block("unwrap_some"), self.add_block("unwrap_none"), self.add_block("unwrap_merge"));
+                self.set_terminator_current(Terminator::Branch { cond: is_value, then_target: some, else_target: none });
+                let result = self.alloc_register();
+
+                self.current_block = some;
+                let payload = self.emit_prelude_payload(value, enum_name, holds_value);
+                if let Some(ty) = self.get_value_type(&payload) {
+                    self.reg_types.insert(result, ty);
+                }
+                self.emit_current(Instruction::Assign { dest: result, value: payload });
+                self.set_terminator_current(Terminator::Jump { target: merge });
+
+                // The default is only evaluated when needed
+                self.current_block = none;
+                let default = self.generate_expr(default)?;
+                self.emit_current(Instruction::Assign { dest: result, value: default });
+                self.set_terminator_current(Terminator::Jump { target: merge });
+
+                self.current_block = merge;
+                Ok(Some(Value::Register(result)))
+            }
+            ("Option", "map", [func]) | ("Result", "map_err", [func]) => {
+                let Value::Global(name) = self.generate_expr(func)? else {
+                    return Err(Error::CodeGen(format!("`{}` takes a function or closure", method)));
+                };
+                let symbol = self.resolve_function(&[&name]).unwrap_or(name);
+                let ret = self.function_signatures.get(&symbol).map(|(ret, _)| ret.clone())
+                    .or_else(|| self.module.functions.iter().find(|f| f.name == symbol).map(|f| f.ret_type.clone()))
+                    .unwrap_or(IRType::I64);
+                let mapped = if method == "map" { holds_value } else { "Err" };
+                let enum_ty = IRType::Ptr(Box::new(IRType::Struct(enum_name.to_string())));
+
+                let is_mapped = self.emit_is_variant(value.clone(), enum_name, mapped);
+                let (apply, keep, merge) = (self.add_block("map_apply"), self.add_block("map_keep"), self.add_block("map_merge"));
+                self.set_terminator_current(Terminator::Branch { cond: is_mapped, then_target: apply, else_target: keep });
+                let result = self.alloc_register();
+                self.reg_types.insert(result, enum_ty.clone());
+
+                self.current_block = apply;
+                let payload = self.emit_prelude_payload(value.clone(), enum_name, mapped);
+                let output = self.emit_call(&symbol, vec![payload], ret);
+                let rebuilt = self.emit_call(&format!("{}_{}", enum_name, mapped), vec![output], enum_ty);
+                self.emit_current(Instruction::Assign { dest: result, value: rebuilt });
+                self.set_terminator_current(Terminator::Jump { target: merge });
+
+                // The other variant passes through, its payload untouched
+                self.current_block = keep;
+                self.emit_current(Instruction::Assign { dest: result, value });
+                self.set_terminator_current(Terminator::Jump { target: merge });
+
+                self.current_block = merge;
+                Ok(Some(Value::Register(result)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Lower a match whose arms are all constant patterns to a single `Switch`
+    fn generate_switch_match(
+        &mut self,
+        match_val: Value,
+        match_ty: IRType,
+        arms: &[ast::MatchArm],
+        cases: &[Option<Vec<i64>>],
+        span: Span,
+    ) -> Result<Value> {
+        let exit_block = self.add_block("match_exit");
+        let result_reg = self.alloc_register();
+        self.reg_types.insert(result_reg, match_ty);
+
+        // Arms after the first `_` can never be reached
+        let reachable = cases.iter().position(|c| c.is_none()).map_or(arms.len(), |i| i + 1);
+        let body_blocks: Vec<BlockId> = (0..reachable)
+            .map(|i| self.add_block(&format!("match_body_{}", i)))
+            .collect();
+
+        let mut switch_cases: Vec<(i64, BlockId)> = Vec::new();
+        let mut default = None;
+        for (case, &body) in cases.iter().zip(&body_blocks) {
+            match case {
+                // An earlier arm with the same value wins
+                Some(values) => for n in values {
+                    if !switch_cases.iter().any(|(c, _)| c == n) {
+                        switch_cases.push((*n, body));
+                    }
+                },
+                None => default = Some(body),
+            }
+        }
+        // Analysis requires `_` where the arms do not list every value, but a
+        // tag cast from an integer can still miss every case
+        let default = match default {
+            Some(body) => body,
+            None => self.unmatched_block(span),
+        };
+        self.set_terminator_current(Terminator::Switch { value: match_val, cases: switch_cases, default });
+
+        for (arm, &body) in arms.iter().zip(&body_blocks) {
+            self.current_block = body;
+            let body_val = self.generate_expr(&arm.body)?;
+            self.emit_current(Instruction::Assign { dest: result_reg, value: body_val });
+            self.set_terminator_current(Terminator::Jump { target: exit_block });
+        }
+
+        self.current_block = exit_block;
+        Ok(Value::Register(result_reg))
+    }
+
+    fn generate_literal(&self, lit: &ast::Literal) -> Value {
+        match lit {
+            ast::Literal::Int(n, _) => Value::Constant(Constant::Int(*n)),
+            ast::Literal::Float(n, _) => Value::Constant(Constant::Float(*n)),
+            ast::Literal::Bool(b, _) => Value::Constant(Constant::Bool(*b)),
+            ast::Literal::String(s, _) => Value::Constant(Constant::String(s.clone())),
+            ast::Literal::Char(c, _) => Value::Constant(Constant::Int(*c as i64)),
+        }
+    }
+
+    // ==================== Helper Methods ====================
+
+    fn alloc_register(&mut self) -> Register {
+        let reg = Register(self.next_register);
+        self.next_register += 1;
+        reg
+    }
+
+    /// Source location of `span` when debug info is on
+    fn source_loc(&self, span: Span) -> Option<SourceLoc> {
+        if !self.debug_info {
+            return None;
+        }
+        let (file, line, col) = self.source_lines.as_ref()?.locate(span);
+        Some(SourceLoc { file: file.to_string(), line, col })
+    }
+
+    /// Convert between `str` and C strings where one meets the other: literals
+    /// become `str` constants, other pointers are measured with `strlen`, and a
+    /// `str` decays to its data pointer
+    fn coerce_str(&mut self, value: Value, target: &IRType) -> Value {
+        let (func, ret) = match (target, self.get_value_type(&value)) {
+            (IRType::Str, Some(IRType::Ptr(_))) => match value {
+                Value::Constant(Constant::String(s)) => return Value::Constant(Constant::Str(s)),
+                _ => (STR_FROM_PTR, IRType::Str),
+            },
+            (IRType::Ptr(_), Some(IRType::Str)) => (STR_AS_PTR, IRType::Ptr(Box::new(IRType::U8))),
+            _ => return value,
+        };
+        self.emit_call(func, vec![value], ret)
+    }
+
+    /// `s.len()`, `s.as_ptr()` and `s.slice(start, end)` on a `str`
+    fn generate_str_method(&mut self, receiver: Value, method: &str, args: &[Expr], span: Span) -> Result<Value> {
+        let receiver = self.coerce_str(receiver, &IRType::Str);
+        match (method, args) {
+            ("len", _) => Ok(self.emit_call(STR_LEN, vec![receiver], IRType::U64)),
+            ("as_ptr", _) => Ok(self.emit_call(STR_AS_PTR, vec![receiver], IRType::Ptr(Box::new(IRType::U8)))),
+            (_, [start, end]) => {
+                let start = self.generate_expr(start)?;
+                let start = self.emit_cast(start, IRType::U64);
+                let end = self.generate_expr(end)?;
+                let end = self.emit_cast(end, IRType::U64);
+                let len = self.emit_call(STR_LEN, vec![receiver.clone()], IRType::U64);
+                let ordered = self.emit_binop(IRBinOp::Le, start.clone(), end.clone(), IRType::Bool);
+                let in_bounds = self.emit_binop(IRBinOp::Le, end.clone(), len, IRType::Bool);
+                let ok = self.emit_binop(IRBinOp::And, ordered, in_bounds, IRType::Bool);
+                self.check_value(ok, "str slice out of range", span);
+                Ok(self.emit_call(STR_SLICE, vec![receiver, start, end], IRType::Str))
+            }
+            _ => Ok(Value::Unit),
+        }
+    }
+
+    /// The `Vec`/`HashMap` handle `value` refers to, loading it through a reference
+    fn collection_handle(&mut self, value: Value) -> Option<(Value, IRType)> {
+        match self.get_value_type(&value)? {
+            ty @ (IRType::Vec(_) | IRType::Map(..)) => Some((value, ty)),
+            IRType::Ptr(inner) if matches!(*inner, IRType::Vec(_) | IRType::Map(..)) => {
+                let handle = self.emit_load(value, &inner);
+                Some((handle, *inner))
+            }
+            _ => None,
+        }
+    }
+
+    /// Methods of the builtin `Vec<T>` and `HashMap<K, V>`; see `middle::collections`
+    fn generate_collection_method(&mut self, handle: Value, ty: &IRType, method: &str, args: &[Expr], span: Span) -> Result<Value> {
+        let ptr_u8 = IRType::Ptr(Box::new(IRType::U8));
+        match ty {
+            IRType::Vec(elem) => {
+                let size = Value::Constant(Constant::Int(self.size_of(elem)));
+                match (method, args) {
+                    ("push", [value]) => {
+                        let value = self.generate_expr(value)?;
+                        let value = self.coerce_str(value, elem);
+                        // A struct value is already the address to copy from
+                        let slot = match (elem.as_ref(), self.get_value_type(&value)) {
+                            (IRType::Struct(_), Some(IRType::Ptr(_))) => value,
+                            _ => self.emit_spill(value, elem),
+                        };
+                        Ok(self.emit_call(VEC_PUSH, vec![handle, slot, size], IRType::Void))
+                    }
+                    ("pop", []) => {
+                        if self.size_of(elem) > 8 {
+                            return Err(Error::CodeGen(
+                                "`pop` on a `Vec` of elements wider than 64 bits, more than an `Option` holds; index the last element instead".to_string()
+                            ));
+                        }
+                        let len = self.emit_call(VEC_LEN, vec![handle.clone()], IRType::U64);
+                        let non_empty = self.emit_binop(IRBinOp::Gt, len, Value::Constant(Constant::Int(0)), IRType::Bool);
+                        self.generate_option(non_empty, |gen| {
+                            let slot = gen.emit_call(VEC_POP, vec![handle, size], ptr_u8);
+                            gen.emit_load(slot, elem)
+                        })
+                    }
+                    ("get", [index]) => {
+                        let index = self.generate_expr(index)?;
+                        let index = self.emit_cast(index, IRType::U64);
+                        let len = self.emit_call(VEC_LEN, vec![handle.clone()], IRType::U64);
+                        let in_bounds = self.emit_binop(IRBinOp::Lt, index.clone(), len, IRType::Bool);
+                        self.generate_option(in_bounds, |gen| gen.emit_call(VEC_GET, vec![handle, index, size], ptr_u8))
+                    }
+                    ("is_empty", []) => {
+                        let len = self.emit_call(VEC_LEN, vec![handle], IRType::U64);
+                        Ok(self.emit_binop(IRBinOp::Eq, len, Value::Constant(Constant::Int(0)), IRType::Bool))
+                    }
+                    ("len", []) => Ok(self.emit_call(VEC_LEN, vec![handle], IRType::U64)),
+                    ("clear", []) => Ok(self.emit_call(VEC_CLEAR, vec![handle], IRType::Void)),
+                    ("free", []) => Ok(self.emit_call(VEC_FREE, vec![handle], IRType::Void)),
+                    _ => Ok(Value::Unit),
+                }
+            }
+            IRType::Map(key_ty, value_ty) => {
+                if let ("len" | "free", []) = (method, args) {
+                    let (func, ret) = if method == "len" { (MAP_LEN, IRType::U64) } else { (MAP_FREE, IRType::Void) };
+                    return Ok(self.emit_call(func, vec![handle], ret));
+                }
+                if let ("is_empty", []) = (method, args) {
+                    let len = self.emit_call(MAP_LEN, vec![handle], IRType::U64);
+                    return Ok(self.emit_binop(IRBinOp::Eq, len, Value::Constant(Constant::Int(0)), IRType::Bool));
+                }
+                let Some(key) = args.first() else { return Ok(Value::Unit) };
+                let (kind, key_ty) = if **key_ty == IRType::Str { (KeyKind::Str, IRType::Str) } else { (KeyKind::Int, IRType::I64) };
+                let key = self.generate_expr(key)?;
+                let key = self.coerce_str(key, &key_ty);
+                let key = self.emit_cast(key, key_ty.clone());
+                let key_slot = self.emit_spill(key, &key_ty);
+                let kind = Value::Constant(Constant::Int(kind as i64));
+                match (method, &args[1..]) {
+                    ("insert", [value]) => {
+                        let value = self.generate_expr(value)?;
+                        let value = self.coerce_str(value, value_ty);
+                        let value_slot = self.emit_spill(value, value_ty);
+                        let size = Value::Constant(Constant::Int(self.size_of(value_ty)));
+                        Ok(self.emit_call(MAP_INSERT, vec![handle, key_slot, kind, value_slot, size], IRType::Void))
+                    }
+                    ("get", []) => {
+                        let found = self.emit_call(MAP_CONTAINS, vec![handle.clone(), key_slot.clone(), kind.clone()], IRType::Bool);
+                        self.check_value(found, "key not found in HashMap", span);
+                        let slot = self.emit_call(MAP_GET, vec![handle, key_slot, kind], ptr_u8);
+                        Ok(self.emit_load(slot, value_ty))
+                    }
+                    ("contains", []) => Ok(self.emit_call(MAP_CONTAINS, vec![handle, key_slot, kind], IRType::Bool)),
+                    ("remove", []) => Ok(self.emit_call(MAP_REMOVE, vec![handle, key_slot, kind], IRType::Bool)),
+                    _ => Ok(Value::Unit),
+                }
+            }
+            _ => Ok(Value::Unit),
+        }
+    }
+
+    /// `Some` of the value `payload` emits when `cond` holds, else `None`;
+    /// the payload is widened to the erased `i64` of the prelude `Option`
+    fn generate_option(&mut self, cond: Value, payload: impl FnOnce(&mut Self) -> Value) -> Result<Value> {
+        if self.constructor_type("Option_Some").is_none() {
+            return Err(Error::CodeGen("`Vec::get` and `Vec::pop` need the prelude `Option`".to_string()));
+        }
+        let enum_ty = IRType::Ptr(Box::new(IRType::Struct("Option".to_string())));
+        let (some, none, merge) = (self.add_block("option_some"), self.add_block("option_none"), self.add_block("option_merge"));
+        self.set_terminator_current(Terminator::Branch { cond, then_target: some, else_target: none });
+        let result = self.alloc_register();
+        self.reg_types.insert(result, enum_ty.clone());
+
+        self.current_block = some;
+        let value = payload(self);
+        let value = self.emit_cast(value, IRType::I64);
+        let built = self.emit_call("Option_Some", vec![value], enum_ty.clone());
+        self.emit_current(Instruction::Assign { dest: result, value: built });
+        self.set_terminator_current(Terminator::Jump { target: merge });
+
+        self.current_block = none;
+        let built = self.emit_call("Option_None", vec![], enum_ty);
+        self.emit_current(Instruction::Assign { dest: result, value: built });
+        self.set_terminator_current(Terminator::Jump { target: merge });
+
+        self.current_block = merge;
+        Ok(Value::Register(result))
+    }
+
+    /// Address of `v[index]` as a `*u8`, panicking at `span` when out of range
+    fn generate_vec_slot(&mut self, handle: Value, elem: &IRType, index: &Expr, span: Span) -> Result<Value> {
+        let index = self.generate_expr(index)?;
+        let index = self.emit_cast(index, IRType::U64);
+        let len = self.emit_call(VEC_LEN, vec![handle.clone()], IRType::U64);
+        let in_bounds = self.emit_binop(IRBinOp::Lt, index.clone(), len, IRType::Bool);
+        self.check_value(in_bounds, "Vec index out of bounds", span);
+        let size = Value::Constant(Constant::Int(self.size_of(elem)));
+        let slot = self.emit_call(VEC_GET, vec![handle, index, size], IRType::Ptr(Box::new(IRType::U8)));
+        Ok(self.emit_cast(slot, IRType::Ptr(Box::new(elem.clone()))))
+    }
+
+    /// Size in bytes of a value of `ty`, with struct layouts from the module
+    fn size_of(&self, ty: &IRType) -> i64 {
+        self.module.layout_of(ty).map_or(ty.size_bytes(), |layout| layout.size) as i64
+    }
+
+    /// Call `func`, returning its result (or unit for `void`)
+    fn emit_call(&mut self, func: &str, args: Vec<Value>, ret: IRType) -> Value {
+        if ret == IRType::Void {
+            self.emit_current(Instruction::Call { dest: None, func: func.to_string(), args });
+            return Value::Unit;
+        }
+        let dest = self.alloc_register();
+        self.emit_current_with_type(Instruction::Call { dest: Some(dest), func: func.to_string(), args }, ret);
+        Value::Register(dest)
+    }
+
+    /// `value` converted to `ty`, unless it already has that type
+    fn emit_cast(&mut self, value: Value, ty: IRType) -> Value {
+        if self.get_value_type(&value).as_ref() == Some(&ty) {
+            return value;
+        }
+        let dest = self.alloc_register();
+        self.emit_current_with_type(Instruction::Cast { dest, value, ty: ty.clone() }, ty);
+        Value::Register(dest)
+    }
+
+    fn emit_binop(&mut self, op: IRBinOp, left: Value, right: Value, ty: IRType) -> Value {
+        let dest = self.alloc_register();
+        self.emit_current_with_type(Instruction::BinOp { dest, op, left, right }, ty);
+        Value::Register(dest)
+    }
+
+    /// The local variable or parameter `expr` names
+    fn local_of(&self, expr: &ast::Expr) -> Option<(Value, IRType)> {
+        let Expr::Ident(ident) = expr else { return None };
+        self.locals.get(&ident.name).cloned()
+    }
+
+    /// Call the function pointer `callee` evaluates to
+    fn generate_indirect_call(&mut self, callee: &ast::Expr, params: &[IRType], ret: &IRType, args: &[ast::Expr]) -> Result<Value> {
+        let callee = self.generate_expr(callee)?;
+        let mut arg_vals = Vec::with_capacity(args.len());
+        for (arg, ty) in args.iter().zip(params) {
+            let val = self.generate_expr(arg)?;
+            arg_vals.push(self.coerce_str(val, ty));
+        }
+        let ty = IRType::Function { params: params.to_vec(), ret: Box::new(ret.clone()) };
+        if *ret == IRType::Void {
+            self.emit_current(Instruction::CallIndirect { dest: None, callee, ty, args: arg_vals });
+            return Ok(Value::Unit);
+        }
+        let dest = self.alloc_register();
+        self.emit_current_with_type(Instruction::CallIndirect { dest: Some(dest), callee, ty, args: arg_vals }, ret.clone());
+        Ok(Value::Register(dest))
+    }
+
+    /// A new enum of type `enum_ty` holding the discriminant `value` (the
+    /// unchecked `n as Enum`)
+    fn emit_enum_from_tag(&mut self, value: Value, tag: &IRType, enum_ty: IRType) -> Value {
+        let size = match &enum_ty {
+            IRType::Ptr(inner) => self.module.layout_of(inner).map_or(8, |l| l.size),
+            _ => 8,
+        };
+        let raw = self.emit_call("malloc", vec![Value::Constant(Constant::Int(size as i64))], IRType::Ptr(Box::new(IRType::U8)));
+        let tag_ptr = self.emit_cast(raw.clone(), IRType::Ptr(Box::new(tag.clone())));
+        let tag_val = self.emit_cast(value, tag.clone());
+        self.emit_current(Instruction::Store { ptr: tag_ptr, value: tag_val, volatile: false });
+        self.emit_cast(raw, enum_ty)
+    }
+
+    /// Load a `ty` from `ptr`, which may be an untyped `*u8`
+    fn emit_load(&mut self, ptr: Value, ty: &IRType) -> Value {
+        let ptr = self.emit_cast(ptr, IRType::Ptr(Box::new(ty.clone())));
+        let dest = self.alloc_register();
+        self.emit_current_with_type(Instruction::Load { dest, ptr, ty: ty.clone(), volatile: false }, ty.clone());
+        Value::Register(dest)
+    }
+
+    /// Generate `expr` for a slot of type `expected`; a tuple literal takes
+    /// its element types from it
+    fn generate_expr_as(&mut self, expr: &ast::Expr, expected: &IRType) -> Result<Value> {
+        match (expr, expected) {
+            (Expr::Tuple { elements, .. }, IRType::Struct(name)) if !elements.is_empty() => {
+                let types = self.struct_defs.get(name)
+                    .filter(|fields| fields.len() == elements.len())
+                    .map(|fields| fields.iter().map(|(_, ty)| ty.clone()).collect::<Vec<_>>());
+                self.generate_tuple(elements, types.as_deref())
+            }
+            _ => self.generate_expr(expr),
+        }
+    }
+
+    /// Build a tuple on the stack; without `types`, elements keep the types
+    /// of their values
+    fn generate_tuple(&mut self, elements: &[ast::Expr], types: Option<&[IRType]>) -> Result<Value> {
+        let mut values = Vec::new();
+        for (i, element) in elements.iter().enumerate() {
+            let value = match types {
+                Some(types) => {
+                    let value = self.generate_expr_as(element, &types[i])?;
+                    (self.coerce_str(value, &types[i]), types[i].clone())
+                }
+                None => {
+                    let value = self.generate_expr(element)?;
+                    let ty = match self.get_value_type(&value) {
+                        // Nested literals are held by value
+                        Some(IRType::Ptr(inner)) if matches!(element, Expr::Tuple { .. } | Expr::StructLit { .. }) => *inner,
+                        ty => ty.unwrap_or(IRType::I64),
+                    };
+                    (value, ty)
+                }
+            };
+            values.push(value);
+        }
+        let tuple_ty = self.define_tuple(values.iter().map(|(_, ty)| ty.clone()).collect());
+        let ptr = self.alloc_register();
+        self.emit_current_with_type(Instruction::Alloca { dest: ptr, ty: tuple_ty.clone() },
+            IRType::Ptr(Box::new(tuple_ty.clone())));
+        for (i, (value, ty)) in values.into_iter().enumerate() {
+            let slot = self.alloc_register();
+            self.emit_current_with_type(Instruction::GetElementPtr {
+                dest: slot,
+                ptr: Value::Register(ptr),
+                index: Value::Constant(Constant::Int(i as i64)),
+                elem_ty: tuple_ty.clone(),
+            }, IRType::Ptr(Box::new(ty.clone())));
+            self.emit_store_field(Value::Register(slot), value, &ty);
+        }
+        Ok(Value::Register(ptr))
+    }
+
+    /// Store `value` in a field of type `field_ty`; a struct held by value is
+    /// copied out of the `Ptr(Struct)` that stands for it
+    fn emit_store_field(&mut self, ptr: Value, value: Value, field_ty: &IRType) {
+        let value = match (field_ty, self.get_value_type(&value)) {
+            (IRType::Struct(_), Some(IRType::Ptr(_))) => self.emit_load(value, field_ty),
+            _ => value,
+        };
+        self.emit_current(Instruction::Store { ptr, value, volatile: false });
+    }
+
+    /// Address of field `index` of the struct `value` points to, and the
+    /// field's type; a struct field is used in place, anything else loaded
+    fn emit_field(&mut self, value: Value, struct_name: &str, index: usize, field_ty: &IRType) -> (Value, IRType) {
+        let addr = self.alloc_register();
+        self.emit_current_with_type(Instruction::GetElementPtr {
+            dest: addr,
+            ptr: value,
+            index: Value::Constant(Constant::Int(index as i64)),
+            elem_ty: IRType::Struct(struct_name.to_string()),
+        }, IRType::Ptr(Box::new(field_ty.clone())));
+        match field_ty {
+            IRType::Struct(_) => (Value::Register(addr), IRType::Ptr(Box::new(field_ty.clone()))),
+            _ => (self.emit_load(Value::Register(addr), field_ty), field_ty.clone()),
+        }
+    }
+
+    /// Load through pointers to struct pointers until `Ptr(Struct)` remains.
+    /// A struct value is already a `Ptr(Struct)`, so each extra `Ptr` layer is
+    /// one deref; the semantic pass rejects chains deeper than its limit.
+    fn auto_deref_struct(&mut self, mut value: Value, mut ty: Option<IRType>) -> (Value, Option<IRType>) {
+        while let Some(IRType::Ptr(inner)) = &ty {
+            let IRType::Ptr(pointee) = inner.as_ref() else { break };
+            if !Self::points_to_struct(pointee) {
+                break;
+            }
+            let loaded = (**inner).clone();
+            let dest = self.alloc_register();
+            self.emit_current_with_type(Instruction::Load {
+                dest,
+                ptr: value,
+                ty: loaded.clone(),
+                volatile: false,
+            }, loaded.clone());
+            value = Value::Register(dest);
+            ty = Some(loaded);
+        }
+        (value, ty)
+    }
+
+    fn is_pointer_type(ty: &AstType) -> bool {
+        matches!(ty, AstType::Pointer(..) | AstType::Ref { .. } | AstType::Volatile(..))
+    }
+
+    /// Element type and length of `[T; N]`, `&[T; N]` or `*[T; N]`
+    fn array_shape_of(&self, ty: &AstType) -> Option<(IRType, usize)> {
+        let array = match ty {
+            AstType::Pointer(inner, _) | AstType::Ref { inner, .. } => inner.as_ref(),
+            other => other,
+        };
+        match array {
+            AstType::Array { elem, size, .. } => Some((self.ast_type_to_ir(elem), *size)),
+            _ => None,
+        }
+    }
+
+    /// Element type and length of the array `iter` evaluates to
+    fn array_shape(&self, iter: &Expr, value: &Value) -> Option<(IRType, usize)> {
+        match self.get_value_type(value) {
+            Some(IRType::Array(elem, len)) => return Some((*elem, len)),
+            Some(IRType::Ptr(inner)) => {
+                if let IRType::Array(elem, len) = *inner {
+                    return Some((*elem, len));
+                }
+            }
+            _ => {}
+        }
+        // A declared `[T; N]` is only a pointer to its first element
+        let name = match iter {
+            Expr::Ref { expr, .. } => expr.as_ref(),
+            other => other,
+        };
+        let Expr::Ident(ident) = name else {
+            return None;
+        };
+        self.array_locals.get(&ident.name).cloned()
+    }
+
+    /// `Struct`, or any number of pointers to one
+    fn points_to_struct(ty: &IRType) -> bool {
+        match ty {
+            IRType::Struct(_) => true,
+            IRType::Ptr(inner) => Self::points_to_struct(inner),
+            _ => false,
+        }
+    }
+
+    /// Store `value` in a fresh stack slot and return its address
+    fn emit_spill(&mut self, value: Value, ty: &IRType) -> Value {
+        let dest = self.alloc_register();
+        self.emit_current_with_type(Instruction::Alloca { dest, ty: ty.clone() }, IRType::Ptr(Box::new(ty.clone())));
+        self.emit_current(Instruction::Store { ptr: Value::Register(dest), value, volatile: false });
+        Value::Register(dest)
+    }
+
+    /// `left op right` on integers of type `ty`, panicking or saturating on
+    /// overflow according to the overflow mode
+    fn emit_checked_binop(&mut self, op: IRBinOp, left: Value, right: Value, ty: IRType, span: Span) -> Value {
+        let dest = self.alloc_register();
+        let overflow_dest = self.alloc_register();
+        self.reg_types.insert(overflow_dest, IRType::Bool);
+        self.emit_current_with_type(
+            Instruction::CheckedBinOp { dest, overflow_dest, op, left: left.clone(), right: right.clone() },
+            ty.clone(),
+        );
+
+        if self.overflow_mode == OverflowMode::Panicking {
+            let fits = self.emit_binop(IRBinOp::Eq, Value::Register(overflow_dest), Value::Constant(Constant::Bool(false)), IRType::Bool);
+            self.check_value(fits, "integer overflow", span);
+            return Value::Register(dest);
+        }
+
+        // Saturating: overwrite the result with the bound the exact result lies beyond
+        let Some((min, max)) = overflow::bounds(&ty) else { return Value::Register(dest) };
+        let (min, max) = (Value::Constant(Constant::Int(min)), Value::Constant(Constant::Int(max)));
+        let result = self.alloc_register();
+        self.emit_current_with_type(Instruction::Assign { dest: result, value: Value::Register(dest) }, ty.clone());
+        let saturate = self.add_block("overflow_saturate");
+        let merge = self.add_block("overflow_merge");
+        self.set_terminator_current(Terminator::Branch {
+            cond: Value::Register(overflow_dest),
+            then_target: saturate,
+            else_target: merge,
+        });
+
+        self.current_block = saturate;
+        let zero = Value::Constant(Constant::Int(0));
+        if matches!(ty, IRType::U8 | IRType::U16 | IRType::U32 | IRType::U64) {
+            // Unsigned subtraction can only fall below zero, addition and multiplication only exceed the maximum
+            let bound = if op == IRBinOp::Sub { min } else { max };
+            self.emit_current(Instruction::Assign { dest: result, value: bound });
+            self.set_terminator_current(Terminator::Jump { target: merge });
+        } else {
+            let negative = match op {
+                IRBinOp::Add => self.emit_binop(IRBinOp::Lt, right, zero, IRType::Bool),
+                IRBinOp::Sub => self.emit_binop(IRBinOp::Gt, right, zero, IRType::Bool),
+                _ => {
+                    let signs = self.emit_binop(IRBinOp::Xor, left, right, ty.clone());
+                    self.emit_binop(IRBinOp::Lt, signs, zero, IRType::Bool)
+                }
+            };
+            let below = self.add_block("overflow_min");
+            let above = self.add_block("overflow_max");
+            self.set_terminator_current(Terminator::Branch { cond: negative, then_target: below, else_target: above });
+            for (block, bound) in [(below, min), (above, max)] {
+                self.current_block = block;
+                self.emit_current(Instruction::Assign { dest: result, value: bound });
+                self.set_terminator_current(Terminator::Jump { target: merge });
+            }
+        }
+
+        self.current_block = merge;
+        Value::Register(result)
+    }
+
+    /// Branch to a panic reporting `message` at `span` unless `cond` holds
+    fn generate_check(&mut self, cond: &Expr, message: &str, span: Span) -> Result<()> {
+        let cond = self.generate_expr(cond)?;
+        self.check_value(cond, message, span);
+        Ok(())
+    }
+
+    /// Branch to a panic reporting `message` at `span` unless the value `cond` holds
+    fn check_value(&mut self, cond: Value, message: &str, span: Span) {
+        let ok = self.add_block("check_ok");
+        let failed = self.add_block("check_failed");
+        self.set_terminator_current(Terminator::Branch { cond, then_target: ok, else_target: failed });
+
+        self.current_block = failed;
+        self.emit_panic(message, span);
+        self.current_block = ok;
+    }
+
+    /// A block reached by the values no arm of the `match` at `span` matches,
+    /// which panics
+    fn unmatched_block(&mut self, span: Span) -> BlockId {
+        let current = self.current_block;
+        let unmatched = self.add_block("match_unmatched");
+        self.current_block = unmatched;
+        self.emit_panic("no match arm matched", span);
+        self.current_block = current;
+        unmatched
+    }
+
+    /// End the current block with a panic reporting `message` at `span`
+    fn emit_panic(&mut self, message: &str, span: Span) {
+        let (file, line) = match &self.source_lines {
+            Some(lines) => {
+                let (file, line, _) = lines.locate(span);
+                (file.to_string(), line)
+            }
+            None => ("<unknown>".to_string(), 0),
+        };
+        self.emit_current(Instruction::Call {
+            dest: None,
+            func: PANIC_FN.to_string(),
+            args: vec![
+                Value::Constant(Constant::String(message.to_string())),
+                Value::Constant(Constant::String(file)),
+                Value::Constant(Constant::Int(line as i64)),
+            ],
+        });
+        self.set_terminator_current(Terminator::Unreachable);
+    }
+
+    /// Check the `requires` clauses of `func` on entry
+    fn generate_preconditions(&mut self, func: &ast::Function) -> Result<()> {
+        for contract in func.contracts.iter().filter(|c| c.kind == ast::ContractKind::Requires) {
+            let message = format!("precondition of '{}' failed", func.name.name);
+            self.generate_check(&contract.condition, &message, contract.span)?;
+        }
+        Ok(())
+    }
+
+    /// Synthesize `__check_invariants_T(s: *T) -> bool`, which binds each
+    /// field of `*s` to its name and tests every invariant of `T`
+    fn generate_invariant_checker(&mut self, def: &ast::StructDef) -> Result<()> {
+        let struct_type = IRType::Struct(def.name.name.clone());
+        let ptr_type = IRType::Ptr(Box::new(struct_type.clone()));
+        let params = self.start_synthesized_function(&invariant_checker(&def.name.name), vec![("s".to_string(), ptr_type)], IRType::Bool);
+        let fields = self.struct_defs.get(&def.name.name).cloned().unwrap_or_default();
+        for (idx, (field, ty)) in fields.into_iter().enumerate() {
+            let value = self.emit_field_value(params[0].clone(), &struct_type, idx, &ty);
+            self.locals.insert(field, (value, ty));
+        }
+
+        let mut holds = Value::Constant(Constant::Bool(true));
+        for contract in &def.invariants {
+            let cond = self.generate_expr(&contract.condition)?;
+            holds = self.emit_binop(IRBinOp::And, holds, cond, IRType::Bool);
+        }
+        self.set_terminator_current(Terminator::Return { value: Some(holds) });
+
+        if let Some(func) = self.current_fn.take() {
+            self.module.functions.push(func);
+        }
+        Ok(())
+    }
+
+    /// Begin a function the compiler generates, as the current function;
+    /// returns registers holding its parameters
+    fn start_synthesized_function(&mut self, name: &str, params: Vec<(String, IRType)>, ret: IRType) -> Vec<Value> {
+        self.next_register = 0;
+        self.locals.clear();
+        self.reg_types.clear();
+        self.local_type_args.clear();
+        self.block_spans.clear();
+        self.last_debug_loc = None;
+        self.sret_ptr = None;
+        self.volatile_locals.clear();
+        self.pointer_locals.clear();
+        self.array_locals.clear();
+
+        let mut func = IRFunction::new(name, params.clone(), ret);
+        self.current_block = func.add_block("entry");
+        self.current_fn = Some(func);
+        params.into_iter().enumerate()
+            .map(|(i, (_, ty))| {
+                let reg = self.alloc_register();
+                self.emit_current_with_type(Instruction::Assign { dest: reg, value: Value::Parameter(i) }, ty);
+                Value::Register(reg)
+            })
+            .collect()
+    }
+
+    /// Field `idx` of the struct `base` points to; aggregates are passed
+    /// around by address, so those are not loaded
+    fn emit_field_value(&mut self, base: Value, struct_type: &IRType, idx: usize, ty: &IRType) -> Value {
+        let field_ptr = self.alloc_register();
+        self.emit_current_with_type(Instruction::GetElementPtr {
+            dest: field_ptr,
+            ptr: base,
+            index: Value::Constant(Constant::Int(idx as i64)),
+            elem_ty: struct_type.clone(),
+        }, IRType::Ptr(Box::new(ty.clone())));
+        if matches!(ty, IRType::Struct(_) | IRType::Array(..)) {
+            return Value::Register(field_ptr);
+        }
+        let dest = self.alloc_register();
+        self.emit_current_with_type(Instruction::Load {
+            dest,
+            ptr: Value::Register(field_ptr),
+            ty: ty.clone(),
+            volatile: false,
+        }, ty.clone());
+        Value::Register(dest)
+    }
+
+    /// Generate the equality function of the `#[derive(Eq)]` struct or enum
+    /// `name`: a struct compares field by field, an enum by variant and then
+    /// by the payload both values hold. Nested derived types are compared
+    /// with their own function.
+    fn generate_equality_function(&mut self, name: &str) {
+        let ty = IRType::Struct(name.to_string());
+        let ptr_type = IRType::Ptr(Box::new(ty.clone()));
+        let params = vec![("a".to_string(), ptr_type.clone()), ("b".to_string(), ptr_type)];
+        let params = self.start_synthesized_function(&equality_function(name), params, IRType::Bool);
+        let (a, b) = (params[0].clone(), params[1].clone());
+
+        let equal = match self.module.enums.iter().find(|e| e.name == name).cloned() {
+            None => {
+                let fields = self.struct_defs.get(name).cloned().unwrap_or_default();
+                let mut equal = Value::Constant(Constant::Bool(true));
+                for (idx, (_, field_ty)) in fields.iter().enumerate() {
+                    let left = self.emit_field_value(a.clone(), &ty, idx, field_ty);
+                    let right = self.emit_field_value(b.clone(), &ty, idx, field_ty);
+                    let same = self.emit_member_equality(left, right, field_ty);
+                    equal = self.emit_binop(IRBinOp::And, equal, same, IRType::Bool);
+                }
+                equal
+            }
+            Some(def) => {
+                let left_tag = self.emit_load(a.clone(), &def.tag);
+                let right_tag = self.emit_load(b.clone(), &def.tag);
+                let same_variant = self.emit_binop(IRBinOp::Eq, left_tag.clone(), right_tag, IRType::Bool);
+                let differ_block = self.add_block("differ");
+                let mut next_block = self.add_block("variant");
+                self.set_terminator_current(Terminator::Branch { cond: same_variant, then_target: next_block, else_target: differ_block });
+                self.current_block = differ_block;
+                self.set_terminator_current(Terminator::Return { value: Some(Value::Constant(Constant::Bool(false))) });
+
+                // Only the payload of the variant both hold is read
+                for variant in def.variants.iter().filter(|v| !v.fields.is_empty()) {
+                    self.current_block = next_block;
+                    let payload_block = self.add_block("payload");
+                    next_block = self.add_block("variant");
+                    let holds = self.emit_binop(IRBinOp::Eq, left_tag.clone(), Value::Constant(Constant::Int(variant.discriminant)), IRType::Bool);
+                    self.set_terminator_current(Terminator::Branch { cond: holds, then_target: payload_block, else_target: next_block });
+
+                    self.current_block = payload_block;
+                    let mut equal = Value::Constant(Constant::Bool(true));
+                    for (offset, field_ty) in self.module.variant_fields(name, &variant.name).unwrap_or_default() {
+                        let (left, _) = self.emit_payload(a.clone(), offset, field_ty.clone());
+                        let (right, _) = self.emit_payload(b.clone(), offset, field_ty.clone());
+                        let same = self.emit_member_equality(left, right, &field_ty);
+                        equal = self.emit_binop(IRBinOp::And, equal, same, IRType::Bool);
+                    }
+                    self.set_terminator_current(Terminator::Return { value: Some(equal) });
+                }
+                self.current_block = next_block;
+                // Variants without a payload are equal once the tags are
+                Value::Constant(Constant::Bool(true))
+            }
+        };
+        self.set_terminator_current(Terminator::Return { value: Some(equal) });
+
+        if let Some(func) = self.current_fn.take() {
+            self.module.functions.push(func);
+        }
+    }
+
+    /// `left == right` for members of type `ty` of a derived type; pointers
+    /// compare by address
+    fn emit_member_equality(&mut self, left: Value, right: Value, ty: &IRType) -> Value {
+        self.emit_equality(left.clone(), right.clone(), ty)
+            .unwrap_or_else(|| self.emit_binop(IRBinOp::Eq, left, right, IRType::Bool))
+    }
+
+    /// Start a new source line in the current block, for `-g`
+    fn mark_debug_loc(&mut self, span: Span) {
+        let Some(loc) = self.source_loc(span) else { return };
+        let key = (self.current_block, loc.file.clone(), loc.line);
+        if self.last_debug_loc.as_ref() != Some(&key) {
+            self.last_debug_loc = Some(key);
+            self.emit_current(Instruction::DebugLoc(loc));
+        }
+    }
+
+    /// Remember where the current block's code starts, for coverage
+    fn note_span(&mut self, span: Span) {
+        if self.coverage.is_some() {
+            self.block_spans.entry(self.current_block.0).or_insert(span);
+        }
+    }
+
+    /// Insert block counters into a finished function when `--coverage` is on.
+    /// Blocks without code of their own are attributed to the function.
+    fn instrument_coverage(&mut self, func: &mut IRFunction, fn_span: Span) {
+        if let Some(coverage) = &mut self.coverage {
+            let spans = &self.block_spans;
+            coverage.instrument(func, |block| spans.get(&block).copied().unwrap_or(fn_span));
+        }
+    }
+
+    fn add_block(&mut self, label: &str) -> BlockId {
+        if let Some(ref mut func) = self.current_fn {
+            func.add_block(label)
+        } else {
+            BlockId(0)
+        }
+    }
+
+    fn emit(&mut self, func: &mut IRFunction, inst: Instruction) {
+        if let Some(block) = func.get_block_mut(self.current_block) {
+            block.push(inst);
+        }
+    }
+
+    fn emit_current(&mut self, mut inst: Instruction) {
+        if let Some(ref mut func) = self.current_fn {
+            // @volatile functions make every memory access volatile (for MMIO)
+            if func.volatile {
+                if let Instruction::Load { volatile, .. } | Instruction::Store { volatile, .. } = &mut inst {
+                    *volatile = true;
+                }
+            }
+            if let Some(block) = func.get_block_mut(self.current_block) {
+                block.push(inst);
+            }
+        }
+    }
+    
+    /// Whether an expression evaluates to a `*volatile T` pointer
+    fn is_volatile_ptr(&self, expr: &ast::Expr) -> bool {
+        match expr {
+            Expr::Ident(ident) => self.volatile_locals.contains(&ident.name),
+            Expr::Cast { ty, .. } => matches!(ty, AstType::Volatile(..)),
+            _ => false,
+        }
+    }
+
+    /// Whether a field access base (`p.f` or `(*p).f`) goes through a volatile pointer
+    fn is_volatile_base(&self, base: &ast::Expr) -> bool {
+        match base {
+            Expr::Deref { expr, .. } => self.is_volatile_ptr(expr),
+            _ => self.is_volatile_ptr(base),
+        }
+    }
+
+    fn emit_current_with_type(&mut self, inst: Instruction, ty: IRType) {
+        if let Instruction::Assign { dest, .. } | 
+               Instruction::BinOp { dest, .. } | 
+               Instruction::CheckedBinOp { dest, .. } | 
+               Instruction::UnaryOp { dest, .. } | 
+               Instruction::Call { dest: Some(dest), .. } | 
+               Instruction::Alloca { dest, .. } | 
+               Instruction::Load { dest, .. } | 
+               Instruction::GetElementPtr { dest, .. } | 
+               Instruction::Cast { dest, .. } |
+               Instruction::Phi { dest, .. } = &inst {
+            self.reg_types.insert(*dest, ty);
+        }
+        self.emit_current(inst);
+    }
+    
+    /// Check if an IR type is an integer type
+    fn is_integer_type(ty: &IRType) -> bool {
+        matches!(ty, IRType::I8 | IRType::I16 | IRType::I32 | IRType::I64 |
+                     IRType::U8 | IRType::U16 | IRType::U32 | IRType::U64)
+    }
+    
+    fn get_value_type(&self, val: &Value) -> Option<IRType> {
+        match val {
+            Value::Register(reg) => self.reg_types.get(reg).cloned(),
+            Value::Parameter(idx) => {
+                if let Some(func) = &self.current_fn {
+                     if *idx < func.params.len() {
+                         return Some(func.params[*idx].1.clone());
+                     }
+                }
+                None
+            },
+            Value::Constant(c) => Some(match c {
+                Constant::Int(_) => IRType::I64,
+                Constant::Float(_) => IRType::F64,
+                Constant::Bool(_) => IRType::Bool,
+                Constant::String(_) => IRType::Ptr(Box::new(IRType::U8)),
+                Constant::Str(_) => IRType::Str,
+                Constant::Null => IRType::Ptr(Box::new(IRType::Void)),
+            }),
+            Value::Global(name) => match self.module.global(name) {
+                Some(global) => Some(IRType::Ptr(Box::new(global.ty.clone()))),
+                None => Some(IRType::Ptr(Box::new(IRType::Void))), // Unknown global
+            },
+            Value::Unit => Some(IRType::Void),
+        }
+    }
+
+    fn set_terminator_current(&mut self, term: Terminator) {
+        if let Some(ref mut func) = self.current_fn {
+            if let Some(block) = func.get_block_mut(self.current_block) {
+                block.set_terminator(term);
+            }
+        }
+    }
+
+    fn get_current_terminator(&self) -> Option<&Terminator> {
+        if let Some(ref func) = self.current_fn {
+            if let Some(block) = func.blocks.get(self.current_block.0) {
+                return block.terminator.as_ref();
+            }
+        }
+        None
+    }
+    
+    fn ast_binop_to_ir(&self, op: ast::BinOp) -> IRBinOp {
+        match op {
+            ast::BinOp::Add => IRBinOp::Add,
+            ast::BinOp::Sub => IRBinOp::Sub,
+            ast::BinOp::Mul => IRBinOp::Mul,
+            ast::BinOp::Div => IRBinOp::Div,
+            ast::BinOp::Mod => IRBinOp::Mod,
+            ast::BinOp::Eq => IRBinOp::Eq,
+            ast::BinOp::Ne => IRBinOp::Ne,
+            ast::BinOp::Lt => IRBinOp::Lt,
+            ast::BinOp::Le => IRBinOp::Le,
+            ast::BinOp::Gt => IRBinOp::Gt,
+            ast::BinOp::Ge => IRBinOp::Ge,
+            ast::BinOp::And => IRBinOp::And,
+            ast::BinOp::Or => IRBinOp::Or,
+            ast::BinOp::BitXor => IRBinOp::Xor,
+            ast::BinOp::Shl => IRBinOp::Shl,
+            ast::BinOp::Shr => IRBinOp::Shr,
+            ast::BinOp::BitAnd => IRBinOp::And,
+            ast::BinOp::BitOr => IRBinOp::Or,
+            ast::BinOp::Assign 
+            | ast::BinOp::AddAssign 
+            | ast::BinOp::SubAssign
+            | ast::BinOp::MulAssign
+            | ast::BinOp::DivAssign => panic!("Assignment should be handled separately"),
+        }
+    }
+
+    /// Try to evaluate a const expression to an integer value at compile time
+    fn try_eval_const_expr(&self, expr: &ast::Expr) -> Option<i64> {
+        match expr {
+            ast::Expr::Literal(lit) => {
+                match lit {
+                    ast::Literal::Int(n, _) => Some(*n),
+                    ast::Literal::Bool(b, _) => Some(if *b { 1 } else { 0 }),
+                    _ => None,
+                }
+            }
+            ast::Expr::Binary { op, left, right, .. } => {
+                let l = self.try_eval_const_expr(left)?;
+                let r = self.try_eval_const_expr(right)?;
+                Some(match op {
                     ast::BinOp::Add => l + r,
                     ast::BinOp::Sub => l - r,
                     ast::BinOp::Mul => l * r,
@@ -2018,6 +4962,94 @@ impl IRGenerator {
         }
     }
 
+    /// Layout of an AST type as the backends will lay it out
+    fn layout_of_ast_type(&self, ty: &AstType) -> Option<Layout> {
+        match ty {
+            // Arrays are lowered to pointers in IR, but size_of reports the full array
+            AstType::Array { elem, size, .. } => {
+                Some(Layout::array(self.layout_of_ast_type(elem)?, *size))
+            }
+            AstType::Tuple(elems, _) if !elems.is_empty() => {
+                let fields = elems.iter()
+                    .map(|e| self.layout_of_ast_type(e))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(layout_struct(&fields, false).layout())
+            }
+            AstType::Named(name, _) if self.is_declared_type(name) => {
+                self.module.layout_of(&IRType::Struct(name.clone()))
+            }
+            _ => self.module.layout_of(&self.ast_type_to_ir(ty)),
+        }
+    }
+
+    /// Check whether a struct or enum with this name has been generated
+    fn is_declared_type(&self, name: &str) -> bool {
+        self.struct_defs.contains_key(name) || self.module.enums.iter().any(|e| e.name == name)
+    }
+
+    /// Lower `Self` / `Self::Assoc` using the current impl block's bindings.
+    /// Unbound associated types are erased like other generic parameters.
+    fn substitute_self_type(&self, name: &str) -> IRType {
+        let Some(target) = &self.current_impl else {
+            return IRType::I64;
+        };
+        match name.strip_prefix("Self::") {
+            Some(assoc) => match self.assoc_types.get(&(target.clone(), assoc.to_string())) {
+                Some(concrete) => self.ast_type_to_ir(concrete),
+                None => IRType::I64,
+            },
+            None => IRType::Struct(target.clone()),
+        }
+    }
+
+    /// `ast_type_to_ir`, also declaring the struct of every tuple type in `ty`
+    fn lower_type(&mut self, ty: &AstType) -> IRType {
+        match ty {
+            AstType::Tuple(elements, _) if !elements.is_empty() => {
+                let elements = elements.iter().map(|e| self.lower_type(e)).collect();
+                return self.define_tuple(elements);
+            }
+            AstType::Pointer(inner, _) | AstType::Volatile(inner, _) | AstType::Slice(inner, _)
+            | AstType::Array { elem: inner, .. } | AstType::Ref { inner, .. } => {
+                self.lower_type(inner);
+            }
+            AstType::Generic(_, args, _) => {
+                for arg in args {
+                    self.lower_type(arg);
+                }
+            }
+            _ => {}
+        }
+        self.ast_type_to_ir(ty)
+    }
+
+    /// Declare the struct a tuple of `elements` is laid out as, if not yet
+    fn define_tuple(&mut self, elements: Vec<IRType>) -> IRType {
+        let name = mangle::tuple_type_name(&elements);
+        if !self.struct_defs.contains_key(&name) {
+            let fields: Vec<_> = elements.into_iter().enumerate()
+                .map(|(i, ty)| (mangle::tuple_field(i), ty))
+                .collect();
+            self.struct_defs.insert(name.clone(), fields.clone());
+            self.module.add_struct(&name, fields, crate::middle::ir::StructRepr::Default);
+        }
+        IRType::Struct(name)
+    }
+
+    /// Types of the parameters of a function; structs and tuples are passed
+    /// by pointer, like the values that hold them
+    fn lower_params(&mut self, params: &[ast::Param]) -> Vec<(String, IRType)> {
+        params.iter()
+            .map(|p| {
+                let ty = match self.lower_type(&p.ty) {
+                    IRType::Struct(name) => IRType::Ptr(Box::new(IRType::Struct(name))),
+                    ty => ty,
+                };
+                (p.name.name.clone(), ty)
+            })
+            .collect()
+    }
+
     fn ast_type_to_ir(&self, ty: &AstType) -> IRType {
         match ty {
             AstType::Named(name, _) => {
@@ -2025,14 +5057,17 @@ impl IRGenerator {
                     "i8" => IRType::I8,
                     "i16" => IRType::I16,
                     "i32" => IRType::I32,
-                    "i64" | "int" => IRType::I64,
+                    "i64" | "int" | "isize" => IRType::I64,
                     "u8" | "byte" => IRType::U8,
                     "u16" => IRType::U16,
-                    "u32" => IRType::U32,
-                    "u64" => IRType::U64,
+                    // A Unicode scalar value
+                    "u32" | "char" => IRType::U32,
+                    "u64" | "usize" => IRType::U64,
                     "f32" => IRType::F32,
                     "f64" | "float" => IRType::F64,
                     "bool" => IRType::Bool,
+                    // Borrowed string slice: data pointer and length
+                    "str" => IRType::Str,
                     // SIMD vector types
                     "f32x4" => IRType::Vector(Box::new(IRType::F32), 4),
                     "f32x8" => IRType::Vector(Box::new(IRType::F32), 8),
@@ -2043,6 +5078,10 @@ impl IRGenerator {
                     "i64x2" => IRType::Vector(Box::new(IRType::I64), 2),
                     "i64x4" => IRType::Vector(Box::new(IRType::I64), 4),
                     "void" | "()" => IRType::Void,
+                    // `Self` and `Self::Assoc` are substituted with the impl's concrete types
+                    s if s == "Self" || s.starts_with("Self::") => self.substitute_self_type(s),
+                    // Type parameters of the interface-bounded function being specialized
+                    s if self.type_args.contains_key(s) => self.ast_type_to_ir(&self.type_args[s]),
                     // Structs are NOT automatically wrapped as pointers
                     // The pointer wrapping happens at usage sites (function calls, etc.)
                     // But single uppercase letters are generic type params - use i64 (type erasure)
@@ -2050,6 +5089,9 @@ impl IRGenerator {
                         if s.len() == 1 && s.chars().next().map(|c| c.is_uppercase()).unwrap_or(false) {
                             // Generic type parameter like T, U, V - use i64 as type erasure
                             IRType::I64
+                        } else if self.module.enums.iter().any(|e| e.name == s) {
+                            // Variant constructors return enums on the heap
+                            IRType::Ptr(Box::new(IRType::Struct(s.to_string())))
                         } else {
                             // Struct type - NOT a pointer, just the struct itself
                             IRType::Struct(s.to_string())
@@ -2058,51 +5100,54 @@ impl IRGenerator {
                 }
 
             }
+            AstType::Generic(name, args, _) if name == "Vec" && args.len() == 1 => {
+                IRType::Vec(Box::new(self.ast_type_to_ir(&args[0])))
+            }
+            AstType::Generic(name, args, _) if name == "HashMap" && args.len() == 2 => {
+                IRType::Map(Box::new(self.ast_type_to_ir(&args[0])), Box::new(self.ast_type_to_ir(&args[1])))
+            }
+            // Instantiations of a generic enum share its erased layout
+            AstType::Generic(name, _, _) if self.generic_enums.contains(name) => {
+                IRType::Ptr(Box::new(IRType::Struct(name.clone())))
+            }
             AstType::Generic(name, args, _) => {
                 // Phase 11: Basic monomorphization stub
-                // Map Box<T> to Box for now (incorrect but compiles)
-                // Real impl need to mangle names: Box_i64
-                let mut mangled = name.clone();
-                for arg in args {
-                     if let AstType::Named(n, _) = arg {
-                         mangled.push_str("_");
-                         mangled.push_str(n);
-                     }
-                }
-                IRType::Struct(mangled)
+                // Box<i64> is laid out as the struct Box_i64
+                let arg_names: Vec<&str> = args.iter()
+                    .filter_map(|arg| match arg {
+                        AstType::Named(n, _) => Some(n.as_str()),
+                        _ => None,
+                    })
+                    .collect();
+                IRType::Struct(mangle::generic_type_name(name, &arg_names))
             }
             AstType::GenericWithArgs { name, args, .. } => {
                 // Const generics monomorphization
                 // Mangle name with both type and const args: Matrix_f32_3_3
-                let mut mangled = name.clone();
-                for arg in args {
-                    mangled.push_str("_");
-                    match arg {
-                        crate::frontend::ast::GenericArg::Type(ty) => {
-                            // Recursively get type name
-                            match ty {
-                                AstType::Named(n, _) => mangled.push_str(n),
-                                AstType::Pointer(_, _) => mangled.push_str("ptr"),
-                                _ => mangled.push_str("T"),
-                            }
-                        }
-                        crate::frontend::ast::GenericArg::Const(expr) => {
-                            // Evaluate const expression to integer
-                            if let Some(val) = self.try_eval_const_expr(expr) {
-                                mangled.push_str(&val.to_string());
-                            } else {
-                                mangled.push_str("N");
-                            }
-                        }
-                    }
-                }
-                IRType::Struct(mangled)
+                let arg_names: Vec<String> = args.iter()
+                    .map(|arg| match arg {
+                        crate::frontend::ast::GenericArg::Type(ty) => match ty {
+                            AstType::Named(n, _) => n.clone(),
+                            AstType::Pointer(_, _) => "ptr".to_string(),
+                            _ => "T".to_string(),
+                        },
+                        // Evaluate const expression to integer
+                        crate::frontend::ast::GenericArg::Const(expr) => self.try_eval_const_expr(expr)
+                            .map(|val| val.to_string())
+                            .unwrap_or_else(|| "N".to_string()),
+                    })
+                    .collect();
+                IRType::Struct(mangle::generic_type_name(name, &arg_names))
             }
             AstType::Pointer(inner, _) => IRType::Ptr(Box::new(self.ast_type_to_ir(inner))),
+            // Volatility is carried by the Load/Store instructions, not the pointer type
+            AstType::Volatile(inner, _) => IRType::Ptr(Box::new(self.ast_type_to_ir(inner))),
             AstType::Array { elem, size: _, .. } => {
                  // Array logic hack
                  IRType::Ptr(Box::new(self.ast_type_to_ir(elem))) 
             }
+            // Slices are passed as a pointer to their first element
+            AstType::Slice(elem, _) => IRType::Ptr(Box::new(self.ast_type_to_ir(elem))),
             AstType::Ref { inner, .. } => {
                 // References are implemented as pointers at the IR level
                 // But structs are already Ptr(Struct), so don't add extra level
@@ -2114,13 +5159,17 @@ impl IRGenerator {
                     IRType::Ptr(Box::new(inner_ty))
                 }
             }
+            AstType::Tuple(elements, _) if elements.is_empty() => IRType::Void, // Unit tuple ()
+            // Laid out as a struct, which `lower_type` declares
             AstType::Tuple(elements, _) => {
-                if elements.is_empty() {
-                    IRType::Void // Unit tuple ()
-                } else {
-                    IRType::Void // TODO: proper tuple support
-                }
+                let elements: Vec<IRType> = elements.iter().map(|e| self.ast_type_to_ir(e)).collect();
+                IRType::Struct(mangle::tuple_type_name(&elements))
             }
+            // Effects only matter to semantic analysis
+            AstType::Function { params, ret, .. } => IRType::Function {
+                params: params.iter().map(|p| self.ast_type_to_ir(p)).collect(),
+                ret: Box::new(self.ast_type_to_ir(ret)),
+            },
             AstType::Unit(_) => IRType::Void,
             _ => IRType::Void,
         }
@@ -2138,7 +5187,10 @@ mod tests {
         let mut parser = Parser::new(lexer);
         let program = parser.parse_program()?;
         let mut gen = IRGenerator::new("test");
-        gen.generate(&program)
+        let module = gen.generate(&program)?;
+        // Every module produced by the tests must also be well-formed
+        crate::middle::validate::validate_module(&module)?;
+        Ok(module)
     }
 
     #[test]
@@ -2168,4 +5220,183 @@ mod tests {
         // Should have entry, then, else, merge blocks
         assert!(module.functions[0].blocks.len() >= 3);
     }
+
+    #[test]
+    fn test_layout_builtins_fold_to_constants() {
+        // Function precedes the struct to exercise forward references
+        let module = generate(
+            "fn f() -> u64 { return size_of::<P>() + align_of::<P>() + offset_of(P, b) }\n\
+             struct P { a: u8, b: u64 }"
+        ).unwrap();
+        let layout = module.struct_layout("P").unwrap();
+        assert_eq!((layout.size, layout.align, layout.offsets.clone()), (16, 8, vec![0, 8]));
+
+        let insts = &module.functions[0].blocks[0].instructions;
+        let consts: Vec<i64> = insts.iter().flat_map(|inst| match inst {
+            Instruction::BinOp { left, right, .. } => vec![left.clone(), right.clone()],
+            _ => vec![],
+        }).filter_map(|v| match v {
+            Value::Constant(Constant::Int(n)) => Some(n),
+            _ => None,
+        }).collect();
+        assert_eq!(consts, vec![16, 8, 8]);
+    }
+
+    #[test]
+    fn test_static_globals() {
+        let module = generate(
+            "fn bump() { COUNTER = COUNTER + 1 }\n\
+             static mut COUNTER: i64 = 4 * 10\n\
+             extern \"C\" { static errno: i32 }"
+        ).unwrap();
+        let counter = module.global("COUNTER").unwrap();
+        assert!(counter.is_mut && !counter.is_extern);
+        assert!(matches!(counter.init, Some(Constant::Int(40))));
+        assert!(module.global("errno").unwrap().is_extern);
+
+        let insts = &module.functions[0].blocks[0].instructions;
+        assert!(insts.iter().any(|i| matches!(i, Instruction::Load { ptr: Value::Global(n), .. } if n == "COUNTER")));
+        assert!(insts.iter().any(|i| matches!(i, Instruction::Store { ptr: Value::Global(n), .. } if n == "COUNTER")));
+    }
+
+    #[test]
+    fn test_associated_type_substitution() {
+        let module = generate(
+            "interface Iterator {\n    type Item\n    fn next(self: *Self) -> Self::Item;\n}\nstruct Counter { count: i64 }\nimpl Iterator for Counter {\n    type Item = u32\n    fn next(self: *Counter) -> Self::Item { return 1 }\n}"
+        ).unwrap();
+        let next = module.functions.iter().find(|f| f.name == "Counter__next").unwrap();
+        assert_eq!(next.ret_type, IRType::U32);
+    }
+
+    #[test]
+    fn test_symbol_mangling() {
+        let module = generate(
+            "struct Point { x: i64 }\nstruct Rect { w: i64 }\n\
+             impl Point { fn new() -> i64 { return 1 } }\n\
+             impl Rect { fn new() -> i64 { return 2 } }\n\
+             mod a { fn helper() -> i64 { return 3 } }\n\
+             mod b { fn helper() -> i64 { return 4 } }\n\
+             #[extern_c]\npub fn exported() -> i64 { return 5 }\n\
+             pub fn api() -> i64 { return a::helper() }\n\
+             fn main() -> i64 { return Point::new() + Rect::new() }"
+        ).unwrap();
+        let names: Vec<&str> = module.functions.iter().map(|f| f.name.as_str()).collect();
+        for expected in ["Point__new", "Rect__new", "a__helper", "b__helper", "exported", "test__api", "main"] {
+            assert!(names.contains(&expected), "missing {} in {:?}", expected, names);
+        }
+
+        let calls = |name: &str| -> Vec<String> {
+            module.functions.iter().find(|f| f.name == name).unwrap()
+                .blocks.iter().flat_map(|b| &b.instructions)
+                .filter_map(|i| match i {
+                    Instruction::Call { func, .. } => Some(func.clone()),
+                    _ => None,
+                }).collect()
+        };
+        assert_eq!(calls("main"), vec!["Point__new", "Rect__new"]);
+        assert_eq!(calls("test__api"), vec!["a__helper"]);
+    }
+
+    #[test]
+    fn test_no_mangle() {
+        let module = generate(
+            "mod boot { #[no_mangle]\npub fn kernel_entry() -> i64 { return 1 } }\n\
+             #[no_mangle]\npub fn api() -> i64 { return boot::kernel_entry() }\n\
+             #[no_mangle]\nstatic mut TICKS: i64 = 0;\n\
+             fn main() -> i64 { return api() }"
+        ).unwrap();
+        for name in ["kernel_entry", "api"] {
+            let func = module.functions.iter().find(|f| f.name == name).unwrap();
+            assert!(func.no_mangle && !func.extern_c);
+        }
+        assert!(module.globals.iter().any(|g| g.name == "TICKS"));
+        let api = module.functions.iter().find(|f| f.name == "api").unwrap();
+        assert!(api.blocks.iter().flat_map(|b| &b.instructions)
+            .any(|i| matches!(i, Instruction::Call { func, .. } if func == "kernel_entry")));
+    }
+
+    #[test]
+    fn test_prelude_enums() {
+        let module = generate(
+            "fn parse(n: i64) -> Result<i64, i64> { if n < 0 { return Err(n) }\n return Ok(n) }\n\
+             fn next(n: i64) -> Result<i64, i64> { let v: i64 = parse(n)?\n return Ok(v + 1) }"
+        ).unwrap();
+        // Only the enums the program uses are declared
+        assert!(module.enums.iter().any(|e| e.name == "Result"));
+        assert!(!module.enums.iter().any(|e| e.name == "Option"));
+        // `?` returns an `Err` early
+        let next = module.functions.iter().find(|f| f.name == "next").unwrap();
+        assert!(next.blocks.iter().any(|b| b.label == "try_return"));
+        assert!(generate("fn main() -> i64 { return 0 }").unwrap().enums.is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_symbol() {
+        let err = generate(
+            "struct Point { x: i64 }\nimpl Point { fn new() -> i64 { return 1 } }\nfn Point__new() -> i64 { return 2 }"
+        ).unwrap_err();
+        assert!(matches!(err, Error::DuplicateSymbol { ref symbol, .. } if symbol == "Point__new"), "{:?}", err);
+    }
+
+    #[test]
+    fn test_const_generic_specialization() {
+        let module = generate(
+            "fn scaled<const N: usize>(x: i64) -> i64 { return x * N }\n\
+             fn unused<const N: usize>() -> i64 { return N }\n\
+             fn main() -> i64 { return scaled::<4>(1) + scaled::<4>(2) + scaled::<8>(3) }"
+        ).unwrap();
+        let names: Vec<&str> = module.functions.iter().map(|f| f.name.as_str()).collect();
+        // Templates are never emitted, each distinct instantiation exactly once
+        assert_eq!(names, vec!["main", "scaled_4", "scaled_8"]);
+        let scaled = &module.functions[1];
+        assert!(scaled.blocks[0].instructions.iter().any(|inst| matches!(
+            inst,
+            Instruction::BinOp { right: Value::Constant(Constant::Int(4)), .. }
+        )), "{:?}", scaled);
+    }
+
+    #[test]
+    fn test_new_and_delete() {
+        let source = "struct Point { x: i64, y: i64 }\nfn f() { let p: *Point = new Point { x: 1, y: 2 }; delete(p) }";
+        let module = generate(source).unwrap();
+        let insts: Vec<&Instruction> = module.functions[0].blocks.iter().flat_map(|b| &b.instructions).collect();
+        // The size is folded from the layout and the literal fills the allocation
+        assert!(insts.iter().any(|i| matches!(i,
+            Instruction::Call { func, args, .. } if func == "malloc" && args[..] == [Value::Constant(Constant::Int(16))]
+        )));
+        assert!(!insts.iter().any(|i| matches!(i, Instruction::Alloca { .. })));
+        assert!(insts.iter().any(|i| matches!(i, Instruction::Call { func, .. } if func == "free")));
+        assert!(!insts.iter().any(|i| matches!(i, Instruction::Cast { value: Value::Constant(Constant::Int(DELETED_POINTER)), .. })));
+
+        // Debug builds poison the deleted variable
+        let program = Parser::new(Lexer::new(source, 0)).parse_program().unwrap();
+        let module = IRGenerator::new("test").with_debug_assertions(true).generate(&program).unwrap();
+        assert!(module.functions[0].blocks.iter().flat_map(|b| &b.instructions).any(|i| matches!(i,
+            Instruction::Cast { value: Value::Constant(Constant::Int(DELETED_POINTER)), .. }
+        )));
+    }
+
+    #[test]
+    fn test_string_concat() {
+        let module = generate(
+            "fn join(a: &String, b: str) -> String effect[alloc] { return a + b }\n\
+             fn main() -> i64 { return 0 }"
+        ).unwrap();
+        let names: Vec<&str> = module.functions.iter().map(|f| f.name.as_str()).collect();
+        // Only the methods `+` reaches are kept
+        for method in ["String__new", "String__as_str", "String__push_str", "String__reserve"] {
+            assert!(names.contains(&method), "{:?}", names);
+        }
+        assert!(!names.contains(&"String__push_char"), "{:?}", names);
+        let join = module.functions.iter().find(|f| f.name == "join").unwrap();
+        let pushes = join.blocks.iter().flat_map(|b| &b.instructions)
+            .filter(|i| matches!(i, Instruction::Call { func, .. } if func == "String__push_str"))
+            .count();
+        assert_eq!(pushes, 2);
+
+        // Programs without `String` lower as before
+        let module = generate("fn main() -> i64 { return 0 }").unwrap();
+        assert_eq!(module.functions.len(), 1);
+        assert!(module.structs.is_empty());
+    }
 }
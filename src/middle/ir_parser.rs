@@ -0,0 +1,1045 @@
+//! IR Parser - read textual Aether IR back into an `IRModule`
+//!
+//! Accepts exactly what `ir_printer` emits, one item per line:
+//!
+//! ```text
+//! module demo
+//! #![no_std]
+//! #[repr(C)]
+//! struct Point { x: i64, y: i64 }
+//! enum Shape { Empty, Circle(f64) }
+//! extern fn puts(s: *u8) -> i32
+//! global mut @COUNTER: i64 = 0
+//! #[requires("n > 0")]
+//! pub fn double(n: i64) -> i64 {
+//!   bb0 entry:
+//!     %0 = mul arg0 2
+//!     ret %0
+//! }
+//! ```
+//!
+//! Everything after `;` on a line is a comment, except inside an array type `[T; N]`.
+
+use crate::middle::ir::*;
+use crate::utils::{Error, Result};
+
+/// Parse a module printed by `ir_printer::print_ir`
+pub fn parse_ir(text: &str) -> Result<IRModule> {
+    IRParser::new(text).parse_module()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    /// `%N`
+    Reg(usize),
+    /// `@name`
+    Global(String),
+    Arrow,
+    Punct(char),
+}
+
+/// Split one line into tokens, stopping at a `;` comment (`;` inside `[...]` is an array type)
+fn tokenize(line: &str) -> std::result::Result<Vec<Token>, String> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let mut brackets = 0usize;
+    let ident_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == ';' && brackets == 0 {
+            break;
+        } else if c == '-' && chars.get(i + 1) == Some(&'>') {
+            tokens.push(Token::Arrow);
+            i += 2;
+        } else if c.is_ascii_digit() || (c == '-' && chars.get(i + 1).is_some_and(|n| n.is_ascii_digit() || *n == 'i')) {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.'
+                || ((chars[i] == '-' || chars[i] == '+') && chars[i - 1] == 'e')) {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            if text == "-inf" {
+                tokens.push(Token::Float(f64::NEG_INFINITY));
+            } else if text.contains(['.', 'e']) {
+                tokens.push(Token::Float(text.parse().map_err(|_| format!("invalid float '{}'", text))?));
+            } else {
+                tokens.push(Token::Int(text.parse().map_err(|_| format!("invalid integer '{}'", text))?));
+            }
+        } else if ident_char(c) {
+            let start = i;
+            while i < chars.len() && ident_char(chars[i]) {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if c == '%' {
+            let start = i + 1;
+            i += 1;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            tokens.push(Token::Reg(text.parse().map_err(|_| "expected a register number after '%'".to_string())?));
+        } else if c == '@' {
+            let start = i + 1;
+            i += 1;
+            while i < chars.len() && ident_char(chars[i]) {
+                i += 1;
+            }
+            tokens.push(Token::Global(chars[start..i].iter().collect()));
+        } else if c == '"' {
+            let (s, next) = unescape(&chars, i + 1)?;
+            tokens.push(Token::Str(s));
+            i = next;
+        } else {
+            match c {
+                '[' => brackets += 1,
+                ']' => brackets = brackets.saturating_sub(1),
+                _ => {}
+            }
+            tokens.push(Token::Punct(c));
+            i += 1;
+        }
+    }
+    Ok(tokens)
+}
+
+/// Read a string literal body in Rust `{:?}` syntax, returning it and the index after the closing quote
+fn unescape(chars: &[char], mut i: usize) -> std::result::Result<(String, usize), String> {
+    let mut out = String::new();
+    while i < chars.len() {
+        match chars[i] {
+            '"' => return Ok((out, i + 1)),
+            '\\' => {
+                let esc = *chars.get(i + 1).ok_or("unterminated escape")?;
+                i += 2;
+                match esc {
+                    'n' => out.push('\n'),
+                    'r' => out.push('\r'),
+                    't' => out.push('\t'),
+                    '0' => out.push('\0'),
+                    '\\' => out.push('\\'),
+                    '"' => out.push('"'),
+                    '\'' => out.push('\''),
+                    'u' => {
+                        if chars.get(i) != Some(&'{') {
+                            return Err("expected '{' in unicode escape".to_string());
+                        }
+                        let end = chars[i..].iter().position(|&c| c == '}').ok_or("unterminated unicode escape")? + i;
+                        let hex: String = chars[i + 1..end].iter().collect();
+                        let code = u32::from_str_radix(&hex, 16).map_err(|_| format!("invalid unicode escape '{}'", hex))?;
+                        out.push(char::from_u32(code).ok_or_else(|| format!("invalid code point {:x}", code))?);
+                        i = end + 1;
+                    }
+                    other => return Err(format!("unknown escape '\\{}'", other)),
+                }
+            }
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    Err("unterminated string".to_string())
+}
+
+/// Name, fields and `#[bits(N)]` widths of a parsed struct
+type StructBody = (String, Vec<(String, IRType)>, Vec<Option<u32>>);
+
+/// Function attributes collected from `#[...]` lines until the next item
+#[derive(Default)]
+struct PendingAttrs {
+    repr: Option<StructRepr>,
+    flags: Vec<String>,
+    sret: Option<IRType>,
+    entry: Option<BlockId>,
+    contracts: IRContracts,
+    loc: Option<SourceLoc>,
+    inline: InlineHint,
+}
+
+struct IRParser<'a> {
+    lines: Vec<(usize, &'a str)>,
+    next_line: usize,
+    /// Tokens of the current line
+    tokens: Vec<Token>,
+    pos: usize,
+    line: usize,
+}
+
+impl<'a> IRParser<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            lines: text.lines().enumerate().map(|(n, l)| (n + 1, l)).collect(),
+            next_line: 0,
+            tokens: Vec::new(),
+            pos: 0,
+            line: 0,
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error::IrParse { line: self.line, message: message.into() }
+    }
+
+    /// Advance to the next line with tokens; false at end of input
+    fn next_line(&mut self) -> Result<bool> {
+        while self.next_line < self.lines.len() {
+            let (n, text) = self.lines[self.next_line];
+            self.next_line += 1;
+            self.line = n;
+            self.tokens = tokenize(text).map_err(|e| self.error(e))?;
+            self.pos = 0;
+            if !self.tokens.is_empty() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset)
+    }
+
+    fn bump(&mut self) -> Result<Token> {
+        let tok = self.tokens.get(self.pos).cloned().ok_or_else(|| self.error("unexpected end of line"))?;
+        self.pos += 1;
+        Ok(tok)
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    fn expect_end(&self) -> Result<()> {
+        match self.peek() {
+            None => Ok(()),
+            Some(tok) => Err(self.error(format!("unexpected {:?} at end of line", tok))),
+        }
+    }
+
+    fn eat_punct(&mut self, c: char) -> bool {
+        if self.peek() == Some(&Token::Punct(c)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_punct(&mut self, c: char) -> Result<()> {
+        if self.eat_punct(c) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}', found {:?}", c, self.peek())))
+        }
+    }
+
+    fn eat_keyword(&mut self, kw: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Ident(s)) if s == kw) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_keyword(&mut self, kw: &str) -> Result<()> {
+        if self.eat_keyword(kw) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}', found {:?}", kw, self.peek())))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        match self.bump()? {
+            Token::Ident(s) => Ok(s),
+            tok => Err(self.error(format!("expected a name, found {:?}", tok))),
+        }
+    }
+
+    fn usize(&mut self) -> Result<usize> {
+        match self.bump()? {
+            Token::Int(n) if n >= 0 => Ok(n as usize),
+            tok => Err(self.error(format!("expected a count, found {:?}", tok))),
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        match self.bump()? {
+            Token::Str(s) => Ok(s),
+            tok => Err(self.error(format!("expected a string, found {:?}", tok))),
+        }
+    }
+
+    fn register(&mut self) -> Result<Register> {
+        match self.bump()? {
+            Token::Reg(n) => Ok(Register(n)),
+            tok => Err(self.error(format!("expected a register, found {:?}", tok))),
+        }
+    }
+
+    /// `bbN`
+    fn block_ref(&mut self) -> Result<BlockId> {
+        let name = self.ident()?;
+        name.strip_prefix("bb")
+            .and_then(|n| n.parse().ok())
+            .map(BlockId)
+            .ok_or_else(|| self.error(format!("expected a block reference, found '{}'", name)))
+    }
+
+    // ==================== Items ====================
+
+    fn parse_module(&mut self) -> Result<IRModule> {
+        if !self.next_line()? {
+            return Err(self.error("empty input"));
+        }
+        self.expect_keyword("module")?;
+        let name = self.ident()?;
+        self.expect_end()?;
+        let mut module = IRModule::new(&name);
+        let mut attrs = PendingAttrs::default();
+
+        while self.next_line()? {
+            if self.eat_punct('#') {
+                if self.eat_punct('!') {
+                    self.expect_punct('[')?;
+                    match self.ident()?.as_str() {
+                        "no_std" => module.no_std = true,
+                        "no_main" => module.no_main = true,
+                        other => return Err(self.error(format!("unknown module attribute '{}'", other))),
+                    }
+                    self.expect_punct(']')?;
+                } else {
+                    self.parse_attribute(&mut attrs)?;
+                }
+                self.expect_end()?;
+                continue;
+            }
+
+            let mut keyword = self.ident()?;
+            let is_pub = keyword == "pub";
+            if is_pub {
+                keyword = self.ident()?;
+            }
+            match keyword.as_str() {
+                "struct" | "union" => {
+                    let (name, fields, bits) = self.parse_struct_body()?;
+                    let repr = attrs.repr.take().unwrap_or_default();
+                    if keyword == "union" {
+                        module.add_union(&name, fields, repr);
+                    } else {
+                        module.add_struct(&name, fields, repr);
+                    }
+                    if let Some(added) = module.structs.last_mut() {
+                        added.is_pub = is_pub;
+                        added.bits = bits;
+                    }
+                }
+                "enum" => module.enums.push(self.parse_enum()?),
+                "extern" => {
+                    if self.eat_keyword("fn") {
+                        let name = self.ident()?;
+                        let params = self.parse_params()?;
+                        self.expect_arrow()?;
+                        let ret_type = self.parse_type()?;
+                        module.externs.push(IRExtern { name, params, ret_type });
+                    } else {
+                        self.expect_keyword("global")?;
+                        module.globals.push(self.parse_global(true)?);
+                    }
+                }
+                "global" => module.globals.push(self.parse_global(false)?),
+                "fn" => {
+                    let mut func = self.parse_function(std::mem::take(&mut attrs))?;
+                    func.is_pub = is_pub;
+                    module.functions.push(func);
+                    continue;
+                }
+                other => return Err(self.error(format!("unexpected '{}'", other))),
+            }
+            self.expect_end()?;
+        }
+        Ok(module)
+    }
+
+    /// `#[name]` or `#[name(arg)]` after the `#`
+    fn parse_attribute(&mut self, attrs: &mut PendingAttrs) -> Result<()> {
+        self.expect_punct('[')?;
+        let name = self.ident()?;
+        match name.as_str() {
+            "simd" | "naked" | "interrupt" | "volatile" | "extern_c" | "no_mangle" | "cold" | "hot" => attrs.flags.push(name),
+            "inline" => {
+                attrs.inline = InlineHint::Always;
+                if self.eat_punct('(') {
+                    match self.ident()?.as_str() {
+                        "never" => attrs.inline = InlineHint::Never,
+                        other => return Err(self.error(format!("unknown inline hint '{}'", other))),
+                    }
+                    self.expect_punct(')')?;
+                }
+            }
+            "repr" => {
+                self.expect_punct('(')?;
+                attrs.repr = Some(match self.ident()?.as_str() {
+                    "C" => StructRepr::C,
+                    "packed" => StructRepr::Packed,
+                    "transparent" => StructRepr::Transparent,
+                    other => return Err(self.error(format!("unknown repr '{}'", other))),
+                });
+                self.expect_punct(')')?;
+            }
+            "sret" => {
+                self.expect_punct('(')?;
+                attrs.sret = Some(self.parse_type()?);
+                self.expect_punct(')')?;
+            }
+            "entry" => {
+                self.expect_punct('(')?;
+                attrs.entry = Some(self.block_ref()?);
+                self.expect_punct(')')?;
+            }
+            "loc" => {
+                self.expect_punct('(')?;
+                let file = self.string()?;
+                self.expect_punct(',')?;
+                let line = self.usize()?;
+                self.expect_punct(',')?;
+                let col = self.usize()?;
+                attrs.loc = Some(SourceLoc { file, line, col });
+                self.expect_punct(')')?;
+            }
+            "requires" | "ensures" | "effects" => {
+                self.expect_punct('(')?;
+                let text = self.string()?;
+                self.expect_punct(')')?;
+                match name.as_str() {
+                    "requires" => attrs.contracts.requires.push(text),
+                    "ensures" => attrs.contracts.ensures.push(text),
+                    _ => attrs.contracts.effects.push(text),
+                }
+            }
+            other => return Err(self.error(format!("unknown attribute '{}'", other))),
+        }
+        self.expect_punct(']')
+    }
+
+    fn expect_arrow(&mut self) -> Result<()> {
+        match self.bump()? {
+            Token::Arrow => Ok(()),
+            tok => Err(self.error(format!("expected '->', found {:?}", tok))),
+        }
+    }
+
+    /// `Name { field: T, bits: U : N, ... }`, giving the name, the fields
+    /// and their bitfield widths
+    fn parse_struct_body(&mut self) -> Result<StructBody> {
+        let name = self.ident()?;
+        self.expect_punct('{')?;
+        let mut fields = Vec::new();
+        let mut bits = Vec::new();
+        while !self.eat_punct('}') {
+            if !fields.is_empty() {
+                self.expect_punct(',')?;
+            }
+            let field = self.ident()?;
+            self.expect_punct(':')?;
+            fields.push((field, self.parse_type()?));
+            // `name: ty : N` is a bitfield of width N
+            bits.push(if self.eat_punct(':') { Some(self.usize()? as u32) } else { None });
+        }
+        if bits.iter().all(Option::is_none) {
+            bits.clear();
+        }
+        Ok((name, fields, bits))
+    }
+
+    /// `Name[: tag] { A, B(T, U) = n }`
+    fn parse_enum(&mut self) -> Result<IREnum> {
+        let name = self.ident()?;
+        let tag = if self.eat_punct(':') { self.parse_type()? } else { IRType::I32 };
+        self.expect_punct('{')?;
+        let mut variants: Vec<IRVariant> = Vec::new();
+        while !self.eat_punct('}') {
+            if !variants.is_empty() {
+                self.expect_punct(',')?;
+            }
+            let variant = self.ident()?;
+            let mut fields = Vec::new();
+            if self.eat_punct('(') {
+                while !self.eat_punct(')') {
+                    if !fields.is_empty() {
+                        self.expect_punct(',')?;
+                    }
+                    fields.push(self.parse_type()?);
+                }
+            }
+            let discriminant = if self.eat_punct('=') {
+                match self.bump()? {
+                    Token::Int(n) => n,
+                    tok => return Err(self.error(format!("expected a discriminant, found {:?}", tok))),
+                }
+            } else {
+                variants.last().map_or(0, |v| v.discriminant.wrapping_add(1))
+            };
+            variants.push(IRVariant { name: variant, fields, discriminant });
+        }
+        Ok(IREnum { name, variants, tag })
+    }
+
+    /// `[mut] @name: T [= constant]` after `global`
+    fn parse_global(&mut self, is_extern: bool) -> Result<IRGlobal> {
+        let is_mut = self.eat_keyword("mut");
+        let name = match self.bump()? {
+            Token::Global(name) => name,
+            tok => return Err(self.error(format!("expected '@name', found {:?}", tok))),
+        };
+        self.expect_punct(':')?;
+        let ty = self.parse_type()?;
+        let init = if self.eat_punct('=') {
+            match self.parse_value()? {
+                Value::Constant(c) => Some(c),
+                other => return Err(self.error(format!("global initializer must be a constant, found {}", other))),
+            }
+        } else {
+            None
+        };
+        Ok(IRGlobal { name, ty, init, is_mut, is_extern })
+    }
+
+    /// `(name: T, ...)`
+    fn parse_params(&mut self) -> Result<Vec<(String, IRType)>> {
+        self.expect_punct('(')?;
+        let mut params = Vec::new();
+        while !self.eat_punct(')') {
+            if !params.is_empty() {
+                self.expect_punct(',')?;
+            }
+            let name = self.ident()?;
+            self.expect_punct(':')?;
+            params.push((name, self.parse_type()?));
+        }
+        Ok(params)
+    }
+
+    fn parse_type(&mut self) -> Result<IRType> {
+        if self.eat_punct('*') {
+            return Ok(IRType::Ptr(Box::new(self.parse_type()?)));
+        }
+        if self.eat_punct('[') {
+            let elem = self.parse_type()?;
+            self.expect_punct(';')?;
+            let count = self.usize()?;
+            self.expect_punct(']')?;
+            return Ok(IRType::Array(Box::new(elem), count));
+        }
+        let name = self.ident()?;
+        if name == "fn" {
+            self.expect_punct('(')?;
+            let mut params = Vec::new();
+            while !self.eat_punct(')') {
+                if !params.is_empty() {
+                    self.expect_punct(',')?;
+                }
+                params.push(self.parse_type()?);
+            }
+            self.expect_arrow()?;
+            let ret = self.parse_type()?;
+            return Ok(IRType::Function { params, ret: Box::new(ret) });
+        }
+        if name == "str" {
+            return Ok(IRType::Str);
+        }
+        if name == "vec" {
+            self.expect_punct('<')?;
+            let elem = self.parse_type()?;
+            self.expect_punct('>')?;
+            return Ok(IRType::Vec(Box::new(elem)));
+        }
+        if name == "map" {
+            self.expect_punct('<')?;
+            let key = self.parse_type()?;
+            self.expect_punct(',')?;
+            let value = self.parse_type()?;
+            self.expect_punct('>')?;
+            return Ok(IRType::Map(Box::new(key), Box::new(value)));
+        }
+        if let Some(ty) = scalar_type(&name) {
+            return Ok(ty);
+        }
+        // SIMD vectors print as `<elem>x<lanes>`, e.g. f32x4
+        if let Some((elem, lanes)) = name.split_once('x') {
+            if let (Some(elem), Ok(lanes)) = (scalar_type(elem), lanes.parse()) {
+                return Ok(IRType::Vector(Box::new(elem), lanes));
+            }
+        }
+        Ok(IRType::Struct(name))
+    }
+
+    fn parse_value(&mut self) -> Result<Value> {
+        match self.bump()? {
+            Token::Reg(n) => Ok(Value::Register(Register(n))),
+            Token::Global(name) => Ok(Value::Global(name)),
+            Token::Int(n) => Ok(Value::Constant(Constant::Int(n))),
+            Token::Float(n) => Ok(Value::Constant(Constant::Float(n))),
+            Token::Str(s) => Ok(Value::Constant(Constant::String(s))),
+            Token::Punct('(') => {
+                self.expect_punct(')')?;
+                Ok(Value::Unit)
+            }
+            Token::Ident(name) => match name.as_str() {
+                "true" => Ok(Value::Constant(Constant::Bool(true))),
+                "false" => Ok(Value::Constant(Constant::Bool(false))),
+                "null" => Ok(Value::Constant(Constant::Null)),
+                "str" => match self.bump()? {
+                    Token::Str(s) => Ok(Value::Constant(Constant::Str(s))),
+                    tok => Err(self.error(format!("expected a string after 'str', found {:?}", tok))),
+                },
+                "NaN" => Ok(Value::Constant(Constant::Float(f64::NAN))),
+                "inf" => Ok(Value::Constant(Constant::Float(f64::INFINITY))),
+                _ => name.strip_prefix("arg")
+                    .and_then(|n| n.parse().ok())
+                    .map(Value::Parameter)
+                    .ok_or_else(|| self.error(format!("expected a value, found '{}'", name))),
+            },
+            tok => Err(self.error(format!("expected a value, found {:?}", tok))),
+        }
+    }
+
+    // ==================== Functions ====================
+
+    /// Signature line after `fn`, then blocks up to the closing `}`
+    fn parse_function(&mut self, attrs: PendingAttrs) -> Result<IRFunction> {
+        let name = self.ident()?;
+        let params = self.parse_params()?;
+        self.expect_arrow()?;
+        let ret_type = self.parse_type()?;
+        self.expect_punct('{')?;
+        self.expect_end()?;
+
+        let mut func = IRFunction::new(&name, params, ret_type);
+        func.sret_type = attrs.sret;
+        func.entry_block = attrs.entry.unwrap_or(BlockId(0));
+        func.contracts = attrs.contracts;
+        func.debug_loc = attrs.loc;
+        func.inline = attrs.inline;
+        for flag in &attrs.flags {
+            match flag.as_str() {
+                "simd" => func.simd = true,
+                "naked" => func.naked = true,
+                "interrupt" => func.interrupt = true,
+                "volatile" => func.volatile = true,
+                "no_mangle" => func.no_mangle = true,
+                "cold" => func.cold = true,
+                "hot" => func.hot = true,
+                _ => func.extern_c = true,
+            }
+        }
+
+        loop {
+            if !self.next_line()? {
+                return Err(self.error(format!("missing '}}' at the end of fn {}", name)));
+            }
+            if self.eat_punct('}') {
+                self.expect_end()?;
+                return Ok(func);
+            }
+            // Block header: `bbN label:`
+            if matches!((self.peek(), self.peek_at(1), self.peek_at(2)),
+                (Some(Token::Ident(_)), Some(Token::Ident(_)), Some(Token::Punct(':')))) {
+                let id = self.block_ref()?;
+                let label = self.ident()?;
+                self.expect_punct(':')?;
+                self.expect_end()?;
+                func.blocks.push(BasicBlock::new(id, &label));
+                continue;
+            }
+            let Some(block) = func.blocks.last_mut() else {
+                return Err(self.error("instruction outside of a block"));
+            };
+            if block.terminator.is_some() {
+                return Err(self.error(format!("instruction after the terminator of block {}", block.label)));
+            }
+            match self.parse_line()? {
+                Line::Instruction(inst) => func.blocks.last_mut().unwrap().push(inst),
+                Line::Terminator(term) => func.blocks.last_mut().unwrap().set_terminator(term),
+            }
+            self.expect_end()?;
+        }
+    }
+
+    fn parse_line(&mut self) -> Result<Line> {
+        if let Some(Token::Reg(_)) = self.peek() {
+            let dest = self.register()?;
+            if self.eat_punct(',') {
+                return self.parse_checked(dest).map(Line::Instruction);
+            }
+            self.expect_punct('=')?;
+            return self.parse_definition(dest).map(Line::Instruction);
+        }
+        let keyword = self.ident()?;
+        match keyword.as_str() {
+            "ret" => {
+                if self.eat_keyword("void") {
+                    Ok(Line::Terminator(Terminator::Return { value: None }))
+                } else {
+                    Ok(Line::Terminator(Terminator::Return { value: Some(self.parse_value()?) }))
+                }
+            }
+            "br" => {
+                if matches!(self.peek(), Some(Token::Ident(s)) if s.starts_with("bb")) && self.peek_at(1).is_none() {
+                    return Ok(Line::Terminator(Terminator::Jump { target: self.block_ref()? }));
+                }
+                let cond = self.parse_value()?;
+                self.expect_punct(',')?;
+                let then_target = self.block_ref()?;
+                self.expect_punct(',')?;
+                let else_target = self.block_ref()?;
+                Ok(Line::Terminator(Terminator::Branch { cond, then_target, else_target }))
+            }
+            "switch" => {
+                let value = self.parse_value()?;
+                self.expect_punct(',')?;
+                let default = self.block_ref()?;
+                self.expect_punct('[')?;
+                let mut cases = Vec::new();
+                while !self.eat_punct(']') {
+                    if !cases.is_empty() {
+                        self.expect_punct(',')?;
+                    }
+                    let case = match self.bump()? {
+                        Token::Int(n) => n,
+                        tok => return Err(self.error(format!("expected a case value, found {:?}", tok))),
+                    };
+                    self.expect_punct(':')?;
+                    cases.push((case, self.block_ref()?));
+                }
+                Ok(Line::Terminator(Terminator::Switch { value, cases, default }))
+            }
+            "unreachable" => Ok(Line::Terminator(Terminator::Unreachable)),
+            "store" => {
+                let volatile = self.eat_keyword("volatile");
+                let value = self.parse_value()?;
+                self.expect_punct(',')?;
+                let ptr = self.parse_value()?;
+                Ok(Line::Instruction(Instruction::Store { ptr, value, volatile }))
+            }
+            "call" => self.parse_call(None).map(Line::Instruction),
+            "call_indirect" => self.parse_call_indirect(None).map(Line::Instruction),
+            "asm" => self.parse_asm().map(Line::Instruction),
+            "loc" => {
+                let file = self.string()?;
+                let line = self.usize()?;
+                let col = self.usize()?;
+                Ok(Line::Instruction(Instruction::DebugLoc(SourceLoc { file, line, col })))
+            }
+            other => Err(self.error(format!("unknown instruction '{}'", other))),
+        }
+    }
+
+    /// `%N, %M = checked op left right` after the first register
+    fn parse_checked(&mut self, dest: Register) -> Result<Instruction> {
+        let overflow_dest = self.register()?;
+        self.expect_punct('=')?;
+        if !self.eat_keyword("checked") {
+            return Err(self.error("expected 'checked' after two destination registers"));
+        }
+        let name = self.ident()?;
+        let op = binop(&name).ok_or_else(|| self.error(format!("unknown binary operator '{}'", name)))?;
+        let left = self.parse_value()?;
+        let right = self.parse_value()?;
+        Ok(Instruction::CheckedBinOp { dest, overflow_dest, op, left, right })
+    }
+
+    /// Right-hand side of `%N = ...`
+    fn parse_definition(&mut self, dest: Register) -> Result<Instruction> {
+        let op = match self.peek() {
+            Some(Token::Ident(op)) => op.clone(),
+            _ => return Ok(Instruction::Assign { dest, value: self.parse_value()? }),
+        };
+        if let Some(bin) = binop(&op) {
+            self.pos += 1;
+            let left = self.parse_value()?;
+            let right = self.parse_value()?;
+            return Ok(Instruction::BinOp { dest, op: bin, left, right });
+        }
+        let unary = match op.as_str() {
+            "neg" => Some(UnaryOp::Neg),
+            "not" => Some(UnaryOp::Not),
+            "bitnot" => Some(UnaryOp::BitNot),
+            _ => None,
+        };
+        if let Some(op) = unary {
+            self.pos += 1;
+            return Ok(Instruction::UnaryOp { dest, op, value: self.parse_value()? });
+        }
+        match op.as_str() {
+            "call" => {
+                self.pos += 1;
+                self.parse_call(Some(dest))
+            }
+            "call_indirect" => {
+                self.pos += 1;
+                self.parse_call_indirect(Some(dest))
+            }
+            "alloca" => {
+                self.pos += 1;
+                Ok(Instruction::Alloca { dest, ty: self.parse_type()? })
+            }
+            "load" => {
+                self.pos += 1;
+                let volatile = self.eat_keyword("volatile");
+                let ty = self.parse_type()?;
+                self.expect_punct(',')?;
+                let ptr = self.parse_value()?;
+                Ok(Instruction::Load { dest, ptr, ty, volatile })
+            }
+            "gep" => {
+                self.pos += 1;
+                let elem_ty = self.parse_type()?;
+                self.expect_punct(',')?;
+                let ptr = self.parse_value()?;
+                self.expect_punct(',')?;
+                let index = self.parse_value()?;
+                Ok(Instruction::GetElementPtr { dest, ptr, index, elem_ty })
+            }
+            "cast" => {
+                self.pos += 1;
+                let value = self.parse_value()?;
+                self.expect_keyword("to")?;
+                Ok(Instruction::Cast { dest, value, ty: self.parse_type()? })
+            }
+            "phi" => {
+                self.pos += 1;
+                let mut incoming = Vec::new();
+                while !self.at_end() {
+                    if !incoming.is_empty() {
+                        self.expect_punct(',')?;
+                    }
+                    self.expect_punct('[')?;
+                    let value = self.parse_value()?;
+                    self.expect_punct(',')?;
+                    let block = self.block_ref()?;
+                    self.expect_punct(']')?;
+                    incoming.push((value, block));
+                }
+                Ok(Instruction::Phi { dest, incoming })
+            }
+            // Plain values such as `true`, `null` or `arg0`
+            _ => Ok(Instruction::Assign { dest, value: self.parse_value()? }),
+        }
+    }
+
+    /// `name(args...)` after `call`
+    fn parse_call(&mut self, dest: Option<Register>) -> Result<Instruction> {
+        let func = self.ident()?;
+        self.expect_punct('(')?;
+        let mut args = Vec::new();
+        while !self.eat_punct(')') {
+            if !args.is_empty() {
+                self.expect_punct(',')?;
+            }
+            args.push(self.parse_value()?);
+        }
+        Ok(Instruction::Call { dest, func, args })
+    }
+
+    /// `type, callee(args...)` after `call_indirect`
+    fn parse_call_indirect(&mut self, dest: Option<Register>) -> Result<Instruction> {
+        let ty = self.parse_type()?;
+        self.expect_punct(',')?;
+        let callee = self.parse_value()?;
+        self.expect_punct('(')?;
+        let mut args = Vec::new();
+        while !self.eat_punct(')') {
+            if !args.is_empty() {
+                self.expect_punct(',')?;
+            }
+            args.push(self.parse_value()?);
+        }
+        Ok(Instruction::CallIndirect { dest, callee, ty, args })
+    }
+
+    /// `!("template", kind("constraint") [value] [-> %N], ...)` after `asm`
+    fn parse_asm(&mut self) -> Result<Instruction> {
+        self.expect_punct('!')?;
+        self.expect_punct('(')?;
+        let template = self.string()?;
+        let mut operands = Vec::new();
+        while self.eat_punct(',') {
+            let kind = match self.ident()?.as_str() {
+                "in" => IRAsmOperandKind::Input,
+                "out" => IRAsmOperandKind::Output,
+                "inout" => IRAsmOperandKind::InOut,
+                "clobber" => IRAsmOperandKind::Clobber,
+                other => return Err(self.error(format!("unknown asm operand kind '{}'", other))),
+            };
+            self.expect_punct('(')?;
+            let constraint = self.string()?;
+            self.expect_punct(')')?;
+            let input = if matches!(self.peek(), Some(Token::Arrow | Token::Punct(',' | ')'))) {
+                None
+            } else {
+                Some(self.parse_value()?)
+            };
+            let output = if self.peek() == Some(&Token::Arrow) {
+                self.pos += 1;
+                Some(self.register()?)
+            } else {
+                None
+            };
+            operands.push(IRAsmOperand { kind, constraint, input, output });
+        }
+        self.expect_punct(')')?;
+        Ok(Instruction::InlineAsm { template, operands })
+    }
+}
+
+enum Line {
+    Instruction(Instruction),
+    Terminator(Terminator),
+}
+
+fn scalar_type(name: &str) -> Option<IRType> {
+    Some(match name {
+        "void" => IRType::Void,
+        "bool" => IRType::Bool,
+        "i8" => IRType::I8,
+        "i16" => IRType::I16,
+        "i32" => IRType::I32,
+        "i64" => IRType::I64,
+        "u8" => IRType::U8,
+        "u16" => IRType::U16,
+        "u32" => IRType::U32,
+        "u64" => IRType::U64,
+        "f32" => IRType::F32,
+        "f64" => IRType::F64,
+        _ => return None,
+    })
+}
+
+fn binop(name: &str) -> Option<BinOp> {
+    Some(match name {
+        "add" => BinOp::Add,
+        "sub" => BinOp::Sub,
+        "mul" => BinOp::Mul,
+        "div" => BinOp::Div,
+        "mod" => BinOp::Mod,
+        "eq" => BinOp::Eq,
+        "ne" => BinOp::Ne,
+        "lt" => BinOp::Lt,
+        "le" => BinOp::Le,
+        "gt" => BinOp::Gt,
+        "ge" => BinOp::Ge,
+        "and" => BinOp::And,
+        "or" => BinOp::Or,
+        "xor" => BinOp::Xor,
+        "shl" => BinOp::Shl,
+        "shr" => BinOp::Shr,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::lexer::Lexer;
+    use crate::frontend::parser::Parser;
+    use crate::middle::ir_gen::IRGenerator;
+    use crate::middle::ir_printer::print_ir;
+
+    #[test]
+    fn test_parse_handwritten() {
+        let module = parse_ir(
+            "module demo ; comment\n\
+             #![no_std]\n\
+             #[repr(C)]\n\
+             pub struct Point { x: i64, y: *u8 }\n\
+             struct Flags { ready: bool : 1, rest: u8 : 7 }\n\
+             enum Shape { Empty, Circle(f64) }\n\
+             enum Errno: u8 { EPERM = 1, ENOENT, EIO = 5 }\n\
+             extern fn puts(s: *u8) -> i32\n\
+             global mut @COUNTER: i64 = -4\n\
+             #[requires(\"n > 0\")]\n\
+             #[cold]\n\
+             #[inline(never)]\n\
+             pub fn double(n: i64) -> i64 {\n\
+               bb0 entry:\n\
+                 %0 = mul arg0 2\n\
+                 %1 = call puts(\"hi\\n\")\n\
+                 br %0, bb1, bb1\n\
+               bb1 exit:\n\
+                 ret %0\n\
+             }\n",
+        ).unwrap();
+        assert!(module.no_std);
+        assert_eq!(module.structs[0].repr, StructRepr::C);
+        assert!(module.structs[0].is_pub);
+        assert!(module.structs[0].bits.is_empty());
+        assert_eq!(module.structs[1].bits, vec![Some(1), Some(7)]);
+        assert_eq!(module.enums[0].variants[1].fields, vec![IRType::F64]);
+        assert_eq!(module.enums[1].tag, IRType::U8);
+        let discriminants: Vec<i64> = module.enums[1].variants.iter().map(|v| v.discriminant).collect();
+        assert_eq!(discriminants, vec![1, 2, 5]);
+        assert!(matches!(module.globals[0].init, Some(Constant::Int(-4))));
+        let func = &module.functions[0];
+        assert_eq!(func.contracts.requires, vec!["n > 0"]);
+        assert!(func.is_pub);
+        assert!(func.cold && !func.hot);
+        assert_eq!(func.inline, InlineHint::Never);
+        assert!(matches!(&func.blocks[0].instructions[1], Instruction::Call { args, .. }
+            if matches!(&args[0], Value::Constant(Constant::String(s)) if s == "hi\n")));
+        assert!(matches!(func.blocks[0].terminator, Some(Terminator::Branch { then_target: BlockId(1), .. })));
+
+        let err = parse_ir("module m\nfn f() -> void {\n  bb0 entry:\n    %0 = frob 1\n").unwrap_err();
+        assert!(matches!(err, Error::IrParse { line: 4, .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn test_parse_checked_binop() {
+        let text = "module m\nfn sq(x: i32) -> i32 {\n  bb0 entry:\n    %0, %1 = checked mul arg0 arg0\n    ret %0\n}\n";
+        let module = parse_ir(text).unwrap();
+        assert!(matches!(module.functions[0].blocks[0].instructions[0], Instruction::CheckedBinOp {
+            dest: Register(0), overflow_dest: Register(1), op: BinOp::Mul, ..
+        }));
+        assert_eq!(print_ir(&parse_ir(&print_ir(&module)).unwrap()), print_ir(&module));
+        assert!(parse_ir("module m\nfn f() -> void {\n  bb0 entry:\n    %0, %1 = mul 1 2\n").is_err());
+    }
+
+    /// Every sample program that lowers to IR (with -g locations) must survive print -> parse unchanged
+    #[test]
+    fn test_round_trip_samples() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+        let mut checked = 0;
+        let mut paths: Vec<_> = std::fs::read_dir(&dir).unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p.extension().is_some_and(|e| e == "aeth"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            let source = std::fs::read_to_string(&path).unwrap();
+            let Ok(program) = Parser::new(Lexer::new(&source, 0)).parse_program() else { continue };
+            let mut gen = IRGenerator::new("sample").with_debug_info(&path.to_string_lossy(), &source);
+            let Ok(module) = gen.generate(&program) else { continue };
+            let text = print_ir(&module);
+            let parsed = parse_ir(&text).unwrap_or_else(|e| panic!("{}: {}\n{}", path.display(), e, text));
+            assert!(parsed == module, "{} did not round-trip", path.display());
+            assert_eq!(print_ir(&parsed), text);
+            checked += 1;
+        }
+        assert!(checked > 20, "only {} samples lowered to IR", checked);
+    }
+}
@@ -1,10 +1,11 @@
 //! IR Printer - Pretty print Aether IR
 //!
-//! Outputs human-readable IR for debugging.
+//! Outputs human-readable IR in the textual format read back by `ir_parser`.
 #![allow(dead_code)]
 
 use std::fmt::Write;
 use crate::middle::ir::*;
+use crate::types::layout::BitStorage;
 
 /// Pretty printer for Aether IR
 pub struct IRPrinter {
@@ -24,10 +25,69 @@ impl IRPrinter {
     pub fn print_module(&mut self, module: &IRModule) -> String {
         self.output.clear();
         
-        writeln!(self.output, "; Module: {}", module.name).unwrap();
+        writeln!(self.output, "module {}", module.name).unwrap();
         writeln!(self.output, "; Functions: {}", module.functions.len()).unwrap();
+        if module.no_std {
+            writeln!(self.output, "#![no_std]").unwrap();
+        }
+        if module.no_main {
+            writeln!(self.output, "#![no_main]").unwrap();
+        }
         writeln!(self.output).unwrap();
 
+        for s in &module.structs {
+            match s.repr {
+                StructRepr::Default => {}
+                StructRepr::C => writeln!(self.output, "#[repr(C)]").unwrap(),
+                StructRepr::Packed => writeln!(self.output, "#[repr(packed)]").unwrap(),
+                StructRepr::Transparent => writeln!(self.output, "#[repr(transparent)]").unwrap(),
+            }
+            let kind = if s.is_union { "union" } else { "struct" };
+            let fields: Vec<String> = s.fields.iter().enumerate()
+                .map(|(i, (name, ty))| match s.bits.get(i).copied().flatten() {
+                    Some(bits) => format!("{}: {} : {}", name, self.type_str(ty), bits),
+                    None => format!("{}: {}", name, self.type_str(ty)),
+                })
+                .collect();
+            let visibility = if s.is_pub { "pub " } else { "" };
+            writeln!(self.output, "{}{} {} {{ {} }}", visibility, kind, s.name, fields.join(", ")).unwrap();
+        }
+        for e in &module.enums {
+            // Discriminants are only spelled out where they skip ahead
+            let mut next = 0;
+            let variants: Vec<String> = e.variants.iter()
+                .map(|v| {
+                    let mut text = v.name.clone();
+                    if !v.fields.is_empty() {
+                        let fields: Vec<String> = v.fields.iter().map(|t| self.type_str(t)).collect();
+                        text.push_str(&format!("({})", fields.join(", ")));
+                    }
+                    if v.discriminant != next {
+                        text.push_str(&format!(" = {}", v.discriminant));
+                    }
+                    next = v.discriminant.wrapping_add(1);
+                    text
+                })
+                .collect();
+            let tag = if e.tag == IRType::I32 { String::new() } else { format!(": {}", self.type_str(&e.tag)) };
+            writeln!(self.output, "enum {}{} {{ {} }}", e.name, tag, variants.join(", ")).unwrap();
+        }
+        for ext in &module.externs {
+            writeln!(self.output, "extern fn {}({}) -> {}", ext.name, self.params_str(&ext.params), self.type_str(&ext.ret_type)).unwrap();
+        }
+        for global in &module.globals {
+            let kind = if global.is_mut { "global mut" } else { "global" };
+            let prefix = if global.is_extern { "extern " } else { "" };
+            write!(self.output, "{}{} @{}: {}", prefix, kind, global.name, self.type_str(&global.ty)).unwrap();
+            if let Some(init) = &global.init {
+                write!(self.output, " = {}", self.constant_str(init)).unwrap();
+            }
+            writeln!(self.output).unwrap();
+        }
+        if !module.structs.is_empty() || !module.enums.is_empty() || !module.externs.is_empty() || !module.globals.is_empty() {
+            writeln!(self.output).unwrap();
+        }
+
         for func in &module.functions {
             self.print_function(func);
             writeln!(self.output).unwrap();
@@ -38,15 +98,35 @@ impl IRPrinter {
 
     /// Print a function
     fn print_function(&mut self, func: &IRFunction) {
-        // Function signature
-        write!(self.output, "fn {}(", func.name).unwrap();
-        for (i, (name, ty)) in func.params.iter().enumerate() {
-            if i > 0 {
-                write!(self.output, ", ").unwrap();
+        // Attributes that are not part of the signature
+        for (flag, name) in [(func.simd, "simd"), (func.naked, "naked"), (func.interrupt, "interrupt"), (func.volatile, "volatile"), (func.extern_c, "extern_c"), (func.no_mangle, "no_mangle"), (func.cold, "cold"), (func.hot, "hot")] {
+            if flag {
+                writeln!(self.output, "#[{}]", name).unwrap();
             }
-            write!(self.output, "{}: {}", name, self.type_str(ty)).unwrap();
         }
-        writeln!(self.output, ") -> {} {{", self.type_str(&func.ret_type)).unwrap();
+        match func.inline {
+            InlineHint::Auto => {}
+            InlineHint::Always => writeln!(self.output, "#[inline]").unwrap(),
+            InlineHint::Never => writeln!(self.output, "#[inline(never)]").unwrap(),
+        }
+        if let Some(ref sret) = func.sret_type {
+            writeln!(self.output, "#[sret({})]", self.type_str(sret)).unwrap();
+        }
+        if func.entry_block != BlockId(0) {
+            writeln!(self.output, "#[entry(bb{})]", func.entry_block.0).unwrap();
+        }
+        if let Some(ref loc) = func.debug_loc {
+            writeln!(self.output, "#[loc({:?}, {}, {})]", loc.file, loc.line, loc.col).unwrap();
+        }
+        for (list, name) in [(&func.contracts.requires, "requires"), (&func.contracts.ensures, "ensures"), (&func.contracts.effects, "effects")] {
+            for item in list {
+                writeln!(self.output, "#[{}({:?})]", name, item).unwrap();
+            }
+        }
+
+        // Function signature
+        let visibility = if func.is_pub { "pub " } else { "" };
+        writeln!(self.output, "{}fn {}({}) -> {} {{", visibility, func.name, self.params_str(&func.params), self.type_str(&func.ret_type)).unwrap();
 
         // Basic blocks
         for block in &func.blocks {
@@ -58,7 +138,7 @@ impl IRPrinter {
 
     /// Print a basic block
     fn print_block(&mut self, block: &BasicBlock) {
-        writeln!(self.output, "  {}:", block.label).unwrap();
+        writeln!(self.output, "  bb{} {}:", block.id.0, block.label).unwrap();
 
         // Instructions
         for inst in &block.instructions {
@@ -91,8 +171,19 @@ impl IRPrinter {
                     self.value_str(right)
                 ).unwrap();
             }
+            Instruction::CheckedBinOp { dest, overflow_dest, op, left, right } => {
+                write!(
+                    self.output,
+                    "{}, {} = checked {} {} {}",
+                    dest,
+                    overflow_dest,
+                    op,
+                    self.value_str(left),
+                    self.value_str(right)
+                ).unwrap();
+            }
             Instruction::Cast { dest, value, ty } => {
-                write!(self.output, "{} = cast {} to {:?}", dest, self.value_str(value), ty).unwrap();
+                write!(self.output, "{} = cast {} to {}", dest, self.value_str(value), self.type_str(ty)).unwrap();
             }
             Instruction::UnaryOp { dest, op, value } => {
                 let op_str = match op {
@@ -115,20 +206,30 @@ impl IRPrinter {
                 }
                 write!(self.output, ")").unwrap();
             }
+            Instruction::CallIndirect { dest, callee, ty, args } => {
+                if let Some(d) = dest {
+                    write!(self.output, "{} = ", d).unwrap();
+                }
+                let args: Vec<String> = args.iter().map(|arg| self.value_str(arg)).collect();
+                write!(self.output, "call_indirect {}, {}({})", self.type_str(ty), self.value_str(callee), args.join(", ")).unwrap();
+            }
             Instruction::Alloca { dest, ty } => {
                 write!(self.output, "{} = alloca {}", dest, self.type_str(ty)).unwrap();
             }
-            Instruction::Load { dest, ptr, ty: _ } => {
-                write!(self.output, "{} = load {}", dest, self.value_str(ptr)).unwrap();
+            Instruction::Load { dest, ptr, ty, volatile } => {
+                let kw = if *volatile { "load volatile" } else { "load" };
+                write!(self.output, "{} = {} {}, {}", dest, kw, self.type_str(ty), self.value_str(ptr)).unwrap();
             }
-            Instruction::Store { ptr, value } => {
-                write!(self.output, "store {}, {}", self.value_str(value), self.value_str(ptr)).unwrap();
+            Instruction::Store { ptr, value, volatile } => {
+                let kw = if *volatile { "store volatile" } else { "store" };
+                write!(self.output, "{} {}, {}", kw, self.value_str(value), self.value_str(ptr)).unwrap();
             }
-            Instruction::GetElementPtr { dest, ptr, index, elem_ty: _ } => {
+            Instruction::GetElementPtr { dest, ptr, index, elem_ty } => {
                 write!(
                     self.output, 
-                    "{} = gep {}, {}", 
+                    "{} = gep {}, {}, {}", 
                     dest, 
+                    self.type_str(elem_ty),
                     self.value_str(ptr), 
                     self.value_str(index)
                 ).unwrap();
@@ -143,35 +244,27 @@ impl IRPrinter {
                 }
             }
             Instruction::InlineAsm { template, operands } => {
-                write!(self.output, "asm!(\"{}\"", template).unwrap();
+                write!(self.output, "asm!({:?}", template).unwrap();
                 for op in operands {
-                    write!(self.output, ", ").unwrap();
-                    match op.kind {
-                        IRAsmOperandKind::Input => {
-                            if let Some(ref val) = op.input {
-                                write!(self.output, "in(\"{}\") {}", op.constraint, self.value_str(val)).unwrap();
-                            }
-                        }
-                        IRAsmOperandKind::Output => {
-                            if let Some(reg) = op.output {
-                                write!(self.output, "out(\"{}\") %{}", op.constraint, reg.0).unwrap();
-                            }
-                        }
-                        IRAsmOperandKind::InOut => {
-                            if let Some(reg) = op.output {
-                                 write!(self.output, "inout(\"{}\") ", op.constraint).unwrap();
-                                 if let Some(ref val) = op.input {
-                                     write!(self.output, "{} -> %{}", self.value_str(val), reg.0).unwrap();
-                                 }
-                            }
-                        }
-                        IRAsmOperandKind::Clobber => {
-                             write!(self.output, "clobber(\"{}\")", op.constraint).unwrap();
-                        }
+                    let kind = match op.kind {
+                        IRAsmOperandKind::Input => "in",
+                        IRAsmOperandKind::Output => "out",
+                        IRAsmOperandKind::InOut => "inout",
+                        IRAsmOperandKind::Clobber => "clobber",
+                    };
+                    write!(self.output, ", {}({:?})", kind, op.constraint).unwrap();
+                    if let Some(ref val) = op.input {
+                        write!(self.output, " {}", self.value_str(val)).unwrap();
+                    }
+                    if let Some(reg) = op.output {
+                        write!(self.output, " -> {}", reg).unwrap();
                     }
                 }
                 write!(self.output, ")").unwrap();
             }
+            Instruction::DebugLoc(loc) => {
+                write!(self.output, "loc {:?} {} {}", loc.file, loc.line, loc.col).unwrap();
+            }
         }
     }
 
@@ -197,23 +290,48 @@ impl IRPrinter {
                     else_target.0
                 ).unwrap();
             }
+            Terminator::Switch { value, cases, default } => {
+                let cases: Vec<String> = cases.iter()
+                    .map(|(case, target)| format!("{}: bb{}", case, target.0))
+                    .collect();
+                write!(self.output, "switch {}, bb{} [{}]", self.value_str(value), default.0, cases.join(", ")).unwrap();
+            }
             Terminator::Unreachable => {
                 write!(self.output, "unreachable").unwrap();
             }
         }
     }
 
+    /// `name: type` list
+    fn params_str(&self, params: &[(String, IRType)]) -> String {
+        params.iter()
+            .map(|(name, ty)| format!("{}: {}", name, self.type_str(ty)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
     /// Convert value to string
     fn value_str(&self, value: &Value) -> String {
         match value {
             Value::Register(r) => format!("{}", r),
-            Value::Constant(c) => format!("{}", c),
+            Value::Constant(c) => self.constant_str(c),
             Value::Parameter(i) => format!("arg{}", i),
             Value::Global(name) => format!("@{}", name),
             Value::Unit => "()".to_string(),
         }
     }
 
+    /// Constants are printed so that the parser can tell them apart:
+    /// floats always have a fractional part and strings are escaped
+    fn constant_str(&self, constant: &Constant) -> String {
+        match constant {
+            Constant::Float(n) => format!("{:?}", n),
+            Constant::String(s) => format!("{:?}", s),
+            Constant::Str(s) => format!("str {:?}", s),
+            other => format!("{}", other),
+        }
+    }
+
     /// Convert type to string
     fn type_str(&self, ty: &IRType) -> String {
         match ty {
@@ -232,6 +350,9 @@ impl IRPrinter {
             IRType::Ptr(inner) => format!("*{}", self.type_str(inner)),
             IRType::Array(elem, size) => format!("[{}; {}]", self.type_str(elem), size),
             IRType::Struct(name) => name.clone(),
+            IRType::Str => "str".to_string(),
+            IRType::Vec(elem) => format!("vec<{}>", self.type_str(elem)),
+            IRType::Map(key, value) => format!("map<{}, {}>", self.type_str(key), self.type_str(value)),
             IRType::Function { params, ret } => {
                 let params_str: Vec<_> = params.iter().map(|t| self.type_str(t)).collect();
                 format!("fn({}) -> {}", params_str.join(", "), self.type_str(ret))
@@ -255,6 +376,58 @@ pub fn print_ir(module: &IRModule) -> String {
     printer.print_module(module)
 }
 
+/// Print a single instruction (for diagnostics)
+pub fn instruction_to_string(inst: &Instruction) -> String {
+    let mut printer = IRPrinter::new();
+    printer.print_instruction(inst);
+    printer.output
+}
+
+/// Print a single terminator (for diagnostics)
+pub fn terminator_to_string(term: &Terminator) -> String {
+    let mut printer = IRPrinter::new();
+    printer.print_terminator(term);
+    printer.output
+}
+
+/// Print a type the way it appears in textual IR
+pub fn type_to_string(ty: &IRType) -> String {
+    IRPrinter::new().type_str(ty)
+}
+
+/// Size, alignment and field offsets of every struct in `module`, for
+/// `--show-struct-layout`
+pub fn print_struct_layouts(module: &IRModule) -> String {
+    let mut out = String::new();
+    for def in module.structs.iter().filter(|s| !s.is_union) {
+        let Some(layout) = module.struct_layout(&def.name) else { continue };
+        let padding = match def.bitfields() {
+            Some(bitfields) => {
+                let data: usize = bitfields.storage.iter()
+                    .map(|member| match *member {
+                        BitStorage::Field(i) => module.layout_of(&def.fields[i].1).map_or(0, |l| l.size),
+                        BitStorage::Unit { bytes } => bytes,
+                    })
+                    .sum();
+                layout.size - data
+            }
+            None if def.repr == StructRepr::Packed => 0,
+            None => module.compute_padding_bytes(&def.fields),
+        };
+        writeln!(out, "struct {}: size {}, align {}, {} bytes of padding", def.name, layout.size, layout.align, padding).unwrap();
+        for (i, ((name, ty), offset)) in def.fields.iter().zip(&layout.offsets).enumerate() {
+            match def.bits.get(i).copied().flatten() {
+                Some(bits) => writeln!(out, "  {:>4}  {}: {} (bits {})", offset, name, type_to_string(ty), bits).unwrap(),
+                None => {
+                    let size = module.layout_of(ty).map_or(0, |l| l.size);
+                    writeln!(out, "  {:>4}  {}: {} (size {})", offset, name, type_to_string(ty), size).unwrap();
+                }
+            }
+        }
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -302,4 +475,16 @@ mod tests {
         assert!(ir.contains("else"));
         println!("{}", ir);
     }
+
+    #[test]
+    fn test_print_struct_layouts() {
+        let source = "struct Packet { flag: bool, count: u64, tag: u16 }\n\
+                      #[field_order(optimal)]\nstruct Compact { flag: bool, count: u64, tag: u16 }";
+        let program = Parser::new(Lexer::new(source, 0)).parse_program().unwrap();
+        let module = IRGenerator::new("test").generate(&program).unwrap();
+        let report = print_struct_layouts(&module);
+        assert!(report.contains("struct Packet: size 24, align 8, 13 bytes of padding"), "{}", report);
+        assert!(report.contains("struct Compact: size 16, align 8, 5 bytes of padding"), "{}", report);
+        assert!(report.contains("     0  count: u64 (size 8)\n     8  tag: u16 (size 2)\n    10  flag: bool (size 1)"), "{}", report);
+    }
 }
@@ -0,0 +1,389 @@
+//! Link-time optimization objects
+//!
+//! `aethc --lto build` stops after IR generation and writes the unoptimized
+//! module to an `.alito` file. `aethc link` reads several back, merges them
+//! into one module and only then runs the optimizer and the backend, so both
+//! see the whole program instead of one module at a time.
+//!
+//! An `.alito` file holds the magic `ALITO`, a format version byte, the
+//! libraries the module links against and the module as textual IR, with
+//! little-endian `u32` counts and lengths:
+//!
+//! ```text
+//! "ALITO" version:u8  libraries:u32 (len:u32 bytes)*  len:u32 ir
+//! ```
+//!
+//! The LLVM backend writes bitcode instead (`LLVMCodeGen::write_bitcode`).
+
+use std::collections::{HashMap, HashSet};
+
+use crate::middle::ir::*;
+use crate::middle::ir_parser::parse_ir;
+use crate::middle::ir_printer::print_ir;
+use crate::middle::mangle;
+use crate::utils::{Error, Result};
+
+/// File extension of LTO objects
+pub const EXTENSION: &str = "alito";
+
+const MAGIC: &[u8] = b"ALITO";
+const VERSION: u8 = 1;
+const BITCODE_MAGIC: &[u8] = b"BC\xC0\xDE";
+
+/// One module compiled with `--lto`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LtoObject {
+    pub module: IRModule,
+    /// Libraries named by `extern ... link = "lib"`
+    pub libraries: Vec<String>,
+}
+
+impl LtoObject {
+    pub fn new(module: IRModule, libraries: Vec<String>) -> Self {
+        Self { module, libraries }
+    }
+
+    /// Encode as the contents of an `.alito` file
+    pub fn to_bytes(&self) -> Vec<u8> {
+        fn push_str(out: &mut Vec<u8>, s: &str) {
+            out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+            out.extend_from_slice(s.as_bytes());
+        }
+        let mut out = MAGIC.to_vec();
+        out.push(VERSION);
+        out.extend_from_slice(&(self.libraries.len() as u32).to_le_bytes());
+        for lib in &self.libraries {
+            push_str(&mut out, lib);
+        }
+        push_str(&mut out, &print_ir(&self.module));
+        out
+    }
+
+    /// Decode the contents of an `.alito` file
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.starts_with(BITCODE_MAGIC) {
+            return Err(Error::Lto("input is LLVM bitcode; link it with `--backend llvm`".into()));
+        }
+        if !bytes.starts_with(MAGIC) {
+            return Err(Error::Lto("not an LTO object (build it with `aethc --lto build`)".into()));
+        }
+        let mut reader = Reader { bytes, pos: MAGIC.len() };
+        let version = reader.take(1)?[0];
+        if version != VERSION {
+            return Err(Error::Lto(format!("unsupported LTO object version {}", version)));
+        }
+        let count = reader.u32()?;
+        let libraries = (0..count).map(|_| reader.string()).collect::<Result<Vec<_>>>()?;
+        let module = parse_ir(&reader.string()?)?;
+        if reader.pos != bytes.len() {
+            return Err(Error::Lto("trailing bytes after the module".into()));
+        }
+        Ok(Self { module, libraries })
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(n).filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| Error::Lto("truncated LTO object".into()))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| Error::Lto("invalid UTF-8 in LTO object".into()))
+    }
+}
+
+/// Merge `objects` into one module named `name`.
+///
+/// Identical definitions of a symbol are kept once. A function or static
+/// that several objects define differently, or whose identical copies call
+/// such a symbol, is renamed in each of them to `module__name`. `main`,
+/// `#[extern_c]` exports and `#[no_mangle]` functions cannot be renamed, and
+/// neither can a symbol another object reaches through an `extern`
+/// declaration. Declarations of symbols the merged module defines are dropped.
+pub fn link(objects: Vec<LtoObject>, name: &str) -> Result<LtoObject> {
+    let prefixes = unique_prefixes(&objects);
+
+    // Objects defining each symbol
+    let mut definitions: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, object) in objects.iter().enumerate() {
+        for symbol in defined_symbols(&object.module) {
+            definitions.entry(symbol).or_default().push(i);
+        }
+    }
+    // Symbols defined differently somewhere, then the equal definitions that
+    // refer to one of them (each module's copy must keep calling its own)
+    let mut colliding: HashSet<&str> = definitions.iter()
+        .filter(|(symbol, defs)| {
+            defs.iter().any(|&i| !same_definition(&objects[defs[0]].module, &objects[i].module, symbol))
+        })
+        .map(|(symbol, _)| *symbol)
+        .collect();
+    loop {
+        let spread: Vec<&str> = definitions.iter()
+            .filter(|(symbol, defs)| defs.len() > 1 && !colliding.contains(*symbol))
+            .filter(|(symbol, defs)| references(&objects[defs[0]].module, symbol).iter().any(|r| colliding.contains(r.as_str())))
+            .map(|(symbol, _)| *symbol)
+            .collect();
+        if spread.is_empty() {
+            break;
+        }
+        colliding.extend(spread);
+    }
+
+    let mut colliding: Vec<&str> = colliding.into_iter().collect();
+    colliding.sort();
+    let mut renames = vec![HashMap::new(); objects.len()];
+    for &symbol in &colliding {
+        let defs = &definitions[symbol];
+        let exported = defs.iter().any(|&i| {
+            objects[i].module.functions.iter().any(|f| f.name == symbol && mangle::keeps_source_name(&f.name, f.extern_c || f.no_mangle))
+        });
+        if exported {
+            let modules: Vec<_> = defs.iter().map(|&i| format!("`{}`", objects[i].module.name)).collect();
+            return Err(Error::Lto(format!("`{}` is defined by {}", symbol, modules.join(", "))));
+        }
+        for &i in defs {
+            renames[i].insert(symbol.to_string(), mangle::function_symbol(&[&prefixes[i]], symbol));
+        }
+    }
+    for object in &objects {
+        let declared = object.module.externs.iter().map(|e| &e.name)
+            .chain(object.module.globals.iter().filter(|g| g.is_extern).map(|g| &g.name));
+        for symbol in declared {
+            if colliding.contains(&symbol.as_str()) {
+                return Err(Error::Lto(format!(
+                    "`{}` declares `{}`, which several modules define differently",
+                    object.module.name, symbol
+                )));
+            }
+        }
+    }
+
+    let mut merged = IRModule::new(name);
+    let mut libraries: Vec<String> = Vec::new();
+    for (object, renames) in objects.into_iter().zip(&renames) {
+        let mut module = object.module;
+        rename_symbols(&mut module, renames);
+        for s in module.structs {
+            match merged.structs.iter().find(|t| t.name == s.name) {
+                Some(existing) if *existing != s => {
+                    return Err(Error::Lto(format!("struct `{}` has different definitions in two modules", s.name)));
+                }
+                Some(_) => {}
+                None => merged.structs.push(s),
+            }
+        }
+        for e in module.enums {
+            match merged.enums.iter().find(|t| t.name == e.name) {
+                Some(existing) if *existing != e => {
+                    return Err(Error::Lto(format!("enum `{}` has different definitions in two modules", e.name)));
+                }
+                Some(_) => {}
+                None => merged.enums.push(e),
+            }
+        }
+        for func in module.functions {
+            if !merged.functions.iter().any(|f| f.name == func.name) {
+                merged.functions.push(func);
+            }
+        }
+        for global in module.globals {
+            if !merged.globals.iter().any(|g| g.name == global.name && g.is_extern == global.is_extern) {
+                merged.globals.push(global);
+            }
+        }
+        for ext in module.externs {
+            if !merged.externs.iter().any(|e| e.name == ext.name) {
+                merged.externs.push(ext);
+            }
+        }
+        merged.no_std |= module.no_std;
+        merged.no_main |= module.no_main;
+        for lib in object.libraries {
+            if !libraries.contains(&lib) {
+                libraries.push(lib);
+            }
+        }
+    }
+
+    let defined: HashSet<String> = defined_symbols(&merged).map(str::to_string).collect();
+    merged.externs.retain(|e| !defined.contains(&e.name));
+    merged.globals.retain(|g| !g.is_extern || !defined.contains(&g.name));
+    Ok(LtoObject::new(merged, libraries))
+}
+
+/// Functions and statics `module` defines
+fn defined_symbols(module: &IRModule) -> impl Iterator<Item = &str> {
+    module.functions.iter().map(|f| f.name.as_str())
+        .chain(module.globals.iter().filter(|g| !g.is_extern).map(|g| g.name.as_str()))
+}
+
+/// Whether `a` and `b` define `symbol` the same way
+fn same_definition(a: &IRModule, b: &IRModule, symbol: &str) -> bool {
+    let function = |m: &IRModule| m.functions.iter().find(|f| f.name == symbol).cloned();
+    let global = |m: &IRModule| m.globals.iter().find(|g| g.name == symbol && !g.is_extern).cloned();
+    function(a) == function(b) && global(a) == global(b)
+}
+
+/// Functions and statics the definition of `symbol` in `module` refers to
+fn references(module: &IRModule, symbol: &str) -> Vec<String> {
+    let Some(func) = module.functions.iter().find(|f| f.name == symbol) else {
+        return Vec::new();
+    };
+    let mut names = Vec::new();
+    for block in &func.blocks {
+        for inst in &block.instructions {
+            if let Instruction::Call { func, .. } = inst {
+                names.push(func.clone());
+            }
+        }
+        let values = block.instructions.iter().flat_map(|i| i.operands())
+            .chain(block.terminator.iter().flat_map(|t| t.operands()));
+        for value in values {
+            if let Value::Global(name) = value {
+                names.push(name.clone());
+            }
+        }
+    }
+    names
+}
+
+/// Module names to prefix renamed symbols with, numbered where two objects share a name
+fn unique_prefixes(objects: &[LtoObject]) -> Vec<String> {
+    let mut prefixes: Vec<String> = Vec::new();
+    for object in objects {
+        let mut prefix = object.module.name.clone();
+        let mut n = 1;
+        while prefixes.contains(&prefix) {
+            prefix = format!("{}{}", object.module.name, n);
+            n += 1;
+        }
+        prefixes.push(prefix);
+    }
+    prefixes
+}
+
+/// Apply `renames` to the definitions of `module` and every reference to them
+fn rename_symbols(module: &mut IRModule, renames: &HashMap<String, String>) {
+    if renames.is_empty() {
+        return;
+    }
+    let rename = |name: &mut String| {
+        if let Some(new) = renames.get(name.as_str()) {
+            *name = new.clone();
+        }
+    };
+    for global in module.globals.iter_mut().filter(|g| !g.is_extern) {
+        rename(&mut global.name);
+    }
+    for func in &mut module.functions {
+        rename(&mut func.name);
+        for block in &mut func.blocks {
+            for inst in &mut block.instructions {
+                if let Instruction::Call { func, .. } = inst {
+                    rename(func);
+                }
+                for value in inst.operands_mut() {
+                    if let Value::Global(name) = value {
+                        rename(name);
+                    }
+                }
+            }
+            for value in block.terminator.iter_mut().flat_map(|t| t.operands_mut()) {
+                if let Value::Global(name) = value {
+                    rename(name);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `fn <name>() -> i64 { return <value> }`
+    fn constant_fn(name: &str, value: i64) -> IRFunction {
+        let mut func = IRFunction::new(name, vec![], IRType::I64);
+        let entry = func.add_block("entry");
+        func.get_block_mut(entry).unwrap()
+            .set_terminator(Terminator::Return { value: Some(Value::Constant(Constant::Int(value))) });
+        func
+    }
+
+    /// `fn <name>() -> i64 { return <callee>() }`
+    fn caller_fn(name: &str, callee: &str) -> IRFunction {
+        let mut func = IRFunction::new(name, vec![], IRType::I64);
+        let entry = func.add_block("entry");
+        let block = func.get_block_mut(entry).unwrap();
+        block.push(Instruction::Call { dest: Some(Register(0)), func: callee.into(), args: vec![] });
+        block.set_terminator(Terminator::Return { value: Some(Value::Register(Register(0))) });
+        func
+    }
+
+    fn object(name: &str, functions: Vec<IRFunction>, externs: &[&str]) -> LtoObject {
+        let mut module = IRModule::new(name);
+        module.functions = functions;
+        module.externs = externs.iter()
+            .map(|e| IRExtern { name: e.to_string(), params: vec![], ret_type: IRType::I64 })
+            .collect();
+        LtoObject::new(module, vec!["m".into()])
+    }
+
+    #[test]
+    fn test_object_round_trip() {
+        let object = object("util", vec![caller_fn("util__twice", "scale"), constant_fn("scale", 2)], &["puts"]);
+        let bytes = object.to_bytes();
+        assert!(bytes.starts_with(b"ALITO"));
+        assert_eq!(LtoObject::from_bytes(&bytes).unwrap(), object);
+        assert!(LtoObject::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(LtoObject::from_bytes(b"BC\xC0\xDE....").unwrap_err().to_string().contains("--backend llvm"));
+    }
+
+    #[test]
+    fn test_link_renames_collisions() {
+        let main = object("main", vec![caller_fn("main", "util__twice"), constant_fn("scale", 10)], &["util__twice"]);
+        let util = object("util", vec![caller_fn("util__twice", "scale"), constant_fn("scale", 2)], &[]);
+        let linked = link(vec![main, util], "app").unwrap();
+
+        let names: Vec<_> = linked.module.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["main", "main__scale", "util__twice", "util__scale"]);
+        assert!(linked.module.externs.is_empty());
+        assert_eq!(linked.libraries, ["m"]);
+        let twice = &linked.module.functions[2];
+        assert!(matches!(&twice.blocks[0].instructions[0], Instruction::Call { func, .. } if func == "util__scale"));
+    }
+
+    #[test]
+    fn test_link_rejects_ambiguity() {
+        // Identical definitions are merged rather than renamed
+        let a = object("a", vec![constant_fn("helper", 1)], &[]);
+        let b = object("b", vec![constant_fn("helper", 1)], &[]);
+        assert_eq!(link(vec![a, b], "app").unwrap().module.functions.len(), 1);
+
+        let a = object("a", vec![constant_fn("main", 0)], &[]);
+        let b = object("b", vec![constant_fn("main", 1)], &[]);
+        assert!(link(vec![a, b], "app").unwrap_err().to_string().contains("`main` is defined by `a`, `b`"));
+
+        let a = object("a", vec![constant_fn("helper", 1)], &[]);
+        let b = object("b", vec![constant_fn("helper", 2)], &[]);
+        let c = object("c", vec![caller_fn("main", "helper")], &["helper"]);
+        assert!(link(vec![a, b, c], "app").is_err());
+    }
+}
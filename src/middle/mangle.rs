@@ -0,0 +1,125 @@
+//! Symbol mangling
+//!
+//! Every function is emitted under a symbol derived from its path so that
+//! same-named items cannot collide in the generated C or LLVM module:
+//!
+//! - impl methods: `Type__method`
+//! - functions inside `mod` blocks: `outer__inner__name`
+//! - `pub` functions of a compilation unit: `unit__name` (what importers link against)
+//! - generic type instantiations append their arguments: `Box<i64>` -> `Box_i64`
+//! - tuples are laid out as structs named by arity and element types:
+//!   `(i64, *u8)` -> `__tuple2_i64_pu8`
+//!
+//! `main`, `extern` declarations, `#[extern_c]` exports and `#[no_mangle]`
+//! functions keep their source names, since the C runtime, foreign callers
+//! and linker scripts refer to them directly.
+
+use crate::middle::ir::IRType;
+
+/// Separator between path components in a symbol
+pub const SEPARATOR: &str = "__";
+
+/// Symbol for an item reached through `segments`, e.g. `geometry::area` -> `geometry__area`
+pub fn path_symbol<S: AsRef<str>>(segments: &[S]) -> String {
+    segments.iter().map(|s| s.as_ref()).collect::<Vec<_>>().join(SEPARATOR)
+}
+
+/// Symbol for `method` of the impl target `type_name`
+pub fn method_symbol(type_name: &str, method: &str) -> String {
+    path_symbol(&[type_name, method])
+}
+
+/// Symbol for a free function `name` declared under `module_path`
+pub fn function_symbol<S: AsRef<str>>(module_path: &[S], name: &str) -> String {
+    let mut segments: Vec<&str> = module_path.iter().map(|s| s.as_ref()).collect();
+    segments.push(name);
+    path_symbol(&segments)
+}
+
+/// Name of a generic type instantiated with `args`
+pub fn generic_type_name<S: AsRef<str>>(name: &str, args: &[S]) -> String {
+    let mut mangled = name.to_string();
+    for arg in args {
+        mangled.push('_');
+        mangled.push_str(arg.as_ref());
+    }
+    mangled
+}
+
+/// Name of the struct a tuple with `elements` is laid out as
+pub fn tuple_type_name(elements: &[IRType]) -> String {
+    let args: Vec<String> = elements.iter().map(type_tag).collect();
+    generic_type_name(&format!("__tuple{}", elements.len()), &args)
+}
+
+/// Field `index` of a tuple struct
+pub fn tuple_field(index: usize) -> String {
+    format!("_{}", index)
+}
+
+/// Identifier-safe spelling of a type inside a mangled name
+pub fn type_tag(ty: &IRType) -> String {
+    match ty {
+        IRType::Void => "void".to_string(),
+        IRType::Bool => "bool".to_string(),
+        IRType::I8 => "i8".to_string(),
+        IRType::I16 => "i16".to_string(),
+        IRType::I32 => "i32".to_string(),
+        IRType::I64 => "i64".to_string(),
+        IRType::U8 => "u8".to_string(),
+        IRType::U16 => "u16".to_string(),
+        IRType::U32 => "u32".to_string(),
+        IRType::U64 => "u64".to_string(),
+        IRType::F32 => "f32".to_string(),
+        IRType::F64 => "f64".to_string(),
+        IRType::Str => "str".to_string(),
+        IRType::Ptr(inner) => format!("p{}", type_tag(inner)),
+        IRType::Array(elem, n) => format!("a{}{}", n, type_tag(elem)),
+        IRType::Struct(name) => name.clone(),
+        IRType::Function { .. } => "fn".to_string(),
+        IRType::Vec(elem) => format!("vec{}", type_tag(elem)),
+        IRType::Map(k, v) => format!("map{}{}", type_tag(k), type_tag(v)),
+        IRType::Vector(elem, n) => format!("{}x{}", type_tag(elem), n),
+    }
+}
+
+/// Whether a function is emitted under its source name
+pub fn keeps_source_name(name: &str, unmangled: bool) -> bool {
+    name == "main" || unmangled
+}
+
+/// Symbol of a function `name` declared under `module_path` in the compilation unit `unit`
+pub fn link_name<S: AsRef<str>>(unit: &str, module_path: &[S], name: &str, is_pub: bool, unmangled: bool) -> String {
+    if keeps_source_name(name, unmangled) {
+        name.to_string()
+    } else if !module_path.is_empty() {
+        function_symbol(module_path, name)
+    } else if is_pub {
+        // Exported from the unit under its module name
+        function_symbol(&[unit], name)
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbols() {
+        assert_eq!(method_symbol("Point", "new"), "Point__new");
+        assert_eq!(function_symbol(&["geometry", "shapes"], "area"), "geometry__shapes__area");
+        assert_eq!(function_symbol::<&str>(&[], "helper"), "helper");
+        assert_eq!(generic_type_name("Matrix", &["f32", "3", "3"]), "Matrix_f32_3_3");
+        assert!(keeps_source_name("main", false));
+        assert!(keeps_source_name("point_x", true));
+        assert!(!keeps_source_name("helper", false));
+        assert_eq!(link_name::<&str>("geometry", &[], "area", true, false), "geometry__area");
+        assert_eq!(link_name::<&str>("geometry", &[], "area", false, false), "area");
+        assert_eq!(link_name("geometry", &["shapes"], "main", true, false), "main");
+        let pair = tuple_type_name(&[IRType::I64, IRType::Ptr(Box::new(IRType::U8))]);
+        assert_eq!(pair, "__tuple2_i64_pu8");
+        assert_eq!(tuple_type_name(&[IRType::Bool, IRType::Struct(pair)]), "__tuple2_bool___tuple2_i64_pu8");
+    }
+}
@@ -1,7 +1,23 @@
 //! Middle-end module - IR and optimization
 
+pub mod bench;
+pub mod collections;
+pub mod coverage;
+pub mod dot;
+pub mod fileio;
+pub mod inline;
 pub mod ir;
 pub mod ir_gen;
+pub mod ir_parser;
 pub mod ir_printer;
+pub mod lto;
+pub mod mangle;
+pub mod mono_const;
 pub mod optimize;
+pub mod overflow;
+pub mod panic;
+pub mod simd;
+pub mod ssa;
+pub mod strip;
+pub mod validate;
 
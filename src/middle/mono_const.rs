@@ -0,0 +1,139 @@
+//! Const generic monomorphization
+//!
+//! A function with const parameters (`fn fill<const N: usize>()`) is lowered
+//! once as a template in which each use of `N` is still a `Value::Global("N")`.
+//! Every call site that supplies const arguments (`fill::<4>()`) gets its own
+//! copy of the template with those globals replaced by constants, emitted as
+//! `fill_4`, so the backends only ever see concrete values.
+
+use crate::middle::ir::{Constant, IRFunction, Terminator, Value};
+use crate::types::type_system::{ConstBinOp, ConstValue};
+use crate::utils::{Error, Result};
+
+/// Value bound to each const parameter, in declaration order
+pub type ConstSubstitution = Vec<(String, ConstValue)>;
+
+/// Specializes function templates for one const substitution
+pub struct ConstMonomorphizer {
+    substitution: ConstSubstitution,
+}
+
+impl ConstMonomorphizer {
+    pub fn new(substitution: ConstSubstitution) -> Self {
+        Self { substitution }
+    }
+
+    /// Clone `template` as `name` with every const parameter replaced by its value
+    pub fn specialize(&self, template: &IRFunction, name: &str) -> Result<IRFunction> {
+        // Arguments may refer to parameters bound before them (`<N, {N + 1}>`)
+        let mut constants: Vec<(&str, Constant)> = Vec::new();
+        for (param, value) in &self.substitution {
+            let c = Self::evaluate(value, &constants).ok_or_else(|| Error::CodeGen(format!(
+                "const argument for '{}' of '{}' is not a constant: {:?}", param, template.name, value
+            )))?;
+            constants.push((param, c));
+        }
+
+        let mut func = template.clone();
+        func.name = name.to_string();
+        let substitute = |value: &mut Value| {
+            if let Value::Global(global) = value {
+                if let Some((_, c)) = constants.iter().find(|(param, _)| param == global) {
+                    *value = Value::Constant(c.clone());
+                }
+            }
+        };
+        for block in &mut func.blocks {
+            for inst in &mut block.instructions {
+                inst.operands_mut().into_iter().for_each(substitute);
+            }
+            match &mut block.terminator {
+                Some(Terminator::Return { value: Some(value) }) => substitute(value),
+                Some(Terminator::Branch { cond, .. }) => substitute(cond),
+                Some(Terminator::Switch { value, .. }) => substitute(value),
+                _ => {}
+            }
+        }
+        Ok(func)
+    }
+
+    /// Evaluate `value` against the constants bound so far
+    fn evaluate(value: &ConstValue, bound: &[(&str, Constant)]) -> Option<Constant> {
+        match value {
+            ConstValue::Bool(b) => Some(Constant::Bool(*b)),
+            ConstValue::Param(name) => bound.iter().find(|(p, _)| p == name).map(|(_, c)| c.clone()),
+            _ => Self::eval_int(value, bound).map(Constant::Int),
+        }
+    }
+
+    fn eval_int(value: &ConstValue, bound: &[(&str, Constant)]) -> Option<i64> {
+        match value {
+            ConstValue::Int(n) => Some(*n),
+            ConstValue::Bool(b) => Some(*b as i64),
+            ConstValue::Param(_) => match Self::evaluate(value, bound)? {
+                Constant::Int(n) => Some(n),
+                Constant::Bool(b) => Some(b as i64),
+                _ => None,
+            },
+            ConstValue::BinOp { op, lhs, rhs } => {
+                let (l, r) = (Self::eval_int(lhs, bound)?, Self::eval_int(rhs, bound)?);
+                match op {
+                    ConstBinOp::Add => l.checked_add(r),
+                    ConstBinOp::Sub => l.checked_sub(r),
+                    ConstBinOp::Mul => l.checked_mul(r),
+                    ConstBinOp::Div => l.checked_div(r),
+                    ConstBinOp::Mod => l.checked_rem(r),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middle::ir::{BinOp, IRType, Instruction, Register};
+
+    #[test]
+    fn test_specialize() {
+        let mut template = IRFunction::new("scaled", vec![("x".to_string(), IRType::I64)], IRType::I64);
+        let entry = template.add_block("entry");
+        let block = template.get_block_mut(entry).unwrap();
+        block.push(Instruction::BinOp {
+            dest: Register(0),
+            op: BinOp::Mul,
+            left: Value::Parameter(0),
+            right: Value::Global("N".to_string()),
+        });
+        block.push(Instruction::Call {
+            dest: None,
+            func: "log".to_string(),
+            args: vec![Value::Global("M".to_string()), Value::Global("other".to_string())],
+        });
+        block.set_terminator(Terminator::Return { value: Some(Value::Register(Register(0))) });
+
+        let mono = ConstMonomorphizer::new(vec![
+            ("N".to_string(), ConstValue::Int(4)),
+            ("M".to_string(), ConstValue::BinOp {
+                op: ConstBinOp::Add,
+                lhs: Box::new(ConstValue::Param("N".to_string())),
+                rhs: Box::new(ConstValue::Int(1)),
+            }),
+        ]);
+        let func = mono.specialize(&template, "scaled_4").unwrap();
+        assert_eq!(func.name, "scaled_4");
+        let insts = &func.blocks[0].instructions;
+        assert!(matches!(&insts[0], Instruction::BinOp { right: Value::Constant(Constant::Int(4)), .. }));
+        match &insts[1] {
+            Instruction::Call { args, .. } => {
+                assert_eq!(args[0], Value::Constant(Constant::Int(5)));
+                // Globals that are not const parameters are left alone
+                assert_eq!(args[1], Value::Global("other".to_string()));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let unresolved = ConstMonomorphizer::new(vec![("N".to_string(), ConstValue::Param("K".to_string()))]);
+        assert!(unresolved.specialize(&template, "scaled_K").is_err());
+    }
+}
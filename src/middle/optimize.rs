@@ -3,7 +3,14 @@
 //! Implements various optimization passes on Aether IR.
 #![allow(dead_code)]
 
+use std::time::Instant;
+
+use crate::feedback::CompilationStats;
+use crate::middle::inline::Inliner;
 use crate::middle::ir::*;
+use crate::middle::ssa::SSAConstruction;
+use crate::middle::validate::validate_module;
+use crate::utils::{Error, Result};
 
 /// Optimization pass trait
 pub trait OptimizationPass {
@@ -20,25 +27,52 @@ pub trait OptimizationPass {
 /// The optimizer - runs optimization passes
 pub struct Optimizer {
     passes: Vec<Box<dyn OptimizationPass>>,
+    /// Validate the IR after every pass that changes it
+    verify: bool,
 }
 
 impl Optimizer {
     pub fn new() -> Self {
-        let mut opt = Self { passes: Vec::new() };
-        // Register default passes
+        let mut opt = Self { passes: Vec::new(), verify: false };
+        // Register default passes; the others see each definition separately
+        opt.add_pass(Box::new(SSAConstruction::new()));
+        opt.add_pass(Box::new(Inliner::new()));
         opt.add_pass(Box::new(ConstantFolding::new()));
         opt.add_pass(Box::new(DeadCodeElimination::new()));
         opt.add_pass(Box::new(SimplifyBranches::new()));
         opt
     }
 
+    /// Only dead code elimination, for `--strip-dead-code` at `-O0`
+    pub fn dead_code_only() -> Self {
+        let mut opt = Self { passes: Vec::new(), verify: false };
+        opt.add_pass(Box::new(DeadCodeElimination::new()));
+        opt
+    }
+
     /// Add an optimization pass
     pub fn add_pass(&mut self, pass: Box<dyn OptimizationPass>) {
         self.passes.push(pass);
     }
 
+    /// Validate the IR after each pass, naming the pass that broke it
+    pub fn with_verification(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
     /// Run all passes on the module
-    pub fn optimize(&mut self, module: &mut IRModule) {
+    pub fn optimize(&mut self, module: &mut IRModule) -> Result<()> {
+        self.run(module, None)
+    }
+
+    /// Like `optimize`, recording each pass's time and the module's
+    /// instruction count around it in `stats.passes`
+    pub fn optimize_with_stats(&mut self, module: &mut IRModule, stats: &mut CompilationStats) -> Result<()> {
+        self.run(module, Some(stats))
+    }
+
+    fn run(&mut self, module: &mut IRModule, mut stats: Option<&mut CompilationStats>) -> Result<()> {
         let mut changed = true;
         let max_iterations = 10;
         let mut iteration = 0;
@@ -47,12 +81,25 @@ impl Optimizer {
         while changed && iteration < max_iterations {
             changed = false;
             for pass in &mut self.passes {
-                if pass.run_on_module(module) {
+                let measured = stats.is_some().then(|| (Instant::now(), module.instruction_count()));
+                let pass_changed = pass.run_on_module(module);
+                if let (Some(stats), Some((start, before))) = (stats.as_deref_mut(), measured) {
+                    let time_ms = start.elapsed().as_secs_f64() * 1000.0;
+                    stats.record_pass(pass.name(), time_ms, before, module.instruction_count());
+                }
+                if pass_changed {
                     changed = true;
+                    if self.verify {
+                        validate_module(module).map_err(|e| match e {
+                            Error::InvalidIr(report) => Error::InvalidIr(format!("after pass '{}':\n{}", pass.name(), report)),
+                            other => other,
+                        })?;
+                    }
                 }
             }
             iteration += 1;
         }
+        Ok(())
     }
 }
 
@@ -199,16 +246,7 @@ impl DeadCodeElimination {
 
             if let Some(block) = func.blocks.get(block_id.0) {
                 if let Some(ref term) = block.terminator {
-                    match term {
-                        Terminator::Jump { target } => {
-                            worklist.push(*target);
-                        }
-                        Terminator::Branch { then_target, else_target, .. } => {
-                            worklist.push(*then_target);
-                            worklist.push(*else_target);
-                        }
-                        Terminator::Return { .. } | Terminator::Unreachable => {}
-                    }
+                    worklist.extend(term.successors());
                 }
             }
         }
@@ -224,6 +262,61 @@ impl DeadCodeElimination {
 
         func.blocks.len() < original_len
     }
+
+    /// Remove instructions whose results are never used, along with stores
+    /// into stack slots that are never read. Instructions with side effects
+    /// (calls, inline asm, volatile loads and stores) are always kept.
+    fn remove_dead_instructions(&self, func: &mut IRFunction) -> bool {
+        use std::collections::HashSet;
+
+        let allocas: HashSet<Register> = func.blocks.iter()
+            .flat_map(|b| &b.instructions)
+            .filter_map(|inst| match inst {
+                Instruction::Alloca { dest, .. } => Some(*dest),
+                _ => None,
+            })
+            .collect();
+
+        // Registers that are read, ignoring plain stores into stack slots
+        let mut used = HashSet::new();
+        for block in &func.blocks {
+            for inst in &block.instructions {
+                let operands = match inst {
+                    Instruction::Store { ptr: Value::Register(r), value, volatile: false }
+                        if allocas.contains(r) => vec![value],
+                    _ => inst.operands(),
+                };
+                for value in operands {
+                    if let Value::Register(r) = value {
+                        used.insert(*r);
+                    }
+                }
+            }
+            if let Some(term) = &block.terminator {
+                for value in term.operands() {
+                    if let Value::Register(r) = value {
+                        used.insert(*r);
+                    }
+                }
+            }
+        }
+
+        let mut changed = false;
+        for block in &mut func.blocks {
+            let before = block.instructions.len();
+            block.instructions.retain(|inst| match inst {
+                Instruction::Store { ptr: Value::Register(r), volatile: false, .. }
+                    if allocas.contains(r) => used.contains(r),
+                _ if inst.has_side_effects() => true,
+                _ => {
+                    let defs = inst.defs();
+                    defs.is_empty() || defs.iter().any(|d| used.contains(d))
+                }
+            });
+            changed |= block.instructions.len() != before;
+        }
+        changed
+    }
 }
 
 impl OptimizationPass for DeadCodeElimination {
@@ -242,7 +335,9 @@ impl OptimizationPass for DeadCodeElimination {
     }
 
     fn run_on_function(&mut self, func: &mut IRFunction) -> bool {
-        self.remove_unreachable_blocks(func)
+        let removed_blocks = self.remove_unreachable_blocks(func);
+        let removed_insts = self.remove_dead_instructions(func);
+        removed_blocks || removed_insts
     }
 }
 
@@ -290,6 +385,11 @@ impl OptimizationPass for SimplifyBranches {
                         *term = Terminator::Jump { target };
                         changed = true;
                     }
+                } else if let Terminator::Switch { value: Value::Constant(Constant::Int(n)), cases, default } = term {
+                    // Switch on a constant: jump straight to the matching case
+                    let target = cases.iter().find(|(case, _)| case == n).map(|(_, t)| *t).unwrap_or(*default);
+                    *term = Terminator::Jump { target };
+                    changed = true;
                 }
             }
         }
@@ -535,15 +635,40 @@ mod tests {
             value: Some(Value::Register(Register(0))) 
         });
 
-        let mut optimizer = Optimizer::new();
-        optimizer.optimize(&mut module);
+        let mut optimizer = Optimizer::new().with_verification(true);
+        optimizer.optimize(&mut module).unwrap();
 
         // Should be folded to 5
-        if let Instruction::Assign { value, .. } = &module.functions[0].blocks[0].instructions[0] {
-            if let Value::Constant(Constant::Int(n)) = value {
-                assert_eq!(*n, 5);
-            }
+        if let Instruction::Assign { value: Value::Constant(Constant::Int(n)), .. } = &module.functions[0].blocks[0].instructions[0] {
+            assert_eq!(*n, 5);
         }
     }
-}
 
+    #[test]
+    fn test_dce_keeps_volatile_store() {
+        let mut module = make_module();
+        let block = &mut module.functions[0].blocks[0];
+        block.push(Instruction::Alloca { dest: Register(0), ty: IRType::I32 });
+        block.push(Instruction::Alloca { dest: Register(1), ty: IRType::I32 });
+        // Neither slot is ever read back
+        block.push(Instruction::Store {
+            ptr: Value::Register(Register(0)),
+            value: Value::Constant(Constant::Int(1)),
+            volatile: false,
+        });
+        block.push(Instruction::Store {
+            ptr: Value::Register(Register(1)),
+            value: Value::Constant(Constant::Int(2)),
+            volatile: true,
+        });
+        block.set_terminator(Terminator::Return { value: None });
+
+        let mut optimizer = Optimizer::new().with_verification(true);
+        optimizer.optimize(&mut module).unwrap();
+
+        let insts = &module.functions[0].blocks[0].instructions;
+        assert_eq!(insts.len(), 2);
+        assert!(matches!(insts[0], Instruction::Alloca { dest: Register(1), .. }));
+        assert!(matches!(insts[1], Instruction::Store { volatile: true, .. }));
+    }
+}
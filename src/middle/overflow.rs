@@ -0,0 +1,69 @@
+//! Integer overflow behavior (`--overflow-mode`)
+//!
+//! By default `+`, `-` and `*` on integers wrap. In the other modes they lower
+//! to `CheckedBinOp`, which also yields an overflow flag: `panicking` branches
+//! to `__aeth_panic("integer overflow", ...)` when it is set, `saturating`
+//! replaces the result with the type's minimum or maximum.
+
+use crate::middle::ir::{BinOp, IRType};
+
+/// What integer arithmetic does when the result does not fit its type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowMode {
+    /// Two's complement wrap-around
+    #[default]
+    Wrapping,
+    /// Clamp to the type's minimum or maximum
+    Saturating,
+    /// Report a runtime panic at the operation
+    Panicking,
+}
+
+impl OverflowMode {
+    pub fn parse(name: &str) -> std::result::Result<Self, String> {
+        match name {
+            "wrapping" => Ok(Self::Wrapping),
+            "saturating" => Ok(Self::Saturating),
+            "panicking" => Ok(Self::Panicking),
+            _ => Err(format!(
+                "unknown overflow mode '{}' (expected wrapping, saturating or panicking)", name
+            )),
+        }
+    }
+}
+
+/// Whether `op` on `ty` has a checked form
+pub fn is_checked(op: BinOp, ty: &IRType) -> bool {
+    matches!(op, BinOp::Add | BinOp::Sub | BinOp::Mul) && bounds(ty).is_some()
+}
+
+/// Minimum and maximum of an integer type
+pub fn bounds(ty: &IRType) -> Option<(i64, i64)> {
+    Some(match ty {
+        IRType::I8 => (i8::MIN as i64, i8::MAX as i64),
+        IRType::I16 => (i16::MIN as i64, i16::MAX as i64),
+        IRType::I32 => (i32::MIN as i64, i32::MAX as i64),
+        IRType::I64 => (i64::MIN, i64::MAX),
+        IRType::U8 => (0, u8::MAX as i64),
+        IRType::U16 => (0, u16::MAX as i64),
+        IRType::U32 => (0, u32::MAX as i64),
+        // Bit pattern of u64::MAX
+        IRType::U64 => (0, -1),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_bounds() {
+        assert_eq!(OverflowMode::parse("saturating"), Ok(OverflowMode::Saturating));
+        assert!(OverflowMode::parse("trapping").is_err());
+        assert_eq!(bounds(&IRType::I8), Some((-128, 127)));
+        assert!(is_checked(BinOp::Mul, &IRType::U32));
+        assert!(!is_checked(BinOp::Div, &IRType::I32));
+        assert!(!is_checked(BinOp::Add, &IRType::F64));
+    }
+}
@@ -0,0 +1,115 @@
+//! Runtime failures (`assert`, contracts, checked operations)
+//!
+//! Every checked failure lowers to `call __aeth_panic(message, file, line)`
+//! followed by `unreachable`. The runtime prints the message and location to
+//! stderr and aborts.
+//!
+//! With `--panic=unwind-trace` each function additionally calls
+//! `__aeth_trace_enter(name)` on entry and `__aeth_trace_exit()` before every
+//! return, maintaining a thread-local shadow stack that the panic handler
+//! prints as a backtrace. The bookkeeping costs a call per function entry and
+//! exit, so the default `--panic=abort` leaves it out.
+
+use crate::middle::ir::{Constant, IRFunction, IRModule, Instruction, Terminator, Value};
+
+/// Runtime function reporting a failure and aborting
+pub const PANIC_FN: &str = "__aeth_panic";
+/// Runtime function pushing a frame onto the shadow stack
+pub const TRACE_ENTER_FN: &str = "__aeth_trace_enter";
+/// Runtime function popping the innermost frame
+pub const TRACE_EXIT_FN: &str = "__aeth_trace_exit";
+
+/// What the program records so that a panic can explain itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PanicMode {
+    /// Print the message and location, then abort
+    #[default]
+    Abort,
+    /// Also keep a shadow stack and print it as a backtrace
+    UnwindTrace,
+}
+
+impl PanicMode {
+    pub fn parse(name: &str) -> std::result::Result<Self, String> {
+        match name {
+            "abort" => Ok(Self::Abort),
+            "unwind-trace" => Ok(Self::UnwindTrace),
+            _ => Err(format!("unknown panic mode '{}' (expected abort or unwind-trace)", name)),
+        }
+    }
+}
+
+/// Push `func` onto the shadow stack as `name` on entry and pop it before each return
+pub fn instrument_trace(func: &mut IRFunction, name: &str) {
+    for (index, block) in func.blocks.iter_mut().enumerate() {
+        if index == 0 {
+            let at = block.instructions.iter()
+                .position(|inst| !matches!(inst, Instruction::Phi { .. }))
+                .unwrap_or(block.instructions.len());
+            block.instructions.insert(at, Instruction::Call {
+                dest: None,
+                func: TRACE_ENTER_FN.to_string(),
+                args: vec![Value::Constant(Constant::String(name.to_string()))],
+            });
+        }
+        if matches!(block.terminator, Some(Terminator::Return { .. })) {
+            block.push(Instruction::Call { dest: None, func: TRACE_EXIT_FN.to_string(), args: vec![] });
+        }
+    }
+}
+
+/// Whether `func` calls one of `runtime_fns`
+fn calls_any(func: &IRFunction, runtime_fns: &[&str]) -> bool {
+    func.blocks.iter().flat_map(|b| &b.instructions).any(|inst| matches!(
+        inst,
+        Instruction::Call { func, .. } if runtime_fns.contains(&func.as_str())
+    ))
+}
+
+/// Whether `module` calls into the panic runtime
+pub fn uses_runtime(module: &IRModule) -> bool {
+    module.functions.iter().any(|f| calls_any(f, &[PANIC_FN, TRACE_ENTER_FN, TRACE_EXIT_FN]))
+}
+
+/// Mode `module` was instrumented for, so IR read back from text keeps its backtraces
+pub fn mode_of(module: &IRModule) -> PanicMode {
+    if module.functions.iter().any(|f| calls_any(f, &[TRACE_ENTER_FN])) {
+        PanicMode::UnwindTrace
+    } else {
+        PanicMode::Abort
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middle::ir::IRType;
+
+    #[test]
+    fn test_instrument_trace() {
+        let mut func = IRFunction::new("f", vec![], IRType::Void);
+        let entry = func.add_block("entry");
+        let exit = func.add_block("exit");
+        func.get_block_mut(entry).unwrap().set_terminator(Terminator::Jump { target: exit });
+        func.get_block_mut(exit).unwrap().set_terminator(Terminator::Return { value: None });
+        let mut module = IRModule::new("m");
+        module.functions.push(func.clone());
+        assert!(!uses_runtime(&module));
+        assert_eq!(mode_of(&module), PanicMode::Abort);
+
+        instrument_trace(&mut func, "geo::f");
+        assert!(matches!(
+            &func.blocks[0].instructions[..],
+            [Instruction::Call { func, args, .. }] if func == TRACE_ENTER_FN && args[0] == Value::Constant(Constant::String("geo::f".to_string()))
+        ));
+        assert!(matches!(
+            &func.blocks[1].instructions[..],
+            [Instruction::Call { func, .. }] if func == TRACE_EXIT_FN
+        ));
+        module.functions = vec![func];
+        assert!(uses_runtime(&module));
+        assert_eq!(mode_of(&module), PanicMode::UnwindTrace);
+        assert_eq!(PanicMode::parse("unwind-trace"), Ok(PanicMode::UnwindTrace));
+        assert!(PanicMode::parse("unwind").is_err());
+    }
+}
@@ -0,0 +1,181 @@
+//! SIMD vector intrinsics: `__simd_<vector>_<op>`, e.g. `__simd_f32x8_add`
+//!
+//! Every vector type in `VECTORS` has the `new`, `splat`, `add`, `sub`,
+//! `mul`, `sum`, `load` and `store` intrinsics; float vectors also have
+//! `div`. The f32x4, f64x2 and i32x4 operations are additionally available
+//! without the `__simd_` prefix. `load` and `store` do not require aligned
+//! pointers. Backends map each intrinsic to their own instructions.
+
+use crate::middle::ir::{IRModule, IRType, Instruction};
+
+/// Element type name and lane count of each vector type
+pub const VECTORS: [(&str, usize); 8] = [
+    ("f32", 4), ("f32", 8),
+    ("f64", 2), ("f64", 4),
+    ("i32", 4), ("i32", 8),
+    ("i64", 2), ("i64", 4),
+];
+
+/// Vectors whose operations also exist without the `__simd_` prefix
+pub const UNPREFIXED: [&str; 3] = ["f32x4", "f64x2", "i32x4"];
+
+/// Operation of an intrinsic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimdOp {
+    /// Build a vector from one scalar per lane
+    New,
+    /// Set every lane to one scalar
+    Splat,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    /// Horizontal sum of the lanes
+    Sum,
+    /// Read a vector from `*elem`
+    Load,
+    /// Write a vector to `*elem`
+    Store,
+}
+
+impl SimdOp {
+    pub const ALL: [SimdOp; 9] = [
+        SimdOp::New, SimdOp::Splat, SimdOp::Add, SimdOp::Sub, SimdOp::Mul,
+        SimdOp::Div, SimdOp::Sum, SimdOp::Load, SimdOp::Store,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SimdOp::New => "new",
+            SimdOp::Splat => "splat",
+            SimdOp::Add => "add",
+            SimdOp::Sub => "sub",
+            SimdOp::Mul => "mul",
+            SimdOp::Div => "div",
+            SimdOp::Sum => "sum",
+            SimdOp::Load => "load",
+            SimdOp::Store => "store",
+        }
+    }
+}
+
+/// A call to a SIMD intrinsic
+#[derive(Debug, Clone, PartialEq)]
+pub struct Intrinsic {
+    pub elem: IRType,
+    pub lanes: usize,
+    pub op: SimdOp,
+}
+
+impl Intrinsic {
+    pub fn vector(&self) -> IRType {
+        IRType::Vector(Box::new(self.elem.clone()), self.lanes)
+    }
+
+    /// Width of the vector in bytes
+    pub fn width(&self) -> usize {
+        self.elem.size_bytes() * self.lanes
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self.elem, IRType::F32 | IRType::F64)
+    }
+
+    /// Parameter and return types
+    pub fn signature(&self) -> (Vec<IRType>, IRType) {
+        let vector = self.vector();
+        let ptr = IRType::Ptr(Box::new(self.elem.clone()));
+        match self.op {
+            SimdOp::New => (vec![self.elem.clone(); self.lanes], vector),
+            SimdOp::Splat => (vec![self.elem.clone()], vector),
+            SimdOp::Add | SimdOp::Sub | SimdOp::Mul | SimdOp::Div => (vec![vector.clone(), vector.clone()], vector),
+            SimdOp::Sum => (vec![vector], self.elem.clone()),
+            SimdOp::Load => (vec![ptr], vector),
+            SimdOp::Store => (vec![ptr, vector], IRType::Void),
+        }
+    }
+}
+
+/// Scalar type of a lane name in `VECTORS`
+fn lane_type(name: &str) -> Option<IRType> {
+    Some(match name {
+        "f32" => IRType::F32,
+        "f64" => IRType::F64,
+        "i32" => IRType::I32,
+        "i64" => IRType::I64,
+        _ => return None,
+    })
+}
+
+/// Name of every intrinsic, with its vector's element name and lane count
+pub fn intrinsics() -> impl Iterator<Item = (String, &'static str, usize, SimdOp)> {
+    VECTORS.iter().flat_map(|&(elem, lanes)| {
+        SimdOp::ALL.iter()
+            .filter(move |op| **op != SimdOp::Div || elem.starts_with('f'))
+            .flat_map(move |&op| {
+                let vector = format!("{}x{}", elem, lanes);
+                let unprefixed = UNPREFIXED.contains(&vector.as_str())
+                    .then(|| format!("{}_{}", vector, op.name()));
+                std::iter::once(format!("__simd_{}_{}", vector, op.name()))
+                    .chain(unprefixed)
+                    .map(move |name| (name, elem, lanes, op))
+            })
+    })
+}
+
+/// The intrinsic `func` calls, if any
+pub fn parse(func: &str) -> Option<Intrinsic> {
+    let (name, prefixed) = match func.strip_prefix("__simd_") {
+        Some(name) => (name, true),
+        None => (func, false),
+    };
+    let (vector, op) = name.split_once('_')?;
+    if !prefixed && !UNPREFIXED.contains(&vector) {
+        return None;
+    }
+    let (elem, lanes) = vector.split_once('x')?;
+    let lanes: usize = lanes.parse().ok()?;
+    if !VECTORS.contains(&(elem, lanes)) {
+        return None;
+    }
+    let op = *SimdOp::ALL.iter().find(|o| o.name() == op)?;
+    let intrinsic = Intrinsic { elem: lane_type(elem)?, lanes, op };
+    (op != SimdOp::Div || intrinsic.is_float()).then_some(intrinsic)
+}
+
+/// Parameter and return types of an intrinsic
+pub fn signature(func: &str) -> Option<(Vec<IRType>, IRType)> {
+    parse(func).map(|intrinsic| intrinsic.signature())
+}
+
+/// Whether `module` uses a vector wider than `bytes`
+pub fn uses_vectors_wider_than(module: &IRModule, bytes: usize) -> bool {
+    let wide = |ty: &IRType| matches!(ty, IRType::Vector(elem, lanes) if elem.size_bytes() * lanes > bytes);
+    module.functions.iter().any(|f| {
+        wide(&f.ret_type)
+            || f.params.iter().any(|(_, ty)| wide(ty))
+            || f.blocks.iter().flat_map(|b| &b.instructions).any(|inst| match inst {
+                Instruction::Alloca { ty, .. } => wide(ty),
+                Instruction::Call { func, .. } => parse(func).is_some_and(|i| wide(&i.vector())),
+                _ => false,
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_intrinsics() {
+        let add = parse("__simd_f32x8_add").unwrap();
+        assert_eq!((add.elem.clone(), add.lanes, add.op), (IRType::F32, 8, SimdOp::Add));
+        assert_eq!(add.width(), 32);
+        assert_eq!(parse("i32x4_mul").unwrap().op, SimdOp::Mul);
+        assert!(parse("i64x2_add").is_none());
+        assert!(parse("__simd_i64x2_div").is_none());
+        assert!(parse("__simd_f32x3_add").is_none());
+        assert_eq!(signature("__simd_i64x4_sum"), Some((vec![IRType::Vector(Box::new(IRType::I64), 4)], IRType::I64)));
+        assert!(intrinsics().all(|(name, ..)| parse(&name).is_some()));
+    }
+}
@@ -0,0 +1,554 @@
+//! SSA construction
+//!
+//! IR generation assigns a mutable variable's register once per assignment,
+//! so one register can be written in several places. This pass gives every
+//! definition its own register: phis are placed at the iterated dominance
+//! frontiers of each such register's definitions (only where it is live), and
+//! uses are renamed while walking the dominator tree.
+//!
+//! Registers that are written once are already in SSA form and keep their
+//! numbers; the IR validator guarantees their definition dominates every use.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::middle::ir::*;
+use crate::middle::optimize::OptimizationPass;
+
+/// Rewrites each function so that no register is written more than once
+pub struct SSAConstruction;
+
+impl SSAConstruction {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for SSAConstruction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OptimizationPass for SSAConstruction {
+    fn name(&self) -> &'static str {
+        "ssa-construction"
+    }
+
+    fn run_on_module(&mut self, module: &mut IRModule) -> bool {
+        let mut changed = false;
+        for func in &mut module.functions {
+            if self.run_on_function(func) {
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    fn run_on_function(&mut self, func: &mut IRFunction) -> bool {
+        if func.blocks.is_empty() {
+            return false;
+        }
+        let vars = multiply_defined(func);
+        if vars.is_empty() {
+            return false;
+        }
+
+        let cfg = Cfg::new(func);
+        // Unreachable blocks have no place in the dominator tree; empty them
+        // the way dead-code elimination does
+        for (b, block) in func.blocks.iter_mut().enumerate() {
+            if cfg.rpo_index[b].is_none() {
+                block.instructions.clear();
+                block.terminator = Some(Terminator::Unreachable);
+            }
+        }
+
+        let phis = place_phis(func, &cfg, &vars);
+        Renamer::new(func, &vars).rename(func, &cfg, &phis);
+        true
+    }
+}
+
+/// Registers with more than one definition
+fn multiply_defined(func: &IRFunction) -> HashSet<Register> {
+    let mut seen = HashSet::new();
+    let mut vars = HashSet::new();
+    for inst in func.blocks.iter().flat_map(|b| &b.instructions) {
+        for reg in inst.defs() {
+            if !seen.insert(reg) {
+                vars.insert(reg);
+            }
+        }
+    }
+    vars
+}
+
+/// Control flow facts for the reachable part of a function
+struct Cfg {
+    /// Reachable blocks in reverse postorder
+    rpo: Vec<usize>,
+    /// Position of each block in `rpo`; `None` if unreachable
+    rpo_index: Vec<Option<usize>>,
+    /// Distinct reachable predecessors of each block
+    preds: Vec<Vec<usize>>,
+    /// Distinct successors of each block
+    succs: Vec<Vec<usize>>,
+    /// Immediate dominator; the entry is its own
+    idom: Vec<Option<usize>>,
+    /// Dominator tree children, in reverse postorder
+    children: Vec<Vec<usize>>,
+}
+
+impl Cfg {
+    fn new(func: &IRFunction) -> Self {
+        let n = func.blocks.len();
+        let entry = func.entry_block.0;
+        let mut succs: Vec<Vec<usize>> = func.blocks.iter()
+            .map(|block| {
+                let mut targets = Vec::new();
+                for BlockId(t) in block.terminator.iter().flat_map(|term| term.successors()) {
+                    if t < n && !targets.contains(&t) {
+                        targets.push(t);
+                    }
+                }
+                targets
+            })
+            .collect();
+
+        let rpo = reverse_postorder(entry, &succs);
+        let mut rpo_index = vec![None; n];
+        for (i, &b) in rpo.iter().enumerate() {
+            rpo_index[b] = Some(i);
+        }
+        for (b, targets) in succs.iter_mut().enumerate() {
+            if rpo_index[b].is_none() {
+                targets.clear();
+            }
+        }
+        let mut preds = vec![Vec::new(); n];
+        for &b in &rpo {
+            for &s in &succs[b] {
+                preds[s].push(b);
+            }
+        }
+
+        let idom = dominators(entry, &rpo, &rpo_index, &preds);
+        let mut children = vec![Vec::new(); n];
+        for &b in &rpo {
+            if let Some(d) = idom[b] {
+                if d != b {
+                    children[d].push(b);
+                }
+            }
+        }
+        Self { rpo, rpo_index, preds, succs, idom, children }
+    }
+
+    /// Dominance frontier of every block (Cooper, Harvey and Kennedy)
+    fn frontiers(&self) -> Vec<HashSet<usize>> {
+        let mut df = vec![HashSet::new(); self.preds.len()];
+        for &b in &self.rpo {
+            if self.preds[b].len() < 2 {
+                continue;
+            }
+            let Some(dom) = self.idom[b] else { continue };
+            for &p in &self.preds[b] {
+                let mut runner = p;
+                while runner != dom {
+                    df[runner].insert(b);
+                    match self.idom[runner] {
+                        Some(next) if next != runner => runner = next,
+                        _ => break,
+                    }
+                }
+            }
+        }
+        df
+    }
+}
+
+fn reverse_postorder(entry: usize, succs: &[Vec<usize>]) -> Vec<usize> {
+    let mut visited = vec![false; succs.len()];
+    let mut order = Vec::new();
+    // (block, index of the next successor to visit)
+    let mut stack = vec![(entry, 0)];
+    visited[entry] = true;
+    while let Some(&mut (b, ref mut next)) = stack.last_mut() {
+        if let Some(&s) = succs[b].get(*next) {
+            *next += 1;
+            if !visited[s] {
+                visited[s] = true;
+                stack.push((s, 0));
+            }
+        } else {
+            order.push(b);
+            stack.pop();
+        }
+    }
+    order.reverse();
+    order
+}
+
+/// Immediate dominators by the iterative algorithm of Cooper, Harvey and Kennedy
+fn dominators(entry: usize, rpo: &[usize], rpo_index: &[Option<usize>], preds: &[Vec<usize>]) -> Vec<Option<usize>> {
+    let mut idom = vec![None; preds.len()];
+    idom[entry] = Some(entry);
+    let intersect = |idom: &[Option<usize>], mut a: usize, mut b: usize| {
+        while a != b {
+            while rpo_index[a] > rpo_index[b] {
+                a = idom[a].expect("processed block has a dominator");
+            }
+            while rpo_index[b] > rpo_index[a] {
+                b = idom[b].expect("processed block has a dominator");
+            }
+        }
+        a
+    };
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &b in rpo.iter().skip(1) {
+            let mut new_idom = None;
+            for &p in &preds[b] {
+                if idom[p].is_none() {
+                    continue;
+                }
+                new_idom = Some(match new_idom {
+                    None => p,
+                    Some(current) => intersect(&idom, p, current),
+                });
+            }
+            if new_idom.is_some() && idom[b] != new_idom {
+                idom[b] = new_idom;
+                changed = true;
+            }
+        }
+    }
+    idom
+}
+
+/// Variables live on entry to each block
+fn live_in(func: &IRFunction, cfg: &Cfg, vars: &HashSet<Register>) -> Vec<HashSet<Register>> {
+    let n = func.blocks.len();
+    let mut uses = vec![HashSet::new(); n];
+    let mut defs = vec![HashSet::new(); n];
+    // Phi operands are read at the end of the incoming block
+    let mut phi_uses = vec![HashSet::new(); n];
+    for &b in &cfg.rpo {
+        let block = &func.blocks[b];
+        for inst in &block.instructions {
+            if let Instruction::Phi { incoming, .. } = inst {
+                for (value, pred) in incoming {
+                    if let Value::Register(r) = value {
+                        if vars.contains(r) && pred.0 < n {
+                            phi_uses[pred.0].insert(*r);
+                        }
+                    }
+                }
+            } else {
+                for r in registers(inst.operands()) {
+                    if vars.contains(&r) && !defs[b].contains(&r) {
+                        uses[b].insert(r);
+                    }
+                }
+            }
+            for r in inst.defs() {
+                if vars.contains(&r) {
+                    defs[b].insert(r);
+                }
+            }
+        }
+        for r in registers(block.terminator.iter().flat_map(|t| t.operands()).collect()) {
+            if vars.contains(&r) && !defs[b].contains(&r) {
+                uses[b].insert(r);
+            }
+        }
+    }
+
+    let mut live = vec![HashSet::new(); n];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &b in cfg.rpo.iter().rev() {
+            let mut out = phi_uses[b].clone();
+            for &s in &cfg.succs[b] {
+                out.extend(live[s].iter().copied());
+            }
+            let mut inn = uses[b].clone();
+            inn.extend(out.into_iter().filter(|r| !defs[b].contains(r)));
+            if inn.len() != live[b].len() {
+                live[b] = inn;
+                changed = true;
+            }
+        }
+    }
+    live
+}
+
+fn registers(values: Vec<&Value>) -> impl Iterator<Item = Register> + '_ {
+    values.into_iter().filter_map(|v| match v {
+        Value::Register(r) => Some(*r),
+        _ => None,
+    })
+}
+
+/// Insert an empty phi for each variable at the iterated dominance frontier
+/// of its definitions, where it is live. Returns the variable of each new
+/// phi, per block, in the order they were placed at the block's start.
+fn place_phis(func: &mut IRFunction, cfg: &Cfg, vars: &HashSet<Register>) -> Vec<Vec<Register>> {
+    let n = func.blocks.len();
+    let live = live_in(func, cfg, vars);
+    let df = cfg.frontiers();
+
+    let mut def_blocks: HashMap<Register, Vec<usize>> = HashMap::new();
+    for &b in &cfg.rpo {
+        for r in func.blocks[b].instructions.iter().flat_map(|i| i.defs()) {
+            if vars.contains(&r) {
+                let blocks = def_blocks.entry(r).or_default();
+                if !blocks.contains(&b) {
+                    blocks.push(b);
+                }
+            }
+        }
+    }
+
+    let mut phis: Vec<Vec<Register>> = vec![Vec::new(); n];
+    // Deterministic order: by register number
+    let mut ordered: Vec<Register> = vars.iter().copied().collect();
+    ordered.sort_by_key(|r| r.0);
+    for var in ordered {
+        let mut worklist = def_blocks.get(&var).cloned().unwrap_or_default();
+        let mut has_phi = HashSet::new();
+        let mut queued: HashSet<usize> = worklist.iter().copied().collect();
+        while let Some(x) = worklist.pop() {
+            let mut frontier: Vec<usize> = df[x].iter().copied().collect();
+            frontier.sort_unstable();
+            for y in frontier {
+                if !live[y].contains(&var) || !has_phi.insert(y) {
+                    continue;
+                }
+                phis[y].push(var);
+                if queued.insert(y) {
+                    worklist.push(y);
+                }
+            }
+        }
+    }
+
+    for (b, vars) in phis.iter().enumerate() {
+        let new_phis = vars.iter().map(|&var| Instruction::Phi { dest: var, incoming: Vec::new() });
+        func.blocks[b].instructions.splice(0..0, new_phis);
+    }
+    phis
+}
+
+/// Renames definitions and uses of the multiply-defined registers
+struct Renamer<'a> {
+    vars: &'a HashSet<Register>,
+    /// Current version of each variable, innermost last
+    stacks: HashMap<Register, Vec<Register>>,
+    next_reg: usize,
+}
+
+impl<'a> Renamer<'a> {
+    fn new(func: &IRFunction, vars: &'a HashSet<Register>) -> Self {
+        let max_reg = func.blocks.iter()
+            .flat_map(|b| &b.instructions)
+            .flat_map(|inst| inst.defs().into_iter().chain(registers(inst.operands()).collect::<Vec<_>>()))
+            .map(|r| r.0)
+            .max()
+            .unwrap_or(0);
+        Self { vars, stacks: HashMap::new(), next_reg: max_reg + 1 }
+    }
+
+    fn current(&self, reg: Register) -> Option<Register> {
+        self.stacks.get(&reg).and_then(|s| s.last().copied())
+    }
+
+    fn rename_use(&self, value: &mut Value) {
+        if let Value::Register(r) = value {
+            if self.vars.contains(r) {
+                if let Some(current) = self.current(*r) {
+                    *r = current;
+                }
+            }
+        }
+    }
+
+    /// Walks the dominator tree from the entry; `phis` gives the variable of
+    /// the phis placed at the start of each block
+    fn rename(&mut self, func: &mut IRFunction, cfg: &Cfg, phis: &[Vec<Register>]) {
+        enum Step {
+            Enter(usize),
+            /// Pop the versions pushed by a block
+            Exit(Vec<Register>),
+        }
+        let mut steps = vec![Step::Enter(func.entry_block.0)];
+        while let Some(step) = steps.pop() {
+            match step {
+                Step::Enter(b) => {
+                    let pushed = self.rename_block(func, cfg, phis, b);
+                    steps.push(Step::Exit(pushed));
+                    for &child in cfg.children[b].iter().rev() {
+                        steps.push(Step::Enter(child));
+                    }
+                }
+                Step::Exit(pushed) => {
+                    for var in pushed {
+                        if let Some(stack) = self.stacks.get_mut(&var) {
+                            stack.pop();
+                        }
+                    }
+                }
+            }
+        }
+
+        // List phi inputs in predecessor order, so the first one is defined
+        // before the phi in block order as often as possible
+        for (b, block) in func.blocks.iter_mut().enumerate() {
+            for inst in block.instructions.iter_mut().take(phis[b].len()) {
+                if let Instruction::Phi { incoming, .. } = inst {
+                    incoming.sort_by_key(|(_, pred)| cfg.rpo_index[pred.0]);
+                }
+            }
+        }
+    }
+
+    fn rename_block(&mut self, func: &mut IRFunction, cfg: &Cfg, phis: &[Vec<Register>], b: usize) -> Vec<Register> {
+        let mut pushed = Vec::new();
+        let block = &mut func.blocks[b];
+        for inst in &mut block.instructions {
+            if !matches!(inst, Instruction::Phi { .. }) {
+                for value in inst.operands_mut() {
+                    self.rename_use(value);
+                }
+            }
+            for dest in defs_mut(inst) {
+                if self.vars.contains(dest) {
+                    let version = Register(self.next_reg);
+                    self.next_reg += 1;
+                    self.stacks.entry(*dest).or_default().push(version);
+                    pushed.push(*dest);
+                    *dest = version;
+                }
+            }
+        }
+        if let Some(term) = &mut block.terminator {
+            for value in term.operands_mut() {
+                self.rename_use(value);
+            }
+        }
+
+        for &s in &cfg.succs[b] {
+            let placed = phis[s].len();
+            for (i, inst) in func.blocks[s].instructions.iter_mut().enumerate() {
+                let Instruction::Phi { incoming, .. } = inst else { break };
+                if i < placed {
+                    // A path on which the variable is never assigned gets no input
+                    if let Some(current) = self.current(phis[s][i]) {
+                        incoming.push((Value::Register(current), BlockId(b)));
+                    }
+                } else {
+                    for (value, pred) in incoming.iter_mut() {
+                        if pred.0 == b {
+                            self.rename_use(value);
+                        }
+                    }
+                }
+            }
+        }
+        pushed
+    }
+}
+
+/// Registers written by an instruction, as `Instruction::defs` lists them
+fn defs_mut(inst: &mut Instruction) -> Vec<&mut Register> {
+    match inst {
+        Instruction::Assign { dest, .. }
+        | Instruction::BinOp { dest, .. }
+        | Instruction::UnaryOp { dest, .. }
+        | Instruction::Alloca { dest, .. }
+        | Instruction::Load { dest, .. }
+        | Instruction::GetElementPtr { dest, .. }
+        | Instruction::Phi { dest, .. }
+        | Instruction::Cast { dest, .. } => vec![dest],
+        Instruction::CheckedBinOp { dest, overflow_dest, .. } => vec![dest, overflow_dest],
+        Instruction::Call { dest, .. } | Instruction::CallIndirect { dest, .. } => dest.iter_mut().collect(),
+        Instruction::InlineAsm { operands, .. } => operands.iter_mut().filter_map(|op| op.output.as_mut()).collect(),
+        Instruction::Store { .. } | Instruction::DebugLoc(_) => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middle::validate::validate_module;
+
+    /// `let mut i = 0; while i < n { i = i + 1 }; return i`
+    fn counting_loop() -> IRFunction {
+        let mut func = IRFunction::new("count", vec![("n".to_string(), IRType::I64)], IRType::I64);
+        let entry = func.add_block("entry");
+        let cond = func.add_block("cond");
+        let body = func.add_block("body");
+        let exit = func.add_block("exit");
+        let i = Register(0);
+        func.blocks[entry.0].push(Instruction::Assign { dest: i, value: Value::Constant(Constant::Int(0)) });
+        func.blocks[entry.0].set_terminator(Terminator::Jump { target: cond });
+        func.blocks[cond.0].push(Instruction::BinOp {
+            dest: Register(1), op: BinOp::Lt, left: Value::Register(i), right: Value::Parameter(0),
+        });
+        func.blocks[cond.0].set_terminator(Terminator::Branch {
+            cond: Value::Register(Register(1)), then_target: body, else_target: exit,
+        });
+        func.blocks[body.0].push(Instruction::BinOp {
+            dest: Register(2), op: BinOp::Add, left: Value::Register(i), right: Value::Constant(Constant::Int(1)),
+        });
+        func.blocks[body.0].push(Instruction::Assign { dest: i, value: Value::Register(Register(2)) });
+        func.blocks[body.0].set_terminator(Terminator::Jump { target: cond });
+        func.blocks[exit.0].set_terminator(Terminator::Return { value: Some(Value::Register(i)) });
+        func
+    }
+
+    #[test]
+    fn test_loop_variable_gets_phi() {
+        let mut func = counting_loop();
+        assert!(SSAConstruction::new().run_on_function(&mut func));
+        assert!(multiply_defined(&func).is_empty());
+
+        // One phi in the loop header merging the initial and incremented values
+        let header = &func.blocks[1].instructions;
+        let Instruction::Phi { dest: phi, incoming } = &header[0] else { panic!("{:?}", header) };
+        let initial = func.blocks[0].instructions[0].dest().unwrap();
+        let incremented = func.blocks[2].instructions[1].dest().unwrap();
+        assert_eq!(incoming, &vec![(Value::Register(initial), BlockId(0)), (Value::Register(incremented), BlockId(2))]);
+        assert!(matches!(&header[1], Instruction::BinOp { left: Value::Register(r), .. } if r == phi));
+        assert_eq!(func.blocks[3].terminator, Some(Terminator::Return { value: Some(Value::Register(*phi)) }));
+
+        let mut module = IRModule::new("test");
+        module.functions.push(func);
+        validate_module(&module).unwrap();
+        // Already in SSA form
+        assert!(!SSAConstruction::new().run_on_module(&mut module));
+    }
+
+    #[test]
+    fn test_no_phi_where_dead() {
+        // x is assigned on both arms but never read after the join
+        let mut func = IRFunction::new("f", vec![("c".to_string(), IRType::Bool)], IRType::Void);
+        let entry = func.add_block("entry");
+        let then = func.add_block("then");
+        let other = func.add_block("else");
+        let join = func.add_block("join");
+        func.blocks[entry.0].set_terminator(Terminator::Branch { cond: Value::Parameter(0), then_target: then, else_target: other });
+        for (b, n) in [(then, 1), (other, 2)] {
+            func.blocks[b.0].push(Instruction::Assign { dest: Register(0), value: Value::Constant(Constant::Int(n)) });
+            func.blocks[b.0].push(Instruction::Call { dest: None, func: "use".to_string(), args: vec![Value::Register(Register(0))] });
+            func.blocks[b.0].set_terminator(Terminator::Jump { target: join });
+        }
+        func.blocks[join.0].set_terminator(Terminator::Return { value: None });
+
+        assert!(SSAConstruction::new().run_on_function(&mut func));
+        assert!(func.blocks[join.0].instructions.is_empty());
+        assert!(multiply_defined(&func).is_empty());
+    }
+}
@@ -0,0 +1,58 @@
+//! `--strip`: remove debug info and coverage instrumentation from a module
+//!
+//! Source locations (`DebugLoc` and each function's `debug_loc`) become
+//! `#line` directives or DWARF in the backends; coverage counters are
+//! `__aeth_cov_*` calls plus the runtime `module.coverage` asks for. Symbol
+//! names are dropped later, by the linker.
+
+use crate::middle::ir::{IRModule, Instruction};
+
+/// Prefix of every coverage runtime symbol
+const COVERAGE_PREFIX: &str = "__aeth_cov_";
+
+/// Remove source locations and coverage instrumentation from `module`,
+/// returning the number of instructions removed
+pub fn strip_module(module: &mut IRModule) -> usize {
+    let mut removed = 0;
+    for func in &mut module.functions {
+        func.debug_loc = None;
+        for block in &mut func.blocks {
+            let before = block.instructions.len();
+            block.instructions.retain(|inst| match inst {
+                Instruction::DebugLoc(_) => false,
+                Instruction::Call { dest: None, func, .. } => !func.starts_with(COVERAGE_PREFIX),
+                _ => true,
+            });
+            removed += before - block.instructions.len();
+        }
+    }
+    module.coverage = None;
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middle::coverage::{CoverageMap, HIT_FN};
+    use crate::middle::ir::{Constant, IRFunction, IRType, SourceLoc, Value};
+
+    #[test]
+    fn test_strip_module() {
+        let mut module = IRModule::new("m");
+        let mut func = IRFunction::new("f", vec![], IRType::Void);
+        let entry = func.add_block("entry");
+        let loc = SourceLoc { file: "f.aeth".to_string(), line: 1, col: 1 };
+        func.debug_loc = Some(loc.clone());
+        let block = func.get_block_mut(entry).unwrap();
+        block.push(Instruction::Call { dest: None, func: HIT_FN.to_string(), args: vec![Value::Constant(Constant::Int(0))] });
+        block.push(Instruction::DebugLoc(loc));
+        block.push(Instruction::Call { dest: None, func: "g".to_string(), args: vec![] });
+        module.functions.push(func);
+        module.coverage = Some(CoverageMap { file: "f.aeth".to_string(), data_path: "f.aethcov".to_string(), points: vec![(1, 1)] });
+
+        assert_eq!(strip_module(&mut module), 2);
+        let func = &module.functions[0];
+        assert_eq!(func.blocks[0].instructions, vec![Instruction::Call { dest: None, func: "g".to_string(), args: vec![] }]);
+        assert!(func.debug_loc.is_none() && module.coverage.is_none());
+    }
+}
@@ -0,0 +1,385 @@
+//! IR Validator
+//!
+//! Structural checks on Aether IR, run after IR generation and (with
+//! `--verify-ir`) after every optimizer pass, so that malformed IR is reported
+//! at its source instead of as an LLVM verifier dump or broken C:
+//!
+//! - every block ends with a terminator
+//! - jump, branch and phi targets are blocks of the function
+//! - every register is defined on every path to each of its uses
+//! - calls pass as many arguments as the callee declares
+//! - loads, stores and GEPs go through pointers and match the pointee type
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::middle::ir::*;
+use crate::middle::ir_printer::{instruction_to_string, terminator_to_string};
+use crate::utils::{Error, Result};
+
+/// A single validation failure
+#[derive(Debug, Clone, PartialEq)]
+pub struct IRViolation {
+    pub function: String,
+    pub block: String,
+    /// The offending instruction or terminator, as printed by `ir_printer`
+    pub instruction: String,
+    pub message: String,
+}
+
+impl fmt::Display for IRViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fn {}, block {}: {}", self.function, self.block, self.message)?;
+        if !self.instruction.is_empty() {
+            write!(f, "\n    {}", self.instruction)?;
+        }
+        Ok(())
+    }
+}
+
+/// Validate a module, returning `Error::InvalidIr` listing every violation
+pub fn validate_module(module: &IRModule) -> Result<()> {
+    let violations = IRValidator::new(module).validate();
+    if violations.is_empty() {
+        return Ok(());
+    }
+    let report: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+    Err(Error::InvalidIr(report.join("\n")))
+}
+
+/// Checks the functions of one module
+pub struct IRValidator<'m> {
+    module: &'m IRModule,
+    violations: Vec<IRViolation>,
+}
+
+/// Where a register is defined: (block index, instruction index)
+type DefSite = (usize, usize);
+
+impl<'m> IRValidator<'m> {
+    pub fn new(module: &'m IRModule) -> Self {
+        Self { module, violations: Vec::new() }
+    }
+
+    /// Run all checks and return the violations found
+    pub fn validate(mut self) -> Vec<IRViolation> {
+        for func in &self.module.functions {
+            self.validate_function(func);
+        }
+        self.violations
+    }
+
+    fn report(&mut self, func: &IRFunction, block: &BasicBlock, instruction: String, message: String) {
+        self.violations.push(IRViolation {
+            function: func.name.clone(),
+            block: block.label.clone(),
+            instruction,
+            message,
+        });
+    }
+
+    fn validate_function(&mut self, func: &IRFunction) {
+        // Declarations have no body to check
+        if func.blocks.is_empty() {
+            return;
+        }
+
+        let defs = collect_defs(func);
+        let types = infer_register_types(func);
+        let successors = successors(func);
+
+        for (b, block) in func.blocks.iter().enumerate() {
+            for (i, inst) in block.instructions.iter().enumerate() {
+                let printed = instruction_to_string(inst);
+
+                if let Instruction::Phi { incoming, .. } = inst {
+                    for (value, pred) in incoming {
+                        if pred.0 >= func.blocks.len() {
+                            self.report(func, block, printed.clone(), format!("phi refers to missing block bb{}", pred.0));
+                        } else {
+                            // The value must be available at the end of the incoming block
+                            let end = func.blocks[pred.0].instructions.len();
+                            self.check_value(func, block, &printed, value, (pred.0, end), &defs, &successors);
+                        }
+                    }
+                } else {
+                    for value in inst.operands() {
+                        self.check_value(func, block, &printed, value, (b, i), &defs, &successors);
+                    }
+                }
+
+                self.check_call(func, block, &printed, inst);
+                self.check_memory(func, block, &printed, inst, &types);
+            }
+
+            match &block.terminator {
+                None => self.report(func, block, String::new(), "block has no terminator".to_string()),
+                Some(term) => {
+                    let printed = terminator_to_string(term);
+                    for target in term.successors() {
+                        if target.0 >= func.blocks.len() {
+                            self.report(func, block, printed.clone(), format!("branch to missing block bb{}", target.0));
+                        }
+                    }
+                    let end = block.instructions.len();
+                    for value in term.operands() {
+                        self.check_value(func, block, &printed, value, (b, end), &defs, &successors);
+                    }
+                }
+            }
+        }
+    }
+
+    /// A register must be defined on every path from the entry to `at`; parameters must exist
+    #[allow(clippy::too_many_arguments)]
+    fn check_value(
+        &mut self,
+        func: &IRFunction,
+        block: &BasicBlock,
+        printed: &str,
+        value: &Value,
+        at: DefSite,
+        defs: &HashMap<Register, Vec<DefSite>>,
+        successors: &[Vec<usize>],
+    ) {
+        match value {
+            Value::Register(reg) => {
+                let Some(sites) = defs.get(reg) else {
+                    self.report(func, block, printed.to_string(), format!("use of undefined register {}", reg));
+                    return;
+                };
+                if !defined_on_all_paths(func.entry_block.0, successors, sites, at) {
+                    self.report(func, block, printed.to_string(), format!("register {} is used before it is defined", reg));
+                }
+            }
+            Value::Parameter(i) if *i >= func.params.len() => {
+                self.report(func, block, printed.to_string(), format!("use of missing parameter arg{}", i));
+            }
+            _ => {}
+        }
+    }
+
+    /// Calls to functions of this module (or externs) must match the declared arity
+    fn check_call(&mut self, func: &IRFunction, block: &BasicBlock, printed: &str, inst: &Instruction) {
+        let Instruction::Call { func: callee, args, .. } = inst else { return };
+        if let Some(target) = self.module.functions.iter().find(|f| &f.name == callee) {
+            if args.len() != target.params.len() {
+                self.report(func, block, printed.to_string(), format!(
+                    "call to '{}' passes {} argument(s), but it takes {}", callee, args.len(), target.params.len()
+                ));
+            }
+        } else if let Some(target) = self.module.externs.iter().find(|e| &e.name == callee) {
+            // Extern C functions may be variadic, so only a shortfall is an error
+            if args.len() < target.params.len() {
+                self.report(func, block, printed.to_string(), format!(
+                    "call to '{}' passes {} argument(s), but it takes at least {}", callee, args.len(), target.params.len()
+                ));
+            }
+        }
+    }
+
+    /// Memory accesses must go through pointers; accesses to an `alloca` must use its type
+    fn check_memory(&mut self, func: &IRFunction, block: &BasicBlock, printed: &str, inst: &Instruction, types: &RegisterTypes) {
+        let (ptr, accessed) = match inst {
+            Instruction::Load { ptr, ty, .. } => (ptr, Some(ty.clone())),
+            Instruction::Store { ptr, value, .. } => (ptr, value_type(value, types)),
+            Instruction::GetElementPtr { ptr, .. } => (ptr, None),
+            _ => return,
+        };
+        let ptr_ty = value_type(ptr, types);
+        if let Some(ty) = &ptr_ty {
+            if !matches!(ty, IRType::Ptr(_)) {
+                self.report(func, block, printed.to_string(), format!("address operand has non-pointer type {:?}", ty));
+                return;
+            }
+        }
+        // Only slots allocated in this function have a reliable pointee type
+        let Value::Register(reg) = ptr else { return };
+        let (Some(slot), Some(accessed)) = (types.allocas.get(reg), accessed) else { return };
+        if !types_compatible(slot, &accessed, value_is_int_constant(inst)) {
+            self.report(func, block, printed.to_string(), format!(
+                "accesses a {:?} slot as {:?}", slot, accessed
+            ));
+        }
+    }
+}
+
+/// Every definition site of every register (asm outputs and overflow flags included)
+fn collect_defs(func: &IRFunction) -> HashMap<Register, Vec<DefSite>> {
+    let mut defs: HashMap<Register, Vec<DefSite>> = HashMap::new();
+    for (b, block) in func.blocks.iter().enumerate() {
+        for (i, inst) in block.instructions.iter().enumerate() {
+            for reg in inst.defs() {
+                defs.entry(reg).or_default().push((b, i));
+            }
+        }
+    }
+    defs
+}
+
+/// Successor block indices of each block
+fn successors(func: &IRFunction) -> Vec<Vec<usize>> {
+    func.blocks.iter()
+        .map(|block| block.terminator.as_ref()
+            .map(|term| term.successors().into_iter().map(|t| t.0).collect())
+            .unwrap_or_default())
+        .collect()
+}
+
+/// Whether every path from `entry` to `at` passes one of the definition `sites`.
+/// Registers may be assigned on several paths (e.g. one per `match` arm), so
+/// this is a search for a definition-free path rather than a dominance check.
+fn defined_on_all_paths(entry: usize, successors: &[Vec<usize>], sites: &[DefSite], at: DefSite) -> bool {
+    if sites.iter().any(|&(b, i)| b == at.0 && i < at.1) {
+        return true;
+    }
+    let defining: HashSet<usize> = sites.iter().map(|&(b, _)| b).collect();
+    let mut visited = HashSet::new();
+    let mut stack = vec![entry];
+    while let Some(b) = stack.pop() {
+        if b == at.0 {
+            return false;
+        }
+        if b >= successors.len() || defining.contains(&b) || !visited.insert(b) {
+            continue;
+        }
+        stack.extend(successors[b].iter().copied());
+    }
+    true
+}
+
+/// Register types recoverable from the instructions themselves
+#[derive(Default)]
+struct RegisterTypes {
+    types: HashMap<Register, IRType>,
+    /// Pointee type of each `alloca` result
+    allocas: HashMap<Register, IRType>,
+}
+
+fn infer_register_types(func: &IRFunction) -> RegisterTypes {
+    let mut types = RegisterTypes::default();
+    for inst in func.blocks.iter().flat_map(|b| &b.instructions) {
+        let ty = match inst {
+            Instruction::Alloca { dest, ty } => {
+                types.allocas.entry(*dest).or_insert_with(|| ty.clone());
+                Some((*dest, IRType::Ptr(Box::new(ty.clone()))))
+            }
+            Instruction::Load { dest, ty, .. } => Some((*dest, ty.clone())),
+            Instruction::Cast { dest, ty, .. } => Some((*dest, ty.clone())),
+            _ => None,
+        };
+        if let Some((reg, ty)) = ty {
+            // A register assigned on several paths keeps its first type
+            types.types.entry(reg).or_insert(ty);
+        }
+    }
+    types
+}
+
+fn value_type(value: &Value, types: &RegisterTypes) -> Option<IRType> {
+    match value {
+        Value::Register(reg) => types.types.get(reg).cloned(),
+        Value::Constant(Constant::Float(_)) => Some(IRType::F64),
+        Value::Constant(Constant::Bool(_)) => Some(IRType::Bool),
+        Value::Constant(Constant::String(_)) => Some(IRType::Ptr(Box::new(IRType::U8))),
+        _ => None,
+    }
+}
+
+/// Integer constants are emitted untyped, so they fit any scalar slot
+fn value_is_int_constant(inst: &Instruction) -> bool {
+    matches!(inst, Instruction::Store { value: Value::Constant(Constant::Int(_) | Constant::Null), .. })
+}
+
+fn is_integer(ty: &IRType) -> bool {
+    matches!(ty, IRType::Bool | IRType::I8 | IRType::I16 | IRType::I32 | IRType::I64
+        | IRType::U8 | IRType::U16 | IRType::U32 | IRType::U64)
+}
+
+fn is_float(ty: &IRType) -> bool {
+    matches!(ty, IRType::F32 | IRType::F64)
+}
+
+/// Whether a slot of type `slot` may be accessed as `accessed`. Pointers are
+/// opaque (as in LLVM) and integers of different widths are converted by
+/// both backends, so only the value category has to match.
+fn types_compatible(slot: &IRType, accessed: &IRType, int_constant: bool) -> bool {
+    if int_constant || slot == accessed {
+        return true;
+    }
+    match (slot, accessed) {
+        (IRType::Ptr(_), IRType::Ptr(_)) => true,
+        (a, b) if is_integer(a) && is_integer(b) => true,
+        (a, b) if is_float(a) && is_float(b) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module_with(func: IRFunction) -> IRModule {
+        let mut module = IRModule::new("test");
+        module.functions.push(func);
+        module
+    }
+
+    #[test]
+    fn test_valid_function() {
+        let mut func = IRFunction::new("f", vec![("x".to_string(), IRType::I64)], IRType::I64);
+        let entry = func.add_block("entry");
+        let exit = func.add_block("exit");
+        let block = func.get_block_mut(entry).unwrap();
+        block.push(Instruction::Alloca { dest: Register(0), ty: IRType::I64 });
+        block.push(Instruction::Store { ptr: Value::Register(Register(0)), value: Value::Parameter(0), volatile: false });
+        block.set_terminator(Terminator::Jump { target: exit });
+        let block = func.get_block_mut(exit).unwrap();
+        block.push(Instruction::Load { dest: Register(1), ptr: Value::Register(Register(0)), ty: IRType::I64, volatile: false });
+        block.set_terminator(Terminator::Return { value: Some(Value::Register(Register(1))) });
+
+        assert!(validate_module(&module_with(func)).is_ok());
+    }
+
+    #[test]
+    fn test_violations() {
+        let mut callee = IRFunction::new("callee", vec![("a".to_string(), IRType::I64)], IRType::Void);
+        callee.add_block("entry");
+        callee.blocks[0].set_terminator(Terminator::Return { value: None });
+
+        let mut func = IRFunction::new("broken", vec![], IRType::Void);
+        let entry = func.add_block("entry");
+        let then = func.add_block("then");
+        let join = func.add_block("join");
+        func.add_block("dangling");
+        let block = func.get_block_mut(entry).unwrap();
+        block.push(Instruction::Alloca { dest: Register(0), ty: IRType::F64 });
+        block.push(Instruction::Store { ptr: Value::Register(Register(0)), value: Value::Constant(Constant::Bool(true)), volatile: false });
+        block.push(Instruction::Call { dest: None, func: "callee".to_string(), args: vec![] });
+        block.set_terminator(Terminator::Branch { cond: Value::Register(Register(9)), then_target: then, else_target: join });
+        let block = func.get_block_mut(then).unwrap();
+        block.push(Instruction::Assign { dest: Register(1), value: Value::Constant(Constant::Int(1)) });
+        block.set_terminator(Terminator::Jump { target: join });
+        let block = func.get_block_mut(join).unwrap();
+        block.push(Instruction::BinOp { dest: Register(2), op: BinOp::Add, left: Value::Register(Register(1)), right: Value::Constant(Constant::Int(1)) });
+        block.set_terminator(Terminator::Jump { target: BlockId(7) });
+
+        let mut module = module_with(func);
+        module.functions.push(callee);
+        let violations = IRValidator::new(&module).validate();
+        let messages: Vec<&str> = violations.iter().map(|v| v.message.as_str()).collect();
+        assert_eq!(messages, vec![
+            "accesses a F64 slot as Bool",
+            "call to 'callee' passes 0 argument(s), but it takes 1",
+            "use of undefined register %9",
+            "register %1 is used before it is defined",
+            "branch to missing block bb7",
+            "block has no terminator",
+        ]);
+        assert_eq!(violations[1].block, "entry");
+        assert_eq!(violations[1].instruction, "call callee()");
+
+        let err = validate_module(&module).unwrap_err().to_string();
+        assert!(err.contains("fn broken, block join: register %1 is used before it is defined"), "{}", err);
+    }
+}
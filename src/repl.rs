@@ -0,0 +1,375 @@
+//! `aethc repl`: evaluate statements and expressions interactively
+//!
+//! The session is a synthetic program: every accepted item, then a `main`
+//! that replays every accepted statement before running the new input, so
+//! `let` bindings and functions persist across lines. Each input is checked
+//! against an analyzer that has already seen the session's items (only a new
+//! item is analyzed), then the program is built with the C backend and run in
+//! a child `aethc run`. Output before a marker line belongs to the replayed
+//! statements and is hidden. Input that fails to compile or run leaves the
+//! session as it was.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+use std::process::{self, Command, Stdio};
+
+use crate::frontend::ast::{BinOp, Expr, Item, Program, Stmt};
+use crate::frontend::lexer::Lexer;
+use crate::frontend::parser::Parser;
+use crate::frontend::semantic::SemanticAnalyzer;
+use crate::middle::ir_gen::IRGenerator;
+use crate::middle::ir_printer::print_ir;
+use crate::types::{PrimitiveType, ResolvedType};
+use crate::utils::{Error, Result};
+
+/// Printed by the synthetic `main` between the replayed and the new statements
+const MARKER: &str = "__aethc_repl_marker__";
+
+/// Declaration added to a session that prints a float
+const PRINTF_EXTERN: &str = "extern \"C\" {\n    fn printf(fmt: *u8, ...) -> i32;\n}";
+
+/// Opens the function new statements are parsed in
+const SNIPPET_PREFIX: &str = "fn __repl_snippet() {";
+
+/// Run the read-eval-print loop until `:quit` or end of input
+pub fn repl(compiler_args: Vec<OsString>) {
+    let exe = std::env::current_exe().unwrap_or_else(|e| {
+        eprintln!("Error: cannot locate the compiler executable: {}", e);
+        process::exit(1);
+    });
+    let dir = std::env::temp_dir().join(format!("aether_repl_{}", process::id()));
+    if let Err(e) = fs::create_dir_all(&dir) {
+        eprintln!("Error: cannot create {}: {}", dir.display(), e);
+        process::exit(1);
+    }
+
+    let interactive = io::stdin().is_terminal();
+    if interactive {
+        println!("AetherLang REPL. :type <expr> shows a type, :ir the last program's IR, :quit exits.");
+    }
+    let mut session = Session::new(exe, compiler_args, dir.join("session.aeth"));
+    let mut lines = io::stdin().lock().lines();
+    while let Some(input) = read_input(&mut lines, interactive) {
+        let input = input.trim();
+        match input {
+            "" => {}
+            ":quit" | ":q" => break,
+            ":ir" => match &session.last_program {
+                Some(source) => match program_ir(source) {
+                    Ok(ir) => print!("{}", ir),
+                    Err(e) => report(&e),
+                },
+                None => println!("no input yet"),
+            },
+            _ => {
+                if let Some(expr) = input.strip_prefix(":type") {
+                    match session.type_of(expr) {
+                        Ok(ty) => println!("{}", ty),
+                        Err(e) => report(&e),
+                    }
+                } else if input.starts_with(':') {
+                    eprintln!("unknown command '{}' (expected :type, :ir or :quit)", input);
+                } else {
+                    session.eval(input);
+                }
+            }
+        }
+        let _ = io::stdout().flush();
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+/// Accepted input and the analyzer that has checked it
+struct Session {
+    exe: PathBuf,
+    compiler_args: Vec<OsString>,
+    /// Where the synthetic program is written for the child compiler
+    path: PathBuf,
+    items: Vec<String>,
+    stmts: Vec<String>,
+    /// Parsed `stmts`, replayed when checking new input
+    parsed_stmts: Vec<Stmt>,
+    /// Has analyzed `items`
+    analyzer: SemanticAnalyzer,
+    /// Synthetic program built for the last input
+    last_program: Option<String>,
+}
+
+/// How the value of a trailing expression is printed
+enum Printer {
+    Int,
+    Float,
+    Bool,
+    Str,
+}
+
+impl Printer {
+    fn for_type(ty: &ResolvedType) -> Option<Self> {
+        match ty {
+            ResolvedType::Primitive(p) if p.is_integer() => Some(Self::Int),
+            ResolvedType::Primitive(p) if p.is_float() => Some(Self::Float),
+            ResolvedType::Primitive(PrimitiveType::Bool) => Some(Self::Bool),
+            ResolvedType::Str => Some(Self::Str),
+            _ => None,
+        }
+    }
+
+    fn statement(&self, expr: &str) -> String {
+        match self {
+            Self::Int => format!("println_i64(({}) as i64)", expr),
+            Self::Float => format!("unsafe {{ printf(\"%g\\n\\0\" as *u8, ({}) as f64) }}", expr),
+            Self::Bool => format!("if {} {{ println(\"true\") }} else {{ println(\"false\") }}", expr),
+            Self::Str => format!("println({})", expr),
+        }
+    }
+}
+
+impl Session {
+    fn new(exe: PathBuf, compiler_args: Vec<OsString>, path: PathBuf) -> Self {
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.set_module_name("repl");
+        Self {
+            exe,
+            compiler_args,
+            path,
+            items: Vec::new(),
+            stmts: Vec::new(),
+            parsed_stmts: Vec::new(),
+            analyzer,
+            last_program: None,
+        }
+    }
+
+    /// Type of `expr` in the session, without running anything
+    fn type_of(&self, expr: &str) -> Result<ResolvedType> {
+        let mut stmts = self.parsed_stmts.clone();
+        stmts.extend(parse_snippet(expr)?);
+        self.analyzer.clone().check_snippet(&stmts)
+    }
+
+    fn eval(&mut self, input: &str) {
+        let program = Parser::new(Lexer::new(input, 0)).parse_program();
+        match program {
+            Ok(program) if !program.items.is_empty() => self.eval_items(input, program),
+            _ => self.eval_stmts(input),
+        }
+    }
+
+    fn eval_items(&mut self, input: &str, program: Program) {
+        if program.items.iter().any(|i| matches!(i, Item::Function(f) if f.name.name == "main")) {
+            eprintln!("error: `main` is generated by the REPL; give the function another name");
+            return;
+        }
+        // Analyze only the new items, on a copy that is kept if they build
+        let mut analyzer = self.analyzer.clone();
+        if let Err(e) = analyzer.analyze(&program) {
+            report(&e);
+            return;
+        }
+        let mut items = self.items.clone();
+        items.push(input.to_string());
+        let stmts = self.stmts.clone();
+        if self.run(&items, &stmts, &[]) {
+            self.items = items;
+            self.analyzer = analyzer;
+        }
+    }
+
+    fn eval_stmts(&mut self, input: &str) {
+        let parsed = match parse_snippet(input) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                report(&e);
+                return;
+            }
+        };
+        let mut stmts = self.parsed_stmts.clone();
+        stmts.extend(parsed.iter().cloned());
+        let ty = match self.analyzer.clone().check_snippet(&stmts) {
+            Ok(ty) => ty,
+            Err(e) => {
+                report(&e);
+                return;
+            }
+        };
+
+        // A trailing expression with a printable value is shown, not kept
+        let printer = match parsed.last() {
+            Some(Stmt::Expr(expr)) if !is_assignment(expr) => Printer::for_type(&ty),
+            _ => None,
+        };
+        let new_stmts = match &printer {
+            Some(printer) => {
+                let tail = parsed.last().map(Stmt::span).unwrap_or_default();
+                let (body, value) = input.split_at((tail.start - SNIPPET_PREFIX.len()).min(input.len()));
+                vec![body.to_string(), printer.statement(value.trim())]
+            }
+            None => vec![input.to_string()],
+        };
+        let (mut items, stmts) = (self.items.clone(), self.stmts.clone());
+        // Floats are printed with printf, unless the session declared it already
+        if matches!(printer, Some(Printer::Float)) && !items.iter().any(|item| item.contains("fn printf(")) {
+            items.push(PRINTF_EXTERN.to_string());
+        }
+        if self.run(&items, &stmts, &new_stmts) {
+            self.stmts.push(new_stmts[0].clone());
+            let kept = if printer.is_some() { &parsed[..parsed.len() - 1] } else { &parsed[..] };
+            self.parsed_stmts.extend(kept.iter().cloned());
+        }
+    }
+
+    /// Build and run the session with `new` statements after the marker;
+    /// prints the new output and whether everything succeeded
+    fn run(&mut self, items: &[String], stmts: &[String], new: &[String]) -> bool {
+        let source = synthesize(items, stmts, new);
+        self.last_program = Some(source.clone());
+        if let Err(e) = fs::write(&self.path, &source) {
+            eprintln!("error: cannot write {}: {}", self.path.display(), e);
+            return false;
+        }
+        let output = Command::new(&self.exe)
+            .args(&self.compiler_args)
+            .arg("run")
+            .arg(&self.path)
+            .stdin(Stdio::null())
+            .output();
+        let output = match output {
+            Ok(output) => output,
+            Err(e) => {
+                eprintln!("error: cannot run compiler: {}", e);
+                return false;
+            }
+        };
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        match stdout.split_once(&format!("{}\n", MARKER)) {
+            Some((_, new_output)) => {
+                print!("{}", new_output);
+                eprint!("{}", stderr);
+                output.status.success()
+            }
+            None => {
+                // Failed before the program started: show the compiler's errors
+                eprint!("{}", stderr);
+                if stderr.is_empty() {
+                    eprintln!("error: the program did not run");
+                }
+                false
+            }
+        }
+    }
+}
+
+fn is_assignment(expr: &Expr) -> bool {
+    matches!(expr, Expr::Binary {
+        op: BinOp::Assign | BinOp::AddAssign | BinOp::SubAssign | BinOp::MulAssign | BinOp::DivAssign,
+        ..
+    })
+}
+
+/// Read one input, continuing onto more lines while brackets are unbalanced
+fn read_input(lines: &mut impl Iterator<Item = io::Result<String>>, interactive: bool) -> Option<String> {
+    let mut input = String::new();
+    loop {
+        if interactive {
+            print!("{}", if input.is_empty() { "aeth> " } else { "  ... " });
+            let _ = io::stdout().flush();
+        }
+        match lines.next() {
+            Some(Ok(line)) => {
+                input.push_str(&line);
+                input.push('\n');
+            }
+            _ => return (!input.is_empty()).then_some(input),
+        }
+        if input.trim_start().starts_with(':') || bracket_depth(&input) <= 0 {
+            return Some(input);
+        }
+    }
+}
+
+/// Open `(`, `[` and `{` minus closed ones, outside string and char literals
+fn bracket_depth(source: &str) -> i32 {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    for c in source.chars() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                _ => {}
+            },
+        }
+    }
+    depth
+}
+
+/// Statements of `input`, parsed as a function body. Their spans are
+/// offsets into `input` plus `SNIPPET_PREFIX.len()`.
+fn parse_snippet(input: &str) -> Result<Vec<Stmt>> {
+    let source = format!("{}{}\n}}", SNIPPET_PREFIX, input);
+    let program = Parser::new(Lexer::new(&source, 0)).parse_program()?;
+    Ok(match program.items.into_iter().next() {
+        Some(Item::Function(f)) => f.body.stmts,
+        _ => Vec::new(),
+    })
+}
+
+/// Source of the session program
+fn synthesize(items: &[String], stmts: &[String], new: &[String]) -> String {
+    // Session bindings are often never read again
+    let mut source = String::from("#![allow(unused)]\n");
+    for item in items {
+        source.push_str(item.trim_end());
+        source.push_str("\n\n");
+    }
+    source.push_str("fn main() effect[io, alloc, panic] {\n");
+    for stmt in stmts {
+        source.push_str(stmt.trim_end());
+        source.push('\n');
+    }
+    source.push_str(&format!("println(\"{}\")\n", MARKER));
+    for stmt in new {
+        source.push_str(stmt.trim_end());
+        source.push('\n');
+    }
+    source.push_str("}\n");
+    source
+}
+
+/// IR of a session program, as `--emit-ir` prints it
+fn program_ir(source: &str) -> Result<String> {
+    let program = Parser::new(Lexer::new(source, 0)).parse_program()?;
+    let module = IRGenerator::new("repl").generate(&program)?;
+    Ok(print_ir(&module))
+}
+
+fn report(error: &Error) {
+    eprintln!("error[{}]: {}", error.code(), error);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bracket_depth() {
+        assert_eq!(bracket_depth("fn f() {"), 1);
+        assert_eq!(bracket_depth("fn f() {\n}\n"), 0);
+        assert_eq!(bracket_depth("println(\"{(\")"), 0);
+        assert_eq!(bracket_depth("let c: u8 = '{'"), 0);
+    }
+}
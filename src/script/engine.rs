@@ -27,6 +27,12 @@ pub enum ComptimeValue {
     None,
 }
 
+impl Default for ComptimeEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ComptimeEngine {
     pub fn new() -> Self {
         Self {
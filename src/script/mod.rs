@@ -3,5 +3,7 @@ pub mod token;
 pub mod ast;
 pub mod parser;
 pub mod transpiler;
+pub mod transform;
+pub mod source_map;
 pub mod engine;
 pub mod ffi;
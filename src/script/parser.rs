@@ -427,7 +427,7 @@ impl Parser {
 
 // Helpers for Expr span extraction
 impl Expr {
-    fn span_owned(&self) -> Span {
+    pub(crate) fn span_owned(&self) -> Span {
         match self {
             Expr::Identifier { span, .. } => *span,
             Expr::Integer { span, .. } => *span,
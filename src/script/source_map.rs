@@ -0,0 +1,129 @@
+//! Maps spans in Core transpiled from Aether Script back to the script
+//!
+//! The transpiler precedes every statement with a `SourceMarker` naming its
+//! line and column in the script, and the glue it adds (closing braces, the
+//! extern prelude) with one naming the enclosing statement. The markers are
+//! comments, so they survive the `SourceTransformer` rewrite and stay in the
+//! `.gen.aeth` file next to the code they describe.
+
+use crate::utils::{SourceMarker, Span};
+
+/// Where a span of the generated Core came from in the script
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptSpan {
+    /// Byte offsets in the script source
+    pub start: usize,
+    pub end: usize,
+    /// Whether the span is in glue code; it then covers the start of the
+    /// statement the glue was generated for
+    pub generated: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceMap {
+    generated: String,
+    /// Byte offset at which each line of the generated source starts
+    generated_lines: Vec<usize>,
+    /// Line of each marker in the generated source, and the marker
+    markers: Vec<(usize, SourceMarker)>,
+    script: String,
+    script_lines: Vec<usize>,
+}
+
+fn line_starts(text: &str) -> Vec<usize> {
+    std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect()
+}
+
+impl SourceMap {
+    /// The map of `generated`, transpiled from `script`, read from its markers
+    pub fn new(generated: &str, script: &str) -> Self {
+        let markers = generated.lines().enumerate()
+            .filter_map(|(i, line)| Some((i + 1, SourceMarker::parse(line)?)))
+            .filter(|(_, marker)| marker.column.is_some())
+            .collect();
+        Self {
+            generated: generated.to_string(),
+            generated_lines: line_starts(generated),
+            markers,
+            script: script.to_string(),
+            script_lines: line_starts(script),
+        }
+    }
+
+    /// The script span `span` of the generated source comes from, or `None`
+    /// if it precedes every marker
+    pub fn map(&self, span: Span) -> Option<ScriptSpan> {
+        let start = span.start.min(self.generated.len());
+        let end = span.end.clamp(start, self.generated.len());
+        let line = self.generated_lines.partition_point(|&s| s <= start);
+        let (marker_line, marker) = self.markers.iter().rev().find(|(at, _)| *at < line)?;
+
+        let (script_line, statement) = self.script_offset(marker.line, marker.column.unwrap_or(1));
+        let script_text = self.line_text(&self.script, &self.script_lines, script_line);
+        let statement_end = script_text.trim_end().len().max(statement - self.script_lines[script_line - 1]);
+        let statement_end = self.script_lines[script_line - 1] + statement_end;
+        if marker.generated || line != marker_line + 1 {
+            return Some(ScriptSpan { start: statement, end: statement_end, generated: true });
+        }
+
+        // The statement's own line: find the spanned text in the script line,
+        // nearest to where its offset from the statement start puts it
+        let generated_text = self.line_text(&self.generated, &self.generated_lines, line);
+        let indent = generated_text.len() - generated_text.trim_start().len();
+        let column = (start - self.generated_lines[line - 1]).saturating_sub(indent);
+        let estimate = (statement + column).min(statement_end);
+        let text = &self.generated[start..end];
+        let line_start = self.script_lines[script_line - 1];
+        let found = (!text.is_empty() && !text.contains('\n'))
+            .then(|| script_text.match_indices(text).map(|(i, _)| line_start + i).min_by_key(|&i| i.abs_diff(estimate)))
+            .flatten();
+        Some(match found {
+            Some(found) => ScriptSpan { start: found, end: found + text.len(), generated: false },
+            None => ScriptSpan { start: estimate, end: statement_end.max(estimate), generated: false },
+        })
+    }
+
+    /// 1-based line of the script, and the byte offset of a 1-based
+    /// character column in it
+    fn script_offset(&self, line: usize, column: usize) -> (usize, usize) {
+        let line = line.clamp(1, self.script_lines.len());
+        let text = self.line_text(&self.script, &self.script_lines, line);
+        let offset = text.char_indices().nth(column - 1).map_or(text.len(), |(i, _)| i);
+        (line, self.script_lines[line - 1] + offset)
+    }
+
+    fn line_text<'a>(&self, text: &'a str, starts: &[usize], line: usize) -> &'a str {
+        let begin = starts[line - 1];
+        let end = starts.get(line).map_or(text.len(), |&next| next - 1);
+        &text[begin..end.max(begin)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::parser::Parser;
+    use crate::script::transpiler::Transpiler;
+
+    #[test]
+    fn test_map_to_script() {
+        let script = "# demo\n\ndef main():\n    x = 1\n    add(\"hi\", x)\n    return 0\n";
+        let module = Parser::new(script).parse().expect("parse failed");
+        let generated = Transpiler::new().with_source_file("demo.ath").transpile(&module);
+        let map = SourceMap::new(&generated, script);
+
+        let call = generated.find("add(").unwrap();
+        let arg = generated[call..].find('x').unwrap() + call;
+        let mapped = map.map(Span::new(arg, arg + 1, 0)).unwrap();
+        assert_eq!((&script[mapped.start..mapped.end], mapped.generated), ("x", false));
+        assert_eq!(mapped.start, script.rfind('x').unwrap());
+
+        let glue = generated.rfind('}').unwrap();
+        let mapped = map.map(Span::new(glue, glue + 1, 0)).unwrap();
+        assert!(mapped.generated);
+        assert_eq!(&script[mapped.start..mapped.end], "def main():");
+
+        let prelude = generated.find("extern").unwrap();
+        assert_eq!(map.map(Span::new(prelude, prelude, 0)).map(|s| (s.start, s.generated)), Some((0, true)));
+    }
+}
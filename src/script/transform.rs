@@ -0,0 +1,422 @@
+//! Source-to-source rewrites of transpiled Aether Script
+//!
+//! The transpiler's text is parsed back into a Core AST, rewritten by a
+//! `SourceTransformer` and printed with the `Formatter`, so the generated
+//! `.gen.aeth` reads like hand-written Aether Core. The built-in rules can be
+//! turned off and custom ones added with a rules file (`--transform-rules`):
+//!
+//! ```toml
+//! disable = ["none-check"]
+//!
+//! [[rule]]
+//! kind = "rename-call"
+//! from = "puts"
+//! to = "println"
+//! ```
+
+use std::fs;
+use std::path::Path;
+
+use crate::backend::cross::{parse_string, parse_string_array, strip_comment};
+use crate::frontend::ast::*;
+use crate::frontend::formatter::Formatter;
+use crate::frontend::lexer::Lexer;
+use crate::frontend::parser::Parser;
+use crate::utils::{Error, Result};
+
+/// One pattern-based rewrite
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransformRule {
+    /// `x == true` -> `x`, `x == false` -> `!x`, and the `!=` forms
+    BoolComparison,
+    /// `if x == None { a } else { b }` -> `match x { Option::None => { a }, _ => { b } }`
+    NoneCheck,
+    /// `"text\0" as *u8` -> `"text"`: string literals already are C strings
+    CStringLiteral,
+    /// Calls of the function `from` call `to` instead
+    RenameCall { from: String, to: String },
+    /// The type `from` is written `to`
+    RenameType { from: String, to: String },
+}
+
+impl TransformRule {
+    /// Rules applied unless a rules file disables them
+    pub fn builtin() -> Vec<TransformRule> {
+        vec![TransformRule::BoolComparison, TransformRule::NoneCheck, TransformRule::CStringLiteral]
+    }
+
+    /// Name of the rule's kind in rules files
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransformRule::BoolComparison => "bool-comparison",
+            TransformRule::NoneCheck => "none-check",
+            TransformRule::CStringLiteral => "c-string-literal",
+            TransformRule::RenameCall { .. } => "rename-call",
+            TransformRule::RenameType { .. } => "rename-type",
+        }
+    }
+}
+
+/// Applies a list of `TransformRule`s to a program
+#[derive(Debug, Clone)]
+pub struct SourceTransformer {
+    rules: Vec<TransformRule>,
+}
+
+impl Default for SourceTransformer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SourceTransformer {
+    /// A transformer with the built-in rules
+    pub fn new() -> Self {
+        Self { rules: TransformRule::builtin() }
+    }
+
+    pub fn rules(&self) -> &[TransformRule] {
+        &self.rules
+    }
+
+    /// Apply a rules file on top of the current rules
+    pub fn load_rules(self, path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| Error::Io(format!("{}: {}", path.display(), e)))?;
+        self.with_rules_file(&text)
+    }
+
+    /// Parse the TOML subset used by rules files: a top-level
+    /// `disable = [...]` naming built-in rules, and `[[rule]]` tables with a
+    /// `kind` and, for the renaming kinds, `from` and `to`
+    pub fn with_rules_file(mut self, text: &str) -> Result<Self> {
+        let mut tables: Vec<RuleTable> = Vec::new();
+        for (n, raw) in text.lines().enumerate() {
+            let line = strip_comment(raw).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = |msg: &str| Error::TransformRules(format!("line {}: {}", n + 1, msg));
+            if line.starts_with('[') {
+                if line != "[[rule]]" {
+                    return Err(invalid(&format!("unknown table {}", line)));
+                }
+                tables.push(RuleTable { line: n + 1, ..Default::default() });
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or_else(|| invalid("expected `key = value`"))?;
+            let value = value.trim();
+            match (tables.last_mut(), key.trim()) {
+                (None, "disable") => {
+                    let names = parse_string_array(value).ok_or_else(|| invalid("expected an array of strings"))?;
+                    for name in names {
+                        if !TransformRule::builtin().iter().any(|r| r.name() == name) {
+                            return Err(invalid(&format!("unknown built-in rule '{}'", name)));
+                        }
+                        self.rules.retain(|r| r.name() != name);
+                    }
+                }
+                (Some(table), key @ ("kind" | "from" | "to")) => {
+                    let value = parse_string(value).ok_or_else(|| invalid("expected a string"))?;
+                    let slot = match key {
+                        "kind" => &mut table.kind,
+                        "from" => &mut table.from,
+                        _ => &mut table.to,
+                    };
+                    *slot = Some(value);
+                }
+                (_, other) => return Err(invalid(&format!("unknown key '{}'", other))),
+            }
+        }
+
+        for RuleTable { line, kind, from, to } in tables {
+            let invalid = |msg: &str| Error::TransformRules(format!("rule at line {}: {}", line, msg));
+            let kind = kind.ok_or_else(|| invalid("missing `kind`"))?;
+            let rule = match kind.as_str() {
+                "rename-call" | "rename-type" => {
+                    let (Some(from), Some(to)) = (from, to) else {
+                        return Err(invalid(&format!("'{}' needs `from` and `to`", kind)));
+                    };
+                    if kind == "rename-call" {
+                        TransformRule::RenameCall { from, to }
+                    } else {
+                        TransformRule::RenameType { from, to }
+                    }
+                }
+                name => TransformRule::builtin().into_iter().find(|r| r.name() == name)
+                    .ok_or_else(|| invalid(&format!("unknown rule kind '{}'", name)))?,
+            };
+            if !self.rules.contains(&rule) {
+                self.rules.push(rule);
+            }
+        }
+        Ok(self)
+    }
+
+    /// Parse `source`, rewrite it and print it back; returns the new source
+    /// and the number of rewrites made
+    pub fn rewrite_source(&self, source: &str) -> Result<(String, usize)> {
+        let mut program = Parser::new(Lexer::new(source, 0)).parse_program()?;
+        let count = self.transform(&mut program);
+        Ok((Formatter::new().with_comments(source).format_program(&program), count))
+    }
+
+    /// Rewrite `program` in place; returns the number of rewrites made
+    pub fn transform(&self, program: &mut Program) -> usize {
+        self.items(&mut program.items)
+    }
+
+    fn items(&self, items: &mut [Item]) -> usize {
+        let mut count = 0;
+        for item in items {
+            count += match item {
+                Item::Function(f) => self.function(f),
+                Item::Struct(s) => s.fields.iter_mut().map(|f| self.ty(&mut f.ty)).sum(),
+                Item::Union(u) => u.fields.iter_mut().map(|f| self.ty(&mut f.ty)).sum(),
+                Item::Enum(e) => e.variants.iter_mut()
+                    .map(|v| v.fields.iter_mut().map(|t| self.ty(t)).sum::<usize>()
+                        + v.discriminant.as_mut().map_or(0, |d| self.expr(d)))
+                    .sum(),
+                Item::Impl(imp) => imp.methods.iter_mut().map(|m| self.function(m)).sum(),
+                Item::Const(c) => c.ty.as_mut().map_or(0, |t| self.ty(t)) + self.expr(&mut c.value),
+                Item::Static(s) => self.ty(&mut s.ty) + s.value.as_mut().map_or(0, |v| self.expr(v)),
+                Item::Module(ModuleDef { items: Some(items), .. }) => self.items(items),
+                Item::Extern(block) => block.items.iter_mut()
+                    .map(|item| match item {
+                        ForeignItem::Fn { params, ret_type, .. } => {
+                            params.iter_mut().map(|p| self.ty(&mut p.ty)).sum::<usize>()
+                                + ret_type.as_mut().map_or(0, |t| self.ty(t))
+                        }
+                        ForeignItem::Static { ty, .. } => self.ty(ty),
+                    })
+                    .sum(),
+                Item::TypeAlias(alias) => self.ty(&mut alias.ty),
+                _ => 0,
+            };
+        }
+        count
+    }
+
+    fn function(&self, f: &mut Function) -> usize {
+        f.params.iter_mut().map(|p| self.ty(&mut p.ty)).sum::<usize>()
+            + f.ret_type.as_mut().map_or(0, |t| self.ty(t))
+            + self.block(&mut f.body)
+    }
+
+    fn block(&self, block: &mut Block) -> usize {
+        self.stmts(&mut block.stmts)
+    }
+
+    fn stmts(&self, stmts: &mut [Stmt]) -> usize {
+        stmts.iter_mut()
+            .map(|stmt| match stmt {
+                Stmt::Let { ty, value, .. } => {
+                    ty.as_mut().map_or(0, |t| self.ty(t)) + value.as_mut().map_or(0, |v| self.expr(v))
+                }
+                Stmt::Expr(expr) => self.expr(expr),
+                Stmt::Return { value, .. } | Stmt::Break { break_value: value, .. } => value.as_mut().map_or(0, |v| self.expr(v)),
+                Stmt::Continue { .. } | Stmt::Empty { .. } => 0,
+            })
+            .sum()
+    }
+
+    /// Rewrite the children of `expr`, then `expr` itself
+    fn expr(&self, expr: &mut Expr) -> usize {
+        let mut count = match expr {
+            Expr::Binary { left, right, .. } => self.expr(left) + self.expr(right),
+            Expr::Unary { expr, .. } | Expr::Field { expr, .. } | Expr::Deref { expr, .. }
+            | Expr::Ref { expr, .. } | Expr::Try { expr, .. } => self.expr(expr),
+            Expr::Call { func, args, .. } => self.expr(func) + args.iter_mut().map(|a| self.expr(a)).sum::<usize>(),
+            Expr::MethodCall { expr, args, .. } => {
+                self.expr(expr) + args.iter_mut().map(|a| self.expr(a)).sum::<usize>()
+            }
+            Expr::Index { expr, index, .. } => self.expr(expr) + self.expr(index),
+            Expr::Block(block) | Expr::Loop { body: block, .. } | Expr::Unsafe { body: block, .. } => self.block(block),
+            Expr::If { cond, then_block, else_block, .. } => {
+                self.expr(cond) + self.block(then_block) + else_block.as_mut().map_or(0, |b| self.block(b))
+            }
+            Expr::Match { expr, arms, .. } => {
+                self.expr(expr) + arms.iter_mut()
+                    .map(|arm| arm.guard.as_mut().map_or(0, |g| self.expr(g)) + self.expr(&mut arm.body))
+                    .sum::<usize>()
+            }
+            Expr::While { cond, body, .. } => self.expr(cond) + self.block(body),
+            Expr::For { iter, body, .. } => self.expr(iter) + self.block(body),
+            Expr::StructLit { fields, .. } => fields.iter_mut().map(|(_, v)| self.expr(v)).sum(),
+            Expr::Array { elements, .. } | Expr::Tuple { elements, .. } => {
+                elements.iter_mut().map(|e| self.expr(e)).sum()
+            }
+            Expr::ArrayFrom { init, .. } => self.expr(init),
+            Expr::DoBlock { stmts, accumulator, .. } => self.stmts(stmts) + self.expr(accumulator),
+            Expr::Cast { expr, ty, .. } => self.expr(expr) + self.ty(ty),
+            Expr::Range { start, end, .. } => {
+                start.as_mut().map_or(0, |e| self.expr(e)) + end.as_mut().map_or(0, |e| self.expr(e))
+            }
+            Expr::Closure { body, .. } => self.expr(body),
+            Expr::Let { ty, value, .. } => ty.as_mut().map_or(0, |t| self.ty(t)) + self.expr(value),
+            Expr::New { ty, count, init, .. } => {
+                self.ty(ty) + count.as_mut().map_or(0, |e| self.expr(e)) + init.as_mut().map_or(0, |e| self.expr(e))
+            }
+            Expr::SizeOf { ty, .. } | Expr::AlignOf { ty, .. } | Expr::OffsetOf { ty, .. } => self.ty(ty),
+            Expr::Literal(_) | Expr::Ident(_) | Expr::Path { .. } | Expr::Asm { .. } => 0,
+        };
+        for rule in &self.rules {
+            if let Some(rewritten) = rewrite_expr(rule, expr) {
+                *expr = rewritten;
+                count += 1;
+            }
+        }
+        count
+    }
+
+    fn ty(&self, ty: &mut Type) -> usize {
+        let mut count = match ty {
+            Type::Generic(_, args, _) | Type::Tuple(args, _) => args.iter_mut().map(|t| self.ty(t)).sum(),
+            Type::Pointer(inner, _) | Type::Slice(inner, _) | Type::Volatile(inner, _)
+            | Type::Ref { inner, .. } | Type::Array { elem: inner, .. } | Type::Owned { inner, .. } => self.ty(inner),
+            Type::Function { params, ret, .. } => params.iter_mut().map(|t| self.ty(t)).sum::<usize>() + self.ty(ret),
+            _ => 0,
+        };
+        for rule in &self.rules {
+            if let TransformRule::RenameType { from, to } = rule {
+                match ty {
+                    Type::Named(name, _) | Type::Generic(name, _, _) if name == from => {
+                        *name = to.clone();
+                        count += 1;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        count
+    }
+}
+
+/// `expr` rewritten by `rule`, or None if the rule does not apply
+fn rewrite_expr(rule: &TransformRule, expr: &Expr) -> Option<Expr> {
+    match (rule, expr) {
+        (TransformRule::BoolComparison, Expr::Binary { left, op: op @ (BinOp::Eq | BinOp::Ne), right, span }) => {
+            let (operand, value) = match (left.as_ref(), right.as_ref()) {
+                (operand, Expr::Literal(Literal::Bool(b, _))) | (Expr::Literal(Literal::Bool(b, _)), operand) => (operand, *b),
+                _ => return None,
+            };
+            if (*op == BinOp::Eq) == value {
+                Some(operand.clone())
+            } else {
+                Some(Expr::Unary { op: UnOp::Not, expr: Box::new(operand.clone()), span: *span })
+            }
+        }
+        (TransformRule::NoneCheck, Expr::If { cond, then_block, else_block, span }) => {
+            let Expr::Binary { left, op: op @ (BinOp::Eq | BinOp::Ne), right, .. } = cond.as_ref() else {
+                return None;
+            };
+            let scrutinee = match (is_none(left), is_none(right)) {
+                (false, true) => left,
+                (true, false) => right,
+                _ => return None,
+            };
+            let other = else_block.clone().unwrap_or(Block { stmts: Vec::new(), span: *span });
+            let (on_none, on_some) = if *op == BinOp::Eq {
+                (then_block.clone(), other)
+            } else {
+                (other, then_block.clone())
+            };
+            let none = Pattern::Variant {
+                enum_name: Some(Ident { name: "Option".to_string(), span: *span }),
+                variant: Ident { name: "None".to_string(), span: *span },
+                fields: Vec::new(),
+                span: *span,
+            };
+            let arm = |pattern, body| MatchArm { pattern, guard: None, body: Expr::Block(body), span: *span };
+            Some(Expr::Match {
+                expr: scrutinee.clone(),
+                arms: vec![arm(none, on_none), arm(Pattern::Wildcard { span: *span }, on_some)],
+                span: *span,
+            })
+        }
+        (TransformRule::CStringLiteral, Expr::Cast { expr: inner, ty: Type::Pointer(pointee, _), .. }) => {
+            let (Expr::Literal(Literal::String(text, lit_span)), Type::Named(name, _)) = (inner.as_ref(), pointee.as_ref()) else {
+                return None;
+            };
+            let text = text.strip_suffix('\0').filter(|t| name == "u8" && !t.contains('\0'))?;
+            Some(Expr::Literal(Literal::String(text.to_string(), *lit_span)))
+        }
+        (TransformRule::RenameCall { from, to }, Expr::Call { func, args, span }) => match func.as_ref() {
+            Expr::Ident(ident) if ident.name == *from => Some(Expr::Call {
+                func: Box::new(Expr::Ident(Ident { name: to.clone(), span: ident.span })),
+                args: args.clone(),
+                span: *span,
+            }),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// `None` or `Option::None`
+fn is_none(expr: &Expr) -> bool {
+    match expr {
+        Expr::Ident(ident) => ident.name == "None",
+        Expr::Path { segments, .. } => {
+            matches!(segments.as_slice(), [option, none] if option.name == "Option" && none.name == "None")
+        }
+        _ => false,
+    }
+}
+
+/// A `[[rule]]` table as read, before it is validated
+#[derive(Default)]
+struct RuleTable {
+    /// Line of the `[[rule]]` header
+    line: usize,
+    kind: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_rewrites() {
+        let source = "// generated\nfn f(x: *u8, b: bool) -> i32 {\n    if b == true {\n        puts(\"hi\\0\" as *u8);\n    }\n    if x == None {\n        return 0;\n    } else {\n        return (1 + 2);\n    }\n}\n";
+        let (rewritten, count) = SourceTransformer::new().rewrite_source(source).unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(rewritten, "\
+// generated
+fn f(x: *u8, b: bool) -> i32 {
+    if b {
+        puts(\"hi\");
+    }
+    match x {
+        Option::None => {
+            return 0
+        },
+        _ => {
+            return 1 + 2
+        },
+    }
+}
+");
+    }
+
+    #[test]
+    fn test_rules_file() {
+        let rules = "# custom\ndisable = [\"none-check\"]\n\n[[rule]]\nkind = \"rename-call\"\nfrom = \"puts\"\nto = \"println\"\n";
+        let transformer = SourceTransformer::new().with_rules_file(rules).unwrap();
+        assert_eq!(transformer.rules(), &[
+            TransformRule::BoolComparison,
+            TransformRule::CStringLiteral,
+            TransformRule::RenameCall { from: "puts".into(), to: "println".into() },
+        ]);
+        let (rewritten, _) = transformer.rewrite_source("fn main() {\n    puts(\"a\\0\" as *u8)\n}\n").unwrap();
+        assert!(rewritten.contains("    println(\"a\")\n"), "{}", rewritten);
+
+        let err = SourceTransformer::new().with_rules_file("[[rule]]\nkind = \"rename-type\"\nfrom = \"int\"\n").unwrap_err();
+        assert!(err.to_string().contains("rule at line 1: 'rename-type' needs `from` and `to`"), "{}", err);
+        assert_eq!(err.code(), "E0301");
+        let err = SourceTransformer::new().with_rules_file("disable = [\"tidy\"]").unwrap_err();
+        assert!(err.to_string().contains("line 1: unknown built-in rule 'tidy'"), "{}", err);
+    }
+}
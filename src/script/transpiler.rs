@@ -4,12 +4,22 @@
 //! Follows the rules defined in docs/AETHER_SCRIPT_SPEC.md
 
 use super::ast::*;
+use super::token::Span;
+use crate::utils::SourceMarker;
 
 pub struct Transpiler {
     indent_level: usize,
     output: String,
     source_file: Option<String>,
     emit_line_directives: bool,
+    /// Statements whose blocks are being emitted, innermost last
+    enclosing: Vec<Span>,
+}
+
+impl Default for Transpiler {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Transpiler {
@@ -19,10 +29,13 @@ impl Transpiler {
             output: String::new(),
             source_file: None,
             emit_line_directives: false,
+            enclosing: Vec::new(),
         }
     }
 
-    /// Enable source mapping with the given source file name
+    /// Enable source mapping with the given source file name: every
+    /// statement is preceded by a marker naming its line and column, and
+    /// glue code by one naming the statement it belongs to
     pub fn with_source_file(mut self, path: &str) -> Self {
         self.source_file = Some(path.to_string());
         self.emit_line_directives = true;
@@ -31,6 +44,8 @@ impl Transpiler {
 
     /// Transpile a ScriptModule to Aether Core source code
     pub fn transpile(&mut self, module: &ScriptModule) -> String {
+        // The prelude has no script statement of its own
+        self.emit_source_marker(Span::new(0, 0, 1, 1), true);
         // Generate prelude comments
         self.emit_line("// Auto-generated from Aether Script (.ath)");
         self.emit_line("// DO NOT EDIT - Regenerate from source");
@@ -54,18 +69,52 @@ impl Transpiler {
         std::mem::take(&mut self.output)
     }
 
-    /// Emit a source mapping comment for debuggers
+    /// Emit a source mapping comment for debuggers and diagnostics
     /// Uses comment format since Core parser doesn't have preprocessor
-    fn emit_source_line(&mut self, line: usize) {
+    fn emit_source_marker(&mut self, span: Span, generated: bool) {
         if self.emit_line_directives {
             if let Some(ref src) = self.source_file {
-                // Use comment-based source mapping that won't break Core parser
-                self.output.push_str(&format!("// @source {}:{}\n", src, line));
+                let marker = SourceMarker { file: src.clone(), line: span.line, column: Some(span.column), generated };
+                self.emit_indent();
+                self.output.push_str(&format!("{}\n", marker));
             }
         }
     }
 
+    /// Emit a marker for the glue after a block: it belongs to the
+    /// statement that opened the block
+    fn emit_glue_marker(&mut self) {
+        if let Some(&span) = self.enclosing.last() {
+            self.emit_source_marker(span, true);
+        }
+    }
+
+    /// Emit `stmts` one level deeper, inside the statement at `span`
+    fn transpile_block(&mut self, span: Span, stmts: &[Stmt]) {
+        self.enclosing.push(span);
+        self.indent_level += 1;
+        for stmt in stmts {
+            self.transpile_stmt(stmt);
+        }
+        self.indent_level -= 1;
+        self.emit_glue_marker();
+        self.enclosing.pop();
+    }
+
     fn transpile_stmt(&mut self, stmt: &Stmt) {
+        let span = match stmt {
+            Stmt::FunctionDef(f) => Some(f.span),
+            Stmt::If(i) => Some(i.span),
+            Stmt::While(w) => Some(w.span),
+            Stmt::For(f) => Some(f.span),
+            Stmt::Return(r) => Some(r.span),
+            Stmt::Assign(a) => Some(a.span),
+            Stmt::Expr(e) => Some(e.span_owned()),
+            Stmt::Pass => None,
+        };
+        if let Some(span) = span {
+            self.emit_source_marker(span, false);
+        }
         match stmt {
             Stmt::FunctionDef(f) => self.transpile_function(f),
             Stmt::If(i) => self.transpile_if(i),
@@ -86,9 +135,6 @@ impl Transpiler {
     }
 
     fn transpile_function(&mut self, f: &FunctionDef) {
-        // Emit source line directive for debuggers
-        self.emit_source_line(f.span.line);
-        
         // Function signature
         self.emit_indent();
 
@@ -134,11 +180,7 @@ impl Transpiler {
         self.emit(" {\n");
 
         // Body
-        self.indent_level += 1;
-        for stmt in &f.body {
-            self.transpile_stmt(stmt);
-        }
-        self.indent_level -= 1;
+        self.transpile_block(f.span, &f.body);
 
         self.emit_indent();
         self.emit_line("}");
@@ -151,22 +193,14 @@ impl Transpiler {
         self.emit(&self.transpile_expr(&i.condition));
         self.emit(" {\n");
 
-        self.indent_level += 1;
-        for stmt in &i.then_block {
-            self.transpile_stmt(stmt);
-        }
-        self.indent_level -= 1;
+        self.transpile_block(i.span, &i.then_block);
 
         self.emit_indent();
         self.emit("}");
 
         if let Some(ref else_block) = i.else_block {
             self.emit(" else {\n");
-            self.indent_level += 1;
-            for stmt in else_block {
-                self.transpile_stmt(stmt);
-            }
-            self.indent_level -= 1;
+            self.transpile_block(i.span, else_block);
             self.emit_indent();
             self.emit("}");
         }
@@ -179,11 +213,7 @@ impl Transpiler {
         self.emit(&self.transpile_expr(&w.condition));
         self.emit(" {\n");
 
-        self.indent_level += 1;
-        for stmt in &w.body {
-            self.transpile_stmt(stmt);
-        }
-        self.indent_level -= 1;
+        self.transpile_block(w.span, &w.body);
 
         self.emit_indent();
         self.emit_line("}");
@@ -194,17 +224,12 @@ impl Transpiler {
         // Transpile: for x in iterable -> for x in iterable
         self.emit("for ");
         self.emit(&f.var);
-        // P5.1: Add type annotation for loop variable (infer from iterable if possible)
-        self.emit(": _");  // Use placeholder, Core compiler will infer
+        // Core loop variables take their type from the iterable
         self.emit(" in ");
         self.emit(&self.transpile_expr(&f.iterable));
         self.emit(" {\n");
 
-        self.indent_level += 1;
-        for stmt in &f.body {
-            self.transpile_stmt(stmt);
-        }
-        self.indent_level -= 1;
+        self.transpile_block(f.span, &f.body);
 
         self.emit_indent();
         self.emit_line("}");
@@ -0,0 +1,919 @@
+//! Compilation Session
+//!
+//! Runs the compiler over one source, read from a file or held in memory,
+//! without printing or exiting: every stage reports into a `CompileResult`.
+//!
+//! ```
+//! use aether_lang::CompileSession;
+//!
+//! let source = "fn main() effect[io] {\n    println_i64(42);\n}\n";
+//! let result = CompileSession::from_source("answer", source).emit_c();
+//! assert!(result.success());
+//! assert!(result.text().unwrap().contains("int main("));
+//! ```
+//!
+//! ```
+//! use aether_lang::{CompileSession, Stage};
+//!
+//! let result = CompileSession::from_source("typo", "fn f() -> i64 {\n    return y\n}\n").check();
+//! assert_eq!(result.failed_stage, Some(Stage::Semantic));
+//! let error = result.errors().next().unwrap();
+//! assert_eq!(error.code, "E0002");
+//! assert_eq!(error.message.to_string(), "Undefined variable: y");
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use crate::backend::cross::Toolchain;
+use crate::backend::codegen::SanitizerFlags;
+use crate::backend::c::DEFAULT_ENTRY;
+use crate::backend::linker::Linker;
+use crate::backend::static_link;
+use crate::backend::CCodeGen;
+use crate::feedback::{CompilationStats, ErrorReport, Location, Severity, DEFAULT_LOCALE};
+use crate::frontend::ast::{Item, Program};
+use crate::frontend::inspect;
+use crate::frontend::lexer::Lexer;
+use crate::frontend::lints;
+use crate::frontend::module::ModuleLoader;
+use crate::frontend::parser::{Parser, DEFAULT_MAX_NESTING};
+use crate::frontend::semantic::{PhaseError, SemanticAnalyzer, DEFAULT_MAX_ERRORS};
+use crate::middle::bench::BenchConfig;
+use crate::middle::ir::IRModule;
+use crate::middle::ir_gen::IRGenerator;
+use crate::middle::ir_printer::print_ir;
+use crate::middle::optimize::Optimizer;
+use crate::middle::overflow::OverflowMode;
+use crate::middle::panic::PanicMode;
+use crate::middle::validate::validate_module;
+use crate::script;
+use crate::script::source_map::SourceMap;
+use crate::utils::{default_jobs, Error, Result, Span};
+
+/// Where the source of a session comes from
+#[derive(Debug, Clone)]
+pub enum SourceInput {
+    /// A file; `.ath` files are Aether Script and are transpiled to Core first
+    Path(PathBuf),
+    /// Core source held in memory; `name` is its module name and the file
+    /// name in diagnostics
+    Memory { name: String, text: String },
+}
+
+/// Pipeline stage, for reporting where a compilation stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Read,
+    Script,
+    Parse,
+    Semantic,
+    IrGen,
+    Validate,
+    Optimize,
+    CodeGen,
+    Build,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Stage::Read => "Read",
+            Stage::Script => "Script parse",
+            Stage::Parse => "Parse",
+            Stage::Semantic => "Semantic",
+            Stage::IrGen => "IR generation",
+            Stage::Validate => "IR validation",
+            Stage::Optimize => "Optimization",
+            Stage::CodeGen => "Code generation",
+            Stage::Build => "Build",
+        })
+    }
+}
+
+/// Output of a session
+#[derive(Debug, Clone, PartialEq)]
+pub enum Artifact {
+    /// Textual Aether IR
+    Ir(String),
+    /// C source
+    C(String),
+    /// A linked executable
+    Executable(Vec<u8>),
+    /// The parsed program as JSON
+    #[cfg(feature = "json-ast")]
+    JsonAst(String),
+}
+
+/// Everything a session found and produced
+#[derive(Debug, Clone)]
+pub struct CompileResult {
+    /// Errors and warnings, in the order they were found
+    pub diagnostics: Vec<ErrorReport>,
+    pub stats: CompilationStats,
+    /// Stage whose errors stopped the compilation
+    pub failed_stage: Option<Stage>,
+    pub artifact: Option<Artifact>,
+    /// Core source generated from an Aether Script input
+    pub transpiled: Option<String>,
+    /// Maps `transpiled` back to the script; the diagnostics have been
+    /// mapped through it and point into the script
+    pub source_map: Option<SourceMap>,
+    /// Number of leading diagnostics already mapped through `source_map`
+    mapped_diagnostics: usize,
+    /// Libraries named by `extern ... link = "lib"` blocks
+    pub link_libraries: Vec<String>,
+    /// The source annotated with the type of each expression (`with_print_types`)
+    pub types: Option<String>,
+    /// Table of the program's symbols (`with_print_symbols`)
+    pub symbols: Option<String>,
+}
+
+impl CompileResult {
+    fn new() -> Self {
+        Self {
+            diagnostics: Vec::new(),
+            stats: CompilationStats::default(),
+            failed_stage: None,
+            artifact: None,
+            transpiled: None,
+            source_map: None,
+            mapped_diagnostics: 0,
+            link_libraries: Vec::new(),
+            types: None,
+            symbols: None,
+        }
+    }
+
+    /// Whether every stage that ran succeeded
+    pub fn success(&self) -> bool {
+        self.failed_stage.is_none()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &ErrorReport> {
+        self.diagnostics.iter().filter(|d| matches!(d.severity, Severity::Error))
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &ErrorReport> {
+        self.diagnostics.iter().filter(|d| matches!(d.severity, Severity::Warning))
+    }
+
+    /// The IR, C or JSON text produced, if any
+    pub fn text(&self) -> Option<&str> {
+        match &self.artifact {
+            Some(Artifact::Ir(text)) | Some(Artifact::C(text)) => Some(text),
+            #[cfg(feature = "json-ast")]
+            Some(Artifact::JsonAst(text)) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// The executable produced, if any
+    pub fn bytes(&self) -> Option<&[u8]> {
+        match &self.artifact {
+            Some(Artifact::Executable(bytes)) => Some(bytes),
+            _ => None,
+        }
+    }
+}
+
+/// One compilation of one source; configure it with the `with_` methods,
+/// then run `check`, `emit_ir`, `emit_c` or `build`
+#[derive(Debug, Clone)]
+pub struct CompileSession {
+    input: SourceInput,
+    root: Option<PathBuf>,
+    opt_level: u8,
+    backend: String,
+    toolchain: Toolchain,
+    strict: Option<bool>,
+    panic: PanicMode,
+    overflow: OverflowMode,
+    debug_assertions: Option<bool>,
+    max_errors: usize,
+    max_nesting: usize,
+    jobs: usize,
+    locale: &'static str,
+    debug_info: bool,
+    verify_ir: bool,
+    strip_dead_code: bool,
+    sanitize: SanitizerFlags,
+    json_panics: bool,
+    /// Entry symbol of a build without the C library
+    freestanding: Option<String>,
+    transform_rules: Option<PathBuf>,
+    bench: Option<BenchConfig>,
+    coverage: Option<PathBuf>,
+    timings: bool,
+    print_types: bool,
+    print_symbols: bool,
+}
+
+impl CompileSession {
+    fn new(input: SourceInput) -> Self {
+        Self {
+            input,
+            root: None,
+            opt_level: 0,
+            backend: "c".to_string(),
+            toolchain: Toolchain::new("native"),
+            strict: None,
+            panic: PanicMode::default(),
+            overflow: OverflowMode::default(),
+            debug_assertions: None,
+            max_errors: DEFAULT_MAX_ERRORS,
+            max_nesting: DEFAULT_MAX_NESTING,
+            jobs: default_jobs(),
+            locale: DEFAULT_LOCALE,
+            debug_info: false,
+            verify_ir: false,
+            strip_dead_code: false,
+            sanitize: SanitizerFlags::default(),
+            json_panics: false,
+            freestanding: None,
+            transform_rules: None,
+            bench: None,
+            coverage: None,
+            timings: false,
+            print_types: false,
+            print_symbols: false,
+        }
+    }
+
+    /// Compile the file at `path`
+    pub fn from_path(path: impl Into<PathBuf>) -> Self {
+        Self::new(SourceInput::Path(path.into()))
+    }
+
+    /// Compile Core source held in memory
+    pub fn from_source(name: &str, text: &str) -> Self {
+        Self::new(SourceInput::Memory { name: name.to_string(), text: text.to_string() })
+    }
+
+    /// Also look for imported modules in `dir`
+    pub fn with_root(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.root = Some(dir.into());
+        self
+    }
+
+    /// Optimization level (0-3) for `emit_c` and `build`
+    pub fn with_opt_level(mut self, level: u8) -> Self {
+        self.opt_level = level;
+        self
+    }
+
+    /// Backend used by `build`; only `c` builds executables in a session
+    pub fn with_backend(mut self, backend: &str) -> Self {
+        self.backend = backend.to_string();
+        self
+    }
+
+    /// Target and C compiler for `emit_c` and `build`
+    pub fn with_toolchain(mut self, toolchain: Toolchain) -> Self {
+        self.toolchain = toolchain;
+        self
+    }
+
+    /// Fail on the checks lenient mode only reports (`--strict`), or only
+    /// report them (`--lenient`), whatever `#[production]` says
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = Some(strict);
+        self
+    }
+
+    pub fn with_panic_mode(mut self, mode: PanicMode) -> Self {
+        self.panic = mode;
+        self
+    }
+
+    pub fn with_overflow_mode(mut self, mode: OverflowMode) -> Self {
+        self.overflow = mode;
+        self
+    }
+
+    /// Keep `debug_assert` calls; by default only at `-O0`
+    pub fn with_debug_assertions(mut self, enabled: bool) -> Self {
+        self.debug_assertions = Some(enabled);
+        self
+    }
+
+    /// Semantic errors kept before the rest are suppressed (0 for no limit)
+    pub fn with_max_errors(mut self, max: usize) -> Self {
+        self.max_errors = max;
+        self
+    }
+
+    /// Deepest nesting of expressions, types and patterns accepted by every
+    /// phase; deeper code is an error rather than a stack overflow
+    pub fn with_max_nesting(mut self, limit: usize) -> Self {
+        self.max_nesting = limit;
+        self
+    }
+
+    /// Worker threads per compilation phase
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Language of diagnostic messages
+    pub fn with_locale(mut self, locale: &'static str) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// Map generated code back to the source (`-g`)
+    pub fn with_debug_info(mut self, enabled: bool) -> Self {
+        self.debug_info = enabled;
+        self
+    }
+
+    /// Validate the IR after every optimizer pass
+    pub fn with_verify_ir(mut self, enabled: bool) -> Self {
+        self.verify_ir = enabled;
+        self
+    }
+
+    /// Remove dead code even at `-O0`
+    pub fn with_strip_dead_code(mut self, enabled: bool) -> Self {
+        self.strip_dead_code = enabled;
+        self
+    }
+
+    pub fn with_sanitizers(mut self, sanitize: SanitizerFlags) -> Self {
+        self.sanitize = sanitize;
+        self
+    }
+
+    /// Report runtime panics of the built program as JSON lines
+    pub fn with_json_panics(mut self, json: bool) -> Self {
+        self.json_panics = json;
+        self
+    }
+
+    /// Build without the C library (`--freestanding`): the program starts
+    /// at `entry` and is linked by the self-hosted linker
+    pub fn with_freestanding(mut self, entry: &str) -> Self {
+        self.freestanding = Some(entry.to_string());
+        self
+    }
+
+    /// Rewrite rules for Core transpiled from Aether Script
+    pub fn with_transform_rules(mut self, path: impl Into<PathBuf>) -> Self {
+        self.transform_rules = Some(path.into());
+        self
+    }
+
+    /// Generate the benchmark harness instead of the program's `main`
+    pub fn with_benchmark(mut self, config: BenchConfig) -> Self {
+        self.bench = Some(config);
+        self
+    }
+
+    /// Count basic block executions into `data_path` (C backend)
+    pub fn with_coverage(mut self, data_path: impl Into<PathBuf>) -> Self {
+        self.coverage = Some(data_path.into());
+        self
+    }
+
+    /// Also record optimizer pass statistics and per-function IR sizes
+    pub fn with_timings(mut self, enabled: bool) -> Self {
+        self.timings = enabled;
+        self
+    }
+
+    /// Also render the type of every expression into `CompileResult::types`
+    pub fn with_print_types(mut self, enabled: bool) -> Self {
+        self.print_types = enabled;
+        self
+    }
+
+    /// Also render the global symbol table into `CompileResult::symbols`
+    pub fn with_print_symbols(mut self, enabled: bool) -> Self {
+        self.print_symbols = enabled;
+        self
+    }
+
+    /// Parse and analyze the source, reporting errors and warnings
+    pub fn check(&self) -> CompileResult {
+        let mut result = CompileResult::new();
+        let start = Instant::now();
+        self.analyze(&mut result);
+        map_to_script(&mut result);
+        result.stats.total_time_ms = elapsed_ms(start);
+        result
+    }
+
+    /// The parsed program as JSON, before semantic analysis; `pretty`
+    /// indents it
+    #[cfg(feature = "json-ast")]
+    pub fn emit_json_ast(&self, pretty: bool) -> CompileResult {
+        let mut result = CompileResult::new();
+        let start = Instant::now();
+        if let Some((program, _)) = self.parse(&mut result) {
+            let json = if pretty { serde_json::to_string_pretty(&program) } else { serde_json::to_string(&program) };
+            result.artifact = Some(Artifact::JsonAst(json.expect("the AST has no maps with non-string keys")));
+        }
+        map_to_script(&mut result);
+        result.stats.total_time_ms = elapsed_ms(start);
+        result
+    }
+
+    /// Aether IR of the source as lowered, before optimization
+    pub fn emit_ir(&self) -> CompileResult {
+        let (mut result, module) = self.lower();
+        if let Some(module) = module {
+            result.artifact = Some(Artifact::Ir(print_ir(&module)));
+        }
+        result
+    }
+
+    /// C source of the optimized module
+    pub fn emit_c(&self) -> CompileResult {
+        let start = Instant::now();
+        let (mut result, module) = self.lower();
+        if let Some(c_source) = module.and_then(|module| self.generate_c(module, &mut result)) {
+            result.artifact = Some(Artifact::C(c_source));
+        }
+        result.stats.total_time_ms = elapsed_ms(start);
+        result
+    }
+
+    /// Executable built from the C source with the host or cross toolchain
+    pub fn build(&self) -> CompileResult {
+        if self.backend != "c" {
+            let mut result = CompileResult::new();
+            let error = Error::CodeGen(format!("a session builds with the c backend, not '{}'", self.backend));
+            self.fail(&mut result, Stage::Build, &error);
+            return result;
+        }
+        let start = Instant::now();
+        let mut result = self.emit_c();
+        if let Some(Artifact::C(c_source)) = result.artifact.take() {
+            let compile_start = Instant::now();
+            let compiled = self.compile_c(&c_source, &result.link_libraries);
+            result.stats.c_compiler_time_ms = elapsed_ms(compile_start);
+            match compiled {
+                Ok(bytes) => result.artifact = Some(Artifact::Executable(bytes)),
+                Err(error) => self.fail(&mut result, Stage::Build, &error),
+            }
+        }
+        result.stats.total_time_ms = elapsed_ms(start);
+        result
+    }
+
+    /// Check the source and lower it to IR, unoptimized
+    pub fn lower(&self) -> (CompileResult, Option<IRModule>) {
+        let mut result = CompileResult::new();
+        let start = Instant::now();
+        let analyzed = self.analyze(&mut result);
+        map_to_script(&mut result);
+        let Some((program, core_source, modules)) = analyzed else {
+            result.stats.total_time_ms = elapsed_ms(start);
+            return (result, None);
+        };
+
+        let ir_start = Instant::now();
+        let mut ir_gen = IRGenerator::new(&self.module_name())
+            .with_panic_mode(self.panic)
+            .with_overflow_mode(self.overflow)
+            .with_debug_assertions(self.debug_assertions.unwrap_or(self.opt_level == 0))
+            .with_jobs(self.jobs)
+            .with_max_nesting(self.max_nesting)
+            .with_module_loader(modules);
+        if let Some(ref config) = self.bench {
+            ir_gen = ir_gen.with_benchmark(config.clone());
+        }
+        let core_path = self.core_path();
+        if self.debug_info {
+            ir_gen = ir_gen.with_debug_info(&core_path, &core_source);
+        } else {
+            ir_gen = ir_gen.with_source(&core_path, &core_source);
+        }
+        if let Some(ref data_path) = self.coverage {
+            let data_path = std::path::absolute(data_path).unwrap_or_else(|_| data_path.clone());
+            ir_gen = ir_gen.with_coverage(&self.file_name(), &core_source, &data_path.to_string_lossy());
+        }
+        let module = match ir_gen.generate(&program) {
+            Ok(module) => module,
+            Err(error) => {
+                self.fail(&mut result, Stage::IrGen, &error);
+                map_to_script(&mut result);
+                return (result, None);
+            }
+        };
+        result.stats.ir_gen_time_ms = elapsed_ms(ir_start);
+        if self.timings {
+            result.stats.record_functions(&module);
+        }
+        result.stats.total_time_ms = elapsed_ms(start);
+        (result, Some(module))
+    }
+
+    /// Read and parse the source; returns the program and the Core source
+    /// it was parsed from
+    fn parse(&self, result: &mut CompileResult) -> Option<(Program, String)> {
+        let core_source = self.core_source(result)?;
+        result.stats.loc = core_source.lines().count();
+
+        let parse_start = Instant::now();
+        let program = match Parser::new(Lexer::new(&core_source, 0)).with_max_nesting(self.max_nesting).parse_program() {
+            Ok(program) => program,
+            Err(error) => {
+                self.fail(result, Stage::Parse, &error);
+                return None;
+            }
+        };
+        result.stats.parse_time_ms = elapsed_ms(parse_start);
+        Some((program, core_source))
+    }
+
+    /// Read, parse and analyze the source; returns the program, the Core
+    /// source it was parsed from and the loader holding its imports
+    fn analyze(&self, result: &mut CompileResult) -> Option<(Program, String, ModuleLoader)> {
+        let (program, core_source) = self.parse(result)?;
+        result.stats.function_count = program.items.iter().map(|item| match item {
+            Item::Function(_) => 1,
+            Item::Impl(block) => block.methods.len(),
+            _ => 0,
+        }).sum();
+        result.stats.type_count = program.items.iter().filter(|i| matches!(i, Item::Struct(_) | Item::Enum(_))).count();
+        result.link_libraries = program.link_libraries();
+
+        let semantic_start = Instant::now();
+        let mut modules = ModuleLoader::new();
+        if let Some(ref root) = self.root {
+            modules.add_search_path(root.clone());
+        }
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.set_module_name(&self.module_name());
+        analyzer.set_jobs(self.jobs);
+        if let Some(strict) = self.strict {
+            analyzer.set_strict_mode(strict);
+        }
+        analyzer.set_max_errors(self.max_errors);
+        analyzer.set_max_nesting(self.max_nesting);
+        analyzer.set_freestanding(self.freestanding.is_some());
+        analyzer.set_module_loader(modules.clone());
+        analyzer.set_record_types(self.print_types);
+        let analyzed = analyzer.analyze_phases(&program);
+        result.stats.semantic_time_ms = elapsed_ms(semantic_start);
+        // Rendered even when checking failed, to help find why
+        if self.print_types {
+            result.types = Some(inspect::annotate_types(&self.file_name(), &core_source, analyzer.expr_types()));
+        }
+        if self.print_symbols {
+            result.symbols = Some(inspect::symbol_table(&self.file_name(), &core_source, analyzer.symbols.globals()));
+        }
+        result.stats.errors_suppressed = analyzer.suppressed_errors();
+        if let Err(error) = analyzed {
+            // Collection and resolution stop at their first error, which the
+            // analyzer does not collect
+            if !matches!(error, PhaseError::Checking(_)) {
+                self.fail(result, Stage::Semantic, error.error());
+            }
+            for error in &analyzer.errors {
+                self.fail(result, Stage::Semantic, error);
+                let report = result.diagnostics.last_mut().expect("fail adds a report");
+                if error.span().is_some_and(|span| span.file_id == 0) {
+                    report.suggest_unsafe_block(error, &core_source);
+                }
+                if analyzer.is_strict_only(error) {
+                    report.add_suggestion(
+                        "this is a warning in lenient mode: mark the item #[prototype] or build with --lenient",
+                        None,
+                        0.5,
+                    );
+                }
+            }
+            return None;
+        }
+        for warning in analyzer.warnings().iter().cloned().chain(lints::check_program(&program)) {
+            let mut report = ErrorReport::from_error_localized(&warning, &self.file_name(), self.locale);
+            report.severity = Severity::Warning;
+            if warning.span().is_some_and(|span| span.file_id == 0) {
+                report.suggest_unsafe_block(&warning, &core_source);
+            }
+            result.diagnostics.push(report);
+        }
+        Some((program, core_source, modules))
+    }
+
+    /// Source text to parse: the input, or Core transpiled from a script
+    fn core_source(&self, result: &mut CompileResult) -> Option<String> {
+        let path = match &self.input {
+            SourceInput::Memory { text, .. } => return Some(text.clone()),
+            SourceInput::Path(path) => path,
+        };
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                self.fail(result, Stage::Read, &Error::Io(format!("cannot read {}: {}", path.display(), e)));
+                return None;
+            }
+        };
+        if !is_script(path) {
+            return Some(source);
+        }
+
+        let script_module = match script::parser::Parser::new(&source).parse() {
+            Ok(module) => module,
+            Err(e) => {
+                self.fail(result, Stage::Script, &Error::ScriptParse(e));
+                return None;
+            }
+        };
+        let generated = script::transpiler::Transpiler::new()
+            .with_source_file(&path.to_string_lossy())
+            .transpile(&script_module);
+        let mut transformer = script::transform::SourceTransformer::new();
+        if let Some(ref rules) = self.transform_rules {
+            transformer = match transformer.load_rules(rules) {
+                Ok(transformer) => transformer,
+                Err(error) => {
+                    self.fail(result, Stage::Script, &error);
+                    return None;
+                }
+            };
+        }
+        // Text that does not parse is kept, so the Core parser reports it
+        let generated = transformer.rewrite_source(&generated)
+            .map_or(generated, |(formatted, _)| formatted);
+        result.source_map = Some(SourceMap::new(&generated, &source));
+        result.transpiled = Some(generated.clone());
+        Some(generated)
+    }
+
+    /// Validate and optimize `module`, then generate C from it
+    fn generate_c(&self, mut module: IRModule, result: &mut CompileResult) -> Option<String> {
+        if let Err(error) = validate_module(&module) {
+            self.fail(result, Stage::Validate, &error);
+            return None;
+        }
+        let optimizer = if self.opt_level > 0 {
+            Some(Optimizer::new())
+        } else if self.strip_dead_code {
+            Some(Optimizer::dead_code_only())
+        } else {
+            None
+        };
+        if let Some(optimizer) = optimizer {
+            let start = Instant::now();
+            let mut optimizer = optimizer.with_verification(self.verify_ir);
+            let optimized = if self.timings {
+                optimizer.optimize_with_stats(&mut module, &mut result.stats)
+            } else {
+                optimizer.optimize(&mut module)
+            };
+            result.stats.optimize_time_ms = elapsed_ms(start);
+            if let Err(error) = optimized {
+                self.fail(result, Stage::Optimize, &error);
+                return None;
+            }
+            if self.timings {
+                result.stats.record_optimized_functions(&module);
+            }
+        }
+        let codegen_start = Instant::now();
+        let mut codegen = CCodeGen::new(&self.toolchain.triple, self.sanitize)
+            .with_toolchain(self.toolchain.clone())
+            .with_json_panics(self.json_panics)
+            .with_freestanding(self.freestanding.is_some(), self.freestanding.as_deref().unwrap_or(DEFAULT_ENTRY))
+            .with_jobs(self.jobs);
+        let generated = codegen.generate_source(&module);
+        result.stats.codegen_time_ms = elapsed_ms(codegen_start);
+        match generated {
+            Ok(c_source) => Some(c_source),
+            Err(error) => {
+                self.fail(result, Stage::CodeGen, &error);
+                None
+            }
+        }
+    }
+
+    /// Compile and link `c_source` in a scratch directory and read the executable back
+    fn compile_c(&self, c_source: &str, libraries: &[String]) -> Result<Vec<u8>> {
+        static BUILDS: AtomicUsize = AtomicUsize::new(0);
+        let scratch = std::env::temp_dir().join(format!(
+            "aether_session_{}_{}", std::process::id(), BUILDS.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&scratch).map_err(|e| Error::Io(e.to_string()))?;
+        let c_path = scratch.join(format!("{}.c", self.module_name()));
+        let exe_path = scratch.join(self.module_name()).with_extension(std::env::consts::EXE_EXTENSION);
+        let built = fs::write(&c_path, c_source)
+            .map_err(|e| Error::Io(e.to_string()))
+            .and_then(|_| self.run_compiler(&c_path, &exe_path, libraries))
+            .and_then(|_| fs::read(&exe_path).map_err(|e| Error::Io(e.to_string())));
+        let _ = fs::remove_dir_all(&scratch);
+        built
+    }
+
+    fn run_compiler(&self, c_path: &Path, exe_path: &Path, libraries: &[String]) -> Result<()> {
+        if let Some(ref entry) = self.freestanding {
+            // Only the object comes from the C compiler; the self-hosted
+            // linker makes the executable
+            let object_path = exe_path.with_extension("o");
+            self.compile_object(c_path, &object_path)?;
+            let object = fs::read(&object_path).map_err(|e| Error::Io(e.to_string()))?;
+            return static_link::link_objects(&[(object_path.display().to_string(), object)], entry, exe_path, None);
+        }
+        let mut linker = Linker::new();
+        for lib in libraries {
+            linker.add_library(lib);
+        }
+        let mut last_stderr = String::new();
+        for compiler in self.toolchain.compilers() {
+            let mut cmd = std::process::Command::new(&compiler);
+            cmd.args(self.toolchain.compiler_args(&compiler))
+                .args(self.sanitize.compiler_args());
+            if self.debug_info {
+                cmd.arg("-g");
+            }
+            cmd.arg("-o").arg(exe_path).arg(c_path).args(linker.link_args());
+            if let Ok(output) = cmd.output() {
+                if output.status.success() {
+                    return Ok(());
+                }
+                last_stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+            }
+        }
+        if last_stderr.is_empty() {
+            return Err(Error::CodeGen(format!("no C compiler found (tried {})", self.toolchain.compilers().join(", "))));
+        }
+        Err(Error::CodeGen(format!("C compilation failed:\n{}", last_stderr)))
+    }
+
+    /// Compile `c_path` to the freestanding object `object_path`
+    fn compile_object(&self, c_path: &Path, object_path: &Path) -> Result<()> {
+        let mut last_stderr = String::new();
+        for compiler in self.toolchain.compilers() {
+            let mut cmd = std::process::Command::new(&compiler);
+            cmd.args(self.toolchain.compiler_args(&compiler))
+                .args(static_link::FREESTANDING_CFLAGS)
+                .arg("-c").arg("-o").arg(object_path).arg(c_path);
+            if let Ok(output) = cmd.output() {
+                if output.status.success() {
+                    return Ok(());
+                }
+                last_stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+            }
+        }
+        if last_stderr.is_empty() {
+            return Err(Error::CodeGen(format!("no C compiler found (tried {})", self.toolchain.compilers().join(", "))));
+        }
+        Err(Error::CodeGen(format!("C compilation failed:\n{}", last_stderr)))
+    }
+
+    /// Record `error` as a diagnostic that stops the compilation at `stage`
+    fn fail(&self, result: &mut CompileResult, stage: Stage, error: &Error) {
+        result.diagnostics.push(ErrorReport::from_error_localized(error, &self.file_name(), self.locale));
+        result.failed_stage = Some(stage);
+    }
+
+    /// Name of the compilation unit: the file stem, or the in-memory name
+    fn module_name(&self) -> String {
+        match &self.input {
+            SourceInput::Path(path) => path.file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("module")
+                .to_string(),
+            SourceInput::Memory { name, .. } => name.clone(),
+        }
+    }
+
+    /// File name diagnostics refer to
+    fn file_name(&self) -> String {
+        match &self.input {
+            SourceInput::Path(path) => path.to_string_lossy().into_owned(),
+            SourceInput::Memory { name, .. } => name.clone(),
+        }
+    }
+
+    /// File the parsed source maps to; script sources map through the
+    /// transpiler's markers to the `.ath` file
+    fn core_path(&self) -> String {
+        match &self.input {
+            SourceInput::Path(path) if is_script(path) => path.with_extension("gen.aeth").to_string_lossy().into_owned(),
+            _ => self.file_name(),
+        }
+    }
+}
+
+/// Point the diagnostics not yet mapped back to the script into it.
+/// Reports in glue code point at the statement it was generated for.
+fn map_to_script(result: &mut CompileResult) {
+    let Some(ref map) = result.source_map else {
+        return;
+    };
+    let map_location = |location: &mut Option<Location>| {
+        let loc = location.as_mut()?;
+        let span = Span::new(loc.line as usize, loc.end_line.unwrap_or(loc.line) as usize, 0);
+        let mapped = map.map(span)?;
+        loc.line = mapped.start as u32;
+        loc.end_line = Some(mapped.end as u32);
+        Some(mapped.generated)
+    };
+    for report in &mut result.diagnostics[result.mapped_diagnostics..] {
+        let generated = map_location(&mut report.location);
+        for suggestion in &mut report.suggestions {
+            map_location(&mut suggestion.location);
+        }
+        for related in &mut report.related {
+            map_location(&mut related.location);
+        }
+        if generated == Some(true) {
+            report.add_suggestion("the code this points into was generated for the script statement shown", None, 0.0);
+        }
+    }
+    result.mapped_diagnostics = result.diagnostics.len();
+}
+
+fn is_script(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "ath")
+}
+
+fn elapsed_ms(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_reports_warnings() {
+        let result = CompileSession::from_source("w", "fn main() effect[io] {\n    let x: i64 = 1;\n}\n").check();
+        assert!(result.success());
+        let codes: Vec<&str> = result.warnings().map(|w| w.code.as_str()).collect();
+        assert_eq!(codes, ["E0040"]);
+        assert_eq!(result.stats.function_count, 1);
+    }
+
+    #[test]
+    fn test_strict_errors_note_lenient_mode() {
+        let source = "fn narrow(n: i64) {\n    let _b: u8 = n\n}\n";
+        let result = CompileSession::from_source("s", source).with_strict(true).check();
+        let error = result.errors().next().expect("lossy conversion is an error in strict mode");
+        assert_eq!(error.code, "E0050");
+        assert!(error.suggestions.iter().any(|s| s.message.contains("--lenient")), "{:?}", error.suggestions);
+
+        let result = CompileSession::from_source("s", &format!("#![production]\n{}", source)).with_strict(false).check();
+        assert!(result.success());
+        assert_eq!(result.warnings().map(|w| w.code.as_str()).collect::<Vec<_>>(), ["E0050"]);
+    }
+
+    #[test]
+    fn test_parse_error_stops_session() {
+        let result = CompileSession::from_source("p", "fn main( {}\n").emit_c();
+        assert_eq!(result.failed_stage, Some(Stage::Parse));
+        assert!(result.artifact.is_none());
+        assert_eq!(result.errors().count(), 1);
+    }
+
+    #[test]
+    fn test_emit_ir_and_max_errors() {
+        let result = CompileSession::from_source("ir", "fn one() -> i64 {\n    1\n}\n").emit_ir();
+        assert!(result.text().unwrap().contains("fn one() -> i64"), "{:?}", result.text());
+
+        let source: String = (0..4).map(|i| format!("fn f{i}() -> i64 {{ return x{i} }}\n")).collect();
+        let result = CompileSession::from_source("many", &source).with_max_errors(1).with_jobs(1).check();
+        assert_eq!(result.errors().count(), 2);
+        assert_eq!(result.stats.errors_suppressed, 3);
+    }
+
+    #[test]
+    fn test_root_resolves_imports() {
+        let source = fs::read_to_string("tests/imports/diamond.aeth").unwrap();
+        let result = CompileSession::from_source("diamond", &source).with_root("tests/imports").emit_c();
+        assert!(result.success(), "{:?}", result.diagnostics);
+        assert!(result.text().unwrap().contains("left"));
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let result = CompileSession::from_path("no/such/file.aeth").check();
+        assert_eq!(result.failed_stage, Some(Stage::Read));
+        assert!(result.errors().next().unwrap().message.to_string().contains("no/such/file.aeth"));
+    }
+
+    #[test]
+    fn test_timings() {
+        let source = "struct P { x: i64 }\nimpl P {\n    fn get(self: ref P) -> i64 { return self.x }\n}\nfn unused() -> i64 {\n    let a: i64 = 2 + 3;\n    return a\n}\nfn main() -> i64 {\n    return 0\n}\n";
+        let result = CompileSession::from_source("t", source).with_opt_level(2).with_timings(true).emit_c();
+        assert!(result.success(), "{:?}", result.diagnostics);
+        let stats = &result.stats;
+        assert_eq!((stats.function_count, stats.type_count, stats.loc), (3, 1, 11));
+        assert!(stats.passes.iter().any(|p| p.name == "constant-folding" && p.runs >= 1), "{:?}", stats.passes);
+        assert!(stats.functions.len() >= 3, "{:?}", stats.functions);
+        // Largest first
+        assert!(stats.functions.windows(2).all(|w| w[0].instructions >= w[1].instructions));
+        assert!(stats.total_time_ms >= stats.optimize_time_ms);
+
+        // Without the flag there is nothing per pass or per function
+        let result = CompileSession::from_source("t", source).with_opt_level(2).emit_c();
+        assert!(result.stats.passes.is_empty() && result.stats.functions.is_empty());
+    }
+}
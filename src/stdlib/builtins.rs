@@ -36,7 +36,7 @@ impl BuiltinRegistry {
         // I/O Functions
         self.register(BuiltinFunc {
             name: "print".to_string(),
-            params: vec![("s".to_string(), ResolvedType::String)],
+            params: vec![("s".to_string(), ResolvedType::Str)],
             ret_type: ResolvedType::UNIT,
             c_name: "aether_print".to_string(),
             variadic: false,
@@ -44,7 +44,7 @@ impl BuiltinRegistry {
 
         self.register(BuiltinFunc {
             name: "println".to_string(),
-            params: vec![("s".to_string(), ResolvedType::String)],
+            params: vec![("s".to_string(), ResolvedType::Str)],
             ret_type: ResolvedType::UNIT,
             c_name: "aether_println".to_string(),
             variadic: false,
@@ -133,12 +133,15 @@ pub fn generate_c_runtime() -> String {
     r#"
 /* AetherLang Runtime Support */
 
-static void aether_print(const char* s) {
-    printf("%s", s);
+typedef struct AethStr { char* data; size_t len; } AethStr;
+
+static void aether_print(AethStr s) {
+    fwrite(s.data, 1, s.len, stdout);
 }
 
-static void aether_println(const char* s) {
-    printf("%s\n", s);
+static void aether_println(AethStr s) {
+    fwrite(s.data, 1, s.len, stdout);
+    putchar('\n');
 }
 
 static void aether_print_i64(int64_t n) {
@@ -4,3 +4,52 @@
 
 pub mod builtins;
 
+use std::sync::OnceLock;
+
+use crate::frontend::ast::{Item, Program};
+use crate::frontend::lexer::Lexer;
+use crate::frontend::parser::Parser;
+
+/// stdlib/string.aeth, whose `String` every program can use without `use`
+const STRING_SOURCE: &str = include_str!("../../stdlib/string.aeth");
+
+/// File id of the spans in the prelude items
+pub const PRELUDE_FILE_ID: usize = usize::MAX;
+
+/// The `String` struct and its `impl` from stdlib/string.aeth; the rest of
+/// the file is only available through `use string`
+pub fn string_items() -> &'static [Item] {
+    static ITEMS: OnceLock<Vec<Item>> = OnceLock::new();
+    ITEMS.get_or_init(|| {
+        let program = Parser::new(Lexer::new(STRING_SOURCE, PRELUDE_FILE_ID))
+            .parse_program()
+            .expect("stdlib/string.aeth parses");
+        program.items.into_iter()
+            .filter(|item| match item {
+                Item::Struct(def) => def.name.name == "String",
+                Item::Impl(block) => block.target.name == "String" && block.interface.is_none(),
+                _ => false,
+            })
+            .collect()
+    })
+}
+
+/// The prelude items `program` uses: none when it declares its own `String`
+pub fn prelude_items(program: &Program) -> &'static [Item] {
+    let declares_string = program.items.iter().any(|item| matches!(item, Item::Struct(def) if def.name.name == "String"));
+    if declares_string { &[] } else { string_items() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string_items() {
+        let items = string_items();
+        assert!(matches!(items, [Item::Struct(_), Item::Impl(block)] if block.methods.iter().any(|m| m.name.name == "push_str")));
+
+        let own = Parser::new(Lexer::new("struct String { bytes: *u8 }", 0)).parse_program().unwrap();
+        assert!(prelude_items(&own).is_empty());
+    }
+}
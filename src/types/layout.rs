@@ -0,0 +1,584 @@
+//! Type Layout Computation
+//!
+//! Assigns sizes, alignments and field offsets using C-compatible rules.
+//! The struct/enum primitives here are shared by the semantic analyzer
+//! (over `ResolvedType`) and the IR (over `IRType`) so both agree on layout.
+#![allow(dead_code)]
+
+use std::collections::{HashMap, HashSet};
+
+use super::type_system::{PrimitiveType, ResolvedType};
+
+/// Pointer size/alignment for the supported 64-bit targets
+pub const POINTER_SIZE: usize = 8;
+
+/// Size of the C `enum` tag emitted for tagged unions without a `repr`
+pub const ENUM_TAG_SIZE: usize = 4;
+
+/// Size and alignment of a type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    pub size: usize,
+    pub align: usize,
+}
+
+impl Layout {
+    pub fn new(size: usize, align: usize) -> Self {
+        Self { size, align: align.max(1) }
+    }
+
+    pub fn pointer() -> Self {
+        Self::new(POINTER_SIZE, POINTER_SIZE)
+    }
+
+    pub fn primitive(prim: &PrimitiveType) -> Self {
+        Self::new(prim.size_of(), prim.align_of())
+    }
+
+    /// Layout of `[T; count]`
+    pub fn array(elem: Layout, count: usize) -> Self {
+        Self::new(elem.size * count, elem.align)
+    }
+
+    /// Layout of a SIMD vector (naturally aligned to its full width)
+    pub fn vector(elem: Layout, lanes: usize) -> Self {
+        let size = elem.size * lanes;
+        Self::new(size, size.max(elem.align))
+    }
+}
+
+/// Computed layout of a struct, including field offsets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructLayout {
+    pub size: usize,
+    pub align: usize,
+    pub offsets: Vec<usize>,
+}
+
+impl StructLayout {
+    pub fn layout(&self) -> Layout {
+        Layout::new(self.size, self.align)
+    }
+}
+
+/// Round `offset` up to the next multiple of `align`
+pub fn align_to(offset: usize, align: usize) -> usize {
+    let align = align.max(1);
+    offset.div_ceil(align) * align
+}
+
+/// Lay out fields in declaration order with C padding rules.
+/// Packed structs place fields back to back with alignment 1.
+pub fn layout_struct(fields: &[Layout], packed: bool) -> StructLayout {
+    let mut offset = 0;
+    let mut align = 1;
+    let mut offsets = Vec::with_capacity(fields.len());
+
+    for field in fields {
+        if !packed {
+            offset = align_to(offset, field.align);
+            align = align.max(field.align);
+        }
+        offsets.push(offset);
+        offset += field.size;
+    }
+
+    StructLayout {
+        size: align_to(offset, align),
+        align,
+        offsets,
+    }
+}
+
+/// Padding bytes `layout_struct` inserts between and after `fields`
+pub fn padding_bytes(fields: &[Layout], packed: bool) -> usize {
+    let data: usize = fields.iter().map(|f| f.size).sum();
+    layout_struct(fields, packed).size - data
+}
+
+/// Field order with the least padding: most aligned fields first, larger
+/// ones first among equally aligned fields, otherwise in declaration order.
+/// Returns declaration indices.
+pub fn optimal_field_order(fields: &[Layout]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..fields.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse((fields[i].align, fields[i].size)));
+    order
+}
+
+/// Lay out fields in `order` (declaration indices), returning the offsets
+/// indexed by declaration position
+pub fn layout_struct_in_order(fields: &[Layout], order: &[usize], packed: bool) -> StructLayout {
+    let ordered: Vec<Layout> = order.iter().map(|&i| fields[i]).collect();
+    let laid_out = layout_struct(&ordered, packed);
+    let mut offsets = vec![0; fields.len()];
+    for (position, &i) in order.iter().enumerate() {
+        offsets[i] = laid_out.offsets[position];
+    }
+    StructLayout { offsets, ..laid_out }
+}
+
+/// Lay out a tagged union: `struct { tag; union { payloads... } }`.
+/// Each payload is the struct layout of one variant's fields.
+pub fn layout_enum(tag: Layout, payloads: &[Layout]) -> Layout {
+    let data: Vec<&Layout> = payloads.iter().filter(|p| p.size > 0).collect();
+    if data.is_empty() {
+        return tag;
+    }
+
+    let align = data.iter().map(|p| p.align).max().unwrap_or(1);
+    let size = data.iter().map(|p| p.size).max().unwrap_or(0);
+    let union = Layout::new(align_to(size, align), align);
+    layout_struct(&[tag, union], false).layout()
+}
+
+/// Lay out an untagged union: every field starts at offset 0, so the union
+/// is as large as its largest field and as aligned as its most aligned one.
+pub fn layout_union(fields: &[Layout], packed: bool) -> StructLayout {
+    let align = if packed { 1 } else { fields.iter().map(|f| f.align).max().unwrap_or(1) };
+    let size = fields.iter().map(|f| f.size).max().unwrap_or(0);
+    StructLayout {
+        size: align_to(size, align),
+        align,
+        offsets: vec![0; fields.len()],
+    }
+}
+
+/// Widest storage unit a run of bitfields may share
+pub const MAX_BITFIELD_UNIT_BITS: u32 = 64;
+
+/// One member of a struct after its bitfields have been packed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitStorage {
+    /// An ordinary field (declaration index)
+    Field(usize),
+    /// An unsigned integer of `bytes` bytes holding a run of bitfields
+    Unit { bytes: usize },
+}
+
+/// Position of a `#[bits(N)]` field inside its storage unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitSlot {
+    /// Index into `BitfieldLayout::storage`
+    pub unit: usize,
+    /// Bit offset from the least significant bit of the unit
+    pub shift: u32,
+    pub width: u32,
+}
+
+/// Packing of `#[bits(N)]` fields.
+///
+/// Consecutive bitfields share one storage unit, filled from the least
+/// significant bit. A run ends at an ordinary field or when the next
+/// bitfield would take it past 64 bits, and its unit is the smallest of
+/// u8/u16/u32/u64 that holds it. Units are laid out like ordinary fields
+/// of that integer type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitfieldLayout {
+    /// Members in memory order
+    pub storage: Vec<BitStorage>,
+    /// Index into `storage` of each declared field
+    pub member: Vec<usize>,
+    /// Slot of each declared field that is a bitfield
+    pub slots: Vec<Option<BitSlot>>,
+}
+
+impl BitfieldLayout {
+    /// Pack fields with the given `#[bits(N)]` widths (None for ordinary fields)
+    pub fn new(bits: &[Option<u32>]) -> Self {
+        let mut storage = Vec::new();
+        let mut member = Vec::with_capacity(bits.len());
+        let mut slots = Vec::with_capacity(bits.len());
+        // Storage index and bits used of the run being filled
+        let mut run: Option<(usize, u32)> = None;
+
+        for (i, width) in bits.iter().enumerate() {
+            match *width {
+                Some(width) => {
+                    let (unit, used) = match run {
+                        Some((unit, used)) if used + width <= MAX_BITFIELD_UNIT_BITS => (unit, used),
+                        _ => {
+                            storage.push(BitStorage::Unit { bytes: 0 });
+                            (storage.len() - 1, 0)
+                        }
+                    };
+                    storage[unit] = BitStorage::Unit { bytes: Self::unit_bytes(used + width) };
+                    member.push(unit);
+                    slots.push(Some(BitSlot { unit, shift: used, width }));
+                    run = Some((unit, used + width));
+                }
+                None => {
+                    storage.push(BitStorage::Field(i));
+                    member.push(storage.len() - 1);
+                    slots.push(None);
+                    run = None;
+                }
+            }
+        }
+
+        Self { storage, member, slots }
+    }
+
+    /// Bytes of the smallest unsigned integer holding `bits` bits
+    pub fn unit_bytes(bits: u32) -> usize {
+        match bits {
+            0..=8 => 1,
+            9..=16 => 2,
+            17..=32 => 4,
+            _ => 8,
+        }
+    }
+
+    /// Lay out the packed struct; `fields` are the layouts of the declared
+    /// fields and the offsets are indexed by declaration position
+    pub fn layout(&self, fields: &[Layout]) -> StructLayout {
+        let members: Vec<Layout> = self.storage.iter()
+            .map(|member| match *member {
+                BitStorage::Field(i) => fields[i],
+                BitStorage::Unit { bytes } => Layout::new(bytes, bytes),
+            })
+            .collect();
+        let laid_out = layout_struct(&members, false);
+        let offsets = self.member.iter().map(|&m| laid_out.offsets[m]).collect();
+        StructLayout { offsets, ..laid_out }
+    }
+}
+
+/// Struct definition as seen by the layout engine
+#[derive(Debug, Clone)]
+struct StructEntry {
+    fields: Vec<(String, ResolvedType)>,
+    packed: bool,
+    /// Fields overlap at offset 0 (a `union`)
+    is_union: bool,
+    /// `#[field_order(optimal)]`: laid out by `optimal_field_order`
+    optimal: bool,
+    /// `#[bits(N)]` fields, packed by `BitfieldLayout`
+    bitfields: Option<BitfieldLayout>,
+}
+
+/// Layout engine over resolved types.
+///
+/// Struct and enum bodies are looked up by name, so fields that refer to
+/// types declared later still get their real layout.
+#[derive(Debug, Clone, Default)]
+pub struct LayoutEngine {
+    structs: HashMap<String, StructEntry>,
+    /// Tag layout and payload types of each variant
+    enums: HashMap<String, (Layout, Vec<Vec<ResolvedType>>)>,
+}
+
+impl LayoutEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a struct definition
+    pub fn register_struct(&mut self, name: &str, fields: Vec<(String, ResolvedType)>, packed: bool) {
+        self.structs.insert(name.to_string(), StructEntry { fields, packed, is_union: false, optimal: false, bitfields: None });
+    }
+
+    /// Register a `#[field_order(optimal)]` struct, whose fields are laid
+    /// out largest first. Offsets are still listed in declaration order.
+    pub fn register_optimal_struct(&mut self, name: &str, fields: Vec<(String, ResolvedType)>) {
+        self.structs.insert(name.to_string(), StructEntry { fields, packed: false, is_union: false, optimal: true, bitfields: None });
+    }
+
+    /// Register a struct with `#[bits(N)]` fields (`bits` has one entry per field)
+    pub fn register_bitfield_struct(&mut self, name: &str, fields: Vec<(String, ResolvedType)>, bits: &[Option<u32>]) {
+        let bitfields = Some(BitfieldLayout::new(bits));
+        self.structs.insert(name.to_string(), StructEntry { fields, packed: false, is_union: false, optimal: false, bitfields });
+    }
+
+    /// Register a union definition
+    pub fn register_union(&mut self, name: &str, fields: Vec<(String, ResolvedType)>, packed: bool) {
+        self.structs.insert(name.to_string(), StructEntry { fields, packed, is_union: true, optimal: false, bitfields: None });
+    }
+
+    /// Register an enum definition (one payload type list per variant)
+    /// whose discriminant is laid out as `tag`
+    pub fn register_enum(&mut self, name: &str, tag: Layout, variants: Vec<Vec<ResolvedType>>) {
+        self.enums.insert(name.to_string(), (tag, variants));
+    }
+
+    /// Payload types of each variant of enum `name`, in declaration order
+    pub fn enum_payloads(&self, name: &str) -> Option<&[Vec<ResolvedType>]> {
+        self.enums.get(name).map(|(_, variants)| variants.as_slice())
+    }
+
+    /// Compute the layout of a type, or None if it has no fixed layout
+    /// (generic parameters, unknown or infinitely sized types).
+    pub fn layout_of(&self, ty: &ResolvedType) -> Option<Layout> {
+        self.layout_inner(ty, &mut HashSet::new())
+    }
+
+    /// Compute the full layout of a named struct
+    pub fn struct_layout(&self, name: &str) -> Option<StructLayout> {
+        self.struct_layout_inner(name, &mut HashSet::new())
+    }
+
+    /// Byte offset of `field` within struct `name`
+    pub fn offset_of(&self, name: &str, field: &str) -> Option<usize> {
+        let entry = self.structs.get(name)?;
+        let index = entry.fields.iter().position(|(f, _)| f == field)?;
+        self.struct_layout(name).map(|l| l.offsets[index])
+    }
+
+    /// Bytes of padding between and after `fields` laid out in order;
+    /// fields without a fixed layout count as taking none
+    pub fn compute_padding_bytes(&self, fields: &[(String, ResolvedType)]) -> usize {
+        let layouts: Vec<Layout> = fields.iter()
+            .filter_map(|(_, ty)| self.layout_of(ty))
+            .collect();
+        padding_bytes(&layouts, false)
+    }
+
+    /// Fields of struct `name`, in declaration order
+    pub fn struct_fields(&self, name: &str) -> Option<&[(String, ResolvedType)]> {
+        self.structs.get(name).filter(|s| !s.is_union).map(|s| s.fields.as_slice())
+    }
+
+    /// Check whether a struct has a field with the given name
+    pub fn has_field(&self, name: &str, field: &str) -> bool {
+        self.structs.get(name)
+            .map(|s| s.fields.iter().any(|(f, _)| f == field))
+            .unwrap_or(false)
+    }
+
+    fn layout_inner(&self, ty: &ResolvedType, visiting: &mut HashSet<String>) -> Option<Layout> {
+        match ty {
+            ResolvedType::Primitive(p) => Some(Layout::primitive(p)),
+            ResolvedType::Pointer(_)
+            | ResolvedType::Reference { .. }
+            | ResolvedType::Function { .. } => Some(Layout::pointer()),
+            // Slices and `str` are fat pointers (data, len)
+            ResolvedType::Slice(_) | ResolvedType::Str => Some(Layout::new(POINTER_SIZE * 2, POINTER_SIZE)),
+            ResolvedType::Array { elem, size } => {
+                Some(Layout::array(self.layout_inner(elem, visiting)?, *size))
+            }
+            ResolvedType::Vector(elem, lanes) => {
+                Some(Layout::vector(self.layout_inner(elem, visiting)?, *lanes))
+            }
+            ResolvedType::Tuple(elems) => {
+                let fields = elems.iter()
+                    .map(|e| self.layout_inner(e, visiting))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(layout_struct(&fields, false).layout())
+            }
+            ResolvedType::Struct { name, .. } => {
+                if self.enums.contains_key(name) {
+                    self.enum_layout_inner(name, visiting)
+                } else {
+                    self.struct_layout_inner(name, visiting).map(|l| l.layout())
+                }
+            }
+            ResolvedType::Enum { name } => self.enum_layout_inner(name, visiting),
+            ResolvedType::Generic(..)
+            | ResolvedType::GenericWithConsts { .. }
+            | ResolvedType::GenericParam(_)
+            | ResolvedType::ConstParam { .. }
+            | ResolvedType::Unknown => None,
+        }
+    }
+
+    fn struct_layout_inner(&self, name: &str, visiting: &mut HashSet<String>) -> Option<StructLayout> {
+        let entry = self.structs.get(name)?;
+        // A struct containing itself by value has no finite size
+        if !visiting.insert(name.to_string()) {
+            return None;
+        }
+        let fields = entry.fields.iter()
+            .map(|(_, ty)| self.layout_inner(ty, visiting))
+            .collect::<Option<Vec<_>>>();
+        visiting.remove(name);
+        let fields = fields?;
+        if entry.is_union {
+            Some(layout_union(&fields, entry.packed))
+        } else if let Some(bitfields) = &entry.bitfields {
+            Some(bitfields.layout(&fields))
+        } else if entry.optimal {
+            Some(layout_struct_in_order(&fields, &optimal_field_order(&fields), false))
+        } else {
+            Some(layout_struct(&fields, entry.packed))
+        }
+    }
+
+    fn enum_layout_inner(&self, name: &str, visiting: &mut HashSet<String>) -> Option<Layout> {
+        let (tag, variants) = self.enums.get(name)?;
+        if !visiting.insert(name.to_string()) {
+            return None;
+        }
+        let payloads = variants.iter()
+            .map(|fields| {
+                let layouts = fields.iter()
+                    .map(|ty| self.layout_inner(ty, visiting))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(layout_struct(&layouts, false).layout())
+            })
+            .collect::<Option<Vec<_>>>();
+        visiting.remove(name);
+        Some(layout_enum(*tag, &payloads?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(name: &str) -> ResolvedType {
+        ResolvedType::Struct { name: name.to_string(), fields: Vec::new() }
+    }
+
+    #[test]
+    fn test_padding_between_fields() {
+        let mut engine = LayoutEngine::new();
+        engine.register_struct("S", vec![
+            ("a".to_string(), ResolvedType::U8),
+            ("b".to_string(), ResolvedType::U64),
+        ], false);
+
+        let layout = engine.struct_layout("S").unwrap();
+        assert_eq!(layout.size, 16);
+        assert_eq!(layout.align, 8);
+        assert_eq!(layout.offsets, vec![0, 8]);
+    }
+
+    #[test]
+    fn test_tail_padding_and_packed() {
+        let mut engine = LayoutEngine::new();
+        let fields = vec![
+            ("a".to_string(), ResolvedType::U32),
+            ("b".to_string(), ResolvedType::U8),
+        ];
+        engine.register_struct("Padded", fields.clone(), false);
+        engine.register_struct("Packed", fields, true);
+
+        assert_eq!(engine.layout_of(&named("Padded")), Some(Layout::new(8, 4)));
+        assert_eq!(engine.layout_of(&named("Packed")), Some(Layout::new(5, 1)));
+    }
+
+    #[test]
+    fn test_nested_struct_and_offset_of() {
+        let mut engine = LayoutEngine::new();
+        // Outer is registered before Inner to exercise lookup by name
+        engine.register_struct("Outer", vec![
+            ("flag".to_string(), ResolvedType::BOOL),
+            ("inner".to_string(), named("Inner")),
+            ("tail".to_string(), ResolvedType::U16),
+        ], false);
+        engine.register_struct("Inner", vec![
+            ("x".to_string(), ResolvedType::I32),
+            ("y".to_string(), ResolvedType::I32),
+        ], false);
+
+        assert_eq!(engine.offset_of("Outer", "inner"), Some(4));
+        assert_eq!(engine.offset_of("Outer", "tail"), Some(12));
+        assert_eq!(engine.layout_of(&named("Outer")), Some(Layout::new(16, 4)));
+        assert_eq!(engine.offset_of("Outer", "missing"), None);
+    }
+
+    #[test]
+    fn test_arrays_tuples_and_pointers() {
+        let engine = LayoutEngine::new();
+        let arr = ResolvedType::Array { elem: Box::new(ResolvedType::U16), size: 5 };
+        assert_eq!(engine.layout_of(&arr), Some(Layout::new(10, 2)));
+
+        let tuple = ResolvedType::Tuple(vec![ResolvedType::U8, ResolvedType::I32, ResolvedType::U8]);
+        assert_eq!(engine.layout_of(&tuple), Some(Layout::new(12, 4)));
+
+        let ptr = ResolvedType::Pointer(Box::new(ResolvedType::U8));
+        assert_eq!(engine.layout_of(&ptr), Some(Layout::new(8, 8)));
+    }
+
+    #[test]
+    fn test_enum_tag_and_payload() {
+        let mut engine = LayoutEngine::new();
+        let tag = Layout::new(ENUM_TAG_SIZE, ENUM_TAG_SIZE);
+        engine.register_enum("Color", tag, vec![vec![], vec![], vec![]]);
+        engine.register_enum("Byte", Layout::new(1, 1), vec![vec![], vec![ResolvedType::U8]]);
+        engine.register_enum("Value", tag, vec![
+            vec![],
+            vec![ResolvedType::I64],
+            vec![ResolvedType::U8, ResolvedType::U8],
+        ]);
+
+        assert_eq!(engine.layout_of(&ResolvedType::Enum { name: "Color".into() }), Some(Layout::new(4, 4)));
+        assert_eq!(engine.layout_of(&ResolvedType::Enum { name: "Value".into() }), Some(Layout::new(16, 8)));
+        assert_eq!(engine.layout_of(&ResolvedType::Enum { name: "Byte".into() }), Some(Layout::new(2, 1)));
+    }
+
+    #[test]
+    fn test_union_overlaps_fields() {
+        let mut engine = LayoutEngine::new();
+        engine.register_union("Bits", vec![
+            ("as_float".to_string(), ResolvedType::F32),
+            ("as_bytes".to_string(), ResolvedType::Array { elem: Box::new(ResolvedType::U8), size: 6 }),
+        ], false);
+
+        let layout = engine.struct_layout("Bits").unwrap();
+        assert_eq!(layout.size, 8);
+        assert_eq!(layout.align, 4);
+        assert_eq!(engine.offset_of("Bits", "as_bytes"), Some(0));
+    }
+
+    #[test]
+    fn test_optimal_field_order() {
+        let mut engine = LayoutEngine::new();
+        let fields = vec![
+            ("flag".to_string(), ResolvedType::BOOL),
+            ("count".to_string(), ResolvedType::U64),
+            ("tag".to_string(), ResolvedType::U16),
+        ];
+        assert_eq!(engine.compute_padding_bytes(&fields), 13);
+        engine.register_struct("Declared", fields.clone(), false);
+        engine.register_optimal_struct("Optimal", fields);
+
+        assert_eq!(engine.layout_of(&named("Declared")), Some(Layout::new(24, 8)));
+        assert_eq!(engine.layout_of(&named("Optimal")), Some(Layout::new(16, 8)));
+        // count, tag, flag; offsets stay in declaration order
+        assert_eq!(engine.struct_layout("Optimal").unwrap().offsets, vec![10, 0, 8]);
+        assert_eq!(engine.offset_of("Optimal", "tag"), Some(8));
+    }
+
+    #[test]
+    fn test_bitfields_share_storage_units() {
+        let packed = BitfieldLayout::new(&[Some(1), Some(7)]);
+        assert_eq!(packed.storage, vec![BitStorage::Unit { bytes: 1 }]);
+        assert_eq!(packed.slots[1], Some(BitSlot { unit: 0, shift: 1, width: 7 }));
+
+        // A run past 64 bits starts a new unit; an ordinary field ends the run
+        let split = BitfieldLayout::new(&[Some(60), Some(10), None, Some(9)]);
+        assert_eq!(split.storage, vec![
+            BitStorage::Unit { bytes: 8 },
+            BitStorage::Unit { bytes: 2 },
+            BitStorage::Field(2),
+            BitStorage::Unit { bytes: 2 },
+        ]);
+        assert_eq!(split.member, vec![0, 1, 2, 3]);
+
+        let mut engine = LayoutEngine::new();
+        engine.register_bitfield_struct("Flags", vec![
+            ("is_ready".to_string(), ResolvedType::BOOL),
+            ("reserved".to_string(), ResolvedType::U8),
+        ], &[Some(1), Some(7)]);
+        engine.register_bitfield_struct("Header", vec![
+            ("tag".to_string(), ResolvedType::U8),
+            ("kind".to_string(), ResolvedType::U16),
+            ("len".to_string(), ResolvedType::U16),
+            ("crc".to_string(), ResolvedType::U8),
+        ], &[None, Some(4), Some(12), None]);
+
+        assert_eq!(engine.layout_of(&named("Flags")), Some(Layout::new(1, 1)));
+        assert_eq!(engine.struct_layout("Header").unwrap().offsets, vec![0, 2, 2, 4]);
+        assert_eq!(engine.layout_of(&named("Header")), Some(Layout::new(6, 2)));
+    }
+
+    #[test]
+    fn test_unsized_types_have_no_layout() {
+        let mut engine = LayoutEngine::new();
+        engine.register_struct("Node", vec![("next".to_string(), named("Node"))], false);
+
+        assert_eq!(engine.layout_of(&named("Node")), None);
+        assert_eq!(engine.layout_of(&ResolvedType::GenericParam("T".into())), None);
+        assert_eq!(engine.layout_of(&named("Undeclared")), None);
+    }
+}
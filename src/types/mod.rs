@@ -1,5 +1,6 @@
 //! Type system module
 
 pub mod type_system;
+pub mod layout;
 
 pub use type_system::*;
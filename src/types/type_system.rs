@@ -1,6 +1,8 @@
 //! Type System for AetherLang
 #![allow(dead_code)]
 
+use crate::frontend::ast::EffectSet;
+
 /// Primitive types
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum PrimitiveType {
@@ -43,6 +45,19 @@ impl PrimitiveType {
         )
     }
     
+    /// Smallest and largest value of an integer type
+    pub fn int_range(&self) -> Option<(i128, i128)> {
+        if !self.is_integer() {
+            return None;
+        }
+        let bits = self.size_of() as u32 * 8;
+        Some(if self.is_signed() {
+            (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1)
+        } else {
+            (0, (1i128 << bits) - 1)
+        })
+    }
+
     /// Check if this is a floating-point type
     pub fn is_float(&self) -> bool {
         matches!(self, Self::F32 | Self::F64)
@@ -60,7 +75,8 @@ pub enum ResolvedType {
     Tuple(Vec<ResolvedType>),
     Struct { name: String, fields: Vec<(String, ResolvedType)> },
     Enum { name: String },
-    Function { params: Vec<ResolvedType>, ret: Box<ResolvedType> },
+    /// Function pointer; the effects limit what calling it may do
+    Function { params: Vec<ResolvedType>, ret: Box<ResolvedType>, effects: Box<EffectSet> },
     /// Generic type instantiation (e.g. Option<i32>)
     Generic(String, Vec<ResolvedType>),
     /// Generic type instantiation with const args (e.g. Matrix<f32, 3, 3>)
@@ -73,14 +89,69 @@ pub enum ResolvedType {
     GenericParam(String),
     /// Const Generic Parameter (e.g. N in struct Array<T, const N: usize>)
     ConstParam { name: String, ty: Box<ResolvedType> },
-    /// String type (pointer to char array)
-    String,
+    /// String slice `str`: a fat pointer `{ data: *u8, len: usize }`
+    Str,
     /// SIMD vector type (element type, lane count)
     /// e.g., Vector(F32, 4) = f32x4
     Vector(Box<ResolvedType>, usize),
     Unknown,
 }
 
+impl std::fmt::Display for ResolvedType {
+    /// The type as it is written in source
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn list(types: &[ResolvedType]) -> String {
+            types.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+        }
+        match self {
+            Self::Primitive(p) => f.write_str(match p {
+                PrimitiveType::I8 => "i8",
+                PrimitiveType::I16 => "i16",
+                PrimitiveType::I32 => "i32",
+                PrimitiveType::I64 => "i64",
+                PrimitiveType::Isize => "isize",
+                PrimitiveType::U8 => "u8",
+                PrimitiveType::U16 => "u16",
+                PrimitiveType::U32 => "u32",
+                PrimitiveType::U64 => "u64",
+                PrimitiveType::Usize => "usize",
+                PrimitiveType::F32 => "f32",
+                PrimitiveType::F64 => "f64",
+                PrimitiveType::Bool => "bool",
+                PrimitiveType::Char => "char",
+                PrimitiveType::Unit => "()",
+                PrimitiveType::Never => "!",
+            }),
+            Self::Pointer(inner) => write!(f, "*{}", inner),
+            Self::Reference { mutable: true, inner } => write!(f, "&mut {}", inner),
+            Self::Reference { mutable: false, inner } => write!(f, "&{}", inner),
+            Self::Array { elem, size } => write!(f, "[{}; {}]", elem, size),
+            Self::Slice(elem) => write!(f, "[{}]", elem),
+            Self::Tuple(elems) => write!(f, "({})", list(elems)),
+            Self::Struct { name, .. } | Self::Enum { name } => f.write_str(name),
+            Self::Function { params, ret, effects } if effects.is_pure || !effects.effects.is_empty() => {
+                write!(f, "fn({}) -> {} {}", list(params), ret, effects)
+            }
+            Self::Function { params, ret, .. } => write!(f, "fn({}) -> {}", list(params), ret),
+            Self::Generic(name, args) => write!(f, "{}<{}>", name, list(args)),
+            Self::GenericWithConsts { name, type_args, const_args } => {
+                let consts = const_args.iter().map(|c| match c {
+                    ConstValue::Int(n) => n.to_string(),
+                    ConstValue::Bool(b) => b.to_string(),
+                    ConstValue::Param(p) => p.clone(),
+                    _ => "_".to_string(),
+                });
+                let args: Vec<String> = type_args.iter().map(ToString::to_string).chain(consts).collect();
+                write!(f, "{}<{}>", name, args.join(", "))
+            }
+            Self::GenericParam(name) | Self::ConstParam { name, .. } => f.write_str(name),
+            Self::Str => f.write_str("str"),
+            Self::Vector(elem, lanes) => write!(f, "{}x{}", elem, lanes),
+            Self::Unknown => f.write_str("_"),
+        }
+    }
+}
+
 /// Compile-time constant value for const generics
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ConstValue {
@@ -1,5 +1,7 @@
 //! Error handling for AetherLang
 
+use crate::frontend::ast::BinOp;
+use crate::frontend::formatter::binop_str;
 use crate::utils::Span;
 use thiserror::Error;
 
@@ -40,14 +42,23 @@ pub enum Error {
     
     #[error("Invalid operator")]
     InvalidOperator { span: Span },
+
+    #[error("Unterminated {what}")]
+    Unterminated { what: &'static str, span: Span },
+
+    #[error("Nesting too deep (more than {limit} levels)")]
+    NestingTooDeep { limit: usize, span: Span },
     
     // ==================== Semantic Errors ====================
     
     #[error("Undefined variable: {name}")]
     UndefinedVariable { name: String, span: Span },
     
-    #[error("Duplicate definition: {name}")]
-    DuplicateDefinition { name: String, span: Span },
+    #[error("Duplicate definition: {name} (first defined at offset {})", .first.start)]
+    DuplicateDefinition { name: String, first: Span, span: Span },
+    
+    #[error("Symbol '{symbol}' is defined twice (at offsets {} and {})", .first.start, .second.start)]
+    DuplicateSymbol { symbol: String, first: Span, second: Span },
     
     #[error("Type mismatch: expected {expected}, got {got}")]
     TypeMismatch {
@@ -69,10 +80,25 @@ pub enum Error {
     
     #[error("Expression is not a struct")]
     NotAStruct { span: Span },
+
+    #[error("No field '{field}' on type '{ty}', which is not a struct or a pointer to one")]
+    FieldOfNonStruct { field: String, ty: String, span: Span },
+
+    #[error("Variable '{name}' is not bound in every alternative of the pattern")]
+    InconsistentOrPattern { name: String, span: Span },
+
+    #[error("Refutable pattern in `let`: use `match` or `if let` to handle the values it does not match")]
+    RefutablePattern { span: Span },
+
+    #[error("Pattern for '{name}' does not mention {fields}; list them or end the pattern with `..`")]
+    MissingPatternFields { name: String, fields: String, span: Span },
     
     #[error("Undefined type: {name}")]
     UndefinedType { name: String, span: Span },
     
+    #[error("No method '{method}' found for type {ty}")]
+    MethodNotFound { method: String, ty: String, span: Span },
+    
     #[error("Unknown field: {field}")]
     UnknownField { field: String, span: Span },
     
@@ -82,6 +108,128 @@ pub enum Error {
     #[error("Expression is not indexable")]
     NotIndexable { span: Span },
     
+    #[error("Type has no known size or layout: {ty}")]
+    UnsizedType { ty: String, span: Span },
+    
+    #[error("Initializer of static '{name}' is not a constant expression")]
+    NonConstInitializer { name: String, span: Span },
+    
+    #[error("{what} requires an unsafe block")]
+    UnsafeRequired { what: String, span: Span },
+
+    #[error("Unnecessary unsafe block: it contains no unsafe operations")]
+    UnnecessaryUnsafe { span: Span },
+
+    #[error("{operation} outside of an unsafe block")]
+    UnsafeOperationOutsideUnsafeBlock { operation: String, span: Span },
+
+    #[error("Invalid invariant of struct '{name}': {reason}")]
+    InvalidInvariant { name: String, reason: String, span: Span },
+    
+    #[error("'{name}' is private to module '{module}'")]
+    PrivateSymbol { name: String, module: String, span: Span },
+
+    #[error("Invalid asm operand '{operand}': {reason}")]
+    InvalidAsmOperand { operand: String, reason: String, span: Span },
+    
+    #[error("repr({repr}) is not valid on {item}")]
+    InvalidRepr { repr: String, item: String, span: Span },
+
+    #[error("'{variant}' has the same discriminant as '{first}'")]
+    DuplicateDiscriminant { variant: String, first: String, span: Span },
+
+    #[error("Discriminant of '{variant}' does not fit in {ty}")]
+    DiscriminantOutOfRange { variant: String, ty: String, span: Span },
+
+    #[error("`let {name}` shadows the parameter '{name}', which becomes inaccessible")]
+    ShadowedParameter { name: String, span: Span },
+
+    #[error("Unused variable '{name}'")]
+    UnusedVariable { name: String, span: Span },
+
+    #[error("{kind} '{name}' is never used")]
+    UnusedItem { kind: String, name: String, span: Span },
+
+    #[error("Field '{name}' is never read")]
+    UnusedField { name: String, span: Span },
+
+    #[error("Unreachable statement")]
+    UnreachableCode { span: Span },
+
+    #[error("Function '{name}' must return {expected}, but its body ends in a statement")]
+    MissingTailValue { name: String, expected: String, span: Span },
+    
+    #[error("Too many errors (limit {limit}); stopping here")]
+    TooManyErrors { limit: usize },
+
+    #[error("Cannot iterate over '{ty}' with `for`")]
+    NotIterable { ty: String, span: Span },
+
+    #[error("Unknown attribute '#[{name}]'")]
+    UnknownAttribute { name: String, span: Span },
+
+    #[error("Attribute '#[{name}]' cannot be applied to {item}")]
+    MisplacedAttribute { name: String, item: String, span: Span },
+
+    #[error("Fields of struct '{name}' take {padding} bytes of padding; ordered largest first they would take {optimal}")]
+    SuboptimalFieldOrder { name: String, padding: usize, optimal: usize, span: Span },
+
+    #[error("Implicit conversion from {from} to {to} may lose information")]
+    LossyConversion { from: String, to: String, span: Span },
+
+    #[error("'{name}' needs the C library, which --freestanding leaves out; declare what the program needs in an `extern` block")]
+    NotFreestanding { name: String, span: Span },
+
+    #[error("Cannot assign to this expression; only variables, fields, elements and dereferenced pointers can be assigned")]
+    InvalidAssignTarget { span: Span },
+
+    #[error("Cannot assign to '{name}', which is not declared `mut`")]
+    AssignToImmutable { name: String, span: Span },
+
+    #[error("Values of type '{ty}' cannot be compared with == or !=")]
+    NotEquatable { ty: String, span: Span },
+
+    #[error("Values of type '{ty}' have no ordering; <, <=, > and >= compare numbers, chars and pointers")]
+    NotOrdered { ty: String, span: Span },
+
+    #[error("Cannot infer the element type of an empty array literal")]
+    EmptyArrayType { span: Span },
+
+    #[error("Cannot infer the type of {what}")]
+    UninferredType { what: String, span: Span },
+
+    #[error("Operator '{}' on '{ty}' needs an impl of '{}' for it taking '{rhs}'", binop_str(*.op), .op.operator_interface().unwrap_or_default().0)]
+    MissingOperatorImpl { op: BinOp, ty: String, rhs: String, span: Span },
+
+    #[error("Invalid bitfield '{field}': {reason}")]
+    InvalidBitfield { field: String, reason: String, span: Span },
+
+    #[error("'{ty}' does not implement interface '{interface}', which the called function requires of it")]
+    UnsatisfiedBound { ty: String, interface: String, span: Span },
+
+    #[error("Method of interface '{interface}' called on '{param}', which has no bound '{param}: {interface}'")]
+    MissingBound { param: String, interface: String, span: Span },
+
+    #[error("'{ty}' cannot be stored in '{container}'; store a pointer to it instead")]
+    UnsupportedPayload { ty: String, container: String, span: Span },
+
+    #[error("Match arms do not cover `{missing}`")]
+    NonExhaustiveMatch { missing: String, span: Span },
+
+    #[error("impl of '{interface}' is missing associated type '{name}'")]
+    MissingAssociatedType { name: String, interface: String, span: Span },
+    
+    #[error("Unsupported signature for main: {reason}")]
+    InvalidMainSignature { reason: String, span: Span },
+
+    #[error("Generic argument count mismatch for '{name}': expected {expected}, got {got}")]
+    GenericArgCountMismatch {
+        name: String,
+        expected: usize,
+        got: usize,
+        span: Span,
+    },
+    
     // ==================== Ownership Errors ====================
     
     #[error("Use of moved value: {var}")]
@@ -119,8 +267,39 @@ pub enum Error {
     #[error("Code generation error: {0}")]
     CodeGen(String),
     
+    #[error("Invalid IR:\n{0}")]
+    InvalidIr(String),
+    
+    #[error("IR parse error at line {line}: {message}")]
+    IrParse { line: usize, message: String },
+    
     #[error("Module error: {0}")]
     ModuleError(String),
+
+    #[error("Cannot import '{module}': {reason}")]
+    ImportFailed { module: String, reason: String, span: Span },
+
+    #[error("Circular import: {}", cycle.join(" -> "))]
+    CyclicImport { cycle: Vec<String>, span: Span },
+
+    #[error("Invalid cross-compilation config: {0}")]
+    CrossConfig(String),
+
+    #[error("Invalid transform rules: {0}")]
+    TransformRules(String),
+
+    /// Aether Script that does not parse
+    #[error("{0}")]
+    ScriptParse(String),
+
+    #[error("LTO link error: {0}")]
+    Lto(String),
+
+    #[error("Invalid linker script: {0}")]
+    LinkerScript(String),
+
+    #[error("Link error: {0}")]
+    Link(String),
 }
 
 impl Error {
@@ -135,16 +314,64 @@ impl Error {
             Self::ExpectedPattern { span } => Some(*span),
             Self::ExpectedArraySize { span } => Some(*span),
             Self::InvalidOperator { span } => Some(*span),
+            Self::Unterminated { span, .. } => Some(*span),
+            Self::NestingTooDeep { span, .. } => Some(*span),
             Self::UndefinedVariable { span, .. } => Some(*span),
             Self::DuplicateDefinition { span, .. } => Some(*span),
+            Self::DuplicateSymbol { second, .. } => Some(*second),
             Self::TypeMismatch { span, .. } => Some(*span),
             Self::ArgCountMismatch { span, .. } => Some(*span),
             Self::NotCallable { span } => Some(*span),
             Self::NotAStruct { span } => Some(*span),
+            Self::FieldOfNonStruct { span, .. } => Some(*span),
+            Self::InconsistentOrPattern { span, .. } => Some(*span),
+            Self::RefutablePattern { span } => Some(*span),
+            Self::MissingPatternFields { span, .. } => Some(*span),
             Self::UndefinedType { span, .. } => Some(*span),
+            Self::MethodNotFound { span, .. } => Some(*span),
             Self::UnknownField { span, .. } => Some(*span),
             Self::CannotDeref { span } => Some(*span),
             Self::NotIndexable { span } => Some(*span),
+            Self::UnsizedType { span, .. } => Some(*span),
+            Self::NonConstInitializer { span, .. } => Some(*span),
+            Self::UnsafeRequired { span, .. } => Some(*span),
+            Self::UnnecessaryUnsafe { span } => Some(*span),
+            Self::UnsafeOperationOutsideUnsafeBlock { span, .. } => Some(*span),
+            Self::InvalidInvariant { span, .. } => Some(*span),
+            Self::PrivateSymbol { span, .. } => Some(*span),
+            Self::InvalidAsmOperand { span, .. } => Some(*span),
+            Self::InvalidRepr { span, .. } => Some(*span),
+            Self::DuplicateDiscriminant { span, .. } => Some(*span),
+            Self::DiscriminantOutOfRange { span, .. } => Some(*span),
+            Self::ShadowedParameter { span, .. } => Some(*span),
+            Self::UnusedVariable { span, .. } => Some(*span),
+            Self::UnusedItem { span, .. } => Some(*span),
+            Self::UnusedField { span, .. } => Some(*span),
+            Self::UnreachableCode { span } => Some(*span),
+            Self::MissingTailValue { span, .. } => Some(*span),
+            Self::NotIterable { span, .. } => Some(*span),
+            Self::UnknownAttribute { span, .. } => Some(*span),
+            Self::MisplacedAttribute { span, .. } => Some(*span),
+            Self::SuboptimalFieldOrder { span, .. } => Some(*span),
+            Self::LossyConversion { span, .. } => Some(*span),
+            Self::NotFreestanding { span, .. } => Some(*span),
+            Self::InvalidAssignTarget { span } => Some(*span),
+            Self::AssignToImmutable { span, .. } => Some(*span),
+            Self::NotEquatable { span, .. } => Some(*span),
+            Self::NotOrdered { span, .. } => Some(*span),
+            Self::EmptyArrayType { span } => Some(*span),
+            Self::UninferredType { span, .. } => Some(*span),
+            Self::MissingOperatorImpl { span, .. } => Some(*span),
+            Self::InvalidBitfield { span, .. } => Some(*span),
+            Self::UnsatisfiedBound { span, .. } => Some(*span),
+            Self::MissingBound { span, .. } => Some(*span),
+            Self::UnsupportedPayload { span, .. } => Some(*span),
+            Self::NonExhaustiveMatch { span, .. } => Some(*span),
+            Self::ImportFailed { span, .. } => Some(*span),
+            Self::CyclicImport { span, .. } => Some(*span),
+            Self::MissingAssociatedType { span, .. } => Some(*span),
+            Self::InvalidMainSignature { span, .. } => Some(*span),
+            Self::GenericArgCountMismatch { span, .. } => Some(*span),
             Self::UseAfterMove { span, .. } => Some(*span),
             Self::CannotMoveWhileBorrowed { span, .. } => Some(*span),
             Self::CannotMutBorrowWhileBorrowed { span, .. } => Some(*span),
@@ -153,7 +380,100 @@ impl Error {
             Self::CannotMoveOutOfBorrow { span, .. } => Some(*span),
             Self::CannotBorrowMutably { span, .. } => Some(*span),
             Self::EffectViolation { span, .. } => Some(*span),
-            Self::Io(_) | Self::Llvm(_) | Self::CodeGen(_) | Self::ModuleError(_) => None,
+            Self::Io(_) | Self::Llvm(_) | Self::CodeGen(_) | Self::InvalidIr(_) | Self::IrParse { .. } | Self::ModuleError(_) | Self::CrossConfig(_) | Self::TransformRules(_) | Self::ScriptParse(_) | Self::Lto(_) | Self::LinkerScript(_) | Self::Link(_) | Self::TooManyErrors { .. } => None,
+        }
+    }
+
+    /// Stable diagnostic code, explained by `aethc explain <code>`
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::TypeMismatch { .. } => "E0001",
+            Self::UndefinedVariable { .. } => "E0002",
+            Self::ArgCountMismatch { .. } => "E0003",
+            Self::DuplicateDefinition { .. } => "E0004",
+            Self::DuplicateSymbol { .. } => "E0005",
+            Self::NotCallable { .. } => "E0006",
+            Self::NotAStruct { .. } => "E0007",
+            Self::UndefinedType { .. } => "E0008",
+            Self::MethodNotFound { .. } => "E0009",
+            Self::EffectViolation { .. } => "E0010",
+            Self::UnknownField { .. } => "E0011",
+            Self::CannotDeref { .. } => "E0012",
+            Self::NotIndexable { .. } => "E0013",
+            Self::UnsizedType { .. } => "E0014",
+            Self::NonConstInitializer { .. } => "E0015",
+            Self::UnsafeRequired { .. } => "E0016",
+            Self::InvalidAsmOperand { .. } => "E0017",
+            Self::InvalidRepr { .. } => "E0018",
+            Self::MissingAssociatedType { .. } => "E0019",
+            Self::InvalidMainSignature { .. } => "E0020",
+            Self::GenericArgCountMismatch { .. } => "E0021",
+            Self::UnnecessaryUnsafe { .. } => "E0022",
+            Self::UnsafeOperationOutsideUnsafeBlock { .. } => "E0023",
+            Self::InvalidInvariant { .. } => "E0024",
+            Self::PrivateSymbol { .. } => "E0025",
+            Self::FieldOfNonStruct { .. } => "E0026",
+            Self::InconsistentOrPattern { .. } => "E0027",
+            Self::RefutablePattern { .. } => "E0028",
+            Self::MissingPatternFields { .. } => "E0029",
+            Self::UseAfterMove { .. } => "E0030",
+            Self::CannotMoveWhileBorrowed { .. } => "E0031",
+            Self::CannotMutBorrowWhileBorrowed { .. } => "E0032",
+            Self::CannotBorrowWhileMutBorrowed { .. } => "E0033",
+            Self::CannotMutBorrowTwice { .. } => "E0034",
+            Self::CannotMoveOutOfBorrow { .. } => "E0035",
+            Self::CannotBorrowMutably { .. } => "E0036",
+            Self::DuplicateDiscriminant { .. } => "E0037",
+            Self::DiscriminantOutOfRange { .. } => "E0038",
+            Self::ShadowedParameter { .. } => "E0039",
+            Self::UnusedVariable { .. } => "E0040",
+            Self::UnusedItem { .. } => "E0041",
+            Self::UnusedField { .. } => "E0042",
+            Self::UnreachableCode { .. } => "E0043",
+            Self::MissingTailValue { .. } => "E0044",
+            Self::TooManyErrors { .. } => "E0045",
+            Self::NotIterable { .. } => "E0046",
+            Self::UnknownAttribute { .. } => "E0047",
+            Self::MisplacedAttribute { .. } => "E0048",
+            Self::SuboptimalFieldOrder { .. } => "E0049",
+            Self::LossyConversion { .. } => "E0050",
+            Self::NotFreestanding { .. } => "E0051",
+            Self::InvalidAssignTarget { .. } => "E0052",
+            Self::AssignToImmutable { .. } => "E0053",
+            Self::NotEquatable { .. } => "E0054",
+            Self::NotOrdered { .. } => "E0055",
+            Self::EmptyArrayType { .. } => "E0056",
+            Self::UninferredType { .. } => "E0057",
+            Self::MissingOperatorImpl { .. } => "E0058",
+            Self::InvalidBitfield { .. } => "E0059",
+            Self::UnsatisfiedBound { .. } => "E0060",
+            Self::MissingBound { .. } => "E0061",
+            Self::UnsupportedPayload { .. } => "E0062",
+            Self::NonExhaustiveMatch { .. } => "E0063",
+            Self::UnexpectedToken { .. } => "E0100",
+            Self::Expected(..) => "E0101",
+            Self::ExpectedIdent { .. } => "E0102",
+            Self::ExpectedType { .. } => "E0103",
+            Self::ExpectedExpr { .. } => "E0104",
+            Self::ExpectedPattern { .. } => "E0105",
+            Self::ExpectedArraySize { .. } => "E0106",
+            Self::InvalidOperator { .. } => "E0107",
+            Self::Unterminated { .. } => "E0108",
+            Self::NestingTooDeep { .. } => "E0109",
+            Self::ModuleError(_) => "E0200",
+            Self::ImportFailed { .. } => "E0201",
+            Self::CyclicImport { .. } => "E0202",
+            Self::CrossConfig(_) => "E0300",
+            Self::TransformRules(_) => "E0301",
+            Self::ScriptParse(_) => "E0302",
+            Self::LinkerScript(_) => "E0303",
+            Self::Link(_) => "E0304",
+            Self::Io(_) => "E9001",
+            Self::Llvm(_) => "E9002",
+            Self::CodeGen(_) => "E9003",
+            Self::InvalidIr(_) => "E9004",
+            Self::IrParse { .. } => "E0400",
+            Self::Lto(_) => "E0401",
         }
     }
 }
@@ -2,6 +2,8 @@
 
 mod span;
 mod error;
+mod parallel;
 
-pub use span::Span;
+pub use span::{SourceLines, SourceMarker, Span, SOURCE_MARKER};
 pub use error::{Error, Result};
+pub use parallel::{default_jobs, map_chunks, STACK_SIZE};
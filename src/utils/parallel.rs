@@ -0,0 +1,61 @@
+//! Work splitting for `--jobs`
+//!
+//! Passes that handle functions independently split them into contiguous
+//! chunks, one per worker thread, and concatenate the results in chunk order.
+//! The output therefore does not depend on the number of workers.
+
+use std::thread;
+
+/// Stack size of the compiler's threads, the driver's and the workers'.
+/// Every phase recurses over nested code, up to the nesting limit.
+pub const STACK_SIZE: usize = 256 << 20;
+
+/// Worker count when `--jobs` is not given
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Run `f` on at most `jobs` contiguous chunks of `items` concurrently,
+/// returning the results in the order of the chunks
+pub fn map_chunks<T, R, F>(items: &[T], jobs: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&[T]) -> R + Sync,
+{
+    let jobs = jobs.clamp(1, items.len().max(1));
+    if jobs == 1 {
+        return vec![f(items)];
+    }
+    let chunk_size = items.len().div_ceil(jobs);
+    thread::scope(|scope| {
+        let workers: Vec<_> = items.chunks(chunk_size)
+            .map(|chunk| {
+                let f = &f;
+                thread::Builder::new()
+                    .stack_size(STACK_SIZE)
+                    .spawn_scoped(scope, move || f(chunk))
+                    .expect("failed to spawn worker thread")
+            })
+            .collect();
+        workers.into_iter()
+            .map(|worker| worker.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_chunks_keeps_order() {
+        let items: Vec<usize> = (0..10).collect();
+        for jobs in [0, 1, 3, 10, 64] {
+            let chunks = map_chunks(&items, jobs, |chunk| chunk.to_vec());
+            assert!(chunks.len() <= jobs.max(1));
+            assert_eq!(chunks.concat(), items);
+        }
+        assert_eq!(map_chunks(&[] as &[usize], 4, |chunk| chunk.len()), vec![0]);
+    }
+}
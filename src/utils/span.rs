@@ -3,6 +3,7 @@
 
 /// A span represents a range in the source code
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "json-ast", derive(serde::Serialize, serde::Deserialize))]
 pub struct Span {
     /// Start byte offset
     pub start: usize,
@@ -48,3 +49,117 @@ impl Default for Span {
         Self::dummy()
     }
 }
+
+/// Comment left by code generators (the `.ath` transpiler) naming the
+/// original location of the next line: `// @source <file>:<line>[:<col>]`
+pub const SOURCE_MARKER: &str = "// @source ";
+
+/// Ending of a marker on glue code, which has no text of its own in the
+/// original file and names the statement it was generated for
+pub const GENERATED_SUFFIX: &str = " (generated)";
+
+/// The location a `SOURCE_MARKER` comment names
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceMarker {
+    pub file: String,
+    /// 1-based line in `file`
+    pub line: usize,
+    /// 1-based column in `file`; markers without one map the lines after
+    /// them to the lines after `line`
+    pub column: Option<usize>,
+    /// Whether the code after the marker is glue generated for the statement
+    pub generated: bool,
+}
+
+impl SourceMarker {
+    /// The marker in a comment line, if it is one
+    pub fn parse(line: &str) -> Option<Self> {
+        let text = line.trim().strip_prefix(SOURCE_MARKER)?;
+        let (text, generated) = match text.strip_suffix(GENERATED_SUFFIX) {
+            Some(text) => (text, true),
+            None => (text, false),
+        };
+        let (rest, last) = text.rsplit_once(':')?;
+        let last: usize = last.parse().ok()?;
+        let (file, line, column) = match rest.rsplit_once(':').and_then(|(file, l)| Some((file, l.parse().ok()?))) {
+            Some((file, line)) => (file, line, Some(last)),
+            None => (rest, last, None),
+        };
+        Some(Self { file: file.to_string(), line, column, generated })
+    }
+}
+
+impl std::fmt::Display for SourceMarker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}:{}", SOURCE_MARKER, self.file, self.line)?;
+        if let Some(column) = self.column {
+            write!(f, ":{}", column)?;
+        }
+        if self.generated {
+            f.write_str(GENERATED_SUFFIX)?;
+        }
+        Ok(())
+    }
+}
+
+/// Resolves byte offsets in one source text to file, line and column.
+/// Lines after a `SOURCE_MARKER` are reported in the file it names: at
+/// its line when it has a column, otherwise counting on from its line.
+#[derive(Clone)]
+pub struct SourceLines {
+    file: String,
+    /// Byte offset at which each line starts
+    line_starts: Vec<usize>,
+    /// Line of each marker, and the marker
+    markers: Vec<(usize, SourceMarker)>,
+}
+
+impl SourceLines {
+    pub fn new(file: &str, source: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        let markers = source.lines().enumerate()
+            .filter_map(|(i, line)| Some((i + 1, SourceMarker::parse(line)?)))
+            .collect();
+        Self { file: file.to_string(), line_starts, markers }
+    }
+
+    /// File, 1-based line and 1-based column of the start of `span`
+    pub fn locate(&self, span: Span) -> (&str, usize, usize) {
+        let line = self.line_starts.partition_point(|&start| start <= span.start);
+        let col = span.start - self.line_starts[line - 1] + 1;
+        match self.markers.iter().rev().find(|(marker, _)| *marker < line) {
+            Some((_, marker)) if marker.column.is_some() => (&marker.file, marker.line, col),
+            Some((at, marker)) => (&marker.file, marker.line + (line - at - 1), col),
+            None => (&self.file, line, col),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate() {
+        let source = "fn a() {}\n// @source demo.ath:10\nfn b() {\n    c()\n}\n";
+        let lines = SourceLines::new("demo.gen.aeth", source);
+        assert_eq!(lines.locate(Span::new(3, 4, 0)), ("demo.gen.aeth", 1, 4));
+        let call = source.find("c()").unwrap();
+        assert_eq!(lines.locate(Span::new(call, call + 3, 0)), ("demo.ath", 11, 5));
+    }
+
+    #[test]
+    fn test_source_marker() {
+        let marker = SourceMarker::parse("    // @source C:/demo.ath:4:9 (generated)").unwrap();
+        assert_eq!(marker, SourceMarker { file: "C:/demo.ath".into(), line: 4, column: Some(9), generated: true });
+        assert_eq!(marker.to_string(), "// @source C:/demo.ath:4:9 (generated)");
+        assert_eq!(SourceMarker::parse("// @source demo.ath:10").unwrap().column, None);
+        assert_eq!(SourceMarker::parse("// source demo.ath:10"), None);
+
+        let source = "// @source demo.ath:3:1\nfn b() {\n    c()\n}\n";
+        let call = source.find("c()").unwrap();
+        assert_eq!(SourceLines::new("demo.gen.aeth", source).locate(Span::new(call, call, 0)), ("demo.ath", 3, 5));
+    }
+}
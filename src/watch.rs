@@ -0,0 +1,287 @@
+//! `aethc watch`: recheck or rebuild whenever a watched file changes
+//!
+//! The watched set is the entry file and every module it imports, directly or
+//! transitively (plus all `.aeth` files under a watched directory). It is
+//! recomputed after each run, so adding a `use` starts watching the new module.
+//! Files are polled for their modification time; a run starts once nothing has
+//! changed for `DEBOUNCE`, so an editor writing several files triggers one run.
+//!
+//! Each run is a child `aethc check` or `aethc build`, which keeps a failing
+//! build from ending the watcher.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{self, Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::frontend::lexer::Lexer;
+use crate::frontend::module::ModuleLoader;
+use crate::frontend::parser::Parser;
+
+/// How often modification times are sampled
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Quiet period after the last change before a run starts
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// What `aethc watch` was asked to do
+pub struct WatchOptions {
+    /// File or directory given on the command line
+    pub input: PathBuf,
+    /// Build an executable instead of only checking
+    pub build: bool,
+    /// Executable path for builds
+    pub output: Option<PathBuf>,
+    /// Shell command run after each successful build
+    pub exec: Option<String>,
+    /// Print one JSON object per run
+    pub json: bool,
+    /// Compiler flags forwarded to every run
+    pub compiler_args: Vec<OsString>,
+}
+
+/// Watch until interrupted
+pub fn watch(options: WatchOptions) -> ! {
+    let (entry, dir) = if options.input.is_dir() {
+        let entry = options.input.join("main.aeth");
+        if !entry.is_file() {
+            eprintln!("Error: {} has no main.aeth", options.input.display());
+            process::exit(1);
+        }
+        (entry, Some(options.input.clone()))
+    } else {
+        (options.input.clone(), None)
+    };
+    let exe = std::env::current_exe().unwrap_or_else(|e| {
+        eprintln!("Error: cannot locate the compiler executable: {}", e);
+        process::exit(1);
+    });
+
+    let mut files = watched_files(&entry, dir.as_deref(), &[]);
+    let mut snapshot = Snapshot::take(&files);
+    let mut running: Option<Child> = None;
+    loop {
+        stop(&mut running);
+        let started = Instant::now();
+        let result = rebuild(&exe, &entry, &options);
+        let elapsed = started.elapsed();
+        files = watched_files(&entry, dir.as_deref(), &files);
+        report(&options, &result, elapsed, &files);
+        if result.success {
+            if let Some(cmd) = &options.exec {
+                running = spawn_shell(cmd);
+            }
+        }
+
+        snapshot = wait_for_change(&files, snapshot);
+    }
+}
+
+/// Outcome of one child compiler run
+struct RunResult {
+    success: bool,
+    /// Everything the compiler wrote to stderr: diagnostics and errors
+    stderr: String,
+}
+
+fn rebuild(exe: &Path, entry: &Path, options: &WatchOptions) -> RunResult {
+    let mut cmd = Command::new(exe);
+    cmd.args(&options.compiler_args);
+    if options.build || options.exec.is_some() {
+        cmd.arg("build").arg(entry);
+        if let Some(output) = &options.output {
+            cmd.arg("-o").arg(output);
+        }
+    } else {
+        cmd.arg("check").arg(entry);
+    }
+    match cmd.stdin(Stdio::null()).output() {
+        Ok(output) => RunResult {
+            success: output.status.success(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        },
+        Err(e) => RunResult { success: false, stderr: format!("Error: cannot run compiler: {}\n", e) },
+    }
+}
+
+/// Print the diagnostics and a status line for one run
+fn report(options: &WatchOptions, result: &RunResult, elapsed: Duration, files: &[PathBuf]) {
+    if options.json {
+        // The child printed one JSON diagnostic per line; anything else
+        // (link errors and the like) is passed on as plain text
+        let mut diagnostics = Vec::new();
+        let mut output = String::new();
+        for line in result.stderr.lines() {
+            match serde_json::from_str::<serde_json::Value>(line) {
+                Ok(value) if value.is_object() => diagnostics.push(value),
+                _ => {
+                    output.push_str(line);
+                    output.push('\n');
+                }
+            }
+        }
+        let event = serde_json::json!({
+            "event": "rebuild",
+            "success": result.success,
+            "duration_ms": elapsed.as_millis() as u64,
+            "files": files.iter().map(|f| f.to_string_lossy()).collect::<Vec<_>>(),
+            "diagnostics": diagnostics,
+            "output": output,
+        });
+        println!("{}", event);
+    } else {
+        print!("\x1b[2J\x1b[H");
+        eprint!("{}", result.stderr);
+        let what = if options.build || options.exec.is_some() { "build" } else { "check" };
+        let status = if result.success { "ok" } else { "failed" };
+        println!(
+            "[watch] {} {} in {:.2}s, watching {} file{}",
+            what, status, elapsed.as_secs_f64(), files.len(), if files.len() == 1 { "" } else { "s" }
+        );
+    }
+    let _ = std::io::stdout().flush();
+}
+
+/// Entry file, the modules it imports and, for a directory, every `.aeth`
+/// file under it. If the entry does not parse the imports cannot be known,
+/// so the previous set is kept.
+fn watched_files(entry: &Path, dir: Option<&Path>, previous: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = vec![entry.to_path_buf()];
+    let parsed = fs::read_to_string(entry).ok().and_then(|source| {
+        Parser::new(Lexer::new(&source, 0)).parse_program().ok()
+    });
+    match parsed {
+        Some(program) => files.extend(ModuleLoader::new().dependency_files(&program.items)),
+        None => files.extend(previous.iter().skip(1).cloned()),
+    }
+    if let Some(dir) = dir {
+        collect_sources(dir, &mut files);
+    }
+    let mut unique = Vec::with_capacity(files.len());
+    for file in files {
+        if !unique.contains(&file) {
+            unique.push(file);
+        }
+    }
+    unique
+}
+
+fn collect_sources(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok().map(|e| e.path())).collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            collect_sources(&path, files);
+        } else if path.extension().is_some_and(|ext| ext == "aeth") {
+            files.push(path);
+        }
+    }
+}
+
+/// Poll until a file changes from `last`, then until nothing has changed for
+/// `DEBOUNCE`; returns the settled snapshot. Files that were added to the set
+/// since `last` was taken only count once they change again.
+fn wait_for_change(files: &[PathBuf], mut last: Snapshot) -> Snapshot {
+    loop {
+        let current = Snapshot::take(files);
+        if current.changed_from(&last) {
+            return wait_until_quiet(files, current);
+        }
+        last = current;
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn wait_until_quiet(files: &[PathBuf], mut last: Snapshot) -> Snapshot {
+    let mut quiet_since = Instant::now();
+    while quiet_since.elapsed() < DEBOUNCE {
+        thread::sleep(POLL_INTERVAL);
+        let current = Snapshot::take(files);
+        if current.changed_from(&last) {
+            quiet_since = Instant::now();
+            last = current;
+        }
+    }
+    last
+}
+
+/// Modification time of each watched file; `None` for a missing file
+#[derive(Debug, Clone, PartialEq)]
+struct Snapshot(HashMap<PathBuf, Option<SystemTime>>);
+
+impl Snapshot {
+    fn take(files: &[PathBuf]) -> Self {
+        Self(files.iter()
+            .map(|f| (f.clone(), fs::metadata(f).and_then(|m| m.modified()).ok()))
+            .collect())
+    }
+
+    /// Whether a file present in both snapshots changed, appeared or vanished
+    fn changed_from(&self, earlier: &Snapshot) -> bool {
+        self.0.iter().any(|(file, time)| earlier.0.get(file).is_some_and(|t| t != time))
+    }
+}
+
+/// Kill the previous `--exec` process, if it is still running
+fn stop(running: &mut Option<Child>) {
+    if let Some(mut child) = running.take() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+fn spawn_shell(cmd: &str) -> Option<Child> {
+    let mut command = if cfg!(windows) {
+        let mut c = Command::new("cmd");
+        c.arg("/C");
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.arg("-c");
+        c
+    };
+    match command.arg(cmd).spawn() {
+        Ok(child) => Some(child),
+        Err(e) => {
+            eprintln!("[watch] cannot run '{}': {}", cmd, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_detects_changes() {
+        let dir = std::env::temp_dir().join(format!("aeth_watch_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("a.aeth");
+        fs::write(&file, "fn main() {}\n").unwrap();
+        let files = vec![file.clone()];
+
+        let before = Snapshot::take(&files);
+        assert!(!Snapshot::take(&files).changed_from(&before));
+        let later = SystemTime::now() + Duration::from_secs(5);
+        fs::File::options().write(true).open(&file).unwrap().set_modified(later).unwrap();
+        assert!(Snapshot::take(&files).changed_from(&before));
+        fs::remove_file(&file).unwrap();
+        assert!(Snapshot::take(&files).changed_from(&before));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_watched_files_follow_imports() {
+        let files = watched_files(Path::new("src_aether/main.aeth"), None, &[]);
+        assert_eq!(files[0], PathBuf::from("src_aether/main.aeth"));
+        assert!(files.contains(&PathBuf::from("src_aether/lexer.aeth")));
+        assert!(files.contains(&PathBuf::from("src_aether/token.aeth")));
+    }
+}
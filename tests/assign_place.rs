@@ -0,0 +1,38 @@
+//! `=` and compound assignment store to every kind of place: variables and
+//! statics, fields, elements and dereferenced pointers
+
+mod common;
+
+use common::{check_errors, exit_code};
+
+/// Each fixture and the exit code its assignments add up to
+const CASES: &[(&str, i32)] = &[
+    ("variable", 11),
+    ("field", 43),
+    ("index", 91),
+    ("deref", 40),
+    ("order", 27),
+];
+
+fn check(backend: &str) {
+    for &(name, expected) in CASES {
+        assert_eq!(exit_code("assign_place", backend, name), expected, "{} on the {} backend", name, backend);
+    }
+}
+
+#[test]
+fn test_assign_places_c() {
+    check("c");
+}
+
+#[cfg(feature = "llvm")]
+#[test]
+fn test_assign_places_llvm() {
+    check("llvm");
+}
+
+#[test]
+fn test_assign_to_immutable() {
+    let stderr = check_errors("fn main() -> i32 {\n    let total: i64 = 1\n    total += 2\n    return total as i32\n}\n");
+    assert!(stderr.contains("Cannot assign to 'total', which is not declared `mut`"), "{}", stderr);
+}
@@ -0,0 +1,22 @@
+//! Field access and method calls see through chains of pointers
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_linked_list_traversal() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let exe = std::env::temp_dir().join(format!("aether_auto_deref_{}", std::process::id()));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["build", "tests/auto_deref/list.aeth", "-o"]).arg(&exe)
+        .current_dir(root)
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let run = Command::new(&exe).output().unwrap();
+    let _ = std::fs::remove_file(&exe);
+    assert!(run.status.success());
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "2\n3\n3\n3\n3\n1\n");
+}
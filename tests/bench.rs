@@ -0,0 +1,39 @@
+//! `aethc bench`: time `#[bench]` functions or one named on the command line
+
+use std::process::Command;
+
+fn bench(args: &[&str]) -> (String, Option<i32>) {
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .arg("bench")
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to start aethc");
+    (String::from_utf8_lossy(&output.stdout).into_owned(), output.status.code())
+}
+
+/// Lines of the form `<name> time: [<lo> <unit> <mean> <unit> <hi> <unit>]`
+fn reported(stdout: &str) -> Vec<String> {
+    stdout.lines()
+        .filter(|line| line.contains("time:   ["))
+        .map(|line| line.split_whitespace().next().unwrap_or("").to_string())
+        .collect()
+}
+
+#[test]
+fn test_bench_annotated_functions() {
+    let (stdout, status) = bench(&["tests/bench/fib.aeth", "-n", "50"]);
+    assert_eq!(status, Some(0), "{}", stdout);
+    assert_eq!(reported(&stdout), vec!["fib_15", "fib_10"], "{}", stdout);
+    assert!(stdout.contains("(50 iterations)"), "{}", stdout);
+}
+
+#[test]
+fn test_bench_named_function() {
+    let (stdout, status) = bench(&["tests/bench/fib.aeth", "fib_10", "--iterations", "5"]);
+    assert_eq!(status, Some(0), "{}", stdout);
+    assert_eq!(reported(&stdout), vec!["fib_10"], "{}", stdout);
+
+    let (_, status) = bench(&["tests/bench/fib.aeth", "fib"]);
+    assert_eq!(status, Some(1));
+}
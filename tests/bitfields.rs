@@ -0,0 +1,31 @@
+//! `#[bits(N)]` fields share one integer with their neighbours, and read
+//! and write only their own bits
+
+mod common;
+
+use common::{check_errors, exit_code};
+
+#[test]
+fn test_flags_c() {
+    assert_eq!(exit_code("bitfields", "c", "flags"), 0);
+}
+
+#[cfg(feature = "llvm")]
+#[test]
+fn test_flags_llvm() {
+    assert_eq!(exit_code("bitfields", "llvm", "flags"), 0);
+}
+
+#[test]
+fn test_bitfield_has_no_address() {
+    let stderr = check_errors("struct F {\n    #[bits(3)]\n    mode: u8,\n}\nfn main() -> i32 {\n    let f: F = F { mode: 1 }\n    let p: *u8 = &f.mode\n    return 0\n}\n");
+    assert!(stderr.contains("E0059"), "{}", stderr);
+    assert!(stderr.contains("a bitfield has no address to borrow"), "{}", stderr);
+}
+
+#[test]
+fn test_bitfield_constant_must_fit() {
+    let stderr = check_errors("struct F {\n    #[bits(3)]\n    mode: u8,\n}\nfn main() -> i32 {\n    let mut f: F = F { mode: 1 }\n    f.mode = 9\n    return 0\n}\n");
+    assert!(stderr.contains("E0059"), "{}", stderr);
+    assert!(stderr.contains("9 does not fit in 3 bits"), "{}", stderr);
+}
@@ -0,0 +1,40 @@
+//! `--emit-c-header` writes a .c/.h pair a C program can include and link
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_link_c_program_against_header() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dir = std::env::temp_dir().join(format!("aether_c_header_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["--emit-c-header", "--no-main", "tests/c_header/mathlib.aeth", "-o"]).arg(dir.join("mathlib.c"))
+        .current_dir(root)
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let header = std::fs::read_to_string(dir.join("mathlib.h")).unwrap();
+    assert!(header.contains("#ifndef AETHER_MATHLIB_H"), "{}", header);
+    assert!(header.contains("typedef int64_t aeth_i64;"), "{}", header);
+    assert!(header.contains("struct Pair {\n    aeth_i64 a;\n    aeth_i64 b;\n};"), "{}", header);
+    assert!(header.contains("/* mathlib::scale */\nvoid mathlib__scale(struct Pair* p, aeth_i64 k);"), "{}", header);
+    assert!(!header.contains(" square("), "{}", header);
+
+    // The host defines its own `square` and `main`
+    let exe = dir.join("host");
+    let status = Command::new("cc")
+        .arg("-I").arg(&dir)
+        .arg("-o").arg(&exe)
+        .arg(root.join("tests/c_header/host.c"))
+        .arg(dir.join("mathlib.c"))
+        .status()
+        .expect("failed to start cc");
+    assert!(status.success());
+    let run = Command::new(&exe).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "5\n");
+    assert_eq!(run.status.code(), Some(42));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
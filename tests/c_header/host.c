@@ -0,0 +1,18 @@
+#include <stdio.h>
+
+#include "mathlib.h"
+
+/* Clashes with mathlib's private `square` unless that one is static */
+long long square(long long n) {
+    return n * n * n;
+}
+
+/* Links against the .c generated with --emit-c-header --no-main */
+int main(void) {
+    struct Pair p = { 3, 4 };
+    mathlib__scale(&p, 2);
+    /* Structs are passed by pointer */
+    aeth_i64 total = mathlib__sum_of_squares(&p);
+    aether_main();
+    return (int)(total - 58 + square(0));
+}
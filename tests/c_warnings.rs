@@ -0,0 +1,93 @@
+//! The generated C compiles cleanly under `-Wall -Wextra` and is reproducible
+
+use aether_lang::CompileSession;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Every `.aeth` file under `tests/` and `examples/`, in path order
+fn sources() -> Vec<PathBuf> {
+    fn walk(dir: &Path, out: &mut Vec<PathBuf>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                walk(&path, out);
+            } else if path.extension().is_some_and(|e| e == "aeth") {
+                out.push(path);
+            }
+        }
+    }
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut out = Vec::new();
+    walk(&root.join("tests"), &mut out);
+    walk(&root.join("examples"), &mut out);
+    out.sort();
+    out
+}
+
+/// Generated C for `path` at `opt_level`, if the C backend can lower it
+fn emit_c(path: &Path, opt_level: u8, jobs: usize) -> Option<String> {
+    let result = CompileSession::from_path(path).with_opt_level(opt_level).with_jobs(jobs).emit_c();
+    result.text().map(str::to_string)
+}
+
+/// What `cc -Wall -Wextra` prints for `c`, or `None` if it does not compile
+fn cc_diagnostics(c: &str, tag: &str) -> Option<String> {
+    let dir = std::env::temp_dir();
+    let c_path = dir.join(format!("aether_c_warnings_{}_{}.c", std::process::id(), tag));
+    let o_path = c_path.with_extension("o");
+    std::fs::write(&c_path, c).unwrap();
+    let output = Command::new("cc")
+        .args(["-O2", "-Wall", "-Wextra", "-c"]).arg(&c_path).arg("-o").arg(&o_path)
+        .output().ok()?;
+    let _ = std::fs::remove_file(&c_path);
+    let _ = std::fs::remove_file(&o_path);
+    output.status.success().then(|| String::from_utf8_lossy(&output.stderr).into_owned())
+}
+
+#[test]
+fn test_generated_c_has_no_warnings() {
+    if Command::new("cc").arg("--version").output().is_err() {
+        return;
+    }
+    let sources = sources();
+    let threads = std::thread::available_parallelism().map_or(4, |n| n.get());
+    let chunk = sources.len().div_ceil(threads);
+    // (sources compiled, warnings per failing source)
+    let (compiled, warnings) = std::thread::scope(|scope| {
+        let workers: Vec<_> = sources.chunks(chunk).enumerate().map(|(t, paths)| scope.spawn(move || {
+            let mut compiled = 0;
+            let mut warnings = Vec::new();
+            for (i, path) in paths.iter().enumerate() {
+                for opt_level in [0, 2] {
+                    let Some(c) = emit_c(path, opt_level, 1) else { continue };
+                    // Sources the C backend cannot lower correctly yet fail outright
+                    let Some(stderr) = cc_diagnostics(&c, &format!("{}_{}_{}", t, i, opt_level)) else { continue };
+                    compiled += 1;
+                    if stderr.contains("warning:") {
+                        warnings.push(format!("{} (-O{}):\n{}", path.display(), opt_level, stderr));
+                    }
+                }
+            }
+            (compiled, warnings)
+        })).collect();
+        workers.into_iter().map(|w| w.join().unwrap()).fold((0, Vec::new()), |(n, mut all), (c, w)| {
+            all.extend(w);
+            (n + c, all)
+        })
+    });
+    assert!(compiled > 100, "only {} programs compiled", compiled);
+    assert!(warnings.is_empty(), "{}", warnings.join("\n"));
+}
+
+#[test]
+fn test_generated_c_is_deterministic() {
+    let mut emitted = 0;
+    for path in sources() {
+        let Some(first) = emit_c(&path, 2, 1) else { continue };
+        assert_eq!(emit_c(&path, 2, 1).as_deref(), Some(first.as_str()), "{}", path.display());
+        // Functions generated on several threads are joined in source order
+        assert_eq!(emit_c(&path, 2, 4).as_deref(), Some(first.as_str()), "{}", path.display());
+        emitted += 1;
+    }
+    assert!(emitted > 50);
+}
@@ -0,0 +1,45 @@
+//! Builtin `Vec<T>` and `HashMap<K, V>` backed by the C runtime
+
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// Build `tests/collections/<name>.aeth`, run it and return its output
+fn run(name: &str) -> Output {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dir = std::env::temp_dir().join(format!("aether_collections_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let exe = dir.join(name);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .arg("build").arg(format!("tests/collections/{}.aeth", name))
+        .arg("-o").arg(&exe)
+        .current_dir(root)
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+    let run = Command::new(&exe).output().unwrap();
+    let _ = std::fs::remove_file(&exe);
+    run
+}
+
+#[test]
+fn test_vec() {
+    let output = run("vec");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "100\n198\nnone\n198\n99\n1002\n10702\nempty\n-1\n7\n3\n4\n5\nof str\n");
+
+    let output = run("elements");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "30\n7\n9\n482\n5\ndot\n36\n");
+
+    let output = run("bounds");
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "panic at tests/collections/bounds.aeth:6: Vec index out of bounds\n");
+}
+
+#[test]
+fn test_hashmap() {
+    let output = run("map");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "200\n22500\n100\nremoved\n121\n11\n2\n37\nfound\n");
+}
@@ -0,0 +1,46 @@
+//! Helpers shared by the integration tests that build the fixtures in
+//! tests/<suite>/ and check the programs aethc rejects
+
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Build tests/<suite>/<name>.aeth with `backend`, run it and return its exit code
+pub fn exit_code(suite: &str, backend: &str, name: &str) -> i32 {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let exe = std::env::temp_dir().join(format!("aether_{}_{}_{}_{}", suite, name, backend, std::process::id()));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["--backend", backend, "build"])
+        .arg(format!("tests/{}/{}.aeth", suite, name))
+        .arg("-o").arg(&exe)
+        .current_dir(root)
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}: {}", name, String::from_utf8_lossy(&output.stderr));
+
+    let run = Command::new(&exe).status().unwrap();
+    let _ = std::fs::remove_file(&exe);
+    run.code().expect("killed by a signal")
+}
+
+/// Run `aethc --error-format=json check` on `source`, which must be
+/// rejected, and return what it printed to stderr
+pub fn check_errors(source: &str) -> String {
+    // Tests of one binary run in parallel, so each needs its own file
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "aether_check_{}_{}.aeth",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed),
+    ));
+    std::fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["--error-format=json", "check"]).arg(&path)
+        .output()
+        .expect("failed to start aethc");
+    let _ = std::fs::remove_file(&path);
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert!(!output.status.success(), "{}", stderr);
+    stderr
+}
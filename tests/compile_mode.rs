@@ -0,0 +1,47 @@
+//! `--emit-object`, `--emit-static-lib` and `--emit-shared-lib`: AetherLang code consumed from C
+
+use std::path::Path;
+use std::process::Command;
+
+/// Compile `tests/compile_mode/squares.aeth` with `flag` into `output`
+fn build(flag: &str, output: &Path) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let result = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args([flag, "build", "tests/compile_mode/squares.aeth", "-o"]).arg(output)
+        .current_dir(root)
+        .output()
+        .expect("failed to start aethc");
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    assert!(output.exists());
+}
+
+#[test]
+fn test_compile_modes() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dir = std::env::temp_dir().join(format!("aether_compile_mode_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let driver = root.join("tests/compile_mode/driver.c");
+    let link_and_run = |inputs: &[&Path], name: &str| {
+        let exe = dir.join(name);
+        let status = Command::new("cc")
+            .arg(&driver).args(inputs).arg("-o").arg(&exe)
+            .status()
+            .expect("failed to start cc");
+        assert!(status.success());
+        Command::new(&exe).status().unwrap().code()
+    };
+
+    let object = dir.join("squares.o");
+    build("--emit-object", &object);
+    assert_eq!(link_and_run(&[&object], "from_object"), Some(25));
+
+    // The archive replaces the object it was built from
+    let archive = dir.join("libsquares.a");
+    build("--emit-static-lib", &archive);
+    assert!(!archive.with_extension("o").exists());
+    assert_eq!(link_and_run(&[&archive], "from_archive"), Some(25));
+
+    build("--emit-shared-lib", &dir.join("libsquares.so"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,7 @@
+#include <stdint.h>
+
+int64_t sum_squares(int64_t a, int64_t b);
+
+int main(void) {
+    return (int)sum_squares(3, 4);
+}
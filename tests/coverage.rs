@@ -0,0 +1,40 @@
+//! `--coverage`: block counters dumped on exit and `aethc cov-report`
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_coverage_report() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dir = std::env::temp_dir().join(format!("aether_cov_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let exe = dir.join("branches");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["--coverage", "build", "tests/coverage/branches.aeth", "-o"]).arg(&exe)
+        .current_dir(root)
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(Command::new(&exe).status().unwrap().code(), Some(2));
+
+    let lcov = dir.join("branches.info");
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .arg("cov-report").arg(dir.join("branches.aethcov"))
+        .arg("tests/coverage/branches.aeth")
+        .arg("--lcov").arg(&lcov)
+        .current_dir(root)
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    // `classify` runs three times, taking the early return twice
+    let report = String::from_utf8_lossy(&output.stdout);
+    assert!(report.contains("        3:    2:    if n > 5 {"), "{}", report);
+    assert!(report.contains("        2:    3:        return 1;"), "{}", report);
+    let lcov = std::fs::read_to_string(&lcov).unwrap();
+    assert!(lcov.starts_with("TN:\nSF:tests/coverage/branches.aeth\n"), "{}", lcov);
+    assert!(lcov.contains("DA:2,3\nDA:3,2\n") && lcov.ends_with("end_of_record\n"), "{}", lcov);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
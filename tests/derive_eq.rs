@@ -0,0 +1,36 @@
+//! `==` and `!=` compare structs and enums that `#[derive(Eq)]` member by
+//! member, enums without payloads by variant, and strings by their bytes
+
+mod common;
+
+use common::{check_errors, exit_code};
+
+/// Each fixture and the exit code its comparisons add up to
+const CASES: &[(&str, i32)] = &[
+    ("structs", 63),
+    ("enums", 127),
+    ("strings", 7),
+];
+
+fn check(backend: &str) {
+    for &(name, expected) in CASES {
+        assert_eq!(exit_code("derive_eq", backend, name), expected, "{} on the {} backend", name, backend);
+    }
+}
+
+#[test]
+fn test_derive_eq_c() {
+    check("c");
+}
+
+#[cfg(feature = "llvm")]
+#[test]
+fn test_derive_eq_llvm() {
+    check("llvm");
+}
+
+#[test]
+fn test_compare_without_derive() {
+    let stderr = check_errors("struct P { x: i32 }\nfn main() -> i32 {\n    let a: P = P { x: 1 }\n    let b: P = P { x: 1 }\n    if a == b { return a.x + b.x }\n    return 0\n}\n");
+    assert!(stderr.contains("Values of type 'P' cannot be compared with == or !="), "{}", stderr);
+}
@@ -0,0 +1,34 @@
+//! Nested tuple, struct and variant patterns bind the parts they name
+
+use std::path::Path;
+use std::process::Command;
+
+const EXPECTED: &str = "321\n5\n42\n4\n1\n7\n9\n9\n-1\n";
+
+fn build_and_run(backend: &str) -> String {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let exe = std::env::temp_dir().join(format!("aether_destructure_{}_{}", backend, std::process::id()));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["--backend", backend, "build", "tests/destructure/nested.aeth", "-o"]).arg(&exe)
+        .current_dir(root)
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let run = Command::new(&exe).output().unwrap();
+    let _ = std::fs::remove_file(&exe);
+    assert!(run.status.success());
+    String::from_utf8_lossy(&run.stdout).into_owned()
+}
+
+#[test]
+fn test_nested_destructuring_c() {
+    assert_eq!(build_and_run("c"), EXPECTED);
+}
+
+#[cfg(feature = "llvm")]
+#[test]
+fn test_nested_destructuring_llvm() {
+    assert_eq!(build_and_run("llvm"), EXPECTED);
+}
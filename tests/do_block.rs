@@ -0,0 +1,21 @@
+//! `[do { .. }; N]` fills a stack array by running the block once per index
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_do_block_fills_array() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let exe = std::env::temp_dir().join(format!("aether_do_block_{}", std::process::id()));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["build", "tests/do_block/squares.aeth", "-o"]).arg(&exe)
+        .current_dir(root)
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let run = Command::new(&exe).output().unwrap();
+    let _ = std::fs::remove_file(&exe);
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "0\n1\n4\n9\n16\n4\n");
+}
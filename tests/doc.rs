@@ -0,0 +1,58 @@
+//! `aethc doc` writes a page per module and the JSON doc database
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use aether_lang::doc::{DocDatabase, ItemKind};
+
+fn doc(format: &str, name: &str) -> PathBuf {
+    let out = std::env::temp_dir().join(format!("aether_doc_{}_{}", name, std::process::id()));
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["doc", "tests/doc/main.aeth", "--format", format, "-o"]).arg(&out)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    out
+}
+
+fn read(dir: &Path, file: &str) -> String {
+    std::fs::read_to_string(dir.join(file)).unwrap_or_else(|e| panic!("{}: {}", file, e))
+}
+
+#[test]
+fn test_doc_html() {
+    let out = doc("html", "html");
+    let index = read(&out, "index.html");
+    let main = read(&out, "main.html");
+    let geo = read(&out, "geo.html");
+    let _ = std::fs::remove_dir_all(&out);
+
+    assert!(index.contains(r#"<a href="geo.html">geo</a>"#), "{}", index);
+    // Private items are left out
+    assert!(!main.contains("fn main"), "{}", main);
+    assert!(geo.contains(r##"<a href="#struct.Square">Square</a>"##), "{}", geo);
+    assert!(geo.contains("<p>A square on the plane.</p>\n<p>Sides are measured in pixels.</p>"), "{}", geo);
+    assert!(geo.contains("<pre class=\"signature\">pub fn perimeter(self: ref Square) -&gt; i64</pre>"), "{}", geo);
+    assert!(geo.contains("<p>Area of <code>s</code></p>"), "{}", geo);
+    assert!(!geo.contains("helper"), "{}", geo);
+}
+
+#[test]
+fn test_doc_markdown_and_database() {
+    let out = doc("markdown", "md");
+    let index = read(&out, "index.md");
+    let geo = read(&out, "geo.md");
+    let database: DocDatabase = serde_json::from_str(&read(&out, "docs.json")).expect("docs.json is a doc database");
+    let _ = std::fs::remove_dir_all(&out);
+
+    assert!(index.contains("  - `Square`: A square on the plane."), "{}", index);
+    assert!(geo.contains("### `area`\n\n```aether\npub fn area(s: Square) -> i64\n```\n\nArea of `s`"), "{}", geo);
+    assert!(geo.contains("- `pub fn perimeter(self: ref Square) -> i64`: Length of the outline"), "{}", geo);
+
+    let names: Vec<&str> = database.modules.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(names, ["main", "geo"]);
+    let area = database.find(Some("geo"), "area").unwrap();
+    assert_eq!(area.kind, ItemKind::Function);
+    assert!(area.hover_markdown().ends_with("Area of `s`"));
+}
@@ -0,0 +1,53 @@
+//! Enums with explicit discriminants match and cast by their declared values
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_discriminants_and_casts() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let exe = std::env::temp_dir().join(format!("aether_enum_discriminants_{}", std::process::id()));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["build", "tests/enum_discriminants/errno.aeth", "-o"]).arg(&exe)
+        .current_dir(root)
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let run = Command::new(&exe).output().unwrap();
+    let _ = std::fs::remove_file(&exe);
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "2\n11\n15\n100\n300\n16\n0\n");
+}
+
+#[test]
+fn test_enums_cross_c_boundary_as_discriminants() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dir = std::env::temp_dir().join(format!("aether_enum_ffi_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let object = dir.join("color.o");
+    let status = Command::new("cc")
+        .args(["-c", "-o"]).arg(&object).arg(root.join("tests/enum_discriminants/color.c"))
+        .status()
+        .expect("failed to start cc");
+    assert!(status.success());
+    let status = Command::new("ar")
+        .arg("rcs").arg(dir.join("libcolor.a")).arg(&object)
+        .status()
+        .expect("failed to start ar");
+    assert!(status.success());
+
+    let exe = dir.join("ffi");
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["build", "tests/enum_discriminants/ffi.aeth", "-o"]).arg(&exe)
+        .arg("-L").arg(&dir)
+        .current_dir(root)
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let run = Command::new(&exe).output().unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "40\n2\n3\n1\n");
+}
@@ -0,0 +1,12 @@
+/* A C API taking and returning `enum color` values */
+#include <stdint.h>
+
+enum color { RED = 1, GREEN = 2, BLUE = 4 };
+
+int64_t color_code(uint8_t c) {
+    return c == BLUE ? 40 : c;
+}
+
+uint8_t next_color(uint8_t c) {
+    return c == BLUE ? RED : (uint8_t)(c * 2);
+}
@@ -0,0 +1,68 @@
+//! `--error-format=json` writes rustc-style diagnostics to stderr, one per line
+
+use serde_json::Value;
+use std::process::Command;
+
+/// Diagnostics `aethc check` prints for `tests/error_format/<name>.aeth` in `format`
+fn diagnostics(format: &str, name: &str) -> Vec<Value> {
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args([&format!("--error-format={}", format), "check", &format!("tests/error_format/{}.aeth", name)])
+        .output()
+        .unwrap();
+    String::from_utf8(output.stderr).unwrap().lines()
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|e| panic!("{}: {}", e, line)))
+        .collect()
+}
+
+#[test]
+fn test_json_lines() {
+    let lines = diagnostics("json", "undefined");
+    assert_eq!(lines.len(), 1);
+    let error = &lines[0];
+    assert_eq!(error["$message_type"], "diagnostic");
+    assert_eq!(error["level"], "error");
+    assert_eq!(error["message"], "Undefined variable: missing");
+    assert_eq!(error["code"]["code"], "E0002");
+    let span = &error["spans"][0];
+    assert_eq!(span["file_name"], "tests/error_format/undefined.aeth");
+    assert_eq!((span["line_start"].as_u64(), span["column_start"].as_u64()), (Some(3), Some(12)));
+    assert!(error["rendered"].as_str().unwrap().contains(" --> tests/error_format/undefined.aeth:3:12\n"));
+
+    let warnings = diagnostics("json", "unused");
+    assert!(!warnings.is_empty());
+    assert!(warnings.iter().all(|w| w["level"] == "warning" && w["spans"][0]["line_start"].is_u64()));
+}
+
+#[test]
+fn test_short_and_ansi() {
+    let short = diagnostics("json-diagnostic-short", "undefined");
+    assert_eq!(short[0]["rendered"], "tests/error_format/undefined.aeth:3:12: error[E0002]: Undefined variable: missing\n");
+    let ansi = diagnostics("json-diagnostic-rendered-ansi", "undefined");
+    assert!(ansi[0]["rendered"].as_str().unwrap().contains("\x1b["));
+}
+
+/// What `aethc check` prints to stderr for `tests/error_format/<name>.aeth` with `args`
+fn stderr(args: &[&str], name: &str) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(args)
+        .args(["check", &format!("tests/error_format/{}.aeth", name)])
+        .output()
+        .unwrap();
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn test_color_flags() {
+    let always = stderr(&["--color=always"], "undefined");
+    assert!(always.contains("\x1b[1;31merror\x1b[0m"), "{:?}", always);
+    let truecolor = stderr(&["--color=truecolor"], "undefined");
+    assert!(truecolor.contains("\x1b[1;38;2;"), "{:?}", truecolor);
+    let warnings = stderr(&["--color=always"], "unused");
+    assert!(warnings.contains("\x1b[33mWarning\x1b[0m"), "{:?}", warnings);
+
+    // Pipes, --no-color and JSON output get no escape codes
+    for args in [&[][..], &["--no-color"], &["--color=never"], &["--color=always", "--json"]] {
+        let plain = stderr(args, "undefined");
+        assert!(!plain.is_empty() && !plain.contains('\x1b'), "{:?}: {:?}", args, plain);
+    }
+}
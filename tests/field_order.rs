@@ -0,0 +1,26 @@
+//! `#[field_order(optimal)]` structs are reordered to minimize padding
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_optimal_field_order() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let exe = std::env::temp_dir().join(format!("aether_field_order_{}", std::process::id()));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["build", "tests/field_order/packet.aeth", "--show-struct-layout", "-o"]).arg(&exe)
+        .current_dir(root)
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("struct Packet: size 16, align 8, 5 bytes of padding"), "{}", stdout);
+    assert!(stdout.contains("struct Header: size 8, align 4, 3 bytes of padding"), "{}", stdout);
+    // Opting out of reordering also opts out of the padding warning
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("E0049"));
+
+    let run = Command::new(&exe).output().unwrap();
+    let _ = std::fs::remove_file(&exe);
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "43\n16\n0\n10\n8\n");
+}
@@ -0,0 +1,22 @@
+//! File I/O builtins: write a file, read it back and observe failures through `last_error()`
+
+use std::process::Command;
+
+#[test]
+fn test_file_roundtrip() {
+    let path = std::env::temp_dir().join(format!("aether_fileio_{}.txt", std::process::id()));
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["run", "tests/fileio/roundtrip.aeth"]).arg(&path)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to start aethc");
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+
+    // The program prints what it read back after the compiler's own output
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let program = &stdout[stdout.find("first line").expect("file contents were not printed")..];
+    assert_eq!(program, "first line\nsecond line\n5\nfirst\n0\n2\nopen failed\n");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "first line\nsecond line\nthird line\n");
+
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,23 @@
+//! `for x in a` visits each element of a fixed-size array
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_for_visits_array_elements() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    for opt in ["-O0", "-O2"] {
+        let exe = std::env::temp_dir().join(format!("aether_for_array_{}{}", std::process::id(), opt));
+
+        let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+            .args([opt, "build", "tests/for_array/elements.aeth", "-o"]).arg(&exe)
+            .current_dir(root)
+            .output()
+            .expect("failed to start aethc");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+        let run = Command::new(&exe).output().unwrap();
+        let _ = std::fs::remove_file(&exe);
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "10\n100\n2\n1\n2\n3\n4\n7\n8\n9\n3\n7\n", "{}", opt);
+    }
+}
@@ -0,0 +1,58 @@
+//! `--freestanding`: programs without the C library, linked by the self-hosted linker
+
+use aether_lang::CompileSession;
+use std::path::Path;
+use std::process::Command;
+
+fn aethc(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to start aethc")
+}
+
+#[test]
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn test_exit_through_syscall() {
+    let dir = std::env::temp_dir().join(format!("aether_freestanding_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let exe = dir.join("exit42");
+    let map = dir.join("exit42.map");
+    let output = aethc(&[
+        "--freestanding", "--link-map", map.to_str().unwrap(),
+        "build", "tests/freestanding/exit42.aeth", "-o", exe.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Linked with the self-hosted linker"));
+    assert_eq!(Command::new(&exe).status().unwrap().code(), Some(42));
+
+    // A static ELF executable that starts at `_start`
+    let image = std::fs::read(&exe).unwrap();
+    assert_eq!(&image[..4], b"\x7fELF");
+    assert_eq!(u16::from_le_bytes([image[16], image[17]]), 2);
+    let map = std::fs::read_to_string(&map).unwrap();
+    assert!(map.lines().any(|line| line.ends_with(" _start")), "{}", map);
+
+    // The session API links the same way
+    let result = CompileSession::from_path(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/freestanding/exit42.aeth"))
+        .with_freestanding("_start")
+        .build();
+    assert!(result.bytes().is_some_and(|bytes| bytes.starts_with(b"\x7fELF")), "{:?}", result.diagnostics);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_hosted_builtins_are_rejected() {
+    let output = aethc(&["--freestanding", "--json", "check", "tests/freestanding/print.aeth"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("\"E0051\""), "{}", stderr);
+    assert!(stderr.contains("'print' needs the C library, which --freestanding leaves out"), "{}", stderr);
+
+    // The entry point must exist
+    let output = aethc(&["--freestanding", "--entry", "kmain", "--emit-c", "build", "tests/freestanding/exit42.aeth", "-o", "/dev/null"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("starts at `kmain`"));
+}
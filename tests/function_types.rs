@@ -0,0 +1,23 @@
+//! Calls through function-typed parameters, with and without optimization
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_calls_through_function_values() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    for opt in ["-O0", "-O2"] {
+        let exe = std::env::temp_dir().join(format!("aether_function_types{}_{}", opt, std::process::id()));
+        let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+            .arg(opt)
+            .args(["build", "tests/function_types/callbacks.aeth", "-o"]).arg(&exe)
+            .current_dir(root)
+            .output()
+            .expect("failed to start aethc");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+        let run = Command::new(&exe).output().unwrap();
+        let _ = std::fs::remove_file(&exe);
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "3\n12\n12\n100\n200\n3\n4\n", "{}", opt);
+    }
+}
@@ -0,0 +1,50 @@
+//! Replays the `parse` fuzz corpus without cargo-fuzz
+
+use aether_lang::frontend::lexer::Lexer;
+use aether_lang::frontend::parser::Parser;
+use aether_lang::frontend::semantic::SemanticAnalyzer;
+use std::path::Path;
+
+/// What `fuzz/fuzz_targets/parse.rs` does with one input
+fn check(source: &str) {
+    if let Ok(program) = Parser::new(Lexer::new(source, 0)).parse_program() {
+        let _ = SemanticAnalyzer::new().analyze(&program);
+    }
+}
+
+#[test]
+fn test_corpus_and_truncations() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/parse");
+    let mut entries = 0;
+    for entry in std::fs::read_dir(corpus).unwrap() {
+        let bytes = std::fs::read(entry.unwrap().path()).unwrap();
+        let source = String::from_utf8_lossy(&bytes);
+        check(&source);
+        // Programs cut off mid-item exercise the parser's error paths
+        for (i, _) in source.match_indices('\n') {
+            check(&source[..i]);
+        }
+        entries += 1;
+    }
+    assert!(entries > 50);
+}
+
+#[test]
+fn test_malformed_inputs() {
+    // Deep nesting is covered by the parser's own tests, which need a bigger stack
+    let inputs = [
+        "",
+        // Unterminated literals and comments
+        "fn main() { \"abc",
+        "fn main() { '\\",
+        "/* /* */",
+        // A multi-byte char literal used to lex as a lifetime
+        "fn main() { let c: char = 'é'; }",
+        // Dangling path and generic segments
+        "fn main() { a:: }",
+        "fn main() { a::<",
+    ];
+    for input in inputs {
+        check(input);
+    }
+}
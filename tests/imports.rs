@@ -0,0 +1,52 @@
+//! Imports may not be circular; errors in imported files name the file
+
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// `aethc check` run next to the fixtures, so `use` finds the other files
+fn check(file: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["check", file])
+        .current_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/imports"))
+        .output()
+        .expect("failed to start aethc")
+}
+
+#[test]
+fn test_circular_imports() {
+    // cycle -> ping -> pong -> ping
+    let output = check("cycle.aeth");
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "Semantic error: Circular import: ping -> pong -> ping\n");
+
+    // A cycle back to the file being compiled starts from it
+    let output = check("tri_a.aeth");
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "Semantic error: Circular import: tri_a -> tri_b -> tri_c -> tri_a\n");
+
+    // Every cycle is reported, in the order the imports are written
+    let output = check("hub.aeth");
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Semantic error: Circular import: ping -> pong -> ping\nSemantic error: Circular import: hub -> spoke -> hub\n"
+    );
+}
+
+#[test]
+fn test_shared_import_is_not_a_cycle() {
+    // diamond -> left -> base, diamond -> right -> base
+    let output = check("diamond.aeth");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn test_error_in_indirect_import() {
+    let output = check("uses_broken.aeth");
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Semantic error: Cannot import 'wrapper': ./broken.aeth:2:11: Expected type annotation required: \
+         use `let x: Type = value;` (AetherLang requires explicit types) (imported through wrapper -> broken)\n"
+    );
+}
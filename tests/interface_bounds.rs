@@ -0,0 +1,26 @@
+//! Functions generic over an interface (`fn area<T: Shape>(s: T)`) are
+//! specialized for each type they are called with
+
+mod common;
+
+use common::{check_errors, exit_code};
+
+#[test]
+fn test_shapes_c() {
+    assert_eq!(exit_code("interface_bounds", "c", "shapes"), 0);
+}
+
+#[cfg(feature = "llvm")]
+#[test]
+fn test_shapes_llvm() {
+    assert_eq!(exit_code("interface_bounds", "llvm", "shapes"), 0);
+}
+
+#[test]
+fn test_missing_impl_names_interface_and_type() {
+    let stderr = check_errors("interface Shape {\n    fn area(self: *Self) -> f64;\n}\nstruct Line { len: f64 }\n\
+        fn area<T: Shape>(s: T) -> f64 {\n    return s.area()\n}\nfn main() -> i32 {\n    let l: Line = Line { len: 2.0 }\n    \
+        let a: f64 = area(l)\n    return 0\n}\n");
+    assert!(stderr.contains("E0060"), "{}", stderr);
+    assert!(stderr.contains("'Line' does not implement interface 'Shape'"), "{}", stderr);
+}
@@ -0,0 +1,26 @@
+//! Struct invariants are checked after every struct literal
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_invariant_violation_panics() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let exe = std::env::temp_dir().join(format!("aether_invariant_{}", std::process::id()));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["build", "tests/invariant/range.aeth", "-o"]).arg(&exe)
+        .current_dir(root)
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let run = Command::new(&exe).output().unwrap();
+    let _ = std::fs::remove_file(&exe);
+    assert!(!run.status.success());
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "7\n");
+    assert_eq!(
+        String::from_utf8_lossy(&run.stderr),
+        "panic at tests/invariant/range.aeth:8: invariant of 'Range' violated\n"
+    );
+}
@@ -0,0 +1,41 @@
+//! `--jobs`: a parallel build produces exactly what a sequential one does
+
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn aethc(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(args)
+        .current_dir(Path::new(env!("CARGO_MANIFEST_DIR")))
+        .output()
+        .expect("failed to start aethc")
+}
+
+/// C source generated for `input` with `jobs` workers
+fn emit_c(input: &str, jobs: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("aether_jobs_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let c_path = dir.join(format!("out_{}.c", jobs));
+    let output = aethc(&["--emit-c", "--jobs", jobs, "build", input, "-o", c_path.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    std::fs::read_to_string(&c_path).unwrap()
+}
+
+#[test]
+fn test_parallel_build_matches_sequential() {
+    for input in ["src_aether/semantic.aeth", "src_aether/codegen.aeth"] {
+        let sequential = emit_c(input, "1");
+        for jobs in ["2", "7"] {
+            assert!(sequential == emit_c(input, jobs), "{} differs with --jobs {}", input, jobs);
+        }
+    }
+
+    // Diagnostics are merged in source order
+    let check = |jobs| aethc(&["--jobs", jobs, "check", "tests/jobs/errors.aeth"]);
+    let (sequential, parallel) = (check("1"), check("4"));
+    assert!(!sequential.status.success());
+    assert_eq!(String::from_utf8_lossy(&sequential.stderr), String::from_utf8_lossy(&parallel.stderr));
+    assert_eq!(sequential.stdout, parallel.stdout);
+
+    let _ = std::fs::remove_dir_all(std::env::temp_dir().join(format!("aether_jobs_{}", std::process::id())));
+}
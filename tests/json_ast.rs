@@ -0,0 +1,33 @@
+//! `--emit-json-ast`: the parsed program as JSON, read back into the AST
+#![cfg(feature = "json-ast")]
+
+use aether_lang::frontend::ast::Program;
+use aether_lang::frontend::lexer::Lexer;
+use aether_lang::frontend::parser::Parser;
+use std::process::Command;
+
+#[test]
+fn test_round_trip() {
+    let dir = std::env::temp_dir().join(format!("aether_json_ast_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let source = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/json_ast/shapes.aeth")).unwrap();
+    let input = dir.join("shapes.aeth");
+    std::fs::write(&input, &source).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["--emit-json-ast", "--json-ast-pretty", "build", input.to_str().unwrap()])
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let json = std::fs::read_to_string(dir.join("shapes.ast.json")).unwrap();
+    assert!(json.starts_with("{\n  \"items\""), "{}", &json[..json.len().min(80)]);
+    assert!(!dir.join("shapes").exists(), "--emit-json-ast stops after parsing");
+
+    // The JSON describes the same program the parser builds, and reads back into it
+    let parsed = Parser::new(Lexer::new(&source, 0)).parse_program().expect("parse failed");
+    let read: Program = serde_json::from_str(&json).expect("JSON does not read back into the AST");
+    assert_eq!(format!("{:?}", read), format!("{:?}", parsed));
+    assert_eq!(serde_json::to_value(&read).unwrap(), serde_json::from_str::<serde_json::Value>(&json).unwrap());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,21 @@
+//! `let` in expression position binds in the enclosing scope and yields its value
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_let_in_conditions() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let exe = std::env::temp_dir().join(format!("aether_let_expr_{}", std::process::id()));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["build", "tests/let_expr/conditions.aeth", "-o"]).arg(&exe)
+        .current_dir(root)
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let run = Command::new(&exe).output().unwrap();
+    let _ = std::fs::remove_file(&exe);
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "4321\n8642\n8643\n10\n");
+}
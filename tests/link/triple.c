@@ -0,0 +1,2 @@
+/* Pre-compiled helper for tests/link_library.rs */
+long triple(long x) { return x * 3; }
@@ -0,0 +1,47 @@
+//! `-L`/`-l` and `extern ... link = "lib"`: link against a pre-compiled static library
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_link_static_library() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dir = std::env::temp_dir().join(format!("aether_link_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let object = dir.join("triple.o");
+    let status = Command::new("cc")
+        .args(["-c", "-o"]).arg(&object).arg(root.join("tests/link/triple.c"))
+        .status()
+        .expect("failed to start cc");
+    assert!(status.success());
+    let status = Command::new("ar")
+        .arg("rcs").arg(dir.join("libtriple.a")).arg(&object)
+        .status()
+        .expect("failed to start ar");
+    assert!(status.success());
+
+    let run = |args: &[&str]| {
+        let exe = dir.join("link_test");
+        let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+            .args(["build", "tests/link/link_test.aeth", "-o"]).arg(&exe)
+            .args(args)
+            .current_dir(root)
+            .output()
+            .expect("failed to start aethc");
+        (output, exe)
+    };
+
+    // The extern block names the library; only its directory is needed
+    let (output, exe) = run(&["-L", dir.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(Command::new(&exe).status().unwrap().code(), Some(42));
+
+    // An unknown library is reported as a link failure
+    let (output, _) = run(&["-L", dir.to_str().unwrap(), "-l", "aether_no_such_lib"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Linking failed") && stderr.contains("aether_no_such_lib"), "{}", stderr);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
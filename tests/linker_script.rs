@@ -0,0 +1,65 @@
+//! Laying out native executables with `--linker-script` and `--link-map`
+
+use std::path::Path;
+use std::process::Command;
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+#[test]
+fn test_native_linker_script() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let exe = std::env::temp_dir().join(format!("aether_linker_script_{}", std::process::id()));
+    let map = exe.with_extension("map");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["--backend", "native", "--linker-script", "tests/linker_script/high.ld", "--link-map"]).arg(&map)
+        .args(["build", "tests/linker_script/fib.aeth", "-o"]).arg(&exe)
+        .current_dir(root)
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let status = Command::new(&exe).status().unwrap();
+    let elf = std::fs::read(&exe).unwrap();
+    let map_text = std::fs::read_to_string(&map).unwrap();
+    let _ = std::fs::remove_file(&exe);
+    let _ = std::fs::remove_file(&map);
+    // fib(10) - 13
+    assert_eq!(status.code(), Some(42));
+
+    // e_entry: `.text` sits 0x2000 past the base address and starts with `_start`
+    assert_eq!(u64::from_le_bytes(elf[24..32].try_into().unwrap()), 0x1000_2000);
+    assert!(map_text.contains("Base address: 0x0000000010000000"), "{}", map_text);
+    let text = map_text.lines().find(|l| l.trim_start().starts_with(".text")).unwrap();
+    assert!(text.contains("0x0000000010002000 0x00001000"), "{}", map_text);
+    for symbol in ["_start", "fib", "main"] {
+        assert!(map_text.lines().any(|l| l.ends_with(&format!(" {}", symbol))), "{}", map_text);
+    }
+}
+
+#[test]
+fn test_native_linker_script_errors() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dir = std::env::temp_dir().join(format!("aether_linker_script_errors_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let exe = dir.join("fib");
+    let cases = [
+        ("ENTRY(reset_handler)", "entry symbol `reset_handler` is not defined"),
+        ("SECTIONS { .data 0x1000 : { } }", "only emits `.text`"),
+        ("SECTIONS { .text 0x1010 : { } }", "page boundary"),
+        ("BASE_ADDRESS(0x8000", "line 1: expected `)`"),
+    ];
+    for (script, message) in cases {
+        let path = dir.join("bad.ld");
+        std::fs::write(&path, script).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+            .args(["--backend", "native", "--linker-script"]).arg(&path)
+            .args(["build", "tests/linker_script/fib.aeth", "-o"]).arg(&exe)
+            .current_dir(root)
+            .output()
+            .expect("failed to start aethc");
+        assert!(!output.status.success(), "{}", script);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(message), "{}: {}", script, stderr);
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}
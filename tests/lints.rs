@@ -0,0 +1,28 @@
+//! Unused and unreachable code warns, and fails the build with --deny-warnings
+
+use std::process::{Command, Output};
+
+fn check(flags: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(flags)
+        .args(["check", "tests/lints/dead.aeth"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to start aethc")
+}
+
+#[test]
+fn test_lint_warnings() {
+    let output = check(&[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "  [!] Warning[E0042]: Field 'Point.y' is never read\n\
+         \x20 [!] Warning[E0041]: Function 'orphan' is never used\n\
+         \x20 [!] Warning[E0043]: Unreachable statement\n"
+    );
+
+    let denied = check(&["--deny-warnings"]);
+    assert!(!denied.status.success());
+    assert!(String::from_utf8_lossy(&denied.stderr).contains("Lint error: Function 'orphan' is never used"));
+}
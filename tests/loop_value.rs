@@ -0,0 +1,22 @@
+//! `loop { .. break value; }` evaluates to the value of its `break`
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_loop_yields_break_value() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    for opt in ["-O0", "-O2"] {
+        let exe = std::env::temp_dir().join(format!("aether_loop_value_{}{}", std::process::id(), opt));
+        let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+            .args([opt, "build", "tests/loop_value/search.aeth", "-o"]).arg(&exe)
+            .current_dir(root)
+            .output()
+            .expect("failed to start aethc");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+        let run = Command::new(&exe).output().unwrap();
+        let _ = std::fs::remove_file(&exe);
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "80\n12\n25\n", "{}", opt);
+    }
+}
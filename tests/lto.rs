@@ -0,0 +1,36 @@
+//! `--lto` objects are merged by `aethc link` and optimized as one program
+
+use std::path::Path;
+use std::process::Command;
+
+fn aethc(args: &[&str], outputs: &[&Path]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(args).args(outputs)
+        .current_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lto"))
+        .output()
+        .expect("failed to start aethc")
+}
+
+#[test]
+fn test_link_modules() {
+    let dir = std::env::temp_dir().join(format!("aether_lto_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (main, counter, exe) = (dir.join("main.alito"), dir.join("counter.alito"), dir.join("app"));
+
+    for (source, object) in [("main.aeth", &main), ("counter.aeth", &counter)] {
+        let built = aethc(&["--lto", "build", source, "-o"], &[object]);
+        assert!(built.status.success(), "{}", String::from_utf8_lossy(&built.stderr));
+    }
+    // Both modules define a private `scale`; each keeps calling its own
+    let linked = aethc(&["link", "-o"], &[&exe, &main, &counter]);
+    assert!(linked.status.success(), "{}", String::from_utf8_lossy(&linked.stderr));
+    let run = Command::new(&exe).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "100\n22\n");
+
+    // Sources are not objects
+    let failed = aethc(&["link", "main.aeth", "-o"], &[&exe]);
+    assert!(!failed.status.success());
+    assert!(String::from_utf8_lossy(&failed.stderr).contains("not an LTO object"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
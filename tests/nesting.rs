@@ -0,0 +1,59 @@
+//! Deeply nested and very long expressions end in a result or a diagnostic,
+//! never a stack overflow
+
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// Compile `source` to an executable with aethc; the output and how long it took
+fn build(name: &str, source: &str, args: &[&str]) -> (std::process::Output, Duration, std::path::PathBuf) {
+    let dir = std::env::temp_dir().join(format!("aether_nesting_{}_{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join(format!("{}.aeth", name));
+    std::fs::write(&input, source).unwrap();
+    let exe = dir.join(name);
+    let start = Instant::now();
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(args)
+        .args(["build", input.to_str().unwrap(), "-o", exe.to_str().unwrap()])
+        .output()
+        .expect("failed to start aethc");
+    (output, start.elapsed(), exe)
+}
+
+#[test]
+fn test_long_binary_chain() {
+    // 100,000 terms nest 100,000 levels to the left
+    let terms = vec!["x"; 100_000].join(" + ");
+    let source = format!("fn main() -> i64 {{\n    let x: i64 = 1;\n    return ({}) % 256 - 100;\n}}\n", terms);
+    let (output, elapsed, _) = build("chain", &source, &["--emit-c"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(elapsed < Duration::from_secs(10), "took {:?}", elapsed);
+    let (output, _, exe) = build("chain", &source, &[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(Command::new(&exe).status().unwrap().code(), Some(100_000 % 256 - 100));
+    let _ = std::fs::remove_dir_all(exe.parent().unwrap());
+}
+
+#[test]
+fn test_deep_nesting_is_an_error() {
+    let parens = format!("fn main() -> i64 {{ return {}1{}; }}\n", "(".repeat(100_000), ")".repeat(100_000));
+    let (output, _, exe) = build("parens", &parens, &["--json"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("\"E0109\"") && stderr.contains("more than 2048 levels"), "{}", stderr);
+    let _ = std::fs::remove_dir_all(exe.parent().unwrap());
+
+    // Method chains parse in a loop but nest in the checker, which stops at
+    // the same limit
+    let methods = format!(
+        "impl i64 {{ fn id(self: i64) -> i64 {{ return self; }} }}\nfn main() -> i64 {{ let x: i64 = 1; return x{}; }}\n",
+        ".id()".repeat(300),
+    );
+    let (output, _, _) = build("methods", &methods, &["--max-nesting", "200"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Nesting too deep (more than 200 levels)"));
+    let (output, _, exe) = build("methods", &methods, &[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(Command::new(&exe).status().unwrap().code(), Some(1));
+    let _ = std::fs::remove_dir_all(exe.parent().unwrap());
+}
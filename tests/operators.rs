@@ -0,0 +1,25 @@
+//! Structs that implement `Add`, `Mul`, `Eq` and `Ord` can be used with
+//! `+`, `*`, `+=`, `==` and `<`, which call their impl methods
+
+mod common;
+
+use common::{check_errors, exit_code};
+
+#[test]
+fn test_vec3_operators_c() {
+    // Every component check passes: 1 + 2 + 4 + 8
+    assert_eq!(exit_code("operators", "c", "vec3"), 15);
+}
+
+#[cfg(feature = "llvm")]
+#[test]
+fn test_vec3_operators_llvm() {
+    assert_eq!(exit_code("operators", "llvm", "vec3"), 15);
+}
+
+#[test]
+fn test_missing_operator_impl() {
+    let stderr = check_errors("struct P { x: i32 }\nfn main() -> i32 {\n    let a: P = P { x: 1 }\n    let b: P = a - a\n    return b.x\n}\n");
+    assert!(stderr.contains("needs an impl of 'Sub' for it taking 'P'"), "{}", stderr);
+    assert!(stderr.contains("impl Sub for P {\\n    fn sub(self: P, other: P) -> P {"), "{}", stderr);
+}
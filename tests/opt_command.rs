@@ -0,0 +1,18 @@
+//! `aethc opt`: optimize textual IR and print the result
+
+use std::process::Command;
+
+#[test]
+fn test_opt_folds_constant_branch() {
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["opt", "tests/opt/fold_branch.ir", "-O2", "--emit-ir", "--verify-ir"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let ir = String::from_utf8_lossy(&output.stdout);
+    assert!(ir.contains("%0 = true"), "{}", ir);
+    assert!(ir.contains("    br bb1\n"), "{}", ir);
+    assert!(!ir.contains("br true"), "{}", ir);
+}
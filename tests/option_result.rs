@@ -0,0 +1,24 @@
+//! The builtin `Option` and `Result`: constructors, methods and `?`
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_prelude_option_and_result() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    for opt in ["-O0", "-O2"] {
+        let exe = std::env::temp_dir().join(format!("aether_option_result_{}{}", std::process::id(), opt));
+
+        let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+            .args([opt, "build", "tests/option_result/prelude.aeth", "-o"]).arg(&exe)
+            .current_dir(root)
+            .output()
+            .expect("failed to start aethc");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+        let run = Command::new(&exe).output().unwrap();
+        let _ = std::fs::remove_file(&exe);
+        // The second sum stops at its first non-digit, `Err(65)`
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "5\n-7\n8\n47\n65\n2\n-59\n0\n1\n-6\n", "{}", opt);
+    }
+}
@@ -0,0 +1,43 @@
+//! `--overflow-mode`: wrapping (default), saturating and panicking arithmetic
+
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// Build `tests/overflow/arith.aeth` with `flags` and run it
+fn run_arith(flags: &[&str], name: &str) -> Output {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dir = std::env::temp_dir().join(format!("aether_overflow_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let exe = dir.join(name);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(flags)
+        .args(["build", "tests/overflow/arith.aeth", "-o"]).arg(&exe)
+        .current_dir(root)
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    Command::new(&exe).output().unwrap()
+}
+
+#[test]
+fn test_overflow_modes() {
+    let wrapping = run_arith(&[], "wrapping");
+    assert_eq!(String::from_utf8_lossy(&wrapping.stdout), "12\n-56\n56\n4294967294\n0\n");
+
+    let saturating = run_arith(&["--overflow-mode=saturating"], "saturating");
+    assert_eq!(
+        String::from_utf8_lossy(&saturating.stdout),
+        "12\n127\n-128\n0\n-9223372036854775808\n"
+    );
+
+    let panicking = run_arith(&["--overflow-check"], "panicking");
+    assert!(!panicking.status.success());
+    assert_eq!(String::from_utf8_lossy(&panicking.stdout), "12\n");
+    assert_eq!(
+        String::from_utf8_lossy(&panicking.stderr),
+        "panic at tests/overflow/arith.aeth:3: integer overflow\n"
+    );
+
+    let _ = std::fs::remove_dir_all(std::env::temp_dir().join(format!("aether_overflow_{}", std::process::id())));
+}
@@ -0,0 +1,40 @@
+//! Runtime failures report through `__aeth_panic`, with a backtrace under `--panic=unwind-trace`
+
+use std::path::Path;
+use std::process::Command;
+
+/// Build `tests/panic/trace.aeth` with `flags`, run it and return its stderr
+fn run_trace(flags: &[&str], name: &str) -> String {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dir = std::env::temp_dir().join(format!("aether_panic_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let exe = dir.join(name);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(flags)
+        .args(["build", "tests/panic/trace.aeth", "-o"]).arg(&exe)
+        .current_dir(root)
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let run = Command::new(&exe).output().unwrap();
+    assert!(!run.status.success());
+    String::from_utf8_lossy(&run.stderr).into_owned()
+}
+
+#[test]
+fn test_panic_reports() {
+    let plain = run_trace(&[], "plain");
+    assert_eq!(plain, "panic at tests/panic/trace.aeth:4: assertion failed\n");
+
+    let traced = run_trace(&["--panic=unwind-trace"], "traced");
+    assert!(traced.ends_with("backtrace:\n  0: main\n  1: outer\n  2: inner\n"), "{}", traced);
+
+    let json = run_trace(&["--panic=unwind-trace", "--json"], "json");
+    assert_eq!(
+        json,
+        "{\"type\":\"panic\",\"message\":\"assertion failed\",\"file\":\"tests/panic/trace.aeth\",\"line\":4,\"backtrace\":[\"main\",\"outer\",\"inner\"]}\n"
+    );
+
+    let _ = std::fs::remove_dir_all(std::env::temp_dir().join(format!("aether_panic_{}", std::process::id())));
+}
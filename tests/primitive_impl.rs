@@ -0,0 +1,25 @@
+//! `impl` blocks on primitive types add methods called on their values
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_extension_methods() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    for opt in ["-O0", "-O2"] {
+        let exe = std::env::temp_dir().join(format!("aether_primitive_impl_{}_{}", std::process::id(), opt));
+
+        let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+            .arg(opt)
+            .args(["build", "tests/primitive_impl/ext.aeth", "-o"]).arg(&exe)
+            .current_dir(root)
+            .output()
+            .expect("failed to start aethc");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+        let run = Command::new(&exe).output().unwrap();
+        let _ = std::fs::remove_file(&exe);
+        assert_eq!(run.status.code(), Some(7), "{}", opt);
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "81\n9\n1\n", "{}", opt);
+    }
+}
@@ -0,0 +1,40 @@
+//! `--print-types` and `--print-symbols` dump what `check` inferred
+
+use std::path::Path;
+use std::process::Command;
+
+fn check(flag: &str) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args([flag, "check", "tests/print_types/demo.aeth"])
+        .current_dir(Path::new(env!("CARGO_MANIFEST_DIR")))
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_print_types() {
+    let out = check("--print-types");
+    let lines: Vec<&str> = out.lines().skip_while(|line| *line != "Types:").skip(1).take(3).collect();
+    assert_eq!(lines, [
+        "    7 |     return p.x * p.x + p.y * p.y",
+        "  at tests/print_types/demo.aeth:7:12: i32  // p.x * p.x + p.y * p.y",
+        "  at tests/print_types/demo.aeth:7:12: i32  // p.x * p.x",
+    ]);
+    assert!(out.contains("  at tests/print_types/demo.aeth:12:12: fn(Point) -> i32  // norm\n"), "{}", out);
+    assert!(!out.contains("Symbols:"), "{}", out);
+}
+
+#[test]
+fn test_print_symbols() {
+    let out = check("--print-symbols");
+    let lines: Vec<&str> = out.lines().skip_while(|line| *line != "Symbols:").skip(1).take(4).collect();
+    assert_eq!(lines, [
+        "NAME    KIND      TYPE              DEFINED",
+        "Point   struct    Point             tests/print_types/demo.aeth:1:1",
+        "norm    function  fn(Point) -> i32  tests/print_types/demo.aeth:6:1",
+        "main    function  fn() -> i32       tests/print_types/demo.aeth:10:1",
+    ]);
+    assert!(!out.contains("Types:"), "{}", out);
+}
@@ -0,0 +1,92 @@
+//! `aethc rename` rewrites every occurrence of an item, across modules
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A scratch copy of `tests/rename`
+fn project(tag: &str) -> PathBuf {
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/rename");
+    let dir = std::env::temp_dir().join(format!("aether_rename_{}_{}", tag, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for file in ["main.aeth", "geo.aeth"] {
+        std::fs::copy(src.join(file), dir.join(file)).unwrap();
+    }
+    dir
+}
+
+fn aethc(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("failed to start aethc")
+}
+
+fn read(dir: &Path, file: &str) -> String {
+    std::fs::read_to_string(dir.join(file)).unwrap()
+}
+
+#[test]
+fn test_rename_struct() {
+    let dir = project("struct");
+    let before = read(&dir, "main.aeth");
+
+    // Type positions, literals, the impl target and `Point::new` all change
+    let output = aethc(&dir, &["rename", "main.aeth", "Point", "Vec2"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(read(&dir, "main.aeth"), before.replace("Point", "Vec2"));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("(11 occurrence(s))"));
+
+    let output = aethc(&dir, &["rename", "main.aeth", "Vec2::sum", "total"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(read(&dir, "main.aeth").contains("fn total(self: ref Vec2)") && read(&dir, "main.aeth").contains("p.total()"));
+
+    let check = aethc(&dir, &["check", "main.aeth"]);
+    assert!(check.status.success(), "{}", String::from_utf8_lossy(&check.stderr));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_rename_imported_function() {
+    let dir = project("module");
+    let (main, geo) = (read(&dir, "main.aeth"), read(&dir, "geo.aeth"));
+
+    // The module defining the function is edited along with its importer
+    let output = aethc(&dir, &["rename", "main.aeth", "area", "rect_area"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(read(&dir, "main.aeth"), main.replace("area", "rect_area"));
+    assert_eq!(read(&dir, "geo.aeth"), geo.replace("area", "rect_area"));
+
+    let output = aethc(&dir, &["rename", "main.aeth", "geo::square", "sq"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(read(&dir, "main.aeth").contains("geo::sq(3)") && read(&dir, "geo.aeth").contains("pub fn sq("));
+
+    let check = aethc(&dir, &["check", "main.aeth"]);
+    assert!(check.status.success(), "{}", String::from_utf8_lossy(&check.stderr));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_rename_conflicts() {
+    let dir = project("conflict");
+    let before = read(&dir, "main.aeth");
+
+    let output = aethc(&dir, &["rename", "main.aeth", "Point", "shift"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("`shift` is already defined") && stderr.contains("main.aeth:15:4"), "{}", stderr);
+
+    // `q` is a local of `shift`, which uses `Point`
+    let output = aethc(&dir, &["rename", "main.aeth", "Point", "q"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("main.aeth:16:9"));
+
+    let output = aethc(&dir, &["rename", "main.aeth", "area", "square"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("geo.aeth:5:8"));
+
+    let output = aethc(&dir, &["rename", "main.aeth", "Point", "match"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("`match` is a keyword"));
+
+    // Nothing is written when a rename is refused
+    assert_eq!(read(&dir, "main.aeth"), before);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,37 @@
+//! `aethc repl` keeps bindings and functions across inputs
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[test]
+fn test_repl_session() {
+    let mut repl = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .arg("repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start aethc");
+    let input = "\
+let x: i64 = 40
+fn add(a: i64, b: i64) -> i64 {
+    return a + b
+}
+add(x, 2)
+:type x > 1
+let y: i64 = true
+println(\"once\")
+x * 2 == 80
+:quit
+";
+    repl.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = repl.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    // The rejected `let y` is not replayed, nor is earlier output repeated
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "42\nbool\nonce\ntrue\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "error[E0001]: Type mismatch: expected Primitive(I64), got Primitive(Bool)\n"
+    );
+}
@@ -0,0 +1,17 @@
+//! `aethc run`: build a program, pass it arguments and forward its exit status
+
+use std::process::Command;
+
+#[test]
+fn test_run_echoes_argument_and_returns_argc() {
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["run", "tests/main_args_test.aeth", "hello"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to start aethc");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.lines().any(|line| line == "hello"), "{}", stdout);
+    // argv[0] plus "hello"
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,26 @@
+//! Diagnostics for Core transpiled from Aether Script point into the `.ath` file
+
+use serde_json::Value;
+use std::process::Command;
+
+const SCRIPT: &str = "tests/script_source_map/type_error.ath";
+
+#[test]
+fn test_type_error_reported_at_script_line() {
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["--error-format=json", "check", SCRIPT])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let error: Value = serde_json::from_str(stderr.lines().next().unwrap()).unwrap();
+    assert_eq!(error["code"]["code"], "E0001");
+    let span = &error["spans"][0];
+    assert_eq!(span["file_name"], SCRIPT);
+    assert_eq!((span["line_start"].as_u64(), span["column_start"].as_u64()), (Some(8), Some(8)));
+    assert_eq!(span["text"][0]["text"], "    if 1:");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc")).args(["check", SCRIPT]).output().unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(&format!(" --> {}:8:8\n", SCRIPT)), "{}", stderr);
+}
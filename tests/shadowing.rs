@@ -0,0 +1,23 @@
+//! A `let` may shadow an earlier binding; shadowing a parameter warns
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_shadowed_bindings() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let exe = std::env::temp_dir().join(format!("aether_shadowing_{}", std::process::id()));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["build", "tests/shadowing/rebind.aeth", "-o"]).arg(&exe)
+        .current_dir(root)
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.matches("Warning[E0039]").count(), 1, "{}", stderr);
+
+    let run = Command::new(&exe).output().unwrap();
+    let _ = std::fs::remove_file(&exe);
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "1\n100\n1\n42\n");
+}
@@ -0,0 +1,25 @@
+//! SIMD intrinsics lower to SSE/AVX on x86-64 and run with the expected lanes
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+#[cfg(target_arch = "x86_64")]
+fn test_integer_lanes() {
+    if !std::arch::is_x86_feature_detected!("avx2") {
+        return;
+    }
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let exe = std::env::temp_dir().join(format!("aether_simd_{}", std::process::id()));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["build", "tests/simd/lanes.aeth", "-o"]).arg(&exe)
+        .current_dir(root)
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let run = Command::new(&exe).output().unwrap();
+    let _ = std::fs::remove_file(&exe);
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "130\n12\n108\n0\n1\n");
+}
@@ -0,0 +1,34 @@
+//! `static` and `static mut` globals, read and written across function calls
+
+use std::path::Path;
+use std::process::Command;
+
+/// Build tests/static_test.aeth with `backend`, run it and return its stdout
+fn run(backend: &str) -> String {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let exe = std::env::temp_dir().join(format!("aether_statics_{}_{}", backend, std::process::id()));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["--backend", backend, "build", "tests/static_test.aeth"])
+        .arg("-o").arg(&exe)
+        .current_dir(root)
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let run = Command::new(&exe).output().unwrap();
+    let _ = std::fs::remove_file(&exe);
+    assert!(run.status.success());
+    String::from_utf8_lossy(&run.stdout).into_owned()
+}
+
+#[test]
+fn test_statics_c() {
+    assert_eq!(run("c"), "15\n-10\n");
+}
+
+#[cfg(feature = "llvm")]
+#[test]
+fn test_statics_llvm() {
+    assert_eq!(run("llvm"), "15\n-10\n");
+}
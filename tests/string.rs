@@ -0,0 +1,33 @@
+//! Heap-allocated `String` from stdlib/string.aeth
+
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// Build `tests/string/<name>.aeth`, run it and return its output
+fn run(name: &str) -> Output {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dir = std::env::temp_dir().join(format!("aether_string_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let exe = dir.join(name);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .arg("build").arg(format!("tests/string/{}.aeth", name))
+        .arg("-o").arg(&exe)
+        .current_dir(root)
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+    let run = Command::new(&exe).output().unwrap();
+    let _ = std::fs::remove_file(&exe);
+    run
+}
+
+#[test]
+fn test_string() {
+    let output = run("text");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "0\nabcdé€😀\n13\n113\n128\n0\nhello, world\nhello, world!world\nworld\n"
+    );
+}
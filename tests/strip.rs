@@ -0,0 +1,31 @@
+//! `--strip` drops debug info and symbols without changing behavior
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn build(flags: &[&str], exe: &Path) {
+    let output = Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(flags)
+        .args(["build", "tests/strip/fib.aeth", "-o"]).arg(exe)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to start aethc");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn test_stripped_binary_is_smaller() {
+    let exe = |name: &str| -> PathBuf {
+        std::env::temp_dir().join(format!("aether_strip_{}_{}", name, std::process::id()))
+    };
+    let (debug, stripped) = (exe("debug"), exe("stripped"));
+    build(&["-g"], &debug);
+    build(&["-g", "--strip", "--strip-dead-code"], &stripped);
+
+    let size = |path: &Path| std::fs::metadata(path).unwrap().len();
+    assert!(size(&stripped) < size(&debug), "{} >= {}", size(&stripped), size(&debug));
+    let run = |path: &Path| Command::new(path).output().unwrap().stdout;
+    assert_eq!(run(&stripped), run(&debug));
+    let _ = std::fs::remove_file(&debug);
+    let _ = std::fs::remove_file(&stripped);
+}
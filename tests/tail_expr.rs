@@ -0,0 +1,33 @@
+//! A block's final expression without a semicolon is its value
+
+use std::path::Path;
+use std::process::Command;
+
+fn aethc(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(args)
+        .current_dir(Path::new(env!("CARGO_MANIFEST_DIR")))
+        .output()
+        .expect("failed to start aethc")
+}
+
+#[test]
+fn test_tail_values() {
+    let exe = std::env::temp_dir().join(format!("aether_tail_expr_{}", std::process::id()));
+    let output = aethc(&["build", "tests/tail_expr/tails.aeth", "-o", exe.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let run = Command::new(&exe).output().unwrap();
+    let _ = std::fs::remove_file(&exe);
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "5\n-1\n0\n1\n100\n42\n4\n3\n5\n90\n8\n");
+}
+
+#[test]
+fn test_semicolon_tail_is_unit() {
+    let output = aethc(&["check", "tests/tail_expr/semicolon.aeth"]);
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Function 'add' must return i64, but its body ends in a statement"),
+        "{}", String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,44 @@
+//! `--timings` reports per-phase times on stderr
+
+use std::path::Path;
+use std::process::{Command, Output};
+
+use aether_lang::feedback::CompilationStats;
+
+fn build(timings: &str, exe: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["-O2", timings, "build", "tests/linker_script/fib.aeth", "-o"]).arg(exe)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to start aethc")
+}
+
+#[test]
+fn test_timings_table() {
+    let exe = std::env::temp_dir().join(format!("aether_timings_table_{}", std::process::id()));
+    let output = build("--timings", &exe);
+    let _ = std::fs::remove_file(&exe);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    for phase in ["Parse", "Semantic analysis", "IR generation", "Optimization", "constant-folding", "Code generation", "C compiler", "Total"] {
+        assert!(stderr.lines().any(|l| l.trim_start().starts_with(phase)), "no {} in:\n{}", phase, stderr);
+    }
+    assert!(stderr.contains("8 lines, 2 functions, 0 types"), "{}", stderr);
+    // Nothing goes to stdout, which stays the build log
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("Time (ms)"));
+}
+
+#[test]
+fn test_timings_json() {
+    let exe = std::env::temp_dir().join(format!("aether_timings_json_{}", std::process::id()));
+    let output = build("--timings=json", &exe);
+    let _ = std::fs::remove_file(&exe);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stats: CompilationStats = serde_json::from_slice(&output.stderr).expect("stderr is the stats JSON");
+    assert_eq!(stats.function_count, 2);
+    assert!(stats.c_compiler_time_ms > 0.0);
+    assert!(stats.total_time_ms >= stats.c_compiler_time_ms + stats.parse_time_ms);
+    assert!(!stats.passes.is_empty());
+    let names: Vec<&str> = stats.functions.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, ["fib", "main"]);
+}
@@ -0,0 +1,34 @@
+//! Items and struct fields of another module must be `pub` to be used
+
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// `aethc check` run next to the fixtures, where `use shapes` finds shapes.aeth
+fn check(file: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_aethc"))
+        .args(["check", file])
+        .current_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/visibility"))
+        .output()
+        .expect("failed to start aethc")
+}
+
+#[test]
+fn test_private_items_and_fields() {
+    let ok = check("ok.aeth");
+    assert!(ok.status.success(), "{}", String::from_utf8_lossy(&ok.stderr));
+
+    for (file, name) in [
+        ("private_field.aeth", "Point.y"),
+        ("private_fn.aeth", "secret"),
+        ("private_import.aeth", "secret"),
+        ("private_path.aeth", "secret"),
+    ] {
+        let output = check(file);
+        assert!(!output.status.success(), "{} passed", file);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            format!("Semantic error: '{}' is private to module 'shapes'\n", name),
+            "{}", file
+        );
+    }
+}